@@ -5,15 +5,11 @@ Provides Python-friendly wrappers for database and Redis operations
 with connection pooling, transaction management, and helper decorators.
 
 Example:
-    from cello import App
-    from cello.database import DatabaseConfig, RedisConfig, transactional
+    from cello import App, DatabaseConfig, Depends
+    from cello.database import transactional
 
     app = App()
-
-    @app.on_startup
-    async def setup():
-        app.state.db = await Database.connect(db_config)
-        app.state.redis = await Redis.connect(redis_config)
+    app.enable_database(DatabaseConfig("postgresql://localhost/mydb"))
 
     @app.post("/transfer")
     @transactional
@@ -27,6 +23,39 @@ from functools import wraps
 from typing import Any, Callable, Optional
 
 
+def atomic(func: Optional[Callable] = None, *, isolation: Optional[str] = None) -> Callable:
+    """
+    Run each request to a route in one database transaction.
+
+    The transaction is opened in Rust on the ``enable_database()`` pool
+    before the handler runs, and the handler's ``Depends("database")`` (or
+    ``Depends("transaction")``) resolves to it instead of the pool. It is
+    committed when the handler returns a 2xx or 3xx response and rolled
+    back when the handler raises or returns any other status. Apply it
+    below the route decorator.
+
+    Args:
+        func: The handler (when used without arguments).
+        isolation: ``"read_uncommitted"``, ``"read_committed"``,
+            ``"repeatable_read"`` or ``"serializable"``; the database
+            default when omitted. Ignored by SQLite.
+
+    Example:
+        @app.post("/transfer")
+        @atomic(isolation="serializable")
+        async def transfer(request, db=Depends("database")):
+            data = request.json()
+            await db.execute("UPDATE accounts SET balance = balance - $1 WHERE id = $2", data["amount"], data["from"])
+            await db.execute("UPDATE accounts SET balance = balance + $1 WHERE id = $2", data["amount"], data["to"])
+            return {"success": True}
+    """
+    def decorator(fn: Callable) -> Callable:
+        fn.__cello_transaction__ = {"isolation": isolation}
+        return fn
+
+    return decorator(func) if func is not None else decorator
+
+
 def transactional(func: Callable) -> Callable:
     """
     Decorator for automatic transaction management.
@@ -100,19 +129,26 @@ class Database:
     """
     Database connection wrapper providing a convenient Python API.
 
-    Wraps the Rust-powered connection pool with Pythonic methods.
+    Wraps the Rust-powered connection pool (PostgreSQL, MySQL or SQLite)
+    with Pythonic methods.
 
     Example:
-        db = Database(config)
+        db = await Database.connect(DatabaseConfig("postgresql://localhost/mydb"))
         rows = await db.fetch_all("SELECT * FROM users")
         user = await db.fetch_one("SELECT * FROM users WHERE id = $1", user_id)
         await db.execute("INSERT INTO users (name) VALUES ($1)", name)
     """
 
-    def __init__(self, config=None):
-        """Initialize database wrapper."""
+    def __init__(self, config=None, pool=None):
+        """
+        Initialize database wrapper.
+
+        Args:
+            config: DatabaseConfig used by ``connect``.
+            pool: An existing ``DatabasePool``, e.g. ``app.database()``.
+        """
         self._config = config
-        self._pool = None
+        self._pool = pool
 
     @classmethod
     async def connect(cls, config) -> "Database":
@@ -124,11 +160,20 @@ class Database:
 
         Returns:
             Connected Database instance.
+
+        Raises:
+            RuntimeError: If no connection can be opened.
         """
-        instance = cls(config)
-        # In a real implementation, this would call Rust to create the pool
-        instance._pool = True  # Placeholder
-        return instance
+        from cello._cello import DatabasePool
+
+        return cls(config, await DatabasePool.connect(config))
+
+    @property
+    def pool(self):
+        """The underlying ``DatabasePool``."""
+        if self._pool is None:
+            raise RuntimeError("Database is not connected; call Database.connect() first")
+        return self._pool
 
     async def fetch_all(self, query: str, *params) -> list[dict]:
         """
@@ -149,8 +194,7 @@ class Database:
         Returns:
             List of row dictionaries.
         """
-        # Placeholder - real implementation calls Rust pool
-        return []
+        return await self.pool.fetch_all(query, *params)
 
     async def fetch_one(self, query: str, *params) -> Optional[dict]:
         """
@@ -167,8 +211,20 @@ class Database:
         Returns:
             Row dictionary or None.
         """
-        rows = await self.fetch_all(query, *params)
-        return rows[0] if rows else None
+        return await self.pool.fetch_one(query, *params)
+
+    async def fetch_val(self, query: str, *params) -> Any:
+        """
+        Execute a query and return the first column of the first row.
+
+        Args:
+            query: SQL query string with $1, $2 placeholders for parameters.
+            *params: Query parameters.
+
+        Returns:
+            The value, or None if there are no rows.
+        """
+        return await self.pool.fetch_val(query, *params)
 
     async def execute(self, query: str, *params) -> int:
         """
@@ -185,26 +241,47 @@ class Database:
         Returns:
             Number of affected rows.
         """
-        return 0
+        return await self.pool.execute(query, *params)
 
-    async def begin(self):
-        """Begin a transaction."""
-        return Transaction(self)
+    async def begin(self, isolation: Optional[str] = None) -> "Transaction":
+        """
+        Begin a transaction.
+
+        Args:
+            isolation: ``"read_uncommitted"``, ``"read_committed"``,
+                ``"repeatable_read"`` or ``"serializable"``; the database
+                default when omitted.
+        """
+        return Transaction(await self.pool.begin(isolation))
+
+    def stats(self) -> dict:
+        """Pool statistics (connections, wait times, query timings)."""
+        return self.pool.stats()
 
     async def close(self):
         """Close the connection pool."""
-        self._pool = None
+        if self._pool is not None:
+            await self._pool.close()
+            self._pool = None
 
 
 class Transaction:
     """
     Database transaction wrapper.
 
-    Provides commit/rollback semantics for a group of operations.
+    Provides commit/rollback semantics for a group of operations. Used as
+    an async context manager it commits on success and rolls back when the
+    block raises.
     """
 
-    def __init__(self, db: Database):
-        self._db = db
+    def __init__(self, tx):
+        """
+        Initialize a Transaction.
+
+        Args:
+            tx: A started ``DatabaseTransaction``.
+        """
+        self._tx = tx
         self._committed = False
         self._rolled_back = False
 
@@ -214,7 +291,7 @@ class Transaction:
         SECURITY: Always use parameterized queries ($1, $2, ...).
         Never interpolate user input directly into the query string.
         """
-        return await self._db.execute(query, *params)
+        return await self._tx.execute(query, *params)
 
     async def fetch_all(self, query: str, *params) -> list[dict]:
         """Fetch all rows within this transaction.
@@ -222,7 +299,7 @@ class Transaction:
         SECURITY: Always use parameterized queries ($1, $2, ...).
         Never interpolate user input directly into the query string.
         """
-        return await self._db.fetch_all(query, *params)
+        return await self._tx.fetch_all(query, *params)
 
     async def fetch_one(self, query: str, *params) -> Optional[dict]:
         """Fetch a single row within this transaction.
@@ -230,23 +307,27 @@ class Transaction:
         SECURITY: Always use parameterized queries ($1, $2, ...).
         Never interpolate user input directly into the query string.
         """
-        return await self._db.fetch_one(query, *params)
+        return await self._tx.fetch_one(query, *params)
 
     async def commit(self):
         """Commit the transaction."""
+        await self._tx.commit()
         self._committed = True
 
     async def rollback(self):
         """Rollback the transaction."""
+        await self._tx.rollback()
         self._rolled_back = True
 
     async def __aenter__(self):
         return self
 
     async def __aexit__(self, exc_type, exc_val, exc_tb):
+        if self._committed or self._rolled_back:
+            return False
         if exc_type is not None:
             await self.rollback()
-        elif not self._committed:
+        else:
             await self.commit()
         return False
 
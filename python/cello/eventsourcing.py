@@ -9,7 +9,7 @@ Example:
     from cello import App
     from cello.eventsourcing import (
         Event, Aggregate, EventStore, Snapshot,
-        EventSourcingConfig, event_handler,
+        EventSourcingConfig, ConcurrencyError, event_handler,
     )
 
     # Define an aggregate with event handlers
@@ -102,6 +102,52 @@ def event_handler(event_type: str) -> Callable:
     return decorator
 
 
+class ConcurrencyError(Exception):
+    """
+    Exception raised when a multi-aggregate append is rejected.
+
+    Either some aggregates were not at their expected versions, in which
+    case nothing was written and ``conflicts`` lists them, or a store
+    without transactions failed part way, in which case ``committed``,
+    ``failed`` and ``pending`` say which appends went through so the
+    caller can retry the rest or compensate.
+
+    Attributes:
+        transaction_id: ID recorded on the transaction's events.
+        conflicts: Dicts with ``aggregate_id``, ``expected`` and ``actual``.
+        committed: Aggregates whose events were written.
+        failed: Aggregate whose append failed, or None.
+        pending: Aggregates that were not attempted.
+
+    Example:
+        try:
+            app.append_events([
+                {"aggregate_id": "account-1", "expected_version": 3,
+                 "events": [{"event_type": "Debited", "data": {"amount": 10}}]},
+                {"aggregate_id": "account-2", "expected_version": 7,
+                 "events": [{"event_type": "Credited", "data": {"amount": 10}}]},
+            ])
+        except ConcurrencyError as e:
+            print(f"Stale aggregates: {e.conflicts}")
+    """
+
+    def __init__(
+        self,
+        message: str,
+        transaction_id: Optional[str] = None,
+        conflicts: Optional[List[Dict[str, Any]]] = None,
+        committed: Optional[List[str]] = None,
+        failed: Optional[str] = None,
+        pending: Optional[List[str]] = None,
+    ):
+        self.transaction_id: Optional[str] = transaction_id
+        self.conflicts: List[Dict[str, Any]] = conflicts or []
+        self.committed: List[str] = committed or []
+        self.failed: Optional[str] = failed
+        self.pending: List[str] = pending or []
+        super().__init__(message)
+
+
 class Event:
     """
     Represents a domain event in the event sourcing system.
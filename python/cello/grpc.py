@@ -36,6 +36,7 @@ Example:
         await channel.close()
 """
 
+import time
 from functools import wraps
 from typing import Any, Callable, Optional
 
@@ -91,16 +92,27 @@ class GrpcError(Exception):
         return f"GrpcError(code={self.code}, message={self.message!r}, details={self.details!r})"
 
 
-def grpc_method(func: Callable = None, *, stream: bool = False) -> Callable:
+def grpc_method(
+    func: Callable = None,
+    *,
+    stream: bool = False,
+    input_type: str = None,
+    output_type: str = None,
+    deadline: float = None,
+) -> Callable:
     """
     Decorator to mark a method as a gRPC endpoint.
 
-    Stores metadata on the function including the method name and whether
-    it uses streaming. Can be used with or without arguments.
+    Stores metadata on the function including the method name, whether
+    it uses streaming, its protobuf message types and its deadline. Can be
+    used with or without arguments.
 
     Args:
         func: The method to decorate (when used without parentheses).
         stream: Whether this method uses streaming responses (default: False).
+        input_type: Registered protobuf message type of the request.
+        output_type: Registered protobuf message type of the response.
+        deadline: Maximum handler run time in seconds.
 
     Returns:
         Decorated function with gRPC metadata attached.
@@ -115,6 +127,10 @@ def grpc_method(func: Callable = None, *, stream: bool = False) -> Callable:
             def streaming_call(self, request):
                 yield {"chunk": 1}
                 yield {"chunk": 2}
+
+            @grpc_method(input_type="users.GetUser", output_type="users.User", deadline=2.0)
+            async def get_user(self, request):
+                return {"id": request.data["id"], "name": "Alice"}
     """
     def decorator(fn: Callable) -> Callable:
         @wraps(fn)
@@ -125,6 +141,9 @@ def grpc_method(func: Callable = None, *, stream: bool = False) -> Callable:
         wrapper._grpc_method = True
         wrapper._grpc_method_name = fn.__name__
         wrapper._grpc_stream = stream
+        wrapper._grpc_input_type = input_type
+        wrapper._grpc_output_type = output_type
+        wrapper._grpc_deadline = deadline
         return wrapper
 
     if func is not None:
@@ -134,12 +153,39 @@ def grpc_method(func: Callable = None, *, stream: bool = False) -> Callable:
     return decorator
 
 
+def protobuf_body(request: str = None, response: str = None) -> Callable:
+    """
+    Accept and return protobuf bodies on a plain HTTP route.
+
+    Message types must be registered with ``app.register_proto_message``
+    (they are shared with gRPC services). ``application/x-protobuf`` request
+    bodies are decoded in Rust and available via ``request.data()``; clients
+    sending ``Accept: application/x-protobuf`` get the handler's dict encoded
+    as ``response``. Apply it below the route decorator.
+
+    Args:
+        request: Fully-qualified message type of request bodies.
+        response: Fully-qualified message type of responses.
+
+    Example:
+        @app.post("/items")
+        @protobuf_body(request="shop.Item", response="shop.Item")
+        def create_item(request):
+            return request.data()
+    """
+    def decorator(fn: Callable) -> Callable:
+        fn.__cello_protobuf__ = {"request_type": request, "response_type": response}
+        return fn
+    return decorator
+
+
 class GrpcRequest:
     """
     Represents an incoming gRPC request.
 
     Encapsulates the target service, method, payload data, and any
-    additional metadata (headers) sent by the client.
+    additional metadata (headers) sent by the client, plus the deadline
+    from the caller's ``grpc-timeout`` or the method's own deadline.
 
     Example:
         request = GrpcRequest(
@@ -156,6 +202,7 @@ class GrpcRequest:
         method: str,
         data: dict = None,
         metadata: dict = None,
+        timeout: float = None,
     ):
         """
         Initialize a gRPC request.
@@ -165,11 +212,13 @@ class GrpcRequest:
             method: Target method name.
             data: Request payload as a dictionary.
             metadata: Optional request metadata (headers).
+            timeout: Seconds left before the deadline, or None for no deadline.
         """
         self._service = service
         self._method = method
         self._data = data or {}
         self._metadata = metadata or {}
+        self._deadline = None if timeout is None else time.monotonic() + timeout
 
     @property
     def service(self) -> str:
@@ -191,6 +240,25 @@ class GrpcRequest:
         """Get the request metadata."""
         return self._metadata
 
+    @property
+    def deadline(self) -> Optional[float]:
+        """Get the deadline as a ``time.monotonic()`` value, or None."""
+        return self._deadline
+
+    def time_remaining(self) -> Optional[float]:
+        """
+        Seconds left before the deadline, or None without one.
+
+        Pass it as the ``timeout`` of downstream calls so they give up when
+        the caller does:
+
+            await channel.call("users.Users", "Get", payload, timeout=request.time_remaining())
+            await client.get(url, timeout=request.time_remaining())
+        """
+        if self._deadline is None:
+            return None
+        return max(self._deadline - time.monotonic(), 0.0)
+
     def __repr__(self) -> str:
         return f"GrpcRequest(service={self._service!r}, method={self._method!r})"
 
@@ -327,6 +395,9 @@ class GrpcService:
                     "name": attr._grpc_method_name,
                     "handler": attr,
                     "stream": attr._grpc_stream,
+                    "input_type": getattr(attr, "_grpc_input_type", None),
+                    "output_type": getattr(attr, "_grpc_output_type", None),
+                    "deadline": getattr(attr, "_grpc_deadline", None),
                 }
 
     def get_methods(self) -> list[dict]:
@@ -449,23 +520,28 @@ class GrpcChannel:
     """
     gRPC client channel for making remote procedure calls.
 
-    Wraps the Rust-powered gRPC client with a Pythonic async API
-    for connecting to remote services and making calls.
+    Wraps the Rust-powered gRPC client (pooled HTTP/2 connections, deadlines,
+    retries with backoff, metadata) with a Pythonic async API. Requests and
+    responses are protobuf-encoded with the message types registered on
+    ``app``; without an app, pass and receive raw ``bytes``.
 
     Example:
-        channel = await GrpcChannel.connect("localhost:50051")
+        channel = await GrpcChannel.connect("localhost:50051", app=app, timeout=5.0)
 
         result = await channel.call(
             "UserService",
             "get_user",
-            {"id": 42}
+            {"id": 42},
+            input_type="users.GetUser",
+            output_type="users.User",
+            metadata={"authorization": "Bearer token123"},
         )
         print(result)  # {"id": 42, "name": "Alice"}
 
         await channel.close()
     """
 
-    def __init__(self, target: str):
+    def __init__(self, target: str, app: Any = None, **options):
         """
         Initialize a gRPC channel.
 
@@ -473,40 +549,67 @@ class GrpcChannel:
 
         Args:
             target: Target address in host:port format.
+            app: Optional App whose registered protobuf messages encode calls.
+            **options: Client options (timeout, max_retries, initial_backoff,
+                max_backoff, metadata, max_message_size).
         """
         self._target = target
+        self._app = app
+        self._options = options
+        self._client = None
         self._connected = False
 
     @classmethod
-    async def connect(cls, target: str) -> "GrpcChannel":
+    async def connect(cls, target: str, app: Any = None, **options) -> "GrpcChannel":
         """
         Create and connect a gRPC channel to the target address.
 
+        Connections are opened lazily on the first call and pooled.
+
         Args:
             target: Target address in host:port format (e.g., "localhost:50051").
+            app: Optional App whose registered protobuf messages encode calls.
+            **options: Client options (see ``__init__``).
 
         Returns:
             A connected GrpcChannel instance.
         """
-        instance = cls(target)
-        # In a real implementation, this would establish a Rust-powered connection
+        from cello._cello import GrpcClient
+
+        instance = cls(target, app=app, **options)
+        instance._client = GrpcClient(target, **options)
         instance._connected = True
         return instance
 
-    async def call(self, service: str, method: str, request: dict) -> dict:
+    async def call(
+        self,
+        service: str,
+        method: str,
+        request: Any,
+        *,
+        input_type: str = None,
+        output_type: str = None,
+        metadata: dict = None,
+        timeout: float = None,
+    ) -> Any:
         """
         Make a unary gRPC call to a remote service method.
 
         Args:
             service: Target service name.
             method: Target method name.
-            request: Request payload as a dictionary.
+            request: Request payload as a dict (encoded as ``input_type``) or bytes.
+            input_type: Protobuf message type of the request.
+            output_type: Protobuf message type of the response.
+            metadata: Optional call metadata.
+            timeout: Deadline in seconds (overrides the channel default).
 
         Returns:
-            Response payload as a dictionary.
+            The decoded response dict, or raw bytes without ``output_type``.
 
         Raises:
             GrpcError: If the call fails or the channel is not connected.
+            ValueError: If a dict request cannot be encoded.
         """
         if not self._connected:
             raise GrpcError(
@@ -514,13 +617,25 @@ class GrpcChannel:
                 message="Channel is not connected",
                 details=f"Target: {self._target}",
             )
-        # Placeholder - real implementation calls Rust gRPC client
-        return {}
+        if isinstance(request, (bytes, bytearray)):
+            payload = bytes(request)
+        elif input_type is None or self._app is None:
+            raise ValueError("Dict requests need input_type and a channel created with app=")
+        else:
+            payload = self._app.proto_encode(input_type, request)
+
+        result = await self._client.call(service, method, payload, metadata, timeout)
+        if result["code"] != GrpcError.OK:
+            raise GrpcError(code=result["code"], message=result["message"])
+        if output_type is None or self._app is None:
+            return result["payload"]
+        return self._app.proto_decode(output_type, result["payload"])
 
     async def close(self) -> None:
         """
         Close the gRPC channel and release resources.
         """
+        self._client = None
         self._connected = False
 
     def __repr__(self) -> str:
@@ -179,6 +179,34 @@ class Not(Guard):
 
         raise ForbiddenError("Guard succeeded but was expected to fail")
 
+def require(expression: str, claims_key: str = None):
+    """
+    Guard requiring a permission expression to hold for the user.
+
+    Names combine with ``&`` (and), ``|`` (or), ``!`` (not) and parentheses;
+    ``&`` binds tighter than ``|``. A name holds when the user's claims list
+    it under ``roles``, ``role``, ``permissions``, ``scope`` or ``scopes``.
+    The claims are read from ``request.context["user"]``, or from the JWT
+    claims set by the auth middleware, and the expression is evaluated in
+    Rust. Requests without claims are rejected with 401, those that do not
+    satisfy the expression with 403.
+
+    Args:
+        expression: e.g. ``"admin | (editor & owner)"``
+        claims_key: Read the claims from this context key only.
+
+    Raises:
+        ValueError: If the expression is malformed.
+
+    Example:
+        @app.delete("/posts/{id}", guards=[require("admin | (editor & posts:delete)")])
+        def delete_post(request):
+            ...
+    """
+    from cello._cello import Require
+
+    return Require(expression, claims_key)
+
 def verify_guards(guards: List[Callable], request: Any):
     """Helper to verify a list of guards (AND logic by default)."""
     for guard in guards:
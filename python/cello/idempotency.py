@@ -0,0 +1,50 @@
+"""
+Cello Idempotency Keys.
+
+Idempotency is enabled on the app (``app.enable_idempotency``); this module
+turns it on for single routes.
+
+Example:
+    from cello import App, idempotent
+
+    app = App()
+    app.enable_idempotency()
+
+    @app.post("/payments")
+    @idempotent(required=True)
+    def create_payment(request):
+        ...
+"""
+
+from typing import Callable, Optional
+
+
+def idempotent(ttl: Optional[float] = None, required: Optional[bool] = None) -> Callable:
+    """
+    Check idempotency keys on a route.
+
+    The first request with a given ``Idempotency-Key`` runs the handler and
+    its response is stored; retries with the same key get that response
+    back, with an ``Idempotent-Replayed: true`` header, instead of running
+    the handler again. A retry that arrives while the first request is still
+    running gets 409, and reusing a key for a different request gets 422.
+    Apply it below the route decorator, after ``app.enable_idempotency()``.
+
+    Args:
+        ttl: How long the response is replayed for, in seconds (default:
+            the ``ttl`` given to ``enable_idempotency``).
+        required: Reject requests without a key with 400 (default: the
+            ``required`` given to ``enable_idempotency``).
+
+    Example:
+        @app.post("/orders")
+        @idempotent(ttl=3600)
+        def create_order(request):
+            return Response.json(place_order(request.json()), status=201)
+    """
+
+    def decorator(fn: Callable) -> Callable:
+        fn.__cello_idempotency__ = {"ttl": ttl, "required": required}
+        return fn
+
+    return decorator
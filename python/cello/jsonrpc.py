@@ -0,0 +1,333 @@
+"""
+Cello JSON-RPC 2.0 Support.
+
+Exposes registered Python functions as JSON-RPC methods at a single
+endpoint, with batch requests, notifications, the standard error codes and
+per-method guards.
+
+Example:
+    from cello import App
+    from cello.guards import Authenticated
+    from cello.jsonrpc import JsonRpcRouter, JsonRpcError
+
+    app = App()
+    rpc = JsonRpcRouter("/rpc")
+
+    @rpc.method()
+    def add(a: int, b: int) -> int:
+        return a + b
+
+    @rpc.method("users.get", guards=[Authenticated()])
+    async def get_user(request, id: int) -> dict:
+        user = await db.get_user(id)
+        if user is None:
+            raise JsonRpcError(-32004, "User not found", {"id": id})
+        return user
+
+    app.register_jsonrpc(rpc)
+
+    # POST /rpc
+    # {"jsonrpc": "2.0", "method": "add", "params": [1, 2], "id": 1}
+    # -> {"jsonrpc": "2.0", "result": 3, "id": 1}
+"""
+
+import asyncio
+import inspect
+import json
+from typing import Any, Callable, Dict, List, Optional
+
+from cello._cello import Response
+
+from .guards import GuardError, verify_guards
+
+# Standard error codes from the JSON-RPC 2.0 specification.
+PARSE_ERROR = -32700
+INVALID_REQUEST = -32600
+METHOD_NOT_FOUND = -32601
+INVALID_PARAMS = -32602
+INTERNAL_ERROR = -32603
+
+# Server-defined code for requests rejected by a guard.
+FORBIDDEN = -32001
+
+_MISSING = object()
+
+
+class JsonRpcError(Exception):
+    """
+    Error returned to the caller as a JSON-RPC error object.
+
+    Raise it from a method to control the error code, message and data.
+    Codes from -32768 to -32000 are reserved by the specification; -32000
+    to -32099 are available for server errors.
+
+    Example:
+        raise JsonRpcError(-32004, "User not found", {"id": 42})
+    """
+
+    def __init__(self, code: int, message: str, data: Any = None):
+        """
+        Initialize a JsonRpcError.
+
+        Args:
+            code: Integer error code.
+            message: Short description of the error.
+            data: Optional additional information for the caller.
+        """
+        super().__init__(message)
+        self.code = code
+        self.message = message
+        self.data = data
+
+    def to_dict(self) -> dict:
+        """Convert to a JSON-RPC error object."""
+        error = {"code": self.code, "message": self.message}
+        if self.data is not None:
+            error["data"] = self.data
+        return error
+
+    def __repr__(self) -> str:
+        return f"JsonRpcError(code={self.code}, message={self.message!r})"
+
+
+class JsonRpcMethod:
+    """
+    A function registered with a JsonRpcRouter.
+
+    A parameter named ``request`` receives the HTTP request; the remaining
+    parameters are bound from the call's positional or named ``params``.
+    """
+
+    def __init__(self, name: str, func: Callable, guards: Optional[List[Callable]] = None):
+        """
+        Initialize a JsonRpcMethod.
+
+        Args:
+            name: Method name callers use.
+            func: Sync or async function implementing the method.
+            guards: Guards checked against the HTTP request before each call.
+        """
+        self.name = name
+        self.func = func
+        self.guards = list(guards or [])
+        self.is_async = inspect.iscoroutinefunction(func)
+        self.doc = inspect.getdoc(func) or ""
+
+        self._full_signature = inspect.signature(func)
+        self.wants_request = "request" in self._full_signature.parameters
+        self._signature = self._full_signature.replace(
+            parameters=[
+                p for n, p in self._full_signature.parameters.items() if n != "request"
+            ]
+        )
+
+    def bind(self, params: Any, request: Any) -> inspect.BoundArguments:
+        """
+        Bind call params to the function's signature.
+
+        Raises:
+            JsonRpcError: With ``INVALID_PARAMS`` if the params don't fit.
+        """
+        try:
+            if isinstance(params, dict):
+                bound = self._signature.bind(**params)
+            else:
+                bound = self._signature.bind(*(params or []))
+        except TypeError as e:
+            raise JsonRpcError(INVALID_PARAMS, "Invalid params", str(e))
+        if not self.wants_request:
+            return bound
+        arguments = {
+            name: request if name == "request" else bound.arguments[name]
+            for name in self._full_signature.parameters
+            if name == "request" or name in bound.arguments
+        }
+        return inspect.BoundArguments(self._full_signature, arguments)
+
+    async def call(self, params: Any, request: Any) -> Any:
+        """Check the guards, then call the function with ``params``."""
+        verify_guards(self.guards, request)
+        bound = self.bind(params, request)
+        result = self.func(*bound.args, **bound.kwargs)
+        if self.is_async or inspect.isawaitable(result):
+            result = await result
+        return result
+
+    def __repr__(self) -> str:
+        return f"JsonRpcMethod(name={self.name!r})"
+
+
+class JsonRpcRouter:
+    """
+    JSON-RPC 2.0 endpoint dispatching calls to registered methods.
+
+    Requests are POSTed to the router's path as a single call object or a
+    batch (array) of them. Calls without an ``id`` are notifications and get
+    no response; a request made only of notifications is answered with
+    ``204 No Content``. Calls in a batch run concurrently.
+
+    Example:
+        rpc = JsonRpcRouter("/rpc", guards=[Authenticated()])
+
+        @rpc.method()
+        def ping() -> str:
+            return "pong"
+
+        app.register_jsonrpc(rpc)
+    """
+
+    def __init__(
+        self,
+        path: str = "/rpc",
+        guards: Optional[List[Callable]] = None,
+        max_batch_size: Optional[int] = 100,
+    ):
+        """
+        Initialize a JsonRpcRouter.
+
+        Args:
+            path: URL path of the endpoint.
+            guards: Guards checked for every method on this router.
+            max_batch_size: Largest batch accepted (None for no limit).
+        """
+        self.path = path
+        self.guards = list(guards or [])
+        self.max_batch_size = max_batch_size
+        self._methods: Dict[str, JsonRpcMethod] = {}
+
+    def method(self, name: Optional[str] = None, guards: Optional[List[Callable]] = None) -> Callable:
+        """
+        Decorator registering a function as a JSON-RPC method.
+
+        Args:
+            name: Method name (defaults to the function's name).
+            guards: Guards checked before the method runs, after the
+                router's own guards.
+
+        Returns:
+            Decorator returning the function unchanged.
+        """
+
+        def decorator(func: Callable) -> Callable:
+            self.add_method(func, name, guards)
+            return func
+
+        return decorator
+
+    def add_method(
+        self,
+        func: Callable,
+        name: Optional[str] = None,
+        guards: Optional[List[Callable]] = None,
+    ) -> JsonRpcMethod:
+        """
+        Register ``func`` as a JSON-RPC method.
+
+        Raises:
+            ValueError: If the name is taken or uses the reserved ``rpc.``
+                prefix.
+        """
+        name = name or func.__name__
+        if name.startswith("rpc."):
+            raise ValueError(f"Method names starting with 'rpc.' are reserved: {name}")
+        if name in self._methods:
+            raise ValueError(f"JSON-RPC method already registered: {name}")
+        method = JsonRpcMethod(name, func, self.guards + list(guards or []))
+        self._methods[name] = method
+        return method
+
+    @property
+    def methods(self) -> List[str]:
+        """Names of the registered methods."""
+        return sorted(self._methods)
+
+    async def handle(self, request: Any) -> Response:
+        """Route handler answering a JSON-RPC HTTP request."""
+        try:
+            payload = json.loads(bytes(request.body()))
+        except (ValueError, UnicodeDecodeError):
+            error = JsonRpcError(PARSE_ERROR, "Parse error")
+            return Response.json(_error_response(error, None))
+
+        result = await self.dispatch(payload, request)
+        if result is None:
+            return Response.no_content()
+        return Response.json(result)
+
+    async def dispatch(self, payload: Any, request: Any = None) -> Any:
+        """
+        Process a decoded call or batch.
+
+        Returns:
+            The response object, a list of them for a batch, or None when
+            nothing needs answering.
+        """
+        if not isinstance(payload, list):
+            return await self._dispatch_one(payload, request)
+
+        if not payload:
+            return _error_response(JsonRpcError(INVALID_REQUEST, "Invalid Request"), None)
+        if self.max_batch_size is not None and len(payload) > self.max_batch_size:
+            error = JsonRpcError(
+                INVALID_REQUEST,
+                "Invalid Request",
+                f"Batch exceeds {self.max_batch_size} calls",
+            )
+            return _error_response(error, None)
+
+        responses = await asyncio.gather(
+            *(self._dispatch_one(call, request) for call in payload)
+        )
+        responses = [r for r in responses if r is not None]
+        return responses or None
+
+    async def _dispatch_one(self, call: Any, request: Any) -> Optional[dict]:
+        if not _is_valid_call(call):
+            call_id = call.get("id") if isinstance(call, dict) else None
+            if not _is_valid_id(call_id):
+                call_id = None
+            return _error_response(JsonRpcError(INVALID_REQUEST, "Invalid Request"), call_id)
+
+        call_id = call.get("id", _MISSING)
+        try:
+            method = self._methods.get(call["method"])
+            if method is None:
+                raise JsonRpcError(METHOD_NOT_FOUND, "Method not found", call["method"])
+            result = await method.call(call.get("params"), request)
+        except JsonRpcError as e:
+            error = e
+        except GuardError as e:
+            error = JsonRpcError(FORBIDDEN, e.message, {"status": e.status_code})
+        except Exception:
+            error = JsonRpcError(INTERNAL_ERROR, "Internal error")
+        else:
+            if call_id is _MISSING:
+                return None
+            return {"jsonrpc": "2.0", "result": result, "id": call_id}
+
+        if call_id is _MISSING:
+            return None
+        return _error_response(error, call_id)
+
+    def __repr__(self) -> str:
+        return f"JsonRpcRouter(path={self.path!r}, methods={self.methods})"
+
+
+def _is_valid_id(value: Any) -> bool:
+    return value is None or isinstance(value, str) or (
+        isinstance(value, int) and not isinstance(value, bool)
+    )
+
+
+def _is_valid_call(call: Any) -> bool:
+    return (
+        isinstance(call, dict)
+        and call.get("jsonrpc") == "2.0"
+        and isinstance(call.get("method"), str)
+        and isinstance(call.get("params", []), (list, dict))
+        and _is_valid_id(call.get("id"))
+    )
+
+
+def _error_response(error: JsonRpcError, call_id: Any) -> dict:
+    return {"jsonrpc": "2.0", "error": error.to_dict(), "id": call_id}
@@ -0,0 +1,60 @@
+"""
+Cello Route Middleware.
+
+Python middleware can be attached to every route (``app.add_middleware``),
+to a group of routes (``Blueprint(..., middleware=[...])`` or
+``app.add_middleware(..., prefix=...)``) or, with this module, to a single
+route.
+
+A middleware is an object with ``before(request)`` and/or
+``after(request, response)`` methods, or a plain callable used as
+``before``. ``before`` gets a copy of the request and may return a
+``Response`` to answer it without running the handler; ``after`` may change
+the response in place or return a new one. Values for later stages go on
+``request.state``, which every copy of the request shares.
+
+Example:
+    from cello import App, Response, use_middleware
+
+    class RequireJson:
+        def before(self, request):
+            if request.get_header("content-type") != "application/json":
+                return Response.json({"error": "JSON required"}, status=415)
+
+    app = App()
+
+    @app.post("/orders")
+    @use_middleware(RequireJson())
+    def create_order(request):
+        ...
+"""
+
+from typing import Callable
+
+
+def use_middleware(*middleware, priority: int = 0) -> Callable:
+    """
+    Attach middleware to a route.
+
+    Middleware runs in order of ``priority`` (lower first); ties run
+    app-wide middleware first, then group middleware, then the route's own
+    in the order written. ``after`` hooks run in reverse order. Apply it
+    below the route decorator.
+
+    Args:
+        *middleware: Middleware objects or callables.
+        priority: Priority of these middleware.
+
+    Example:
+        @app.get("/reports")
+        @use_middleware(audit_log, Timing(), priority=-10)
+        def reports(request):
+            ...
+    """
+
+    def decorator(fn: Callable) -> Callable:
+        attached = [(m, priority) for m in middleware]
+        fn.__cello_middleware__ = attached + list(getattr(fn, "__cello_middleware__", []))
+        return fn
+
+    return decorator
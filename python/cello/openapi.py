@@ -0,0 +1,110 @@
+"""
+OpenAPI metadata helpers.
+
+Route decorators collect summaries, tags, Pydantic request/response models and
+guards, and feed them into the Rust OpenAPI generator, which serves the
+OpenAPI 3.1 spec at /openapi.json.
+
+Example:
+    from pydantic import BaseModel
+    from cello import App, api_doc
+
+    class Item(BaseModel):
+        name: str
+
+    @app.post("/items", tags=["items"])
+    @api_doc(summary="Create an item", response_model=Item)
+    def create_item(request, item: Item):
+        return item.model_dump()
+"""
+
+import inspect
+from typing import get_type_hints
+
+try:
+    from pydantic import BaseModel
+    HAS_PYDANTIC = True
+except ImportError:
+    HAS_PYDANTIC = False
+
+_OPENAPI_ATTR = "__cello_openapi__"
+
+
+def api_doc(
+    summary: str = None,
+    description: str = None,
+    tags: list = None,
+    request_model=None,
+    response_model=None,
+    deprecated: bool = False,
+    operation_id: str = None,
+):
+    """
+    Attach OpenAPI metadata to a route handler.
+
+    Apply it below the route decorator so the metadata is present when the
+    route is registered. Values given here override those inferred from the
+    handler's docstring and type hints.
+    """
+    def decorator(func):
+        setattr(func, _OPENAPI_ATTR, {
+            "summary": summary,
+            "description": description,
+            "tags": tags,
+            "request_model": request_model,
+            "response_model": response_model,
+            "deprecated": deprecated,
+            "operation_id": operation_id,
+        })
+        return func
+    return decorator
+
+
+def _is_model(annotation) -> bool:
+    return HAS_PYDANTIC and isinstance(annotation, type) and issubclass(annotation, BaseModel)
+
+
+def model_schema(model):
+    """Return the JSON Schema for a Pydantic model, or None."""
+    if not _is_model(model):
+        return None
+    if hasattr(model, "model_json_schema"):
+        return model.model_json_schema()
+    return model.schema()
+
+
+def _infer_models(func):
+    """Find request and response models from the handler's type hints."""
+    try:
+        hints = get_type_hints(func)
+        params = inspect.signature(func).parameters
+    except (TypeError, ValueError, NameError):
+        return None, None
+
+    request_model = next(
+        (hints[name] for name in params if name in hints and _is_model(hints[name])),
+        None,
+    )
+    response_model = hints.get("return")
+    return request_model, response_model if _is_model(response_model) else None
+
+
+def route_metadata(method: str, path: str, func, tags=None, summary=None,
+                   description=None, guards=None) -> dict:
+    """Build the OpenAPI metadata for a route as keyword arguments for the Rust registry."""
+    doc = inspect.getdoc(func) or ""
+    explicit = getattr(func, _OPENAPI_ATTR, {})
+    request_model, response_model = _infer_models(func)
+    validated_body = getattr(func, "__cello_validation__", {}).get("body")
+
+    return {
+        "summary": explicit.get("summary") or summary or (doc.split("\n")[0].strip() if doc else f"{method} {path}"),
+        "description": explicit.get("description") or description or (doc or None),
+        "tags": explicit.get("tags") or tags or [],
+        "request_schema": model_schema(explicit.get("request_model") or request_model) or validated_body,
+        "response_schema": model_schema(explicit.get("response_model") or response_model),
+        # Guarded routes are documented as requiring a bearer token
+        "security": ["bearerAuth"] if guards else [],
+        "deprecated": explicit.get("deprecated", False),
+        "operation_id": explicit.get("operation_id"),
+    }
@@ -70,6 +70,7 @@ Example:
         return {"error": "Order saga failed", "details": status}
 """
 
+import asyncio
 import inspect
 import time
 import uuid
@@ -158,6 +159,9 @@ class SagaStep:
         action: Async callable that performs the step's work.
         compensate: Optional async callable to undo the step's work.
         timeout: Optional timeout in seconds for the step.
+        kind: ``"action"``, ``"timer"`` or ``"signal"``.
+        delay: Seconds a timer step waits.
+        until: Unix timestamp a timer step waits until.
         status: Current status (one of StepStatus constants).
         result: Result data from execution, or None.
         error: Error from execution, or None.
@@ -192,10 +196,64 @@ class SagaStep:
         self.action: Callable = action
         self.compensate: Optional[Callable] = compensate
         self.timeout: Optional[float] = timeout
+        self.kind: str = "action"
+        self.delay: Optional[float] = None
+        self.until: Optional[float] = None
         self.status: str = StepStatus.PENDING
         self.result: Optional[Any] = None
         self.error: Optional[Exception] = None
 
+    @classmethod
+    def timer(
+        cls,
+        name: str,
+        delay: Optional[float] = None,
+        until: Optional[float] = None,
+        compensate: Optional[Callable] = None,
+    ) -> "SagaStep":
+        """
+        Create a step that waits ``delay`` seconds, or until the Unix
+        timestamp ``until``.
+
+        On the Rust executor (``app.run_saga``) the wake-up time is recorded
+        on the execution, so a restored and resumed execution only waits
+        for what is left. The step's result is ``{"wake_at": ms}``.
+
+        Example:
+            SagaStep.timer("cool_off", delay=24 * 3600)
+        """
+        if (delay is None) == (until is None):
+            raise ValueError("a timer step needs exactly one of delay and until")
+        step = cls(name, None, compensate)
+        step.kind = "timer"
+        step.delay = delay
+        step.until = until
+        return step
+
+    @classmethod
+    def signal(
+        cls,
+        name: str,
+        notify: Optional[Callable] = None,
+        compensate: Optional[Callable] = None,
+        timeout: Optional[float] = None,
+    ) -> "SagaStep":
+        """
+        Create a step that waits for ``app.signal_saga(token, payload)``.
+
+        ``notify`` is called once with the step context, including the
+        ``signal_token``, before the step waits; use it to send the token
+        to whoever will answer (an approval email, a message). The signal's
+        payload is the step's result. ``timeout`` (seconds) bounds the wait.
+        Signal steps run on the Rust executor (``app.run_saga``) only.
+
+        Example:
+            SagaStep.signal("manager_approval", notify=send_approval_email, timeout=3 * 86400)
+        """
+        step = cls(name, notify, compensate, timeout)
+        step.kind = "signal"
+        return step
+
     async def execute(self, context: Dict[str, Any]) -> Any:
         """
         Execute the step's forward action.
@@ -221,7 +279,15 @@ class SagaStep:
         """
         self.status = StepStatus.RUNNING
         try:
-            if inspect.iscoroutinefunction(self.action):
+            if self.kind == "signal":
+                raise RuntimeError(
+                    f"signal step {self.name!r} needs the Rust executor (app.run_saga)"
+                )
+            if self.kind == "timer":
+                wait = self.delay if self.until is None else self.until - time.time()
+                await asyncio.sleep(max(wait, 0))
+                self.result = None
+            elif inspect.iscoroutinefunction(self.action):
                 self.result = await self.action(context)
             else:
                 self.result = self.action(context)
@@ -0,0 +1,289 @@
+"""
+Cello Testing Module.
+
+Provides a test client that sends requests straight into a Cello app's
+Rust request pipeline (routing, middleware, guards, handlers) without
+starting a server or opening a socket, plus WebSocket test sessions that
+run the route's handler against an in-memory connection.
+
+Example:
+    from cello import App
+    from cello.testing import TestClient
+
+    app = App()
+
+    @app.get("/users/{id}")
+    def get_user(request):
+        return {"id": request.params["id"]}
+
+    @app.websocket("/ws")
+    def echo(ws):
+        while (msg := ws.recv()) is not None:
+            ws.send_text(f"Echo: {msg.text}")
+
+    def test_get_user():
+        client = TestClient(app)
+        response = client.get("/users/1")
+        assert response.status_code == 200
+        assert response.json() == {"id": "1"}
+
+    def test_echo():
+        with TestClient(app).websocket_connect("/ws") as ws:
+            ws.send_text("hi")
+            assert ws.receive_text() == "Echo: hi"
+"""
+
+import asyncio
+import json as _json
+from typing import Any, Dict, Optional
+from urllib.parse import urlencode
+
+from cello._cello import (
+    TestClient as _RustTestClient,
+    TestResponse,
+    WebSocketMessage,
+)
+
+__all__ = ["TestClient", "TestResponse", "WebSocketDenied", "WebSocketTestSession"]
+
+
+class WebSocketDenied(Exception):
+    """
+    Raised when middleware or a guard rejects a WebSocket upgrade.
+
+    Attributes:
+        response: The ``Response`` sent instead of completing the handshake
+        status_code: Its HTTP status
+    """
+
+    def __init__(self, response):
+        super().__init__(f"WebSocket upgrade rejected with status {response.status}")
+        self.response = response
+        self.status_code = response.status
+
+
+class TestClient:
+    """
+    Synchronous in-process client for testing a Cello app.
+
+    Each request runs on the client's own event loop, so both sync and
+    async handlers work. Use it from regular (non-async) test functions.
+
+    Args:
+        app: The ``App`` (or its underlying Rust ``Cello``) to test
+        headers: Headers sent with every request
+    """
+
+    __test__ = False  # Not a pytest test class
+
+    def __init__(self, app, headers: Optional[Dict[str, str]] = None):
+        self._client = _RustTestClient(getattr(app, "_app", app))
+        self._headers = dict(headers or {})
+        self._loop = None
+
+    def request(
+        self,
+        method: str,
+        path: str,
+        params: Optional[Dict[str, Any]] = None,
+        headers: Optional[Dict[str, str]] = None,
+        json: Any = None,
+        data: Any = None,
+    ) -> TestResponse:
+        """
+        Send a request and return the response.
+
+        Args:
+            method: HTTP method
+            path: Request path, optionally with a query string
+            params: Query parameters appended to the path
+            headers: Request headers
+            json: Value sent as a JSON body
+            data: Raw body as ``str`` or ``bytes``, or a dict sent form-encoded
+
+        Returns:
+            TestResponse with ``status_code``, ``headers``, ``content``,
+            ``text`` and ``json()``
+        """
+        all_headers = {**self._headers, **(headers or {})}
+        lower = {k.lower() for k in all_headers}
+        body = None
+        if json is not None:
+            body = _json.dumps(json).encode()
+            if "content-type" not in lower:
+                all_headers["Content-Type"] = "application/json"
+        elif isinstance(data, dict):
+            body = urlencode(data).encode()
+            if "content-type" not in lower:
+                all_headers["Content-Type"] = "application/x-www-form-urlencoded"
+        elif isinstance(data, str):
+            body = data.encode()
+        elif data is not None:
+            body = bytes(data)
+
+        if params:
+            path += ("&" if "?" in path else "?") + urlencode(params, doseq=True)
+
+        return self._run(
+            lambda: self._client.request(method, path, list(all_headers.items()), body)
+        )
+
+    def get(self, path: str, **kwargs) -> TestResponse:
+        """Send a GET request."""
+        return self.request("GET", path, **kwargs)
+
+    def post(self, path: str, **kwargs) -> TestResponse:
+        """Send a POST request."""
+        return self.request("POST", path, **kwargs)
+
+    def put(self, path: str, **kwargs) -> TestResponse:
+        """Send a PUT request."""
+        return self.request("PUT", path, **kwargs)
+
+    def patch(self, path: str, **kwargs) -> TestResponse:
+        """Send a PATCH request."""
+        return self.request("PATCH", path, **kwargs)
+
+    def delete(self, path: str, **kwargs) -> TestResponse:
+        """Send a DELETE request."""
+        return self.request("DELETE", path, **kwargs)
+
+    def head(self, path: str, **kwargs) -> TestResponse:
+        """Send a HEAD request."""
+        return self.request("HEAD", path, **kwargs)
+
+    def options(self, path: str, **kwargs) -> TestResponse:
+        """Send an OPTIONS request."""
+        return self.request("OPTIONS", path, **kwargs)
+
+    def websocket_connect(
+        self,
+        path: str,
+        extensions: Optional[str] = None,
+        headers: Optional[Dict[str, str]] = None,
+    ) -> "WebSocketTestSession":
+        """
+        Open a WebSocket session with the handler registered for ``path``.
+
+        The handler runs in a background thread; use the session as a
+        context manager so it is closed and the handler's errors surface.
+        ``extensions`` is the client's ``Sec-WebSocket-Extensions`` offer.
+
+        The upgrade request, with ``headers``, first passes through the app's
+        middleware and guards; raises ``WebSocketDenied`` if they reject it.
+        """
+        all_headers = {**self._headers, **(headers or {})}
+        try:
+            session = self._client.websocket_connect(
+                path, extensions, list(all_headers.items())
+            )
+        except ConnectionRefusedError as exc:
+            raise WebSocketDenied(exc.args[0]) from None
+        return WebSocketTestSession(session)
+
+    def close(self):
+        """Close the client's event loop."""
+        if self._loop is not None:
+            self._loop.close()
+            self._loop = None
+
+    def __enter__(self):
+        return self
+
+    def __exit__(self, *exc):
+        self.close()
+
+    def _run(self, make_awaitable):
+        # The Rust awaitable must be created while the loop is running.
+        if self._loop is None:
+            self._loop = asyncio.new_event_loop()
+
+        async def wait():
+            return await make_awaitable()
+
+        return self._loop.run_until_complete(wait())
+
+
+class WebSocketTestSession:
+    """
+    Client side of a WebSocket connection to a handler under test.
+
+    Args:
+        session: The Rust ``WebSocketSession``
+    """
+
+    __test__ = False
+
+    def __init__(self, session):
+        self._session = session
+
+    def send_text(self, text: str):
+        """Send a text message."""
+        self._session.send_text(text)
+
+    def send_bytes(self, data: bytes):
+        """Send a binary message."""
+        self._session.send_bytes(data)
+
+    def send_json(self, value: Any):
+        """Send a value as a JSON text message."""
+        self._session.send_text(_json.dumps(value))
+
+    def receive(self, timeout: float = 5.0) -> Optional[WebSocketMessage]:
+        """
+        Receive the next message from the handler.
+
+        Server pings are answered while this waits. Returns None once the
+        handler has closed the connection; raises TimeoutError if nothing
+        arrives within ``timeout`` seconds.
+        """
+        return self._session.receive(timeout)
+
+    def receive_text(self, timeout: float = 5.0) -> str:
+        """Receive a text message."""
+        message = self.receive(timeout)
+        if message is None or not message.is_text():
+            raise AssertionError(f"Expected a text message, got {message!r}")
+        return message.text
+
+    def receive_bytes(self, timeout: float = 5.0) -> bytes:
+        """Receive a binary message."""
+        message = self.receive(timeout)
+        if message is None or not message.is_binary():
+            raise AssertionError(f"Expected a binary message, got {message!r}")
+        return bytes(message.data)
+
+    def receive_json(self, timeout: float = 5.0) -> Any:
+        """Receive a text message and parse it as JSON."""
+        return _json.loads(self.receive_text(timeout))
+
+    @property
+    def closed(self) -> bool:
+        """Whether the handler has closed the connection."""
+        return self._session.closed
+
+    @property
+    def close_reason(self) -> Optional[str]:
+        """Why the server closed the connection (e.g. ``"pong_timeout"``), if it did."""
+        return self._session.close_reason
+
+    @property
+    def extensions(self) -> Optional[str]:
+        """The negotiated ``Sec-WebSocket-Extensions`` value, if any."""
+        return self._session.extensions
+
+    def close(self, timeout: float = 5.0):
+        """Close the connection and wait for the handler to return."""
+        self._session.close(timeout)
+
+    def __enter__(self):
+        return self
+
+    def __exit__(self, exc_type, exc, tb):
+        if exc_type is None:
+            self.close()
+        else:
+            try:
+                self.close()
+            except Exception:
+                pass
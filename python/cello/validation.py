@@ -88,3 +88,50 @@ def wrap_handler_with_validation(handler):
         return handler(request, *args, **kwargs)
 
     return wrapper
+
+
+_VALIDATION_ATTR = "__cello_validation__"
+
+
+def _to_schema(spec):
+    """Compile a JSON Schema dict or a Pydantic model class to a schema dict."""
+    if spec is None or isinstance(spec, dict):
+        return spec
+    if HAS_PYDANTIC and isinstance(spec, type) and issubclass(spec, BaseModel):
+        if hasattr(spec, "model_json_schema"):
+            return spec.model_json_schema()
+        return spec.schema()
+    raise TypeError(f"Expected a JSON Schema dict or Pydantic model, got {spec!r}")
+
+
+def validate_request(body=None, query=None, params=None):
+    """
+    Validate requests natively in Rust before the handler runs.
+
+    Each argument is a JSON Schema dict or a Pydantic model class. Invalid
+    requests receive a 422 response with ``{"detail": [...]}`` error details.
+    Apply it below the route decorator.
+
+    Example:
+        @app.post("/users/{id}")
+        @validate_request(body=UserCreate, params={"type": "object",
+                          "properties": {"id": {"type": "integer"}}})
+        def update_user(request):
+            ...
+    """
+    schemas = {
+        "body": _to_schema(body),
+        "query": _to_schema(query),
+        "params": _to_schema(params),
+    }
+
+    def decorator(func):
+        setattr(func, _VALIDATION_ATTR, schemas)
+        return func
+    return decorator
+
+
+def route_validation(func) -> dict:
+    """Return the schemas attached by ``validate_request`` (empty if none)."""
+    return getattr(func, _VALIDATION_ATTR, {})
+
@@ -0,0 +1,66 @@
+"""
+Cello Webhook Support.
+
+Outgoing webhooks are configured on the app (``app.enable_webhooks``,
+``app.register_webhook``); this module verifies inbound ones.
+
+Example:
+    from cello import App, verify_webhook
+
+    app = App()
+
+    @app.post("/hooks/stripe")
+    @verify_webhook("stripe", secret="whsec_...")
+    def stripe_events(request):
+        event = request.json()
+        ...
+"""
+
+from typing import Callable, List, Optional, Union
+
+
+def verify_webhook(
+    scheme: str,
+    secret: Union[str, List[str]],
+    tolerance: float = 300.0,
+    replay_window: Optional[float] = 300.0,
+) -> Callable:
+    """
+    Verify the HMAC signature of inbound webhooks on a route.
+
+    Verification runs in Rust, with constant-time comparison, before any
+    middleware or the handler sees the payload. Requests with a missing or
+    invalid signature, a signed timestamp more than ``tolerance`` seconds
+    off, or a signature already accepted within ``replay_window`` seconds
+    are rejected with 401. Apply it below the route decorator.
+
+    Args:
+        scheme: ``"stripe"`` (``Stripe-Signature``), ``"github"``
+            (``X-Hub-Signature-256``), ``"slack"`` (``X-Slack-Signature``)
+            or ``"cello"`` (``X-Webhook-Signature``, as sent by
+            ``app.register_webhook``).
+        secret: Signing secret, or a list of secrets accepted during key
+            rotation.
+        tolerance: Allowed clock difference for signed timestamps, in
+            seconds.
+        replay_window: How long accepted signatures are remembered, in
+            seconds (None disables replay protection).
+
+    Example:
+        @app.post("/hooks/github")
+        @verify_webhook("github", secret=["new-secret", "old-secret"])
+        def github_events(request):
+            return {"ok": True}
+    """
+    secrets = [secret] if isinstance(secret, str) else list(secret)
+
+    def decorator(fn: Callable) -> Callable:
+        fn.__cello_webhook__ = {
+            "scheme": scheme,
+            "secrets": secrets,
+            "tolerance": tolerance,
+            "replay_window": replay_window,
+        }
+        return fn
+
+    return decorator
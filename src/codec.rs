@@ -0,0 +1,852 @@
+//! Binary body codecs for Cello (MessagePack and CBOR).
+//!
+//! Provides:
+//! - `BodyFormat` content negotiation from `Content-Type` / `Accept`
+//! - Direct Python -> MessagePack/CBOR encoding on the same walker as JSON
+//! - MessagePack/CBOR decoding into `serde_json::Value` for request bodies
+//!
+//! Protobuf is negotiated here too, but its codec lives in
+//! `middleware::protobuf` because it needs per-route message descriptors.
+//!
+//! Decoded payloads share the JSON data model, so validation and
+//! `request.data()` treat every format alike. Binary strings that are not
+//! valid UTF-8 and extension types have no JSON equivalent and are rejected.
+
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyFloat};
+
+use crate::json::{
+    parse_json, python_to_json_bytes_direct, write_python_value, ConversionError, ConversionLimits,
+    ValueWriter, DEFAULT_MAX_JSON_DEPTH,
+};
+use crate::middleware::protobuf::PROTOBUF_CONTENT_TYPE;
+use crate::negotiation;
+
+// ============================================================================
+// Body Formats
+// ============================================================================
+
+/// Serialization format of a request or response body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BodyFormat {
+    #[default]
+    Json,
+    MsgPack,
+    Cbor,
+    /// Protocol Buffers; needs a message type bound to the route
+    Protobuf,
+}
+
+impl BodyFormat {
+    /// Canonical media type for the format.
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            BodyFormat::Json => "application/json",
+            BodyFormat::MsgPack => "application/msgpack",
+            BodyFormat::Cbor => "application/cbor",
+            BodyFormat::Protobuf => PROTOBUF_CONTENT_TYPE,
+        }
+    }
+
+    /// Map a media type (parameters allowed) to a format.
+    pub fn from_media_type(media_type: &str) -> Option<Self> {
+        let essence = media_type.split(';').next().unwrap_or("").trim();
+        match essence.to_ascii_lowercase().as_str() {
+            "application/json" => Some(BodyFormat::Json),
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => {
+                Some(BodyFormat::MsgPack)
+            }
+            "application/cbor" => Some(BodyFormat::Cbor),
+            "application/x-protobuf"
+            | "application/protobuf"
+            | "application/vnd.google.protobuf" => Some(BodyFormat::Protobuf),
+            essence if essence.ends_with("+json") => Some(BodyFormat::Json),
+            _ => None,
+        }
+    }
+
+    /// Format of a request body, defaulting to JSON for unknown or missing types.
+    pub fn from_content_type(content_type: Option<&str>) -> Self {
+        content_type
+            .and_then(Self::from_media_type)
+            .unwrap_or_default()
+    }
+
+    /// Pick the response format from an `Accept` header.
+    ///
+    /// Uses the RFC 9110 rules of `negotiation::best_match`, with JSON
+    /// offered first so that it wins ties and wildcards. Falls back to JSON
+    /// when nothing supported is acceptable.
+    pub fn negotiate(accept: Option<&str>) -> Self {
+        negotiation::best_match(accept, NEGOTIATED_TYPES)
+            .and_then(Self::from_media_type)
+            .unwrap_or_default()
+    }
+}
+
+/// Media types a response can be encoded as, in order of preference.
+const NEGOTIATED_TYPES: &[&str] = &[
+    "application/json",
+    "application/msgpack",
+    "application/x-msgpack",
+    "application/vnd.msgpack",
+    "application/cbor",
+    PROTOBUF_CONTENT_TYPE,
+    "application/protobuf",
+    "application/vnd.google.protobuf",
+];
+
+/// Protobuf payloads are only meaningful against a message descriptor.
+const PROTOBUF_NEEDS_TYPE: &str =
+    "Protobuf bodies need a message type; bind one to the route with protobuf_body()";
+
+// ============================================================================
+// Encoding (Python -> bytes)
+// ============================================================================
+
+/// PERF: Encode a Python object directly in the given format.
+/// Returns Ok(None) for Response objects, like `python_to_json_bytes_direct`.
+pub fn python_to_bytes_direct(
+    py: Python<'_>,
+    obj: &PyAny,
+    format: BodyFormat,
+) -> Result<Option<Vec<u8>>, String> {
+    let mut buf = Vec::with_capacity(128);
+    let result = match format {
+        BodyFormat::Json => return python_to_json_bytes_direct(py, obj),
+        BodyFormat::MsgPack => write_python_value(
+            obj,
+            &mut MsgPackWriter,
+            &mut buf,
+            ConversionLimits::default(),
+        ),
+        BodyFormat::Cbor => {
+            write_python_value(obj, &mut CborWriter, &mut buf, ConversionLimits::default())
+        }
+        BodyFormat::Protobuf => return Err(PROTOBUF_NEEDS_TYPE.to_string()),
+    };
+
+    match result {
+        Ok(()) => Ok(Some(buf)),
+        // Not a plain value - likely a Response object, fall back to Value path
+        Err(ConversionError::Unsupported(_)) if is_cello_response(obj) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Encode a Python object, rejecting anything that is not plain data.
+pub fn python_to_bytes(obj: &PyAny, format: BodyFormat) -> Result<Vec<u8>, String> {
+    python_to_bytes_direct(obj.py(), obj, format)?
+        .ok_or_else(|| format!("Cannot encode {obj:?} as {}", format.content_type()))
+}
+
+fn is_cello_response(obj: &PyAny) -> bool {
+    obj.get_type()
+        .name()
+        .map(|name| name == "Response")
+        .unwrap_or(false)
+}
+
+/// A Python scalar in the shared binary data model.
+enum Scalar<'py> {
+    Nil,
+    Bool(bool),
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    Str(String),
+    Bytes(&'py [u8]),
+}
+
+impl<'py> Scalar<'py> {
+    #[inline]
+    fn extract(obj: &'py PyAny) -> Option<Self> {
+        if obj.is_none() {
+            return Some(Scalar::Nil);
+        }
+        // bool must come before int since bool is a subclass of int in Python
+        if let Ok(b) = obj.extract::<bool>() {
+            return Some(Scalar::Bool(b));
+        }
+        if let Ok(i) = obj.extract::<i64>() {
+            return Some(Scalar::Int(i));
+        }
+        if let Ok(u) = obj.extract::<u64>() {
+            return Some(Scalar::UInt(u));
+        }
+        // Exact floats only; Decimal and friends go through the native path
+        if let Ok(f) = obj.downcast::<PyFloat>() {
+            return Some(Scalar::Float(f.value()));
+        }
+        if let Ok(s) = obj.extract::<String>() {
+            return Some(Scalar::Str(s));
+        }
+        if let Ok(bytes) = obj.downcast::<PyBytes>() {
+            return Some(Scalar::Bytes(bytes.as_bytes()));
+        }
+        None
+    }
+}
+
+/// MessagePack output for the direct walker.
+struct MsgPackWriter;
+
+impl MsgPackWriter {
+    fn write_str(s: &str, buf: &mut Vec<u8>) {
+        let len = s.len();
+        if len < 32 {
+            buf.push(0xa0 | len as u8);
+        } else if len <= u8::MAX as usize {
+            buf.push(0xd9);
+            buf.push(len as u8);
+        } else if len <= u16::MAX as usize {
+            buf.push(0xda);
+            buf.extend_from_slice(&(len as u16).to_be_bytes());
+        } else {
+            buf.push(0xdb);
+            buf.extend_from_slice(&(len as u32).to_be_bytes());
+        }
+        buf.extend_from_slice(s.as_bytes());
+    }
+
+    fn write_uint(u: u64, buf: &mut Vec<u8>) {
+        if u < 128 {
+            buf.push(u as u8);
+        } else if u <= u8::MAX as u64 {
+            buf.push(0xcc);
+            buf.push(u as u8);
+        } else if u <= u16::MAX as u64 {
+            buf.push(0xcd);
+            buf.extend_from_slice(&(u as u16).to_be_bytes());
+        } else if u <= u32::MAX as u64 {
+            buf.push(0xce);
+            buf.extend_from_slice(&(u as u32).to_be_bytes());
+        } else {
+            buf.push(0xcf);
+            buf.extend_from_slice(&u.to_be_bytes());
+        }
+    }
+
+    fn write_int(i: i64, buf: &mut Vec<u8>) {
+        if i >= 0 {
+            Self::write_uint(i as u64, buf);
+        } else if i >= -32 {
+            buf.push(i as i8 as u8);
+        } else if i >= i8::MIN as i64 {
+            buf.push(0xd0);
+            buf.push(i as i8 as u8);
+        } else if i >= i16::MIN as i64 {
+            buf.push(0xd1);
+            buf.extend_from_slice(&(i as i16).to_be_bytes());
+        } else if i >= i32::MIN as i64 {
+            buf.push(0xd2);
+            buf.extend_from_slice(&(i as i32).to_be_bytes());
+        } else {
+            buf.push(0xd3);
+            buf.extend_from_slice(&i.to_be_bytes());
+        }
+    }
+
+    fn write_len(len: usize, fix: u8, fix_max: usize, marker16: u8, buf: &mut Vec<u8>) {
+        if len <= fix_max {
+            buf.push(fix | len as u8);
+        } else if len <= u16::MAX as usize {
+            buf.push(marker16);
+            buf.extend_from_slice(&(len as u16).to_be_bytes());
+        } else {
+            buf.push(marker16 + 1);
+            buf.extend_from_slice(&(len as u32).to_be_bytes());
+        }
+    }
+}
+
+impl ValueWriter for MsgPackWriter {
+    fn scalar(&mut self, obj: &PyAny, buf: &mut Vec<u8>) -> Result<bool, ConversionError> {
+        let Some(scalar) = Scalar::extract(obj) else {
+            return Ok(false);
+        };
+        match scalar {
+            Scalar::Nil => buf.push(0xc0),
+            Scalar::Bool(b) => buf.push(if b { 0xc3 } else { 0xc2 }),
+            Scalar::Int(i) => Self::write_int(i, buf),
+            Scalar::UInt(u) => Self::write_uint(u, buf),
+            Scalar::Float(f) => {
+                buf.push(0xcb);
+                buf.extend_from_slice(&f.to_be_bytes());
+            }
+            Scalar::Str(s) => Self::write_str(&s, buf),
+            Scalar::Bytes(bytes) => {
+                let len = bytes.len();
+                if len <= u8::MAX as usize {
+                    buf.push(0xc4);
+                    buf.push(len as u8);
+                } else if len <= u16::MAX as usize {
+                    buf.push(0xc5);
+                    buf.extend_from_slice(&(len as u16).to_be_bytes());
+                } else {
+                    buf.push(0xc6);
+                    buf.extend_from_slice(&(len as u32).to_be_bytes());
+                }
+                buf.extend_from_slice(bytes);
+            }
+        }
+        Ok(true)
+    }
+
+    fn begin(&mut self, is_object: bool, len: usize, buf: &mut Vec<u8>) {
+        if is_object {
+            Self::write_len(len, 0x80, 15, 0xde, buf);
+        } else {
+            Self::write_len(len, 0x90, 15, 0xdc, buf);
+        }
+    }
+
+    fn child(&mut self, _first: bool, key: Option<&str>, buf: &mut Vec<u8>) {
+        if let Some(key) = key {
+            Self::write_str(key, buf);
+        }
+    }
+
+    fn end(&mut self, _is_object: bool, _buf: &mut Vec<u8>) {}
+}
+
+/// CBOR (RFC 8949) output for the direct walker, using definite lengths.
+struct CborWriter;
+
+impl CborWriter {
+    fn write_head(major: u8, value: u64, buf: &mut Vec<u8>) {
+        let major = major << 5;
+        if value < 24 {
+            buf.push(major | value as u8);
+        } else if value <= u8::MAX as u64 {
+            buf.push(major | 24);
+            buf.push(value as u8);
+        } else if value <= u16::MAX as u64 {
+            buf.push(major | 25);
+            buf.extend_from_slice(&(value as u16).to_be_bytes());
+        } else if value <= u32::MAX as u64 {
+            buf.push(major | 26);
+            buf.extend_from_slice(&(value as u32).to_be_bytes());
+        } else {
+            buf.push(major | 27);
+            buf.extend_from_slice(&value.to_be_bytes());
+        }
+    }
+
+    fn write_str(s: &str, buf: &mut Vec<u8>) {
+        Self::write_head(3, s.len() as u64, buf);
+        buf.extend_from_slice(s.as_bytes());
+    }
+}
+
+impl ValueWriter for CborWriter {
+    fn scalar(&mut self, obj: &PyAny, buf: &mut Vec<u8>) -> Result<bool, ConversionError> {
+        let Some(scalar) = Scalar::extract(obj) else {
+            return Ok(false);
+        };
+        match scalar {
+            Scalar::Nil => buf.push(0xf6),
+            Scalar::Bool(b) => buf.push(if b { 0xf5 } else { 0xf4 }),
+            Scalar::Int(i) if i >= 0 => Self::write_head(0, i as u64, buf),
+            // Negative integers encode -1 - n
+            Scalar::Int(i) => Self::write_head(1, !(i as u64), buf),
+            Scalar::UInt(u) => Self::write_head(0, u, buf),
+            Scalar::Float(f) => {
+                buf.push(0xfb);
+                buf.extend_from_slice(&f.to_be_bytes());
+            }
+            Scalar::Str(s) => Self::write_str(&s, buf),
+            Scalar::Bytes(bytes) => {
+                Self::write_head(2, bytes.len() as u64, buf);
+                buf.extend_from_slice(bytes);
+            }
+        }
+        Ok(true)
+    }
+
+    fn begin(&mut self, is_object: bool, len: usize, buf: &mut Vec<u8>) {
+        Self::write_head(if is_object { 5 } else { 4 }, len as u64, buf);
+    }
+
+    fn child(&mut self, _first: bool, key: Option<&str>, buf: &mut Vec<u8>) {
+        if let Some(key) = key {
+            Self::write_str(key, buf);
+        }
+    }
+
+    fn end(&mut self, _is_object: bool, _buf: &mut Vec<u8>) {}
+}
+
+// ============================================================================
+// Decoding (bytes -> serde_json::Value)
+// ============================================================================
+
+/// Decode a request body in the given format.
+pub fn decode(input: &[u8], format: BodyFormat) -> Result<serde_json::Value, String> {
+    match format {
+        BodyFormat::Json => {
+            let text = std::str::from_utf8(input).map_err(|e| e.to_string())?;
+            parse_json(text)
+        }
+        BodyFormat::MsgPack => decode_msgpack(input),
+        BodyFormat::Cbor => decode_cbor(input),
+        BodyFormat::Protobuf => Err(PROTOBUF_NEEDS_TYPE.to_string()),
+    }
+}
+
+/// Decode a MessagePack document.
+pub fn decode_msgpack(input: &[u8]) -> Result<serde_json::Value, String> {
+    let mut reader = Reader::new(input);
+    let value = reader.msgpack_value(0)?;
+    reader.finish()?;
+    Ok(value)
+}
+
+/// Decode a CBOR document.
+pub fn decode_cbor(input: &[u8]) -> Result<serde_json::Value, String> {
+    let mut reader = Reader::new(input);
+    let value = reader.cbor_value(0)?.ok_or("Unexpected CBOR break")?;
+    reader.finish()?;
+    Ok(value)
+}
+
+/// Elements reserved up front for a declared array or map length. A byte of
+/// input can declare a 32-byte `Value`, so larger collections grow as they
+/// are read.
+const MAX_PREALLOCATED: usize = 1024;
+
+/// Byte reader shared by both decoders.
+///
+/// Recursion is bounded by `DEFAULT_MAX_JSON_DEPTH`, and declared lengths are
+/// checked against the remaining input before allocating.
+struct Reader<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(input: &'a [u8]) -> Self {
+        Self { input, pos: 0 }
+    }
+
+    fn finish(&self) -> Result<(), String> {
+        if self.pos != self.input.len() {
+            return Err(format!("Trailing bytes at offset {}", self.pos));
+        }
+        Ok(())
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if self.input.len() - self.pos < n {
+            return Err("Unexpected end of input".to_string());
+        }
+        let bytes = &self.input[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn uint(&mut self, size: usize) -> Result<u64, String> {
+        Ok(self
+            .take(size)?
+            .iter()
+            .fold(0u64, |acc, b| (acc << 8) | *b as u64))
+    }
+
+    /// Check a declared element count against the remaining input.
+    fn count(&self, len: u64) -> Result<usize, String> {
+        let remaining = (self.input.len() - self.pos) as u64;
+        if len > remaining {
+            return Err("Declared length exceeds input".to_string());
+        }
+        Ok(len as usize)
+    }
+
+    fn text(&mut self, len: u64) -> Result<String, String> {
+        let len = self.count(len)?;
+        std::str::from_utf8(self.take(len)?)
+            .map(str::to_string)
+            .map_err(|_| "Invalid UTF-8 in string".to_string())
+    }
+
+    fn enter(depth: usize) -> Result<(), String> {
+        if depth >= DEFAULT_MAX_JSON_DEPTH {
+            return Err(ConversionError::DepthLimitExceeded {
+                limit: DEFAULT_MAX_JSON_DEPTH,
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    // ---- MessagePack ----
+
+    fn msgpack_value(&mut self, depth: usize) -> Result<serde_json::Value, String> {
+        use serde_json::Value;
+
+        let marker = self.byte()?;
+        Ok(match marker {
+            0x00..=0x7f => Value::from(marker),
+            0x80..=0x8f => self.msgpack_map((marker & 0x0f) as u64, depth)?,
+            0x90..=0x9f => self.msgpack_array((marker & 0x0f) as u64, depth)?,
+            0xa0..=0xbf => Value::String(self.text((marker & 0x1f) as u64)?),
+            0xc0 => Value::Null,
+            0xc2 => Value::Bool(false),
+            0xc3 => Value::Bool(true),
+            0xc4..=0xc6 => {
+                let len = self.uint(1 << (marker - 0xc4))?;
+                Value::String(
+                    self.text(len)
+                        .map_err(|_| "Binary values must be UTF-8 text".to_string())?,
+                )
+            }
+            0xca => Value::from(f32::from_bits(self.uint(4)? as u32) as f64),
+            0xcb => Value::from(f64::from_bits(self.uint(8)?)),
+            0xcc..=0xcf => Value::from(self.uint(1 << (marker - 0xcc))?),
+            0xd0 => Value::from(self.uint(1)? as u8 as i8),
+            0xd1 => Value::from(self.uint(2)? as u16 as i16),
+            0xd2 => Value::from(self.uint(4)? as u32 as i32),
+            0xd3 => Value::from(self.uint(8)? as i64),
+            0xd9..=0xdb => {
+                let len = self.uint(1 << (marker - 0xd9))?;
+                Value::String(self.text(len)?)
+            }
+            0xdc | 0xdd => {
+                let len = self.uint(if marker == 0xdc { 2 } else { 4 })?;
+                self.msgpack_array(len, depth)?
+            }
+            0xde | 0xdf => {
+                let len = self.uint(if marker == 0xde { 2 } else { 4 })?;
+                self.msgpack_map(len, depth)?
+            }
+            0xe0..=0xff => Value::from(marker as i8),
+            _ => return Err(format!("Unsupported MessagePack type 0x{marker:02x}")),
+        })
+    }
+
+    fn msgpack_array(&mut self, len: u64, depth: usize) -> Result<serde_json::Value, String> {
+        Self::enter(depth)?;
+        let len = self.count(len)?;
+        let mut items = Vec::with_capacity(len.min(MAX_PREALLOCATED));
+        for _ in 0..len {
+            items.push(self.msgpack_value(depth + 1)?);
+        }
+        Ok(serde_json::Value::Array(items))
+    }
+
+    fn msgpack_map(&mut self, len: u64, depth: usize) -> Result<serde_json::Value, String> {
+        Self::enter(depth)?;
+        let len = self.count(len)?;
+        let mut map = serde_json::Map::with_capacity(len.min(MAX_PREALLOCATED));
+        for _ in 0..len {
+            let key = object_key(self.msgpack_value(depth + 1)?)?;
+            let value = self.msgpack_value(depth + 1)?;
+            map.insert(key, value);
+        }
+        Ok(serde_json::Value::Object(map))
+    }
+
+    // ---- CBOR ----
+
+    /// Read a CBOR argument; `None` means indefinite length.
+    fn cbor_argument(&mut self, info: u8) -> Result<Option<u64>, String> {
+        match info {
+            0..=23 => Ok(Some(info as u64)),
+            24..=27 => Ok(Some(self.uint(1 << (info - 24))?)),
+            31 => Ok(None),
+            _ => Err(format!("Invalid CBOR additional info {info}")),
+        }
+    }
+
+    /// Decode one CBOR item; `None` is the indefinite-length break marker.
+    fn cbor_value(&mut self, depth: usize) -> Result<Option<serde_json::Value>, String> {
+        use serde_json::Value;
+
+        let initial = self.byte()?;
+        let (major, info) = (initial >> 5, initial & 0x1f);
+        if initial == 0xff {
+            return Ok(None);
+        }
+
+        let value = match major {
+            0 => Value::from(self.cbor_definite(info)?),
+            1 => {
+                let n = self.cbor_definite(info)?;
+                i64::try_from(n)
+                    .map(|n| Value::from(-1 - n))
+                    .map_err(|_| "CBOR negative integer out of range".to_string())?
+            }
+            2 | 3 => {
+                let bytes = self.cbor_string(major, info)?;
+                Value::String(String::from_utf8(bytes).map_err(|_| {
+                    if major == 2 {
+                        "Binary values must be UTF-8 text".to_string()
+                    } else {
+                        "Invalid UTF-8 in string".to_string()
+                    }
+                })?)
+            }
+            4 => {
+                Self::enter(depth)?;
+                let mut items = Vec::new();
+                match self.cbor_argument(info)? {
+                    Some(len) => {
+                        let len = self.count(len)?;
+                        items.reserve(len.min(MAX_PREALLOCATED));
+                        for _ in 0..len {
+                            items.push(self.cbor_item(depth + 1)?);
+                        }
+                    }
+                    None => {
+                        while let Some(item) = self.cbor_value(depth + 1)? {
+                            items.push(item);
+                        }
+                    }
+                }
+                Value::Array(items)
+            }
+            5 => {
+                Self::enter(depth)?;
+                let mut map = serde_json::Map::new();
+                match self.cbor_argument(info)? {
+                    Some(len) => {
+                        for _ in 0..self.count(len)? {
+                            let key = object_key(self.cbor_item(depth + 1)?)?;
+                            map.insert(key, self.cbor_item(depth + 1)?);
+                        }
+                    }
+                    None => {
+                        while let Some(key) = self.cbor_value(depth + 1)? {
+                            map.insert(object_key(key)?, self.cbor_item(depth + 1)?);
+                        }
+                    }
+                }
+                Value::Object(map)
+            }
+            // Tags (dates, bignums, ...) decode to their content
+            6 => {
+                self.cbor_definite(info)?;
+                self.cbor_item(depth)?
+            }
+            _ => match info {
+                20 => Value::Bool(false),
+                21 => Value::Bool(true),
+                22 | 23 => Value::Null,
+                25 => Value::from(f16_to_f64(self.uint(2)? as u16)),
+                26 => Value::from(f32::from_bits(self.uint(4)? as u32) as f64),
+                27 => Value::from(f64::from_bits(self.uint(8)?)),
+                _ => return Err(format!("Unsupported CBOR simple value {info}")),
+            },
+        };
+        Ok(Some(value))
+    }
+
+    fn cbor_item(&mut self, depth: usize) -> Result<serde_json::Value, String> {
+        self.cbor_value(depth)?
+            .ok_or_else(|| "Unexpected CBOR break".to_string())
+    }
+
+    fn cbor_definite(&mut self, info: u8) -> Result<u64, String> {
+        self.cbor_argument(info)?
+            .ok_or_else(|| "Indefinite length not allowed here".to_string())
+    }
+
+    /// Read a byte or text string, joining indefinite-length chunks.
+    fn cbor_string(&mut self, major: u8, info: u8) -> Result<Vec<u8>, String> {
+        match self.cbor_argument(info)? {
+            Some(len) => {
+                let len = self.count(len)?;
+                Ok(self.take(len)?.to_vec())
+            }
+            None => {
+                let mut out = Vec::new();
+                loop {
+                    let initial = self.byte()?;
+                    if initial == 0xff {
+                        return Ok(out);
+                    }
+                    if initial >> 5 != major {
+                        return Err("Mismatched CBOR string chunk".to_string());
+                    }
+                    let len = self.cbor_definite(initial & 0x1f)?;
+                    let len = self.count(len)?;
+                    out.extend_from_slice(self.take(len)?);
+                }
+            }
+        }
+    }
+}
+
+/// Convert a decoded map key to a JSON object key.
+fn object_key(key: serde_json::Value) -> Result<String, String> {
+    match key {
+        serde_json::Value::String(s) => Ok(s),
+        serde_json::Value::Number(n) => Ok(n.to_string()),
+        _ => Err(ConversionError::NonStringKey.into()),
+    }
+}
+
+/// Widen an IEEE 754 half-precision float.
+fn f16_to_f64(half: u16) -> f64 {
+    let exponent = (half >> 10) & 0x1f;
+    let mantissa = (half & 0x3ff) as f64;
+    let magnitude = match exponent {
+        0 => mantissa * 2f64.powi(-24),
+        31 if mantissa == 0.0 => f64::INFINITY,
+        31 => f64::NAN,
+        _ => (1.0 + mantissa / 1024.0) * 2f64.powi(exponent as i32 - 15),
+    };
+    if half & 0x8000 != 0 {
+        -magnitude
+    } else {
+        magnitude
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pyo3::types::PyDict;
+    use serde_json::json;
+
+    fn encode(format: BodyFormat, src: &str) -> Vec<u8> {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let obj = py.eval(src, None, None).unwrap();
+            python_to_bytes(obj, format).unwrap()
+        })
+    }
+
+    #[test]
+    fn test_negotiate() {
+        assert_eq!(BodyFormat::negotiate(None), BodyFormat::Json);
+        assert_eq!(BodyFormat::negotiate(Some("*/*")), BodyFormat::Json);
+        assert_eq!(
+            BodyFormat::negotiate(Some("application/msgpack")),
+            BodyFormat::MsgPack
+        );
+        assert_eq!(
+            BodyFormat::negotiate(Some("application/json, application/cbor")),
+            BodyFormat::Json
+        );
+        assert_eq!(
+            BodyFormat::negotiate(Some("application/json;q=0.5, application/cbor")),
+            BodyFormat::Cbor
+        );
+        assert_eq!(
+            BodyFormat::from_content_type(Some("application/x-msgpack; charset=binary")),
+            BodyFormat::MsgPack
+        );
+        assert_eq!(BodyFormat::from_content_type(None), BodyFormat::Json);
+        assert_eq!(
+            BodyFormat::negotiate(Some("application/x-protobuf")),
+            BodyFormat::Protobuf
+        );
+        // A refused JSON is not brought back by a wildcard
+        assert_eq!(
+            BodyFormat::negotiate(Some("application/json;q=0, */*")),
+            BodyFormat::MsgPack
+        );
+        // The most specific range sets a type's quality
+        assert_eq!(
+            BodyFormat::negotiate(Some("application/*;q=0.9, application/cbor;q=0.2")),
+            BodyFormat::Json
+        );
+        assert_eq!(
+            BodyFormat::negotiate(Some(
+                "application/vnd.msgpack;q=0.8, application/json;q=0.5"
+            )),
+            BodyFormat::MsgPack
+        );
+        assert_eq!(BodyFormat::negotiate(Some("text/html")), BodyFormat::Json);
+    }
+
+    #[test]
+    fn test_msgpack_encoding() {
+        // {"a": 1, "b": [true, None]}
+        assert_eq!(
+            encode(BodyFormat::MsgPack, "{'a': 1, 'b': [True, None]}"),
+            vec![0x82, 0xa1, b'a', 0x01, 0xa1, b'b', 0x92, 0xc3, 0xc0]
+        );
+        assert_eq!(encode(BodyFormat::MsgPack, "-1"), vec![0xff]);
+        assert_eq!(encode(BodyFormat::MsgPack, "-200"), vec![0xd1, 0xff, 0x38]);
+        assert_eq!(
+            encode(BodyFormat::MsgPack, "b'hi'"),
+            vec![0xc4, 2, b'h', b'i']
+        );
+    }
+
+    #[test]
+    fn test_cbor_encoding() {
+        // RFC 8949 Appendix A examples
+        assert_eq!(encode(BodyFormat::Cbor, "-1000"), vec![0x39, 0x03, 0xe7]);
+        assert_eq!(
+            encode(BodyFormat::Cbor, "1000000"),
+            vec![0x1a, 0x00, 0x0f, 0x42, 0x40]
+        );
+        assert_eq!(
+            encode(BodyFormat::Cbor, "{'a': 1, 'b': [2, 3]}"),
+            vec![0xa2, 0x61, 0x61, 0x01, 0x61, 0x62, 0x82, 0x02, 0x03]
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let src = "{'name': 'cello', 'n': -70000, 'big': 2**63, 'pi': 3.5, 'tags': ['x' * 40, False], 'nested': {'k': None}}";
+        let expected = json!({
+            "name": "cello", "n": -70000, "big": 9223372036854775808u64, "pi": 3.5,
+            "tags": ["x".repeat(40), false], "nested": {"k": null}
+        });
+        for format in [BodyFormat::MsgPack, BodyFormat::Cbor] {
+            let bytes = encode(format, src);
+            assert_eq!(decode(&bytes, format).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_cbor_indefinite_and_half_floats() {
+        // [_ 1, "ab" (chunked)], then 1.5 as a half float
+        let bytes = [
+            0x9f, 0x01, 0x7f, 0x61, b'a', 0x61, b'b', 0xff, 0xf9, 0x3e, 0x00, 0xff,
+        ];
+        assert_eq!(decode_cbor(&bytes).unwrap(), json!([1, "ab", 1.5]));
+    }
+
+    #[test]
+    fn test_decode_rejects_malformed_input() {
+        // Truncated map, oversize declared length, trailing bytes
+        assert!(decode_msgpack(&[0x81, 0xa1, b'a']).is_err());
+        assert!(decode_msgpack(&[0xdd, 0xff, 0xff, 0xff, 0xff]).is_err());
+        assert!(decode_cbor(&[0x01, 0x02]).is_err());
+        // Deep nesting is bounded
+        let deep = vec![0x91; DEFAULT_MAX_JSON_DEPTH + 1];
+        assert!(decode_msgpack(&deep).unwrap_err().contains("depth"));
+        // Collections larger than the pre-allocation still decode whole
+        let mut long = vec![0xdd, 0x00, 0x00, 0x27, 0x10];
+        long.extend([0xc0; 10_000]);
+        assert_eq!(
+            decode_msgpack(&long).unwrap().as_array().unwrap().len(),
+            10_000
+        );
+    }
+
+    #[test]
+    fn test_response_objects_fall_back() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let dict = PyDict::new(py);
+            assert!(python_to_bytes_direct(py, dict, BodyFormat::Cbor)
+                .unwrap()
+                .is_some());
+            let response = Py::new(py, crate::response::Response::new(200)).unwrap();
+            assert!(
+                python_to_bytes_direct(py, response.as_ref(py), BodyFormat::MsgPack)
+                    .unwrap()
+                    .is_none()
+            );
+        });
+    }
+}
@@ -0,0 +1,642 @@
+//! Application Configuration Loading for Cello Framework
+//!
+//! Merges configuration from several sources into one tree, each layer
+//! overriding the ones before it:
+//! 1. Defaults passed by the application
+//! 2. TOML, YAML or JSON files
+//! 3. The active profile: a file's `[profiles.<name>]` table, then a
+//!    sibling `<stem>.<name>.<ext>` file if one exists
+//! 4. Environment variables such as `CELLO_REDIS__URL`, where `__`
+//!    separates nested keys
+//!
+//! The profile is chosen explicitly or by `CELLO_PROFILE`, and is `dev`
+//! otherwise. Sections such as `[server]`, `[redis]` or `[grpc]` feed the
+//! matching config classes, so a misspelled or mistyped option fails at
+//! startup.
+//!
+//! # Example
+//! ```toml
+//! [server]
+//! port = 8000
+//!
+//! [redis]
+//! url = "redis://localhost:6379"
+//!
+//! [profiles.prod.server]
+//! host = "0.0.0.0"
+//! workers = 4
+//! ```
+//!
+//! ```python
+//! app = App()
+//! app.load_config("cello.toml")   # CELLO_PROFILE=prod CELLO_REDIS__URL=...
+//! app.enable_redis()              # built from [redis]
+//! app.run()                       # host, port and workers from [server]
+//! ```
+
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList, PyTuple, PyType};
+use serde_json::{Map, Value};
+use std::path::{Path, PathBuf};
+
+/// Profile used when none is given.
+pub const DEFAULT_PROFILE: &str = "dev";
+
+/// Variables under the prefix that are not configuration keys.
+const RESERVED_ENV: &[&str] = &["PROFILE", "WORKER"];
+
+// ============================================================================
+// Errors
+// ============================================================================
+
+/// Configuration loading and access errors.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    /// A file could not be read.
+    Io { path: String, message: String },
+    /// A file could not be parsed.
+    Parse { path: String, message: String },
+    /// Required keys are missing.
+    Missing(Vec<String>),
+    /// A key holds a value of the wrong type.
+    Type { key: String, expected: &'static str },
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Io { path, message } => write!(f, "cannot read {path}: {message}"),
+            ConfigError::Parse { path, message } => write!(f, "cannot parse {path}: {message}"),
+            ConfigError::Missing(keys) => {
+                write!(f, "missing required config: {}", keys.join(", "))
+            }
+            ConfigError::Type { key, expected } => {
+                write!(f, "config '{key}' must be {expected}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<ConfigError> for PyErr {
+    fn from(err: ConfigError) -> PyErr {
+        match err {
+            ConfigError::Io { .. } => {
+                pyo3::exceptions::PyFileNotFoundError::new_err(err.to_string())
+            }
+            ConfigError::Type { .. } => pyo3::exceptions::PyTypeError::new_err(err.to_string()),
+            _ => pyo3::exceptions::PyValueError::new_err(err.to_string()),
+        }
+    }
+}
+
+// ============================================================================
+// Sources
+// ============================================================================
+
+/// Deep-merge `overlay` into `base`; tables merge key by key and any other
+/// value replaces the one below it.
+pub fn merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Parse a configuration file, choosing the format by extension.
+pub fn parse_file(path: &Path) -> Result<Value, ConfigError> {
+    let display = path.display().to_string();
+    let text = std::fs::read_to_string(path).map_err(|e| ConfigError::Io {
+        path: display.clone(),
+        message: e.to_string(),
+    })?;
+    let parse_error = |message: String| ConfigError::Parse {
+        path: display.clone(),
+        message,
+    };
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let value = match extension.as_str() {
+        "toml" => toml::from_str(&text).map_err(|e| parse_error(e.to_string()))?,
+        "yaml" | "yml" => serde_yaml::from_str(&text).map_err(|e| parse_error(e.to_string()))?,
+        "json" => serde_json::from_str(&text).map_err(|e| parse_error(e.to_string()))?,
+        other => {
+            return Err(parse_error(format!(
+                "unsupported format '{other}' (expected toml, yaml or json)"
+            )))
+        }
+    };
+    match value {
+        Value::Object(_) => Ok(value),
+        Value::Null => Ok(Value::Object(Map::new())),
+        _ => Err(parse_error("top level must be a table".to_string())),
+    }
+}
+
+/// The profile file next to `path`: `cello.toml` becomes `cello.prod.toml`.
+fn profile_file(path: &Path, profile: &str) -> Option<PathBuf> {
+    let stem = path.file_stem()?.to_str()?;
+    let extension = path.extension()?.to_str()?;
+    Some(path.with_file_name(format!("{stem}.{profile}.{extension}")))
+}
+
+/// Parse an environment value: JSON scalars, arrays and tables keep their
+/// type and anything else is a string.
+fn parse_env_value(raw: &str) -> Value {
+    serde_json::from_str(raw).unwrap_or_else(|_| Value::String(raw.to_string()))
+}
+
+/// Overrides from variables named `<PREFIX>_<KEY>`, with `__` separating
+/// nested keys. Keys are lower-cased.
+pub fn env_overrides(prefix: &str, vars: impl IntoIterator<Item = (String, String)>) -> Value {
+    let prefix = format!("{}_", prefix.to_ascii_uppercase());
+    let mut overrides = Value::Object(Map::new());
+    for (name, raw) in vars {
+        let Some(key) = name.strip_prefix(&prefix) else {
+            continue;
+        };
+        if key.is_empty() || RESERVED_ENV.contains(&key) {
+            continue;
+        }
+        let parts: Vec<&str> = key.split("__").collect();
+        let value = parts
+            .into_iter()
+            .rev()
+            .fold(parse_env_value(&raw), |value, part| {
+                Value::Object(Map::from_iter([(part.to_ascii_lowercase(), value)]))
+            });
+        merge(&mut overrides, value);
+    }
+    overrides
+}
+
+// ============================================================================
+// Loader
+// ============================================================================
+
+/// Builds an [`AppConfig`] from defaults, files, a profile and the
+/// environment.
+#[derive(Debug, Clone)]
+pub struct ConfigLoader {
+    defaults: Value,
+    files: Vec<PathBuf>,
+    profile: Option<String>,
+    env_prefix: String,
+}
+
+impl Default for ConfigLoader {
+    fn default() -> Self {
+        Self {
+            defaults: Value::Object(Map::new()),
+            files: Vec::new(),
+            profile: None,
+            env_prefix: "CELLO".to_string(),
+        }
+    }
+}
+
+impl ConfigLoader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lowest-priority values.
+    pub fn defaults(mut self, defaults: Value) -> Self {
+        self.defaults = defaults;
+        self
+    }
+
+    /// Add a file; later files override earlier ones.
+    pub fn file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.files.push(path.into());
+        self
+    }
+
+    /// Select the profile instead of reading `<PREFIX>_PROFILE`.
+    pub fn profile(mut self, profile: &str) -> Self {
+        self.profile = Some(profile.to_string());
+        self
+    }
+
+    /// Prefix of environment overrides (default `CELLO`).
+    pub fn env_prefix(mut self, prefix: &str) -> Self {
+        self.env_prefix = prefix.to_string();
+        self
+    }
+
+    /// Load from the process environment.
+    pub fn load(&self) -> Result<AppConfig, ConfigError> {
+        self.load_with_env(std::env::vars())
+    }
+
+    /// Load using the given environment variables.
+    pub fn load_with_env(
+        &self,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<AppConfig, ConfigError> {
+        let vars: Vec<(String, String)> = vars.into_iter().collect();
+        let profile_var = format!("{}_PROFILE", self.env_prefix.to_ascii_uppercase());
+        let profile = self
+            .profile
+            .clone()
+            .or_else(|| {
+                vars.iter()
+                    .find(|(name, _)| *name == profile_var)
+                    .map(|(_, value)| value.clone())
+            })
+            .unwrap_or_else(|| DEFAULT_PROFILE.to_string());
+
+        let mut values = self.defaults.clone();
+        for path in &self.files {
+            let mut file = parse_file(path)?;
+            let profiles = file
+                .as_object_mut()
+                .and_then(|table| table.remove("profiles"));
+            merge(&mut values, file);
+            if let Some(section) = profiles.and_then(|mut p| p.get_mut(&profile).map(Value::take)) {
+                merge(&mut values, section);
+            }
+            if let Some(path) = profile_file(path, &profile).filter(|p| p.is_file()) {
+                merge(&mut values, parse_file(&path)?);
+            }
+        }
+        merge(&mut values, env_overrides(&self.env_prefix, vars));
+        if let Some(table) = values.as_object_mut() {
+            table.remove("profiles");
+        }
+
+        Ok(AppConfig { values, profile })
+    }
+}
+
+// ============================================================================
+// Merged Configuration
+// ============================================================================
+
+/// Merged configuration with typed access by dotted path.
+#[derive(Debug, Clone)]
+pub struct AppConfig {
+    values: Value,
+    profile: String,
+}
+
+impl AppConfig {
+    /// The active profile.
+    pub fn profile(&self) -> &str {
+        &self.profile
+    }
+
+    /// The whole tree.
+    pub fn values(&self) -> &Value {
+        &self.values
+    }
+
+    /// The value at a dotted path such as `server.port`.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        key.split('.')
+            .try_fold(&self.values, |value, part| value.get(part))
+            .filter(|value| !value.is_null())
+    }
+
+    fn typed<'a, T>(
+        &'a self,
+        key: &str,
+        expected: &'static str,
+        convert: impl FnOnce(&'a Value) -> Option<T>,
+    ) -> Result<Option<T>, ConfigError> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(value) => convert(value).map(Some).ok_or(ConfigError::Type {
+                key: key.to_string(),
+                expected,
+            }),
+        }
+    }
+
+    pub fn get_str(&self, key: &str) -> Result<Option<&str>, ConfigError> {
+        self.typed(key, "a string", Value::as_str)
+    }
+
+    pub fn get_i64(&self, key: &str) -> Result<Option<i64>, ConfigError> {
+        self.typed(key, "an integer", Value::as_i64)
+    }
+
+    pub fn get_f64(&self, key: &str) -> Result<Option<f64>, ConfigError> {
+        self.typed(key, "a number", Value::as_f64)
+    }
+
+    pub fn get_bool(&self, key: &str) -> Result<Option<bool>, ConfigError> {
+        self.typed(key, "a boolean", Value::as_bool)
+    }
+
+    /// A table such as `redis`.
+    pub fn section(&self, name: &str) -> Result<Option<&Map<String, Value>>, ConfigError> {
+        self.typed(name, "a table", Value::as_object)
+    }
+
+    /// Fail listing every key that is not set.
+    pub fn require(&self, keys: &[&str]) -> Result<(), ConfigError> {
+        let missing: Vec<String> = keys
+            .iter()
+            .filter(|key| self.get(key).is_none())
+            .map(|key| key.to_string())
+            .collect();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Missing(missing))
+        }
+    }
+}
+
+// ============================================================================
+// Python Bindings
+// ============================================================================
+
+/// Python handle to a merged configuration.
+#[pyclass(name = "AppConfig")]
+#[derive(Clone)]
+pub struct PyAppConfig {
+    inner: AppConfig,
+}
+
+impl PyAppConfig {
+    pub fn inner(&self) -> &AppConfig {
+        &self.inner
+    }
+
+    fn to_python(&self, py: Python<'_>, value: Option<&Value>) -> PyResult<Option<PyObject>> {
+        value
+            .map(|value| crate::json::json_to_python(py, value))
+            .transpose()
+    }
+}
+
+#[pymethods]
+impl PyAppConfig {
+    /// Load configuration from `path` (a path or list of paths), the
+    /// profile and environment variables prefixed with `env_prefix`.
+    #[staticmethod]
+    #[pyo3(signature = (path=None, profile=None, env_prefix="CELLO", defaults=None))]
+    fn load(
+        py: Python<'_>,
+        path: Option<&PyAny>,
+        profile: Option<&str>,
+        env_prefix: &str,
+        defaults: Option<&PyDict>,
+    ) -> PyResult<Self> {
+        let mut loader = ConfigLoader::new().env_prefix(env_prefix);
+        if let Some(defaults) = defaults {
+            loader = loader.defaults(
+                crate::json::python_to_json(py, defaults)
+                    .map_err(pyo3::exceptions::PyTypeError::new_err)?,
+            );
+        }
+        if let Some(path) = path {
+            let paths: Vec<String> = match path.downcast::<PyList>() {
+                Ok(list) => list.extract()?,
+                Err(_) => vec![path.str()?.to_string()],
+            };
+            for path in paths {
+                loader = loader.file(path);
+            }
+        }
+        if let Some(profile) = profile {
+            loader = loader.profile(profile);
+        }
+        Ok(Self {
+            inner: loader.load()?,
+        })
+    }
+
+    /// The active profile.
+    #[getter]
+    fn profile(&self) -> &str {
+        self.inner.profile()
+    }
+
+    /// The value at a dotted path, or `default`.
+    #[pyo3(signature = (key, default=None))]
+    fn get(&self, py: Python<'_>, key: &str, default: Option<PyObject>) -> PyResult<PyObject> {
+        Ok(self
+            .to_python(py, self.inner.get(key))?
+            .or(default)
+            .unwrap_or_else(|| py.None()))
+    }
+
+    #[pyo3(signature = (key, default=None))]
+    fn get_str(&self, key: &str, default: Option<String>) -> PyResult<Option<String>> {
+        Ok(self.inner.get_str(key)?.map(str::to_string).or(default))
+    }
+
+    #[pyo3(signature = (key, default=None))]
+    fn get_int(&self, key: &str, default: Option<i64>) -> PyResult<Option<i64>> {
+        Ok(self.inner.get_i64(key)?.or(default))
+    }
+
+    #[pyo3(signature = (key, default=None))]
+    fn get_float(&self, key: &str, default: Option<f64>) -> PyResult<Option<f64>> {
+        Ok(self.inner.get_f64(key)?.or(default))
+    }
+
+    #[pyo3(signature = (key, default=None))]
+    fn get_bool(&self, key: &str, default: Option<bool>) -> PyResult<Option<bool>> {
+        Ok(self.inner.get_bool(key)?.or(default))
+    }
+
+    /// Raise `ValueError` listing every key that is not set.
+    #[pyo3(signature = (*keys))]
+    fn require(&self, keys: &PyTuple) -> PyResult<()> {
+        let keys: Vec<String> = keys.extract()?;
+        let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+        Ok(self.inner.require(&keys)?)
+    }
+
+    /// A section as a dict, empty if it is not set.
+    fn section(&self, py: Python<'_>, name: &str) -> PyResult<PyObject> {
+        match self.inner.section(name)? {
+            Some(table) => crate::json::json_to_python(py, &Value::Object(table.clone())),
+            None => Ok(PyDict::new(py).into()),
+        }
+    }
+
+    /// Build `cls(**section)`, e.g. `config.build("redis", RedisConfig)`.
+    /// Returns `None` if the section is not set.
+    fn build(&self, py: Python<'_>, name: &str, cls: &PyType) -> PyResult<Option<PyObject>> {
+        if self.inner.section(name)?.is_none() {
+            return Ok(None);
+        }
+        let section = self.section(py, name)?;
+        let kwargs: &PyDict = section.downcast(py)?;
+        cls.call((), Some(kwargs))
+            .map(|obj| Some(obj.into()))
+            .map_err(|e| {
+                pyo3::exceptions::PyValueError::new_err(format!(
+                    "invalid [{name}] config: {}",
+                    e.value(py)
+                ))
+            })
+    }
+
+    /// The whole configuration as a dict.
+    fn to_dict(&self, py: Python<'_>) -> PyResult<PyObject> {
+        crate::json::json_to_python(py, self.inner.values())
+    }
+
+    fn __getitem__(&self, py: Python<'_>, key: &str) -> PyResult<PyObject> {
+        self.to_python(py, self.inner.get(key))?
+            .ok_or_else(|| pyo3::exceptions::PyKeyError::new_err(key.to_string()))
+    }
+
+    fn __contains__(&self, key: &str) -> bool {
+        self.inner.get(key).is_some()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("AppConfig(profile='{}')", self.inner.profile())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn write(dir: &Path, name: &str, contents: &str) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn env(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut base = json!({"server": {"host": "127.0.0.1", "port": 8000}, "debug": true});
+        merge(&mut base, json!({"server": {"port": 9000}, "debug": false}));
+        assert_eq!(
+            base,
+            json!({"server": {"host": "127.0.0.1", "port": 9000}, "debug": false})
+        );
+    }
+
+    #[test]
+    fn test_env_overrides() {
+        let vars = env(&[
+            ("CELLO_SERVER__PORT", "9000"),
+            ("CELLO_REDIS__URL", "redis://cache:6379"),
+            ("CELLO_KAFKA__BROKERS", r#"["a:9092","b:9092"]"#),
+            ("CELLO_PROFILE", "prod"),
+            ("CELLO_WORKER", "1"),
+            ("OTHER_PORT", "1"),
+        ]);
+        assert_eq!(
+            env_overrides("CELLO", vars),
+            json!({
+                "server": {"port": 9000},
+                "redis": {"url": "redis://cache:6379"},
+                "kafka": {"brokers": ["a:9092", "b:9092"]},
+            })
+        );
+    }
+
+    #[test]
+    fn test_layers_and_profiles() {
+        let dir = std::env::temp_dir().join(format!("cello-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = write(
+            &dir,
+            "cello.toml",
+            r#"
+            [server]
+            host = "127.0.0.1"
+            port = 8000
+
+            [profiles.prod.server]
+            host = "0.0.0.0"
+            "#,
+        );
+        write(&dir, "cello.prod.toml", "[server]\nworkers = 4\n");
+
+        let loader = ConfigLoader::new()
+            .defaults(json!({"server": {"workers": 1}, "debug": false}))
+            .file(&path);
+
+        let dev = loader.load_with_env(Vec::new()).unwrap();
+        assert_eq!(dev.profile(), "dev");
+        assert_eq!(dev.get_str("server.host").unwrap(), Some("127.0.0.1"));
+        assert_eq!(dev.get_i64("server.workers").unwrap(), Some(1));
+        assert!(dev.get("profiles").is_none());
+
+        let prod = loader
+            .load_with_env(env(&[
+                ("CELLO_PROFILE", "prod"),
+                ("CELLO_SERVER__PORT", "80"),
+            ]))
+            .unwrap();
+        assert_eq!(prod.profile(), "prod");
+        assert_eq!(prod.get_str("server.host").unwrap(), Some("0.0.0.0"));
+        assert_eq!(prod.get_i64("server.workers").unwrap(), Some(4));
+        assert_eq!(prod.get_i64("server.port").unwrap(), Some(80));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_yaml_and_errors() {
+        let dir = std::env::temp_dir().join(format!("cello-config-yaml-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = write(
+            &dir,
+            "app.yaml",
+            "redis:\n  url: redis://localhost\n  pool_size: 5\n",
+        );
+        let config = ConfigLoader::new()
+            .file(&path)
+            .load_with_env(Vec::new())
+            .unwrap();
+        assert_eq!(config.get_i64("redis.pool_size").unwrap(), Some(5));
+        assert!(matches!(
+            config.get_bool("redis.url"),
+            Err(ConfigError::Type { .. })
+        ));
+        assert_eq!(
+            config.require(&["redis.url", "database.url", "grpc"]),
+            Err(ConfigError::Missing(vec![
+                "database.url".to_string(),
+                "grpc".to_string()
+            ]))
+        );
+
+        let bad = write(&dir, "bad.toml", "[server\n");
+        assert!(matches!(
+            ConfigLoader::new().file(&bad).load_with_env(Vec::new()),
+            Err(ConfigError::Parse { .. })
+        ));
+        assert!(matches!(
+            ConfigLoader::new()
+                .file(dir.join("missing.toml"))
+                .load_with_env(Vec::new()),
+            Err(ConfigError::Io { .. })
+        ));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
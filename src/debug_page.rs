@@ -0,0 +1,282 @@
+//! Development error pages.
+//!
+//! With debug pages enabled, an exception that no exception handler claims
+//! is answered with a page showing its traceback, the request and the route
+//! handler that raised it: HTML for browsers, JSON for clients that accept
+//! JSON. The page exposes source paths and request headers, so it is meant
+//! for development only.
+
+use std::fmt::Write;
+
+use pyo3::prelude::*;
+use serde_json::json;
+
+use crate::error::PythonExceptionInfo;
+use crate::redaction::RedactionRules;
+use crate::request::Request;
+use crate::response::Response;
+
+/// An unhandled exception, with the request and handler it came from.
+pub struct DebugPage {
+    exception: PythonExceptionInfo,
+    method: String,
+    path: String,
+    query: Vec<(String, String)>,
+    params: Vec<(String, String)>,
+    headers: Vec<(String, String)>,
+    /// Qualified name of the route handler
+    handler: Option<String>,
+    /// `file:line` where the route handler is defined
+    source: Option<String>,
+}
+
+impl DebugPage {
+    /// Capture `err`, raised by `handler` while handling `request`, with
+    /// `redaction` applied to the request and exception.
+    pub fn capture(
+        py: Python<'_>,
+        request: &Request,
+        err: &PyErr,
+        handler: &PyAny,
+        redaction: Option<&RedactionRules>,
+    ) -> Self {
+        let sorted = |map: &std::collections::HashMap<String, String>| {
+            let mut pairs: Vec<_> = map.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+            pairs.sort();
+            pairs
+        };
+        let handler = unwrap(handler);
+
+        let mut page = Self {
+            exception: PythonExceptionInfo::from_pyerr(py, err),
+            method: request.method.clone(),
+            path: request.path.clone(),
+            query: sorted(&request.query_params),
+            params: sorted(&request.params),
+            headers: request
+                .headers
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            handler: handler_name(handler),
+            source: handler_source(handler),
+        };
+        if let Some(rules) = redaction {
+            page.redact(rules);
+        }
+        page
+    }
+
+    fn redact(&mut self, rules: &RedactionRules) {
+        for pairs in [&mut self.query, &mut self.params, &mut self.headers] {
+            for (name, value) in pairs.iter_mut() {
+                *value = rules.value(name, value).into_owned();
+            }
+        }
+        self.path = rules.text(&self.path).into_owned();
+        let exception = &mut self.exception;
+        exception.message = rules.text(&exception.message).into_owned();
+        if let Some(traceback) = &mut exception.traceback {
+            *traceback = rules.text(traceback).into_owned();
+        }
+    }
+
+    /// The 500 response: JSON when the request accepts JSON but not HTML,
+    /// otherwise an HTML page.
+    pub fn render(&self, request: &Request) -> Response {
+        let accept = request.headers.get("accept").unwrap_or("");
+        if accept.contains("json") && !accept.contains("text/html") {
+            Response::from_json_value(self.to_json(), 500)
+        } else {
+            Response::html(&self.to_html(), Some(500))
+        }
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        let object = |pairs: &[(String, String)]| {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.clone(), json!(v)))
+                .collect::<serde_json::Map<_, _>>()
+        };
+        json!({
+            "error": {
+                "type": self.exception.exception_type,
+                "message": self.exception.message,
+                "traceback": self.exception.traceback,
+            },
+            "request": {
+                "method": self.method,
+                "path": self.path,
+                "query": object(&self.query),
+                "params": object(&self.params),
+                "headers": object(&self.headers),
+            },
+            "route": {
+                "handler": self.handler,
+                "source": self.source,
+            },
+        })
+    }
+
+    fn to_html(&self) -> String {
+        let title = format!(
+            "{} at {} {}",
+            self.exception.exception_type, self.method, self.path
+        );
+        let mut html = String::with_capacity(4096);
+        let _ = write!(
+            html,
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+             <title>{title}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n\
+             <header><h1>{title}</h1><p>{message}</p></header>\n",
+            title = escape(&title),
+            message = escape(&self.exception.message),
+        );
+
+        html.push_str("<section><h2>Traceback</h2><pre>");
+        html.push_str(&escape(
+            self.exception
+                .traceback
+                .as_deref()
+                .unwrap_or("No traceback available"),
+        ));
+        html.push_str("</pre></section>\n");
+
+        let route = [
+            ("Handler", self.handler.clone().unwrap_or_default()),
+            ("Source", self.source.clone().unwrap_or_default()),
+        ]
+        .map(|(k, v)| (k.to_string(), v));
+        table(&mut html, "Route", &route);
+        table(
+            &mut html,
+            "Request",
+            &[
+                ("Method".to_string(), self.method.clone()),
+                ("Path".to_string(), self.path.clone()),
+            ],
+        );
+        table(&mut html, "Path Parameters", &self.params);
+        table(&mut html, "Query Parameters", &self.query);
+        table(&mut html, "Headers", &self.headers);
+
+        html.push_str(
+            "<footer>Debug pages are enabled. Disable them in production.</footer>\n\
+             </body>\n</html>\n",
+        );
+        html
+    }
+}
+
+const STYLE: &str = "body{margin:0;font-family:system-ui,sans-serif;color:#222}\
+header{background:#b3261e;color:#fff;padding:1.5em 2em}\
+h1{margin:0;font-size:1.4em}header p{margin:.5em 0 0;font-family:monospace}\
+section{padding:0 2em}h2{font-size:1.1em;border-bottom:1px solid #ddd;padding-bottom:.3em}\
+pre{background:#f6f6f6;padding:1em;overflow-x:auto}\
+table{border-collapse:collapse}td{padding:.2em 1em .2em 0;vertical-align:top;font-family:monospace}\
+td:first-child{color:#666}footer{padding:2em;color:#888;font-size:.9em}";
+
+/// Append a section with a two-column table, skipped when `rows` is empty.
+fn table(html: &mut String, heading: &str, rows: &[(String, String)]) {
+    if rows.is_empty() {
+        return;
+    }
+    let _ = write!(html, "<section><h2>{heading}</h2><table>");
+    for (name, value) in rows {
+        let _ = write!(
+            html,
+            "<tr><td>{}</td><td>{}</td></tr>",
+            escape(name),
+            escape(value)
+        );
+    }
+    html.push_str("</table></section>\n");
+}
+
+fn escape(text: &str) -> std::borrow::Cow<'_, str> {
+    quick_xml::escape::escape(text)
+}
+
+/// `module.qualname` of a route handler.
+pub(crate) fn handler_name(handler: &PyAny) -> Option<String> {
+    let handler = unwrap(handler);
+    let qualname: String = handler.getattr("__qualname__").ok()?.extract().ok()?;
+    match handler
+        .getattr("__module__")
+        .and_then(|m| m.extract::<String>())
+    {
+        Ok(module) => Some(format!("{module}.{qualname}")),
+        Err(_) => Some(qualname),
+    }
+}
+
+/// The function wrapped by route decorators, which use functools.wraps.
+fn unwrap(handler: &PyAny) -> &PyAny {
+    handler
+        .py()
+        .import("inspect")
+        .and_then(|inspect| inspect.getattr("unwrap")?.call1((handler,)))
+        .unwrap_or(handler)
+}
+
+/// `file:line` of a function's definition.
+fn handler_source(handler: &PyAny) -> Option<String> {
+    let code = handler.getattr("__code__").ok()?;
+    let file: String = code.getattr("co_filename").ok()?.extract().ok()?;
+    let line: u32 = code.getattr("co_firstlineno").ok()?.extract().ok()?;
+    Some(format!("{file}:{line}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(accept: &str) -> Response {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let globals = pyo3::types::PyDict::new(py);
+            py.run(
+                "def show_user(request):\n    raise KeyError('<id>')\n",
+                Some(globals),
+                None,
+            )
+            .unwrap();
+            let handler = globals.get_item("show_user").unwrap().unwrap();
+            let err = handler.call1((py.None(),)).unwrap_err();
+
+            let mut request = Request::new("GET", "/users/7");
+            request.params.insert("id".to_string(), "7".to_string());
+            request.headers.insert("accept", accept);
+            DebugPage::capture(py, &request, &err, handler, None).render(&request)
+        })
+    }
+
+    #[test]
+    fn test_html_page() {
+        let response = page("text/html,application/xhtml+xml");
+        assert_eq!(response.status, 500);
+        let body = String::from_utf8(response.body()).unwrap();
+        assert!(body.contains("<title>KeyError at GET /users/7</title>"));
+        assert!(body.contains("&lt;id&gt;"));
+        assert!(body.contains("<td>id</td><td>7</td>"));
+        assert!(body.contains("show_user"));
+    }
+
+    #[test]
+    fn test_json_page() {
+        let response = page("application/json");
+        assert_eq!(response.status, 500);
+        let body: serde_json::Value = serde_json::from_slice(&response.body()).unwrap();
+        assert_eq!(body["error"]["type"], "KeyError");
+        assert_eq!(body["request"]["params"]["id"], "7");
+        assert!(body["route"]["source"]
+            .as_str()
+            .unwrap()
+            .starts_with("<string>:"));
+        assert!(body["error"]["traceback"]
+            .as_str()
+            .unwrap()
+            .contains("in show_user"));
+    }
+}
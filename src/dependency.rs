@@ -9,7 +9,7 @@
 //! - Dependency overrides for testing
 
 use parking_lot::RwLock;
-use pyo3::PyObject;
+use pyo3::prelude::*;
 use std::any::{Any, TypeId};
 use std::collections::HashMap;
 use std::future::Future;
@@ -186,6 +186,79 @@ impl<T: Send + Sync + Clone + 'static> Provider for ValueProvider<T> {
     }
 }
 
+// ============================================================================
+// Python Dependencies
+// ============================================================================
+
+/// A Python callable registered as a named dependency.
+///
+/// Parameters whose default is `Depends(...)` are resolved first, and a
+/// parameter named `request` receives the current request. The callable may
+/// be async; its coroutine is awaited.
+#[derive(Debug)]
+pub struct PyFactory {
+    func: PyObject,
+    scope: DependencyScope,
+    params: Vec<(String, String)>,
+    wants_request: bool,
+}
+
+impl PyFactory {
+    /// Inspect `func`'s signature and build a factory for it.
+    pub fn new(py: Python<'_>, func: PyObject, scope: DependencyScope) -> PyResult<Self> {
+        let params = depends_params(py, func.as_ref(py))?;
+        let wants_request = py
+            .import("inspect")?
+            .call_method1("signature", (func.as_ref(py),))?
+            .getattr("parameters")?
+            .contains("request")?;
+        Ok(Self {
+            func,
+            scope,
+            params,
+            wants_request,
+        })
+    }
+
+    /// Names of the dependencies this factory needs.
+    pub fn dependencies(&self) -> impl Iterator<Item = &str> {
+        self.params.iter().map(|(_, dep)| dep.as_str())
+    }
+}
+
+/// Parameters of `func` declared with a `Depends(...)` default, as
+/// `(parameter name, dependency name)` pairs.
+pub fn depends_params(py: Python<'_>, func: &PyAny) -> PyResult<Vec<(String, String)>> {
+    let depends_type = py.import("cello")?.getattr("Depends")?;
+    let signature = py.import("inspect")?.call_method1("signature", (func,))?;
+    let mut params = Vec::new();
+    for item in signature
+        .getattr("parameters")?
+        .call_method0("items")?
+        .iter()?
+    {
+        let (name, param): (String, &PyAny) = item?.extract()?;
+        let default = param.getattr("default")?;
+        if default.is_instance(depends_type)? {
+            params.push((name, default.getattr("dependency")?.extract()?));
+        }
+    }
+    Ok(params)
+}
+
+/// Python dependencies for one call, keyed by dependency name.
+///
+/// Request-scoped factories run at most once per call, so every dependant
+/// in the graph shares the same instance.
+pub type PyDependencyCache = HashMap<String, PyObject>;
+
+/// Python dependencies that vary with the request, such as the resources of
+/// the request's tenant.
+pub trait RequestScope: Send + Sync {
+    /// Dependencies for `request`; they take precedence over registered ones.
+    fn dependencies(&self, py: Python<'_>, request: &Request) -> PyResult<PyDependencyCache>;
+}
+
 // ============================================================================
 // Dependency Container
 // ============================================================================
@@ -203,10 +276,33 @@ pub struct DependencyContainer {
     overrides: Arc<RwLock<HashMap<TypeId, Box<dyn Provider>>>>,
     /// Named Python singletons
     py_singletons: Arc<RwLock<HashMap<String, PyObject>>>,
+    /// Named Python factories
+    py_factories: Arc<RwLock<HashMap<String, Arc<PyFactory>>>>,
+    /// Values produced by singleton-scoped Python factories
+    py_factory_cache: Arc<RwLock<HashMap<String, PyObject>>>,
+    /// Python dependency overrides (for testing)
+    py_overrides: Arc<RwLock<HashMap<String, PyOverride>>>,
+    /// Request-dependent Python dependencies
+    request_scope: Arc<RwLock<Option<Arc<dyn RequestScope>>>>,
     /// PERF: Cached flag to avoid RwLock read on every request
     has_py_singletons_cached: Arc<AtomicBool>,
 }
 
+/// Where resolving a Python dependency starts.
+enum PyLookup {
+    /// A value cached, overridden or registered as a singleton
+    Value(PyObject),
+    /// A factory to call
+    Factory(Arc<PyFactory>),
+}
+
+/// Replacement for a named Python dependency.
+#[derive(Debug)]
+enum PyOverride {
+    Value(PyObject),
+    Factory(Arc<PyFactory>),
+}
+
 impl DependencyContainer {
     /// Create a new dependency container.
     pub fn new() -> Self {
@@ -216,6 +312,10 @@ impl DependencyContainer {
             singleton_cache: Arc::new(RwLock::new(HashMap::new())),
             overrides: Arc::new(RwLock::new(HashMap::new())),
             py_singletons: Arc::new(RwLock::new(HashMap::new())),
+            py_factories: Arc::new(RwLock::new(HashMap::new())),
+            py_factory_cache: Arc::new(RwLock::new(HashMap::new())),
+            py_overrides: Arc::new(RwLock::new(HashMap::new())),
+            request_scope: Arc::new(RwLock::new(None)),
             has_py_singletons_cached: Arc::new(AtomicBool::new(false)),
         }
     }
@@ -260,13 +360,203 @@ impl DependencyContainer {
         self.py_singletons.read().get(name).cloned()
     }
 
-    /// Check if any Python singletons are registered (for fast-path optimization).
+    /// Check if any Python singletons or factories are registered (for
+    /// fast-path optimization).
     /// PERF: Uses atomic flag instead of acquiring RwLock on every request.
     #[inline]
     pub fn has_py_singletons(&self) -> bool {
         self.has_py_singletons_cached.load(Ordering::Relaxed)
     }
 
+    /// Provide dependencies that vary with the request.
+    pub fn set_request_scope(&self, scope: Arc<dyn RequestScope>) {
+        *self.request_scope.write() = Some(scope);
+        self.has_py_singletons_cached.store(true, Ordering::Relaxed);
+    }
+
+    /// The request-dependent dependencies for `request`, if any are provided.
+    pub fn request_dependencies(
+        &self,
+        py: Python<'_>,
+        request: &Request,
+    ) -> PyResult<PyDependencyCache> {
+        let scope = self.request_scope.read().clone();
+        match scope {
+            Some(scope) => scope.dependencies(py, request),
+            None => Ok(PyDependencyCache::new()),
+        }
+    }
+
+    /// Register a Python factory under `name`.
+    pub fn register_py_factory(&self, name: &str, factory: PyFactory) {
+        self.py_factory_cache.write().remove(name);
+        self.py_factories
+            .write()
+            .insert(name.to_string(), Arc::new(factory));
+        self.has_py_singletons_cached.store(true, Ordering::Relaxed);
+    }
+
+    /// Replace the Python dependency `name` with `value`.
+    ///
+    /// When `name` is a factory, `value` must be callable and is used as the
+    /// replacement factory (keeping the original scope); otherwise `value`
+    /// is injected as is.
+    pub fn override_py(&self, py: Python<'_>, name: &str, value: PyObject) -> PyResult<()> {
+        let scope = self.py_factories.read().get(name).map(|f| f.scope);
+        let replacement = match scope {
+            Some(scope) => {
+                if !value.as_ref(py).is_callable() {
+                    return Err(pyo3::exceptions::PyTypeError::new_err(format!(
+                        "Override for factory dependency '{name}' must be callable"
+                    )));
+                }
+                PyOverride::Factory(Arc::new(PyFactory::new(py, value, scope)?))
+            }
+            None => PyOverride::Value(value),
+        };
+        self.py_factory_cache.write().remove(name);
+        self.py_overrides
+            .write()
+            .insert(name.to_string(), replacement);
+        self.has_py_singletons_cached.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Remove the override for the Python dependency `name`.
+    pub fn clear_py_override(&self, name: &str) {
+        if self.py_overrides.write().remove(name).is_some() {
+            self.py_factory_cache.write().remove(name);
+        }
+    }
+
+    /// Remove all Python dependency overrides.
+    pub fn clear_py_overrides(&self) {
+        let mut overrides = self.py_overrides.write();
+        let mut cache = self.py_factory_cache.write();
+        for name in overrides.keys() {
+            cache.remove(name);
+        }
+        overrides.clear();
+    }
+
+    /// Resolve the Python dependency `name` and everything it depends on.
+    ///
+    /// Overrides take precedence over registered singletons and factories.
+    /// Request-scoped values are memoised in `cache`, singleton-scoped ones
+    /// in the container. A coroutine returned by an async factory is awaited,
+    /// with the GIL released, before its value is cached or passed on. A
+    /// dependency that (indirectly) depends on itself fails with
+    /// `CircularDependency` naming the cycle.
+    pub async fn resolve_py(
+        &self,
+        name: &str,
+        request: &PyObject,
+        cache: &mut PyDependencyCache,
+    ) -> DependencyResult<PyObject> {
+        self.resolve_py_in(name, request, cache, &mut Vec::new())
+            .await
+    }
+
+    fn resolve_py_in<'a>(
+        &'a self,
+        name: &'a str,
+        request: &'a PyObject,
+        cache: &'a mut PyDependencyCache,
+        path: &'a mut Vec<String>,
+    ) -> Pin<Box<dyn Future<Output = DependencyResult<PyObject>> + Send + 'a>> {
+        Box::pin(async move {
+            if let Some(start) = path.iter().position(|n| n == name) {
+                let mut cycle = path[start..].to_vec();
+                cycle.push(name.to_string());
+                return Err(DependencyError::CircularDependency(cycle.join(" -> ")));
+            }
+            let factory = match Python::with_gil(|py| self.lookup_py(py, name, cache))? {
+                PyLookup::Value(value) => return Ok(value),
+                PyLookup::Factory(factory) => factory,
+            };
+
+            path.push(name.to_string());
+            let mut values = Vec::with_capacity(factory.params.len());
+            for (_, dep) in &factory.params {
+                values.push(self.resolve_py_in(dep, request, cache, path).await?);
+            }
+            path.pop();
+
+            let failed = |e: PyErr| DependencyError::ProviderFailed(format!("{name}: {e}"));
+            let pending = Python::with_gil(|py| -> PyResult<_> {
+                let kwargs = pyo3::types::PyDict::new(py);
+                for ((param, _), value) in factory.params.iter().zip(values) {
+                    kwargs.set_item(param, value)?;
+                }
+                if factory.wants_request {
+                    kwargs.set_item("request", request.clone_ref(py))?;
+                }
+                let value = factory.func.call(py, (), Some(kwargs))?;
+                if !crate::event_loop::is_coroutine(py, &value)? {
+                    return Ok(Err(value));
+                }
+                crate::event_loop::into_future(value.as_ref(py)).map(Ok)
+            })
+            .map_err(failed)?;
+            let value = match pending {
+                Ok(future) => future.await.map_err(failed)?,
+                Err(value) => value,
+            };
+
+            // Requests racing to create a singleton all get the first value
+            Ok(Python::with_gil(|py| match factory.scope {
+                DependencyScope::Singleton => self
+                    .py_factory_cache
+                    .write()
+                    .entry(name.to_string())
+                    .or_insert(value)
+                    .clone_ref(py),
+                DependencyScope::Request => {
+                    cache.insert(name.to_string(), value.clone_ref(py));
+                    value
+                }
+                DependencyScope::Transient => value,
+            }))
+        })
+    }
+
+    /// The value `name` already has for this request, or the factory to call.
+    fn lookup_py(
+        &self,
+        py: Python<'_>,
+        name: &str,
+        cache: &PyDependencyCache,
+    ) -> DependencyResult<PyLookup> {
+        if let Some(value) = cache.get(name) {
+            return Ok(PyLookup::Value(value.clone_ref(py)));
+        }
+
+        let factory = match self.py_overrides.read().get(name) {
+            Some(PyOverride::Value(value)) => return Ok(PyLookup::Value(value.clone_ref(py))),
+            Some(PyOverride::Factory(factory)) => Some(factory.clone()),
+            None => None,
+        };
+        let factory = match factory {
+            Some(factory) => factory,
+            None => match self.py_factories.read().get(name) {
+                Some(factory) => factory.clone(),
+                None => {
+                    return self
+                        .get_py_singleton(name)
+                        .map(PyLookup::Value)
+                        .ok_or_else(|| DependencyError::NotFound(name.to_string()));
+                }
+            },
+        };
+
+        if factory.scope == DependencyScope::Singleton {
+            if let Some(value) = self.py_factory_cache.read().get(name) {
+                return Ok(PyLookup::Value(value.clone_ref(py)));
+            }
+        }
+        Ok(PyLookup::Factory(factory))
+    }
+
     /// Override a provider (useful for testing).
     pub fn override_provider<T: 'static>(&self, provider: Box<dyn Provider>) {
         let type_id = TypeId::of::<T>();
@@ -381,6 +671,7 @@ impl DependencyContainer {
     /// Clear singleton cache.
     pub fn clear_singleton_cache(&self) {
         self.singleton_cache.write().clear();
+        self.py_factory_cache.write().clear();
     }
 }
 
@@ -511,4 +802,126 @@ mod tests {
         assert!(result.is_err());
         matches!(result.unwrap_err(), DependencyError::NotFound(_));
     }
+
+    // ---------- Python Dependency Tests ----------
+
+    fn py_factory(py: Python<'_>, code: &str, deps: &[(&str, &str)]) -> PyFactory {
+        PyFactory {
+            func: py.eval(code, None, None).unwrap().into(),
+            scope: DependencyScope::Request,
+            params: deps
+                .iter()
+                .map(|(p, d)| (p.to_string(), d.to_string()))
+                .collect(),
+            wants_request: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_py_nested_dependencies_share_request_scope() {
+        pyo3::prepare_freethreaded_python();
+        let (container, request) = Python::with_gil(|py| {
+            let container = DependencyContainer::new();
+            container.register_py_singleton("config", "prod".into_py(py));
+            container.register_py_factory(
+                "db",
+                py_factory(
+                    py,
+                    "lambda config: {'dsn': config}",
+                    &[("config", "config")],
+                ),
+            );
+            container.register_py_factory(
+                "service",
+                py_factory(py, "lambda a, b: a is b", &[("a", "db"), ("b", "db")]),
+            );
+            (container, py.None())
+        });
+
+        let mut cache = PyDependencyCache::new();
+        let shared = container
+            .resolve_py("service", &request, &mut cache)
+            .await
+            .unwrap();
+        Python::with_gil(|py| assert!(shared.extract::<bool>(py).unwrap()));
+        assert!(cache.contains_key("db"));
+
+        Python::with_gil(|py| {
+            container
+                .override_py(py, "config", "test".into_py(py))
+                .unwrap()
+        });
+        let db = container
+            .resolve_py("db", &request, &mut PyDependencyCache::new())
+            .await
+            .unwrap();
+        Python::with_gil(|py| {
+            let dsn: String = db.as_ref(py).get_item("dsn").unwrap().extract().unwrap();
+            assert_eq!(dsn, "test");
+        });
+
+        container.clear_py_overrides();
+        assert!(matches!(
+            container
+                .resolve_py("missing", &request, &mut PyDependencyCache::new())
+                .await,
+            Err(DependencyError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_py_async_dependencies_are_awaited() {
+        pyo3::prepare_freethreaded_python();
+        let (container, request) = Python::with_gil(|py| {
+            crate::event_loop::start(py).unwrap();
+            let locals = pyo3::types::PyDict::new(py);
+            py.run(
+                "async def user(token):\n    return {'token': token}\n",
+                None,
+                Some(locals),
+            )
+            .unwrap();
+            let container = DependencyContainer::new();
+            container.register_py_singleton("token", "t-1".into_py(py));
+            container.register_py_factory(
+                "user",
+                PyFactory {
+                    func: locals.get_item("user").unwrap().unwrap().into(),
+                    ..py_factory(py, "None", &[("token", "token")])
+                },
+            );
+            container.register_py_factory(
+                "name",
+                py_factory(py, "lambda user: user['token']", &[("user", "user")]),
+            );
+            (container, py.None())
+        });
+
+        let name = container
+            .resolve_py("name", &request, &mut PyDependencyCache::new())
+            .await
+            .unwrap();
+        Python::with_gil(|py| assert_eq!(name.extract::<String>(py).unwrap(), "t-1"));
+    }
+
+    #[tokio::test]
+    async fn test_py_circular_dependency() {
+        pyo3::prepare_freethreaded_python();
+        let (container, request) = Python::with_gil(|py| {
+            let container = DependencyContainer::new();
+            container.register_py_factory("a", py_factory(py, "lambda b: b", &[("b", "b")]));
+            container.register_py_factory("b", py_factory(py, "lambda c: c", &[("c", "c")]));
+            container.register_py_factory("c", py_factory(py, "lambda a: a", &[("a", "a")]));
+            (container, py.None())
+        });
+
+        let err = container
+            .resolve_py("b", &request, &mut PyDependencyCache::new())
+            .await
+            .unwrap_err();
+        match err {
+            DependencyError::CircularDependency(chain) => assert_eq!(chain, "b -> c -> a -> b"),
+            other => panic!("unexpected error: {other}"),
+        }
+    }
 }
@@ -3,17 +3,21 @@
 //! This module provides:
 //! - RFC 7807 Problem Details for structured errors
 //! - Error handler registry (global, status-based, exception-based)
-//! - Blueprint-scoped error handlers
 //! - Python exception capture with traceback
 
 use parking_lot::RwLock;
 use pyo3::prelude::*;
+use pyo3::types::{PyTuple, PyType};
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use thiserror::Error;
 
+use crate::debug_page::DebugPage;
+use crate::error_reporting::ErrorReporter;
+use crate::redaction::Redaction;
 use crate::request::Request;
 use crate::response::Response;
 
@@ -78,6 +82,13 @@ impl ProblemDetails {
         self.extensions.insert(key, JsonValue::String(value));
     }
 
+    /// Additional members, such as `correlation_id`, `errors` or `traceback`.
+    #[getter]
+    pub fn extensions(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let extensions = serde_json::to_value(&self.extensions).unwrap_or_default();
+        crate::json::json_to_python(py, &extensions)
+    }
+
     /// Convert to JSON string.
     pub fn to_json(&self) -> PyResult<String> {
         serde_json::to_string(self)
@@ -158,6 +169,33 @@ pub enum AppError {
 }
 
 impl AppError {
+    /// The error behind a framework error response with `status`.
+    pub fn from_status(status: u16, message: &str) -> Self {
+        let message = message.to_string();
+        match status {
+            400 => AppError::BadRequest(message),
+            401 => AppError::Unauthorized(message),
+            403 => AppError::Forbidden(message),
+            404 => AppError::NotFound(message),
+            409 => AppError::Conflict(message),
+            422 => AppError::Validation {
+                message,
+                errors: Vec::new(),
+            },
+            429 => AppError::RateLimited {
+                retry_after: None,
+                message,
+            },
+            500 => AppError::Internal(message),
+            504 => AppError::Timeout(message),
+            _ => AppError::Custom {
+                status,
+                message,
+                type_uri: status_type_uri(status).map(str::to_string),
+            },
+        }
+    }
+
     /// Get the HTTP status code for this error.
     pub fn status_code(&self) -> u16 {
         match self {
@@ -190,9 +228,15 @@ impl AppError {
             AppError::Handler(_) => ("/errors/handler-error", "Handler Error"),
             AppError::Internal(_) => ("/errors/internal", "Internal Server Error"),
             AppError::PythonException(_) => ("/errors/python-exception", "Internal Server Error"),
-            AppError::Custom { type_uri, .. } => {
-                (type_uri.as_deref().unwrap_or("/errors/custom"), "Error")
-            }
+            AppError::Custom {
+                status, type_uri, ..
+            } => (
+                type_uri.as_deref().unwrap_or("about:blank"),
+                hyper::StatusCode::from_u16(*status)
+                    .ok()
+                    .and_then(|code| code.canonical_reason())
+                    .unwrap_or("Error"),
+            ),
         };
 
         let mut details = ProblemDetails {
@@ -237,6 +281,29 @@ impl AppError {
     }
 }
 
+/// The problem type of the other statuses the framework answers with.
+///
+/// Statuses without one are sent as `about:blank`, whose title is the
+/// status's reason phrase (RFC 9457, section 4.2.1).
+fn status_type_uri(status: u16) -> Option<&'static str> {
+    Some(match status {
+        405 => "/errors/method-not-allowed",
+        406 => "/errors/not-acceptable",
+        408 => "/errors/request-timeout",
+        411 => "/errors/length-required",
+        412 => "/errors/precondition-failed",
+        413 => "/errors/payload-too-large",
+        415 => "/errors/unsupported-media-type",
+        416 => "/errors/range-not-satisfiable",
+        426 => "/errors/upgrade-required",
+        428 => "/errors/precondition-required",
+        431 => "/errors/request-header-fields-too-large",
+        502 => "/errors/bad-gateway",
+        503 => "/errors/service-unavailable",
+        _ => return None,
+    })
+}
+
 /// Field-level validation error.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FieldError {
@@ -286,9 +353,16 @@ impl PythonExceptionInfo {
             .map(|n| n.to_string())
             .unwrap_or_else(|_| "Unknown".to_string());
 
-        let message = err.to_string();
+        let message = err
+            .value(py)
+            .str()
+            .map(|s| s.to_string())
+            .unwrap_or_default();
 
-        let traceback = err.traceback(py).and_then(|tb| tb.format().ok());
+        let traceback = err
+            .traceback(py)
+            .and_then(|tb| tb.format().ok())
+            .map(|tb| format!("{tb}{exception_type}: {message}\n"));
 
         Self {
             exception_type,
@@ -310,6 +384,10 @@ impl std::fmt::Display for PythonExceptionInfo {
 pub type ErrorHandlerFn = Arc<dyn Fn(&AppError, &Request) -> Response + Send + Sync>;
 
 /// Python error handler wrapper.
+///
+/// The handler is called as `handler(request, exc)` and may be sync or
+/// async. It returns a `Response`, a `ProblemDetails`, or a value sent as the
+/// JSON body of the error's status.
 pub struct PyErrorHandler {
     handler: PyObject,
 }
@@ -319,65 +397,77 @@ impl PyErrorHandler {
         Self { handler }
     }
 
-    pub fn handle(&self, error: &AppError, request: &Request) -> Response {
-        Python::with_gil(|py| {
-            // Create error info dict for Python
-            let error_dict = pyo3::types::PyDict::new(py);
-            let _ = error_dict.set_item("message", error.to_string());
-            let _ = error_dict.set_item("status", error.status_code());
-            let _ = error_dict.set_item("type", format!("{:?}", std::mem::discriminant(error)));
-
-            if let AppError::PythonException(info) = error {
-                let _ = error_dict.set_item("exception_type", &info.exception_type);
-                if let Some(tb) = &info.traceback {
-                    let _ = error_dict.set_item("traceback", tb);
-                }
+    /// Call the handler and build its response; `None` if the handler raised.
+    pub async fn handle(&self, request: PyObject, exc: PyObject, status: u16) -> Option<Response> {
+        let called = Python::with_gil(|py| -> PyResult<_> {
+            let result = self.handler.call1(py, (request, exc))?;
+            let is_coro = py
+                .import("inspect")?
+                .call_method1("iscoroutine", (result.as_ref(py),))?
+                .is_true()?;
+            if is_coro {
+                crate::event_loop::into_future(result.as_ref(py)).map(Err)
+            } else {
+                Ok(Ok(result))
             }
+        });
+        let result = match called {
+            Ok(Ok(result)) => Ok(result),
+            Ok(Err(future)) => future.await,
+            Err(e) => Err(e),
+        };
 
-            match self.handler.call1(py, (error_dict, request.clone())) {
-                Ok(result) => {
-                    // Check if result is a Response
-                    if let Ok(response) = result.extract::<Response>(py) {
-                        return response;
-                    }
-                    // Check if result is a ProblemDetails
-                    if let Ok(problem) = result.extract::<ProblemDetails>(py) {
-                        return problem.to_response();
-                    }
-                    // Fallback: try to convert result to string
-                    if let Ok(s) = result.extract::<String>(py) {
-                        let mut resp = Response::new(error.status_code());
-                        resp.set_body(s.into_bytes());
-                        resp.set_header("Content-Type", "application/json");
-                        return resp;
-                    }
-                    // Fallback to default error response
-                    error.to_response()
+        Python::with_gil(|py| {
+            match result.and_then(|value| handler_response(py, value.as_ref(py), status)) {
+                Ok(response) => Some(response),
+                Err(e) => {
+                    eprintln!("Error handler failed: {e}");
+                    None
                 }
-                Err(_) => error.to_response(),
             }
         })
     }
 }
 
+/// Response for the value returned by an error handler.
+fn handler_response(py: Python<'_>, value: &PyAny, status: u16) -> PyResult<Response> {
+    if let Ok(response) = value.extract::<Response>() {
+        return Ok(response);
+    }
+    if let Ok(problem) = value.extract::<ProblemDetails>() {
+        return Ok(problem.to_response());
+    }
+    let body =
+        crate::json::python_to_json(py, value).map_err(pyo3::exceptions::PyValueError::new_err)?;
+    Ok(Response::from_json_value(body, status))
+}
+
 /// Error handler registry.
 ///
 /// Manages error handlers at different levels:
-/// - Global handlers
-/// - Status-code specific handlers
-/// - Exception-type specific handlers
-/// - Blueprint-scoped handlers
+/// - Exception-type specific handlers, for exceptions raised by route handlers
+/// - Status-code specific handlers, for error responses built by the framework
+/// - A global handler, used when neither matches
 pub struct ErrorHandlerRegistry {
     /// Global error handler.
     global: RwLock<Option<Arc<PyErrorHandler>>>,
     /// Per-status code handlers.
     status_handlers: RwLock<HashMap<u16, Arc<PyErrorHandler>>>,
-    /// Per-exception type handlers (by type name string).
-    exception_handlers: RwLock<HashMap<String, Arc<PyErrorHandler>>>,
-    /// Blueprint-scoped handler registries.
-    blueprint_handlers: RwLock<HashMap<String, Arc<ErrorHandlerRegistry>>>,
+    /// Per-exception type handlers, matched along the exception's MRO.
+    exception_handlers: RwLock<Vec<(Py<PyType>, Arc<PyErrorHandler>)>>,
     /// Debug mode flag (shows tracebacks).
     debug_mode: RwLock<bool>,
+    /// Send framework errors as `application/problem+json`.
+    problem_details: AtomicBool,
+    /// Answer unhandled exceptions with a development error page.
+    debug_pages: AtomicBool,
+    /// Reports unhandled exceptions and server errors to Sentry.
+    reporter: RwLock<Option<Arc<ErrorReporter>>>,
+    /// Redaction applied to debug pages and error reports.
+    redaction: Redaction,
+    /// Set once a handler is registered or problem details are enabled, so
+    /// error paths skip the locks otherwise.
+    enabled: AtomicBool,
 }
 
 impl ErrorHandlerRegistry {
@@ -386,9 +476,13 @@ impl ErrorHandlerRegistry {
         Self {
             global: RwLock::new(None),
             status_handlers: RwLock::new(HashMap::new()),
-            exception_handlers: RwLock::new(HashMap::new()),
-            blueprint_handlers: RwLock::new(HashMap::new()),
+            exception_handlers: RwLock::new(Vec::new()),
             debug_mode: RwLock::new(false),
+            problem_details: AtomicBool::new(false),
+            debug_pages: AtomicBool::new(false),
+            reporter: RwLock::new(None),
+            redaction: Redaction::new(),
+            enabled: AtomicBool::new(false),
         }
     }
 
@@ -402,9 +496,53 @@ impl ErrorHandlerRegistry {
         *self.debug_mode.read()
     }
 
+    /// Send framework error responses as RFC 7807 `application/problem+json`
+    /// instead of `{"error": ..., "status": ...}`.
+    pub fn set_problem_details(&self, enabled: bool) {
+        self.problem_details.store(enabled, Ordering::Release);
+        if enabled {
+            self.enabled.store(true, Ordering::Release);
+        }
+    }
+
+    /// Check if framework errors are sent as problem details.
+    pub fn problem_details(&self) -> bool {
+        self.problem_details.load(Ordering::Acquire)
+    }
+
+    /// Answer exceptions no exception handler claims with a development
+    /// error page (see [`crate::debug_page`]) instead of a 500 response.
+    pub fn set_debug_pages(&self, enabled: bool) {
+        self.debug_pages.store(enabled, Ordering::Release);
+        if enabled {
+            self.enabled.store(true, Ordering::Release);
+        }
+    }
+
+    /// Report exceptions no exception handler claims and server errors
+    /// with `reporter`.
+    pub fn set_reporter(&self, reporter: Arc<ErrorReporter>) {
+        *self.reporter.write() = Some(reporter);
+        self.enabled.store(true, Ordering::Release);
+    }
+
+    /// The error reporter, if enabled.
+    pub fn reporter(&self) -> Option<Arc<ErrorReporter>> {
+        if !self.is_active() {
+            return None;
+        }
+        self.reporter.read().clone()
+    }
+
+    /// The app's redaction rules.
+    pub fn redaction(&self) -> &Redaction {
+        &self.redaction
+    }
+
     /// Register a global error handler.
     pub fn set_global_handler(&self, handler: PyObject) {
         *self.global.write() = Some(Arc::new(PyErrorHandler::new(handler)));
+        self.enabled.store(true, Ordering::Release);
     }
 
     /// Register a handler for a specific status code.
@@ -412,83 +550,205 @@ impl ErrorHandlerRegistry {
         self.status_handlers
             .write()
             .insert(status, Arc::new(PyErrorHandler::new(handler)));
+        self.enabled.store(true, Ordering::Release);
     }
 
-    /// Register a handler for a specific exception type.
-    pub fn set_exception_handler(&self, exception_type: impl Into<String>, handler: PyObject) {
-        self.exception_handlers.write().insert(
-            exception_type.into(),
-            Arc::new(PyErrorHandler::new(handler)),
-        );
-    }
-
-    /// Register a blueprint-scoped handler registry.
-    pub fn set_blueprint_handlers(
+    /// Register a handler for an exception type and its subclasses.
+    pub fn set_exception_handler(
         &self,
-        blueprint: impl Into<String>,
-        registry: Arc<ErrorHandlerRegistry>,
+        py: Python<'_>,
+        exception_type: &PyType,
+        handler: PyObject,
     ) {
-        self.blueprint_handlers
-            .write()
-            .insert(blueprint.into(), registry);
-    }
-
-    /// Handle an error, finding the most specific handler.
-    pub fn handle(&self, error: &AppError, request: &Request, blueprint: Option<&str>) -> Response {
-        // Try blueprint-scoped handlers first
-        if let Some(bp_name) = blueprint {
-            if let Some(bp_registry) = self.blueprint_handlers.read().get(bp_name) {
-                let response = bp_registry.handle(error, request, None);
-                // Only use blueprint handler if it produced a non-default response
-                if response.status != 500 || bp_registry.has_handlers() {
-                    return response;
-                }
-            }
+        let handler = Arc::new(PyErrorHandler::new(handler));
+        let mut handlers = self.exception_handlers.write();
+        match handlers
+            .iter_mut()
+            .find(|(registered, _)| registered.as_ref(py).is(exception_type))
+        {
+            Some(entry) => entry.1 = handler,
+            None => handlers.push((exception_type.into(), handler)),
         }
+        self.enabled.store(true, Ordering::Release);
+    }
 
-        // Try exception-type handler for Python exceptions
-        if let AppError::PythonException(info) = error {
-            if let Some(handler) = self.exception_handlers.read().get(&info.exception_type) {
-                return handler.handle(error, request);
-            }
+    /// Check if any handlers are registered or problem details are enabled.
+    #[inline]
+    pub fn is_active(&self) -> bool {
+        self.enabled.load(Ordering::Acquire)
+    }
+
+    /// Check if the framework's error response for `status` is replaced, by
+    /// a status handler or by problem details.
+    pub fn customizes(&self, status: u16) -> bool {
+        self.is_active() && (self.problem_details() || self.status_handler(status).is_some())
+    }
+
+    fn status_handler(&self, status: u16) -> Option<Arc<PyErrorHandler>> {
+        self.status_handlers
+            .read()
+            .get(&status)
+            .cloned()
+            .or_else(|| self.global.read().clone())
+    }
+
+    /// The handler registered for the closest class in the exception's MRO.
+    fn exception_handler(&self, exc: &PyAny) -> Option<Arc<PyErrorHandler>> {
+        let handlers = self.exception_handlers.read();
+        if handlers.is_empty() {
+            return None;
         }
+        let mro = exc.get_type().getattr("__mro__").ok()?;
+        let mro = mro.downcast::<PyTuple>().ok()?;
+        mro.iter().find_map(|class| {
+            handlers
+                .iter()
+                .find(|(registered, _)| registered.as_ref(exc.py()).is(class))
+                .map(|(_, handler)| handler.clone())
+        })
+    }
 
-        // Try status code handler
-        let status = error.status_code();
-        if let Some(handler) = self.status_handlers.read().get(&status) {
-            return handler.handle(error, request);
+    /// Handle an exception raised by a route handler.
+    ///
+    /// An exception handler gets the exception; otherwise a guard's
+    /// rejection is sent as the framework's error response for its status.
+    /// Other exceptions, `route_handler` having raised them, are reported
+    /// when error reporting is enabled, then get the debug page if enabled,
+    /// or their `ProblemDetails` goes to the 500 handler or
+    /// is sent as problem+json. Returns `None` when none applies or an
+    /// exception handler failed.
+    pub async fn handle_exception(
+        &self,
+        request: PyObject,
+        err: &PyErr,
+        route_handler: &PyObject,
+    ) -> Option<Response> {
+        let (handler, exc) = Python::with_gil(|py| {
+            let exc = err.value(py);
+            (self.exception_handler(exc), exc.into_py(py))
+        });
+        if let Some(handler) = handler {
+            return handler.handle(request, exc, 500).await;
+        }
+        let rejected = Python::with_gil(|py| {
+            let (status, message) = client_error(py, err)?;
+            Some((request.extract::<Request>(py).ok()?, status, message))
+        });
+        if let Some((request, status, message)) = rejected {
+            return Some(self.error_response(&request, status, &message).await);
+        }
+        if let Some(reporter) = self.reporter() {
+            Python::with_gil(|py| {
+                let request = request.extract::<Request>(py).ok();
+                let route = crate::debug_page::handler_name(route_handler.as_ref(py));
+                reporter.capture_exception(py, err, request.as_ref(), route.as_deref());
+            });
+        }
+        if self.debug_pages.load(Ordering::Acquire) {
+            return Python::with_gil(|py| {
+                let request = request.extract::<Request>(py).ok()?;
+                let redaction = self.redaction.rules();
+                let page = DebugPage::capture(
+                    py,
+                    &request,
+                    err,
+                    route_handler.as_ref(py),
+                    redaction.as_deref(),
+                );
+                Some(page.render(&request))
+            });
+        }
+        if !self.customizes(500) {
+            return None;
         }
 
-        // Try global handler
-        if let Some(handler) = self.global.read().as_ref() {
-            return handler.handle(error, request);
+        let (request, problem) = Python::with_gil(|py| {
+            let request = request.extract::<Request>(py).ok()?;
+            let info = PythonExceptionInfo::from_pyerr(py, err);
+            let traceback = info.traceback.clone();
+            let mut problem = AppError::PythonException(info).to_problem_details();
+            self.describe(&mut problem, &request);
+            if let Some(traceback) = traceback.filter(|_| self.is_debug()) {
+                problem
+                    .extensions
+                    .insert("traceback".to_string(), JsonValue::String(traceback));
+            }
+            Some((request, problem))
+        })?;
+        Some(self.respond(&request, problem).await)
+    }
+
+    /// The framework's error response with `status`: customized by a status
+    /// handler or problem details, or the plain `{"error", "status"}` body.
+    pub async fn error_response(&self, request: &Request, status: u16, message: &str) -> Response {
+        if self.customizes(status) {
+            let problem = self.problem(request, status, message);
+            return self.respond(request, problem).await;
         }
+        Response::error(status, message)
+    }
 
-        // Default: convert error to response
-        let mut response = error.to_response();
+    /// The problem describing a framework error response with `status`.
+    pub fn problem(&self, request: &Request, status: u16, message: &str) -> ProblemDetails {
+        let mut problem = AppError::from_status(status, message).to_problem_details();
+        problem.detail = Some(message.to_string());
+        self.describe(&mut problem, request);
+        problem
+    }
 
-        // In debug mode, include traceback for Python exceptions
-        if self.is_debug() {
-            if let AppError::PythonException(info) = error {
-                if let Some(tb) = &info.traceback {
-                    let mut problem = error.to_problem_details();
-                    problem
-                        .extensions
-                        .insert("traceback".to_string(), serde_json::json!(tb));
-                    response = problem.to_response();
-                }
-            }
+    /// Add the request's path and correlation ID to a problem.
+    fn describe(&self, problem: &mut ProblemDetails, request: &Request) {
+        problem.instance = Some(request.path.clone());
+        if let Some(id) = correlation_id(request) {
+            problem
+                .extensions
+                .insert("correlation_id".to_string(), JsonValue::String(id));
         }
+    }
 
-        response
+    /// The response for a framework error: built by the status handler,
+    /// sent as problem+json, or the plain `{"error", "status"}` body.
+    ///
+    /// The status handler gets the `ProblemDetails`; if it fails, the default
+    /// response is sent.
+    pub async fn respond(&self, request: &Request, problem: ProblemDetails) -> Response {
+        if let Some(handler) = self.status_handler(problem.status) {
+            let (py_request, py_problem) =
+                Python::with_gil(|py| (request.clone().into_py(py), problem.clone().into_py(py)));
+            if let Some(response) = handler.handle(py_request, py_problem, problem.status).await {
+                return response;
+            }
+        }
+        if self.problem_details() {
+            problem.to_response()
+        } else {
+            Response::error(
+                problem.status,
+                problem.detail.as_deref().unwrap_or(&problem.title),
+            )
+        }
     }
+}
 
-    /// Check if any handlers are registered.
-    pub fn has_handlers(&self) -> bool {
-        self.global.read().is_some()
-            || !self.status_handlers.read().is_empty()
-            || !self.exception_handlers.read().is_empty()
+/// The request's correlation ID: the ID assigned by the request ID
+/// middleware, or the one sent by the client.
+pub(crate) fn correlation_id(request: &Request) -> Option<String> {
+    crate::middleware::request_id::get_request_id(request).or_else(|| {
+        ["x-request-id", "x-correlation-id"]
+            .iter()
+            .find_map(|name| request.headers.get(name))
+            .map(str::to_string)
+    })
+}
+
+/// Status and message for an exception the client caused: a guard rejection
+/// or a request body that does not decode.
+pub fn client_error(py: Python<'_>, err: &PyErr) -> Option<(u16, String)> {
+    if err.is_instance_of::<crate::request::BodyDecodeError>(py) {
+        return Some((400, err.value(py).to_string()));
     }
+    let rejection = crate::middleware::guards::rejection(py, err)?;
+    Some((rejection.status(), rejection.message().to_string()))
 }
 
 impl Default for ErrorHandlerRegistry {
@@ -497,18 +757,6 @@ impl Default for ErrorHandlerRegistry {
     }
 }
 
-impl Clone for ErrorHandlerRegistry {
-    fn clone(&self) -> Self {
-        Self {
-            global: RwLock::new(self.global.read().clone()),
-            status_handlers: RwLock::new(self.status_handlers.read().clone()),
-            exception_handlers: RwLock::new(self.exception_handlers.read().clone()),
-            blueprint_handlers: RwLock::new(self.blueprint_handlers.read().clone()),
-            debug_mode: RwLock::new(*self.debug_mode.read()),
-        }
-    }
-}
-
 /// Python-exposed error handler registry.
 #[pyclass]
 pub struct PyErrorHandlerRegistry {
@@ -540,8 +788,9 @@ impl PyErrorHandlerRegistry {
     }
 
     /// Register an exception type handler.
-    pub fn exception_handler(&self, exception_type: String, handler: PyObject) {
-        self.inner.set_exception_handler(exception_type, handler);
+    pub fn exception_handler(&self, py: Python<'_>, exception_type: &PyType, handler: PyObject) {
+        self.inner
+            .set_exception_handler(py, exception_type, handler);
     }
 }
 
@@ -571,6 +820,24 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_app_error_from_status() {
+        assert!(matches!(
+            AppError::from_status(404, "gone"),
+            AppError::NotFound(_)
+        ));
+        assert_eq!(AppError::from_status(429, "slow down").status_code(), 429);
+
+        let problem = AppError::from_status(405, "Method Not Allowed").to_problem_details();
+        assert_eq!(problem.status, 405);
+        assert_eq!(problem.title, "Method Not Allowed");
+        assert_eq!(problem.type_uri, "/errors/method-not-allowed");
+
+        let problem = AppError::from_status(418, "Teapot").to_problem_details();
+        assert_eq!(problem.type_uri, "about:blank");
+        assert_eq!(problem.title, "I'm a teapot");
+    }
+
     #[test]
     fn test_problem_details_serialization() {
         let problem = ProblemDetails {
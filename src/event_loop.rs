@@ -0,0 +1,258 @@
+//! Persistent asyncio event loop for Python coroutines.
+//!
+//! The server runs on its own Tokio runtime with the GIL released, so no
+//! asyncio loop is running on its threads. `start()` creates one loop in a
+//! background daemon thread, once per process; coroutines returned by async
+//! handlers, hooks and jobs are submitted to it with `call_soon_threadsafe`
+//! and awaited from Tokio.
+//!
+//! Where no persistent loop has been started (the test client, code called
+//! from Python), coroutines run on the caller's loop as before.
+//!
+//! [`call_with_timeout`] calls a sync or async callable under a deadline
+//! that stops the call rather than just no longer waiting for it.
+//! [`call_with_deadline`] stops it the same way but returns at the deadline,
+//! leaving an interrupted call to finish in the background.
+
+use std::future::Future;
+use std::os::raw::c_long;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use pyo3::exceptions::{PyRuntimeError, PyTimeoutError};
+use pyo3::prelude::*;
+use pyo3::sync::GILOnceCell;
+use pyo3::types::{PyDict, PyTuple};
+use pyo3_asyncio::TaskLocals;
+
+/// The persistent loop, once started.
+static EVENT_LOOP: GILOnceCell<TaskLocals> = GILOnceCell::new();
+
+/// Start the persistent event loop if it is not running yet.
+pub fn start(py: Python<'_>) -> PyResult<()> {
+    EVENT_LOOP.get_or_try_init(py, || {
+        let event_loop = py.import("asyncio")?.call_method0("new_event_loop")?;
+        let kwargs = PyDict::new(py);
+        kwargs.set_item("target", event_loop.getattr("run_forever")?)?;
+        kwargs.set_item("name", "cello-asyncio")?;
+        kwargs.set_item("daemon", true)?;
+        py.import("threading")?
+            .getattr("Thread")?
+            .call((), Some(kwargs))?
+            .call_method0("start")?;
+        TaskLocals::new(event_loop).copy_context(py)
+    })?;
+    Ok(())
+}
+
+/// Convert an awaitable into a Rust future.
+///
+/// The awaitable is scheduled on the persistent loop when it is running,
+/// and on the current task's loop otherwise.
+pub fn into_future(awaitable: &PyAny) -> PyResult<impl Future<Output = PyResult<PyObject>> + Send> {
+    let py = awaitable.py();
+    match EVENT_LOOP.get(py) {
+        Some(locals) => pyo3_asyncio::into_future_with_locals(locals, awaitable),
+        None => pyo3_asyncio::into_future_with_locals(
+            &pyo3_asyncio::tokio::get_current_locals(py)?,
+            awaitable,
+        ),
+    }
+}
+
+/// Why a call made by [`call_with_timeout`] returned no value.
+#[derive(Debug)]
+pub enum CallError {
+    /// The callable raised.
+    Raised(PyErr),
+    /// The timeout passed and the call was stopped.
+    TimedOut,
+}
+
+/// Where a bounded sync call is, guarded so a timeout never interrupts the
+/// thread once the call has returned.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum CallState {
+    Starting,
+    Running(c_long),
+    Cancelled,
+    Done,
+}
+
+/// Call `callable` with the arguments built by `args`, awaiting a returned
+/// coroutine, and stop the call once `timeout` passes.
+///
+/// Without a timeout the callable is called inline. With one it is called on
+/// a blocking thread: a sync callable still running at the deadline has
+/// `TimeoutError` raised in its thread, which stops it at its next bytecode
+/// (a blocking C call, such as `time.sleep`, finishes first), and a
+/// coroutine is awaited under `asyncio.wait_for`, which cancels it. Either
+/// way this returns only once the call has stopped, so a caller that retries
+/// after [`CallError::TimedOut`] never runs two attempts at once.
+pub async fn call_with_timeout<F>(
+    callable: Arc<PyObject>,
+    args: F,
+    timeout: Option<Duration>,
+) -> Result<PyObject, CallError>
+where
+    F: FnOnce(Python<'_>) -> PyResult<Py<PyTuple>> + Send + 'static,
+{
+    call_bounded(callable, args, timeout, true).await
+}
+
+/// Like [`call_with_timeout`], but return [`CallError::TimedOut`] as soon as
+/// `timeout` passes.
+///
+/// The call is still stopped, but a sync callable in a blocking C call
+/// finishes that call on its own thread after this returns. Use it where the
+/// caller must answer on time, such as a request deadline, and never starts
+/// another attempt.
+pub async fn call_with_deadline<F>(
+    callable: Arc<PyObject>,
+    args: F,
+    timeout: Option<Duration>,
+) -> Result<PyObject, CallError>
+where
+    F: FnOnce(Python<'_>) -> PyResult<Py<PyTuple>> + Send + 'static,
+{
+    call_bounded(callable, args, timeout, false).await
+}
+
+/// Make a bounded call, waiting for an interrupted sync call to stop when
+/// `wait` is set.
+async fn call_bounded<F>(
+    callable: Arc<PyObject>,
+    args: F,
+    timeout: Option<Duration>,
+    wait: bool,
+) -> Result<PyObject, CallError>
+where
+    F: FnOnce(Python<'_>) -> PyResult<Py<PyTuple>> + Send + 'static,
+{
+    let Some(timeout) = timeout else {
+        let ret = Python::with_gil(|py| callable.call1(py, args(py)?.as_ref(py)))
+            .map_err(CallError::Raised)?;
+        return await_coroutine(ret, None).await;
+    };
+    let deadline = Instant::now() + timeout;
+    let state = Arc::new(Mutex::new(CallState::Starting));
+
+    let mut worker = tokio::task::spawn_blocking({
+        let state = state.clone();
+        move || Python::with_gil(|py| call_in_worker(py, &callable, args, &state, deadline))
+    });
+    let called = match tokio::time::timeout_at(deadline.into(), &mut worker).await {
+        Ok(joined) => joined,
+        Err(_) if wait => {
+            interrupt(&state);
+            worker.await
+        }
+        Err(_) => {
+            // Taking the GIL may itself wait on the running call
+            tokio::task::spawn_blocking(move || interrupt(&state));
+            return Err(CallError::TimedOut);
+        }
+    };
+    let ret = called.map_err(|e| CallError::Raised(PyRuntimeError::new_err(e.to_string())))??;
+    if wait {
+        return await_coroutine(ret, Some(deadline)).await;
+    }
+    tokio::time::timeout_at(deadline.into(), await_coroutine(ret, Some(deadline)))
+        .await
+        .unwrap_or(Err(CallError::TimedOut))
+}
+
+/// Stop a bounded sync call that is still running: raise `TimeoutError` in
+/// its thread, or keep it from starting.
+fn interrupt(state: &Mutex<CallState>) {
+    Python::with_gil(|_| {
+        let mut state = state.lock();
+        if let CallState::Running(ident) = *state {
+            // SAFETY: called with the GIL held, on a thread that is still
+            // inside the call
+            unsafe {
+                pyo3::ffi::PyThreadState_SetAsyncExc(ident, pyo3::ffi::PyExc_TimeoutError);
+            }
+        }
+        if *state != CallState::Done {
+            *state = CallState::Cancelled;
+        }
+    });
+}
+
+/// Make a bounded call on the current (blocking) thread, recording it in
+/// `state` so the caller can interrupt it. A returned coroutine is wrapped in
+/// `asyncio.wait_for` with the time left.
+fn call_in_worker<F>(
+    py: Python<'_>,
+    callable: &PyObject,
+    args: F,
+    state: &Mutex<CallState>,
+    deadline: Instant,
+) -> Result<PyObject, CallError>
+where
+    F: FnOnce(Python<'_>) -> PyResult<Py<PyTuple>>,
+{
+    let ident: c_long = py
+        .import("threading")
+        .and_then(|threading| threading.call_method0("get_ident"))
+        .and_then(|ident| ident.extract::<u64>())
+        .map_err(CallError::Raised)? as c_long;
+    {
+        let mut state = state.lock();
+        if *state == CallState::Cancelled {
+            return Err(CallError::TimedOut);
+        }
+        *state = CallState::Running(ident);
+    }
+    let ret = args(py).and_then(|args| callable.call1(py, args.as_ref(py)));
+    if std::mem::replace(&mut *state.lock(), CallState::Done) == CallState::Cancelled {
+        // SAFETY: clears this thread's own pending exception, if the timeout
+        // was raised after the call's last bytecode
+        unsafe {
+            pyo3::ffi::PyThreadState_SetAsyncExc(ident, std::ptr::null_mut());
+        }
+        return Err(CallError::TimedOut);
+    }
+    let ret = ret.map_err(CallError::Raised)?;
+    if !is_coroutine(py, &ret).map_err(CallError::Raised)? {
+        return Ok(ret);
+    }
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    py.import("asyncio")
+        .and_then(|asyncio| asyncio.call_method1("wait_for", (ret, remaining.as_secs_f64())))
+        .map(Into::into)
+        .map_err(CallError::Raised)
+}
+
+/// Await `ret` if it is a coroutine, reporting a `TimeoutError` raised once
+/// `deadline` has passed as a timeout.
+async fn await_coroutine(ret: PyObject, deadline: Option<Instant>) -> Result<PyObject, CallError> {
+    let future = Python::with_gil(|py| -> PyResult<_> {
+        if !is_coroutine(py, &ret)? {
+            return Ok(None);
+        }
+        into_future(ret.as_ref(py)).map(Some)
+    })
+    .map_err(CallError::Raised)?;
+    let Some(future) = future else {
+        return Ok(ret);
+    };
+    future.await.map_err(|err| {
+        let timed_out = deadline.is_some_and(|deadline| deadline <= Instant::now())
+            && Python::with_gil(|py| err.is_instance_of::<PyTimeoutError>(py));
+        if timed_out {
+            CallError::TimedOut
+        } else {
+            CallError::Raised(err)
+        }
+    })
+}
+
+/// Whether `value` is a coroutine.
+pub fn is_coroutine(py: Python<'_>, value: &PyObject) -> PyResult<bool> {
+    py.import("inspect")?
+        .call_method1("iscoroutine", (value.as_ref(py),))?
+        .is_true()
+}
@@ -8,11 +8,25 @@
 
 use parking_lot::RwLock;
 use pyo3::prelude::*;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use pyo3::types::{PyBytes, PyString};
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU16, AtomicU32, Ordering};
+use std::sync::{Arc, OnceLock};
 
-use crate::json::{python_to_json, python_to_json_bytes_direct};
+use crate::codec::{python_to_bytes_direct, BodyFormat};
+use crate::error::ErrorHandlerRegistry;
+use crate::json::{python_to_json, python_to_json_bytes_direct, python_to_json_bytes_unchecked};
+use crate::middleware::idempotency::IdempotencyMiddleware;
+use crate::middleware::object_store::ObjectDownload;
+use crate::middleware::protobuf::ProtoBinding;
+use crate::middleware::route_middleware::RouteMiddleware;
+use crate::middleware::transaction::TransactionPolicy;
+use crate::middleware::webhook_verify::WebhookVerifier;
 use crate::request::Request;
+use crate::response::{HeaderPolicy, JsonStream, Response};
+use crate::routing::{Canary, Disabled};
+use crate::validation::RequestValidator;
 
 /// Result from handler invocation - either pre-serialized JSON bytes or a serde_json::Value.
 /// PERF: The bytes variant skips the intermediate serde_json::Value allocation for the common
@@ -22,10 +36,71 @@ pub enum HandlerResult {
     JsonBytes(Vec<u8>),
     /// serde_json::Value (Response objects that need special handling)
     JsonValue(serde_json::Value),
+    /// Pre-serialized bytes in a negotiated non-JSON format (MessagePack, CBOR)
+    Encoded(BodyFormat, Vec<u8>),
+    /// A text or bytes body from a route with a response preset, with its content type
+    Raw(&'static str, Vec<u8>),
+    /// A JSON array encoded while the response is sent
+    Stream(JsonStream),
+    /// A file response, streamed from disk by the server
+    File(Response),
+    /// An object store download, streamed from the store by the server
+    Download(ObjectDownload),
+    /// A response built by the app's exception handler for a raised exception
+    Handled(Response),
 }
 
+/// Outcome of calling a handler while holding the GIL.
+enum Called<F> {
+    /// A sync handler's serialized result
+    Done(HandlerResult),
+    /// A coroutine to await, with the request it was given
+    Awaiting(PyObject, F),
+    /// The handler raised, with the request it was given
+    Raised(PyObject, PyErr),
+}
+
+/// What a route declares it always returns, fixed at registration.
+///
+/// PERF: Lets serialization go straight to the right encoder instead of
+/// sniffing the Python type of every result. A result of another type still
+/// falls back to the general path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponsePreset {
+    /// JSON-serializable data (dict, list or scalar)
+    Json,
+    /// A `str`, sent as `text/plain`
+    Text,
+    /// A `bytes` object, sent as `application/octet-stream`
+    Bytes,
+}
+
+impl FromStr for ResponsePreset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(ResponsePreset::Json),
+            "text" => Ok(ResponsePreset::Text),
+            "bytes" => Ok(ResponsePreset::Bytes),
+            _ => Err(format!(
+                "Unknown response format '{s}'; expected 'json', 'text' or 'bytes'"
+            )),
+        }
+    }
+}
+
+/// Signatures are checked against the whole body, so a route that verifies
+/// webhooks must buffer it.
+const WEBHOOK_STREAM_BODY: &str =
+    "Webhook verification needs the buffered request body; it cannot be combined with @stream_body";
+
 /// Cached metadata for a handler to avoid per-request introspection.
-struct HandlerMeta {
+///
+/// PERF: Shared via `Arc` with the router so a route match yields the handler
+/// directly, without a registry lock or index lookup.
+#[derive(Debug)]
+pub struct HandlerMeta {
     /// The Python handler callable
     handler: PyObject,
     /// Whether this handler is async (returns coroutine)
@@ -36,6 +111,196 @@ struct HandlerMeta {
     di_params: RwLock<Option<Vec<(String, String)>>>,
     /// Whether DI params have been resolved
     di_checked: AtomicBool,
+    /// Request schemas checked before the handler runs (set once, read lock-free)
+    validator: OnceLock<Arc<RequestValidator>>,
+    /// Protobuf message types for request/response bodies (set once, read lock-free)
+    protobuf: OnceLock<Arc<ProtoBinding>>,
+    /// Inbound webhook signature check (set once, read lock-free)
+    webhook: OnceLock<Arc<WebhookVerifier>>,
+    /// Transaction opened around each call (set once, read lock-free)
+    transaction: OnceLock<TransactionPolicy>,
+    /// Idempotency-key check around each call (set once, read lock-free)
+    idempotency: OnceLock<Arc<IdempotencyMiddleware>>,
+    /// Headers applied to each response (set once, read lock-free)
+    header_policy: OnceLock<Arc<HeaderPolicy>>,
+    /// Canary version of this handler (set once, read lock-free)
+    canary: OnceLock<Arc<Canary>>,
+    /// Middleware chain compiled for this handler's route
+    middleware: RwLock<Option<Arc<RouteMiddleware>>>,
+    /// Whether the request body is streamed to the handler unbuffered
+    stream_body: AtomicBool,
+    /// Status answered instead of calling the handler, or 0 while enabled
+    disabled_status: AtomicU16,
+    /// `Retry-After` seconds sent while disabled, or 0 for none
+    disabled_retry_after: AtomicU32,
+    /// Declared return type of the handler (set once, read lock-free)
+    response_preset: OnceLock<ResponsePreset>,
+}
+
+impl HandlerMeta {
+    /// Get the request validator attached to this handler, if any.
+    #[inline]
+    pub fn validator(&self) -> Option<&Arc<RequestValidator>> {
+        self.validator.get()
+    }
+
+    /// Attach a request validator. Each handler can be given one validator.
+    pub fn set_validator(&self, validator: RequestValidator) -> Result<(), String> {
+        self.validator
+            .set(Arc::new(validator))
+            .map_err(|_| "Request validation is already configured for this route".to_string())
+    }
+
+    /// Get the protobuf message types bound to this handler, if any.
+    #[inline]
+    pub fn protobuf(&self) -> Option<&Arc<ProtoBinding>> {
+        self.protobuf.get()
+    }
+
+    /// Bind protobuf message types. Each handler can be given one binding.
+    pub fn set_protobuf(&self, binding: ProtoBinding) -> Result<(), String> {
+        self.protobuf
+            .set(Arc::new(binding))
+            .map_err(|_| "Protobuf bodies are already configured for this route".to_string())
+    }
+
+    /// Get the webhook signature verifier attached to this handler, if any.
+    #[inline]
+    pub fn webhook(&self) -> Option<&Arc<WebhookVerifier>> {
+        self.webhook.get()
+    }
+
+    /// Attach a webhook signature verifier. Each handler can be given one,
+    /// and only if its request body is buffered: the signature covers it.
+    pub fn set_webhook(&self, verifier: WebhookVerifier) -> Result<(), String> {
+        if self.stream_body() {
+            return Err(WEBHOOK_STREAM_BODY.to_string());
+        }
+        self.webhook
+            .set(Arc::new(verifier))
+            .map_err(|_| "Webhook verification is already configured for this route".to_string())
+    }
+
+    /// Get the transaction policy of this handler, if it runs in one.
+    #[inline]
+    pub fn transaction(&self) -> Option<&TransactionPolicy> {
+        self.transaction.get()
+    }
+
+    /// Run each call in a transaction. Each handler can be given one policy.
+    pub fn set_transaction(&self, policy: TransactionPolicy) -> Result<(), String> {
+        self.transaction
+            .set(policy)
+            .map_err(|_| "A transaction is already configured for this route".to_string())
+    }
+
+    /// Get the idempotency-key check of this handler, if any.
+    #[inline]
+    pub fn idempotency(&self) -> Option<&Arc<IdempotencyMiddleware>> {
+        self.idempotency.get()
+    }
+
+    /// Check idempotency keys on each call. Each handler can be given one
+    /// check.
+    pub fn set_idempotency(&self, idempotency: IdempotencyMiddleware) -> Result<(), String> {
+        self.idempotency
+            .set(Arc::new(idempotency))
+            .map_err(|_| "Idempotency keys are already configured for this route".to_string())
+    }
+
+    /// Get the response header policy of this handler, if any.
+    #[inline]
+    pub fn header_policy(&self) -> Option<&Arc<HeaderPolicy>> {
+        self.header_policy.get()
+    }
+
+    /// Attach a response header policy. Each handler can be given one.
+    pub fn set_header_policy(&self, policy: HeaderPolicy) -> Result<(), String> {
+        self.header_policy
+            .set(Arc::new(policy))
+            .map_err(|_| "A header policy is already configured for this route".to_string())
+    }
+
+    /// Get the canary version of this handler, if any.
+    #[inline]
+    pub fn canary(&self) -> Option<&Arc<Canary>> {
+        self.canary.get()
+    }
+
+    /// Route a share of calls to a canary handler. Each handler can be given
+    /// one canary.
+    pub fn set_canary(&self, canary: Canary) -> Result<(), String> {
+        self.canary
+            .set(Arc::new(canary))
+            .map_err(|_| "A canary is already configured for this route".to_string())
+    }
+
+    /// Get the middleware chain compiled for this handler's route, if any.
+    #[inline]
+    pub fn middleware(&self) -> Option<Arc<RouteMiddleware>> {
+        self.middleware.read().clone()
+    }
+
+    /// Replace the middleware chain of this handler's route.
+    pub fn set_middleware(&self, middleware: Option<Arc<RouteMiddleware>>) {
+        *self.middleware.write() = middleware;
+    }
+
+    /// Whether the request body is streamed to the handler unbuffered.
+    #[inline]
+    pub fn stream_body(&self) -> bool {
+        self.stream_body.load(Ordering::Relaxed)
+    }
+
+    /// Stream the request body to the handler instead of buffering it.
+    /// Routes that verify webhook signatures cannot stream.
+    pub fn set_stream_body(&self) -> Result<(), String> {
+        if self.webhook().is_some() {
+            return Err(WEBHOOK_STREAM_BODY.to_string());
+        }
+        self.stream_body.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// How this handler's route answers while disabled, or `None` while it
+    /// is enabled.
+    #[inline]
+    pub fn disabled(&self) -> Option<Disabled> {
+        match self.disabled_status.load(Ordering::Acquire) {
+            0 => None,
+            status => Some(Disabled {
+                status,
+                retry_after: match self.disabled_retry_after.load(Ordering::Relaxed) {
+                    0 => None,
+                    seconds => Some(seconds),
+                },
+            }),
+        }
+    }
+
+    /// Disable the route, or enable it again with `None`. Calls already
+    /// running are not affected.
+    pub fn set_disabled(&self, disabled: Option<Disabled>) {
+        let (status, retry_after) = disabled
+            .map(|d| (d.status, d.retry_after.unwrap_or(0)))
+            .unwrap_or((0, 0));
+        self.disabled_retry_after
+            .store(retry_after, Ordering::Relaxed);
+        self.disabled_status.store(status, Ordering::Release);
+    }
+
+    /// Get the declared return type of this handler, if any.
+    #[inline]
+    pub fn response_preset(&self) -> Option<ResponsePreset> {
+        self.response_preset.get().copied()
+    }
+
+    /// Declare the handler's return type. Each handler can be given one.
+    pub fn set_response_preset(&self, preset: ResponsePreset) -> Result<(), String> {
+        self.response_preset
+            .set(preset)
+            .map_err(|_| "A response format is already configured for this route".to_string())
+    }
 }
 
 /// Registry for Python handler functions.
@@ -43,8 +308,10 @@ struct HandlerMeta {
 pub struct HandlerRegistry {
     /// Store handlers with cached metadata
     handlers: Arc<RwLock<Vec<Arc<HandlerMeta>>>>,
-    /// PERF: Cached flag for whether any DI singletons exist (avoids lock per request)
+    /// PERF: Cached flag for whether any DI dependencies exist (avoids lock per request)
     has_dependencies: Arc<AtomicBool>,
+    /// Exception and status handlers registered by the app
+    error_handlers: Arc<ErrorHandlerRegistry>,
 }
 
 impl HandlerRegistry {
@@ -53,6 +320,7 @@ impl HandlerRegistry {
         HandlerRegistry {
             handlers: Arc::new(RwLock::new(Vec::new())),
             has_dependencies: Arc::new(AtomicBool::new(false)),
+            error_handlers: Arc::new(ErrorHandlerRegistry::new()),
         }
     }
 
@@ -61,17 +329,37 @@ impl HandlerRegistry {
     /// # Returns
     /// The unique handler ID for this function.
     pub fn register(&mut self, handler: PyObject) -> usize {
+        self.register_with_meta(handler).0
+    }
+
+    /// Register a Python handler function and return its shared metadata.
+    ///
+    /// The returned `Arc<HandlerMeta>` is meant to be stored in the router entry
+    /// so request dispatch can use `invoke_meta_async` without touching the registry.
+    pub fn register_with_meta(&mut self, handler: PyObject) -> (usize, Arc<HandlerMeta>) {
         let meta = Arc::new(HandlerMeta {
             handler,
             is_async: AtomicBool::new(false),
             async_checked: AtomicBool::new(false),
             di_params: RwLock::new(None),
             di_checked: AtomicBool::new(false),
+            validator: OnceLock::new(),
+            protobuf: OnceLock::new(),
+            webhook: OnceLock::new(),
+            transaction: OnceLock::new(),
+            idempotency: OnceLock::new(),
+            header_policy: OnceLock::new(),
+            canary: OnceLock::new(),
+            middleware: RwLock::new(None),
+            stream_body: AtomicBool::new(false),
+            disabled_status: AtomicU16::new(0),
+            disabled_retry_after: AtomicU32::new(0),
+            response_preset: OnceLock::new(),
         });
         let mut handlers = self.handlers.write();
         let id = handlers.len();
-        handlers.push(meta);
-        id
+        handlers.push(meta.clone());
+        (id, meta)
     }
 
     /// Get a handler by its ID.
@@ -83,11 +371,17 @@ impl HandlerRegistry {
 
     /// Get handler metadata by ID.
     #[inline]
-    fn get_meta(&self, id: usize) -> Option<Arc<HandlerMeta>> {
+    pub fn get_meta(&self, id: usize) -> Option<Arc<HandlerMeta>> {
         let handlers = self.handlers.read();
         handlers.get(id).cloned()
     }
 
+    /// The app's exception and status handlers.
+    #[inline]
+    pub fn error_handlers(&self) -> &Arc<ErrorHandlerRegistry> {
+        &self.error_handlers
+    }
+
     /// Notify that dependencies have been registered.
     pub fn set_has_dependencies(&self, has: bool) {
         self.has_dependencies.store(has, Ordering::Relaxed);
@@ -98,15 +392,18 @@ impl HandlerRegistry {
     /// PERF OPTIMIZATIONS:
     /// 1. Caches async detection per handler (avoid inspect.iscoroutine every call)
     /// 2. Caches DI parameter resolution per handler (avoid inspect.signature every call)
-    /// 3. Skips DI entirely when no singletons registered (atomic check, no lock)
-    /// 4. Three-phase GIL acquisition: call → await (GIL free) → serialize
-    /// 5. Returns HandlerResult::JsonBytes for common dict returns (skips serde_json::Value)
+    /// 3. Skips DI entirely when no dependencies registered (atomic check, no lock)
+    /// 4. Sync handlers are called and serialized under a single GIL acquisition
+    /// 5. Returns HandlerResult::JsonBytes for common dict returns (skips serde_json::Value),
+    ///    or HandlerResult::Encoded when the client accepts MessagePack/CBOR
     ///
     /// GIL RELEASE ARCHITECTURE:
     ///   Phase 1 (GIL):    Call Python handler, do DI resolution, detect coroutine.
-    ///   Phase 2 (no GIL): pyo3-asyncio converts coroutine → Rust Future; Tokio awaits it.
-    ///                      GIL is released during Python I/O waits inside the coroutine.
-    ///   Phase 3 (GIL):    Serialize result back to HandlerResult.
+    ///                      Async dependencies are awaited with the GIL released.
+    ///                      A sync result is serialized here and returned.
+    ///   Phase 2 (no GIL): A coroutine is submitted to the persistent asyncio loop
+    ///                      (see `event_loop`); Tokio awaits its result.
+    ///   Phase 3 (GIL):    Serialize the coroutine's result back to HandlerResult.
     pub async fn invoke_async(
         &self,
         handler_id: usize,
@@ -116,148 +413,140 @@ impl HandlerRegistry {
         let meta = self
             .get_meta(handler_id)
             .ok_or_else(|| format!("Handler {handler_id} not found"))?;
+        self.invoke_meta_async(
+            &meta,
+            request,
+            dependency_container,
+            crate::dependency::PyDependencyCache::new(),
+        )
+        .await
+    }
 
+    /// Invoke a handler from its cached metadata (async-aware).
+    ///
+    /// PERF: Hot-path entry point used with the `Arc<HandlerMeta>` returned by
+    /// the router, so no registry lock is taken per request.
+    ///
+    /// `scope` holds dependencies already resolved for this request (such as
+    /// its transaction); they take precedence over registered ones and over
+    /// those of the container's request scope.
+    pub async fn invoke_meta_async(
+        &self,
+        meta: &HandlerMeta,
+        request: Request,
+        dependency_container: Arc<crate::dependency::DependencyContainer>,
+        scope: crate::dependency::PyDependencyCache,
+    ) -> Result<HandlerResult, String> {
         // PERF: Fast atomic check instead of RwLock read on dependency container
         let has_dependencies = self.has_dependencies.load(Ordering::Relaxed)
             && dependency_container.has_py_singletons();
 
+        // Negotiate the response format before the request moves into Python
+        let format = BodyFormat::negotiate(request.headers.get("accept"));
+
         // ── Phase 1 (GIL): call handler, detect coroutine ──────────────────────
-        let (raw_result, is_coroutine) =
-            Python::with_gil(|py| -> Result<(PyObject, bool), String> {
-                let call_result: PyObject = if has_dependencies {
-                    // DI resolution — cache parameter info on first call
-                    if !meta.di_checked.load(Ordering::Relaxed) {
-                        let mut di_params = Vec::new();
-                        if let Ok(inspect) = py.import("inspect") {
-                            if let Ok(sig) =
-                                inspect.call_method1("signature", (meta.handler.as_ref(py),))
-                            {
-                                if let Ok(parameters) = sig.getattr("parameters") {
-                                    let cello_module = py.import("cello").ok();
-                                    let depends_type =
-                                        cello_module.and_then(|m| m.getattr("Depends").ok());
-
-                                    if let Ok(items) = parameters.call_method0("items") {
-                                        if let Ok(iter) = items.iter() {
-                                            for item in iter.flatten() {
-                                                if let (Ok(name), Ok(param)) = (
-                                                    item.get_item(0)
-                                                        .and_then(|v| v.extract::<String>()),
-                                                    item.get_item(1),
-                                                ) {
-                                                    if let Ok(default) = param.getattr("default") {
-                                                        if let Some(dt) = &depends_type {
-                                                            if default
-                                                                .is_instance(dt)
-                                                                .unwrap_or(false)
-                                                            {
-                                                                if let Ok(dep_name) = default
-                                                                    .getattr("dependency")
-                                                                    .and_then(|d| {
-                                                                        d.extract::<String>()
-                                                                    })
-                                                                {
-                                                                    di_params
-                                                                        .push((name, dep_name));
-                                                                }
-                                                            }
-                                                        }
-                                                    }
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                        *meta.di_params.write() = Some(di_params);
-                        meta.di_checked.store(true, Ordering::Relaxed);
-                    }
+        //
+        // A handler with dependencies leaves the GIL section with its request
+        // and the dependency graph's request scope; the graph is resolved,
+        // awaiting async factories, and the handler is called afterwards.
+        let prepared = Python::with_gil(|py| {
+            if has_dependencies {
+                // DI resolution — cache parameter info on first call
+                if !meta.di_checked.load(Ordering::Relaxed) {
+                    let di_params = crate::dependency::depends_params(py, meta.handler.as_ref(py))
+                        .unwrap_or_default();
+                    *meta.di_params.write() = Some(di_params);
+                    meta.di_checked.store(true, Ordering::Relaxed);
+                }
 
-                    let di_guard = meta.di_params.read();
-                    match di_guard.as_ref() {
-                        Some(params) if params.is_empty() => {
-                            // No DI params — fast path
-                            meta.handler
-                                .call1(py, (request,))
-                                .map_err(|e| format!("Handler error: {e}"))?
-                        }
-                        Some(params) => {
-                            let kwargs = pyo3::types::PyDict::new(py);
-                            for (param_name, dep_name) in params {
-                                if let Some(dep_value) =
-                                    dependency_container.get_py_singleton(dep_name)
-                                {
-                                    let _ = kwargs.set_item(param_name, dep_value);
-                                }
-                            }
-                            meta.handler
-                                .call(py, (request,), Some(kwargs))
-                                .map_err(|e| format!("Handler error: {e}"))?
-                        }
-                        None => {
-                            // Params not cached yet (should not happen); use fast path
-                            meta.handler
-                                .call1(py, (request,))
-                                .map_err(|e| format!("Handler error: {e}"))?
-                        }
+                if let Some(params) = meta.di_params.read().as_ref().filter(|p| !p.is_empty()) {
+                    // Dependants share request-scoped values within this call
+                    let mut cache = dependency_container
+                        .request_dependencies(py, &request)
+                        .map_err(|e| format!("Dependency error: {e}"))?;
+                    cache.extend(scope);
+                    return Ok(Err((request.into_py(py), params.clone(), cache)));
+                }
+            }
+
+            // No DI params — fast path: direct call, no locks
+            let request = request.into_py(py);
+            let result = meta.handler.call1(py, (request.clone_ref(py),));
+            call_outcome(py, meta, format, request, result).map(Ok)
+        })?;
+        let pending = match prepared {
+            Ok(called) => called,
+            Err((request, params, mut cache)) => {
+                let mut values = Vec::with_capacity(params.len());
+                for (_, dep_name) in &params {
+                    let value = dependency_container
+                        .resolve_py(dep_name, &request, &mut cache)
+                        .await
+                        .map_err(|e| format!("Dependency error: {e}"))?;
+                    values.push(value);
+                }
+                Python::with_gil(|py| {
+                    let kwargs = pyo3::types::PyDict::new(py);
+                    for ((param_name, _), value) in params.iter().zip(values) {
+                        let _ = kwargs.set_item(param_name, value);
                     }
-                } else {
-                    // FAST PATH: direct call, no DI, no locks
-                    meta.handler
-                        .call1(py, (request,))
-                        .map_err(|e| format!("Handler error: {e}"))?
-                };
-
-                // Cache async detection per handler (first call probes, then reads atomically)
-                let is_coro = if meta.async_checked.load(Ordering::Relaxed) {
-                    meta.is_async.load(Ordering::Relaxed)
-                } else {
-                    let is_async = py
-                        .import("inspect")
-                        .and_then(|inspect| {
-                            inspect.call_method1("iscoroutine", (call_result.as_ref(py),))
-                        })
-                        .and_then(|r| r.is_true())
-                        .unwrap_or(false);
-                    meta.is_async.store(is_async, Ordering::Relaxed);
-                    meta.async_checked.store(true, Ordering::Relaxed);
-                    is_async
-                };
-
-                // PyObject (Py<PyAny>) is Send — safe to move out of GIL closure
-                Ok((call_result, is_coro))
-            })?;
-
-        // ── Phase 2 (GIL released during I/O waits): drive coroutine via Tokio ─
+                    let result = meta
+                        .handler
+                        .call(py, (request.clone_ref(py),), Some(kwargs));
+                    call_outcome(py, meta, format, request, result)
+                })?
+            }
+        };
+        let (request, future) = match pending {
+            Called::Done(result) => return Ok(result),
+            Called::Awaiting(request, future) => (request, future),
+            Called::Raised(request, e) => {
+                return self
+                    .handle_exception(meta, request, e, "Handler error")
+                    .await
+            }
+        };
+
+        // ── Phase 2 (GIL released during I/O waits): drive coroutine ───────────
         //
-        // pyo3_asyncio::tokio::into_future wraps the Python coroutine as a Rust
-        // Future that Tokio can poll. The GIL is acquired only when the coroutine
-        // has work to do (Python bytecode execution) and released between steps,
-        // so other Tokio tasks can make progress during I/O waits.
-        let final_result: PyObject = if is_coroutine {
-            let future = Python::with_gil(|py| {
-                pyo3_asyncio::tokio::into_future(raw_result.as_ref(py))
-                    .map_err(|e| format!("Async setup error: {e}"))
-            })?;
-            // GIL is fully released here while Tokio drives the coroutine
-            future
-                .await
-                .map_err(|e| format!("Async handler error: {e}"))?
-        } else {
-            raw_result
+        // The coroutine runs on the asyncio loop's thread, which takes the GIL
+        // only while it executes Python bytecode, so other Tokio tasks make
+        // progress during its I/O waits.
+        let final_result = match future.await {
+            Ok(result) => result,
+            Err(e) => {
+                return self
+                    .handle_exception(meta, request, e, "Async handler error")
+                    .await
+            }
         };
 
         // ── Phase 3 (GIL): serialize result ─────────────────────────────────────
-        Python::with_gil(|py| {
-            // PERF: Try direct-to-bytes first (skips serde_json::Value allocation)
-            match python_to_json_bytes_direct(py, final_result.as_ref(py))? {
-                Some(bytes) => Ok(HandlerResult::JsonBytes(bytes)),
-                None => {
-                    python_to_json(py, final_result.as_ref(py)).map(HandlerResult::JsonValue)
-                }
+        Python::with_gil(|py| serialize_result(py, meta, format, final_result.as_ref(py)))
+    }
+
+    /// Response from the app's exception handlers for an exception raised by a
+    /// handler, or the error itself when none applies.
+    async fn handle_exception(
+        &self,
+        meta: &HandlerMeta,
+        request: PyObject,
+        err: PyErr,
+        context: &str,
+    ) -> Result<HandlerResult, String> {
+        // Guard rejections and undecodable bodies are answered even when no
+        // error handlers are registered
+        let rejected = || Python::with_gil(|py| crate::error::client_error(py, &err).is_some());
+        if self.error_handlers.is_active() || rejected() {
+            if let Some(response) = self
+                .error_handlers
+                .handle_exception(request, &err, &meta.handler)
+                .await
+            {
+                return Ok(HandlerResult::Handled(response));
             }
-        })
+        }
+        Err(format!("{context}: {err}"))
     }
 
     /// Invoke a handler synchronously (legacy method for compatibility).
@@ -298,3 +587,118 @@ impl Default for HandlerRegistry {
         Self::new()
     }
 }
+
+/// Sort a handler call's result into a serialized sync result, a coroutine
+/// to await, or the exception it raised.
+fn call_outcome(
+    py: Python<'_>,
+    meta: &HandlerMeta,
+    format: BodyFormat,
+    request: PyObject,
+    call_result: PyResult<PyObject>,
+) -> Result<Called<impl Future<Output = PyResult<PyObject>> + Send>, String> {
+    let call_result = match call_result {
+        Ok(result) => result,
+        Err(e) => return Ok(Called::Raised(request, e)),
+    };
+
+    // Cache async detection per handler (first call probes, then reads atomically)
+    let is_coro = if meta.async_checked.load(Ordering::Relaxed) {
+        meta.is_async.load(Ordering::Relaxed)
+    } else {
+        let is_async = py
+            .import("inspect")
+            .and_then(|inspect| inspect.call_method1("iscoroutine", (call_result.as_ref(py),)))
+            .and_then(|r| r.is_true())
+            .unwrap_or(false);
+        meta.is_async.store(is_async, Ordering::Relaxed);
+        meta.async_checked.store(true, Ordering::Relaxed);
+        is_async
+    };
+
+    // PERF: Sync results are serialized without releasing the GIL
+    if !is_coro {
+        return serialize_result(py, meta, format, call_result.as_ref(py)).map(Called::Done);
+    }
+    crate::event_loop::into_future(call_result.as_ref(py))
+        .map(|future| Called::Awaiting(request, future))
+        .map_err(|e| format!("Async setup error: {e}"))
+}
+
+/// Serialize a handler's return value in the negotiated format.
+fn serialize_result(
+    py: Python<'_>,
+    meta: &HandlerMeta,
+    format: BodyFormat,
+    result: &PyAny,
+) -> Result<HandlerResult, String> {
+    // Streamed arrays are always JSON, whatever the client accepts
+    if let Ok(stream) = result.downcast::<PyCell<JsonStream>>() {
+        return Ok(HandlerResult::Stream(stream.borrow().clone()));
+    }
+
+    if let Ok(download) = result.downcast::<PyCell<ObjectDownload>>() {
+        return Ok(HandlerResult::Download(download.borrow().clone()));
+    }
+
+    // File bodies are read by the server, not serialized
+    if let Ok(response) = result.downcast::<PyCell<Response>>() {
+        let response = response.borrow();
+        if response.is_file() {
+            return Ok(HandlerResult::File(response.clone()));
+        }
+    }
+
+    match (meta.response_preset(), format) {
+        (Some(ResponsePreset::Text), _) => {
+            if let Ok(text) = result.downcast::<PyString>() {
+                let body = text
+                    .to_str()
+                    .map_err(|e| e.to_string())?
+                    .as_bytes()
+                    .to_vec();
+                return Ok(HandlerResult::Raw("text/plain; charset=utf-8", body));
+            }
+        }
+        (Some(ResponsePreset::Bytes), _) => {
+            if let Ok(bytes) = result.downcast::<PyBytes>() {
+                let body = bytes.as_bytes().to_vec();
+                return Ok(HandlerResult::Raw("application/octet-stream", body));
+            }
+        }
+        (Some(ResponsePreset::Json), BodyFormat::Json) => {
+            if let Ok(bytes) = python_to_json_bytes_unchecked(py, result) {
+                return Ok(HandlerResult::JsonBytes(bytes));
+            }
+        }
+        _ => {}
+    }
+
+    match format {
+        BodyFormat::Json => {}
+        // Protobuf needs the route's response type; otherwise fall back to JSON
+        BodyFormat::Protobuf => {
+            if let Some(binding) = meta.protobuf() {
+                let value = python_to_json(py, result)?;
+                let is_response = value.get("__cello_response__").is_some();
+                if !is_response {
+                    if let Some(encoded) = binding.encode_response(&value) {
+                        return Ok(HandlerResult::Encoded(format, encoded?));
+                    }
+                }
+                return Ok(HandlerResult::JsonValue(value));
+            }
+        }
+        _ => {
+            if let Some(bytes) = python_to_bytes_direct(py, result, format)? {
+                return Ok(HandlerResult::Encoded(format, bytes));
+            }
+        }
+    }
+
+    // PERF: Try direct-to-bytes first (skips serde_json::Value allocation)
+    match python_to_json_bytes_direct(py, result)? {
+        Some(bytes) => Ok(HandlerResult::JsonBytes(bytes)),
+        None => python_to_json(py, result).map(HandlerResult::JsonValue),
+    }
+}
@@ -3,11 +3,23 @@
 //! Backed by reqwest + Tokio. The GIL is never held during network I/O —
 //! coroutines are driven by `pyo3_asyncio::tokio::future_into_py`, so HTTP
 //! wait time is pure Rust with no Python scheduler overhead.
+//!
+//! Requests to `upstream://<group>/<path>` are load-balanced across a named
+//! upstream group (see [`crate::upstream`]).
 
 use std::collections::HashMap;
+use std::sync::Arc;
 
+use bytes::Bytes;
+use parking_lot::RwLock;
 use pyo3::prelude::*;
-use pyo3::types::PyBytes;
+use pyo3::types::{PyBytes, PyDict};
+use reqwest::Method;
+
+use crate::upstream::{parse_upstream_url, HealthConfig, LoadBalancing, UpstreamGroup};
+
+/// Upstream groups registered on a client, by name.
+type UpstreamGroups = Arc<RwLock<HashMap<String, Arc<UpstreamGroup>>>>;
 
 // ── Response ──────────────────────────────────────────────────────────────────
 
@@ -69,9 +81,20 @@ impl PyHttpResponse {
 ///     async def proxy(request):
 ///         resp = await client.get("https://example.com")
 ///         return {"status": resp.status, "body": resp.text}
+///
+/// Each request method takes a `timeout` in seconds, under the client's
+/// own, e.g. the time left on a gRPC call being served::
+///
+///     resp = await client.get(url, timeout=request.time_remaining())
+///
+/// Load-balanced upstreams::
+///
+///     client.add_upstream_group("users", ["http://10.0.0.1", "http://10.0.0.2"])
+///     resp = await client.get("upstream://users/api/users/1")
 #[pyclass(name = "AsyncClient")]
 pub struct PyAsyncClient {
     client: reqwest::Client,
+    groups: UpstreamGroups,
 }
 
 #[pymethods]
@@ -83,25 +106,98 @@ impl PyAsyncClient {
             .timeout(std::time::Duration::from_secs_f64(timeout))
             .build()
             .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))?;
-        Ok(Self { client })
+        Ok(Self {
+            client,
+            groups: Arc::new(RwLock::new(HashMap::new())),
+        })
+    }
+
+    /// Register a named group of upstreams, addressed as
+    /// `upstream://<name>/<path>`.
+    ///
+    /// `upstreams` holds base URLs or `(url, weight)` pairs. An upstream is
+    /// ejected for `ejection` seconds after `max_failures` consecutive
+    /// connection failures or 5xx responses; a request that cannot connect
+    /// is retried on up to `retries` other upstreams.
+    #[pyo3(signature = (name, upstreams, strategy = "round_robin", max_failures = 3, ejection = 30.0, retries = 1))]
+    fn add_upstream_group(
+        &self,
+        name: &str,
+        upstreams: Vec<&PyAny>,
+        strategy: &str,
+        max_failures: u32,
+        ejection: f64,
+        retries: usize,
+    ) -> PyResult<()> {
+        if upstreams.is_empty() {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "An upstream group needs at least one upstream",
+            ));
+        }
+        let upstreams = upstreams
+            .into_iter()
+            .map(|item| match item.extract::<String>() {
+                Ok(url) => Ok((url, 1)),
+                Err(_) => item.extract::<(String, u32)>(),
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+        let strategy: LoadBalancing = strategy
+            .parse()
+            .map_err(pyo3::exceptions::PyValueError::new_err)?;
+        let health = HealthConfig {
+            max_failures: max_failures.max(1),
+            ejection: crate::seconds_arg("ejection", ejection)?,
+        };
+        let group = UpstreamGroup::new(name, upstreams, strategy)
+            .with_health(health)
+            .with_retries(retries);
+        self.groups
+            .write()
+            .insert(name.to_string(), Arc::new(group));
+        Ok(())
+    }
+
+    /// Current state of each upstream in a group, as a list of dicts with
+    /// `url`, `weight`, `healthy`, `active`, `requests` and `failures`.
+    fn upstream_status(&self, py: Python<'_>, name: &str) -> PyResult<Vec<PyObject>> {
+        let group = upstream_group(&self.groups, name)?;
+        group
+            .upstreams()
+            .iter()
+            .map(|upstream| {
+                let status = PyDict::new(py);
+                status.set_item("url", upstream.url())?;
+                status.set_item("weight", upstream.weight())?;
+                status.set_item("healthy", !upstream.is_ejected())?;
+                status.set_item("active", upstream.active())?;
+                status.set_item("requests", upstream.requests())?;
+                status.set_item("failures", upstream.failures())?;
+                Ok(status.into())
+            })
+            .collect()
     }
 
     /// Send a GET request.
-    #[pyo3(signature = (url, headers = None))]
+    #[pyo3(signature = (url, headers = None, timeout = None))]
     fn get<'py>(
         &self,
         py: Python<'py>,
         url: String,
         headers: Option<HashMap<String, String>>,
+        timeout: Option<f64>,
     ) -> PyResult<&'py PyAny> {
-        let client = self.client.clone();
+        let timeout = timeout
+            .map(|t| crate::seconds_arg("timeout", t))
+            .transpose()?;
+        let (client, groups) = (self.client.clone(), self.groups.clone());
         pyo3_asyncio::tokio::future_into_py(py, async move {
-            dispatch(client.get(&url), headers, None, None).await
+            let request = dispatch(client, groups, Method::GET, url, headers, None, None);
+            with_timeout(timeout, request).await
         })
     }
 
     /// Send a POST request.
-    #[pyo3(signature = (url, json = None, content = None, headers = None))]
+    #[pyo3(signature = (url, json = None, content = None, headers = None, timeout = None))]
     fn post<'py>(
         &self,
         py: Python<'py>,
@@ -109,16 +205,29 @@ impl PyAsyncClient {
         json: Option<PyObject>,
         content: Option<Vec<u8>>,
         headers: Option<HashMap<String, String>>,
+        timeout: Option<f64>,
     ) -> PyResult<&'py PyAny> {
         let json_bytes = to_json_bytes(py, json)?;
-        let client = self.client.clone();
+        let timeout = timeout
+            .map(|t| crate::seconds_arg("timeout", t))
+            .transpose()?;
+        let (client, groups) = (self.client.clone(), self.groups.clone());
         pyo3_asyncio::tokio::future_into_py(py, async move {
-            dispatch(client.post(&url), headers, json_bytes, content).await
+            let request = dispatch(
+                client,
+                groups,
+                Method::POST,
+                url,
+                headers,
+                json_bytes,
+                content,
+            );
+            with_timeout(timeout, request).await
         })
     }
 
     /// Send a PUT request.
-    #[pyo3(signature = (url, json = None, content = None, headers = None))]
+    #[pyo3(signature = (url, json = None, content = None, headers = None, timeout = None))]
     fn put<'py>(
         &self,
         py: Python<'py>,
@@ -126,16 +235,29 @@ impl PyAsyncClient {
         json: Option<PyObject>,
         content: Option<Vec<u8>>,
         headers: Option<HashMap<String, String>>,
+        timeout: Option<f64>,
     ) -> PyResult<&'py PyAny> {
         let json_bytes = to_json_bytes(py, json)?;
-        let client = self.client.clone();
+        let timeout = timeout
+            .map(|t| crate::seconds_arg("timeout", t))
+            .transpose()?;
+        let (client, groups) = (self.client.clone(), self.groups.clone());
         pyo3_asyncio::tokio::future_into_py(py, async move {
-            dispatch(client.put(&url), headers, json_bytes, content).await
+            let request = dispatch(
+                client,
+                groups,
+                Method::PUT,
+                url,
+                headers,
+                json_bytes,
+                content,
+            );
+            with_timeout(timeout, request).await
         })
     }
 
     /// Send a PATCH request.
-    #[pyo3(signature = (url, json = None, content = None, headers = None))]
+    #[pyo3(signature = (url, json = None, content = None, headers = None, timeout = None))]
     fn patch<'py>(
         &self,
         py: Python<'py>,
@@ -143,25 +265,43 @@ impl PyAsyncClient {
         json: Option<PyObject>,
         content: Option<Vec<u8>>,
         headers: Option<HashMap<String, String>>,
+        timeout: Option<f64>,
     ) -> PyResult<&'py PyAny> {
         let json_bytes = to_json_bytes(py, json)?;
-        let client = self.client.clone();
+        let timeout = timeout
+            .map(|t| crate::seconds_arg("timeout", t))
+            .transpose()?;
+        let (client, groups) = (self.client.clone(), self.groups.clone());
         pyo3_asyncio::tokio::future_into_py(py, async move {
-            dispatch(client.patch(&url), headers, json_bytes, content).await
+            let request = dispatch(
+                client,
+                groups,
+                Method::PATCH,
+                url,
+                headers,
+                json_bytes,
+                content,
+            );
+            with_timeout(timeout, request).await
         })
     }
 
     /// Send a DELETE request.
-    #[pyo3(signature = (url, headers = None))]
+    #[pyo3(signature = (url, headers = None, timeout = None))]
     fn delete<'py>(
         &self,
         py: Python<'py>,
         url: String,
         headers: Option<HashMap<String, String>>,
+        timeout: Option<f64>,
     ) -> PyResult<&'py PyAny> {
-        let client = self.client.clone();
+        let timeout = timeout
+            .map(|t| crate::seconds_arg("timeout", t))
+            .transpose()?;
+        let (client, groups) = (self.client.clone(), self.groups.clone());
         pyo3_asyncio::tokio::future_into_py(py, async move {
-            dispatch(client.delete(&url), headers, None, None).await
+            let request = dispatch(client, groups, Method::DELETE, url, headers, None, None);
+            with_timeout(timeout, request).await
         })
     }
 
@@ -178,9 +318,7 @@ impl PyAsyncClient {
         _exc_val: PyObject,
         _exc_tb: PyObject,
     ) -> PyResult<&'py PyAny> {
-        pyo3_asyncio::tokio::future_into_py(py, async {
-            Ok(Python::with_gil(|py| py.None()))
-        })
+        pyo3_asyncio::tokio::future_into_py(py, async { Ok(Python::with_gil(|py| py.None())) })
     }
 }
 
@@ -201,16 +339,40 @@ fn to_json_bytes(py: Python<'_>, json: Option<PyObject>) -> PyResult<Option<Vec<
     }
 }
 
-/// Build and fire the request, then collect the response into a `PyHttpResponse`.
-///
-/// The GIL is held only for the final `into_py` call; all network I/O runs
-/// without the GIL.
-async fn dispatch(
-    mut builder: reqwest::RequestBuilder,
-    headers: Option<HashMap<String, String>>,
-    json_bytes: Option<Vec<u8>>,
-    content: Option<Vec<u8>>,
+/// Run `request` within `timeout` (the per-request limit, under the
+/// client's own), failing with `TimeoutError` when it runs out.
+async fn with_timeout(
+    timeout: Option<std::time::Duration>,
+    request: impl std::future::Future<Output = PyResult<PyObject>>,
 ) -> PyResult<PyObject> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, request)
+            .await
+            .unwrap_or_else(|_| {
+                Err(pyo3::exceptions::PyTimeoutError::new_err(
+                    "Request timed out",
+                ))
+            }),
+        None => request.await,
+    }
+}
+
+fn upstream_group(groups: &UpstreamGroups, name: &str) -> PyResult<Arc<UpstreamGroup>> {
+    groups.read().get(name).cloned().ok_or_else(|| {
+        pyo3::exceptions::PyValueError::new_err(format!("Unknown upstream group '{name}'"))
+    })
+}
+
+/// Build a request; bodies are `Bytes` so a retry resends them without copying.
+fn build_request(
+    client: &reqwest::Client,
+    method: Method,
+    url: &str,
+    headers: &Option<HashMap<String, String>>,
+    json_bytes: &Option<Bytes>,
+    content: &Option<Bytes>,
+) -> reqwest::RequestBuilder {
+    let mut builder = client.request(method, url);
     if let Some(hdrs) = headers {
         for (k, v) in hdrs {
             builder = builder.header(k.as_str(), v.as_str());
@@ -220,15 +382,94 @@ async fn dispatch(
     if let Some(bytes) = json_bytes {
         builder = builder
             .header("Content-Type", "application/json")
-            .body(bytes);
+            .body(bytes.clone());
     } else if let Some(body) = content {
-        builder = builder.body(body);
+        builder = builder.body(body.clone());
     }
+    builder
+}
 
-    let resp = builder
-        .send()
-        .await
-        .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))?;
+/// Send a request to `path` on an upstream picked from `group`, retrying on
+/// the next upstream when the connection fails.
+async fn send_to_group(
+    client: &reqwest::Client,
+    group: &UpstreamGroup,
+    method: Method,
+    path: &str,
+    headers: &Option<HashMap<String, String>>,
+    json_bytes: &Option<Bytes>,
+    content: &Option<Bytes>,
+) -> Result<reqwest::Response, String> {
+    let mut tried = Vec::new();
+    let mut last_error = format!("Upstream group '{}' has no upstreams", group.name());
+    while tried.len() <= group.retries() {
+        let Some(index) = group.select(&tried) else {
+            break;
+        };
+        let guard = group.acquire(index);
+        let url = guard.upstream().url_for(path);
+        let result = build_request(client, method.clone(), &url, headers, json_bytes, content)
+            .send()
+            .await;
+        drop(guard);
+
+        match result {
+            Ok(resp) => {
+                if resp.status().is_server_error() {
+                    group.report_failure(index);
+                } else {
+                    group.report_success(index);
+                }
+                return Ok(resp);
+            }
+            Err(e) => {
+                group.report_failure(index);
+                if !e.is_connect() {
+                    return Err(e.to_string());
+                }
+                last_error = e.to_string();
+                tried.push(index);
+            }
+        }
+    }
+    Err(last_error)
+}
+
+/// Build and fire the request, then collect the response into a `PyHttpResponse`.
+///
+/// The GIL is held only for the final `into_py` call; all network I/O runs
+/// without the GIL.
+async fn dispatch(
+    client: reqwest::Client,
+    groups: UpstreamGroups,
+    method: Method,
+    url: String,
+    headers: Option<HashMap<String, String>>,
+    json_bytes: Option<Vec<u8>>,
+    content: Option<Vec<u8>>,
+) -> PyResult<PyObject> {
+    let json_bytes = json_bytes.map(Bytes::from);
+    let content = content.map(Bytes::from);
+    let resp = match parse_upstream_url(&url) {
+        Some((name, path)) => {
+            let group = upstream_group(&groups, name)?;
+            send_to_group(
+                &client,
+                &group,
+                method,
+                path,
+                &headers,
+                &json_bytes,
+                &content,
+            )
+            .await
+        }
+        None => build_request(&client, method, &url, &headers, &json_bytes, &content)
+            .send()
+            .await
+            .map_err(|e| e.to_string()),
+    }
+    .map_err(pyo3::exceptions::PyRuntimeError::new_err)?;
 
     let status = resp.status().as_u16();
     let hdrs: HashMap<String, String> = resp
@@ -2,21 +2,327 @@
 //!
 //! Uses simd-json for fast JSON parsing and serialization,
 //! with serde_json as fallback.
+//!
+//! Conversions between Python objects and JSON values walk nested structures
+//! with an explicit stack instead of recursion, and enforce depth and size
+//! limits so adversarial payloads produce a `ConversionError` rather than
+//! overflowing the worker's stack.
+//!
+//! Besides dicts, lists and primitives, the walkers serialize common library
+//! types natively, in the manner of orjson: datetimes as ISO-8601 strings,
+//! UUIDs as strings, Decimals as strings or floats, enums by value, and
+//! dataclasses, Pydantic models and plain objects as dicts of their fields.
+
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use pyo3::prelude::*;
-use pyo3::types::{PyDict, PyList, PyTuple};
+use pyo3::sync::GILOnceCell;
+use pyo3::types::iter::PyDictIterator;
+use pyo3::types::{PyDict, PyFloat, PyList, PyModule, PyString, PyTuple, PyType};
+use thiserror::Error;
+
+/// Default maximum nesting depth for JSON conversions.
+pub const DEFAULT_MAX_JSON_DEPTH: usize = 256;
+
+/// Default maximum number of values (scalars and containers) in one conversion.
+pub const DEFAULT_MAX_JSON_ITEMS: usize = 10_000_000;
+
+// ============================================================================
+// Conversion Limits & Errors
+// ============================================================================
+
+/// Limits applied when converting between Python objects and JSON.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConversionLimits {
+    /// Maximum container nesting depth
+    pub max_depth: usize,
+    /// Maximum number of values visited
+    pub max_items: usize,
+}
+
+impl ConversionLimits {
+    /// Create new conversion limits.
+    pub fn new(max_depth: usize, max_items: usize) -> Self {
+        Self {
+            max_depth,
+            max_items,
+        }
+    }
+}
+
+impl Default for ConversionLimits {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_JSON_DEPTH, DEFAULT_MAX_JSON_ITEMS)
+    }
+}
+
+/// Structured error for JSON <-> Python conversions.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ConversionError {
+    #[error("JSON nesting depth exceeds limit of {limit}")]
+    DepthLimitExceeded { limit: usize },
+
+    #[error("JSON value count exceeds limit of {limit}")]
+    SizeLimitExceeded { limit: usize },
+
+    #[error("Dict keys must be strings")]
+    NonStringKey,
+
+    #[error("Cannot convert Python object to JSON: {0}")]
+    Unsupported(String),
+
+    #[error("Python error during conversion: {0}")]
+    Python(String),
+}
+
+impl From<ConversionError> for String {
+    fn from(err: ConversionError) -> Self {
+        err.to_string()
+    }
+}
+
+impl From<ConversionError> for PyErr {
+    fn from(err: ConversionError) -> Self {
+        pyo3::exceptions::PyValueError::new_err(err.to_string())
+    }
+}
+
+/// Tracks depth and item budgets during a single conversion.
+struct Budget {
+    limits: ConversionLimits,
+    items: usize,
+}
+
+impl Budget {
+    #[inline]
+    fn new(limits: ConversionLimits) -> Self {
+        Self { limits, items: 0 }
+    }
+
+    /// Account for one more value.
+    #[inline]
+    fn visit(&mut self) -> Result<(), ConversionError> {
+        self.items += 1;
+        if self.items > self.limits.max_items {
+            return Err(ConversionError::SizeLimitExceeded {
+                limit: self.limits.max_items,
+            });
+        }
+        Ok(())
+    }
+
+    /// Check that a container can be opened at the given stack depth.
+    #[inline]
+    fn enter(&self, depth: usize) -> Result<(), ConversionError> {
+        if depth >= self.limits.max_depth {
+            return Err(ConversionError::DepthLimitExceeded {
+                limit: self.limits.max_depth,
+            });
+        }
+        Ok(())
+    }
+}
+
+// ============================================================================
+// Native Types
+// ============================================================================
+
+/// How `decimal.Decimal` values are serialized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecimalMode {
+    /// As a string, keeping every digit (default)
+    String,
+    /// As a float, which may lose precision
+    Float,
+}
+
+impl FromStr for DecimalMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "str" | "string" => Ok(DecimalMode::String),
+            "float" => Ok(DecimalMode::Float),
+            _ => Err(format!(
+                "Unknown decimal mode '{s}'; expected 'str' or 'float'"
+            )),
+        }
+    }
+}
+
+/// Process-wide Decimal handling; serialization has no per-app context.
+static DECIMALS_AS_FLOAT: AtomicBool = AtomicBool::new(false);
+
+/// Set how `decimal.Decimal` values are serialized.
+pub fn set_decimal_mode(mode: DecimalMode) {
+    DECIMALS_AS_FLOAT.store(mode == DecimalMode::Float, Ordering::Relaxed);
+}
+
+/// How `decimal.Decimal` values are serialized.
+pub fn decimal_mode() -> DecimalMode {
+    if DECIMALS_AS_FLOAT.load(Ordering::Relaxed) {
+        DecimalMode::Float
+    } else {
+        DecimalMode::String
+    }
+}
+
+/// Standard library types and helpers used to recognise native values.
+struct NativeTypes {
+    date: PyObject,
+    time: PyObject,
+    uuid: PyObject,
+    decimal: PyObject,
+    enumeration: PyObject,
+    dataclass_fields: PyObject,
+}
+
+static NATIVE_TYPES: GILOnceCell<NativeTypes> = GILOnceCell::new();
+
+impl NativeTypes {
+    fn get(py: Python<'_>) -> PyResult<&NativeTypes> {
+        NATIVE_TYPES.get_or_try_init(py, || {
+            let datetime = py.import("datetime")?;
+            Ok(NativeTypes {
+                date: datetime.getattr("date")?.into(),
+                time: datetime.getattr("time")?.into(),
+                uuid: py.import("uuid")?.getattr("UUID")?.into(),
+                decimal: py.import("decimal")?.getattr("Decimal")?.into(),
+                enumeration: py.import("enum")?.getattr("Enum")?.into(),
+                dataclass_fields: py.import("dataclasses")?.getattr("fields")?.into(),
+            })
+        })
+    }
+}
+
+/// Convert a value the walkers cannot encode directly to a plain equivalent.
+///
+/// Returns a `str`, `float` or `dict` to encode in its place, or `None` if
+/// the type is not supported. This only runs after the scalar and container
+/// checks fail, so dicts, lists and primitives pay nothing for it.
+fn python_native(obj: &PyAny) -> Result<Option<&PyAny>, ConversionError> {
+    native_value(obj).map_err(|e| ConversionError::Python(e.to_string()))
+}
+
+/// `python_native`, reporting Python errors as `PyErr`.
+fn native_value(obj: &PyAny) -> PyResult<Option<&PyAny>> {
+    let py = obj.py();
+    if obj.is_instance_of::<PyType>() || obj.is_instance_of::<PyModule>() {
+        return Ok(None);
+    }
+    let types = NativeTypes::get(py)?;
+
+    // datetime.datetime is a subclass of datetime.date
+    if obj.is_instance(types.date.as_ref(py))? || obj.is_instance(types.time.as_ref(py))? {
+        return obj.call_method0("isoformat").map(Some);
+    }
+    if obj.is_instance(types.uuid.as_ref(py))? {
+        return Ok(Some(obj.str()?));
+    }
+    if obj.is_instance(types.decimal.as_ref(py))? {
+        return match decimal_mode() {
+            DecimalMode::String => Ok(Some(obj.str()?)),
+            DecimalMode::Float => Ok(Some(PyFloat::new(py, obj.extract::<f64>()?))),
+        };
+    }
+    if obj.is_instance(types.enumeration.as_ref(py))? {
+        return obj.getattr("value").map(Some);
+    }
+
+    let cls = obj.get_type();
+    if cls.hasattr("__dataclass_fields__")? {
+        let dict = PyDict::new(py);
+        for field in types.dataclass_fields.as_ref(py).call1((obj,))?.iter()? {
+            let name: &PyString = field?.getattr("name")?.downcast()?;
+            dict.set_item(name, obj.getattr(name)?)?;
+        }
+        return Ok(Some(dict));
+    }
+    // Pydantic v2, then v1
+    if cls.hasattr("model_dump")? {
+        return obj.call_method0("model_dump").map(Some);
+    }
+    if cls.hasattr("__fields__")? && cls.hasattr("dict")? {
+        return obj.call_method0("dict").map(Some);
+    }
+
+    // Numeric types such as numpy floats, and ints beyond 64 bits
+    if cls.hasattr("__float__")? {
+        return Ok(Some(PyFloat::new(py, obj.extract::<f64>()?)));
+    }
+
+    // Plain objects: their public attributes
+    if !obj.is_callable() {
+        if let Ok(attrs) = obj.getattr("__dict__") {
+            if let Ok(attrs) = attrs.downcast::<PyDict>() {
+                let dict = PyDict::new(py);
+                for (key, value) in attrs.iter() {
+                    if !key.extract::<&str>().is_ok_and(|k| k.starts_with('_')) {
+                        dict.set_item(key, value)?;
+                    }
+                }
+                return Ok(Some(dict));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+// ============================================================================
+// Parsing & Serialization
+// ============================================================================
+
+/// Check that raw JSON text does not nest deeper than `max_depth`.
+///
+/// This is a cheap byte scan (string-aware) run before handing input to the
+/// parser, so adversarial `[[[[...` bodies are rejected up front.
+pub fn check_json_depth(input: &[u8], max_depth: usize) -> Result<(), String> {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for &byte in input {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if byte == b'\\' {
+                escaped = true;
+            } else if byte == b'"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match byte {
+            b'"' => in_string = true,
+            b'[' | b'{' => {
+                depth += 1;
+                if depth > max_depth {
+                    return Err(format!(
+                        "JSON parse error: {}",
+                        ConversionError::DepthLimitExceeded { limit: max_depth }
+                    ));
+                }
+            }
+            b']' | b'}' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    Ok(())
+}
 
 /// Parse JSON string to serde_json::Value.
 /// Uses SIMD acceleration on x86_64 and aarch64 (NEON), falls back to serde_json
 /// on other architectures for maximum cross-platform compatibility.
 #[inline]
 pub fn parse_json(input: &str) -> Result<serde_json::Value, String> {
+    check_json_depth(input.as_bytes(), DEFAULT_MAX_JSON_DEPTH)?;
     #[cfg(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64"))]
     {
         // simd-json requires mutable input, so we need to copy
         let mut input_bytes = input.as_bytes().to_vec();
-        simd_json::serde::from_slice(&mut input_bytes)
-            .map_err(|e| format!("JSON parse error: {e}"))
+        simd_json::serde::from_slice(&mut input_bytes).map_err(|e| format!("JSON parse error: {e}"))
     }
     #[cfg(not(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64")))]
     {
@@ -28,6 +334,7 @@ pub fn parse_json(input: &str) -> Result<serde_json::Value, String> {
 /// Uses SIMD acceleration where available, serde_json fallback otherwise.
 #[inline]
 pub fn parse_json_bytes(input: &mut [u8]) -> Result<serde_json::Value, String> {
+    check_json_depth(input, DEFAULT_MAX_JSON_DEPTH)?;
     #[cfg(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64"))]
     {
         simd_json::serde::from_slice(input).map_err(|e| format!("JSON parse error: {e}"))
@@ -56,111 +363,283 @@ pub fn serialize_json_pretty(value: &serde_json::Value) -> Result<String, String
     serde_json::to_string_pretty(value).map_err(|e| format!("JSON serialize error: {e}"))
 }
 
-/// Convert a Python object to serde_json::Value.
+/// Read an integer from a JSON number or numeric string, as the binary
+/// codecs accept for 64-bit fields.
+pub(crate) fn json_i64(value: &serde_json::Value) -> Option<i64> {
+    match value {
+        serde_json::Value::Number(n) => n
+            .as_i64()
+            .or_else(|| n.as_f64().filter(|f| f.fract() == 0.0).map(|f| f as i64)),
+        serde_json::Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+// ============================================================================
+// Python -> JSON
+// ============================================================================
+
+/// Cursor over the children of a Python container being walked iteratively.
+enum PyCursor<'py> {
+    List { list: &'py PyList, index: usize },
+    Tuple { tuple: &'py PyTuple, index: usize },
+    Dict { iter: PyDictIterator<'py> },
+}
+
+impl<'py> PyCursor<'py> {
+    /// Open a cursor if the object is a list, dict, or tuple.
+    #[inline]
+    fn open(obj: &'py PyAny) -> Option<Self> {
+        if let Ok(list) = obj.downcast::<PyList>() {
+            return Some(PyCursor::List { list, index: 0 });
+        }
+        if let Ok(dict) = obj.downcast::<PyDict>() {
+            return Some(PyCursor::Dict { iter: dict.iter() });
+        }
+        if let Ok(tuple) = obj.downcast::<PyTuple>() {
+            return Some(PyCursor::Tuple { tuple, index: 0 });
+        }
+        None
+    }
+
+    #[inline]
+    fn is_object(&self) -> bool {
+        matches!(self, PyCursor::Dict { .. })
+    }
+
+    #[inline]
+    fn len_hint(&self) -> usize {
+        match self {
+            PyCursor::List { list, .. } => list.len(),
+            PyCursor::Tuple { tuple, .. } => tuple.len(),
+            PyCursor::Dict { iter } => iter.len(),
+        }
+    }
+
+    /// Advance to the next child, returning its key (for dicts) and value.
+    #[inline]
+    fn next_child(&mut self) -> Result<Option<(Option<String>, &'py PyAny)>, ConversionError> {
+        match self {
+            PyCursor::List { list, index } => {
+                if *index >= list.len() {
+                    return Ok(None);
+                }
+                let item = list
+                    .get_item(*index)
+                    .map_err(|e| ConversionError::Python(e.to_string()))?;
+                *index += 1;
+                Ok(Some((None, item)))
+            }
+            PyCursor::Tuple { tuple, index } => {
+                if *index >= tuple.len() {
+                    return Ok(None);
+                }
+                let item = tuple
+                    .get_item(*index)
+                    .map_err(|e| ConversionError::Python(e.to_string()))?;
+                *index += 1;
+                Ok(Some((None, item)))
+            }
+            PyCursor::Dict { iter } => match iter.next() {
+                Some((key, value)) => {
+                    let key = key
+                        .extract::<String>()
+                        .map_err(|_| ConversionError::NonStringKey)?;
+                    Ok(Some((Some(key), value)))
+                }
+                None => Ok(None),
+            },
+        }
+    }
+}
+
+/// Convert a Python scalar (None, bool, int, float, str) to a JSON value.
 #[inline]
-pub fn python_to_json(py: Python<'_>, obj: &PyAny) -> Result<serde_json::Value, String> {
+fn python_scalar_to_json(obj: &PyAny) -> Option<serde_json::Value> {
     // Handle None
     if obj.is_none() {
-        return Ok(serde_json::Value::Null);
+        return Some(serde_json::Value::Null);
     }
 
     // Handle bool (must come before int check since bool is subclass of int in Python)
     if let Ok(b) = obj.extract::<bool>() {
-        return Ok(serde_json::Value::Bool(b));
+        return Some(serde_json::Value::Bool(b));
     }
 
     // Handle int
     if let Ok(i) = obj.extract::<i64>() {
-        return Ok(serde_json::Value::Number(i.into()));
+        return Some(serde_json::Value::Number(i.into()));
     }
 
-    // Handle float
-    if let Ok(f) = obj.extract::<f64>() {
-        return Ok(serde_json::json!(f));
+    // Handle float (not via __float__, which would swallow Decimal)
+    if let Ok(f) = obj.downcast::<PyFloat>() {
+        return Some(serde_json::json!(f.value()));
     }
 
     // Handle string
     if let Ok(s) = obj.extract::<String>() {
-        return Ok(serde_json::Value::String(s));
+        return Some(serde_json::Value::String(s));
     }
 
-    // Handle list
-    if let Ok(list) = obj.downcast::<PyList>() {
-        // PERF: Pre-allocate vec with known capacity
-        let mut items = Vec::with_capacity(list.len());
-        for item in list.iter() {
-            items.push(python_to_json(py, item)?);
+    None
+}
+
+/// Convert a Cello `Response` object into its tagged JSON representation.
+fn python_response_to_json(obj: &PyAny) -> serde_json::Value {
+    let mut response_obj = serde_json::Map::new();
+    response_obj.insert(
+        "__cello_response__".to_string(),
+        serde_json::Value::Bool(true),
+    );
+
+    if let Ok(status) = obj.getattr("status") {
+        if let Ok(s) = status.extract::<u16>() {
+            response_obj.insert("status".to_string(), serde_json::Value::Number(s.into()));
         }
-        return Ok(serde_json::Value::Array(items));
-    }
-
-    // Handle dict
-    if let Ok(dict) = obj.downcast::<PyDict>() {
-        // PERF: Pre-allocate map with known capacity
-        let mut map = serde_json::Map::with_capacity(dict.len());
-        for (key, value) in dict.iter() {
-            let key_str = key
-                .extract::<String>()
-                .map_err(|_| "Dict keys must be strings".to_string())?;
-            let value_json = python_to_json(py, value)?;
-            map.insert(key_str, value_json);
+    }
+
+    if let Ok(headers) = obj.getattr("headers") {
+        if let Ok(dict) = headers.downcast::<PyDict>() {
+            let mut headers_map = serde_json::Map::new();
+            for (key, value) in dict.iter() {
+                if let (Ok(k), Ok(v)) = (key.extract::<String>(), value.extract::<String>()) {
+                    headers_map.insert(k, serde_json::Value::String(v));
+                }
+            }
+            response_obj.insert(
+                "headers".to_string(),
+                serde_json::Value::Object(headers_map),
+            );
         }
-        return Ok(serde_json::Value::Object(map));
     }
 
-    // Handle tuple
-    if let Ok(tuple) = obj.downcast::<PyTuple>() {
-        // PERF: Pre-allocate vec with known capacity
-        let mut items = Vec::with_capacity(tuple.len());
-        for item in tuple.iter() {
-            items.push(python_to_json(py, item)?);
+    // Get body - use body() which is Python-accessible
+    if let Ok(body_bytes) = obj.call_method0("body") {
+        if let Ok(bytes) = body_bytes.extract::<Vec<u8>>() {
+            match String::from_utf8(bytes) {
+                Ok(body_str) => {
+                    response_obj.insert("body".to_string(), serde_json::Value::String(body_str));
+                }
+                // Binary bodies (MessagePack, CBOR, images) survive as base64
+                Err(e) => {
+                    use base64::Engine;
+                    let encoded = base64::engine::general_purpose::STANDARD.encode(e.as_bytes());
+                    response_obj.insert(
+                        "body_base64".to_string(),
+                        serde_json::Value::String(encoded),
+                    );
+                }
+            }
         }
-        return Ok(serde_json::Value::Array(items));
     }
 
-    // Handle Response object - check by class name
-    let class_name = obj.get_type().name().unwrap_or("");
-    if class_name == "Response" {
-        let mut response_obj = serde_json::Map::new();
-        response_obj.insert(
-            "__cello_response__".to_string(),
-            serde_json::Value::Bool(true),
-        );
+    serde_json::Value::Object(response_obj)
+}
+
+/// Partially-built JSON container on the conversion stack.
+struct JsonFrame<'py> {
+    cursor: PyCursor<'py>,
+    key: Option<String>,
+    out: JsonPartial,
+}
 
-        if let Ok(status) = obj.getattr("status") {
-            if let Ok(s) = status.extract::<u16>() {
-                response_obj.insert("status".to_string(), serde_json::Value::Number(s.into()));
+enum JsonPartial {
+    Array(Vec<serde_json::Value>),
+    Object(serde_json::Map<String, serde_json::Value>),
+}
+
+impl JsonFrame<'_> {
+    #[inline]
+    fn push(&mut self, value: serde_json::Value) {
+        match &mut self.out {
+            JsonPartial::Array(items) => items.push(value),
+            JsonPartial::Object(map) => {
+                map.insert(self.key.take().unwrap_or_default(), value);
             }
         }
+    }
 
-        if let Ok(headers) = obj.getattr("headers") {
-            if let Ok(dict) = headers.downcast::<PyDict>() {
-                let mut headers_map = serde_json::Map::new();
-                for (key, value) in dict.iter() {
-                    if let (Ok(k), Ok(v)) = (key.extract::<String>(), value.extract::<String>()) {
-                        headers_map.insert(k, serde_json::Value::String(v));
-                    }
-                }
-                response_obj.insert(
-                    "headers".to_string(),
-                    serde_json::Value::Object(headers_map),
-                );
-            }
+    #[inline]
+    fn finish(self) -> serde_json::Value {
+        match self.out {
+            JsonPartial::Array(items) => serde_json::Value::Array(items),
+            JsonPartial::Object(map) => serde_json::Value::Object(map),
         }
+    }
+}
 
-        // Get body - use body() which is Python-accessible
-        if let Ok(body_bytes) = obj.call_method0("body") {
-            if let Ok(bytes) = body_bytes.extract::<Vec<u8>>() {
-                if let Ok(body_str) = String::from_utf8(bytes) {
-                    response_obj.insert("body".to_string(), serde_json::Value::String(body_str));
+/// Convert a Python object to serde_json::Value using the default limits.
+#[inline]
+pub fn python_to_json(py: Python<'_>, obj: &PyAny) -> Result<serde_json::Value, String> {
+    python_to_json_with_limits(py, obj, ConversionLimits::default()).map_err(String::from)
+}
+
+/// Convert a Python object to serde_json::Value with explicit limits.
+///
+/// Nested lists, tuples, and dicts are walked with an explicit stack, so
+/// conversion depth is bounded by `limits.max_depth` rather than the native
+/// stack size.
+pub fn python_to_json_with_limits(
+    _py: Python<'_>,
+    obj: &PyAny,
+    limits: ConversionLimits,
+) -> Result<serde_json::Value, ConversionError> {
+    let mut budget = Budget::new(limits);
+    let mut stack: Vec<JsonFrame<'_>> = Vec::new();
+    let mut current = obj;
+
+    loop {
+        budget.visit()?;
+
+        // Convert the current node, or open a new container frame for it.
+        let mut completed = if let Some(value) = python_scalar_to_json(current) {
+            Some(value)
+        } else if let Some(cursor) = PyCursor::open(current) {
+            budget.enter(stack.len())?;
+            // PERF: Pre-allocate with the container's known length
+            let out = if cursor.is_object() {
+                JsonPartial::Object(serde_json::Map::with_capacity(cursor.len_hint()))
+            } else {
+                JsonPartial::Array(Vec::with_capacity(cursor.len_hint()))
+            };
+            stack.push(JsonFrame {
+                cursor,
+                key: None,
+                out,
+            });
+            None
+        } else if current.get_type().name().unwrap_or("") == "Response" {
+            // Handle Response object - check by class name
+            Some(python_response_to_json(current))
+        } else if let Some(native) = python_native(current)? {
+            // Walk the plain equivalent in place of the original
+            current = native;
+            continue;
+        } else {
+            return Err(ConversionError::Unsupported(format!("{current:?}")));
+        };
+
+        // Hand finished values to their parents until a frame has more children.
+        loop {
+            let Some(top) = stack.last_mut() else {
+                return Ok(completed.unwrap_or(serde_json::Value::Null));
+            };
+            if let Some(value) = completed.take() {
+                top.push(value);
+            }
+            match top.cursor.next_child()? {
+                Some((key, child)) => {
+                    top.key = key;
+                    current = child;
+                    break;
+                }
+                None => {
+                    let frame = stack.pop().expect("stack is non-empty");
+                    completed = Some(frame.finish());
                 }
             }
         }
-
-        return Ok(serde_json::Value::Object(response_obj));
     }
-
-    Err(format!("Cannot convert Python object to JSON: {obj:?}"))
 }
 
 /// PERF: Convert a Python object directly to JSON bytes, skipping intermediate serde_json::Value.
@@ -172,111 +651,197 @@ pub fn python_to_json_bytes_direct(py: Python<'_>, obj: &PyAny) -> Result<Option
     // Most handlers return dicts, so fast-path that.
     if obj.downcast::<PyDict>().is_ok() || obj.downcast::<PyList>().is_ok() {
         let mut buf = Vec::with_capacity(128);
-        write_json_value(py, obj, &mut buf)?;
+        write_json_value(py, obj, &mut buf, ConversionLimits::default())?;
         return Ok(Some(buf));
     }
 
-    // Check primitives
-    if obj.is_none()
-        || obj.extract::<bool>().is_ok()
-        || obj.extract::<i64>().is_ok()
-        || obj.extract::<f64>().is_ok()
-        || obj.extract::<String>().is_ok()
-    {
-        let mut buf = Vec::with_capacity(64);
-        write_json_value(py, obj, &mut buf)?;
-        return Ok(Some(buf));
+    // Response objects fall back to the Value path
+    if obj.get_type().name().unwrap_or("") == "Response" {
+        return Ok(None);
     }
 
-    // Not a simple type - likely a Response object, fall back to Value path
-    Ok(None)
+    // Primitives and native types (datetimes, dataclasses, models)
+    let mut buf = Vec::with_capacity(64);
+    write_json_value(py, obj, &mut buf, ConversionLimits::default())?;
+    Ok(Some(buf))
 }
 
-/// Write a Python object as JSON directly to a byte buffer.
-fn write_json_value(py: Python<'_>, obj: &PyAny, buf: &mut Vec<u8>) -> Result<(), String> {
+/// Convert a Python object to JSON bytes without checking its type first.
+///
+/// For routes declared to return JSON: the object goes straight to the
+/// walker, which rejects anything it cannot encode.
+#[inline]
+pub fn python_to_json_bytes_unchecked(py: Python<'_>, obj: &PyAny) -> Result<Vec<u8>, String> {
+    let mut buf = Vec::with_capacity(128);
+    write_json_value(py, obj, &mut buf, ConversionLimits::default())?;
+    Ok(buf)
+}
+
+/// Append a Python object as JSON to `buf`.
+#[inline]
+pub(crate) fn write_json(py: Python<'_>, obj: &PyAny, buf: &mut Vec<u8>) -> Result<(), String> {
+    write_json_value(py, obj, buf, ConversionLimits::default()).map_err(String::from)
+}
+
+/// Output format for the direct Python-to-bytes walker.
+///
+/// The walker owns traversal, depth and size limits; implementors only encode
+/// scalars and container framing. JSON lives here, binary formats in `codec`.
+pub(crate) trait ValueWriter {
+    /// Write a scalar. Returns false if the object is not a scalar.
+    fn scalar(&mut self, obj: &PyAny, buf: &mut Vec<u8>) -> Result<bool, ConversionError>;
+
+    /// Open a container with `len` children.
+    fn begin(&mut self, is_object: bool, len: usize, buf: &mut Vec<u8>);
+
+    /// Prepare for the next child, writing its key for objects.
+    fn child(&mut self, first: bool, key: Option<&str>, buf: &mut Vec<u8>);
+
+    /// Close a container.
+    fn end(&mut self, is_object: bool, buf: &mut Vec<u8>);
+}
+
+/// JSON text output for the direct walker.
+struct JsonWriter;
+
+impl ValueWriter for JsonWriter {
+    #[inline]
+    fn scalar(&mut self, obj: &PyAny, buf: &mut Vec<u8>) -> Result<bool, ConversionError> {
+        write_json_scalar(obj, buf)
+    }
+
+    #[inline]
+    fn begin(&mut self, is_object: bool, _len: usize, buf: &mut Vec<u8>) {
+        buf.push(if is_object { b'{' } else { b'[' });
+    }
+
+    #[inline]
+    fn child(&mut self, first: bool, key: Option<&str>, buf: &mut Vec<u8>) {
+        if !first {
+            buf.push(b',');
+        }
+        if let Some(key) = key {
+            write_json_string(key, buf);
+            buf.push(b':');
+        }
+    }
+
+    #[inline]
+    fn end(&mut self, is_object: bool, buf: &mut Vec<u8>) {
+        buf.push(if is_object { b'}' } else { b']' });
+    }
+}
+
+/// Write a Python scalar as JSON. Returns false if the object is not a scalar.
+#[inline]
+fn write_json_scalar(obj: &PyAny, buf: &mut Vec<u8>) -> Result<bool, ConversionError> {
     use std::io::Write;
 
     // Handle None
     if obj.is_none() {
         buf.extend_from_slice(b"null");
-        return Ok(());
+        return Ok(true);
     }
 
     // Handle bool (must come before int check since bool is subclass of int in Python)
     if let Ok(b) = obj.extract::<bool>() {
         buf.extend_from_slice(if b { b"true" } else { b"false" });
-        return Ok(());
+        return Ok(true);
     }
 
     // Handle int
     if let Ok(i) = obj.extract::<i64>() {
-        write!(buf, "{i}").map_err(|e| e.to_string())?;
-        return Ok(());
+        write!(buf, "{i}").map_err(|e| ConversionError::Python(e.to_string()))?;
+        return Ok(true);
     }
 
-    // Handle float
-    if let Ok(f) = obj.extract::<f64>() {
+    // Handle float (not via __float__, which would swallow Decimal)
+    if let Ok(f) = obj.downcast::<PyFloat>() {
+        let f = f.value();
         if f.is_finite() {
-            write!(buf, "{f}").map_err(|e| e.to_string())?;
+            write!(buf, "{f}").map_err(|e| ConversionError::Python(e.to_string()))?;
         } else {
             buf.extend_from_slice(b"null");
         }
-        return Ok(());
+        return Ok(true);
     }
 
     // Handle string - need to JSON-escape
     if let Ok(s) = obj.extract::<String>() {
         write_json_string(&s, buf);
-        return Ok(());
+        return Ok(true);
     }
 
-    // Handle list
-    if let Ok(list) = obj.downcast::<PyList>() {
-        buf.push(b'[');
-        for (i, item) in list.iter().enumerate() {
-            if i > 0 {
-                buf.push(b',');
-            }
-            write_json_value(py, item, buf)?;
-        }
-        buf.push(b']');
-        return Ok(());
-    }
-
-    // Handle dict
-    if let Ok(dict) = obj.downcast::<PyDict>() {
-        buf.push(b'{');
-        let mut first = true;
-        for (key, value) in dict.iter() {
-            let key_str = key
-                .extract::<String>()
-                .map_err(|_| "Dict keys must be strings".to_string())?;
-            if !first {
-                buf.push(b',');
-            }
-            first = false;
-            write_json_string(&key_str, buf);
-            buf.push(b':');
-            write_json_value(py, value, buf)?;
+    Ok(false)
+}
+
+/// Container being written on the direct-serialization stack.
+struct WriteFrame<'py> {
+    cursor: PyCursor<'py>,
+    first: bool,
+}
+
+/// Write a Python object as JSON directly to a byte buffer.
+#[inline]
+fn write_json_value(
+    _py: Python<'_>,
+    obj: &PyAny,
+    buf: &mut Vec<u8>,
+    limits: ConversionLimits,
+) -> Result<(), ConversionError> {
+    write_python_value(obj, &mut JsonWriter, buf, limits)
+}
+
+/// Walk a Python object iteratively, encoding it with `writer`.
+pub(crate) fn write_python_value<W: ValueWriter>(
+    obj: &PyAny,
+    writer: &mut W,
+    buf: &mut Vec<u8>,
+    limits: ConversionLimits,
+) -> Result<(), ConversionError> {
+    let mut budget = Budget::new(limits);
+    let mut stack: Vec<WriteFrame<'_>> = Vec::new();
+    let mut current = obj;
+
+    loop {
+        budget.visit()?;
+
+        if !writer.scalar(current, buf)? {
+            let Some(cursor) = PyCursor::open(current) else {
+                // Write the plain equivalent in place of the original
+                match python_native(current)? {
+                    Some(native) => current = native,
+                    None => return Err(ConversionError::Unsupported(format!("{current:?}"))),
+                }
+                continue;
+            };
+            budget.enter(stack.len())?;
+            writer.begin(cursor.is_object(), cursor.len_hint(), buf);
+            stack.push(WriteFrame {
+                cursor,
+                first: true,
+            });
         }
-        buf.push(b'}');
-        return Ok(());
-    }
 
-    // Handle tuple
-    if let Ok(tuple) = obj.downcast::<PyTuple>() {
-        buf.push(b'[');
-        for (i, item) in tuple.iter().enumerate() {
-            if i > 0 {
-                buf.push(b',');
+        // Close finished containers until one has another child to write.
+        loop {
+            let Some(top) = stack.last_mut() else {
+                return Ok(());
+            };
+            match top.cursor.next_child()? {
+                Some((key, child)) => {
+                    writer.child(top.first, key.as_deref(), buf);
+                    top.first = false;
+                    current = child;
+                    break;
+                }
+                None => {
+                    let frame = stack.pop().expect("stack is non-empty");
+                    writer.end(frame.cursor.is_object(), buf);
+                }
             }
-            write_json_value(py, item, buf)?;
         }
-        buf.push(b']');
-        return Ok(());
     }
-
-    Err(format!("Cannot convert Python object to JSON: {obj:?}"))
 }
 
 /// Write a JSON-escaped string to the buffer.
@@ -312,43 +877,132 @@ fn write_json_string(s: &str, buf: &mut Vec<u8>) {
     buf.push(b'"');
 }
 
-/// Convert a serde_json::Value to a Python object.
+// ============================================================================
+// JSON -> Python
+// ============================================================================
+
+/// Convert a JSON scalar to a Python object (containers return None).
 #[inline]
-pub fn json_to_python(py: Python<'_>, value: &serde_json::Value) -> PyResult<PyObject> {
+fn json_scalar_to_python(py: Python<'_>, value: &serde_json::Value) -> Option<PyObject> {
     match value {
-        serde_json::Value::Null => Ok(py.None()),
-        serde_json::Value::Bool(b) => Ok(b.into_py(py)),
+        serde_json::Value::Null => Some(py.None()),
+        serde_json::Value::Bool(b) => Some(b.into_py(py)),
         serde_json::Value::Number(n) => {
             if let Some(i) = n.as_i64() {
-                Ok(i.into_py(py))
+                Some(i.into_py(py))
             } else if let Some(f) = n.as_f64() {
-                Ok(f.into_py(py))
+                Some(f.into_py(py))
             } else {
-                Ok(py.None())
+                Some(py.None())
             }
         }
-        serde_json::Value::String(s) => Ok(s.into_py(py)),
+        serde_json::Value::String(s) => Some(s.into_py(py)),
+        serde_json::Value::Array(_) | serde_json::Value::Object(_) => None,
+    }
+}
+
+/// Python container being filled on the conversion stack.
+enum PyFrame<'v, 'py> {
+    List(&'py PyList, std::slice::Iter<'v, serde_json::Value>),
+    Dict(&'py PyDict, serde_json::map::Iter<'v>),
+}
+
+/// Convert a serde_json::Value to a Python object using the default limits.
+#[inline]
+pub fn json_to_python(py: Python<'_>, value: &serde_json::Value) -> PyResult<PyObject> {
+    json_to_python_with_limits(py, value, ConversionLimits::default())
+}
+
+/// Convert a serde_json::Value to a Python object with explicit limits.
+///
+/// Child containers are attached to their parent as soon as they are created
+/// and then filled from an explicit stack, so no recursion is needed.
+pub fn json_to_python_with_limits(
+    py: Python<'_>,
+    value: &serde_json::Value,
+    limits: ConversionLimits,
+) -> PyResult<PyObject> {
+    let mut budget = Budget::new(limits);
+    budget.visit()?;
+
+    if let Some(obj) = json_scalar_to_python(py, value) {
+        return Ok(obj);
+    }
+
+    let (root, frame): (PyObject, PyFrame<'_, '_>) = match value {
         serde_json::Value::Array(arr) => {
             let list = PyList::empty(py);
-            for item in arr {
-                list.append(json_to_python(py, item)?)?;
-            }
-            Ok(list.into_py(py))
+            (list.into_py(py), PyFrame::List(list, arr.iter()))
         }
         serde_json::Value::Object(obj) => {
             let dict = PyDict::new(py);
-            for (key, val) in obj {
-                dict.set_item(key, json_to_python(py, val)?)?;
-            }
-            Ok(dict.into_py(py))
+            (dict.into_py(py), PyFrame::Dict(dict, obj.iter()))
+        }
+        _ => unreachable!("scalars handled above"),
+    };
+
+    let mut stack = vec![frame];
+    while let Some(top) = stack.last_mut() {
+        let (parent_list, parent_dict, key, child) = match top {
+            PyFrame::List(list, iter) => match iter.next() {
+                Some(child) => (Some(*list), None, None, child),
+                None => {
+                    stack.pop();
+                    continue;
+                }
+            },
+            PyFrame::Dict(dict, iter) => match iter.next() {
+                Some((key, child)) => (None, Some(*dict), Some(key), child),
+                None => {
+                    stack.pop();
+                    continue;
+                }
+            },
+        };
+
+        budget.visit()?;
+        let (child_obj, child_frame): (PyObject, Option<PyFrame<'_, '_>>) =
+            match json_scalar_to_python(py, child) {
+                Some(obj) => (obj, None),
+                None => {
+                    budget.enter(stack.len())?;
+                    match child {
+                        serde_json::Value::Array(arr) => {
+                            let list = PyList::empty(py);
+                            (list.into_py(py), Some(PyFrame::List(list, arr.iter())))
+                        }
+                        serde_json::Value::Object(obj) => {
+                            let dict = PyDict::new(py);
+                            (dict.into_py(py), Some(PyFrame::Dict(dict, obj.iter())))
+                        }
+                        _ => unreachable!("scalars handled above"),
+                    }
+                }
+            };
+
+        if let Some(list) = parent_list {
+            list.append(child_obj)?;
+        } else if let (Some(dict), Some(key)) = (parent_dict, key) {
+            dict.set_item(key, child_obj)?;
+        }
+
+        if let Some(frame) = child_frame {
+            stack.push(frame);
         }
     }
+
+    Ok(root)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn with_py<F: FnOnce(Python<'_>)>(f: F) {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(f);
+    }
+
     #[test]
     fn test_parse_json() {
         let result = parse_json(r#"{"name": "test", "value": 42}"#);
@@ -380,4 +1034,140 @@ mod tests {
         assert!(json_str.contains("hello"));
         assert!(json_str.contains("10"));
     }
+
+    #[test]
+    fn test_check_json_depth() {
+        assert!(check_json_depth(br#"{"a": [1, {"b": 2}]}"#, 3).is_ok());
+        assert!(check_json_depth(br#"{"a": [1, {"b": 2}]}"#, 2).is_err());
+        // Brackets inside strings are ignored, including escaped quotes
+        assert!(check_json_depth(br#"["[[[[\"[[[["]"#, 1).is_ok());
+
+        let deep = "[".repeat(100_000);
+        assert!(parse_json(&deep).is_err());
+    }
+
+    #[test]
+    fn test_python_json_roundtrip() {
+        with_py(|py| {
+            let value = serde_json::json!({
+                "name": "cello",
+                "tags": ["fast", "rust"],
+                "nested": {"ok": true, "n": 1.5, "none": null}
+            });
+            let obj = json_to_python(py, &value).unwrap();
+            let back = python_to_json(py, obj.as_ref(py)).unwrap();
+            assert_eq!(back, value);
+
+            let bytes = python_to_json_bytes_direct(py, obj.as_ref(py))
+                .unwrap()
+                .unwrap();
+            let parsed: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(parsed, value);
+        });
+    }
+
+    #[test]
+    fn test_python_to_json_depth_limit() {
+        with_py(|py| {
+            // Build a list nested far deeper than the native stack could recurse
+            let root = PyList::empty(py);
+            let mut current = root;
+            for _ in 0..100_000 {
+                let child = PyList::empty(py);
+                current.append(child).unwrap();
+                current = child;
+            }
+
+            let err =
+                python_to_json_with_limits(py, root, ConversionLimits::default()).unwrap_err();
+            assert_eq!(
+                err,
+                ConversionError::DepthLimitExceeded {
+                    limit: DEFAULT_MAX_JSON_DEPTH
+                }
+            );
+            assert!(python_to_json_bytes_direct(py, root).is_err());
+        });
+    }
+
+    #[test]
+    fn test_conversion_size_limit() {
+        with_py(|py| {
+            let value = serde_json::json!([1, 2, 3, 4, 5]);
+            let limits = ConversionLimits::new(8, 3);
+            assert!(json_to_python_with_limits(py, &value, limits).is_err());
+
+            let obj = json_to_python(py, &value).unwrap();
+            let err = python_to_json_with_limits(py, obj.as_ref(py), limits).unwrap_err();
+            assert_eq!(err, ConversionError::SizeLimitExceeded { limit: 3 });
+        });
+    }
+
+    #[test]
+    fn test_json_to_python_depth_limit() {
+        with_py(|py| {
+            let mut value = serde_json::json!(1);
+            for _ in 0..10 {
+                value = serde_json::json!([value]);
+            }
+            assert!(json_to_python_with_limits(py, &value, ConversionLimits::new(5, 100)).is_err());
+            assert!(json_to_python_with_limits(py, &value, ConversionLimits::new(11, 100)).is_ok());
+        });
+    }
+
+    #[test]
+    fn test_native_types() {
+        with_py(|py| {
+            let locals = PyDict::new(py);
+            py.run(
+                r#"
+import dataclasses, datetime, decimal, enum, uuid
+
+class Color(enum.Enum):
+    RED = "red"
+
+@dataclasses.dataclass
+class Point:
+    x: int
+    y: int
+
+class Plain:
+    def __init__(self):
+        self.name = "plain"
+        self._hidden = 1
+
+value = {
+    "at": datetime.datetime(2024, 5, 1, 12, 30, tzinfo=datetime.timezone.utc),
+    "day": datetime.date(2024, 5, 1),
+    "id": uuid.UUID("12345678-1234-5678-1234-567812345678"),
+    "price": decimal.Decimal("19.990"),
+    "color": Color.RED,
+    "points": [Point(1, 2)],
+    "plain": Plain(),
+}
+"#,
+                None,
+                Some(locals),
+            )
+            .unwrap();
+            let value = locals.get_item("value").unwrap().unwrap();
+            let expected = serde_json::json!({
+                "at": "2024-05-01T12:30:00+00:00",
+                "day": "2024-05-01",
+                "id": "12345678-1234-5678-1234-567812345678",
+                "price": "19.990",
+                "color": "red",
+                "points": [{"x": 1, "y": 2}],
+                "plain": {"name": "plain"},
+            });
+
+            assert_eq!(python_to_json(py, value).unwrap(), expected);
+            let bytes = python_to_json_bytes_direct(py, value).unwrap().unwrap();
+            let parsed: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(parsed, expected);
+
+            let function = py.eval("len", None, None).unwrap();
+            assert!(python_to_json_bytes_direct(py, function).is_err());
+        });
+    }
 }
@@ -24,20 +24,28 @@
 // Core modules
 pub mod arena;
 pub mod blueprint;
+pub mod codec;
 pub mod handler;
 pub mod json;
 pub mod multipart;
+pub mod negotiation;
 pub mod router;
 pub mod sse;
+pub mod validation;
 pub mod websocket;
 
 // Enterprise modules (available for direct use)
 pub mod context;
+pub mod debug_page;
 pub mod dependency;
 pub mod dto;
 pub mod error;
+pub mod error_reporting;
+pub mod event_loop;
 pub mod lifecycle;
 pub mod middleware;
+pub mod profiling;
+pub mod redaction;
 pub mod request;
 pub mod response;
 pub mod routing;
@@ -46,14 +54,20 @@ pub mod timeout;
 
 // New v0.5.0 modules
 pub mod background;
+pub mod config;
 pub mod openapi;
+pub mod reload;
+pub mod scheduler;
 pub mod template;
+pub mod wsgi;
 
 // v1.1.0 - MiniJinja template engine
 pub mod minijinja_engine;
 
 // Rust-native async HTTP client
 pub mod http_client;
+pub mod upstream;
+pub mod webhooks;
 
 use pyo3::prelude::*;
 use std::sync::Arc;
@@ -65,7 +79,6 @@ use server::Server;
 use sse::{SseEvent, SseStream};
 use websocket::{WebSocket, WebSocketMessage, WebSocketRegistry};
 
-
 /// The main Cello application class exposed to Python.
 ///
 /// This class manages routes, middleware, and starts the HTTP server.
@@ -78,9 +91,48 @@ pub struct Cello {
     dependency_container: Arc<dependency::DependencyContainer>,
     guards: Arc<middleware::guards::GuardsMiddleware>,
     prometheus: Arc<parking_lot::RwLock<Option<middleware::prometheus::PrometheusMiddleware>>>,
+    statsd: Option<Arc<middleware::statsd::StatsdClient>>,
     cache_store: Arc<parking_lot::RwLock<Option<Arc<dyn middleware::cache::CacheStore>>>>,
-    startup_handlers: Vec<PyObject>,
-    shutdown_handlers: Vec<PyObject>,
+    cache: Option<Arc<middleware::memory_cache::MemoryCache<PyObject>>>,
+    tenants: Option<Arc<middleware::tenant::TenantRegistry>>,
+    ip_filter: Option<Arc<middleware::ip_filter::IpFilter>>,
+    throttle: Option<server::throttle::ThrottleConfig>,
+    slow_request_threshold: Option<std::time::Duration>,
+    server_timing: bool,
+    json_etags: bool,
+    query_array_syntax: request::QueryArraySyntax,
+    response_checks: Option<server::integrity::ResponseChecks>,
+    stats: server::stats::StatsSources,
+    stats_endpoint: Option<String>,
+    recorder: Option<Arc<server::recording::Recorder>>,
+    method_override: Option<server::method_override::MethodOverride>,
+    server_name: Option<String>,
+    redirect_http: Option<server::redirect::RedirectConfig>,
+    acme: Option<server::acme::AcmeConfig>,
+    connections: Arc<server::connections::ConnectionMetrics>,
+    database: Option<Arc<middleware::sql::SqlPool>>,
+    #[cfg(feature = "mongodb")]
+    mongo: Option<Arc<middleware::mongo::MongoClient>>,
+    object_store: Option<Arc<middleware::object_store::ObjectStore>>,
+    redis_client: Option<Arc<dyn middleware::redis::RedisClient>>,
+    distributed_locks: Option<Arc<middleware::distributed_lock::DistributedLock>>,
+    idempotency: Option<middleware::idempotency::IdempotencyMiddleware>,
+    mirror_stats: Option<Arc<middleware::mirror::MirrorStats>>,
+    fault_injection: Option<middleware::fault_injection::FaultInjectionMiddleware>,
+    route_toggles: routing::ToggleLog,
+    route_middleware: middleware::RouteMiddlewareRegistry,
+    lifecycle: lifecycle::ServerLifecycle,
+    scheduler: Arc<scheduler::Scheduler>,
+    reloader: Option<Arc<reload::Reloader>>,
+    openapi: openapi::OpenAPIGenerator,
+    proto_descriptors: middleware::protobuf::SharedDescriptorPool,
+    grpc_server: Option<Arc<middleware::grpc::GrpcServer>>,
+    saga_orchestrator: Option<Arc<middleware::saga::SagaOrchestrator>>,
+    event_store: Option<Arc<dyn middleware::eventsourcing::EventStore>>,
+    event_encryption: Option<Arc<middleware::eventsourcing::EventEncryption>>,
+    command_bus: Option<Arc<middleware::cqrs::CommandBus>>,
+    query_bus: Option<Arc<middleware::cqrs::QueryBus>>,
+    webhooks: Option<Arc<webhooks::WebhookDispatcher>>,
 }
 
 #[pymethods]
@@ -88,17 +140,59 @@ impl Cello {
     /// Create a new Cello application instance.
     #[new]
     pub fn new() -> Self {
+        let router = Router::new();
         Cello {
-            router: Router::new(),
+            openapi: openapi::OpenAPIGenerator::new("Cello API", "1.0.1").router(router.clone()),
+            router,
             handlers: HandlerRegistry::new(),
             middleware: middleware::MiddlewareChain::new(),
             websocket_handlers: WebSocketRegistry::new(),
             dependency_container: Arc::new(dependency::DependencyContainer::new()),
             guards: Arc::new(middleware::guards::GuardsMiddleware::new()),
             prometheus: Arc::new(parking_lot::RwLock::new(None)),
+            statsd: None,
             cache_store: Arc::new(parking_lot::RwLock::new(None)),
-            startup_handlers: Vec::new(),
-            shutdown_handlers: Vec::new(),
+            cache: None,
+            tenants: None,
+            ip_filter: None,
+            throttle: None,
+            slow_request_threshold: None,
+            server_timing: false,
+            json_etags: false,
+            query_array_syntax: request::QueryArraySyntax::default(),
+            response_checks: None,
+            stats: server::stats::StatsSources::new(),
+            stats_endpoint: None,
+            recorder: None,
+            method_override: None,
+            server_name: None,
+            redirect_http: None,
+            acme: None,
+            connections: Arc::new(server::connections::ConnectionMetrics::new()),
+            database: None,
+            #[cfg(feature = "mongodb")]
+            mongo: None,
+            object_store: None,
+            redis_client: None,
+            distributed_locks: None,
+            idempotency: None,
+            mirror_stats: None,
+            fault_injection: None,
+            route_toggles: routing::ToggleLog::new(),
+            route_middleware: middleware::RouteMiddlewareRegistry::new(),
+            lifecycle: lifecycle::ServerLifecycle::new(),
+            scheduler: Arc::new(scheduler::Scheduler::new()),
+            reloader: None,
+            proto_descriptors: Arc::new(parking_lot::RwLock::new(
+                middleware::protobuf::DescriptorPool::new(),
+            )),
+            grpc_server: None,
+            saga_orchestrator: None,
+            event_store: None,
+            event_encryption: None,
+            command_bus: None,
+            query_bus: None,
+            webhooks: None,
         }
     }
 
@@ -143,6 +237,121 @@ impl Cello {
         Ok(())
     }
 
+    /// Configure WebSocket heartbeats and limits for new connections.
+    ///
+    /// Pings are sent every `ping_interval` seconds and the connection is
+    /// closed if a pong does not follow within `pong_timeout`; connections
+    /// silent for `idle_timeout` seconds, or sending a message larger than
+    /// `max_message_size` bytes, are closed too.
+    ///
+    /// With `compression`, permessage-deflate is accepted from clients that
+    /// offer it; messages under `compression_threshold` bytes are sent
+    /// uncompressed.
+    ///
+    /// When the principal's credentials expire, `on_token_expiry="close"`
+    /// closes the connection after `token_expiry_grace` seconds unless the
+    /// handler refreshed them; `"ignore"` leaves it open.
+    #[pyo3(signature = (
+        ping_interval=None,
+        pong_timeout=10.0,
+        idle_timeout=None,
+        max_message_size=None,
+        compression=false,
+        compression_threshold=256,
+        server_max_window_bits=15,
+        client_max_window_bits=15,
+        on_token_expiry="close",
+        token_expiry_grace=0.0,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn configure_websockets(
+        &mut self,
+        ping_interval: Option<f64>,
+        pong_timeout: f64,
+        idle_timeout: Option<f64>,
+        max_message_size: Option<usize>,
+        compression: bool,
+        compression_threshold: usize,
+        server_max_window_bits: u8,
+        client_max_window_bits: u8,
+        on_token_expiry: &str,
+        token_expiry_grace: f64,
+    ) -> PyResult<()> {
+        let policy = websocket::ExpiryPolicy::parse(on_token_expiry).ok_or_else(|| {
+            pyo3::exceptions::PyValueError::new_err(format!(
+                "on_token_expiry must be 'close' or 'ignore', got {on_token_expiry:?}"
+            ))
+        })?;
+        let mut config = websocket::WebSocketConfig::default()
+            .with_pong_timeout(seconds_arg("pong_timeout", pong_timeout)?)
+            .with_expiry_policy(policy)
+            .with_expiry_grace(seconds_arg("token_expiry_grace", token_expiry_grace)?);
+        if let Some(interval) = ping_interval {
+            config = config.with_ping_interval(seconds_arg("ping_interval", interval)?);
+        }
+        if let Some(idle) = idle_timeout {
+            config = config.with_idle_timeout(seconds_arg("idle_timeout", idle)?);
+        }
+        if let Some(max) = max_message_size {
+            config = config.with_max_message_size(max);
+        }
+        if compression {
+            for (name, bits) in [
+                ("server_max_window_bits", server_max_window_bits),
+                ("client_max_window_bits", client_max_window_bits),
+            ] {
+                if !(9..=15).contains(&bits) {
+                    return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                        "{name} must be between 9 and 15"
+                    )));
+                }
+            }
+            config = config.with_deflate(
+                websocket::deflate::DeflateConfig::default()
+                    .with_window_bits(server_max_window_bits, client_max_window_bits)
+                    .with_threshold(compression_threshold),
+            );
+        }
+        self.websocket_handlers.set_config(config);
+        Ok(())
+    }
+
+    /// Liveness of each open WebSocket connection.
+    pub fn websocket_connections(&self, py: Python<'_>) -> PyResult<Vec<PyObject>> {
+        self.websocket_handlers
+            .open_connections()
+            .iter()
+            .map(|peer| Ok(peer.liveness.to_dict(py, true)?.into()))
+            .collect()
+    }
+
+    /// Serve a WSGI application under a path prefix.
+    ///
+    /// The app answers requests below `prefix` that no route matches, on a
+    /// thread pool running at most `workers` calls at once.
+    #[pyo3(signature = (prefix, app, workers=wsgi::DEFAULT_WORKERS))]
+    pub fn mount_wsgi(
+        &mut self,
+        py: Python<'_>,
+        prefix: &str,
+        app: PyObject,
+        workers: usize,
+    ) -> PyResult<()> {
+        if !prefix.starts_with('/') {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "Mount prefix must start with '/'",
+            ));
+        }
+        if !app.as_ref(py).is_callable() {
+            return Err(pyo3::exceptions::PyTypeError::new_err(
+                "WSGI app must be callable",
+            ));
+        }
+        self.router
+            .mount(Arc::new(wsgi::WsgiMount::new(prefix, app, workers)));
+        Ok(())
+    }
+
     /// Register a blueprint.
     pub fn register_blueprint(&mut self, blueprint: &Blueprint) -> PyResult<()> {
         let routes = blueprint.get_all_routes();
@@ -183,11 +392,99 @@ impl Cello {
 
         let mw = middleware::prometheus::PrometheusMiddleware::with_config(config)
             .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))?;
+        if let Some(filter) = &self.ip_filter {
+            filter
+                .register_metrics(&mw.metrics().registry)
+                .map_err(pyo3::exceptions::PyRuntimeError::new_err)?;
+        }
+        self.connections
+            .register_metrics(&mw.metrics().registry)
+            .map_err(pyo3::exceptions::PyRuntimeError::new_err)?;
 
         *self.prometheus.write() = Some(mw);
         Ok(())
     }
 
+    /// Push request metrics to a StatsD agent over UDP, every
+    /// `flush_interval` seconds. With `dogstatsd`, metrics carry `tags`
+    /// and the request's `method`, `status` and `path`.
+    #[pyo3(signature = (host="127.0.0.1", port=8125, prefix="cello", tags=None, dogstatsd=false, flush_interval=10.0, max_packet_size=1432, exclude_paths=None))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn enable_statsd(
+        &mut self,
+        host: &str,
+        port: u16,
+        prefix: &str,
+        tags: Option<std::collections::BTreeMap<String, String>>,
+        dogstatsd: bool,
+        flush_interval: f64,
+        max_packet_size: usize,
+        exclude_paths: Option<Vec<String>>,
+    ) -> PyResult<()> {
+        let mut config = middleware::statsd::StatsdConfig::new(host, port)
+            .prefix(prefix)
+            .dogstatsd(dogstatsd)
+            .flush_interval(seconds_arg("flush_interval", flush_interval)?)
+            .max_packet_size(max_packet_size);
+        for (key, value) in tags.unwrap_or_default() {
+            config = config.tag(&key, &value);
+        }
+        for path in exclude_paths.unwrap_or_default() {
+            config = config.exclude_path(&path);
+        }
+        let client = middleware::statsd::StatsdClient::new(config)
+            .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))?;
+        self.middleware
+            .add(middleware::statsd::StatsdMiddleware::new(client.clone()));
+        self.statsd = Some(client);
+        Ok(())
+    }
+
+    /// Add `value` to a StatsD counter.
+    #[pyo3(signature = (name, value=1, tags=None))]
+    pub fn statsd_increment(
+        &self,
+        name: &str,
+        value: i64,
+        tags: Option<std::collections::BTreeMap<String, String>>,
+    ) -> PyResult<()> {
+        let tags = tags.unwrap_or_default();
+        self.statsd()?.increment(name, value, &tag_refs(&tags));
+        Ok(())
+    }
+
+    /// Record a StatsD timer, in milliseconds.
+    #[pyo3(signature = (name, millis, tags=None))]
+    pub fn statsd_timing(
+        &self,
+        name: &str,
+        millis: f64,
+        tags: Option<std::collections::BTreeMap<String, String>>,
+    ) -> PyResult<()> {
+        let tags = tags.unwrap_or_default();
+        self.statsd()?.timing(name, millis, &tag_refs(&tags));
+        Ok(())
+    }
+
+    /// Set a StatsD gauge.
+    #[pyo3(signature = (name, value, tags=None))]
+    pub fn statsd_gauge(
+        &self,
+        name: &str,
+        value: f64,
+        tags: Option<std::collections::BTreeMap<String, String>>,
+    ) -> PyResult<()> {
+        let tags = tags.unwrap_or_default();
+        self.statsd()?.gauge(name, value, &tag_refs(&tags));
+        Ok(())
+    }
+
+    /// Send the buffered StatsD metrics now, e.g. before exiting.
+    pub fn statsd_flush(&self) -> PyResult<()> {
+        self.statsd()?.flush();
+        Ok(())
+    }
+
     /// Enable rate limiting.
     #[pyo3(signature = (config))]
     pub fn enable_rate_limit(&mut self, config: PyRateLimitConfig) -> PyResult<()> {
@@ -225,25 +522,661 @@ impl Cello {
             }
         };
 
+        let mw = if config.key_by == "tenant" {
+            mw.key_extractor(middleware::rate_limit::KeyExtractors::tenant())
+        } else {
+            mw
+        };
+
+        self.middleware.add(mw);
+        Ok(())
+    }
+
+    /// Enable IP allow/deny filtering.
+    ///
+    /// Denied ranges are always rejected; when `allow` is given, only its
+    /// ranges are admitted. `X-Forwarded-For` is only trusted from peers in
+    /// `trusted_proxies`. The lists can be changed at runtime through
+    /// `ip_filter()`.
+    #[pyo3(signature = (allow=None, deny=None, trusted_proxies=None, exclude_paths=None, status=403))]
+    pub fn enable_ip_filter(
+        &mut self,
+        allow: Option<Vec<String>>,
+        deny: Option<Vec<String>>,
+        trusted_proxies: Option<Vec<String>>,
+        exclude_paths: Option<Vec<String>>,
+        status: u16,
+    ) -> PyResult<()> {
+        use middleware::ip_filter::{IpFilter, IpFilterMiddleware};
+
+        let filter = IpFilter::new(
+            &allow.unwrap_or_default(),
+            &deny.unwrap_or_default(),
+            &trusted_proxies.unwrap_or_default(),
+        )
+        .map_err(pyo3::exceptions::PyValueError::new_err)?;
+        let filter = Arc::new(filter);
+        if let Some(prometheus) = self.prometheus.read().as_ref() {
+            filter
+                .register_metrics(&prometheus.metrics().registry)
+                .map_err(pyo3::exceptions::PyRuntimeError::new_err)?;
+        }
+
+        let mut mw = IpFilterMiddleware::new(filter.clone()).status(status);
+        for path in exclude_paths.unwrap_or_default() {
+            mw = mw.exclude(&path);
+        }
         self.middleware.add(mw);
+        self.ip_filter = Some(filter);
+        Ok(())
+    }
+
+    /// The IP filter created by `enable_ip_filter()`.
+    pub fn ip_filter(&self) -> PyResult<middleware::ip_filter::PyIpFilter> {
+        let filter = self.ip_filter.clone().ok_or_else(|| {
+            pyo3::exceptions::PyRuntimeError::new_err(
+                "IP filter is not enabled; call enable_ip_filter() first",
+            )
+        })?;
+        Ok(middleware::ip_filter::PyIpFilter::new(filter))
+    }
+
+    /// Enable bandwidth throttling and slow-client protection.
+    ///
+    /// `header_timeout` bounds how long a client may take to send the request
+    /// head; `min_body_rate` (bytes/sec, enforced after `body_grace` seconds)
+    /// rejects slow uploads with 408. Bandwidth limits are bytes/sec in each
+    /// direction, per connection or shared by all connections of a client IP.
+    #[pyo3(signature = (header_timeout=None, min_body_rate=None, body_grace=5.0, connection_bandwidth=None, client_bandwidth=None, max_connections_per_client=None))]
+    pub fn enable_throttling(
+        &mut self,
+        header_timeout: Option<f64>,
+        min_body_rate: Option<u64>,
+        body_grace: f64,
+        connection_bandwidth: Option<u64>,
+        client_bandwidth: Option<u64>,
+        max_connections_per_client: Option<usize>,
+    ) -> PyResult<()> {
+        let seconds = |name: &str, value: f64| {
+            std::time::Duration::try_from_secs_f64(value).map_err(|_| {
+                pyo3::exceptions::PyValueError::new_err(format!(
+                    "{name} must be a non-negative number of seconds"
+                ))
+            })
+        };
+
+        let mut config = server::throttle::ThrottleConfig::new();
+        if let Some(timeout) = header_timeout {
+            config = config.header_read_timeout(seconds("header_timeout", timeout)?);
+        }
+        if let Some(rate) = min_body_rate {
+            config = config.min_body_rate(rate, seconds("body_grace", body_grace)?);
+        }
+        if let Some(rate) = connection_bandwidth {
+            config = config.connection_bandwidth(rate);
+        }
+        if let Some(rate) = client_bandwidth {
+            config = config.client_bandwidth(rate);
+        }
+        if let Some(max) = max_connections_per_client {
+            config = config.max_connections_per_client(max);
+        }
+        config
+            .validate()
+            .map_err(pyo3::exceptions::PyValueError::new_err)?;
+        self.throttle = Some(config);
+        Ok(())
+    }
+
+    /// Log requests slower than `threshold` seconds with the time spent in
+    /// each phase of handling them.
+    #[pyo3(signature = (threshold=1.0))]
+    pub fn enable_slow_request_log(&mut self, threshold: f64) -> PyResult<()> {
+        let threshold = std::time::Duration::try_from_secs_f64(threshold).map_err(|_| {
+            pyo3::exceptions::PyValueError::new_err(
+                "threshold must be a non-negative number of seconds",
+            )
+        })?;
+        self.slow_request_threshold = Some(threshold);
+        Ok(())
+    }
+
+    /// Write `tracing` events, including `request.logger` records, to
+    /// stderr as JSON lines (text with `json=False`). `level` takes a level
+    /// name or `RUST_LOG`-style directives such as
+    /// `"warn,cello.request=info"`. Returns False when a subscriber was
+    /// already installed in this process.
+    #[pyo3(signature = (level="info", json=true))]
+    pub fn enable_structured_logging(&self, level: &str, json: bool) -> PyResult<bool> {
+        let filter = tracing_subscriber::EnvFilter::try_new(level)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        let subscriber = tracing_subscriber::fmt()
+            .with_env_filter(filter)
+            .with_writer(std::io::stderr);
+        let installed = if json {
+            subscriber.json().try_init().is_ok()
+        } else {
+            subscriber.try_init().is_ok()
+        };
+        Ok(installed)
+    }
+
+    /// Send each request's phase times to the client in a `Server-Timing`
+    /// header.
+    pub fn enable_server_timing(&mut self) {
+        self.server_timing = true;
+    }
+
+    /// Tag JSON responses to GET and HEAD requests with a weak ETag of
+    /// their body (an XXH3 hash), and answer `304 Not Modified` without the
+    /// body when the request's `If-None-Match` already has it.
+    pub fn enable_json_etags(&mut self) {
+        self.json_etags = true;
+    }
+
+    /// Set how repeated query parameters are written, for
+    /// `request.query_list()`: `"brackets"` (the default) reads both
+    /// `tag=a&tag=b` and `tag[]=a&tag[]=b` as `tag`, `"repeat"` keeps
+    /// `tag[]` as a name of its own.
+    pub fn set_query_array_syntax(&mut self, syntax: &str) -> PyResult<()> {
+        self.query_array_syntax = request::QueryArraySyntax::parse(syntax).ok_or_else(|| {
+            pyo3::exceptions::PyValueError::new_err(format!(
+                "Unknown query array syntax '{syntax}'; use 'brackets' or 'repeat'"
+            ))
+        })?;
+        Ok(())
+    }
+
+    /// Check buffered responses as they are sent: set `Content-Length` from
+    /// the body, add a `digest` (`"md5"` for `Content-MD5`, `"sha-256"` for
+    /// `Digest`) and answer 500 instead of sending a body over
+    /// `max_body_size` bytes.
+    #[pyo3(signature = (content_length=true, digest=None, max_body_size=None))]
+    pub fn enable_response_checks(
+        &mut self,
+        content_length: bool,
+        digest: Option<&str>,
+        max_body_size: Option<usize>,
+    ) -> PyResult<()> {
+        let mut checks = server::integrity::ResponseChecks::new().content_length(content_length);
+        if let Some(digest) = digest {
+            let algorithm = server::integrity::DigestAlgorithm::parse(digest).ok_or_else(|| {
+                pyo3::exceptions::PyValueError::new_err(format!(
+                    "Unknown digest '{digest}'; use 'md5' or 'sha-256'"
+                ))
+            })?;
+            checks = checks.digest(algorithm);
+        }
+        if let Some(bytes) = max_body_size {
+            checks = checks.max_body_size(bytes);
+        }
+        self.response_checks = Some(checks);
+        Ok(())
+    }
+
+    /// Serve the statistics of the server and every enabled component as
+    /// one JSON object at `endpoint`, gzip-compressed for clients that
+    /// accept it.
+    #[pyo3(signature = (endpoint="/stats"))]
+    pub fn enable_stats(&mut self, endpoint: &str) {
+        self.stats_endpoint = Some(endpoint.to_string());
+    }
+
+    /// The statistics of the server and every enabled component, keyed by
+    /// component. They are collected without holding the GIL.
+    pub fn stats_snapshot(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let stats = self.stats.clone();
+        let snapshot = py.allow_threads(move || stats.snapshot());
+        json::json_to_python(py, &snapshot)
+    }
+
+    /// Record a `sample_rate` fraction of requests with the response each
+    /// got, keeping the last `per_route` pairs of every route. Bodies are
+    /// truncated to `max_body_size` bytes and the redaction rules apply.
+    /// Paths starting with one of `exclude_paths` are never recorded.
+    #[pyo3(signature = (sample_rate=1.0, max_body_size=4096, per_route=20, exclude_paths=None))]
+    pub fn enable_recording(
+        &mut self,
+        sample_rate: f64,
+        max_body_size: usize,
+        per_route: usize,
+        exclude_paths: Option<Vec<String>>,
+    ) -> PyResult<()> {
+        let mut config = server::recording::RecordingConfig::default()
+            .sample_rate(sample_rate)
+            .map_err(pyo3::exceptions::PyValueError::new_err)?
+            .max_body_size(max_body_size)
+            .per_route(per_route);
+        for prefix in exclude_paths.iter().flatten() {
+            config = config.exclude_path(prefix);
+        }
+        let redaction = self.handlers.error_handlers().redaction().clone();
+        self.recorder = Some(Arc::new(server::recording::Recorder::new(
+            config, redaction,
+        )));
+        Ok(())
+    }
+
+    /// The recorded pairs of `route` (`"GET /users/{id}"`), or of every
+    /// route, oldest first.
+    #[pyo3(signature = (route=None))]
+    pub fn recordings(&self, py: Python<'_>, route: Option<&str>) -> PyResult<PyObject> {
+        let recordings = self.recorder()?.recordings(route);
+        let value = serde_json::to_value(recordings)
+            .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))?;
+        json::json_to_python(py, &value)
+    }
+
+    /// The routes with recordings, mapped to how many each has.
+    pub fn recording_routes(&self) -> PyResult<std::collections::HashMap<String, usize>> {
+        Ok(self.recorder()?.routes().into_iter().collect())
+    }
+
+    /// Drop every recording.
+    pub fn clear_recordings(&self) -> PyResult<()> {
+        self.recorder()?.clear();
+        Ok(())
+    }
+
+    /// Let POST requests ask for one of `methods` (PUT, PATCH and DELETE by
+    /// default) in the `header` request header or the `form_field` field
+    /// of a urlencoded form. Either source can be disabled with `None`.
+    #[pyo3(signature = (methods=None, header=Some("X-HTTP-Method-Override"), form_field=Some("_method")))]
+    pub fn enable_method_override(
+        &mut self,
+        methods: Option<Vec<String>>,
+        header: Option<&str>,
+        form_field: Option<&str>,
+    ) -> PyResult<()> {
+        let mut overrides = server::method_override::MethodOverride::new()
+            .header(header)
+            .form_field(form_field);
+        if let Some(methods) = methods {
+            let methods: Vec<&str> = methods.iter().map(String::as_str).collect();
+            overrides = overrides
+                .methods(&methods)
+                .map_err(pyo3::exceptions::PyValueError::new_err)?;
+        }
+        self.method_override = Some(overrides);
+        Ok(())
+    }
+
+    /// Redirect plain HTTP requests on `port` to the HTTPS server, which
+    /// then sends a `Strict-Transport-Security` header with `hsts_max_age`
+    /// (seconds; `0` disables it).
+    ///
+    /// `https_port` is the port in redirect URLs, by default the port the
+    /// server runs on.
+    #[pyo3(signature = (port=80, https_port=None, hsts_max_age=31536000, hsts_include_subdomains=false))]
+    pub fn enable_https_redirect(
+        &mut self,
+        port: u16,
+        https_port: Option<u16>,
+        hsts_max_age: u64,
+        hsts_include_subdomains: bool,
+    ) {
+        let mut config = server::redirect::RedirectConfig::new(port);
+        if let Some(https_port) = https_port {
+            config = config.https_port(https_port);
+        }
+        config = if hsts_max_age == 0 {
+            config.no_hsts()
+        } else {
+            let hsts = middleware::security::HstsConfig::new(hsts_max_age);
+            config.hsts(if hsts_include_subdomains {
+                hsts.include_subdomains()
+            } else {
+                hsts
+            })
+        };
+        self.redirect_http = Some(config);
+    }
+
+    /// Serve HTTPS with certificates obtained and renewed automatically
+    /// from an ACME CA (Let's Encrypt by default).
+    ///
+    /// `challenge` is `"http-01"` (answered on `http_port`) or
+    /// `"tls-alpn-01"` (answered by the HTTPS listener itself). Keys and
+    /// certificates are cached in `cache_dir`; certificates are renewed
+    /// `renew_before_days` before they expire.
+    #[pyo3(signature = (domains, email=None, directory=None, challenge="http-01", cache_dir=".cello/acme", http_port=80, renew_before_days=30))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn enable_acme(
+        &mut self,
+        domains: Vec<String>,
+        email: Option<&str>,
+        directory: Option<&str>,
+        challenge: &str,
+        cache_dir: &str,
+        http_port: u16,
+        renew_before_days: u64,
+    ) -> PyResult<()> {
+        let challenge = server::acme::ChallengeType::parse(challenge).ok_or_else(|| {
+            pyo3::exceptions::PyValueError::new_err(format!(
+                "Unsupported ACME challenge {challenge:?}; use \"http-01\" or \"tls-alpn-01\""
+            ))
+        })?;
+        let mut config = server::acme::AcmeConfig::new(domains)
+            .directory(directory.unwrap_or(server::acme::LETS_ENCRYPT))
+            .challenge(challenge)
+            .cache_dir(cache_dir)
+            .http_port(http_port)
+            .renew_before(std::time::Duration::from_secs(renew_before_days * 86400));
+        if let Some(email) = email {
+            config = config.email(email);
+        }
+        config
+            .validate()
+            .map_err(pyo3::exceptions::PyValueError::new_err)?;
+
+        println!("🔒 ACME certificates enabled:");
+        println!("   Domains: {}", config.domains.join(", "));
+        println!("   Directory: {}", config.directory_url);
+        println!("   Challenge: {}", config.challenge.as_str());
+        self.acme = Some(config);
+        Ok(())
+    }
+
+    /// Add a guard checked before route handlers.
+    ///
+    /// A `require()` guard is evaluated natively; other guards are Python
+    /// callables. With `prefix`, the guard only runs for paths under it.
+    #[pyo3(signature = (guard, prefix=None))]
+    pub fn add_guard(
+        &mut self,
+        py: Python<'_>,
+        guard: PyObject,
+        prefix: Option<&str>,
+    ) -> PyResult<()> {
+        use middleware::guards::{Guard, PyRequire, PythonGuard, ScopedGuard};
+
+        let native = guard
+            .extract::<PyRef<'_, PyRequire>>(py)
+            .map(|require| require.guard().clone());
+        let guard: Arc<dyn Guard> = match native {
+            Ok(native) => Arc::new(native),
+            Err(_) => Arc::new(PythonGuard::new(guard)),
+        };
+        match prefix {
+            Some(prefix) => self.guards.add_guard(ScopedGuard::new(guard, prefix)),
+            None => self.guards.add_shared_guard(guard),
+        }
+        Ok(())
+    }
+
+    /// Register an error handler for an HTTP status code or an exception type.
+    ///
+    /// Exception handlers receive the exception raised by a route handler,
+    /// matched by the closest class in its MRO. Status handlers receive the
+    /// `ProblemDetails` of an error response built by the framework, such as
+    /// 404 for unknown routes or 500 for unhandled exceptions.
+    pub fn add_exception_handler(
+        &mut self,
+        py: Python<'_>,
+        key: &PyAny,
+        handler: PyObject,
+    ) -> PyResult<()> {
+        if !handler.as_ref(py).is_callable() {
+            return Err(pyo3::exceptions::PyTypeError::new_err(
+                "Exception handler must be callable",
+            ));
+        }
+        let error_handlers = self.handlers.error_handlers();
+        if let Ok(exception_type) = key.downcast::<pyo3::types::PyType>() {
+            if !exception_type.is_subclass_of::<pyo3::exceptions::PyBaseException>()? {
+                return Err(pyo3::exceptions::PyTypeError::new_err(
+                    "Exception handler type must be an exception class",
+                ));
+            }
+            error_handlers.set_exception_handler(py, exception_type, handler);
+            return Ok(());
+        }
+        match key.extract::<u16>() {
+            Ok(status) if (400..600).contains(&status) => {
+                error_handlers.set_status_handler(status, handler);
+                Ok(())
+            }
+            Ok(status) => Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "Status {status} is not an error status"
+            ))),
+            Err(_) => Err(pyo3::exceptions::PyTypeError::new_err(
+                "exception_handler() takes a status code or an exception class",
+            )),
+        }
+    }
+
+    /// Send the framework's error responses as RFC 7807 problem details.
+    ///
+    /// 404, 405, 422, 500 and middleware or guard rejections become
+    /// `application/problem+json` with the request path as `instance` and
+    /// its correlation ID. With `debug`, unhandled exceptions include their
+    /// traceback.
+    #[pyo3(signature = (debug=false))]
+    pub fn enable_problem_details(&mut self, debug: bool) {
+        let error_handlers = self.handlers.error_handlers();
+        error_handlers.set_debug(debug);
+        error_handlers.set_problem_details(true);
+    }
+
+    /// Answer unhandled exceptions with a development error page showing the
+    /// traceback, request and route (JSON when the client accepts JSON).
+    pub fn enable_debug_pages(&mut self) {
+        self.handlers.error_handlers().set_debug_pages(true);
+    }
+
+    /// Redact secrets and personal data from the access log, error reports
+    /// and debug pages.
+    ///
+    /// `headers` are replaced whole (the default list when omitted),
+    /// `fields` are JSON field paths or bare field names, and matches of
+    /// the regexes in `patterns` are replaced in any text.
+    #[pyo3(signature = (headers=None, fields=None, patterns=None, replacement="[REDACTED]"))]
+    pub fn enable_redaction(
+        &mut self,
+        headers: Option<Vec<String>>,
+        fields: Option<Vec<String>>,
+        patterns: Option<Vec<String>>,
+        replacement: &str,
+    ) -> PyResult<()> {
+        let mut rules = redaction::RedactionRules::new().replacement(replacement);
+        let headers = headers.unwrap_or_else(|| {
+            redaction::DEFAULT_HEADERS
+                .iter()
+                .map(|h| h.to_string())
+                .collect()
+        });
+        for header in &headers {
+            rules = rules.header(header);
+        }
+        for field in fields.iter().flatten() {
+            rules = rules.field(field);
+        }
+        for pattern in patterns.iter().flatten() {
+            rules = rules.pattern(pattern).map_err(|e| {
+                pyo3::exceptions::PyValueError::new_err(format!(
+                    "Invalid redaction pattern '{pattern}': {e}"
+                ))
+            })?;
+        }
+        self.handlers.error_handlers().redaction().set(rules);
         Ok(())
     }
 
-    pub fn add_guard(&mut self, guard: PyObject) -> PyResult<()> {
-        let python_guard = middleware::guards::PythonGuard::new(guard);
-        self.guards.add_guard(python_guard);
+    /// `value` with the redaction rules applied: a string has patterns
+    /// redacted, a dict or list has fields and patterns redacted.
+    pub fn redact(&self, py: Python<'_>, value: &PyAny) -> PyResult<PyObject> {
+        let Some(rules) = self.handlers.error_handlers().redaction().rules() else {
+            return Ok(value.into_py(py));
+        };
+        if let Ok(text) = value.extract::<&str>() {
+            return Ok(rules.text(text).into_py(py));
+        }
+        let mut json =
+            json::python_to_json(py, value).map_err(pyo3::exceptions::PyTypeError::new_err)?;
+        rules.json(&mut json);
+        json::json_to_python(py, &json)
+    }
+
+    /// Report unhandled exceptions, server errors and panics to Sentry.
+    ///
+    /// `before_send` gets each event dict after scrubbing and returns it,
+    /// changed or not, or `None` to drop it.
+    #[pyo3(signature = (config, before_send=None))]
+    pub fn enable_sentry(
+        &mut self,
+        py: Python<'_>,
+        config: PySentryConfig,
+        before_send: Option<PyObject>,
+    ) -> PyResult<()> {
+        if let Some(before_send) = &before_send {
+            if !before_send.as_ref(py).is_callable() {
+                return Err(pyo3::exceptions::PyTypeError::new_err(
+                    "before_send must be callable",
+                ));
+            }
+        }
+        let mut sentry_config = error_reporting::SentryConfig::new(&config.dsn)
+            .and_then(|c| c.sample_rate(config.sample_rate))
+            .map_err(pyo3::exceptions::PyValueError::new_err)?
+            .timeout(std::time::Duration::from_secs_f64(config.timeout_secs))
+            .max_pending(config.max_pending);
+        if let Some(environment) = &config.environment {
+            sentry_config = sentry_config.environment(environment);
+        }
+        if let Some(release) = &config.release {
+            sentry_config = sentry_config.release(release);
+        }
+        if let Some(server_name) = &config.server_name {
+            sentry_config = sentry_config.server_name(server_name);
+        }
+        if let Some(headers) = config.send_headers {
+            sentry_config = sentry_config.send_headers(headers);
+        }
+        if let Some(fields) = config.scrub_fields {
+            sentry_config = sentry_config.scrub_fields(fields);
+        }
+
+        let redaction = self.handlers.error_handlers().redaction().clone();
+        let reporter = Arc::new(
+            error_reporting::ErrorReporter::new(
+                sentry_config,
+                before_send,
+                pyo3_asyncio::tokio::get_runtime().handle().clone(),
+            )
+            .with_redaction(redaction),
+        );
+        let stats = reporter.clone();
+        self.stats.register("sentry", move || stats.stats());
+        self.handlers.error_handlers().set_reporter(reporter);
         Ok(())
     }
 
+    /// Report an exception caught by the app, with `request`'s context.
+    /// Returns the event ID, or `None` if the event was not sent.
+    #[pyo3(signature = (exception, request=None))]
+    pub fn capture_exception(
+        &self,
+        py: Python<'_>,
+        exception: &PyAny,
+        request: Option<request::Request>,
+    ) -> PyResult<Option<String>> {
+        let reporter = self.handlers.error_handlers().reporter().ok_or_else(|| {
+            pyo3::exceptions::PyRuntimeError::new_err(
+                "Sentry is not enabled; call enable_sentry() first",
+            )
+        })?;
+        if !exception.is_instance_of::<pyo3::exceptions::PyBaseException>() {
+            return Err(pyo3::exceptions::PyTypeError::new_err(
+                "capture_exception() takes an exception",
+            ));
+        }
+        let err = PyErr::from_value(exception);
+        Ok(reporter.capture_exception(py, &err, request.as_ref(), None))
+    }
+
+    /// Wait up to `timeout` seconds for reported errors to be sent.
+    /// Returns whether every event was sent or failed in time.
+    #[pyo3(signature = (timeout=2.0))]
+    pub fn flush_errors(&self, py: Python<'_>, timeout: f64) -> bool {
+        match self.handlers.error_handlers().reporter() {
+            Some(reporter) => {
+                py.allow_threads(|| reporter.flush(std::time::Duration::from_secs_f64(timeout)))
+            }
+            None => true,
+        }
+    }
+
     /// Register a singleton dependency.
     pub fn register_singleton(&mut self, name: String, value: PyObject) {
         self.dependency_container
             .register_py_singleton(&name, value);
+        self.handlers.set_has_dependencies(true);
+    }
+
+    /// Register a factory dependency.
+    ///
+    /// The factory's own `Depends(...)` parameters are resolved before it is
+    /// called, and a parameter named `request` receives the request. `scope`
+    /// is "request" (once per request), "singleton" (once per app) or
+    /// "transient" (every time it is needed).
+    #[pyo3(signature = (name, factory, scope="request"))]
+    pub fn register_dependency(
+        &mut self,
+        py: Python<'_>,
+        name: String,
+        factory: PyObject,
+        scope: &str,
+    ) -> PyResult<()> {
+        if !factory.as_ref(py).is_callable() {
+            return Err(pyo3::exceptions::PyTypeError::new_err(
+                "Dependency factory must be callable",
+            ));
+        }
+        let scope = match scope {
+            "request" => dependency::DependencyScope::Request,
+            "singleton" => dependency::DependencyScope::Singleton,
+            "transient" => dependency::DependencyScope::Transient,
+            other => {
+                return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                    "Unknown dependency scope '{other}' (expected request, singleton or transient)"
+                )))
+            }
+        };
+        let factory = dependency::PyFactory::new(py, factory, scope)?;
+        self.dependency_container
+            .register_py_factory(&name, factory);
+        self.handlers.set_has_dependencies(true);
+        Ok(())
+    }
+
+    /// Replace a dependency, typically in tests.
+    ///
+    /// Factories are replaced by another callable; singletons by a value.
+    pub fn override_dependency(
+        &mut self,
+        py: Python<'_>,
+        name: String,
+        value: PyObject,
+    ) -> PyResult<()> {
+        self.dependency_container.override_py(py, &name, value)?;
+        self.handlers.set_has_dependencies(true);
+        Ok(())
+    }
+
+    /// Remove the override for `name`, or all overrides when omitted.
+    #[pyo3(signature = (name=None))]
+    pub fn clear_dependency_override(&mut self, name: Option<String>) {
+        match name {
+            Some(name) => self.dependency_container.clear_py_override(&name),
+            None => self.dependency_container.clear_py_overrides(),
+        }
     }
 
     /// Enable logging middleware.
     pub fn enable_logging(&mut self) {
-        self.middleware.add(middleware::LoggingMiddleware::new());
+        let redaction = self.handlers.error_handlers().redaction().clone();
+        self.middleware
+            .add(middleware::LoggingMiddleware::new().with_redaction(redaction));
     }
 
     /// Enable compression middleware.
@@ -256,6 +1189,19 @@ impl Cello {
         self.middleware.add(compression);
     }
 
+    /// Configure JSON serialization of handler results.
+    ///
+    /// `decimal` is `"str"` (lossless, the default) or `"float"`. The
+    /// setting applies to the whole process.
+    #[pyo3(signature = (decimal="str"))]
+    pub fn configure_json(&self, decimal: &str) -> PyResult<()> {
+        let mode: json::DecimalMode = decimal
+            .parse()
+            .map_err(pyo3::exceptions::PyValueError::new_err)?;
+        json::set_decimal_mode(mode);
+        Ok(())
+    }
+
     /// Enable caching middleware.
     #[pyo3(signature = (ttl=300, methods=None, exclude_paths=None))]
     pub fn enable_caching(
@@ -272,6 +1218,11 @@ impl Cello {
         if let Some(e) = exclude_paths {
             config.exclude_paths = e;
         }
+        if let Some(cache) = &self.cache {
+            config.store = Arc::new(middleware::memory_cache::MemoryCache::<
+                middleware::cache::CachedResponse,
+            >::new(cache.config().clone()));
+        }
 
         let mw = middleware::cache::CacheMiddleware::with_config(config.clone());
 
@@ -281,35 +1232,633 @@ impl Cello {
         self.middleware.add_async(mw);
     }
 
-    /// Enable circuit breaker middleware.
-    #[pyo3(signature = (failure_threshold=5, reset_timeout=30, half_open_target=3, failure_codes=None))]
-    pub fn enable_circuit_breaker(
+    /// Enable the in-memory cache, available as `cache()` and the `"cache"`
+    /// dependency. Response caching enabled afterwards uses the same bounds
+    /// and policy. `max_memory` is in bytes and `ttl` in seconds.
+    #[pyo3(signature = (max_entries=10000, max_memory=None, policy="lru", ttl=None, shards=16))]
+    pub fn enable_cache(
         &mut self,
-        failure_threshold: u32,
-        reset_timeout: u64,
-        half_open_target: u32,
-        failure_codes: Option<Vec<u16>>,
-    ) {
-        let mut config = middleware::circuit_breaker::CircuitBreakerConfig::default();
-        config.failure_threshold = failure_threshold;
-        config.reset_timeout = std::time::Duration::from_secs(reset_timeout);
-        config.half_open_target = half_open_target;
-        if let Some(codes) = failure_codes {
-            config.failure_codes = codes;
+        py: Python<'_>,
+        max_entries: usize,
+        max_memory: Option<usize>,
+        policy: &str,
+        ttl: Option<f64>,
+        shards: usize,
+    ) -> PyResult<()> {
+        use middleware::memory_cache::{EvictionPolicy, MemoryCache, MemoryCacheConfig, PyCache};
+        if max_entries == 0 {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "max_entries must be at least 1",
+            ));
         }
+        let policy: EvictionPolicy = policy
+            .parse()
+            .map_err(pyo3::exceptions::PyValueError::new_err)?;
+        let mut config = MemoryCacheConfig::new()
+            .max_entries(max_entries)
+            .policy(policy)
+            .shards(shards);
+        if let Some(bytes) = max_memory {
+            config = config.max_memory(bytes);
+        }
+        if let Some(ttl) = ttl {
+            config = config.default_ttl(seconds_arg("ttl", ttl)?);
+        }
+        let cache = Arc::new(MemoryCache::new(config));
+        self.dependency_container
+            .register_py_singleton("cache", PyCache::new(cache.clone()).into_py(py));
+        self.handlers.set_has_dependencies(true);
+        self.cache = Some(cache);
+        Ok(())
+    }
+
+    /// The in-memory cache created by `enable_cache()`.
+    pub fn cache(&self) -> PyResult<middleware::memory_cache::PyCache> {
+        let cache = self.cache.clone().ok_or_else(|| {
+            pyo3::exceptions::PyRuntimeError::new_err(
+                "Cache is not enabled; call enable_cache() first",
+            )
+        })?;
+        Ok(middleware::memory_cache::PyCache::new(cache))
+    }
+
+    /// Enable multi-tenancy.
+    ///
+    /// Each request's tenant is resolved from `resolve` (any of
+    /// `"subdomain"`, `"header"` and `"path"`, tried in order) and exposed
+    /// as `request.tenant` and the `"tenant"` dependency. A registered
+    /// tenant's database pool replaces `"database"`, and `"cache"` is the
+    /// app cache with keys prefixed by tenant. Call `enable_cache()` first
+    /// for the per-tenant cache.
+    #[pyo3(signature = (resolve=None, header="X-Tenant-ID", path_param="tenant", base_domain=None, required=true, allow_unknown=false, exclude_paths=None))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn enable_tenancy(
+        &mut self,
+        py: Python<'_>,
+        resolve: Option<Vec<String>>,
+        header: &str,
+        path_param: &str,
+        base_domain: Option<&str>,
+        required: bool,
+        allow_unknown: bool,
+        exclude_paths: Option<Vec<String>>,
+    ) -> PyResult<()> {
+        use middleware::tenant::{
+            TenancyConfig, TenantMiddleware, TenantRegistry, TenantScope, TenantSource,
+        };
+        let resolve = resolve.unwrap_or_else(|| vec!["subdomain".into(), "header".into()]);
+        let sources = resolve
+            .iter()
+            .map(|source| match source.parse() {
+                Ok(TenantSource::Header(_)) => Ok(TenantSource::Header(header.to_lowercase())),
+                Ok(TenantSource::PathParam(_)) => Ok(TenantSource::PathParam(path_param.into())),
+                other => other.map_err(pyo3::exceptions::PyValueError::new_err),
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+        let mut config = TenancyConfig::new()
+            .sources(sources)
+            .required(required)
+            .allow_unknown(allow_unknown);
+        if let Some(domain) = base_domain {
+            config = config.base_domain(domain);
+        }
+        if let Some(paths) = exclude_paths {
+            config.exclude_paths = paths;
+        }
+
+        let registry = Arc::new(TenantRegistry::new());
+        self.middleware
+            .add(TenantMiddleware::new(config, registry.clone()));
+        self.dependency_container
+            .set_request_scope(Arc::new(TenantScope::new(
+                registry.clone(),
+                self.cache.clone(),
+            )));
+        self.dependency_container
+            .register_py_singleton("tenant", py.None());
+        self.handlers.set_has_dependencies(true);
+        self.tenants = Some(registry);
+        Ok(())
+    }
+
+    /// Register a tenant with its own database pool, cache key prefix, rate
+    /// limit (a token-bucket `RateLimitConfig`) and metadata.
+    #[pyo3(signature = (tenant_id, database=None, cache_prefix=None, rate_limit=None, metadata=None))]
+    pub fn add_tenant(
+        &self,
+        py: Python<'_>,
+        tenant_id: &str,
+        database: Option<PyDatabaseConfig>,
+        cache_prefix: Option<&str>,
+        rate_limit: Option<PyRateLimitConfig>,
+        metadata: Option<&PyAny>,
+    ) -> PyResult<()> {
+        let registry = self.tenants.as_ref().ok_or_else(|| {
+            pyo3::exceptions::PyRuntimeError::new_err(
+                "Tenancy is not enabled; call enable_tenancy() first",
+            )
+        })?;
+        let mut tenant =
+            middleware::tenant::Tenant::new(tenant_id).with_metadata(optional_json(py, metadata)?);
+        if let Some(config) = database {
+            let _runtime = pyo3_asyncio::tokio::get_runtime().enter();
+            let pool = middleware::sql::SqlPool::connect_lazy(&config.to_config())
+                .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+            tenant = tenant.with_database(Arc::new(pool));
+        }
+        if let Some(prefix) = cache_prefix {
+            tenant = tenant.with_cache_prefix(prefix);
+        }
+        if let Some(limit) = rate_limit {
+            tenant = tenant.with_rate_limit(middleware::rate_limit::TokenBucketConfig::new(
+                limit.capacity,
+                limit.refill_rate as f64,
+            ));
+        }
+        registry.insert(tenant);
+        Ok(())
+    }
+
+    /// Unregister a tenant, returning whether it was registered.
+    pub fn remove_tenant(&self, tenant_id: &str) -> bool {
+        self.tenants
+            .as_ref()
+            .is_some_and(|registry| registry.remove(tenant_id))
+    }
+
+    /// Registered tenant ids.
+    pub fn tenants(&self) -> Vec<String> {
+        self.tenants
+            .as_ref()
+            .map(|registry| registry.ids())
+            .unwrap_or_default()
+    }
+
+    /// Add a Python middleware to every route, or to the routes under
+    /// `prefix`.
+    ///
+    /// The middleware's `before(request)` may return a `Response` to answer
+    /// early, and its `after(request, response)` may change the response; a
+    /// plain callable is used as `before`. Lower `priority` runs first.
+    #[pyo3(signature = (middleware, prefix=None, priority=0))]
+    pub fn add_middleware(
+        &self,
+        py: Python<'_>,
+        middleware: PyObject,
+        prefix: Option<&str>,
+        priority: i32,
+    ) -> PyResult<()> {
+        let middleware = middleware::PythonMiddleware::new(py, middleware)?;
+        self.route_middleware.attach(
+            middleware::MiddlewareScope::prefix(prefix.unwrap_or("")),
+            Arc::new(middleware),
+            Some(priority),
+        );
+        Ok(())
+    }
+
+    /// Add a Python middleware to a registered route; see `add_middleware()`.
+    #[pyo3(signature = (method, path, middleware, priority=0))]
+    pub fn add_route_middleware(
+        &self,
+        py: Python<'_>,
+        method: &str,
+        path: &str,
+        middleware: PyObject,
+        priority: i32,
+    ) -> PyResult<()> {
+        if self.router.route_handler(method, path).is_none() {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "No route registered for {method} {path}"
+            )));
+        }
+        let middleware = middleware::PythonMiddleware::new(py, middleware)?;
+        self.route_middleware.attach(
+            middleware::MiddlewareScope::route(method, path),
+            Arc::new(middleware),
+            Some(priority),
+        );
+        Ok(())
+    }
+
+    /// Names of the middleware run for a route, in order.
+    pub fn route_middleware(&self, method: &str, path: &str) -> Vec<String> {
+        self.route_middleware
+            .chain(&method.to_uppercase(), path)
+            .names()
+            .into_iter()
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Name a registered route, so `url_for` can build its URLs.
+    pub fn name_route(&self, method: &str, path: &str, name: &str) -> PyResult<()> {
+        self.router
+            .name_route(name, method, path)
+            .map_err(pyo3::exceptions::PyValueError::new_err)
+    }
+
+    /// Route names and the path patterns they build, as `{name: path}`.
+    pub fn route_names(&self) -> std::collections::HashMap<String, String> {
+        self.router.route_names().into_iter().collect()
+    }
+
+    /// Set the host and scheme of absolute URLs built by `url_for`.
+    #[pyo3(signature = (host, scheme="http"))]
+    pub fn set_server_name(&mut self, host: &str, scheme: &str) -> PyResult<()> {
+        if !matches!(scheme, "http" | "https") {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "Unknown URL scheme '{scheme}'; use 'http' or 'https'"
+            )));
+        }
+        self.server_name = Some(format!("{scheme}://{}", host.trim_end_matches('/')));
+        Ok(())
+    }
+
+    /// Build the URL of the route named `name`.
+    ///
+    /// `params` fill the route's path parameters, in order; the others are
+    /// sent as query parameters, repeated ones included. `external` builds
+    /// an absolute URL with the host set by `set_server_name`.
+    #[pyo3(signature = (name, params=Vec::new(), external=false))]
+    pub fn url_for(
+        &self,
+        name: &str,
+        params: Vec<(String, String)>,
+        external: bool,
+    ) -> PyResult<String> {
+        let mut path_params = std::collections::HashMap::new();
+        for (key, value) in &params {
+            path_params
+                .entry(key.clone())
+                .or_insert_with(|| value.clone());
+        }
+        let (mut url, used) = self
+            .router
+            .url_path(name, &path_params)
+            .map_err(pyo3::exceptions::PyValueError::new_err)?;
+        let query = request::encode_query(
+            params
+                .iter()
+                .filter(|(key, _)| !used.contains(key))
+                .map(|(key, value)| (key.as_str(), value.as_str())),
+        );
+        if !query.is_empty() {
+            url.push('?');
+            url.push_str(&query);
+        }
+        if external {
+            let base = self.server_name.as_ref().ok_or_else(|| {
+                pyo3::exceptions::PyValueError::new_err(
+                    "Absolute URLs need a host; call set_server_name() first",
+                )
+            })?;
+            url.insert_str(0, base);
+        }
+        Ok(url)
+    }
+
+    /// Add a native request/response transform by name.
+    ///
+    /// Transforms run in Rust before routing; see `middleware::transform`
+    /// for the built-ins and their options.
+    #[pyo3(signature = (kind, **options))]
+    pub fn add_transform(
+        &self,
+        py: Python<'_>,
+        kind: &str,
+        options: Option<&pyo3::types::PyDict>,
+    ) -> PyResult<()> {
+        let options = match optional_json(py, options.map(|o| o.as_ref()))? {
+            serde_json::Value::Object(options) => options,
+            _ => serde_json::Map::new(),
+        };
+        let transform = middleware::transform::create(kind, &options)
+            .map_err(pyo3::exceptions::PyValueError::new_err)?;
+        self.middleware.add_transform(transform);
+        Ok(())
+    }
+
+    /// Enable circuit breaker middleware.
+    #[pyo3(signature = (failure_threshold=5, reset_timeout=30, half_open_target=3, failure_codes=None))]
+    pub fn enable_circuit_breaker(
+        &mut self,
+        failure_threshold: u32,
+        reset_timeout: u64,
+        half_open_target: u32,
+        failure_codes: Option<Vec<u16>>,
+    ) {
+        let mut config = middleware::circuit_breaker::CircuitBreakerConfig::default();
+        config.failure_threshold = failure_threshold;
+        config.reset_timeout = std::time::Duration::from_secs(reset_timeout);
+        config.half_open_target = half_open_target;
+        if let Some(codes) = failure_codes {
+            config.failure_codes = codes;
+        }
+
+        let mw = middleware::circuit_breaker::CircuitBreakerMiddleware::new(config);
+        self.middleware.add(mw);
+    }
+
+    /// Mirror a sample of requests to a shadow upstream.
+    ///
+    /// `percentage` of the requests matching `methods` and `paths` (all if
+    /// unset) are copied to `target`, in the background; the responses are
+    /// discarded. At most `max_in_flight` copies are pending at once, and
+    /// `timeout` is in seconds.
+    #[pyo3(signature = (target, percentage=100.0, timeout=5.0, methods=None, paths=None, exclude_paths=None, max_in_flight=100))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn enable_mirroring(
+        &mut self,
+        target: &str,
+        percentage: f64,
+        timeout: f64,
+        methods: Option<Vec<String>>,
+        paths: Option<Vec<String>>,
+        exclude_paths: Option<Vec<String>>,
+        max_in_flight: usize,
+    ) -> PyResult<()> {
+        use middleware::mirror::{MirrorConfig, MirrorMiddleware};
+        if !target.starts_with("http://") && !target.starts_with("https://") {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "target must be an http:// or https:// URL",
+            ));
+        }
+        if !(0.0..=100.0).contains(&percentage) {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "percentage must be between 0 and 100",
+            ));
+        }
+        let mut config = MirrorConfig::new(target)
+            .with_percentage(percentage)
+            .with_timeout(seconds_arg("timeout", timeout)?)
+            .with_max_in_flight(max_in_flight);
+        if let Some(methods) = methods {
+            config = config.with_methods(methods.iter().map(String::as_str).collect());
+        }
+        for path in paths.unwrap_or_default() {
+            config = config.include_path(&path);
+        }
+        for path in exclude_paths.unwrap_or_default() {
+            config = config.exclude_path(&path);
+        }
+        let mw =
+            MirrorMiddleware::new(config).map_err(pyo3::exceptions::PyRuntimeError::new_err)?;
+        self.mirror_stats = Some(mw.stats());
+        self.middleware.add_async(mw);
+
+        println!("🪞 Traffic mirroring enabled:");
+        println!("   Target: {target}");
+        println!("   Percentage: {percentage}%");
+        Ok(())
+    }
+
+    /// Counters of mirrored requests: `mirrored`, `dropped` (too many in
+    /// flight), `failed` and `in_flight`.
+    pub fn mirror_stats(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let stats = self.mirror_stats.as_ref().ok_or_else(|| {
+            pyo3::exceptions::PyRuntimeError::new_err(
+                "Mirroring is not enabled; call enable_mirroring() first",
+            )
+        })?;
+        to_python(py, &stats.snapshot())
+    }
+
+    /// Enable fault injection. Nothing is injected until faults are added
+    /// with `add_fault()`.
+    pub fn enable_fault_injection(&mut self) -> PyResult<()> {
+        if self.fault_injection.is_some() {
+            return Err(pyo3::exceptions::PyRuntimeError::new_err(
+                "Fault injection is already enabled",
+            ));
+        }
+        let mw = middleware::fault_injection::FaultInjectionMiddleware::new();
+        self.fault_injection = Some(mw.clone());
+        self.middleware.add_async(mw);
+
+        println!("💥 Fault injection enabled; do not use in production");
+        Ok(())
+    }
+
+    /// Inject a fault into `percentage` of the requests matching `methods`,
+    /// `paths`, `exclude_paths` and `header` (all if unset).
+    ///
+    /// `kind` is `"latency"` (`delay` seconds), `"error"` (`status`) or
+    /// `"abort"` (the connection is dropped).
+    #[pyo3(signature = (kind, percentage=100.0, delay=None, status=503, methods=None, paths=None, exclude_paths=None, header=None, header_value=None))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_fault(
+        &self,
+        kind: &str,
+        percentage: f64,
+        delay: Option<f64>,
+        status: u16,
+        methods: Option<Vec<String>>,
+        paths: Option<Vec<String>>,
+        exclude_paths: Option<Vec<String>>,
+        header: Option<&str>,
+        header_value: Option<&str>,
+    ) -> PyResult<()> {
+        use middleware::fault_injection::{Fault, FaultRule};
+        let faults = self.fault_injection.as_ref().ok_or_else(|| {
+            pyo3::exceptions::PyRuntimeError::new_err(
+                "Fault injection is not enabled; call enable_fault_injection() first",
+            )
+        })?;
+        let fault = match kind {
+            "latency" => {
+                let delay = delay.ok_or_else(|| {
+                    pyo3::exceptions::PyValueError::new_err("latency faults require a delay")
+                })?;
+                Fault::Latency(seconds_arg("delay", delay)?)
+            }
+            "error" if (400..=599).contains(&status) => Fault::Error(status),
+            "error" => {
+                return Err(pyo3::exceptions::PyValueError::new_err(
+                    "status must be between 400 and 599",
+                ))
+            }
+            "abort" => Fault::Abort,
+            other => {
+                return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                    "Unknown fault kind '{other}'; expected latency, error or abort"
+                )))
+            }
+        };
+        if !(0.0..=100.0).contains(&percentage) {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "percentage must be between 0 and 100",
+            ));
+        }
+        let mut rule = FaultRule::new(fault).with_percentage(percentage);
+        if let Some(methods) = methods {
+            rule = rule.with_methods(methods.iter().map(String::as_str).collect());
+        }
+        for path in paths.unwrap_or_default() {
+            rule = rule.include_path(&path);
+        }
+        for path in exclude_paths.unwrap_or_default() {
+            rule = rule.exclude_path(&path);
+        }
+        match (header, header_value) {
+            (Some(name), value) => rule = rule.with_header(name, value),
+            (None, Some(_)) => {
+                return Err(pyo3::exceptions::PyValueError::new_err(
+                    "header_value requires a header",
+                ))
+            }
+            (None, None) => {}
+        }
+        faults.add_rule(rule);
+        Ok(())
+    }
+
+    /// Remove all injected faults.
+    pub fn clear_faults(&self) {
+        if let Some(faults) = &self.fault_injection {
+            faults.clear();
+        }
+    }
+
+    /// Counters of injected faults: `delayed`, `errors` and `aborted`.
+    pub fn fault_stats(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let faults = self.fault_injection.as_ref().ok_or_else(|| {
+            pyo3::exceptions::PyRuntimeError::new_err(
+                "Fault injection is not enabled; call enable_fault_injection() first",
+            )
+        })?;
+        to_python(py, &faults.stats().snapshot())
+    }
 
-        let mw = middleware::circuit_breaker::CircuitBreakerMiddleware::new(config);
-        self.middleware.add(mw);
-    }
-
     /// Register a startup handler.
-    pub fn on_startup(&mut self, handler: PyObject) {
-        self.startup_handlers.push(handler);
+    ///
+    /// Startup handlers run before the server accepts connections. A handler
+    /// running longer than `timeout` seconds fails; with `on_error="abort"`
+    /// a failure stops the server from starting.
+    #[pyo3(signature = (handler, timeout=None, on_error="continue"))]
+    pub fn on_startup(
+        &mut self,
+        handler: PyObject,
+        timeout: Option<f64>,
+        on_error: &str,
+    ) -> PyResult<()> {
+        let hook = server_hook(handler, timeout, on_error)?;
+        self.lifecycle.on_startup(hook);
+        Ok(())
     }
 
     /// Register a shutdown handler.
-    pub fn on_shutdown(&mut self, handler: PyObject) {
-        self.shutdown_handlers.push(handler);
+    ///
+    /// Shutdown handlers run after in-flight requests have drained. With
+    /// `on_error="abort"` a failure skips the remaining shutdown handlers.
+    #[pyo3(signature = (handler, timeout=None, on_error="continue"))]
+    pub fn on_shutdown(
+        &mut self,
+        handler: PyObject,
+        timeout: Option<f64>,
+        on_error: &str,
+    ) -> PyResult<()> {
+        let hook = server_hook(handler, timeout, on_error)?;
+        self.lifecycle.on_shutdown(hook);
+        Ok(())
+    }
+
+    /// Register a scheduled job running a sync or async Python callable.
+    ///
+    /// Exactly one of `cron` (five-field expression, UTC) or `every`
+    /// (seconds) must be given. `overlap` is `"skip"`, `"queue"` or
+    /// `"parallel"`; each run is delayed by up to `jitter` seconds.
+    #[pyo3(signature = (name, handler, cron=None, every=None, overlap="skip", jitter=0.0))]
+    pub fn schedule_job(
+        &self,
+        name: &str,
+        handler: PyObject,
+        cron: Option<&str>,
+        every: Option<f64>,
+        overlap: &str,
+        jitter: f64,
+    ) -> PyResult<()> {
+        let invalid =
+            |e: scheduler::SchedulerError| pyo3::exceptions::PyValueError::new_err(e.to_string());
+        let schedule = match (cron, every) {
+            (Some(cron), None) => scheduler::Schedule::cron(cron),
+            (None, Some(every)) if every.is_finite() && every > 0.0 => {
+                scheduler::Schedule::every(std::time::Duration::from_secs_f64(every))
+            }
+            (None, Some(_)) => Err(scheduler::SchedulerError::InvalidSchedule(
+                "every must be a positive number of seconds".to_string(),
+            )),
+            _ => Err(scheduler::SchedulerError::InvalidSchedule(
+                "exactly one of cron or every is required".to_string(),
+            )),
+        }
+        .map_err(invalid)?;
+        let overlap: scheduler::OverlapPolicy = overlap.parse().map_err(invalid)?;
+
+        let job = scheduler::Job::new(name, schedule, scheduler::JobHandler::python(handler))
+            .with_overlap(overlap)
+            .with_jitter(std::time::Duration::try_from_secs_f64(jitter).unwrap_or_default());
+        self.scheduler.add_job(job).map_err(invalid)
+    }
+
+    /// Metrics for every scheduled job, as a list of dicts.
+    pub fn scheduled_jobs(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let stats = serde_json::to_value(self.scheduler.stats())
+            .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))?;
+        json::json_to_python(py, &stats)
+    }
+
+    /// Run a scheduled job now and await it. Resolves to `False` if the run
+    /// was skipped by the job's overlap policy.
+    pub fn run_job<'py>(&self, py: Python<'py>, name: String) -> PyResult<&'py PyAny> {
+        let scheduler = self.scheduler.clone();
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            scheduler
+                .trigger(&name)
+                .await
+                .map_err(|e| pyo3::exceptions::PyKeyError::new_err(e.to_string()))
+        })
+    }
+
+    /// Set whether this process runs scheduled jobs. In cluster mode only
+    /// one worker is the leader.
+    pub fn set_scheduler_leader(&self, leader: bool) {
+        self.scheduler.set_leader(leader);
+    }
+
+    /// Reload routes in-process when source files change (development mode).
+    ///
+    /// `loader` is called without arguments after a change and must return
+    /// the `Cello` instance of a freshly imported app; its routes replace
+    /// the running server's routes.
+    #[pyo3(signature = (loader, watch_dirs=None, extensions=None, debounce=0.2))]
+    pub fn enable_reload(
+        &mut self,
+        loader: PyObject,
+        watch_dirs: Option<Vec<String>>,
+        extensions: Option<Vec<String>>,
+        debounce: f64,
+    ) -> PyResult<()> {
+        let dirs = watch_dirs
+            .unwrap_or_else(|| vec![".".to_string()])
+            .into_iter()
+            .map(std::path::PathBuf::from)
+            .collect();
+        let mut config =
+            reload::ReloadConfig::new(dirs).with_debounce(seconds_arg("debounce", debounce)?);
+        if let Some(extensions) = extensions {
+            config = config.with_extensions(extensions);
+        }
+        let reloader = reload::Reloader::new(config, move || {
+            Python::with_gil(|py| {
+                let app = loader.call0(py)?;
+                let app = app.extract::<PyRef<Cello>>(py)?;
+                Ok((app.router.clone(), app.handlers.clone()))
+            })
+            .map_err(|e: PyErr| e.to_string())
+        });
+        self.reloader = Some(Arc::new(reloader));
+        Ok(())
     }
 
     /// Invalidate cache tags.
@@ -416,25 +1965,136 @@ impl Cello {
         }
     }
 
-    /// Enable database connection pooling.
+    /// Enable the SQL connection pool.
+    ///
+    /// The backend (PostgreSQL, MySQL or SQLite) is chosen from the URL and
+    /// connections are opened on first use. The pool is registered as the
+    /// `"database"` dependency.
     #[pyo3(signature = (config))]
-    pub fn enable_database(&mut self, config: PyDatabaseConfig) {
-        let db_config = middleware::database::DatabaseConfig {
-            url: config.url.clone(),
-            pool_size: config.pool_size,
-            min_idle: config.min_idle,
-            max_lifetime: std::time::Duration::from_secs(config.max_lifetime_secs),
-            connection_timeout: std::time::Duration::from_secs(config.connection_timeout_secs),
-            idle_timeout: std::time::Duration::from_secs(config.idle_timeout_secs),
-            statement_cache_size: 100,
-            application_name: config.application_name.clone(),
-        };
-
-        // Register the config as a singleton for DI
-        let _pool = middleware::database::MockDatabasePool::new(db_config);
+    pub fn enable_database(&mut self, py: Python<'_>, config: PyDatabaseConfig) -> PyResult<()> {
+        // The pool spawns its maintenance tasks on the shared runtime.
+        let _runtime = pyo3_asyncio::tokio::get_runtime().enter();
+        let pool = middleware::sql::SqlPool::connect_lazy(&config.to_config())
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        let pool = Arc::new(pool);
+        let handle = middleware::sql::PyDatabasePool::new(pool.clone());
+        self.dependency_container
+            .register_py_singleton("database", handle.into_py(py));
+        self.handlers.set_has_dependencies(true);
         println!("🗄️  Database pool enabled:");
-        println!("   URL: {}", config.url);
+        println!("   Backend: {}", pool.backend());
         println!("   Pool size: {}", config.pool_size);
+        self.database = Some(pool);
+        Ok(())
+    }
+
+    /// The SQL pool created by `enable_database`.
+    pub fn database(&self) -> PyResult<middleware::sql::PyDatabasePool> {
+        Ok(middleware::sql::PyDatabasePool::new(self.sql_pool()?))
+    }
+
+    /// SQL pool statistics: connections, wait times and query timings.
+    pub fn database_stats(&self, py: Python<'_>) -> PyResult<PyObject> {
+        to_python(py, &self.sql_pool()?.stats())
+    }
+
+    /// Enable the MongoDB client.
+    ///
+    /// Connections are opened on first use. The default database is
+    /// registered as the `"mongo"` dependency. Requires the `mongodb`
+    /// feature.
+    #[pyo3(signature = (config))]
+    pub fn enable_mongo(&mut self, py: Python<'_>, config: PyMongoConfig) -> PyResult<()> {
+        #[cfg(feature = "mongodb")]
+        {
+            use middleware::mongo::{MongoClient, MongoError, PyMongoDatabase};
+            let config = config.to_config();
+            // Parsing a mongodb+srv:// URL resolves DNS records.
+            let client = py
+                .allow_threads(|| {
+                    pyo3_asyncio::tokio::get_runtime().block_on(MongoClient::from_config(&config))
+                })
+                .map_err(|e| match e {
+                    MongoError::Config(_) => pyo3::exceptions::PyValueError::new_err(e.to_string()),
+                    _ => pyo3::exceptions::PyConnectionError::new_err(e.to_string()),
+                })?;
+            let handle = PyMongoDatabase::new(client.database(None));
+            self.dependency_container
+                .register_py_singleton("mongo", handle.into_py(py));
+            self.handlers.set_has_dependencies(true);
+            println!("🍃 MongoDB client enabled:");
+            println!("   Database: {}", client.default_database());
+            println!("   Pool size: {}", config.max_pool_size);
+            self.mongo = Some(Arc::new(client));
+            Ok(())
+        }
+        #[cfg(not(feature = "mongodb"))]
+        {
+            let _ = (py, config);
+            Err(pyo3::exceptions::PyRuntimeError::new_err(
+                "MongoDB support is not compiled in; build cello with the `mongodb` feature",
+            ))
+        }
+    }
+
+    /// A MongoDB database from `enable_mongo`, or its default database.
+    #[pyo3(signature = (name=None))]
+    pub fn mongo(&self, py: Python<'_>, name: Option<&str>) -> PyResult<PyObject> {
+        #[cfg(feature = "mongodb")]
+        {
+            let client = self.mongo.as_ref().ok_or_else(|| {
+                pyo3::exceptions::PyRuntimeError::new_err(
+                    "MongoDB is not enabled; call enable_mongo() first",
+                )
+            })?;
+            Ok(middleware::mongo::PyMongoDatabase::new(client.database(name)).into_py(py))
+        }
+        #[cfg(not(feature = "mongodb"))]
+        {
+            let _ = (py, name);
+            Err(pyo3::exceptions::PyRuntimeError::new_err(
+                "MongoDB is not enabled; call enable_mongo() first",
+            ))
+        }
+    }
+
+    /// Enable the S3-compatible object store client.
+    ///
+    /// No connection is opened until the first request. The bucket is
+    /// registered as a dependency under `name`.
+    #[pyo3(signature = (config, name="storage"))]
+    pub fn enable_object_store(
+        &mut self,
+        py: Python<'_>,
+        config: PyObjectStoreConfig,
+        name: &str,
+    ) -> PyResult<()> {
+        use middleware::object_store::{ObjectStore, PyObjectStore};
+        let store = ObjectStore::new(config.to_config())
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        let store = Arc::new(store);
+        self.dependency_container
+            .register_py_singleton(name, PyObjectStore::new(store.clone()).into_py(py));
+        self.handlers.set_has_dependencies(true);
+        println!("🪣 Object store enabled:");
+        println!("   Bucket: {}", config.bucket);
+        if let Some(endpoint) = &config.endpoint {
+            println!("   Endpoint: {}", endpoint);
+        } else {
+            println!("   Region: {}", config.region);
+        }
+        self.object_store = Some(store);
+        Ok(())
+    }
+
+    /// The object store client created by `enable_object_store`.
+    pub fn object_store(&self) -> PyResult<middleware::object_store::PyObjectStore> {
+        let store = self.object_store.clone().ok_or_else(|| {
+            pyo3::exceptions::PyRuntimeError::new_err(
+                "Object store is not enabled; call enable_object_store() first",
+            )
+        })?;
+        Ok(middleware::object_store::PyObjectStore::new(store))
     }
 
     /// Enable Redis connection.
@@ -454,7 +2114,11 @@ impl Cello {
             key_prefix: config.key_prefix.clone(),
         };
 
-        let _client = middleware::redis::MockRedisClient::new(redis_config);
+        let client: Arc<dyn middleware::redis::RedisClient> =
+            Arc::new(middleware::redis::MockRedisClient::new(redis_config));
+        let stats = client.clone();
+        self.stats.register("redis", move || stats.stats());
+        self.redis_client = Some(client);
         println!("🔴 Redis connection enabled:");
         println!("   URL: {}", config.url);
         println!("   Pool size: {}", config.pool_size);
@@ -463,6 +2127,147 @@ impl Cello {
         }
     }
 
+    /// Enable distributed locks.
+    ///
+    /// `backend` is `"memory"` (coordinates tasks within this process) or
+    /// `"redis"` (coordinates cluster workers through a shared Redis
+    /// client; rejected while `enable_redis()` is in-process only).
+    /// `ttl` is the lease and `timeout` the default acquire timeout, both in
+    /// seconds; with `auto_renew` the lease is renewed while held.
+    #[pyo3(signature = (backend="memory", ttl=30.0, timeout=10.0, auto_renew=true))]
+    pub fn enable_distributed_locks(
+        &mut self,
+        backend: &str,
+        ttl: f64,
+        timeout: f64,
+        auto_renew: bool,
+    ) -> PyResult<()> {
+        use middleware::distributed_lock::{
+            DistributedLock, InMemoryLockBackend, LockBackend, LockConfig, RedisLockBackend,
+        };
+        let backend: Arc<dyn LockBackend> = match backend {
+            "memory" => Arc::new(InMemoryLockBackend::new()),
+            "redis" => Arc::new(RedisLockBackend::new(vec![self.shared_redis_client()?])),
+            other => {
+                return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                    "unknown lock backend '{other}' (expected memory or redis)"
+                )))
+            }
+        };
+        let config = LockConfig::new()
+            .with_ttl(seconds_arg("ttl", ttl)?)
+            .with_acquire_timeout(seconds_arg("timeout", timeout)?)
+            .with_auto_renew(auto_renew);
+        self.distributed_locks = Some(Arc::new(DistributedLock::with_config(backend, config)));
+        Ok(())
+    }
+
+    /// A lock on `key`, used as an async context manager. `timeout` and
+    /// `ttl` (seconds) default to the values given to
+    /// `enable_distributed_locks()`.
+    #[pyo3(signature = (key, timeout=None, ttl=None))]
+    pub fn lock(
+        &self,
+        key: &str,
+        timeout: Option<f64>,
+        ttl: Option<f64>,
+    ) -> PyResult<middleware::distributed_lock::PyDistributedLock> {
+        let locks = self.distributed_locks.clone().ok_or_else(|| {
+            pyo3::exceptions::PyRuntimeError::new_err(
+                "Distributed locks are not enabled; call enable_distributed_locks() first",
+            )
+        })?;
+        Ok(middleware::distributed_lock::PyDistributedLock::new(
+            locks,
+            key,
+            timeout.map(|t| seconds_arg("timeout", t)).transpose()?,
+            ttl.map(|t| seconds_arg("ttl", t)).transpose()?,
+        ))
+    }
+
+    /// Enable idempotency keys.
+    ///
+    /// Requests carrying `header` run once; retries with the same key get
+    /// the stored response for `ttl` seconds. `backend` is `"memory"` or
+    /// `"redis"` (shared by cluster workers; rejected while `enable_redis()`
+    /// is in-process only).
+    /// Routes opt in with `set_route_idempotency()`; with `paths`, every
+    /// request in `methods` under those prefixes is checked as well.
+    /// `lock_timeout` is how long a request that never finishes holds its
+    /// key.
+    #[pyo3(signature = (backend="memory", ttl=86400.0, header="Idempotency-Key", methods=None, paths=None, required=false, lock_timeout=60.0))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn enable_idempotency(
+        &mut self,
+        backend: &str,
+        ttl: f64,
+        header: &str,
+        methods: Option<Vec<String>>,
+        paths: Option<Vec<String>>,
+        required: bool,
+        lock_timeout: f64,
+    ) -> PyResult<()> {
+        use middleware::idempotency::{
+            IdempotencyConfig, IdempotencyMiddleware, IdempotencyStore, InMemoryIdempotencyStore,
+            RedisIdempotencyStore,
+        };
+        let store: Arc<dyn IdempotencyStore> = match backend {
+            "memory" => Arc::new(InMemoryIdempotencyStore::new()),
+            "redis" => Arc::new(RedisIdempotencyStore::new(self.shared_redis_client()?)),
+            other => {
+                return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                    "unknown idempotency backend '{other}' (expected memory or redis)"
+                )))
+            }
+        };
+        let mut config = IdempotencyConfig::new()
+            .with_header(header)
+            .with_ttl(seconds_arg("ttl", ttl)?)
+            .with_lock_timeout(seconds_arg("lock_timeout", lock_timeout)?)
+            .with_required(required);
+        if let Some(methods) = methods {
+            config = config.with_methods(methods.iter().map(String::as_str).collect());
+        }
+        let idempotency = IdempotencyMiddleware::with_config(store, config.clone());
+        if let Some(paths) = paths {
+            let config = paths
+                .iter()
+                .fold(config, |config, path| config.include_path(path));
+            let middleware =
+                IdempotencyMiddleware::with_config(idempotency.store().clone(), config);
+            self.middleware.add_async(middleware);
+        }
+        self.idempotency = Some(idempotency);
+        Ok(())
+    }
+
+    /// Check idempotency keys on a registered route, with the store and
+    /// header given to `enable_idempotency()`. `ttl` (seconds) and
+    /// `required` override its defaults for this route.
+    #[pyo3(signature = (method, path, ttl=None, required=None))]
+    pub fn set_route_idempotency(
+        &self,
+        method: &str,
+        path: &str,
+        ttl: Option<f64>,
+        required: Option<bool>,
+    ) -> PyResult<()> {
+        let idempotency = self.idempotency.as_ref().ok_or_else(|| {
+            pyo3::exceptions::PyRuntimeError::new_err(
+                "Idempotency keys are not enabled; call enable_idempotency() first",
+            )
+        })?;
+        let ttl = ttl.map(|t| seconds_arg("ttl", t)).transpose()?;
+        let handler = self.router.route_handler(method, path).ok_or_else(|| {
+            pyo3::exceptions::PyValueError::new_err(format!(
+                "No route registered for {method} {path}"
+            ))
+        })?;
+        handler
+            .set_idempotency(idempotency.for_route(ttl, required))
+            .map_err(pyo3::exceptions::PyValueError::new_err)
+    }
+
     // ========================================================================
     // v0.9.0 - API Protocol Features
     // ========================================================================
@@ -483,7 +2288,13 @@ impl Cello {
             concurrency_limit: config.concurrency_limit,
         };
 
-        let _server = middleware::grpc::GrpcServer::new(grpc_config);
+        let server = middleware::grpc::GrpcServer::new(grpc_config)
+            .with_descriptors(self.proto_descriptors.clone());
+        server.register_descriptor_services();
+        let server = Arc::new(server);
+        let stats = server.clone();
+        self.stats.register("grpc", move || stats.stats());
+        self.grpc_server = Some(server);
         println!("🔌 gRPC enabled:");
         println!("   Address: {}", config.address);
         println!("   Reflection: {}", config.reflection);
@@ -502,13 +2313,236 @@ impl Cello {
         }
     }
 
-    /// Enable message queue integration.
-    #[pyo3(signature = (config))]
-    pub fn enable_messaging(&mut self, config: PyKafkaConfig) {
-        println!("📨 Message queue enabled:");
-        println!("   Brokers: {}", config.brokers.join(", "));
-        if let Some(ref group_id) = config.group_id {
-            println!("   Group ID: {group_id}");
+    /// Attach a Python handler (sync or async) to a unary gRPC method.
+    ///
+    /// Message types default to those of a loaded service descriptor; without
+    /// them the handler exchanges raw `bytes`. `deadline` is in seconds.
+    #[pyo3(signature = (service, method, handler, input_type=None, output_type=None, deadline=None))]
+    pub fn add_grpc_method(
+        &self,
+        service: &str,
+        method: &str,
+        handler: PyObject,
+        input_type: Option<&str>,
+        output_type: Option<&str>,
+        deadline: Option<f64>,
+    ) -> PyResult<()> {
+        let server = self.grpc_server()?;
+        let (input_type, output_type) = {
+            let pool = self.proto_descriptors.read();
+            let descriptor = pool.service(service).and_then(|s| s.method(method));
+            (
+                input_type
+                    .map(str::to_string)
+                    .or_else(|| descriptor.map(|d| d.input_type.clone()))
+                    .unwrap_or_default(),
+                output_type
+                    .map(str::to_string)
+                    .or_else(|| descriptor.map(|d| d.output_type.clone()))
+                    .unwrap_or_default(),
+            )
+        };
+        let handler = middleware::grpc::GrpcHandler::python(
+            handler,
+            self.proto_descriptors.clone(),
+            &output_type,
+        );
+        let mut method_def =
+            middleware::grpc::GrpcMethodDef::unary(method, &input_type, &output_type)
+                .with_handler(handler);
+        if let Some(deadline) = deadline {
+            let deadline = std::time::Duration::try_from_secs_f64(deadline)
+                .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+            method_def = method_def.with_deadline(deadline);
+        }
+        server.add_method(service, method_def);
+        Ok(())
+    }
+
+    /// Dispatch a gRPC call in-process and await the response.
+    ///
+    /// Resolves to a dict with `code`, `message`, `payload` (bytes) and `metadata`.
+    #[pyo3(signature = (service, method, payload, metadata=None))]
+    pub fn grpc_call<'py>(
+        &self,
+        py: Python<'py>,
+        service: &str,
+        method: &str,
+        payload: Vec<u8>,
+        metadata: Option<std::collections::HashMap<String, String>>,
+    ) -> PyResult<&'py PyAny> {
+        let server = self.grpc_server()?;
+        let mut request = middleware::grpc::GrpcRequest::new(service, method, payload);
+        request.metadata = metadata.unwrap_or_default();
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            let response = server.handle_request(&request).await;
+            Python::with_gil(|py| response.to_py_dict(py))
+        })
+    }
+
+    /// REST mappings declared by a gRPC method's `google.api.http` option.
+    ///
+    /// Returns `(http_method, path_template, body, response_body)` tuples.
+    pub fn grpc_http_rules(
+        &self,
+        service: &str,
+        method: &str,
+    ) -> Vec<(String, String, Option<String>, Option<String>)> {
+        self.proto_descriptors
+            .read()
+            .service(service)
+            .and_then(|s| s.method(method))
+            .map(|m| {
+                m.http_rules
+                    .iter()
+                    .map(|rule| {
+                        (
+                            rule.method.clone(),
+                            rule.path.clone(),
+                            rule.body.clone(),
+                            rule.response_body.clone(),
+                        )
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Convert an HttpRule path template to a route path.
+    pub fn grpc_route_path(&self, template: &str) -> PyResult<String> {
+        middleware::transcoding::route_path(template)
+            .map_err(pyo3::exceptions::PyValueError::new_err)
+    }
+
+    /// Serve an HTTP request through a unary gRPC method, building the request
+    /// message from the JSON body, path and query and returning JSON.
+    #[pyo3(signature = (request, service, method, body=None, response_body=None))]
+    pub fn grpc_transcode<'py>(
+        &self,
+        py: Python<'py>,
+        request: PyRef<'_, request::Request>,
+        service: String,
+        method: String,
+        body: Option<String>,
+        response_body: Option<String>,
+    ) -> PyResult<&'py PyAny> {
+        let server = self.grpc_server()?;
+        let request = request.clone();
+        let rule = middleware::protobuf::HttpRule {
+            method: request.method.clone(),
+            path: request.path.clone(),
+            body,
+            response_body,
+        };
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            let response =
+                middleware::transcoding::transcode(&server, &service, &method, &rule, &request)
+                    .await;
+            Ok(response)
+        })
+    }
+
+    /// Load messages, enums and services from a compiled `FileDescriptorSet` file.
+    ///
+    /// Returns the fully-qualified names of the services it defines, which are
+    /// registered with the gRPC server when it is enabled.
+    pub fn load_proto_descriptors(&self, path: &str) -> PyResult<Vec<String>> {
+        let bytes = std::fs::read(path).map_err(|e| {
+            pyo3::exceptions::PyIOError::new_err(format!("Cannot read {path}: {e}"))
+        })?;
+        let names = self
+            .proto_descriptors
+            .write()
+            .load_file_descriptor_set(&bytes)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        if let Some(server) = &self.grpc_server {
+            server.register_descriptor_services();
+        }
+        Ok(names)
+    }
+
+    /// Register a protobuf message type shared by gRPC services and HTTP routes.
+    ///
+    /// Each field is a `(name, number, type)` or `(name, number, type, repeated)`
+    /// tuple, where `type` is a scalar keyword or another message/enum name.
+    pub fn register_proto_message(
+        &self,
+        name: &str,
+        fields: Vec<&pyo3::types::PyTuple>,
+    ) -> PyResult<()> {
+        use middleware::protobuf::{FieldDescriptor, FieldType, MessageDescriptor};
+
+        let mut message = MessageDescriptor::new(name);
+        for field in fields {
+            if !(3..=4).contains(&field.len()) {
+                return Err(pyo3::exceptions::PyValueError::new_err(
+                    "Fields must be (name, number, type[, repeated]) tuples",
+                ));
+            }
+            let mut descriptor = FieldDescriptor::new(
+                field.get_item(0)?.extract()?,
+                field.get_item(1)?.extract()?,
+                FieldType::parse(field.get_item(2)?.extract()?),
+            );
+            if field.len() == 4 && field.get_item(3)?.is_true()? {
+                descriptor = descriptor.repeated();
+            }
+            message = message.field(descriptor);
+        }
+        self.proto_descriptors
+            .write()
+            .add_message(message)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))
+    }
+
+    /// Register a protobuf enum type from a `{name: number}` mapping.
+    pub fn register_proto_enum(&self, name: &str, values: std::collections::HashMap<String, i32>) {
+        let mut values: Vec<(String, i32)> = values.into_iter().collect();
+        values.sort_by_key(|(_, number)| *number);
+        self.proto_descriptors
+            .write()
+            .add_enum(middleware::protobuf::EnumDescriptor::new(name, values));
+    }
+
+    /// Encode a dict as a registered protobuf message.
+    pub fn proto_encode<'py>(
+        &self,
+        py: Python<'py>,
+        message_type: &str,
+        data: &PyAny,
+    ) -> PyResult<&'py pyo3::types::PyBytes> {
+        let value =
+            json::python_to_json(py, data).map_err(pyo3::exceptions::PyValueError::new_err)?;
+        let bytes = self
+            .proto_descriptors
+            .read()
+            .encode(message_type, &value)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        Ok(pyo3::types::PyBytes::new(py, &bytes))
+    }
+
+    /// Decode bytes as a registered protobuf message into a dict.
+    pub fn proto_decode(
+        &self,
+        py: Python<'_>,
+        message_type: &str,
+        data: &[u8],
+    ) -> PyResult<PyObject> {
+        let value = self
+            .proto_descriptors
+            .read()
+            .decode(message_type, data)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        json::json_to_python(py, &value)
+    }
+
+    /// Enable message queue integration.
+    #[pyo3(signature = (config))]
+    pub fn enable_messaging(&mut self, config: PyKafkaConfig) {
+        println!("📨 Message queue enabled:");
+        println!("   Brokers: {}", config.brokers.join(", "));
+        if let Some(ref group_id) = config.group_id {
+            println!("   Group ID: {group_id}");
         }
     }
 
@@ -537,9 +2571,39 @@ impl Cello {
     // ========================================================================
 
     /// Enable event sourcing support.
-    #[pyo3(signature = (config=None))]
-    pub fn enable_event_sourcing(&mut self, config: Option<PyEventSourcingConfig>) {
+    ///
+    /// With `encrypt` (aggregate IDs, `prefix*` or `*`), the data of those
+    /// aggregates' events is encrypted with per-aggregate keys kept in the
+    /// `secrets` backend (`memory` or `redis`, which needs a Redis server
+    /// shared by all workers).
+    #[pyo3(signature = (config=None, encrypt=None, secrets="memory"))]
+    pub fn enable_event_sourcing(
+        &mut self,
+        config: Option<PyEventSourcingConfig>,
+        encrypt: Option<Vec<String>>,
+        secrets: &str,
+    ) -> PyResult<()> {
+        use middleware::eventsourcing::{EncryptedEventStore, EventEncryption};
+        use middleware::secrets::{InMemorySecretsProvider, RedisSecretsProvider, SecretsProvider};
+
         let config = config.unwrap_or_else(PyEventSourcingConfig::memory);
+        let encryption = match encrypt {
+            Some(patterns) => {
+                let provider: Arc<dyn SecretsProvider> = match secrets {
+                    "memory" => Arc::new(InMemorySecretsProvider::new()),
+                    "redis" => Arc::new(RedisSecretsProvider::new(self.shared_redis_client()?)),
+                    other => {
+                        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                            "unknown secrets backend '{other}' (expected memory or redis)"
+                        )))
+                    }
+                };
+                Some(Arc::new(
+                    EventEncryption::new(provider).with_aggregates(patterns),
+                ))
+            }
+            None => None,
+        };
 
         let es_config = middleware::eventsourcing::EventSourcingConfig {
             store_type: config.store_type.clone(),
@@ -550,7 +2614,27 @@ impl Cello {
             connection_url: config.connection_url.clone(),
         };
 
-        let _store = middleware::eventsourcing::InMemoryEventStore::with_config(es_config);
+        let memory_store = Arc::new(middleware::eventsourcing::InMemoryEventStore::with_config(
+            es_config,
+        ));
+        let stats = memory_store.clone();
+        self.stats.register("event_sourcing", move || stats.stats());
+        let store: Arc<dyn middleware::eventsourcing::EventStore> = match &encryption {
+            Some(encryption) => {
+                Arc::new(EncryptedEventStore::new(memory_store, encryption.clone()))
+            }
+            None => memory_store,
+        };
+        if let Some(bus) = &self.command_bus {
+            bus.set_event_store(store.clone());
+        }
+        if let Some(webhooks) = &self.webhooks {
+            if let Err(e) = webhooks.forward_events(store.clone()) {
+                tracing::warn!("{e}");
+            }
+        }
+        self.event_store = Some(store);
+        self.event_encryption = encryption;
         println!("Event sourcing enabled:");
         println!("   Store type: {}", config.store_type);
         println!(
@@ -565,6 +2649,233 @@ impl Cello {
         if let Some(ref url) = config.connection_url {
             println!("   Connection: {url}");
         }
+        if self.event_encryption.is_some() {
+            println!("   Encryption: enabled ({secrets} keys)");
+        }
+        Ok(())
+    }
+
+    /// Events of an aggregate after `after_version`, as a list of dicts.
+    /// Unknown aggregates have no events.
+    #[pyo3(signature = (aggregate_id, after_version=None))]
+    pub fn get_events(
+        &self,
+        py: Python<'_>,
+        aggregate_id: &str,
+        after_version: Option<u64>,
+    ) -> PyResult<PyObject> {
+        use middleware::eventsourcing::EventSourcingError;
+
+        let events = match self.event_store()?.get_events(aggregate_id, after_version) {
+            Ok(events) => events,
+            Err(EventSourcingError::AggregateNotFound(_)) => Vec::new(),
+            Err(e) => return Err(pyo3::exceptions::PyRuntimeError::new_err(e.to_string())),
+        };
+        to_python(py, &events)
+    }
+
+    /// Delete an aggregate's encryption key so its events can no longer be
+    /// read. Returns whether a key existed.
+    pub fn shred_aggregate(&self, aggregate_id: &str) -> PyResult<bool> {
+        let encryption = self.event_encryption.clone().ok_or_else(|| {
+            pyo3::exceptions::PyRuntimeError::new_err(
+                "Event encryption is not enabled; pass encrypt= to enable_event_sourcing()",
+            )
+        })?;
+        encryption
+            .shred(aggregate_id)
+            .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))
+    }
+
+    /// Append events to several aggregates as one transaction.
+    ///
+    /// Each stream is a dict with `aggregate_id`, `events` (dicts with
+    /// `event_type`, `data` and `metadata`) and `expected_version`, which
+    /// defaults to the aggregate's current version. Returns a dict with
+    /// `transaction_id` and the stored `events`; when the versions conflict
+    /// or the store fails part way, the dict instead carries an `error`
+    /// and `conflicts` or `committed`, `failed` and `pending`.
+    pub fn append_events(&self, py: Python<'_>, streams: &PyAny) -> PyResult<PyObject> {
+        use middleware::eventsourcing::{Event, EventSourcingError, EventTransaction};
+
+        let store = self.event_store()?;
+        let streams =
+            json::python_to_json(py, streams).map_err(pyo3::exceptions::PyValueError::new_err)?;
+        let streams: Vec<PyStreamAppend> = serde_json::from_value(streams)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+
+        let runtime_err =
+            |e: EventSourcingError| pyo3::exceptions::PyRuntimeError::new_err(e.to_string());
+        let mut transaction = EventTransaction::new();
+        for stream in streams {
+            let expected_version = match stream.expected_version {
+                Some(version) => version,
+                None => match store.get_events(&stream.aggregate_id, None) {
+                    Ok(events) => events.last().map(|e| e.version).unwrap_or(0),
+                    Err(EventSourcingError::AggregateNotFound(_)) => 0,
+                    Err(e) => return Err(runtime_err(e)),
+                },
+            };
+            let events = stream
+                .events
+                .into_iter()
+                .zip(expected_version + 1..)
+                .map(|(event, version)| {
+                    let mut stored =
+                        Event::new(&stream.aggregate_id, &event.event_type, event.data, version);
+                    stored.metadata = event.metadata;
+                    stored
+                })
+                .collect();
+            transaction = transaction.append(&stream.aggregate_id, expected_version, events);
+        }
+
+        let result = match store.append_transaction(&transaction) {
+            Ok(()) => {
+                let mut stored = Vec::new();
+                for stream in &transaction.streams {
+                    let mut events = store
+                        .get_events(&stream.aggregate_id, Some(stream.expected_version))
+                        .map_err(runtime_err)?;
+                    events.truncate(stream.events.len());
+                    stored.extend(events);
+                }
+                serde_json::json!({ "transaction_id": transaction.id, "events": stored })
+            }
+            Err(e) => {
+                let error = e.to_string();
+                match e {
+                    EventSourcingError::Conflicts(conflicts) => serde_json::json!({
+                        "transaction_id": transaction.id,
+                        "error": error,
+                        "conflicts": conflicts,
+                    }),
+                    EventSourcingError::PartialAppend {
+                        committed,
+                        failed,
+                        pending,
+                        ..
+                    } => serde_json::json!({
+                        "transaction_id": transaction.id,
+                        "error": error,
+                        "committed": committed,
+                        "failed": failed,
+                        "pending": pending,
+                    }),
+                    e => return Err(runtime_err(e)),
+                }
+            }
+        };
+        json::json_to_python(py, &result)
+    }
+
+    /// Enable outgoing webhooks.
+    ///
+    /// Deliveries are retried up to `max_attempts` times, waiting
+    /// `initial_backoff` seconds before the first retry and doubling up to
+    /// `max_backoff`. With event sourcing enabled, appended domain events are
+    /// delivered to the endpoints subscribed to their type.
+    #[pyo3(signature = (max_attempts=5, initial_backoff=1.0, max_backoff=300.0, timeout=10.0))]
+    pub fn enable_webhooks(
+        &mut self,
+        max_attempts: u32,
+        initial_backoff: f64,
+        max_backoff: f64,
+        timeout: f64,
+    ) -> PyResult<()> {
+        let config = webhooks::WebhookConfig::default()
+            .with_max_attempts(max_attempts)
+            .with_backoff(
+                seconds_arg("initial_backoff", initial_backoff)?,
+                seconds_arg("max_backoff", max_backoff)?,
+            )
+            .with_timeout(seconds_arg("timeout", timeout)?);
+        let dispatcher = Arc::new(webhooks::WebhookDispatcher::new(
+            config,
+            Arc::new(webhooks::HttpTransport::default()),
+            pyo3_asyncio::tokio::get_runtime().handle().clone(),
+        ));
+        if let Some(store) = &self.event_store {
+            dispatcher
+                .forward_events(store.clone())
+                .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))?;
+        }
+        if let Some(previous) = self.webhooks.replace(dispatcher) {
+            previous.stop_forwarding();
+        }
+        Ok(())
+    }
+
+    /// Register a webhook endpoint for event types (exact names, `prefix.*`
+    /// or `*`), returning its id. Payloads are signed when `secret` is set.
+    #[pyo3(signature = (url, events, secret=None, id=None))]
+    pub fn register_webhook(
+        &self,
+        url: &str,
+        events: Vec<String>,
+        secret: Option<&str>,
+        id: Option<String>,
+    ) -> PyResult<String> {
+        let id = id.unwrap_or_else(|| format!("whe_{}", uuid::Uuid::new_v4().simple()));
+        let mut endpoint = webhooks::WebhookEndpoint::new(&id, url, events);
+        if let Some(secret) = secret {
+            endpoint = endpoint.with_secret(secret);
+        }
+        self.webhooks()?
+            .register(endpoint)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        Ok(id)
+    }
+
+    /// Remove a webhook endpoint. Returns whether it was registered.
+    pub fn unregister_webhook(&self, id: &str) -> PyResult<bool> {
+        Ok(self.webhooks()?.unregister(id))
+    }
+
+    /// Registered webhook endpoints, as a list of dicts.
+    pub fn webhook_endpoints(&self, py: Python<'_>) -> PyResult<PyObject> {
+        to_python(py, &self.webhooks()?.endpoints())
+    }
+
+    /// Deliver an event to the subscribed endpoints in the background,
+    /// returning the delivery ids.
+    pub fn dispatch_webhook(
+        &self,
+        py: Python<'_>,
+        event_type: &str,
+        payload: &PyAny,
+    ) -> PyResult<Vec<String>> {
+        let payload =
+            json::python_to_json(py, payload).map_err(pyo3::exceptions::PyValueError::new_err)?;
+        Ok(self.webhooks()?.dispatch(event_type, payload))
+    }
+
+    /// A delivery with its status and attempts, or `None` if unknown.
+    pub fn webhook_delivery(&self, py: Python<'_>, id: &str) -> PyResult<PyObject> {
+        match self.webhooks()?.delivery(id) {
+            Some(delivery) => to_python(py, &delivery),
+            None => Ok(py.None()),
+        }
+    }
+
+    /// Recent deliveries, newest first.
+    #[pyo3(signature = (limit=None))]
+    pub fn webhook_deliveries(&self, py: Python<'_>, limit: Option<usize>) -> PyResult<PyObject> {
+        to_python(py, &self.webhooks()?.deliveries(limit))
+    }
+
+    /// Webhook delivery statistics.
+    pub fn webhook_stats(&self, py: Python<'_>) -> PyResult<PyObject> {
+        to_python(py, &self.webhooks()?.stats())
+    }
+
+    /// Wait for the deliveries in progress to finish.
+    pub fn drain_webhooks<'py>(&self, py: Python<'py>) -> PyResult<&'py PyAny> {
+        let webhooks = self.webhooks()?;
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            webhooks.drain().await;
+            Ok(())
+        })
     }
 
     /// Enable CQRS (Command Query Responsibility Segregation) support.
@@ -579,8 +2890,14 @@ impl Cello {
             max_retries: config.max_retries,
         };
 
-        let _command_bus = middleware::cqrs::CommandBus::with_config(cqrs_config.clone());
-        let _query_bus = middleware::cqrs::QueryBus::with_config(cqrs_config);
+        let command_bus = middleware::cqrs::CommandBus::with_config(cqrs_config.clone());
+        if let Some(store) = &self.event_store {
+            command_bus.set_event_store(store.clone());
+        }
+        self.command_bus = Some(Arc::new(command_bus));
+        self.query_bus = Some(Arc::new(middleware::cqrs::QueryBus::with_config(
+            cqrs_config,
+        )));
         println!("CQRS enabled:");
         println!("   Event sync: {}", config.enable_event_sync);
         println!("   Command timeout: {}ms", config.command_timeout_ms);
@@ -588,23 +2905,221 @@ impl Cello {
         println!("   Max retries: {}", config.max_retries);
     }
 
+    /// Register a sync or async Python handler for a command type.
+    ///
+    /// The handler is called with the command as a dict (`id`,
+    /// `command_type`, `data`, `metadata`, `timestamp`).
+    pub fn register_command_handler(&self, command_type: &str, handler: PyObject) -> PyResult<()> {
+        self.command_bus()?.register_handler(
+            command_type,
+            middleware::cqrs::CommandHandler::python(handler),
+        );
+        Ok(())
+    }
+
+    /// Register a sync or async Python handler for a query type.
+    ///
+    /// The handler is called with the query as a dict (`id`, `query_type`,
+    /// `params`, `timestamp`).
+    pub fn register_query_handler(&self, query_type: &str, handler: PyObject) -> PyResult<()> {
+        self.query_bus()?
+            .register_handler(query_type, middleware::cqrs::QueryHandler::python(handler));
+        Ok(())
+    }
+
+    /// Add middleware to both CQRS buses.
+    ///
+    /// `roles` maps a command or query type to the role it requires (read
+    /// from the comma-separated `roles` metadata value); `validators` maps a
+    /// type to a callable that raises to reject its payload. Commands with
+    /// an `idempotency_key` metadata value are deduplicated for
+    /// `idempotency_ttl_secs` when set.
+    #[pyo3(signature = (logging=false, idempotency_ttl_secs=None, roles=None, validators=None))]
+    pub fn enable_cqrs_pipeline(
+        &self,
+        logging: bool,
+        idempotency_ttl_secs: Option<u64>,
+        roles: Option<std::collections::HashMap<String, String>>,
+        validators: Option<std::collections::HashMap<String, PyObject>>,
+    ) -> PyResult<()> {
+        use middleware::cqrs::{
+            AuthorizationMiddleware, DeduplicationMiddleware, LoggingMiddleware,
+            ValidationMiddleware,
+        };
+        let command_bus = self.command_bus()?;
+        let query_bus = self.query_bus()?;
+
+        if logging {
+            command_bus.add_middleware(LoggingMiddleware::new());
+            query_bus.add_middleware(LoggingMiddleware::new());
+        }
+        if let Some(ttl_secs) = idempotency_ttl_secs {
+            command_bus.add_middleware(DeduplicationMiddleware::new(ttl_secs));
+        }
+        if let Some(roles) = roles {
+            let authorization = || {
+                roles
+                    .iter()
+                    .fold(AuthorizationMiddleware::new(), |m, (message_type, role)| {
+                        m.require(message_type, role)
+                    })
+            };
+            command_bus.add_middleware(authorization());
+            query_bus.add_middleware(authorization());
+        }
+        if let Some(validators) = validators {
+            let validators: Vec<_> = validators
+                .into_iter()
+                .map(|(message_type, validator)| (message_type, Arc::new(validator)))
+                .collect();
+            let validation = || {
+                validators.iter().fold(
+                    ValidationMiddleware::new(),
+                    |m, (message_type, validator)| {
+                        let validator = validator.clone();
+                        m.validate(message_type, move |payload| {
+                            Python::with_gil(|py| {
+                                validator
+                                    .call1(py, (json::json_to_python(py, payload)?,))
+                                    .map(|_| ())
+                            })
+                            .map_err(|e| Python::with_gil(|py| e.value(py).to_string()))
+                        })
+                    },
+                )
+            };
+            command_bus.add_middleware(validation());
+            query_bus.add_middleware(validation());
+        }
+        Ok(())
+    }
+
+    /// Dispatch a command and await its result as a dict with `success`,
+    /// `data` and `error`.
+    #[pyo3(signature = (command_type, data=None, metadata=None))]
+    pub fn dispatch_command<'py>(
+        &self,
+        py: Python<'py>,
+        command_type: &str,
+        data: Option<&PyAny>,
+        metadata: Option<std::collections::HashMap<String, String>>,
+    ) -> PyResult<&'py PyAny> {
+        let bus = self.command_bus()?;
+        let mut command = middleware::cqrs::Command::new(command_type, optional_json(py, data)?);
+        command.metadata = metadata.unwrap_or_default();
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            let result = bus.dispatch(&command).await.map_err(cqrs_error_to_py)?;
+            Python::with_gil(|py| json::json_to_python(py, &command_result_to_json(result)))
+        })
+    }
+
+    /// Execute a query and await its result as a dict with `found`, `data`
+    /// and `error`.
+    #[pyo3(signature = (query_type, params=None, metadata=None))]
+    pub fn execute_query<'py>(
+        &self,
+        py: Python<'py>,
+        query_type: &str,
+        params: Option<&PyAny>,
+        metadata: Option<std::collections::HashMap<String, String>>,
+    ) -> PyResult<&'py PyAny> {
+        let bus = self.query_bus()?;
+        let mut query = middleware::cqrs::QueryDef::new(query_type, optional_json(py, params)?);
+        query.metadata = metadata.unwrap_or_default();
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            let result = bus.execute(&query).await.map_err(cqrs_error_to_py)?;
+            Python::with_gil(|py| json::json_to_python(py, &query_result_to_json(result)))
+        })
+    }
+
+    /// Execute `(query_type, params)` pairs concurrently and await their
+    /// results, in order.
+    pub fn execute_queries<'py>(
+        &self,
+        py: Python<'py>,
+        queries: Vec<(String, Option<&PyAny>)>,
+    ) -> PyResult<&'py PyAny> {
+        let bus = self.query_bus()?;
+        let queries = queries
+            .into_iter()
+            .map(|(query_type, params)| {
+                Ok(middleware::cqrs::QueryDef::new(
+                    &query_type,
+                    optional_json(py, params)?,
+                ))
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            let results = bus
+                .execute_all(&queries)
+                .await
+                .into_iter()
+                .map(|result| result.map(query_result_to_json).map_err(cqrs_error_to_py))
+                .collect::<PyResult<Vec<_>>>()?;
+            Python::with_gil(|py| json::json_to_python(py, &serde_json::Value::Array(results)))
+        })
+    }
+
     /// Enable Saga pattern for distributed transaction orchestration.
-    #[pyo3(signature = (config=None))]
-    pub fn enable_saga(&mut self, config: Option<PySagaConfig>) {
+    ///
+    /// With `dedup` (`"memory"`, or `"redis"` to share it across workers and
+    /// restarts; rejected while `enable_redis()` is in-process only), the
+    /// results of completed steps are kept
+    /// for `dedup_ttl` seconds under their idempotency keys, and retried or
+    /// re-run steps that already completed are not run again.
+    #[pyo3(signature = (config=None, dedup=None, dedup_ttl=86400.0))]
+    pub fn enable_saga(
+        &mut self,
+        config: Option<PySagaConfig>,
+        dedup: Option<&str>,
+        dedup_ttl: f64,
+    ) -> PyResult<()> {
+        use middleware::saga::{InMemorySagaDedupStore, RedisSagaDedupStore, SagaDedupStore};
         let config = config.unwrap_or_else(PySagaConfig::default);
+        let dedup_store: Option<Arc<dyn SagaDedupStore>> = match dedup {
+            None => None,
+            Some("memory") => Some(Arc::new(InMemorySagaDedupStore::new())),
+            Some("redis") => Some(Arc::new(RedisSagaDedupStore::new(
+                self.shared_redis_client()?,
+            ))),
+            Some(other) => {
+                return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                    "unknown saga dedup store '{other}' (expected memory or redis)"
+                )))
+            }
+        };
+        let dedup_ttl = seconds_arg("dedup_ttl", dedup_ttl)?;
 
         let saga_config = middleware::saga::SagaConfig {
             max_retries: config.max_retries,
             retry_delay_ms: config.retry_delay_ms,
+            backoff_multiplier: config.backoff_multiplier,
             timeout_ms: config.timeout_ms,
             enable_logging: config.enable_logging,
+            compensation_max_retries: config.compensation_max_retries,
+            compensation_retry_delay_ms: config.compensation_retry_delay_ms,
         };
 
-        let _orchestrator = middleware::saga::SagaOrchestrator::with_config(saga_config);
+        let mut orchestrator = middleware::saga::SagaOrchestrator::with_config(saga_config);
+        if let Some(store) = &dedup_store {
+            orchestrator = orchestrator.with_dedup_store(store.clone(), dedup_ttl);
+        }
+        let orchestrator = Arc::new(orchestrator);
+        let stats = orchestrator.clone();
+        self.stats.register("saga", move || stats.stats());
+        self.saga_orchestrator = Some(orchestrator);
         println!("Saga orchestration enabled:");
         println!("   Max retries: {}", config.max_retries);
         println!("   Retry delay: {}ms", config.retry_delay_ms);
+        println!("   Backoff multiplier: {}", config.backoff_multiplier);
         println!("   Timeout: {}ms", config.timeout_ms);
+        println!(
+            "   Compensation retries: {}",
+            config.compensation_max_retries
+        );
+        if let Some(store) = &dedup_store {
+            println!("   Step dedup: {} ({}s)", store.name(), dedup_ttl.as_secs());
+        }
         println!(
             "   Logging: {}",
             if config.enable_logging {
@@ -613,122 +3128,690 @@ impl Cello {
                 "disabled"
             }
         );
+        Ok(())
     }
 
-    // ========================================================================
-    // End Advanced Pattern Features
-    // ========================================================================
+    /// Add a step to a saga, performed by `handler` and undone by `compensate`.
+    ///
+    /// The saga is created on first use and steps run in the order they are
+    /// added. Handlers may be sync or async and are called with a dict of
+    /// `execution_id`, `input` and the `results` of earlier steps; compensation
+    /// handlers also get the `step` and its `result`.
+    ///
+    /// `kind` is `"action"`, `"timer"` (waiting `delay_ms`, or until the Unix
+    /// timestamp `until_ms`) or `"signal"` (waiting for `signal_saga()` with
+    /// the step's token, for at most `timeout_ms`). Timer and signal steps
+    /// need no handler; a signal step's handler is called once with the
+    /// `signal_token` before it waits.
+    #[pyo3(signature = (saga, step, handler=None, timeout_ms=None, compensate=None, kind="action", delay_ms=None, until_ms=None))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_saga_step(
+        &self,
+        saga: &str,
+        step: &str,
+        handler: Option<PyObject>,
+        timeout_ms: Option<u64>,
+        compensate: Option<PyObject>,
+        kind: &str,
+        delay_ms: Option<u64>,
+        until_ms: Option<u64>,
+    ) -> PyResult<()> {
+        use middleware::saga::StepKind;
+        let kind = match (kind, delay_ms, until_ms) {
+            ("action", None, None) if handler.is_some() => StepKind::Action,
+            ("timer", Some(delay_ms), None) => StepKind::Delay { delay_ms },
+            ("timer", None, Some(at_ms)) => StepKind::Until { at_ms },
+            ("signal", None, None) => StepKind::Signal,
+            _ => {
+                return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                    "invalid saga step '{step}': an action needs a handler, a timer \
+                     exactly one of delay_ms and until_ms, and a signal neither"
+                )))
+            }
+        };
+        let orchestrator = self.saga_orchestrator()?;
+        let mut step_def = middleware::saga::SagaStepDef::new(step).with_kind(kind);
+        if let Some(timeout_ms) = timeout_ms {
+            step_def = step_def.with_timeout(timeout_ms);
+        }
+        orchestrator.add_step(saga, step_def);
+        if let Some(handler) = handler {
+            orchestrator
+                .register_step_handler(
+                    saga,
+                    step,
+                    middleware::saga::SagaStepHandler::python(handler),
+                )
+                .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        }
+        if let Some(compensate) = compensate {
+            orchestrator
+                .register_compensation(
+                    saga,
+                    step,
+                    middleware::saga::SagaStepHandler::python(compensate),
+                )
+                .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// Run a saga and await its final execution, returned as a dict.
+    ///
+    /// Failed steps are retried per the saga config before the execution is
+    /// compensated; the cause is reported under `error` and compensations that
+    /// kept failing under `compensation_errors`. Step idempotency keys derive
+    /// from `key`, or from a random UUID.
+    #[pyo3(signature = (saga, input=None, key=None))]
+    pub fn run_saga<'py>(
+        &self,
+        py: Python<'py>,
+        saga: String,
+        input: Option<&PyAny>,
+        key: Option<String>,
+    ) -> PyResult<&'py PyAny> {
+        let orchestrator = self.saga_orchestrator()?;
+        let input = match input {
+            Some(input) => {
+                json::python_to_json(py, input).map_err(pyo3::exceptions::PyValueError::new_err)?
+            }
+            None => serde_json::Value::Null,
+        };
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            saga_execution_to_py(
+                orchestrator
+                    .run_with_key(&saga, input, key.as_deref())
+                    .await,
+            )
+        })
+    }
+
+    /// List saga executions, newest first.
+    ///
+    /// Filters by saga name, status and start time (Unix seconds, `since`
+    /// inclusive, `until` exclusive). Returns a dict with `executions`,
+    /// `total`, `offset` and `limit`.
+    #[pyo3(signature = (saga=None, status=None, since=None, until=None, offset=0, limit=None))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn saga_executions(
+        &self,
+        py: Python<'_>,
+        saga: Option<String>,
+        status: Option<&str>,
+        since: Option<u64>,
+        until: Option<u64>,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> PyResult<PyObject> {
+        let orchestrator = self.saga_orchestrator()?;
+        let query = middleware::saga::ExecutionQuery {
+            saga_name: saga,
+            status: status
+                .map(str::parse)
+                .transpose()
+                .map_err(pyo3::exceptions::PyValueError::new_err)?,
+            started_after: since,
+            started_before: until,
+            offset,
+            limit,
+        };
+        let page = serde_json::to_value(orchestrator.query_executions(&query))
+            .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))?;
+        json::json_to_python(py, &page)
+    }
+
+    /// Get a saga execution as a dict, or `None` if it is unknown.
+    pub fn saga_execution(&self, py: Python<'_>, execution_id: &str) -> PyResult<PyObject> {
+        let orchestrator = self.saga_orchestrator()?;
+        match orchestrator.get_execution(execution_id) {
+            Ok(execution) => {
+                let value = serde_json::to_value(&execution)
+                    .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))?;
+                json::json_to_python(py, &value)
+            }
+            Err(_) => Ok(py.None()),
+        }
+    }
+
+    /// Continue a running saga execution from its first unfinished step.
+    pub fn resume_saga<'py>(&self, py: Python<'py>, execution_id: String) -> PyResult<&'py PyAny> {
+        let orchestrator = self.saga_orchestrator()?;
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            saga_execution_to_py(orchestrator.resume_execution(&execution_id).await)
+        })
+    }
+
+    /// Complete the saga step waiting for `token` with `payload`.
+    ///
+    /// Raises `ValueError` if no step is waiting for the token.
+    #[pyo3(signature = (token, payload=None))]
+    pub fn signal_saga(
+        &self,
+        py: Python<'_>,
+        token: &str,
+        payload: Option<&PyAny>,
+    ) -> PyResult<()> {
+        let orchestrator = self.saga_orchestrator()?;
+        orchestrator
+            .signal(token, optional_json(py, payload)?)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))
+    }
+
+    /// Track a saga execution saved as a dict by `saga_execution()`, for
+    /// example before a restart. Resume it with `resume_saga()`.
+    pub fn restore_saga(&self, py: Python<'_>, execution: &PyAny) -> PyResult<()> {
+        let orchestrator = self.saga_orchestrator()?;
+        let value =
+            json::python_to_json(py, execution).map_err(pyo3::exceptions::PyValueError::new_err)?;
+        let execution = serde_json::from_value(value)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        orchestrator
+            .restore_execution(execution)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))
+    }
+
+    /// Compensate a stuck saga execution, or retry its failed compensations.
+    #[pyo3(signature = (execution_id, reason="forced compensation".to_string()))]
+    pub fn force_compensate_saga<'py>(
+        &self,
+        py: Python<'py>,
+        execution_id: String,
+        reason: String,
+    ) -> PyResult<&'py PyAny> {
+        let orchestrator = self.saga_orchestrator()?;
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            saga_execution_to_py(orchestrator.force_compensate(&execution_id, &reason).await)
+        })
+    }
+
+    /// Export a registered saga definition.
+    ///
+    /// `format` is `"json"` for a state-machine dict, or `"dot"` / `"mermaid"`
+    /// for diagram source as a string.
+    #[pyo3(signature = (saga, format="json"))]
+    pub fn export_saga(&self, py: Python<'_>, saga: &str, format: &str) -> PyResult<PyObject> {
+        let orchestrator = self.saga_orchestrator()?;
+        let definition = orchestrator
+            .definition(saga)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        match format {
+            "json" => {
+                let value = serde_json::to_value(definition.state_machine())
+                    .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))?;
+                json::json_to_python(py, &value)
+            }
+            "dot" => Ok(definition.to_dot().into_py(py)),
+            "mermaid" => Ok(definition.to_mermaid().into_py(py)),
+            other => Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "unknown saga export format: {other}"
+            ))),
+        }
+    }
+
+    // ========================================================================
+    // End Advanced Pattern Features
+    // ========================================================================
+
+    /// Enable OpenAPI documentation endpoints.
+    /// This adds:
+    /// - GET /docs - Swagger UI
+    /// - GET /redoc - ReDoc documentation
+    /// - GET /openapi.json - OpenAPI 3.1 JSON schema generated from the router
+    #[pyo3(signature = (title=None, version=None))]
+    pub fn enable_openapi(
+        &mut self,
+        py: Python<'_>,
+        title: Option<String>,
+        version: Option<String>,
+    ) -> PyResult<()> {
+        let title = title.unwrap_or_else(|| "Cello API".to_string());
+        let version = version.unwrap_or_else(|| "1.0.1".to_string());
+
+        self.openapi = self.openapi.clone().title(&title).version(&version);
+
+        let docs_handler = Py::new(
+            py,
+            openapi::DocsPageHandler::new(openapi::swagger_ui_html("/openapi.json", &title)),
+        )?;
+        let redoc_handler = Py::new(
+            py,
+            openapi::DocsPageHandler::new(openapi::redoc_html("/openapi.json", &title)),
+        )?;
+        let openapi_handler = Py::new(py, openapi::OpenApiSpecHandler::new(self.openapi.clone()))?;
+
+        self.add_route("GET", "/docs", docs_handler.into_py(py))?;
+        self.add_route("GET", "/redoc", redoc_handler.into_py(py))?;
+        self.add_route("GET", "/openapi.json", openapi_handler.into_py(py))?;
+
+        println!("📚 OpenAPI docs enabled:");
+        println!("   Swagger UI: /docs");
+        println!("   ReDoc:      /redoc");
+        println!("   OpenAPI:    /openapi.json");
+
+        Ok(())
+    }
+
+    /// Attach OpenAPI metadata to a route.
+    ///
+    /// Accepted keywords: `summary`, `description`, `tags`, `request_schema`,
+    /// `response_schema`, `security`, `deprecated` and `operation_id`. Schemas
+    /// are JSON Schema dicts, e.g. from a Pydantic model's `model_json_schema()`.
+    #[pyo3(signature = (method, path, **metadata))]
+    pub fn register_openapi_route(
+        &self,
+        py: Python<'_>,
+        method: &str,
+        path: &str,
+        metadata: Option<&pyo3::types::PyDict>,
+    ) -> PyResult<()> {
+        let mut route = openapi::RouteMetadata::new(method, path);
+
+        if let Some(metadata) = metadata {
+            for (key, value) in metadata.iter() {
+                if value.is_none() {
+                    continue;
+                }
+                let key: &str = key.extract()?;
+                match key {
+                    "summary" => route.summary = Some(value.extract()?),
+                    "description" => route.description = Some(value.extract()?),
+                    "tags" => route.tags = value.extract()?,
+                    "security" => route.security = value.extract()?,
+                    "deprecated" => route.deprecated = value.extract()?,
+                    "operation_id" => route.operation_id = Some(value.extract()?),
+                    "request_schema" => {
+                        route.request_schema = Some(
+                            json::python_to_json(py, value)
+                                .map_err(pyo3::exceptions::PyValueError::new_err)?,
+                        )
+                    }
+                    "response_schema" => {
+                        route.response_schema = Some(
+                            json::python_to_json(py, value)
+                                .map_err(pyo3::exceptions::PyValueError::new_err)?,
+                        )
+                    }
+                    other => {
+                        return Err(pyo3::exceptions::PyTypeError::new_err(format!(
+                            "Unknown OpenAPI metadata field: {other}"
+                        )))
+                    }
+                }
+            }
+        }
+
+        self.openapi.register_route(route);
+        Ok(())
+    }
+
+    /// Attach JSON Schemas that requests to a route must satisfy.
+    ///
+    /// Invalid requests are rejected in Rust with a 422 response before the
+    /// handler runs. Query and path parameters are coerced to the property
+    /// types declared in their object schemas.
+    #[pyo3(signature = (method, path, body=None, query=None, params=None))]
+    pub fn set_route_validation(
+        &self,
+        py: Python<'_>,
+        method: &str,
+        path: &str,
+        body: Option<&PyAny>,
+        query: Option<&PyAny>,
+        params: Option<&PyAny>,
+    ) -> PyResult<()> {
+        let to_value = |schema: &PyAny| {
+            json::python_to_json(py, schema).map_err(pyo3::exceptions::PyValueError::new_err)
+        };
+
+        let mut validator = validation::RequestValidator::new();
+        if let Some(schema) = body {
+            validator = validator
+                .body(&to_value(schema)?)
+                .map_err(pyo3::exceptions::PyValueError::new_err)?;
+        }
+        if let Some(schema) = query {
+            validator = validator
+                .query(&to_value(schema)?)
+                .map_err(pyo3::exceptions::PyValueError::new_err)?;
+        }
+        if let Some(schema) = params {
+            validator = validator
+                .params(&to_value(schema)?)
+                .map_err(pyo3::exceptions::PyValueError::new_err)?;
+        }
+        if validator.is_empty() {
+            return Ok(());
+        }
+
+        let handler = self.router.route_handler(method, path).ok_or_else(|| {
+            pyo3::exceptions::PyValueError::new_err(format!(
+                "No route registered for {method} {path}"
+            ))
+        })?;
+        handler
+            .set_validator(validator)
+            .map_err(pyo3::exceptions::PyValueError::new_err)
+    }
+
+    /// Bind protobuf message types to a registered route.
+    ///
+    /// `application/x-protobuf` request bodies are decoded as `request_type`
+    /// (available via `request.data()`), and clients sending
+    /// `Accept: application/x-protobuf` get results encoded as `response_type`.
+    #[pyo3(signature = (method, path, request_type=None, response_type=None))]
+    pub fn set_route_protobuf(
+        &self,
+        method: &str,
+        path: &str,
+        request_type: Option<&str>,
+        response_type: Option<&str>,
+    ) -> PyResult<()> {
+        if request_type.is_none() && response_type.is_none() {
+            return Ok(());
+        }
+        let binding = middleware::protobuf::ProtoBinding::new(
+            self.proto_descriptors.clone(),
+            request_type,
+            response_type,
+        )
+        .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+
+        let handler = self.router.route_handler(method, path).ok_or_else(|| {
+            pyo3::exceptions::PyValueError::new_err(format!(
+                "No route registered for {method} {path}"
+            ))
+        })?;
+        handler
+            .set_protobuf(binding)
+            .map_err(pyo3::exceptions::PyValueError::new_err)
+    }
+
+    /// Verify inbound webhook signatures on a registered route.
+    ///
+    /// `scheme` is `"stripe"`, `"github"`, `"slack"` or `"cello"`. Requests
+    /// with a missing or invalid signature, a timestamp more than
+    /// `tolerance` seconds off, or a signature already accepted within
+    /// `replay_window` seconds are rejected with 401 before any Python code
+    /// runs. Extra `secrets` are accepted too, for key rotation.
+    #[pyo3(signature = (method, path, scheme, secrets, tolerance=300.0, replay_window=Some(300.0)))]
+    pub fn set_route_webhook(
+        &self,
+        method: &str,
+        path: &str,
+        scheme: &str,
+        secrets: Vec<String>,
+        tolerance: f64,
+        replay_window: Option<f64>,
+    ) -> PyResult<()> {
+        let scheme: middleware::webhook_verify::SignatureScheme = scheme
+            .parse()
+            .map_err(pyo3::exceptions::PyValueError::new_err)?;
+        let (first, rest) = secrets.split_first().ok_or_else(|| {
+            pyo3::exceptions::PyValueError::new_err("At least one webhook secret is required")
+        })?;
+        let replay_window = replay_window
+            .map(|secs| seconds_arg("replay_window", secs))
+            .transpose()?;
+        let verifier = rest.iter().fold(
+            middleware::webhook_verify::WebhookVerifier::new(scheme, first),
+            |verifier, secret| verifier.with_secret(secret),
+        );
+        let verifier = verifier
+            .with_tolerance(seconds_arg("tolerance", tolerance)?)
+            .with_replay_window(replay_window);
+
+        let handler = self.router.route_handler(method, path).ok_or_else(|| {
+            pyo3::exceptions::PyValueError::new_err(format!(
+                "No route registered for {method} {path}"
+            ))
+        })?;
+        handler
+            .set_webhook(verifier)
+            .map_err(pyo3::exceptions::PyValueError::new_err)
+    }
+
+    /// Run each request to a registered route in a database transaction.
+    ///
+    /// The transaction is opened on the `enable_database()` pool before the
+    /// handler runs and replaces the pool as its `"database"` dependency (it
+    /// is also available as `"transaction"`). It is committed when the
+    /// handler returns a 2xx or 3xx response and rolled back when it raises
+    /// or returns any other status. `isolation` is `"read_uncommitted"`,
+    /// `"read_committed"`, `"repeatable_read"` or `"serializable"`.
+    #[pyo3(signature = (method, path, isolation=None))]
+    pub fn set_route_transaction(
+        &self,
+        method: &str,
+        path: &str,
+        isolation: Option<&str>,
+    ) -> PyResult<()> {
+        let mut policy = middleware::transaction::TransactionPolicy::new();
+        if let Some(isolation) = isolation {
+            policy = policy.with_isolation(
+                isolation
+                    .parse()
+                    .map_err(pyo3::exceptions::PyValueError::new_err)?,
+            );
+        }
+
+        let handler = self.router.route_handler(method, path).ok_or_else(|| {
+            pyo3::exceptions::PyValueError::new_err(format!(
+                "No route registered for {method} {path}"
+            ))
+        })?;
+        handler
+            .set_transaction(policy)
+            .map_err(pyo3::exceptions::PyValueError::new_err)
+    }
+
+    /// Stream the request body of a registered route to its handler.
+    ///
+    /// The body is not buffered; the handler reads it with
+    /// `request.stream()` or `await request.read()`. Routes that verify
+    /// webhook signatures cannot stream.
+    pub fn set_route_stream_body(&self, method: &str, path: &str) -> PyResult<()> {
+        let handler = self.router.route_handler(method, path).ok_or_else(|| {
+            pyo3::exceptions::PyValueError::new_err(format!(
+                "No route registered for {method} {path}"
+            ))
+        })?;
+        handler
+            .set_stream_body()
+            .map_err(pyo3::exceptions::PyValueError::new_err)
+    }
+
+    /// Declare what a registered route's handler always returns.
+    ///
+    /// `format` is `"json"`, `"text"` or `"bytes"`. Results are serialized
+    /// without inspecting their Python type; a result of another type falls
+    /// back to the usual handling.
+    pub fn set_route_response_format(
+        &self,
+        method: &str,
+        path: &str,
+        format: &str,
+    ) -> PyResult<()> {
+        let preset: handler::ResponsePreset = format
+            .parse()
+            .map_err(pyo3::exceptions::PyValueError::new_err)?;
+        let handler = self.router.route_handler(method, path).ok_or_else(|| {
+            pyo3::exceptions::PyValueError::new_err(format!(
+                "No route registered for {method} {path}"
+            ))
+        })?;
+        handler
+            .set_response_preset(preset)
+            .map_err(pyo3::exceptions::PyValueError::new_err)
+    }
+
+    /// Apply a header policy to each response of a registered route.
+    ///
+    /// `cache` is a preset (`"no-store"`, `"no-cache"`, `"private"`,
+    /// `"public"`, `"immutable"`, combined with `max_age` seconds) or a raw
+    /// `Cache-Control` value; it is only set on responses below 400. `vary`
+    /// is merged into the response's `Vary`. Headers set by the handler win.
+    #[pyo3(signature = (method, path, cache=None, max_age=None, vary=None, headers=None))]
+    pub fn set_route_headers(
+        &self,
+        method: &str,
+        path: &str,
+        cache: Option<&str>,
+        max_age: Option<u64>,
+        vary: Option<Vec<String>>,
+        headers: Option<Vec<(String, String)>>,
+    ) -> PyResult<()> {
+        let mut policy = response::HeaderPolicy::new();
+        match cache {
+            Some(cache) => {
+                policy = policy
+                    .cache(cache, max_age)
+                    .map_err(pyo3::exceptions::PyValueError::new_err)?;
+            }
+            None if max_age.is_some() => {
+                return Err(pyo3::exceptions::PyValueError::new_err(
+                    "max_age requires a cache preset",
+                ));
+            }
+            None => {}
+        }
+        policy = policy.vary(vary.unwrap_or_default());
+        for (name, value) in headers.unwrap_or_default() {
+            policy = policy.header(&name, &value);
+        }
+
+        let handler = self.router.route_handler(method, path).ok_or_else(|| {
+            pyo3::exceptions::PyValueError::new_err(format!(
+                "No route registered for {method} {path}"
+            ))
+        })?;
+        handler
+            .set_header_policy(policy)
+            .map_err(pyo3::exceptions::PyValueError::new_err)
+    }
 
-    /// Enable OpenAPI documentation endpoints.
-    /// This adds:
-    /// - GET /docs - Swagger UI
-    /// - GET /redoc - ReDoc documentation
-    /// - GET /openapi.json - OpenAPI JSON schema
-    #[pyo3(signature = (title=None, version=None))]
-    pub fn enable_openapi(
+    /// Send `weight` percent of a registered route's requests to a canary
+    /// `handler`; the route's handler serves the rest.
+    ///
+    /// Clients pick a version with the `header` or `cookie` (`canary` or
+    /// `stable`). The route's middleware, guards and validation apply to both.
+    #[pyo3(signature = (method, path, handler, weight=5.0, header=Some("X-Canary"), cookie=None))]
+    pub fn set_route_canary(
         &mut self,
-        py: Python<'_>,
-        title: Option<String>,
-        version: Option<String>,
+        method: &str,
+        path: &str,
+        handler: PyObject,
+        weight: f64,
+        header: Option<&str>,
+        cookie: Option<&str>,
     ) -> PyResult<()> {
-        let title = title.unwrap_or_else(|| "Cello API".to_string());
-        let version = version.unwrap_or_else(|| "1.0.1".to_string());
+        if !(0.0..=100.0).contains(&weight) {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "weight must be between 0 and 100",
+            ));
+        }
+        let route = self.router.route_handler(method, path).ok_or_else(|| {
+            pyo3::exceptions::PyValueError::new_err(format!(
+                "No route registered for {method} {path}"
+            ))
+        })?;
+        let (_, meta) = self.handlers.register_with_meta(handler);
+        let mut canary = routing::Canary::new(meta, weight);
+        if let Some(header) = header {
+            canary = canary.with_header(header);
+        }
+        if let Some(cookie) = cookie {
+            canary = canary.with_cookie(cookie);
+        }
+        route
+            .set_canary(canary)
+            .map_err(pyo3::exceptions::PyValueError::new_err)
+    }
 
-        // Store title and version for later use
-        let title_clone = title.clone();
-        let version_clone = version.clone();
-
-        // Create a Python handler for /docs (Swagger UI)
-        let docs_code = format!(
-            r#"
-def docs_handler(request):
-    from cello import Response
-    html = '''<!DOCTYPE html>
-<html lang="en">
-<head>
-    <meta charset="UTF-8">
-    <meta name="viewport" content="width=device-width, initial-scale=1.0">
-    <title>{title_clone} - Swagger UI</title>
-    <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5.9.0/swagger-ui.css" />
-    <style>
-        body {{ margin: 0; padding: 0; }}
-        .swagger-ui .topbar {{ display: none; }}
-    </style>
-</head>
-<body>
-    <div id="swagger-ui"></div>
-    <script src="https://unpkg.com/swagger-ui-dist@5.9.0/swagger-ui-bundle.js"></script>
-    <script>
-        window.onload = () => {{
-            window.ui = SwaggerUIBundle({{
-                url: "/openapi.json",
-                dom_id: '#swagger-ui',
-                deepLinking: true,
-                presets: [SwaggerUIBundle.presets.apis, SwaggerUIBundle.SwaggerUIStandalonePreset],
-                layout: "StandaloneLayout"
-            }});
-        }};
-    </script>
-</body>
-</html>'''
-    return Response.html(html)
-"#
-        );
+    /// Change the share of a route's requests sent to its canary.
+    pub fn set_canary_weight(&self, method: &str, path: &str, weight: f64) -> PyResult<()> {
+        if !(0.0..=100.0).contains(&weight) {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "weight must be between 0 and 100",
+            ));
+        }
+        self.route_canary(method, path)?.set_weight(weight);
+        Ok(())
+    }
 
-        // Create /redoc handler
-        let redoc_code = format!(
-            r#"
-def redoc_handler(request):
-    from cello import Response
-    html = '''<!DOCTYPE html>
-<html lang="en">
-<head>
-    <meta charset="UTF-8">
-    <meta name="viewport" content="width=device-width, initial-scale=1.0">
-    <title>{title_clone} - ReDoc</title>
-    <link href="https://fonts.googleapis.com/css?family=Montserrat:300,400,700|Roboto:300,400,700" rel="stylesheet">
-    <style>body {{ margin: 0; padding: 0; }}</style>
-</head>
-<body>
-    <redoc spec-url="/openapi.json"></redoc>
-    <script src="https://cdn.redoc.ly/redoc/latest/bundles/redoc.standalone.js"></script>
-</body>
-</html>'''
-    return Response.html(html)
-"#
-        );
+    /// Requests, errors and latency of a route's stable and canary handlers.
+    pub fn canary_stats(&self, py: Python<'_>, method: &str, path: &str) -> PyResult<PyObject> {
+        to_python(py, &self.route_canary(method, path)?.snapshot())
+    }
 
-        // Create /openapi.json handler
-        let openapi_code = format!(
-            r#"
-def openapi_handler(request):
-    return {{
-        "openapi": "3.0.3",
-        "info": {{
-            "title": "{title_clone}",
-            "version": "{version_clone}",
-            "description": "{title_clone} - Powered by Cello Framework"
-        }},
-        "paths": {{}}
-    }}
-"#
-        );
+    /// Disable a registered route while the server runs.
+    ///
+    /// New requests are answered with `status` (503, with `retry_after`
+    /// seconds in `Retry-After`, or 404) without running middleware or the
+    /// handler; requests already being handled finish. The toggle is
+    /// recorded in the audit log with its `reason` and `actor`.
+    #[pyo3(signature = (method, path, status=503, retry_after=None, reason=None, actor=None))]
+    pub fn disable_route(
+        &self,
+        method: &str,
+        path: &str,
+        status: u16,
+        retry_after: Option<u32>,
+        reason: Option<&str>,
+        actor: Option<&str>,
+    ) -> PyResult<()> {
+        let disabled = routing::Disabled::new(status, retry_after)
+            .map_err(pyo3::exceptions::PyValueError::new_err)?;
+        self.toggle_route(method, path, Some(disabled), reason, actor)
+    }
 
-        // Execute Python code and register handlers
-        let docs_handler = py.eval(&format!("{docs_code}\ndocs_handler"), None, None)?;
-        let redoc_handler = py.eval(&format!("{redoc_code}\nredoc_handler"), None, None)?;
-        let openapi_handler = py.eval(&format!("{openapi_code}\nopenapi_handler"), None, None)?;
+    /// Enable a route disabled with `disable_route()`.
+    #[pyo3(signature = (method, path, reason=None, actor=None))]
+    pub fn enable_route(
+        &self,
+        method: &str,
+        path: &str,
+        reason: Option<&str>,
+        actor: Option<&str>,
+    ) -> PyResult<()> {
+        self.toggle_route(method, path, None, reason, actor)
+    }
 
-        self.add_route("GET", "/docs", docs_handler.into())?;
-        self.add_route("GET", "/redoc", redoc_handler.into())?;
-        self.add_route("GET", "/openapi.json", openapi_handler.into())?;
+    /// The disabled routes, with the status they answer with.
+    pub fn disabled_routes(&self, py: Python<'_>) -> PyResult<PyObject> {
+        #[derive(serde::Serialize)]
+        struct DisabledRoute {
+            method: String,
+            path: String,
+            #[serde(flatten)]
+            disabled: routing::Disabled,
+        }
 
-        println!("📚 OpenAPI docs enabled:");
-        println!("   Swagger UI: /docs");
-        println!("   ReDoc:      /redoc");
-        println!("   OpenAPI:    /openapi.json");
+        let routes: Vec<DisabledRoute> = self
+            .router
+            .routes()
+            .into_iter()
+            .filter_map(|(method, path)| {
+                let disabled = self.router.route_handler(&method, &path)?.disabled()?;
+                Some(DisabledRoute {
+                    method,
+                    path,
+                    disabled,
+                })
+            })
+            .collect();
+        to_python(py, &routes)
+    }
 
-        Ok(())
+    /// Audit log of routes disabled and enabled, oldest first.
+    pub fn route_toggles(&self, py: Python<'_>) -> PyResult<PyObject> {
+        to_python(py, &self.route_toggles.entries())
+    }
+
+    /// Get the generated OpenAPI specification as a JSON string.
+    pub fn openapi_json(&self) -> PyResult<String> {
+        self.openapi
+            .to_json()
+            .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))
     }
 
     /// Start the HTTP server.
@@ -740,19 +3823,87 @@ def openapi_handler(request):
         port: Option<u16>,
         workers: Option<usize>,
     ) -> PyResult<()> {
-        let host_owned = host.unwrap_or("127.0.0.1").to_string();
-        let port = port.unwrap_or(8000);
-
-        // Clone everything needed inside the Send + 'static async block.
-        let router = self.router.clone();
-        let handlers = self.handlers.clone();
-        let middleware = self.middleware.clone();
-        let websocket_handlers = self.websocket_handlers.clone();
-        let dependency_container = self.dependency_container.clone();
-        let guards = self.guards.clone();
-        let prometheus = self.prometheus.clone();
-        let startup_handlers = self.startup_handlers.clone();
-        let shutdown_handlers = self.shutdown_handlers.clone();
+        let mut config =
+            server::ServerConfig::new(host.unwrap_or("127.0.0.1"), port.unwrap_or(8000));
+        config.workers = workers.unwrap_or(0);
+        let host = config.host.clone();
+        let mut server = self.server(config);
+
+        // Coroutines from async handlers, hooks and jobs run on one
+        // persistent asyncio loop
+        event_loop::start(py)?;
+
+        // Scheduled jobs start after the startup handlers and stop before
+        // the shutdown handlers.
+        let mut lifecycle = lifecycle::ServerLifecycle::new();
+        for hook in self.lifecycle.startup_hooks() {
+            lifecycle.on_startup(hook.clone());
+        }
+        let scheduler = self.scheduler.clone();
+        lifecycle.on_startup(lifecycle::ServerHook::new("scheduler", move || {
+            scheduler.start();
+            async { Ok(()) }
+        }));
+        let scheduler = self.scheduler.clone();
+        lifecycle.on_shutdown(lifecycle::ServerHook::new("scheduler", move || {
+            let scheduler = scheduler.clone();
+            async move {
+                scheduler.stop().await;
+                Ok(())
+            }
+        }));
+        for hook in self.lifecycle.shutdown_hooks() {
+            lifecycle.on_shutdown(hook.clone());
+        }
+        // Certificates are loaded from the cache or ordered once the server
+        // is up; only the leader process talks to the CA.
+        if let Some(config) = self.acme.clone() {
+            let store = Arc::new(server::tls::CertStore::new());
+            server = server.with_certificates(store.clone());
+            // On a shared port the redirect listener answers HTTP-01 challenges
+            let shared_port = self.redirect_http.as_ref().map(|r| r.port) == Some(config.http_port);
+            let manager = Arc::new(server::acme::AcmeManager::new(config, store));
+            if shared_port {
+                server = server.with_http_challenges(manager.clone());
+            }
+            let issue = self.scheduler.is_leader();
+            let stop = tokio_util::sync::CancellationToken::new();
+            let stopped = stop.clone();
+            lifecycle.on_startup(lifecycle::ServerHook::new("acme", move || {
+                let manager = manager.clone();
+                let stop = stop.clone();
+                let host = host.clone();
+                async move {
+                    let challenge_host = (!shared_port).then_some(host.as_str());
+                    manager.start(challenge_host, issue, stop).await
+                }
+            }));
+            lifecycle.on_shutdown(lifecycle::ServerHook::new("acme", move || {
+                stopped.cancel();
+                async { Ok(()) }
+            }));
+        }
+        if let Some(reloader) = self.reloader.clone() {
+            let routes = server.routes();
+            let watcher = reloader.clone();
+            lifecycle.on_startup(lifecycle::ServerHook::new("reloader", move || {
+                let result = watcher.start(routes.clone()).map_err(|e| e.to_string());
+                async move { result }
+            }));
+            lifecycle.on_shutdown(lifecycle::ServerHook::new("reloader", move || {
+                reloader.stop();
+                async { Ok(()) }
+            }));
+        }
+
+        // Records queued by request loggers are written before exit
+        lifecycle.on_shutdown(lifecycle::ServerHook::new("logger", || async {
+            let _ = tokio::task::spawn_blocking(|| {
+                request::logger::flush(std::time::Duration::from_secs(1))
+            })
+            .await;
+            Ok(())
+        }));
 
         // Release the GIL and run a native Tokio current-thread runtime.
         //
@@ -767,53 +3918,179 @@ def openapi_handler(request):
                 .enable_all()
                 .build()
                 .expect("failed to build Tokio runtime")
-                .block_on(async move {
-                    let mut config = server::ServerConfig::new(&host_owned, port);
-                    config.workers = workers.unwrap_or(0);
-
-                    let server = Server::new(
-                        config,
-                        router,
-                        handlers,
-                        middleware,
-                        websocket_handlers,
-                        dependency_container,
-                        guards,
-                        prometheus,
-                    );
-
-                    // Startup hooks
-                    for handler in &startup_handlers {
-                        if let Err(e) = run_lifecycle_handler_async(handler.clone()).await {
-                            eprintln!("Error in startup handler: {e}");
-                        }
-                    }
-
-                    let _ = server.run().await;
-
-                    // Shutdown hooks
-                    for handler in &shutdown_handlers {
-                        match run_lifecycle_handler_async(handler.clone()).await {
-                            Err(e) if !e.to_string().contains("KeyboardInterrupt") => {
-                                eprintln!("Error in shutdown handler: {e}");
-                            }
-                            _ => {}
-                        }
-                    }
-                })
-        });
-        Ok(())
+                .block_on(server.with_lifecycle(lifecycle).run())
+        })
     }
 
     /// Internal route registration.
     fn add_route(&mut self, method: &str, path: &str, handler: PyObject) -> PyResult<()> {
-        let handler_id = self.handlers.register(handler);
+        let (handler_id, meta) = self.handlers.register_with_meta(handler);
         self.router
-            .add_route(method, path, handler_id)
+            .add_handler_route(method, path, handler_id, meta)
             .map_err(pyo3::exceptions::PyValueError::new_err)
     }
 }
 
+impl Cello {
+    /// The canary of a registered route.
+    fn route_canary(&self, method: &str, path: &str) -> PyResult<Arc<routing::Canary>> {
+        self.router
+            .route_handler(method, path)
+            .and_then(|handler| handler.canary().cloned())
+            .ok_or_else(|| {
+                pyo3::exceptions::PyValueError::new_err(format!(
+                    "No canary configured for {method} {path}"
+                ))
+            })
+    }
+
+    /// Disable (or enable, with `None`) a registered route and record it.
+    fn toggle_route(
+        &self,
+        method: &str,
+        path: &str,
+        disabled: Option<routing::Disabled>,
+        reason: Option<&str>,
+        actor: Option<&str>,
+    ) -> PyResult<()> {
+        let handler = self.router.route_handler(method, path).ok_or_else(|| {
+            pyo3::exceptions::PyValueError::new_err(format!(
+                "No route registered for {method} {path}"
+            ))
+        })?;
+        handler.set_disabled(disabled);
+        self.route_toggles.record(routing::RouteToggle::now(
+            method, path, disabled, reason, actor,
+        ));
+        Ok(())
+    }
+
+    /// A server for this app's routes, middleware and handlers.
+    fn server(&self, mut config: server::ServerConfig) -> Server {
+        self.route_middleware.compile(&self.router);
+        config.throttle = self.throttle.clone();
+        config.slow_request_threshold = self.slow_request_threshold;
+        config.server_timing = self.server_timing;
+        config.json_etags = self.json_etags;
+        config.query_array_syntax = self.query_array_syntax;
+        config.response_checks = self.response_checks.clone();
+        config.stats_endpoint = self.stats_endpoint.clone();
+        config.recorder = self.recorder.clone();
+        config.method_override = self.method_override.clone();
+        config.redirect_http = self.redirect_http.clone();
+        let server = Server::new(
+            config,
+            self.router.clone(),
+            self.handlers.clone(),
+            self.middleware.clone(),
+            self.websocket_handlers.clone(),
+            self.dependency_container.clone(),
+            self.guards.clone(),
+            self.prometheus.clone(),
+        )
+        .with_connection_metrics(self.connections.clone());
+        let server = match &self.database {
+            Some(pool) => server.with_database(pool.clone()),
+            None => server,
+        };
+        server.with_stats(self.stats.clone())
+    }
+
+    /// The StatsD client created by `enable_statsd`.
+    fn statsd(&self) -> PyResult<&Arc<middleware::statsd::StatsdClient>> {
+        self.statsd.as_ref().ok_or_else(|| {
+            pyo3::exceptions::PyRuntimeError::new_err(
+                "StatsD is not enabled; call enable_statsd() first",
+            )
+        })
+    }
+
+    /// The recorder created by `enable_recording`.
+    fn recorder(&self) -> PyResult<&Arc<server::recording::Recorder>> {
+        self.recorder.as_ref().ok_or_else(|| {
+            pyo3::exceptions::PyRuntimeError::new_err(
+                "Recording is not enabled; call enable_recording() first",
+            )
+        })
+    }
+
+    /// The gRPC server created by `enable_grpc`.
+    fn grpc_server(&self) -> PyResult<Arc<middleware::grpc::GrpcServer>> {
+        self.grpc_server.clone().ok_or_else(|| {
+            pyo3::exceptions::PyRuntimeError::new_err(
+                "gRPC is not enabled; call enable_grpc() first",
+            )
+        })
+    }
+
+    fn sql_pool(&self) -> PyResult<Arc<middleware::sql::SqlPool>> {
+        self.database.clone().ok_or_else(|| {
+            pyo3::exceptions::PyRuntimeError::new_err(
+                "Database is not enabled; call enable_database() first",
+            )
+        })
+    }
+
+    fn webhooks(&self) -> PyResult<Arc<webhooks::WebhookDispatcher>> {
+        self.webhooks.clone().ok_or_else(|| {
+            pyo3::exceptions::PyRuntimeError::new_err(
+                "Webhooks are not enabled; call enable_webhooks() first",
+            )
+        })
+    }
+
+    fn event_store(&self) -> PyResult<Arc<dyn middleware::eventsourcing::EventStore>> {
+        self.event_store.clone().ok_or_else(|| {
+            pyo3::exceptions::PyRuntimeError::new_err(
+                "Event sourcing is not enabled; call enable_event_sourcing() first",
+            )
+        })
+    }
+
+    fn command_bus(&self) -> PyResult<Arc<middleware::cqrs::CommandBus>> {
+        self.command_bus.clone().ok_or_else(|| {
+            pyo3::exceptions::PyRuntimeError::new_err(
+                "CQRS is not enabled; call enable_cqrs() first",
+            )
+        })
+    }
+
+    fn query_bus(&self) -> PyResult<Arc<middleware::cqrs::QueryBus>> {
+        self.query_bus.clone().ok_or_else(|| {
+            pyo3::exceptions::PyRuntimeError::new_err(
+                "CQRS is not enabled; call enable_cqrs() first",
+            )
+        })
+    }
+
+    fn saga_orchestrator(&self) -> PyResult<Arc<middleware::saga::SagaOrchestrator>> {
+        self.saga_orchestrator.clone().ok_or_else(|| {
+            pyo3::exceptions::PyRuntimeError::new_err(
+                "Sagas are not enabled; call enable_saga() first",
+            )
+        })
+    }
+
+    /// The Redis client for a `"redis"` backend, which is only useful if
+    /// every worker shares it. The client from `enable_redis()` is
+    /// in-process, so it is rejected rather than silently giving each
+    /// worker its own data.
+    fn shared_redis_client(&self) -> PyResult<Arc<dyn middleware::redis::RedisClient>> {
+        let client = self.redis_client.clone().ok_or_else(|| {
+            pyo3::exceptions::PyRuntimeError::new_err(
+                "Redis is not enabled; call enable_redis() first",
+            )
+        })?;
+        if client.is_in_process() {
+            return Err(pyo3::exceptions::PyRuntimeError::new_err(
+                "The redis backend needs a Redis server shared by all workers, but \
+                 enable_redis() only provides an in-process client; use \"memory\"",
+            ));
+        }
+        Ok(client)
+    }
+}
+
 impl Default for Cello {
     fn default() -> Self {
         Self::new()
@@ -1406,6 +4683,63 @@ impl PyOpenTelemetryConfig {
     }
 }
 
+/// Python-exposed Sentry error reporting configuration.
+#[pyclass(name = "SentryConfig")]
+#[derive(Clone)]
+pub struct PySentryConfig {
+    #[pyo3(get, set)]
+    pub dsn: String,
+    #[pyo3(get, set)]
+    pub environment: Option<String>,
+    #[pyo3(get, set)]
+    pub release: Option<String>,
+    #[pyo3(get, set)]
+    pub server_name: Option<String>,
+    #[pyo3(get, set)]
+    pub sample_rate: f64,
+    #[pyo3(get, set)]
+    pub send_headers: Option<Vec<String>>,
+    #[pyo3(get, set)]
+    pub scrub_fields: Option<Vec<String>>,
+    #[pyo3(get, set)]
+    pub timeout_secs: f64,
+    #[pyo3(get, set)]
+    pub max_pending: usize,
+}
+
+#[pymethods]
+impl PySentryConfig {
+    #[new]
+    #[pyo3(signature = (dsn, environment=None, release=None, server_name=None, sample_rate=1.0, send_headers=None, scrub_fields=None, timeout_secs=5.0, max_pending=100))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        dsn: &str,
+        environment: Option<String>,
+        release: Option<String>,
+        server_name: Option<String>,
+        sample_rate: f64,
+        send_headers: Option<Vec<String>>,
+        scrub_fields: Option<Vec<String>>,
+        timeout_secs: f64,
+        max_pending: usize,
+    ) -> PyResult<Self> {
+        error_reporting::SentryConfig::new(dsn)
+            .and_then(|config| config.sample_rate(sample_rate))
+            .map_err(pyo3::exceptions::PyValueError::new_err)?;
+        Ok(Self {
+            dsn: dsn.to_string(),
+            environment,
+            release,
+            server_name,
+            sample_rate,
+            send_headers,
+            scrub_fields,
+            timeout_secs,
+            max_pending,
+        })
+    }
+}
+
 /// Python-exposed Health Check configuration.
 #[pyclass(name = "HealthCheckConfig")]
 #[derive(Clone)]
@@ -1523,6 +4857,22 @@ impl PyDatabaseConfig {
     }
 }
 
+impl PyDatabaseConfig {
+    /// Convert to the pool configuration.
+    pub fn to_config(&self) -> middleware::database::DatabaseConfig {
+        middleware::database::DatabaseConfig {
+            url: self.url.clone(),
+            pool_size: self.pool_size,
+            min_idle: self.min_idle,
+            max_lifetime: std::time::Duration::from_secs(self.max_lifetime_secs),
+            connection_timeout: std::time::Duration::from_secs(self.connection_timeout_secs),
+            idle_timeout: std::time::Duration::from_secs(self.idle_timeout_secs),
+            statement_cache_size: 100,
+            application_name: self.application_name.clone(),
+        }
+    }
+}
+
 /// Python-exposed Redis configuration.
 #[pyclass(name = "RedisConfig")]
 #[derive(Clone)]
@@ -1611,6 +4961,195 @@ impl PyRedisConfig {
     }
 }
 
+/// Python-exposed MongoDB configuration.
+#[pyclass(name = "MongoConfig")]
+#[derive(Clone)]
+pub struct PyMongoConfig {
+    #[pyo3(get, set)]
+    pub url: String,
+    #[pyo3(get, set)]
+    pub database: Option<String>,
+    #[pyo3(get, set)]
+    pub min_pool_size: u32,
+    #[pyo3(get, set)]
+    pub max_pool_size: u32,
+    #[pyo3(get, set)]
+    pub connect_timeout_secs: u64,
+    #[pyo3(get, set)]
+    pub server_selection_timeout_secs: u64,
+    #[pyo3(get, set)]
+    pub app_name: Option<String>,
+}
+
+#[pymethods]
+impl PyMongoConfig {
+    #[new]
+    #[pyo3(signature = (url="mongodb://127.0.0.1:27017", database=None, min_pool_size=0, max_pool_size=10, connect_timeout_secs=5, server_selection_timeout_secs=30, app_name=None))]
+    pub fn new(
+        url: &str,
+        database: Option<String>,
+        min_pool_size: u32,
+        max_pool_size: u32,
+        connect_timeout_secs: u64,
+        server_selection_timeout_secs: u64,
+        app_name: Option<String>,
+    ) -> Self {
+        Self {
+            url: url.to_string(),
+            database,
+            min_pool_size,
+            max_pool_size,
+            connect_timeout_secs,
+            server_selection_timeout_secs,
+            app_name,
+        }
+    }
+
+    /// Create config for local development.
+    #[staticmethod]
+    #[pyo3(signature = (database="cello"))]
+    pub fn local(database: &str) -> Self {
+        Self::new(
+            "mongodb://127.0.0.1:27017",
+            Some(database.to_string()),
+            0,
+            5,
+            5,
+            5,
+            None,
+        )
+    }
+}
+
+impl PyMongoConfig {
+    /// Convert to the client configuration.
+    pub fn to_config(&self) -> middleware::mongo::MongoConfig {
+        middleware::mongo::MongoConfig {
+            url: self.url.clone(),
+            database: self.database.clone(),
+            min_pool_size: self.min_pool_size,
+            max_pool_size: self.max_pool_size,
+            connect_timeout: std::time::Duration::from_secs(self.connect_timeout_secs),
+            server_selection_timeout: std::time::Duration::from_secs(
+                self.server_selection_timeout_secs,
+            ),
+            app_name: self.app_name.clone(),
+        }
+    }
+}
+
+/// Python-exposed object store configuration.
+///
+/// Credentials default to the `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY`
+/// and `AWS_SESSION_TOKEN` environment variables.
+#[pyclass(name = "ObjectStoreConfig")]
+#[derive(Clone)]
+pub struct PyObjectStoreConfig {
+    #[pyo3(get, set)]
+    pub bucket: String,
+    #[pyo3(get, set)]
+    pub endpoint: Option<String>,
+    #[pyo3(get, set)]
+    pub region: String,
+    #[pyo3(get, set)]
+    pub access_key: Option<String>,
+    #[pyo3(get, set)]
+    pub secret_key: Option<String>,
+    #[pyo3(get, set)]
+    pub session_token: Option<String>,
+    #[pyo3(get, set)]
+    pub path_style: bool,
+    #[pyo3(get, set)]
+    pub timeout_secs: u64,
+    #[pyo3(get, set)]
+    pub part_size: usize,
+}
+
+#[pymethods]
+impl PyObjectStoreConfig {
+    #[new]
+    #[pyo3(signature = (bucket, endpoint=None, region="us-east-1", access_key=None, secret_key=None, session_token=None, path_style=false, timeout_secs=30, part_size=8388608))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        bucket: &str,
+        endpoint: Option<String>,
+        region: &str,
+        access_key: Option<String>,
+        secret_key: Option<String>,
+        session_token: Option<String>,
+        path_style: bool,
+        timeout_secs: u64,
+        part_size: usize,
+    ) -> Self {
+        Self {
+            bucket: bucket.to_string(),
+            endpoint,
+            region: region.to_string(),
+            access_key,
+            secret_key,
+            session_token,
+            path_style,
+            timeout_secs,
+            part_size,
+        }
+    }
+
+    /// Create config for a MinIO server.
+    #[staticmethod]
+    #[pyo3(signature = (bucket, endpoint="http://127.0.0.1:9000", access_key="minioadmin", secret_key="minioadmin"))]
+    pub fn minio(bucket: &str, endpoint: &str, access_key: &str, secret_key: &str) -> Self {
+        Self::new(
+            bucket,
+            Some(endpoint.to_string()),
+            "us-east-1",
+            Some(access_key.to_string()),
+            Some(secret_key.to_string()),
+            None,
+            true,
+            30,
+            8 * 1024 * 1024,
+        )
+    }
+
+    /// Create config for Google Cloud Storage with HMAC keys.
+    #[staticmethod]
+    pub fn gcs(bucket: &str, access_key: &str, secret_key: &str) -> Self {
+        Self::new(
+            bucket,
+            Some("https://storage.googleapis.com".to_string()),
+            "auto",
+            Some(access_key.to_string()),
+            Some(secret_key.to_string()),
+            None,
+            true,
+            30,
+            8 * 1024 * 1024,
+        )
+    }
+}
+
+impl PyObjectStoreConfig {
+    /// Convert to the client configuration.
+    pub fn to_config(&self) -> middleware::object_store::ObjectStoreConfig {
+        let mut config = middleware::object_store::ObjectStoreConfig::new(&self.bucket);
+        config.endpoint = self.endpoint.clone();
+        config.region = self.region.clone();
+        if let Some(access_key) = &self.access_key {
+            config.access_key = access_key.clone();
+        }
+        if let Some(secret_key) = &self.secret_key {
+            config.secret_key = secret_key.clone();
+        }
+        if self.session_token.is_some() || self.access_key.is_some() {
+            config.session_token = self.session_token.clone();
+        }
+        config.path_style = self.path_style;
+        config.timeout = std::time::Duration::from_secs(self.timeout_secs);
+        config.part_size = self.part_size;
+        config
+    }
+}
+
 /// Python-exposed GraphQL configuration.
 #[pyclass(name = "GraphQLConfig")]
 #[derive(Clone)]
@@ -1980,62 +5519,162 @@ pub struct PySagaConfig {
     pub timeout_ms: u64,
     #[pyo3(get, set)]
     pub enable_logging: bool,
+    #[pyo3(get, set)]
+    pub backoff_multiplier: f64,
+    #[pyo3(get, set)]
+    pub compensation_max_retries: u32,
+    #[pyo3(get, set)]
+    pub compensation_retry_delay_ms: u64,
 }
 
 #[pymethods]
 impl PySagaConfig {
     #[new]
-    #[pyo3(signature = (max_retries=3, retry_delay_ms=1000, timeout_ms=30000, enable_logging=true))]
+    #[pyo3(signature = (max_retries=3, retry_delay_ms=1000, timeout_ms=30000, enable_logging=true, backoff_multiplier=2.0, compensation_max_retries=3, compensation_retry_delay_ms=1000))]
     pub fn new(
         max_retries: u32,
         retry_delay_ms: u64,
         timeout_ms: u64,
         enable_logging: bool,
+        backoff_multiplier: f64,
+        compensation_max_retries: u32,
+        compensation_retry_delay_ms: u64,
     ) -> Self {
         Self {
             max_retries,
             retry_delay_ms,
             timeout_ms,
             enable_logging,
+            backoff_multiplier,
+            compensation_max_retries,
+            compensation_retry_delay_ms,
         }
     }
 
     /// Create a default Saga configuration.
     #[staticmethod]
     pub fn default() -> Self {
-        Self::new(3, 1000, 30000, true)
+        Self::new(3, 1000, 30000, true, 2.0, 3, 1000)
     }
 }
 
-/// Helper to call lifecycle handlers (sync or async).
-/// Drive a lifecycle hook (startup/shutdown) to completion.
-///
-/// Handles both sync `def` and `async def` hooks. For async hooks the coroutine
-/// is driven by Tokio via pyo3-asyncio so the GIL is released during I/O waits,
-/// consistent with how request handlers are executed.
-async fn run_lifecycle_handler_async(handler: PyObject) -> Result<(), String> {
-    // Phase 1 (GIL): call the handler; detect whether it returned a coroutine.
-    let (result, is_coro) = Python::with_gil(|py| -> PyResult<(PyObject, bool)> {
-        let ret = handler.call0(py)?;
-        let inspect = py.import("inspect")?;
-        let is_coro = inspect
-            .call_method1("iscoroutine", (ret.as_ref(py),))?
-            .is_true()?;
-        Ok((ret, is_coro))
+/// Convert a saga execution result to a dict, raising `ValueError` on error.
+fn saga_execution_to_py(
+    result: Result<middleware::saga::SagaExecution, middleware::saga::SagaError>,
+) -> PyResult<PyObject> {
+    let execution = result.map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+    let value = serde_json::to_value(&execution)
+        .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))?;
+    Python::with_gil(|py| json::json_to_python(py, &value))
+}
+
+/// One aggregate's events passed to `append_events`.
+#[derive(serde::Deserialize)]
+struct PyStreamAppend {
+    aggregate_id: String,
+    #[serde(default)]
+    expected_version: Option<u64>,
+    events: Vec<PyNewEvent>,
+}
+
+/// An event to append, as passed from Python.
+#[derive(serde::Deserialize)]
+struct PyNewEvent {
+    event_type: String,
+    #[serde(default)]
+    data: serde_json::Value,
+    #[serde(default)]
+    metadata: std::collections::HashMap<String, String>,
+}
+
+/// Convert a serializable value to Python through JSON.
+fn to_python<T: serde::Serialize>(py: Python<'_>, value: &T) -> PyResult<PyObject> {
+    let value = serde_json::to_value(value)
+        .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))?;
+    json::json_to_python(py, &value)
+}
+
+/// Borrow tags passed from Python.
+fn tag_refs(tags: &std::collections::BTreeMap<String, String>) -> Vec<(&str, &str)> {
+    tags.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect()
+}
+
+/// Convert a non-negative number of seconds passed from Python.
+fn seconds_arg(name: &str, secs: f64) -> PyResult<std::time::Duration> {
+    std::time::Duration::try_from_secs_f64(secs).map_err(|_| {
+        pyo3::exceptions::PyValueError::new_err(format!(
+            "{name} must be a non-negative number of seconds"
+        ))
     })
-    .map_err(|e| e.to_string())?;
+}
 
-    // Phase 2 (GIL released): await coroutine via Tokio if needed.
-    if is_coro {
-        let future = Python::with_gil(|py| {
-            pyo3_asyncio::tokio::into_future(result.as_ref(py)).map_err(|e| e.to_string())
-        })?;
-        future.await.map(|_| ()).map_err(|e| e.to_string())?;
+/// Convert an optional Python value to JSON, `None` becoming `null`.
+fn optional_json(py: Python<'_>, value: Option<&PyAny>) -> PyResult<serde_json::Value> {
+    match value {
+        Some(value) => {
+            json::python_to_json(py, value).map_err(pyo3::exceptions::PyValueError::new_err)
+        }
+        None => Ok(serde_json::Value::Null),
     }
+}
 
-    Ok(())
+/// Map a CQRS error to `TimeoutError` for timeouts and `ValueError` otherwise.
+fn cqrs_error_to_py(error: middleware::cqrs::CqrsError) -> PyErr {
+    match error {
+        middleware::cqrs::CqrsError::TimeoutError(_) => {
+            pyo3::exceptions::PyTimeoutError::new_err(error.to_string())
+        }
+        _ => pyo3::exceptions::PyValueError::new_err(error.to_string()),
+    }
+}
+
+/// Shape a command result like `cello.cqrs.CommandResult`.
+fn command_result_to_json(result: middleware::cqrs::CommandResult) -> serde_json::Value {
+    use middleware::cqrs::CommandResult;
+    match result {
+        CommandResult::Success(data) => {
+            serde_json::json!({"success": true, "data": data, "error": null})
+        }
+        CommandResult::Failure(error) => {
+            serde_json::json!({"success": false, "data": null, "error": error})
+        }
+        CommandResult::Rejected(reason) => serde_json::json!({
+            "success": false,
+            "data": null,
+            "error": format!("Rejected: {reason}"),
+        }),
+    }
+}
+
+/// Shape a query result like `cello.cqrs.QueryResult`.
+fn query_result_to_json(result: middleware::cqrs::QueryResult) -> serde_json::Value {
+    use middleware::cqrs::QueryResult;
+    match result {
+        QueryResult::Success(data) => {
+            serde_json::json!({"found": true, "data": data, "error": null})
+        }
+        QueryResult::NotFound => serde_json::json!({"found": false, "data": null, "error": null}),
+        QueryResult::Error(error) => {
+            serde_json::json!({"found": false, "data": null, "error": error})
+        }
+    }
 }
 
+/// Build a server lifecycle hook from Python `on_startup`/`on_shutdown` arguments.
+fn server_hook(
+    handler: PyObject,
+    timeout: Option<f64>,
+    on_error: &str,
+) -> PyResult<lifecycle::ServerHook> {
+    let on_error = on_error
+        .parse::<lifecycle::HookErrorPolicy>()
+        .map_err(pyo3::exceptions::PyValueError::new_err)?;
+    let mut hook = lifecycle::ServerHook::python(handler).with_error_policy(on_error);
+    if let Some(secs) = timeout {
+        hook = hook.with_timeout(seconds_arg("timeout", secs)?);
+    }
+    Ok(hook)
+}
 
 /// Python module definition.
 #[pymodule]
@@ -2043,7 +5682,12 @@ fn _cello(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
     // Core classes
     m.add_class::<Cello>()?;
     m.add_class::<request::Request>()?;
+    m.add_class::<request::BodyStream>()?;
+    m.add_class::<request::NdjsonStream>()?;
+    m.add_class::<request::RequestState>()?;
+    m.add_class::<request::RequestLogger>()?;
     m.add_class::<response::Response>()?;
+    m.add_class::<response::JsonStream>()?;
 
     // Blueprint
     m.add_class::<Blueprint>()?;
@@ -2052,6 +5696,11 @@ fn _cello(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
     m.add_class::<WebSocket>()?;
     m.add_class::<WebSocketMessage>()?;
 
+    // Test client
+    m.add_class::<server::test_client::PyTestClient>()?;
+    m.add_class::<server::test_client::PyTestResponse>()?;
+    m.add_class::<server::test_client::PyWebSocketSession>()?;
+
     // SSE
     m.add_class::<SseEvent>()?;
     m.add_class::<SseStream>()?;
@@ -2086,15 +5735,32 @@ fn _cello(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
     // Rust-native async HTTP client
     m.add_class::<http_client::PyAsyncClient>()?;
     m.add_class::<http_client::PyHttpResponse>()?;
+    m.add_class::<middleware::grpc::PyGrpcClient>()?;
+    m.add_class::<middleware::distributed_lock::PyDistributedLock>()?;
+    m.add_class::<middleware::memory_cache::PyCache>()?;
+    m.add_class::<middleware::ip_filter::PyIpFilter>()?;
+    m.add_class::<config::PyAppConfig>()?;
+    m.add_class::<middleware::sql::PyDatabasePool>()?;
+    m.add_class::<middleware::sql::PyDatabaseTransaction>()?;
+    m.add_class::<middleware::mongo::PyObjectId>()?;
+    m.add_class::<middleware::object_store::PyObjectStore>()?;
+    m.add_class::<middleware::object_store::ObjectDownload>()?;
+    #[cfg(feature = "mongodb")]
+    m.add_class::<middleware::mongo::PyMongoDatabase>()?;
+    #[cfg(feature = "mongodb")]
+    m.add_class::<middleware::mongo::PyMongoCollection>()?;
 
     // v0.7.0+ / v0.8.0 - Enterprise & Data Layer Configuration Classes
     m.add_class::<PyOpenTelemetryConfig>()?;
+    m.add_class::<PySentryConfig>()?;
     m.add_class::<PyHealthCheckConfig>()?;
     m.add_class::<PyDatabaseConfig>()?;
     m.add_class::<PyGraphQLConfig>()?;
 
     // v0.8.0 - Data Layer Configuration Classes
     m.add_class::<PyRedisConfig>()?;
+    m.add_class::<PyMongoConfig>()?;
+    m.add_class::<PyObjectStoreConfig>()?;
 
     // v0.9.0 - API Protocol Configuration Classes
     m.add_class::<PyGrpcConfig>()?;
@@ -2109,6 +5775,14 @@ fn _cello(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
 
     // RFC 7807 error type
     m.add_class::<error::ProblemDetails>()?;
+    m.add("BodyDecodeError", _py.get_type::<request::BodyDecodeError>())?;
+
+    // Guards
+    m.add_class::<middleware::guards::PyRequire>()?;
+
+    // Profiling
+    m.add_class::<profiling::CpuProfiler>()?;
+    m.add_function(wrap_pyfunction!(profiling::memory_stats, m)?)?;
 
     Ok(())
 }
@@ -1,7 +1,7 @@
 //! Hooks and lifecycle events for Cello.
 //!
 //! This module provides:
-//! - Startup/shutdown hooks (async)
+//! - Startup/shutdown hooks (async), awaited by the server with timeouts
 //! - Before/after request hooks
 //! - Exception hooks
 //! - Signal handlers (SIGTERM, SIGHUP, etc.)
@@ -9,12 +9,15 @@
 
 use parking_lot::RwLock;
 use pyo3::prelude::*;
+use pyo3::types::PyTuple;
 use std::collections::HashMap;
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::Arc;
+use std::time::Duration;
 
 use crate::error::AppError;
+use crate::event_loop::{call_with_deadline, CallError};
 use crate::request::Request;
 use crate::response::Response;
 
@@ -109,7 +112,9 @@ impl Signal {
     /// are Unix-specific and are no-ops on other platforms.
     pub fn is_supported(&self) -> bool {
         #[cfg(unix)]
-        { true }
+        {
+            true
+        }
         #[cfg(not(unix))]
         {
             matches!(self, Signal::SIGTERM | Signal::SIGINT)
@@ -513,6 +518,209 @@ impl Clone for SignalHandlers {
     }
 }
 
+/// What the server does when a startup or shutdown hook fails or times out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HookErrorPolicy {
+    /// Log the error and run the remaining hooks.
+    #[default]
+    Continue,
+    /// Stop running hooks; a failing startup hook keeps the server from
+    /// accepting connections.
+    Abort,
+}
+
+impl std::str::FromStr for HookErrorPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "continue" => Ok(Self::Continue),
+            "abort" => Ok(Self::Abort),
+            other => Err(format!(
+                "Invalid hook error policy '{other}': expected 'continue' or 'abort'"
+            )),
+        }
+    }
+}
+
+/// One run of a [`ServerHook`] under its timeout; `None` if it timed out.
+type HookRun = Pin<Box<dyn Future<Output = Option<Result<(), String>>> + Send>>;
+
+/// A startup or shutdown hook awaited by the server.
+#[derive(Clone)]
+pub struct ServerHook {
+    name: String,
+    hook: Arc<dyn Fn(Option<Duration>) -> HookRun + Send + Sync>,
+    timeout: Option<Duration>,
+    on_error: HookErrorPolicy,
+}
+
+impl ServerHook {
+    /// Create a hook from an async Rust closure.
+    ///
+    /// A timed-out hook's future is dropped, so it stops at its next
+    /// `.await`.
+    pub fn new<F, Fut>(name: &str, hook: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), String>> + Send + 'static,
+    {
+        Self {
+            name: name.to_string(),
+            hook: Arc::new(move |timeout| {
+                let run = hook();
+                Box::pin(async move {
+                    match timeout {
+                        Some(timeout) => tokio::time::timeout(timeout, run).await.ok(),
+                        None => Some(run.await),
+                    }
+                })
+            }),
+            timeout: None,
+            on_error: HookErrorPolicy::default(),
+        }
+    }
+
+    /// Create a hook from a sync or async Python callable.
+    ///
+    /// Coroutines are driven by Tokio via pyo3-asyncio so the GIL is
+    /// released while the hook waits on I/O. Both kinds fail at the hook's
+    /// timeout and are stopped; a sync hook blocked in a C call finishes it
+    /// in the background (see
+    /// [`call_with_deadline`](crate::event_loop::call_with_deadline)).
+    pub fn python(handler: PyObject) -> Self {
+        let name = Python::with_gil(|py| {
+            handler
+                .getattr(py, "__qualname__")
+                .and_then(|n| n.extract::<String>(py))
+                .unwrap_or_else(|_| "hook".to_string())
+        });
+        let handler = Arc::new(handler);
+        Self {
+            name,
+            hook: Arc::new(move |timeout| Box::pin(run_python_hook(handler.clone(), timeout))),
+            timeout: None,
+            on_error: HookErrorPolicy::default(),
+        }
+    }
+
+    /// Stop the hook and fail it if it has not finished within `timeout`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Set what happens when the hook fails.
+    pub fn with_error_policy(mut self, on_error: HookErrorPolicy) -> Self {
+        self.on_error = on_error;
+        self
+    }
+
+    /// The hook name used in error messages.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The hook's error policy.
+    pub fn error_policy(&self) -> HookErrorPolicy {
+        self.on_error
+    }
+
+    /// Run the hook to completion, honouring its timeout.
+    pub async fn execute(&self) -> Result<(), String> {
+        (self.hook)(self.timeout).await.unwrap_or_else(|| {
+            let timeout = self.timeout.unwrap_or_default();
+            Err(format!("timed out after {:.1}s", timeout.as_secs_f64()))
+        })
+    }
+}
+
+/// Call a Python lifecycle hook, awaiting it if it returns a coroutine, and
+/// stop it once `timeout` passes. Returns `None` at the timeout.
+async fn run_python_hook(
+    handler: Arc<PyObject>,
+    timeout: Option<Duration>,
+) -> Option<Result<(), String>> {
+    match call_with_deadline(handler, |py| Ok(PyTuple::empty(py).into()), timeout).await {
+        Ok(_) => Some(Ok(())),
+        Err(CallError::Raised(e)) => Some(Err(e.to_string())),
+        Err(CallError::TimedOut) => None,
+    }
+}
+
+/// Startup and shutdown hooks run by [`Server::run`](crate::server::Server::run).
+///
+/// Startup hooks run in registration order after the socket is bound and
+/// before the first connection is accepted. Shutdown hooks run in
+/// registration order once in-flight requests have drained.
+#[derive(Clone, Default)]
+pub struct ServerLifecycle {
+    startup: Vec<ServerHook>,
+    shutdown: Vec<ServerHook>,
+}
+
+impl ServerLifecycle {
+    /// Create an empty lifecycle.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a startup hook.
+    pub fn on_startup(&mut self, hook: ServerHook) {
+        self.startup.push(hook);
+    }
+
+    /// Add a shutdown hook.
+    pub fn on_shutdown(&mut self, hook: ServerHook) {
+        self.shutdown.push(hook);
+    }
+
+    /// The registered startup hooks.
+    pub fn startup_hooks(&self) -> &[ServerHook] {
+        &self.startup
+    }
+
+    /// The registered shutdown hooks.
+    pub fn shutdown_hooks(&self) -> &[ServerHook] {
+        &self.shutdown
+    }
+
+    /// Run the startup hooks.
+    ///
+    /// Returns an error from the first failing hook whose policy is
+    /// [`HookErrorPolicy::Abort`]; other failures are logged.
+    pub async fn run_startup(&self) -> Result<(), String> {
+        for hook in &self.startup {
+            if let Err(e) = hook.execute().await {
+                let message = format!("Startup hook '{}' failed: {e}", hook.name);
+                if hook.on_error == HookErrorPolicy::Abort {
+                    return Err(message);
+                }
+                eprintln!("{message}");
+            }
+        }
+        Ok(())
+    }
+
+    /// Run the shutdown hooks.
+    ///
+    /// Failures are logged; a failing hook with [`HookErrorPolicy::Abort`]
+    /// skips the hooks after it.
+    pub async fn run_shutdown(&self) {
+        for hook in &self.shutdown {
+            if let Err(e) = hook.execute().await {
+                // KeyboardInterrupt is expected when CTRL+C initiates shutdown; suppress it.
+                if !e.contains("KeyboardInterrupt") {
+                    eprintln!("Shutdown hook '{}' failed: {e}", hook.name);
+                }
+                if hook.on_error == HookErrorPolicy::Abort {
+                    break;
+                }
+            }
+        }
+    }
+}
+
 /// Route-specific hook overrides.
 #[derive(Clone, Default)]
 pub struct RouteHooks {
@@ -683,4 +891,119 @@ mod tests {
             assert!(!Signal::SIGUSR2.is_supported());
         }
     }
+
+    // ---------- Server Lifecycle Tests ----------
+
+    fn recording_hook(
+        name: &'static str,
+        log: &Arc<parking_lot::Mutex<Vec<&'static str>>>,
+        result: Result<(), String>,
+    ) -> ServerHook {
+        let log = log.clone();
+        ServerHook::new(name, move || {
+            let log = log.clone();
+            let result = result.clone();
+            async move {
+                log.lock().push(name);
+                result
+            }
+        })
+    }
+
+    #[test]
+    fn test_hook_error_policy_from_str() {
+        assert_eq!("abort".parse(), Ok(HookErrorPolicy::Abort));
+        assert_eq!("Continue".parse(), Ok(HookErrorPolicy::Continue));
+        assert!("ignore".parse::<HookErrorPolicy>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_startup_hooks_run_in_order() {
+        let log = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let mut lifecycle = ServerLifecycle::new();
+        lifecycle.on_startup(recording_hook("a", &log, Ok(())));
+        lifecycle.on_startup(recording_hook("b", &log, Err("boom".into())));
+        lifecycle.on_startup(recording_hook("c", &log, Ok(())));
+
+        assert!(lifecycle.run_startup().await.is_ok());
+        assert_eq!(*log.lock(), vec!["a", "b", "c"]);
+    }
+
+    #[tokio::test]
+    async fn test_startup_hook_abort() {
+        let log = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let mut lifecycle = ServerLifecycle::new();
+        lifecycle.on_startup(
+            recording_hook("db", &log, Err("refused".into()))
+                .with_error_policy(HookErrorPolicy::Abort),
+        );
+        lifecycle.on_startup(recording_hook("cache", &log, Ok(())));
+
+        let err = lifecycle.run_startup().await.unwrap_err();
+        assert_eq!(err, "Startup hook 'db' failed: refused");
+        assert_eq!(*log.lock(), vec!["db"]);
+    }
+
+    #[tokio::test]
+    async fn test_hook_timeout() {
+        let hook = ServerHook::new("slow", || async {
+            tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+            Ok(())
+        })
+        .with_timeout(std::time::Duration::from_millis(20))
+        .with_error_policy(HookErrorPolicy::Abort);
+        let mut lifecycle = ServerLifecycle::new();
+        lifecycle.on_startup(hook);
+
+        let err = lifecycle.run_startup().await.unwrap_err();
+        assert!(err.contains("timed out"));
+    }
+
+    #[tokio::test]
+    async fn test_sync_python_hook_stopped_at_timeout() {
+        pyo3::prepare_freethreaded_python();
+        let (hook, state) = Python::with_gil(|py| {
+            let globals = pyo3::types::PyDict::new(py);
+            py.run(
+                "import time\n\
+                 state = {'finished': False}\n\
+                 def warm_cache():\n    \
+                     time.sleep(0.5)\n    \
+                     state['finished'] = True\n",
+                Some(globals),
+                None,
+            )
+            .unwrap();
+            let hook: PyObject = globals.get_item("warm_cache").unwrap().unwrap().into();
+            let state: PyObject = globals.get_item("state").unwrap().unwrap().into();
+            (ServerHook::python(hook), state)
+        });
+        let hook = hook.with_timeout(std::time::Duration::from_millis(50));
+        assert_eq!(hook.name(), "warm_cache");
+
+        // The hook fails at its timeout, not once its blocking call returns
+        let started = std::time::Instant::now();
+        let err = hook.execute().await.unwrap_err();
+        assert!(err.contains("timed out"));
+        assert!(started.elapsed() < std::time::Duration::from_millis(300));
+        tokio::time::sleep(std::time::Duration::from_millis(700)).await;
+        Python::with_gil(|py| {
+            let finished = state.as_ref(py).get_item("finished").unwrap();
+            assert!(!finished.extract::<bool>().unwrap());
+        });
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_hook_abort_skips_rest() {
+        let log = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let mut lifecycle = ServerLifecycle::new();
+        lifecycle.on_shutdown(recording_hook("a", &log, Err("boom".into())));
+        lifecycle.on_shutdown(
+            recording_hook("b", &log, Err("boom".into())).with_error_policy(HookErrorPolicy::Abort),
+        );
+        lifecycle.on_shutdown(recording_hook("c", &log, Ok(())));
+
+        lifecycle.run_shutdown().await;
+        assert_eq!(*log.lock(), vec!["a", "b"]);
+    }
 }
@@ -331,6 +331,14 @@ impl Middleware for JwtAuth {
     fn name(&self) -> &str {
         "jwt_auth"
     }
+
+    fn credential_location(&self) -> Option<CredentialLocation> {
+        // The header is checked first, so a refreshed token goes there.
+        Some(CredentialLocation::Header {
+            name: self.header_name.clone(),
+            prefix: self.token_prefix.clone(),
+        })
+    }
 }
 
 // ============================================================================
@@ -464,6 +472,54 @@ impl Middleware for BasicAuth {
     }
 }
 
+// ============================================================================
+// Credential Location
+// ============================================================================
+
+/// Where an auth middleware reads its credential, so a fresh one can be
+/// presented the same way, e.g. when a WebSocket refreshes its token.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CredentialLocation {
+    /// A header, whose value is `prefix` followed by the credential.
+    Header { name: String, prefix: String },
+    /// A query parameter.
+    Query(String),
+    /// A cookie.
+    Cookie(String),
+}
+
+impl CredentialLocation {
+    /// Put `credential` on `request` where the middleware looks for it,
+    /// replacing any previous one.
+    pub fn apply(&self, request: &mut Request, credential: &str) {
+        match self {
+            CredentialLocation::Header { name, prefix } => {
+                request
+                    .headers
+                    .insert(name, &format!("{prefix}{credential}"));
+            }
+            CredentialLocation::Query(name) => {
+                request
+                    .query_params
+                    .insert(name.clone(), credential.to_string());
+            }
+            CredentialLocation::Cookie(name) => {
+                let mut cookies: Vec<String> = request
+                    .headers
+                    .get("cookie")
+                    .unwrap_or("")
+                    .split(';')
+                    .map(str::trim)
+                    .filter(|c| !c.is_empty() && c.split('=').next() != Some(name.as_str()))
+                    .map(str::to_string)
+                    .collect();
+                cookies.push(format!("{name}={credential}"));
+                request.headers.insert("cookie", &cookies.join("; "));
+            }
+        }
+    }
+}
+
 // ============================================================================
 // API Key Authentication
 // ============================================================================
@@ -570,7 +626,7 @@ impl ApiKeyAuth {
     /// Extract API key from request.
     fn extract_key(&self, request: &Request) -> Option<String> {
         match &self.location {
-            ApiKeyLocation::Header(name) => request.headers.get(&name.to_lowercase()).cloned(),
+            ApiKeyLocation::Header(name) => request.headers.get(name).map(str::to_string),
             ApiKeyLocation::Query(name) => request.query_params.get(name).cloned(),
             ApiKeyLocation::Cookie(name) => {
                 if let Some(cookie_header) = request.headers.get("cookie") {
@@ -621,6 +677,17 @@ impl Middleware for ApiKeyAuth {
     fn name(&self) -> &str {
         "api_key_auth"
     }
+
+    fn credential_location(&self) -> Option<CredentialLocation> {
+        Some(match &self.location {
+            ApiKeyLocation::Header(name) => CredentialLocation::Header {
+                name: name.clone(),
+                prefix: String::new(),
+            },
+            ApiKeyLocation::Query(name) => CredentialLocation::Query(name.clone()),
+            ApiKeyLocation::Cookie(name) => CredentialLocation::Cookie(name.clone()),
+        })
+    }
 }
 
 // ============================================================================
@@ -704,6 +771,31 @@ mod tests {
         assert_eq!(auth.name(), "api_key_auth");
     }
 
+    #[test]
+    fn test_credential_location() {
+        let config = JwtConfig::new(b"secret-key-for-testing");
+        let auth = JwtAuth::new(config)
+            .header_name("X-Token")
+            .token_prefix("Token ");
+        let mut request = Request::default();
+        auth.credential_location()
+            .unwrap()
+            .apply(&mut request, "abc");
+        assert_eq!(request.headers.get("x-token"), Some("Token abc"));
+        assert_eq!(auth.extract_token(&request), Some("abc".to_string()));
+
+        let auth = ApiKeyAuth::new("key").location(ApiKeyLocation::Cookie("api_key".to_string()));
+        request.headers.insert("cookie", "theme=dark; api_key=old");
+        auth.credential_location()
+            .unwrap()
+            .apply(&mut request, "new");
+        assert_eq!(
+            request.headers.get("cookie"),
+            Some("theme=dark; api_key=new")
+        );
+        assert_eq!(auth.extract_key(&request), Some("new".to_string()));
+    }
+
     #[test]
     fn test_token_blacklist() {
         let blacklist = TokenBlacklist::new();
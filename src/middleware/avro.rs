@@ -0,0 +1,873 @@
+//! Apache Avro support for Cello.
+//!
+//! Provides:
+//! - Parsing Avro schemas from their JSON definition
+//! - Binary encoding/decoding of values against a schema
+//!
+//! Values use the JSON data model the rest of Cello uses: records as
+//! objects, enums as symbol names, `bytes` and `fixed` as base64 strings, and
+//! unions as the plain branch value (the Avro JSON `{"type": value}` wrapper
+//! is also accepted when encoding). Logical types are encoded as their
+//! underlying type.
+//!
+//! # Example
+//! ```rust,ignore
+//! let schema = AvroSchema::parse(r#"{
+//!     "type": "record", "name": "Order",
+//!     "fields": [{"name": "id", "type": "long"}, {"name": "note", "type": ["null", "string"]}]
+//! }"#)?;
+//! let bytes = schema.encode(&json!({"id": 42, "note": null}))?;
+//! assert_eq!(schema.decode(&bytes)?, json!({"id": 42, "note": null}));
+//! ```
+
+use base64::Engine;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use thiserror::Error;
+
+use crate::json::{json_i64, DEFAULT_MAX_JSON_DEPTH};
+
+// ============================================================================
+// Errors
+// ============================================================================
+
+/// Error raised while parsing Avro schemas or encoding/decoding values.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum AvroError {
+    #[error("Invalid Avro schema: {0}")]
+    InvalidSchema(String),
+
+    #[error("Value at '{path}' expects {expected}")]
+    TypeMismatch {
+        path: String,
+        expected: &'static str,
+    },
+
+    #[error("Malformed Avro data: {0}")]
+    Malformed(String),
+
+    #[error("Avro nesting depth exceeds limit of {limit}")]
+    DepthLimitExceeded { limit: usize },
+}
+
+impl From<AvroError> for String {
+    fn from(err: AvroError) -> Self {
+        err.to_string()
+    }
+}
+
+// ============================================================================
+// Schema
+// ============================================================================
+
+/// A record field.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AvroField {
+    pub name: String,
+    pub schema: AvroType,
+    /// Value used when the field is missing while encoding.
+    pub default: Option<Value>,
+}
+
+/// An Avro type. Named types are referenced by full name after their first
+/// definition.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AvroType {
+    Null,
+    Boolean,
+    Int,
+    Long,
+    Float,
+    Double,
+    Bytes,
+    String,
+    Record {
+        name: String,
+        fields: Vec<AvroField>,
+    },
+    Enum {
+        name: String,
+        symbols: Vec<String>,
+    },
+    Array(Box<AvroType>),
+    Map(Box<AvroType>),
+    Union(Vec<AvroType>),
+    Fixed {
+        name: String,
+        size: usize,
+    },
+    /// Reference to a named type defined elsewhere in the schema.
+    Named(String),
+}
+
+impl AvroType {
+    /// Type name used by the JSON union wrapper (`{"string": "x"}`).
+    fn type_name(&self) -> &str {
+        match self {
+            AvroType::Null => "null",
+            AvroType::Boolean => "boolean",
+            AvroType::Int => "int",
+            AvroType::Long => "long",
+            AvroType::Float => "float",
+            AvroType::Double => "double",
+            AvroType::Bytes => "bytes",
+            AvroType::String => "string",
+            AvroType::Array(_) => "array",
+            AvroType::Map(_) => "map",
+            AvroType::Union(_) => "union",
+            AvroType::Record { name, .. }
+            | AvroType::Enum { name, .. }
+            | AvroType::Fixed { name, .. }
+            | AvroType::Named(name) => name,
+        }
+    }
+}
+
+/// A parsed Avro schema.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AvroSchema {
+    root: AvroType,
+    named: HashMap<String, AvroType>,
+}
+
+fn invalid(message: impl Into<String>) -> AvroError {
+    AvroError::InvalidSchema(message.into())
+}
+
+fn full_name(name: &str, namespace: Option<&str>) -> String {
+    match namespace {
+        Some(ns) if !ns.is_empty() && !name.contains('.') => format!("{ns}.{name}"),
+        _ => name.to_string(),
+    }
+}
+
+struct SchemaParser {
+    named: HashMap<String, AvroType>,
+}
+
+impl SchemaParser {
+    fn parse(
+        &mut self,
+        value: &Value,
+        namespace: Option<&str>,
+        depth: usize,
+    ) -> Result<AvroType, AvroError> {
+        check_depth(depth)?;
+        match value {
+            Value::String(name) => self.parse_name(name, namespace),
+            Value::Array(branches) => {
+                let branches = branches
+                    .iter()
+                    .map(|b| self.parse(b, namespace, depth + 1))
+                    .collect::<Result<Vec<_>, _>>()?;
+                if branches.iter().any(|b| matches!(b, AvroType::Union(_))) {
+                    return Err(invalid("unions may not immediately contain unions"));
+                }
+                Ok(AvroType::Union(branches))
+            }
+            Value::Object(object) => self.parse_complex(object, namespace, depth),
+            other => Err(invalid(format!("unexpected schema value {other}"))),
+        }
+    }
+
+    fn parse_name(&self, name: &str, namespace: Option<&str>) -> Result<AvroType, AvroError> {
+        Ok(match name {
+            "null" => AvroType::Null,
+            "boolean" => AvroType::Boolean,
+            "int" => AvroType::Int,
+            "long" => AvroType::Long,
+            "float" => AvroType::Float,
+            "double" => AvroType::Double,
+            "bytes" => AvroType::Bytes,
+            "string" => AvroType::String,
+            other => {
+                let qualified = full_name(other, namespace);
+                if self.named.contains_key(&qualified) {
+                    AvroType::Named(qualified)
+                } else if self.named.contains_key(other) {
+                    AvroType::Named(other.to_string())
+                } else {
+                    return Err(invalid(format!("unknown type '{other}'")));
+                }
+            }
+        })
+    }
+
+    fn define(
+        &mut self,
+        object: &Map<String, Value>,
+        namespace: Option<&str>,
+    ) -> Result<(String, Option<String>), AvroError> {
+        let name = object
+            .get("name")
+            .and_then(Value::as_str)
+            .ok_or_else(|| invalid("named type without a name"))?;
+        let namespace = object
+            .get("namespace")
+            .and_then(Value::as_str)
+            .or(namespace);
+        let name = full_name(name, namespace);
+        if self.named.contains_key(&name) {
+            return Err(invalid(format!("type '{name}' is defined twice")));
+        }
+        // Enclosed definitions default to the namespace of this type
+        let inner = name.rsplit_once('.').map(|(ns, _)| ns.to_string());
+        Ok((name, inner))
+    }
+
+    fn parse_complex(
+        &mut self,
+        object: &Map<String, Value>,
+        namespace: Option<&str>,
+        depth: usize,
+    ) -> Result<AvroType, AvroError> {
+        let kind = object
+            .get("type")
+            .ok_or_else(|| invalid("schema object without a type"))?;
+        let kind = match kind {
+            Value::String(kind) => kind.as_str(),
+            // {"type": {...}} and {"type": [...]} wrap another schema
+            other => return self.parse(other, namespace, depth + 1),
+        };
+        match kind {
+            "record" | "error" => {
+                let (name, inner) = self.define(object, namespace)?;
+                // Registered first so fields can refer to the record itself
+                self.named
+                    .insert(name.clone(), AvroType::Named(name.clone()));
+                let mut fields = Vec::new();
+                for field in object
+                    .get("fields")
+                    .and_then(Value::as_array)
+                    .ok_or_else(|| invalid(format!("record '{name}' has no fields")))?
+                {
+                    let field_name = field
+                        .get("name")
+                        .and_then(Value::as_str)
+                        .ok_or_else(|| invalid(format!("field of '{name}' has no name")))?;
+                    let schema = field
+                        .get("type")
+                        .ok_or_else(|| invalid(format!("field '{field_name}' has no type")))?;
+                    fields.push(AvroField {
+                        name: field_name.to_string(),
+                        schema: self.parse(schema, inner.as_deref(), depth + 1)?,
+                        default: field.get("default").cloned(),
+                    });
+                }
+                let record = AvroType::Record {
+                    name: name.clone(),
+                    fields,
+                };
+                self.named.insert(name, record.clone());
+                Ok(record)
+            }
+            "enum" => {
+                let (name, _) = self.define(object, namespace)?;
+                let symbols = object
+                    .get("symbols")
+                    .and_then(Value::as_array)
+                    .ok_or_else(|| invalid(format!("enum '{name}' has no symbols")))?
+                    .iter()
+                    .map(|s| {
+                        s.as_str().map(str::to_string).ok_or_else(|| {
+                            invalid(format!("enum '{name}' has a non-string symbol"))
+                        })
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                let enum_type = AvroType::Enum {
+                    name: name.clone(),
+                    symbols,
+                };
+                self.named.insert(name, enum_type.clone());
+                Ok(enum_type)
+            }
+            "fixed" => {
+                let (name, _) = self.define(object, namespace)?;
+                let size = object
+                    .get("size")
+                    .and_then(Value::as_u64)
+                    .ok_or_else(|| invalid(format!("fixed '{name}' has no size")))?;
+                let fixed = AvroType::Fixed {
+                    name: name.clone(),
+                    size: size as usize,
+                };
+                self.named.insert(name, fixed.clone());
+                Ok(fixed)
+            }
+            "array" => {
+                let items = object
+                    .get("items")
+                    .ok_or_else(|| invalid("array without items"))?;
+                Ok(AvroType::Array(Box::new(self.parse(
+                    items,
+                    namespace,
+                    depth + 1,
+                )?)))
+            }
+            "map" => {
+                let values = object
+                    .get("values")
+                    .ok_or_else(|| invalid("map without values"))?;
+                Ok(AvroType::Map(Box::new(self.parse(
+                    values,
+                    namespace,
+                    depth + 1,
+                )?)))
+            }
+            // Primitive with attributes, e.g. {"type": "long", "logicalType": "timestamp-millis"}
+            primitive => self.parse_name(primitive, namespace),
+        }
+    }
+}
+
+impl AvroSchema {
+    /// Parse a schema from its JSON definition.
+    pub fn parse(definition: &str) -> Result<Self, AvroError> {
+        let value: Value = serde_json::from_str(definition)
+            .map_err(|e| invalid(format!("schema is not JSON: {e}")))?;
+        Self::from_value(&value)
+    }
+
+    /// Parse a schema from an already-decoded JSON definition.
+    pub fn from_value(definition: &Value) -> Result<Self, AvroError> {
+        let mut parser = SchemaParser {
+            named: HashMap::new(),
+        };
+        let root = parser.parse(definition, None, 0)?;
+        Ok(Self {
+            root,
+            named: parser.named,
+        })
+    }
+
+    /// The top-level type.
+    pub fn root(&self) -> &AvroType {
+        &self.root
+    }
+
+    /// Look up a named type by full name.
+    pub fn named(&self, name: &str) -> Option<&AvroType> {
+        self.named.get(name)
+    }
+
+    /// Encode a JSON value in Avro binary form.
+    pub fn encode(&self, value: &Value) -> Result<Vec<u8>, AvroError> {
+        let mut buf = Vec::with_capacity(64);
+        self.encode_value(&self.root, value, "$", &mut buf, 0)?;
+        Ok(buf)
+    }
+
+    /// Decode Avro binary data into a JSON value.
+    pub fn decode(&self, input: &[u8]) -> Result<Value, AvroError> {
+        let mut reader = Reader { input, pos: 0 };
+        let value = self.decode_value(&self.root, &mut reader, 0)?;
+        if reader.pos != input.len() {
+            return Err(AvroError::Malformed(format!(
+                "{} trailing bytes",
+                input.len() - reader.pos
+            )));
+        }
+        Ok(value)
+    }
+
+    fn resolve<'a>(&'a self, schema: &'a AvroType) -> Result<&'a AvroType, AvroError> {
+        match schema {
+            AvroType::Named(name) => self
+                .named
+                .get(name)
+                .filter(|t| !matches!(t, AvroType::Named(_)))
+                .ok_or_else(|| invalid(format!("unknown type '{name}'"))),
+            other => Ok(other),
+        }
+    }
+}
+
+// ============================================================================
+// Binary Encoding
+// ============================================================================
+
+fn check_depth(depth: usize) -> Result<(), AvroError> {
+    if depth >= DEFAULT_MAX_JSON_DEPTH {
+        return Err(AvroError::DepthLimitExceeded {
+            limit: DEFAULT_MAX_JSON_DEPTH,
+        });
+    }
+    Ok(())
+}
+
+fn mismatch(path: &str, expected: &'static str) -> AvroError {
+    AvroError::TypeMismatch {
+        path: path.to_string(),
+        expected,
+    }
+}
+
+fn write_long(value: i64, buf: &mut Vec<u8>) {
+    let mut zigzag = ((value << 1) ^ (value >> 63)) as u64;
+    while zigzag >= 0x80 {
+        buf.push((zigzag as u8) | 0x80);
+        zigzag >>= 7;
+    }
+    buf.push(zigzag as u8);
+}
+
+fn write_bytes(bytes: &[u8], buf: &mut Vec<u8>) {
+    write_long(bytes.len() as i64, buf);
+    buf.extend_from_slice(bytes);
+}
+
+fn json_bytes(value: &Value) -> Option<Vec<u8>> {
+    value
+        .as_str()
+        .and_then(|s| base64::engine::general_purpose::STANDARD.decode(s).ok())
+}
+
+fn accepts_null(schema: &AvroType) -> bool {
+    match schema {
+        AvroType::Null => true,
+        AvroType::Union(branches) => branches.iter().any(accepts_null),
+        _ => false,
+    }
+}
+
+impl AvroSchema {
+    fn encode_value(
+        &self,
+        schema: &AvroType,
+        value: &Value,
+        path: &str,
+        buf: &mut Vec<u8>,
+        depth: usize,
+    ) -> Result<(), AvroError> {
+        check_depth(depth)?;
+        match self.resolve(schema)? {
+            AvroType::Null => {
+                if !value.is_null() {
+                    return Err(mismatch(path, "null"));
+                }
+            }
+            AvroType::Boolean => {
+                let b = value.as_bool().ok_or_else(|| mismatch(path, "a boolean"))?;
+                buf.push(u8::from(b));
+            }
+            AvroType::Int => {
+                let n = json_i64(value)
+                    .filter(|n| i32::try_from(*n).is_ok())
+                    .ok_or_else(|| mismatch(path, "a 32-bit integer"))?;
+                write_long(n, buf);
+            }
+            AvroType::Long => {
+                let n = json_i64(value).ok_or_else(|| mismatch(path, "an integer"))?;
+                write_long(n, buf);
+            }
+            AvroType::Float => {
+                let n = value.as_f64().ok_or_else(|| mismatch(path, "a number"))?;
+                buf.extend_from_slice(&(n as f32).to_le_bytes());
+            }
+            AvroType::Double => {
+                let n = value.as_f64().ok_or_else(|| mismatch(path, "a number"))?;
+                buf.extend_from_slice(&n.to_le_bytes());
+            }
+            AvroType::Bytes => {
+                let bytes = json_bytes(value).ok_or_else(|| mismatch(path, "a base64 string"))?;
+                write_bytes(&bytes, buf);
+            }
+            AvroType::String => {
+                let s = value.as_str().ok_or_else(|| mismatch(path, "a string"))?;
+                write_bytes(s.as_bytes(), buf);
+            }
+            AvroType::Record { fields, .. } => {
+                let object = value
+                    .as_object()
+                    .ok_or_else(|| mismatch(path, "an object"))?;
+                for field in fields {
+                    let field_path = format!("{path}.{}", field.name);
+                    let field_value = match (object.get(&field.name), &field.default) {
+                        (Some(v), _) => v,
+                        (None, Some(default)) => default,
+                        (None, None) if accepts_null(&field.schema) => &Value::Null,
+                        (None, None) => return Err(mismatch(&field_path, "a value")),
+                    };
+                    self.encode_value(&field.schema, field_value, &field_path, buf, depth + 1)?;
+                }
+            }
+            AvroType::Enum { symbols, .. } => {
+                let index = value
+                    .as_str()
+                    .and_then(|s| symbols.iter().position(|symbol| symbol == s))
+                    .ok_or_else(|| mismatch(path, "an enum symbol"))?;
+                write_long(index as i64, buf);
+            }
+            AvroType::Array(items) => {
+                let array = value.as_array().ok_or_else(|| mismatch(path, "an array"))?;
+                if !array.is_empty() {
+                    write_long(array.len() as i64, buf);
+                    for (i, item) in array.iter().enumerate() {
+                        let item_path = format!("{path}[{i}]");
+                        self.encode_value(items, item, &item_path, buf, depth + 1)?;
+                    }
+                }
+                write_long(0, buf);
+            }
+            AvroType::Map(values) => {
+                let object = value
+                    .as_object()
+                    .ok_or_else(|| mismatch(path, "an object"))?;
+                if !object.is_empty() {
+                    write_long(object.len() as i64, buf);
+                    for (key, item) in object {
+                        write_bytes(key.as_bytes(), buf);
+                        let item_path = format!("{path}.{key}");
+                        self.encode_value(values, item, &item_path, buf, depth + 1)?;
+                    }
+                }
+                write_long(0, buf);
+            }
+            AvroType::Union(branches) => self.encode_union(branches, value, path, buf, depth)?,
+            AvroType::Fixed { size, .. } => {
+                let bytes = json_bytes(value)
+                    .filter(|b| b.len() == *size)
+                    .ok_or_else(|| mismatch(path, "a base64 string of the fixed size"))?;
+                buf.extend_from_slice(&bytes);
+            }
+            AvroType::Named(_) => unreachable!("resolved above"),
+        }
+        Ok(())
+    }
+
+    /// Encode the first branch that accepts `value`, or the branch named by
+    /// a `{"type": value}` wrapper.
+    fn encode_union(
+        &self,
+        branches: &[AvroType],
+        value: &Value,
+        path: &str,
+        buf: &mut Vec<u8>,
+        depth: usize,
+    ) -> Result<(), AvroError> {
+        if let Some((name, inner)) = value
+            .as_object()
+            .filter(|o| o.len() == 1)
+            .and_then(|o| o.iter().next())
+        {
+            if let Some(index) = branches.iter().position(|b| b.type_name() == name) {
+                write_long(index as i64, buf);
+                return self.encode_value(&branches[index], inner, path, buf, depth + 1);
+            }
+        }
+        for (index, branch) in branches.iter().enumerate() {
+            let mut attempt = Vec::new();
+            write_long(index as i64, &mut attempt);
+            if self
+                .encode_value(branch, value, path, &mut attempt, depth + 1)
+                .is_ok()
+            {
+                buf.extend_from_slice(&attempt);
+                return Ok(());
+            }
+        }
+        Err(mismatch(path, "a value matching a union branch"))
+    }
+}
+
+// ============================================================================
+// Binary Decoding
+// ============================================================================
+
+struct Reader<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, len: usize) -> Result<&[u8], AvroError> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.input.len())
+            .ok_or_else(|| AvroError::Malformed("unexpected end of data".to_string()))?;
+        let bytes = &self.input[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn long(&mut self) -> Result<i64, AvroError> {
+        let mut value: u64 = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.take(1)?[0];
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(((value >> 1) as i64) ^ -((value & 1) as i64));
+            }
+        }
+        Err(AvroError::Malformed("varint is too long".to_string()))
+    }
+
+    fn length(&mut self) -> Result<usize, AvroError> {
+        let len = self.long()?;
+        usize::try_from(len)
+            .ok()
+            .filter(|len| *len <= self.input.len() - self.pos)
+            .ok_or_else(|| AvroError::Malformed(format!("invalid length {len}")))
+    }
+
+    fn bytes(&mut self) -> Result<&[u8], AvroError> {
+        let len = self.length()?;
+        self.take(len)
+    }
+
+    /// Item count of the next array/map block; a negative count is followed
+    /// by the block's size in bytes.
+    fn block_count(&mut self) -> Result<usize, AvroError> {
+        let count = self.long()?;
+        if count < 0 {
+            self.long()?;
+        }
+        // Every item takes at least one byte, which bounds the allocation
+        usize::try_from(count.unsigned_abs())
+            .ok()
+            .filter(|count| *count <= self.input.len() - self.pos)
+            .ok_or_else(|| AvroError::Malformed(format!("invalid block count {count}")))
+    }
+}
+
+impl AvroSchema {
+    fn decode_value(
+        &self,
+        schema: &AvroType,
+        reader: &mut Reader<'_>,
+        depth: usize,
+    ) -> Result<Value, AvroError> {
+        check_depth(depth)?;
+        Ok(match self.resolve(schema)? {
+            AvroType::Null => Value::Null,
+            AvroType::Boolean => match reader.take(1)?[0] {
+                0 => Value::Bool(false),
+                1 => Value::Bool(true),
+                other => return Err(AvroError::Malformed(format!("invalid boolean {other}"))),
+            },
+            AvroType::Int => {
+                let n = reader.long()?;
+                i32::try_from(n)
+                    .map_err(|_| AvroError::Malformed(format!("int {n} out of range")))?
+                    .into()
+            }
+            AvroType::Long => reader.long()?.into(),
+            AvroType::Float => {
+                let bytes: [u8; 4] = reader.take(4)?.try_into().expect("took 4 bytes");
+                Value::from(f64::from(f32::from_le_bytes(bytes)))
+            }
+            AvroType::Double => {
+                let bytes: [u8; 8] = reader.take(8)?.try_into().expect("took 8 bytes");
+                Value::from(f64::from_le_bytes(bytes))
+            }
+            AvroType::Bytes => {
+                Value::String(base64::engine::general_purpose::STANDARD.encode(reader.bytes()?))
+            }
+            AvroType::String => {
+                let bytes = reader.bytes()?;
+                Value::String(
+                    std::str::from_utf8(bytes)
+                        .map_err(|_| AvroError::Malformed("invalid UTF-8 string".to_string()))?
+                        .to_string(),
+                )
+            }
+            AvroType::Record { fields, .. } => {
+                let mut object = Map::new();
+                for field in fields {
+                    let value = self.decode_value(&field.schema, reader, depth + 1)?;
+                    object.insert(field.name.clone(), value);
+                }
+                Value::Object(object)
+            }
+            AvroType::Enum { symbols, .. } => {
+                let index = reader.long()?;
+                let symbol = usize::try_from(index)
+                    .ok()
+                    .and_then(|i| symbols.get(i))
+                    .ok_or_else(|| AvroError::Malformed(format!("invalid enum index {index}")))?;
+                Value::String(symbol.clone())
+            }
+            AvroType::Array(items) => {
+                let mut array = Vec::new();
+                loop {
+                    let count = reader.block_count()?;
+                    if count == 0 {
+                        break;
+                    }
+                    for _ in 0..count {
+                        array.push(self.decode_value(items, reader, depth + 1)?);
+                    }
+                }
+                Value::Array(array)
+            }
+            AvroType::Map(values) => {
+                let mut object = Map::new();
+                loop {
+                    let count = reader.block_count()?;
+                    if count == 0 {
+                        break;
+                    }
+                    for _ in 0..count {
+                        let key = std::str::from_utf8(reader.bytes()?)
+                            .map_err(|_| AvroError::Malformed("invalid UTF-8 map key".to_string()))?
+                            .to_string();
+                        let value = self.decode_value(values, reader, depth + 1)?;
+                        object.insert(key, value);
+                    }
+                }
+                Value::Object(object)
+            }
+            AvroType::Union(branches) => {
+                let index = reader.long()?;
+                let branch = usize::try_from(index)
+                    .ok()
+                    .and_then(|i| branches.get(i))
+                    .ok_or_else(|| AvroError::Malformed(format!("invalid union index {index}")))?;
+                self.decode_value(branch, reader, depth + 1)?
+            }
+            AvroType::Fixed { size, .. } => {
+                Value::String(base64::engine::general_purpose::STANDARD.encode(reader.take(*size)?))
+            }
+            AvroType::Named(_) => unreachable!("resolved above"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn order_schema() -> AvroSchema {
+        AvroSchema::parse(
+            r#"{
+                "type": "record",
+                "name": "Order",
+                "namespace": "shop",
+                "fields": [
+                    {"name": "id", "type": "long"},
+                    {"name": "quantity", "type": "int", "default": 1},
+                    {"name": "status", "type": {"type": "enum", "name": "Status", "symbols": ["NEW", "PAID"]}},
+                    {"name": "note", "type": ["null", "string"]},
+                    {"name": "tags", "type": {"type": "array", "items": "string"}},
+                    {"name": "prices", "type": {"type": "map", "values": "double"}},
+                    {"name": "parent", "type": ["null", "Order"], "default": null},
+                    {"name": "digest", "type": {"type": "fixed", "name": "Digest", "size": 2}}
+                ]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_parse_schema() {
+        let schema = order_schema();
+        assert!(matches!(schema.root(), AvroType::Record { name, .. } if name == "shop.Order"));
+        assert!(schema.named("shop.Status").is_some());
+        assert!(schema.named("shop.Digest").is_some());
+
+        assert!(AvroSchema::parse(r#""string""#).is_ok());
+        assert!(matches!(
+            AvroSchema::parse(
+                r#"{"type": "record", "name": "A", "fields": [{"name": "b", "type": "Missing"}]}"#
+            ),
+            Err(AvroError::InvalidSchema(_))
+        ));
+        assert!(AvroSchema::parse("not json").is_err());
+    }
+
+    #[test]
+    fn test_encode_primitives() {
+        let long = AvroSchema::parse(r#""long""#).unwrap();
+        assert_eq!(long.encode(&json!(0)).unwrap(), vec![0x00]);
+        assert_eq!(long.encode(&json!(-1)).unwrap(), vec![0x01]);
+        assert_eq!(long.encode(&json!(64)).unwrap(), vec![0x80, 0x01]);
+        assert_eq!(long.decode(&[0x80, 0x01]).unwrap(), json!(64));
+
+        let string = AvroSchema::parse(r#""string""#).unwrap();
+        assert_eq!(
+            string.encode(&json!("foo")).unwrap(),
+            vec![0x06, b'f', b'o', b'o']
+        );
+
+        let int = AvroSchema::parse(r#"{"type": "int"}"#).unwrap();
+        assert!(matches!(
+            int.encode(&json!(1_i64 << 40)),
+            Err(AvroError::TypeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_record_roundtrip() {
+        let schema = order_schema();
+        let order = json!({
+            "id": 42,
+            "quantity": 3,
+            "status": "PAID",
+            "note": "gift",
+            "tags": ["a", "b"],
+            "prices": {"unit": 2.5},
+            "parent": {
+                "id": 41, "status": "NEW", "note": null, "tags": [],
+                "prices": {}, "digest": "AAE="
+            },
+            "digest": "AQI="
+        });
+        let bytes = schema.encode(&order).unwrap();
+        let decoded = schema.decode(&bytes).unwrap();
+        assert_eq!(decoded["id"], json!(42));
+        assert_eq!(decoded["status"], json!("PAID"));
+        assert_eq!(decoded["note"], json!("gift"));
+        assert_eq!(decoded["tags"], json!(["a", "b"]));
+        assert_eq!(decoded["prices"], json!({"unit": 2.5}));
+        assert_eq!(decoded["digest"], json!("AQI="));
+        // Defaults fill missing fields
+        assert_eq!(decoded["parent"]["quantity"], json!(1));
+        assert_eq!(decoded["parent"]["parent"], Value::Null);
+    }
+
+    #[test]
+    fn test_union_wrapper() {
+        let schema = AvroSchema::parse(r#"["null", "long", "string"]"#).unwrap();
+        assert_eq!(
+            schema.encode(&json!({"string": "7"})).unwrap(),
+            vec![0x04, 0x02, b'7']
+        );
+        assert_eq!(schema.encode(&json!(7)).unwrap(), vec![0x02, 0x0e]);
+        assert_eq!(schema.decode(&[0x00]).unwrap(), Value::Null);
+    }
+
+    #[test]
+    fn test_encode_errors() {
+        let schema = order_schema();
+        let err = schema
+            .encode(&json!({"id": 1, "status": "LOST", "tags": [], "prices": {}, "digest": "AQI="}))
+            .unwrap_err();
+        assert_eq!(
+            err,
+            AvroError::TypeMismatch {
+                path: "$.status".to_string(),
+                expected: "an enum symbol",
+            }
+        );
+        assert!(schema.encode(&json!({"status": "NEW"})).is_err());
+    }
+
+    #[test]
+    fn test_decode_malformed() {
+        let schema = AvroSchema::parse(r#""string""#).unwrap();
+        assert!(matches!(
+            schema.decode(&[0x06, b'f']),
+            Err(AvroError::Malformed(_))
+        ));
+        assert!(matches!(
+            schema.decode(&[0x02, b'f', 0x00]),
+            Err(AvroError::Malformed(_))
+        ));
+
+        let array = AvroSchema::parse(r#"{"type": "array", "items": "null"}"#).unwrap();
+        // A huge block count must not allocate or loop
+        assert!(array.decode(&[0xfe, 0xff, 0xff, 0xff, 0x0f]).is_err());
+    }
+}
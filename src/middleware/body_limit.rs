@@ -282,7 +282,7 @@ impl Middleware for BodyLimitMiddleware {
         }
 
         // Get Content-Type
-        let content_type = request.headers.get("content-type").map(|s| s.as_str());
+        let content_type = request.headers.get("content-type");
 
         // Get limit for this request
         let limit = self.config.get_limit(&request.path, content_type);
@@ -17,6 +17,7 @@ use std::time::{Duration, SystemTime};
 use std::future::Future;
 use std::pin::Pin;
 
+use super::memory_cache::MemoryCache;
 use super::{AsyncMiddleware, MiddlewareAction, MiddlewareResult};
 use crate::request::Request;
 use crate::response::Response;
@@ -362,7 +363,7 @@ pub struct CacheConfig {
 impl Default for CacheConfig {
     fn default() -> Self {
         Self {
-            store: Arc::new(InMemoryCacheStore::new()),
+            store: Arc::new(MemoryCache::<CachedResponse>::default()),
             key_builder: Arc::new(DefaultCacheKeyBuilder::new()),
             default_ttl: 300, // 5 minutes
             methods: vec!["GET".to_string(), "HEAD".to_string()],
@@ -494,7 +495,10 @@ impl CacheMiddleware {
 
     /// Check if response should be cached.
     fn should_cache_response(&self, response: &Response) -> bool {
-        self.config.status_codes.contains(&response.status)
+        // Streamed and file bodies are never held in full
+        !response.is_streaming()
+            && !response.is_file()
+            && self.config.status_codes.contains(&response.status)
     }
 
     /// Generate ETag for response.
@@ -0,0 +1,589 @@
+//! Choreography-mode sagas for Cello Framework.
+//!
+//! In a choreographed saga no orchestrator calls the participating services:
+//! each one reacts to and publishes events on a message broker. This module
+//! follows those events and advances a saga execution from them:
+//! - Topic bindings map events to a step completing, failing or being
+//!   compensated
+//! - A correlation ID on each message (the `x-saga-correlation-id` header,
+//!   falling back to the message key) selects the execution
+//! - An event for the first step with a new correlation ID starts an execution
+//! - When a step fails, compensation commands are published for the steps
+//!   completed before it
+//! - Once an execution finishes, its correlation ID is kept for a retention
+//!   period so redeliveries are still recognised, then dropped
+//!
+//! # Example
+//! ```rust,ignore
+//! let orchestrator = Arc::new(SagaOrchestrator::new());
+//! orchestrator.add_step("order", SagaStepDef::new("reserve"));
+//! orchestrator.add_step("order", SagaStepDef::new("charge"));
+//!
+//! let choreography = SagaChoreography::new(orchestrator, "order")
+//!     .on_completed("inventory.reserved", "reserve")
+//!     .on_completed("payment.charged", "charge")
+//!     .on_failed("payment.declined", "charge")
+//!     .with_compensation_topic("reserve", "inventory.release")
+//!     .on_compensated("inventory.released", "reserve")
+//!     .with_producer(producer);
+//!
+//! choreography.subscribe(&consumer)?;
+//! loop {
+//!     for execution in choreography.pump(&consumer)? {
+//!         println!("{} is {}", execution.id, execution.status);
+//!     }
+//! }
+//! ```
+
+use serde_json::{json, Value as JsonValue};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::RwLock;
+
+use super::messaging::{Message, MessageConsumer, MessageProducer, MessagingError};
+use super::saga::{SagaError, SagaExecution, SagaOrchestrator, SagaStatus};
+
+/// Header carrying the saga correlation ID on choreography messages.
+pub const CORRELATION_HEADER: &str = "x-saga-correlation-id";
+
+/// How long the correlation ID of a finished execution is kept by default.
+pub const DEFAULT_CORRELATION_RETENTION: Duration = Duration::from_secs(3600);
+
+/// What an event reports about a saga step.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StepEvent {
+    /// The named step completed; the payload is its result.
+    Completed(String),
+    /// The named step failed; the payload describes the error.
+    Failed(String),
+    /// The named step was compensated.
+    Compensated(String),
+}
+
+/// The execution tracked for a correlation ID.
+struct Correlation {
+    execution_id: String,
+    /// When the entry may be dropped, set once the execution has finished.
+    expires_at: Option<Instant>,
+}
+
+/// Saga whose steps advance on events consumed from a message broker.
+pub struct SagaChoreography {
+    orchestrator: Arc<SagaOrchestrator>,
+    saga_name: String,
+    /// Step events keyed by topic.
+    bindings: HashMap<String, StepEvent>,
+    /// Topics compensation commands are published to, keyed by step.
+    compensation_topics: HashMap<String, String>,
+    producer: Option<Arc<dyn MessageProducer>>,
+    /// Executions keyed by correlation ID.
+    correlations: RwLock<HashMap<String, Correlation>>,
+    /// How long a finished execution's correlation is kept.
+    retention: Duration,
+}
+
+impl SagaChoreography {
+    /// Follow events for `saga_name`, registered with `orchestrator`.
+    pub fn new(orchestrator: Arc<SagaOrchestrator>, saga_name: &str) -> Self {
+        Self {
+            orchestrator,
+            saga_name: saga_name.to_string(),
+            bindings: HashMap::new(),
+            compensation_topics: HashMap::new(),
+            producer: None,
+            correlations: RwLock::new(HashMap::new()),
+            retention: DEFAULT_CORRELATION_RETENTION,
+        }
+    }
+
+    /// Treat messages on `topic` as `step` completing.
+    pub fn on_completed(mut self, topic: &str, step: &str) -> Self {
+        self.bindings
+            .insert(topic.to_string(), StepEvent::Completed(step.to_string()));
+        self
+    }
+
+    /// Treat messages on `topic` as `step` failing.
+    pub fn on_failed(mut self, topic: &str, step: &str) -> Self {
+        self.bindings
+            .insert(topic.to_string(), StepEvent::Failed(step.to_string()));
+        self
+    }
+
+    /// Treat messages on `topic` as `step` having been compensated.
+    ///
+    /// A compensating step with such a binding stays `Compensating` until
+    /// the event arrives; without one it is considered compensated as soon
+    /// as its compensation command is published.
+    pub fn on_compensated(mut self, topic: &str, step: &str) -> Self {
+        self.bindings
+            .insert(topic.to_string(), StepEvent::Compensated(step.to_string()));
+        self
+    }
+
+    /// Publish a command to `topic` when `step` must be compensated.
+    ///
+    /// The command is keyed by the correlation ID and carries the
+    /// `correlation_id`, `execution_id`, `step`, its `result` and the saga
+    /// `input` as JSON.
+    pub fn with_compensation_topic(mut self, step: &str, topic: &str) -> Self {
+        self.compensation_topics
+            .insert(step.to_string(), topic.to_string());
+        self
+    }
+
+    /// Producer used to publish compensation commands.
+    pub fn with_producer(mut self, producer: Arc<dyn MessageProducer>) -> Self {
+        self.producer = Some(producer);
+        self
+    }
+
+    /// Keep the correlation ID of a finished execution for `retention`
+    /// (default one hour).
+    ///
+    /// Until then, redelivered events for the execution are ignored; after
+    /// it, a redelivered first-step event would start a new execution.
+    /// Expired correlations are dropped as new executions start.
+    pub fn with_retention(mut self, retention: Duration) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// Topics this choreography consumes.
+    pub fn topics(&self) -> Vec<&str> {
+        let mut topics: Vec<&str> = self.bindings.keys().map(String::as_str).collect();
+        topics.sort_unstable();
+        topics
+    }
+
+    /// Subscribe `consumer` to every bound topic.
+    pub fn subscribe(&self, consumer: &dyn MessageConsumer) -> Result<(), MessagingError> {
+        consumer.subscribe(&self.topics())
+    }
+
+    /// Execution ID tracked for `correlation_id`, if any.
+    pub fn execution_id(&self, correlation_id: &str) -> Option<String> {
+        self.correlations
+            .read()
+            .get(correlation_id)
+            .map(|c| c.execution_id.clone())
+    }
+
+    /// Number of correlation IDs tracked, including finished executions
+    /// still within their retention period.
+    pub fn correlation_count(&self) -> usize {
+        self.correlations.read().len()
+    }
+
+    /// Correlation ID of a message: the correlation header, else its key.
+    pub fn correlation_id(message: &Message) -> Option<&str> {
+        message
+            .headers
+            .get(CORRELATION_HEADER)
+            .map(String::as_str)
+            .or(message.key.as_deref())
+    }
+
+    /// Apply one message to its saga execution.
+    ///
+    /// Returns the updated execution, or `None` for messages on unbound
+    /// topics. Events for executions that have already finished are ignored
+    /// so that redeliveries are harmless.
+    pub fn handle(&self, message: &Message) -> Result<Option<SagaExecution>, SagaError> {
+        let Some(event) = self.bindings.get(&message.topic) else {
+            return Ok(None);
+        };
+        let correlation_id = Self::correlation_id(message)
+            .ok_or_else(|| SagaError::MissingCorrelation(message.id.clone()))?;
+        let execution_id = self.resolve(correlation_id, event, message)?;
+
+        let execution = self.orchestrator.get_execution(&execution_id)?;
+        if is_finished(&execution.status) {
+            self.retire(correlation_id);
+            return Ok(Some(execution));
+        }
+
+        match event {
+            StepEvent::Completed(step) if execution.status == SagaStatus::Running => {
+                self.orchestrator
+                    .complete_step(&execution_id, step, Some(payload(message)))?;
+            }
+            StepEvent::Completed(_) => {}
+            StepEvent::Failed(step) if execution.status == SagaStatus::Running => {
+                let error = failure_message(message);
+                let pending = self
+                    .orchestrator
+                    .begin_compensation(&execution_id, step, &error)?;
+                for (step, result) in pending {
+                    self.request_compensation(correlation_id, &execution, &step, result)?;
+                }
+            }
+            StepEvent::Failed(_) => {}
+            StepEvent::Compensated(step) => {
+                // Only steps still awaiting compensation can settle.
+                match self
+                    .orchestrator
+                    .finish_compensation(&execution_id, step, Ok(()))
+                {
+                    Ok(()) | Err(SagaError::StepNotFound(_)) => {}
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+
+        let execution = self.orchestrator.get_execution(&execution_id)?;
+        if is_finished(&execution.status) {
+            self.retire(correlation_id);
+        }
+        Ok(Some(execution))
+    }
+
+    /// Poll `consumer` once, apply each message and commit it.
+    ///
+    /// Returns the executions that messages advanced. Messages that cannot be
+    /// applied (no correlation ID, unknown execution) are committed so they
+    /// are not redelivered forever, and logged when saga logging is enabled.
+    pub fn pump(
+        &self,
+        consumer: &dyn MessageConsumer,
+    ) -> Result<Vec<SagaExecution>, MessagingError> {
+        let mut advanced = Vec::new();
+        for message in consumer.poll()? {
+            match self.handle(&message) {
+                Ok(Some(execution)) => advanced.push(execution),
+                Ok(None) => {}
+                Err(e) => {
+                    if self.orchestrator.config().enable_logging {
+                        println!(
+                            "Saga '{}' ignored message '{}' on '{}': {e}",
+                            self.saga_name, message.id, message.topic
+                        );
+                    }
+                }
+            }
+            consumer.commit(&message)?;
+        }
+        Ok(advanced)
+    }
+
+    /// Find the execution for `correlation_id`, starting one when the event
+    /// is about the saga's first step.
+    fn resolve(
+        &self,
+        correlation_id: &str,
+        event: &StepEvent,
+        message: &Message,
+    ) -> Result<String, SagaError> {
+        if let Some(execution_id) = self.execution_id(correlation_id) {
+            return Ok(execution_id);
+        }
+
+        let step = match event {
+            StepEvent::Completed(step) | StepEvent::Failed(step) => step,
+            StepEvent::Compensated(_) => {
+                return Err(SagaError::ExecutionNotFound(correlation_id.to_string()))
+            }
+        };
+        if !self.orchestrator.is_first_step(&self.saga_name, step)? {
+            return Err(SagaError::ExecutionNotFound(correlation_id.to_string()));
+        }
+
+        let mut correlations = self.correlations.write();
+        if let Some(correlation) = correlations.get(correlation_id) {
+            return Ok(correlation.execution_id.clone());
+        }
+        let execution_id = self
+            .orchestrator
+            .start_execution_with_input(&self.saga_name, payload(message))?;
+        // Expired correlations are dropped as new ones come in
+        let now = Instant::now();
+        correlations.retain(|_, c| c.expires_at.is_none_or(|at| at > now));
+        correlations.insert(
+            correlation_id.to_string(),
+            Correlation {
+                execution_id: execution_id.clone(),
+                expires_at: None,
+            },
+        );
+        Ok(execution_id)
+    }
+
+    /// Start the retention period of a finished execution's correlation.
+    fn retire(&self, correlation_id: &str) {
+        if let Some(correlation) = self.correlations.write().get_mut(correlation_id) {
+            correlation
+                .expires_at
+                .get_or_insert_with(|| Instant::now() + self.retention);
+        }
+    }
+
+    /// Publish the compensation command for `step`, or settle the step when
+    /// no service confirms compensation through an event.
+    fn request_compensation(
+        &self,
+        correlation_id: &str,
+        execution: &SagaExecution,
+        step: &str,
+        result: JsonValue,
+    ) -> Result<(), SagaError> {
+        let confirmed = self
+            .bindings
+            .values()
+            .any(|e| matches!(e, StepEvent::Compensated(s) if s == step));
+
+        let outcome = match (self.compensation_topics.get(step), &self.producer) {
+            (Some(topic), Some(producer)) => {
+                let command = json!({
+                    "correlation_id": correlation_id,
+                    "execution_id": execution.id,
+                    "step": step,
+                    "result": result,
+                    "input": execution.input,
+                });
+                producer
+                    .send(topic, Some(correlation_id), command.to_string().as_bytes())
+                    .map_err(|e| format!("cannot publish to '{topic}': {e}"))
+            }
+            (Some(topic), None) => Err(format!("no producer to publish to '{topic}'")),
+            (None, _) => Ok(()),
+        };
+
+        match outcome {
+            Ok(()) if confirmed => Ok(()),
+            outcome => self
+                .orchestrator
+                .finish_compensation(&execution.id, step, outcome),
+        }
+    }
+}
+
+/// Whether an execution has reached a final status.
+fn is_finished(status: &SagaStatus) -> bool {
+    matches!(
+        status,
+        SagaStatus::Completed | SagaStatus::Compensated | SagaStatus::Failed
+    )
+}
+
+/// Message payload as JSON, falling back to a string or null.
+fn payload(message: &Message) -> JsonValue {
+    message
+        .value_json()
+        .or_else(|| {
+            message
+                .value_str()
+                .map(|s| JsonValue::String(s.to_string()))
+        })
+        .unwrap_or(JsonValue::Null)
+}
+
+/// Error reported by a failure event: its `error` field, or the payload.
+fn failure_message(message: &Message) -> String {
+    match payload(message) {
+        JsonValue::Object(map) => match map.get("error") {
+            Some(JsonValue::String(error)) => error.clone(),
+            Some(error) => error.to_string(),
+            None => JsonValue::Object(map).to_string(),
+        },
+        JsonValue::String(error) if !error.is_empty() => error,
+        JsonValue::Null => "step failed".to_string(),
+        other => other.to_string(),
+    }
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::messaging::{MockConsumer, MockProducer};
+    use crate::middleware::saga::{SagaConfig, SagaStepDef, StepStatus};
+
+    fn event(topic: &str, correlation_id: &str, value: JsonValue) -> Message {
+        Message {
+            id: format!("{topic}-{correlation_id}"),
+            topic: topic.to_string(),
+            key: None,
+            value: value.to_string().into_bytes(),
+            headers: HashMap::from([(CORRELATION_HEADER.to_string(), correlation_id.to_string())]),
+            timestamp: 0,
+            partition: None,
+            offset: None,
+        }
+    }
+
+    fn order_choreography(producer: Arc<MockProducer>) -> SagaChoreography {
+        let orchestrator = Arc::new(SagaOrchestrator::with_config(
+            SagaConfig::new().with_logging(false),
+        ));
+        for step in ["reserve", "charge", "ship"] {
+            orchestrator.add_step("order", SagaStepDef::new(step));
+        }
+        SagaChoreography::new(orchestrator, "order")
+            .on_completed("inventory.reserved", "reserve")
+            .on_completed("payment.charged", "charge")
+            .on_completed("order.shipped", "ship")
+            .on_failed("shipping.failed", "ship")
+            .with_compensation_topic("reserve", "inventory.release")
+            .on_compensated("inventory.released", "reserve")
+            .with_compensation_topic("charge", "payment.refund")
+            .with_producer(producer)
+    }
+
+    #[test]
+    fn test_events_advance_execution() {
+        let choreography = order_choreography(Arc::new(MockProducer::new()));
+        let consumer = MockConsumer::new();
+        choreography.subscribe(&consumer).unwrap();
+        assert_eq!(consumer.subscriptions().len(), 5);
+
+        consumer.enqueue(event("inventory.reserved", "o-1", json!({"sku": "A"})));
+        consumer.enqueue(event("payment.charged", "o-1", json!({"amount": 5})));
+        consumer.enqueue(event("order.shipped", "o-1", json!("track-1")));
+        let advanced = choreography.pump(&consumer).unwrap();
+
+        let execution = advanced.last().unwrap();
+        assert_eq!(execution.status, SagaStatus::Completed);
+        assert_eq!(execution.input, json!({"sku": "A"}));
+        assert_eq!(execution.steps[2].result, Some(json!("track-1")));
+        assert_eq!(consumer.committed_ids().len(), 3);
+    }
+
+    #[test]
+    fn test_failure_publishes_compensations() {
+        let producer = Arc::new(MockProducer::new());
+        let choreography = order_choreography(producer.clone());
+        for message in [
+            event("inventory.reserved", "o-2", json!({"sku": "B"})),
+            event("payment.charged", "o-2", json!({"payment": "p-1"})),
+            event("shipping.failed", "o-2", json!({"error": "no courier"})),
+        ] {
+            choreography.handle(&message).unwrap();
+        }
+
+        let sent = producer.sent_messages();
+        let topics: Vec<&str> = sent.iter().map(|m| m.topic.as_str()).collect();
+        assert_eq!(topics, ["payment.refund", "inventory.release"]);
+        let refund = sent[0].value_json().unwrap();
+        assert_eq!(refund["result"], json!({"payment": "p-1"}));
+        assert_eq!(sent[0].key.as_deref(), Some("o-2"));
+
+        // The refund has no confirmation event; the release waits for one.
+        let execution_id = choreography.execution_id("o-2").unwrap();
+        let execution = choreography
+            .orchestrator
+            .get_execution(&execution_id)
+            .unwrap();
+        assert_eq!(execution.status, SagaStatus::Compensating);
+        assert_eq!(execution.steps[1].status, StepStatus::Compensated);
+        assert_eq!(execution.steps[2].error.as_deref(), Some("no courier"));
+
+        let execution = choreography
+            .handle(&event("inventory.released", "o-2", JsonValue::Null))
+            .unwrap()
+            .unwrap();
+        assert_eq!(execution.status, SagaStatus::Compensated);
+    }
+
+    #[test]
+    fn test_compensation_without_producer_fails() {
+        let orchestrator = Arc::new(SagaOrchestrator::with_config(
+            SagaConfig::new().with_logging(false),
+        ));
+        orchestrator.add_step("pay", SagaStepDef::new("charge"));
+        orchestrator.add_step("pay", SagaStepDef::new("notify"));
+        let choreography = SagaChoreography::new(orchestrator, "pay")
+            .on_completed("charged", "charge")
+            .on_failed("notify.failed", "notify")
+            .with_compensation_topic("charge", "refund");
+
+        choreography
+            .handle(&event("charged", "p", json!(1)))
+            .unwrap();
+        let execution = choreography
+            .handle(&event("notify.failed", "p", json!("smtp down")))
+            .unwrap()
+            .unwrap();
+        assert_eq!(execution.status, SagaStatus::Failed);
+        assert_eq!(execution.compensation_errors.len(), 1);
+    }
+
+    #[test]
+    fn test_correlation_and_unknown_events() {
+        let choreography = order_choreography(Arc::new(MockProducer::new()));
+
+        let mut keyed = event("inventory.reserved", "ignored", json!({}));
+        keyed.headers.clear();
+        keyed.key = Some("o-3".to_string());
+        choreography.handle(&keyed).unwrap();
+        assert!(choreography.execution_id("o-3").is_some());
+
+        keyed.key = None;
+        assert_eq!(
+            choreography.handle(&keyed).unwrap_err(),
+            SagaError::MissingCorrelation(keyed.id.clone())
+        );
+        // Only the first step can start an execution.
+        assert_eq!(
+            choreography
+                .handle(&event("payment.charged", "o-4", json!({})))
+                .unwrap_err(),
+            SagaError::ExecutionNotFound("o-4".to_string())
+        );
+        assert!(choreography
+            .handle(&event("unrelated", "o-3", json!({})))
+            .unwrap()
+            .is_none());
+
+        // Redelivering the first event does not start a second execution.
+        choreography
+            .handle(&event("inventory.reserved", "o-3", json!({})))
+            .unwrap();
+        assert_eq!(choreography.orchestrator.execution_count(), 1);
+    }
+
+    #[test]
+    fn test_finished_correlations_expire() {
+        let choreography = order_choreography(Arc::new(MockProducer::new()))
+            .with_retention(Duration::from_millis(20));
+        for message in [
+            event("inventory.reserved", "o-5", json!({})),
+            event("payment.charged", "o-5", json!({})),
+            event("order.shipped", "o-5", json!({})),
+        ] {
+            choreography.handle(&message).unwrap();
+        }
+        choreography
+            .handle(&event("inventory.reserved", "o-6", json!({})))
+            .unwrap();
+        assert_eq!(choreography.correlation_count(), 2);
+
+        // Within the retention period a redelivery is still ignored.
+        let execution = choreography
+            .handle(&event("order.shipped", "o-5", json!({})))
+            .unwrap()
+            .unwrap();
+        assert_eq!(execution.status, SagaStatus::Completed);
+
+        std::thread::sleep(Duration::from_millis(30));
+        choreography
+            .handle(&event("inventory.reserved", "o-7", json!({})))
+            .unwrap();
+        assert!(choreography.execution_id("o-5").is_none());
+        assert!(choreography.execution_id("o-6").is_some());
+        assert_eq!(choreography.correlation_count(), 2);
+    }
+
+    #[test]
+    fn test_failure_message() {
+        let failure = |value: JsonValue| failure_message(&event("t", "c", value));
+        assert_eq!(failure(json!({"error": "declined"})), "declined");
+        assert_eq!(failure(json!({"error": {"code": 5}})), r#"{"code":5}"#);
+        assert_eq!(failure(json!("timeout")), "timeout");
+        assert_eq!(failure(JsonValue::Null), "step failed");
+    }
+}
@@ -315,7 +315,7 @@ impl CorsMiddleware {
 
     /// Get origin from request.
     fn get_origin(&self, request: &Request) -> Option<String> {
-        request.headers.get("origin").cloned()
+        request.headers.get("origin").map(str::to_string)
     }
 
     /// Build preflight response.
@@ -3,6 +3,12 @@
 //! Provides a complete CQRS implementation with:
 //! - Command bus for dispatching write operations
 //! - Query bus for dispatching read operations
+//! - Async Rust and Python handlers dispatched through Tokio
+//! - Per-dispatch timeouts and concurrent query execution
+//! - Middleware pipeline around both buses (validation, authorization,
+//!   logging, idempotency-key deduplication) and command retries
+//! - Event synchronization: events recorded by successful commands are
+//!   appended to an event store and optionally published to a topic
 //! - Configurable timeouts and retry policies
 //! - Statistics and monitoring
 //! - In-memory handler registries for development and testing
@@ -37,13 +43,23 @@
 //!     return {"query": query}
 //! ```
 
+use pyo3::prelude::*;
+use pyo3::types::PyTuple;
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
+
+use super::eventsourcing::{Event, EventSourcingError, EventStore, EventTransaction};
+use super::messaging::MessageProducer;
+use crate::event_loop::{call_with_timeout, CallError};
+use crate::json::{json_to_python, python_to_json};
 
 // ============================================================================
 // Configuration
@@ -55,13 +71,16 @@ use parking_lot::RwLock;
 /// retry policies, and event synchronization.
 #[derive(Clone, Debug)]
 pub struct CqrsConfig {
-    /// Whether to synchronize commands with the event store.
+    /// Whether to append the events recorded by successful commands to the
+    /// command bus's event store.
     pub enable_event_sync: bool,
-    /// Maximum time in milliseconds to wait for a command to complete.
+    /// Maximum time in milliseconds to wait for a command to complete
+    /// (0 = no limit).
     pub command_timeout_ms: u64,
-    /// Maximum time in milliseconds to wait for a query to complete.
+    /// Maximum time in milliseconds to wait for a query to complete
+    /// (0 = no limit).
     pub query_timeout_ms: u64,
-    /// Maximum number of retries for failed commands.
+    /// Maximum number of retries for commands that fail or time out.
     pub max_retries: u32,
 }
 
@@ -212,6 +231,9 @@ pub struct QueryDef {
     pub query_type: String,
     /// Query parameters.
     pub params: JsonValue,
+    /// Additional metadata (e.g., user ID, roles).
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
     /// Unix timestamp (seconds) when the query was created.
     pub timestamp: u64,
 }
@@ -230,6 +252,7 @@ impl QueryDef {
             ),
             query_type: query_type.to_string(),
             params,
+            metadata: HashMap::new(),
             timestamp: std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap_or_default()
@@ -237,10 +260,21 @@ impl QueryDef {
         }
     }
 
+    /// Add metadata to the query.
+    pub fn with_metadata(mut self, key: &str, value: &str) -> Self {
+        self.metadata.insert(key.to_string(), value.to_string());
+        self
+    }
+
     /// Get the query type.
     pub fn query_type(&self) -> &str {
         &self.query_type
     }
+
+    /// Get a metadata value by key.
+    pub fn get_metadata(&self, key: &str) -> Option<&str> {
+        self.metadata.get(key).map(|s| s.as_str())
+    }
 }
 
 /// Result of executing a query.
@@ -275,8 +309,96 @@ impl QueryResult {
 // Command Bus
 // ============================================================================
 
-/// Type alias for a synchronous command handler function.
-pub type CommandHandlerFn = Arc<dyn Fn(&Command) -> CommandResult + Send + Sync>;
+/// Future returned by a command handler.
+pub type CommandFuture = Pin<Box<dyn Future<Output = CommandResult> + Send>>;
+
+/// Future of one bounded command handler call; `None` if it ran out of time.
+type CommandAttemptFuture = Pin<Box<dyn Future<Output = Option<CommandResult>> + Send>>;
+
+/// Handler processing one command type.
+///
+/// The handler receives its own copy of the command, so async handlers can
+/// hold it across awaits.
+#[derive(Clone)]
+pub struct CommandHandler(
+    Arc<dyn Fn(Command, Option<Duration>) -> CommandAttemptFuture + Send + Sync>,
+);
+
+impl CommandHandler {
+    /// Create a handler from an async Rust closure.
+    pub fn new<F, Fut>(handler: F) -> Self
+    where
+        F: Fn(Command) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = CommandResult> + Send + 'static,
+    {
+        Self(Arc::new(move |command, timeout| {
+            Box::pin(bounded(timeout, handler(command)))
+        }))
+    }
+
+    /// Create a handler from a synchronous Rust closure.
+    pub fn sync<F>(handler: F) -> Self
+    where
+        F: Fn(&Command) -> CommandResult + Send + Sync + 'static,
+    {
+        Self::new(move |command| std::future::ready(handler(&command)))
+    }
+
+    /// Create a handler from a sync or async Python callable, called with
+    /// the command as a dict.
+    ///
+    /// A returned `cello.cqrs.CommandResult` is used as is and any other
+    /// value becomes a successful result. A raised exception fails the
+    /// command. A call that times out is stopped (see
+    /// [`call_with_timeout`](crate::event_loop::call_with_timeout)).
+    pub fn python(handler: PyObject) -> Self {
+        let handler = Arc::new(handler);
+        Self(Arc::new(move |command, timeout| {
+            let handler = handler.clone();
+            Box::pin(async move {
+                let command = serde_json::to_value(&command).unwrap_or(JsonValue::Null);
+                Some(
+                    match call_python_handler(handler, command, timeout).await? {
+                        Ok(result) => {
+                            Python::with_gil(|py| command_result_from_python(py, &result))
+                                .unwrap_or_else(CommandResult::Failure)
+                        }
+                        Err(e) => CommandResult::Failure(e),
+                    },
+                )
+            })
+        }))
+    }
+
+    /// Invoke the handler.
+    pub fn call(&self, command: Command) -> CommandFuture {
+        let attempt = (self.0)(command, None);
+        Box::pin(async move {
+            attempt
+                .await
+                .unwrap_or_else(|| CommandResult::Failure("timed out".to_string()))
+        })
+    }
+
+    /// Invoke the handler, giving up once `timeout` passes (`None` = no
+    /// limit). Returns `None` if it did.
+    pub fn call_with_timeout(
+        &self,
+        command: Command,
+        timeout: Option<Duration>,
+    ) -> impl Future<Output = Option<CommandResult>> + Send {
+        (self.0)(command, timeout)
+    }
+}
+
+impl std::fmt::Debug for CommandHandler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("CommandHandler")
+    }
+}
+
+/// Producer and topic that synchronized events are published to.
+type EventPublisher = (Arc<dyn MessageProducer>, String);
 
 /// Command bus for dispatching commands to registered handlers.
 ///
@@ -284,7 +406,13 @@ pub type CommandHandlerFn = Arc<dyn Fn(&Command) -> CommandResult + Send + Sync>
 /// by their `command_type` field.
 pub struct CommandBus {
     /// Registered command handlers keyed by command type.
-    handlers: Arc<RwLock<HashMap<String, CommandHandlerFn>>>,
+    handlers: Arc<RwLock<HashMap<String, CommandHandler>>>,
+    /// Middleware run around every dispatch, in priority order.
+    middleware: Arc<RwLock<Vec<Arc<dyn CommandMiddleware>>>>,
+    /// Store receiving the events recorded by successful commands.
+    event_store: Arc<RwLock<Option<Arc<dyn EventStore>>>>,
+    /// Producer and topic that stored events are published to.
+    event_publisher: Arc<RwLock<Option<EventPublisher>>>,
     /// Internal metrics tracker.
     metrics: Arc<CqrsMetrics>,
     /// Configuration reference.
@@ -296,6 +424,9 @@ impl CommandBus {
     pub fn new() -> Self {
         Self {
             handlers: Arc::new(RwLock::new(HashMap::new())),
+            middleware: Arc::new(RwLock::new(Vec::new())),
+            event_store: Arc::new(RwLock::new(None)),
+            event_publisher: Arc::new(RwLock::new(None)),
             metrics: Arc::new(CqrsMetrics::default()),
             config: CqrsConfig::default(),
         }
@@ -305,38 +436,173 @@ impl CommandBus {
     pub fn with_config(config: CqrsConfig) -> Self {
         Self {
             handlers: Arc::new(RwLock::new(HashMap::new())),
+            middleware: Arc::new(RwLock::new(Vec::new())),
+            event_store: Arc::new(RwLock::new(None)),
+            event_publisher: Arc::new(RwLock::new(None)),
             metrics: Arc::new(CqrsMetrics::default()),
             config,
         }
     }
 
-    /// Register a handler for a specific command type.
+    /// Register a synchronous handler for a specific command type.
     pub fn register<F>(&self, command_type: &str, handler: F)
     where
         F: Fn(&Command) -> CommandResult + Send + Sync + 'static,
     {
+        self.register_handler(command_type, CommandHandler::sync(handler));
+    }
+
+    /// Register an async handler for a specific command type.
+    pub fn register_async<F, Fut>(&self, command_type: &str, handler: F)
+    where
+        F: Fn(Command) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = CommandResult> + Send + 'static,
+    {
+        self.register_handler(command_type, CommandHandler::new(handler));
+    }
+
+    /// Register a handler for a specific command type.
+    pub fn register_handler(&self, command_type: &str, handler: CommandHandler) {
         self.handlers
             .write()
-            .insert(command_type.to_string(), Arc::new(handler));
+            .insert(command_type.to_string(), handler);
     }
 
-    /// Dispatch a command to its registered handler.
-    pub fn dispatch(&self, command: &Command) -> Result<CommandResult, CqrsError> {
-        let handlers = self.handlers.read();
-        match handlers.get(&command.command_type) {
-            Some(handler) => {
-                self.metrics.record_command_processed();
-                let result = handler(command);
-                if result.is_failure() {
+    /// Add middleware run around every dispatch.
+    ///
+    /// `before` hooks run in priority order (lower first) and `after` hooks
+    /// in reverse.
+    pub fn add_middleware<M: CommandMiddleware + 'static>(&self, middleware: M) {
+        let mut chain = self.middleware.write();
+        chain.push(Arc::new(middleware));
+        chain.sort_by_key(|m| m.priority());
+    }
+
+    /// Append the events recorded by successful commands to `store`.
+    ///
+    /// Only used while `enable_event_sync` is set in the configuration. See
+    /// [`CommandBus::dispatch`] for how handlers record events.
+    pub fn set_event_store(&self, store: Arc<dyn EventStore>) {
+        *self.event_store.write() = Some(store);
+    }
+
+    /// Publish every synchronized event as JSON to `topic`, keyed by its
+    /// aggregate ID.
+    pub fn set_event_publisher(&self, producer: Arc<dyn MessageProducer>, topic: &str) {
+        *self.event_publisher.write() = Some((producer, topic.to_string()));
+    }
+
+    /// Dispatch a command through the middleware to its registered handler.
+    ///
+    /// A failed or timed-out attempt is retried up to the configured
+    /// `max_retries`; each attempt is bounded by the command timeout, and a
+    /// retry starts only once the timed-out attempt has stopped, so two
+    /// attempts never run at once. Rejections are not retried.
+    ///
+    /// With event sync enabled and an event store set, a successful result
+    /// whose data holds an `events` list has those events appended to the
+    /// store before it is returned. Each entry needs `aggregate_id` and
+    /// `event_type` and may carry `data`, `metadata` and `expected_version`
+    /// (defaults to the aggregate's current version). The events are
+    /// appended as one [`EventTransaction`], atomically on stores that
+    /// support it, and replace the entries in the result; a concurrency
+    /// conflict on any aggregate fails the attempt, so the handler is
+    /// retried against the new state.
+    pub async fn dispatch(&self, command: &Command) -> Result<CommandResult, CqrsError> {
+        let handler = self.handlers.read().get(&command.command_type).cloned();
+        let Some(handler) = handler else {
+            self.metrics.record_command_error();
+            return Err(CqrsError::CommandNotFound(command.command_type.clone()));
+        };
+
+        self.metrics.record_command_processed();
+        let mut command = command.clone();
+        let middleware = self.middleware.read().clone();
+        for m in &middleware {
+            match m.before(&mut command) {
+                Ok(BusAction::Continue) => {}
+                Ok(BusAction::Stop(result)) => return Ok(result),
+                Err(e) => {
                     self.metrics.record_command_error();
+                    return Err(e);
                 }
-                Ok(result)
             }
-            None => {
-                self.metrics.record_command_error();
-                Err(CqrsError::CommandNotFound(command.command_type.clone()))
+        }
+
+        let mut attempt = 0;
+        let mut result = loop {
+            let outcome = handler
+                .call_with_timeout(command.clone(), limit(self.config.command_timeout_ms))
+                .await;
+            let outcome = outcome.map(|result| self.sync_events(&command, result));
+            let retryable = outcome.as_ref().is_none_or(CommandResult::is_failure);
+            if !retryable || attempt >= self.config.max_retries {
+                break outcome;
+            }
+            attempt += 1;
+        }
+        .ok_or_else(|| {
+            self.metrics.record_command_error();
+            CqrsError::TimeoutError(format!(
+                "command '{}' did not complete within {}ms",
+                command.command_type, self.config.command_timeout_ms
+            ))
+        })?;
+
+        for m in middleware.iter().rev() {
+            m.after(&command, &mut result);
+        }
+        if result.is_failure() {
+            self.metrics.record_command_error();
+        }
+        Ok(result)
+    }
+
+    /// Append the events recorded in a successful result to the event
+    /// store and publish them, returning the result with the stored events.
+    fn sync_events(&self, command: &Command, result: CommandResult) -> CommandResult {
+        if !self.config.enable_event_sync {
+            return result;
+        }
+        let Some(store) = self.event_store.read().clone() else {
+            return result;
+        };
+        let CommandResult::Success(mut data) = result else {
+            return result;
+        };
+        let Some(recorded) = data.get("events").cloned() else {
+            return CommandResult::Success(data);
+        };
+        let recorded: Vec<RecordedEvent> = match serde_json::from_value(recorded) {
+            Ok(recorded) => recorded,
+            Err(e) => return CommandResult::Failure(format!("Invalid recorded events: {e}")),
+        };
+
+        // Group by aggregate, keeping the order events were recorded in.
+        let mut streams: Vec<(String, Vec<RecordedEvent>)> = Vec::new();
+        for event in recorded {
+            match streams.iter_mut().find(|(id, _)| *id == event.aggregate_id) {
+                Some((_, events)) => events.push(event),
+                None => streams.push((event.aggregate_id.clone(), vec![event])),
+            }
+        }
+
+        let stored = match append_recorded(store.as_ref(), command, streams) {
+            Ok(stored) => stored,
+            Err(e) => return CommandResult::Failure(e.to_string()),
+        };
+
+        if let Some((producer, topic)) = self.event_publisher.read().clone() {
+            for event in &stored {
+                let payload = serde_json::to_vec(event).unwrap_or_default();
+                if let Err(e) = producer.send(&topic, Some(&event.aggregate_id), &payload) {
+                    tracing::warn!("Failed to publish event {} to {topic}: {e}", event.id);
+                }
             }
         }
+
+        data["events"] = serde_json::to_value(&stored).unwrap_or(JsonValue::Null);
+        CommandResult::Success(data)
     }
 
     /// Check if a handler is registered for a given command type.
@@ -366,12 +632,156 @@ impl Default for CommandBus {
     }
 }
 
+/// An event recorded in a successful command result, not yet stored.
+#[derive(Deserialize)]
+struct RecordedEvent {
+    aggregate_id: String,
+    event_type: String,
+    #[serde(default)]
+    data: JsonValue,
+    #[serde(default)]
+    metadata: HashMap<String, String>,
+    #[serde(default)]
+    expected_version: Option<u64>,
+}
+
+/// Add the events a command recorded for one aggregate, tagged with the
+/// command's ID and type, to `transaction`.
+fn add_recorded(
+    store: &dyn EventStore,
+    transaction: EventTransaction,
+    command: &Command,
+    aggregate_id: &str,
+    recorded: Vec<RecordedEvent>,
+) -> Result<EventTransaction, EventSourcingError> {
+    let expected_version = match recorded.first().and_then(|e| e.expected_version) {
+        Some(version) => version,
+        None => match store.get_events(aggregate_id, None) {
+            Ok(events) => events.last().map(|e| e.version).unwrap_or(0),
+            Err(EventSourcingError::AggregateNotFound(_)) => 0,
+            Err(e) => return Err(e),
+        },
+    };
+    let events: Vec<Event> = recorded
+        .into_iter()
+        .zip(expected_version + 1..)
+        .map(|(recorded, version)| {
+            let mut event = Event::new(aggregate_id, &recorded.event_type, recorded.data, version);
+            event.metadata = recorded.metadata;
+            event
+                .with_metadata("command_id", &command.id)
+                .with_metadata("command_type", &command.command_type)
+        })
+        .collect();
+    Ok(transaction.append(aggregate_id, expected_version, events))
+}
+
+/// Append the events a command recorded, grouped by aggregate, as one
+/// transaction and return them as stored.
+fn append_recorded(
+    store: &dyn EventStore,
+    command: &Command,
+    streams: Vec<(String, Vec<RecordedEvent>)>,
+) -> Result<Vec<Event>, EventSourcingError> {
+    let mut transaction = EventTransaction::new();
+    for (aggregate_id, recorded) in streams {
+        transaction = add_recorded(store, transaction, command, &aggregate_id, recorded)?;
+    }
+    store.append_transaction(&transaction)?;
+
+    let mut stored = Vec::new();
+    for stream in &transaction.streams {
+        let mut events = store.get_events(&stream.aggregate_id, Some(stream.expected_version))?;
+        events.truncate(stream.events.len());
+        stored.extend(events);
+    }
+    Ok(stored)
+}
+
 // ============================================================================
 // Query Bus
 // ============================================================================
 
-/// Type alias for a synchronous query handler function.
-pub type QueryHandlerFn = Arc<dyn Fn(&QueryDef) -> QueryResult + Send + Sync>;
+/// Future returned by a query handler.
+pub type QueryFuture = Pin<Box<dyn Future<Output = QueryResult> + Send>>;
+
+/// Future of one bounded query handler call; `None` if it ran out of time.
+type QueryAttemptFuture = Pin<Box<dyn Future<Output = Option<QueryResult>> + Send>>;
+
+/// Handler executing one query type.
+#[derive(Clone)]
+pub struct QueryHandler(
+    Arc<dyn Fn(QueryDef, Option<Duration>) -> QueryAttemptFuture + Send + Sync>,
+);
+
+impl QueryHandler {
+    /// Create a handler from an async Rust closure.
+    pub fn new<F, Fut>(handler: F) -> Self
+    where
+        F: Fn(QueryDef) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = QueryResult> + Send + 'static,
+    {
+        Self(Arc::new(move |query, timeout| {
+            Box::pin(bounded(timeout, handler(query)))
+        }))
+    }
+
+    /// Create a handler from a synchronous Rust closure.
+    pub fn sync<F>(handler: F) -> Self
+    where
+        F: Fn(&QueryDef) -> QueryResult + Send + Sync + 'static,
+    {
+        Self::new(move |query| std::future::ready(handler(&query)))
+    }
+
+    /// Create a handler from a sync or async Python callable, called with
+    /// the query as a dict.
+    ///
+    /// A returned `cello.cqrs.QueryResult` is used as is, `None` means not
+    /// found and any other value is the result data. A raised exception
+    /// becomes an error result. A call that times out is stopped (see
+    /// [`call_with_timeout`](crate::event_loop::call_with_timeout)).
+    pub fn python(handler: PyObject) -> Self {
+        let handler = Arc::new(handler);
+        Self(Arc::new(move |query, timeout| {
+            let handler = handler.clone();
+            Box::pin(async move {
+                let query = serde_json::to_value(&query).unwrap_or(JsonValue::Null);
+                Some(match call_python_handler(handler, query, timeout).await? {
+                    Ok(result) => Python::with_gil(|py| query_result_from_python(py, &result))
+                        .unwrap_or_else(QueryResult::Error),
+                    Err(e) => QueryResult::Error(e),
+                })
+            })
+        }))
+    }
+
+    /// Invoke the handler.
+    pub fn call(&self, query: QueryDef) -> QueryFuture {
+        let attempt = (self.0)(query, None);
+        Box::pin(async move {
+            attempt
+                .await
+                .unwrap_or_else(|| QueryResult::Error("timed out".to_string()))
+        })
+    }
+
+    /// Invoke the handler, giving up once `timeout` passes (`None` = no
+    /// limit). Returns `None` if it did.
+    pub fn call_with_timeout(
+        &self,
+        query: QueryDef,
+        timeout: Option<Duration>,
+    ) -> impl Future<Output = Option<QueryResult>> + Send {
+        (self.0)(query, timeout)
+    }
+}
+
+impl std::fmt::Debug for QueryHandler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("QueryHandler")
+    }
+}
 
 /// Query bus for dispatching queries to registered handlers.
 ///
@@ -379,7 +789,9 @@ pub type QueryHandlerFn = Arc<dyn Fn(&QueryDef) -> QueryResult + Send + Sync>;
 /// by their `query_type` field.
 pub struct QueryBus {
     /// Registered query handlers keyed by query type.
-    handlers: Arc<RwLock<HashMap<String, QueryHandlerFn>>>,
+    handlers: Arc<RwLock<HashMap<String, QueryHandler>>>,
+    /// Middleware run around every execution, in priority order.
+    middleware: Arc<RwLock<Vec<Arc<dyn QueryMiddleware>>>>,
     /// Internal metrics tracker.
     metrics: Arc<CqrsMetrics>,
     /// Configuration reference.
@@ -391,6 +803,7 @@ impl QueryBus {
     pub fn new() -> Self {
         Self {
             handlers: Arc::new(RwLock::new(HashMap::new())),
+            middleware: Arc::new(RwLock::new(Vec::new())),
             metrics: Arc::new(CqrsMetrics::default()),
             config: CqrsConfig::default(),
         }
@@ -400,38 +813,94 @@ impl QueryBus {
     pub fn with_config(config: CqrsConfig) -> Self {
         Self {
             handlers: Arc::new(RwLock::new(HashMap::new())),
+            middleware: Arc::new(RwLock::new(Vec::new())),
             metrics: Arc::new(CqrsMetrics::default()),
             config,
         }
     }
 
-    /// Register a handler for a specific query type.
+    /// Register a synchronous handler for a specific query type.
     pub fn register<F>(&self, query_type: &str, handler: F)
     where
         F: Fn(&QueryDef) -> QueryResult + Send + Sync + 'static,
     {
+        self.register_handler(query_type, QueryHandler::sync(handler));
+    }
+
+    /// Register an async handler for a specific query type.
+    pub fn register_async<F, Fut>(&self, query_type: &str, handler: F)
+    where
+        F: Fn(QueryDef) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = QueryResult> + Send + 'static,
+    {
+        self.register_handler(query_type, QueryHandler::new(handler));
+    }
+
+    /// Register a handler for a specific query type.
+    pub fn register_handler(&self, query_type: &str, handler: QueryHandler) {
         self.handlers
             .write()
-            .insert(query_type.to_string(), Arc::new(handler));
+            .insert(query_type.to_string(), handler);
     }
 
-    /// Execute a query through its registered handler.
-    pub fn execute(&self, query: &QueryDef) -> Result<QueryResult, CqrsError> {
-        let handlers = self.handlers.read();
-        match handlers.get(&query.query_type) {
-            Some(handler) => {
-                self.metrics.record_query_processed();
-                let result = handler(query);
-                if result.is_error() {
+    /// Add middleware run around every execution.
+    ///
+    /// `before` hooks run in priority order (lower first) and `after` hooks
+    /// in reverse.
+    pub fn add_middleware<M: QueryMiddleware + 'static>(&self, middleware: M) {
+        let mut chain = self.middleware.write();
+        chain.push(Arc::new(middleware));
+        chain.sort_by_key(|m| m.priority());
+    }
+
+    /// Execute a query through the middleware and its registered handler.
+    ///
+    /// Fails with a timeout error if the handler runs longer than the
+    /// configured query timeout.
+    pub async fn execute(&self, query: &QueryDef) -> Result<QueryResult, CqrsError> {
+        let handler = self.handlers.read().get(&query.query_type).cloned();
+        let Some(handler) = handler else {
+            self.metrics.record_query_error();
+            return Err(CqrsError::QueryNotFound(query.query_type.clone()));
+        };
+
+        self.metrics.record_query_processed();
+        let mut query = query.clone();
+        let middleware = self.middleware.read().clone();
+        for m in &middleware {
+            match m.before(&mut query) {
+                Ok(BusAction::Continue) => {}
+                Ok(BusAction::Stop(result)) => return Ok(result),
+                Err(e) => {
                     self.metrics.record_query_error();
+                    return Err(e);
                 }
-                Ok(result)
             }
-            None => {
+        }
+
+        let mut result = handler
+            .call_with_timeout(query.clone(), limit(self.config.query_timeout_ms))
+            .await
+            .ok_or_else(|| {
                 self.metrics.record_query_error();
-                Err(CqrsError::QueryNotFound(query.query_type.clone()))
-            }
+                CqrsError::TimeoutError(format!(
+                    "query '{}' did not complete within {}ms",
+                    query.query_type, self.config.query_timeout_ms
+                ))
+            })?;
+        for m in middleware.iter().rev() {
+            m.after(&query, &mut result);
+        }
+        if result.is_error() {
+            self.metrics.record_query_error();
         }
+        Ok(result)
+    }
+
+    /// Execute several queries concurrently, returning their results in
+    /// order.
+    pub async fn execute_all(&self, queries: &[QueryDef]) -> Vec<Result<QueryResult, CqrsError>> {
+        futures_util::future::join_all(queries.iter().map(|query| self.execute(query))).await
     }
 
     /// Check if a handler is registered for a given query type.
@@ -461,6 +930,420 @@ impl Default for QueryBus {
     }
 }
 
+// ============================================================================
+// Bus Middleware
+// ============================================================================
+
+/// Action to take after a bus middleware's `before` hook.
+#[derive(Debug, Clone)]
+pub enum BusAction<R> {
+    /// Continue to the next middleware and the handler.
+    Continue,
+    /// Skip the handler and return this result.
+    Stop(R),
+}
+
+/// Middleware wrapping every command dispatched through a [`CommandBus`].
+pub trait CommandMiddleware: Send + Sync {
+    /// Called before the handler; may modify the command or short-circuit.
+    fn before(&self, _command: &mut Command) -> Result<BusAction<CommandResult>, CqrsError> {
+        Ok(BusAction::Continue)
+    }
+
+    /// Called with the handler's final result, after any retries.
+    fn after(&self, _command: &Command, _result: &mut CommandResult) {}
+
+    /// Middleware priority (lower = runs first).
+    fn priority(&self) -> i32 {
+        0
+    }
+
+    /// Middleware name for debugging.
+    fn name(&self) -> &str {
+        "unnamed"
+    }
+}
+
+/// Middleware wrapping every query executed through a [`QueryBus`].
+pub trait QueryMiddleware: Send + Sync {
+    /// Called before the handler; may modify the query or short-circuit.
+    fn before(&self, _query: &mut QueryDef) -> Result<BusAction<QueryResult>, CqrsError> {
+        Ok(BusAction::Continue)
+    }
+
+    /// Called with the handler's result.
+    fn after(&self, _query: &QueryDef, _result: &mut QueryResult) {}
+
+    /// Middleware priority (lower = runs first).
+    fn priority(&self) -> i32 {
+        0
+    }
+
+    /// Middleware name for debugging.
+    fn name(&self) -> &str {
+        "unnamed"
+    }
+}
+
+/// Validator for a command's data or a query's params.
+pub type PayloadValidator = Arc<dyn Fn(&JsonValue) -> Result<(), String> + Send + Sync>;
+
+/// Validates commands and queries of registered types before dispatch.
+///
+/// An invalid command is rejected; an invalid query fails with a
+/// validation error.
+#[derive(Default)]
+pub struct ValidationMiddleware {
+    validators: HashMap<String, PayloadValidator>,
+}
+
+impl ValidationMiddleware {
+    /// Create validation middleware with no validators.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Validate the payload of a command or query type.
+    pub fn validate<F>(mut self, message_type: &str, validator: F) -> Self
+    where
+        F: Fn(&JsonValue) -> Result<(), String> + Send + Sync + 'static,
+    {
+        self.validators
+            .insert(message_type.to_string(), Arc::new(validator));
+        self
+    }
+
+    fn check(&self, message_type: &str, payload: &JsonValue) -> Result<(), String> {
+        match self.validators.get(message_type) {
+            Some(validator) => validator(payload),
+            None => Ok(()),
+        }
+    }
+}
+
+impl CommandMiddleware for ValidationMiddleware {
+    fn before(&self, command: &mut Command) -> Result<BusAction<CommandResult>, CqrsError> {
+        Ok(match self.check(&command.command_type, &command.data) {
+            Ok(()) => BusAction::Continue,
+            Err(e) => BusAction::Stop(CommandResult::Rejected(e)),
+        })
+    }
+
+    fn priority(&self) -> i32 {
+        -80
+    }
+
+    fn name(&self) -> &str {
+        "validation"
+    }
+}
+
+impl QueryMiddleware for ValidationMiddleware {
+    fn before(&self, query: &mut QueryDef) -> Result<BusAction<QueryResult>, CqrsError> {
+        self.check(&query.query_type, &query.params)
+            .map(|()| BusAction::Continue)
+            .map_err(CqrsError::ValidationError)
+    }
+
+    fn priority(&self) -> i32 {
+        -80
+    }
+
+    fn name(&self) -> &str {
+        "validation"
+    }
+}
+
+/// Requires roles for commands and queries of registered types.
+///
+/// Roles are read from a comma-separated metadata value (`roles` by
+/// default). An unauthorized command is rejected; an unauthorized query
+/// fails with [`CqrsError::Unauthorized`].
+pub struct AuthorizationMiddleware {
+    required: HashMap<String, String>,
+    roles_key: String,
+}
+
+impl AuthorizationMiddleware {
+    /// Create authorization middleware with no requirements.
+    pub fn new() -> Self {
+        Self {
+            required: HashMap::new(),
+            roles_key: "roles".to_string(),
+        }
+    }
+
+    /// Require `role` for a command or query type.
+    pub fn require(mut self, message_type: &str, role: &str) -> Self {
+        self.required
+            .insert(message_type.to_string(), role.to_string());
+        self
+    }
+
+    /// Read roles from a different metadata key.
+    pub fn roles_key(mut self, key: &str) -> Self {
+        self.roles_key = key.to_string();
+        self
+    }
+
+    fn check(&self, message_type: &str, metadata: &HashMap<String, String>) -> Result<(), String> {
+        let Some(role) = self.required.get(message_type) else {
+            return Ok(());
+        };
+        let granted = metadata
+            .get(&self.roles_key)
+            .is_some_and(|roles| roles.split(',').any(|r| r.trim() == role));
+        if granted {
+            Ok(())
+        } else {
+            Err(format!("Unauthorized: {role} role required"))
+        }
+    }
+}
+
+impl Default for AuthorizationMiddleware {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CommandMiddleware for AuthorizationMiddleware {
+    fn before(&self, command: &mut Command) -> Result<BusAction<CommandResult>, CqrsError> {
+        Ok(match self.check(&command.command_type, &command.metadata) {
+            Ok(()) => BusAction::Continue,
+            Err(e) => BusAction::Stop(CommandResult::Rejected(e)),
+        })
+    }
+
+    fn priority(&self) -> i32 {
+        -90
+    }
+
+    fn name(&self) -> &str {
+        "authorization"
+    }
+}
+
+impl QueryMiddleware for AuthorizationMiddleware {
+    fn before(&self, query: &mut QueryDef) -> Result<BusAction<QueryResult>, CqrsError> {
+        self.check(&query.query_type, &query.metadata)
+            .map(|()| BusAction::Continue)
+            .map_err(CqrsError::Unauthorized)
+    }
+
+    fn priority(&self) -> i32 {
+        -90
+    }
+
+    fn name(&self) -> &str {
+        "authorization"
+    }
+}
+
+/// Logs every command and query with its outcome via `tracing`.
+#[derive(Default)]
+pub struct LoggingMiddleware;
+
+impl LoggingMiddleware {
+    /// Create logging middleware.
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl CommandMiddleware for LoggingMiddleware {
+    fn before(&self, command: &mut Command) -> Result<BusAction<CommandResult>, CqrsError> {
+        tracing::info!(
+            "Dispatching command {} ({})",
+            command.command_type,
+            command.id
+        );
+        Ok(BusAction::Continue)
+    }
+
+    fn after(&self, command: &Command, result: &mut CommandResult) {
+        match result {
+            CommandResult::Success(_) => {
+                tracing::info!(
+                    "Command {} ({}) succeeded",
+                    command.command_type,
+                    command.id
+                )
+            }
+            CommandResult::Failure(e) | CommandResult::Rejected(e) => tracing::warn!(
+                "Command {} ({}) failed: {e}",
+                command.command_type,
+                command.id
+            ),
+        }
+    }
+
+    fn priority(&self) -> i32 {
+        -100
+    }
+
+    fn name(&self) -> &str {
+        "logging"
+    }
+}
+
+impl QueryMiddleware for LoggingMiddleware {
+    fn before(&self, query: &mut QueryDef) -> Result<BusAction<QueryResult>, CqrsError> {
+        tracing::info!("Executing query {} ({})", query.query_type, query.id);
+        Ok(BusAction::Continue)
+    }
+
+    fn after(&self, query: &QueryDef, result: &mut QueryResult) {
+        if let QueryResult::Error(e) = result {
+            tracing::warn!("Query {} ({}) failed: {e}", query.query_type, query.id);
+        }
+    }
+
+    fn priority(&self) -> i32 {
+        -100
+    }
+
+    fn name(&self) -> &str {
+        "logging"
+    }
+}
+
+/// Deduplicates commands carrying an idempotency key in their metadata.
+///
+/// The successful result of a command is remembered for the TTL, and a
+/// later command with the same type and key gets it back without running
+/// the handler again.
+pub struct DeduplicationMiddleware {
+    key: String,
+    ttl: Duration,
+    results: Mutex<HashMap<(String, String), (Instant, CommandResult)>>,
+}
+
+impl DeduplicationMiddleware {
+    /// Remember results for `ttl_secs`, keyed by the `idempotency_key`
+    /// metadata value.
+    pub fn new(ttl_secs: u64) -> Self {
+        Self {
+            key: "idempotency_key".to_string(),
+            ttl: Duration::from_secs(ttl_secs),
+            results: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Read idempotency keys from a different metadata key.
+    pub fn metadata_key(mut self, key: &str) -> Self {
+        self.key = key.to_string();
+        self
+    }
+
+    fn cache_key(&self, command: &Command) -> Option<(String, String)> {
+        command
+            .metadata
+            .get(&self.key)
+            .map(|key| (command.command_type.clone(), key.clone()))
+    }
+}
+
+impl CommandMiddleware for DeduplicationMiddleware {
+    fn before(&self, command: &mut Command) -> Result<BusAction<CommandResult>, CqrsError> {
+        let Some(key) = self.cache_key(command) else {
+            return Ok(BusAction::Continue);
+        };
+        let mut results = self.results.lock();
+        results.retain(|_, (stored, _)| stored.elapsed() < self.ttl);
+        Ok(match results.get(&key) {
+            Some((_, result)) => BusAction::Stop(result.clone()),
+            None => BusAction::Continue,
+        })
+    }
+
+    fn after(&self, command: &Command, result: &mut CommandResult) {
+        if let (Some(key), true) = (self.cache_key(command), result.is_success()) {
+            self.results
+                .lock()
+                .insert(key, (Instant::now(), result.clone()));
+        }
+    }
+
+    fn priority(&self) -> i32 {
+        -70
+    }
+
+    fn name(&self) -> &str {
+        "deduplication"
+    }
+}
+
+// ============================================================================
+// Handler Helpers
+// ============================================================================
+
+/// A configured timeout in milliseconds as a limit (0 = no limit).
+fn limit(timeout_ms: u64) -> Option<Duration> {
+    (timeout_ms > 0).then(|| Duration::from_millis(timeout_ms))
+}
+
+/// Await `future`, giving up once `timeout` passes.
+async fn bounded<T>(timeout: Option<Duration>, future: impl Future<Output = T>) -> Option<T> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, future).await.ok(),
+        None => Some(future.await),
+    }
+}
+
+/// Drive a Python handler to completion within `timeout`, awaiting any
+/// coroutine via Tokio with the GIL released. Returns `None` on timeout.
+async fn call_python_handler(
+    handler: Arc<PyObject>,
+    arg: JsonValue,
+    timeout: Option<Duration>,
+) -> Option<Result<PyObject, String>> {
+    let args = move |py: Python<'_>| -> PyResult<Py<PyTuple>> {
+        Ok(PyTuple::new(py, [json_to_python(py, &arg)?]).into())
+    };
+    match call_with_timeout(handler, args, timeout).await {
+        Ok(result) => Some(Ok(result)),
+        Err(CallError::TimedOut) => None,
+        Err(CallError::Raised(e)) => Some(Err(e.to_string())),
+    }
+}
+
+/// Convert a Python command handler's return value to a `CommandResult`.
+fn command_result_from_python(py: Python<'_>, value: &PyObject) -> Result<CommandResult, String> {
+    let value = value.as_ref(py);
+    if value.hasattr("success").unwrap_or(false) && value.hasattr("error").unwrap_or(false) {
+        let attr = |name: &str| value.getattr(name).map_err(|e| e.to_string());
+        if attr("success")?.is_true().map_err(|e| e.to_string())? {
+            return python_to_json(py, attr("data")?).map(CommandResult::Success);
+        }
+        let error: String = attr("error")?.extract().unwrap_or_default();
+        return Ok(match error.strip_prefix("Rejected: ") {
+            Some(reason) => CommandResult::Rejected(reason.to_string()),
+            None => CommandResult::Failure(error),
+        });
+    }
+    python_to_json(py, value).map(CommandResult::Success)
+}
+
+/// Convert a Python query handler's return value to a `QueryResult`.
+fn query_result_from_python(py: Python<'_>, value: &PyObject) -> Result<QueryResult, String> {
+    let value = value.as_ref(py);
+    let data = if value.hasattr("found").unwrap_or(false) && value.hasattr("error").unwrap_or(false)
+    {
+        let error = value.getattr("error").map_err(|e| e.to_string())?;
+        if !error.is_none() {
+            return Ok(QueryResult::Error(error.to_string()));
+        }
+        value.getattr("data").map_err(|e| e.to_string())?
+    } else {
+        value
+    };
+    if data.is_none() {
+        return Ok(QueryResult::NotFound);
+    }
+    python_to_json(py, data).map(QueryResult::Success)
+}
+
 // ============================================================================
 // Error Types
 // ============================================================================
@@ -478,6 +1361,8 @@ pub enum CqrsError {
     TimeoutError(String),
     /// Command or query validation failed.
     ValidationError(String),
+    /// The caller lacks a role the command or query requires.
+    Unauthorized(String),
 }
 
 impl std::fmt::Display for CqrsError {
@@ -492,6 +1377,7 @@ impl std::fmt::Display for CqrsError {
             CqrsError::HandlerError(msg) => write!(f, "Handler error: {msg}"),
             CqrsError::TimeoutError(msg) => write!(f, "Timeout: {msg}"),
             CqrsError::ValidationError(msg) => write!(f, "Validation error: {msg}"),
+            CqrsError::Unauthorized(msg) => write!(f, "{msg}"),
         }
     }
 }
@@ -663,8 +1549,8 @@ mod tests {
 
     // ---------- CommandBus Tests ----------
 
-    #[test]
-    fn test_command_bus_register_and_dispatch() {
+    #[tokio::test]
+    async fn test_command_bus_register_and_dispatch() {
         let bus = CommandBus::new();
 
         bus.register("CreateOrder", |cmd: &Command| {
@@ -679,28 +1565,28 @@ mod tests {
         assert_eq!(bus.handler_count(), 1);
 
         let cmd = Command::new("CreateOrder", serde_json::json!({"item": "widget"}));
-        let result = bus.dispatch(&cmd).unwrap();
+        let result = bus.dispatch(&cmd).await.unwrap();
         assert!(result.is_success());
     }
 
-    #[test]
-    fn test_command_bus_unknown_command() {
+    #[tokio::test]
+    async fn test_command_bus_unknown_command() {
         let bus = CommandBus::new();
         let cmd = Command::new("UnknownCommand", serde_json::json!({}));
-        let result = bus.dispatch(&cmd);
+        let result = bus.dispatch(&cmd).await;
         assert!(result.is_err());
         assert!(matches!(result.unwrap_err(), CqrsError::CommandNotFound(_)));
     }
 
-    #[test]
-    fn test_command_bus_stats() {
+    #[tokio::test]
+    async fn test_command_bus_stats() {
         let bus = CommandBus::new();
 
         bus.register("TestCmd", |_| CommandResult::Success(serde_json::json!({})));
 
         let cmd = Command::new("TestCmd", serde_json::json!({}));
-        bus.dispatch(&cmd).unwrap();
-        bus.dispatch(&cmd).unwrap();
+        bus.dispatch(&cmd).await.unwrap();
+        bus.dispatch(&cmd).await.unwrap();
 
         let stats = bus.stats();
         assert_eq!(stats.commands_processed, 2);
@@ -709,8 +1595,8 @@ mod tests {
 
     // ---------- QueryBus Tests ----------
 
-    #[test]
-    fn test_query_bus_register_and_execute() {
+    #[tokio::test]
+    async fn test_query_bus_register_and_execute() {
         let bus = QueryBus::new();
 
         bus.register("GetUser", |query: &QueryDef| {
@@ -725,33 +1611,374 @@ mod tests {
         assert_eq!(bus.handler_count(), 1);
 
         let query = QueryDef::new("GetUser", serde_json::json!({"id": "user-1"}));
-        let result = bus.execute(&query).unwrap();
+        let result = bus.execute(&query).await.unwrap();
         assert!(result.is_success());
     }
 
-    #[test]
-    fn test_query_bus_unknown_query() {
+    #[tokio::test]
+    async fn test_query_bus_unknown_query() {
         let bus = QueryBus::new();
         let query = QueryDef::new("UnknownQuery", serde_json::json!({}));
-        let result = bus.execute(&query);
+        let result = bus.execute(&query).await;
         assert!(result.is_err());
         assert!(matches!(result.unwrap_err(), CqrsError::QueryNotFound(_)));
     }
 
-    #[test]
-    fn test_query_bus_stats() {
+    #[tokio::test]
+    async fn test_query_bus_stats() {
         let bus = QueryBus::new();
 
         bus.register("TestQuery", |_| QueryResult::Success(serde_json::json!({})));
 
         let query = QueryDef::new("TestQuery", serde_json::json!({}));
-        bus.execute(&query).unwrap();
+        bus.execute(&query).await.unwrap();
 
         let stats = bus.stats();
         assert_eq!(stats.queries_processed, 1);
         assert_eq!(stats.query_errors, 0);
     }
 
+    #[tokio::test]
+    async fn test_command_bus_async_handler_timeout() {
+        let bus = CommandBus::with_config(CqrsConfig::new().with_command_timeout(20));
+        bus.register_async("Ship", |cmd: Command| async move {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+            CommandResult::Success(cmd.data)
+        });
+        bus.register_async("Stall", |_| async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            CommandResult::Success(JsonValue::Null)
+        });
+
+        let result = bus
+            .dispatch(&Command::new("Ship", serde_json::json!({"id": 7})))
+            .await
+            .unwrap();
+        assert!(matches!(result, CommandResult::Success(data) if data["id"] == 7));
+
+        let result = bus
+            .dispatch(&Command::new("Stall", serde_json::json!({})))
+            .await;
+        assert!(matches!(result, Err(CqrsError::TimeoutError(_))));
+        assert_eq!(bus.stats().command_errors, 1);
+    }
+
+    #[tokio::test]
+    async fn test_query_bus_executes_concurrently() {
+        let bus = QueryBus::with_config(CqrsConfig::new().with_query_timeout(0));
+        bus.register_async("Slow", |query: QueryDef| {
+            Box::pin(async move {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                QueryResult::Success(query.params)
+            }) as QueryFuture
+        });
+
+        let queries: Vec<_> = (0..5)
+            .map(|i| QueryDef::new("Slow", serde_json::json!(i)))
+            .chain([QueryDef::new("Missing", serde_json::json!({}))])
+            .collect();
+        let started = std::time::Instant::now();
+        let results = bus.execute_all(&queries).await;
+        assert!(started.elapsed() < Duration::from_millis(400));
+        assert!(matches!(&results[4], Ok(QueryResult::Success(n)) if n == 4));
+        assert!(matches!(results[5], Err(CqrsError::QueryNotFound(_))));
+        assert_eq!(bus.stats().queries_processed, 5);
+    }
+
+    // ---------- Pipeline Tests ----------
+
+    #[tokio::test]
+    async fn test_command_bus_retries_failures() {
+        let bus = CommandBus::with_config(CqrsConfig::new().with_max_retries(2));
+        let calls = Arc::new(AtomicU64::new(0));
+        let counter = calls.clone();
+        bus.register("Flaky", move |_| {
+            if counter.fetch_add(1, Ordering::SeqCst) < 2 {
+                CommandResult::Failure("transient".to_string())
+            } else {
+                CommandResult::Success(JsonValue::Null)
+            }
+        });
+        let rejections = Arc::new(AtomicU64::new(0));
+        let counter = rejections.clone();
+        bus.register("Invalid", move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            CommandResult::Rejected("bad input".to_string())
+        });
+
+        let result = bus
+            .dispatch(&Command::new("Flaky", serde_json::json!({})))
+            .await
+            .unwrap();
+        assert!(result.is_success());
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        let result = bus
+            .dispatch(&Command::new("Invalid", serde_json::json!({})))
+            .await
+            .unwrap();
+        assert!(result.is_rejected());
+        assert_eq!(rejections.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_command_pipeline_validation_and_authorization() {
+        let bus = CommandBus::new();
+        bus.register("CreateOrder", |cmd: &Command| {
+            CommandResult::Success(cmd.data.clone())
+        });
+        bus.add_middleware(LoggingMiddleware::new());
+        bus.add_middleware(ValidationMiddleware::new().validate("CreateOrder", |data| {
+            match data["items"].as_array() {
+                Some(items) if !items.is_empty() => Ok(()),
+                _ => Err("Order must have at least one item".to_string()),
+            }
+        }));
+        bus.add_middleware(AuthorizationMiddleware::new().require("CreateOrder", "customer"));
+
+        let order = serde_json::json!({"items": [1]});
+        let result = bus
+            .dispatch(&Command::new("CreateOrder", order.clone()))
+            .await
+            .unwrap();
+        assert!(
+            matches!(result, CommandResult::Rejected(e) if e == "Unauthorized: customer role required")
+        );
+
+        let result = bus
+            .dispatch(
+                &Command::new("CreateOrder", serde_json::json!({"items": []}))
+                    .with_metadata("roles", "admin, customer"),
+            )
+            .await
+            .unwrap();
+        assert!(matches!(result, CommandResult::Rejected(e) if e.contains("at least one item")));
+
+        let result = bus
+            .dispatch(&Command::new("CreateOrder", order).with_metadata("roles", "customer"))
+            .await
+            .unwrap();
+        assert!(result.is_success());
+    }
+
+    #[tokio::test]
+    async fn test_command_pipeline_deduplicates_by_idempotency_key() {
+        let bus = CommandBus::new();
+        let calls = Arc::new(AtomicU64::new(0));
+        let counter = calls.clone();
+        bus.register("Charge", move |_| {
+            let n = counter.fetch_add(1, Ordering::SeqCst);
+            CommandResult::Success(serde_json::json!({"charge": n}))
+        });
+        bus.add_middleware(DeduplicationMiddleware::new(60));
+
+        let charge = |key: &str| {
+            Command::new("Charge", serde_json::json!({})).with_metadata("idempotency_key", key)
+        };
+        let first = bus.dispatch(&charge("k1")).await.unwrap();
+        let replay = bus.dispatch(&charge("k1")).await.unwrap();
+        let other = bus.dispatch(&charge("k2")).await.unwrap();
+        bus.dispatch(&Command::new("Charge", serde_json::json!({})))
+            .await
+            .unwrap();
+
+        assert!(matches!(&replay, CommandResult::Success(d) if d["charge"] == 0));
+        assert!(matches!(first, CommandResult::Success(d) if d["charge"] == 0));
+        assert!(matches!(other, CommandResult::Success(d) if d["charge"] == 1));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_query_pipeline_errors() {
+        let bus = QueryBus::new();
+        bus.register("GetUser", |q: &QueryDef| {
+            QueryResult::Success(q.params.clone())
+        });
+        bus.add_middleware(ValidationMiddleware::new().validate("GetUser", |params| {
+            match params.get("id") {
+                Some(_) => Ok(()),
+                None => Err("id is required".to_string()),
+            }
+        }));
+        bus.add_middleware(AuthorizationMiddleware::new().require("GetUser", "admin"));
+
+        let query = QueryDef::new("GetUser", serde_json::json!({}));
+        assert!(matches!(
+            bus.execute(&query).await,
+            Err(CqrsError::Unauthorized(_))
+        ));
+        let query = query.with_metadata("roles", "admin");
+        assert!(matches!(
+            bus.execute(&query).await,
+            Err(CqrsError::ValidationError(_))
+        ));
+        let query =
+            QueryDef::new("GetUser", serde_json::json!({"id": 1})).with_metadata("roles", "admin");
+        assert!(bus.execute(&query).await.unwrap().is_success());
+        assert_eq!(bus.stats().query_errors, 2);
+    }
+
+    // ---------- Event Sync Tests ----------
+
+    fn order_events_handler(command: &Command) -> CommandResult {
+        let id = command.data["order_id"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        CommandResult::Success(serde_json::json!({
+            "order_id": id,
+            "events": [
+                {"aggregate_id": id, "event_type": "OrderCreated", "data": {"total": 10}},
+                {"aggregate_id": "inventory", "event_type": "StockReserved"},
+                {"aggregate_id": id, "event_type": "OrderConfirmed"},
+            ],
+        }))
+    }
+
+    #[tokio::test]
+    async fn test_event_sync_appends_and_publishes_events() {
+        use crate::middleware::eventsourcing::InMemoryEventStore;
+        use crate::middleware::messaging::MockProducer;
+
+        let store = Arc::new(InMemoryEventStore::new());
+        let producer = Arc::new(MockProducer::new());
+        let bus = CommandBus::new();
+        bus.set_event_store(store.clone());
+        bus.set_event_publisher(producer.clone(), "orders");
+        bus.register("CreateOrder", order_events_handler);
+
+        let command = Command::new("CreateOrder", serde_json::json!({"order_id": "o-1"}));
+        let result = bus.dispatch(&command).await.unwrap();
+        let CommandResult::Success(data) = result else {
+            panic!("expected success");
+        };
+        assert_eq!(data["order_id"], "o-1");
+        let versions: Vec<_> = data["events"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| (e["aggregate_id"].clone(), e["version"].clone()))
+            .collect();
+        assert_eq!(
+            versions,
+            vec![
+                ("o-1".into(), 1.into()),
+                ("o-1".into(), 2.into()),
+                ("inventory".into(), 1.into()),
+            ]
+        );
+
+        let events = store.get_events("o-1", None).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].data["total"], 10);
+        assert_eq!(events[1].event_type(), "OrderConfirmed");
+        assert_eq!(
+            events[0].get_metadata("command_id"),
+            Some(command.id.as_str())
+        );
+        assert_eq!(events[0].get_metadata("command_type"), Some("CreateOrder"));
+        assert!(events[0].position > 0);
+
+        let sent = producer.sent_messages();
+        assert_eq!(sent.len(), 3);
+        assert!(sent.iter().all(|m| m.topic == "orders"));
+        assert_eq!(sent[0].key.as_deref(), Some("o-1"));
+        assert_eq!(sent[0].value_json().unwrap()["event_type"], "OrderCreated");
+
+        // A second command continues from the aggregate's current version.
+        bus.dispatch(&command).await.unwrap();
+        assert_eq!(store.get_events("o-1", None).unwrap().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_event_sync_disabled() {
+        use crate::middleware::eventsourcing::InMemoryEventStore;
+
+        let store = Arc::new(InMemoryEventStore::new());
+        let bus = CommandBus::with_config(CqrsConfig::default().with_event_sync(false));
+        bus.set_event_store(store.clone());
+        bus.register("CreateOrder", order_events_handler);
+
+        let command = Command::new("CreateOrder", serde_json::json!({"order_id": "o-1"}));
+        let result = bus.dispatch(&command).await.unwrap();
+        let CommandResult::Success(data) = result else {
+            panic!("expected success");
+        };
+        assert!(data["events"][0].get("version").is_none());
+        assert!(store.get_events("o-1", None).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_event_sync_concurrency_conflict_fails_command() {
+        use crate::middleware::eventsourcing::InMemoryEventStore;
+
+        let store = Arc::new(InMemoryEventStore::new());
+        let bus = CommandBus::with_config(CqrsConfig::default().with_max_retries(0));
+        bus.set_event_store(store.clone());
+        bus.register("Rename", |_cmd: &Command| {
+            CommandResult::Success(serde_json::json!({
+                "events": [{"aggregate_id": "u-1", "event_type": "Renamed", "expected_version": 0}],
+            }))
+        });
+
+        let command = Command::new("Rename", JsonValue::Null);
+        assert!(bus.dispatch(&command).await.unwrap().is_success());
+        let result = bus.dispatch(&command).await.unwrap();
+        assert!(result.is_failure());
+        assert_eq!(store.get_events("u-1", None).unwrap().len(), 1);
+        assert_eq!(bus.stats().command_errors, 1);
+    }
+
+    #[tokio::test]
+    async fn test_event_sync_multi_aggregate_conflict_writes_nothing() {
+        use crate::middleware::eventsourcing::{InMemoryEventStore, TRANSACTION_ID_KEY};
+
+        let store = Arc::new(InMemoryEventStore::new());
+        let bus = CommandBus::with_config(CqrsConfig::default().with_max_retries(0));
+        bus.set_event_store(store.clone());
+        bus.register("Transfer", |_cmd: &Command| {
+            CommandResult::Success(serde_json::json!({
+                "events": [
+                    {"aggregate_id": "acc-1", "event_type": "Debited"},
+                    {"aggregate_id": "acc-2", "event_type": "Credited", "expected_version": 0},
+                ],
+            }))
+        });
+
+        let command = Command::new("Transfer", JsonValue::Null);
+        assert!(bus.dispatch(&command).await.unwrap().is_success());
+        let debit = store.get_events("acc-1", None).unwrap();
+        let credit = store.get_events("acc-2", None).unwrap();
+        assert_eq!(
+            debit[0].get_metadata(TRANSACTION_ID_KEY),
+            credit[0].get_metadata(TRANSACTION_ID_KEY)
+        );
+
+        // acc-2 is no longer at version 0, so the debit is not written either.
+        assert!(bus.dispatch(&command).await.unwrap().is_failure());
+        assert_eq!(store.get_events("acc-1", None).unwrap().len(), 1);
+        assert_eq!(store.get_events("acc-2", None).unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_event_sync_invalid_events() {
+        use crate::middleware::eventsourcing::InMemoryEventStore;
+
+        let bus = CommandBus::with_config(CqrsConfig::default().with_max_retries(0));
+        bus.set_event_store(Arc::new(InMemoryEventStore::new()));
+        bus.register("Bad", |_cmd: &Command| {
+            CommandResult::Success(serde_json::json!({"events": [{"event_type": "X"}]}))
+        });
+
+        let result = bus
+            .dispatch(&Command::new("Bad", JsonValue::Null))
+            .await
+            .unwrap();
+        assert!(
+            matches!(result, CommandResult::Failure(e) if e.starts_with("Invalid recorded events"))
+        );
+    }
+
     // ---------- Error Display Tests ----------
 
     #[test]
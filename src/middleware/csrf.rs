@@ -316,7 +316,10 @@ impl CsrfMiddleware {
 
     /// Check if path should skip CSRF.
     fn should_skip(&self, path: &str) -> bool {
-        self.config.skip_paths.iter().any(|p| path_matches_skip(path, p))
+        self.config
+            .skip_paths
+            .iter()
+            .any(|p| path_matches_skip(path, p))
     }
 
     /// Extract token from cookie.
@@ -335,8 +338,8 @@ impl CsrfMiddleware {
     /// Extract token from request (header or form).
     fn get_request_token(&self, request: &Request) -> Option<String> {
         // Try header first
-        if let Some(token) = request.headers.get(&self.config.header_name.to_lowercase()) {
-            return Some(token.clone());
+        if let Some(token) = request.headers.get(&self.config.header_name) {
+            return Some(token.to_string());
         }
 
         // Try form field (from query params for now)
@@ -350,7 +353,7 @@ impl CsrfMiddleware {
 
     /// Validate origin/referer headers.
     fn validate_origin(&self, request: &Request) -> bool {
-        let host = request.headers.get("host").cloned().unwrap_or_default();
+        let host = request.headers.get("host").unwrap_or_default();
 
         // Check Origin header
         if let Some(origin) = request.headers.get("origin") {
@@ -359,15 +362,15 @@ impl CsrfMiddleware {
                 let origin_host = origin
                     .trim_start_matches("http://")
                     .trim_start_matches("https://");
-                return origin_host.starts_with(&host);
+                return origin_host.starts_with(host);
             }
-            return self.config.allowed_origins.contains(origin);
+            return self.config.allowed_origins.iter().any(|o| o == origin);
         }
 
         // Fall back to Referer header
         if let Some(referer) = request.headers.get("referer") {
             if self.config.allowed_origins.is_empty() {
-                return referer.contains(&host);
+                return referer.contains(host);
             }
             return self
                 .config
@@ -123,6 +123,10 @@ pub struct DatabaseStats {
     pub peak_active_connections: usize,
     /// Connection timeouts
     pub connection_timeouts: u64,
+    /// Average time spent waiting for a connection in milliseconds
+    pub avg_wait_time_ms: f64,
+    /// Longest time spent waiting for a connection in milliseconds
+    pub max_wait_time_ms: f64,
 }
 
 /// Connection pool metrics.
@@ -135,6 +139,9 @@ pub struct PoolMetrics {
     query_time_sum_ms: AtomicU64,
     peak_active: AtomicUsize,
     connection_timeouts: AtomicU64,
+    wait_count: AtomicU64,
+    wait_time_sum_us: AtomicU64,
+    max_wait_us: AtomicU64,
 }
 
 impl Default for PoolMetrics {
@@ -148,6 +155,9 @@ impl Default for PoolMetrics {
             query_time_sum_ms: AtomicU64::new(0),
             peak_active: AtomicUsize::new(0),
             connection_timeouts: AtomicU64::new(0),
+            wait_count: AtomicU64::new(0),
+            wait_time_sum_us: AtomicU64::new(0),
+            max_wait_us: AtomicU64::new(0),
         }
     }
 }
@@ -190,9 +200,29 @@ impl PoolMetrics {
         self.connection_timeouts.fetch_add(1, Ordering::Relaxed);
     }
 
+    /// Record a newly opened connection.
+    pub fn record_opened(&self) {
+        self.total_connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record how long a caller waited to get a connection from the pool.
+    pub fn record_wait(&self, wait: Duration) {
+        let wait_us = wait.as_micros().min(u64::MAX as u128) as u64;
+        self.wait_count.fetch_add(1, Ordering::Relaxed);
+        self.wait_time_sum_us.fetch_add(wait_us, Ordering::Relaxed);
+        self.max_wait_us.fetch_max(wait_us, Ordering::Relaxed);
+    }
+
+    /// Record the number of connections currently checked out.
+    pub fn record_active(&self, active: usize) {
+        self.peak_active.fetch_max(active, Ordering::Relaxed);
+    }
+
     pub fn get_stats(&self) -> DatabaseStats {
         let total_queries = self.total_queries.load(Ordering::Relaxed);
         let query_time_sum = self.query_time_sum_ms.load(Ordering::Relaxed);
+        let wait_count = self.wait_count.load(Ordering::Relaxed);
+        let wait_time_sum = self.wait_time_sum_us.load(Ordering::Relaxed);
 
         DatabaseStats {
             total_connections: self.total_connections.load(Ordering::Relaxed),
@@ -207,6 +237,12 @@ impl PoolMetrics {
             },
             peak_active_connections: self.peak_active.load(Ordering::Relaxed),
             connection_timeouts: self.connection_timeouts.load(Ordering::Relaxed),
+            avg_wait_time_ms: if wait_count > 0 {
+                wait_time_sum as f64 / wait_count as f64 / 1000.0
+            } else {
+                0.0
+            },
+            max_wait_time_ms: self.max_wait_us.load(Ordering::Relaxed) as f64 / 1000.0,
         }
     }
 }
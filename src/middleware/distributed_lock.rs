@@ -0,0 +1,781 @@
+//! Distributed Locks for Cello Framework.
+//!
+//! Provides mutual exclusion across tasks, processes and hosts with:
+//! - Lease-based locks that expire if their holder dies
+//! - Acquire with timeout and randomized retry
+//! - Automatic lease renewal while the lock is held
+//! - Token-checked release, so an expired holder cannot release a lock
+//!   someone else has since acquired
+//! - In-process and Redis (Redlock-style quorum) backends
+//!
+//! The in-process backend only coordinates tasks within one process.
+//! `RedisLockBackend` coordinates cluster workers through Redis servers they
+//! share, but `enable_distributed_locks(backend="redis")` raises
+//! `RuntimeError` while `enable_redis()` only provides an in-process client.
+//!
+//! # Example
+//! ```python
+//! app.enable_distributed_locks(backend="memory", ttl=30)
+//!
+//! @app.schedule(cron="0 * * * *")
+//! async def hourly_rollup():
+//!     async with app.lock("rollup", timeout=0):
+//!         await rollup()
+//! ```
+
+use parking_lot::Mutex;
+use pyo3::prelude::*;
+use rand::Rng;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::redis::RedisClient;
+
+// ============================================================================
+// Configuration
+// ============================================================================
+
+/// Distributed lock configuration.
+#[derive(Clone, Debug)]
+pub struct LockConfig {
+    /// Lease duration; the lock expires this long after it was acquired or
+    /// last renewed.
+    pub ttl: Duration,
+    /// How long `acquire` keeps retrying before giving up.
+    pub acquire_timeout: Duration,
+    /// Base delay between acquire attempts; a random delay of up to the
+    /// same length is added to each.
+    pub retry_interval: Duration,
+    /// Renew the lease every third of the TTL while the lock is held.
+    pub auto_renew: bool,
+}
+
+impl Default for LockConfig {
+    fn default() -> Self {
+        Self {
+            ttl: Duration::from_secs(30),
+            acquire_timeout: Duration::from_secs(10),
+            retry_interval: Duration::from_millis(50),
+            auto_renew: true,
+        }
+    }
+}
+
+impl LockConfig {
+    /// Create a configuration with defaults.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the lease duration.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Set how long `acquire` retries.
+    pub fn with_acquire_timeout(mut self, timeout: Duration) -> Self {
+        self.acquire_timeout = timeout;
+        self
+    }
+
+    /// Set the base delay between acquire attempts.
+    pub fn with_retry_interval(mut self, interval: Duration) -> Self {
+        self.retry_interval = interval;
+        self
+    }
+
+    /// Enable or disable automatic lease renewal.
+    pub fn with_auto_renew(mut self, enabled: bool) -> Self {
+        self.auto_renew = enabled;
+        self
+    }
+}
+
+// ============================================================================
+// Backends
+// ============================================================================
+
+/// Storage for lock leases.
+///
+/// Every operation names the holder by a token unique to one acquisition,
+/// so only the current holder can renew or release a lease.
+pub trait LockBackend: Send + Sync {
+    /// Take the lease on `key` for `ttl` if nobody holds it. Returns
+    /// whether the lease was taken.
+    fn try_acquire(&self, key: &str, token: &str, ttl: Duration) -> Result<bool, LockError>;
+
+    /// Extend the lease on `key` to `ttl` from now if `token` still holds
+    /// it. Returns whether the lease was extended.
+    fn renew(&self, key: &str, token: &str, ttl: Duration) -> Result<bool, LockError>;
+
+    /// Give up the lease on `key` if `token` still holds it. Returns
+    /// whether the lease was released.
+    fn release(&self, key: &str, token: &str) -> Result<bool, LockError>;
+
+    /// Backend name for diagnostics.
+    fn name(&self) -> &str;
+}
+
+/// In-process lock backend.
+///
+/// Coordinates tasks within one process only.
+#[derive(Default)]
+pub struct InMemoryLockBackend {
+    leases: Mutex<HashMap<String, (String, Instant)>>,
+}
+
+impl InMemoryLockBackend {
+    /// Create an empty backend.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl LockBackend for InMemoryLockBackend {
+    fn try_acquire(&self, key: &str, token: &str, ttl: Duration) -> Result<bool, LockError> {
+        let mut leases = self.leases.lock();
+        let now = Instant::now();
+        if leases.get(key).is_some_and(|(_, expires)| now < *expires) {
+            return Ok(false);
+        }
+        leases.insert(key.to_string(), (token.to_string(), now + ttl));
+        Ok(true)
+    }
+
+    fn renew(&self, key: &str, token: &str, ttl: Duration) -> Result<bool, LockError> {
+        let now = Instant::now();
+        match self.leases.lock().get_mut(key) {
+            Some((holder, expires)) if holder == token && now < *expires => {
+                *expires = now + ttl;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    fn release(&self, key: &str, token: &str) -> Result<bool, LockError> {
+        let mut leases = self.leases.lock();
+        match leases.get(key) {
+            Some((holder, expires)) if holder == token => {
+                let live = Instant::now() < *expires;
+                leases.remove(key);
+                Ok(live)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    fn name(&self) -> &str {
+        "memory"
+    }
+}
+
+/// Redis lock backend using the Redlock algorithm.
+///
+/// Leases are taken on every independent Redis node, and a lock is held
+/// only if a majority granted it with time left on the lease after
+/// accounting for the time spent acquiring and for clock drift. With a
+/// single node this is the plain `SET NX PX` lock.
+pub struct RedisLockBackend {
+    nodes: Vec<Arc<dyn RedisClient>>,
+    key_prefix: String,
+    clock_drift_factor: f64,
+}
+
+impl RedisLockBackend {
+    /// Create a backend over one or more independent Redis nodes.
+    pub fn new(nodes: Vec<Arc<dyn RedisClient>>) -> Self {
+        Self {
+            nodes,
+            key_prefix: "lock:".to_string(),
+            clock_drift_factor: 0.01,
+        }
+    }
+
+    /// Prefix for lock keys (default `lock:`).
+    pub fn with_key_prefix(mut self, prefix: &str) -> Self {
+        self.key_prefix = prefix.to_string();
+        self
+    }
+
+    fn quorum(&self) -> usize {
+        self.nodes.len() / 2 + 1
+    }
+
+    fn key(&self, key: &str) -> String {
+        format!("{}{key}", self.key_prefix)
+    }
+
+    /// Count the nodes for which `op` succeeds; node errors count as
+    /// failures.
+    fn count<F>(&self, op: F) -> usize
+    where
+        F: Fn(&dyn RedisClient) -> Result<bool, super::redis::RedisError>,
+    {
+        self.nodes
+            .iter()
+            .filter(|node| op(node.as_ref()).unwrap_or(false))
+            .count()
+    }
+}
+
+impl LockBackend for RedisLockBackend {
+    fn try_acquire(&self, key: &str, token: &str, ttl: Duration) -> Result<bool, LockError> {
+        if self.nodes.is_empty() {
+            return Err(LockError::Backend("no Redis nodes configured".to_string()));
+        }
+        let key = self.key(key);
+        let started = Instant::now();
+        let granted = self.count(|node| node.set_nx(&key, token, ttl));
+
+        let drift = ttl.mul_f64(self.clock_drift_factor) + Duration::from_millis(2);
+        let valid = ttl
+            .checked_sub(started.elapsed() + drift)
+            .is_some_and(|left| !left.is_zero());
+        if granted >= self.quorum() && valid {
+            return Ok(true);
+        }
+        // Undo partial grants so the key is free for the next attempt.
+        self.count(|node| node.delete_if_eq(&key, token));
+        Ok(false)
+    }
+
+    fn renew(&self, key: &str, token: &str, ttl: Duration) -> Result<bool, LockError> {
+        let key = self.key(key);
+        Ok(self.count(|node| node.expire_if_eq(&key, token, ttl)) >= self.quorum())
+    }
+
+    fn release(&self, key: &str, token: &str) -> Result<bool, LockError> {
+        let key = self.key(key);
+        Ok(self.count(|node| node.delete_if_eq(&key, token)) >= self.quorum())
+    }
+
+    fn name(&self) -> &str {
+        "redis"
+    }
+}
+
+// ============================================================================
+// Locks
+// ============================================================================
+
+/// Acquires named locks from a backend.
+pub struct DistributedLock {
+    backend: Arc<dyn LockBackend>,
+    config: LockConfig,
+}
+
+impl DistributedLock {
+    /// Create a lock manager with default configuration.
+    pub fn new(backend: Arc<dyn LockBackend>) -> Self {
+        Self::with_config(backend, LockConfig::default())
+    }
+
+    /// Create a lock manager with a specific configuration.
+    pub fn with_config(backend: Arc<dyn LockBackend>, config: LockConfig) -> Self {
+        Self { backend, config }
+    }
+
+    /// Create a lock manager over an in-process backend.
+    pub fn in_memory() -> Self {
+        Self::new(Arc::new(InMemoryLockBackend::new()))
+    }
+
+    /// Get the configuration.
+    pub fn config(&self) -> &LockConfig {
+        &self.config
+    }
+
+    /// Get the backend.
+    pub fn backend(&self) -> &Arc<dyn LockBackend> {
+        &self.backend
+    }
+
+    /// Try once to take `key` with the configured TTL.
+    pub fn try_acquire(&self, key: &str) -> Result<Option<LockGuard>, LockError> {
+        self.try_acquire_for(key, self.config.ttl)
+    }
+
+    /// Take `key`, retrying until the configured acquire timeout.
+    pub async fn acquire(&self, key: &str) -> Result<LockGuard, LockError> {
+        self.acquire_with(key, self.config.acquire_timeout, self.config.ttl)
+            .await
+    }
+
+    /// Take `key` with a lease of `ttl`, retrying for up to `timeout`
+    /// (zero tries once).
+    pub async fn acquire_with(
+        &self,
+        key: &str,
+        timeout: Duration,
+        ttl: Duration,
+    ) -> Result<LockGuard, LockError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(guard) = self.try_acquire_for(key, ttl)? {
+                return Ok(guard);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(LockError::Timeout(key.to_string()));
+            }
+            let base = self.config.retry_interval;
+            let delay = base + base.mul_f64(rand::thread_rng().gen::<f64>());
+            tokio::time::sleep(delay.min(deadline - now)).await;
+        }
+    }
+
+    fn try_acquire_for(&self, key: &str, ttl: Duration) -> Result<Option<LockGuard>, LockError> {
+        let token = uuid::Uuid::new_v4().to_string();
+        if !self.backend.try_acquire(key, &token, ttl)? {
+            return Ok(None);
+        }
+        let mut guard = LockGuard {
+            key: key.to_string(),
+            token,
+            backend: self.backend.clone(),
+            held: Arc::new(AtomicBool::new(true)),
+            renewer: None,
+        };
+        if self.config.auto_renew && tokio::runtime::Handle::try_current().is_ok() {
+            guard.renewer = Some(tokio::spawn(renew_loop(
+                guard.backend.clone(),
+                guard.key.clone(),
+                guard.token.clone(),
+                ttl,
+                guard.held.clone(),
+            )));
+        }
+        Ok(Some(guard))
+    }
+}
+
+/// Renew a lease every third of its TTL until it is lost or the task is
+/// aborted.
+async fn renew_loop(
+    backend: Arc<dyn LockBackend>,
+    key: String,
+    token: String,
+    ttl: Duration,
+    held: Arc<AtomicBool>,
+) {
+    loop {
+        tokio::time::sleep(ttl / 3).await;
+        match backend.renew(&key, &token, ttl) {
+            Ok(true) => {}
+            Ok(false) => {
+                tracing::warn!("Lost distributed lock '{key}'");
+                held.store(false, Ordering::SeqCst);
+                return;
+            }
+            Err(e) => tracing::warn!("Failed to renew distributed lock '{key}': {e}"),
+        }
+    }
+}
+
+/// A held lock, released when dropped.
+pub struct LockGuard {
+    key: String,
+    token: String,
+    backend: Arc<dyn LockBackend>,
+    held: Arc<AtomicBool>,
+    renewer: Option<tokio::task::JoinHandle<()>>,
+}
+
+impl LockGuard {
+    /// The lock's key.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// The token identifying this acquisition.
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    /// Whether the lease is still believed held. Turns `false` once renewal
+    /// finds the lease expired and taken over, or after release.
+    pub fn is_held(&self) -> bool {
+        self.held.load(Ordering::SeqCst)
+    }
+
+    /// Extend the lease to `ttl` from now. Returns whether it was still
+    /// held.
+    pub fn renew(&self, ttl: Duration) -> Result<bool, LockError> {
+        let renewed = self.backend.renew(&self.key, &self.token, ttl)?;
+        if !renewed {
+            self.held.store(false, Ordering::SeqCst);
+        }
+        Ok(renewed)
+    }
+
+    /// Release the lock. Returns whether it was still held.
+    pub fn release(mut self) -> Result<bool, LockError> {
+        self.release_inner()
+    }
+
+    fn release_inner(&mut self) -> Result<bool, LockError> {
+        if let Some(renewer) = self.renewer.take() {
+            renewer.abort();
+        }
+        if !self.held.swap(false, Ordering::SeqCst) {
+            return Ok(false);
+        }
+        self.backend.release(&self.key, &self.token)
+    }
+}
+
+impl Drop for LockGuard {
+    fn drop(&mut self) {
+        if let Err(e) = self.release_inner() {
+            tracing::warn!("Failed to release distributed lock '{}': {e}", self.key);
+        }
+    }
+}
+
+impl std::fmt::Debug for LockGuard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LockGuard")
+            .field("key", &self.key)
+            .field("held", &self.is_held())
+            .finish()
+    }
+}
+
+// ============================================================================
+// Python Integration
+// ============================================================================
+
+/// Python-facing lock on one key, used as an async context manager.
+///
+/// Entering acquires the lock (raising `TimeoutError` if it cannot be taken
+/// in time) and exiting releases it. The same object can be entered again
+/// after it has been exited.
+#[pyclass(name = "DistributedLock")]
+pub struct PyDistributedLock {
+    locks: Arc<DistributedLock>,
+    key: String,
+    timeout: Duration,
+    ttl: Duration,
+    guard: Arc<Mutex<Option<LockGuard>>>,
+}
+
+impl PyDistributedLock {
+    /// Create a lock on `key`, defaulting the timeout and TTL to the
+    /// manager's configuration.
+    pub fn new(
+        locks: Arc<DistributedLock>,
+        key: &str,
+        timeout: Option<Duration>,
+        ttl: Option<Duration>,
+    ) -> Self {
+        let config = locks.config().clone();
+        Self {
+            locks,
+            key: key.to_string(),
+            timeout: timeout.unwrap_or(config.acquire_timeout),
+            ttl: ttl.unwrap_or(config.ttl),
+            guard: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    fn acquiring(&self) -> impl std::future::Future<Output = PyResult<()>> + Send + 'static {
+        let locks = self.locks.clone();
+        let guard = self.guard.clone();
+        let (key, timeout, ttl) = (self.key.clone(), self.timeout, self.ttl);
+        async move {
+            if guard.lock().is_some() {
+                return Err(pyo3::exceptions::PyRuntimeError::new_err(format!(
+                    "lock '{key}' is already acquired"
+                )));
+            }
+            let acquired = locks
+                .acquire_with(&key, timeout, ttl)
+                .await
+                .map_err(lock_error_to_py)?;
+            *guard.lock() = Some(acquired);
+            Ok(())
+        }
+    }
+}
+
+#[pymethods]
+impl PyDistributedLock {
+    /// The lock's key.
+    #[getter]
+    fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Whether the lock is currently held by this object.
+    #[getter]
+    fn held(&self) -> bool {
+        self.guard.lock().as_ref().is_some_and(LockGuard::is_held)
+    }
+
+    /// Acquire the lock, awaiting until it is taken.
+    fn acquire<'py>(&self, py: Python<'py>) -> PyResult<&'py PyAny> {
+        pyo3_asyncio::tokio::future_into_py(py, self.acquiring())
+    }
+
+    /// Release the lock. Returns whether it was still held.
+    fn release(&self) -> PyResult<bool> {
+        match self.guard.lock().take() {
+            Some(guard) => guard.release().map_err(lock_error_to_py),
+            None => Ok(false),
+        }
+    }
+
+    fn __aenter__<'py>(slf: PyRef<'py, Self>, py: Python<'py>) -> PyResult<&'py PyAny> {
+        let acquiring = slf.acquiring();
+        let this: PyObject = slf.into_py(py);
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            acquiring.await?;
+            Ok(this)
+        })
+    }
+
+    #[pyo3(signature = (_exc_type=None, _exc_value=None, _traceback=None))]
+    fn __aexit__<'py>(
+        &self,
+        py: Python<'py>,
+        _exc_type: Option<&PyAny>,
+        _exc_value: Option<&PyAny>,
+        _traceback: Option<&PyAny>,
+    ) -> PyResult<&'py PyAny> {
+        self.release()?;
+        pyo3_asyncio::tokio::future_into_py(py, async move { Ok(false) })
+    }
+}
+
+fn lock_error_to_py(e: LockError) -> PyErr {
+    match e {
+        LockError::Timeout(_) => pyo3::exceptions::PyTimeoutError::new_err(e.to_string()),
+        LockError::Backend(_) => pyo3::exceptions::PyRuntimeError::new_err(e.to_string()),
+    }
+}
+
+// ============================================================================
+// Errors
+// ============================================================================
+
+/// Distributed lock errors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LockError {
+    /// The lock could not be acquired before the timeout.
+    Timeout(String),
+    /// The backend failed.
+    Backend(String),
+}
+
+impl std::fmt::Display for LockError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LockError::Timeout(key) => write!(f, "Timed out acquiring lock: {key}"),
+            LockError::Backend(msg) => write!(f, "Lock backend error: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for LockError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::redis::{MockRedisClient, RedisConfig};
+
+    fn quick_config() -> LockConfig {
+        LockConfig::new()
+            .with_ttl(Duration::from_millis(300))
+            .with_acquire_timeout(Duration::from_millis(100))
+            .with_retry_interval(Duration::from_millis(5))
+    }
+
+    fn redis_nodes(n: usize) -> Vec<Arc<MockRedisClient>> {
+        (0..n)
+            .map(|_| Arc::new(MockRedisClient::new(RedisConfig::default())))
+            .collect()
+    }
+
+    fn redis_backend(nodes: &[Arc<MockRedisClient>]) -> Arc<RedisLockBackend> {
+        Arc::new(RedisLockBackend::new(
+            nodes
+                .iter()
+                .map(|n| n.clone() as Arc<dyn RedisClient>)
+                .collect(),
+        ))
+    }
+
+    // ---------- Backend Tests ----------
+
+    #[test]
+    fn test_in_memory_backend_leases() {
+        let backend = InMemoryLockBackend::new();
+        let ttl = Duration::from_secs(10);
+        assert!(backend.try_acquire("k", "a", ttl).unwrap());
+        assert!(!backend.try_acquire("k", "b", ttl).unwrap());
+        assert!(!backend.renew("k", "b", ttl).unwrap());
+        assert!(!backend.release("k", "b").unwrap());
+        assert!(backend.renew("k", "a", ttl).unwrap());
+        assert!(backend.release("k", "a").unwrap());
+        assert!(backend.try_acquire("k", "b", ttl).unwrap());
+    }
+
+    #[test]
+    fn test_in_memory_backend_expired_lease_is_free() {
+        let backend = InMemoryLockBackend::new();
+        assert!(backend
+            .try_acquire("k", "a", Duration::from_millis(10))
+            .unwrap());
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(!backend.renew("k", "a", Duration::from_secs(1)).unwrap());
+        assert!(backend
+            .try_acquire("k", "b", Duration::from_secs(1))
+            .unwrap());
+        assert!(!backend.release("k", "a").unwrap());
+    }
+
+    #[test]
+    fn test_redis_backend_single_node() {
+        let nodes = redis_nodes(1);
+        let backend = redis_backend(&nodes);
+        let ttl = Duration::from_secs(10);
+        assert!(backend.try_acquire("jobs", "a", ttl).unwrap());
+        assert_eq!(
+            nodes[0].get("lock:jobs").unwrap().unwrap().as_str(),
+            Some("a")
+        );
+        assert!(!backend.try_acquire("jobs", "b", ttl).unwrap());
+        assert!(!backend.release("jobs", "b").unwrap());
+        assert!(backend.release("jobs", "a").unwrap());
+        assert!(nodes[0].get("lock:jobs").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_redis_backend_quorum() {
+        let nodes = redis_nodes(3);
+        let backend = redis_backend(&nodes);
+        let ttl = Duration::from_secs(10);
+
+        // One node already held by someone else: 2 of 3 is still a quorum.
+        nodes[0].set_nx("lock:k", "other", ttl).unwrap();
+        assert!(backend.try_acquire("k", "a", ttl).unwrap());
+        assert!(backend.renew("k", "a", ttl).unwrap());
+        assert!(backend.release("k", "a").unwrap());
+
+        // Two nodes held elsewhere: no quorum, and the partial grant is undone.
+        nodes[1].set_nx("lock:k", "other", ttl).unwrap();
+        assert!(!backend.try_acquire("k", "b", ttl).unwrap());
+        assert!(nodes[2].get("lock:k").unwrap().is_none());
+    }
+
+    // ---------- DistributedLock Tests ----------
+
+    #[tokio::test]
+    async fn test_lock_excludes_until_released() {
+        let locks =
+            DistributedLock::with_config(Arc::new(InMemoryLockBackend::new()), quick_config());
+        let guard = locks.acquire("k").await.unwrap();
+        assert_eq!(guard.key(), "k");
+        assert!(guard.is_held());
+        assert!(locks.try_acquire("k").unwrap().is_none());
+        assert_eq!(
+            locks.acquire("k").await.unwrap_err(),
+            LockError::Timeout("k".to_string())
+        );
+
+        assert!(guard.release().unwrap());
+        assert!(locks.try_acquire("k").unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_lock_released_on_drop() {
+        let locks = DistributedLock::in_memory();
+        {
+            let _guard = locks.try_acquire("k").unwrap().unwrap();
+        }
+        assert!(locks.try_acquire("k").unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_acquire_waits_for_release() {
+        let locks = Arc::new(DistributedLock::with_config(
+            Arc::new(InMemoryLockBackend::new()),
+            quick_config(),
+        ));
+        let guard = locks.acquire("k").await.unwrap();
+        let waiter = {
+            let locks = locks.clone();
+            tokio::spawn(async move { locks.acquire("k").await.map(|g| g.token().to_string()) })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let first_token = guard.token().to_string();
+        drop(guard);
+        let token = waiter.await.unwrap().unwrap();
+        assert_ne!(token, first_token);
+    }
+
+    #[tokio::test]
+    async fn test_auto_renew_keeps_lease() {
+        let locks = DistributedLock::with_config(
+            Arc::new(InMemoryLockBackend::new()),
+            quick_config().with_ttl(Duration::from_millis(60)),
+        );
+        let guard = locks.acquire("k").await.unwrap();
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert!(guard.is_held());
+        assert!(locks.try_acquire("k").unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_lease_expires_without_renewal() {
+        let locks = DistributedLock::with_config(
+            Arc::new(InMemoryLockBackend::new()),
+            quick_config()
+                .with_ttl(Duration::from_millis(30))
+                .with_auto_renew(false),
+        );
+        let stale = locks.acquire("k").await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let fresh = locks.try_acquire("k").unwrap().unwrap();
+
+        // The stale holder can neither renew nor release the new lease.
+        assert!(!stale.renew(Duration::from_secs(1)).unwrap());
+        assert!(!stale.is_held());
+        assert!(!stale.release().unwrap());
+        assert!(fresh.is_held());
+        assert!(locks.try_acquire("k").unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_auto_renew_detects_lost_lease() {
+        let backend = Arc::new(InMemoryLockBackend::new());
+        let locks = DistributedLock::with_config(
+            backend.clone(),
+            quick_config().with_ttl(Duration::from_millis(60)),
+        );
+        let guard = locks.acquire("k").await.unwrap();
+        // Someone force-releases the lease and takes it over.
+        backend.release("k", guard.token()).unwrap();
+        backend
+            .try_acquire("k", "intruder", Duration::from_secs(10))
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        assert!(!guard.is_held());
+    }
+
+    #[test]
+    fn test_error_display() {
+        assert_eq!(
+            LockError::Timeout("jobs".to_string()).to_string(),
+            "Timed out acquiring lock: jobs"
+        );
+        assert_eq!(
+            LockError::Backend("down".to_string()).to_string(),
+            "Lock backend error: down"
+        );
+    }
+}
@@ -8,6 +8,7 @@
 
 use sha2::{Digest, Sha256};
 use std::collections::HashSet;
+use xxhash_rust::xxh3::xxh3_64;
 
 use super::{Middleware, MiddlewareAction, MiddlewareResult};
 use crate::request::Request;
@@ -34,6 +35,8 @@ pub enum EtagMethod {
     ContentHash,
     /// CRC32 checksum (faster, less collision resistant)
     Crc32,
+    /// XXH3 64-bit hash (fastest, not cryptographic)
+    Xxh3,
     /// Length + hash prefix (very fast)
     #[default]
     LengthHash,
@@ -56,6 +59,7 @@ impl EtagMethod {
                 let crc = crc32fast::hash(body);
                 format!("{crc:08x}")
             }
+            EtagMethod::Xxh3 => format!("{:016x}", xxh3_64(body)),
             EtagMethod::LengthHash => {
                 // Fast: length + first 8 bytes of SHA-256
                 let len = body.len();
@@ -128,6 +132,12 @@ impl EtagConfig {
         self
     }
 
+    /// Use XXH3 method.
+    pub fn xxh3(mut self) -> Self {
+        self.method = EtagMethod::Xxh3;
+        self
+    }
+
     /// Use length + hash method.
     pub fn length_hash(mut self) -> Self {
         self.method = EtagMethod::LengthHash;
@@ -231,6 +241,11 @@ impl EtagMiddleware {
             return false;
         }
 
+        // Streamed and file bodies are not known up front
+        if response.is_streaming() || response.is_file() {
+            return false;
+        }
+
         // Check body size
         let body_len = response.body_bytes().len();
         if body_len < self.config.min_size {
@@ -382,6 +397,12 @@ pub fn generate_strong_etag(data: &[u8]) -> String {
     format!("\"{}\"", hex::encode(&hash[..16]))
 }
 
+/// Weak ETag of a JSON response body, from its XXH3 hash. Weak, so it
+/// still validates the body after compression.
+pub fn json_etag(body: &[u8]) -> String {
+    format!("W/\"{}\"", EtagMethod::Xxh3.generate(body))
+}
+
 /// Check if ETag matches If-None-Match header.
 pub fn matches_if_none_match(if_none_match: &str, etag: &str) -> bool {
     let client_etags = EtagMiddleware::parse_if_none_match(if_none_match);
@@ -445,5 +466,11 @@ mod tests {
         let strong_etag = generate_strong_etag(data);
         assert!(!strong_etag.starts_with("W/"));
         assert!(strong_etag.starts_with('"'));
+
+        let json = json_etag(br#"{"id":1}"#);
+        assert_eq!(json.len(), 20);
+        assert_eq!(json, json_etag(br#"{"id":1}"#));
+        assert_ne!(json, json_etag(br#"{"id":2}"#));
+        assert!(matches_if_none_match(&json.replace("W/", ""), &json));
     }
 }
@@ -4,6 +4,13 @@
 //! - Immutable event log with append-only storage
 //! - Aggregate state reconstruction from events
 //! - Snapshot support for performance optimization
+//! - Aggregate repository with per-event-type apply functions and
+//!   snapshot-accelerated loading
+//! - Global all-events stream with catch-up and live subscriptions and
+//!   per-subscriber checkpoints
+//! - Multi-aggregate transactions, atomic where the store supports it and
+//!   run as a checked sequence of appends otherwise
+//! - Per-aggregate encryption of event data with crypto-shredding
 //! - In-memory event store for development and testing
 //! - Configurable snapshot intervals and event TTL
 //!
@@ -31,11 +38,17 @@
 
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
+use base64::Engine;
 use parking_lot::RwLock;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use tokio::sync::watch;
+
+use super::secrets::{SecretsError, SecretsProvider};
 
 // ============================================================================
 // Configuration
@@ -49,7 +62,7 @@ use parking_lot::RwLock;
 pub struct EventSourcingConfig {
     /// Storage backend type (e.g., "memory", "postgresql").
     pub store_type: String,
-    /// Number of events between automatic snapshots.
+    /// Number of events between snapshots taken by an [`AggregateRepository`].
     pub snapshot_interval: u32,
     /// Whether snapshot creation is enabled.
     pub enable_snapshots: bool,
@@ -140,6 +153,10 @@ pub struct Event {
     pub metadata: HashMap<String, String>,
     /// Sequential version number within the aggregate.
     pub version: u64,
+    /// Global sequence number in the all-events stream, assigned by the
+    /// store on append (0 until then).
+    #[serde(default)]
+    pub position: u64,
     /// Unix timestamp (seconds) when the event was created.
     pub timestamp: u64,
 }
@@ -154,6 +171,7 @@ impl Event {
             data,
             metadata: HashMap::new(),
             version,
+            position: 0,
             timestamp: std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap_or_default()
@@ -320,6 +338,103 @@ impl Snapshot {
     }
 }
 
+// ============================================================================
+// Multi-Aggregate Transactions
+// ============================================================================
+
+/// Metadata key tagging the events appended by a transaction.
+pub const TRANSACTION_ID_KEY: &str = "transaction_id";
+
+/// Events for one aggregate in an [`EventTransaction`].
+#[derive(Clone, Debug)]
+pub struct StreamAppend {
+    /// Aggregate the events belong to.
+    pub aggregate_id: String,
+    /// Version the aggregate must be at before the append.
+    pub expected_version: u64,
+    /// Events to append, in order.
+    pub events: Vec<Event>,
+}
+
+/// Events for several aggregates appended as one unit with
+/// [`EventStore::append_transaction`].
+#[derive(Clone, Debug)]
+pub struct EventTransaction {
+    /// Transaction identifier, recorded on every event under
+    /// [`TRANSACTION_ID_KEY`].
+    pub id: String,
+    /// Appends in the order they are written.
+    pub streams: Vec<StreamAppend>,
+}
+
+impl Default for EventTransaction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventTransaction {
+    /// Create an empty transaction with a random ID.
+    pub fn new() -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            streams: Vec::new(),
+        }
+    }
+
+    /// Append `events` to `aggregate_id`, which must be at
+    /// `expected_version`.
+    ///
+    /// Events for an aggregate already in the transaction are added to its
+    /// stream, keeping its expected version.
+    pub fn append(mut self, aggregate_id: &str, expected_version: u64, events: Vec<Event>) -> Self {
+        let events = events
+            .into_iter()
+            .map(|event| event.with_metadata(TRANSACTION_ID_KEY, &self.id));
+        match self
+            .streams
+            .iter_mut()
+            .find(|s| s.aggregate_id == aggregate_id)
+        {
+            Some(stream) => stream.events.extend(events),
+            None => self.streams.push(StreamAppend {
+                aggregate_id: aggregate_id.to_string(),
+                expected_version,
+                events: events.collect(),
+            }),
+        }
+        self
+    }
+
+    /// IDs of the aggregates in the transaction, in order.
+    pub fn aggregate_ids(&self) -> Vec<String> {
+        self.streams
+            .iter()
+            .map(|s| s.aggregate_id.clone())
+            .collect()
+    }
+}
+
+/// An aggregate whose version did not match the expected one.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AggregateConflict {
+    pub aggregate_id: String,
+    pub expected: u64,
+    pub actual: u64,
+}
+
+/// Current version of `aggregate_id` in `store`, 0 if it has no events.
+fn current_version<S: EventStore + ?Sized>(
+    store: &S,
+    aggregate_id: &str,
+) -> Result<u64, EventSourcingError> {
+    match store.get_events(aggregate_id, None) {
+        Ok(events) => Ok(events.last().map(|e| e.version).unwrap_or(0)),
+        Err(EventSourcingError::AggregateNotFound(_)) => Ok(0),
+        Err(e) => Err(e),
+    }
+}
+
 // ============================================================================
 // Event Store Trait
 // ============================================================================
@@ -352,6 +467,72 @@ pub trait EventStore: Send + Sync {
 
     /// Persist a snapshot for an aggregate.
     fn save_snapshot(&self, snapshot: &Snapshot) -> Result<(), EventSourcingError>;
+
+    /// Read up to `limit` events from the all-events stream, in global
+    /// order, starting after position `after`.
+    fn read_all(&self, after: u64, limit: usize) -> Result<Vec<Event>, EventSourcingError>;
+
+    /// Watch the position of the latest event in the all-events stream.
+    ///
+    /// The value changes after every successful append, so live
+    /// subscribers can wait on it instead of polling.
+    fn watch_head(&self) -> watch::Receiver<u64>;
+
+    /// Retrieve the position a subscriber last checkpointed, if any.
+    fn get_checkpoint(&self, subscriber: &str) -> Result<Option<u64>, EventSourcingError>;
+
+    /// Persist the position a subscriber has processed up to.
+    fn save_checkpoint(&self, subscriber: &str, position: u64) -> Result<(), EventSourcingError>;
+
+    /// Whether [`append_transaction`](Self::append_transaction) writes all
+    /// of a transaction's events or none.
+    fn supports_transactions(&self) -> bool {
+        false
+    }
+
+    /// Append the events of several aggregates as one unit.
+    ///
+    /// Every expected version is checked first; if any differs, nothing is
+    /// written and the error lists every conflicting aggregate. Stores with
+    /// transactions then write all streams at once. This default, for
+    /// stores without, acts as a process manager: it appends the streams
+    /// in order, and if one still fails (another writer got in after the
+    /// check) reports which aggregates were committed, which failed and
+    /// which are pending, so the caller can retry the rest or compensate.
+    fn append_transaction(&self, transaction: &EventTransaction) -> Result<(), EventSourcingError> {
+        let mut conflicts = Vec::new();
+        for stream in &transaction.streams {
+            let actual = current_version(self, &stream.aggregate_id)?;
+            if actual != stream.expected_version {
+                conflicts.push(AggregateConflict {
+                    aggregate_id: stream.aggregate_id.clone(),
+                    expected: stream.expected_version,
+                    actual,
+                });
+            }
+        }
+        if !conflicts.is_empty() {
+            return Err(EventSourcingError::Conflicts(conflicts));
+        }
+
+        for (i, stream) in transaction.streams.iter().enumerate() {
+            if let Err(error) = self.append_events(
+                &stream.aggregate_id,
+                &stream.events,
+                stream.expected_version,
+            ) {
+                let ids = transaction.aggregate_ids();
+                return Err(EventSourcingError::PartialAppend {
+                    transaction_id: transaction.id.clone(),
+                    committed: ids[..i].to_vec(),
+                    failed: stream.aggregate_id.clone(),
+                    pending: ids[i + 1..].to_vec(),
+                    reason: Box::new(error),
+                });
+            }
+        }
+        Ok(())
+    }
 }
 
 // ============================================================================
@@ -367,6 +548,12 @@ pub struct InMemoryEventStore {
     events: Arc<RwLock<HashMap<String, Vec<Event>>>>,
     /// Latest snapshot per aggregate ID.
     snapshots: Arc<RwLock<HashMap<String, Snapshot>>>,
+    /// All events in global order; an event's position is its index + 1.
+    log: Arc<RwLock<Vec<Event>>>,
+    /// Last processed position per subscriber.
+    checkpoints: Arc<RwLock<HashMap<String, u64>>>,
+    /// Position of the latest appended event.
+    head: watch::Sender<u64>,
     /// Internal metrics tracker.
     metrics: Arc<EventSourcingMetrics>,
     /// Configuration reference.
@@ -376,12 +563,7 @@ pub struct InMemoryEventStore {
 impl InMemoryEventStore {
     /// Create a new in-memory event store with default configuration.
     pub fn new() -> Self {
-        Self {
-            events: Arc::new(RwLock::new(HashMap::new())),
-            snapshots: Arc::new(RwLock::new(HashMap::new())),
-            metrics: Arc::new(EventSourcingMetrics::default()),
-            config: EventSourcingConfig::default(),
-        }
+        Self::with_config(EventSourcingConfig::default())
     }
 
     /// Create a new in-memory event store with a specific configuration.
@@ -389,6 +571,9 @@ impl InMemoryEventStore {
         Self {
             events: Arc::new(RwLock::new(HashMap::new())),
             snapshots: Arc::new(RwLock::new(HashMap::new())),
+            log: Arc::new(RwLock::new(Vec::new())),
+            checkpoints: Arc::new(RwLock::new(HashMap::new())),
+            head: watch::channel(0).0,
             metrics: Arc::new(EventSourcingMetrics::default()),
             config,
         }
@@ -415,10 +600,48 @@ impl InMemoryEventStore {
             .get_stats(&self.events.read(), &self.snapshots.read())
     }
 
-    /// Clear all stored events and snapshots.
+    /// Clear all stored events, snapshots and subscriber checkpoints.
     pub fn clear(&self) {
         self.events.write().clear();
         self.snapshots.write().clear();
+        self.log.write().clear();
+        self.checkpoints.write().clear();
+        self.head.send_replace(0);
+    }
+
+    /// Fail if appending `count` events would exceed the per-aggregate limit.
+    fn check_limit(
+        &self,
+        store: &HashMap<String, Vec<Event>>,
+        aggregate_id: &str,
+        count: usize,
+    ) -> Result<(), EventSourcingError> {
+        let stored = store.get(aggregate_id).map_or(0, Vec::len);
+        if stored + count > self.config.max_events_per_aggregate {
+            return Err(EventSourcingError::StoreError(format!(
+                "Aggregate '{}' would exceed max events limit of {}",
+                aggregate_id, self.config.max_events_per_aggregate
+            )));
+        }
+        Ok(())
+    }
+
+    /// Append checked events to an aggregate and the global log.
+    fn write(&self, store: &mut HashMap<String, Vec<Event>>, aggregate_id: &str, events: &[Event]) {
+        let aggregate_events = store.entry(aggregate_id.to_string()).or_default();
+        // Positions are assigned under the aggregate lock so the global log
+        // stays in append order.
+        let mut log = self.log.write();
+        for event in events {
+            let mut event = event.clone();
+            event.position = log.len() as u64 + 1;
+            log.push(event.clone());
+            aggregate_events.push(event);
+            self.metrics.record_event_appended();
+        }
+        let head = log.len() as u64;
+        drop(log);
+        self.head.send_replace(head);
     }
 }
 
@@ -436,10 +659,13 @@ impl EventStore for InMemoryEventStore {
         expected_version: u64,
     ) -> Result<(), EventSourcingError> {
         let mut store = self.events.write();
-        let aggregate_events = store.entry(aggregate_id.to_string()).or_default();
 
         // Check for concurrency conflicts.
-        let current_version = aggregate_events.last().map(|e| e.version).unwrap_or(0);
+        let current_version = store
+            .get(aggregate_id)
+            .and_then(|events| events.last())
+            .map(|e| e.version)
+            .unwrap_or(0);
         if current_version != expected_version {
             return Err(EventSourcingError::ConcurrencyConflict {
                 aggregate_id: aggregate_id.to_string(),
@@ -447,54 +673,8 @@ impl EventStore for InMemoryEventStore {
                 actual: current_version,
             });
         }
-
-        // Check max events per aggregate.
-        if aggregate_events.len() + events.len() > self.config.max_events_per_aggregate {
-            return Err(EventSourcingError::StoreError(format!(
-                "Aggregate '{}' would exceed max events limit of {}",
-                aggregate_id, self.config.max_events_per_aggregate
-            )));
-        }
-
-        for event in events {
-            aggregate_events.push(event.clone());
-            self.metrics.record_event_appended();
-        }
-
-        // Auto-snapshot if enabled and interval reached.
-        if self.config.enable_snapshots && self.config.snapshot_interval > 0 {
-            let new_version = aggregate_events.last().map(|e| e.version).unwrap_or(0);
-            if new_version > 0 && new_version % (self.config.snapshot_interval as u64) == 0 {
-                // Build a minimal aggregate state for the snapshot.
-                let mut state = JsonValue::Object(serde_json::Map::new());
-                for evt in aggregate_events.iter() {
-                    if let (Some(state_map), Some(event_map)) =
-                        (state.as_object_mut(), evt.data.as_object())
-                    {
-                        for (k, v) in event_map {
-                            state_map.insert(k.clone(), v.clone());
-                        }
-                    }
-                }
-
-                let snapshot = Snapshot {
-                    aggregate_id: aggregate_id.to_string(),
-                    version: new_version,
-                    state,
-                    timestamp: std::time::SystemTime::now()
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .unwrap_or_default()
-                        .as_secs(),
-                };
-
-                drop(store); // Release events lock before acquiring snapshots lock.
-                self.snapshots
-                    .write()
-                    .insert(aggregate_id.to_string(), snapshot);
-                self.metrics.record_snapshot_created();
-            }
-        }
-
+        self.check_limit(&store, aggregate_id, events.len())?;
+        self.write(&mut store, aggregate_id, events);
         Ok(())
     }
 
@@ -532,6 +712,618 @@ impl EventStore for InMemoryEventStore {
         self.metrics.record_snapshot_created();
         Ok(())
     }
+
+    fn read_all(&self, after: u64, limit: usize) -> Result<Vec<Event>, EventSourcingError> {
+        let log = self.log.read();
+        let start = (after as usize).min(log.len());
+        Ok(log[start..].iter().take(limit).cloned().collect())
+    }
+
+    fn watch_head(&self) -> watch::Receiver<u64> {
+        self.head.subscribe()
+    }
+
+    fn get_checkpoint(&self, subscriber: &str) -> Result<Option<u64>, EventSourcingError> {
+        Ok(self.checkpoints.read().get(subscriber).copied())
+    }
+
+    fn save_checkpoint(&self, subscriber: &str, position: u64) -> Result<(), EventSourcingError> {
+        self.checkpoints
+            .write()
+            .insert(subscriber.to_string(), position);
+        Ok(())
+    }
+
+    fn supports_transactions(&self) -> bool {
+        true
+    }
+
+    fn append_transaction(&self, transaction: &EventTransaction) -> Result<(), EventSourcingError> {
+        // Every stream is checked and written under one lock.
+        let mut store = self.events.write();
+        let conflicts: Vec<AggregateConflict> = transaction
+            .streams
+            .iter()
+            .filter_map(|stream| {
+                let actual = store
+                    .get(&stream.aggregate_id)
+                    .and_then(|events| events.last())
+                    .map(|e| e.version)
+                    .unwrap_or(0);
+                (actual != stream.expected_version).then(|| AggregateConflict {
+                    aggregate_id: stream.aggregate_id.clone(),
+                    expected: stream.expected_version,
+                    actual,
+                })
+            })
+            .collect();
+        if !conflicts.is_empty() {
+            return Err(EventSourcingError::Conflicts(conflicts));
+        }
+        for stream in &transaction.streams {
+            self.check_limit(&store, &stream.aggregate_id, stream.events.len())?;
+        }
+        for stream in &transaction.streams {
+            self.write(&mut store, &stream.aggregate_id, &stream.events);
+        }
+        Ok(())
+    }
+}
+
+// ============================================================================
+// Encryption
+// ============================================================================
+
+/// Metadata key naming the secret an event's data is encrypted with.
+pub const ENCRYPTION_KEY_ID: &str = "encryption_key_id";
+
+/// Metadata key set to `"true"` on events whose key has been deleted.
+pub const SHREDDED_KEY: &str = "shredded";
+
+/// Per-aggregate encryption of event data with AES-256-GCM.
+///
+/// Each aggregate gets its own random data key, kept in a
+/// [`SecretsProvider`] under `{key_prefix}{aggregate_id}:{generation}`,
+/// with the ID of the current key stored under `{key_prefix}{aggregate_id}`.
+/// An event's data is replaced by the base64 of nonce and ciphertext,
+/// authenticated against the event ID, and the key ID is recorded under
+/// [`ENCRYPTION_KEY_ID`]. [`shred`](Self::shred) deletes an aggregate's key:
+/// its events stay in the log, but read back with `null` data and
+/// [`SHREDDED_KEY`] set. Events appended afterwards get a key of a new
+/// generation, so they stay readable.
+pub struct EventEncryption {
+    secrets: Arc<dyn SecretsProvider>,
+    key_prefix: String,
+    aggregates: Vec<String>,
+    rng: SystemRandom,
+}
+
+impl EventEncryption {
+    /// Encrypt every aggregate's events with keys from `secrets`.
+    pub fn new(secrets: Arc<dyn SecretsProvider>) -> Self {
+        Self {
+            secrets,
+            key_prefix: "events:".to_string(),
+            aggregates: Vec::new(),
+            rng: SystemRandom::new(),
+        }
+    }
+
+    /// Prefix for key IDs (default `events:`).
+    pub fn with_key_prefix(mut self, prefix: &str) -> Self {
+        self.key_prefix = prefix.to_string();
+        self
+    }
+
+    /// Only encrypt aggregates matching these patterns: exact IDs, `prefix*`
+    /// or `*`. All aggregates are encrypted when none are given.
+    pub fn with_aggregates(mut self, patterns: Vec<String>) -> Self {
+        self.aggregates = patterns;
+        self
+    }
+
+    /// Whether events of `aggregate_id` are encrypted.
+    pub fn applies_to(&self, aggregate_id: &str) -> bool {
+        self.aggregates.is_empty()
+            || self
+                .aggregates
+                .iter()
+                .any(|pattern| match pattern.strip_suffix('*') {
+                    Some(prefix) => aggregate_id.starts_with(prefix),
+                    None => pattern == aggregate_id,
+                })
+    }
+
+    /// ID of the secret holding `aggregate_id`'s current key, if it has one.
+    pub fn key_id(&self, aggregate_id: &str) -> Result<Option<String>, EventSourcingError> {
+        let current = self.secrets.get(&self.current_key_ref(aggregate_id))?;
+        Ok(current.map(|id| String::from_utf8_lossy(&id).into_owned()))
+    }
+
+    /// Encrypt an event's data, creating the aggregate's key if needed.
+    /// Events of aggregates not covered are returned unchanged.
+    pub fn encrypt(&self, event: &Event) -> Result<Event, EventSourcingError> {
+        if !self.applies_to(&event.aggregate_id) {
+            return Ok(event.clone());
+        }
+        let (key_id, key) = self.current_key(&event.aggregate_id)?;
+        let plaintext = serde_json::to_vec(&event.data)
+            .map_err(|e| EventSourcingError::SerializationError(e.to_string()))?;
+        let mut event = event.clone();
+        event.data = JsonValue::String(self.seal(&key, event.id.as_bytes(), plaintext)?);
+        Ok(event.with_metadata(ENCRYPTION_KEY_ID, &key_id))
+    }
+
+    /// Decrypt an event's data. Events whose key was deleted come back
+    /// with `null` data and [`SHREDDED_KEY`] set; unencrypted events are
+    /// returned unchanged.
+    pub fn decrypt(&self, mut event: Event) -> Result<Event, EventSourcingError> {
+        let Some(key_id) = event.metadata.get(ENCRYPTION_KEY_ID).cloned() else {
+            return Ok(event);
+        };
+        let Some(key) = self.secrets.get(&key_id)? else {
+            event.data = JsonValue::Null;
+            return Ok(event.with_metadata(SHREDDED_KEY, "true"));
+        };
+        let JsonValue::String(sealed) = &event.data else {
+            return Err(EventSourcingError::EncryptionError(format!(
+                "event '{}' has no ciphertext",
+                event.id
+            )));
+        };
+        let plaintext = self.open(&key, event.id.as_bytes(), sealed)?;
+        event.data = serde_json::from_slice(&plaintext)
+            .map_err(|e| EventSourcingError::SerializationError(e.to_string()))?;
+        Ok(event)
+    }
+
+    /// Delete `aggregate_id`'s key, making its encrypted events and
+    /// snapshots unreadable. Returns whether a key existed.
+    pub fn shred(&self, aggregate_id: &str) -> Result<bool, EventSourcingError> {
+        let Some(key_id) = self.key_id(aggregate_id)? else {
+            return Ok(false);
+        };
+        // Unlink the key first so no new event is sealed with it.
+        self.secrets.delete(&self.current_key_ref(aggregate_id))?;
+        Ok(self.secrets.delete(&key_id)?)
+    }
+
+    /// ID of the secret naming `aggregate_id`'s current key.
+    fn current_key_ref(&self, aggregate_id: &str) -> String {
+        format!("{}{aggregate_id}", self.key_prefix)
+    }
+
+    /// Fetch an aggregate's current key and its ID, generating a key of a
+    /// new generation if the aggregate has none.
+    fn current_key(&self, aggregate_id: &str) -> Result<(String, Vec<u8>), EventSourcingError> {
+        if let Some(key_id) = self.key_id(aggregate_id)? {
+            let key = self.data_key(&key_id)?;
+            return Ok((key_id, key));
+        }
+        let mut key = vec![0u8; 32];
+        self.rng
+            .fill(&mut key)
+            .map_err(|_| EventSourcingError::EncryptionError("no randomness".to_string()))?;
+        let current = self.current_key_ref(aggregate_id);
+        let key_id = format!("{current}:{}", uuid::Uuid::new_v4());
+        // The key is stored before it is linked, so whoever reads the link
+        // finds the key.
+        self.secrets.put_if_absent(&key_id, &key)?;
+        if self.secrets.put_if_absent(&current, key_id.as_bytes())? {
+            return Ok((key_id, key));
+        }
+        // Another writer linked a key first.
+        self.secrets.delete(&key_id)?;
+        let key_id = self.key_id(aggregate_id)?.ok_or_else(|| {
+            EventSourcingError::EncryptionError(format!("key of '{aggregate_id}' was deleted"))
+        })?;
+        let key = self.data_key(&key_id)?;
+        Ok((key_id, key))
+    }
+
+    /// Fetch a data key that must exist.
+    fn data_key(&self, key_id: &str) -> Result<Vec<u8>, EventSourcingError> {
+        self.secrets.get(key_id)?.ok_or_else(|| {
+            EventSourcingError::EncryptionError(format!("key '{key_id}' was deleted"))
+        })
+    }
+
+    fn seal(
+        &self,
+        key: &[u8],
+        aad: &[u8],
+        mut data: Vec<u8>,
+    ) -> Result<String, EventSourcingError> {
+        let failed = |_| EventSourcingError::EncryptionError("encryption failed".to_string());
+        let key = LessSafeKey::new(UnboundKey::new(&AES_256_GCM, key).map_err(failed)?);
+        let mut nonce = [0u8; NONCE_LEN];
+        self.rng.fill(&mut nonce).map_err(failed)?;
+        key.seal_in_place_append_tag(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::from(aad),
+            &mut data,
+        )
+        .map_err(failed)?;
+        let mut sealed = nonce.to_vec();
+        sealed.extend(data);
+        Ok(base64::engine::general_purpose::STANDARD.encode(sealed))
+    }
+
+    fn open(&self, key: &[u8], aad: &[u8], sealed: &str) -> Result<Vec<u8>, EventSourcingError> {
+        let failed = |_| EventSourcingError::EncryptionError("decryption failed".to_string());
+        let mut sealed = base64::engine::general_purpose::STANDARD
+            .decode(sealed)
+            .map_err(|e| EventSourcingError::EncryptionError(e.to_string()))?;
+        if sealed.len() < NONCE_LEN {
+            return Err(EventSourcingError::EncryptionError(
+                "ciphertext too short".to_string(),
+            ));
+        }
+        let mut data = sealed.split_off(NONCE_LEN);
+        let key = LessSafeKey::new(UnboundKey::new(&AES_256_GCM, key).map_err(failed)?);
+        let nonce = Nonce::try_assume_unique_for_key(&sealed).map_err(failed)?;
+        let len = key
+            .open_in_place(nonce, Aad::from(aad), &mut data)
+            .map_err(failed)?
+            .len();
+        data.truncate(len);
+        Ok(data)
+    }
+}
+
+/// An [`EventStore`] that encrypts event data and snapshot state before
+/// handing them to another store, and decrypts them on the way out.
+pub struct EncryptedEventStore {
+    inner: Arc<dyn EventStore>,
+    encryption: Arc<EventEncryption>,
+}
+
+impl EncryptedEventStore {
+    /// Wrap `inner`, encrypting with `encryption`.
+    pub fn new(inner: Arc<dyn EventStore>, encryption: Arc<EventEncryption>) -> Self {
+        Self { inner, encryption }
+    }
+
+    fn encrypt_all(&self, events: &[Event]) -> Result<Vec<Event>, EventSourcingError> {
+        events.iter().map(|e| self.encryption.encrypt(e)).collect()
+    }
+
+    fn decrypt_all(&self, events: Vec<Event>) -> Result<Vec<Event>, EventSourcingError> {
+        events
+            .into_iter()
+            .map(|e| self.encryption.decrypt(e))
+            .collect()
+    }
+}
+
+impl EventStore for EncryptedEventStore {
+    fn append_events(
+        &self,
+        aggregate_id: &str,
+        events: &[Event],
+        expected_version: u64,
+    ) -> Result<(), EventSourcingError> {
+        let events = self.encrypt_all(events)?;
+        self.inner
+            .append_events(aggregate_id, &events, expected_version)
+    }
+
+    fn get_events(
+        &self,
+        aggregate_id: &str,
+        from_version: Option<u64>,
+    ) -> Result<Vec<Event>, EventSourcingError> {
+        self.decrypt_all(self.inner.get_events(aggregate_id, from_version)?)
+    }
+
+    fn get_snapshot(&self, aggregate_id: &str) -> Result<Option<Snapshot>, EventSourcingError> {
+        let Some(mut snapshot) = self.inner.get_snapshot(aggregate_id)? else {
+            return Ok(None);
+        };
+        if !self.encryption.applies_to(aggregate_id) {
+            return Ok(Some(snapshot));
+        }
+        // Snapshot state is sealed like an event and kept with its key ID;
+        // a shredded snapshot is dropped so the aggregate is rebuilt from
+        // its (shredded) events.
+        let (Some(key_id), Some(sealed)) = (
+            snapshot
+                .state
+                .get(ENCRYPTION_KEY_ID)
+                .and_then(JsonValue::as_str),
+            snapshot.state.get("data"),
+        ) else {
+            return Err(EventSourcingError::EncryptionError(format!(
+                "snapshot of '{aggregate_id}' is not encrypted"
+            )));
+        };
+        let event =
+            snapshot_envelope(&snapshot, sealed.clone()).with_metadata(ENCRYPTION_KEY_ID, key_id);
+        let event = self.encryption.decrypt(event)?;
+        if event.has_metadata(SHREDDED_KEY) {
+            return Ok(None);
+        }
+        snapshot.state = event.data;
+        Ok(Some(snapshot))
+    }
+
+    fn save_snapshot(&self, snapshot: &Snapshot) -> Result<(), EventSourcingError> {
+        let event = snapshot_envelope(snapshot, snapshot.state.clone());
+        let mut snapshot = snapshot.clone();
+        let sealed = self.encryption.encrypt(&event)?;
+        if let Some(key_id) = sealed.get_metadata(ENCRYPTION_KEY_ID) {
+            snapshot.state = serde_json::json!({ENCRYPTION_KEY_ID: key_id, "data": sealed.data});
+        }
+        self.inner.save_snapshot(&snapshot)
+    }
+
+    fn read_all(&self, after: u64, limit: usize) -> Result<Vec<Event>, EventSourcingError> {
+        self.decrypt_all(self.inner.read_all(after, limit)?)
+    }
+
+    fn watch_head(&self) -> watch::Receiver<u64> {
+        self.inner.watch_head()
+    }
+
+    fn get_checkpoint(&self, subscriber: &str) -> Result<Option<u64>, EventSourcingError> {
+        self.inner.get_checkpoint(subscriber)
+    }
+
+    fn save_checkpoint(&self, subscriber: &str, position: u64) -> Result<(), EventSourcingError> {
+        self.inner.save_checkpoint(subscriber, position)
+    }
+
+    fn supports_transactions(&self) -> bool {
+        self.inner.supports_transactions()
+    }
+
+    fn append_transaction(&self, transaction: &EventTransaction) -> Result<(), EventSourcingError> {
+        let mut transaction = transaction.clone();
+        for stream in &mut transaction.streams {
+            stream.events = self.encrypt_all(&stream.events)?;
+        }
+        self.inner.append_transaction(&transaction)
+    }
+}
+
+/// Event carrying a snapshot's state for sealing, authenticated against
+/// an ID unique to the aggregate's snapshot.
+fn snapshot_envelope(snapshot: &Snapshot, state: JsonValue) -> Event {
+    let mut event = Event::new(&snapshot.aggregate_id, "Snapshot", state, snapshot.version);
+    event.id = format!("snapshot-{}", snapshot.aggregate_id);
+    event
+}
+
+// ============================================================================
+// Aggregate Repository
+// ============================================================================
+
+/// Function folding an event into an aggregate's state.
+pub type ApplyFn = Arc<dyn Fn(&mut JsonValue, &Event) + Send + Sync>;
+
+/// Loads and saves aggregates of one type through an [`EventStore`].
+///
+/// Loading starts from the latest snapshot and replays only newer events.
+/// Each event is folded into the state by the apply function registered for
+/// its type; events without one only advance the version. Saving appends
+/// the uncommitted events and, per the [`EventSourcingConfig`], stores a
+/// snapshot whenever the version crosses a multiple of the snapshot
+/// interval.
+pub struct AggregateRepository {
+    aggregate_type: String,
+    store: Arc<dyn EventStore>,
+    appliers: HashMap<String, ApplyFn>,
+    config: EventSourcingConfig,
+}
+
+impl AggregateRepository {
+    /// Create a repository for `aggregate_type` backed by `store`.
+    pub fn new(aggregate_type: &str, store: Arc<dyn EventStore>) -> Self {
+        Self {
+            aggregate_type: aggregate_type.to_string(),
+            store,
+            appliers: HashMap::new(),
+            config: EventSourcingConfig::default(),
+        }
+    }
+
+    /// Use the snapshot settings of `config`.
+    pub fn with_config(mut self, config: EventSourcingConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Register the apply function for an event type.
+    pub fn on<F>(mut self, event_type: &str, apply: F) -> Self
+    where
+        F: Fn(&mut JsonValue, &Event) + Send + Sync + 'static,
+    {
+        self.register(event_type, apply);
+        self
+    }
+
+    /// Register the apply function for an event type.
+    pub fn register<F>(&mut self, event_type: &str, apply: F)
+    where
+        F: Fn(&mut JsonValue, &Event) + Send + Sync + 'static,
+    {
+        self.appliers
+            .insert(event_type.to_string(), Arc::new(apply));
+    }
+
+    /// Get the aggregate type handled by this repository.
+    pub fn aggregate_type(&self) -> &str {
+        &self.aggregate_type
+    }
+
+    /// Load an aggregate from its latest snapshot and the events after it.
+    pub fn load(&self, aggregate_id: &str) -> Result<AggregateState, EventSourcingError> {
+        let mut aggregate = match self.store.get_snapshot(aggregate_id)? {
+            Some(snapshot) => AggregateState::from_snapshot(&snapshot),
+            None => AggregateState::new(aggregate_id, &self.aggregate_type),
+        };
+        aggregate.aggregate_type = self.aggregate_type.clone();
+
+        let events = match self.store.get_events(aggregate_id, Some(aggregate.version)) {
+            Ok(events) => events,
+            Err(EventSourcingError::AggregateNotFound(_)) if aggregate.version > 0 => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        for event in &events {
+            self.fold(&mut aggregate, event);
+        }
+        Ok(aggregate)
+    }
+
+    /// Load an aggregate, or start a new one if it has no events yet.
+    pub fn load_or_new(&self, aggregate_id: &str) -> Result<AggregateState, EventSourcingError> {
+        match self.load(aggregate_id) {
+            Err(EventSourcingError::AggregateNotFound(_)) => {
+                Ok(AggregateState::new(aggregate_id, &self.aggregate_type))
+            }
+            result => result,
+        }
+    }
+
+    /// Record a new event on an aggregate, applying it to the state.
+    ///
+    /// The event stays uncommitted until [`save`](Self::save).
+    pub fn record(&self, aggregate: &mut AggregateState, event_type: &str, data: JsonValue) {
+        let event = Event::new(&aggregate.id, event_type, data, aggregate.version + 1);
+        self.fold(aggregate, &event);
+        aggregate.events.push(event);
+    }
+
+    /// Append an aggregate's uncommitted events, snapshotting if due.
+    ///
+    /// Fails with a concurrency conflict if the aggregate was changed in
+    /// the store since it was loaded.
+    pub fn save(&self, aggregate: &mut AggregateState) -> Result<(), EventSourcingError> {
+        if !aggregate.has_uncommitted_events() {
+            return Ok(());
+        }
+        let expected_version = aggregate.version - aggregate.uncommitted_count() as u64;
+        self.store
+            .append_events(&aggregate.id, &aggregate.events, expected_version)?;
+        aggregate.clear_uncommitted_events();
+
+        let interval = self.config.snapshot_interval as u64;
+        if self.config.enable_snapshots
+            && interval > 0
+            && aggregate.version / interval > expected_version / interval
+        {
+            self.store
+                .save_snapshot(&Snapshot::from_aggregate(aggregate))?;
+        }
+        Ok(())
+    }
+
+    fn fold(&self, aggregate: &mut AggregateState, event: &Event) {
+        if let Some(apply) = self.appliers.get(&event.event_type) {
+            apply(&mut aggregate.state, event);
+        }
+        aggregate.version = event.version;
+    }
+}
+
+// ============================================================================
+// Subscriptions
+// ============================================================================
+
+/// Default number of events fetched from the store per read.
+const DEFAULT_SUBSCRIPTION_BATCH: usize = 256;
+
+/// An ordered subscription to the all-events stream.
+///
+/// The subscription first catches up on stored events, then waits for new
+/// ones as they are appended. Call [`checkpoint`](Self::checkpoint) after
+/// processing events so a restarted subscriber resumes where it left off.
+pub struct EventSubscription {
+    store: Arc<dyn EventStore>,
+    subscriber: String,
+    position: u64,
+    pending: VecDeque<Event>,
+    head: watch::Receiver<u64>,
+    batch_size: usize,
+}
+
+impl EventSubscription {
+    /// Subscribe, resuming after the subscriber's saved checkpoint or from
+    /// the start of the stream if it has none.
+    pub fn new(store: Arc<dyn EventStore>, subscriber: &str) -> Result<Self, EventSourcingError> {
+        let position = store.get_checkpoint(subscriber)?.unwrap_or(0);
+        Ok(Self::from_position(store, subscriber, position))
+    }
+
+    /// Subscribe starting after `position`, ignoring any saved checkpoint.
+    pub fn from_position(store: Arc<dyn EventStore>, subscriber: &str, position: u64) -> Self {
+        let head = store.watch_head();
+        Self {
+            store,
+            subscriber: subscriber.to_string(),
+            position,
+            pending: VecDeque::new(),
+            head,
+            batch_size: DEFAULT_SUBSCRIPTION_BATCH,
+        }
+    }
+
+    /// Set how many events are fetched from the store per read.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Get the subscriber name.
+    pub fn subscriber(&self) -> &str {
+        &self.subscriber
+    }
+
+    /// Get the position of the last event returned.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Check whether every appended event has been returned.
+    pub fn is_caught_up(&self) -> bool {
+        self.pending.is_empty() && self.position >= *self.head.borrow()
+    }
+
+    /// Return the next stored event without waiting, or `None` when caught up.
+    pub fn try_next(&mut self) -> Result<Option<Event>, EventSourcingError> {
+        if self.pending.is_empty() {
+            // Mark the head as seen before reading so an append racing with
+            // the read still wakes `next`.
+            let head = *self.head.borrow_and_update();
+            if self.position >= head {
+                return Ok(None);
+            }
+            self.pending = self.store.read_all(self.position, self.batch_size)?.into();
+        }
+        let event = self.pending.pop_front();
+        if let Some(event) = &event {
+            self.position = event.position;
+        }
+        Ok(event)
+    }
+
+    /// Return the next event, waiting for one to be appended if caught up.
+    pub async fn next(&mut self) -> Result<Event, EventSourcingError> {
+        loop {
+            if let Some(event) = self.try_next()? {
+                return Ok(event);
+            }
+            self.head
+                .changed()
+                .await
+                .map_err(|_| EventSourcingError::StoreError("Event store closed".to_string()))?;
+        }
+    }
+
+    /// Persist the current position as the subscriber's checkpoint.
+    pub fn checkpoint(&self) -> Result<(), EventSourcingError> {
+        self.store.save_checkpoint(&self.subscriber, self.position)
+    }
 }
 
 // ============================================================================
@@ -557,6 +1349,22 @@ pub enum EventSourcingError {
     SerializationError(String),
     /// Error during snapshot creation or retrieval.
     SnapshotError(String),
+    /// Event data could not be encrypted or decrypted.
+    EncryptionError(String),
+    /// Aggregates of a transaction were not at their expected versions;
+    /// nothing was written.
+    Conflicts(Vec<AggregateConflict>),
+    /// A transaction on a store without transactions failed part way.
+    PartialAppend {
+        transaction_id: String,
+        /// Aggregates whose events were written
+        committed: Vec<String>,
+        /// Aggregate whose append failed
+        failed: String,
+        /// Aggregates not attempted
+        pending: Vec<String>,
+        reason: Box<EventSourcingError>,
+    },
 }
 
 impl std::fmt::Display for EventSourcingError {
@@ -579,12 +1387,43 @@ impl std::fmt::Display for EventSourcingError {
                 write!(f, "Event serialization error: {msg}")
             }
             EventSourcingError::SnapshotError(msg) => write!(f, "Snapshot error: {msg}"),
+            EventSourcingError::EncryptionError(msg) => write!(f, "Event encryption error: {msg}"),
+            EventSourcingError::Conflicts(conflicts) => {
+                write!(f, "Concurrency conflicts on ")?;
+                for (i, c) in conflicts.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(
+                        f,
+                        "aggregate '{}' (expected version {}, actual {})",
+                        c.aggregate_id, c.expected, c.actual
+                    )?;
+                }
+                Ok(())
+            }
+            EventSourcingError::PartialAppend {
+                transaction_id,
+                committed,
+                failed,
+                reason,
+                ..
+            } => write!(
+                f,
+                "Transaction '{transaction_id}' failed on aggregate '{failed}' after committing {committed:?}: {reason}"
+            ),
         }
     }
 }
 
 impl std::error::Error for EventSourcingError {}
 
+impl From<SecretsError> for EventSourcingError {
+    fn from(e: SecretsError) -> Self {
+        EventSourcingError::EncryptionError(e.to_string())
+    }
+}
+
 // ============================================================================
 // Statistics & Metrics
 // ============================================================================
@@ -937,6 +1776,467 @@ mod tests {
         store.clear();
         assert_eq!(store.total_events(), 0);
         assert_eq!(store.total_aggregates(), 0);
+        assert!(store.read_all(0, 10).unwrap().is_empty());
+        assert_eq!(*store.watch_head().borrow(), 0);
+    }
+
+    // ---------- AggregateRepository Tests ----------
+
+    fn account_repository(store: Arc<dyn EventStore>) -> AggregateRepository {
+        AggregateRepository::new("Account", store)
+            .with_config(EventSourcingConfig::memory().with_snapshot_interval(3))
+            .on("Deposited", |state, event| {
+                let balance = state["balance"].as_i64().unwrap_or(0);
+                state["balance"] = (balance + event.data["amount"].as_i64().unwrap()).into();
+            })
+            .on("Withdrawn", |state, event| {
+                let balance = state["balance"].as_i64().unwrap_or(0);
+                state["balance"] = (balance - event.data["amount"].as_i64().unwrap()).into();
+            })
+    }
+
+    #[test]
+    fn test_repository_applies_registered_functions() {
+        let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+        let repo = account_repository(store.clone());
+
+        let mut account = repo.load_or_new("acc-1").unwrap();
+        repo.record(
+            &mut account,
+            "Deposited",
+            serde_json::json!({"amount": 100}),
+        );
+        repo.record(&mut account, "Withdrawn", serde_json::json!({"amount": 30}));
+        repo.record(&mut account, "Audited", serde_json::json!({"amount": 999}));
+        assert_eq!(account.state["balance"], 70);
+        assert_eq!(account.uncommitted_count(), 3);
+        repo.save(&mut account).unwrap();
+        assert!(!account.has_uncommitted_events());
+
+        let loaded = repo.load("acc-1").unwrap();
+        assert_eq!(loaded.aggregate_type, "Account");
+        assert_eq!(loaded.version, 3);
+        assert_eq!(loaded.state["balance"], 70);
+        assert!(matches!(
+            repo.load("missing"),
+            Err(EventSourcingError::AggregateNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_repository_loads_from_snapshot() {
+        let memory = Arc::new(InMemoryEventStore::new());
+        let store: Arc<dyn EventStore> = memory.clone();
+        let repo = account_repository(store.clone());
+
+        let mut account = repo.load_or_new("acc-1").unwrap();
+        repo.record(&mut account, "Deposited", serde_json::json!({"amount": 10}));
+        repo.record(&mut account, "Deposited", serde_json::json!({"amount": 10}));
+        repo.save(&mut account).unwrap();
+        assert_eq!(memory.total_snapshots(), 0);
+
+        // Crossing the interval in one save still snapshots.
+        repo.record(&mut account, "Deposited", serde_json::json!({"amount": 10}));
+        repo.record(&mut account, "Deposited", serde_json::json!({"amount": 10}));
+        repo.save(&mut account).unwrap();
+        let snapshot = store.get_snapshot("acc-1").unwrap().unwrap();
+        assert_eq!(snapshot.version, 4);
+        assert_eq!(snapshot.state["balance"], 40);
+
+        // Only events newer than the snapshot are replayed.
+        store
+            .save_snapshot(&Snapshot {
+                state: serde_json::json!({"balance": 1000}),
+                ..snapshot
+            })
+            .unwrap();
+        repo.record(&mut account, "Withdrawn", serde_json::json!({"amount": 1}));
+        repo.save(&mut account).unwrap();
+        let loaded = repo.load("acc-1").unwrap();
+        assert_eq!(loaded.version, 5);
+        assert_eq!(loaded.state["balance"], 999);
+    }
+
+    #[test]
+    fn test_repository_detects_concurrent_changes() {
+        let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+        let repo = account_repository(store);
+
+        let mut first = repo.load_or_new("acc-1").unwrap();
+        let mut second = repo.load_or_new("acc-1").unwrap();
+        repo.record(&mut first, "Deposited", serde_json::json!({"amount": 1}));
+        repo.record(&mut second, "Deposited", serde_json::json!({"amount": 2}));
+        repo.save(&mut first).unwrap();
+        assert!(matches!(
+            repo.save(&mut second),
+            Err(EventSourcingError::ConcurrencyConflict { .. })
+        ));
+    }
+
+    // ---------- All-Events Stream Tests ----------
+
+    #[test]
+    fn test_store_read_all_in_global_order() {
+        let store = InMemoryEventStore::new();
+        store
+            .append_events("a", &[Event::new("a", "E1", serde_json::json!({}), 1)], 0)
+            .unwrap();
+        store
+            .append_events("b", &[Event::new("b", "E2", serde_json::json!({}), 1)], 0)
+            .unwrap();
+        store
+            .append_events("a", &[Event::new("a", "E3", serde_json::json!({}), 2)], 1)
+            .unwrap();
+
+        let all = store.read_all(0, 10).unwrap();
+        let types: Vec<_> = all.iter().map(|e| e.event_type.as_str()).collect();
+        assert_eq!(types, ["E1", "E2", "E3"]);
+        assert_eq!(all[2].position, 3);
+        assert_eq!(store.get_events("a", None).unwrap()[1].position, 3);
+        assert_eq!(store.read_all(1, 1).unwrap()[0].event_type, "E2");
+        assert!(store.read_all(3, 10).unwrap().is_empty());
+        assert_eq!(*store.watch_head().borrow(), 3);
+    }
+
+    #[test]
+    fn test_subscription_resumes_from_checkpoint() {
+        let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+        let events: Vec<_> = (1..=3)
+            .map(|v| Event::new("a", "E", serde_json::json!({}), v))
+            .collect();
+        store.append_events("a", &events, 0).unwrap();
+
+        let mut sub = EventSubscription::new(store.clone(), "projection")
+            .unwrap()
+            .with_batch_size(2);
+        assert_eq!(sub.try_next().unwrap().unwrap().position, 1);
+        assert_eq!(sub.try_next().unwrap().unwrap().position, 2);
+        sub.checkpoint().unwrap();
+        assert_eq!(store.get_checkpoint("projection").unwrap(), Some(2));
+
+        let mut resumed = EventSubscription::new(store.clone(), "projection").unwrap();
+        assert_eq!(resumed.try_next().unwrap().unwrap().position, 3);
+        assert!(resumed.try_next().unwrap().is_none());
+        assert!(resumed.is_caught_up());
+        assert_eq!(store.get_checkpoint("other").unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_subscription_receives_live_events() {
+        let store = Arc::new(InMemoryEventStore::new());
+        let mut sub = EventSubscription::new(store.clone(), "integration").unwrap();
+        assert!(sub.try_next().unwrap().is_none());
+
+        let writer = store.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            let events = vec![Event::new("a", "Created", serde_json::json!({}), 1)];
+            writer.append_events("a", &events, 0).unwrap();
+        });
+
+        let event = tokio::time::timeout(std::time::Duration::from_secs(1), sub.next())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.event_type, "Created");
+        assert_eq!(sub.position(), 1);
+    }
+
+    // ---------- Transaction Tests ----------
+
+    fn opened(aggregate_id: &str, version: u64) -> Vec<Event> {
+        vec![Event::new(
+            aggregate_id,
+            "Opened",
+            serde_json::json!({}),
+            version,
+        )]
+    }
+
+    #[test]
+    fn test_transaction_appends_all_streams() {
+        let store = InMemoryEventStore::new();
+        assert!(store.supports_transactions());
+        let tx = EventTransaction::new()
+            .append("a", 0, opened("a", 1))
+            .append("b", 0, opened("b", 1));
+        store.append_transaction(&tx).unwrap();
+
+        let a = store.get_events("a", None).unwrap();
+        let b = store.get_events("b", None).unwrap();
+        assert_eq!(a[0].metadata.get(TRANSACTION_ID_KEY), Some(&tx.id));
+        assert_eq!(b[0].metadata.get(TRANSACTION_ID_KEY), Some(&tx.id));
+        assert_eq!((a[0].position, b[0].position), (1, 2));
+    }
+
+    #[test]
+    fn test_transaction_conflict_writes_nothing() {
+        let store = InMemoryEventStore::new();
+        store.append_events("a", &opened("a", 1), 0).unwrap();
+        store.append_events("b", &opened("b", 1), 0).unwrap();
+
+        let tx = EventTransaction::new()
+            .append("a", 0, opened("a", 1))
+            .append("b", 1, opened("b", 2))
+            .append("c", 3, opened("c", 4));
+        let Err(EventSourcingError::Conflicts(conflicts)) = store.append_transaction(&tx) else {
+            panic!("expected conflicts");
+        };
+        assert_eq!(
+            conflicts,
+            vec![
+                AggregateConflict {
+                    aggregate_id: "a".to_string(),
+                    expected: 0,
+                    actual: 1,
+                },
+                AggregateConflict {
+                    aggregate_id: "c".to_string(),
+                    expected: 3,
+                    actual: 0,
+                },
+            ]
+        );
+        assert_eq!(store.get_events("b", None).unwrap().len(), 1);
+        assert_eq!(store.read_all(0, 10).unwrap().len(), 2);
+    }
+
+    /// A store without transactions whose appends to one aggregate fail.
+    struct FlakyStore {
+        inner: InMemoryEventStore,
+        failing: &'static str,
+    }
+
+    impl EventStore for FlakyStore {
+        fn append_events(
+            &self,
+            aggregate_id: &str,
+            events: &[Event],
+            expected_version: u64,
+        ) -> Result<(), EventSourcingError> {
+            if aggregate_id == self.failing {
+                return Err(EventSourcingError::StoreError("unavailable".to_string()));
+            }
+            self.inner
+                .append_events(aggregate_id, events, expected_version)
+        }
+
+        fn get_events(
+            &self,
+            aggregate_id: &str,
+            from_version: Option<u64>,
+        ) -> Result<Vec<Event>, EventSourcingError> {
+            self.inner.get_events(aggregate_id, from_version)
+        }
+
+        fn get_snapshot(&self, aggregate_id: &str) -> Result<Option<Snapshot>, EventSourcingError> {
+            self.inner.get_snapshot(aggregate_id)
+        }
+
+        fn save_snapshot(&self, snapshot: &Snapshot) -> Result<(), EventSourcingError> {
+            self.inner.save_snapshot(snapshot)
+        }
+
+        fn read_all(&self, after: u64, limit: usize) -> Result<Vec<Event>, EventSourcingError> {
+            self.inner.read_all(after, limit)
+        }
+
+        fn watch_head(&self) -> watch::Receiver<u64> {
+            self.inner.watch_head()
+        }
+
+        fn get_checkpoint(&self, subscriber: &str) -> Result<Option<u64>, EventSourcingError> {
+            self.inner.get_checkpoint(subscriber)
+        }
+
+        fn save_checkpoint(
+            &self,
+            subscriber: &str,
+            position: u64,
+        ) -> Result<(), EventSourcingError> {
+            self.inner.save_checkpoint(subscriber, position)
+        }
+    }
+
+    #[test]
+    fn test_transaction_without_store_support_reports_progress() {
+        let store = FlakyStore {
+            inner: InMemoryEventStore::new(),
+            failing: "b",
+        };
+        assert!(!store.supports_transactions());
+        let tx = EventTransaction::new()
+            .append("a", 0, opened("a", 1))
+            .append("b", 0, opened("b", 1))
+            .append("c", 0, opened("c", 1));
+        match store.append_transaction(&tx) {
+            Err(EventSourcingError::PartialAppend {
+                transaction_id,
+                committed,
+                failed,
+                pending,
+                reason,
+            }) => {
+                assert_eq!(transaction_id, tx.id);
+                assert_eq!(committed, vec!["a".to_string()]);
+                assert_eq!(failed, "b");
+                assert_eq!(pending, vec!["c".to_string()]);
+                assert!(matches!(*reason, EventSourcingError::StoreError(_)));
+            }
+            other => panic!("expected partial append, got {other:?}"),
+        }
+        assert_eq!(store.get_events("a", None).unwrap().len(), 1);
+        assert!(store.get_events("c", None).is_err());
+    }
+
+    // ---------- Encryption Tests ----------
+
+    fn encrypted_store(
+        patterns: Vec<String>,
+    ) -> (
+        Arc<InMemoryEventStore>,
+        Arc<EventEncryption>,
+        EncryptedEventStore,
+    ) {
+        let inner = Arc::new(InMemoryEventStore::new());
+        let secrets = Arc::new(crate::middleware::secrets::InMemorySecretsProvider::new());
+        let encryption = Arc::new(EventEncryption::new(secrets).with_aggregates(patterns));
+        let store = EncryptedEventStore::new(inner.clone(), encryption.clone());
+        (inner, encryption, store)
+    }
+
+    #[test]
+    fn test_encrypted_store_round_trip() {
+        let (inner, encryption, store) = encrypted_store(vec!["user-*".to_string()]);
+        let data = serde_json::json!({"email": "ada@example.com"});
+        let events = vec![Event::new("user-1", "Registered", data.clone(), 1)];
+        store.append_events("user-1", &events, 0).unwrap();
+        let events = vec![Event::new("order-1", "Placed", serde_json::json!({}), 1)];
+        store.append_events("order-1", &events, 0).unwrap();
+
+        let raw = inner.get_events("user-1", None).unwrap();
+        assert!(raw[0].data.is_string());
+        assert!(!raw[0].data.as_str().unwrap().contains("ada"));
+        let key_id = encryption.key_id("user-1").unwrap().unwrap();
+        assert!(key_id.starts_with("events:user-1:"));
+        assert_eq!(
+            raw[0].get_metadata(ENCRYPTION_KEY_ID),
+            Some(key_id.as_str())
+        );
+        assert!(!inner.get_events("order-1", None).unwrap()[0].has_metadata(ENCRYPTION_KEY_ID));
+
+        assert_eq!(store.get_events("user-1", None).unwrap()[0].data, data);
+        assert_eq!(store.read_all(0, 10).unwrap()[0].data, data);
+    }
+
+    #[test]
+    fn test_shredding_forgets_event_data() {
+        let (_, encryption, store) = encrypted_store(Vec::new());
+        let events = vec![
+            Event::new(
+                "user-1",
+                "Registered",
+                serde_json::json!({"name": "Ada"}),
+                1,
+            ),
+            Event::new(
+                "user-1",
+                "Renamed",
+                serde_json::json!({"name": "Lovelace"}),
+                2,
+            ),
+        ];
+        store.append_events("user-1", &events, 0).unwrap();
+        let events = vec![Event::new(
+            "user-2",
+            "Registered",
+            serde_json::json!({"name": "Bob"}),
+            1,
+        )];
+        store.append_events("user-2", &events, 0).unwrap();
+        store
+            .save_snapshot(&Snapshot {
+                aggregate_id: "user-1".to_string(),
+                version: 2,
+                state: serde_json::json!({"name": "Lovelace"}),
+                timestamp: 0,
+            })
+            .unwrap();
+        assert_eq!(
+            store.get_snapshot("user-1").unwrap().unwrap().state["name"],
+            "Lovelace"
+        );
+
+        assert!(encryption.shred("user-1").unwrap());
+        assert!(!encryption.shred("user-1").unwrap());
+        let events = store.get_events("user-1", None).unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(events.iter().all(|e| e.data.is_null()));
+        assert_eq!(events[1].get_metadata(SHREDDED_KEY), Some("true"));
+        assert!(store.get_snapshot("user-1").unwrap().is_none());
+        assert_eq!(
+            store.get_events("user-2", None).unwrap()[0].data["name"],
+            "Bob"
+        );
+    }
+
+    #[test]
+    fn test_append_after_shredding_uses_new_key() {
+        let (_, encryption, store) = encrypted_store(Vec::new());
+        let events = vec![Event::new(
+            "user-1",
+            "Registered",
+            serde_json::json!({"name": "Ada"}),
+            1,
+        )];
+        store.append_events("user-1", &events, 0).unwrap();
+        let first_key = encryption.key_id("user-1").unwrap().unwrap();
+        assert!(encryption.shred("user-1").unwrap());
+        assert_eq!(encryption.key_id("user-1").unwrap(), None);
+
+        let events = vec![Event::new(
+            "user-1",
+            "Registered",
+            serde_json::json!({"name": "Bob"}),
+            2,
+        )];
+        store.append_events("user-1", &events, 1).unwrap();
+        let second_key = encryption.key_id("user-1").unwrap().unwrap();
+        assert_ne!(first_key, second_key);
+
+        let events = store.get_events("user-1", None).unwrap();
+        assert!(events[0].data.is_null());
+        assert_eq!(events[0].get_metadata(SHREDDED_KEY), Some("true"));
+        assert_eq!(events[1].data["name"], "Bob");
+        assert!(!events[1].has_metadata(SHREDDED_KEY));
+
+        store
+            .save_snapshot(&Snapshot {
+                aggregate_id: "user-1".to_string(),
+                version: 2,
+                state: serde_json::json!({"name": "Bob"}),
+                timestamp: 0,
+            })
+            .unwrap();
+        assert_eq!(
+            store.get_snapshot("user-1").unwrap().unwrap().state["name"],
+            "Bob"
+        );
+    }
+
+    #[test]
+    fn test_tampered_ciphertext_is_rejected() {
+        let (_, encryption, _) = encrypted_store(Vec::new());
+        let event = Event::new("user-1", "Registered", serde_json::json!({"a": 1}), 1);
+        let mut sealed = encryption.encrypt(&event).unwrap();
+        assert_eq!(encryption.decrypt(sealed.clone()).unwrap().data["a"], 1);
+
+        // Ciphertext moved to another event fails authentication.
+        sealed.id = "evt-user-1-2".to_string();
+        assert!(matches!(
+            encryption.decrypt(sealed),
+            Err(EventSourcingError::EncryptionError(_))
+        ));
     }
 
     // ---------- Error Display Tests ----------
@@ -0,0 +1,373 @@
+//! Fault injection for Cello Framework.
+//!
+//! Injects failures into a sample of requests, to test how clients cope with
+//! a slow or failing service (retries, timeouts, circuit breakers):
+//! - Latency: the request is delayed, then handled normally
+//! - Error: the request is answered with an error status without running
+//!   the handler
+//! - Abort: the connection is dropped without a response
+//!
+//! Each rule applies to a percentage of the requests matching its method,
+//! path prefix and header filters. Nothing is injected unless rules are
+//! added explicitly.
+//!
+//! # Example
+//! ```python
+//! app.enable_fault_injection()
+//! app.add_fault("latency", delay=0.5, percentage=20, paths=["/api/"])
+//! app.add_fault("error", status=503, percentage=5, header="X-Chaos")
+//! ```
+
+use parking_lot::RwLock;
+use rand::Rng;
+use serde::Serialize;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::{AsyncMiddleware, MiddlewareAction, MiddlewareResult};
+use crate::request::Request;
+use crate::response::Response;
+
+/// Header added to injected error responses.
+pub const FAULT_HEADER: &str = "X-Fault-Injected";
+
+// ============================================================================
+// Rules
+// ============================================================================
+
+/// A failure to inject.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Fault {
+    /// Delay the request, then handle it normally
+    Latency(Duration),
+    /// Answer with this status without running the handler
+    Error(u16),
+    /// Drop the connection without a response
+    Abort,
+}
+
+impl Fault {
+    /// Fault name, as used in stats.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Fault::Latency(_) => "latency",
+            Fault::Error(_) => "error",
+            Fault::Abort => "abort",
+        }
+    }
+}
+
+/// A fault and the requests it applies to.
+#[derive(Clone, Debug)]
+pub struct FaultRule {
+    /// The failure injected
+    pub fault: Fault,
+    /// Percentage of matching requests affected, from 0 to 100
+    pub percentage: f64,
+    /// Affect only these methods (all if empty)
+    pub methods: Vec<String>,
+    /// Affect only requests under these path prefixes (all if empty)
+    pub include_paths: Vec<String>,
+    /// Never affect requests under these path prefixes
+    pub exclude_paths: Vec<String>,
+    /// Affect only requests carrying this header, with this value if set
+    pub header: Option<(String, Option<String>)>,
+}
+
+impl FaultRule {
+    /// Inject `fault` into every request.
+    pub fn new(fault: Fault) -> Self {
+        Self {
+            fault,
+            percentage: 100.0,
+            methods: Vec::new(),
+            include_paths: Vec::new(),
+            exclude_paths: Vec::new(),
+            header: None,
+        }
+    }
+
+    /// Set the percentage of matching requests affected, clamped to 0..=100.
+    pub fn with_percentage(mut self, percentage: f64) -> Self {
+        self.percentage = percentage.clamp(0.0, 100.0);
+        self
+    }
+
+    /// Affect only these methods.
+    pub fn with_methods(mut self, methods: Vec<&str>) -> Self {
+        self.methods = methods.iter().map(|m| m.to_ascii_uppercase()).collect();
+        self
+    }
+
+    /// Affect only requests under this path prefix.
+    pub fn include_path(mut self, path: &str) -> Self {
+        self.include_paths.push(path.to_string());
+        self
+    }
+
+    /// Never affect requests under this path prefix.
+    pub fn exclude_path(mut self, path: &str) -> Self {
+        self.exclude_paths.push(path.to_string());
+        self
+    }
+
+    /// Affect only requests carrying `name`, with `value` if given.
+    pub fn with_header(mut self, name: &str, value: Option<&str>) -> Self {
+        self.header = Some((name.to_string(), value.map(str::to_string)));
+        self
+    }
+
+    /// Whether `request` is eligible for this fault, before sampling.
+    fn matches(&self, request: &Request) -> bool {
+        let path = request.path.as_str();
+        let header = match &self.header {
+            Some((name, value)) => match request.headers.get(name) {
+                Some(actual) => value.as_deref().is_none_or(|v| v == actual),
+                None => false,
+            },
+            None => true,
+        };
+        header
+            && (self.methods.is_empty() || self.methods.contains(&request.method))
+            && !self.exclude_paths.iter().any(|p| path.starts_with(p))
+            && (self.include_paths.is_empty()
+                || self.include_paths.iter().any(|p| path.starts_with(p)))
+    }
+
+    fn sampled(&self) -> bool {
+        match self.percentage {
+            p if p >= 100.0 => true,
+            p if p <= 0.0 => false,
+            p => rand::thread_rng().gen_bool(p / 100.0),
+        }
+    }
+}
+
+// ============================================================================
+// Statistics
+// ============================================================================
+
+/// Counters of injected faults.
+#[derive(Debug, Default)]
+pub struct FaultStats {
+    /// Requests delayed
+    pub delayed: AtomicU64,
+    /// Requests answered with an error
+    pub errors: AtomicU64,
+    /// Connections dropped
+    pub aborted: AtomicU64,
+}
+
+impl FaultStats {
+    fn record(&self, fault: &Fault) {
+        let counter = match fault {
+            Fault::Latency(_) => &self.delayed,
+            Fault::Error(_) => &self.errors,
+            Fault::Abort => &self.aborted,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Current values of the counters.
+    pub fn snapshot(&self) -> FaultSnapshot {
+        FaultSnapshot {
+            delayed: self.delayed.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+            aborted: self.aborted.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Point-in-time copy of [`FaultStats`].
+#[derive(Debug, Clone, Serialize)]
+pub struct FaultSnapshot {
+    pub delayed: u64,
+    pub errors: u64,
+    pub aborted: u64,
+}
+
+// ============================================================================
+// Middleware
+// ============================================================================
+
+/// Middleware injecting faults into sampled requests.
+///
+/// Rules are applied in the order they were added: every sampled latency
+/// rule delays the request, and the first sampled error or abort rule ends
+/// it. Rules can be added and cleared while the server is running, through
+/// any clone of the middleware.
+#[derive(Clone, Default)]
+pub struct FaultInjectionMiddleware {
+    rules: Arc<RwLock<Vec<FaultRule>>>,
+    stats: Arc<FaultStats>,
+}
+
+impl FaultInjectionMiddleware {
+    /// Create a middleware with no rules; it injects nothing until rules
+    /// are added.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a rule.
+    pub fn add_rule(&self, rule: FaultRule) {
+        self.rules.write().push(rule);
+    }
+
+    /// Remove all rules.
+    pub fn clear(&self) {
+        self.rules.write().clear();
+    }
+
+    /// The current rules.
+    pub fn rules(&self) -> Vec<FaultRule> {
+        self.rules.read().clone()
+    }
+
+    /// Counters, shared with the middleware.
+    pub fn stats(&self) -> Arc<FaultStats> {
+        self.stats.clone()
+    }
+
+    /// The faults sampled for `request`, in order, up to the first one that
+    /// ends it.
+    fn sample(&self, request: &Request) -> Vec<Fault> {
+        let mut faults = Vec::new();
+        for rule in self.rules.read().iter() {
+            if rule.matches(request) && rule.sampled() {
+                faults.push(rule.fault.clone());
+                if !matches!(rule.fault, Fault::Latency(_)) {
+                    break;
+                }
+            }
+        }
+        faults
+    }
+}
+
+impl AsyncMiddleware for FaultInjectionMiddleware {
+    fn before_async<'a>(
+        &'a self,
+        request: &'a mut Request,
+    ) -> Pin<Box<dyn Future<Output = MiddlewareResult> + Send + 'a>> {
+        Box::pin(async move {
+            for fault in self.sample(request) {
+                self.stats.record(&fault);
+                match fault {
+                    Fault::Latency(delay) => tokio::time::sleep(delay).await,
+                    Fault::Error(status) => {
+                        let mut response = Response::error(status, "Injected fault");
+                        response.set_header(FAULT_HEADER, "error");
+                        return Ok(MiddlewareAction::Stop(response));
+                    }
+                    Fault::Abort => return Ok(MiddlewareAction::Stop(Response::aborted())),
+                }
+            }
+            Ok(MiddlewareAction::Continue)
+        })
+    }
+
+    fn priority(&self) -> i32 {
+        -110 // Run early, so delays and failures hit the whole request
+    }
+
+    fn name(&self) -> &str {
+        "fault_injection"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, path: &str) -> Request {
+        let mut request = Request::default();
+        request.method = method.to_string();
+        request.path = path.to_string();
+        request
+    }
+
+    #[tokio::test]
+    async fn test_error_and_abort_faults() {
+        let faults = FaultInjectionMiddleware::new();
+        let mut req = request("GET", "/api/users");
+        assert!(matches!(
+            faults.before_async(&mut req).await,
+            Ok(MiddlewareAction::Continue)
+        ));
+
+        faults.add_rule(FaultRule::new(Fault::Error(503)).include_path("/api/"));
+        match faults.before_async(&mut req).await {
+            Ok(MiddlewareAction::Stop(response)) => {
+                assert_eq!(response.status, 503);
+                assert_eq!(
+                    response.headers.get(FAULT_HEADER).map(String::as_str),
+                    Some("error")
+                );
+            }
+            _ => panic!("expected an injected error"),
+        }
+
+        let mut other = request("GET", "/health");
+        assert!(matches!(
+            faults.before_async(&mut other).await,
+            Ok(MiddlewareAction::Continue)
+        ));
+
+        faults.clear();
+        faults.add_rule(FaultRule::new(Fault::Abort));
+        match faults.before_async(&mut other).await {
+            Ok(MiddlewareAction::Stop(response)) => assert!(response.is_aborted()),
+            _ => panic!("expected an aborted response"),
+        }
+
+        let stats = faults.stats().snapshot();
+        assert_eq!((stats.errors, stats.aborted, stats.delayed), (1, 1, 0));
+    }
+
+    #[tokio::test]
+    async fn test_latency_then_error() {
+        let faults = FaultInjectionMiddleware::new();
+        faults.add_rule(FaultRule::new(Fault::Latency(Duration::from_millis(30))));
+        faults.add_rule(FaultRule::new(Fault::Error(500)).with_methods(vec!["post"]));
+
+        let started = std::time::Instant::now();
+        let mut req = request("GET", "/");
+        assert!(matches!(
+            faults.before_async(&mut req).await,
+            Ok(MiddlewareAction::Continue)
+        ));
+        assert!(started.elapsed() >= Duration::from_millis(30));
+
+        let mut req = request("POST", "/");
+        assert!(matches!(
+            faults.before_async(&mut req).await,
+            Ok(MiddlewareAction::Stop(_))
+        ));
+        let stats = faults.stats().snapshot();
+        assert_eq!((stats.delayed, stats.errors), (2, 1));
+    }
+
+    #[test]
+    fn test_rule_filters() {
+        let rule = FaultRule::new(Fault::Abort)
+            .with_header("X-Chaos", Some("on"))
+            .exclude_path("/health");
+        let mut req = request("GET", "/orders");
+        assert!(!rule.matches(&req));
+        req.headers.insert("x-chaos", "off");
+        assert!(!rule.matches(&req));
+        req.headers.insert("x-chaos", "on");
+        assert!(rule.matches(&req));
+        req.path = "/health".to_string();
+        assert!(!rule.matches(&req));
+
+        assert!(!FaultRule::new(Fault::Abort).with_percentage(0.0).sampled());
+        assert!(FaultRule::new(Fault::Abort)
+            .with_percentage(150.0)
+            .sampled());
+    }
+}
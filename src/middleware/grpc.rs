@@ -6,6 +6,12 @@
 //! - gRPC reflection for service introspection
 //! - gRPC-Web support for browser clients
 //! - Connection keepalive and concurrency limits
+//! - Message encoding/decoding via the shared protobuf `DescriptorPool`
+//! - Services loaded from compiled `FileDescriptorSet`s, with payload validation
+//! - Per-method handlers (Rust closures or sync/async Python callables)
+//!   with deadlines and metadata propagation; a caller's `grpc-timeout`
+//!   is enforced and its remaining time exposed to Python handlers
+//! - HTTP/2 client with connection pooling, deadlines, retries and metadata
 //!
 //! # Example
 //! ```python
@@ -27,9 +33,23 @@
 //! ```
 
 use parking_lot::RwLock;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyTuple};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper_util::client::legacy::{connect::HttpConnector, Client};
+use hyper_util::rt::TokioExecutor;
+
+use super::protobuf::{DescriptorPool, ServiceDescriptor, SharedDescriptorPool};
+use crate::event_loop::{call_with_deadline, CallError};
+use crate::json::{json_to_python, python_to_json};
 
 // ============================================================================
 // gRPC Configuration
@@ -116,6 +136,18 @@ pub enum GrpcMethodType {
     BidirectionalStreaming,
 }
 
+impl GrpcMethodType {
+    /// Method type from the client/server streaming flags of a method descriptor.
+    pub fn from_streaming(client_streaming: bool, server_streaming: bool) -> Self {
+        match (client_streaming, server_streaming) {
+            (false, false) => GrpcMethodType::Unary,
+            (true, false) => GrpcMethodType::ClientStreaming,
+            (false, true) => GrpcMethodType::ServerStreaming,
+            (true, true) => GrpcMethodType::BidirectionalStreaming,
+        }
+    }
+}
+
 impl std::fmt::Display for GrpcMethodType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -142,6 +174,12 @@ pub struct GrpcMethodDef {
     pub input_type: String,
     /// Fully qualified output message type (e.g., "helloworld.HelloReply")
     pub output_type: String,
+    /// Maximum time the handler may run before DEADLINE_EXCEEDED is returned
+    #[serde(default)]
+    pub deadline: Option<Duration>,
+    /// Handler invoked by `GrpcServer::handle_request`
+    #[serde(skip)]
+    pub handler: Option<GrpcHandler>,
 }
 
 impl GrpcMethodDef {
@@ -157,9 +195,23 @@ impl GrpcMethodDef {
             method_type,
             input_type: input_type.to_string(),
             output_type: output_type.to_string(),
+            deadline: None,
+            handler: None,
         }
     }
 
+    /// Attach the handler that serves this method.
+    pub fn with_handler(mut self, handler: GrpcHandler) -> Self {
+        self.handler = Some(handler);
+        self
+    }
+
+    /// Set the per-method deadline.
+    pub fn with_deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
     /// Create a unary method definition.
     pub fn unary(name: &str, input_type: &str, output_type: &str) -> Self {
         Self::new(name, GrpcMethodType::Unary, input_type, output_type)
@@ -196,6 +248,182 @@ impl GrpcMethodDef {
     }
 }
 
+// ============================================================================
+// gRPC Method Handler
+// ============================================================================
+
+/// Future returned by a gRPC method handler.
+pub type GrpcHandlerFuture = Pin<Box<dyn Future<Output = Result<GrpcResponse, GrpcError>> + Send>>;
+
+/// Handler attached to a `GrpcMethodDef`.
+///
+/// The handler receives the request (raw payload plus metadata) and returns
+/// the response, whose metadata is sent back to the caller as trailers. A
+/// call still running at the request's deadline is stopped and fails with
+/// `DEADLINE_EXCEEDED`.
+#[derive(Clone)]
+pub struct GrpcHandler(Arc<dyn Fn(GrpcRequest) -> GrpcHandlerFuture + Send + Sync>);
+
+impl GrpcHandler {
+    /// Create a handler from an async Rust closure.
+    pub fn new<F, Fut>(handler: F) -> Self
+    where
+        F: Fn(GrpcRequest) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<GrpcResponse, GrpcError>> + Send + 'static,
+    {
+        Self(Arc::new(move |request| {
+            let deadline = request.deadline;
+            let call = handler(request);
+            Box::pin(async move {
+                match deadline {
+                    Some(deadline) => tokio::time::timeout_at(deadline.into(), call)
+                        .await
+                        .unwrap_or(Err(GrpcError::DeadlineExceeded)),
+                    None => call.await,
+                }
+            })
+        }))
+    }
+
+    /// Create a handler from a synchronous Rust closure.
+    pub fn sync<F>(handler: F) -> Self
+    where
+        F: Fn(GrpcRequest) -> Result<GrpcResponse, GrpcError> + Send + Sync + 'static,
+    {
+        Self::new(move |request| std::future::ready(handler(request)))
+    }
+
+    /// Create a handler from a sync or async Python callable.
+    ///
+    /// The callable receives a `cello.grpc.GrpcRequest` whose `data` is the
+    /// request's decoded [`message`](GrpcRequest::message) (raw `bytes` if
+    /// the input type is not in the pool). It may return a dict, `bytes`, or a `cello.grpc.GrpcResponse`,
+    /// and may raise `cello.grpc.GrpcError` to fail with a specific status.
+    ///
+    /// At the deadline the call fails with `DEADLINE_EXCEEDED` straight
+    /// away: a coroutine is cancelled and a sync callable has `TimeoutError`
+    /// raised in its thread, finishing any blocking C call in the background
+    /// (see [`call_with_deadline`](crate::event_loop::call_with_deadline)).
+    pub fn python(handler: PyObject, descriptors: SharedDescriptorPool, output_type: &str) -> Self {
+        let handler = Arc::new(handler);
+        let output_type = output_type.to_string();
+        Self(Arc::new(move |request| {
+            let handler = handler.clone();
+            let descriptors = descriptors.clone();
+            let output_type = output_type.clone();
+            Box::pin(async move {
+                call_python_handler(handler, &descriptors, &output_type, request).await
+            })
+        }))
+    }
+
+    /// Invoke the handler.
+    pub fn call(&self, request: GrpcRequest) -> GrpcHandlerFuture {
+        (self.0)(request)
+    }
+}
+
+impl std::fmt::Debug for GrpcHandler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("GrpcHandler")
+    }
+}
+
+/// Drive a Python gRPC handler to completion.
+///
+/// Follows the same phases as HTTP handlers: call under the GIL, await any
+/// coroutine via Tokio with the GIL released, then convert the result. The
+/// call is stopped at the request's deadline.
+async fn call_python_handler(
+    handler: Arc<PyObject>,
+    descriptors: &SharedDescriptorPool,
+    output_type: &str,
+    request: GrpcRequest,
+) -> Result<GrpcResponse, GrpcError> {
+    // Phases 1 and 2: call the handler with the request object, then await
+    // any coroutine via Tokio with the GIL released
+    let timeout = request.time_remaining();
+    let args = move |py: Python<'_>| -> PyResult<Py<PyTuple>> {
+        let data: PyObject = match &request.message {
+            Some(value) => json_to_python(py, value)?,
+            None => PyBytes::new(py, &request.payload).into(),
+        };
+        let remaining = request.time_remaining().map(|t| t.as_secs_f64());
+        let py_request = py.import("cello.grpc")?.getattr("GrpcRequest")?.call1((
+            &request.service,
+            &request.method,
+            data,
+            request.metadata.clone(),
+            remaining,
+        ))?;
+        Ok(PyTuple::new(py, [py_request]).into())
+    };
+    let result = match call_with_deadline(handler, args, timeout).await {
+        Ok(result) => result,
+        Err(CallError::TimedOut) => return Err(GrpcError::DeadlineExceeded),
+        Err(CallError::Raised(err)) => return python_error_response(err),
+    };
+
+    // Phase 3 (GIL): split the return value into status, metadata and payload
+    let converted = Python::with_gil(|py| -> PyResult<_> {
+        let result = result.as_ref(py);
+        let (data, status, metadata) = if result.hasattr("status_code")? {
+            let status = GrpcStatus::error(
+                result.getattr("status_code")?.extract()?,
+                &result.getattr("message")?.extract::<String>()?,
+            );
+            let metadata: HashMap<String, String> = result.getattr("metadata")?.extract()?;
+            (result.getattr("data")?, status, metadata)
+        } else {
+            (result, GrpcStatus::ok(), HashMap::new())
+        };
+
+        // Raw bytes are sent as-is; anything else is encoded as `output_type`
+        let (raw, value) = if data.is_none() || status.is_error() {
+            (Vec::new(), None)
+        } else if let Ok(bytes) = data.downcast::<PyBytes>() {
+            (bytes.as_bytes().to_vec(), None)
+        } else {
+            let value =
+                python_to_json(py, data).map_err(pyo3::exceptions::PyValueError::new_err)?;
+            (Vec::new(), Some(value))
+        };
+        Ok((raw, value, status, metadata))
+    });
+    let (raw, value, status, metadata) =
+        converted.map_err(|e| GrpcError::Unknown(e.to_string()))?;
+
+    let payload = match value {
+        Some(value) => descriptors
+            .read()
+            .encode(output_type, &value)
+            .map_err(|e| GrpcError::Unknown(e.to_string()))?,
+        None => raw,
+    };
+
+    Ok(GrpcResponse {
+        status,
+        payload,
+        metadata,
+    })
+}
+
+/// Map an exception raised by a Python handler to a response.
+///
+/// `cello.grpc.GrpcError` (anything with integer `code` and `message`) keeps
+/// its status; other exceptions become UNKNOWN.
+fn python_error_response(err: PyErr) -> Result<GrpcResponse, GrpcError> {
+    Python::with_gil(|py| {
+        let value = err.value(py);
+        let code = value.getattr("code").and_then(|c| c.extract::<i32>());
+        let message = value.getattr("message").and_then(|m| m.extract::<String>());
+        match (code, message) {
+            (Ok(code), Ok(message)) => Ok(GrpcResponse::error(GrpcStatus::error(code, &message))),
+            _ => Err(GrpcError::Unknown(err.to_string())),
+        }
+    })
+}
+
 // ============================================================================
 // gRPC Service Definition
 // ============================================================================
@@ -291,10 +519,17 @@ impl GrpcError {
     }
 
     /// Convert a gRPC error into a GrpcStatus.
+    ///
+    /// `Unknown` carries its message as-is; the code already says it is
+    /// unknown.
     pub fn to_status(&self) -> GrpcStatus {
+        let message = match self {
+            GrpcError::Unknown(msg) => msg.clone(),
+            _ => self.to_string(),
+        };
         GrpcStatus {
             code: self.code(),
-            message: self.to_string(),
+            message,
             details: None,
         }
     }
@@ -386,6 +621,11 @@ pub struct GrpcRequest {
     pub payload: Vec<u8>,
     /// Request metadata (headers) as key-value pairs
     pub metadata: HashMap<String, String>,
+    /// When the caller stops waiting, from its `grpc-timeout`
+    pub deadline: Option<Instant>,
+    /// The payload decoded with the method's input type, set on dispatch
+    /// when that type is in the descriptor pool
+    pub message: Option<serde_json::Value>,
 }
 
 impl GrpcRequest {
@@ -396,9 +636,23 @@ impl GrpcRequest {
             method: method.to_string(),
             payload,
             metadata: HashMap::new(),
+            deadline: None,
+            message: None,
         }
     }
 
+    /// Set the deadline to `timeout` from now.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.deadline = Some(Instant::now() + timeout);
+        self
+    }
+
+    /// Time left before the deadline, if there is one.
+    pub fn time_remaining(&self) -> Option<Duration> {
+        self.deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+
     /// Add a metadata entry to the request.
     pub fn with_metadata(mut self, key: &str, value: &str) -> Self {
         self.metadata.insert(key.to_string(), value.to_string());
@@ -465,6 +719,16 @@ impl GrpcResponse {
     pub fn is_ok(&self) -> bool {
         self.status.is_ok()
     }
+
+    /// Convert to a `{"code", "message", "payload", "metadata"}` dict for Python.
+    pub fn to_py_dict(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let dict = pyo3::types::PyDict::new(py);
+        dict.set_item("code", self.status.code)?;
+        dict.set_item("message", &self.status.message)?;
+        dict.set_item("payload", pyo3::types::PyBytes::new(py, &self.payload))?;
+        dict.set_item("metadata", &self.metadata)?;
+        Ok(dict.to_object(py))
+    }
 }
 
 // ============================================================================
@@ -497,6 +761,8 @@ pub struct GrpcServer {
     running: Arc<RwLock<bool>>,
     /// Request statistics
     stats: Arc<RwLock<GrpcStats>>,
+    /// Message descriptors, shared with protobuf HTTP routes
+    descriptors: SharedDescriptorPool,
 }
 
 impl GrpcServer {
@@ -507,15 +773,131 @@ impl GrpcServer {
             services: Arc::new(RwLock::new(HashMap::new())),
             running: Arc::new(RwLock::new(false)),
             stats: Arc::new(RwLock::new(GrpcStats::default())),
+            descriptors: Arc::new(RwLock::new(DescriptorPool::new())),
         }
     }
 
+    /// Use an existing descriptor pool (e.g. the app's, shared with HTTP routes).
+    pub fn with_descriptors(mut self, descriptors: SharedDescriptorPool) -> Self {
+        self.descriptors = descriptors;
+        self
+    }
+
+    /// Get the message descriptor pool.
+    pub fn descriptors(&self) -> &SharedDescriptorPool {
+        &self.descriptors
+    }
+
+    /// Decode a request payload using the method's input message type.
+    pub fn decode_input(
+        &self,
+        service_name: &str,
+        method_name: &str,
+        payload: &[u8],
+    ) -> Result<serde_json::Value, GrpcError> {
+        let method = self.resolve_method(service_name, method_name)?;
+        self.descriptors
+            .read()
+            .decode(&method.input_type, payload)
+            .map_err(|_| GrpcError::InvalidMessage)
+    }
+
+    /// Encode a response value using the method's output message type.
+    pub fn encode_output(
+        &self,
+        service_name: &str,
+        method_name: &str,
+        value: &serde_json::Value,
+    ) -> Result<Vec<u8>, GrpcError> {
+        let method = self.resolve_method(service_name, method_name)?;
+        self.descriptors
+            .read()
+            .encode(&method.output_type, value)
+            .map_err(|e| GrpcError::Unknown(e.to_string()))
+    }
+
     /// Register a service definition with the server.
     pub fn register_service(&self, service_def: GrpcServiceDef) {
         let mut services = self.services.write();
         services.insert(service_def.name.clone(), service_def);
     }
 
+    /// Load a compiled `FileDescriptorSet` into the descriptor pool and
+    /// register the services it defines.
+    ///
+    /// Returns the fully-qualified names of the loaded services.
+    pub fn load_file_descriptor_set(&self, bytes: &[u8]) -> Result<Vec<String>, GrpcError> {
+        let names = self
+            .descriptors
+            .write()
+            .load_file_descriptor_set(bytes)
+            .map_err(|e| GrpcError::Unknown(e.to_string()))?;
+        self.register_descriptor_services();
+        Ok(names)
+    }
+
+    /// Register every service in the descriptor pool.
+    ///
+    /// Methods already registered keep their handlers and deadlines.
+    pub fn register_descriptor_services(&self) {
+        let descriptors: Vec<ServiceDescriptor> = {
+            let pool = self.descriptors.read();
+            pool.service_names()
+                .iter()
+                .filter_map(|name| pool.service(name).cloned())
+                .collect()
+        };
+        for descriptor in descriptors {
+            for method in &descriptor.methods {
+                let existing = self
+                    .get_service(&descriptor.name)
+                    .and_then(|service| service.get_method(&method.name).cloned());
+                let mut method_def = GrpcMethodDef::new(
+                    &method.name,
+                    GrpcMethodType::from_streaming(
+                        method.client_streaming,
+                        method.server_streaming,
+                    ),
+                    &method.input_type,
+                    &method.output_type,
+                );
+                if let Some(existing) = existing {
+                    method_def.handler = existing.handler;
+                    method_def.deadline = existing.deadline;
+                }
+                self.add_method(&descriptor.name, method_def);
+            }
+        }
+    }
+
+    /// Add (or replace) a single method on a service, creating the service if needed.
+    ///
+    /// Missing input/output types are filled in from the descriptor pool.
+    pub fn add_method(&self, service_name: &str, mut method: GrpcMethodDef) {
+        if method.input_type.is_empty() || method.output_type.is_empty() {
+            if let Some(descriptor) = self
+                .descriptors
+                .read()
+                .service(service_name)
+                .and_then(|service| service.method(&method.name))
+            {
+                if method.input_type.is_empty() {
+                    method.input_type = descriptor.input_type.clone();
+                }
+                if method.output_type.is_empty() {
+                    method.output_type = descriptor.output_type.clone();
+                }
+            }
+        }
+
+        let mut services = self.services.write();
+        let service = services
+            .entry(service_name.to_string())
+            .or_insert_with(|| GrpcServiceDef::new(service_name));
+        service.methods.retain(|m| m.name != method.name);
+        service.methods.push(method);
+    }
+
     /// Get a service definition by its fully qualified name.
     pub fn get_service(&self, name: &str) -> Option<GrpcServiceDef> {
         let services = self.services.read();
@@ -564,8 +946,10 @@ impl GrpcServer {
     /// Process an incoming gRPC request and return a response.
     ///
     /// This validates the request against registered services and methods,
-    /// checks message size limits, and updates server statistics.
-    pub fn handle_request(&self, request: &GrpcRequest) -> GrpcResponse {
+    /// checks message size limits, dispatches to the method's handler under
+    /// its deadline, and updates server statistics. Methods without a handler
+    /// answer UNIMPLEMENTED.
+    pub async fn handle_request(&self, request: &GrpcRequest) -> GrpcResponse {
         // Update stats
         {
             let mut stats = self.stats.write();
@@ -574,37 +958,73 @@ impl GrpcServer {
         }
 
         // Validate message size
-        if request.payload.len() > self.config.max_message_size {
-            let mut stats = self.stats.write();
-            stats.total_errors += 1;
-            stats.active_streams = stats.active_streams.saturating_sub(1);
-            return GrpcResponse::error(GrpcStatus::error(
+        let response = if request.payload.len() > self.config.max_message_size {
+            GrpcResponse::error(GrpcStatus::error(
                 GrpcError::InvalidMessage.code(),
                 &format!(
                     "Message size {} exceeds maximum allowed size {}",
                     request.payload.len(),
                     self.config.max_message_size
                 ),
-            ));
+            ))
+        } else {
+            // Resolve service and method
+            match self.resolve_method(&request.service, &request.method) {
+                Ok(method_def) => self.dispatch(&method_def, request).await,
+                Err(err) => GrpcResponse::from_error(&err),
+            }
+        };
+
+        let mut stats = self.stats.write();
+        if !response.is_ok() {
+            stats.total_errors += 1;
         }
+        stats.active_streams = stats.active_streams.saturating_sub(1);
+        response
+    }
 
-        // Resolve service and method
-        let result = self.resolve_method(&request.service, &request.method);
-        match result {
-            Ok(_method_def) => {
-                // In a full implementation, this would dispatch to the actual handler.
-                // For now, return a placeholder success response.
-                let mut stats = self.stats.write();
-                stats.active_streams = stats.active_streams.saturating_sub(1);
-                GrpcResponse::ok(Vec::new())
-            }
-            Err(err) => {
-                let mut stats = self.stats.write();
-                stats.total_errors += 1;
-                stats.active_streams = stats.active_streams.saturating_sub(1);
-                GrpcResponse::from_error(&err)
+    /// Run a method's handler, enforcing the earlier of the caller's
+    /// deadline (`grpc-timeout`) and the method's.
+    ///
+    /// Payloads are decoded into the request's `message` when the input
+    /// type is in the pool; undecodable payloads are rejected.
+    async fn dispatch(&self, method_def: &GrpcMethodDef, request: &GrpcRequest) -> GrpcResponse {
+        let Some(handler) = &method_def.handler else {
+            return GrpcResponse::from_error(&GrpcError::MethodNotFound);
+        };
+
+        let mut request = request.clone();
+        {
+            let pool = self.descriptors.read();
+            if pool.message(&method_def.input_type).is_some() {
+                match pool.decode(&method_def.input_type, &request.payload) {
+                    Ok(message) => request.message = Some(message),
+                    Err(e) => {
+                        return GrpcResponse::error(GrpcStatus::error(
+                            GrpcError::InvalidMessage.code(),
+                            &format!("Invalid {}: {e}", method_def.input_type),
+                        ))
+                    }
+                }
             }
         }
+
+        if request.deadline.is_none() {
+            request.deadline = request
+                .get_metadata("grpc-timeout")
+                .and_then(parse_grpc_timeout)
+                .map(|timeout| Instant::now() + timeout);
+        }
+        if let Some(limit) = method_def.deadline {
+            let deadline = Instant::now() + limit;
+            request.deadline = Some(request.deadline.map_or(deadline, |d| d.min(deadline)));
+        }
+
+        let result = match request.deadline {
+            Some(deadline) if deadline <= Instant::now() => Err(GrpcError::DeadlineExceeded),
+            _ => handler.call(request).await,
+        };
+        result.unwrap_or_else(|err| GrpcResponse::from_error(&err))
     }
 
     /// Get the number of registered services.
@@ -618,6 +1038,435 @@ impl GrpcServer {
     }
 }
 
+// ============================================================================
+// gRPC Client
+// ============================================================================
+
+/// Parse a `grpc-timeout` header value: up to 8 digits and a unit, `H`,
+/// `M`, `S`, `m` (milliseconds), `u` (microseconds) or `n` (nanoseconds).
+pub fn parse_grpc_timeout(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let (digits, unit) = value.split_at(value.len().checked_sub(1)?);
+    if digits.is_empty() || digits.len() > 8 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let amount: u64 = digits.parse().ok()?;
+    Some(match unit {
+        "H" => Duration::from_secs(amount * 3600),
+        "M" => Duration::from_secs(amount * 60),
+        "S" => Duration::from_secs(amount),
+        "m" => Duration::from_millis(amount),
+        "u" => Duration::from_micros(amount),
+        "n" => Duration::from_nanos(amount),
+        _ => return None,
+    })
+}
+
+/// gRPC status code for RESOURCE_EXHAUSTED (no `GrpcError` variant).
+const RESOURCE_EXHAUSTED: i32 = 8;
+
+/// Headers that carry gRPC protocol state rather than user metadata.
+const RESERVED_HEADERS: &[&str] = &[
+    "content-type",
+    "content-length",
+    "te",
+    "grpc-status",
+    "grpc-message",
+    "grpc-timeout",
+    "grpc-encoding",
+    "grpc-accept-encoding",
+];
+
+/// gRPC client configuration.
+#[derive(Clone, Debug)]
+pub struct GrpcClientConfig {
+    /// Target address (e.g., "localhost:50051" or "http://10.0.0.5:50051")
+    pub target: String,
+    /// Default deadline for each call, across all retry attempts
+    pub timeout: Option<Duration>,
+    /// Retries after the first attempt for retryable status codes
+    pub max_retries: u32,
+    /// Backoff before the first retry; doubled on each further retry
+    pub initial_backoff: Duration,
+    /// Upper bound for the retry backoff
+    pub max_backoff: Duration,
+    /// Status codes that trigger a retry (default: UNAVAILABLE)
+    pub retryable_codes: Vec<i32>,
+    /// Metadata sent with every call (per-call metadata wins)
+    pub metadata: HashMap<String, String>,
+    /// Maximum response message size in bytes
+    pub max_message_size: usize,
+    /// How long idle pooled connections are kept open
+    pub pool_idle_timeout: Duration,
+}
+
+impl GrpcClientConfig {
+    /// Create a client configuration for the given target.
+    pub fn new(target: &str) -> Self {
+        Self {
+            target: target.to_string(),
+            timeout: None,
+            max_retries: 2,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(2),
+            retryable_codes: vec![GrpcError::Unavailable.code()],
+            metadata: HashMap::new(),
+            max_message_size: 4 * 1024 * 1024,
+            pool_idle_timeout: Duration::from_secs(90),
+        }
+    }
+
+    /// Set the default call deadline.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Set the retry policy.
+    pub fn with_retries(
+        mut self,
+        max_retries: u32,
+        initial_backoff: Duration,
+        max_backoff: Duration,
+    ) -> Self {
+        self.max_retries = max_retries;
+        self.initial_backoff = initial_backoff;
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Add a metadata entry sent with every call.
+    pub fn with_metadata(mut self, key: &str, value: &str) -> Self {
+        self.metadata.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Set the maximum response message size.
+    pub fn with_max_message_size(mut self, size: usize) -> Self {
+        self.max_message_size = size;
+        self
+    }
+}
+
+/// gRPC client over pooled HTTP/2 connections.
+///
+/// Connections are opened lazily and multiplexed; calls are retried with
+/// exponential backoff on retryable status codes while the deadline allows.
+pub struct GrpcClient {
+    /// Client configuration
+    config: GrpcClientConfig,
+    /// Base URI (scheme and authority) calls are sent to
+    base_uri: String,
+    /// Pooled HTTP/2 client
+    http: Client<HttpConnector, Full<Bytes>>,
+}
+
+impl GrpcClient {
+    /// Create a client. Only plaintext (`http://`) targets are supported.
+    pub fn new(config: GrpcClientConfig) -> Result<Self, GrpcError> {
+        let base_uri = if config.target.contains("://") {
+            config.target.trim_end_matches('/').to_string()
+        } else {
+            format!("http://{}", config.target)
+        };
+        let uri: hyper::Uri = base_uri
+            .parse()
+            .map_err(|e| GrpcError::Unknown(format!("Invalid target: {e}")))?;
+        if uri.scheme_str() != Some("http") || uri.authority().is_none() {
+            return Err(GrpcError::Unknown(format!(
+                "Unsupported target '{}': expected host:port or http://host:port",
+                config.target
+            )));
+        }
+
+        let http = Client::builder(TokioExecutor::new())
+            .http2_only(true)
+            .pool_idle_timeout(config.pool_idle_timeout)
+            .build_http();
+        Ok(Self {
+            config,
+            base_uri,
+            http,
+        })
+    }
+
+    /// Get the client configuration.
+    pub fn config(&self) -> &GrpcClientConfig {
+        &self.config
+    }
+
+    /// Make a unary call, retrying retryable failures within the deadline.
+    ///
+    /// `timeout` overrides the configured default deadline. Failures are
+    /// reported in the response status; transport errors map to UNAVAILABLE.
+    pub async fn call(&self, request: &GrpcRequest, timeout: Option<Duration>) -> GrpcResponse {
+        let deadline = timeout.or(self.config.timeout).map(|t| Instant::now() + t);
+        let mut backoff = self.config.initial_backoff;
+        let mut attempt = 0;
+
+        loop {
+            let remaining = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+            let response = match remaining {
+                Some(remaining) if remaining.is_zero() => {
+                    GrpcResponse::from_error(&GrpcError::DeadlineExceeded)
+                }
+                Some(remaining) => {
+                    tokio::time::timeout(remaining, self.send(request, Some(remaining)))
+                        .await
+                        .unwrap_or_else(|_| GrpcResponse::from_error(&GrpcError::DeadlineExceeded))
+                }
+                None => self.send(request, None).await,
+            };
+
+            let retryable = self.config.retryable_codes.contains(&response.status.code);
+            if response.is_ok() || !retryable || attempt >= self.config.max_retries {
+                return response;
+            }
+
+            let pause = match deadline {
+                Some(d) => backoff.min(d.saturating_duration_since(Instant::now())),
+                None => backoff,
+            };
+            if pause.is_zero() {
+                return response;
+            }
+            tokio::time::sleep(pause).await;
+            backoff = (backoff * 2).min(self.config.max_backoff);
+            attempt += 1;
+        }
+    }
+
+    /// Send a single attempt.
+    async fn send(&self, request: &GrpcRequest, timeout: Option<Duration>) -> GrpcResponse {
+        let mut body = Vec::with_capacity(5 + request.payload.len());
+        body.push(0); // uncompressed
+        body.extend_from_slice(&(request.payload.len() as u32).to_be_bytes());
+        body.extend_from_slice(&request.payload);
+
+        let mut builder = hyper::Request::post(format!("{}{}", self.base_uri, request.full_path()))
+            .header("content-type", "application/grpc")
+            .header("te", "trailers");
+        if let Some(timeout) = timeout {
+            builder = builder.header("grpc-timeout", format!("{}m", timeout.as_millis().max(1)));
+        }
+        let mut metadata = self.config.metadata.clone();
+        metadata.extend(request.metadata.clone());
+        for (key, value) in &metadata {
+            builder = builder.header(key.as_str(), value.as_str());
+        }
+        let http_request = match builder.body(Full::new(Bytes::from(body))) {
+            Ok(http_request) => http_request,
+            Err(e) => {
+                return GrpcResponse::error(GrpcStatus::error(
+                    GrpcError::Internal.code(),
+                    &format!("Invalid request: {e}"),
+                ))
+            }
+        };
+
+        let response = match self.http.request(http_request).await {
+            Ok(response) => response,
+            Err(e) => {
+                return GrpcResponse::error(GrpcStatus::error(
+                    GrpcError::Unavailable.code(),
+                    &format!("Connection failed: {e}"),
+                ))
+            }
+        };
+        let (parts, body) = response.into_parts();
+        if parts.status != hyper::StatusCode::OK {
+            return GrpcResponse::error(http_status_to_grpc(parts.status));
+        }
+        let collected = match body.collect().await {
+            Ok(collected) => collected,
+            Err(e) => {
+                return GrpcResponse::error(GrpcStatus::error(
+                    GrpcError::Unavailable.code(),
+                    &format!("Stream error: {e}"),
+                ))
+            }
+        };
+
+        // Status comes in the trailers, or in the headers for trailers-only responses
+        let mut metadata = HashMap::new();
+        let mut code = None;
+        let mut message = String::new();
+        for (name, value) in parts
+            .headers
+            .iter()
+            .chain(collected.trailers().into_iter().flatten())
+        {
+            let value = value.to_str().unwrap_or_default();
+            match name.as_str() {
+                "grpc-status" => code = value.parse::<i32>().ok(),
+                "grpc-message" => {
+                    message = urlencoding::decode(value)
+                        .map(|m| m.into_owned())
+                        .unwrap_or_else(|_| value.to_string())
+                }
+                name if RESERVED_HEADERS.contains(&name) => {}
+                name => {
+                    metadata.insert(name.to_string(), value.to_string());
+                }
+            }
+        }
+
+        let Some(code) = code else {
+            return GrpcResponse::error(GrpcStatus::error(
+                GrpcError::Internal.code(),
+                "Response is missing grpc-status",
+            ));
+        };
+        let status = if code == 0 {
+            GrpcStatus::ok()
+        } else {
+            GrpcStatus::error(code, &message)
+        };
+
+        let payload = match self.read_message(&collected.to_bytes()) {
+            Ok(payload) => payload,
+            Err(status) => return GrpcResponse::error(status),
+        };
+        GrpcResponse {
+            status,
+            payload,
+            metadata,
+        }
+    }
+
+    /// Extract the message from a length-prefixed gRPC response body.
+    fn read_message(&self, body: &[u8]) -> Result<Vec<u8>, GrpcStatus> {
+        if body.is_empty() {
+            return Ok(Vec::new());
+        }
+        if body.len() < 5 {
+            return Err(GrpcStatus::error(
+                GrpcError::Internal.code(),
+                "Truncated message frame",
+            ));
+        }
+        if body[0] != 0 {
+            return Err(GrpcStatus::error(
+                GrpcError::Internal.code(),
+                "Compressed responses are not supported",
+            ));
+        }
+        let len = u32::from_be_bytes([body[1], body[2], body[3], body[4]]) as usize;
+        if len > self.config.max_message_size {
+            return Err(GrpcStatus::error(
+                RESOURCE_EXHAUSTED,
+                &format!(
+                    "Message size {len} exceeds maximum allowed size {}",
+                    self.config.max_message_size
+                ),
+            ));
+        }
+        body.get(5..5 + len)
+            .map(|message| message.to_vec())
+            .ok_or_else(|| GrpcStatus::error(GrpcError::Internal.code(), "Truncated message"))
+    }
+}
+
+/// Map a non-200 HTTP status to a gRPC status (per the gRPC HTTP/2 spec).
+fn http_status_to_grpc(status: hyper::StatusCode) -> GrpcStatus {
+    let code = match status.as_u16() {
+        400 => GrpcError::Internal.code(),
+        401 => GrpcError::Unauthenticated.code(),
+        403 => GrpcError::PermissionDenied.code(),
+        404 => GrpcError::MethodNotFound.code(),
+        429 | 502 | 503 | 504 => GrpcError::Unavailable.code(),
+        _ => GrpcError::Unknown(String::new()).code(),
+    };
+    GrpcStatus::error(code, &format!("HTTP status {status}"))
+}
+
+/// Python-facing async gRPC client.
+///
+/// Calls resolve to a dict with `code`, `message`, `payload` (bytes) and
+/// `metadata`; message encoding is left to the caller (see `cello.grpc.GrpcChannel`).
+#[pyclass(name = "GrpcClient")]
+pub struct PyGrpcClient {
+    client: Arc<GrpcClient>,
+}
+
+#[pymethods]
+impl PyGrpcClient {
+    /// Create a client for `target`; durations are in seconds.
+    #[new]
+    #[pyo3(signature = (
+        target,
+        timeout=None,
+        max_retries=2,
+        initial_backoff=0.1,
+        max_backoff=2.0,
+        metadata=None,
+        max_message_size=4194304
+    ))]
+    pub fn new(
+        target: &str,
+        timeout: Option<f64>,
+        max_retries: u32,
+        initial_backoff: f64,
+        max_backoff: f64,
+        metadata: Option<HashMap<String, String>>,
+        max_message_size: usize,
+    ) -> PyResult<Self> {
+        let mut config = GrpcClientConfig::new(target)
+            .with_retries(
+                max_retries,
+                seconds(initial_backoff)?,
+                seconds(max_backoff)?,
+            )
+            .with_max_message_size(max_message_size);
+        if let Some(timeout) = timeout {
+            config = config.with_timeout(seconds(timeout)?);
+        }
+        config.metadata = metadata.unwrap_or_default();
+        let client = GrpcClient::new(config)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        Ok(Self {
+            client: Arc::new(client),
+        })
+    }
+
+    /// The configured target.
+    #[getter]
+    pub fn target(&self) -> String {
+        self.client.config().target.clone()
+    }
+
+    /// Make a unary call with a serialized request message.
+    #[pyo3(signature = (service, method, payload, metadata=None, timeout=None))]
+    pub fn call<'py>(
+        &self,
+        py: Python<'py>,
+        service: &str,
+        method: &str,
+        payload: Vec<u8>,
+        metadata: Option<HashMap<String, String>>,
+        timeout: Option<f64>,
+    ) -> PyResult<&'py PyAny> {
+        let client = self.client.clone();
+        let timeout = timeout.map(seconds).transpose()?;
+        let mut request = GrpcRequest::new(service, method, payload);
+        request.metadata = metadata.unwrap_or_default();
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            let response = client.call(&request, timeout).await;
+            Python::with_gil(|py| response.to_py_dict(py))
+        })
+    }
+
+    fn __repr__(&self) -> String {
+        format!("GrpcClient(target={:?})", self.client.config().target)
+    }
+}
+
+/// Convert Python seconds into a `Duration`.
+fn seconds(value: f64) -> PyResult<Duration> {
+    Duration::try_from_secs_f64(value)
+        .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))
+}
+
 // ============================================================================
 // gRPC Statistics
 // ============================================================================
@@ -788,6 +1637,10 @@ mod tests {
         assert_eq!(status.code, 7);
         assert_eq!(status.message, "gRPC error: permission denied");
         assert!(status.details.is_none());
+
+        let status = GrpcError::Unknown("KeyError: 'k'".to_string()).to_status();
+        assert_eq!(status.code, 2);
+        assert_eq!(status.message, "KeyError: 'k'");
     }
 
     #[test]
@@ -939,38 +1792,369 @@ mod tests {
         assert!(err.is_err());
     }
 
-    #[test]
-    fn test_grpc_server_handle_request() {
+    /// Unary method that echoes the request payload back.
+    fn echo_method() -> GrpcMethodDef {
+        GrpcMethodDef::unary("Echo", "EchoRequest", "EchoResponse").with_handler(GrpcHandler::sync(
+            |request| Ok(GrpcResponse::ok(request.payload)),
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_grpc_server_handle_request() {
         let server = GrpcServer::new(GrpcConfig::new());
 
-        let service = GrpcServiceDef::new("test.Service").add_method(GrpcMethodDef::unary(
-            "Echo",
-            "EchoRequest",
-            "EchoResponse",
-        ));
+        let service = GrpcServiceDef::new("test.Service")
+            .add_method(echo_method())
+            .add_method(GrpcMethodDef::unary(
+                "NoHandler",
+                "EchoRequest",
+                "EchoResponse",
+            ));
 
         server.register_service(service);
 
         // Successful request
         let request = GrpcRequest::new("test.Service", "Echo", vec![0x01, 0x02]);
-        let response = server.handle_request(&request);
+        let response = server.handle_request(&request).await;
         assert!(response.is_ok());
+        assert_eq!(response.payload, vec![0x01, 0x02]);
+
+        // Method without a handler
+        let request = GrpcRequest::new("test.Service", "NoHandler", vec![]);
+        let response = server.handle_request(&request).await;
+        assert_eq!(response.status.code, GrpcError::MethodNotFound.code());
 
         // Service not found
         let request = GrpcRequest::new("missing.Service", "Echo", vec![]);
-        let response = server.handle_request(&request);
+        let response = server.handle_request(&request).await;
         assert!(!response.is_ok());
         assert_eq!(response.status.code, GrpcError::ServiceNotFound.code());
 
         // Method not found
         let request = GrpcRequest::new("test.Service", "Missing", vec![]);
-        let response = server.handle_request(&request);
+        let response = server.handle_request(&request).await;
         assert!(!response.is_ok());
         assert_eq!(response.status.code, GrpcError::MethodNotFound.code());
     }
 
+    #[tokio::test]
+    async fn test_grpc_server_handler_deadline_and_metadata() {
+        let server = GrpcServer::new(GrpcConfig::new());
+
+        let whoami = GrpcMethodDef::unary("WhoAmI", "Empty", "Empty").with_handler(
+            GrpcHandler::sync(|request| {
+                let user = request.get_metadata("x-user").unwrap_or("anonymous");
+                Ok(GrpcResponse::ok(user.as_bytes().to_vec()).with_metadata("x-served-by", "test"))
+            }),
+        );
+        let slow = GrpcMethodDef::unary("Slow", "Empty", "Empty")
+            .with_deadline(Duration::from_millis(10))
+            .with_handler(GrpcHandler::new(|_| async {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                Ok(GrpcResponse::ok(Vec::new()))
+            }));
+        let failing = GrpcMethodDef::unary("Fail", "Empty", "Empty")
+            .with_handler(GrpcHandler::sync(|_| Err(GrpcError::PermissionDenied)));
+        server.register_service(
+            GrpcServiceDef::new("test.Service")
+                .add_method(whoami)
+                .add_method(slow)
+                .add_method(failing),
+        );
+
+        let request =
+            GrpcRequest::new("test.Service", "WhoAmI", vec![]).with_metadata("x-user", "ada");
+        let response = server.handle_request(&request).await;
+        assert_eq!(response.payload, b"ada");
+        assert_eq!(
+            response.metadata.get("x-served-by").map(String::as_str),
+            Some("test")
+        );
+
+        let request = GrpcRequest::new("test.Service", "Slow", vec![]);
+        let response = server.handle_request(&request).await;
+        assert_eq!(response.status.code, GrpcError::DeadlineExceeded.code());
+
+        let request = GrpcRequest::new("test.Service", "Fail", vec![]);
+        let response = server.handle_request(&request).await;
+        assert_eq!(response.status.code, GrpcError::PermissionDenied.code());
+
+        // Replacing a method keeps the service's other methods
+        server.add_method(
+            "test.Service",
+            GrpcMethodDef::unary("Fail", "Empty", "Empty"),
+        );
+        assert_eq!(server.get_service("test.Service").unwrap().methods.len(), 3);
+        let response = server.handle_request(&request).await;
+        assert_eq!(response.status.code, GrpcError::MethodNotFound.code());
+        assert_eq!(server.stats().total_errors, 3);
+    }
+
     #[test]
-    fn test_grpc_server_message_size_limit() {
+    fn test_parse_grpc_timeout() {
+        assert_eq!(parse_grpc_timeout("2S"), Some(Duration::from_secs(2)));
+        assert_eq!(parse_grpc_timeout("1H"), Some(Duration::from_secs(3600)));
+        assert_eq!(parse_grpc_timeout("250m"), Some(Duration::from_millis(250)));
+        assert_eq!(
+            parse_grpc_timeout("99999999n"),
+            Some(Duration::from_nanos(99999999))
+        );
+        assert_eq!(parse_grpc_timeout("123456789S"), None);
+        assert_eq!(parse_grpc_timeout("1.5S"), None);
+        assert_eq!(parse_grpc_timeout("10"), None);
+        assert_eq!(parse_grpc_timeout("S"), None);
+    }
+
+    #[tokio::test]
+    async fn test_grpc_server_caller_deadline() {
+        let server = GrpcServer::new(GrpcConfig::new());
+        let remaining = GrpcMethodDef::unary("Remaining", "Empty", "Empty")
+            .with_deadline(Duration::from_secs(60))
+            .with_handler(GrpcHandler::new(|request: GrpcRequest| async move {
+                let remaining = request.time_remaining().unwrap();
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                Ok(GrpcResponse::ok(
+                    remaining.as_millis().to_string().into_bytes(),
+                ))
+            }));
+        server.register_service(GrpcServiceDef::new("test.Service").add_method(remaining));
+
+        // The caller's deadline wins over a later method deadline
+        let request = GrpcRequest::new("test.Service", "Remaining", vec![])
+            .with_metadata("grpc-timeout", "5S");
+        let response = server.handle_request(&request).await;
+        let millis: u128 = String::from_utf8(response.payload)
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(millis > 4000 && millis <= 5000);
+
+        let request = GrpcRequest::new("test.Service", "Remaining", vec![])
+            .with_metadata("grpc-timeout", "10m");
+        let response = server.handle_request(&request).await;
+        assert_eq!(response.status.code, GrpcError::DeadlineExceeded.code());
+
+        let request =
+            GrpcRequest::new("test.Service", "Remaining", vec![]).with_timeout(Duration::ZERO);
+        let response = server.handle_request(&request).await;
+        assert_eq!(response.status.code, GrpcError::DeadlineExceeded.code());
+    }
+
+    /// Serve `server` over plaintext HTTP/2 on a local port for client tests.
+    async fn serve_http2(server: Arc<GrpcServer>) -> std::net::SocketAddr {
+        use http_body_util::StreamBody;
+        use hyper::body::Frame;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let server = server.clone();
+                let service = hyper::service::service_fn(
+                    move |req: hyper::Request<hyper::body::Incoming>| {
+                        let server = server.clone();
+                        async move {
+                            let mut path = req.uri().path().trim_start_matches('/').splitn(2, '/');
+                            let (service, method) = (path.next().unwrap(), path.next().unwrap());
+                            let mut request = GrpcRequest::new(service, method, Vec::new());
+                            for (name, value) in req.headers() {
+                                request
+                                    .metadata
+                                    .insert(name.to_string(), value.to_str().unwrap().to_string());
+                            }
+                            let body = req.into_body().collect().await.unwrap().to_bytes();
+                            request.payload = body[5..].to_vec();
+
+                            let response = server.handle_request(&request).await;
+                            let mut framed = vec![0];
+                            framed
+                                .extend_from_slice(&(response.payload.len() as u32).to_be_bytes());
+                            framed.extend_from_slice(&response.payload);
+                            let mut trailers = hyper::HeaderMap::new();
+                            trailers.insert("grpc-status", response.status.code.into());
+                            trailers.insert(
+                                "grpc-message",
+                                urlencoding::encode(&response.status.message)
+                                    .parse()
+                                    .unwrap(),
+                            );
+                            for (key, value) in &response.metadata {
+                                trailers.insert(
+                                    hyper::header::HeaderName::from_bytes(key.as_bytes()).unwrap(),
+                                    value.parse().unwrap(),
+                                );
+                            }
+                            let frames: Vec<Result<_, std::convert::Infallible>> = vec![
+                                Ok(Frame::data(Bytes::from(framed))),
+                                Ok(Frame::trailers(trailers)),
+                            ];
+                            Ok::<_, std::convert::Infallible>(
+                                hyper::Response::builder()
+                                    .header("content-type", "application/grpc")
+                                    .body(StreamBody::new(futures_util::stream::iter(frames)))
+                                    .unwrap(),
+                            )
+                        }
+                    },
+                );
+                tokio::spawn(
+                    hyper::server::conn::http2::Builder::new(TokioExecutor::new())
+                        .serve_connection(hyper_util::rt::TokioIo::new(stream), service),
+                );
+            }
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_grpc_client_calls_server() {
+        let flaky_calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = flaky_calls.clone();
+        let server = Arc::new(GrpcServer::new(GrpcConfig::new()));
+        server.register_service(
+            GrpcServiceDef::new("test.Service")
+                .add_method(echo_method())
+                .add_method(GrpcMethodDef::unary("Meta", "Empty", "Empty").with_handler(
+                    GrpcHandler::sync(|request| {
+                        let token = request.get_metadata("authorization").unwrap_or_default();
+                        let timeout = request.get_metadata("grpc-timeout").unwrap_or_default();
+                        Ok(GrpcResponse::ok(format!("{token}|{timeout}").into_bytes())
+                            .with_metadata("x-served-by", "test"))
+                    }),
+                ))
+                .add_method(
+                    GrpcMethodDef::unary("Flaky", "Empty", "Empty").with_handler(
+                        GrpcHandler::sync(move |_| {
+                            match counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst) {
+                                0 | 1 => Err(GrpcError::Unavailable),
+                                _ => Ok(GrpcResponse::ok(b"recovered".to_vec())),
+                            }
+                        }),
+                    ),
+                )
+                .add_method(GrpcMethodDef::unary("Slow", "Empty", "Empty").with_handler(
+                    GrpcHandler::new(|_| async {
+                        tokio::time::sleep(Duration::from_secs(5)).await;
+                        Ok(GrpcResponse::ok(Vec::new()))
+                    }),
+                )),
+        );
+        let addr = serve_http2(server).await;
+
+        let config = GrpcClientConfig::new(&addr.to_string())
+            .with_retries(2, Duration::from_millis(1), Duration::from_millis(5))
+            .with_metadata("authorization", "Bearer default");
+        let client = GrpcClient::new(config).unwrap();
+
+        let request = GrpcRequest::new("test.Service", "Echo", b"hello".to_vec());
+        let response = client.call(&request, None).await;
+        assert!(response.is_ok());
+        assert_eq!(response.payload, b"hello");
+
+        // Per-call metadata wins over defaults; the deadline is sent as grpc-timeout
+        let request = GrpcRequest::new("test.Service", "Meta", Vec::new())
+            .with_metadata("authorization", "Bearer call");
+        let response = client.call(&request, Some(Duration::from_secs(5))).await;
+        let echoed = String::from_utf8(response.payload).unwrap();
+        assert!(echoed.starts_with("Bearer call|"));
+        assert!(echoed.ends_with('m'));
+        assert_eq!(
+            response.metadata.get("x-served-by").map(String::as_str),
+            Some("test")
+        );
+
+        // UNAVAILABLE is retried with backoff
+        let request = GrpcRequest::new("test.Service", "Flaky", Vec::new());
+        let response = client.call(&request, None).await;
+        assert_eq!(response.payload, b"recovered");
+        assert_eq!(flaky_calls.load(std::sync::atomic::Ordering::SeqCst), 3);
+
+        // Non-retryable errors come back as-is
+        let request = GrpcRequest::new("test.Service", "Missing", Vec::new());
+        let response = client.call(&request, None).await;
+        assert_eq!(response.status.code, GrpcError::MethodNotFound.code());
+        assert_eq!(response.status.message, "gRPC error: method not found");
+
+        let request = GrpcRequest::new("test.Service", "Slow", Vec::new());
+        let response = client.call(&request, Some(Duration::from_millis(20))).await;
+        assert_eq!(response.status.code, GrpcError::DeadlineExceeded.code());
+    }
+
+    #[tokio::test]
+    async fn test_grpc_client_unreachable_target() {
+        assert!(GrpcClient::new(GrpcClientConfig::new("https://example.com")).is_err());
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let config = GrpcClientConfig::new(&format!("http://{addr}")).with_retries(
+            1,
+            Duration::from_millis(1),
+            Duration::from_millis(1),
+        );
+        let client = GrpcClient::new(config).unwrap();
+        let request = GrpcRequest::new("test.Service", "Echo", Vec::new());
+        let response = client.call(&request, None).await;
+        assert_eq!(response.status.code, GrpcError::Unavailable.code());
+    }
+
+    #[tokio::test]
+    async fn test_grpc_server_loads_descriptor_set() {
+        use crate::middleware::protobuf::tests::shop_descriptor_set;
+
+        let server = GrpcServer::new(GrpcConfig::new());
+        // Handler registered before the descriptors are loaded, without types
+        server.add_method(
+            "shop.Catalog",
+            GrpcMethodDef::unary("Get", "", "").with_handler(GrpcHandler::sync(|request| {
+                Ok(GrpcResponse::ok(request.payload))
+            })),
+        );
+        assert!(server
+            .load_file_descriptor_set(b"not a descriptor")
+            .is_err());
+        let names = server
+            .load_file_descriptor_set(&shop_descriptor_set())
+            .unwrap();
+        assert_eq!(names, vec!["shop.Catalog".to_string()]);
+
+        let service = server.get_service("shop.Catalog").unwrap();
+        let get = service.get_method("Get").unwrap();
+        assert_eq!(get.input_type, "shop.GetItem");
+        assert_eq!(get.output_type, "shop.Item");
+        assert!(get.handler.is_some());
+        assert_eq!(
+            service.get_method("Watch").unwrap().method_type,
+            GrpcMethodType::ServerStreaming
+        );
+
+        // Types are filled in for methods added after loading too
+        server.add_method("shop.Catalog", GrpcMethodDef::unary("Watch", "", ""));
+        let watch = server.get_service("shop.Catalog").unwrap();
+        assert_eq!(
+            watch.get_method("Watch").unwrap().input_type,
+            "shop.GetItem"
+        );
+
+        // Payloads are validated against the input type before dispatch
+        let payload = server
+            .descriptors()
+            .read()
+            .encode("shop.GetItem", &serde_json::json!({"id": 3}))
+            .unwrap();
+        let request = GrpcRequest::new("shop.Catalog", "Get", payload.clone());
+        assert_eq!(server.handle_request(&request).await.payload, payload);
+
+        let request = GrpcRequest::new("shop.Catalog", "Get", vec![0x0a, 0x09]);
+        let response = server.handle_request(&request).await;
+        assert_eq!(response.status.code, GrpcError::InvalidMessage.code());
+        assert!(response.status.message.contains("shop.GetItem"));
+    }
+
+    #[tokio::test]
+    async fn test_grpc_server_message_size_limit() {
         let config = GrpcConfig::new().with_max_message_size(10);
         let server = GrpcServer::new(config);
 
@@ -984,20 +2168,16 @@ mod tests {
         // Payload exceeds max message size
         let oversized_payload = vec![0u8; 20];
         let request = GrpcRequest::new("test.Service", "Echo", oversized_payload);
-        let response = server.handle_request(&request);
+        let response = server.handle_request(&request).await;
         assert!(!response.is_ok());
         assert_eq!(response.status.code, GrpcError::InvalidMessage.code());
     }
 
-    #[test]
-    fn test_grpc_server_stats() {
+    #[tokio::test]
+    async fn test_grpc_server_stats() {
         let server = GrpcServer::new(GrpcConfig::new());
 
-        let service = GrpcServiceDef::new("test.Service").add_method(GrpcMethodDef::unary(
-            "Echo",
-            "EchoRequest",
-            "EchoResponse",
-        ));
+        let service = GrpcServiceDef::new("test.Service").add_method(echo_method());
         server.register_service(service);
 
         // Initial stats
@@ -1008,7 +2188,7 @@ mod tests {
 
         // After successful request
         let request = GrpcRequest::new("test.Service", "Echo", vec![]);
-        server.handle_request(&request);
+        server.handle_request(&request).await;
 
         let stats = server.stats();
         assert_eq!(stats.total_requests, 1);
@@ -1016,7 +2196,7 @@ mod tests {
 
         // After failed request
         let bad_request = GrpcRequest::new("missing.Service", "Echo", vec![]);
-        server.handle_request(&bad_request);
+        server.handle_request(&bad_request).await;
 
         let stats = server.stats();
         assert_eq!(stats.total_requests, 2);
@@ -1071,4 +2251,89 @@ mod tests {
             GrpcMethodType::ServerStreaming
         );
     }
+
+    #[test]
+    fn test_grpc_server_message_codec() {
+        use crate::middleware::protobuf::{FieldDescriptor, FieldType, MessageDescriptor};
+
+        let server = GrpcServer::new(GrpcConfig::default());
+        server.register_service(
+            GrpcServiceDef::new("greet.Greeter").add_method(GrpcMethodDef::unary(
+                "Hello",
+                "greet.Req",
+                "greet.Req",
+            )),
+        );
+        server
+            .descriptors()
+            .write()
+            .add_message(
+                MessageDescriptor::new("greet.Req").field(FieldDescriptor::new(
+                    "name",
+                    1,
+                    FieldType::parse("string"),
+                )),
+            )
+            .unwrap();
+
+        let value = serde_json::json!({"name": "Ada"});
+        let bytes = server
+            .encode_output("greet.Greeter", "Hello", &value)
+            .unwrap();
+        assert_eq!(
+            server
+                .decode_input("greet.Greeter", "Hello", &bytes)
+                .unwrap(),
+            value
+        );
+        assert!(matches!(
+            server.decode_input("greet.Greeter", "Hello", &[0x0a, 0x09]),
+            Err(GrpcError::InvalidMessage)
+        ));
+        assert!(matches!(
+            server.decode_input("greet.Greeter", "Bye", &bytes),
+            Err(GrpcError::MethodNotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_grpc_server_passes_decoded_message() {
+        use crate::middleware::protobuf::{FieldDescriptor, FieldType, MessageDescriptor};
+
+        let server = GrpcServer::new(GrpcConfig::default());
+        server.register_service(GrpcServiceDef::new("greet.Greeter").add_method(
+            GrpcMethodDef::unary("Hello", "greet.Req", "greet.Req").with_handler(
+                GrpcHandler::sync(|request| {
+                    let name = request.message.as_ref().unwrap()["name"].clone();
+                    Ok(GrpcResponse::ok(name.as_str().unwrap().as_bytes().to_vec()))
+                }),
+            ),
+        ));
+        server
+            .descriptors()
+            .write()
+            .add_message(
+                MessageDescriptor::new("greet.Req").field(FieldDescriptor::new(
+                    "name",
+                    1,
+                    FieldType::parse("string"),
+                )),
+            )
+            .unwrap();
+
+        let bytes = server
+            .encode_output(
+                "greet.Greeter",
+                "Hello",
+                &serde_json::json!({"name": "Ada"}),
+            )
+            .unwrap();
+        let request = GrpcRequest::new("greet.Greeter", "Hello", bytes);
+        let response = server.handle_request(&request).await;
+        assert_eq!(response.payload, b"Ada");
+
+        let request = GrpcRequest::new("greet.Greeter", "Hello", vec![0x0a, 0x09]);
+        let response = server.handle_request(&request).await;
+        assert_eq!(response.status.code, GrpcError::InvalidMessage.code());
+    }
 }
@@ -4,6 +4,7 @@
 //! - Role-based access control (RBAC)
 //! - Permission-based guards
 //! - Composable guards (AND, OR, NOT)
+//! - Permission expressions (`admin | (editor & owner)`)
 //! - Route-level and controller-level guards
 //! - Custom guard logic
 
@@ -41,6 +42,64 @@ impl std::fmt::Display for GuardError {
 
 impl std::error::Error for GuardError {}
 
+impl GuardError {
+    /// HTTP status of the rejection.
+    pub fn status(&self) -> u16 {
+        match self {
+            GuardError::Forbidden(_) => 403,
+            GuardError::Unauthorized(_) => 401,
+            GuardError::Custom(_, code) => *code,
+        }
+    }
+
+    /// Message of the rejection.
+    pub fn message(&self) -> &str {
+        match self {
+            GuardError::Forbidden(msg) | GuardError::Unauthorized(msg) => msg,
+            GuardError::Custom(msg, _) => msg,
+        }
+    }
+}
+
+/// The rejection carried by a `cello.guards.GuardError` raised by a Python
+/// guard, or `None` for any other exception.
+pub fn rejection(py: Python<'_>, err: &PyErr) -> Option<GuardError> {
+    let class = py.import("cello.guards").ok()?.getattr("GuardError").ok()?;
+    let exc = err.value(py);
+    if !exc.is_instance(class).unwrap_or(false) {
+        return None;
+    }
+    let message: String = exc.getattr("message").ok()?.extract().ok()?;
+    let status: u16 = exc.getattr("status_code").ok()?.extract().ok()?;
+    Some(match status {
+        401 => GuardError::Unauthorized(message),
+        403 => GuardError::Forbidden(message),
+        _ => GuardError::Custom(message, status),
+    })
+}
+
+/// The `cello.guards` exception for a rejection, raised to Python callers.
+fn to_pyerr(py: Python<'_>, err: GuardError) -> PyErr {
+    let Ok(guards) = py.import("cello.guards") else {
+        return pyo3::exceptions::PyPermissionError::new_err(err.message().to_string());
+    };
+    let raised = match &err {
+        GuardError::Unauthorized(msg) => guards
+            .getattr("UnauthorizedError")
+            .and_then(|class| class.call1((msg,))),
+        GuardError::Forbidden(msg) => guards
+            .getattr("ForbiddenError")
+            .and_then(|class| class.call1((msg,))),
+        GuardError::Custom(msg, code) => guards
+            .getattr("GuardError")
+            .and_then(|class| class.call1((msg, *code))),
+    };
+    match raised {
+        Ok(exc) => PyErr::from_value(exc),
+        Err(e) => e,
+    }
+}
+
 impl From<GuardError> for MiddlewareError {
     fn from(err: GuardError) -> Self {
         match err {
@@ -441,6 +500,295 @@ impl Guard for NotGuard {
     }
 }
 
+/// Guard that runs another guard only for paths under a prefix, for guards
+/// attached to a group of routes.
+pub struct ScopedGuard {
+    guard: Arc<dyn Guard>,
+    prefix: String,
+}
+
+impl ScopedGuard {
+    pub fn new(guard: Arc<dyn Guard>, prefix: &str) -> Self {
+        Self {
+            guard,
+            prefix: prefix.trim_end_matches('/').to_string(),
+        }
+    }
+}
+
+impl Guard for ScopedGuard {
+    fn check(&self, request: &Request) -> GuardResult {
+        self.guard.check(request)
+    }
+
+    fn priority(&self) -> i32 {
+        self.guard.priority()
+    }
+
+    fn name(&self) -> &str {
+        self.guard.name()
+    }
+
+    fn should_run(&self, path: &str) -> bool {
+        (self.prefix.is_empty() || path_matches_skip(path, &self.prefix))
+            && self.guard.should_run(path)
+    }
+}
+
+// ============================================================================
+// Permission Expressions
+// ============================================================================
+
+/// A boolean expression over the roles, permissions and scopes of a user,
+/// such as `admin | (editor & owner)`.
+///
+/// Names combine with `&` (and), `|` (or), `!` (not) and parentheses; `&`
+/// binds tighter than `|`. A name holds when the user's claims list it as a
+/// role, a permission or a scope.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PermissionExpr {
+    Claim(String),
+    Not(Box<PermissionExpr>),
+    And(Vec<PermissionExpr>),
+    Or(Vec<PermissionExpr>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Name(String),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+impl PermissionExpr {
+    /// Parse an expression.
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let tokens = tokenize(source)?;
+        let mut pos = 0;
+        let expr = parse_or(&tokens, &mut pos)?;
+        match tokens.get(pos) {
+            None => Ok(expr),
+            Some(token) => Err(format!("Unexpected {token:?} in permission expression")),
+        }
+    }
+
+    /// Whether `claims` satisfy the expression.
+    pub fn evaluate(&self, claims: &HashSet<&str>) -> bool {
+        match self {
+            PermissionExpr::Claim(name) => claims.contains(name.as_str()),
+            PermissionExpr::Not(expr) => !expr.evaluate(claims),
+            PermissionExpr::And(exprs) => exprs.iter().all(|e| e.evaluate(claims)),
+            PermissionExpr::Or(exprs) => exprs.iter().any(|e| e.evaluate(claims)),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let is_name = |c: char| c.is_alphanumeric() || matches!(c, '_' | '-' | ':' | '.' | '*' | '/');
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+    while let Some(c) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            // `&&` and `||` read the same as `&` and `|`
+            '&' => {
+                chars.next_if_eq(&'&');
+                Token::And
+            }
+            '|' => {
+                chars.next_if_eq(&'|');
+                Token::Or
+            }
+            '!' => Token::Not,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            c if is_name(c) => {
+                let mut name = c.to_string();
+                while let Some(c) = chars.next_if(|&c| is_name(c)) {
+                    name.push(c);
+                }
+                Token::Name(name)
+            }
+            c => {
+                return Err(format!(
+                    "Unexpected character {c:?} in permission expression"
+                ))
+            }
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+fn parse_or(tokens: &[Token], pos: &mut usize) -> Result<PermissionExpr, String> {
+    let mut terms = vec![parse_and(tokens, pos)?];
+    while tokens.get(*pos) == Some(&Token::Or) {
+        *pos += 1;
+        terms.push(parse_and(tokens, pos)?);
+    }
+    Ok(if terms.len() == 1 {
+        terms.remove(0)
+    } else {
+        PermissionExpr::Or(terms)
+    })
+}
+
+fn parse_and(tokens: &[Token], pos: &mut usize) -> Result<PermissionExpr, String> {
+    let mut terms = vec![parse_unary(tokens, pos)?];
+    while tokens.get(*pos) == Some(&Token::And) {
+        *pos += 1;
+        terms.push(parse_unary(tokens, pos)?);
+    }
+    Ok(if terms.len() == 1 {
+        terms.remove(0)
+    } else {
+        PermissionExpr::And(terms)
+    })
+}
+
+fn parse_unary(tokens: &[Token], pos: &mut usize) -> Result<PermissionExpr, String> {
+    let token = tokens
+        .get(*pos)
+        .ok_or_else(|| "Permission expression ends unexpectedly".to_string())?;
+    *pos += 1;
+    match token {
+        Token::Name(name) => Ok(PermissionExpr::Claim(name.clone())),
+        Token::Not => Ok(PermissionExpr::Not(Box::new(parse_unary(tokens, pos)?))),
+        Token::Open => {
+            let expr = parse_or(tokens, pos)?;
+            if tokens.get(*pos) != Some(&Token::Close) {
+                return Err("Missing ')' in permission expression".to_string());
+            }
+            *pos += 1;
+            Ok(expr)
+        }
+        token => Err(format!("Unexpected {token:?} in permission expression")),
+    }
+}
+
+/// Roles, permissions and scopes listed in a user's claims, as arrays or
+/// space-separated strings.
+fn claim_names(claims: &serde_json::Value) -> HashSet<&str> {
+    let mut names = HashSet::new();
+    for key in ["roles", "role", "permissions", "scope", "scopes"] {
+        match claims.get(key) {
+            Some(serde_json::Value::Array(items)) => {
+                names.extend(items.iter().filter_map(|item| item.as_str()));
+            }
+            Some(serde_json::Value::String(value)) => names.extend(value.split_whitespace()),
+            _ => {}
+        }
+    }
+    names
+}
+
+/// Guard that requires a permission expression to hold for the user.
+///
+/// The claims are read from the request context: the user set by the app
+/// (`user`) or the JWT claims set by the auth middleware (`jwt_claims`).
+#[derive(Debug, Clone)]
+pub struct ExpressionGuard {
+    expr: PermissionExpr,
+    source: String,
+    claims_keys: Vec<String>,
+}
+
+impl ExpressionGuard {
+    /// Create a guard for `expression`.
+    pub fn new(expression: &str) -> Result<Self, String> {
+        Ok(Self {
+            expr: PermissionExpr::parse(expression)?,
+            source: expression.trim().to_string(),
+            claims_keys: vec!["user".to_string(), "jwt_claims".to_string()],
+        })
+    }
+
+    /// Read the claims from this context key only.
+    pub fn claims_key(mut self, key: &str) -> Self {
+        self.claims_keys = vec![key.to_string()];
+        self
+    }
+
+    /// The expression, as written.
+    pub fn expression(&self) -> &str {
+        &self.source
+    }
+}
+
+impl Guard for ExpressionGuard {
+    fn check(&self, request: &Request) -> GuardResult {
+        let claims = self
+            .claims_keys
+            .iter()
+            .find_map(|key| request.context.get(key))
+            .ok_or_else(|| GuardError::Unauthorized("Authentication required".to_string()))?;
+        if self.expr.evaluate(&claim_names(claims)) {
+            Ok(())
+        } else {
+            Err(GuardError::Forbidden(format!("Requires {}", self.source)))
+        }
+    }
+
+    fn name(&self) -> &str {
+        "expression_guard"
+    }
+
+    fn priority(&self) -> i32 {
+        -30
+    }
+}
+
+/// Python-exposed permission expression guard, returned by
+/// `cello.guards.require()`.
+#[pyclass(name = "Require")]
+pub struct PyRequire {
+    guard: ExpressionGuard,
+}
+
+impl PyRequire {
+    /// The guard, for evaluation by the guards middleware.
+    pub fn guard(&self) -> &ExpressionGuard {
+        &self.guard
+    }
+}
+
+#[pymethods]
+impl PyRequire {
+    #[new]
+    #[pyo3(signature = (expression, claims_key=None))]
+    pub fn new(expression: &str, claims_key: Option<&str>) -> PyResult<Self> {
+        let guard =
+            ExpressionGuard::new(expression).map_err(pyo3::exceptions::PyValueError::new_err)?;
+        Ok(Self {
+            guard: match claims_key {
+                Some(key) => guard.claims_key(key),
+                None => guard,
+            },
+        })
+    }
+
+    /// The expression, as written.
+    #[getter]
+    pub fn expression(&self) -> &str {
+        self.guard.expression()
+    }
+
+    /// Check a request, raising `ForbiddenError` or `UnauthorizedError`.
+    pub fn __call__(&self, py: Python<'_>, request: PyRef<'_, Request>) -> PyResult<bool> {
+        self.guard
+            .check(&request)
+            .map(|()| true)
+            .map_err(|e| to_pyerr(py, e))
+    }
+
+    fn __repr__(&self) -> String {
+        format!("require({:?})", self.guard.expression())
+    }
+}
+
 /// Guard that calls a Python function.
 pub struct PythonGuard {
     handler: PyObject,
@@ -456,10 +804,10 @@ impl Guard for PythonGuard {
     fn check(&self, request: &Request) -> GuardResult {
         Python::with_gil(|py| {
             // Call the Python guard with the request
-            let result = self
-                .handler
-                .call1(py, (request.clone(),))
-                .map_err(|e| GuardError::Custom(format!("Python guard error: {e}"), 500))?;
+            let result = self.handler.call1(py, (request.clone(),)).map_err(|e| {
+                rejection(py, &e)
+                    .unwrap_or_else(|| GuardError::Custom(format!("Python guard error: {e}"), 500))
+            })?;
 
             // Check if result is None (pass) or raises error (fail) or returns False (fail)
             if result.is_none(py) {
@@ -510,8 +858,13 @@ impl GuardsMiddleware {
 
     /// Add a guard.
     pub fn add_guard<G: Guard + 'static>(&self, guard: G) {
+        self.add_shared_guard(Arc::new(guard));
+    }
+
+    /// Add a guard that is shared with other owners.
+    pub fn add_shared_guard(&self, guard: Arc<dyn Guard>) {
         let mut guards = self.guards.write();
-        guards.push(Arc::new(guard));
+        guards.push(guard);
         // Sort by priority
         guards.sort_by_key(|g| g.priority());
     }
@@ -693,6 +1046,74 @@ mod tests {
         assert!(guard.check(&request).is_ok());
     }
 
+    #[test]
+    fn test_permission_expression() {
+        let expr = PermissionExpr::parse("admin | (editor & owner)").unwrap();
+        assert_eq!(
+            expr,
+            PermissionExpr::Or(vec![
+                PermissionExpr::Claim("admin".to_string()),
+                PermissionExpr::And(vec![
+                    PermissionExpr::Claim("editor".to_string()),
+                    PermissionExpr::Claim("owner".to_string()),
+                ]),
+            ])
+        );
+        let claims = |names: &[&'static str]| names.iter().copied().collect::<HashSet<_>>();
+        assert!(expr.evaluate(&claims(&["admin"])));
+        assert!(expr.evaluate(&claims(&["editor", "owner"])));
+        assert!(!expr.evaluate(&claims(&["editor"])));
+
+        // `&` binds tighter than `|`; `!` tighter than both
+        let expr = PermissionExpr::parse("a || b && !c").unwrap();
+        assert!(expr.evaluate(&claims(&["b"])));
+        assert!(!expr.evaluate(&claims(&["b", "c"])));
+        assert!(PermissionExpr::parse("users:write & !users:banned").is_ok());
+
+        for invalid in [
+            "",
+            "admin |",
+            "(admin",
+            "admin)",
+            "admin editor",
+            "admin $ x",
+        ] {
+            assert!(PermissionExpr::parse(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_expression_guard() {
+        let guard = ExpressionGuard::new("admin | (editor & posts:write)").unwrap();
+        let mut request = Request::default();
+        assert_eq!(guard.check(&request).unwrap_err().status(), 401);
+
+        request.context.insert(
+            "user".to_string(),
+            serde_json::json!({"roles": ["editor"], "permissions": ["posts:read"]}),
+        );
+        let err = guard.check(&request).unwrap_err();
+        assert_eq!(err.status(), 403);
+        assert_eq!(err.message(), "Requires admin | (editor & posts:write)");
+
+        // JWT claims with an OAuth scope string
+        let mut request = Request::default();
+        request.context.insert(
+            "jwt_claims".to_string(),
+            serde_json::json!({"role": "editor", "scope": "posts:read posts:write"}),
+        );
+        assert!(guard.check(&request).is_ok());
+    }
+
+    #[test]
+    fn test_scoped_guard() {
+        let guard = ScopedGuard::new(Arc::new(AuthenticatedGuard::new()), "/admin/");
+        assert!(guard.should_run("/admin"));
+        assert!(guard.should_run("/admin/users"));
+        assert!(!guard.should_run("/administrator"));
+        assert!(ScopedGuard::new(Arc::new(AuthenticatedGuard::new()), "/").should_run("/x"));
+    }
+
     #[test]
     fn test_custom_guard() {
         let guard = CustomGuard::new("ip_whitelist", |request: &Request| {
@@ -700,7 +1121,6 @@ mod tests {
                 .headers
                 .get("x-forwarded-for")
                 .or_else(|| request.headers.get("x-real-ip"))
-                .map(|s| s.as_str())
                 .unwrap_or("unknown");
 
             if ip == "127.0.0.1" || ip == "::1" {
@@ -711,15 +1131,11 @@ mod tests {
         });
 
         let mut request = Request::default();
-        request
-            .headers
-            .insert("x-real-ip".to_string(), "127.0.0.1".to_string());
+        request.headers.insert("x-real-ip", "127.0.0.1");
 
         assert!(guard.check(&request).is_ok());
 
-        request
-            .headers
-            .insert("x-real-ip".to_string(), "192.168.1.1".to_string());
+        request.headers.insert("x-real-ip", "192.168.1.1");
         assert!(guard.check(&request).is_err());
     }
 }
@@ -0,0 +1,1010 @@
+//! Idempotency keys for Cello Framework.
+//!
+//! Clients retry requests whose response they never saw, which for a
+//! payment or an order means doing the work twice. With idempotency keys a
+//! client sends the same `Idempotency-Key` header on each retry, and:
+//! - The first request runs and its response is stored for the TTL
+//! - Retries get the stored response, marked `Idempotent-Replayed: true`
+//! - A retry arriving while the first request still runs gets `409`
+//! - Reusing a key for a different request (path, query or body)
+//!   gets `422`
+//!
+//! Keys are scoped to the caller (the authenticated user, or else the
+//! `Authorization` header), the method and the route, so a key sent by one
+//! client never replays a response stored for another.
+//!
+//! Responses with a 5xx or `429` status are not stored, so retrying them
+//! runs the handler again. `enable_idempotency()` stores keys in memory, so
+//! each worker process keeps its own. `RedisIdempotencyStore` shares them
+//! between cluster workers through a Redis server, but the `"redis"` backend
+//! raises `RuntimeError` while `enable_redis()` only provides an in-process
+//! client.
+//!
+//! # Example
+//! ```python
+//! from cello import idempotent
+//!
+//! app.enable_idempotency(ttl=86400)
+//!
+//! @app.post("/payments")
+//! @idempotent(required=True)
+//! def create_payment(request):
+//!     ...
+//! ```
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::redis::{RedisClient, RedisValue};
+use super::{AsyncMiddleware, MiddlewareAction, MiddlewareError, MiddlewareResult};
+use crate::request::Request;
+use crate::response::Response;
+
+/// Header marking a replayed response.
+pub const REPLAYED_HEADER: &str = "Idempotent-Replayed";
+
+/// Longest accepted idempotency key.
+const MAX_KEY_LENGTH: usize = 255;
+
+/// Request context entry carrying a claimed key from `before_async` to
+/// `after_async`.
+const CONTEXT_KEY: &str = "__idempotency";
+
+// ============================================================================
+// Configuration
+// ============================================================================
+
+/// Idempotency configuration.
+#[derive(Clone, Debug)]
+pub struct IdempotencyConfig {
+    /// Request header carrying the key
+    pub header: String,
+    /// Methods checked by the app-wide middleware
+    pub methods: Vec<String>,
+    /// How long a response is replayed for
+    pub ttl: Duration,
+    /// How long a key stays claimed by a request that never finishes, e.g.
+    /// because its worker died
+    pub lock_timeout: Duration,
+    /// Reject requests without a key with `400`
+    pub required: bool,
+    /// Path prefixes checked by the app-wide middleware (all if empty)
+    pub include_paths: Vec<String>,
+    /// Path prefixes skipped by the app-wide middleware
+    pub exclude_paths: Vec<String>,
+}
+
+impl Default for IdempotencyConfig {
+    fn default() -> Self {
+        Self {
+            header: "Idempotency-Key".to_string(),
+            methods: vec!["POST".to_string(), "PATCH".to_string()],
+            ttl: Duration::from_secs(24 * 60 * 60),
+            lock_timeout: Duration::from_secs(60),
+            required: false,
+            include_paths: Vec::new(),
+            exclude_paths: Vec::new(),
+        }
+    }
+}
+
+impl IdempotencyConfig {
+    /// Create a configuration with defaults.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the key header.
+    pub fn with_header(mut self, header: &str) -> Self {
+        self.header = header.to_string();
+        self
+    }
+
+    /// Set the methods checked by the app-wide middleware.
+    pub fn with_methods(mut self, methods: Vec<&str>) -> Self {
+        self.methods = methods.iter().map(|m| m.to_ascii_uppercase()).collect();
+        self
+    }
+
+    /// Set how long responses are replayed for.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Set how long an unfinished request holds its key.
+    pub fn with_lock_timeout(mut self, timeout: Duration) -> Self {
+        self.lock_timeout = timeout;
+        self
+    }
+
+    /// Require a key on every checked request.
+    pub fn with_required(mut self, required: bool) -> Self {
+        self.required = required;
+        self
+    }
+
+    /// Only check requests under this path prefix.
+    pub fn include_path(mut self, path: &str) -> Self {
+        self.include_paths.push(path.to_string());
+        self
+    }
+
+    /// Don't check requests under this path prefix.
+    pub fn exclude_path(mut self, path: &str) -> Self {
+        self.exclude_paths.push(path.to_string());
+        self
+    }
+}
+
+// ============================================================================
+// Stored Responses
+// ============================================================================
+
+/// A response stored for replay.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StoredResponse {
+    /// Status code
+    pub status: u16,
+    /// Headers, including `Content-Type`
+    pub headers: Vec<(String, String)>,
+    /// Body
+    #[serde(with = "base64_body")]
+    pub body: Vec<u8>,
+}
+
+impl StoredResponse {
+    /// Capture a response.
+    pub fn from_response(response: &Response) -> Self {
+        let mut headers: Vec<_> = response
+            .headers
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        headers.sort();
+        Self {
+            status: response.status,
+            headers,
+            body: response.body_bytes().to_vec(),
+        }
+    }
+
+    /// Rebuild the response, marked as a replay.
+    pub fn to_response(&self) -> Response {
+        let mut response = Response::new(self.status);
+        response.set_body(self.body.clone());
+        for (key, value) in &self.headers {
+            response.set_header(key, value);
+        }
+        response.set_header(REPLAYED_HEADER, "true");
+        response
+    }
+}
+
+mod base64_body {
+    use base64::Engine;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(body: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&base64::engine::general_purpose::STANDARD.encode(body))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// State of a key in a store.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+enum Record {
+    /// A request holds the key and has not finished
+    Pending { fingerprint: String },
+    /// A request finished with `response`
+    Complete {
+        fingerprint: String,
+        response: StoredResponse,
+    },
+}
+
+/// Result of claiming a key.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Claim {
+    /// The key was free and is now held by the caller
+    Acquired,
+    /// Another request holds the key
+    InProgress { fingerprint: String },
+    /// A request already finished with this key
+    Completed {
+        fingerprint: String,
+        response: StoredResponse,
+    },
+}
+
+impl From<Record> for Claim {
+    fn from(record: Record) -> Self {
+        match record {
+            Record::Pending { fingerprint } => Claim::InProgress { fingerprint },
+            Record::Complete {
+                fingerprint,
+                response,
+            } => Claim::Completed {
+                fingerprint,
+                response,
+            },
+        }
+    }
+}
+
+// ============================================================================
+// Stores
+// ============================================================================
+
+/// Storage for idempotency keys.
+///
+/// A request first claims its key with the fingerprint of its content, then
+/// either completes it with the response or releases it so that a retry
+/// can run.
+pub trait IdempotencyStore: Send + Sync {
+    /// Claim `key` for `lock_ttl` if it is free, or report who has it.
+    fn claim(
+        &self,
+        key: &str,
+        fingerprint: &str,
+        lock_ttl: Duration,
+    ) -> Result<Claim, IdempotencyError>;
+
+    /// Store the response of the request holding `key` for `ttl`.
+    fn complete(
+        &self,
+        key: &str,
+        fingerprint: &str,
+        response: &StoredResponse,
+        ttl: Duration,
+    ) -> Result<(), IdempotencyError>;
+
+    /// Free `key` if the request with `fingerprint` still holds it.
+    fn release(&self, key: &str, fingerprint: &str) -> Result<(), IdempotencyError>;
+
+    /// Store name for diagnostics.
+    fn name(&self) -> &str;
+}
+
+/// In-process idempotency store.
+#[derive(Default)]
+pub struct InMemoryIdempotencyStore {
+    records: Mutex<HashMap<String, (Record, Instant)>>,
+}
+
+impl InMemoryIdempotencyStore {
+    /// Create an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of live keys.
+    pub fn len(&self) -> usize {
+        let now = Instant::now();
+        self.records
+            .lock()
+            .values()
+            .filter(|(_, expires)| *expires > now)
+            .count()
+    }
+
+    /// Whether the store holds no live keys.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl IdempotencyStore for InMemoryIdempotencyStore {
+    fn claim(
+        &self,
+        key: &str,
+        fingerprint: &str,
+        lock_ttl: Duration,
+    ) -> Result<Claim, IdempotencyError> {
+        let now = Instant::now();
+        let mut records = self.records.lock();
+        if let Some((record, expires)) = records.get(key) {
+            if *expires > now {
+                return Ok(record.clone().into());
+            }
+        }
+        // Expired keys are dropped as new ones come in
+        records.retain(|_, (_, expires)| *expires > now);
+        let pending = Record::Pending {
+            fingerprint: fingerprint.to_string(),
+        };
+        records.insert(key.to_string(), (pending, now + lock_ttl));
+        Ok(Claim::Acquired)
+    }
+
+    fn complete(
+        &self,
+        key: &str,
+        fingerprint: &str,
+        response: &StoredResponse,
+        ttl: Duration,
+    ) -> Result<(), IdempotencyError> {
+        let complete = Record::Complete {
+            fingerprint: fingerprint.to_string(),
+            response: response.clone(),
+        };
+        self.records
+            .lock()
+            .insert(key.to_string(), (complete, Instant::now() + ttl));
+        Ok(())
+    }
+
+    fn release(&self, key: &str, fingerprint: &str) -> Result<(), IdempotencyError> {
+        let mut records = self.records.lock();
+        let held = matches!(
+            records.get(key),
+            Some((Record::Pending { fingerprint: held }, _)) if held == fingerprint
+        );
+        if held {
+            records.remove(key);
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> &str {
+        "memory"
+    }
+}
+
+/// Redis idempotency store.
+///
+/// Keys are claimed with `SET NX PX`, so concurrent requests on different
+/// workers see each other's claims, and stored as JSON.
+pub struct RedisIdempotencyStore {
+    client: Arc<dyn RedisClient>,
+    key_prefix: String,
+}
+
+impl RedisIdempotencyStore {
+    /// Create a store on a Redis connection.
+    pub fn new(client: Arc<dyn RedisClient>) -> Self {
+        Self {
+            client,
+            key_prefix: "idempotency:".to_string(),
+        }
+    }
+
+    /// Prefix for Redis keys (default `idempotency:`).
+    pub fn with_key_prefix(mut self, prefix: &str) -> Self {
+        self.key_prefix = prefix.to_string();
+        self
+    }
+
+    fn key(&self, key: &str) -> String {
+        format!("{}{key}", self.key_prefix)
+    }
+
+    fn pending(fingerprint: &str) -> Result<String, IdempotencyError> {
+        encode(&Record::Pending {
+            fingerprint: fingerprint.to_string(),
+        })
+    }
+}
+
+fn encode(record: &Record) -> Result<String, IdempotencyError> {
+    serde_json::to_string(record).map_err(|e| IdempotencyError::Serialization(e.to_string()))
+}
+
+impl IdempotencyStore for RedisIdempotencyStore {
+    fn claim(
+        &self,
+        key: &str,
+        fingerprint: &str,
+        lock_ttl: Duration,
+    ) -> Result<Claim, IdempotencyError> {
+        let key = self.key(key);
+        let pending = Self::pending(fingerprint)?;
+        // A key expiring between SET NX and GET is claimed on the second try
+        for _ in 0..2 {
+            if self.client.set_nx(&key, &pending, lock_ttl)? {
+                return Ok(Claim::Acquired);
+            }
+            let Some(value) = self.client.get(&key)? else {
+                continue;
+            };
+            let json = match &value {
+                RedisValue::String(s) => s.as_bytes(),
+                RedisValue::Bytes(b) => b.as_slice(),
+                _ => {
+                    return Err(IdempotencyError::Serialization(format!(
+                        "bad value at {key}"
+                    )))
+                }
+            };
+            let record: Record = serde_json::from_slice(json)
+                .map_err(|e| IdempotencyError::Serialization(e.to_string()))?;
+            return Ok(record.into());
+        }
+        Ok(Claim::InProgress {
+            fingerprint: fingerprint.to_string(),
+        })
+    }
+
+    fn complete(
+        &self,
+        key: &str,
+        fingerprint: &str,
+        response: &StoredResponse,
+        ttl: Duration,
+    ) -> Result<(), IdempotencyError> {
+        let complete = encode(&Record::Complete {
+            fingerprint: fingerprint.to_string(),
+            response: response.clone(),
+        })?;
+        self.client
+            .set(&self.key(key), RedisValue::String(complete), Some(ttl))?;
+        Ok(())
+    }
+
+    fn release(&self, key: &str, fingerprint: &str) -> Result<(), IdempotencyError> {
+        self.client
+            .delete_if_eq(&self.key(key), &Self::pending(fingerprint)?)?;
+        Ok(())
+    }
+
+    fn name(&self) -> &str {
+        "redis"
+    }
+}
+
+// ============================================================================
+// Middleware
+// ============================================================================
+
+/// What to do with a request carrying an idempotency key.
+#[derive(Debug)]
+pub enum Admission {
+    /// Run the handler; the reservation, if any, stores its response
+    Proceed(Option<Reservation>),
+    /// Send the stored response of an earlier request instead
+    Replay(Response),
+}
+
+/// Idempotency-key middleware.
+///
+/// Added to the middleware chain it checks requests by method and path;
+/// attached to a route with `HandlerMeta::set_idempotency` it checks every
+/// request to that route.
+#[derive(Clone)]
+pub struct IdempotencyMiddleware {
+    store: Arc<dyn IdempotencyStore>,
+    config: IdempotencyConfig,
+}
+
+impl IdempotencyMiddleware {
+    /// Create a middleware on `store` with defaults.
+    pub fn new(store: Arc<dyn IdempotencyStore>) -> Self {
+        Self::with_config(store, IdempotencyConfig::default())
+    }
+
+    /// Create a middleware on `store`.
+    pub fn with_config(store: Arc<dyn IdempotencyStore>, config: IdempotencyConfig) -> Self {
+        Self { store, config }
+    }
+
+    /// Create a middleware with an in-memory store.
+    pub fn in_memory() -> Self {
+        Self::new(Arc::new(InMemoryIdempotencyStore::new()))
+    }
+
+    /// The configuration.
+    pub fn config(&self) -> &IdempotencyConfig {
+        &self.config
+    }
+
+    /// The store.
+    pub fn store(&self) -> &Arc<dyn IdempotencyStore> {
+        &self.store
+    }
+
+    /// A copy for one route, sharing the store, with its own TTL and
+    /// whether the key is required.
+    pub fn for_route(&self, ttl: Option<Duration>, required: Option<bool>) -> Self {
+        let mut config = self.config.clone();
+        if let Some(ttl) = ttl {
+            config.ttl = ttl;
+        }
+        if let Some(required) = required {
+            config.required = required;
+        }
+        Self::with_config(self.store.clone(), config)
+    }
+
+    /// Whether the app-wide middleware checks `request`.
+    fn applies(&self, request: &Request) -> bool {
+        let path = request.path.as_str();
+        self.config.methods.contains(&request.method)
+            && !self
+                .config
+                .exclude_paths
+                .iter()
+                .any(|p| path.starts_with(p))
+            && (self.config.include_paths.is_empty()
+                || self
+                    .config
+                    .include_paths
+                    .iter()
+                    .any(|p| path.starts_with(p)))
+    }
+
+    /// Claim the request's key, or decide how to answer it without running
+    /// the handler.
+    pub fn admit(&self, request: &Request) -> Result<Admission, MiddlewareError> {
+        let key = match request.headers.get(&self.config.header) {
+            Some(key) => key.trim(),
+            None if self.config.required => {
+                return Err(MiddlewareError::bad_request(&format!(
+                    "Missing {} header",
+                    self.config.header
+                )));
+            }
+            None => return Ok(Admission::Proceed(None)),
+        };
+        if key.is_empty() || key.len() > MAX_KEY_LENGTH {
+            return Err(MiddlewareError::bad_request(&format!(
+                "{} must be 1 to {MAX_KEY_LENGTH} characters",
+                self.config.header
+            )));
+        }
+
+        let key = scoped_key(request, key);
+        let fingerprint = fingerprint(request);
+        let claim = self
+            .store
+            .claim(&key, &fingerprint, self.config.lock_timeout)
+            .map_err(|e| MiddlewareError::new(&e.to_string(), 503))?;
+        match claim {
+            Claim::Acquired => Ok(Admission::Proceed(Some(self.reservation(key, fingerprint)))),
+            Claim::InProgress { fingerprint: held }
+            | Claim::Completed {
+                fingerprint: held, ..
+            } if held != fingerprint => Err(MiddlewareError::new(
+                &format!(
+                    "{} was already used for a different request",
+                    self.config.header
+                ),
+                422,
+            )),
+            Claim::InProgress { .. } => Err(MiddlewareError::new(
+                &format!(
+                    "A request with this {} is still being processed",
+                    self.config.header
+                ),
+                409,
+            )),
+            Claim::Completed { response, .. } => Ok(Admission::Replay(response.to_response())),
+        }
+    }
+
+    fn reservation(&self, key: String, fingerprint: String) -> Reservation {
+        Reservation {
+            store: self.store.clone(),
+            key,
+            fingerprint,
+            ttl: self.config.ttl,
+            done: false,
+        }
+    }
+}
+
+impl std::fmt::Debug for IdempotencyMiddleware {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IdempotencyMiddleware")
+            .field("store", &self.store.name())
+            .field("config", &self.config)
+            .finish()
+    }
+}
+
+/// The store key for the client's `key`: prefixed with a hash of the caller,
+/// method and route, so equal keys from different callers never meet.
+fn scoped_key(request: &Request, key: &str) -> String {
+    let mut hasher = Sha256::new();
+    match request.context.get("user").and_then(|user| user.get("id")) {
+        Some(id) => {
+            hasher.update(b"user:");
+            hasher.update(id.to_string().as_bytes());
+        }
+        None => {
+            hasher.update(b"authorization:");
+            hasher.update(request.headers.get("Authorization").unwrap_or_default());
+        }
+    }
+    hasher.update([0]);
+    hasher.update(request.method.as_bytes());
+    hasher.update([0]);
+    hasher.update(request.route.as_deref().unwrap_or(&request.path).as_bytes());
+    format!("{:x}:{key}", hasher.finalize())
+}
+
+/// Hash of what makes two requests the same: method, path, query and body.
+fn fingerprint(request: &Request) -> String {
+    let mut query: Vec<_> = request.query_params.iter().collect();
+    query.sort();
+    let mut hasher = Sha256::new();
+    hasher.update(request.method.as_bytes());
+    hasher.update([0]);
+    hasher.update(request.path.as_bytes());
+    for (name, value) in query {
+        hasher.update([0]);
+        hasher.update(name.as_bytes());
+        hasher.update([b'=']);
+        hasher.update(value.as_bytes());
+    }
+    hasher.update([0]);
+    hasher.update(&request.body);
+    format!("{:x}", hasher.finalize())
+}
+
+/// A claimed key, released when dropped unless the response was stored.
+pub struct Reservation {
+    store: Arc<dyn IdempotencyStore>,
+    key: String,
+    fingerprint: String,
+    ttl: Duration,
+    done: bool,
+}
+
+impl Reservation {
+    /// Store `response` for replay; responses that a retry should not get
+    /// (5xx, `429`, streamed and file bodies) release the key instead.
+    pub fn finish(mut self, response: &Response) {
+        self.done = true;
+        let replayable = response.status < 500
+            && response.status != 429
+            && !response.is_streaming()
+            && !response.is_file();
+        let result = if replayable {
+            let stored = StoredResponse::from_response(response);
+            self.store
+                .complete(&self.key, &self.fingerprint, &stored, self.ttl)
+        } else {
+            self.store.release(&self.key, &self.fingerprint)
+        };
+        if let Err(e) = result {
+            tracing::warn!(key = %self.key, "Failed to store idempotent response: {e}");
+        }
+    }
+
+    /// The claimed key as stored, scoped to the caller and route.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        if !self.done {
+            if let Err(e) = self.store.release(&self.key, &self.fingerprint) {
+                tracing::warn!(key = %self.key, "Failed to release idempotency key: {e}");
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for Reservation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Reservation")
+            .field("key", &self.key)
+            .finish()
+    }
+}
+
+impl AsyncMiddleware for IdempotencyMiddleware {
+    fn before_async<'a>(
+        &'a self,
+        request: &'a mut Request,
+    ) -> Pin<Box<dyn Future<Output = MiddlewareResult> + Send + 'a>> {
+        Box::pin(async move {
+            if !self.applies(request) {
+                return Ok(MiddlewareAction::Continue);
+            }
+            match self.admit(request)? {
+                Admission::Replay(response) => Ok(MiddlewareAction::Stop(response)),
+                Admission::Proceed(None) => Ok(MiddlewareAction::Continue),
+                Admission::Proceed(Some(mut reservation)) => {
+                    // Handed over to after_async through the request context
+                    reservation.done = true;
+                    request.context.insert(
+                        CONTEXT_KEY.to_string(),
+                        serde_json::json!([reservation.key, reservation.fingerprint]),
+                    );
+                    Ok(MiddlewareAction::Continue)
+                }
+            }
+        })
+    }
+
+    fn after_async<'a>(
+        &'a self,
+        request: &'a Request,
+        response: &'a mut Response,
+    ) -> Pin<Box<dyn Future<Output = MiddlewareResult> + Send + 'a>> {
+        Box::pin(async move {
+            let claimed = request
+                .context
+                .get(CONTEXT_KEY)
+                .and_then(|v| serde_json::from_value::<(String, String)>(v.clone()).ok());
+            if let Some((key, fingerprint)) = claimed {
+                self.reservation(key, fingerprint).finish(response);
+            }
+            Ok(MiddlewareAction::Continue)
+        })
+    }
+
+    fn priority(&self) -> i32 {
+        -20 // Run after auth and rate limits
+    }
+
+    fn name(&self) -> &str {
+        "idempotency"
+    }
+}
+
+// ============================================================================
+// Errors
+// ============================================================================
+
+/// Idempotency store error.
+#[derive(Debug, Clone)]
+pub enum IdempotencyError {
+    /// The store could not be reached
+    Backend(String),
+    /// A stored record could not be encoded or decoded
+    Serialization(String),
+}
+
+impl std::fmt::Display for IdempotencyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IdempotencyError::Backend(msg) => write!(f, "Idempotency store error: {msg}"),
+            IdempotencyError::Serialization(msg) => {
+                write!(f, "Idempotency record error: {msg}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for IdempotencyError {}
+
+impl From<super::redis::RedisError> for IdempotencyError {
+    fn from(e: super::redis::RedisError) -> Self {
+        IdempotencyError::Backend(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::redis::{MockRedisClient, RedisConfig};
+
+    fn request(key: Option<&str>, body: &str) -> Request {
+        let mut request = Request::default();
+        request.method = "POST".to_string();
+        request.path = "/payments".to_string();
+        request.body = body.as_bytes().to_vec();
+        if let Some(key) = key {
+            request.headers.insert("Idempotency-Key", key);
+        }
+        request
+    }
+
+    fn created() -> Response {
+        let mut response = Response::new(201);
+        response.set_header("Content-Type", "application/json");
+        response.set_body(b"{\"id\":1}".to_vec());
+        response
+    }
+
+    fn reservation(admission: Admission) -> Reservation {
+        match admission {
+            Admission::Proceed(Some(reservation)) => reservation,
+            other => panic!("expected a reservation, got {other:?}"),
+        }
+    }
+
+    fn check_store(store: Arc<dyn IdempotencyStore>) {
+        let lock = Duration::from_secs(60);
+        let ttl = Duration::from_secs(60);
+        assert_eq!(store.claim("k", "a", lock).unwrap(), Claim::Acquired);
+        assert_eq!(
+            store.claim("k", "a", lock).unwrap(),
+            Claim::InProgress {
+                fingerprint: "a".to_string()
+            }
+        );
+
+        // Only the holder releases
+        store.release("k", "b").unwrap();
+        assert!(matches!(
+            store.claim("k", "a", lock).unwrap(),
+            Claim::InProgress { .. }
+        ));
+        store.release("k", "a").unwrap();
+        assert_eq!(store.claim("k", "a", lock).unwrap(), Claim::Acquired);
+
+        let stored = StoredResponse::from_response(&created());
+        store.complete("k", "a", &stored, ttl).unwrap();
+        assert_eq!(
+            store.claim("k", "a", lock).unwrap(),
+            Claim::Completed {
+                fingerprint: "a".to_string(),
+                response: stored,
+            }
+        );
+        // Completed keys are kept on release
+        store.release("k", "a").unwrap();
+        assert!(matches!(
+            store.claim("k", "a", lock).unwrap(),
+            Claim::Completed { .. }
+        ));
+    }
+
+    #[test]
+    fn test_in_memory_store() {
+        check_store(Arc::new(InMemoryIdempotencyStore::new()));
+    }
+
+    #[test]
+    fn test_in_memory_store_expiry() {
+        let store = InMemoryIdempotencyStore::new();
+        let short = Duration::from_millis(10);
+        assert_eq!(store.claim("k", "a", short).unwrap(), Claim::Acquired);
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(store.is_empty());
+        assert_eq!(store.claim("k", "b", short).unwrap(), Claim::Acquired);
+    }
+
+    #[test]
+    fn test_redis_store() {
+        let client = Arc::new(MockRedisClient::new(RedisConfig::default()));
+        check_store(Arc::new(RedisIdempotencyStore::new(client.clone())));
+        assert!(client.exists("idempotency:k").unwrap());
+    }
+
+    #[test]
+    fn test_replay() {
+        let middleware = IdempotencyMiddleware::in_memory();
+        let first = reservation(middleware.admit(&request(Some("k1"), "{}")).unwrap());
+        assert!(first.key().ends_with(":k1"));
+
+        // A retry while the first request runs conflicts
+        let err = middleware.admit(&request(Some("k1"), "{}")).unwrap_err();
+        assert_eq!(err.status, 409);
+
+        first.finish(&created());
+        match middleware.admit(&request(Some("k1"), "{}")).unwrap() {
+            Admission::Replay(response) => {
+                assert_eq!(response.status, 201);
+                assert_eq!(response.body_bytes(), b"{\"id\":1}");
+                assert_eq!(
+                    response.headers.get("Content-Type").map(String::as_str),
+                    Some("application/json")
+                );
+                assert_eq!(
+                    response.headers.get(REPLAYED_HEADER).map(String::as_str),
+                    Some("true")
+                );
+            }
+            other => panic!("expected a replay, got {other:?}"),
+        }
+
+        // Same key, different body
+        let err = middleware
+            .admit(&request(Some("k1"), "{\"x\":1}"))
+            .unwrap_err();
+        assert_eq!(err.status, 422);
+    }
+
+    #[test]
+    fn test_keys_are_scoped_to_the_caller() {
+        let middleware = IdempotencyMiddleware::in_memory();
+        let as_user = |authorization: &str| {
+            let mut request = request(Some("k1"), "{}");
+            request.headers.insert("Authorization", authorization);
+            request
+        };
+        reservation(middleware.admit(&as_user("Bearer alice")).unwrap()).finish(&created());
+        assert!(matches!(
+            middleware.admit(&as_user("Bearer alice")).unwrap(),
+            Admission::Replay(_)
+        ));
+        reservation(middleware.admit(&as_user("Bearer bob")).unwrap());
+
+        // An authenticated user is identified by ID, whatever the header
+        let mut carol = as_user("Bearer alice");
+        carol
+            .context
+            .insert("user".to_string(), serde_json::json!({"id": "carol"}));
+        reservation(middleware.admit(&carol).unwrap());
+
+        // Each route has its own keys
+        let mut refund = as_user("Bearer alice");
+        refund.route = Some("/refunds".into());
+        reservation(middleware.admit(&refund).unwrap());
+    }
+
+    #[test]
+    fn test_failures_are_not_replayed() {
+        let middleware = IdempotencyMiddleware::in_memory();
+        let first = reservation(middleware.admit(&request(Some("k"), "")).unwrap());
+        first.finish(&Response::new(503));
+        let retry = reservation(middleware.admit(&request(Some("k"), "")).unwrap());
+
+        // Dropped without finishing, e.g. on an early return
+        drop(retry);
+        reservation(middleware.admit(&request(Some("k"), "")).unwrap());
+    }
+
+    #[test]
+    fn test_missing_key() {
+        let middleware = IdempotencyMiddleware::in_memory();
+        assert!(matches!(
+            middleware.admit(&request(None, "")).unwrap(),
+            Admission::Proceed(None)
+        ));
+
+        let route = middleware.for_route(None, Some(true));
+        assert_eq!(route.admit(&request(None, "")).unwrap_err().status, 400);
+        assert_eq!(route.admit(&request(Some(""), "")).unwrap_err().status, 400);
+        let long = "k".repeat(MAX_KEY_LENGTH + 1);
+        assert_eq!(
+            route.admit(&request(Some(&long), "")).unwrap_err().status,
+            400
+        );
+    }
+
+    #[tokio::test]
+    async fn test_middleware_chain() {
+        let config = IdempotencyConfig::new().exclude_path("/internal");
+        let middleware =
+            IdempotencyMiddleware::with_config(Arc::new(InMemoryIdempotencyStore::new()), config);
+
+        let mut first = request(Some("k"), "{}");
+        let action = middleware.before_async(&mut first).await.unwrap();
+        assert!(matches!(action, MiddlewareAction::Continue));
+        let mut response = created();
+        middleware
+            .after_async(&first.clone_without_body(), &mut response)
+            .await
+            .unwrap();
+
+        let mut retry = request(Some("k"), "{}");
+        match middleware.before_async(&mut retry).await.unwrap() {
+            MiddlewareAction::Stop(response) => assert_eq!(response.status, 201),
+            MiddlewareAction::Continue => panic!("expected a replay"),
+        }
+
+        // Unchecked methods and paths pass through
+        let mut get = request(Some("k"), "{}");
+        get.method = "GET".to_string();
+        assert!(matches!(
+            middleware.before_async(&mut get).await.unwrap(),
+            MiddlewareAction::Continue
+        ));
+        let mut internal = request(Some("k"), "{}");
+        internal.path = "/internal/jobs".to_string();
+        assert!(matches!(
+            middleware.before_async(&mut internal).await.unwrap(),
+            MiddlewareAction::Continue
+        ));
+    }
+}
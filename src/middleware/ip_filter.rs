@@ -0,0 +1,525 @@
+//! IP allow/deny list middleware.
+//!
+//! Requests are matched against CIDR ranges by client IP:
+//! - Addresses in the deny list are rejected.
+//! - If the allow list is not empty, addresses outside it are rejected.
+//!
+//! The client IP is the connection's peer address. When the peer is a
+//! trusted proxy, `X-Forwarded-For` is read right to left and the first
+//! address that is not a trusted proxy is the client, so clients cannot
+//! spoof their address by sending the header themselves.
+//!
+//! Ranges can be added and removed while the server runs, and rejected
+//! requests are counted in `cello_ip_filter_blocked_total{reason}`.
+
+use parking_lot::RwLock;
+use prometheus::{IntCounterVec, Opts, Registry};
+use pyo3::prelude::*;
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use super::{path_matches_skip, Middleware, MiddlewareAction, MiddlewareError, MiddlewareResult};
+use crate::request::Request;
+
+// ============================================================================
+// CIDR Ranges
+// ============================================================================
+
+/// An IPv4 or IPv6 address range in CIDR notation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    /// Check if the range contains `ip`. IPv4-mapped IPv6 addresses match
+    /// IPv4 ranges.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.network, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                mask_u32(u32::from(ip), self.prefix) == u32::from(net)
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                mask_u128(u128::from(ip), self.prefix) == u128::from(net)
+            }
+            _ => false,
+        }
+    }
+}
+
+fn mask_u32(bits: u32, prefix: u8) -> u32 {
+    match prefix {
+        0 => 0,
+        _ => bits & (u32::MAX << (32 - u32::from(prefix))),
+    }
+}
+
+fn mask_u128(bits: u128, prefix: u8) -> u128 {
+    match prefix {
+        0 => 0,
+        _ => bits & (u128::MAX << (128 - u32::from(prefix))),
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = String;
+
+    /// Parse `"10.0.0.0/8"`, `"2001:db8::/32"`, or a single address.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid IP range '{s}'");
+        let (addr, prefix) = match s.trim().split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s.trim(), None),
+        };
+        let addr = IpAddr::from_str(addr)
+            .map_err(|_| invalid())?
+            .to_canonical();
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .parse::<u8>()
+                .ok()
+                .filter(|p| *p <= max)
+                .ok_or_else(invalid)?,
+            None => max,
+        };
+        // Normalize the network address so equal ranges compare equal
+        let network = match addr {
+            IpAddr::V4(v4) => IpAddr::V4(mask_u32(u32::from(v4), prefix).into()),
+            IpAddr::V6(v6) => IpAddr::V6(mask_u128(u128::from(v6), prefix).into()),
+        };
+        Ok(Cidr { network, prefix })
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix)
+    }
+}
+
+fn parse_ranges(ranges: &[String]) -> Result<Vec<Cidr>, String> {
+    ranges.iter().map(|r| r.parse()).collect()
+}
+
+// ============================================================================
+// Filter
+// ============================================================================
+
+/// Why a request was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockReason {
+    /// The client IP is in the deny list.
+    Denied,
+    /// The allow list is set and does not contain the client IP.
+    NotAllowed,
+    /// The client IP could not be determined while an allow list is set.
+    Unknown,
+}
+
+impl BlockReason {
+    fn label(self) -> &'static str {
+        match self {
+            BlockReason::Denied => "denied",
+            BlockReason::NotAllowed => "not_allowed",
+            BlockReason::Unknown => "unknown",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Rules {
+    allow: Vec<Cidr>,
+    deny: Vec<Cidr>,
+}
+
+/// Shared, runtime-updatable allow/deny lists.
+pub struct IpFilter {
+    rules: RwLock<Rules>,
+    trusted_proxies: Vec<Cidr>,
+    blocked: IntCounterVec,
+}
+
+impl IpFilter {
+    /// Create a filter from CIDR ranges.
+    pub fn new(
+        allow: &[String],
+        deny: &[String],
+        trusted_proxies: &[String],
+    ) -> Result<Self, String> {
+        let blocked = IntCounterVec::new(
+            Opts::new(
+                "cello_ip_filter_blocked_total",
+                "Requests rejected by the IP filter",
+            ),
+            &["reason"],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(IpFilter {
+            rules: RwLock::new(Rules {
+                allow: parse_ranges(allow)?,
+                deny: parse_ranges(deny)?,
+            }),
+            trusted_proxies: parse_ranges(trusted_proxies)?,
+            blocked,
+        })
+    }
+
+    /// Add a range to the allow list. Returns false if already present.
+    pub fn allow(&self, range: &str) -> Result<bool, String> {
+        let cidr = range.parse()?;
+        Ok(insert(&mut self.rules.write().allow, cidr))
+    }
+
+    /// Add a range to the deny list. Returns false if already present.
+    pub fn deny(&self, range: &str) -> Result<bool, String> {
+        let cidr = range.parse()?;
+        Ok(insert(&mut self.rules.write().deny, cidr))
+    }
+
+    /// Remove a range from both lists. Returns whether it was present.
+    pub fn remove(&self, range: &str) -> Result<bool, String> {
+        let cidr: Cidr = range.parse()?;
+        let mut rules = self.rules.write();
+        let before = rules.allow.len() + rules.deny.len();
+        rules.allow.retain(|c| *c != cidr);
+        rules.deny.retain(|c| *c != cidr);
+        Ok(rules.allow.len() + rules.deny.len() < before)
+    }
+
+    /// The allow list, in CIDR notation.
+    pub fn allowed(&self) -> Vec<String> {
+        self.rules
+            .read()
+            .allow
+            .iter()
+            .map(Cidr::to_string)
+            .collect()
+    }
+
+    /// The deny list, in CIDR notation.
+    pub fn denied(&self) -> Vec<String> {
+        self.rules.read().deny.iter().map(Cidr::to_string).collect()
+    }
+
+    fn is_trusted(&self, ip: IpAddr) -> bool {
+        self.trusted_proxies.iter().any(|c| c.contains(ip))
+    }
+
+    /// Resolve the client IP of a request, trusting forwarding headers only
+    /// from trusted proxies.
+    pub fn client_ip(&self, request: &Request) -> Option<IpAddr> {
+        let peer = request.remote_addr.map(|addr| addr.ip().to_canonical());
+        if let Some(peer) = peer {
+            if !self.is_trusted(peer) {
+                return Some(peer);
+            }
+        }
+
+        let forwarded: Vec<IpAddr> = request
+            .headers
+            .get("x-forwarded-for")
+            .map(|xff| {
+                xff.split(',')
+                    .filter_map(|ip| IpAddr::from_str(ip.trim()).ok())
+                    .map(|ip| ip.to_canonical())
+                    .collect()
+            })
+            .unwrap_or_default();
+        forwarded
+            .iter()
+            .rev()
+            .find(|ip| !self.is_trusted(**ip))
+            .or(forwarded.first())
+            .copied()
+            .or_else(|| {
+                request
+                    .headers
+                    .get("x-real-ip")
+                    .and_then(|ip| IpAddr::from_str(ip.trim()).ok())
+            })
+            .or(peer)
+    }
+
+    /// Check a client IP against the lists.
+    pub fn check(&self, ip: Option<IpAddr>) -> Result<(), BlockReason> {
+        let rules = self.rules.read();
+        match ip {
+            Some(ip) if rules.deny.iter().any(|c| c.contains(ip)) => Err(BlockReason::Denied),
+            Some(ip) if !rules.allow.is_empty() && !rules.allow.iter().any(|c| c.contains(ip)) => {
+                Err(BlockReason::NotAllowed)
+            }
+            None if !rules.allow.is_empty() => Err(BlockReason::Unknown),
+            _ => Ok(()),
+        }
+    }
+
+    fn record_block(&self, reason: BlockReason) {
+        self.blocked.with_label_values(&[reason.label()]).inc();
+    }
+
+    /// Number of requests rejected for `reason`.
+    pub fn blocked(&self, reason: BlockReason) -> u64 {
+        self.blocked.with_label_values(&[reason.label()]).get()
+    }
+
+    /// Expose the block counter in a Prometheus registry.
+    pub fn register_metrics(&self, registry: &Registry) -> Result<(), String> {
+        registry
+            .register(Box::new(self.blocked.clone()))
+            .map_err(|e| e.to_string())
+    }
+}
+
+fn insert(ranges: &mut Vec<Cidr>, cidr: Cidr) -> bool {
+    if ranges.contains(&cidr) {
+        false
+    } else {
+        ranges.push(cidr);
+        true
+    }
+}
+
+// ============================================================================
+// Middleware
+// ============================================================================
+
+/// Rejects requests whose client IP the filter does not admit.
+pub struct IpFilterMiddleware {
+    filter: Arc<IpFilter>,
+    status: u16,
+    exclude_paths: Vec<String>,
+}
+
+impl IpFilterMiddleware {
+    /// Create the middleware; rejected requests get `status` (403 by default).
+    pub fn new(filter: Arc<IpFilter>) -> Self {
+        IpFilterMiddleware {
+            filter,
+            status: 403,
+            exclude_paths: Vec::new(),
+        }
+    }
+
+    /// Set the status code of rejected requests.
+    pub fn status(mut self, status: u16) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Skip the filter for a path and its sub-paths.
+    pub fn exclude(mut self, path: &str) -> Self {
+        self.exclude_paths.push(path.to_string());
+        self
+    }
+}
+
+impl Middleware for IpFilterMiddleware {
+    fn before(&self, request: &mut Request) -> MiddlewareResult {
+        let ip = self.filter.client_ip(request);
+        match self.filter.check(ip) {
+            Ok(()) => Ok(MiddlewareAction::Continue),
+            Err(reason) => {
+                self.filter.record_block(reason);
+                Err(MiddlewareError::new("Forbidden", self.status).with_code("IP_BLOCKED"))
+            }
+        }
+    }
+
+    fn priority(&self) -> i32 {
+        -140
+    }
+
+    fn name(&self) -> &str {
+        "ip_filter"
+    }
+
+    fn should_run(&self, path: &str) -> bool {
+        !self
+            .exclude_paths
+            .iter()
+            .any(|p| path_matches_skip(path, p))
+    }
+
+    fn skip_paths(&self) -> &[String] {
+        &self.exclude_paths
+    }
+}
+
+// ============================================================================
+// Python API
+// ============================================================================
+
+/// Runtime handle to the app's IP filter, from `app.ip_filter`.
+#[pyclass(name = "IpFilter")]
+pub struct PyIpFilter {
+    inner: Arc<IpFilter>,
+}
+
+impl PyIpFilter {
+    pub fn new(inner: Arc<IpFilter>) -> Self {
+        PyIpFilter { inner }
+    }
+}
+
+#[pymethods]
+impl PyIpFilter {
+    /// Add a CIDR range to the allow list.
+    fn allow(&self, range: &str) -> PyResult<bool> {
+        self.inner
+            .allow(range)
+            .map_err(pyo3::exceptions::PyValueError::new_err)
+    }
+
+    /// Add a CIDR range to the deny list.
+    fn deny(&self, range: &str) -> PyResult<bool> {
+        self.inner
+            .deny(range)
+            .map_err(pyo3::exceptions::PyValueError::new_err)
+    }
+
+    /// Remove a CIDR range from both lists.
+    fn remove(&self, range: &str) -> PyResult<bool> {
+        self.inner
+            .remove(range)
+            .map_err(pyo3::exceptions::PyValueError::new_err)
+    }
+
+    /// The allow list, in CIDR notation.
+    #[getter]
+    fn allowed(&self) -> Vec<String> {
+        self.inner.allowed()
+    }
+
+    /// The deny list, in CIDR notation.
+    #[getter]
+    fn denied(&self) -> Vec<String> {
+        self.inner.denied()
+    }
+
+    /// Check whether an address would be admitted.
+    fn is_allowed(&self, ip: &str) -> PyResult<bool> {
+        let ip = IpAddr::from_str(ip).map_err(|_| {
+            pyo3::exceptions::PyValueError::new_err(format!("Invalid IP address '{ip}'"))
+        })?;
+        Ok(self.inner.check(Some(ip.to_canonical())).is_ok())
+    }
+
+    /// Rejected request counts by reason, with a `"total"`.
+    fn stats(&self) -> std::collections::HashMap<&'static str, u64> {
+        let reasons = [
+            BlockReason::Denied,
+            BlockReason::NotAllowed,
+            BlockReason::Unknown,
+        ];
+        let mut stats: std::collections::HashMap<&'static str, u64> = reasons
+            .iter()
+            .map(|r| (r.label(), self.inner.blocked(*r)))
+            .collect();
+        stats.insert("total", stats.values().sum());
+        stats
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "IpFilter(allowed={:?}, denied={:?})",
+            self.inner.allowed(),
+            self.inner.denied()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(peer: &str, xff: Option<&str>) -> Request {
+        let mut request = Request::new("GET", "/");
+        if let Some(xff) = xff {
+            request.headers.insert("x-forwarded-for", xff);
+        }
+        request.remote_addr = Some(format!("{peer}:40000").parse().unwrap());
+        request
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_cidr() {
+        let net: Cidr = "10.1.2.3/8".parse().unwrap();
+        assert_eq!(net.to_string(), "10.0.0.0/8");
+        assert!(net.contains(ip("10.200.0.1")));
+        assert!(net.contains(ip("::ffff:10.0.0.1")));
+        assert!(!net.contains(ip("11.0.0.1")));
+        assert!(!net.contains(ip("::1")));
+
+        let v6: Cidr = "2001:db8::/32".parse().unwrap();
+        assert!(v6.contains(ip("2001:db8:ffff::1")));
+        assert!(!v6.contains(ip("2001:db9::1")));
+
+        let any: Cidr = "0.0.0.0/0".parse().unwrap();
+        assert!(any.contains(ip("203.0.113.9")));
+        assert_eq!(
+            "192.168.0.1".parse::<Cidr>().unwrap().to_string(),
+            "192.168.0.1/32"
+        );
+        assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+        assert!("example.com".parse::<Cidr>().is_err());
+    }
+
+    #[test]
+    fn test_client_ip() {
+        let filter = IpFilter::new(&[], &[], &["10.0.0.0/8".into()]).unwrap();
+        // Untrusted peers cannot spoof X-Forwarded-For
+        assert_eq!(
+            filter.client_ip(&request("203.0.113.5", Some("1.1.1.1"))),
+            Some(ip("203.0.113.5"))
+        );
+        // Behind trusted proxies, the rightmost untrusted hop is the client
+        assert_eq!(
+            filter.client_ip(&request(
+                "10.0.0.2",
+                Some("6.6.6.6, 198.51.100.7, 10.0.0.9")
+            )),
+            Some(ip("198.51.100.7"))
+        );
+        assert_eq!(
+            filter.client_ip(&request("10.0.0.2", None)),
+            Some(ip("10.0.0.2"))
+        );
+    }
+
+    #[test]
+    fn test_middleware() {
+        let filter = Arc::new(
+            IpFilter::new(&["192.168.0.0/16".into()], &["192.168.66.0/24".into()], &[]).unwrap(),
+        );
+        let mw = IpFilterMiddleware::new(filter.clone());
+        assert!(mw.before(&mut request("192.168.1.1", None)).is_ok());
+        assert_eq!(
+            mw.before(&mut request("192.168.66.6", None))
+                .unwrap_err()
+                .status,
+            403
+        );
+        assert!(mw.before(&mut request("8.8.8.8", None)).is_err());
+        assert_eq!(filter.blocked(BlockReason::Denied), 1);
+        assert_eq!(filter.blocked(BlockReason::NotAllowed), 1);
+
+        assert!(filter.allow("8.8.8.0/24").unwrap());
+        assert!(!filter.allow("8.8.8.1/24").unwrap());
+        assert!(mw.before(&mut request("8.8.8.8", None)).is_ok());
+        assert!(filter.remove("192.168.66.0/24").unwrap());
+        assert!(mw.before(&mut request("192.168.66.6", None)).is_ok());
+        assert!(!filter.remove("192.168.66.0/24").unwrap());
+
+        let registry = Registry::new();
+        filter.register_metrics(&registry).unwrap();
+        assert_eq!(registry.gather().len(), 1);
+    }
+}
@@ -0,0 +1,767 @@
+//! In-memory cache store for Cello.
+//!
+//! Provides:
+//! - A sharded concurrent map, so lookups on different keys rarely contend
+//! - LRU or LFU eviction once an entry count or memory bound is reached
+//! - Per-entry TTL with a configurable default
+//! - Hit, miss, eviction and expiration statistics
+//!
+//! The same store backs `app.cache` and, through [`CacheStore`], response
+//! caching.
+//!
+//! # Example
+//! ```python
+//! app.enable_cache(max_entries=50_000, max_memory=64 * 1024 * 1024, policy="lfu", ttl=300)
+//!
+//! @app.get("/users/{id}")
+//! async def get_user(request, db=Depends("database")):
+//!     user_id = request.params["id"]
+//!     return await app.cache.get_or_set(
+//!         f"user:{user_id}",
+//!         lambda: db.fetch_one("SELECT * FROM users WHERE id = ?", user_id),
+//!         ttl=60,
+//!     )
+//! ```
+
+use parking_lot::Mutex;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict, PyString};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::cache::{CacheError, CacheStore, CachedResponse};
+
+// ============================================================================
+// Configuration
+// ============================================================================
+
+/// Which entry to evict when the cache is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EvictionPolicy {
+    /// Least recently used.
+    #[default]
+    Lru,
+    /// Least frequently used, ties broken by least recently used.
+    Lfu,
+}
+
+impl FromStr for EvictionPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "lru" => Ok(EvictionPolicy::Lru),
+            "lfu" => Ok(EvictionPolicy::Lfu),
+            other => Err(format!(
+                "unknown eviction policy '{other}' (expected lru or lfu)"
+            )),
+        }
+    }
+}
+
+/// In-memory cache configuration.
+#[derive(Debug, Clone)]
+pub struct MemoryCacheConfig {
+    /// Maximum number of entries.
+    pub max_entries: usize,
+    /// Maximum estimated size of keys and values in bytes, if bounded.
+    pub max_memory: Option<usize>,
+    /// Eviction policy.
+    pub policy: EvictionPolicy,
+    /// TTL for entries stored without one, if they should expire.
+    pub default_ttl: Option<Duration>,
+    /// Number of independently locked shards.
+    pub shards: usize,
+}
+
+impl Default for MemoryCacheConfig {
+    fn default() -> Self {
+        Self {
+            max_entries: 10000,
+            max_memory: None,
+            policy: EvictionPolicy::Lru,
+            default_ttl: None,
+            shards: 16,
+        }
+    }
+}
+
+impl MemoryCacheConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+
+    pub fn max_memory(mut self, bytes: usize) -> Self {
+        self.max_memory = Some(bytes);
+        self
+    }
+
+    pub fn policy(mut self, policy: EvictionPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn default_ttl(mut self, ttl: Duration) -> Self {
+        self.default_ttl = Some(ttl);
+        self
+    }
+
+    pub fn shards(mut self, shards: usize) -> Self {
+        self.shards = shards;
+        self
+    }
+}
+
+// ============================================================================
+// Statistics
+// ============================================================================
+
+/// Snapshot of cache statistics.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MemoryCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub inserts: u64,
+    pub evictions: u64,
+    pub expirations: u64,
+    pub entries: usize,
+    pub memory_bytes: usize,
+}
+
+impl MemoryCacheStats {
+    /// Fraction of lookups that were hits.
+    pub fn hit_ratio(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            self.hits as f64 / lookups as f64
+        }
+    }
+}
+
+#[derive(Default)]
+struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    inserts: AtomicU64,
+    evictions: AtomicU64,
+    expirations: AtomicU64,
+}
+
+// ============================================================================
+// Cache
+// ============================================================================
+
+struct Entry<V> {
+    value: V,
+    size: usize,
+    expires_at: Option<Instant>,
+    last_access: u64,
+    frequency: u64,
+}
+
+impl<V> Entry<V> {
+    fn is_expired(&self, now: Instant) -> bool {
+        self.expires_at.is_some_and(|at| now >= at)
+    }
+}
+
+struct Shard<V> {
+    entries: HashMap<String, Entry<V>>,
+    memory: usize,
+    max_entries: usize,
+    max_memory: Option<usize>,
+    clock: u64,
+}
+
+impl<V> Shard<V> {
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    fn remove(&mut self, key: &str) -> Option<Entry<V>> {
+        let entry = self.entries.remove(key)?;
+        self.memory -= entry.size;
+        Some(entry)
+    }
+
+    fn is_full(&self) -> bool {
+        self.entries.len() > self.max_entries
+            || self.max_memory.is_some_and(|max| self.memory > max)
+    }
+
+    fn purge_expired(&mut self, now: Instant) -> usize {
+        let before = self.entries.len();
+        self.entries.retain(|_, entry| !entry.is_expired(now));
+        self.memory = self.entries.values().map(|entry| entry.size).sum();
+        before - self.entries.len()
+    }
+
+    /// The entry to evict, never `keep` (the one just inserted).
+    fn victim(&self, policy: EvictionPolicy, keep: &str) -> Option<String> {
+        let entries = self.entries.iter().filter(|(key, _)| key.as_str() != keep);
+        let victim = match policy {
+            EvictionPolicy::Lru => entries.min_by_key(|(_, entry)| entry.last_access),
+            EvictionPolicy::Lfu => {
+                entries.min_by_key(|(_, entry)| (entry.frequency, entry.last_access))
+            }
+        };
+        victim.map(|(key, _)| key.clone())
+    }
+}
+
+/// Sharded in-memory cache with LRU or LFU eviction and per-entry TTL.
+///
+/// Each shard holds an equal part of the entry and memory bounds, so the
+/// whole cache never exceeds them.
+pub struct MemoryCache<V> {
+    shards: Vec<Mutex<Shard<V>>>,
+    config: MemoryCacheConfig,
+    counters: Counters,
+}
+
+impl<V: Clone> MemoryCache<V> {
+    pub fn new(config: MemoryCacheConfig) -> Self {
+        let count = config.shards.clamp(1, config.max_entries.max(1));
+        let shards = (0..count)
+            .map(|i| {
+                Mutex::new(Shard {
+                    entries: HashMap::new(),
+                    memory: 0,
+                    max_entries: split(config.max_entries, count, i),
+                    max_memory: config.max_memory.map(|max| split(max, count, i)),
+                    clock: 0,
+                })
+            })
+            .collect();
+        Self {
+            shards,
+            config,
+            counters: Counters::default(),
+        }
+    }
+
+    /// The configuration this cache was created with.
+    pub fn config(&self) -> &MemoryCacheConfig {
+        &self.config
+    }
+
+    fn shard(&self, key: &str) -> &Mutex<Shard<V>> {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        &self.shards[hasher.finish() as usize % self.shards.len()]
+    }
+
+    /// Get a value, counting a hit or miss.
+    pub fn get(&self, key: &str) -> Option<V> {
+        let now = Instant::now();
+        let mut shard = self.shard(key).lock();
+        let tick = shard.tick();
+        let value = match shard.entries.get_mut(key) {
+            Some(entry) if !entry.is_expired(now) => {
+                entry.last_access = tick;
+                entry.frequency += 1;
+                Some(entry.value.clone())
+            }
+            Some(_) => {
+                shard.remove(key);
+                self.counters.expirations.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+            None => None,
+        };
+        let counter = if value.is_some() {
+            &self.counters.hits
+        } else {
+            &self.counters.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        value
+    }
+
+    /// Whether a live entry exists, without touching it or the statistics.
+    pub fn contains(&self, key: &str) -> bool {
+        let now = Instant::now();
+        self.shard(key)
+            .lock()
+            .entries
+            .get(key)
+            .is_some_and(|entry| !entry.is_expired(now))
+    }
+
+    /// Store a value whose estimated size is `size` bytes, expiring after
+    /// `ttl` or the default TTL.
+    ///
+    /// Returns `false` if the value alone exceeds the memory bound and was
+    /// not stored.
+    pub fn insert(&self, key: &str, value: V, size: usize, ttl: Option<Duration>) -> bool {
+        let now = Instant::now();
+        let size = size + key.len();
+        let mut shard = self.shard(key).lock();
+        shard.remove(key);
+        if shard.max_memory.is_some_and(|max| size > max) {
+            return false;
+        }
+
+        let tick = shard.tick();
+        shard.entries.insert(
+            key.to_string(),
+            Entry {
+                value,
+                size,
+                expires_at: ttl.or(self.config.default_ttl).map(|ttl| now + ttl),
+                last_access: tick,
+                frequency: 1,
+            },
+        );
+        shard.memory += size;
+        self.counters.inserts.fetch_add(1, Ordering::Relaxed);
+
+        if shard.is_full() {
+            let expired = shard.purge_expired(now);
+            self.counters
+                .expirations
+                .fetch_add(expired as u64, Ordering::Relaxed);
+        }
+        while shard.is_full() {
+            let Some(victim) = shard.victim(self.config.policy, key) else {
+                break;
+            };
+            shard.remove(&victim);
+            self.counters.evictions.fetch_add(1, Ordering::Relaxed);
+        }
+        true
+    }
+
+    /// Remove an entry, returning whether it existed.
+    pub fn remove(&self, key: &str) -> bool {
+        self.shard(key).lock().remove(key).is_some()
+    }
+
+    /// Keep only the entries for which `keep` returns true.
+    pub fn retain(&self, mut keep: impl FnMut(&str, &V) -> bool) {
+        for shard in &self.shards {
+            let mut shard = shard.lock();
+            shard.entries.retain(|key, entry| keep(key, &entry.value));
+            shard.memory = shard.entries.values().map(|entry| entry.size).sum();
+        }
+    }
+
+    /// Number of entries whose key starts with `prefix`.
+    pub fn count_prefixed(&self, prefix: &str) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| {
+                let shard = shard.lock();
+                shard
+                    .entries
+                    .keys()
+                    .filter(|k| k.starts_with(prefix))
+                    .count()
+            })
+            .sum()
+    }
+
+    /// Drop expired entries, returning how many were removed.
+    pub fn purge_expired(&self) -> usize {
+        let now = Instant::now();
+        let expired: usize = self
+            .shards
+            .iter()
+            .map(|shard| shard.lock().purge_expired(now))
+            .sum();
+        self.counters
+            .expirations
+            .fetch_add(expired as u64, Ordering::Relaxed);
+        expired
+    }
+
+    /// Remove every entry.
+    pub fn clear(&self) {
+        for shard in &self.shards {
+            let mut shard = shard.lock();
+            shard.entries.clear();
+            shard.memory = 0;
+        }
+    }
+
+    /// Number of entries, including expired ones not yet purged.
+    pub fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.lock().entries.len())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Current statistics.
+    pub fn stats(&self) -> MemoryCacheStats {
+        let (entries, memory_bytes) = self.shards.iter().fold((0, 0), |(n, m), shard| {
+            let shard = shard.lock();
+            (n + shard.entries.len(), m + shard.memory)
+        });
+        MemoryCacheStats {
+            hits: self.counters.hits.load(Ordering::Relaxed),
+            misses: self.counters.misses.load(Ordering::Relaxed),
+            inserts: self.counters.inserts.load(Ordering::Relaxed),
+            evictions: self.counters.evictions.load(Ordering::Relaxed),
+            expirations: self.counters.expirations.load(Ordering::Relaxed),
+            entries,
+            memory_bytes,
+        }
+    }
+}
+
+/// Share `total` across `parts`, giving the remainder to the first shards.
+fn split(total: usize, parts: usize, index: usize) -> usize {
+    total / parts + usize::from(index < total % parts)
+}
+
+impl Default for MemoryCache<CachedResponse> {
+    fn default() -> Self {
+        Self::new(MemoryCacheConfig::default())
+    }
+}
+
+#[async_trait::async_trait]
+impl CacheStore for MemoryCache<CachedResponse> {
+    async fn get(&self, key: &str) -> Result<Option<CachedResponse>, CacheError> {
+        Ok(MemoryCache::get(self, key))
+    }
+
+    async fn set(&self, key: &str, response: CachedResponse) -> Result<(), CacheError> {
+        let size = response.body.len()
+            + response
+                .headers
+                .iter()
+                .map(|(name, value)| name.len() + value.len())
+                .sum::<usize>();
+        let ttl = Duration::from_secs(response.ttl);
+        self.insert(key, response, size, Some(ttl));
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), CacheError> {
+        self.remove(key);
+        Ok(())
+    }
+
+    async fn clear(&self) -> Result<(), CacheError> {
+        MemoryCache::clear(self);
+        Ok(())
+    }
+
+    async fn invalidate_tags(&self, tags: &[String]) -> Result<(), CacheError> {
+        self.retain(|_, response| !response.tags.iter().any(|t| tags.contains(t)));
+        Ok(())
+    }
+}
+
+// ============================================================================
+// Python Bindings
+// ============================================================================
+
+/// Estimated size of a Python value in bytes.
+fn weigh(value: &PyAny) -> usize {
+    if let Ok(bytes) = value.downcast::<PyBytes>() {
+        return bytes.as_bytes().len();
+    }
+    if let Ok(text) = value.downcast::<PyString>() {
+        return text.len().unwrap_or(0);
+    }
+    value
+        .call_method0("__sizeof__")
+        .and_then(|size| size.extract())
+        .unwrap_or(64)
+}
+
+/// Python handle to the application cache, available as `app.cache`.
+///
+/// Values are stored by reference; mutating a cached object mutates the
+/// cached value. A handle with a key prefix (such as a tenant's) sees only
+/// its own entries.
+#[pyclass(name = "Cache")]
+#[derive(Clone)]
+pub struct PyCache {
+    inner: Arc<MemoryCache<PyObject>>,
+    prefix: String,
+}
+
+impl PyCache {
+    pub fn new(inner: Arc<MemoryCache<PyObject>>) -> Self {
+        Self {
+            inner,
+            prefix: String::new(),
+        }
+    }
+
+    /// A handle whose keys are all prefixed with `prefix`.
+    pub fn with_prefix(inner: Arc<MemoryCache<PyObject>>, prefix: &str) -> Self {
+        Self {
+            inner,
+            prefix: prefix.to_string(),
+        }
+    }
+
+    fn key(&self, key: &str) -> String {
+        format!("{}{key}", self.prefix)
+    }
+}
+
+fn ttl_arg(ttl: Option<f64>) -> PyResult<Option<Duration>> {
+    ttl.map(|secs| {
+        Duration::try_from_secs_f64(secs).map_err(|_| {
+            pyo3::exceptions::PyValueError::new_err("ttl must be a non-negative number of seconds")
+        })
+    })
+    .transpose()
+}
+
+#[pymethods]
+impl PyCache {
+    /// Get a value, or `default` if it is missing or expired.
+    #[pyo3(signature = (key, default=None))]
+    fn get(&self, py: Python<'_>, key: &str, default: Option<PyObject>) -> PyObject {
+        self.inner
+            .get(&self.key(key))
+            .or(default)
+            .unwrap_or_else(|| py.None())
+    }
+
+    /// Store a value for `ttl` seconds, or the cache's default TTL.
+    ///
+    /// Returns `False` if the value exceeds the memory bound on its own.
+    #[pyo3(signature = (key, value, ttl=None))]
+    fn set(&self, key: &str, value: &PyAny, ttl: Option<f64>) -> PyResult<bool> {
+        let size = weigh(value);
+        Ok(self
+            .inner
+            .insert(&self.key(key), value.into(), size, ttl_arg(ttl)?))
+    }
+
+    /// Remove a value, returning whether it existed.
+    fn delete(&self, key: &str) -> bool {
+        self.inner.remove(&self.key(key))
+    }
+
+    /// Return the cached value for `key`, or call `factory`, cache its
+    /// result and return it. `factory` may return an awaitable, such as a
+    /// database query.
+    #[pyo3(signature = (key, factory, ttl=None))]
+    fn get_or_set<'py>(
+        &self,
+        py: Python<'py>,
+        key: String,
+        factory: &PyAny,
+        ttl: Option<f64>,
+    ) -> PyResult<&'py PyAny> {
+        let ttl = ttl_arg(ttl)?;
+        let key = self.key(&key);
+        if let Some(value) = self.inner.get(&key) {
+            return pyo3_asyncio::tokio::future_into_py(py, async move { Ok(value) });
+        }
+
+        let result = factory.call0()?;
+        let cache = self.inner.clone();
+        if result.hasattr("__await__")? {
+            let pending = pyo3_asyncio::tokio::into_future(result)?;
+            pyo3_asyncio::tokio::future_into_py(py, async move {
+                let value = pending.await?;
+                Python::with_gil(|py| {
+                    cache.insert(&key, value.clone_ref(py), weigh(value.as_ref(py)), ttl);
+                });
+                Ok(value)
+            })
+        } else {
+            cache.insert(&key, result.into(), weigh(result), ttl);
+            let value: PyObject = result.into();
+            pyo3_asyncio::tokio::future_into_py(py, async move { Ok(value) })
+        }
+    }
+
+    /// Drop expired entries, returning how many were removed.
+    fn purge_expired(&self) -> usize {
+        self.inner.purge_expired()
+    }
+
+    /// Remove every entry (only this prefix's entries if it has one).
+    fn clear(&self) {
+        if self.prefix.is_empty() {
+            self.inner.clear()
+        } else {
+            self.inner.retain(|key, _| !key.starts_with(&self.prefix))
+        }
+    }
+
+    /// Statistics as a dict.
+    fn stats(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let stats = self.inner.stats();
+        let dict = PyDict::new(py);
+        dict.set_item("hits", stats.hits)?;
+        dict.set_item("misses", stats.misses)?;
+        dict.set_item("hit_ratio", stats.hit_ratio())?;
+        dict.set_item("inserts", stats.inserts)?;
+        dict.set_item("evictions", stats.evictions)?;
+        dict.set_item("expirations", stats.expirations)?;
+        dict.set_item("entries", stats.entries)?;
+        dict.set_item("memory_bytes", stats.memory_bytes)?;
+        Ok(dict.into())
+    }
+
+    fn __contains__(&self, key: &str) -> bool {
+        self.inner.contains(&self.key(key))
+    }
+
+    fn __len__(&self) -> usize {
+        if self.prefix.is_empty() {
+            self.inner.len()
+        } else {
+            self.inner.count_prefixed(&self.prefix)
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        let config = self.inner.config();
+        format!(
+            "Cache(policy={:?}, entries={}, max_entries={})",
+            config.policy,
+            self.inner.len(),
+            config.max_entries
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache(policy: EvictionPolicy, max_entries: usize) -> MemoryCache<u32> {
+        MemoryCache::new(
+            MemoryCacheConfig::new()
+                .max_entries(max_entries)
+                .policy(policy)
+                .shards(1),
+        )
+    }
+
+    #[test]
+    fn test_lru_eviction() {
+        let cache = cache(EvictionPolicy::Lru, 2);
+        cache.insert("a", 1, 0, None);
+        cache.insert("b", 2, 0, None);
+        assert_eq!(cache.get("a"), Some(1));
+        cache.insert("c", 3, 0, None);
+
+        assert!(cache.contains("a"));
+        assert!(!cache.contains("b"));
+        assert!(cache.contains("c"));
+        assert_eq!(cache.stats().evictions, 1);
+    }
+
+    #[test]
+    fn test_lfu_eviction() {
+        let cache = cache(EvictionPolicy::Lfu, 2);
+        cache.insert("a", 1, 0, None);
+        cache.insert("b", 2, 0, None);
+        cache.get("a");
+        cache.get("a");
+        cache.get("b");
+        cache.insert("c", 3, 0, None);
+
+        assert!(cache.contains("a"));
+        assert!(!cache.contains("b"));
+        assert!(cache.contains("c"));
+
+        // The new entry is never the one evicted.
+        cache.insert("d", 4, 0, None);
+        assert!(cache.contains("a"));
+        assert!(cache.contains("d"));
+    }
+
+    #[test]
+    fn test_ttl_expiry() {
+        let cache = cache(EvictionPolicy::Lru, 10);
+        cache.insert("short", 1, 0, Some(Duration::ZERO));
+        cache.insert("long", 2, 0, Some(Duration::from_secs(60)));
+
+        assert_eq!(cache.get("short"), None);
+        assert_eq!(cache.get("long"), Some(2));
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.expirations), (1, 1, 1));
+        assert_eq!(stats.hit_ratio(), 0.5);
+    }
+
+    #[test]
+    fn test_memory_bound() {
+        let cache = MemoryCache::new(
+            MemoryCacheConfig::new()
+                .max_memory(100)
+                .shards(4)
+                .max_entries(1000),
+        );
+        for i in 0..20 {
+            cache.insert(&format!("k{i}"), i, 20, None);
+        }
+        assert!(cache.stats().memory_bytes <= 100);
+        assert!(!cache.insert("huge", 0, 500, None));
+        assert!(!cache.contains("huge"));
+    }
+
+    #[test]
+    fn test_sharded_entry_bound() {
+        let cache = MemoryCache::new(MemoryCacheConfig::new().max_entries(10).shards(4));
+        for i in 0..100 {
+            cache.insert(&format!("k{i}"), i, 0, None);
+        }
+        assert!(cache.len() <= 10);
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_policy_from_str() {
+        assert_eq!("LFU".parse::<EvictionPolicy>(), Ok(EvictionPolicy::Lfu));
+        assert_eq!("lru".parse::<EvictionPolicy>(), Ok(EvictionPolicy::Lru));
+        assert!("fifo".parse::<EvictionPolicy>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_response_store_tags() {
+        let store: MemoryCache<CachedResponse> = MemoryCache::default();
+        let response = |tags: Vec<&str>| CachedResponse {
+            body: b"ok".to_vec(),
+            status: 200,
+            headers: HashMap::new(),
+            cached_at: std::time::SystemTime::now(),
+            ttl: 60,
+            etag: None,
+            last_modified: None,
+            tags: tags.into_iter().map(String::from).collect(),
+        };
+        CacheStore::set(&store, "/users", response(vec!["users"]))
+            .await
+            .unwrap();
+        CacheStore::set(&store, "/posts", response(vec!["posts"]))
+            .await
+            .unwrap();
+
+        store.invalidate_tags(&["users".to_string()]).await.unwrap();
+        assert!(CacheStore::get(&store, "/users").await.unwrap().is_none());
+        assert!(CacheStore::get(&store, "/posts").await.unwrap().is_some());
+    }
+}
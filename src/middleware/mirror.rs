@@ -0,0 +1,440 @@
+//! Traffic mirroring for Cello Framework.
+//!
+//! Copies a sample of incoming requests to a secondary upstream, such as a
+//! new version of a service, to test it against production traffic:
+//! - Mirrored requests are sent in the background and their responses are
+//!   discarded, so clients never wait on or see the shadow upstream
+//! - A percentage of requests is sampled, optionally filtered by method
+//!   and path
+//! - Requests in flight to the shadow are capped; once the cap is reached
+//!   further copies are dropped rather than queued
+//! - Copies carry an `X-Mirrored-From` header so the shadow can tell them
+//!   apart, e.g. to skip side effects such as sending emails
+//!
+//! # Example
+//! ```python
+//! app.enable_mirroring("http://users-v2.internal:8000", percentage=10)
+//! ```
+
+use rand::Rng;
+use serde::Serialize;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::{AsyncMiddleware, MiddlewareAction, MiddlewareResult};
+use crate::request::Request;
+
+/// Header added to mirrored requests.
+pub const MIRROR_HEADER: &str = "X-Mirrored-From";
+
+/// Headers that describe the client connection rather than the request, and
+/// are not copied.
+const HOP_BY_HOP: &[&str] = &[
+    "connection",
+    "content-length",
+    "host",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+// ============================================================================
+// Configuration
+// ============================================================================
+
+/// Traffic mirroring configuration.
+#[derive(Clone, Debug)]
+pub struct MirrorConfig {
+    /// Base URL of the shadow upstream; the request path is appended
+    pub target: String,
+    /// Percentage of requests mirrored, from 0 to 100
+    pub percentage: f64,
+    /// Timeout for each mirrored request
+    pub timeout: Duration,
+    /// Mirror only these methods (all if empty)
+    pub methods: Vec<String>,
+    /// Mirror only requests under these path prefixes (all if empty)
+    pub include_paths: Vec<String>,
+    /// Never mirror requests under these path prefixes
+    pub exclude_paths: Vec<String>,
+    /// Most mirrored requests in flight at once
+    pub max_in_flight: usize,
+}
+
+impl MirrorConfig {
+    /// Mirror every request to `target`.
+    pub fn new(target: &str) -> Self {
+        Self {
+            target: target.trim_end_matches('/').to_string(),
+            percentage: 100.0,
+            timeout: Duration::from_secs(5),
+            methods: Vec::new(),
+            include_paths: Vec::new(),
+            exclude_paths: Vec::new(),
+            max_in_flight: 100,
+        }
+    }
+
+    /// Set the percentage of requests mirrored, clamped to 0..=100.
+    pub fn with_percentage(mut self, percentage: f64) -> Self {
+        self.percentage = percentage.clamp(0.0, 100.0);
+        self
+    }
+
+    /// Set the timeout of mirrored requests.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Mirror only these methods.
+    pub fn with_methods(mut self, methods: Vec<&str>) -> Self {
+        self.methods = methods.iter().map(|m| m.to_ascii_uppercase()).collect();
+        self
+    }
+
+    /// Mirror only requests under this path prefix.
+    pub fn include_path(mut self, path: &str) -> Self {
+        self.include_paths.push(path.to_string());
+        self
+    }
+
+    /// Never mirror requests under this path prefix.
+    pub fn exclude_path(mut self, path: &str) -> Self {
+        self.exclude_paths.push(path.to_string());
+        self
+    }
+
+    /// Set the most mirrored requests in flight at once.
+    pub fn with_max_in_flight(mut self, max: usize) -> Self {
+        self.max_in_flight = max;
+        self
+    }
+}
+
+// ============================================================================
+// Statistics
+// ============================================================================
+
+/// Counters of mirrored requests.
+#[derive(Debug, Default)]
+pub struct MirrorStats {
+    /// Copies sent
+    pub mirrored: AtomicU64,
+    /// Copies dropped because too many were in flight
+    pub dropped: AtomicU64,
+    /// Copies that failed or timed out
+    pub failed: AtomicU64,
+    in_flight: AtomicUsize,
+}
+
+impl MirrorStats {
+    /// Mirrored requests currently in flight.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Relaxed)
+    }
+
+    /// Current values of the counters.
+    pub fn snapshot(&self) -> MirrorSnapshot {
+        MirrorSnapshot {
+            mirrored: self.mirrored.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+            in_flight: self.in_flight(),
+        }
+    }
+}
+
+/// Point-in-time copy of [`MirrorStats`].
+#[derive(Debug, Clone, Serialize)]
+pub struct MirrorSnapshot {
+    pub mirrored: u64,
+    pub dropped: u64,
+    pub failed: u64,
+    pub in_flight: usize,
+}
+
+// ============================================================================
+// Middleware
+// ============================================================================
+
+/// Middleware copying sampled requests to a shadow upstream.
+pub struct MirrorMiddleware {
+    config: MirrorConfig,
+    client: reqwest::Client,
+    stats: Arc<MirrorStats>,
+}
+
+impl MirrorMiddleware {
+    /// Create a mirroring middleware.
+    pub fn new(config: MirrorConfig) -> Result<Self, String> {
+        let client = reqwest::Client::builder()
+            .timeout(config.timeout)
+            .build()
+            .map_err(|e| e.to_string())?;
+        Ok(Self {
+            config,
+            client,
+            stats: Arc::new(MirrorStats::default()),
+        })
+    }
+
+    /// The configuration.
+    pub fn config(&self) -> &MirrorConfig {
+        &self.config
+    }
+
+    /// Counters, shared with the middleware.
+    pub fn stats(&self) -> Arc<MirrorStats> {
+        self.stats.clone()
+    }
+
+    /// Whether `request` is eligible for mirroring, before sampling.
+    fn matches(&self, request: &Request) -> bool {
+        let path = request.path.as_str();
+        (self.config.methods.is_empty() || self.config.methods.contains(&request.method))
+            && !self
+                .config
+                .exclude_paths
+                .iter()
+                .any(|p| path.starts_with(p))
+            && (self.config.include_paths.is_empty()
+                || self
+                    .config
+                    .include_paths
+                    .iter()
+                    .any(|p| path.starts_with(p)))
+    }
+
+    fn sampled(&self) -> bool {
+        match self.config.percentage {
+            p if p >= 100.0 => true,
+            p if p <= 0.0 => false,
+            p => rand::thread_rng().gen_bool(p / 100.0),
+        }
+    }
+
+    /// Build the copy of `request` sent to the shadow upstream.
+    fn copy(&self, request: &Request) -> Result<reqwest::RequestBuilder, String> {
+        let method =
+            reqwest::Method::from_bytes(request.method.as_bytes()).map_err(|e| e.to_string())?;
+        let url = format!("{}{}", self.config.target, request.path);
+        let mut builder = self.client.request(method, url);
+        if !request.query_params.is_empty() {
+            builder = builder.query(&request.query_params);
+        }
+        for (name, value) in request.headers.iter() {
+            if !HOP_BY_HOP.contains(&name) {
+                builder = builder.header(name, value);
+            }
+        }
+        let origin = request
+            .headers
+            .get("host")
+            .filter(|host| !host.is_empty())
+            .unwrap_or("cello");
+        Ok(builder
+            .header(MIRROR_HEADER, origin)
+            .body(request.body.clone()))
+    }
+
+    /// Send a copy of `request` in the background if it is sampled.
+    fn mirror(&self, request: &Request) {
+        if !self.matches(request) || !self.sampled() {
+            return;
+        }
+        let stats = self.stats.clone();
+        let taken = stats
+            .in_flight
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+                (n < self.config.max_in_flight).then_some(n + 1)
+            });
+        if taken.is_err() {
+            stats.dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        let copy = match self.copy(request) {
+            Ok(copy) => copy,
+            Err(e) => {
+                tracing::warn!("Failed to mirror {} {}: {e}", request.method, request.path);
+                stats.failed.fetch_add(1, Ordering::Relaxed);
+                stats.in_flight.fetch_sub(1, Ordering::AcqRel);
+                return;
+            }
+        };
+        stats.mirrored.fetch_add(1, Ordering::Relaxed);
+        tokio::spawn(async move {
+            // The shadow's response is read to completion and discarded
+            let result = match copy.send().await {
+                Ok(response) => response.bytes().await.map(drop),
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                tracing::debug!("Mirrored request failed: {e}");
+                stats.failed.fetch_add(1, Ordering::Relaxed);
+            }
+            stats.in_flight.fetch_sub(1, Ordering::AcqRel);
+        });
+    }
+}
+
+impl AsyncMiddleware for MirrorMiddleware {
+    fn before_async<'a>(
+        &'a self,
+        request: &'a mut Request,
+    ) -> Pin<Box<dyn Future<Output = MiddlewareResult> + Send + 'a>> {
+        Box::pin(async move {
+            // Streamed bodies are read by the handler and can't be copied
+            if request.body_stream.is_none() {
+                self.mirror(request);
+            }
+            Ok(MiddlewareAction::Continue)
+        })
+    }
+
+    fn priority(&self) -> i32 {
+        -120 // Run early, before middleware that rewrites the request
+    }
+
+    fn name(&self) -> &str {
+        "mirror"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use http_body_util::{BodyExt, Full};
+    use hyper::server::conn::http1;
+    use hyper::service::service_fn;
+    use hyper_util::rt::TokioIo;
+    use parking_lot::Mutex;
+    use std::convert::Infallible;
+
+    type Seen = Arc<Mutex<Vec<(String, String, Option<String>, Vec<u8>)>>>;
+
+    /// A shadow upstream recording the requests it receives.
+    async fn shadow() -> (String, Seen) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let seen: Seen = Arc::default();
+        let record = seen.clone();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let record = record.clone();
+                tokio::spawn(async move {
+                    let service = service_fn(move |req: hyper::Request<hyper::body::Incoming>| {
+                        let record = record.clone();
+                        async move {
+                            let method = req.method().to_string();
+                            let uri = req.uri().to_string();
+                            let mirrored = req
+                                .headers()
+                                .get(MIRROR_HEADER)
+                                .and_then(|v| v.to_str().ok())
+                                .map(str::to_string);
+                            let body = req.into_body().collect().await.unwrap().to_bytes();
+                            record.lock().push((method, uri, mirrored, body.to_vec()));
+                            Ok::<_, Infallible>(hyper::Response::new(Full::new(Bytes::new())))
+                        }
+                    });
+                    let _ = http1::Builder::new()
+                        .serve_connection(TokioIo::new(stream), service)
+                        .await;
+                });
+            }
+        });
+        (url, seen)
+    }
+
+    fn request(method: &str, path: &str) -> Request {
+        let mut request = Request::default();
+        request.method = method.to_string();
+        request.path = path.to_string();
+        request.headers.insert("host", "api.example.com");
+        request
+    }
+
+    async fn settle(stats: &MirrorStats) {
+        for _ in 0..200 {
+            if stats.in_flight() == 0 {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("mirrored requests did not finish");
+    }
+
+    #[tokio::test]
+    async fn test_mirror_copies_request() {
+        let (url, seen) = shadow().await;
+        let middleware = MirrorMiddleware::new(MirrorConfig::new(&url)).unwrap();
+        let mut req = request("POST", "/orders");
+        req.query_params.insert("dry".to_string(), "1".to_string());
+        req.body = b"{\"id\":1}".to_vec();
+
+        let action = middleware.before_async(&mut req).await.unwrap();
+        assert!(matches!(action, MiddlewareAction::Continue));
+        settle(&middleware.stats()).await;
+
+        let seen = seen.lock();
+        assert_eq!(seen.len(), 1);
+        let (method, uri, mirrored, body) = &seen[0];
+        assert_eq!(method, "POST");
+        assert_eq!(uri, "/orders?dry=1");
+        assert_eq!(mirrored.as_deref(), Some("api.example.com"));
+        assert_eq!(body, b"{\"id\":1}");
+        assert_eq!(middleware.stats().mirrored.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn test_mirror_filters_and_samples() {
+        let (url, seen) = shadow().await;
+        let config = MirrorConfig::new(&url)
+            .with_methods(vec!["get"])
+            .exclude_path("/health");
+        let middleware = MirrorMiddleware::new(config).unwrap();
+        for (method, path) in [("GET", "/a"), ("POST", "/a"), ("GET", "/health")] {
+            middleware
+                .before_async(&mut request(method, path))
+                .await
+                .unwrap();
+        }
+        settle(&middleware.stats()).await;
+        assert_eq!(seen.lock().len(), 1);
+
+        let none = MirrorMiddleware::new(MirrorConfig::new(&url).with_percentage(0.0)).unwrap();
+        none.before_async(&mut request("GET", "/a")).await.unwrap();
+        assert_eq!(none.stats().mirrored.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn test_mirror_failures_and_cap() {
+        // Nothing listens on the target
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+
+        let middleware = MirrorMiddleware::new(MirrorConfig::new(&url)).unwrap();
+        middleware
+            .before_async(&mut request("GET", "/"))
+            .await
+            .unwrap();
+        settle(&middleware.stats()).await;
+        assert_eq!(middleware.stats().failed.load(Ordering::Relaxed), 1);
+
+        let capped = MirrorMiddleware::new(MirrorConfig::new(&url).with_max_in_flight(0)).unwrap();
+        capped.before_async(&mut request("GET", "/")).await.unwrap();
+        assert_eq!(capped.stats().dropped.load(Ordering::Relaxed), 1);
+        assert_eq!(capped.stats().mirrored.load(Ordering::Relaxed), 0);
+    }
+}
@@ -24,25 +24,50 @@ pub mod csrf;
 pub mod etag;
 pub mod exception_handler;
 pub mod guards;
+pub mod ip_filter;
+pub mod memory_cache;
 pub mod prometheus;
 pub mod rate_limit;
 pub mod request_id;
+pub mod route_middleware;
 pub mod security;
 pub mod session;
 pub mod static_files;
+pub mod webhook_verify;
 
 // Enterprise modules
 pub mod database;
+pub mod distributed_lock;
+pub mod fault_injection;
 pub mod graphql;
 pub mod health;
+pub mod idempotency;
 pub mod messaging;
+pub mod mirror;
+pub mod mongo;
+#[cfg(feature = "nats")]
+pub mod nats;
+pub mod object_store;
+#[cfg(feature = "rabbitmq")]
+pub mod rabbitmq;
 pub mod redis;
+pub mod schema_registry;
+pub mod secrets;
+pub mod sql;
+pub mod statsd;
 pub mod telemetry;
+pub mod tenant;
+pub mod transaction;
+pub mod transform;
 
 // v0.9.0 - API Protocol modules
+pub mod avro;
 pub mod grpc;
+pub mod protobuf;
+pub mod transcoding;
 
 // v0.10.0 - Advanced Pattern modules
+pub mod choreography;
 pub mod cqrs;
 pub mod eventsourcing;
 pub mod saga;
@@ -53,13 +78,15 @@ pub use crate::dto::*;
 use parking_lot::RwLock;
 use std::future::Future;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
-use crate::request::Request;
+use crate::request::{Extensions, Request};
 use crate::response::Response;
 
 // Re-export all middleware types
-pub use auth::{ApiKeyAuth, BasicAuth, JwtAuth};
+pub use auth::{ApiKeyAuth, BasicAuth, CredentialLocation, JwtAuth};
 pub use body_limit::BodyLimitMiddleware;
 pub use cache::{
     create_cache_key, CacheConfig, CacheError, CacheKeyBuilder, CacheMiddleware, CacheStore,
@@ -81,15 +108,24 @@ pub use guards::{
 pub use prometheus::{PrometheusConfig, PrometheusMetrics, PrometheusMiddleware};
 pub use rate_limit::{RateLimitMiddleware, RateLimitStore, SlidingWindowConfig, TokenBucketConfig};
 pub use request_id::RequestIdMiddleware;
+pub use route_middleware::{
+    MiddlewareScope, PythonMiddleware, RouteMiddleware, RouteMiddlewareRegistry,
+};
 pub use security::{ContentSecurityPolicy, HstsConfig, SecurityHeadersMiddleware};
 pub use session::{InMemorySessionStore, SessionMiddleware, SessionStore};
 pub use static_files::StaticFilesMiddleware;
+pub use webhook_verify::{SignatureScheme, WebhookVerifier};
 
 // Enterprise module re-exports
 pub use database::{
     DatabaseConfig, DatabaseConnection, DatabaseError, DatabasePool, DatabaseStats, FromSql,
     MockDatabasePool, Row, SqlValue, ToSql,
 };
+pub use distributed_lock::{
+    DistributedLock, InMemoryLockBackend, LockBackend, LockConfig, LockError, LockGuard,
+    RedisLockBackend,
+};
+pub use fault_injection::{Fault, FaultInjectionMiddleware, FaultRule, FaultSnapshot, FaultStats};
 pub use graphql::{
     GraphQLConfig, GraphQLError, GraphQLMiddleware, GraphQLRequest, GraphQLResponse, GraphQLSchema,
     ResolverContext, ResolverFn,
@@ -98,22 +134,30 @@ pub use health::{
     HealthCheckConfig, HealthCheckMiddleware, HealthCheckResult, HealthReport, HealthStatus,
     SystemInfo,
 };
+pub use idempotency::{
+    IdempotencyConfig, IdempotencyError, IdempotencyMiddleware, IdempotencyStore,
+    InMemoryIdempotencyStore, RedisIdempotencyStore,
+};
 pub use messaging::{
-    KafkaConfig, Message, MessageConsumer, MessageProducer, MessageQueueConfig, MessageResult,
-    MessagingError, MessagingStats, MockConsumer, MockProducer, ProducerConfig, RabbitMQConfig,
-    SqsConfig,
+    BatchMessageHandler, BatchResult, ConsumerConcurrency, ConsumerRunner, DelayStore,
+    DelayedMessage, DelayedProducer, FileDelayStore, InMemoryDelayStore, KafkaConfig, Message,
+    MessageConsumer, MessageHandler, MessageOrdering, MessageProducer, MessageQueueConfig,
+    MessageResult, MessagingError, MessagingStats, MockConsumer, MockProducer, NatsConfig,
+    ProducerConfig, RabbitMQConfig, SqsConfig,
 };
+pub use mirror::{MirrorConfig, MirrorMiddleware, MirrorSnapshot, MirrorStats};
 pub use redis::{
     MockRedisClient, RedisClient, RedisConfig, RedisError, RedisPoolMetrics, RedisStats, RedisValue,
 };
+pub use secrets::{InMemorySecretsProvider, RedisSecretsProvider, SecretsError, SecretsProvider};
 pub use telemetry::{
     OpenTelemetryConfig, OpenTelemetryMiddleware, TelemetryMetrics, TelemetryStats, TraceContext,
 };
 
 // v0.9.0 - API Protocol re-exports
 pub use grpc::{
-    GrpcConfig, GrpcError, GrpcMethodDef, GrpcMethodType, GrpcRequest, GrpcResponse, GrpcServer,
-    GrpcServiceDef, GrpcStats, GrpcStatus,
+    GrpcConfig, GrpcError, GrpcHandler, GrpcMethodDef, GrpcMethodType, GrpcRequest, GrpcResponse,
+    GrpcServer, GrpcServiceDef, GrpcStats, GrpcStatus,
 };
 
 // v0.10.0 - Advanced Pattern re-exports
@@ -122,12 +166,13 @@ pub use cqrs::{
     QueryResult,
 };
 pub use eventsourcing::{
-    AggregateState, Event, EventSourcingConfig, EventSourcingError, EventSourcingStats, EventStore,
-    InMemoryEventStore, Snapshot,
+    AggregateRepository, AggregateState, ApplyFn, Event, EventSourcingConfig, EventSourcingError,
+    EventSourcingStats, EventStore, EventSubscription, InMemoryEventStore, Snapshot,
 };
 pub use saga::{
-    SagaConfig, SagaDefinition, SagaError, SagaExecution, SagaOrchestrator, SagaStats, SagaStatus,
-    SagaStep, SagaStepDef, StepStatus,
+    InMemorySagaDedupStore, RedisSagaDedupStore, SagaConfig, SagaDedupStore, SagaDefinition,
+    SagaError, SagaExecution, SagaOrchestrator, SagaStats, SagaStatus, SagaStep, SagaStepDef,
+    StepStatus,
 };
 
 // ============================================================================
@@ -201,6 +246,23 @@ impl MiddlewareError {
     }
 }
 
+/// How a response ended, passed to [`Middleware::on_response_end`].
+#[derive(Debug, Clone)]
+pub struct ResponseEnd {
+    pub method: String,
+    pub path: String,
+    pub status: u16,
+    /// Body bytes sent to the client
+    pub bytes: u64,
+    /// Time from receiving the request to the end of the response
+    pub duration: Duration,
+    /// Whether the client went away, or the body failed, before the body
+    /// was sent in full
+    pub aborted: bool,
+    /// The request's extensions, holding what middleware stored in them
+    pub extensions: Extensions,
+}
+
 /// Check if a request path matches a skip pattern.
 ///
 /// Matches when:
@@ -263,6 +325,34 @@ pub trait Middleware: Send + Sync {
     fn skip_paths(&self) -> &[String] {
         &[]
     }
+
+    /// Largest request body, in bytes, kept for `after`.
+    ///
+    /// `after` normally gets the request without its body, so the body is
+    /// never copied. Middleware that must read it there (to hash or audit
+    /// the payload) returns a limit; larger bodies are still left out.
+    #[inline]
+    fn after_body_limit(&self) -> Option<usize> {
+        None
+    }
+
+    /// Whether [`Self::on_response_end`] should be called.
+    #[inline]
+    fn observes_response_end(&self) -> bool {
+        false
+    }
+
+    /// Called once the response body has been sent in full, or the client
+    /// went away. `after` runs before a streamed or SSE body is produced,
+    /// so this is where its final size, duration and outcome are known.
+    #[inline]
+    fn on_response_end(&self, _end: &ResponseEnd) {}
+
+    /// Where this middleware reads a credential, for auth middleware.
+    #[inline]
+    fn credential_location(&self) -> Option<auth::CredentialLocation> {
+        None
+    }
 }
 
 /// Trait for implementing asynchronous middleware.
@@ -301,6 +391,13 @@ pub trait AsyncMiddleware: Send + Sync {
     fn name(&self) -> &str {
         "unnamed_async"
     }
+
+    /// Largest request body, in bytes, kept for `after_async`; see
+    /// [`Middleware::after_body_limit`].
+    #[inline]
+    fn after_body_limit(&self) -> Option<usize> {
+        None
+    }
 }
 
 /// A wrapper for middleware with priority.
@@ -321,6 +418,11 @@ struct AsyncMiddlewareEntry {
 pub struct MiddlewareChain {
     sync_middlewares: Arc<RwLock<Vec<MiddlewareEntry>>>,
     async_middlewares: Arc<RwLock<Vec<AsyncMiddlewareEntry>>>,
+    transforms: Arc<RwLock<Vec<Arc<dyn transform::Transform>>>>,
+    /// Largest `after_body_limit` of the middleware, 0 if none keeps bodies
+    after_body_limit: Arc<AtomicUsize>,
+    /// Middleware told when responses end
+    end_observers: Arc<RwLock<Vec<Arc<dyn Middleware>>>>,
 }
 
 impl MiddlewareChain {
@@ -329,14 +431,22 @@ impl MiddlewareChain {
         MiddlewareChain {
             sync_middlewares: Arc::new(RwLock::new(Vec::new())),
             async_middlewares: Arc::new(RwLock::new(Vec::new())),
+            transforms: Arc::new(RwLock::new(Vec::new())),
+            after_body_limit: Arc::new(AtomicUsize::new(0)),
+            end_observers: Arc::new(RwLock::new(Vec::new())),
         }
     }
 
     /// Add a synchronous middleware to the chain.
     pub fn add<M: Middleware + 'static>(&self, middleware: M) {
         let priority = middleware.priority();
+        self.keep_after_body(middleware.after_body_limit());
+        let middleware: Arc<dyn Middleware> = Arc::new(middleware);
+        if middleware.observes_response_end() {
+            self.end_observers.write().push(middleware.clone());
+        }
         let entry = MiddlewareEntry {
-            middleware: Arc::new(middleware),
+            middleware,
             priority,
         };
 
@@ -348,6 +458,7 @@ impl MiddlewareChain {
     /// Add an asynchronous middleware to the chain.
     pub fn add_async<M: AsyncMiddleware + 'static>(&self, middleware: M) {
         let priority = middleware.priority();
+        self.keep_after_body(middleware.after_body_limit());
         let entry = AsyncMiddlewareEntry {
             middleware: Arc::new(middleware),
             priority,
@@ -358,6 +469,57 @@ impl MiddlewareChain {
         middlewares.sort_by_key(|e| e.priority);
     }
 
+    fn keep_after_body(&self, limit: Option<usize>) {
+        if let Some(limit) = limit {
+            self.after_body_limit.fetch_max(limit, Ordering::Relaxed);
+        }
+    }
+
+    /// Largest request body kept for after-middleware, 0 if none asks for it.
+    #[inline]
+    pub fn after_body_limit(&self) -> usize {
+        self.after_body_limit.load(Ordering::Relaxed)
+    }
+
+    /// The middleware told when responses end, in registration order.
+    #[inline]
+    pub fn response_end_observers(&self) -> Vec<Arc<dyn Middleware>> {
+        self.end_observers.read().clone()
+    }
+
+    /// Add a native transform, run in registration order before routing.
+    pub fn add_transform(&self, transform: Arc<dyn transform::Transform>) {
+        if transform.rewrites_response() {
+            self.add(transform::ResponseTransform(transform.clone()));
+        }
+        self.transforms.write().push(transform);
+    }
+
+    /// Check if any transforms are registered.
+    #[inline]
+    pub fn has_transforms(&self) -> bool {
+        !self.transforms.read().is_empty()
+    }
+
+    /// Run the transforms on a request head before routing, stopping at the
+    /// first that answers the request.
+    pub fn transform_request(&self, head: &mut hyper::http::request::Parts) -> Option<Response> {
+        let transforms = self.transforms.read();
+        transforms
+            .iter()
+            .find_map(|transform| transform.request(head))
+    }
+
+    /// Where the first auth middleware that runs for `path` reads its
+    /// credential.
+    pub fn credential_location(&self, path: &str) -> Option<auth::CredentialLocation> {
+        let middlewares = self.sync_middlewares.read();
+        middlewares
+            .iter()
+            .filter(|entry| entry.middleware.should_run(path))
+            .find_map(|entry| entry.middleware.credential_location())
+    }
+
     /// Execute all sync middleware before handlers.
     #[inline]
     pub fn execute_before(&self, request: &mut Request) -> MiddlewareResult {
@@ -488,6 +650,8 @@ pub struct LoggingMiddleware {
     pub log_body: bool,
     pub log_headers: bool,
     pub skip_paths: Vec<String>,
+    /// Redaction applied to logged paths and headers
+    pub redaction: crate::redaction::Redaction,
 }
 
 impl LoggingMiddleware {
@@ -496,9 +660,15 @@ impl LoggingMiddleware {
             log_body: false,
             log_headers: false,
             skip_paths: vec!["/health".to_string(), "/metrics".to_string()],
+            redaction: crate::redaction::Redaction::new(),
         }
     }
 
+    pub fn with_redaction(mut self, redaction: crate::redaction::Redaction) -> Self {
+        self.redaction = redaction;
+        self
+    }
+
     pub fn with_body(mut self) -> Self {
         self.log_body = true;
         self
@@ -523,26 +693,23 @@ impl Default for LoggingMiddleware {
 
 impl Middleware for LoggingMiddleware {
     fn before(&self, request: &mut Request) -> MiddlewareResult {
-        println!("--> {} {}", request.method, request.path);
+        let rules = self.redaction.rules();
+        let path = match &rules {
+            Some(rules) => rules.text(&request.path),
+            None => request.path.as_str().into(),
+        };
+        println!("--> {} {}", request.method, path);
         if self.log_headers {
             for (key, value) in &request.headers {
-                println!("    {key}: {value}");
+                match &rules {
+                    Some(rules) => println!("    {key}: {}", rules.value(key, value)),
+                    None => println!("    {key}: {value}"),
+                }
             }
         }
         Ok(MiddlewareAction::Continue)
     }
 
-    fn after(&self, request: &Request, response: &mut Response) -> MiddlewareResult {
-        println!(
-            "<-- {} {} {} {}",
-            request.method,
-            request.path,
-            response.status,
-            status_text(response.status)
-        );
-        Ok(MiddlewareAction::Continue)
-    }
-
     fn priority(&self) -> i32 {
         -200 // Run very early
     }
@@ -551,6 +718,32 @@ impl Middleware for LoggingMiddleware {
         "logging"
     }
 
+    fn observes_response_end(&self) -> bool {
+        true
+    }
+
+    // Logged once the body is sent, so streamed responses show their size
+    fn on_response_end(&self, end: &ResponseEnd) {
+        if !self.should_run(&end.path) {
+            return;
+        }
+        let rules = self.redaction.rules();
+        let path = match &rules {
+            Some(rules) => rules.text(&end.path),
+            None => end.path.as_str().into(),
+        };
+        println!(
+            "<-- {} {} {} {} {}B {:.1}ms{}",
+            end.method,
+            path,
+            end.status,
+            status_text(end.status),
+            end.bytes,
+            end.duration.as_secs_f64() * 1000.0,
+            if end.aborted { " (aborted)" } else { "" }
+        );
+    }
+
     fn should_run(&self, path: &str) -> bool {
         !self.skip_paths.iter().any(|p| path.starts_with(p))
     }
@@ -631,6 +824,15 @@ impl CompressionMiddleware {
             return false;
         }
 
+        // Already encoded, e.g. the gzip-compressed metrics page
+        if response
+            .headers
+            .keys()
+            .any(|name| name.eq_ignore_ascii_case("content-encoding"))
+        {
+            return false;
+        }
+
         // Check if content type is compressible
         if let Some(content_type) = response.headers.get("Content-Type") {
             return self
@@ -652,7 +854,7 @@ impl Default for CompressionMiddleware {
 impl Middleware for CompressionMiddleware {
     fn after(&self, request: &Request, response: &mut Response) -> MiddlewareResult {
         // Check if client accepts gzip
-        let accept_encoding = request.headers.get("accept-encoding").map(|s| s.as_str());
+        let accept_encoding = request.headers.get("accept-encoding");
         if let Some(encoding) = accept_encoding {
             if encoding.contains("gzip") && self.should_compress(response) {
                 // Compress the response body
@@ -677,7 +879,7 @@ impl Middleware for CompressionMiddleware {
 }
 
 /// Compress bytes using gzip.
-fn compress_gzip(data: &[u8], level: u32) -> Result<Vec<u8>, std::io::Error> {
+pub(crate) fn compress_gzip(data: &[u8], level: u32) -> Result<Vec<u8>, std::io::Error> {
     use flate2::write::GzEncoder;
     use flate2::Compression;
     use std::io::Write;
@@ -749,4 +951,31 @@ mod tests {
         assert!(names.contains(&"test1".to_string()));
         assert!(names.contains(&"test2".to_string()));
     }
+
+    struct AuditMiddleware;
+
+    impl Middleware for AuditMiddleware {
+        fn after_body_limit(&self) -> Option<usize> {
+            Some(4)
+        }
+    }
+
+    #[test]
+    fn test_after_body_limit() {
+        let chain = MiddlewareChain::new();
+        chain.add(TestMiddleware {
+            name: "plain".to_string(),
+            priority: 0,
+        });
+        assert_eq!(chain.after_body_limit(), 0);
+        chain.add(AuditMiddleware);
+        assert_eq!(chain.after_body_limit(), 4);
+
+        let mut request = Request::new("POST", "/orders");
+        request.body = b"1234".to_vec();
+        assert!(request.clone_for_after(0).body.is_empty());
+        assert_eq!(request.clone_for_after(4).body, b"1234");
+        request.body = b"12345".to_vec();
+        assert!(request.clone_for_after(4).body.is_empty());
+    }
 }
@@ -0,0 +1,830 @@
+//! MongoDB integration for Cello.
+//!
+//! Provides:
+//! - `MongoConfig` for the driver's connection pool
+//! - BSON <-> Python conversion: documents as dicts, `ObjectId`, UTC
+//!   datetimes, binary data and decimals
+//! - Async database and collection handles for Python handlers with CRUD,
+//!   aggregation and raw commands (requires the `mongodb` feature)
+//!
+//! # Example
+//! ```python
+//! from cello import App, Depends, MongoConfig
+//!
+//! app = App()
+//! app.enable_mongo(MongoConfig("mongodb://localhost:27017", database="shop"))
+//!
+//! @app.get("/products/{sku}")
+//! async def get_product(request, mongo=Depends("mongo")):
+//!     return await mongo["products"].find_one({"sku": request.params["sku"]}, projection={"_id": 0})
+//! ```
+
+use bson::oid::ObjectId;
+use bson::{Binary, Bson, DateTime, Decimal128, Document};
+use pyo3::basic::CompareOp;
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyBytes, PyDict, PyFloat, PyList, PyLong, PyString, PyTuple};
+use std::time::Duration;
+
+// ============================================================================
+// Configuration
+// ============================================================================
+
+/// MongoDB client configuration.
+#[derive(Clone, Debug)]
+pub struct MongoConfig {
+    /// Connection string (`mongodb://` or `mongodb+srv://`)
+    pub url: String,
+    /// Default database; falls back to the one in the URL, then `"cello"`
+    pub database: Option<String>,
+    /// Connections kept open per server
+    pub min_pool_size: u32,
+    /// Maximum connections per server
+    pub max_pool_size: u32,
+    /// Timeout for opening a connection
+    pub connect_timeout: Duration,
+    /// How long an operation waits for a suitable server
+    pub server_selection_timeout: Duration,
+    /// Application name reported to the server
+    pub app_name: Option<String>,
+}
+
+impl Default for MongoConfig {
+    fn default() -> Self {
+        Self {
+            url: "mongodb://127.0.0.1:27017".to_string(),
+            database: None,
+            min_pool_size: 0,
+            max_pool_size: 10,
+            connect_timeout: Duration::from_secs(5),
+            server_selection_timeout: Duration::from_secs(30),
+            app_name: None,
+        }
+    }
+}
+
+impl MongoConfig {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn database(mut self, name: &str) -> Self {
+        self.database = Some(name.to_string());
+        self
+    }
+
+    pub fn pool_size(mut self, min: u32, max: u32) -> Self {
+        self.min_pool_size = min;
+        self.max_pool_size = max;
+        self
+    }
+}
+
+/// MongoDB errors.
+#[derive(Debug, Clone)]
+pub enum MongoError {
+    /// Invalid connection string or options
+    Config(String),
+    /// No server reachable
+    Connection(String),
+    /// The server rejected an operation
+    Operation(String),
+}
+
+impl std::fmt::Display for MongoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MongoError::Config(msg) => write!(f, "MongoDB config error: {msg}"),
+            MongoError::Connection(msg) => write!(f, "MongoDB connection error: {msg}"),
+            MongoError::Operation(msg) => write!(f, "MongoDB error: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for MongoError {}
+
+// ============================================================================
+// BSON Conversion
+// ============================================================================
+
+/// A BSON ObjectId.
+///
+/// `ObjectId()` generates a new id and `ObjectId(hex)` parses one.
+#[pyclass(name = "ObjectId")]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct PyObjectId {
+    oid: ObjectId,
+}
+
+impl PyObjectId {
+    pub fn new(oid: ObjectId) -> Self {
+        Self { oid }
+    }
+}
+
+#[pymethods]
+impl PyObjectId {
+    #[new]
+    #[pyo3(signature = (oid=None))]
+    fn py_new(oid: Option<&str>) -> PyResult<Self> {
+        match oid {
+            Some(hex) => ObjectId::parse_str(hex)
+                .map(Self::new)
+                .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string())),
+            None => Ok(Self::new(ObjectId::new())),
+        }
+    }
+
+    /// When the id was generated, as a UTC datetime.
+    #[getter]
+    fn generation_time(&self, py: Python<'_>) -> PyResult<PyObject> {
+        datetime_to_py(py, self.oid.timestamp())
+    }
+
+    fn __str__(&self) -> String {
+        self.oid.to_hex()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("ObjectId('{}')", self.oid.to_hex())
+    }
+
+    fn __richcmp__(&self, other: &PyAny, op: CompareOp, py: Python<'_>) -> PyObject {
+        match other.extract::<PyObjectId>() {
+            Ok(other) => match op {
+                CompareOp::Eq => (self.oid == other.oid).into_py(py),
+                CompareOp::Ne => (self.oid != other.oid).into_py(py),
+                CompareOp::Lt => (self.oid < other.oid).into_py(py),
+                CompareOp::Le => (self.oid <= other.oid).into_py(py),
+                CompareOp::Gt => (self.oid > other.oid).into_py(py),
+                CompareOp::Ge => (self.oid >= other.oid).into_py(py),
+            },
+            Err(_) => py.NotImplemented(),
+        }
+    }
+
+    fn __hash__(&self) -> u64 {
+        use std::hash::{Hash, Hasher};
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        self.oid.bytes().hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+/// Convert a BSON value to Python.
+///
+/// Documents become dicts, arrays lists, datetimes timezone-aware UTC
+/// `datetime`s, binary data `bytes` and decimals `decimal.Decimal`. Other
+/// BSON types are returned in their relaxed Extended JSON form.
+pub fn bson_to_py(py: Python<'_>, value: &Bson) -> PyResult<PyObject> {
+    Ok(match value {
+        Bson::Null | Bson::Undefined => py.None(),
+        Bson::Boolean(v) => v.into_py(py),
+        Bson::Int32(v) => v.into_py(py),
+        Bson::Int64(v) => v.into_py(py),
+        Bson::Double(v) => v.into_py(py),
+        Bson::String(v) => v.into_py(py),
+        Bson::Array(items) => {
+            let items = items
+                .iter()
+                .map(|item| bson_to_py(py, item))
+                .collect::<PyResult<Vec<_>>>()?;
+            PyList::new(py, items).into()
+        }
+        Bson::Document(doc) => document_to_py(py, doc)?,
+        Bson::ObjectId(oid) => PyObjectId::new(*oid).into_py(py),
+        Bson::DateTime(dt) => datetime_to_py(py, *dt)?,
+        Bson::Binary(binary) => PyBytes::new(py, &binary.bytes).into(),
+        Bson::Decimal128(d) => py
+            .import("decimal")?
+            .getattr("Decimal")?
+            .call1((d.to_string(),))?
+            .into(),
+        other => crate::json::json_to_python(py, &other.clone().into_relaxed_extjson())?,
+    })
+}
+
+/// Convert a BSON document to a Python dict.
+pub fn document_to_py(py: Python<'_>, doc: &Document) -> PyResult<PyObject> {
+    let dict = PyDict::new(py);
+    for (key, value) in doc {
+        dict.set_item(key, bson_to_py(py, value)?)?;
+    }
+    Ok(dict.into())
+}
+
+/// Convert a Python value to BSON.
+///
+/// Naive datetimes are taken to be UTC. Raises `TypeError` for values
+/// with no BSON equivalent and `OverflowError` for ints beyond 64 bits.
+pub fn py_to_bson(value: &PyAny) -> PyResult<Bson> {
+    let py = value.py();
+    Ok(if value.is_none() {
+        Bson::Null
+    } else if let Ok(v) = value.downcast::<PyBool>() {
+        Bson::Boolean(v.is_true())
+    } else if value.is_instance_of::<PyLong>() {
+        let v: i64 = value.extract()?;
+        i32::try_from(v).map_or(Bson::Int64(v), Bson::Int32)
+    } else if value.is_instance_of::<PyFloat>() {
+        Bson::Double(value.extract()?)
+    } else if let Ok(v) = value.downcast::<PyString>() {
+        Bson::String(v.to_str()?.to_string())
+    } else if let Ok(v) = value.downcast::<PyBytes>() {
+        Bson::Binary(Binary {
+            subtype: bson::spec::BinarySubtype::Generic,
+            bytes: v.as_bytes().to_vec(),
+        })
+    } else if value.is_instance_of::<PyDict>() {
+        Bson::Document(py_to_document(value)?)
+    } else if value.is_instance_of::<PyList>() || value.is_instance_of::<PyTuple>() {
+        Bson::Array(
+            value
+                .iter()?
+                .map(|item| py_to_bson(item?))
+                .collect::<PyResult<_>>()?,
+        )
+    } else if let Ok(oid) = value.extract::<PyObjectId>() {
+        Bson::ObjectId(oid.oid)
+    } else if value.is_instance(py.import("datetime")?.getattr("datetime")?)? {
+        let aware = if value.getattr("tzinfo")?.is_none() {
+            let kwargs = PyDict::new(py);
+            kwargs.set_item(
+                "tzinfo",
+                py.import("datetime")?.getattr("timezone")?.getattr("utc")?,
+            )?;
+            value.call_method("replace", (), Some(kwargs))?
+        } else {
+            value
+        };
+        let seconds: f64 = aware.call_method0("timestamp")?.extract()?;
+        Bson::DateTime(DateTime::from_millis((seconds * 1000.0).round() as i64))
+    } else if value.is_instance(py.import("decimal")?.getattr("Decimal")?)? {
+        let text: String = value.str()?.extract()?;
+        Bson::Decimal128(
+            text.parse::<Decimal128>()
+                .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?,
+        )
+    } else {
+        return Err(pyo3::exceptions::PyTypeError::new_err(format!(
+            "cannot encode object of type {} to BSON",
+            value.get_type().name()?
+        )));
+    })
+}
+
+/// Convert a Python dict to a BSON document.
+pub fn py_to_document(value: &PyAny) -> PyResult<Document> {
+    let dict = value.downcast::<PyDict>().map_err(|_| {
+        pyo3::exceptions::PyTypeError::new_err(format!(
+            "expected a dict for a BSON document, got {}",
+            value.get_type().name().unwrap_or("object")
+        ))
+    })?;
+    let mut doc = Document::new();
+    for (key, value) in dict {
+        let key: String = key.str()?.extract()?;
+        doc.insert(key, py_to_bson(value)?);
+    }
+    Ok(doc)
+}
+
+fn datetime_to_py(py: Python<'_>, dt: DateTime) -> PyResult<PyObject> {
+    let datetime = py.import("datetime")?;
+    let utc = datetime.getattr("timezone")?.getattr("utc")?;
+    let seconds = dt.timestamp_millis() as f64 / 1000.0;
+    Ok(datetime
+        .getattr("datetime")?
+        .call_method1("fromtimestamp", (seconds, utc))?
+        .into())
+}
+
+// ============================================================================
+// Client
+// ============================================================================
+
+#[cfg(feature = "mongodb")]
+pub use client::{MongoClient, PyMongoCollection, PyMongoDatabase};
+
+#[cfg(feature = "mongodb")]
+mod client {
+    use super::*;
+    use futures_util::TryStreamExt;
+    use mongodb::error::ErrorKind;
+    use mongodb::options::{ClientOptions, FindOptions, ReplaceOptions, UpdateOptions};
+    use mongodb::{Collection, Database};
+
+    impl From<mongodb::error::Error> for MongoError {
+        fn from(e: mongodb::error::Error) -> Self {
+            match *e.kind {
+                ErrorKind::InvalidArgument { .. } => MongoError::Config(e.to_string()),
+                ErrorKind::ServerSelection { .. }
+                | ErrorKind::Io(_)
+                | ErrorKind::ConnectionPoolCleared { .. } => MongoError::Connection(e.to_string()),
+                _ => MongoError::Operation(e.to_string()),
+            }
+        }
+    }
+
+    fn mongo_error_to_py(e: MongoError) -> PyErr {
+        match e {
+            MongoError::Config(_) => pyo3::exceptions::PyValueError::new_err(e.to_string()),
+            MongoError::Connection(_) => {
+                pyo3::exceptions::PyConnectionError::new_err(e.to_string())
+            }
+            MongoError::Operation(_) => pyo3::exceptions::PyRuntimeError::new_err(e.to_string()),
+        }
+    }
+
+    fn optional_document(value: Option<&PyAny>) -> PyResult<Option<Document>> {
+        value
+            .filter(|v| !v.is_none())
+            .map(py_to_document)
+            .transpose()
+    }
+
+    /// MongoDB client with the driver's connection pool.
+    #[derive(Clone, Debug)]
+    pub struct MongoClient {
+        client: mongodb::Client,
+        database: String,
+    }
+
+    impl MongoClient {
+        /// Create a client; connections are opened on first use.
+        pub async fn from_config(config: &MongoConfig) -> Result<Self, MongoError> {
+            let mut options = ClientOptions::parse(&config.url).await?;
+            options.min_pool_size = Some(config.min_pool_size);
+            options.max_pool_size = Some(config.max_pool_size.max(1));
+            options.connect_timeout = Some(config.connect_timeout);
+            options.server_selection_timeout = Some(config.server_selection_timeout);
+            if config.app_name.is_some() {
+                options.app_name = config.app_name.clone();
+            }
+            let database = config
+                .database
+                .clone()
+                .or_else(|| options.default_database.clone())
+                .unwrap_or_else(|| "cello".to_string());
+            Ok(Self {
+                client: mongodb::Client::with_options(options)?,
+                database,
+            })
+        }
+
+        /// Name of the default database.
+        pub fn default_database(&self) -> &str {
+            &self.database
+        }
+
+        /// Get a database, or the default one.
+        pub fn database(&self, name: Option<&str>) -> Database {
+            self.client.database(name.unwrap_or(&self.database))
+        }
+    }
+
+    /// Python handle to a MongoDB database.
+    ///
+    /// `db["name"]` or `db.collection("name")` returns a collection.
+    #[pyclass(name = "MongoDatabase")]
+    #[derive(Clone)]
+    pub struct PyMongoDatabase {
+        db: Database,
+    }
+
+    impl PyMongoDatabase {
+        pub fn new(db: Database) -> Self {
+            Self { db }
+        }
+    }
+
+    #[pymethods]
+    impl PyMongoDatabase {
+        /// Database name.
+        #[getter]
+        fn name(&self) -> String {
+            self.db.name().to_string()
+        }
+
+        /// Get a collection.
+        fn collection(&self, name: &str) -> PyMongoCollection {
+            PyMongoCollection {
+                coll: self.db.collection(name),
+            }
+        }
+
+        fn __getitem__(&self, name: &str) -> PyMongoCollection {
+            self.collection(name)
+        }
+
+        /// Names of the collections in the database.
+        fn list_collection_names<'py>(&self, py: Python<'py>) -> PyResult<&'py PyAny> {
+            let db = self.db.clone();
+            pyo3_asyncio::tokio::future_into_py(py, async move {
+                db.list_collection_names(None)
+                    .await
+                    .map_err(|e| mongo_error_to_py(e.into()))
+            })
+        }
+
+        /// Run a database command, e.g. `{"ping": 1}`.
+        fn command<'py>(&self, py: Python<'py>, command: &PyAny) -> PyResult<&'py PyAny> {
+            let (db, command) = (self.db.clone(), py_to_document(command)?);
+            pyo3_asyncio::tokio::future_into_py(py, async move {
+                let reply = db
+                    .run_command(command, None)
+                    .await
+                    .map_err(|e| mongo_error_to_py(e.into()))?;
+                Python::with_gil(|py| document_to_py(py, &reply))
+            })
+        }
+
+        fn __repr__(&self) -> String {
+            format!("MongoDatabase({:?})", self.db.name())
+        }
+    }
+
+    /// Python handle to a MongoDB collection.
+    ///
+    /// Methods return awaitables; filters, updates and documents are dicts.
+    #[pyclass(name = "MongoCollection")]
+    #[derive(Clone)]
+    pub struct PyMongoCollection {
+        coll: Collection<Document>,
+    }
+
+    fn documents_to_py(py: Python<'_>, docs: &[Document]) -> PyResult<PyObject> {
+        let docs = docs
+            .iter()
+            .map(|doc| document_to_py(py, doc))
+            .collect::<PyResult<Vec<_>>>()?;
+        Ok(PyList::new(py, docs).into())
+    }
+
+    fn update_result_to_py(
+        py: Python<'_>,
+        result: &mongodb::results::UpdateResult,
+    ) -> PyResult<PyObject> {
+        let dict = PyDict::new(py);
+        dict.set_item("matched_count", result.matched_count)?;
+        dict.set_item("modified_count", result.modified_count)?;
+        let upserted_id = match &result.upserted_id {
+            Some(id) => bson_to_py(py, id)?,
+            None => py.None(),
+        };
+        dict.set_item("upserted_id", upserted_id)?;
+        Ok(dict.into())
+    }
+
+    #[pymethods]
+    impl PyMongoCollection {
+        /// Collection name.
+        #[getter]
+        fn name(&self) -> String {
+            self.coll.name().to_string()
+        }
+
+        /// Insert a document and return its `_id`.
+        fn insert_one<'py>(&self, py: Python<'py>, document: &PyAny) -> PyResult<&'py PyAny> {
+            let (coll, document) = (self.coll.clone(), py_to_document(document)?);
+            pyo3_asyncio::tokio::future_into_py(py, async move {
+                let result = coll
+                    .insert_one(document, None)
+                    .await
+                    .map_err(|e| mongo_error_to_py(e.into()))?;
+                Python::with_gil(|py| bson_to_py(py, &result.inserted_id))
+            })
+        }
+
+        /// Insert documents and return their `_id`s in order.
+        fn insert_many<'py>(&self, py: Python<'py>, documents: &PyAny) -> PyResult<&'py PyAny> {
+            let coll = self.coll.clone();
+            let documents = documents
+                .iter()?
+                .map(|doc| py_to_document(doc?))
+                .collect::<PyResult<Vec<_>>>()?;
+            pyo3_asyncio::tokio::future_into_py(py, async move {
+                let result = coll
+                    .insert_many(documents, None)
+                    .await
+                    .map_err(|e| mongo_error_to_py(e.into()))?;
+                let mut ids: Vec<_> = result.inserted_ids.into_iter().collect();
+                ids.sort_by_key(|(index, _)| *index);
+                Python::with_gil(|py| {
+                    let ids = ids
+                        .iter()
+                        .map(|(_, id)| bson_to_py(py, id))
+                        .collect::<PyResult<Vec<_>>>()?;
+                    Ok(PyList::new(py, ids).to_object(py))
+                })
+            })
+        }
+
+        /// Find matching documents.
+        ///
+        /// `sort` is a dict such as `{"created": -1}`.
+        #[pyo3(signature = (filter=None, projection=None, sort=None, limit=None, skip=None))]
+        fn find<'py>(
+            &self,
+            py: Python<'py>,
+            filter: Option<&PyAny>,
+            projection: Option<&PyAny>,
+            sort: Option<&PyAny>,
+            limit: Option<i64>,
+            skip: Option<u64>,
+        ) -> PyResult<&'py PyAny> {
+            let coll = self.coll.clone();
+            let filter = optional_document(filter)?;
+            let options = FindOptions::builder()
+                .projection(optional_document(projection)?)
+                .sort(optional_document(sort)?)
+                .limit(limit)
+                .skip(skip)
+                .build();
+            pyo3_asyncio::tokio::future_into_py(py, async move {
+                let docs: Vec<Document> =
+                    async { coll.find(filter, options).await?.try_collect().await }
+                        .await
+                        .map_err(|e: mongodb::error::Error| mongo_error_to_py(e.into()))?;
+                Python::with_gil(|py| documents_to_py(py, &docs))
+            })
+        }
+
+        /// Find the first matching document, or None.
+        #[pyo3(signature = (filter=None, projection=None, sort=None))]
+        fn find_one<'py>(
+            &self,
+            py: Python<'py>,
+            filter: Option<&PyAny>,
+            projection: Option<&PyAny>,
+            sort: Option<&PyAny>,
+        ) -> PyResult<&'py PyAny> {
+            let coll = self.coll.clone();
+            let filter = optional_document(filter)?;
+            let options = FindOptions::builder()
+                .projection(optional_document(projection)?)
+                .sort(optional_document(sort)?)
+                .limit(Some(1))
+                .build();
+            pyo3_asyncio::tokio::future_into_py(py, async move {
+                let doc = async { coll.find(filter, options).await?.try_next().await }
+                    .await
+                    .map_err(|e: mongodb::error::Error| mongo_error_to_py(e.into()))?;
+                Python::with_gil(|py| match doc {
+                    Some(doc) => document_to_py(py, &doc),
+                    None => Ok(py.None()),
+                })
+            })
+        }
+
+        /// Update the first matching document.
+        ///
+        /// Returns `{"matched_count", "modified_count", "upserted_id"}`.
+        #[pyo3(signature = (filter, update, upsert=false))]
+        fn update_one<'py>(
+            &self,
+            py: Python<'py>,
+            filter: &PyAny,
+            update: &PyAny,
+            upsert: bool,
+        ) -> PyResult<&'py PyAny> {
+            let (coll, filter, update) = (
+                self.coll.clone(),
+                py_to_document(filter)?,
+                py_to_document(update)?,
+            );
+            let options = UpdateOptions::builder().upsert(upsert).build();
+            pyo3_asyncio::tokio::future_into_py(py, async move {
+                let result = coll
+                    .update_one(filter, update, options)
+                    .await
+                    .map_err(|e| mongo_error_to_py(e.into()))?;
+                Python::with_gil(|py| update_result_to_py(py, &result))
+            })
+        }
+
+        /// Update all matching documents.
+        #[pyo3(signature = (filter, update, upsert=false))]
+        fn update_many<'py>(
+            &self,
+            py: Python<'py>,
+            filter: &PyAny,
+            update: &PyAny,
+            upsert: bool,
+        ) -> PyResult<&'py PyAny> {
+            let (coll, filter, update) = (
+                self.coll.clone(),
+                py_to_document(filter)?,
+                py_to_document(update)?,
+            );
+            let options = UpdateOptions::builder().upsert(upsert).build();
+            pyo3_asyncio::tokio::future_into_py(py, async move {
+                let result = coll
+                    .update_many(filter, update, options)
+                    .await
+                    .map_err(|e| mongo_error_to_py(e.into()))?;
+                Python::with_gil(|py| update_result_to_py(py, &result))
+            })
+        }
+
+        /// Replace the first matching document.
+        #[pyo3(signature = (filter, replacement, upsert=false))]
+        fn replace_one<'py>(
+            &self,
+            py: Python<'py>,
+            filter: &PyAny,
+            replacement: &PyAny,
+            upsert: bool,
+        ) -> PyResult<&'py PyAny> {
+            let (coll, filter, replacement) = (
+                self.coll.clone(),
+                py_to_document(filter)?,
+                py_to_document(replacement)?,
+            );
+            let options = ReplaceOptions::builder().upsert(upsert).build();
+            pyo3_asyncio::tokio::future_into_py(py, async move {
+                let result = coll
+                    .replace_one(filter, replacement, options)
+                    .await
+                    .map_err(|e| mongo_error_to_py(e.into()))?;
+                Python::with_gil(|py| update_result_to_py(py, &result))
+            })
+        }
+
+        /// Delete the first matching document; returns the number deleted.
+        fn delete_one<'py>(&self, py: Python<'py>, filter: &PyAny) -> PyResult<&'py PyAny> {
+            let (coll, filter) = (self.coll.clone(), py_to_document(filter)?);
+            pyo3_asyncio::tokio::future_into_py(py, async move {
+                let result = coll
+                    .delete_one(filter, None)
+                    .await
+                    .map_err(|e| mongo_error_to_py(e.into()))?;
+                Ok(result.deleted_count)
+            })
+        }
+
+        /// Delete all matching documents; returns the number deleted.
+        fn delete_many<'py>(&self, py: Python<'py>, filter: &PyAny) -> PyResult<&'py PyAny> {
+            let (coll, filter) = (self.coll.clone(), py_to_document(filter)?);
+            pyo3_asyncio::tokio::future_into_py(py, async move {
+                let result = coll
+                    .delete_many(filter, None)
+                    .await
+                    .map_err(|e| mongo_error_to_py(e.into()))?;
+                Ok(result.deleted_count)
+            })
+        }
+
+        /// Count matching documents.
+        #[pyo3(signature = (filter=None))]
+        fn count_documents<'py>(
+            &self,
+            py: Python<'py>,
+            filter: Option<&PyAny>,
+        ) -> PyResult<&'py PyAny> {
+            let (coll, filter) = (self.coll.clone(), optional_document(filter)?);
+            pyo3_asyncio::tokio::future_into_py(py, async move {
+                coll.count_documents(filter, None)
+                    .await
+                    .map_err(|e| mongo_error_to_py(e.into()))
+            })
+        }
+
+        /// Run an aggregation pipeline (a list of stage dicts) and return
+        /// the resulting documents.
+        fn aggregate<'py>(&self, py: Python<'py>, pipeline: &PyAny) -> PyResult<&'py PyAny> {
+            let coll = self.coll.clone();
+            let pipeline = pipeline
+                .iter()?
+                .map(|stage| py_to_document(stage?))
+                .collect::<PyResult<Vec<_>>>()?;
+            pyo3_asyncio::tokio::future_into_py(py, async move {
+                let docs: Vec<Document> =
+                    async { coll.aggregate(pipeline, None).await?.try_collect().await }
+                        .await
+                        .map_err(|e: mongodb::error::Error| mongo_error_to_py(e.into()))?;
+                Python::with_gil(|py| documents_to_py(py, &docs))
+            })
+        }
+
+        /// Distinct values of a field across matching documents.
+        #[pyo3(signature = (field, filter=None))]
+        fn distinct<'py>(
+            &self,
+            py: Python<'py>,
+            field: String,
+            filter: Option<&PyAny>,
+        ) -> PyResult<&'py PyAny> {
+            let (coll, filter) = (self.coll.clone(), optional_document(filter)?);
+            pyo3_asyncio::tokio::future_into_py(py, async move {
+                let values = coll
+                    .distinct(field, filter, None)
+                    .await
+                    .map_err(|e| mongo_error_to_py(e.into()))?;
+                Python::with_gil(|py| bson_to_py(py, &Bson::Array(values)))
+            })
+        }
+
+        fn __repr__(&self) -> String {
+            format!("MongoCollection({:?})", self.coll.namespace().to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_defaults() {
+        let config = MongoConfig::new("mongodb://db:27017")
+            .database("shop")
+            .pool_size(2, 20);
+        assert_eq!(config.database.as_deref(), Some("shop"));
+        assert_eq!(config.max_pool_size, 20);
+        assert_eq!(config.server_selection_timeout, Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_bson_round_trip() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let oid = ObjectId::new();
+            let doc = bson::doc! {
+                "_id": oid,
+                "name": "pen",
+                "qty": 3,
+                "big": 5_000_000_000_i64,
+                "price": 1.5,
+                "tags": ["a", "b"],
+                "meta": { "ok": true, "none": Bson::Null },
+                "created": DateTime::from_millis(1_700_000_000_123),
+                "blob": Binary { subtype: bson::spec::BinarySubtype::Generic, bytes: vec![1, 2] },
+                "amount": "12.50".parse::<Decimal128>().unwrap(),
+            };
+            let value = document_to_py(py, &doc).unwrap();
+            let value = value.as_ref(py);
+            assert_eq!(
+                value
+                    .get_item("_id")
+                    .unwrap()
+                    .extract::<PyObjectId>()
+                    .unwrap()
+                    .oid,
+                oid
+            );
+            assert_eq!(
+                value
+                    .get_item("created")
+                    .unwrap()
+                    .call_method0("isoformat")
+                    .unwrap()
+                    .extract::<String>()
+                    .unwrap(),
+                "2023-11-14T22:13:20.123000+00:00"
+            );
+            assert_eq!(py_to_document(value).unwrap(), doc);
+        });
+    }
+
+    #[test]
+    fn test_py_to_bson_errors() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let list = PyList::new(py, [1, 2]);
+            assert!(py_to_document(list).is_err());
+            let unsupported = py.eval("{'s': {1, 2}}", None, None).unwrap();
+            assert!(py_to_document(unsupported)
+                .unwrap_err()
+                .is_instance_of::<pyo3::exceptions::PyTypeError>(py));
+            let naive = py
+                .eval(
+                    "__import__('datetime').datetime(2024, 1, 2, 3, 4, 5)",
+                    None,
+                    None,
+                )
+                .unwrap();
+            assert_eq!(
+                py_to_bson(naive).unwrap(),
+                Bson::DateTime(DateTime::from_millis(1_704_164_645_000))
+            );
+        });
+    }
+
+    #[cfg(feature = "mongodb")]
+    #[tokio::test]
+    async fn test_client_default_database() {
+        let client = MongoClient::from_config(&MongoConfig::new("mongodb://localhost:27017/shop"))
+            .await
+            .unwrap();
+        assert_eq!(client.default_database(), "shop");
+        assert_eq!(client.database(Some("other")).name(), "other");
+        let err = MongoClient::from_config(&MongoConfig::new("postgres://localhost"))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, MongoError::Config(_)));
+    }
+}
@@ -0,0 +1,608 @@
+//! NATS adapter for Cello Framework.
+//!
+//! Implements `MessageProducer` and `MessageConsumer` on top of `async-nats`
+//! (enabled by the `nats` feature):
+//! - Core NATS subjects, with queue groups for load-balanced subscribers
+//! - JetStream streams with durable pull consumers and explicit acks
+//! - Ack/nak/term settlement from a `MessageResult`
+//!
+//! Topics map to NATS subjects. Message keys travel in the `x-message-key`
+//! header. Core NATS is at-most-once, so settling a core message is a no-op.
+//!
+//! # Example
+//! ```rust,ignore
+//! let config = NatsConfig::local()
+//!     .with_jetstream("ORDERS", &["orders.>"])
+//!     .with_durable_name("billing");
+//!
+//! let producer = NatsProducer::connect(&config)?;
+//! producer.send("orders.created", Some("order-42"), br#"{"id": 42}"#)?;
+//!
+//! let consumer = NatsConsumer::connect(&config)?;
+//! consumer.subscribe(&["orders.created"])?;
+//! for message in consumer.poll()? {
+//!     consumer.settle(&message, MessageResult::Ack)?;
+//! }
+//! ```
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use async_nats::header::HeaderMap;
+use async_nats::jetstream::{self, consumer::pull, AckKind};
+use async_nats::{Client, ConnectErrorKind, ConnectOptions};
+use futures_executor::block_on;
+use futures_util::StreamExt;
+use parking_lot::{Mutex, RwLock};
+use tokio::runtime::Runtime;
+use tokio::task::JoinHandle;
+
+use super::messaging::{
+    Message, MessageConsumer, MessageProducer, MessageResult, MessagingError, MessagingMetrics,
+    MessagingStats, NatsConfig, KEY_HEADER,
+};
+
+/// Header JetStream uses to deduplicate publishes.
+const MSG_ID_HEADER: &str = "Nats-Msg-Id";
+
+/// Default time `poll` waits for the first message.
+const DEFAULT_POLL_TIMEOUT: Duration = Duration::from_secs(1);
+
+fn unknown(err: impl std::fmt::Display) -> MessagingError {
+    MessagingError::Unknown(err.to_string())
+}
+
+fn map_connect_error(err: async_nats::ConnectError) -> MessagingError {
+    match err.kind() {
+        ConnectErrorKind::Authentication | ConnectErrorKind::AuthorizationViolation => {
+            MessagingError::Authentication
+        }
+        ConnectErrorKind::TimedOut => MessagingError::Timeout,
+        ConnectErrorKind::Io | ConnectErrorKind::Dns | ConnectErrorKind::MaxReconnects => {
+            MessagingError::Connection
+        }
+        _ => unknown(err),
+    }
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// JetStream consumer name for `subject`: names may not contain `.`, `*`,
+/// `>` or whitespace.
+fn consumer_name(durable: &str, subject: &str) -> String {
+    let subject: String = subject
+        .chars()
+        .map(|c| match c {
+            '.' | '*' | '>' => '_',
+            c if c.is_whitespace() => '_',
+            c => c,
+        })
+        .collect();
+    format!("{durable}-{subject}")
+}
+
+/// Tokio runtime owned by an adapter.
+///
+/// `async-nats` needs a Tokio reactor, while the messaging traits are
+/// synchronous and may be called from any thread (including a Tokio worker),
+/// so futures run on this runtime and are waited on from the caller.
+struct Bridge {
+    runtime: Option<Runtime>,
+}
+
+impl Bridge {
+    fn new() -> Result<Self, MessagingError> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("cello-nats")
+            .enable_all()
+            .build()
+            .map_err(unknown)?;
+        Ok(Self {
+            runtime: Some(runtime),
+        })
+    }
+
+    fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.runtime
+            .as_ref()
+            .expect("runtime is only taken on drop")
+            .spawn(future)
+    }
+
+    fn run<F, T>(&self, future: F) -> Result<T, MessagingError>
+    where
+        F: Future<Output = Result<T, MessagingError>> + Send + 'static,
+        T: Send + 'static,
+    {
+        block_on(self.spawn(future)).map_err(unknown)?
+    }
+}
+
+impl Drop for Bridge {
+    fn drop(&mut self) {
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+fn connect(bridge: &Bridge, config: &NatsConfig) -> Result<Client, MessagingError> {
+    let mut options = ConnectOptions::new()
+        .connection_timeout(Duration::from_secs(config.connection_timeout_secs));
+    if let Some(name) = &config.name {
+        options = options.name(name);
+    }
+    let servers = config.servers.clone();
+    bridge.run(async move { options.connect(servers).await.map_err(map_connect_error) })
+}
+
+/// Look up the configured stream, creating it over `stream_subjects` if missing.
+async fn stream(
+    context: &jetstream::Context,
+    config: &NatsConfig,
+) -> Result<jetstream::stream::Stream, MessagingError> {
+    let name = config.stream.clone().unwrap_or_default();
+    context
+        .get_or_create_stream(jetstream::stream::Config {
+            name,
+            subjects: config.stream_subjects.clone(),
+            ..Default::default()
+        })
+        .await
+        .map_err(unknown)
+}
+
+// ============================================================================
+// Producer
+// ============================================================================
+
+/// Publishes messages to NATS subjects, through JetStream when configured.
+pub struct NatsProducer {
+    client: Client,
+    jetstream: Option<jetstream::Context>,
+    stats: Arc<MessagingMetrics>,
+    bridge: Bridge,
+}
+
+impl NatsProducer {
+    /// Connect to the servers and, for JetStream, make sure the stream exists.
+    pub fn connect(config: &NatsConfig) -> Result<Self, MessagingError> {
+        let bridge = Bridge::new()?;
+        let client = connect(&bridge, config)?;
+        let jetstream = if config.is_jetstream() {
+            let context = jetstream::new(client.clone());
+            let (ctx, config) = (context.clone(), config.clone());
+            bridge.run(async move { stream(&ctx, &config).await.map(|_| ()) })?;
+            Some(context)
+        } else {
+            None
+        };
+        Ok(Self {
+            client,
+            jetstream,
+            stats: Arc::new(MessagingMetrics::default()),
+            bridge,
+        })
+    }
+
+    /// Get messaging statistics.
+    pub fn stats(&self) -> MessagingStats {
+        self.stats.get_stats()
+    }
+
+    /// Flush pending publishes and close the connection.
+    pub fn close(&self) -> Result<(), MessagingError> {
+        let client = self.client.clone();
+        self.bridge.run(async move {
+            client.flush().await.map_err(unknown)?;
+            client.drain().await.map_err(unknown)
+        })
+    }
+
+    fn headers(key: Option<&str>) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(MSG_ID_HEADER, uuid::Uuid::new_v4().to_string().as_str());
+        if let Some(key) = key {
+            headers.insert(KEY_HEADER, key);
+        }
+        headers
+    }
+
+    /// Publish all messages, then wait until each one is stored (JetStream)
+    /// or flushed to the server (core NATS).
+    fn publish(
+        &self,
+        messages: Vec<(String, Option<String>, Vec<u8>)>,
+    ) -> Result<(), MessagingError> {
+        let client = self.client.clone();
+        let jetstream = self.jetstream.clone();
+        let stats = self.stats.clone();
+        self.bridge.run(async move {
+            match jetstream {
+                Some(context) => {
+                    let mut acks = Vec::with_capacity(messages.len());
+                    for (topic, key, value) in messages {
+                        let headers = Self::headers(key.as_deref());
+                        let ack = context
+                            .publish_with_headers(topic, headers, value.into())
+                            .await;
+                        acks.push(ack.map_err(unknown)?);
+                    }
+                    for ack in acks {
+                        ack.await.map_err(unknown)?;
+                        stats.record_sent();
+                    }
+                }
+                None => {
+                    let count = messages.len();
+                    for (topic, key, value) in messages {
+                        let headers = Self::headers(key.as_deref());
+                        client
+                            .publish_with_headers(topic, headers, value.into())
+                            .await
+                            .map_err(unknown)?;
+                    }
+                    client.flush().await.map_err(unknown)?;
+                    for _ in 0..count {
+                        stats.record_sent();
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+impl MessageProducer for NatsProducer {
+    fn send(&self, topic: &str, key: Option<&str>, value: &[u8]) -> Result<(), MessagingError> {
+        let message = (topic.to_string(), key.map(str::to_string), value.to_vec());
+        let result = self.publish(vec![message]);
+        if result.is_err() {
+            self.stats.record_failed();
+        }
+        result
+    }
+
+    fn send_batch(
+        &self,
+        messages: Vec<(String, Option<String>, Vec<u8>)>,
+    ) -> Result<(), MessagingError> {
+        let result = self.publish(messages);
+        if result.is_err() {
+            self.stats.record_failed();
+        }
+        result
+    }
+}
+
+// ============================================================================
+// Consumer
+// ============================================================================
+
+/// JetStream acknowledgement for a `MessageResult`.
+fn ack_kind(result: &MessageResult) -> AckKind {
+    match result {
+        MessageResult::Ack => AckKind::Ack,
+        MessageResult::Nack | MessageResult::Requeue => AckKind::Nak(None),
+        // JetStream has no dead-letter queue; terminated messages raise an
+        // advisory that a dead-letter handler can subscribe to
+        MessageResult::Reject | MessageResult::DeadLetter => AckKind::Term,
+    }
+}
+
+enum Delivery {
+    Core(async_nats::Message),
+    JetStream(jetstream::Message),
+}
+
+/// Consumes from NATS subjects.
+///
+/// With core NATS each topic is a (queue group) subscription. With JetStream
+/// each topic gets a pull consumer filtered to it, durable when a durable
+/// name or queue group is configured. JetStream messages stay unsettled until
+/// `commit` or `settle`, and are redelivered after `ack_wait_secs` otherwise.
+pub struct NatsConsumer {
+    config: NatsConfig,
+    client: Client,
+    jetstream: Option<jetstream::Context>,
+    sender: Sender<Delivery>,
+    receiver: Mutex<Receiver<Delivery>>,
+    subscriptions: RwLock<HashMap<String, JoinHandle<()>>>,
+    unsettled: Mutex<HashMap<String, jetstream::Message>>,
+    poll_timeout: Duration,
+    stats: Arc<MessagingMetrics>,
+    bridge: Bridge,
+}
+
+impl NatsConsumer {
+    /// Connect to the servers and, for JetStream, make sure the stream exists.
+    pub fn connect(config: &NatsConfig) -> Result<Self, MessagingError> {
+        let bridge = Bridge::new()?;
+        let client = connect(&bridge, config)?;
+        let jetstream = if config.is_jetstream() {
+            let context = jetstream::new(client.clone());
+            let (ctx, cfg) = (context.clone(), config.clone());
+            bridge.run(async move { stream(&ctx, &cfg).await.map(|_| ()) })?;
+            Some(context)
+        } else {
+            None
+        };
+        let (sender, receiver) = mpsc::channel();
+        Ok(Self {
+            config: config.clone(),
+            client,
+            jetstream,
+            sender,
+            receiver: Mutex::new(receiver),
+            subscriptions: RwLock::new(HashMap::new()),
+            unsettled: Mutex::new(HashMap::new()),
+            poll_timeout: DEFAULT_POLL_TIMEOUT,
+            stats: Arc::new(MessagingMetrics::default()),
+            bridge,
+        })
+    }
+
+    /// Set how long `poll` waits for the first message.
+    pub fn with_poll_timeout(mut self, timeout: Duration) -> Self {
+        self.poll_timeout = timeout;
+        self
+    }
+
+    /// Get messaging statistics.
+    pub fn stats(&self) -> MessagingStats {
+        self.stats.get_stats()
+    }
+
+    /// Settle a polled message according to `result`.
+    ///
+    /// Core NATS messages have nothing to settle, so this only records the
+    /// outcome for them.
+    pub fn settle(&self, message: &Message, result: MessageResult) -> Result<(), MessagingError> {
+        if result != MessageResult::Ack {
+            self.stats.record_failed();
+        }
+        if self.jetstream.is_none() {
+            return Ok(());
+        }
+        let delivery =
+            self.unsettled.lock().remove(&message.id).ok_or_else(|| {
+                MessagingError::Unknown(format!("Unknown delivery {}", message.id))
+            })?;
+        let kind = ack_kind(&result);
+        self.bridge
+            .run(async move { delivery.ack_with(kind).await.map_err(unknown) })
+    }
+
+    /// Stop all subscriptions and close the connection; unsettled JetStream
+    /// messages are redelivered.
+    pub fn close(&self) -> Result<(), MessagingError> {
+        for (_, task) in self.subscriptions.write().drain() {
+            task.abort();
+        }
+        let client = self.client.clone();
+        self.bridge
+            .run(async move { client.drain().await.map_err(unknown) })
+    }
+
+    /// Forward a core subscription into the poll channel.
+    fn subscribe_core(&self, subject: &str) -> Result<JoinHandle<()>, MessagingError> {
+        let client = self.client.clone();
+        let subject = subject.to_string();
+        let group = self.config.queue_group.clone();
+        let mut subscriber = self.bridge.run(async move {
+            match group {
+                Some(group) => client.queue_subscribe(subject, group).await,
+                None => client.subscribe(subject).await,
+            }
+            .map_err(unknown)
+        })?;
+        let sender = self.sender.clone();
+        Ok(self.bridge.spawn(async move {
+            while let Some(message) = subscriber.next().await {
+                if sender.send(Delivery::Core(message)).is_err() {
+                    break;
+                }
+            }
+        }))
+    }
+
+    /// Forward a JetStream pull consumer filtered to `subject` into the poll channel.
+    fn subscribe_jetstream(
+        &self,
+        context: &jetstream::Context,
+        subject: &str,
+    ) -> Result<JoinHandle<()>, MessagingError> {
+        let (context, config) = (context.clone(), self.config.clone());
+        let durable = config
+            .durable_name
+            .clone()
+            .or_else(|| config.queue_group.clone())
+            .map(|name| consumer_name(&name, subject));
+        let consumer_config = pull::Config {
+            durable_name: durable.clone(),
+            filter_subject: subject.to_string(),
+            ack_policy: jetstream::consumer::AckPolicy::Explicit,
+            ack_wait: Duration::from_secs(config.ack_wait_secs),
+            ..Default::default()
+        };
+        let mut messages = self.bridge.run(async move {
+            let stream = stream(&context, &config).await?;
+            let consumer: jetstream::consumer::Consumer<pull::Config> = match &durable {
+                Some(name) => stream.get_or_create_consumer(name, consumer_config).await,
+                None => stream.create_consumer(consumer_config).await,
+            }
+            .map_err(unknown)?;
+            consumer.messages().await.map_err(unknown)
+        })?;
+        let sender = self.sender.clone();
+        Ok(self.bridge.spawn(async move {
+            while let Some(message) = messages.next().await {
+                // Errors here are transient (heartbeats missed, reconnects);
+                // the stream keeps retrying on its own
+                if let Ok(message) = message {
+                    if sender.send(Delivery::JetStream(message)).is_err() {
+                        break;
+                    }
+                }
+            }
+        }))
+    }
+
+    fn to_message(&self, delivery: Delivery) -> Message {
+        let (message, jetstream) = match delivery {
+            Delivery::Core(message) => (message, None),
+            Delivery::JetStream(message) => (message.message.clone(), Some(message)),
+        };
+        let mut headers = HashMap::new();
+        if let Some(map) = &message.headers {
+            for (name, values) in map.iter() {
+                if let Some(value) = values.first() {
+                    headers.insert(name.to_string(), value.to_string());
+                }
+            }
+        }
+        let mut id = headers
+            .remove(MSG_ID_HEADER)
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        let mut timestamp = now_millis();
+        let mut offset = None;
+        if let Some(delivery) = jetstream {
+            if let Ok(info) = delivery.info() {
+                // Redeliveries reuse the publish id, so settle by sequence
+                id = info.stream_sequence.to_string();
+                offset = Some(info.stream_sequence as i64);
+                timestamp = (info.published.unix_timestamp_nanos() / 1_000_000) as u64;
+            }
+            self.unsettled.lock().insert(id.clone(), delivery);
+        }
+        Message {
+            id,
+            topic: message.subject.to_string(),
+            key: headers.remove(KEY_HEADER),
+            value: message.payload.to_vec(),
+            headers,
+            timestamp,
+            partition: None,
+            offset,
+        }
+    }
+}
+
+impl MessageConsumer for NatsConsumer {
+    fn subscribe(&self, topics: &[&str]) -> Result<(), MessagingError> {
+        for topic in topics {
+            if self.subscriptions.read().contains_key(*topic) {
+                continue;
+            }
+            let task = match &self.jetstream {
+                Some(context) => self.subscribe_jetstream(context, topic)?,
+                None => self.subscribe_core(topic)?,
+            };
+            self.subscriptions.write().insert(topic.to_string(), task);
+        }
+        self.stats
+            .set_active_consumers(self.subscriptions.read().len());
+        Ok(())
+    }
+
+    /// Wait up to the poll timeout for a message, then drain whatever else
+    /// has arrived, up to `max_batch` messages.
+    fn poll(&self) -> Result<Vec<Message>, MessagingError> {
+        let receiver = self.receiver.lock();
+        let first = match receiver.recv_timeout(self.poll_timeout) {
+            Ok(delivery) => delivery,
+            Err(RecvTimeoutError::Timeout) => return Ok(Vec::new()),
+            Err(RecvTimeoutError::Disconnected) => return Err(MessagingError::BrokerUnavailable),
+        };
+        let limit = self.config.max_batch.max(1);
+        let mut deliveries = vec![first];
+        while deliveries.len() < limit {
+            match receiver.try_recv() {
+                Ok(delivery) => deliveries.push(delivery),
+                Err(_) => break,
+            }
+        }
+        drop(receiver);
+
+        Ok(deliveries
+            .into_iter()
+            .map(|delivery| {
+                self.stats.record_received();
+                self.to_message(delivery)
+            })
+            .collect())
+    }
+
+    fn commit(&self, message: &Message) -> Result<(), MessagingError> {
+        self.settle(message, MessageResult::Ack)
+    }
+
+    fn settle(&self, message: &Message, result: MessageResult) -> Result<(), MessagingError> {
+        NatsConsumer::settle(self, message, result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ack_kind_mapping() {
+        assert!(matches!(ack_kind(&MessageResult::Ack), AckKind::Ack));
+        assert!(matches!(ack_kind(&MessageResult::Nack), AckKind::Nak(None)));
+        assert!(matches!(
+            ack_kind(&MessageResult::Requeue),
+            AckKind::Nak(None)
+        ));
+        assert!(matches!(ack_kind(&MessageResult::Reject), AckKind::Term));
+        assert!(matches!(
+            ack_kind(&MessageResult::DeadLetter),
+            AckKind::Term
+        ));
+    }
+
+    #[test]
+    fn test_consumer_name() {
+        assert_eq!(consumer_name("billing", "orders"), "billing-orders");
+        assert_eq!(
+            consumer_name("billing", "orders.*.created"),
+            "billing-orders___created"
+        );
+        assert_eq!(consumer_name("billing", "orders.>"), "billing-orders__");
+    }
+
+    #[test]
+    fn test_producer_headers() {
+        let headers = NatsProducer::headers(Some("order-42"));
+        assert_eq!(headers.get(KEY_HEADER).unwrap().as_str(), "order-42");
+        assert!(headers.get(MSG_ID_HEADER).is_some());
+        assert!(NatsProducer::headers(None).get(KEY_HEADER).is_none());
+    }
+
+    #[test]
+    fn test_connect_unreachable_server() {
+        let mut config = NatsConfig::default().with_servers(&["nats://127.0.0.1:1"]);
+        config.connection_timeout_secs = 1;
+        assert!(matches!(
+            NatsProducer::connect(&config),
+            Err(MessagingError::Connection) | Err(MessagingError::Timeout)
+        ));
+        assert!(matches!(
+            NatsConsumer::connect(&config),
+            Err(MessagingError::Connection) | Err(MessagingError::Timeout)
+        ));
+    }
+}
@@ -3,6 +3,7 @@
 //! Provides:
 //! - Request count metrics
 //! - Request duration histograms
+//! - Per-phase request duration histograms
 //! - Response size metrics
 //! - Status code distribution
 //! - Active requests gauge
@@ -11,16 +12,16 @@
 
 use prometheus::{
     register_counter_vec_with_registry, register_gauge_vec_with_registry,
-    register_histogram_vec_with_registry, CounterVec, Encoder, GaugeVec, HistogramVec, Registry,
-    TextEncoder,
+    register_histogram_vec_with_registry, CounterVec, Encoder, GaugeVec, Histogram, HistogramVec,
+    Registry, TextEncoder,
 };
 use std::collections::HashMap;
 use std::sync::Arc;
-use std::time::Instant;
 
-use super::{Middleware, MiddlewareAction, MiddlewareResult};
+use super::{Middleware, MiddlewareAction, MiddlewareResult, ResponseEnd};
 use crate::request::Request;
 use crate::response::Response;
+use crate::server::timing::{Phase, RequestTimer};
 
 // ============================================================================
 // Configuration
@@ -123,6 +124,10 @@ pub struct PrometheusMetrics {
     pub http_response_size_bytes: HistogramVec,
     /// HTTP requests in progress gauge
     pub http_requests_in_progress: GaugeVec,
+    /// Time spent in each phase of handling a request
+    pub http_request_phase_duration_seconds: HistogramVec,
+    /// `http_request_phase_duration_seconds` resolved for each phase
+    phase_histograms: Vec<Histogram>,
     /// The registry for these metrics
     pub registry: Arc<Registry>,
 }
@@ -207,16 +212,49 @@ impl PrometheusMetrics {
             registry.clone()
         )?;
 
+        // Request phase durations
+        let http_request_phase_duration_seconds = register_histogram_vec_with_registry!(
+            format!(
+                "{}_{}_request_phase_duration_seconds",
+                config.namespace, config.subsystem
+            ),
+            "Time spent in each phase of handling HTTP requests, in seconds",
+            &["phase"],
+            config.buckets.clone(),
+            registry.clone()
+        )?;
+        let phase_histograms = Phase::ALL
+            .iter()
+            .map(|phase| http_request_phase_duration_seconds.with_label_values(&[phase.name()]))
+            .collect();
+
         Ok(Self {
             http_requests_total,
             http_request_duration_seconds,
             http_request_size_bytes,
             http_response_size_bytes,
             http_requests_in_progress,
+            http_request_phase_duration_seconds,
+            phase_histograms,
             registry,
         })
     }
 
+    /// Observe the phases of a handled request.
+    pub fn observe_phases(&self, timer: &RequestTimer) {
+        for phase in Phase::ALL {
+            if phase != Phase::Write {
+                self.phase_histogram(phase)
+                    .observe(timer.spent(phase).as_secs_f64());
+            }
+        }
+    }
+
+    /// The phase duration histogram for `phase`.
+    pub fn phase_histogram(&self, phase: Phase) -> Histogram {
+        self.phase_histograms[phase as usize].clone()
+    }
+
     /// Get the metrics as a string in Prometheus text format.
     pub fn encode(&self) -> Result<String, prometheus::Error> {
         let encoder = TextEncoder::new();
@@ -231,7 +269,16 @@ impl PrometheusMetrics {
 // Middleware
 // ============================================================================
 
+/// Marks a request counted as in progress, until its response ends.
+#[derive(Clone, Copy)]
+struct InProgress;
+
 /// Prometheus metrics middleware.
+///
+/// Requests are counted when their response ends rather than in `after`,
+/// so streamed and SSE responses are measured with their full size and
+/// duration.
+#[derive(Clone)]
 pub struct PrometheusMiddleware {
     config: PrometheusConfig,
     metrics: Arc<PrometheusMetrics>,
@@ -267,6 +314,11 @@ impl PrometheusMiddleware {
         self.metrics.clone()
     }
 
+    /// Whether requests to `path` are measured.
+    pub fn observes(&self, path: &str) -> bool {
+        path != self.config.endpoint && !self.should_exclude(path)
+    }
+
     /// Check if path should be excluded from metrics.
     fn should_exclude(&self, path: &str) -> bool {
         self.config
@@ -324,13 +376,18 @@ impl PrometheusMiddleware {
         labels
     }
 
-    /// Serve metrics endpoint.
-    fn serve_metrics(&self) -> Response {
+    /// The metrics page, if `path` is the metrics endpoint.
+    pub fn endpoint_response(&self, path: &str, gzip: bool) -> Option<Response> {
+        (path == self.config.endpoint).then(|| self.serve_metrics(gzip))
+    }
+
+    /// Serve metrics endpoint, gzip-compressed when `gzip` is set.
+    fn serve_metrics(&self, gzip: bool) -> Response {
         match self.metrics.encode() {
             Ok(metrics) => {
                 let mut response = Response::new(200);
                 response.set_header("Content-Type", "text/plain; version=0.0.4");
-                response.set_body(metrics.into_bytes());
+                crate::server::stats::set_body(&mut response, metrics.into_bytes(), gzip);
                 response
             }
             Err(e) => {
@@ -352,7 +409,8 @@ impl Middleware for PrometheusMiddleware {
     fn before(&self, request: &mut Request) -> MiddlewareResult {
         // Serve metrics endpoint
         if request.path == self.config.endpoint {
-            return Ok(MiddlewareAction::Stop(self.serve_metrics()));
+            let gzip = crate::server::stats::accepts_gzip(request.headers.get("accept-encoding"));
+            return Ok(MiddlewareAction::Stop(self.serve_metrics(gzip)));
         }
 
         // Skip excluded paths
@@ -387,22 +445,26 @@ impl Middleware for PrometheusMiddleware {
                 .observe(request.body.len() as f64);
         }
 
-        // Store start time in request context
-        request.context.insert(
-            "__prometheus_start_time__".to_string(),
-            serde_json::json!(Instant::now().elapsed().as_secs_f64()),
-        );
+        request.extensions.insert(InProgress);
 
         Ok(MiddlewareAction::Continue)
     }
 
-    fn after(&self, request: &Request, response: &mut Response) -> MiddlewareResult {
-        // Skip excluded paths
-        if self.should_exclude(&request.path) {
-            return Ok(MiddlewareAction::Continue);
+    fn name(&self) -> &str {
+        "prometheus"
+    }
+
+    fn observes_response_end(&self) -> bool {
+        true
+    }
+
+    fn on_response_end(&self, end: &ResponseEnd) {
+        // Only requests counted in progress by `before`
+        if end.extensions.remove::<InProgress>().is_none() {
+            return;
         }
 
-        let labels = self.build_labels(&request.method, &request.path, response.status);
+        let labels = self.build_labels(&end.method, &end.path, end.status);
         let label_refs: Vec<&str> = labels.iter().map(|s| s.as_str()).collect();
 
         // Increment request counter
@@ -422,35 +484,24 @@ impl Middleware for PrometheusMiddleware {
             .with_label_values(&in_progress_labels)
             .dec();
 
-        // Track response size
+        // Track response size, as sent
         if self.config.track_body_size {
             self.metrics
                 .http_response_size_bytes
                 .with_label_values(&label_refs)
-                .observe(response.body_bytes().len() as f64);
+                .observe(end.bytes as f64);
         }
 
-        // Track request duration
-        if let Some(start_time) = request.context.get("__prometheus_start_time__") {
-            if let Some(start) = start_time.as_f64() {
-                let duration = Instant::now().elapsed().as_secs_f64() - start;
-                self.metrics
-                    .http_request_duration_seconds
-                    .with_label_values(&label_refs)
-                    .observe(duration);
-            }
-        }
-
-        Ok(MiddlewareAction::Continue)
+        // Track request duration, up to the end of the body
+        self.metrics
+            .http_request_duration_seconds
+            .with_label_values(&label_refs)
+            .observe(end.duration.as_secs_f64());
     }
 
     fn priority(&self) -> i32 {
         -200 // Run very early
     }
-
-    fn name(&self) -> &str {
-        "prometheus"
-    }
 }
 
 #[cfg(test)]
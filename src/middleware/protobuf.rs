@@ -0,0 +1,1462 @@
+//! Protocol Buffers support for Cello.
+//!
+//! Provides:
+//! - Message and enum descriptors, collected in a `DescriptorPool`
+//! - Wire-format encoding/decoding against those descriptors
+//! - Route bindings so plain HTTP endpoints accept and return
+//!   `application/x-protobuf` bodies
+//! - Loading messages, enums and services from compiled `FileDescriptorSet`s
+//!
+//! The same pool backs the gRPC server (see `grpc.rs`), so a message type is
+//! described once and used by both gRPC services and HTTP routes.
+//!
+//! Messages map to the JSON data model the rest of Cello uses: fields by
+//! their proto name, enums as value names, `bytes` as base64 strings, and
+//! 64-bit integers as numbers (strings are accepted when encoding).
+//!
+//! # Example
+//! ```python
+//! app.register_proto_message("shop.Item", [
+//!     ("id", 1, "int64"),
+//!     ("name", 2, "string"),
+//!     ("tags", 3, "string", True),
+//! ])
+//!
+//! @app.post("/items")
+//! @protobuf_body(request="shop.Item", response="shop.Item")
+//! def create_item(request):
+//!     return request.data()
+//! ```
+
+use base64::Engine;
+use parking_lot::RwLock;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+use thiserror::Error;
+
+use crate::json::{json_i64, DEFAULT_MAX_JSON_DEPTH};
+
+// ============================================================================
+// Errors
+// ============================================================================
+
+/// Error raised while describing, encoding or decoding protobuf messages.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ProtobufError {
+    #[error("Unknown protobuf type: {0}")]
+    UnknownType(String),
+
+    #[error("Invalid descriptor: {0}")]
+    InvalidDescriptor(String),
+
+    #[error("Field '{field}' expects {expected}")]
+    TypeMismatch {
+        field: String,
+        expected: &'static str,
+    },
+
+    #[error("Malformed protobuf message: {0}")]
+    Malformed(String),
+
+    #[error("Protobuf nesting depth exceeds limit of {limit}")]
+    DepthLimitExceeded { limit: usize },
+}
+
+impl From<ProtobufError> for String {
+    fn from(err: ProtobufError) -> Self {
+        err.to_string()
+    }
+}
+
+// ============================================================================
+// Descriptors
+// ============================================================================
+
+/// Protobuf scalar value types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScalarType {
+    Double,
+    Float,
+    Int32,
+    Int64,
+    UInt32,
+    UInt64,
+    SInt32,
+    SInt64,
+    Fixed32,
+    Fixed64,
+    SFixed32,
+    SFixed64,
+    Bool,
+    String,
+    Bytes,
+}
+
+impl ScalarType {
+    /// Parse a `.proto` scalar type name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "double" => ScalarType::Double,
+            "float" => ScalarType::Float,
+            "int32" => ScalarType::Int32,
+            "int64" => ScalarType::Int64,
+            "uint32" => ScalarType::UInt32,
+            "uint64" => ScalarType::UInt64,
+            "sint32" => ScalarType::SInt32,
+            "sint64" => ScalarType::SInt64,
+            "fixed32" => ScalarType::Fixed32,
+            "fixed64" => ScalarType::Fixed64,
+            "sfixed32" => ScalarType::SFixed32,
+            "sfixed64" => ScalarType::SFixed64,
+            "bool" => ScalarType::Bool,
+            "string" => ScalarType::String,
+            "bytes" => ScalarType::Bytes,
+            _ => return None,
+        })
+    }
+
+    fn wire_type(self) -> WireType {
+        match self {
+            ScalarType::Double | ScalarType::Fixed64 | ScalarType::SFixed64 => WireType::Fixed64,
+            ScalarType::Float | ScalarType::Fixed32 | ScalarType::SFixed32 => WireType::Fixed32,
+            ScalarType::String | ScalarType::Bytes => WireType::Len,
+            _ => WireType::Varint,
+        }
+    }
+
+    /// proto3 default value in the JSON data model.
+    fn default_value(self) -> Value {
+        match self {
+            ScalarType::Double | ScalarType::Float => Value::from(0.0),
+            ScalarType::Bool => Value::Bool(false),
+            ScalarType::String | ScalarType::Bytes => Value::String(String::new()),
+            _ => Value::from(0),
+        }
+    }
+}
+
+/// Type of a message field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldType {
+    Scalar(ScalarType),
+    /// A message or enum, resolved against the pool when used
+    Named(String),
+}
+
+impl FieldType {
+    /// Parse a type name: a scalar keyword or a message/enum name.
+    pub fn parse(name: &str) -> Self {
+        match ScalarType::from_name(name) {
+            Some(scalar) => FieldType::Scalar(scalar),
+            None => FieldType::Named(name.trim_start_matches('.').to_string()),
+        }
+    }
+}
+
+/// A field within a message descriptor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldDescriptor {
+    /// Field name as written in the `.proto` file
+    pub name: String,
+    /// Field number (tag)
+    pub number: u32,
+    /// Field type
+    pub field_type: FieldType,
+    /// Whether the field is `repeated`
+    pub repeated: bool,
+}
+
+impl FieldDescriptor {
+    /// Create a singular field.
+    pub fn new(name: &str, number: u32, field_type: FieldType) -> Self {
+        Self {
+            name: name.to_string(),
+            number,
+            field_type,
+            repeated: false,
+        }
+    }
+
+    /// Mark the field as `repeated`.
+    pub fn repeated(mut self) -> Self {
+        self.repeated = true;
+        self
+    }
+}
+
+/// A message type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageDescriptor {
+    /// Fully-qualified name (e.g. "shop.Item")
+    pub name: String,
+    /// Fields in declaration order
+    pub fields: Vec<FieldDescriptor>,
+}
+
+impl MessageDescriptor {
+    /// Create an empty message descriptor.
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            fields: Vec::new(),
+        }
+    }
+
+    /// Add a field.
+    pub fn field(mut self, field: FieldDescriptor) -> Self {
+        self.fields.push(field);
+        self
+    }
+
+    /// Look up a field by number.
+    pub fn field_by_number(&self, number: u32) -> Option<&FieldDescriptor> {
+        self.fields.iter().find(|f| f.number == number)
+    }
+
+    fn check(&self) -> Result<(), ProtobufError> {
+        for (i, field) in self.fields.iter().enumerate() {
+            if field.number == 0 || field.number > MAX_FIELD_NUMBER {
+                return Err(ProtobufError::InvalidDescriptor(format!(
+                    "{}.{} has invalid field number {}",
+                    self.name, field.name, field.number
+                )));
+            }
+            if self.fields[..i]
+                .iter()
+                .any(|f| f.number == field.number || f.name == field.name)
+            {
+                return Err(ProtobufError::InvalidDescriptor(format!(
+                    "{}.{} duplicates another field",
+                    self.name, field.name
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An enum type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnumDescriptor {
+    /// Fully-qualified name
+    pub name: String,
+    /// Value names and numbers
+    pub values: Vec<(String, i32)>,
+}
+
+impl EnumDescriptor {
+    /// Create an enum descriptor.
+    pub fn new(name: &str, values: Vec<(String, i32)>) -> Self {
+        Self {
+            name: name.to_string(),
+            values,
+        }
+    }
+
+    fn number_of(&self, name: &str) -> Option<i32> {
+        self.values.iter().find(|(n, _)| n == name).map(|(_, v)| *v)
+    }
+
+    fn name_of(&self, number: i32) -> Option<&str> {
+        self.values
+            .iter()
+            .find(|(_, v)| *v == number)
+            .map(|(n, _)| n.as_str())
+    }
+}
+
+/// A method within a service.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MethodDescriptor {
+    /// Method name
+    pub name: String,
+    /// Fully-qualified request message type
+    pub input_type: String,
+    /// Fully-qualified response message type
+    pub output_type: String,
+    /// Whether the client sends a stream of requests
+    pub client_streaming: bool,
+    /// Whether the server sends a stream of responses
+    pub server_streaming: bool,
+    /// REST mappings from the method's `google.api.http` option
+    pub http_rules: Vec<HttpRule>,
+}
+
+/// A REST mapping for a method, as declared by a `google.api.http` option.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpRule {
+    /// HTTP method (e.g. "GET")
+    pub method: String,
+    /// Path template (e.g. "/v1/items/{id}")
+    pub path: String,
+    /// Request field filled from the body: `*` for the whole message
+    pub body: Option<String>,
+    /// Response field returned as the body instead of the whole message
+    pub response_body: Option<String>,
+}
+
+impl HttpRule {
+    /// Create a rule without a body mapping.
+    pub fn new(method: &str, path: &str) -> Self {
+        Self {
+            method: method.to_uppercase(),
+            path: path.to_string(),
+            body: None,
+            response_body: None,
+        }
+    }
+
+    /// Map the request body to `field` (`*` for the whole message).
+    pub fn with_body(mut self, field: &str) -> Self {
+        self.body = Some(field.to_string());
+        self
+    }
+
+    /// Return only `field` of the response message.
+    pub fn with_response_body(mut self, field: &str) -> Self {
+        self.response_body = Some(field.to_string());
+        self
+    }
+}
+
+/// A service type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceDescriptor {
+    /// Fully-qualified name (e.g. "shop.Catalog")
+    pub name: String,
+    /// Methods in declaration order
+    pub methods: Vec<MethodDescriptor>,
+}
+
+impl ServiceDescriptor {
+    /// Look up a method by name.
+    pub fn method(&self, name: &str) -> Option<&MethodDescriptor> {
+        self.methods.iter().find(|m| m.name == name)
+    }
+}
+
+/// Largest valid protobuf field number.
+const MAX_FIELD_NUMBER: u32 = (1 << 29) - 1;
+
+/// Registry of message, enum and service types.
+#[derive(Debug, Clone, Default)]
+pub struct DescriptorPool {
+    messages: HashMap<String, MessageDescriptor>,
+    enums: HashMap<String, EnumDescriptor>,
+    services: HashMap<String, ServiceDescriptor>,
+}
+
+/// Descriptor pool shared between the gRPC server and HTTP routes.
+pub type SharedDescriptorPool = Arc<RwLock<DescriptorPool>>;
+
+enum Resolved<'a> {
+    Message(&'a MessageDescriptor),
+    Enum(&'a EnumDescriptor),
+}
+
+impl DescriptorPool {
+    /// Create an empty pool.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register (or replace) a message type.
+    pub fn add_message(&mut self, message: MessageDescriptor) -> Result<(), ProtobufError> {
+        message.check()?;
+        self.messages.insert(message.name.clone(), message);
+        Ok(())
+    }
+
+    /// Register (or replace) an enum type.
+    pub fn add_enum(&mut self, descriptor: EnumDescriptor) {
+        self.enums.insert(descriptor.name.clone(), descriptor);
+    }
+
+    /// Look up a message type.
+    pub fn message(&self, name: &str) -> Option<&MessageDescriptor> {
+        self.messages.get(name.trim_start_matches('.'))
+    }
+
+    /// Look up an enum type.
+    pub fn enum_type(&self, name: &str) -> Option<&EnumDescriptor> {
+        self.enums.get(name.trim_start_matches('.'))
+    }
+
+    /// Names of all registered message types.
+    pub fn message_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.messages.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Register (or replace) a service type.
+    pub fn add_service(&mut self, service: ServiceDescriptor) {
+        self.services.insert(service.name.clone(), service);
+    }
+
+    /// Look up a service type.
+    pub fn service(&self, name: &str) -> Option<&ServiceDescriptor> {
+        self.services.get(name.trim_start_matches('.'))
+    }
+
+    /// Names of all registered service types.
+    pub fn service_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.services.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    fn resolve(&self, name: &str) -> Result<Resolved<'_>, ProtobufError> {
+        if let Some(message) = self.message(name) {
+            return Ok(Resolved::Message(message));
+        }
+        if let Some(descriptor) = self.enum_type(name) {
+            return Ok(Resolved::Enum(descriptor));
+        }
+        Err(ProtobufError::UnknownType(name.to_string()))
+    }
+
+    fn require_message(&self, name: &str) -> Result<&MessageDescriptor, ProtobufError> {
+        self.message(name)
+            .ok_or_else(|| ProtobufError::UnknownType(name.to_string()))
+    }
+
+    /// Encode a JSON value (an object) as the named message.
+    pub fn encode(&self, message_type: &str, value: &Value) -> Result<Vec<u8>, ProtobufError> {
+        let message = self.require_message(message_type)?;
+        let mut buf = Vec::with_capacity(64);
+        self.encode_message(message, value, &mut buf, 0)?;
+        Ok(buf)
+    }
+
+    /// Decode bytes as the named message into a JSON object.
+    pub fn decode(&self, message_type: &str, input: &[u8]) -> Result<Value, ProtobufError> {
+        let message = self.require_message(message_type)?;
+        self.decode_message(message, input, 0)
+    }
+}
+
+// ============================================================================
+// Wire Format
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WireType {
+    Varint = 0,
+    Fixed64 = 1,
+    Len = 2,
+    Fixed32 = 5,
+}
+
+fn write_varint(mut value: u64, buf: &mut Vec<u8>) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn write_tag(number: u32, wire_type: WireType, buf: &mut Vec<u8>) {
+    write_varint(((number as u64) << 3) | wire_type as u64, buf);
+}
+
+fn write_len_prefixed(bytes: &[u8], buf: &mut Vec<u8>) {
+    write_varint(bytes.len() as u64, buf);
+    buf.extend_from_slice(bytes);
+}
+
+fn check_depth(depth: usize) -> Result<(), ProtobufError> {
+    if depth >= DEFAULT_MAX_JSON_DEPTH {
+        return Err(ProtobufError::DepthLimitExceeded {
+            limit: DEFAULT_MAX_JSON_DEPTH,
+        });
+    }
+    Ok(())
+}
+
+fn mismatch(field: &FieldDescriptor, expected: &'static str) -> ProtobufError {
+    ProtobufError::TypeMismatch {
+        field: field.name.clone(),
+        expected,
+    }
+}
+
+fn json_u64(value: &Value) -> Option<u64> {
+    match value {
+        Value::Number(n) => n.as_u64(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+fn json_f64(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+impl DescriptorPool {
+    fn encode_message(
+        &self,
+        message: &MessageDescriptor,
+        value: &Value,
+        buf: &mut Vec<u8>,
+        depth: usize,
+    ) -> Result<(), ProtobufError> {
+        check_depth(depth)?;
+        let Value::Object(object) = value else {
+            return Err(ProtobufError::TypeMismatch {
+                field: message.name.clone(),
+                expected: "an object",
+            });
+        };
+
+        for field in &message.fields {
+            let Some(value) = object.get(&field.name).filter(|v| !v.is_null()) else {
+                continue;
+            };
+            if field.repeated {
+                let Value::Array(items) = value else {
+                    return Err(mismatch(field, "a list"));
+                };
+                self.encode_repeated(field, items, buf, depth)?;
+            } else {
+                self.encode_field(field, value, true, buf, depth)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn encode_repeated(
+        &self,
+        field: &FieldDescriptor,
+        items: &[Value],
+        buf: &mut Vec<u8>,
+        depth: usize,
+    ) -> Result<(), ProtobufError> {
+        // Numeric scalars and enums are packed into one length-delimited record
+        let packed = match &field.field_type {
+            FieldType::Scalar(scalar) => scalar.wire_type() != WireType::Len,
+            FieldType::Named(name) => matches!(self.resolve(name)?, Resolved::Enum(_)),
+        };
+        if !packed {
+            for item in items {
+                self.encode_field(field, item, false, buf, depth)?;
+            }
+            return Ok(());
+        }
+        if items.is_empty() {
+            return Ok(());
+        }
+
+        let mut packed_buf = Vec::with_capacity(items.len() * 2);
+        for item in items {
+            self.encode_value(field, item, &mut packed_buf, depth)?;
+        }
+        write_tag(field.number, WireType::Len, buf);
+        write_len_prefixed(&packed_buf, buf);
+        Ok(())
+    }
+
+    /// Encode one field record (tag + value). Singular proto3 defaults are skipped.
+    fn encode_field(
+        &self,
+        field: &FieldDescriptor,
+        value: &Value,
+        skip_default: bool,
+        buf: &mut Vec<u8>,
+        depth: usize,
+    ) -> Result<(), ProtobufError> {
+        let mut value_buf = Vec::new();
+        let wire_type = self.encode_value(field, value, &mut value_buf, depth)?;
+        let is_default = match wire_type {
+            WireType::Len => value_buf == [0] && !self.is_message(field),
+            _ => value_buf.iter().all(|b| *b == 0),
+        };
+        if skip_default && is_default {
+            return Ok(());
+        }
+        write_tag(field.number, wire_type, buf);
+        buf.extend_from_slice(&value_buf);
+        Ok(())
+    }
+
+    fn is_message(&self, field: &FieldDescriptor) -> bool {
+        matches!(&field.field_type, FieldType::Named(name) if self.message(name).is_some())
+    }
+
+    /// Encode a single value without its tag, returning its wire type.
+    fn encode_value(
+        &self,
+        field: &FieldDescriptor,
+        value: &Value,
+        buf: &mut Vec<u8>,
+        depth: usize,
+    ) -> Result<WireType, ProtobufError> {
+        let scalar = match &field.field_type {
+            FieldType::Scalar(scalar) => *scalar,
+            FieldType::Named(name) => match self.resolve(name)? {
+                Resolved::Message(message) => {
+                    let mut nested = Vec::new();
+                    self.encode_message(message, value, &mut nested, depth + 1)?;
+                    write_len_prefixed(&nested, buf);
+                    return Ok(WireType::Len);
+                }
+                Resolved::Enum(descriptor) => {
+                    let number = match value {
+                        Value::String(name) => descriptor.number_of(name),
+                        other => json_i64(other).map(|n| n as i32),
+                    }
+                    .ok_or_else(|| mismatch(field, "an enum value"))?;
+                    write_varint(number as i64 as u64, buf);
+                    return Ok(WireType::Varint);
+                }
+            },
+        };
+
+        match scalar {
+            ScalarType::Double => {
+                let f = json_f64(value).ok_or_else(|| mismatch(field, "a number"))?;
+                buf.extend_from_slice(&f.to_le_bytes());
+            }
+            ScalarType::Float => {
+                let f = json_f64(value).ok_or_else(|| mismatch(field, "a number"))?;
+                buf.extend_from_slice(&(f as f32).to_le_bytes());
+            }
+            ScalarType::Int32 | ScalarType::Int64 => {
+                let i = json_i64(value).ok_or_else(|| mismatch(field, "an integer"))?;
+                let i = if scalar == ScalarType::Int32 {
+                    i32::try_from(i).map_err(|_| mismatch(field, "a 32-bit integer"))? as i64
+                } else {
+                    i
+                };
+                write_varint(i as u64, buf);
+            }
+            ScalarType::UInt32 | ScalarType::UInt64 => {
+                let u = json_u64(value).ok_or_else(|| mismatch(field, "an unsigned integer"))?;
+                if scalar == ScalarType::UInt32 && u > u32::MAX as u64 {
+                    return Err(mismatch(field, "a 32-bit unsigned integer"));
+                }
+                write_varint(u, buf);
+            }
+            ScalarType::SInt32 | ScalarType::SInt64 => {
+                let i = json_i64(value).ok_or_else(|| mismatch(field, "an integer"))?;
+                if scalar == ScalarType::SInt32 && i32::try_from(i).is_err() {
+                    return Err(mismatch(field, "a 32-bit integer"));
+                }
+                write_varint(((i << 1) ^ (i >> 63)) as u64, buf);
+            }
+            ScalarType::Fixed32 => {
+                let u = json_u64(value)
+                    .and_then(|u| u32::try_from(u).ok())
+                    .ok_or_else(|| mismatch(field, "a 32-bit unsigned integer"))?;
+                buf.extend_from_slice(&u.to_le_bytes());
+            }
+            ScalarType::Fixed64 => {
+                let u = json_u64(value).ok_or_else(|| mismatch(field, "an unsigned integer"))?;
+                buf.extend_from_slice(&u.to_le_bytes());
+            }
+            ScalarType::SFixed32 => {
+                let i = json_i64(value)
+                    .and_then(|i| i32::try_from(i).ok())
+                    .ok_or_else(|| mismatch(field, "a 32-bit integer"))?;
+                buf.extend_from_slice(&i.to_le_bytes());
+            }
+            ScalarType::SFixed64 => {
+                let i = json_i64(value).ok_or_else(|| mismatch(field, "an integer"))?;
+                buf.extend_from_slice(&i.to_le_bytes());
+            }
+            ScalarType::Bool => {
+                let b = value
+                    .as_bool()
+                    .ok_or_else(|| mismatch(field, "a boolean"))?;
+                buf.push(b as u8);
+            }
+            ScalarType::String => {
+                let s = value.as_str().ok_or_else(|| mismatch(field, "a string"))?;
+                write_len_prefixed(s.as_bytes(), buf);
+            }
+            ScalarType::Bytes => {
+                let s = value
+                    .as_str()
+                    .ok_or_else(|| mismatch(field, "a base64 string"))?;
+                let bytes = base64::engine::general_purpose::STANDARD
+                    .decode(s)
+                    .map_err(|_| mismatch(field, "a base64 string"))?;
+                write_len_prefixed(&bytes, buf);
+            }
+        }
+        Ok(scalar.wire_type())
+    }
+
+    fn decode_message(
+        &self,
+        message: &MessageDescriptor,
+        input: &[u8],
+        depth: usize,
+    ) -> Result<Value, ProtobufError> {
+        check_depth(depth)?;
+
+        // proto3 semantics: absent scalars read as their defaults
+        let mut object = Map::with_capacity(message.fields.len());
+        for field in &message.fields {
+            let default = if field.repeated {
+                Some(Value::Array(Vec::new()))
+            } else {
+                match &field.field_type {
+                    FieldType::Scalar(scalar) => Some(scalar.default_value()),
+                    FieldType::Named(name) => match self.resolve(name)? {
+                        Resolved::Enum(descriptor) => Some(
+                            descriptor
+                                .name_of(0)
+                                .map(Value::from)
+                                .unwrap_or_else(|| Value::from(0)),
+                        ),
+                        Resolved::Message(_) => None,
+                    },
+                }
+            };
+            if let Some(default) = default {
+                object.insert(field.name.clone(), default);
+            }
+        }
+
+        let mut reader = WireReader { input, pos: 0 };
+        while !reader.is_empty() {
+            let key = reader.varint()?;
+            let number = (key >> 3) as u32;
+            let wire_type = (key & 0x7) as u8;
+            let Some(field) = message.field_by_number(number) else {
+                reader.skip(wire_type)?;
+                continue;
+            };
+
+            // Packed repeated numerics arrive as one length-delimited record
+            let packable = matches!(&field.field_type, FieldType::Scalar(s) if s.wire_type() != WireType::Len)
+                || matches!(&field.field_type, FieldType::Named(n) if self.enum_type(n).is_some());
+            if field.repeated && packable && wire_type == WireType::Len as u8 {
+                let mut packed = WireReader {
+                    input: reader.len_delimited()?,
+                    pos: 0,
+                };
+                while !packed.is_empty() {
+                    let value = self.decode_value(field, &mut packed, depth)?;
+                    push_repeated(&mut object, field, value);
+                }
+                continue;
+            }
+
+            let value = self.decode_value_checked(field, wire_type, &mut reader, depth)?;
+            if field.repeated {
+                push_repeated(&mut object, field, value);
+            } else {
+                object.insert(field.name.clone(), value);
+            }
+        }
+        Ok(Value::Object(object))
+    }
+
+    fn decode_value_checked(
+        &self,
+        field: &FieldDescriptor,
+        wire_type: u8,
+        reader: &mut WireReader<'_>,
+        depth: usize,
+    ) -> Result<Value, ProtobufError> {
+        let expected = match &field.field_type {
+            FieldType::Scalar(scalar) => scalar.wire_type(),
+            FieldType::Named(name) => match self.resolve(name)? {
+                Resolved::Message(_) => WireType::Len,
+                Resolved::Enum(_) => WireType::Varint,
+            },
+        };
+        if wire_type != expected as u8 {
+            return Err(ProtobufError::Malformed(format!(
+                "field '{}' has wire type {wire_type}, expected {}",
+                field.name, expected as u8
+            )));
+        }
+        self.decode_value(field, reader, depth)
+    }
+
+    fn decode_value(
+        &self,
+        field: &FieldDescriptor,
+        reader: &mut WireReader<'_>,
+        depth: usize,
+    ) -> Result<Value, ProtobufError> {
+        let scalar = match &field.field_type {
+            FieldType::Scalar(scalar) => *scalar,
+            FieldType::Named(name) => {
+                return match self.resolve(name)? {
+                    Resolved::Message(message) => {
+                        self.decode_message(message, reader.len_delimited()?, depth + 1)
+                    }
+                    Resolved::Enum(descriptor) => {
+                        let number = reader.varint()? as i32;
+                        Ok(descriptor
+                            .name_of(number)
+                            .map(Value::from)
+                            .unwrap_or_else(|| Value::from(number)))
+                    }
+                };
+            }
+        };
+
+        Ok(match scalar {
+            ScalarType::Double => Value::from(f64::from_le_bytes(reader.fixed::<8>()?)),
+            ScalarType::Float => Value::from(f32::from_le_bytes(reader.fixed::<4>()?) as f64),
+            ScalarType::Int32 => Value::from(reader.varint()? as i32),
+            ScalarType::Int64 => Value::from(reader.varint()? as i64),
+            ScalarType::UInt32 => Value::from(reader.varint()? as u32),
+            ScalarType::UInt64 => Value::from(reader.varint()?),
+            ScalarType::SInt32 | ScalarType::SInt64 => {
+                let n = reader.varint()?;
+                let i = ((n >> 1) as i64) ^ -((n & 1) as i64);
+                if scalar == ScalarType::SInt32 {
+                    Value::from(i as i32)
+                } else {
+                    Value::from(i)
+                }
+            }
+            ScalarType::Fixed32 => Value::from(u32::from_le_bytes(reader.fixed::<4>()?)),
+            ScalarType::Fixed64 => Value::from(u64::from_le_bytes(reader.fixed::<8>()?)),
+            ScalarType::SFixed32 => Value::from(i32::from_le_bytes(reader.fixed::<4>()?)),
+            ScalarType::SFixed64 => Value::from(i64::from_le_bytes(reader.fixed::<8>()?)),
+            ScalarType::Bool => Value::Bool(reader.varint()? != 0),
+            ScalarType::String => {
+                let bytes = reader.len_delimited()?;
+                Value::String(
+                    std::str::from_utf8(bytes)
+                        .map_err(|_| {
+                            ProtobufError::Malformed(format!(
+                                "field '{}' is not valid UTF-8",
+                                field.name
+                            ))
+                        })?
+                        .to_string(),
+                )
+            }
+            ScalarType::Bytes => Value::String(
+                base64::engine::general_purpose::STANDARD.encode(reader.len_delimited()?),
+            ),
+        })
+    }
+}
+
+fn push_repeated(object: &mut Map<String, Value>, field: &FieldDescriptor, value: Value) {
+    if let Some(Value::Array(items)) = object.get_mut(&field.name) {
+        items.push(value);
+    }
+}
+
+/// Cursor over protobuf wire-format bytes.
+struct WireReader<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> WireReader<'a> {
+    fn is_empty(&self) -> bool {
+        self.pos >= self.input.len()
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], ProtobufError> {
+        if self.input.len() - self.pos < n {
+            return Err(ProtobufError::Malformed(
+                "unexpected end of input".to_string(),
+            ));
+        }
+        let bytes = &self.input[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn varint(&mut self) -> Result<u64, ProtobufError> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.take(1)?[0];
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(ProtobufError::Malformed("varint is too long".to_string()))
+    }
+
+    fn fixed<const N: usize>(&mut self) -> Result<[u8; N], ProtobufError> {
+        let mut out = [0u8; N];
+        out.copy_from_slice(self.take(N)?);
+        Ok(out)
+    }
+
+    fn len_delimited(&mut self) -> Result<&'a [u8], ProtobufError> {
+        let len = usize::try_from(self.varint()?)
+            .map_err(|_| ProtobufError::Malformed("length overflows".to_string()))?;
+        self.take(len)
+    }
+
+    /// Skip an unknown field.
+    fn skip(&mut self, wire_type: u8) -> Result<(), ProtobufError> {
+        match wire_type {
+            0 => {
+                self.varint()?;
+            }
+            1 => {
+                self.take(8)?;
+            }
+            2 => {
+                self.len_delimited()?;
+            }
+            5 => {
+                self.take(4)?;
+            }
+            other => {
+                return Err(ProtobufError::Malformed(format!(
+                    "unsupported wire type {other}"
+                )))
+            }
+        }
+        Ok(())
+    }
+}
+
+// ============================================================================
+// Descriptor Sets
+// ============================================================================
+
+/// `FieldDescriptorProto.Label.LABEL_REPEATED`
+const LABEL_REPEATED: i64 = 3;
+
+/// Extension number of `google.api.http` on `MethodOptions`.
+const HTTP_RULE_EXTENSION: u32 = 72295728;
+
+/// The subset of `google/protobuf/descriptor.proto` needed to read descriptor sets.
+fn descriptor_proto_pool() -> &'static DescriptorPool {
+    static POOL: OnceLock<DescriptorPool> = OnceLock::new();
+    POOL.get_or_init(|| {
+        let field = |name, number, type_name| {
+            FieldDescriptor::new(name, number, FieldType::parse(type_name))
+        };
+        let messages = [
+            MessageDescriptor::new("google.protobuf.FileDescriptorSet")
+                .field(field("file", 1, "google.protobuf.FileDescriptorProto").repeated()),
+            MessageDescriptor::new("google.protobuf.FileDescriptorProto")
+                .field(field("name", 1, "string"))
+                .field(field("package", 2, "string"))
+                .field(field("message_type", 4, "google.protobuf.DescriptorProto").repeated())
+                .field(field("enum_type", 5, "google.protobuf.EnumDescriptorProto").repeated())
+                .field(field("service", 6, "google.protobuf.ServiceDescriptorProto").repeated()),
+            MessageDescriptor::new("google.protobuf.DescriptorProto")
+                .field(field("name", 1, "string"))
+                .field(field("field", 2, "google.protobuf.FieldDescriptorProto").repeated())
+                .field(field("nested_type", 3, "google.protobuf.DescriptorProto").repeated())
+                .field(field("enum_type", 4, "google.protobuf.EnumDescriptorProto").repeated()),
+            MessageDescriptor::new("google.protobuf.FieldDescriptorProto")
+                .field(field("name", 1, "string"))
+                .field(field("number", 3, "int32"))
+                .field(field("label", 4, "int32"))
+                .field(field("type", 5, "int32"))
+                .field(field("type_name", 6, "string")),
+            MessageDescriptor::new("google.protobuf.EnumDescriptorProto")
+                .field(field("name", 1, "string"))
+                .field(field("value", 2, "google.protobuf.EnumValueDescriptorProto").repeated()),
+            MessageDescriptor::new("google.protobuf.EnumValueDescriptorProto")
+                .field(field("name", 1, "string"))
+                .field(field("number", 2, "int32")),
+            MessageDescriptor::new("google.protobuf.ServiceDescriptorProto")
+                .field(field("name", 1, "string"))
+                .field(field("method", 2, "google.protobuf.MethodDescriptorProto").repeated()),
+            MessageDescriptor::new("google.protobuf.MethodDescriptorProto")
+                .field(field("name", 1, "string"))
+                .field(field("input_type", 2, "string"))
+                .field(field("output_type", 3, "string"))
+                .field(field("options", 4, "google.protobuf.MethodOptions"))
+                .field(field("client_streaming", 5, "bool"))
+                .field(field("server_streaming", 6, "bool")),
+            MessageDescriptor::new("google.protobuf.MethodOptions").field(field(
+                "http",
+                HTTP_RULE_EXTENSION,
+                "google.api.HttpRule",
+            )),
+            MessageDescriptor::new("google.api.HttpRule")
+                .field(field("get", 2, "string"))
+                .field(field("put", 3, "string"))
+                .field(field("post", 4, "string"))
+                .field(field("delete", 5, "string"))
+                .field(field("patch", 6, "string"))
+                .field(field("body", 7, "string"))
+                .field(field("custom", 8, "google.api.CustomHttpPattern"))
+                .field(field("additional_bindings", 11, "google.api.HttpRule").repeated())
+                .field(field("response_body", 12, "string")),
+            MessageDescriptor::new("google.api.CustomHttpPattern")
+                .field(field("kind", 1, "string"))
+                .field(field("path", 2, "string")),
+        ];
+        let mut pool = DescriptorPool::new();
+        for message in messages {
+            pool.add_message(message)
+                .expect("descriptor.proto subset is valid");
+        }
+        pool
+    })
+}
+
+/// Scalar type for a `FieldDescriptorProto.Type` number.
+fn scalar_for_descriptor_type(number: i64) -> Option<ScalarType> {
+    Some(match number {
+        1 => ScalarType::Double,
+        2 => ScalarType::Float,
+        3 => ScalarType::Int64,
+        4 => ScalarType::UInt64,
+        5 => ScalarType::Int32,
+        6 => ScalarType::Fixed64,
+        7 => ScalarType::Fixed32,
+        8 => ScalarType::Bool,
+        9 => ScalarType::String,
+        12 => ScalarType::Bytes,
+        13 => ScalarType::UInt32,
+        15 => ScalarType::SFixed32,
+        16 => ScalarType::SFixed64,
+        17 => ScalarType::SInt32,
+        18 => ScalarType::SInt64,
+        _ => return None,
+    })
+}
+
+fn qualify(scope: &str, name: &str) -> String {
+    if scope.is_empty() {
+        name.to_string()
+    } else {
+        format!("{scope}.{name}")
+    }
+}
+
+fn str_of<'a>(value: &'a Value, key: &str) -> &'a str {
+    value[key].as_str().unwrap_or_default()
+}
+
+fn list_of<'a>(value: &'a Value, key: &str) -> &'a [Value] {
+    value[key].as_array().map(Vec::as_slice).unwrap_or_default()
+}
+
+fn non_empty(value: &Value, key: &str) -> Option<String> {
+    Some(str_of(value, key))
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+}
+
+/// Read a decoded `google.api.HttpRule` and its additional bindings.
+fn load_http_rules(rule: &Value) -> Vec<HttpRule> {
+    let pattern = ["get", "put", "post", "delete", "patch"]
+        .into_iter()
+        .find_map(|verb| non_empty(rule, verb).map(|path| (verb.to_uppercase(), path)))
+        .or_else(|| {
+            let custom = &rule["custom"];
+            Some((
+                non_empty(custom, "kind")?.to_uppercase(),
+                non_empty(custom, "path")?,
+            ))
+        });
+
+    let mut rules: Vec<HttpRule> = pattern
+        .map(|(method, path)| HttpRule {
+            method,
+            path,
+            body: non_empty(rule, "body"),
+            response_body: non_empty(rule, "response_body"),
+        })
+        .into_iter()
+        .collect();
+    for binding in list_of(rule, "additional_bindings") {
+        rules.extend(load_http_rules(binding));
+    }
+    rules
+}
+
+impl DescriptorPool {
+    /// Load every message, enum and service from a serialized `FileDescriptorSet`
+    /// (as written by `protoc --include_imports --descriptor_set_out=...`).
+    ///
+    /// Returns the fully-qualified names of the services it defines. Nothing is
+    /// registered if any type in the set is invalid.
+    pub fn load_file_descriptor_set(&mut self, bytes: &[u8]) -> Result<Vec<String>, ProtobufError> {
+        let set = descriptor_proto_pool()
+            .decode("google.protobuf.FileDescriptorSet", bytes)
+            .map_err(|e| ProtobufError::InvalidDescriptor(e.to_string()))?;
+
+        let mut staged = DescriptorPool::new();
+        for file in list_of(&set, "file") {
+            let package = str_of(file, "package");
+            for message in list_of(file, "message_type") {
+                staged.load_message(package, message)?;
+            }
+            for descriptor in list_of(file, "enum_type") {
+                staged.add_enum(load_enum(package, descriptor));
+            }
+            for service in list_of(file, "service") {
+                staged.add_service(ServiceDescriptor {
+                    name: qualify(package, str_of(service, "name")),
+                    methods: list_of(service, "method")
+                        .iter()
+                        .map(|method| MethodDescriptor {
+                            name: str_of(method, "name").to_string(),
+                            input_type: str_of(method, "input_type")
+                                .trim_start_matches('.')
+                                .to_string(),
+                            output_type: str_of(method, "output_type")
+                                .trim_start_matches('.')
+                                .to_string(),
+                            client_streaming: method["client_streaming"] == true,
+                            server_streaming: method["server_streaming"] == true,
+                            http_rules: load_http_rules(&method["options"]["http"]),
+                        })
+                        .collect(),
+                });
+            }
+        }
+
+        let services = staged.service_names();
+        self.messages.extend(staged.messages);
+        self.enums.extend(staged.enums);
+        self.services.extend(staged.services);
+        Ok(services)
+    }
+
+    fn load_message(&mut self, scope: &str, message: &Value) -> Result<(), ProtobufError> {
+        let name = qualify(scope, str_of(message, "name"));
+        for nested in list_of(message, "nested_type") {
+            self.load_message(&name, nested)?;
+        }
+        for descriptor in list_of(message, "enum_type") {
+            self.add_enum(load_enum(&name, descriptor));
+        }
+
+        let mut descriptor = MessageDescriptor::new(&name);
+        for field in list_of(message, "field") {
+            let field_name = str_of(field, "name");
+            let field_type = match field["type"].as_i64().unwrap_or_default() {
+                // TYPE_MESSAGE and TYPE_ENUM reference another type by name
+                11 | 14 => FieldType::parse(str_of(field, "type_name")),
+                number => {
+                    FieldType::Scalar(scalar_for_descriptor_type(number).ok_or_else(|| {
+                        ProtobufError::InvalidDescriptor(format!(
+                            "{name}.{field_name} has unsupported type {number}"
+                        ))
+                    })?)
+                }
+            };
+            let number = field["number"].as_u64().unwrap_or_default() as u32;
+            let mut field_descriptor = FieldDescriptor::new(field_name, number, field_type);
+            if field["label"].as_i64() == Some(LABEL_REPEATED) {
+                field_descriptor = field_descriptor.repeated();
+            }
+            descriptor = descriptor.field(field_descriptor);
+        }
+        self.add_message(descriptor)
+    }
+}
+
+fn load_enum(scope: &str, descriptor: &Value) -> EnumDescriptor {
+    EnumDescriptor::new(
+        &qualify(scope, str_of(descriptor, "name")),
+        list_of(descriptor, "value")
+            .iter()
+            .map(|value| {
+                (
+                    str_of(value, "name").to_string(),
+                    value["number"].as_i64().unwrap_or_default() as i32,
+                )
+            })
+            .collect(),
+    )
+}
+
+// ============================================================================
+// HTTP Route Binding
+// ============================================================================
+
+/// Media type for protobuf HTTP bodies.
+pub const PROTOBUF_CONTENT_TYPE: &str = "application/x-protobuf";
+
+/// Message types bound to an HTTP route.
+#[derive(Debug, Clone)]
+pub struct ProtoBinding {
+    pool: SharedDescriptorPool,
+    /// Message type of `application/x-protobuf` request bodies
+    pub request_type: Option<String>,
+    /// Message type of protobuf responses
+    pub response_type: Option<String>,
+}
+
+impl ProtoBinding {
+    /// Bind message types, checking that they exist in the pool.
+    pub fn new(
+        pool: SharedDescriptorPool,
+        request_type: Option<&str>,
+        response_type: Option<&str>,
+    ) -> Result<Self, ProtobufError> {
+        {
+            let guard = pool.read();
+            for name in request_type.iter().chain(response_type.iter()) {
+                guard.require_message(name)?;
+            }
+        }
+        Ok(Self {
+            pool,
+            request_type: request_type.map(str::to_string),
+            response_type: response_type.map(str::to_string),
+        })
+    }
+
+    /// Decode a request body, if the route declares a request type.
+    pub fn decode_request(&self, body: &[u8]) -> Option<Result<Value, ProtobufError>> {
+        let message_type = self.request_type.as_deref()?;
+        Some(self.pool.read().decode(message_type, body))
+    }
+
+    /// Encode a handler result, if the route declares a response type.
+    pub fn encode_response(&self, value: &Value) -> Option<Result<Vec<u8>, ProtobufError>> {
+        let message_type = self.response_type.as_deref()?;
+        Some(self.pool.read().encode(message_type, value))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn pool() -> DescriptorPool {
+        let mut pool = DescriptorPool::new();
+        pool.add_enum(EnumDescriptor::new(
+            "shop.Status",
+            vec![("DRAFT".to_string(), 0), ("LIVE".to_string(), 1)],
+        ));
+        pool.add_message(
+            MessageDescriptor::new("shop.Price")
+                .field(FieldDescriptor::new(
+                    "amount",
+                    1,
+                    FieldType::parse("sint64"),
+                ))
+                .field(FieldDescriptor::new(
+                    "currency",
+                    2,
+                    FieldType::parse("string"),
+                )),
+        )
+        .unwrap();
+        pool.add_message(
+            MessageDescriptor::new("shop.Item")
+                .field(FieldDescriptor::new("id", 1, FieldType::parse("int64")))
+                .field(FieldDescriptor::new("name", 2, FieldType::parse("string")))
+                .field(FieldDescriptor::new("tags", 3, FieldType::parse("string")).repeated())
+                .field(FieldDescriptor::new("sizes", 4, FieldType::parse("int32")).repeated())
+                .field(FieldDescriptor::new(
+                    "price",
+                    5,
+                    FieldType::parse(".shop.Price"),
+                ))
+                .field(FieldDescriptor::new(
+                    "status",
+                    6,
+                    FieldType::parse("shop.Status"),
+                ))
+                .field(FieldDescriptor::new("ratio", 7, FieldType::parse("double")))
+                .field(FieldDescriptor::new("blob", 8, FieldType::parse("bytes"))),
+        )
+        .unwrap();
+        pool
+    }
+
+    #[test]
+    fn test_encode_known_bytes() {
+        // Canonical example from the protobuf encoding guide: field 1 = 150
+        let mut pool = DescriptorPool::new();
+        pool.add_message(MessageDescriptor::new("Test1").field(FieldDescriptor::new(
+            "a",
+            1,
+            FieldType::parse("int32"),
+        )))
+        .unwrap();
+        assert_eq!(
+            pool.encode("Test1", &json!({"a": 150})).unwrap(),
+            vec![0x08, 0x96, 0x01]
+        );
+        // proto3 defaults are not written
+        assert!(pool.encode("Test1", &json!({"a": 0})).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let pool = pool();
+        let item = json!({
+            "id": 9007199254740993i64,
+            "name": "Cello",
+            "tags": ["rust", "python"],
+            "sizes": [1, -2, 300],
+            "price": {"amount": -1250, "currency": "EUR"},
+            "status": "LIVE",
+            "ratio": 0.5,
+            "blob": "AAEC"
+        });
+        let bytes = pool.encode("shop.Item", &item).unwrap();
+        assert_eq!(pool.decode("shop.Item", &bytes).unwrap(), item);
+    }
+
+    #[test]
+    fn test_decode_fills_defaults_and_skips_unknown_fields() {
+        let pool = pool();
+        // name = "x", then unknown field 15 (varint 1)
+        let decoded = pool
+            .decode("shop.Item", &[0x12, 0x01, b'x', 0x78, 0x01])
+            .unwrap();
+        assert_eq!(decoded["name"], "x");
+        assert_eq!(decoded["id"], 0);
+        assert_eq!(decoded["tags"], json!([]));
+        assert_eq!(decoded["status"], "DRAFT");
+        assert!(decoded.get("price").is_none());
+    }
+
+    #[test]
+    fn test_unpacked_repeated_scalars_are_accepted() {
+        let pool = pool();
+        let decoded = pool.decode("shop.Item", &[0x20, 0x01, 0x20, 0x02]).unwrap();
+        assert_eq!(decoded["sizes"], json!([1, 2]));
+    }
+
+    #[test]
+    fn test_errors() {
+        let pool = pool();
+        assert!(matches!(
+            pool.encode("shop.Missing", &json!({})),
+            Err(ProtobufError::UnknownType(_))
+        ));
+        assert!(matches!(
+            pool.encode("shop.Item", &json!({"name": 5})),
+            Err(ProtobufError::TypeMismatch { .. })
+        ));
+        assert!(matches!(
+            pool.decode("shop.Item", &[0x12, 0x05, b'x']),
+            Err(ProtobufError::Malformed(_))
+        ));
+        // Wrong wire type for a string field
+        assert!(pool.decode("shop.Item", &[0x10, 0x01]).is_err());
+
+        let mut pool = DescriptorPool::new();
+        assert!(pool
+            .add_message(
+                MessageDescriptor::new("Dup")
+                    .field(FieldDescriptor::new("a", 1, FieldType::parse("int32")))
+                    .field(FieldDescriptor::new("b", 1, FieldType::parse("int32"))),
+            )
+            .is_err());
+    }
+
+    /// Serialized `FileDescriptorSet` for a small `shop` package.
+    pub(crate) fn shop_descriptor_set() -> Vec<u8> {
+        let set = json!({"file": [{
+            "name": "shop.proto",
+            "package": "shop",
+            "message_type": [
+                {
+                    "name": "Item",
+                    "field": [
+                        {"name": "id", "number": 1, "label": 1, "type": 3},
+                        {"name": "tags", "number": 2, "label": 3, "type": 9},
+                        {"name": "status", "number": 3, "label": 1, "type": 14,
+                         "type_name": ".shop.Item.Status"},
+                    ],
+                    "enum_type": [{"name": "Status", "value": [
+                        {"name": "DRAFT", "number": 0},
+                        {"name": "LIVE", "number": 1},
+                    ]}],
+                },
+                {
+                    "name": "GetItem",
+                    "field": [{"name": "id", "number": 1, "label": 1, "type": 3}],
+                },
+                {
+                    "name": "UpdateItem",
+                    "field": [
+                        {"name": "item", "number": 1, "label": 1, "type": 11,
+                         "type_name": ".shop.Item"},
+                        {"name": "notify", "number": 2, "label": 1, "type": 8},
+                    ],
+                },
+            ],
+            "service": [{"name": "Catalog", "method": [
+                {"name": "Get", "input_type": ".shop.GetItem", "output_type": ".shop.Item",
+                 "options": {"http": {"get": "/v1/items/{id}"}}},
+                {"name": "Update", "input_type": ".shop.UpdateItem", "output_type": ".shop.Item",
+                 "options": {"http": {
+                     "patch": "/v1/items/{item.id}", "body": "item",
+                     "additional_bindings": [
+                         {"put": "/v1/items/{item.id}", "body": "*", "response_body": "tags"},
+                     ],
+                 }}},
+                {"name": "Watch", "input_type": ".shop.GetItem", "output_type": ".shop.Item",
+                 "server_streaming": true},
+            ]}],
+        }]});
+        descriptor_proto_pool()
+            .encode("google.protobuf.FileDescriptorSet", &set)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_load_file_descriptor_set() {
+        let mut pool = DescriptorPool::new();
+        let services = pool
+            .load_file_descriptor_set(&shop_descriptor_set())
+            .unwrap();
+        assert_eq!(services, vec!["shop.Catalog".to_string()]);
+
+        let catalog = pool.service("shop.Catalog").unwrap();
+        let get = catalog.method("Get").unwrap();
+        assert_eq!(
+            (get.input_type.as_str(), get.output_type.as_str()),
+            ("shop.GetItem", "shop.Item")
+        );
+        assert!(catalog.method("Watch").unwrap().server_streaming);
+        assert_eq!(get.http_rules, vec![HttpRule::new("GET", "/v1/items/{id}")]);
+        assert_eq!(
+            catalog.method("Update").unwrap().http_rules,
+            vec![
+                HttpRule::new("PATCH", "/v1/items/{item.id}").with_body("item"),
+                HttpRule::new("PUT", "/v1/items/{item.id}")
+                    .with_body("*")
+                    .with_response_body("tags"),
+            ]
+        );
+        assert!(catalog.method("Watch").unwrap().http_rules.is_empty());
+
+        let item = json!({"id": 7, "tags": ["a", "b"], "status": "LIVE"});
+        let bytes = pool.encode("shop.Item", &item).unwrap();
+        assert_eq!(pool.decode("shop.Item", &bytes).unwrap(), item);
+        assert!(pool.enum_type("shop.Item.Status").is_some());
+
+        // Garbage input leaves the pool untouched
+        let mut empty = DescriptorPool::new();
+        assert!(matches!(
+            empty.load_file_descriptor_set(&[0x0a, 0x05, 0x01]),
+            Err(ProtobufError::InvalidDescriptor(_))
+        ));
+        assert!(empty.message_names().is_empty());
+    }
+
+    #[test]
+    fn test_binding_checks_types() {
+        let shared: SharedDescriptorPool = Arc::new(RwLock::new(pool()));
+        assert!(ProtoBinding::new(shared.clone(), Some("shop.Nope"), None).is_err());
+
+        let binding = ProtoBinding::new(shared, Some("shop.Price"), None).unwrap();
+        let value = binding.decode_request(&[0x08, 0x03]).unwrap().unwrap();
+        assert_eq!(value["amount"], -2);
+        assert!(binding.encode_response(&value).is_none());
+    }
+}
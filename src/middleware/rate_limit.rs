@@ -678,7 +678,7 @@ impl KeyExtractors {
             }
             // Check X-Real-IP
             if let Some(ip) = request.headers.get("x-real-ip") {
-                return ip.clone();
+                return ip.to_string();
             }
             // Fallback to remote address (would need to be set by server)
             request
@@ -710,8 +710,17 @@ impl KeyExtractors {
             request
                 .headers
                 .get(&header)
-                .cloned()
-                .unwrap_or_else(|| "unknown".to_string())
+                .unwrap_or("unknown")
+                .to_string()
+        })
+    }
+
+    /// Extract the tenant resolved by the tenant middleware.
+    pub fn tenant() -> KeyExtractor {
+        Arc::new(|request: &Request| {
+            super::tenant::tenant_id(request)
+                .unwrap_or("unknown")
+                .to_string()
         })
     }
 
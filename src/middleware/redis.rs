@@ -347,6 +347,18 @@ pub trait RedisClient: Send + Sync {
     /// Set TTL on a key.
     fn expire(&self, key: &str, ttl: Duration) -> Result<bool, RedisError>;
 
+    /// Set a string value with a TTL only if the key does not exist
+    /// (`SET key value NX PX ttl`). Returns whether the key was set.
+    fn set_nx(&self, key: &str, value: &str, ttl: Duration) -> Result<bool, RedisError>;
+
+    /// Delete a key only if it holds `expected`, atomically (the usual
+    /// compare-and-delete script). Returns whether the key was deleted.
+    fn delete_if_eq(&self, key: &str, expected: &str) -> Result<bool, RedisError>;
+
+    /// Reset a key's TTL only if it holds `expected`, atomically. Returns
+    /// whether the TTL was reset.
+    fn expire_if_eq(&self, key: &str, expected: &str, ttl: Duration) -> Result<bool, RedisError>;
+
     /// Increment a key's integer value.
     fn incr(&self, key: &str) -> Result<i64, RedisError>;
 
@@ -397,6 +409,12 @@ pub trait RedisClient: Send + Sync {
 
     /// Close all connections.
     fn close(&self);
+
+    /// Whether the data lives in this process only, so other workers and a
+    /// restarted process do not see it.
+    fn is_in_process(&self) -> bool {
+        false
+    }
 }
 
 /// In-memory mock Redis client for testing.
@@ -492,6 +510,44 @@ impl RedisClient for MockRedisClient {
         }
     }
 
+    fn set_nx(&self, key: &str, value: &str, ttl: Duration) -> Result<bool, RedisError> {
+        let start = Instant::now();
+        let mut data = self.data.write();
+        let mut ttls = self.ttls.write();
+        let live = data.contains_key(key) && ttls.get(key).is_none_or(|e| Instant::now() <= *e);
+        if !live {
+            data.insert(key.to_string(), RedisValue::String(value.to_string()));
+            ttls.insert(key.to_string(), Instant::now() + ttl);
+            self.metrics.record_set();
+        }
+        self.metrics
+            .record_command(start.elapsed().as_millis() as u64, false);
+        Ok(!live)
+    }
+
+    fn delete_if_eq(&self, key: &str, expected: &str) -> Result<bool, RedisError> {
+        self.clean_expired(key);
+        let mut data = self.data.write();
+        if data.get(key).and_then(|v| v.as_str()) != Some(expected) {
+            return Ok(false);
+        }
+        data.remove(key);
+        self.ttls.write().remove(key);
+        Ok(true)
+    }
+
+    fn expire_if_eq(&self, key: &str, expected: &str, ttl: Duration) -> Result<bool, RedisError> {
+        self.clean_expired(key);
+        let data = self.data.read();
+        if data.get(key).and_then(|v| v.as_str()) != Some(expected) {
+            return Ok(false);
+        }
+        self.ttls
+            .write()
+            .insert(key.to_string(), Instant::now() + ttl);
+        Ok(true)
+    }
+
     fn incr(&self, key: &str) -> Result<i64, RedisError> {
         let mut data = self.data.write();
         let current = data.get(key).and_then(|v| v.as_i64()).unwrap_or(0);
@@ -662,6 +718,10 @@ impl RedisClient for MockRedisClient {
     fn close(&self) {
         // No-op for mock
     }
+
+    fn is_in_process(&self) -> bool {
+        true
+    }
 }
 
 #[cfg(test)]
@@ -707,6 +767,27 @@ mod tests {
         assert!(!client.exists("key3").unwrap());
     }
 
+    #[test]
+    fn test_mock_redis_conditional_ops() {
+        let client = MockRedisClient::new(RedisConfig::default());
+        let ttl = Duration::from_secs(10);
+
+        assert!(client.set_nx("lock", "a", ttl).unwrap());
+        assert!(!client.set_nx("lock", "b", ttl).unwrap());
+        assert!(!client.expire_if_eq("lock", "b", ttl).unwrap());
+        assert!(client.expire_if_eq("lock", "a", ttl).unwrap());
+        assert!(!client.delete_if_eq("lock", "b").unwrap());
+        assert!(client.delete_if_eq("lock", "a").unwrap());
+        assert!(!client.exists("lock").unwrap());
+
+        // An expired key can be set again.
+        assert!(client
+            .set_nx("lock", "a", Duration::from_millis(10))
+            .unwrap());
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(client.set_nx("lock", "b", ttl).unwrap());
+    }
+
     #[test]
     fn test_mock_redis_incr_decr() {
         let client = MockRedisClient::new(RedisConfig::default());
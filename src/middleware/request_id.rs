@@ -208,8 +208,8 @@ impl Middleware for RequestIdMiddleware {
         let request_id = if self.trust_incoming {
             request
                 .headers
-                .get(&self.incoming_header.to_lowercase())
-                .cloned()
+                .get(&self.incoming_header)
+                .map(str::to_string)
         } else {
             None
         };
@@ -0,0 +1,488 @@
+//! Per-route middleware for Cello Framework.
+//!
+//! Middleware can be attached to a single route, or to every route under a
+//! path prefix (a group, e.g. a blueprint). Attachments are compiled when the
+//! server is built into one flat chain per route, so dispatching a request
+//! only walks a slice; nothing is matched or allocated per request.
+//!
+//! Chains run in order of priority (lower first). Ties run outermost first:
+//! app-wide middleware, then groups from the shortest prefix, then the
+//! route's own, each in the order they were attached. `after` hooks run in
+//! reverse order.
+//!
+//! Route middleware runs after the global middleware and guards, just before
+//! the request is validated and handled.
+
+use parking_lot::RwLock;
+use pyo3::prelude::*;
+use std::sync::Arc;
+
+use super::{path_matches_skip, Middleware, MiddlewareAction, MiddlewareError, MiddlewareResult};
+use crate::request::Request;
+use crate::response::Response;
+use crate::router::Router;
+
+// ============================================================================
+// Compiled Chain
+// ============================================================================
+
+/// The compiled middleware chain of one route.
+pub struct RouteMiddleware {
+    chain: Vec<Arc<dyn Middleware>>,
+    after_body_limit: usize,
+}
+
+impl RouteMiddleware {
+    /// Run the `before` hooks in order, stopping at the first that answers.
+    #[inline]
+    pub fn before(&self, request: &mut Request) -> MiddlewareResult {
+        for middleware in &self.chain {
+            match middleware.before(request)? {
+                MiddlewareAction::Continue => continue,
+                action @ MiddlewareAction::Stop(_) => return Ok(action),
+            }
+        }
+        Ok(MiddlewareAction::Continue)
+    }
+
+    /// Run the `after` hooks in reverse order.
+    #[inline]
+    pub fn after(&self, request: &Request, response: &mut Response) -> MiddlewareResult {
+        for middleware in self.chain.iter().rev() {
+            match middleware.after(request, response)? {
+                MiddlewareAction::Continue => continue,
+                action @ MiddlewareAction::Stop(_) => return Ok(action),
+            }
+        }
+        Ok(MiddlewareAction::Continue)
+    }
+
+    /// Largest request body kept for the `after` hooks, 0 if none asks
+    /// for it.
+    #[inline]
+    pub fn after_body_limit(&self) -> usize {
+        self.after_body_limit
+    }
+
+    /// Names of the middleware, in the order their `before` hooks run.
+    pub fn names(&self) -> Vec<&str> {
+        self.chain.iter().map(|m| m.name()).collect()
+    }
+
+    /// Number of middleware in the chain.
+    pub fn len(&self) -> usize {
+        self.chain.len()
+    }
+
+    /// Whether the chain is empty.
+    pub fn is_empty(&self) -> bool {
+        self.chain.is_empty()
+    }
+}
+
+impl std::fmt::Debug for RouteMiddleware {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.names()).finish()
+    }
+}
+
+// ============================================================================
+// Attachments
+// ============================================================================
+
+/// Routes a middleware is attached to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MiddlewareScope {
+    /// Every route under a path prefix; `""` is every route
+    Prefix(String),
+    /// A single route, by method and path pattern
+    Route { method: String, path: String },
+}
+
+impl MiddlewareScope {
+    /// Every route under `prefix`.
+    pub fn prefix(prefix: &str) -> Self {
+        MiddlewareScope::Prefix(prefix.trim_end_matches('/').to_string())
+    }
+
+    /// The route registered for `method` and `path`.
+    pub fn route(method: &str, path: &str) -> Self {
+        MiddlewareScope::Route {
+            method: method.to_uppercase(),
+            path: path.to_string(),
+        }
+    }
+
+    fn applies_to(&self, method: &str, path: &str) -> bool {
+        match self {
+            MiddlewareScope::Prefix(prefix) => prefix.is_empty() || path_matches_skip(path, prefix),
+            MiddlewareScope::Route { method: m, path: p } => m == method && p == path,
+        }
+    }
+
+    /// Ties in priority run outermost first.
+    fn depth(&self) -> usize {
+        match self {
+            MiddlewareScope::Prefix(prefix) => prefix.len(),
+            MiddlewareScope::Route { .. } => usize::MAX,
+        }
+    }
+}
+
+struct Attachment {
+    scope: MiddlewareScope,
+    priority: i32,
+    middleware: Arc<dyn Middleware>,
+}
+
+/// Middleware attached to routes and groups, compiled into per-route chains.
+#[derive(Clone, Default)]
+pub struct RouteMiddlewareRegistry {
+    attachments: Arc<RwLock<Vec<Attachment>>>,
+}
+
+impl RouteMiddlewareRegistry {
+    /// Create an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Attach `middleware` to the routes in `scope`. `priority` overrides
+    /// the middleware's own.
+    pub fn attach(
+        &self,
+        scope: MiddlewareScope,
+        middleware: Arc<dyn Middleware>,
+        priority: Option<i32>,
+    ) {
+        let priority = priority.unwrap_or_else(|| middleware.priority());
+        self.attachments.write().push(Attachment {
+            scope,
+            priority,
+            middleware,
+        });
+    }
+
+    /// Whether nothing is attached.
+    pub fn is_empty(&self) -> bool {
+        self.attachments.read().is_empty()
+    }
+
+    /// The chain of the route registered for `method` and `path`.
+    pub fn chain(&self, method: &str, path: &str) -> RouteMiddleware {
+        let attachments = self.attachments.read();
+        let mut matching: Vec<&Attachment> = attachments
+            .iter()
+            .filter(|a| a.scope.applies_to(method, path))
+            .collect();
+        // Stable, so attachment order breaks the remaining ties
+        matching.sort_by_key(|a| (a.priority, a.scope.depth()));
+        RouteMiddleware {
+            chain: matching.iter().map(|a| a.middleware.clone()).collect(),
+            after_body_limit: matching
+                .iter()
+                .filter_map(|a| a.middleware.after_body_limit())
+                .max()
+                .unwrap_or(0),
+        }
+    }
+
+    /// Compile the chain of every route of `router` into its handler
+    /// metadata, replacing chains compiled before.
+    pub fn compile(&self, router: &Router) {
+        if self.is_empty() {
+            return;
+        }
+        for (method, path, handler) in router.route_handlers() {
+            let chain = self.chain(&method, &path);
+            handler.set_middleware((!chain.is_empty()).then(|| Arc::new(chain)));
+        }
+    }
+}
+
+// ============================================================================
+// Python Middleware
+// ============================================================================
+
+/// Middleware implemented in Python.
+///
+/// The object's `before(request)` hook may return a `Response` to answer
+/// the request without running the handler; its `after(request, response)`
+/// hook may change the response in place or return a new one. A plain
+/// callable is used as the `before` hook. An `after_body_limit` attribute
+/// keeps request bodies up to that many bytes for `after`.
+pub struct PythonMiddleware {
+    before: Option<PyObject>,
+    after: Option<PyObject>,
+    name: String,
+    after_body_limit: Option<usize>,
+}
+
+impl PythonMiddleware {
+    /// Wrap a Python middleware object or callable.
+    pub fn new(py: Python<'_>, middleware: PyObject) -> PyResult<Self> {
+        let object = middleware.as_ref(py);
+        let hook = |name: &str| -> Option<PyObject> {
+            let hook = object.getattr(name).ok()?;
+            hook.is_callable().then(|| hook.into())
+        };
+        let (mut before, after) = (hook("before"), hook("after"));
+        if before.is_none() && after.is_none() {
+            if !object.is_callable() {
+                return Err(pyo3::exceptions::PyTypeError::new_err(
+                    "Middleware must be callable or define before() or after()",
+                ));
+            }
+            before = Some(middleware.clone_ref(py));
+        }
+        let name = object
+            .getattr("__name__")
+            .or_else(|_| object.get_type().getattr("__name__"))
+            .and_then(|name| name.extract::<String>())
+            .unwrap_or_else(|_| "python_middleware".to_string());
+        let after_body_limit = match object.getattr("after_body_limit") {
+            Ok(limit) if !limit.is_none() => Some(limit.extract::<usize>()?),
+            _ => None,
+        };
+        Ok(Self {
+            before,
+            after,
+            name,
+            after_body_limit,
+        })
+    }
+
+    fn failed(&self, err: PyErr) -> MiddlewareError {
+        MiddlewareError::internal(&format!("Middleware {} failed: {err}", self.name))
+    }
+}
+
+impl Middleware for PythonMiddleware {
+    fn before(&self, request: &mut Request) -> MiddlewareResult {
+        let Some(hook) = &self.before else {
+            return Ok(MiddlewareAction::Continue);
+        };
+        Python::with_gil(|py| {
+            let result = hook
+                .call1(py, (request.clone(),))
+                .map_err(|e| self.failed(e))?;
+            if result.is_none(py) {
+                return Ok(MiddlewareAction::Continue);
+            }
+            let response = result.extract::<Response>(py).map_err(|_| {
+                MiddlewareError::internal(&format!(
+                    "Middleware {} must return None or a Response",
+                    self.name
+                ))
+            })?;
+            Ok(MiddlewareAction::Stop(response))
+        })
+    }
+
+    fn after(&self, request: &Request, response: &mut Response) -> MiddlewareResult {
+        let Some(hook) = &self.after else {
+            return Ok(MiddlewareAction::Continue);
+        };
+        Python::with_gil(|py| {
+            let cell = Py::new(py, response.clone()).map_err(|e| self.failed(e))?;
+            let result = hook
+                .call1(py, (request.clone(), cell.clone_ref(py)))
+                .map_err(|e| self.failed(e))?;
+            *response = if result.is_none(py) {
+                cell.borrow(py).clone()
+            } else {
+                result.extract::<Response>(py).map_err(|_| {
+                    MiddlewareError::internal(&format!(
+                        "Middleware {} must return None or a Response",
+                        self.name
+                    ))
+                })?
+            };
+            Ok(MiddlewareAction::Continue)
+        })
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn after_body_limit(&self) -> Option<usize> {
+        self.after_body_limit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parking_lot::Mutex;
+
+    /// Records the order its hooks run in.
+    struct Recorder {
+        name: &'static str,
+        priority: i32,
+        log: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Middleware for Recorder {
+        fn before(&self, _request: &mut Request) -> MiddlewareResult {
+            self.log.lock().push(format!("before:{}", self.name));
+            Ok(MiddlewareAction::Continue)
+        }
+
+        fn after(&self, _request: &Request, _response: &mut Response) -> MiddlewareResult {
+            self.log.lock().push(format!("after:{}", self.name));
+            Ok(MiddlewareAction::Continue)
+        }
+
+        fn priority(&self) -> i32 {
+            self.priority
+        }
+
+        fn name(&self) -> &str {
+            self.name
+        }
+    }
+
+    fn recorder(
+        name: &'static str,
+        priority: i32,
+        log: &Arc<Mutex<Vec<String>>>,
+    ) -> Arc<dyn Middleware> {
+        Arc::new(Recorder {
+            name,
+            priority,
+            log: log.clone(),
+        })
+    }
+
+    #[test]
+    fn test_chain_order() {
+        let log = Arc::default();
+        let registry = RouteMiddlewareRegistry::new();
+        registry.attach(
+            MiddlewareScope::route("get", "/api/users"),
+            recorder("route", 0, &log),
+            None,
+        );
+        registry.attach(
+            MiddlewareScope::prefix("/api/"),
+            recorder("group", 0, &log),
+            None,
+        );
+        registry.attach(MiddlewareScope::prefix(""), recorder("app", 0, &log), None);
+        registry.attach(
+            MiddlewareScope::prefix("/api"),
+            recorder("first", 5, &log),
+            Some(-10),
+        );
+        registry.attach(
+            MiddlewareScope::prefix("/admin"),
+            recorder("admin", 0, &log),
+            None,
+        );
+
+        let chain = registry.chain("GET", "/api/users");
+        assert_eq!(chain.names(), vec!["first", "app", "group", "route"]);
+        assert_eq!(
+            registry.chain("POST", "/api/users").names(),
+            vec!["first", "app", "group"]
+        );
+        assert_eq!(registry.chain("GET", "/apix").names(), vec!["app"]);
+
+        let mut request = Request::default();
+        let mut response = Response::new(200);
+        chain.before(&mut request).unwrap();
+        chain.after(&request, &mut response).unwrap();
+        assert_eq!(
+            *log.lock(),
+            vec![
+                "before:first",
+                "before:app",
+                "before:group",
+                "before:route",
+                "after:route",
+                "after:group",
+                "after:app",
+                "after:first",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compile_into_routes() {
+        pyo3::prepare_freethreaded_python();
+        let mut handlers = crate::handler::HandlerRegistry::new();
+        let mut router = Router::new();
+        for path in ["/api/users", "/health"] {
+            let (id, meta) = Python::with_gil(|py| handlers.register_with_meta(py.None()));
+            router.add_handler_route("GET", path, id, meta).unwrap();
+        }
+
+        let log = Arc::default();
+        let registry = RouteMiddlewareRegistry::new();
+        registry.attach(
+            MiddlewareScope::prefix("/api"),
+            recorder("group", 0, &log),
+            None,
+        );
+        registry.compile(&router);
+
+        let api = router.route_handler("GET", "/api/users").unwrap();
+        assert_eq!(api.middleware().unwrap().names(), vec!["group"]);
+        assert!(router
+            .route_handler("GET", "/health")
+            .unwrap()
+            .middleware()
+            .is_none());
+    }
+
+    #[test]
+    fn test_python_middleware() {
+        pyo3::prepare_freethreaded_python();
+        let middleware = Python::with_gil(|py| {
+            let globals = pyo3::types::PyDict::new(py);
+            globals
+                .set_item("Response", py.get_type::<Response>())
+                .unwrap();
+            py.run(
+                r#"
+class Tagger:
+    def before(self, request):
+        if request.path == "/blocked":
+            return Response.text("blocked", status=403)
+
+    def after(self, request, response):
+        response.set_header("X-Tagged", "yes")
+"#,
+                Some(globals),
+                None,
+            )
+            .unwrap();
+            let object = py.eval("Tagger()", Some(globals), None).unwrap();
+            PythonMiddleware::new(py, object.into()).unwrap()
+        });
+        assert_eq!(middleware.name(), "Tagger");
+
+        let mut request = Request::default();
+        request.path = "/blocked".to_string();
+        match middleware.before(&mut request) {
+            Ok(MiddlewareAction::Stop(response)) => assert_eq!(response.status, 403),
+            _ => panic!("expected the request to be answered"),
+        }
+
+        request.path = "/ok".to_string();
+        assert!(matches!(
+            middleware.before(&mut request),
+            Ok(MiddlewareAction::Continue)
+        ));
+        let mut response = Response::new(200);
+        middleware.after(&request, &mut response).unwrap();
+        assert_eq!(
+            response.headers.get("X-Tagged").map(String::as_str),
+            Some("yes")
+        );
+
+        let invalid = Python::with_gil(|py| PythonMiddleware::new(py, py.None()));
+        assert!(invalid.is_err());
+    }
+}
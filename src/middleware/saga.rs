@@ -4,7 +4,15 @@
 //! - Saga definition and step registration
 //! - Saga execution with forward and compensation flows
 //! - Automatic compensation on step failure
-//! - Configurable retries and timeouts
+//! - Async step execution with retries, backoff, and step/saga deadlines
+//! - Compensation handlers run in reverse order with their own retry policy
+//! - Execution queries with filtering and paging, manual resume and
+//!   forced compensation
+//! - Per-step idempotency keys, with completed steps deduplicated through an
+//!   in-memory or Redis store so retries and resumes don't repeat them
+//! - Timer steps that wait for a duration or until a timestamp, and signal
+//!   steps that wait for an external callback carrying a correlation token
+//! - Definition export as a JSON state machine, Graphviz DOT or Mermaid
 //! - Execution tracking and statistics
 //!
 //! # Example
@@ -27,13 +35,23 @@
 //!     return {"saga": "OrderCreation", "status": "started"}
 //! ```
 
+use pyo3::prelude::*;
+use pyo3::types::PyTuple;
 use serde::{Deserialize, Serialize};
-use serde_json::Value as JsonValue;
+use serde_json::{json, Value as JsonValue};
 use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
+use tokio::sync::oneshot;
+
+use super::redis::{RedisClient, RedisValue};
+use crate::event_loop::{call_with_timeout, CallError};
+use crate::json::{json_to_python, python_to_json};
 
 // ============================================================================
 // Configuration
@@ -46,12 +64,18 @@ use parking_lot::RwLock;
 pub struct SagaConfig {
     /// Maximum number of retries for a failed step before triggering compensation.
     pub max_retries: u32,
-    /// Delay in milliseconds between retry attempts.
+    /// Delay in milliseconds before the first retry.
     pub retry_delay_ms: u64,
+    /// Factor the retry delay grows by after each failed attempt.
+    pub backoff_multiplier: f64,
     /// Maximum time in milliseconds for an entire saga execution.
     pub timeout_ms: u64,
     /// Whether to log saga execution steps.
     pub enable_logging: bool,
+    /// Maximum number of retries for a failed compensation.
+    pub compensation_max_retries: u32,
+    /// Delay in milliseconds before the first compensation retry.
+    pub compensation_retry_delay_ms: u64,
 }
 
 impl Default for SagaConfig {
@@ -59,8 +83,11 @@ impl Default for SagaConfig {
         Self {
             max_retries: 3,
             retry_delay_ms: 1000,
+            backoff_multiplier: 2.0,
             timeout_ms: 30000,
             enable_logging: true,
+            compensation_max_retries: 3,
+            compensation_retry_delay_ms: 1000,
         }
     }
 }
@@ -83,6 +110,37 @@ impl SagaConfig {
         self
     }
 
+    /// Set the factor the retry delay grows by (1.0 for a fixed delay).
+    pub fn with_backoff(mut self, multiplier: f64) -> Self {
+        self.backoff_multiplier = multiplier;
+        self
+    }
+
+    /// Set the retry policy for compensation handlers.
+    pub fn with_compensation_retries(mut self, retries: u32, delay_ms: u64) -> Self {
+        self.compensation_max_retries = retries;
+        self.compensation_retry_delay_ms = delay_ms;
+        self
+    }
+
+    /// Delay before retrying a step after `attempt` failed attempts.
+    pub fn retry_delay(&self, attempt: u32) -> Duration {
+        self.backoff(self.retry_delay_ms, attempt)
+    }
+
+    /// Delay before retrying a compensation after `attempt` failed attempts.
+    pub fn compensation_retry_delay(&self, attempt: u32) -> Duration {
+        self.backoff(self.compensation_retry_delay_ms, attempt)
+    }
+
+    fn backoff(&self, base_ms: u64, attempt: u32) -> Duration {
+        let factor = self
+            .backoff_multiplier
+            .max(1.0)
+            .powi(attempt.saturating_sub(1) as i32);
+        Duration::from_millis((base_ms as f64 * factor).min(u64::MAX as f64) as u64)
+    }
+
     /// Set the saga timeout in milliseconds.
     pub fn with_timeout(mut self, timeout_ms: u64) -> Self {
         self.timeout_ms = timeout_ms;
@@ -111,10 +169,14 @@ pub enum StepStatus {
     Completed,
     /// Step execution failed.
     Failed,
+    /// Step is waiting for its timer or signal.
+    Waiting,
     /// Compensation is in progress for this step.
     Compensating,
     /// Step has been successfully compensated.
     Compensated,
+    /// Compensation for this step failed and may be retried.
+    CompensationFailed,
 }
 
 impl std::fmt::Display for StepStatus {
@@ -124,8 +186,10 @@ impl std::fmt::Display for StepStatus {
             StepStatus::Running => write!(f, "Running"),
             StepStatus::Completed => write!(f, "Completed"),
             StepStatus::Failed => write!(f, "Failed"),
+            StepStatus::Waiting => write!(f, "Waiting"),
             StepStatus::Compensating => write!(f, "Compensating"),
             StepStatus::Compensated => write!(f, "Compensated"),
+            StepStatus::CompensationFailed => write!(f, "CompensationFailed"),
         }
     }
 }
@@ -160,10 +224,49 @@ impl std::fmt::Display for SagaStatus {
     }
 }
 
+impl std::str::FromStr for SagaStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "pending" => Ok(SagaStatus::Pending),
+            "running" => Ok(SagaStatus::Running),
+            "completed" => Ok(SagaStatus::Completed),
+            "failed" => Ok(SagaStatus::Failed),
+            "compensating" => Ok(SagaStatus::Compensating),
+            "compensated" => Ok(SagaStatus::Compensated),
+            _ => Err(format!("Unknown saga status: {s}")),
+        }
+    }
+}
+
 // ============================================================================
 // Saga Step Types
 // ============================================================================
 
+/// What a step does when it runs.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StepKind {
+    /// Call the step handler, retrying on failure.
+    #[default]
+    Action,
+    /// Wait `delay_ms` after the step starts.
+    Delay { delay_ms: u64 },
+    /// Wait until the Unix timestamp `at_ms` (milliseconds).
+    Until { at_ms: u64 },
+    /// Wait for [`SagaOrchestrator::signal`] with the step's token; the
+    /// signal payload is the step result. The step's `timeout_ms` bounds
+    /// the wait.
+    Signal,
+}
+
+impl StepKind {
+    /// Whether the step waits rather than calling its handler.
+    pub fn is_wait(&self) -> bool {
+        *self != StepKind::Action
+    }
+}
+
 /// Definition of a single step within a saga.
 ///
 /// Each step has a name, an optional description, and may have
@@ -178,6 +281,9 @@ pub struct SagaStepDef {
     pub has_compensation: bool,
     /// Optional per-step timeout override in milliseconds.
     pub timeout_ms: Option<u64>,
+    /// Action, timer or signal.
+    #[serde(default)]
+    pub kind: StepKind,
 }
 
 impl SagaStepDef {
@@ -188,9 +294,31 @@ impl SagaStepDef {
             description: None,
             has_compensation: false,
             timeout_ms: None,
+            kind: StepKind::Action,
         }
     }
 
+    /// A step that waits `delay_ms` after it starts.
+    pub fn delay(name: &str, delay_ms: u64) -> Self {
+        Self::new(name).with_kind(StepKind::Delay { delay_ms })
+    }
+
+    /// A step that waits until the Unix timestamp `at_ms` (milliseconds).
+    pub fn until(name: &str, at_ms: u64) -> Self {
+        Self::new(name).with_kind(StepKind::Until { at_ms })
+    }
+
+    /// A step that waits for a signal with its correlation token.
+    pub fn signal(name: &str) -> Self {
+        Self::new(name).with_kind(StepKind::Signal)
+    }
+
+    /// Set what the step does.
+    pub fn with_kind(mut self, kind: StepKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
     /// Set the step description.
     pub fn with_description(mut self, description: &str) -> Self {
         self.description = Some(description.to_string());
@@ -221,6 +349,12 @@ pub struct SagaStep {
     pub result: Option<JsonValue>,
     /// Error message if the step failed.
     pub error: Option<String>,
+    /// When a timer step wakes up, as a Unix timestamp in milliseconds.
+    #[serde(default)]
+    pub wake_at: Option<u64>,
+    /// Correlation token a signal step waits for.
+    #[serde(default)]
+    pub signal_token: Option<String>,
 }
 
 impl SagaStep {
@@ -231,6 +365,8 @@ impl SagaStep {
             status: StepStatus::Pending,
             result: None,
             error: None,
+            wake_at: None,
+            signal_token: None,
         }
     }
 
@@ -298,6 +434,292 @@ impl SagaDefinition {
     pub fn step_count(&self) -> usize {
         self.steps.len()
     }
+
+    /// Describe the saga as a state machine, including compensation edges.
+    ///
+    /// Each step is a state left on `completed` for the next step and on
+    /// `failed` for the compensation of the closest earlier step that has
+    /// one. Compensations run back towards the first step on
+    /// `compensated` or `compensation_failed`; the last one ends the saga
+    /// `compensated` only if every compensation succeeded.
+    pub fn state_machine(&self) -> SagaStateMachine {
+        let mut states = vec![SagaState::terminal("start", StateKind::Start, "start")];
+        let mut transitions = Vec::new();
+        for (i, step) in self.steps.iter().enumerate() {
+            states.push(SagaState {
+                id: format!("s{i}"),
+                kind: StateKind::Step,
+                label: step.name.clone(),
+                step: Some(step.name.clone()),
+                timeout_ms: step.timeout_ms,
+            });
+            if step.has_compensation {
+                states.push(SagaState {
+                    id: format!("c{i}"),
+                    kind: StateKind::Compensation,
+                    label: format!("compensate {}", step.name),
+                    step: Some(step.name.clone()),
+                    timeout_ms: step.timeout_ms,
+                });
+            }
+        }
+        states.push(SagaState::terminal(
+            "completed",
+            StateKind::Completed,
+            "completed",
+        ));
+        states.push(SagaState::terminal(
+            "compensated",
+            StateKind::Compensated,
+            "compensated",
+        ));
+        let has_compensation = self.steps.iter().any(|s| s.has_compensation);
+        if has_compensation {
+            states.push(SagaState::terminal("failed", StateKind::Failed, "failed"));
+        }
+
+        // Compensation state reached when step `i` fails: the closest earlier
+        // step with a compensation, else the saga is compensated outright.
+        let compensation_before = |i: usize| {
+            self.steps[..i]
+                .iter()
+                .rposition(|s| s.has_compensation)
+                .map_or_else(|| "compensated".to_string(), |j| format!("c{j}"))
+        };
+
+        let first = if self.steps.is_empty() {
+            "completed"
+        } else {
+            "s0"
+        };
+        transitions.push(SagaTransition::new(
+            "start",
+            first,
+            "start",
+            TransitionKind::Forward,
+        ));
+        for (i, step) in self.steps.iter().enumerate() {
+            let next = if i + 1 < self.steps.len() {
+                format!("s{}", i + 1)
+            } else {
+                "completed".to_string()
+            };
+            let from = format!("s{i}");
+            transitions.push(SagaTransition::new(
+                &from,
+                &next,
+                "completed",
+                TransitionKind::Forward,
+            ));
+            transitions.push(SagaTransition::new(
+                &from,
+                &compensation_before(i),
+                "failed",
+                TransitionKind::Failure,
+            ));
+
+            if step.has_compensation {
+                let from = format!("c{i}");
+                match compensation_before(i).as_str() {
+                    "compensated" => {
+                        transitions.push(SagaTransition::new(
+                            &from,
+                            "compensated",
+                            "all_compensated",
+                            TransitionKind::Compensation,
+                        ));
+                        transitions.push(SagaTransition::new(
+                            &from,
+                            "failed",
+                            "compensation_failed",
+                            TransitionKind::Compensation,
+                        ));
+                    }
+                    previous => {
+                        for on in ["compensated", "compensation_failed"] {
+                            transitions.push(SagaTransition::new(
+                                &from,
+                                previous,
+                                on,
+                                TransitionKind::Compensation,
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+
+        SagaStateMachine {
+            saga: self.name.clone(),
+            description: self.description.clone(),
+            initial: "start".to_string(),
+            states,
+            transitions,
+        }
+    }
+
+    /// Render the saga as a Graphviz DOT digraph.
+    pub fn to_dot(&self) -> String {
+        let machine = self.state_machine();
+        let mut out = format!("digraph {} {{\n    rankdir=LR;\n", dot_quote(&self.name));
+        for state in &machine.states {
+            let shape = match state.kind {
+                StateKind::Start => "shape=circle",
+                StateKind::Step => "shape=box",
+                StateKind::Compensation => "shape=box, style=dashed",
+                _ => "shape=doublecircle",
+            };
+            out.push_str(&format!(
+                "    {} [label={}, {shape}];\n",
+                state.id,
+                dot_quote(&state.label)
+            ));
+        }
+        for t in &machine.transitions {
+            let style = match t.kind {
+                TransitionKind::Forward => "",
+                TransitionKind::Failure => ", color=red",
+                TransitionKind::Compensation => ", style=dashed",
+            };
+            out.push_str(&format!(
+                "    {} -> {} [label={}{style}];\n",
+                t.from,
+                t.to,
+                dot_quote(&t.on)
+            ));
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// Render the saga as a Mermaid flowchart.
+    pub fn to_mermaid(&self) -> String {
+        let machine = self.state_machine();
+        let mut out = String::from("flowchart TD\n");
+        for state in &machine.states {
+            let label = mermaid_escape(&state.label);
+            let node = match state.kind {
+                StateKind::Step | StateKind::Compensation => format!("[\"{label}\"]"),
+                _ => format!("((\"{label}\"))"),
+            };
+            out.push_str(&format!("    {}{node}\n", state.id));
+        }
+        for t in &machine.transitions {
+            let arrow = match t.kind {
+                TransitionKind::Compensation => "-.->",
+                _ => "-->",
+            };
+            out.push_str(&format!("    {} {arrow}|{}| {}\n", t.from, t.on, t.to));
+        }
+        out
+    }
+}
+
+/// Quote a string as a DOT identifier.
+fn dot_quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Escape a string for a quoted Mermaid label.
+fn mermaid_escape(s: &str) -> String {
+    s.replace('"', "#quot;")
+}
+
+/// Kind of state in a [`SagaStateMachine`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StateKind {
+    /// Initial state.
+    Start,
+    /// Forward execution of a step.
+    Step,
+    /// Compensation of a step.
+    Compensation,
+    /// Every step completed.
+    Completed,
+    /// Every necessary step was compensated.
+    Compensated,
+    /// A compensation failed.
+    Failed,
+}
+
+/// Kind of edge in a [`SagaStateMachine`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransitionKind {
+    /// Progress to the next step.
+    Forward,
+    /// A step failed and compensation begins.
+    Failure,
+    /// Progress through compensation.
+    Compensation,
+}
+
+/// State of a [`SagaStateMachine`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SagaState {
+    /// Identifier used by transitions (`s{n}` for steps, `c{n}` for their
+    /// compensations).
+    pub id: String,
+    /// What the state represents.
+    pub kind: StateKind,
+    /// Human-readable label.
+    pub label: String,
+    /// Step the state belongs to, if any.
+    pub step: Option<String>,
+    /// Timeout of the step, if any.
+    pub timeout_ms: Option<u64>,
+}
+
+impl SagaState {
+    fn terminal(id: &str, kind: StateKind, label: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            kind,
+            label: label.to_string(),
+            step: None,
+            timeout_ms: None,
+        }
+    }
+}
+
+/// Edge of a [`SagaStateMachine`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SagaTransition {
+    /// Source state ID.
+    pub from: String,
+    /// Target state ID.
+    pub to: String,
+    /// Event taking the edge.
+    pub on: String,
+    /// What the edge represents.
+    pub kind: TransitionKind,
+}
+
+impl SagaTransition {
+    fn new(from: &str, to: &str, on: &str, kind: TransitionKind) -> Self {
+        Self {
+            from: from.to_string(),
+            to: to.to_string(),
+            on: on.to_string(),
+            kind,
+        }
+    }
+}
+
+/// Saga definition described as states and transitions.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SagaStateMachine {
+    /// Saga name.
+    pub saga: String,
+    /// Saga description, if any.
+    pub description: Option<String>,
+    /// ID of the initial state.
+    pub initial: String,
+    /// All states.
+    pub states: Vec<SagaState>,
+    /// All transitions.
+    pub transitions: Vec<SagaTransition>,
 }
 
 // ============================================================================
@@ -321,6 +743,19 @@ pub struct SagaExecution {
     pub started_at: u64,
     /// Unix timestamp (seconds) when execution completed (if finished).
     pub completed_at: Option<u64>,
+    /// Input the execution was started with.
+    #[serde(default)]
+    pub input: JsonValue,
+    /// Why the execution failed, if it did.
+    #[serde(default)]
+    pub error: Option<SagaError>,
+    /// Compensations that still failed after their retries.
+    #[serde(default)]
+    pub compensation_errors: Vec<SagaError>,
+    /// Key the idempotency keys of the execution's steps derive from: a
+    /// random UUID, or the key the execution was run with.
+    #[serde(default)]
+    pub idempotency_key: String,
 }
 
 impl SagaExecution {
@@ -338,9 +773,19 @@ impl SagaExecution {
                 .unwrap_or_default()
                 .as_secs(),
             completed_at: None,
+            input: JsonValue::Null,
+            error: None,
+            compensation_errors: Vec::new(),
+            idempotency_key: uuid::Uuid::new_v4().to_string(),
         }
     }
 
+    /// Idempotency key of the step `step_name`, stable across retries and
+    /// resumes of this execution.
+    pub fn step_key(&self, step_name: &str) -> String {
+        format!("{}:{}:{step_name}", self.saga_name, self.idempotency_key)
+    }
+
     /// Get the current step index (first non-completed step).
     pub fn current_step_index(&self) -> Option<usize> {
         self.steps
@@ -372,6 +817,341 @@ impl SagaExecution {
     }
 }
 
+// ============================================================================
+// Execution Queries
+// ============================================================================
+
+/// Default number of executions per page.
+pub const DEFAULT_PAGE_SIZE: usize = 50;
+
+/// Largest page [`SagaOrchestrator::query_executions`] returns.
+pub const MAX_PAGE_SIZE: usize = 1000;
+
+/// Filter and page for listing saga executions.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ExecutionQuery {
+    /// Only executions of this saga.
+    pub saga_name: Option<String>,
+    /// Only executions in this status.
+    pub status: Option<SagaStatus>,
+    /// Only executions started at or after this Unix timestamp (seconds).
+    pub started_after: Option<u64>,
+    /// Only executions started before this Unix timestamp (seconds).
+    pub started_before: Option<u64>,
+    /// Number of matching executions to skip.
+    pub offset: usize,
+    /// Page size; `None` uses [`DEFAULT_PAGE_SIZE`].
+    pub limit: Option<usize>,
+}
+
+impl ExecutionQuery {
+    /// Create a query matching every execution.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only match executions of `saga_name`.
+    pub fn with_saga(mut self, saga_name: &str) -> Self {
+        self.saga_name = Some(saga_name.to_string());
+        self
+    }
+
+    /// Only match executions in `status`.
+    pub fn with_status(mut self, status: SagaStatus) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    /// Only match executions started in `[from, to)` (Unix seconds).
+    pub fn started_between(mut self, from: Option<u64>, to: Option<u64>) -> Self {
+        self.started_after = from;
+        self.started_before = to;
+        self
+    }
+
+    /// Select the page of `limit` executions after skipping `offset`.
+    pub fn page(mut self, offset: usize, limit: usize) -> Self {
+        self.offset = offset;
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Effective page size, capped at [`MAX_PAGE_SIZE`].
+    pub fn limit(&self) -> usize {
+        self.limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE)
+    }
+
+    /// Whether `execution` passes the filters.
+    pub fn matches(&self, execution: &SagaExecution) -> bool {
+        self.saga_name
+            .as_ref()
+            .is_none_or(|name| &execution.saga_name == name)
+            && self.status.as_ref().is_none_or(|s| &execution.status == s)
+            && self.started_after.is_none_or(|t| execution.started_at >= t)
+            && self.started_before.is_none_or(|t| execution.started_at < t)
+    }
+}
+
+/// One page of saga executions.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ExecutionPage {
+    /// Executions on this page, newest first.
+    pub executions: Vec<SagaExecution>,
+    /// Number of executions matching the query across all pages.
+    pub total: usize,
+    /// Offset of this page.
+    pub offset: usize,
+    /// Page size used.
+    pub limit: usize,
+}
+
+/// Sequence number from an `exec-{saga}-{n}` execution ID, for ordering
+/// executions started in the same second.
+fn execution_sequence(id: &str) -> u64 {
+    id.rsplit('-')
+        .next()
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(0)
+}
+
+// ============================================================================
+// Step Handlers
+// ============================================================================
+
+/// Future returned by a step handler.
+pub type SagaStepFuture = Pin<Box<dyn Future<Output = Result<JsonValue, String>> + Send>>;
+
+/// Future of one bounded handler call; `None` if it ran out of time.
+type SagaAttemptFuture = Pin<Box<dyn Future<Output = Option<Result<JsonValue, String>>> + Send>>;
+
+/// Handler that performs or compensates a saga step.
+///
+/// The handler receives the step context, an object with the execution's
+/// `execution_id`, its `input`, the `results` of the steps completed so far
+/// (keyed by step name) and the step's `idempotency_key`, to pass on to the
+/// services it calls. Compensation handlers additionally get the
+/// `step` being undone and its `result`. A step handler's result is recorded
+/// on the step; an error fails the attempt.
+#[derive(Clone)]
+pub struct SagaStepHandler(
+    Arc<dyn Fn(JsonValue, Option<Duration>) -> SagaAttemptFuture + Send + Sync>,
+);
+
+impl SagaStepHandler {
+    /// Create a handler from an async Rust closure.
+    pub fn new<F, Fut>(handler: F) -> Self
+    where
+        F: Fn(JsonValue) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<JsonValue, String>> + Send + 'static,
+    {
+        Self(Arc::new(move |context, timeout| {
+            let call = handler(context);
+            Box::pin(async move {
+                match timeout {
+                    Some(timeout) => tokio::time::timeout(timeout, call).await.ok(),
+                    None => Some(call.await),
+                }
+            })
+        }))
+    }
+
+    /// Create a handler from a synchronous Rust closure.
+    pub fn sync<F>(handler: F) -> Self
+    where
+        F: Fn(JsonValue) -> Result<JsonValue, String> + Send + Sync + 'static,
+    {
+        Self::new(move |context| std::future::ready(handler(context)))
+    }
+
+    /// Create a handler from a sync or async Python callable, called with the
+    /// context as a dict. A raised exception fails the attempt.
+    ///
+    /// A timed-out attempt is stopped, not abandoned: a coroutine is
+    /// cancelled and a sync callable is interrupted (see
+    /// [`call_with_timeout`](crate::event_loop::call_with_timeout)), so it
+    /// never runs on alongside its retry.
+    pub fn python(handler: PyObject) -> Self {
+        let handler = Arc::new(handler);
+        Self(Arc::new(move |context, timeout| {
+            let handler = handler.clone();
+            Box::pin(call_python_step(handler, context, timeout))
+        }))
+    }
+
+    /// Invoke the handler.
+    pub fn call(&self, context: JsonValue) -> SagaStepFuture {
+        let attempt = (self.0)(context, None);
+        Box::pin(async move {
+            attempt
+                .await
+                .unwrap_or_else(|| Err("timed out".to_string()))
+        })
+    }
+
+    /// Invoke the handler, giving up once `timeout` passes. Returns `None`
+    /// if it did.
+    pub fn call_with_timeout(
+        &self,
+        context: JsonValue,
+        timeout: Duration,
+    ) -> impl Future<Output = Option<Result<JsonValue, String>>> + Send {
+        (self.0)(context, Some(timeout))
+    }
+}
+
+impl std::fmt::Debug for SagaStepHandler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SagaStepHandler")
+    }
+}
+
+/// Drive a Python step handler to completion within `timeout`, awaiting any
+/// coroutine via Tokio with the GIL released. Returns `None` on timeout.
+async fn call_python_step(
+    handler: Arc<PyObject>,
+    context: JsonValue,
+    timeout: Option<Duration>,
+) -> Option<Result<JsonValue, String>> {
+    let args = move |py: Python<'_>| -> PyResult<Py<PyTuple>> {
+        Ok(PyTuple::new(py, [json_to_python(py, &context)?]).into())
+    };
+    let result = match call_with_timeout(handler, args, timeout).await {
+        Ok(result) => result,
+        Err(CallError::TimedOut) => return None,
+        Err(CallError::Raised(e)) => return Some(Err(e.to_string())),
+    };
+    Some(Python::with_gil(|py| python_to_json(py, result.as_ref(py))))
+}
+
+// ============================================================================
+// Step Deduplication
+// ============================================================================
+
+/// Storage for the results of completed steps, keyed by step idempotency
+/// key.
+///
+/// Before each attempt the orchestrator looks the step up; a stored result
+/// completes the step without calling its handler again. Results are stored
+/// as soon as a step succeeds, so a step whose completion was lost to a
+/// crash is not applied twice when its execution is run again.
+pub trait SagaDedupStore: Send + Sync {
+    /// The stored result of the step with `key`, if it completed.
+    fn get(&self, key: &str) -> Result<Option<JsonValue>, SagaError>;
+
+    /// Store the `result` of the step with `key` for `ttl`.
+    fn put(&self, key: &str, result: &JsonValue, ttl: Duration) -> Result<(), SagaError>;
+
+    /// Store name for diagnostics.
+    fn name(&self) -> &str;
+}
+
+/// In-process dedup store.
+#[derive(Default)]
+pub struct InMemorySagaDedupStore {
+    results: Mutex<HashMap<String, (JsonValue, Instant)>>,
+}
+
+impl InMemorySagaDedupStore {
+    /// Create an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of live results.
+    pub fn len(&self) -> usize {
+        let now = Instant::now();
+        self.results
+            .lock()
+            .values()
+            .filter(|(_, expires)| *expires > now)
+            .count()
+    }
+
+    /// Whether the store holds no live results.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl SagaDedupStore for InMemorySagaDedupStore {
+    fn get(&self, key: &str) -> Result<Option<JsonValue>, SagaError> {
+        Ok(self
+            .results
+            .lock()
+            .get(key)
+            .filter(|(_, expires)| *expires > Instant::now())
+            .map(|(result, _)| result.clone()))
+    }
+
+    fn put(&self, key: &str, result: &JsonValue, ttl: Duration) -> Result<(), SagaError> {
+        let now = Instant::now();
+        let mut results = self.results.lock();
+        // Expired results are dropped as new ones come in
+        results.retain(|_, (_, expires)| *expires > now);
+        results.insert(key.to_string(), (result.clone(), now + ttl));
+        Ok(())
+    }
+
+    fn name(&self) -> &str {
+        "memory"
+    }
+}
+
+/// Redis dedup store, shared by every worker using the same Redis.
+pub struct RedisSagaDedupStore {
+    client: Arc<dyn RedisClient>,
+    key_prefix: String,
+}
+
+impl RedisSagaDedupStore {
+    /// Create a store on a Redis connection.
+    pub fn new(client: Arc<dyn RedisClient>) -> Self {
+        Self {
+            client,
+            key_prefix: "saga:dedup:".to_string(),
+        }
+    }
+
+    /// Prefix for Redis keys (default `saga:dedup:`).
+    pub fn with_key_prefix(mut self, prefix: &str) -> Self {
+        self.key_prefix = prefix.to_string();
+        self
+    }
+}
+
+impl SagaDedupStore for RedisSagaDedupStore {
+    fn get(&self, key: &str) -> Result<Option<JsonValue>, SagaError> {
+        let key = format!("{}{key}", self.key_prefix);
+        let value = self
+            .client
+            .get(&key)
+            .map_err(|e| SagaError::StoreError(e.to_string()))?;
+        let json = match &value {
+            None => return Ok(None),
+            Some(RedisValue::String(s)) => s.as_bytes(),
+            Some(RedisValue::Bytes(b)) => b.as_slice(),
+            Some(_) => return Err(SagaError::StoreError(format!("bad value at {key}"))),
+        };
+        serde_json::from_slice(json)
+            .map(Some)
+            .map_err(|e| SagaError::StoreError(e.to_string()))
+    }
+
+    fn put(&self, key: &str, result: &JsonValue, ttl: Duration) -> Result<(), SagaError> {
+        self.client
+            .set(
+                &format!("{}{key}", self.key_prefix),
+                RedisValue::String(result.to_string()),
+                Some(ttl),
+            )
+            .map_err(|e| SagaError::StoreError(e.to_string()))
+    }
+
+    fn name(&self) -> &str {
+        "redis"
+    }
+}
+
 // ============================================================================
 // Saga Orchestrator
 // ============================================================================
@@ -385,10 +1165,18 @@ pub struct SagaOrchestrator {
     sagas: Arc<RwLock<HashMap<String, SagaDefinition>>>,
     /// Saga executions keyed by execution ID.
     executions: Arc<RwLock<HashMap<String, SagaExecution>>>,
+    /// Step handlers keyed by saga name, then step name.
+    handlers: Arc<RwLock<HashMap<String, HashMap<String, SagaStepHandler>>>>,
+    /// Compensation handlers keyed by saga name, then step name.
+    compensations: Arc<RwLock<HashMap<String, HashMap<String, SagaStepHandler>>>>,
     /// Internal metrics tracker.
     metrics: Arc<SagaMetrics>,
     /// Configuration reference.
     config: SagaConfig,
+    /// Store of completed step results, and how long they are kept.
+    dedup: Option<(Arc<dyn SagaDedupStore>, Duration)>,
+    /// Signal steps waiting, keyed by correlation token.
+    signals: Arc<Mutex<HashMap<String, oneshot::Sender<JsonValue>>>>,
     /// Counter for generating unique execution IDs.
     execution_counter: AtomicU64,
 }
@@ -399,8 +1187,12 @@ impl SagaOrchestrator {
         Self {
             sagas: Arc::new(RwLock::new(HashMap::new())),
             executions: Arc::new(RwLock::new(HashMap::new())),
+            handlers: Arc::new(RwLock::new(HashMap::new())),
+            compensations: Arc::new(RwLock::new(HashMap::new())),
             metrics: Arc::new(SagaMetrics::default()),
             config: SagaConfig::default(),
+            dedup: None,
+            signals: Arc::new(Mutex::new(HashMap::new())),
             execution_counter: AtomicU64::new(0),
         }
     }
@@ -410,17 +1202,37 @@ impl SagaOrchestrator {
         Self {
             sagas: Arc::new(RwLock::new(HashMap::new())),
             executions: Arc::new(RwLock::new(HashMap::new())),
+            handlers: Arc::new(RwLock::new(HashMap::new())),
+            compensations: Arc::new(RwLock::new(HashMap::new())),
             metrics: Arc::new(SagaMetrics::default()),
             config,
+            dedup: None,
+            signals: Arc::new(Mutex::new(HashMap::new())),
             execution_counter: AtomicU64::new(0),
         }
     }
 
+    /// Deduplicate steps through `store`, keeping completed step results
+    /// for `ttl`.
+    pub fn with_dedup_store(mut self, store: Arc<dyn SagaDedupStore>, ttl: Duration) -> Self {
+        self.dedup = Some((store, ttl));
+        self
+    }
+
     /// Register a saga definition.
     pub fn register_saga(&self, saga: SagaDefinition) {
         self.sagas.write().insert(saga.name.clone(), saga);
     }
 
+    /// Get a copy of a registered saga definition.
+    pub fn definition(&self, saga_name: &str) -> Result<SagaDefinition, SagaError> {
+        self.sagas
+            .read()
+            .get(saga_name)
+            .cloned()
+            .ok_or_else(|| SagaError::SagaNotFound(saga_name.to_string()))
+    }
+
     /// Start a new execution of a registered saga.
     ///
     /// Returns the execution ID on success, or an error if the saga
@@ -451,6 +1263,28 @@ impl SagaOrchestrator {
         Ok(execution_id)
     }
 
+    /// Whether `step_name` is the first step of `saga_name`.
+    pub fn is_first_step(&self, saga_name: &str, step_name: &str) -> Result<bool, SagaError> {
+        let sagas = self.sagas.read();
+        let saga = sagas
+            .get(saga_name)
+            .ok_or_else(|| SagaError::SagaNotFound(saga_name.to_string()))?;
+        Ok(saga.steps.first().is_some_and(|s| s.name == step_name))
+    }
+
+    /// Start a new execution of a registered saga, recording its input.
+    pub fn start_execution_with_input(
+        &self,
+        saga_name: &str,
+        input: JsonValue,
+    ) -> Result<String, SagaError> {
+        let execution_id = self.start_execution(saga_name)?;
+        if let Some(execution) = self.executions.write().get_mut(&execution_id) {
+            execution.input = input;
+        }
+        Ok(execution_id)
+    }
+
     /// Get a snapshot of a saga execution by ID.
     pub fn get_execution(&self, execution_id: &str) -> Result<SagaExecution, SagaError> {
         self.executions
@@ -460,6 +1294,53 @@ impl SagaOrchestrator {
             .ok_or_else(|| SagaError::ExecutionNotFound(execution_id.to_string()))
     }
 
+    /// Track an execution saved elsewhere, for example before a restart.
+    ///
+    /// The saga must be registered and the ID unused. Resume a `Running`
+    /// execution with [`resume_execution`](Self::resume_execution); its
+    /// timers keep their wake-up times and its signal steps their tokens.
+    pub fn restore_execution(&self, execution: SagaExecution) -> Result<(), SagaError> {
+        if !self.sagas.read().contains_key(&execution.saga_name) {
+            return Err(SagaError::SagaNotFound(execution.saga_name));
+        }
+        let mut executions = self.executions.write();
+        if executions.contains_key(&execution.id) {
+            return Err(SagaError::InvalidState(format!(
+                "execution '{}' already exists",
+                execution.id
+            )));
+        }
+        // New IDs must not collide with restored ones
+        self.execution_counter
+            .fetch_max(execution_sequence(&execution.id) + 1, Ordering::Relaxed);
+        executions.insert(execution.id.clone(), execution);
+        Ok(())
+    }
+
+    /// List executions matching `query`, newest first, one page at a time.
+    pub fn query_executions(&self, query: &ExecutionQuery) -> ExecutionPage {
+        let mut matching: Vec<SagaExecution> = self
+            .executions
+            .read()
+            .values()
+            .filter(|e| query.matches(e))
+            .cloned()
+            .collect();
+        matching.sort_by_key(|e| std::cmp::Reverse((e.started_at, execution_sequence(&e.id))));
+
+        let limit = query.limit();
+        ExecutionPage {
+            total: matching.len(),
+            executions: matching
+                .into_iter()
+                .skip(query.offset)
+                .take(limit)
+                .collect(),
+            offset: query.offset,
+            limit,
+        }
+    }
+
     /// List all saga executions.
     pub fn list_executions(&self) -> Vec<SagaExecution> {
         self.executions.read().values().cloned().collect()
@@ -521,6 +1402,10 @@ impl SagaOrchestrator {
     }
 
     /// Mark a step as failed and trigger compensation for completed steps.
+    ///
+    /// This only records compensation; use
+    /// [`fail_and_compensate`](Self::fail_and_compensate) to run the
+    /// registered compensation handlers.
     pub fn fail_step(
         &self,
         execution_id: &str,
@@ -594,39 +1479,830 @@ impl SagaOrchestrator {
         Ok(())
     }
 
-    /// Get the number of registered saga definitions.
-    pub fn saga_count(&self) -> usize {
-        self.sagas.read().len()
+    /// Append a step to a saga, registering an empty definition first if the
+    /// saga is unknown. A step with the same name is replaced in place.
+    pub fn add_step(&self, saga_name: &str, step: SagaStepDef) {
+        let mut sagas = self.sagas.write();
+        let saga = sagas
+            .entry(saga_name.to_string())
+            .or_insert_with(|| SagaDefinition::new(saga_name));
+        match saga.steps.iter_mut().find(|s| s.name == step.name) {
+            Some(existing) => *existing = step,
+            None => saga.add_step(step),
+        }
     }
 
-    /// Get the number of tracked executions.
-    pub fn execution_count(&self) -> usize {
-        self.executions.read().len()
+    /// Register the handler that performs `step_name` of `saga_name`.
+    pub fn register_step_handler(
+        &self,
+        saga_name: &str,
+        step_name: &str,
+        handler: SagaStepHandler,
+    ) -> Result<(), SagaError> {
+        let sagas = self.sagas.read();
+        let saga = sagas
+            .get(saga_name)
+            .ok_or_else(|| SagaError::SagaNotFound(saga_name.to_string()))?;
+        if !saga.steps.iter().any(|s| s.name == step_name) {
+            return Err(SagaError::StepNotFound(step_name.to_string()));
+        }
+        self.handlers
+            .write()
+            .entry(saga_name.to_string())
+            .or_default()
+            .insert(step_name.to_string(), handler);
+        Ok(())
     }
 
-    /// Get the configuration.
-    pub fn config(&self) -> &SagaConfig {
-        &self.config
+    /// Register the handler that undoes `step_name` of `saga_name`, marking the
+    /// step as having a compensation action.
+    pub fn register_compensation(
+        &self,
+        saga_name: &str,
+        step_name: &str,
+        handler: SagaStepHandler,
+    ) -> Result<(), SagaError> {
+        let mut sagas = self.sagas.write();
+        let step = sagas
+            .get_mut(saga_name)
+            .ok_or_else(|| SagaError::SagaNotFound(saga_name.to_string()))?
+            .steps
+            .iter_mut()
+            .find(|s| s.name == step_name)
+            .ok_or_else(|| SagaError::StepNotFound(step_name.to_string()))?;
+        step.has_compensation = true;
+        self.compensations
+            .write()
+            .entry(saga_name.to_string())
+            .or_default()
+            .insert(step_name.to_string(), handler);
+        Ok(())
     }
 
-    /// Get current statistics.
-    pub fn stats(&self) -> SagaStats {
-        self.metrics.get_stats()
-    }
-}
+    /// Mark a step as failed and start compensating the steps completed
+    /// before it.
+    ///
+    /// Returns the steps awaiting compensation, last completed first, with
+    /// their results. Each must be settled with
+    /// [`finish_compensation`](Self::finish_compensation); when there are
+    /// none the execution is compensated immediately.
+    pub fn begin_compensation(
+        &self,
+        execution_id: &str,
+        step_name: &str,
+        error: &str,
+    ) -> Result<Vec<(String, JsonValue)>, SagaError> {
+        let mut executions = self.executions.write();
+        let execution = executions
+            .get_mut(execution_id)
+            .ok_or_else(|| SagaError::ExecutionNotFound(execution_id.to_string()))?;
 
-impl Default for SagaOrchestrator {
+        let step = execution
+            .steps
+            .iter_mut()
+            .find(|s| s.name == step_name)
+            .ok_or_else(|| SagaError::StepNotFound(step_name.to_string()))?;
+        step.status = StepStatus::Failed;
+        step.error = Some(error.to_string());
+
+        if self.config.enable_logging {
+            println!(
+                "Saga '{}' step '{}' failed: {}",
+                execution.saga_name, step_name, error
+            );
+        }
+
+        execution.status = SagaStatus::Compensating;
+        let mut pending = Vec::new();
+        for s in execution.steps.iter_mut().rev() {
+            if s.status == StepStatus::Completed {
+                s.status = StepStatus::Compensating;
+                pending.push((s.name.clone(), s.result.clone().unwrap_or_default()));
+            }
+        }
+        self.metrics.record_execution_failed();
+        self.settle_compensation(execution);
+
+        Ok(pending)
+    }
+
+    /// Record the outcome of compensating `step_name`.
+    ///
+    /// A failure is recorded as [`SagaError::CompensationFailed`] in
+    /// `compensation_errors` and marks the step `CompensationFailed`. Once no
+    /// step is left compensating, the execution ends `Compensated`, or
+    /// `Failed` if any compensation failed.
+    pub fn finish_compensation(
+        &self,
+        execution_id: &str,
+        step_name: &str,
+        outcome: Result<(), String>,
+    ) -> Result<(), SagaError> {
+        let mut executions = self.executions.write();
+        let execution = executions
+            .get_mut(execution_id)
+            .ok_or_else(|| SagaError::ExecutionNotFound(execution_id.to_string()))?;
+        let step = execution
+            .steps
+            .iter_mut()
+            .find(|s| s.name == step_name && s.status == StepStatus::Compensating)
+            .ok_or_else(|| SagaError::StepNotFound(step_name.to_string()))?;
+
+        match outcome {
+            Ok(()) => step.status = StepStatus::Compensated,
+            Err(message) => {
+                step.status = StepStatus::CompensationFailed;
+                step.error = Some(format!("compensation failed: {message}"));
+                execution
+                    .compensation_errors
+                    .push(SagaError::CompensationFailed(format!(
+                        "step '{step_name}': {message}"
+                    )));
+            }
+        }
+        self.settle_compensation(execution);
+        Ok(())
+    }
+
+    /// Finish a compensating execution once none of its steps are pending.
+    fn settle_compensation(&self, execution: &mut SagaExecution) {
+        if execution.status != SagaStatus::Compensating
+            || execution
+                .steps
+                .iter()
+                .any(|s| s.status == StepStatus::Compensating)
+        {
+            return;
+        }
+
+        execution.status = if execution.compensation_errors.is_empty() {
+            self.metrics.record_execution_compensated();
+            SagaStatus::Compensated
+        } else {
+            SagaStatus::Failed
+        };
+        execution.completed_at = Some(
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        );
+
+        if self.config.enable_logging {
+            println!(
+                "Saga '{}' execution '{}' {}",
+                execution.saga_name,
+                execution.id,
+                if execution.compensation_errors.is_empty() {
+                    "compensated"
+                } else {
+                    "failed to compensate"
+                }
+            );
+        }
+    }
+
+    /// Mark a step as failed and compensate the steps completed before it.
+    ///
+    /// Compensation handlers run in reverse step order, each attempt bounded
+    /// by the step's `timeout_ms` and retried up to
+    /// `compensation_max_retries` times with backoff. Steps without a handler
+    /// are marked compensated directly. A compensation that keeps failing is
+    /// recorded as [`SagaError::CompensationFailed`] in
+    /// `compensation_errors`, its step is marked failed and the execution
+    /// ends `Failed` rather than `Compensated`; the remaining steps are still
+    /// compensated.
+    pub async fn fail_and_compensate(
+        &self,
+        execution_id: &str,
+        step_name: &str,
+        error: &str,
+    ) -> Result<(), SagaError> {
+        let pending = self.begin_compensation(execution_id, step_name, error)?;
+        self.compensate_pending(execution_id, pending).await
+    }
+
+    /// Run the compensation handlers for `pending` steps, in order.
+    async fn compensate_pending(
+        &self,
+        execution_id: &str,
+        pending: Vec<(String, JsonValue)>,
+    ) -> Result<(), SagaError> {
+        let execution = self.get_execution(execution_id)?;
+        let results: serde_json::Map<String, JsonValue> = pending.iter().cloned().collect();
+        let context = json!({
+            "execution_id": execution_id,
+            "input": execution.input,
+            "results": results,
+        });
+
+        let handlers = self
+            .compensations
+            .read()
+            .get(&execution.saga_name)
+            .cloned()
+            .unwrap_or_default();
+        let timeouts: HashMap<String, Option<u64>> = self
+            .sagas
+            .read()
+            .get(&execution.saga_name)
+            .map(|saga| {
+                saga.steps
+                    .iter()
+                    .map(|s| (s.name.clone(), s.timeout_ms))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        for (name, result) in pending {
+            let outcome = match handlers.get(&name) {
+                Some(handler) => {
+                    let mut context = context.clone();
+                    context["step"] = json!(name);
+                    context["result"] = result;
+                    let timeout = timeouts.get(&name).copied().flatten();
+                    self.run_compensation(&name, handler, context, timeout)
+                        .await
+                }
+                None => Ok(()),
+            };
+            self.finish_compensation(execution_id, &name, outcome)?;
+        }
+
+        Ok(())
+    }
+
+    /// Attempt a compensation until it succeeds or runs out of retries.
+    async fn run_compensation(
+        &self,
+        step_name: &str,
+        handler: &SagaStepHandler,
+        context: JsonValue,
+        timeout_ms: Option<u64>,
+    ) -> Result<(), String> {
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let outcome = match timeout_ms {
+                Some(ms) => handler
+                    .call_with_timeout(context.clone(), Duration::from_millis(ms))
+                    .await
+                    .unwrap_or_else(|| Err(format!("compensation timed out after {ms}ms"))),
+                None => handler.call(context.clone()).await,
+            };
+            let message = match outcome {
+                Ok(_) => return Ok(()),
+                Err(message) => message,
+            };
+
+            if self.config.enable_logging {
+                println!(
+                    "Saga step '{step_name}' compensation attempt {attempts} failed: {message}"
+                );
+            }
+            if attempts > self.config.compensation_max_retries {
+                return Err(message);
+            }
+            tokio::time::sleep(self.config.compensation_retry_delay(attempts)).await;
+        }
+    }
+
+    /// Run a saga to completion with its registered step handlers.
+    ///
+    /// Steps run in order. A failed or timed-out attempt is retried up to
+    /// `max_retries` times, waiting `retry_delay_ms` grown by
+    /// `backoff_multiplier` between attempts. Each attempt is bounded by the
+    /// step's `timeout_ms` and the whole run by the saga's `timeout_ms`. Only
+    /// once a step has no attempts or time left is the execution failed and
+    /// its completed steps compensated (see
+    /// [`fail_and_compensate`](Self::fail_and_compensate)); the cause is
+    /// recorded in `error`.
+    ///
+    /// Returns the final execution, or an error if the saga is unknown or a
+    /// step has no handler.
+    pub async fn run(&self, saga_name: &str, input: JsonValue) -> Result<SagaExecution, SagaError> {
+        self.run_with_key(saga_name, input, None).await
+    }
+
+    /// Run a saga as in [`run`](Self::run), deriving its step idempotency
+    /// keys from `key` instead of a random UUID.
+    ///
+    /// With a dedup store, running the saga again with the same `key` (for
+    /// example after a crash) completes the steps that already succeeded
+    /// from their stored results instead of calling their handlers.
+    pub async fn run_with_key(
+        &self,
+        saga_name: &str,
+        input: JsonValue,
+        key: Option<&str>,
+    ) -> Result<SagaExecution, SagaError> {
+        let steps = self.step_handlers(saga_name, |_| true)?;
+        let execution_id = self.start_execution_with_input(saga_name, input.clone())?;
+        if let Some(key) = key {
+            if let Some(execution) = self.executions.write().get_mut(&execution_id) {
+                execution.idempotency_key = key.to_string();
+            }
+        }
+        let context = json!({
+            "execution_id": execution_id,
+            "input": input,
+            "results": {},
+        });
+        self.drive(&execution_id, steps, context).await
+    }
+
+    /// Continue a `Running` execution from its first unfinished step.
+    ///
+    /// For executions left running by a crash or lost handler, using the
+    /// same retry, backoff and deadline rules as [`run`](Self::run); the
+    /// saga deadline restarts from now.
+    pub async fn resume_execution(&self, execution_id: &str) -> Result<SagaExecution, SagaError> {
+        let execution = self.get_execution(execution_id)?;
+        if execution.status != SagaStatus::Running {
+            return Err(SagaError::InvalidState(format!(
+                "execution '{execution_id}' is {}, not Running",
+                execution.status
+            )));
+        }
+
+        let mut results = serde_json::Map::new();
+        for step in &execution.steps {
+            if step.status == StepStatus::Completed {
+                results.insert(step.name.clone(), step.result.clone().unwrap_or_default());
+            }
+        }
+        let steps = self.step_handlers(&execution.saga_name, |name| !results.contains_key(name))?;
+        let context = json!({
+            "execution_id": execution_id,
+            "input": execution.input,
+            "results": results,
+        });
+        self.drive(execution_id, steps, context).await
+    }
+
+    /// Compensate an execution that is stuck, without waiting for its steps.
+    ///
+    /// A `Running` execution has its first unfinished step failed with
+    /// `reason` and is compensated as in
+    /// [`fail_and_compensate`](Self::fail_and_compensate). An execution that
+    /// is `Compensating`, or `Failed` because compensations failed, has
+    /// those compensations run again.
+    pub async fn force_compensate(
+        &self,
+        execution_id: &str,
+        reason: &str,
+    ) -> Result<SagaExecution, SagaError> {
+        let execution = self.get_execution(execution_id)?;
+        match execution.status {
+            SagaStatus::Running => {
+                let step = execution
+                    .steps
+                    .iter()
+                    .find(|s| s.status != StepStatus::Completed)
+                    .ok_or_else(|| {
+                        SagaError::InvalidState(format!(
+                            "execution '{execution_id}' has no unfinished step"
+                        ))
+                    })?;
+                self.fail_and_compensate(execution_id, &step.name, reason)
+                    .await?;
+            }
+            SagaStatus::Compensating | SagaStatus::Failed => {
+                let pending = {
+                    let mut executions = self.executions.write();
+                    let execution = executions
+                        .get_mut(execution_id)
+                        .ok_or_else(|| SagaError::ExecutionNotFound(execution_id.to_string()))?;
+                    let mut pending = Vec::new();
+                    for s in execution.steps.iter_mut().rev() {
+                        if matches!(
+                            s.status,
+                            StepStatus::Compensating | StepStatus::CompensationFailed
+                        ) {
+                            s.status = StepStatus::Compensating;
+                            s.error = None;
+                            pending.push((s.name.clone(), s.result.clone().unwrap_or_default()));
+                        }
+                    }
+                    if pending.is_empty() {
+                        return Err(SagaError::InvalidState(format!(
+                            "execution '{execution_id}' has nothing to compensate"
+                        )));
+                    }
+                    if self.config.enable_logging {
+                        println!(
+                            "Saga '{}' execution '{execution_id}' force-compensated: {reason}",
+                            execution.saga_name
+                        );
+                    }
+                    execution.status = SagaStatus::Compensating;
+                    execution.compensation_errors.clear();
+                    pending
+                };
+                self.compensate_pending(execution_id, pending).await?;
+            }
+            status => {
+                return Err(SagaError::InvalidState(format!(
+                    "execution '{execution_id}' is {status}"
+                )))
+            }
+        }
+        self.get_execution(execution_id)
+    }
+
+    /// Step definitions and handlers for the steps of `saga_name` selected by
+    /// `include`, failing if an action step has no handler. Timer and signal
+    /// steps without one get a handler that does nothing.
+    fn step_handlers(
+        &self,
+        saga_name: &str,
+        include: impl Fn(&str) -> bool,
+    ) -> Result<Vec<(SagaStepDef, SagaStepHandler)>, SagaError> {
+        let sagas = self.sagas.read();
+        let saga = sagas
+            .get(saga_name)
+            .ok_or_else(|| SagaError::SagaNotFound(saga_name.to_string()))?;
+        let handlers = self.handlers.read();
+        saga.steps
+            .iter()
+            .filter(|step| include(&step.name))
+            .map(|step| {
+                handlers
+                    .get(saga_name)
+                    .and_then(|h| h.get(&step.name))
+                    .cloned()
+                    .or_else(|| {
+                        step.kind
+                            .is_wait()
+                            .then(|| SagaStepHandler::sync(|_| Ok(JsonValue::Null)))
+                    })
+                    .map(|handler| (step.clone(), handler))
+                    .ok_or_else(|| SagaError::StepNotFound(step.name.clone()))
+            })
+            .collect()
+    }
+
+    /// Run `steps` of an execution in order, compensating on failure.
+    async fn drive(
+        &self,
+        execution_id: &str,
+        steps: Vec<(SagaStepDef, SagaStepHandler)>,
+        mut context: JsonValue,
+    ) -> Result<SagaExecution, SagaError> {
+        let started = Instant::now();
+        let mut deadline = started + Duration::from_millis(self.config.timeout_ms);
+        let execution = self.get_execution(execution_id)?;
+
+        for (step, handler) in steps {
+            context["idempotency_key"] = json!(execution.step_key(&step.name));
+            let outcome = if step.kind.is_wait() {
+                // Time spent waiting does not count towards the saga deadline
+                let waiting = Instant::now();
+                let outcome = self
+                    .wait_step(execution_id, &step, &handler, &context, deadline)
+                    .await;
+                deadline += waiting.elapsed();
+                outcome
+            } else {
+                self.run_step(execution_id, &step, &handler, &context, deadline)
+                    .await
+            };
+            match outcome {
+                Ok(result) => {
+                    context["results"][&step.name] = result.clone();
+                    self.complete_step(execution_id, &step.name, Some(result))?;
+                }
+                Err((cause, message)) => {
+                    if let Some(execution) = self.executions.write().get_mut(execution_id) {
+                        execution.error = Some(cause);
+                    }
+                    self.fail_and_compensate(execution_id, &step.name, &message)
+                        .await?;
+                    return self.get_execution(execution_id);
+                }
+            }
+        }
+
+        self.metrics
+            .record_duration(started.elapsed().as_millis() as u64);
+        self.get_execution(execution_id)
+    }
+
+    /// Attempt a step until it succeeds or runs out of retries or time.
+    ///
+    /// With a dedup store, each attempt first looks for a stored result of
+    /// the step and returns it without calling the handler; a result is
+    /// stored as soon as an attempt succeeds. On failure, returns the cause
+    /// and the last attempt's error message.
+    async fn run_step(
+        &self,
+        execution_id: &str,
+        step: &SagaStepDef,
+        handler: &SagaStepHandler,
+        context: &JsonValue,
+        deadline: Instant,
+    ) -> Result<JsonValue, (SagaError, String)> {
+        self.set_step_status(execution_id, &step.name, StepStatus::Running);
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let limit = step
+                .timeout_ms
+                .map(Duration::from_millis)
+                .map_or(remaining, |t| t.min(remaining));
+
+            let attempt = match self.deduplicate(step, context) {
+                Ok(Some(result)) => return Ok(result),
+                Ok(None) => handler.call_with_timeout(context.clone(), limit).await,
+                Err(error) => Some(Err(error.to_string())),
+            };
+            let message = match attempt {
+                Some(Ok(result)) => {
+                    self.store_result(step, context, &result);
+                    return Ok(result);
+                }
+                Some(Err(error)) => error,
+                None if limit == remaining => {
+                    let message = format!("saga deadline of {}ms exceeded", self.config.timeout_ms);
+                    return Err((SagaError::TimeoutError(message.clone()), message));
+                }
+                None => format!("step timed out after {}ms", limit.as_millis()),
+            };
+
+            if self.config.enable_logging {
+                println!(
+                    "Saga step '{}' attempt {attempts} failed: {message}",
+                    step.name
+                );
+            }
+            if attempts > self.config.max_retries {
+                let cause = SagaError::MaxRetriesExceeded {
+                    step: step.name.clone(),
+                    attempts,
+                };
+                return Err((cause, message));
+            }
+            let delay = self.config.retry_delay(attempts);
+            if Instant::now() + delay >= deadline {
+                let cause = SagaError::TimeoutError(format!(
+                    "saga deadline of {}ms exceeded while retrying '{}'",
+                    self.config.timeout_ms, step.name
+                ));
+                return Err((cause, message));
+            }
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// The stored result of `step`, if the dedup store has one.
+    fn deduplicate(
+        &self,
+        step: &SagaStepDef,
+        context: &JsonValue,
+    ) -> Result<Option<JsonValue>, SagaError> {
+        let (Some((store, _)), Some(key)) = (&self.dedup, context["idempotency_key"].as_str())
+        else {
+            return Ok(None);
+        };
+        let result = store.get(key)?;
+        if result.is_some() {
+            self.metrics.record_step_deduplicated();
+            if self.config.enable_logging {
+                println!(
+                    "Saga step '{}' already completed as '{key}'; skipping",
+                    step.name
+                );
+            }
+        }
+        Ok(result)
+    }
+
+    /// Store the result of `step` in the dedup store, if there is one.
+    ///
+    /// The step has already succeeded, so a failing store is only logged.
+    fn store_result(&self, step: &SagaStepDef, context: &JsonValue, result: &JsonValue) {
+        let (Some((store, ttl)), Some(key)) = (&self.dedup, context["idempotency_key"].as_str())
+        else {
+            return;
+        };
+        if let Err(error) = store.put(key, result, *ttl) {
+            eprintln!(
+                "Saga step '{}' result not stored in {} dedup store: {error}",
+                step.name,
+                store.name()
+            );
+        }
+    }
+
+    /// Wait out a timer or signal step; action steps are run as by
+    /// [`run_step`](Self::run_step).
+    ///
+    /// A timer's wake-up time and a signal's token are recorded on the step
+    /// when it first starts and reused when the execution is resumed. A
+    /// signal step's handler is called once with the `signal_token` in its
+    /// context, after the token is registered, to hand it to whoever will
+    /// send the signal.
+    async fn wait_step(
+        &self,
+        execution_id: &str,
+        step: &SagaStepDef,
+        handler: &SagaStepHandler,
+        context: &JsonValue,
+        deadline: Instant,
+    ) -> Result<JsonValue, (SagaError, String)> {
+        match self.deduplicate(step, context) {
+            Ok(Some(result)) => return Ok(result),
+            Ok(None) => {}
+            Err(error) => {
+                let message = error.to_string();
+                return Err((error, message));
+            }
+        }
+        let state = self
+            .get_execution(execution_id)
+            .ok()
+            .and_then(|e| e.steps.into_iter().find(|s| s.name == step.name));
+
+        let result = match step.kind {
+            StepKind::Action => {
+                return self
+                    .run_step(execution_id, step, handler, context, deadline)
+                    .await
+            }
+            StepKind::Delay { delay_ms } => {
+                let wake_at = state.and_then(|s| s.wake_at);
+                self.sleep_until(
+                    execution_id,
+                    step,
+                    wake_at.unwrap_or(unix_millis() + delay_ms),
+                )
+                .await
+            }
+            StepKind::Until { at_ms } => {
+                let wake_at = state.and_then(|s| s.wake_at);
+                self.sleep_until(execution_id, step, wake_at.unwrap_or(at_ms))
+                    .await
+            }
+            StepKind::Signal => {
+                let existing = state.and_then(|s| s.signal_token);
+                let resumed = existing.is_some();
+                let token = existing.unwrap_or_else(|| uuid::Uuid::new_v4().simple().to_string());
+                let (sender, receiver) = oneshot::channel();
+                self.signals.lock().insert(token.clone(), sender);
+                self.update_step(execution_id, &step.name, |s| {
+                    s.status = StepStatus::Waiting;
+                    s.signal_token = Some(token.clone());
+                });
+                if self.config.enable_logging {
+                    println!("Saga step '{}' waiting for signal '{token}'", step.name);
+                }
+
+                let outcome = self
+                    .await_signal(step, handler, context, &token, resumed, receiver, deadline)
+                    .await;
+                self.signals.lock().remove(&token);
+                outcome?
+            }
+        };
+        self.store_result(step, context, &result);
+        Ok(result)
+    }
+
+    /// Record `wake_at` (Unix milliseconds) on a timer step and sleep until
+    /// then.
+    async fn sleep_until(&self, execution_id: &str, step: &SagaStepDef, wake_at: u64) -> JsonValue {
+        self.update_step(execution_id, &step.name, |s| {
+            s.status = StepStatus::Waiting;
+            s.wake_at = Some(wake_at);
+        });
+        let now = unix_millis();
+        if wake_at > now {
+            tokio::time::sleep(Duration::from_millis(wake_at - now)).await;
+        }
+        json!({"wake_at": wake_at})
+    }
+
+    /// Notify a signal step's handler unless `resumed`, then wait for the
+    /// signal on `receiver`, bounded by the step's `timeout_ms`.
+    #[allow(clippy::too_many_arguments)]
+    async fn await_signal(
+        &self,
+        step: &SagaStepDef,
+        handler: &SagaStepHandler,
+        context: &JsonValue,
+        token: &str,
+        resumed: bool,
+        receiver: oneshot::Receiver<JsonValue>,
+        deadline: Instant,
+    ) -> Result<JsonValue, (SagaError, String)> {
+        if !resumed {
+            let mut context = context.clone();
+            context["signal_token"] = json!(token);
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match handler.call_with_timeout(context, remaining).await {
+                Some(Ok(_)) => {}
+                Some(Err(message)) => {
+                    let cause = SagaError::MaxRetriesExceeded {
+                        step: step.name.clone(),
+                        attempts: 1,
+                    };
+                    return Err((cause, message));
+                }
+                None => {
+                    let message = format!("saga deadline of {}ms exceeded", self.config.timeout_ms);
+                    return Err((SagaError::TimeoutError(message.clone()), message));
+                }
+            }
+        }
+
+        let received = match step.timeout_ms {
+            Some(ms) => tokio::time::timeout(Duration::from_millis(ms), receiver)
+                .await
+                .map_err(|_| {
+                    let message = format!("no signal within {ms}ms");
+                    (SagaError::TimeoutError(message.clone()), message)
+                })?,
+            None => receiver.await,
+        };
+        received.map_err(|_| {
+            let message = format!("signal '{token}' was abandoned");
+            (SagaError::InvalidState(message.clone()), message)
+        })
+    }
+
+    /// Deliver `payload` to the signal step waiting for `token`, completing
+    /// the step with it.
+    pub fn signal(&self, token: &str, payload: JsonValue) -> Result<(), SagaError> {
+        let sender = self.signals.lock().remove(token).ok_or_else(|| {
+            SagaError::InvalidState(format!("no step is waiting for signal '{token}'"))
+        })?;
+        sender.send(payload).map_err(|_| {
+            SagaError::InvalidState(format!("no step is waiting for signal '{token}'"))
+        })
+    }
+
+    /// Tokens of the signal steps waiting now.
+    pub fn waiting_signals(&self) -> Vec<String> {
+        self.signals.lock().keys().cloned().collect()
+    }
+
+    fn set_step_status(&self, execution_id: &str, step_name: &str, status: StepStatus) {
+        self.update_step(execution_id, step_name, |step| step.status = status);
+    }
+
+    fn update_step(&self, execution_id: &str, step_name: &str, update: impl FnOnce(&mut SagaStep)) {
+        if let Some(step) = self
+            .executions
+            .write()
+            .get_mut(execution_id)
+            .and_then(|e| e.steps.iter_mut().find(|s| s.name == step_name))
+        {
+            update(step);
+        }
+    }
+
+    /// Get the number of registered saga definitions.
+    pub fn saga_count(&self) -> usize {
+        self.sagas.read().len()
+    }
+
+    /// Get the number of tracked executions.
+    pub fn execution_count(&self) -> usize {
+        self.executions.read().len()
+    }
+
+    /// Get the configuration.
+    pub fn config(&self) -> &SagaConfig {
+        &self.config
+    }
+
+    /// Get current statistics.
+    pub fn stats(&self) -> SagaStats {
+        self.metrics.get_stats()
+    }
+}
+
+impl Default for SagaOrchestrator {
     fn default() -> Self {
         Self::new()
     }
 }
 
+/// Current time as a Unix timestamp in milliseconds.
+fn unix_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
 // ============================================================================
 // Error Types
 // ============================================================================
 
 /// Saga error types.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum SagaError {
     /// The requested saga definition was not found.
     SagaNotFound(String),
@@ -640,6 +2316,12 @@ pub enum SagaError {
     TimeoutError(String),
     /// Maximum retry attempts have been exhausted.
     MaxRetriesExceeded { step: String, attempts: u32 },
+    /// A choreography event carried no correlation ID.
+    MissingCorrelation(String),
+    /// The execution is not in a state that allows the operation.
+    InvalidState(String),
+    /// The dedup store failed.
+    StoreError(String),
 }
 
 impl std::fmt::Display for SagaError {
@@ -660,6 +2342,11 @@ impl std::fmt::Display for SagaError {
                     "Max retries exceeded for step '{step}' after {attempts} attempts"
                 )
             }
+            SagaError::MissingCorrelation(id) => {
+                write!(f, "Message has no saga correlation ID: {id}")
+            }
+            SagaError::InvalidState(msg) => write!(f, "Invalid saga state: {msg}"),
+            SagaError::StoreError(msg) => write!(f, "Saga dedup store error: {msg}"),
         }
     }
 }
@@ -683,6 +2370,9 @@ pub struct SagaStats {
     pub compensated: u64,
     /// Average execution duration in milliseconds.
     pub avg_duration_ms: f64,
+    /// Step attempts completed from the dedup store instead of run again.
+    #[serde(default)]
+    pub deduplicated_steps: u64,
 }
 
 /// Internal atomic metrics tracker for saga operations.
@@ -693,6 +2383,7 @@ struct SagaMetrics {
     compensated: AtomicU64,
     total_duration_ms: AtomicU64,
     completed_count: AtomicU64,
+    deduplicated_steps: AtomicU64,
 }
 
 impl Default for SagaMetrics {
@@ -704,6 +2395,7 @@ impl Default for SagaMetrics {
             compensated: AtomicU64::new(0),
             total_duration_ms: AtomicU64::new(0),
             completed_count: AtomicU64::new(0),
+            deduplicated_steps: AtomicU64::new(0),
         }
     }
 }
@@ -726,7 +2418,10 @@ impl SagaMetrics {
         self.compensated.fetch_add(1, Ordering::Relaxed);
     }
 
-    #[allow(dead_code)]
+    pub fn record_step_deduplicated(&self) {
+        self.deduplicated_steps.fetch_add(1, Ordering::Relaxed);
+    }
+
     pub fn record_duration(&self, duration_ms: u64) {
         self.total_duration_ms
             .fetch_add(duration_ms, Ordering::Relaxed);
@@ -746,6 +2441,7 @@ impl SagaMetrics {
             } else {
                 0.0
             },
+            deduplicated_steps: self.deduplicated_steps.load(Ordering::Relaxed),
         }
     }
 }
@@ -818,6 +2514,81 @@ mod tests {
         assert_eq!(saga.get_steps()[2].name, "process_payment");
     }
 
+    #[test]
+    fn test_state_machine_compensation_edges() {
+        let mut saga = SagaDefinition::new("order");
+        saga.add_step(SagaStepDef::new("reserve").with_compensation());
+        saga.add_step(SagaStepDef::new("notify"));
+        saga.add_step(SagaStepDef::new("charge").with_compensation());
+
+        let machine = saga.state_machine();
+        assert_eq!(machine.initial, "start");
+        let ids: Vec<_> = machine.states.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(
+            ids,
+            [
+                "start",
+                "s0",
+                "c0",
+                "s1",
+                "s2",
+                "c2",
+                "completed",
+                "compensated",
+                "failed"
+            ]
+        );
+
+        let edge = |from: &str, on: &str| {
+            machine
+                .transitions
+                .iter()
+                .find(|t| t.from == from && t.on == on)
+                .map(|t| t.to.clone())
+        };
+        assert_eq!(edge("s2", "completed").as_deref(), Some("completed"));
+        assert_eq!(edge("s0", "failed").as_deref(), Some("compensated"));
+        // Failures skip steps without a compensation.
+        assert_eq!(edge("s2", "failed").as_deref(), Some("c0"));
+        assert_eq!(edge("c2", "compensation_failed").as_deref(), Some("c0"));
+        assert_eq!(
+            edge("c0", "all_compensated").as_deref(),
+            Some("compensated")
+        );
+        assert_eq!(edge("c0", "compensation_failed").as_deref(), Some("failed"));
+
+        let json = serde_json::to_value(&machine).unwrap();
+        assert_eq!(json["states"][2]["kind"], "compensation");
+        assert_eq!(json["transitions"][0]["kind"], "forward");
+    }
+
+    #[test]
+    fn test_state_machine_empty_saga() {
+        let machine = SagaDefinition::new("noop").state_machine();
+        assert_eq!(machine.states.len(), 3);
+        assert_eq!(machine.transitions.len(), 1);
+        assert_eq!(machine.transitions[0].to, "completed");
+    }
+
+    #[test]
+    fn test_definition_dot_and_mermaid() {
+        let mut saga = SagaDefinition::new("order \"v2\"");
+        saga.add_step(SagaStepDef::new("reserve").with_compensation());
+        saga.add_step(SagaStepDef::new("charge"));
+
+        let dot = saga.to_dot();
+        assert!(dot.starts_with("digraph \"order \\\"v2\\\"\" {"));
+        assert!(dot.contains("    c0 [label=\"compensate reserve\", shape=box, style=dashed];"));
+        assert!(dot.contains("    s1 -> c0 [label=\"failed\", color=red];"));
+        assert!(dot.ends_with("}\n"));
+
+        let mermaid = saga.to_mermaid();
+        assert!(mermaid.starts_with("flowchart TD\n"));
+        assert!(mermaid.contains("    s0[\"reserve\"]\n"));
+        assert!(mermaid.contains("    c0 -.->|all_compensated| compensated\n"));
+        assert!(mermaid.contains("    s0 -->|completed| s1\n"));
+    }
+
     // ---------- SagaStep Tests ----------
 
     #[test]
@@ -895,6 +2666,10 @@ mod tests {
         let result = orchestrator.start_execution("NonExistent");
         assert!(result.is_err());
         assert!(matches!(result.unwrap_err(), SagaError::SagaNotFound(_)));
+        assert!(matches!(
+            orchestrator.definition("NonExistent"),
+            Err(SagaError::SagaNotFound(_))
+        ));
     }
 
     #[test]
@@ -1045,6 +2820,10 @@ mod tests {
         assert_eq!(format!("{}", StepStatus::Failed), "Failed");
         assert_eq!(format!("{}", StepStatus::Compensating), "Compensating");
         assert_eq!(format!("{}", StepStatus::Compensated), "Compensated");
+        assert_eq!(
+            format!("{}", StepStatus::CompensationFailed),
+            "CompensationFailed"
+        );
     }
 
     #[test]
@@ -1056,4 +2835,630 @@ mod tests {
         assert_eq!(format!("{}", SagaStatus::Compensating), "Compensating");
         assert_eq!(format!("{}", SagaStatus::Compensated), "Compensated");
     }
+
+    // ---------- Executor Tests ----------
+
+    fn two_step_orchestrator(config: SagaConfig) -> SagaOrchestrator {
+        let orchestrator = SagaOrchestrator::with_config(config.with_logging(false));
+        orchestrator.add_step("order", SagaStepDef::new("reserve").with_compensation());
+        orchestrator.add_step("order", SagaStepDef::new("charge").with_compensation());
+        orchestrator
+    }
+
+    #[test]
+    fn test_retry_delay_backoff() {
+        let config = SagaConfig::new().with_retry_delay(100).with_backoff(2.0);
+        assert_eq!(config.retry_delay(1), Duration::from_millis(100));
+        assert_eq!(config.retry_delay(3), Duration::from_millis(400));
+        let fixed = config.with_backoff(1.0);
+        assert_eq!(fixed.retry_delay(3), Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn test_run_threads_results_between_steps() {
+        let orchestrator = two_step_orchestrator(SagaConfig::new());
+        orchestrator
+            .register_step_handler(
+                "order",
+                "reserve",
+                SagaStepHandler::sync(|ctx| Ok(json!({"qty": ctx["input"]["qty"]}))),
+            )
+            .unwrap();
+        orchestrator
+            .register_step_handler(
+                "order",
+                "charge",
+                SagaStepHandler::sync(|ctx| {
+                    Ok(json!(
+                        ctx["results"]["reserve"]["qty"].as_i64().unwrap() * 10
+                    ))
+                }),
+            )
+            .unwrap();
+
+        let execution = orchestrator.run("order", json!({"qty": 3})).await.unwrap();
+        assert_eq!(execution.status, SagaStatus::Completed);
+        assert_eq!(execution.steps[1].result, Some(json!(30)));
+        assert!(execution.error.is_none());
+        assert_eq!(orchestrator.stats().completed, 1);
+    }
+
+    #[tokio::test]
+    async fn test_run_retries_before_succeeding() {
+        let orchestrator = two_step_orchestrator(SagaConfig::new().with_retry_delay(1));
+        let calls = Arc::new(AtomicU64::new(0));
+        let counter = calls.clone();
+        orchestrator
+            .register_step_handler(
+                "order",
+                "reserve",
+                SagaStepHandler::sync(move |_| match counter.fetch_add(1, Ordering::SeqCst) {
+                    0 | 1 => Err("busy".to_string()),
+                    _ => Ok(JsonValue::Null),
+                }),
+            )
+            .unwrap();
+        orchestrator
+            .register_step_handler(
+                "order",
+                "charge",
+                SagaStepHandler::sync(|_| Ok(JsonValue::Null)),
+            )
+            .unwrap();
+
+        let execution = orchestrator.run("order", JsonValue::Null).await.unwrap();
+        assert_eq!(execution.status, SagaStatus::Completed);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_run_compensates_after_retries_exhausted() {
+        let orchestrator =
+            two_step_orchestrator(SagaConfig::new().with_max_retries(2).with_retry_delay(1));
+        orchestrator
+            .register_step_handler(
+                "order",
+                "reserve",
+                SagaStepHandler::sync(|_| Ok(JsonValue::Null)),
+            )
+            .unwrap();
+        orchestrator
+            .register_step_handler(
+                "order",
+                "charge",
+                SagaStepHandler::sync(|_| Err("card declined".to_string())),
+            )
+            .unwrap();
+
+        let execution = orchestrator.run("order", JsonValue::Null).await.unwrap();
+        assert_eq!(execution.status, SagaStatus::Compensated);
+        assert_eq!(execution.steps[0].status, StepStatus::Compensated);
+        assert_eq!(execution.steps[1].error.as_deref(), Some("card declined"));
+        assert_eq!(
+            execution.error,
+            Some(SagaError::MaxRetriesExceeded {
+                step: "charge".to_string(),
+                attempts: 3,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_enforces_step_timeout() {
+        let orchestrator = SagaOrchestrator::with_config(
+            SagaConfig::new()
+                .with_max_retries(1)
+                .with_retry_delay(1)
+                .with_logging(false),
+        );
+        orchestrator.add_step("slow", SagaStepDef::new("wait").with_timeout(10));
+        orchestrator
+            .register_step_handler(
+                "slow",
+                "wait",
+                SagaStepHandler::new(|_| async {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    Ok(JsonValue::Null)
+                }),
+            )
+            .unwrap();
+
+        let execution = orchestrator.run("slow", JsonValue::Null).await.unwrap();
+        assert!(matches!(
+            execution.error,
+            Some(SagaError::MaxRetriesExceeded { attempts: 2, .. })
+        ));
+        assert_eq!(
+            execution.steps[0].error.as_deref(),
+            Some("step timed out after 10ms")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_enforces_saga_deadline() {
+        let orchestrator = SagaOrchestrator::with_config(
+            SagaConfig::new()
+                .with_timeout(20)
+                .with_retry_delay(1)
+                .with_logging(false),
+        );
+        orchestrator.add_step("slow", SagaStepDef::new("wait"));
+        orchestrator
+            .register_step_handler(
+                "slow",
+                "wait",
+                SagaStepHandler::new(|_| async {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    Ok(JsonValue::Null)
+                }),
+            )
+            .unwrap();
+
+        let execution = orchestrator.run("slow", JsonValue::Null).await.unwrap();
+        assert_eq!(execution.status, SagaStatus::Compensated);
+        assert!(matches!(execution.error, Some(SagaError::TimeoutError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_run_requires_handlers() {
+        let orchestrator = two_step_orchestrator(SagaConfig::new());
+        orchestrator
+            .register_step_handler(
+                "order",
+                "reserve",
+                SagaStepHandler::sync(|_| Ok(JsonValue::Null)),
+            )
+            .unwrap();
+        assert_eq!(
+            orchestrator
+                .run("order", JsonValue::Null)
+                .await
+                .unwrap_err(),
+            SagaError::StepNotFound("charge".to_string())
+        );
+        assert_eq!(orchestrator.execution_count(), 0);
+        assert!(orchestrator
+            .register_step_handler(
+                "order",
+                "ship",
+                SagaStepHandler::sync(|_| Ok(JsonValue::Null))
+            )
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_run_compensates_in_reverse_with_retries() {
+        let orchestrator = SagaOrchestrator::with_config(
+            SagaConfig::new()
+                .with_max_retries(0)
+                .with_compensation_retries(1, 1)
+                .with_logging(false),
+        );
+        for step in ["reserve", "charge", "ship"] {
+            orchestrator.add_step("order", SagaStepDef::new(step));
+        }
+        let undone = Arc::new(RwLock::new(Vec::new()));
+        for step in ["reserve", "charge"] {
+            orchestrator
+                .register_step_handler(
+                    "order",
+                    step,
+                    SagaStepHandler::sync(move |_| Ok(json!(step))),
+                )
+                .unwrap();
+            let undone = undone.clone();
+            let failures = Arc::new(AtomicU64::new(0));
+            orchestrator
+                .register_compensation(
+                    "order",
+                    step,
+                    SagaStepHandler::sync(move |ctx| {
+                        // "charge" fails its first compensation attempt.
+                        if ctx["step"] == "charge" && failures.fetch_add(1, Ordering::SeqCst) == 0 {
+                            return Err("gateway busy".to_string());
+                        }
+                        undone.write().push(ctx["result"].clone());
+                        Ok(JsonValue::Null)
+                    }),
+                )
+                .unwrap();
+        }
+        orchestrator
+            .register_step_handler(
+                "order",
+                "ship",
+                SagaStepHandler::sync(|_| Err("no courier".to_string())),
+            )
+            .unwrap();
+
+        let execution = orchestrator.run("order", json!({"id": 1})).await.unwrap();
+        assert_eq!(execution.status, SagaStatus::Compensated);
+        assert_eq!(*undone.read(), vec![json!("charge"), json!("reserve")]);
+        assert!(execution.compensation_errors.is_empty());
+        assert_eq!(execution.steps[0].status, StepStatus::Compensated);
+        assert_eq!(execution.steps[2].status, StepStatus::Failed);
+        assert_eq!(orchestrator.stats().compensated, 1);
+    }
+
+    #[tokio::test]
+    async fn test_run_records_compensation_failures() {
+        let orchestrator = two_step_orchestrator(
+            SagaConfig::new()
+                .with_max_retries(0)
+                .with_compensation_retries(1, 1),
+        );
+        orchestrator
+            .register_step_handler(
+                "order",
+                "reserve",
+                SagaStepHandler::sync(|_| Ok(JsonValue::Null)),
+            )
+            .unwrap();
+        orchestrator
+            .register_compensation(
+                "order",
+                "reserve",
+                SagaStepHandler::sync(|_| Err("stock service down".to_string())),
+            )
+            .unwrap();
+        orchestrator
+            .register_step_handler(
+                "order",
+                "charge",
+                SagaStepHandler::sync(|_| Err("card declined".to_string())),
+            )
+            .unwrap();
+
+        let execution = orchestrator.run("order", JsonValue::Null).await.unwrap();
+        assert_eq!(execution.status, SagaStatus::Failed);
+        assert_eq!(
+            execution.compensation_errors,
+            vec![SagaError::CompensationFailed(
+                "step 'reserve': stock service down".to_string()
+            )]
+        );
+        assert_eq!(execution.steps[0].status, StepStatus::CompensationFailed);
+        assert_eq!(orchestrator.stats().compensated, 0);
+    }
+
+    #[test]
+    fn test_register_compensation_marks_step() {
+        let orchestrator = two_step_orchestrator(SagaConfig::new());
+        let undo = SagaStepHandler::sync(|_| Ok(JsonValue::Null));
+        orchestrator
+            .register_compensation("order", "charge", undo.clone())
+            .unwrap();
+        assert!(orchestrator.sagas.read()["order"].steps[1].has_compensation);
+        assert_eq!(
+            orchestrator
+                .register_compensation("order", "ship", undo)
+                .unwrap_err(),
+            SagaError::StepNotFound("ship".to_string())
+        );
+        let config = SagaConfig::new().with_compensation_retries(2, 50);
+        assert_eq!(
+            config.compensation_retry_delay(2),
+            Duration::from_millis(100)
+        );
+    }
+
+    // ---------- Query & Operator Tests ----------
+
+    #[test]
+    fn test_query_executions_filters_and_pages() {
+        let orchestrator = two_step_orchestrator(SagaConfig::new());
+        orchestrator.add_step("refund", SagaStepDef::new("pay"));
+        let mut ids = Vec::new();
+        for _ in 0..5 {
+            ids.push(orchestrator.start_execution("order").unwrap());
+        }
+        orchestrator.start_execution("refund").unwrap();
+        orchestrator.fail_step(&ids[0], "reserve", "boom").unwrap();
+
+        let page =
+            orchestrator.query_executions(&ExecutionQuery::new().with_saga("order").page(1, 2));
+        assert_eq!(page.total, 5);
+        let page_ids: Vec<&str> = page.executions.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(page_ids, [ids[3].as_str(), ids[2].as_str()]);
+
+        let compensated = ExecutionQuery::new().with_status("compensated".parse().unwrap());
+        assert_eq!(orchestrator.query_executions(&compensated).total, 1);
+
+        let now = orchestrator.get_execution(&ids[0]).unwrap().started_at;
+        let future = ExecutionQuery::new().started_between(Some(now + 60), None);
+        assert_eq!(orchestrator.query_executions(&future).total, 0);
+        let past = ExecutionQuery::new().started_between(None, Some(now + 60));
+        assert_eq!(orchestrator.query_executions(&past).total, 6);
+
+        assert_eq!(ExecutionQuery::new().page(0, 10_000).limit(), MAX_PAGE_SIZE);
+        assert!("stuck".parse::<SagaStatus>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_resume_execution_runs_remaining_steps() {
+        let orchestrator = two_step_orchestrator(SagaConfig::new());
+        orchestrator
+            .register_step_handler(
+                "order",
+                "reserve",
+                SagaStepHandler::sync(|_| panic!("completed steps are not re-run")),
+            )
+            .unwrap();
+        orchestrator
+            .register_step_handler(
+                "order",
+                "charge",
+                SagaStepHandler::sync(|ctx| Ok(ctx["results"]["reserve"].clone())),
+            )
+            .unwrap();
+
+        let id = orchestrator
+            .start_execution_with_input("order", json!({"id": 7}))
+            .unwrap();
+        orchestrator
+            .complete_step(&id, "reserve", Some(json!("r-1")))
+            .unwrap();
+        let execution = orchestrator.resume_execution(&id).await.unwrap();
+        assert_eq!(execution.status, SagaStatus::Completed);
+        assert_eq!(execution.steps[1].result, Some(json!("r-1")));
+
+        assert!(matches!(
+            orchestrator.resume_execution(&id).await,
+            Err(SagaError::InvalidState(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_force_compensate_stuck_executions() {
+        let orchestrator = two_step_orchestrator(SagaConfig::new().with_compensation_retries(0, 1));
+        let healthy = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let flag = healthy.clone();
+        orchestrator
+            .register_compensation(
+                "order",
+                "reserve",
+                SagaStepHandler::sync(move |_| {
+                    if flag.load(Ordering::SeqCst) {
+                        Ok(JsonValue::Null)
+                    } else {
+                        Err("stock service down".to_string())
+                    }
+                }),
+            )
+            .unwrap();
+
+        // A running execution is failed at its unfinished step.
+        let id = orchestrator.start_execution("order").unwrap();
+        orchestrator.complete_step(&id, "reserve", None).unwrap();
+        let execution = orchestrator
+            .force_compensate(&id, "operator abort")
+            .await
+            .unwrap();
+        assert_eq!(execution.status, SagaStatus::Failed);
+        assert_eq!(execution.steps[1].error.as_deref(), Some("operator abort"));
+        assert_eq!(execution.steps[0].status, StepStatus::CompensationFailed);
+
+        // Failed compensations are retried.
+        healthy.store(true, Ordering::SeqCst);
+        let execution = orchestrator.force_compensate(&id, "retry").await.unwrap();
+        assert_eq!(execution.status, SagaStatus::Compensated);
+        assert_eq!(execution.steps[0].status, StepStatus::Compensated);
+        assert!(execution.compensation_errors.is_empty());
+
+        assert!(matches!(
+            orchestrator.force_compensate(&id, "again").await,
+            Err(SagaError::InvalidState(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_run_with_key_deduplicates_completed_steps() {
+        let store = Arc::new(InMemorySagaDedupStore::new());
+        let orchestrator = two_step_orchestrator(SagaConfig::new().with_max_retries(0))
+            .with_dedup_store(store.clone(), Duration::from_secs(60));
+        let reserved = Arc::new(AtomicU64::new(0));
+        let counter = reserved.clone();
+        orchestrator
+            .register_step_handler(
+                "order",
+                "reserve",
+                SagaStepHandler::sync(move |ctx| {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    Ok(ctx["idempotency_key"].clone())
+                }),
+            )
+            .unwrap();
+        let healthy = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let flag = healthy.clone();
+        orchestrator
+            .register_step_handler(
+                "order",
+                "charge",
+                SagaStepHandler::sync(move |_| {
+                    if flag.load(Ordering::SeqCst) {
+                        Ok(json!("c-1"))
+                    } else {
+                        Err("card declined".to_string())
+                    }
+                }),
+            )
+            .unwrap();
+
+        let execution = orchestrator
+            .run_with_key("order", json!({"id": 7}), Some("order-7"))
+            .await
+            .unwrap();
+        assert_eq!(execution.status, SagaStatus::Compensated);
+        assert_eq!(
+            execution.steps[0].result,
+            Some(json!("order:order-7:reserve"))
+        );
+        assert_eq!(store.len(), 1);
+
+        // Run again with the same key: the reserve step is not repeated.
+        healthy.store(true, Ordering::SeqCst);
+        let execution = orchestrator
+            .run_with_key("order", json!({"id": 7}), Some("order-7"))
+            .await
+            .unwrap();
+        assert_eq!(execution.status, SagaStatus::Completed);
+        assert_eq!(
+            execution.steps[0].result,
+            Some(json!("order:order-7:reserve"))
+        );
+        assert_eq!(reserved.load(Ordering::SeqCst), 1);
+        assert_eq!(orchestrator.stats().deduplicated_steps, 1);
+
+        // Executions without a key get their own.
+        orchestrator.run("order", json!({"id": 8})).await.unwrap();
+        assert_eq!(reserved.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_redis_dedup_store() {
+        use crate::middleware::redis::{MockRedisClient, RedisConfig};
+
+        let client = Arc::new(MockRedisClient::new(RedisConfig::default()));
+        let store = RedisSagaDedupStore::new(client.clone());
+        assert_eq!(store.get("order:k:reserve").unwrap(), None);
+        store
+            .put(
+                "order:k:reserve",
+                &json!({"id": "r-1"}),
+                Duration::from_secs(60),
+            )
+            .unwrap();
+        assert_eq!(
+            store.get("order:k:reserve").unwrap(),
+            Some(json!({"id": "r-1"}))
+        );
+        assert!(client.get("saga:dedup:order:k:reserve").unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_timer_steps() {
+        let orchestrator = SagaOrchestrator::with_config(SagaConfig::new().with_logging(false));
+        orchestrator.add_step("reminder", SagaStepDef::delay("cool_off", 20));
+        orchestrator.add_step("reminder", SagaStepDef::until("due", 1_000));
+        orchestrator.add_step("reminder", SagaStepDef::new("send"));
+        orchestrator
+            .register_step_handler(
+                "reminder",
+                "send",
+                SagaStepHandler::sync(|ctx| Ok(ctx["results"]["due"]["wake_at"].clone())),
+            )
+            .unwrap();
+
+        let started = Instant::now();
+        let execution = orchestrator.run("reminder", JsonValue::Null).await.unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(20));
+        assert_eq!(execution.status, SagaStatus::Completed);
+        assert!(execution.steps[0].wake_at.is_some());
+        assert_eq!(execution.steps[2].result, Some(json!(1_000)));
+
+        // Definitions without a kind are actions.
+        let step: SagaStepDef = serde_json::from_value(
+            json!({"name": "a", "description": null, "has_compensation": false, "timeout_ms": null}),
+        )
+        .unwrap();
+        assert_eq!(step.kind, StepKind::Action);
+        assert_eq!(
+            serde_json::to_value(SagaStepDef::delay("d", 5)).unwrap()["kind"],
+            json!({"Delay": {"delay_ms": 5}})
+        );
+    }
+
+    fn approval_orchestrator(notified: Arc<Mutex<Vec<String>>>) -> Arc<SagaOrchestrator> {
+        let orchestrator = SagaOrchestrator::with_config(SagaConfig::new().with_logging(false));
+        orchestrator.add_step("expense", SagaStepDef::signal("approve"));
+        orchestrator.add_step("expense", SagaStepDef::new("pay"));
+        orchestrator
+            .register_step_handler(
+                "expense",
+                "approve",
+                SagaStepHandler::sync(move |ctx| {
+                    let token = ctx["signal_token"].as_str().unwrap().to_string();
+                    notified.lock().push(token);
+                    Ok(JsonValue::Null)
+                }),
+            )
+            .unwrap();
+        orchestrator
+            .register_step_handler(
+                "expense",
+                "pay",
+                SagaStepHandler::sync(|ctx| Ok(ctx["results"]["approve"]["by"].clone())),
+            )
+            .unwrap();
+        Arc::new(orchestrator)
+    }
+
+    async fn waiting_token(orchestrator: &SagaOrchestrator) -> String {
+        for _ in 0..100 {
+            if let Some(token) = orchestrator.waiting_signals().pop() {
+                return token;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        panic!("no signal step is waiting");
+    }
+
+    #[tokio::test]
+    async fn test_signal_step_waits_for_token() {
+        let notified = Arc::new(Mutex::new(Vec::new()));
+        let orchestrator = approval_orchestrator(notified.clone());
+        let running = orchestrator.clone();
+        let task = tokio::spawn(async move { running.run("expense", JsonValue::Null).await });
+
+        let token = waiting_token(&orchestrator).await;
+        assert_eq!(*notified.lock(), vec![token.clone()]);
+        let waiting = orchestrator.list_executions().pop().unwrap();
+        assert_eq!(waiting.steps[0].status, StepStatus::Waiting);
+        assert_eq!(
+            waiting.steps[0].signal_token.as_deref(),
+            Some(token.as_str())
+        );
+
+        assert!(orchestrator.signal("unknown", JsonValue::Null).is_err());
+        orchestrator.signal(&token, json!({"by": "ann"})).unwrap();
+        let execution = task.await.unwrap().unwrap();
+        assert_eq!(execution.status, SagaStatus::Completed);
+        assert_eq!(execution.steps[1].result, Some(json!("ann")));
+        assert!(orchestrator.waiting_signals().is_empty());
+
+        // A signal that never comes times the step out.
+        orchestrator.add_step("expense", SagaStepDef::signal("approve").with_timeout(20));
+        let execution = orchestrator.run("expense", JsonValue::Null).await.unwrap();
+        assert_eq!(execution.status, SagaStatus::Compensated);
+        assert!(matches!(execution.error, Some(SagaError::TimeoutError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_restore_and_resume_waiting_execution() {
+        let notified = Arc::new(Mutex::new(Vec::new()));
+        let before = approval_orchestrator(notified.clone());
+        let running = before.clone();
+        tokio::spawn(async move { running.run("expense", JsonValue::Null).await });
+        let token = waiting_token(&before).await;
+        let saved = serde_json::to_value(before.list_executions().pop().unwrap()).unwrap();
+
+        // After a restart the execution is restored and resumed: the step
+        // keeps its token and is not notified again.
+        let after = approval_orchestrator(notified.clone());
+        let restored: SagaExecution = serde_json::from_value(saved).unwrap();
+        let id = restored.id.clone();
+        after.restore_execution(restored.clone()).unwrap();
+        assert!(after.restore_execution(restored).is_err());
+        assert_ne!(after.start_execution("expense").unwrap(), id);
+
+        let resuming = after.clone();
+        let resume_id = id.clone();
+        let task = tokio::spawn(async move { resuming.resume_execution(&resume_id).await });
+        assert_eq!(waiting_token(&after).await, token);
+        after.signal(&token, json!({"by": "bob"})).unwrap();
+        let execution = task.await.unwrap().unwrap();
+        assert_eq!(execution.status, SagaStatus::Completed);
+        assert_eq!(execution.steps[1].result, Some(json!("bob")));
+        assert_eq!(notified.lock().len(), 1);
+    }
 }
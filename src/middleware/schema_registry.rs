@@ -0,0 +1,841 @@
+//! Message serialization and Confluent Schema Registry support.
+//!
+//! Provides:
+//! - The `MessageSerializer` trait with JSON, Avro and Protobuf serializers
+//! - The Confluent wire format (magic byte, big-endian schema ID, and for
+//!   Protobuf the message-index list) via `FramedSerializer`
+//! - An async `SchemaRegistryClient` for subject lookup, registration and
+//!   compatibility checks, with schemas cached by ID
+//!
+//! Serializers plug into any broker adapter through
+//! `MessageProducer::send_value` and `Message::decode`.
+//!
+//! # Example
+//! ```rust,ignore
+//! let registry = SchemaRegistryClient::new(SchemaRegistryConfig::new("http://localhost:8081"))?;
+//! let subject = SchemaRegistryClient::subject_name("orders", false); // "orders-value"
+//! let serializer = registry.avro_serializer(&subject).await?;
+//!
+//! producer.send_value("orders", Some("order-42"), &json!({"id": 42}), &serializer)?;
+//!
+//! for message in consumer.poll()? {
+//!     let order = registry.decode(&message.value).await?;
+//! }
+//! ```
+
+use parking_lot::RwLock;
+use serde_json::{json, Value as JsonValue};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+
+use super::avro::{AvroError, AvroSchema};
+use super::messaging::MessagingError;
+use super::protobuf::{ProtobufError, SharedDescriptorPool};
+
+/// First byte of every Confluent-framed payload.
+pub const MAGIC_BYTE: u8 = 0;
+
+/// Content type the Schema Registry REST API speaks.
+const REGISTRY_CONTENT_TYPE: &str = "application/vnd.schemaregistry.v1+json";
+
+/// Registry error code for an unknown subject.
+const SUBJECT_NOT_FOUND: i64 = 40401;
+
+// ============================================================================
+// Errors
+// ============================================================================
+
+/// Error raised while serializing messages or talking to the registry.
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum SchemaError {
+    #[error(transparent)]
+    Avro(#[from] AvroError),
+
+    #[error(transparent)]
+    Protobuf(#[from] ProtobufError),
+
+    #[error("Invalid JSON payload: {0}")]
+    Json(String),
+
+    #[error("Invalid framed payload: {0}")]
+    Framing(String),
+
+    #[error("Payload was written with schema {0}, which this serializer cannot read")]
+    UnknownSchemaId(u32),
+
+    #[error("Unsupported schema type: {0}")]
+    Unsupported(String),
+
+    #[error("Schema registry error {code}: {message}")]
+    Registry { code: i64, message: String },
+
+    #[error("Schema registry request failed: {0}")]
+    Http(String),
+}
+
+impl From<SchemaError> for MessagingError {
+    fn from(err: SchemaError) -> Self {
+        match err {
+            SchemaError::Http(_) => MessagingError::Connection,
+            SchemaError::Registry { code, .. } if code == SUBJECT_NOT_FOUND => {
+                MessagingError::QueueNotFound
+            }
+            SchemaError::Registry { code: 401, .. } | SchemaError::Registry { code: 403, .. } => {
+                MessagingError::Authentication
+            }
+            SchemaError::Registry { .. } | SchemaError::Unsupported(_) => {
+                MessagingError::Unknown(err.to_string())
+            }
+            _ => MessagingError::Serialization,
+        }
+    }
+}
+
+// ============================================================================
+// Serializers
+// ============================================================================
+
+/// Schema type of a serializer or registered schema.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaFormat {
+    Json,
+    Avro,
+    Protobuf,
+}
+
+impl SchemaFormat {
+    /// Name used in the registry's `schemaType` field.
+    pub fn registry_name(&self) -> &'static str {
+        match self {
+            SchemaFormat::Json => "JSON",
+            SchemaFormat::Avro => "AVRO",
+            SchemaFormat::Protobuf => "PROTOBUF",
+        }
+    }
+
+    /// Parse a registry `schemaType`; the registry omits it for Avro.
+    pub fn from_registry_name(name: Option<&str>) -> Option<Self> {
+        match name {
+            None | Some("AVRO") => Some(SchemaFormat::Avro),
+            Some("JSON") => Some(SchemaFormat::Json),
+            Some("PROTOBUF") => Some(SchemaFormat::Protobuf),
+            Some(_) => None,
+        }
+    }
+}
+
+/// Converts message values to and from payload bytes.
+pub trait MessageSerializer: Send + Sync {
+    /// Schema type this serializer writes.
+    fn format(&self) -> SchemaFormat;
+
+    /// Serialize a value into payload bytes.
+    fn serialize(&self, value: &JsonValue) -> Result<Vec<u8>, SchemaError>;
+
+    /// Deserialize payload bytes into a value.
+    fn deserialize(&self, payload: &[u8]) -> Result<JsonValue, SchemaError>;
+}
+
+/// Plain JSON payloads.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonSerializer;
+
+impl MessageSerializer for JsonSerializer {
+    fn format(&self) -> SchemaFormat {
+        SchemaFormat::Json
+    }
+
+    fn serialize(&self, value: &JsonValue) -> Result<Vec<u8>, SchemaError> {
+        serde_json::to_vec(value).map_err(|e| SchemaError::Json(e.to_string()))
+    }
+
+    fn deserialize(&self, payload: &[u8]) -> Result<JsonValue, SchemaError> {
+        crate::json::parse_json_bytes(&mut payload.to_vec()).map_err(SchemaError::Json)
+    }
+}
+
+/// Avro binary payloads for a fixed schema.
+#[derive(Debug, Clone)]
+pub struct AvroSerializer {
+    schema: Arc<AvroSchema>,
+}
+
+impl AvroSerializer {
+    pub fn new(schema: AvroSchema) -> Self {
+        Self {
+            schema: Arc::new(schema),
+        }
+    }
+
+    /// Parse the schema from its JSON definition.
+    pub fn parse(definition: &str) -> Result<Self, SchemaError> {
+        Ok(Self::new(AvroSchema::parse(definition)?))
+    }
+
+    pub fn schema(&self) -> &AvroSchema {
+        &self.schema
+    }
+}
+
+impl MessageSerializer for AvroSerializer {
+    fn format(&self) -> SchemaFormat {
+        SchemaFormat::Avro
+    }
+
+    fn serialize(&self, value: &JsonValue) -> Result<Vec<u8>, SchemaError> {
+        Ok(self.schema.encode(value)?)
+    }
+
+    fn deserialize(&self, payload: &[u8]) -> Result<JsonValue, SchemaError> {
+        Ok(self.schema.decode(payload)?)
+    }
+}
+
+/// Protobuf payloads for a message type registered in a descriptor pool.
+#[derive(Clone)]
+pub struct ProtobufSerializer {
+    pool: SharedDescriptorPool,
+    message_type: String,
+}
+
+impl ProtobufSerializer {
+    pub fn new(pool: SharedDescriptorPool, message_type: &str) -> Self {
+        Self {
+            pool,
+            message_type: message_type.to_string(),
+        }
+    }
+}
+
+impl MessageSerializer for ProtobufSerializer {
+    fn format(&self) -> SchemaFormat {
+        SchemaFormat::Protobuf
+    }
+
+    fn serialize(&self, value: &JsonValue) -> Result<Vec<u8>, SchemaError> {
+        Ok(self.pool.read().encode(&self.message_type, value)?)
+    }
+
+    fn deserialize(&self, payload: &[u8]) -> Result<JsonValue, SchemaError> {
+        Ok(self.pool.read().decode(&self.message_type, payload)?)
+    }
+}
+
+// ============================================================================
+// Wire Format
+// ============================================================================
+
+/// Prefix a payload with the magic byte and schema ID. Protobuf payloads also
+/// get a message-index list; this always names the first message in the
+/// schema, written as the single byte `0`.
+pub fn frame(schema_id: u32, format: SchemaFormat, payload: &[u8]) -> Vec<u8> {
+    let mut framed = Vec::with_capacity(payload.len() + 6);
+    framed.push(MAGIC_BYTE);
+    framed.extend_from_slice(&schema_id.to_be_bytes());
+    if format == SchemaFormat::Protobuf {
+        framed.push(0);
+    }
+    framed.extend_from_slice(payload);
+    framed
+}
+
+fn read_varint(input: &[u8], pos: &mut usize) -> Result<u64, SchemaError> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *input
+            .get(*pos)
+            .ok_or_else(|| SchemaError::Framing("truncated message indexes".to_string()))?;
+        *pos += 1;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(SchemaError::Framing("varint is too long".to_string()))
+}
+
+/// Split a framed payload into its schema ID and the serialized value,
+/// skipping the Protobuf message-index list.
+pub fn unframe(data: &[u8], format: SchemaFormat) -> Result<(u32, &[u8]), SchemaError> {
+    if data.len() < 5 {
+        return Err(SchemaError::Framing(
+            "payload is shorter than the header".to_string(),
+        ));
+    }
+    if data[0] != MAGIC_BYTE {
+        return Err(SchemaError::Framing(format!(
+            "unknown magic byte {}",
+            data[0]
+        )));
+    }
+    let schema_id = u32::from_be_bytes([data[1], data[2], data[3], data[4]]);
+    let mut pos = 5;
+    if format == SchemaFormat::Protobuf {
+        // Zigzag-encoded count, then that many indexes; 0 means [0]
+        let count = read_varint(data, &mut pos)? >> 1;
+        for _ in 0..count {
+            read_varint(data, &mut pos)?;
+        }
+    }
+    Ok((schema_id, &data[pos..]))
+}
+
+/// Wraps a serializer in the Confluent wire format for a registered schema.
+#[derive(Clone)]
+pub struct FramedSerializer {
+    schema_id: u32,
+    inner: Arc<dyn MessageSerializer>,
+}
+
+impl FramedSerializer {
+    pub fn new(schema_id: u32, inner: Arc<dyn MessageSerializer>) -> Self {
+        Self { schema_id, inner }
+    }
+
+    /// Registry ID written into every payload.
+    pub fn schema_id(&self) -> u32 {
+        self.schema_id
+    }
+}
+
+impl MessageSerializer for FramedSerializer {
+    fn format(&self) -> SchemaFormat {
+        self.inner.format()
+    }
+
+    fn serialize(&self, value: &JsonValue) -> Result<Vec<u8>, SchemaError> {
+        let payload = self.inner.serialize(value)?;
+        Ok(frame(self.schema_id, self.format(), &payload))
+    }
+
+    /// Decode with the wrapped serializer. Avro needs the writer's schema,
+    /// so Avro payloads written with a different schema ID are rejected; use
+    /// `SchemaRegistryClient::decode` for those.
+    fn deserialize(&self, payload: &[u8]) -> Result<JsonValue, SchemaError> {
+        let (schema_id, body) = unframe(payload, self.format())?;
+        if schema_id != self.schema_id && self.format() == SchemaFormat::Avro {
+            return Err(SchemaError::UnknownSchemaId(schema_id));
+        }
+        self.inner.deserialize(body)
+    }
+}
+
+// ============================================================================
+// Registry Client
+// ============================================================================
+
+/// Schema Registry connection settings.
+#[derive(Clone, Debug)]
+pub struct SchemaRegistryConfig {
+    /// Base URL (e.g., "http://localhost:8081").
+    pub url: String,
+    /// Basic auth credentials (username, password).
+    pub basic_auth: Option<(String, String)>,
+    /// Request timeout in seconds.
+    pub timeout_secs: u64,
+}
+
+impl SchemaRegistryConfig {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_string(),
+            basic_auth: None,
+            timeout_secs: 10,
+        }
+    }
+
+    /// Authenticate with HTTP basic auth (API key and secret on Confluent Cloud).
+    pub fn with_basic_auth(mut self, username: &str, password: &str) -> Self {
+        self.basic_auth = Some((username.to_string(), password.to_string()));
+        self
+    }
+}
+
+/// A schema stored in the registry.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegisteredSchema {
+    pub id: u32,
+    pub subject: Option<String>,
+    pub version: Option<i64>,
+    pub schema: String,
+    pub format: SchemaFormat,
+}
+
+/// Async client for the Confluent Schema Registry REST API.
+///
+/// Schemas are immutable once registered, so lookups by ID (and the parsed
+/// Avro schemas used by `decode`) are cached for the client's lifetime.
+pub struct SchemaRegistryClient {
+    config: SchemaRegistryConfig,
+    http: reqwest::Client,
+    schemas: RwLock<HashMap<u32, RegisteredSchema>>,
+    avro: RwLock<HashMap<u32, Arc<AvroSchema>>>,
+}
+
+impl SchemaRegistryClient {
+    pub fn new(config: SchemaRegistryConfig) -> Result<Self, SchemaError> {
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .build()
+            .map_err(|e| SchemaError::Http(e.to_string()))?;
+        Ok(Self {
+            config,
+            http,
+            schemas: RwLock::new(HashMap::new()),
+            avro: RwLock::new(HashMap::new()),
+        })
+    }
+
+    /// Subject for a topic's keys or values under the default
+    /// `TopicNameStrategy` (`{topic}-key` / `{topic}-value`).
+    pub fn subject_name(topic: &str, is_key: bool) -> String {
+        format!("{topic}-{}", if is_key { "key" } else { "value" })
+    }
+
+    async fn request(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        body: Option<JsonValue>,
+    ) -> Result<JsonValue, SchemaError> {
+        let mut request = self
+            .http
+            .request(method, format!("{}{path}", self.config.url))
+            .header("Accept", REGISTRY_CONTENT_TYPE);
+        if let Some((username, password)) = &self.config.basic_auth {
+            request = request.basic_auth(username, Some(password));
+        }
+        if let Some(body) = body {
+            request = request
+                .header("Content-Type", REGISTRY_CONTENT_TYPE)
+                .body(body.to_string());
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| SchemaError::Http(e.to_string()))?;
+        let status = response.status();
+        let bytes = response
+            .bytes()
+            .await
+            .map_err(|e| SchemaError::Http(e.to_string()))?;
+        let value: JsonValue = serde_json::from_slice(&bytes).unwrap_or(JsonValue::Null);
+        if !status.is_success() {
+            return Err(SchemaError::Registry {
+                code: value["error_code"]
+                    .as_i64()
+                    .unwrap_or(i64::from(status.as_u16())),
+                message: value["message"]
+                    .as_str()
+                    .map(str::to_string)
+                    .unwrap_or_else(|| status.to_string()),
+            });
+        }
+        Ok(value)
+    }
+
+    fn parse_schema(value: &JsonValue, id: Option<u32>) -> Result<RegisteredSchema, SchemaError> {
+        let malformed = || SchemaError::Http(format!("unexpected registry response: {value}"));
+        let id = match id {
+            Some(id) => id,
+            None => value["id"]
+                .as_u64()
+                .and_then(|id| u32::try_from(id).ok())
+                .ok_or_else(malformed)?,
+        };
+        let schema_type = value["schemaType"].as_str();
+        Ok(RegisteredSchema {
+            id,
+            subject: value["subject"].as_str().map(str::to_string),
+            version: value["version"].as_i64(),
+            schema: value["schema"].as_str().ok_or_else(malformed)?.to_string(),
+            format: SchemaFormat::from_registry_name(schema_type)
+                .ok_or_else(|| SchemaError::Unsupported(schema_type.unwrap_or("").to_string()))?,
+        })
+    }
+
+    fn schema_body(schema: &str, format: SchemaFormat) -> JsonValue {
+        let mut body = json!({ "schema": schema });
+        if format != SchemaFormat::Avro {
+            body["schemaType"] = json!(format.registry_name());
+        }
+        body
+    }
+
+    /// Register `schema` under `subject`, returning its ID. Registering a
+    /// schema that already exists returns the existing ID.
+    pub async fn register(
+        &self,
+        subject: &str,
+        schema: &str,
+        format: SchemaFormat,
+    ) -> Result<u32, SchemaError> {
+        let path = format!("/subjects/{}/versions", urlencoding::encode(subject));
+        let response = self
+            .request(
+                reqwest::Method::POST,
+                &path,
+                Some(Self::schema_body(schema, format)),
+            )
+            .await?;
+        response["id"]
+            .as_u64()
+            .and_then(|id| u32::try_from(id).ok())
+            .ok_or_else(|| SchemaError::Http(format!("unexpected registry response: {response}")))
+    }
+
+    /// Latest version registered under `subject`.
+    pub async fn latest(&self, subject: &str) -> Result<RegisteredSchema, SchemaError> {
+        let path = format!("/subjects/{}/versions/latest", urlencoding::encode(subject));
+        let response = self.request(reqwest::Method::GET, &path, None).await?;
+        let schema = Self::parse_schema(&response, None)?;
+        self.schemas.write().insert(schema.id, schema.clone());
+        Ok(schema)
+    }
+
+    /// Schema with the given ID.
+    pub async fn schema_by_id(&self, id: u32) -> Result<RegisteredSchema, SchemaError> {
+        if let Some(schema) = self.schemas.read().get(&id) {
+            return Ok(schema.clone());
+        }
+        let response = self
+            .request(reqwest::Method::GET, &format!("/schemas/ids/{id}"), None)
+            .await?;
+        let schema = Self::parse_schema(&response, Some(id))?;
+        self.schemas.write().insert(id, schema.clone());
+        Ok(schema)
+    }
+
+    /// Whether `schema` is compatible with the latest version of `subject`
+    /// under the subject's compatibility level. A subject with no versions
+    /// accepts any schema.
+    pub async fn check_compatibility(
+        &self,
+        subject: &str,
+        schema: &str,
+        format: SchemaFormat,
+    ) -> Result<bool, SchemaError> {
+        let path = format!(
+            "/compatibility/subjects/{}/versions/latest",
+            urlencoding::encode(subject)
+        );
+        match self
+            .request(
+                reqwest::Method::POST,
+                &path,
+                Some(Self::schema_body(schema, format)),
+            )
+            .await
+        {
+            Ok(response) => Ok(response["is_compatible"].as_bool().unwrap_or(false)),
+            Err(SchemaError::Registry { code, .. }) if code == SUBJECT_NOT_FOUND => Ok(true),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Set the compatibility level of `subject` (e.g., "BACKWARD", "FULL", "NONE").
+    pub async fn set_compatibility(&self, subject: &str, level: &str) -> Result<(), SchemaError> {
+        let path = format!("/config/{}", urlencoding::encode(subject));
+        self.request(
+            reqwest::Method::PUT,
+            &path,
+            Some(json!({ "compatibility": level })),
+        )
+        .await
+        .map(|_| ())
+    }
+
+    /// Framed serializer for the latest Avro schema of `subject`.
+    pub async fn avro_serializer(&self, subject: &str) -> Result<FramedSerializer, SchemaError> {
+        let latest = self.latest(subject).await?;
+        if latest.format != SchemaFormat::Avro {
+            return Err(SchemaError::Unsupported(format!(
+                "subject '{subject}' holds a {} schema",
+                latest.format.registry_name()
+            )));
+        }
+        let schema = self.avro_schema(&latest)?;
+        Ok(FramedSerializer::new(
+            latest.id,
+            Arc::new(AvroSerializer { schema }),
+        ))
+    }
+
+    /// Framed serializer writing the latest schema ID of `subject` with
+    /// `inner` (e.g., a `JsonSerializer` or `ProtobufSerializer`).
+    pub async fn serializer(
+        &self,
+        subject: &str,
+        inner: Arc<dyn MessageSerializer>,
+    ) -> Result<FramedSerializer, SchemaError> {
+        let latest = self.latest(subject).await?;
+        if latest.format != inner.format() {
+            return Err(SchemaError::Unsupported(format!(
+                "subject '{subject}' holds a {} schema",
+                latest.format.registry_name()
+            )));
+        }
+        Ok(FramedSerializer::new(latest.id, inner))
+    }
+
+    /// Decode a framed Avro or JSON payload with the schema it was written
+    /// with. Protobuf payloads need a `FramedSerializer` around a
+    /// `ProtobufSerializer` for the message type.
+    pub async fn decode(&self, payload: &[u8]) -> Result<JsonValue, SchemaError> {
+        let (schema_id, _) = unframe(payload, SchemaFormat::Avro)?;
+        let schema = self.schema_by_id(schema_id).await?;
+        match schema.format {
+            SchemaFormat::Avro => {
+                let (_, body) = unframe(payload, SchemaFormat::Avro)?;
+                Ok(self.avro_schema(&schema)?.decode(body)?)
+            }
+            SchemaFormat::Json => {
+                let (_, body) = unframe(payload, SchemaFormat::Json)?;
+                JsonSerializer.deserialize(body)
+            }
+            SchemaFormat::Protobuf => Err(SchemaError::Unsupported(
+                "decoding PROTOBUF payloads without a message type".to_string(),
+            )),
+        }
+    }
+
+    fn avro_schema(&self, schema: &RegisteredSchema) -> Result<Arc<AvroSchema>, SchemaError> {
+        if let Some(parsed) = self.avro.read().get(&schema.id) {
+            return Ok(parsed.clone());
+        }
+        let parsed = Arc::new(AvroSchema::parse(&schema.schema)?);
+        self.avro.write().insert(schema.id, parsed.clone());
+        Ok(parsed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::messaging::{Message, MessageProducer, MockProducer};
+    use crate::middleware::protobuf::{
+        DescriptorPool, FieldDescriptor, FieldType, MessageDescriptor,
+    };
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    const ORDER_SCHEMA: &str = r#"{"type": "record", "name": "Order", "fields": [{"name": "id", "type": "long"}, {"name": "item", "type": "string"}]}"#;
+
+    #[test]
+    fn test_frame_roundtrip() {
+        let framed = frame(7, SchemaFormat::Avro, b"abc");
+        assert_eq!(framed, vec![0, 0, 0, 0, 7, b'a', b'b', b'c']);
+        assert_eq!(
+            unframe(&framed, SchemaFormat::Avro).unwrap(),
+            (7, &b"abc"[..])
+        );
+
+        let framed = frame(1, SchemaFormat::Protobuf, b"x");
+        assert_eq!(framed, vec![0, 0, 0, 0, 1, 0, b'x']);
+        assert_eq!(
+            unframe(&framed, SchemaFormat::Protobuf).unwrap(),
+            (1, &b"x"[..])
+        );
+        // Explicit index list [1, 0]: count 2 zigzags to 4
+        let explicit = [0, 0, 0, 0, 1, 4, 1, 0, b'x'];
+        assert_eq!(unframe(&explicit, SchemaFormat::Protobuf).unwrap().1, b"x");
+
+        assert!(matches!(
+            unframe(&[1, 0, 0, 0, 7], SchemaFormat::Avro),
+            Err(SchemaError::Framing(_))
+        ));
+        assert!(unframe(&[0, 0], SchemaFormat::Json).is_err());
+    }
+
+    #[test]
+    fn test_serializers() {
+        let value = json!({"id": 42, "item": "book"});
+
+        let json_bytes = JsonSerializer.serialize(&value).unwrap();
+        assert_eq!(JsonSerializer.deserialize(&json_bytes).unwrap(), value);
+
+        let avro = AvroSerializer::parse(ORDER_SCHEMA).unwrap();
+        let avro_bytes = avro.serialize(&value).unwrap();
+        assert_eq!(avro_bytes, vec![84, 8, b'b', b'o', b'o', b'k']);
+        assert_eq!(avro.deserialize(&avro_bytes).unwrap(), value);
+
+        let mut pool = DescriptorPool::new();
+        pool.add_message(
+            MessageDescriptor::new("shop.Order")
+                .field(FieldDescriptor::new("id", 1, FieldType::parse("int64")))
+                .field(FieldDescriptor::new("item", 2, FieldType::parse("string"))),
+        )
+        .unwrap();
+        let protobuf = ProtobufSerializer::new(Arc::new(RwLock::new(pool)), "shop.Order");
+        let proto_bytes = protobuf.serialize(&value).unwrap();
+        assert_eq!(protobuf.deserialize(&proto_bytes).unwrap(), value);
+    }
+
+    #[test]
+    fn test_framed_serializer() {
+        let avro = Arc::new(AvroSerializer::parse(ORDER_SCHEMA).unwrap());
+        let serializer = FramedSerializer::new(3, avro.clone());
+        let value = json!({"id": 1, "item": "pen"});
+        let payload = serializer.serialize(&value).unwrap();
+        assert_eq!(&payload[..5], &[0, 0, 0, 0, 3]);
+        assert_eq!(serializer.deserialize(&payload).unwrap(), value);
+
+        let other = FramedSerializer::new(4, avro);
+        assert_eq!(
+            other.deserialize(&payload),
+            Err(SchemaError::UnknownSchemaId(3))
+        );
+    }
+
+    #[test]
+    fn test_send_value_and_decode() {
+        let producer = MockProducer::new();
+        let serializer = FramedSerializer::new(9, Arc::new(JsonSerializer));
+        producer
+            .send_value("orders", Some("k"), &json!({"id": 5}), &serializer)
+            .unwrap();
+        let sent: Message = producer.sent_messages().remove(0);
+        assert_eq!(sent.value[0], MAGIC_BYTE);
+        assert_eq!(sent.decode(&serializer).unwrap(), json!({"id": 5}));
+        assert!(matches!(
+            sent.decode(&AvroSerializer::parse(ORDER_SCHEMA).unwrap()),
+            Err(MessagingError::Serialization)
+        ));
+    }
+
+    #[test]
+    fn test_subject_name_and_error_mapping() {
+        assert_eq!(
+            SchemaRegistryClient::subject_name("orders", false),
+            "orders-value"
+        );
+        assert_eq!(
+            SchemaRegistryClient::subject_name("orders", true),
+            "orders-key"
+        );
+        assert!(matches!(
+            MessagingError::from(SchemaError::Http("refused".to_string())),
+            MessagingError::Connection
+        ));
+        assert!(matches!(
+            MessagingError::from(SchemaError::Registry {
+                code: SUBJECT_NOT_FOUND,
+                message: "Subject not found".to_string(),
+            }),
+            MessagingError::QueueNotFound
+        ));
+    }
+
+    /// Serve canned registry responses, keyed by "METHOD /path".
+    async fn mock_registry(routes: Vec<(&'static str, u16, &'static str)>) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let routes = routes.clone();
+                tokio::spawn(async move {
+                    let mut buf = vec![0u8; 8192];
+                    let n = socket.read(&mut buf).await.unwrap();
+                    let request = String::from_utf8_lossy(&buf[..n]).to_string();
+                    let line = request.lines().next().unwrap_or("").to_string();
+                    let (status, body) = routes
+                        .iter()
+                        .find(|(route, _, _)| line.starts_with(&format!("{route} ")))
+                        .map(|(_, status, body)| (*status, *body))
+                        .unwrap_or((
+                            404,
+                            r#"{"error_code": 404, "message": "HTTP 404 Not Found"}"#,
+                        ));
+                    let response = format!(
+                        "HTTP/1.1 {status} X\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                        body.len()
+                    );
+                    socket.write_all(response.as_bytes()).await.unwrap();
+                });
+            }
+        });
+        format!("http://{addr}")
+    }
+
+    #[tokio::test]
+    async fn test_registry_client() {
+        let latest = r#"{"subject": "orders-value", "version": 2, "id": 11, "schema": "{\"type\": \"record\", \"name\": \"Order\", \"fields\": [{\"name\": \"id\", \"type\": \"long\"}, {\"name\": \"item\", \"type\": \"string\"}]}"}"#;
+        let url = mock_registry(vec![
+            ("GET /subjects/orders-value/versions/latest", 200, latest),
+            ("GET /subjects/missing-value/versions/latest", 404, r#"{"error_code": 40401, "message": "Subject 'missing-value' not found."}"#),
+            ("POST /subjects/orders-value/versions", 200, r#"{"id": 12}"#),
+            ("POST /compatibility/subjects/orders-value/versions/latest", 200, r#"{"is_compatible": false}"#),
+            ("POST /compatibility/subjects/new-value/versions/latest", 404, r#"{"error_code": 40401, "message": "Subject 'new-value' not found."}"#),
+            ("GET /schemas/ids/11", 200, r#"{"schema": "{\"type\": \"record\", \"name\": \"Order\", \"fields\": [{\"name\": \"id\", \"type\": \"long\"}, {\"name\": \"item\", \"type\": \"string\"}]}"}"#),
+            ("GET /schemas/ids/20", 200, r#"{"schemaType": "JSON", "schema": "{}"}"#),
+            ("PUT /config/orders-value", 200, r#"{"compatibility": "FULL"}"#),
+        ])
+        .await;
+        let registry = SchemaRegistryClient::new(SchemaRegistryConfig::new(&url)).unwrap();
+
+        let schema = registry.latest("orders-value").await.unwrap();
+        assert_eq!(schema.id, 11);
+        assert_eq!(schema.version, Some(2));
+        assert_eq!(schema.format, SchemaFormat::Avro);
+        assert!(matches!(
+            registry.latest("missing-value").await,
+            Err(SchemaError::Registry {
+                code: SUBJECT_NOT_FOUND,
+                ..
+            })
+        ));
+
+        assert_eq!(
+            registry
+                .register("orders-value", ORDER_SCHEMA, SchemaFormat::Avro)
+                .await
+                .unwrap(),
+            12
+        );
+        assert!(!registry
+            .check_compatibility("orders-value", ORDER_SCHEMA, SchemaFormat::Avro)
+            .await
+            .unwrap());
+        assert!(registry
+            .check_compatibility("new-value", ORDER_SCHEMA, SchemaFormat::Avro)
+            .await
+            .unwrap());
+        registry
+            .set_compatibility("orders-value", "FULL")
+            .await
+            .unwrap();
+
+        let serializer = registry.avro_serializer("orders-value").await.unwrap();
+        assert_eq!(serializer.schema_id(), 11);
+        let value = json!({"id": 7, "item": "lamp"});
+        let payload = serializer.serialize(&value).unwrap();
+        assert_eq!(registry.decode(&payload).await.unwrap(), value);
+
+        let json_payload = frame(20, SchemaFormat::Json, br#"{"a": 1}"#);
+        assert_eq!(
+            registry.decode(&json_payload).await.unwrap(),
+            json!({"a": 1})
+        );
+
+        assert!(matches!(
+            registry
+                .serializer("orders-value", Arc::new(JsonSerializer))
+                .await,
+            Err(SchemaError::Unsupported(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_registry_unreachable() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+        let registry = SchemaRegistryClient::new(SchemaRegistryConfig::new(&url)).unwrap();
+        assert!(matches!(
+            registry.latest("orders-value").await,
+            Err(SchemaError::Http(_))
+        ));
+    }
+}
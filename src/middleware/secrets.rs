@@ -0,0 +1,186 @@
+//! Secrets providers for Cello Framework.
+//!
+//! A secrets provider holds key material by ID so that components needing
+//! keys (such as event encryption) do not keep them next to the data they
+//! protect. Deleting a secret is final: whatever it encrypted can no longer
+//! be read.
+//!
+//! - `InMemorySecretsProvider` for development and tests
+//! - `RedisSecretsProvider` to share keys between cluster workers
+
+use base64::Engine;
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::redis::RedisClient;
+
+/// Storage for secrets by ID.
+pub trait SecretsProvider: Send + Sync {
+    /// Look up a secret.
+    fn get(&self, id: &str) -> Result<Option<Vec<u8>>, SecretsError>;
+
+    /// Store a secret unless one exists under `id`. Returns whether it was
+    /// stored.
+    fn put_if_absent(&self, id: &str, secret: &[u8]) -> Result<bool, SecretsError>;
+
+    /// Delete a secret. Returns whether it existed.
+    fn delete(&self, id: &str) -> Result<bool, SecretsError>;
+
+    /// Backend name, for logs and stats.
+    fn name(&self) -> &str;
+}
+
+/// Secrets held in process memory.
+#[derive(Default)]
+pub struct InMemorySecretsProvider {
+    secrets: RwLock<HashMap<String, Vec<u8>>>,
+}
+
+impl InMemorySecretsProvider {
+    /// Create an empty provider.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of stored secrets.
+    pub fn len(&self) -> usize {
+        self.secrets.read().len()
+    }
+
+    /// Whether no secrets are stored.
+    pub fn is_empty(&self) -> bool {
+        self.secrets.read().is_empty()
+    }
+}
+
+impl SecretsProvider for InMemorySecretsProvider {
+    fn get(&self, id: &str) -> Result<Option<Vec<u8>>, SecretsError> {
+        Ok(self.secrets.read().get(id).cloned())
+    }
+
+    fn put_if_absent(&self, id: &str, secret: &[u8]) -> Result<bool, SecretsError> {
+        let mut secrets = self.secrets.write();
+        if secrets.contains_key(id) {
+            return Ok(false);
+        }
+        secrets.insert(id.to_string(), secret.to_vec());
+        Ok(true)
+    }
+
+    fn delete(&self, id: &str) -> Result<bool, SecretsError> {
+        Ok(self.secrets.write().remove(id).is_some())
+    }
+
+    fn name(&self) -> &str {
+        "memory"
+    }
+}
+
+/// Secrets stored base64-encoded in Redis.
+pub struct RedisSecretsProvider {
+    client: Arc<dyn RedisClient>,
+    key_prefix: String,
+}
+
+impl RedisSecretsProvider {
+    /// Create a provider on a Redis connection.
+    pub fn new(client: Arc<dyn RedisClient>) -> Self {
+        Self {
+            client,
+            key_prefix: "secrets:".to_string(),
+        }
+    }
+
+    /// Prefix for Redis keys (default `secrets:`).
+    pub fn with_key_prefix(mut self, prefix: &str) -> Self {
+        self.key_prefix = prefix.to_string();
+        self
+    }
+}
+
+impl SecretsProvider for RedisSecretsProvider {
+    fn get(&self, id: &str) -> Result<Option<Vec<u8>>, SecretsError> {
+        let key = format!("{}{id}", self.key_prefix);
+        let Some(value) = self.client.get(&key)? else {
+            return Ok(None);
+        };
+        let encoded = value
+            .as_bytes()
+            .ok_or_else(|| SecretsError::Backend(format!("bad value at {key}")))?;
+        base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .map(Some)
+            .map_err(|e| SecretsError::Backend(format!("bad value at {key}: {e}")))
+    }
+
+    fn put_if_absent(&self, id: &str, secret: &[u8]) -> Result<bool, SecretsError> {
+        let encoded = base64::engine::general_purpose::STANDARD.encode(secret);
+        // Redis needs an expiry for SET NX here; secrets are kept for a
+        // century, which is as good as forever.
+        let ttl = std::time::Duration::from_secs(100 * 365 * 24 * 3600);
+        Ok(self
+            .client
+            .set_nx(&format!("{}{id}", self.key_prefix), &encoded, ttl)?)
+    }
+
+    fn delete(&self, id: &str) -> Result<bool, SecretsError> {
+        Ok(self.client.delete(&format!("{}{id}", self.key_prefix))?)
+    }
+
+    fn name(&self) -> &str {
+        "redis"
+    }
+}
+
+/// Secrets provider errors.
+#[derive(Debug, Clone)]
+pub enum SecretsError {
+    /// The backend could not be reached or returned bad data
+    Backend(String),
+}
+
+impl std::fmt::Display for SecretsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SecretsError::Backend(msg) => write!(f, "Secrets provider error: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for SecretsError {}
+
+impl From<super::redis::RedisError> for SecretsError {
+    fn from(e: super::redis::RedisError) -> Self {
+        SecretsError::Backend(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::redis::{MockRedisClient, RedisConfig};
+
+    fn check_provider(provider: &dyn SecretsProvider) {
+        assert_eq!(provider.get("k1").unwrap(), None);
+        assert!(provider.put_if_absent("k1", b"first").unwrap());
+        assert!(!provider.put_if_absent("k1", b"second").unwrap());
+        assert_eq!(provider.get("k1").unwrap(), Some(b"first".to_vec()));
+        assert!(provider.delete("k1").unwrap());
+        assert!(!provider.delete("k1").unwrap());
+        assert_eq!(provider.get("k1").unwrap(), None);
+    }
+
+    #[test]
+    fn test_in_memory_provider() {
+        let provider = InMemorySecretsProvider::new();
+        check_provider(&provider);
+        assert!(provider.is_empty());
+    }
+
+    #[test]
+    fn test_redis_provider() {
+        let client = Arc::new(MockRedisClient::new(RedisConfig::default()));
+        check_provider(&RedisSecretsProvider::new(client));
+    }
+}
@@ -0,0 +1,1194 @@
+//! SQL connection pool backed by sqlx.
+//!
+//! Provides:
+//! - One pool type for PostgreSQL, MySQL and SQLite, chosen from the URL
+//! - Parameterised `execute`/`fetch_*` queries with values mapped to `SqlValue`
+//! - Transactions that roll back unless committed
+//! - Pool metrics: active/idle connections, acquire wait time, query timing
+//!
+//! PostgreSQL and MySQL rows are decoded through sqlx's `Any` driver, and
+//! SQLite rows through its own driver, since `Any` reads SQLite integers as
+//! 32-bit. Integers, floats, text, booleans, blobs and NULL are supported;
+//! other column types (timestamps, UUIDs, numerics) should be cast to text
+//! in the query.
+//!
+//! Each SQLite in-memory connection (`sqlite::memory:`) is a separate
+//! database, so pools for one are limited to a single connection that is
+//! never closed.
+//!
+//! # Example
+//! ```python
+//! from cello import App, Depends, DatabaseConfig
+//!
+//! app = App()
+//! app.enable_database(DatabaseConfig("postgresql://localhost/mydb"))
+//!
+//! @app.get("/users/{id}")
+//! async def get_user(request, db=Depends("database")):
+//!     return await db.fetch_one("SELECT id, name FROM users WHERE id = $1", int(request.params["id"]))
+//! ```
+
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyBytes, PyDict, PyFloat, PyList, PyLong, PyString, PyTuple};
+use sqlx::any::Any;
+use sqlx::database::HasArguments;
+use sqlx::pool::{PoolConnection, PoolOptions};
+use sqlx::query::Query;
+use sqlx::sqlite::{Sqlite, SqliteConnection};
+use sqlx::{
+    AnyConnection, Column, ColumnIndex, Database, Decode, Encode, Row, Type, TypeInfo as _,
+    ValueRef as _,
+};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::Mutex;
+
+use super::database::{DatabaseConfig, DatabaseError, DatabaseStats, PoolMetrics, SqlValue};
+
+/// A result row: column names and values in select order.
+pub type Record = Vec<(String, SqlValue)>;
+
+// ============================================================================
+// Pool
+// ============================================================================
+
+/// Connection pool for PostgreSQL, MySQL or SQLite.
+pub struct SqlPool {
+    pool: Pool,
+    backend: &'static str,
+    metrics: Arc<PoolMetrics>,
+}
+
+impl SqlPool {
+    /// Create a pool that opens connections on first use.
+    ///
+    /// Must be called within a Tokio runtime. Fails if the URL is not a
+    /// `postgres://`, `mysql://` or `sqlite:` URL.
+    pub fn connect_lazy(config: &DatabaseConfig) -> Result<Self, DatabaseError> {
+        sqlx::any::install_default_drivers();
+        let backend = backend_of(&config.url)?;
+        let metrics = Arc::new(PoolMetrics::default());
+        let config_error = |e: sqlx::Error| DatabaseError::Config(e.to_string());
+        let pool = if backend == "sqlite" {
+            let mut options = pool_options::<Sqlite>(config, &metrics);
+            if is_sqlite_memory(&config.url) {
+                options = options
+                    .max_connections(1)
+                    .min_connections(config.min_idle.min(1) as u32)
+                    .idle_timeout(None)
+                    .max_lifetime(None);
+            }
+            Pool::Sqlite(options.connect_lazy(&config.url).map_err(config_error)?)
+        } else {
+            let options = pool_options::<Any>(config, &metrics);
+            Pool::Any(options.connect_lazy(&config.url).map_err(config_error)?)
+        };
+        Ok(Self {
+            pool,
+            backend,
+            metrics,
+        })
+    }
+
+    /// Create a pool and check that a connection can be opened.
+    pub async fn connect(config: &DatabaseConfig) -> Result<Self, DatabaseError> {
+        let pool = Self::connect_lazy(config)?;
+        pool.acquire().await?;
+        Ok(pool)
+    }
+
+    /// Database backend: `"postgres"`, `"mysql"` or `"sqlite"`.
+    pub fn backend(&self) -> &'static str {
+        self.backend
+    }
+
+    /// Run a statement and return the number of rows affected.
+    pub async fn execute(&self, sql: &str, params: &[SqlValue]) -> Result<u64, DatabaseError> {
+        let mut conn = self.acquire().await?;
+        timed(&self.metrics, execute_on(conn.as_mut(), sql, params)).await
+    }
+
+    /// Run a query and return all rows.
+    pub async fn fetch_all(
+        &self,
+        sql: &str,
+        params: &[SqlValue],
+    ) -> Result<Vec<Record>, DatabaseError> {
+        let mut conn = self.acquire().await?;
+        timed(&self.metrics, fetch_on(conn.as_mut(), sql, params)).await
+    }
+
+    /// Run a query and return its first row, if any.
+    pub async fn fetch_one(
+        &self,
+        sql: &str,
+        params: &[SqlValue],
+    ) -> Result<Option<Record>, DatabaseError> {
+        Ok(self.fetch_all(sql, params).await?.into_iter().next())
+    }
+
+    /// Start a transaction on a pooled connection.
+    pub async fn begin(&self) -> Result<SqlTransaction, DatabaseError> {
+        self.begin_with(None).await
+    }
+
+    /// Start a transaction, optionally at a given isolation level.
+    ///
+    /// SQLite transactions are always serializable, so the level is ignored
+    /// there.
+    pub async fn begin_with(
+        &self,
+        isolation: Option<IsolationLevel>,
+    ) -> Result<SqlTransaction, DatabaseError> {
+        let tx_error = |e: sqlx::Error| DatabaseError::Transaction(e.to_string());
+        let set_level = isolation
+            .filter(|_| self.backend != "sqlite")
+            .map(|level| format!("SET TRANSACTION ISOLATION LEVEL {level}"));
+
+        let mut conn = self.acquire().await?;
+        // MySQL sets the level of the next transaction, Postgres of the current one.
+        if let (Some(sql), "mysql") = (&set_level, self.backend) {
+            execute_on(conn.as_mut(), sql, &[])
+                .await
+                .map_err(tx_error)?;
+        }
+        let mut tx = match conn {
+            PooledConnection::Any(conn) => {
+                Transaction::Any(sqlx::Transaction::begin(conn).await.map_err(tx_error)?)
+            }
+            PooledConnection::Sqlite(conn) => {
+                Transaction::Sqlite(sqlx::Transaction::begin(conn).await.map_err(tx_error)?)
+            }
+        };
+        if let (Some(sql), "postgres") = (&set_level, self.backend) {
+            execute_on(tx.as_mut(), sql, &[]).await.map_err(tx_error)?;
+        }
+        Ok(SqlTransaction {
+            tx: Some(tx),
+            metrics: self.metrics.clone(),
+        })
+    }
+
+    /// Pool statistics, with active/idle counts taken from the pool itself.
+    pub fn stats(&self) -> DatabaseStats {
+        let mut stats = self.metrics.get_stats();
+        stats.active_connections = self.active();
+        stats.idle_connections = self.pool.num_idle();
+        stats
+    }
+
+    /// Close the pool, waiting for checked-out connections to be returned.
+    pub async fn close(&self) {
+        self.pool.close().await;
+    }
+
+    async fn acquire(&self) -> Result<PooledConnection, DatabaseError> {
+        let start = Instant::now();
+        let conn = match &self.pool {
+            Pool::Any(pool) => pool.acquire().await.map(PooledConnection::Any),
+            Pool::Sqlite(pool) => pool.acquire().await.map(PooledConnection::Sqlite),
+        }
+        .map_err(|e| self.acquire_error(e))?;
+        self.checked_out(start);
+        Ok(conn)
+    }
+
+    fn checked_out(&self, start: Instant) {
+        self.metrics.record_wait(start.elapsed());
+        self.metrics.record_active(self.active());
+    }
+
+    fn active(&self) -> usize {
+        (self.pool.size() as usize).saturating_sub(self.pool.num_idle())
+    }
+
+    fn acquire_error(&self, e: sqlx::Error) -> DatabaseError {
+        match e {
+            sqlx::Error::PoolTimedOut => {
+                self.metrics.record_timeout();
+                DatabaseError::Timeout
+            }
+            other => DatabaseError::Connection(other.to_string()),
+        }
+    }
+}
+
+impl std::fmt::Debug for SqlPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SqlPool")
+            .field("backend", &self.backend)
+            .field("size", &self.pool.size())
+            .finish()
+    }
+}
+
+/// The sqlx pool behind a [`SqlPool`].
+enum Pool {
+    Any(sqlx::Pool<Any>),
+    Sqlite(sqlx::Pool<Sqlite>),
+}
+
+impl Pool {
+    fn size(&self) -> u32 {
+        match self {
+            Pool::Any(pool) => pool.size(),
+            Pool::Sqlite(pool) => pool.size(),
+        }
+    }
+
+    fn num_idle(&self) -> usize {
+        match self {
+            Pool::Any(pool) => pool.num_idle(),
+            Pool::Sqlite(pool) => pool.num_idle(),
+        }
+    }
+
+    async fn close(&self) {
+        match self {
+            Pool::Any(pool) => pool.close().await,
+            Pool::Sqlite(pool) => pool.close().await,
+        }
+    }
+}
+
+/// A connection checked out of a [`Pool`].
+enum PooledConnection {
+    Any(PoolConnection<Any>),
+    Sqlite(PoolConnection<Sqlite>),
+}
+
+impl PooledConnection {
+    fn as_mut(&mut self) -> Connection<'_> {
+        match self {
+            PooledConnection::Any(conn) => Connection::Any(conn),
+            PooledConnection::Sqlite(conn) => Connection::Sqlite(conn),
+        }
+    }
+}
+
+/// An open transaction on a [`PooledConnection`].
+enum Transaction {
+    Any(sqlx::Transaction<'static, Any>),
+    Sqlite(sqlx::Transaction<'static, Sqlite>),
+}
+
+impl Transaction {
+    fn as_mut(&mut self) -> Connection<'_> {
+        match self {
+            Transaction::Any(tx) => Connection::Any(tx),
+            Transaction::Sqlite(tx) => Connection::Sqlite(tx),
+        }
+    }
+
+    async fn commit(self) -> Result<(), sqlx::Error> {
+        match self {
+            Transaction::Any(tx) => tx.commit().await,
+            Transaction::Sqlite(tx) => tx.commit().await,
+        }
+    }
+
+    async fn rollback(self) -> Result<(), sqlx::Error> {
+        match self {
+            Transaction::Any(tx) => tx.rollback().await,
+            Transaction::Sqlite(tx) => tx.rollback().await,
+        }
+    }
+}
+
+/// A connection to run one query on.
+enum Connection<'c> {
+    Any(&'c mut AnyConnection),
+    Sqlite(&'c mut SqliteConnection),
+}
+
+/// Pool options from `config`, counting opened connections in `metrics`.
+fn pool_options<DB: Database>(
+    config: &DatabaseConfig,
+    metrics: &Arc<PoolMetrics>,
+) -> PoolOptions<DB> {
+    let opened = metrics.clone();
+    PoolOptions::new()
+        .max_connections(config.pool_size.max(1) as u32)
+        .min_connections(config.min_idle.min(config.pool_size) as u32)
+        .acquire_timeout(config.connection_timeout)
+        .idle_timeout(config.idle_timeout)
+        .max_lifetime(config.max_lifetime)
+        .after_connect(move |_, _| {
+            let opened = opened.clone();
+            Box::pin(async move {
+                opened.record_opened();
+                Ok(())
+            })
+        })
+}
+
+/// Whether a SQLite URL opens an in-memory database.
+fn is_sqlite_memory(url: &str) -> bool {
+    url.contains(":memory:") || url.contains("mode=memory")
+}
+
+/// Transaction isolation level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IsolationLevel {
+    ReadUncommitted,
+    ReadCommitted,
+    RepeatableRead,
+    Serializable,
+}
+
+impl std::fmt::Display for IsolationLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            IsolationLevel::ReadUncommitted => "READ UNCOMMITTED",
+            IsolationLevel::ReadCommitted => "READ COMMITTED",
+            IsolationLevel::RepeatableRead => "REPEATABLE READ",
+            IsolationLevel::Serializable => "SERIALIZABLE",
+        })
+    }
+}
+
+impl std::str::FromStr for IsolationLevel {
+    type Err = String;
+
+    /// Parse `"read_committed"`, `"read committed"` or `"READ-COMMITTED"`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().replace(['_', '-'], " ").as_str() {
+            "read uncommitted" => Ok(IsolationLevel::ReadUncommitted),
+            "read committed" => Ok(IsolationLevel::ReadCommitted),
+            "repeatable read" => Ok(IsolationLevel::RepeatableRead),
+            "serializable" => Ok(IsolationLevel::Serializable),
+            _ => Err(format!(
+                "Unknown isolation level '{s}' (expected read_uncommitted, read_committed, repeatable_read or serializable)"
+            )),
+        }
+    }
+}
+
+/// A transaction holding one pooled connection until committed or rolled
+/// back. Dropping it without committing rolls it back.
+pub struct SqlTransaction {
+    tx: Option<Transaction>,
+    metrics: Arc<PoolMetrics>,
+}
+
+impl SqlTransaction {
+    /// Run a statement in the transaction.
+    pub async fn execute(&mut self, sql: &str, params: &[SqlValue]) -> Result<u64, DatabaseError> {
+        let conn = self.tx.as_mut().ok_or_else(finished)?.as_mut();
+        timed(&self.metrics, execute_on(conn, sql, params)).await
+    }
+
+    /// Run a query in the transaction and return all rows.
+    pub async fn fetch_all(
+        &mut self,
+        sql: &str,
+        params: &[SqlValue],
+    ) -> Result<Vec<Record>, DatabaseError> {
+        let conn = self.tx.as_mut().ok_or_else(finished)?.as_mut();
+        timed(&self.metrics, fetch_on(conn, sql, params)).await
+    }
+
+    /// Commit the transaction.
+    pub async fn commit(mut self) -> Result<(), DatabaseError> {
+        match self.tx.take() {
+            Some(tx) => tx
+                .commit()
+                .await
+                .map_err(|e| DatabaseError::Transaction(e.to_string())),
+            None => Err(finished()),
+        }
+    }
+
+    /// Roll the transaction back.
+    pub async fn rollback(mut self) -> Result<(), DatabaseError> {
+        match self.tx.take() {
+            Some(tx) => tx
+                .rollback()
+                .await
+                .map_err(|e| DatabaseError::Transaction(e.to_string())),
+            None => Err(finished()),
+        }
+    }
+}
+
+fn finished() -> DatabaseError {
+    DatabaseError::Transaction("transaction is already finished".to_string())
+}
+
+fn backend_of(url: &str) -> Result<&'static str, DatabaseError> {
+    let scheme = url.split(':').next().unwrap_or_default();
+    match scheme.to_ascii_lowercase().as_str() {
+        "postgres" | "postgresql" => Ok("postgres"),
+        "mysql" | "mariadb" => Ok("mysql"),
+        "sqlite" => Ok("sqlite"),
+        _ => Err(DatabaseError::Config(format!(
+            "Unsupported database URL scheme '{scheme}' (expected postgres, mysql or sqlite)"
+        ))),
+    }
+}
+
+async fn timed<T>(
+    metrics: &PoolMetrics,
+    query: impl Future<Output = Result<T, sqlx::Error>>,
+) -> Result<T, DatabaseError> {
+    let start = Instant::now();
+    let result = query.await;
+    metrics.record_query(start.elapsed().as_millis() as u64, result.is_err());
+    result.map_err(|e| DatabaseError::Query(e.to_string()))
+}
+
+fn bind_all<'q, DB>(
+    sql: &'q str,
+    params: &[SqlValue],
+) -> Query<'q, DB, <DB as HasArguments<'q>>::Arguments>
+where
+    DB: Database,
+    Option<String>: Encode<'q, DB> + Type<DB>,
+    bool: Encode<'q, DB> + Type<DB>,
+    i64: Encode<'q, DB> + Type<DB>,
+    f64: Encode<'q, DB> + Type<DB>,
+    String: Encode<'q, DB> + Type<DB>,
+    Vec<u8>: Encode<'q, DB> + Type<DB>,
+{
+    params
+        .iter()
+        .fold(sqlx::query(sql), |query, value| match value {
+            SqlValue::Null => query.bind(None::<String>),
+            SqlValue::Bool(v) => query.bind(*v),
+            SqlValue::Int(v) => query.bind(*v),
+            SqlValue::Float(v) => query.bind(*v),
+            SqlValue::Text(v) => query.bind(v.clone()),
+            SqlValue::Bytes(v) => query.bind(v.clone()),
+            SqlValue::Timestamp(v) => query.bind(v.to_rfc3339()),
+            SqlValue::Uuid(v) => query.bind(v.to_string()),
+            SqlValue::Json(v) => query.bind(v.to_string()),
+        })
+}
+
+async fn execute_on(
+    conn: Connection<'_>,
+    sql: &str,
+    params: &[SqlValue],
+) -> Result<u64, sqlx::Error> {
+    Ok(match conn {
+        Connection::Any(conn) => bind_all(sql, params).execute(conn).await?.rows_affected(),
+        Connection::Sqlite(conn) => bind_all(sql, params).execute(conn).await?.rows_affected(),
+    })
+}
+
+async fn fetch_on(
+    conn: Connection<'_>,
+    sql: &str,
+    params: &[SqlValue],
+) -> Result<Vec<Record>, sqlx::Error> {
+    match conn {
+        Connection::Any(conn) => {
+            let rows = bind_all(sql, params).fetch_all(conn).await?;
+            rows.iter().map(record).collect()
+        }
+        Connection::Sqlite(conn) => {
+            let rows = bind_all(sql, params).fetch_all(conn).await?;
+            rows.iter().map(record).collect()
+        }
+    }
+}
+
+fn record<R>(row: &R) -> Result<Record, sqlx::Error>
+where
+    R: Row,
+    usize: ColumnIndex<R>,
+    for<'r> bool: Decode<'r, R::Database> + Type<R::Database>,
+    for<'r> i64: Decode<'r, R::Database> + Type<R::Database>,
+    for<'r> f64: Decode<'r, R::Database> + Type<R::Database>,
+    for<'r> String: Decode<'r, R::Database> + Type<R::Database>,
+    for<'r> Vec<u8>: Decode<'r, R::Database> + Type<R::Database>,
+{
+    row.columns()
+        .iter()
+        .map(|column| Ok((column.name().to_string(), value(row, column.ordinal())?)))
+        .collect()
+}
+
+fn value<R>(row: &R, index: usize) -> Result<SqlValue, sqlx::Error>
+where
+    R: Row,
+    usize: ColumnIndex<R>,
+    for<'r> bool: Decode<'r, R::Database> + Type<R::Database>,
+    for<'r> i64: Decode<'r, R::Database> + Type<R::Database>,
+    for<'r> f64: Decode<'r, R::Database> + Type<R::Database>,
+    for<'r> String: Decode<'r, R::Database> + Type<R::Database>,
+    for<'r> Vec<u8>: Decode<'r, R::Database> + Type<R::Database>,
+{
+    let raw = row.try_get_raw(index)?;
+    if raw.is_null() {
+        return Ok(SqlValue::Null);
+    }
+    // The type names of the Any driver's value kinds and of SQLite's
+    // storage classes.
+    let kind = raw.type_info().name().to_string();
+    Ok(match kind.as_str() {
+        "BOOLEAN" => SqlValue::Bool(row.try_get(index)?),
+        "SMALLINT" | "INTEGER" | "BIGINT" => SqlValue::Int(row.try_get(index)?),
+        "REAL" | "DOUBLE" => SqlValue::Float(row.try_get(index)?),
+        "BLOB" => SqlValue::Bytes(row.try_get(index)?),
+        "TEXT" => SqlValue::Text(row.try_get(index)?),
+        _ => {
+            return Err(sqlx::Error::ColumnDecode {
+                index: index.to_string(),
+                source: format!("unsupported column type {kind}; cast it to text").into(),
+            })
+        }
+    })
+}
+
+// ============================================================================
+// Python API
+// ============================================================================
+
+/// Python handle to the SQL pool.
+///
+/// Query methods take the SQL followed by positional parameters and return
+/// awaitables; rows are returned as dicts.
+#[pyclass(name = "DatabasePool")]
+#[derive(Clone)]
+pub struct PyDatabasePool {
+    pool: Arc<SqlPool>,
+}
+
+impl PyDatabasePool {
+    pub fn new(pool: Arc<SqlPool>) -> Self {
+        Self { pool }
+    }
+}
+
+#[pymethods]
+impl PyDatabasePool {
+    /// Connect a new pool, checking that a connection can be opened.
+    #[staticmethod]
+    fn connect(py: Python<'_>, config: crate::PyDatabaseConfig) -> PyResult<&PyAny> {
+        let config = config.to_config();
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            let pool = SqlPool::connect(&config)
+                .await
+                .map_err(database_error_to_py)?;
+            Ok(PyDatabasePool::new(Arc::new(pool)))
+        })
+    }
+
+    /// Database backend: "postgres", "mysql" or "sqlite".
+    #[getter]
+    fn backend(&self) -> &'static str {
+        self.pool.backend()
+    }
+
+    /// Run a statement and return the number of rows affected.
+    #[pyo3(signature = (sql, *params))]
+    fn execute<'py>(&self, py: Python<'py>, sql: String, params: &PyTuple) -> PyResult<&'py PyAny> {
+        let (pool, params) = (self.pool.clone(), sql_params(params)?);
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            pool.execute(&sql, &params)
+                .await
+                .map_err(database_error_to_py)
+        })
+    }
+
+    /// Run a query and return all rows as dicts.
+    #[pyo3(signature = (sql, *params))]
+    fn fetch_all<'py>(
+        &self,
+        py: Python<'py>,
+        sql: String,
+        params: &PyTuple,
+    ) -> PyResult<&'py PyAny> {
+        let (pool, params) = (self.pool.clone(), sql_params(params)?);
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            let rows = pool
+                .fetch_all(&sql, &params)
+                .await
+                .map_err(database_error_to_py)?;
+            Python::with_gil(|py| records_to_py(py, &rows))
+        })
+    }
+
+    /// Run a query and return the first row as a dict, or None.
+    #[pyo3(signature = (sql, *params))]
+    fn fetch_one<'py>(
+        &self,
+        py: Python<'py>,
+        sql: String,
+        params: &PyTuple,
+    ) -> PyResult<&'py PyAny> {
+        let (pool, params) = (self.pool.clone(), sql_params(params)?);
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            let row = pool
+                .fetch_one(&sql, &params)
+                .await
+                .map_err(database_error_to_py)?;
+            Python::with_gil(|py| optional_record_to_py(py, row.as_ref()))
+        })
+    }
+
+    /// Run a query and return the first column of the first row, or None.
+    #[pyo3(signature = (sql, *params))]
+    fn fetch_val<'py>(
+        &self,
+        py: Python<'py>,
+        sql: String,
+        params: &PyTuple,
+    ) -> PyResult<&'py PyAny> {
+        let (pool, params) = (self.pool.clone(), sql_params(params)?);
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            let row = pool
+                .fetch_one(&sql, &params)
+                .await
+                .map_err(database_error_to_py)?;
+            Python::with_gil(|py| first_value_to_py(py, row.as_ref()))
+        })
+    }
+
+    /// Start a transaction and return it once it has begun.
+    ///
+    /// `isolation` is `"read_uncommitted"`, `"read_committed"`,
+    /// `"repeatable_read"` or `"serializable"`.
+    #[pyo3(signature = (isolation=None))]
+    fn begin<'py>(&self, py: Python<'py>, isolation: Option<&str>) -> PyResult<&'py PyAny> {
+        let tx = PyDatabaseTransaction::new(self.pool.clone(), isolation_arg(isolation)?);
+        let starting = tx.starting();
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            starting.await?;
+            Ok(tx)
+        })
+    }
+
+    /// A transaction used as `async with db.transaction() as tx:`; it
+    /// commits when the block exits normally and rolls back on error.
+    #[pyo3(signature = (isolation=None))]
+    fn transaction(&self, isolation: Option<&str>) -> PyResult<PyDatabaseTransaction> {
+        Ok(PyDatabaseTransaction::new(
+            self.pool.clone(),
+            isolation_arg(isolation)?,
+        ))
+    }
+
+    /// Pool statistics as a dict.
+    fn stats(&self, py: Python<'_>) -> PyResult<PyObject> {
+        crate::json::json_to_python(
+            py,
+            &serde_json::to_value(self.pool.stats())
+                .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))?,
+        )
+    }
+
+    /// Close the pool.
+    fn close<'py>(&self, py: Python<'py>) -> PyResult<&'py PyAny> {
+        let pool = self.pool.clone();
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            pool.close().await;
+            Ok(())
+        })
+    }
+
+    fn __repr__(&self) -> String {
+        format!("DatabasePool(backend={:?})", self.pool.backend())
+    }
+}
+
+enum TransactionState {
+    NotStarted,
+    Active(SqlTransaction),
+    Finished,
+}
+
+/// Python handle to a transaction.
+#[pyclass(name = "DatabaseTransaction")]
+#[derive(Clone)]
+pub struct PyDatabaseTransaction {
+    pool: Arc<SqlPool>,
+    isolation: Option<IsolationLevel>,
+    state: Arc<Mutex<TransactionState>>,
+}
+
+impl PyDatabaseTransaction {
+    fn new(pool: Arc<SqlPool>, isolation: Option<IsolationLevel>) -> Self {
+        Self {
+            pool,
+            isolation,
+            state: Arc::new(Mutex::new(TransactionState::NotStarted)),
+        }
+    }
+
+    /// Wrap a transaction that has already begun.
+    pub fn started(pool: Arc<SqlPool>, tx: SqlTransaction) -> Self {
+        Self {
+            pool,
+            isolation: None,
+            state: Arc::new(Mutex::new(TransactionState::Active(tx))),
+        }
+    }
+
+    /// Commit or roll back unless the transaction has already finished.
+    ///
+    /// Returns whether it was still active.
+    pub fn settling(
+        &self,
+        commit: bool,
+    ) -> impl Future<Output = Result<bool, DatabaseError>> + Send + 'static {
+        let state = self.state.clone();
+        async move {
+            let mut state = state.lock().await;
+            match std::mem::replace(&mut *state, TransactionState::Finished) {
+                TransactionState::Active(tx) if commit => tx.commit().await.map(|_| true),
+                TransactionState::Active(tx) => tx.rollback().await.map(|_| true),
+                _ => Ok(false),
+            }
+        }
+    }
+
+    fn starting(&self) -> impl Future<Output = PyResult<()>> + Send + 'static {
+        let (pool, isolation, state) = (self.pool.clone(), self.isolation, self.state.clone());
+        async move {
+            let mut state = state.lock().await;
+            if !matches!(*state, TransactionState::NotStarted) {
+                return Err(pyo3::exceptions::PyRuntimeError::new_err(
+                    "transaction has already been started",
+                ));
+            }
+            let tx = pool
+                .begin_with(isolation)
+                .await
+                .map_err(database_error_to_py)?;
+            *state = TransactionState::Active(tx);
+            Ok(())
+        }
+    }
+
+    fn finishing(&self, commit: bool) -> impl Future<Output = PyResult<()>> + Send + 'static {
+        let state = self.state.clone();
+        async move {
+            let mut state = state.lock().await;
+            match std::mem::replace(&mut *state, TransactionState::Finished) {
+                TransactionState::Active(tx) if commit => tx.commit().await,
+                TransactionState::Active(tx) => tx.rollback().await,
+                _ => Err(finished()),
+            }
+            .map_err(database_error_to_py)
+        }
+    }
+
+    fn querying(
+        &self,
+        sql: String,
+        params: Vec<SqlValue>,
+    ) -> impl Future<Output = PyResult<Vec<Record>>> + Send + 'static {
+        let state = self.state.clone();
+        async move {
+            match &mut *state.lock().await {
+                TransactionState::Active(tx) => tx
+                    .fetch_all(&sql, &params)
+                    .await
+                    .map_err(database_error_to_py),
+                _ => Err(database_error_to_py(not_active())),
+            }
+        }
+    }
+}
+
+fn not_active() -> DatabaseError {
+    DatabaseError::Transaction("transaction is not active".to_string())
+}
+
+#[pymethods]
+impl PyDatabaseTransaction {
+    /// Run a statement in the transaction.
+    #[pyo3(signature = (sql, *params))]
+    fn execute<'py>(&self, py: Python<'py>, sql: String, params: &PyTuple) -> PyResult<&'py PyAny> {
+        let (state, params) = (self.state.clone(), sql_params(params)?);
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            match &mut *state.lock().await {
+                TransactionState::Active(tx) => tx.execute(&sql, &params).await,
+                _ => Err(not_active()),
+            }
+            .map_err(database_error_to_py)
+        })
+    }
+
+    /// Run a query in the transaction and return all rows as dicts.
+    #[pyo3(signature = (sql, *params))]
+    fn fetch_all<'py>(
+        &self,
+        py: Python<'py>,
+        sql: String,
+        params: &PyTuple,
+    ) -> PyResult<&'py PyAny> {
+        let querying = self.querying(sql, sql_params(params)?);
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            let rows = querying.await?;
+            Python::with_gil(|py| records_to_py(py, &rows))
+        })
+    }
+
+    /// Run a query in the transaction and return the first row, or None.
+    #[pyo3(signature = (sql, *params))]
+    fn fetch_one<'py>(
+        &self,
+        py: Python<'py>,
+        sql: String,
+        params: &PyTuple,
+    ) -> PyResult<&'py PyAny> {
+        let querying = self.querying(sql, sql_params(params)?);
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            let rows = querying.await?;
+            Python::with_gil(|py| optional_record_to_py(py, rows.first()))
+        })
+    }
+
+    /// Run a query in the transaction and return the first value, or None.
+    #[pyo3(signature = (sql, *params))]
+    fn fetch_val<'py>(
+        &self,
+        py: Python<'py>,
+        sql: String,
+        params: &PyTuple,
+    ) -> PyResult<&'py PyAny> {
+        let querying = self.querying(sql, sql_params(params)?);
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            let rows = querying.await?;
+            Python::with_gil(|py| first_value_to_py(py, rows.first()))
+        })
+    }
+
+    /// Commit the transaction.
+    fn commit<'py>(&self, py: Python<'py>) -> PyResult<&'py PyAny> {
+        pyo3_asyncio::tokio::future_into_py(py, self.finishing(true))
+    }
+
+    /// Roll the transaction back.
+    fn rollback<'py>(&self, py: Python<'py>) -> PyResult<&'py PyAny> {
+        pyo3_asyncio::tokio::future_into_py(py, self.finishing(false))
+    }
+
+    fn __aenter__<'py>(slf: PyRef<'py, Self>, py: Python<'py>) -> PyResult<&'py PyAny> {
+        let state = slf.state.clone();
+        let starting = slf.starting();
+        let this: PyObject = slf.into_py(py);
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            // A transaction from `begin()` has already started.
+            if matches!(*state.lock().await, TransactionState::NotStarted) {
+                starting.await?;
+            }
+            Ok(this)
+        })
+    }
+
+    #[pyo3(signature = (exc_type=None, _exc_value=None, _traceback=None))]
+    fn __aexit__<'py>(
+        &self,
+        py: Python<'py>,
+        exc_type: Option<&PyAny>,
+        _exc_value: Option<&PyAny>,
+        _traceback: Option<&PyAny>,
+    ) -> PyResult<&'py PyAny> {
+        let state = self.state.clone();
+        let finishing = self.finishing(exc_type.is_none_or(|t| t.is_none()));
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            // Leave a transaction committed or rolled back inside the block alone.
+            if matches!(*state.lock().await, TransactionState::Active(_)) {
+                finishing.await?;
+            }
+            Ok(false)
+        })
+    }
+}
+
+fn database_error_to_py(e: DatabaseError) -> PyErr {
+    match e {
+        DatabaseError::Timeout => pyo3::exceptions::PyTimeoutError::new_err(e.to_string()),
+        DatabaseError::Config(_) => pyo3::exceptions::PyValueError::new_err(e.to_string()),
+        _ => pyo3::exceptions::PyRuntimeError::new_err(e.to_string()),
+    }
+}
+
+fn isolation_arg(isolation: Option<&str>) -> PyResult<Option<IsolationLevel>> {
+    isolation
+        .map(str::parse)
+        .transpose()
+        .map_err(pyo3::exceptions::PyValueError::new_err)
+}
+
+fn sql_params(params: &PyTuple) -> PyResult<Vec<SqlValue>> {
+    params.iter().map(sql_value).collect()
+}
+
+fn sql_value(value: &PyAny) -> PyResult<SqlValue> {
+    Ok(if value.is_none() {
+        SqlValue::Null
+    } else if let Ok(v) = value.downcast::<PyBool>() {
+        SqlValue::Bool(v.is_true())
+    } else if value.is_instance_of::<PyLong>() {
+        SqlValue::Int(value.extract()?)
+    } else if value.is_instance_of::<PyFloat>() {
+        SqlValue::Float(value.extract()?)
+    } else if let Ok(v) = value.downcast::<PyString>() {
+        SqlValue::Text(v.to_str()?.to_string())
+    } else if let Ok(v) = value.downcast::<PyBytes>() {
+        SqlValue::Bytes(v.as_bytes().to_vec())
+    } else if value.is_instance_of::<PyDict>() || value.is_instance_of::<PyList>() {
+        SqlValue::Json(
+            crate::json::python_to_json(value.py(), value)
+                .map_err(pyo3::exceptions::PyTypeError::new_err)?,
+        )
+    } else if value.hasattr("isoformat")? {
+        // datetime, date and time
+        SqlValue::Text(value.call_method0("isoformat")?.extract()?)
+    } else {
+        SqlValue::Text(value.str()?.to_str()?.to_string())
+    })
+}
+
+fn value_to_py(py: Python<'_>, value: &SqlValue) -> PyResult<PyObject> {
+    Ok(match value {
+        SqlValue::Null => py.None(),
+        SqlValue::Bool(v) => v.into_py(py),
+        SqlValue::Int(v) => v.into_py(py),
+        SqlValue::Float(v) => v.into_py(py),
+        SqlValue::Text(v) => v.into_py(py),
+        SqlValue::Bytes(v) => PyBytes::new(py, v).into(),
+        SqlValue::Timestamp(v) => v.to_rfc3339().into_py(py),
+        SqlValue::Uuid(v) => v.to_string().into_py(py),
+        SqlValue::Json(v) => crate::json::json_to_python(py, v)?,
+    })
+}
+
+fn record_to_py(py: Python<'_>, record: &Record) -> PyResult<PyObject> {
+    let row = PyDict::new(py);
+    for (column, value) in record {
+        row.set_item(column, value_to_py(py, value)?)?;
+    }
+    Ok(row.into())
+}
+
+fn records_to_py(py: Python<'_>, records: &[Record]) -> PyResult<PyObject> {
+    let rows = records
+        .iter()
+        .map(|record| record_to_py(py, record))
+        .collect::<PyResult<Vec<_>>>()?;
+    Ok(PyList::new(py, rows).into())
+}
+
+fn optional_record_to_py(py: Python<'_>, record: Option<&Record>) -> PyResult<PyObject> {
+    record.map_or_else(|| Ok(py.None()), |record| record_to_py(py, record))
+}
+
+fn first_value_to_py(py: Python<'_>, record: Option<&Record>) -> PyResult<PyObject> {
+    match record.and_then(|record| record.first()) {
+        Some((_, value)) => value_to_py(py, value),
+        None => Ok(py.None()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn memory_pool() -> SqlPool {
+        // Every SQLite in-memory connection is its own database, so keep one.
+        let config = DatabaseConfig::new("sqlite::memory:").pool_size(1);
+        let pool = SqlPool::connect(&config).await.unwrap();
+        pool.execute(
+            "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT NOT NULL, score REAL, avatar BLOB)",
+            &[],
+        )
+        .await
+        .unwrap();
+        pool
+    }
+
+    #[tokio::test]
+    async fn test_execute_and_fetch() {
+        let pool = memory_pool().await;
+        assert_eq!(pool.backend(), "sqlite");
+
+        let inserted = pool
+            .execute(
+                "INSERT INTO users (name, score, avatar) VALUES ($1, $2, $3), ($4, $5, $6)",
+                &[
+                    SqlValue::Text("alice".into()),
+                    SqlValue::Float(9.5),
+                    SqlValue::Bytes(vec![1, 2]),
+                    SqlValue::Text("bob".into()),
+                    SqlValue::Null,
+                    SqlValue::Null,
+                ],
+            )
+            .await
+            .unwrap();
+        assert_eq!(inserted, 2);
+
+        let rows = pool
+            .fetch_all("SELECT id, name, score, avatar FROM users ORDER BY id", &[])
+            .await
+            .unwrap();
+        assert_eq!(rows.len(), 2);
+        let columns: Vec<_> = rows[0].iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(columns, ["id", "name", "score", "avatar"]);
+        assert!(matches!(rows[0][0].1, SqlValue::Int(1)));
+        assert!(matches!(&rows[0][1].1, SqlValue::Text(name) if name == "alice"));
+        assert!(matches!(rows[0][2].1, SqlValue::Float(score) if score == 9.5));
+        assert!(matches!(&rows[0][3].1, SqlValue::Bytes(bytes) if bytes == &[1, 2]));
+        assert!(matches!(rows[1][2].1, SqlValue::Null));
+
+        let missing = pool
+            .fetch_one("SELECT name FROM users WHERE id = $1", &[SqlValue::Int(99)])
+            .await
+            .unwrap();
+        assert!(missing.is_none());
+        assert!(pool.execute("SELECT * FROM nope", &[]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_integers_keep_64_bits() {
+        let pool = memory_pool().await;
+        pool.execute(
+            "INSERT INTO users (id, name) VALUES ($1, $2)",
+            &[SqlValue::Int(3_000_000_000), SqlValue::Text("eve".into())],
+        )
+        .await
+        .unwrap();
+        let row = pool
+            .fetch_one("SELECT id FROM users", &[])
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(matches!(row[0].1, SqlValue::Int(3_000_000_000)));
+
+        let row = pool
+            .fetch_one(
+                "SELECT 2147483648, -2147483649, 4611686018427387904, 9223372036854775807",
+                &[],
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        let values: Vec<_> = row
+            .iter()
+            .map(|(_, value)| match value {
+                SqlValue::Int(n) => *n,
+                other => panic!("unexpected {other:?}"),
+            })
+            .collect();
+        assert_eq!(values, [2_147_483_648, -2_147_483_649, 1 << 62, i64::MAX]);
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_memory_pool_shares_one_database() {
+        // The default pool size would otherwise give each connection its
+        // own empty database.
+        let pool = SqlPool::connect(&DatabaseConfig::new("sqlite::memory:"))
+            .await
+            .unwrap();
+        pool.execute("CREATE TABLE t (n INTEGER)", &[])
+            .await
+            .unwrap();
+        let pool = &pool;
+        let queries = (0..8).map(|n| async move {
+            pool.execute("INSERT INTO t VALUES ($1)", &[SqlValue::Int(n)])
+                .await
+        });
+        for inserted in futures_util::future::join_all(queries).await {
+            assert_eq!(inserted.unwrap(), 1);
+        }
+        assert_eq!(
+            pool.fetch_all("SELECT n FROM t", &[]).await.unwrap().len(),
+            8
+        );
+    }
+
+    async fn count(pool: &SqlPool) -> i64 {
+        let row = pool
+            .fetch_one("SELECT COUNT(*) AS n FROM users", &[])
+            .await
+            .unwrap()
+            .unwrap();
+        match row[0].1 {
+            SqlValue::Int(n) => n,
+            ref other => panic!("unexpected {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transaction_commit_and_rollback() {
+        let pool = memory_pool().await;
+        let mut tx = pool.begin().await.unwrap();
+        tx.execute(
+            "INSERT INTO users (name) VALUES ($1)",
+            &[SqlValue::Text("carol".into())],
+        )
+        .await
+        .unwrap();
+        tx.rollback().await.unwrap();
+        assert_eq!(count(&pool).await, 0);
+
+        let mut tx = pool.begin().await.unwrap();
+        tx.execute(
+            "INSERT INTO users (name) VALUES ($1)",
+            &[SqlValue::Text("dave".into())],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            tx.fetch_all("SELECT name FROM users", &[])
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+        tx.commit().await.unwrap();
+        assert_eq!(count(&pool).await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_pool_stats() {
+        let pool = memory_pool().await;
+        pool.fetch_all("SELECT 1", &[]).await.unwrap();
+        let _ = pool.fetch_all("SELECT broken", &[]).await;
+
+        // Connections are handed back to the pool by a background task.
+        for _ in 0..100 {
+            if pool.stats().idle_connections == 1 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        let stats = pool.stats();
+        assert_eq!(stats.total_connections, 1);
+        assert_eq!(stats.total_queries, 3);
+        assert_eq!(stats.total_errors, 1);
+        assert_eq!(stats.active_connections, 0);
+        assert_eq!(stats.idle_connections, 1);
+        assert_eq!(stats.peak_active_connections, 1);
+        assert!(stats.max_wait_time_ms >= stats.avg_wait_time_ms);
+    }
+
+    #[test]
+    fn test_backend_from_url() {
+        assert_eq!(backend_of("postgresql://localhost/db").unwrap(), "postgres");
+        assert_eq!(backend_of("mysql://root@localhost/db").unwrap(), "mysql");
+        assert_eq!(backend_of("sqlite::memory:").unwrap(), "sqlite");
+        assert!(matches!(
+            backend_of("mongodb://localhost"),
+            Err(DatabaseError::Config(_))
+        ));
+    }
+
+    #[test]
+    fn test_isolation_level_from_str() {
+        assert_eq!("read_committed".parse(), Ok(IsolationLevel::ReadCommitted));
+        assert_eq!(
+            "REPEATABLE-READ".parse(),
+            Ok(IsolationLevel::RepeatableRead)
+        );
+        assert_eq!(IsolationLevel::Serializable.to_string(), "SERIALIZABLE");
+        assert!("snapshot".parse::<IsolationLevel>().is_err());
+    }
+}
@@ -332,9 +332,7 @@ impl StaticFilesMiddleware {
             let norm_canonical = canonical_str
                 .strip_prefix(r"\\?\")
                 .unwrap_or(&canonical_str);
-            let norm_root = root_str
-                .strip_prefix(r"\\?\")
-                .unwrap_or(&root_str);
+            let norm_root = root_str.strip_prefix(r"\\?\").unwrap_or(&root_str);
 
             if !norm_canonical.starts_with(norm_root) {
                 return None;
@@ -429,11 +427,7 @@ impl StaticFilesMiddleware {
         }
 
         // Get accept-encoding
-        let accept_encoding = request
-            .headers
-            .get("accept-encoding")
-            .map(|s| s.as_str())
-            .unwrap_or("");
+        let accept_encoding = request.headers.get("accept-encoding").unwrap_or("");
 
         // Check for precompressed version
         let (file_path, encoding) = self
@@ -445,7 +439,7 @@ impl StaticFilesMiddleware {
         if self.config.etag {
             let etag = self.generate_etag(&metadata);
             if let Some(if_none_match) = request.headers.get("if-none-match") {
-                if if_none_match == &etag {
+                if if_none_match == etag {
                     let mut response = Response::new(304);
                     response.set_header("ETag", &etag);
                     return Some(response);
@@ -458,7 +452,7 @@ impl StaticFilesMiddleware {
             if let Ok(modified) = metadata.modified() {
                 // Simple comparison - could be more sophisticated
                 let modified_str = format_http_date(modified);
-                if &modified_str == if_modified_since {
+                if modified_str == if_modified_since {
                     return Some(Response::new(304));
                 }
             }
@@ -0,0 +1,493 @@
+//! StatsD / DogStatsD metrics exporter for Cello Framework.
+//!
+//! An alternative to Prometheus scraping that pushes metrics to a StatsD
+//! or DogStatsD agent over UDP:
+//! - Every request counts `http.requests`, times `http.request.duration`
+//!   (ms) and adds its body size to `http.response.bytes`, tagged with
+//!   `method`, `status` and `path`, once the response has been sent
+//! - Application code can push its own counters, timers and gauges
+//! - Counters are summed and metrics buffered between flushes, which send
+//!   them in packets of at most `max_packet_size` bytes
+//! - DogStatsD gets tags (`|#key:value`); plain StatsD has no tags, so
+//!   they are left out
+//!
+//! Sending is fire-and-forget, as UDP is: an agent that is down loses the
+//! metrics, but never slows requests down.
+//!
+//! # Example
+//! ```python
+//! app.enable_statsd("127.0.0.1", 8125, dogstatsd=True, tags={"env": "prod"})
+//! app.statsd_increment("signups", tags={"plan": "pro"})
+//! ```
+
+use parking_lot::{Mutex, RwLock};
+use std::collections::{HashMap, HashSet};
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+
+use super::{path_matches_skip, Middleware, ResponseEnd};
+
+/// Buffered timer and gauge samples that trigger an early flush.
+const MAX_BUFFERED_SAMPLES: usize = 1000;
+
+// ============================================================================
+// Configuration
+// ============================================================================
+
+/// StatsD exporter configuration.
+#[derive(Clone, Debug)]
+pub struct StatsdConfig {
+    /// Agent host
+    pub host: String,
+    /// Agent UDP port
+    pub port: u16,
+    /// Prefix of metric names, e.g. `cello` for `cello.http.requests`
+    pub prefix: String,
+    /// Tags sent with every metric
+    pub tags: Vec<(String, String)>,
+    /// Send DogStatsD tags
+    pub dogstatsd: bool,
+    /// Time between flushes
+    pub flush_interval: Duration,
+    /// Largest UDP packet sent, in bytes
+    pub max_packet_size: usize,
+    /// Paths not measured
+    pub exclude_paths: Vec<String>,
+    /// Distinct `path` tags before others are tagged `OTHER`
+    pub max_path_cardinality: usize,
+}
+
+impl StatsdConfig {
+    /// Push to the agent at `host:port`, flushing every 10 seconds.
+    pub fn new(host: &str, port: u16) -> Self {
+        Self {
+            host: host.to_string(),
+            port,
+            prefix: "cello".to_string(),
+            tags: Vec::new(),
+            dogstatsd: false,
+            flush_interval: Duration::from_secs(10),
+            // Fits an Ethernet MTU without fragmenting
+            max_packet_size: 1432,
+            exclude_paths: Vec::new(),
+            max_path_cardinality: 1000,
+        }
+    }
+
+    /// Set the metric name prefix; empty for none.
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.trim_end_matches('.').to_string();
+        self
+    }
+
+    /// Add a tag sent with every metric.
+    pub fn tag(mut self, key: &str, value: &str) -> Self {
+        self.tags.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// Send DogStatsD tags.
+    pub fn dogstatsd(mut self, enabled: bool) -> Self {
+        self.dogstatsd = enabled;
+        self
+    }
+
+    /// Set the time between flushes.
+    pub fn flush_interval(mut self, interval: Duration) -> Self {
+        self.flush_interval = interval;
+        self
+    }
+
+    /// Set the largest UDP packet sent.
+    pub fn max_packet_size(mut self, bytes: usize) -> Self {
+        self.max_packet_size = bytes;
+        self
+    }
+
+    /// Don't measure requests under `path`.
+    pub fn exclude_path(mut self, path: &str) -> Self {
+        self.exclude_paths.push(path.to_string());
+        self
+    }
+}
+
+// ============================================================================
+// Client
+// ============================================================================
+
+/// Metrics buffered until the next flush.
+#[derive(Default)]
+struct Buffer {
+    /// Sums, by name and tags
+    counters: HashMap<(String, String), i64>,
+    /// Lines of timer and gauge samples
+    samples: Vec<String>,
+}
+
+/// Buffers metrics and pushes them to the agent.
+pub struct StatsdClient {
+    config: StatsdConfig,
+    socket: UdpSocket,
+    target: SocketAddr,
+    /// Tags sent with every metric, formatted
+    global_tags: Vec<String>,
+    buffer: Mutex<Buffer>,
+    paths: RwLock<HashSet<String>>,
+    packets_sent: AtomicU64,
+    send_errors: AtomicU64,
+}
+
+impl StatsdClient {
+    /// Create a client and start flushing every `flush_interval`.
+    pub fn new(config: StatsdConfig) -> std::io::Result<Arc<Self>> {
+        let target = (config.host.as_str(), config.port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("StatsD host '{}' has no address", config.host),
+                )
+            })?;
+        let socket = UdpSocket::bind(if target.is_ipv4() {
+            "0.0.0.0:0"
+        } else {
+            "[::]:0"
+        })?;
+        socket.set_nonblocking(true)?;
+        let global_tags = config.tags.iter().map(|(k, v)| format_tag(k, v)).collect();
+        let client = Arc::new(Self {
+            config,
+            socket,
+            target,
+            global_tags,
+            buffer: Mutex::new(Buffer::default()),
+            paths: RwLock::new(HashSet::new()),
+            packets_sent: AtomicU64::new(0),
+            send_errors: AtomicU64::new(0),
+        });
+        let flusher = Arc::downgrade(&client);
+        let interval = client.config.flush_interval;
+        std::thread::spawn(move || Self::run_flusher(flusher, interval));
+        Ok(client)
+    }
+
+    /// The configuration.
+    pub fn config(&self) -> &StatsdConfig {
+        &self.config
+    }
+
+    /// Add `value` to a counter.
+    pub fn increment(&self, name: &str, value: i64, tags: &[(&str, &str)]) {
+        let key = (self.metric_name(name), self.format_tags(tags));
+        *self.buffer.lock().counters.entry(key).or_insert(0) += value;
+    }
+
+    /// Record a duration, in milliseconds.
+    pub fn timing(&self, name: &str, millis: f64, tags: &[(&str, &str)]) {
+        self.sample(name, millis, "ms", tags);
+    }
+
+    /// Set a gauge.
+    pub fn gauge(&self, name: &str, value: f64, tags: &[(&str, &str)]) {
+        self.sample(name, value, "g", tags);
+    }
+
+    /// Send the buffered metrics now.
+    pub fn flush(&self) {
+        let buffer = std::mem::take(&mut *self.buffer.lock());
+        let mut lines: Vec<String> = buffer
+            .counters
+            .into_iter()
+            .map(|((name, tags), value)| format!("{name}:{value}|c{tags}"))
+            .collect();
+        lines.sort();
+        lines.extend(buffer.samples);
+        for packet in pack(&lines, self.config.max_packet_size) {
+            match self.socket.send_to(packet.as_bytes(), self.target) {
+                Ok(_) => self.packets_sent.fetch_add(1, Ordering::Relaxed),
+                Err(_) => self.send_errors.fetch_add(1, Ordering::Relaxed),
+            };
+        }
+    }
+
+    /// Packets sent and failed sends, for diagnostics.
+    pub fn packet_counts(&self) -> (u64, u64) {
+        (
+            self.packets_sent.load(Ordering::Relaxed),
+            self.send_errors.load(Ordering::Relaxed),
+        )
+    }
+
+    fn sample(&self, name: &str, value: f64, kind: &str, tags: &[(&str, &str)]) {
+        let line = format!(
+            "{}:{}|{kind}{}",
+            self.metric_name(name),
+            format_value(value),
+            self.format_tags(tags)
+        );
+        let full = {
+            let mut buffer = self.buffer.lock();
+            buffer.samples.push(line);
+            buffer.samples.len() >= MAX_BUFFERED_SAMPLES
+        };
+        if full {
+            self.flush();
+        }
+    }
+
+    fn metric_name(&self, name: &str) -> String {
+        let name = sanitize(name, &[':', '|', '@', '#', ',', ' ', '\n']);
+        if self.config.prefix.is_empty() {
+            name
+        } else {
+            format!("{}.{name}", self.config.prefix)
+        }
+    }
+
+    /// `|#tag,tag` with the global tags, or nothing for plain StatsD.
+    fn format_tags(&self, tags: &[(&str, &str)]) -> String {
+        if !self.config.dogstatsd || (tags.is_empty() && self.global_tags.is_empty()) {
+            return String::new();
+        }
+        let tags: Vec<String> = self
+            .global_tags
+            .iter()
+            .cloned()
+            .chain(tags.iter().map(|(k, v)| format_tag(k, v)))
+            .collect();
+        format!("|#{}", tags.join(","))
+    }
+
+    /// The `path` tag, capped at `max_path_cardinality` distinct paths.
+    fn path_tag(&self, path: &str) -> String {
+        if self.paths.read().contains(path) {
+            return path.to_string();
+        }
+        let mut paths = self.paths.write();
+        if paths.len() >= self.config.max_path_cardinality {
+            return "OTHER".to_string();
+        }
+        paths.insert(path.to_string());
+        path.to_string()
+    }
+
+    fn run_flusher(client: Weak<Self>, interval: Duration) {
+        loop {
+            std::thread::sleep(interval);
+            let Some(client) = client.upgrade() else {
+                return;
+            };
+            client.flush();
+        }
+    }
+}
+
+impl Drop for StatsdClient {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+/// Format a DogStatsD `key:value` tag.
+fn format_tag(key: &str, value: &str) -> String {
+    let reserved = [',', '|', '#', '\n'];
+    format!(
+        "{}:{}",
+        sanitize(key, &[':', ',', '|', '#', '\n']),
+        sanitize(value, &reserved)
+    )
+}
+
+/// Replace the characters that would break the line protocol.
+fn sanitize(text: &str, reserved: &[char]) -> String {
+    text.chars()
+        .map(|c| if reserved.contains(&c) { '_' } else { c })
+        .collect()
+}
+
+/// A value with up to three decimals, without trailing zeros.
+fn format_value(value: f64) -> String {
+    let text = format!("{value:.3}");
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+/// Join `lines` into packets of at most `max_size` bytes. A line longer
+/// than that is sent alone.
+fn pack(lines: &[String], max_size: usize) -> Vec<String> {
+    let mut packets = Vec::new();
+    let mut packet = String::new();
+    for line in lines {
+        if !packet.is_empty() && packet.len() + 1 + line.len() > max_size {
+            packets.push(std::mem::take(&mut packet));
+        }
+        if !packet.is_empty() {
+            packet.push('\n');
+        }
+        packet.push_str(line);
+    }
+    if !packet.is_empty() {
+        packets.push(packet);
+    }
+    packets
+}
+
+// ============================================================================
+// Middleware
+// ============================================================================
+
+/// Pushes request metrics to StatsD once each response has been sent.
+pub struct StatsdMiddleware {
+    client: Arc<StatsdClient>,
+}
+
+impl StatsdMiddleware {
+    /// Measure requests into `client`.
+    pub fn new(client: Arc<StatsdClient>) -> Self {
+        Self { client }
+    }
+}
+
+impl Middleware for StatsdMiddleware {
+    fn observes_response_end(&self) -> bool {
+        true
+    }
+
+    fn on_response_end(&self, end: &ResponseEnd) {
+        let config = self.client.config();
+        if config
+            .exclude_paths
+            .iter()
+            .any(|pattern| path_matches_skip(&end.path, pattern))
+        {
+            return;
+        }
+        let status = end.status.to_string();
+        let path = self.client.path_tag(&end.path);
+        let tags = [
+            ("method", end.method.as_str()),
+            ("status", status.as_str()),
+            ("path", path.as_str()),
+        ];
+        self.client.increment("http.requests", 1, &tags);
+        self.client
+            .increment("http.response.bytes", end.bytes as i64, &tags);
+        if end.aborted {
+            self.client.increment("http.requests.aborted", 1, &tags);
+        }
+        self.client.timing(
+            "http.request.duration",
+            end.duration.as_secs_f64() * 1000.0,
+            &tags,
+        );
+    }
+
+    fn priority(&self) -> i32 {
+        -200
+    }
+
+    fn name(&self) -> &str {
+        "statsd"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn agent() -> (UdpSocket, u16) {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        let port = socket.local_addr().unwrap().port();
+        (socket, port)
+    }
+
+    fn receive(socket: &UdpSocket) -> String {
+        let mut buf = [0u8; 2048];
+        let len = socket.recv(&mut buf).unwrap();
+        String::from_utf8_lossy(&buf[..len]).into_owned()
+    }
+
+    #[test]
+    fn test_dogstatsd_lines() {
+        let (socket, port) = agent();
+        let config = StatsdConfig::new("127.0.0.1", port)
+            .dogstatsd(true)
+            .tag("env", "prod")
+            .flush_interval(Duration::from_secs(3600));
+        let client = StatsdClient::new(config).unwrap();
+
+        client.increment("signups", 1, &[("plan", "pro")]);
+        client.increment("signups", 2, &[("plan", "pro")]);
+        client.timing("db.query", 12.5, &[]);
+        client.gauge("queue.depth", 7.0, &[("queue", "a,b")]);
+        client.flush();
+
+        assert_eq!(
+            receive(&socket),
+            "cello.signups:3|c|#env:prod,plan:pro\n\
+             cello.db.query:12.5|ms|#env:prod\n\
+             cello.queue.depth:7|g|#env:prod,queue:a_b"
+        );
+        assert_eq!(client.packet_counts(), (1, 0));
+    }
+
+    #[test]
+    fn test_plain_statsd_and_packets() {
+        let (socket, port) = agent();
+        let config = StatsdConfig::new("127.0.0.1", port)
+            .prefix("")
+            .tag("env", "prod")
+            .max_packet_size(20)
+            .flush_interval(Duration::from_secs(3600));
+        let client = StatsdClient::new(config).unwrap();
+
+        // Plain StatsD has no tags
+        client.increment("a.requests", 1, &[("path", "/")]);
+        client.increment("b.requests", 4, &[]);
+        client.flush();
+        assert_eq!(receive(&socket), "a.requests:1|c");
+        assert_eq!(receive(&socket), "b.requests:4|c");
+
+        assert_eq!(
+            pack(&["abc".to_string(), "de".to_string(), "f".repeat(9)], 8),
+            vec!["abc\nde".to_string(), "f".repeat(9)]
+        );
+    }
+
+    #[test]
+    fn test_request_metrics() {
+        let (socket, port) = agent();
+        let config = StatsdConfig::new("127.0.0.1", port)
+            .dogstatsd(true)
+            .exclude_path("/health")
+            .flush_interval(Duration::from_secs(3600));
+        let client = StatsdClient::new(config).unwrap();
+        let middleware = StatsdMiddleware::new(client.clone());
+
+        let mut end = ResponseEnd {
+            method: "GET".to_string(),
+            path: "/users".to_string(),
+            status: 200,
+            bytes: 512,
+            duration: Duration::from_millis(25),
+            aborted: false,
+            extensions: crate::request::Extensions::new(),
+        };
+        middleware.on_response_end(&end);
+        end.path = "/health".to_string();
+        middleware.on_response_end(&end);
+        client.flush();
+
+        let packet = receive(&socket);
+        let tags = "|#method:GET,status:200,path:/users";
+        assert!(packet.contains(&format!("cello.http.requests:1|c{tags}")));
+        assert!(packet.contains(&format!("cello.http.response.bytes:512|c{tags}")));
+        assert!(packet.contains(&format!("cello.http.request.duration:25|ms{tags}")));
+        assert!(!packet.contains("/health"));
+    }
+}
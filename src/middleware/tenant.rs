@@ -0,0 +1,491 @@
+//! Multi-tenancy for Cello.
+//!
+//! Provides:
+//! - Tenant resolution from the subdomain, a header or a path parameter
+//! - A tenant context on the request (`request.tenant`)
+//! - Per-tenant resources injected by name: the `"database"` pool, a
+//!   `"cache"` whose keys are prefixed with the tenant, and `"tenant"`
+//! - Per-tenant rate limits
+//!
+//! # Example
+//! ```python
+//! app.enable_cache()
+//! app.enable_tenancy(resolve=["subdomain", "header"], base_domain="example.com")
+//! app.add_tenant("acme", database=DatabaseConfig("postgres://.../acme"),
+//!                rate_limit=RateLimitConfig.token_bucket(capacity=100, refill_rate=10))
+//!
+//! @app.get("/orders")
+//! async def orders(request, db=Depends("database"), tenant=Depends("tenant")):
+//!     return await db.fetch_all("SELECT * FROM orders")
+//! ```
+
+use parking_lot::RwLock;
+use pyo3::prelude::*;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use super::memory_cache::{MemoryCache, PyCache};
+use super::rate_limit::{RateLimitConfig, RateLimitStore, TokenBucketConfig, TokenBucketStore};
+use super::sql::{PyDatabasePool, SqlPool};
+use super::{path_matches_skip, Middleware, MiddlewareAction, MiddlewareError, MiddlewareResult};
+use crate::dependency::{PyDependencyCache, RequestScope};
+use crate::request::Request;
+use crate::response::Response;
+
+/// Request context key holding the resolved tenant.
+pub const TENANT_CONTEXT_KEY: &str = "tenant";
+
+// ============================================================================
+// Resolution
+// ============================================================================
+
+/// Where the tenant is read from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TenantSource {
+    /// The first label of the host, e.g. `acme` in `acme.example.com`.
+    Subdomain,
+    /// A request header.
+    Header(String),
+    /// A route path parameter, e.g. `tenant` in `/t/{tenant}/orders`.
+    PathParam(String),
+}
+
+impl TenantSource {
+    fn name(&self) -> &'static str {
+        match self {
+            TenantSource::Subdomain => "subdomain",
+            TenantSource::Header(_) => "header",
+            TenantSource::PathParam(_) => "path",
+        }
+    }
+}
+
+impl FromStr for TenantSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "subdomain" => Ok(TenantSource::Subdomain),
+            "header" => Ok(TenantSource::Header("x-tenant-id".to_string())),
+            "path" => Ok(TenantSource::PathParam("tenant".to_string())),
+            other => Err(format!(
+                "unknown tenant source '{other}' (expected subdomain, header or path)"
+            )),
+        }
+    }
+}
+
+/// Tenant resolution settings.
+#[derive(Debug, Clone)]
+pub struct TenancyConfig {
+    /// Sources tried in order.
+    pub sources: Vec<TenantSource>,
+    /// Domain under which subdomains name tenants. Without it the
+    /// subdomain source never resolves.
+    pub base_domain: Option<String>,
+    /// Reject requests without a tenant (400).
+    pub required: bool,
+    /// Accept tenants that were not registered with `add_tenant` (they
+    /// get no database or rate limit of their own).
+    pub allow_unknown: bool,
+    /// Paths that skip tenant resolution, with everything below them.
+    pub exclude_paths: Vec<String>,
+}
+
+impl Default for TenancyConfig {
+    fn default() -> Self {
+        Self {
+            sources: vec![
+                TenantSource::Subdomain,
+                TenantSource::Header("x-tenant-id".to_string()),
+            ],
+            base_domain: None,
+            required: true,
+            allow_unknown: false,
+            exclude_paths: vec!["/health".to_string(), "/metrics".to_string()],
+        }
+    }
+}
+
+impl TenancyConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn sources(mut self, sources: Vec<TenantSource>) -> Self {
+        self.sources = sources;
+        self
+    }
+
+    pub fn base_domain(mut self, domain: &str) -> Self {
+        self.base_domain = Some(domain.trim_start_matches('.').to_ascii_lowercase());
+        self
+    }
+
+    pub fn required(mut self, required: bool) -> Self {
+        self.required = required;
+        self
+    }
+
+    pub fn allow_unknown(mut self, allow: bool) -> Self {
+        self.allow_unknown = allow;
+        self
+    }
+
+    pub fn exclude_path(mut self, path: &str) -> Self {
+        self.exclude_paths.push(path.to_string());
+        self
+    }
+
+    /// The tenant named by the host's subdomain below `base_domain`.
+    fn subdomain(&self, host: &str) -> Option<String> {
+        let base = self.base_domain.as_deref()?;
+        let host = host.split(':').next()?.to_ascii_lowercase();
+        let label = host.strip_suffix(base)?.strip_suffix('.')?.to_string();
+        (!label.is_empty() && !label.contains('.') && label != "www").then_some(label)
+    }
+
+    /// Resolve the tenant id and the source that named it.
+    pub fn resolve(&self, request: &Request) -> Option<(String, &'static str)> {
+        self.sources.iter().find_map(|source| {
+            let id = match source {
+                TenantSource::Subdomain => request
+                    .headers
+                    .get("host")
+                    .and_then(|host| self.subdomain(host)),
+                TenantSource::Header(name) => request.headers.get(name).map(str::to_string),
+                TenantSource::PathParam(name) => request.params.get(name).cloned(),
+            }?;
+            let id = id.trim().to_string();
+            (!id.is_empty()).then_some((id, source.name()))
+        })
+    }
+}
+
+// ============================================================================
+// Registry
+// ============================================================================
+
+/// A registered tenant and its resources.
+pub struct Tenant {
+    /// Tenant identifier.
+    pub id: String,
+    /// The tenant's own SQL pool, injected as `"database"`.
+    pub database: Option<Arc<SqlPool>>,
+    /// Prefix of the tenant's cache keys.
+    pub cache_prefix: String,
+    /// The tenant's request rate limit.
+    pub rate_limit: Option<TokenBucketConfig>,
+    /// Application data exposed on the tenant context.
+    pub metadata: serde_json::Value,
+}
+
+impl Tenant {
+    pub fn new(id: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            database: None,
+            cache_prefix: format!("tenant:{id}:"),
+            rate_limit: None,
+            metadata: serde_json::Value::Null,
+        }
+    }
+
+    pub fn with_database(mut self, pool: Arc<SqlPool>) -> Self {
+        self.database = Some(pool);
+        self
+    }
+
+    pub fn with_cache_prefix(mut self, prefix: &str) -> Self {
+        self.cache_prefix = prefix.to_string();
+        self
+    }
+
+    pub fn with_rate_limit(mut self, limit: TokenBucketConfig) -> Self {
+        self.rate_limit = Some(limit);
+        self
+    }
+
+    pub fn with_metadata(mut self, metadata: serde_json::Value) -> Self {
+        self.metadata = metadata;
+        self
+    }
+}
+
+/// Registered tenants, shared by the middleware and the request scope.
+#[derive(Default)]
+pub struct TenantRegistry {
+    tenants: RwLock<HashMap<String, Arc<Tenant>>>,
+}
+
+impl TenantRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register or replace a tenant.
+    pub fn insert(&self, tenant: Tenant) {
+        self.tenants
+            .write()
+            .insert(tenant.id.clone(), Arc::new(tenant));
+    }
+
+    pub fn remove(&self, id: &str) -> bool {
+        self.tenants.write().remove(id).is_some()
+    }
+
+    pub fn get(&self, id: &str) -> Option<Arc<Tenant>> {
+        self.tenants.read().get(id).cloned()
+    }
+
+    /// Registered tenant ids, sorted.
+    pub fn ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self.tenants.read().keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+}
+
+// ============================================================================
+// Middleware
+// ============================================================================
+
+/// Resolves the tenant of each request and enforces its rate limit.
+pub struct TenantMiddleware {
+    config: TenancyConfig,
+    registry: Arc<TenantRegistry>,
+    limits: TokenBucketStore,
+}
+
+impl TenantMiddleware {
+    pub fn new(config: TenancyConfig, registry: Arc<TenantRegistry>) -> Self {
+        Self {
+            config,
+            registry,
+            limits: TokenBucketStore::new(),
+        }
+    }
+
+    fn rate_limited(&self, tenant: &Tenant) -> Option<Response> {
+        let limit = tenant.rate_limit.clone()?;
+        let state = self
+            .limits
+            .check(&tenant.id, &RateLimitConfig::TokenBucket(limit));
+        if !state.exceeded {
+            return None;
+        }
+        let mut response = Response::error(429, "Tenant rate limit exceeded");
+        response.set_header("X-RateLimit-Limit", &state.limit.to_string());
+        response.set_header("X-RateLimit-Remaining", &state.remaining.to_string());
+        response.set_header("X-RateLimit-Reset", &state.reset.to_string());
+        Some(response)
+    }
+}
+
+impl Middleware for TenantMiddleware {
+    fn before(&self, request: &mut Request) -> MiddlewareResult {
+        if self
+            .config
+            .exclude_paths
+            .iter()
+            .any(|path| path_matches_skip(&request.path, path))
+        {
+            return Ok(MiddlewareAction::Continue);
+        }
+
+        let Some((id, source)) = self.config.resolve(request) else {
+            if self.config.required {
+                return Err(MiddlewareError::bad_request("Tenant could not be resolved"));
+            }
+            return Ok(MiddlewareAction::Continue);
+        };
+
+        let tenant = self.registry.get(&id);
+        let metadata = match &tenant {
+            Some(tenant) => {
+                if let Some(response) = self.rate_limited(tenant) {
+                    return Ok(MiddlewareAction::Stop(response));
+                }
+                tenant.metadata.clone()
+            }
+            None if self.config.allow_unknown => serde_json::Value::Null,
+            None => {
+                return Err(MiddlewareError::not_found(&format!(
+                    "Unknown tenant '{id}'"
+                )))
+            }
+        };
+
+        request.set_context_internal(
+            TENANT_CONTEXT_KEY,
+            serde_json::json!({
+                "id": id,
+                "source": source,
+                "metadata": metadata,
+            }),
+        );
+        Ok(MiddlewareAction::Continue)
+    }
+
+    fn priority(&self) -> i32 {
+        -70 // Run before sessions, auth and rate limits, which may key on the tenant
+    }
+
+    fn name(&self) -> &str {
+        "tenant"
+    }
+}
+
+/// The id of the request's tenant, if one was resolved.
+pub fn tenant_id(request: &Request) -> Option<&str> {
+    request
+        .context
+        .get(TENANT_CONTEXT_KEY)
+        .and_then(|tenant| tenant.get("id"))
+        .and_then(|id| id.as_str())
+}
+
+// ============================================================================
+// Dependency Scope
+// ============================================================================
+
+/// Injects the request's tenant and its resources.
+pub struct TenantScope {
+    registry: Arc<TenantRegistry>,
+    cache: Option<Arc<MemoryCache<PyObject>>>,
+}
+
+impl TenantScope {
+    pub fn new(registry: Arc<TenantRegistry>, cache: Option<Arc<MemoryCache<PyObject>>>) -> Self {
+        Self { registry, cache }
+    }
+}
+
+impl RequestScope for TenantScope {
+    fn dependencies(&self, py: Python<'_>, request: &Request) -> PyResult<PyDependencyCache> {
+        let mut scope = PyDependencyCache::new();
+        let Some(context) = request.context.get(TENANT_CONTEXT_KEY) else {
+            return Ok(scope);
+        };
+        let Some(id) = tenant_id(request) else {
+            return Ok(scope);
+        };
+        scope.insert(
+            "tenant".to_string(),
+            crate::json::json_to_python(py, context)?,
+        );
+
+        let tenant = self.registry.get(id);
+        if let Some(pool) = tenant.as_ref().and_then(|t| t.database.clone()) {
+            scope.insert(
+                "database".to_string(),
+                PyDatabasePool::new(pool).into_py(py),
+            );
+        }
+        if let Some(cache) = &self.cache {
+            let prefix = match &tenant {
+                Some(tenant) => tenant.cache_prefix.clone(),
+                None => format!("tenant:{id}:"),
+            };
+            scope.insert(
+                "cache".to_string(),
+                PyCache::with_prefix(cache.clone(), &prefix).into_py(py),
+            );
+        }
+        Ok(scope)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(headers: &[(&str, &str)]) -> Request {
+        let mut request = Request::new("GET", "/orders");
+        for (name, value) in headers {
+            request.headers.insert(name, value);
+        }
+        request
+    }
+
+    #[test]
+    fn test_resolve_sources() {
+        let config = TenancyConfig::new().base_domain("example.com");
+        assert_eq!(
+            config.resolve(&request(&[("host", "acme.example.com:8000")])),
+            Some(("acme".to_string(), "subdomain"))
+        );
+        assert_eq!(
+            config.resolve(&request(&[
+                ("host", "example.com"),
+                ("x-tenant-id", "globex")
+            ])),
+            Some(("globex".to_string(), "header"))
+        );
+        assert_eq!(
+            config.resolve(&request(&[("host", "www.example.com")])),
+            None
+        );
+
+        // Without a base domain, hosts never name tenants
+        let open = TenancyConfig::new();
+        assert_eq!(open.subdomain("acme.example.com"), None);
+        assert_eq!(
+            open.resolve(&request(&[
+                ("host", "api.example.com"),
+                ("x-tenant-id", "acme")
+            ])),
+            Some(("acme".to_string(), "header"))
+        );
+
+        let path = TenancyConfig::new().sources(vec!["path".parse().unwrap()]);
+        let mut req = request(&[]);
+        req.params
+            .insert("tenant".to_string(), "initech".to_string());
+        assert_eq!(path.resolve(&req), Some(("initech".to_string(), "path")));
+    }
+
+    #[test]
+    fn test_middleware() {
+        let registry = Arc::new(TenantRegistry::new());
+        registry.insert(
+            Tenant::new("acme")
+                .with_rate_limit(TokenBucketConfig::new(1, 0.0))
+                .with_metadata(serde_json::json!({"plan": "pro"})),
+        );
+        let mw = TenantMiddleware::new(TenancyConfig::new(), registry);
+
+        let mut req = request(&[("x-tenant-id", "acme")]);
+        assert!(matches!(
+            mw.before(&mut req),
+            Ok(MiddlewareAction::Continue)
+        ));
+        assert_eq!(tenant_id(&req), Some("acme"));
+        assert_eq!(req.context["tenant"]["metadata"]["plan"], "pro");
+
+        // The tenant's one-request budget is spent.
+        let mut req = request(&[("x-tenant-id", "acme")]);
+        match mw.before(&mut req) {
+            Ok(MiddlewareAction::Stop(response)) => assert_eq!(response.status, 429),
+            _ => panic!("expected rate limit"),
+        }
+
+        let mut req = request(&[("x-tenant-id", "unknown")]);
+        assert_eq!(mw.before(&mut req).err().map(|e| e.status), Some(404));
+        let mut req = request(&[]);
+        assert_eq!(mw.before(&mut req).err().map(|e| e.status), Some(400));
+        let mut req = Request::new("GET", "/health");
+        assert!(matches!(
+            mw.before(&mut req),
+            Ok(MiddlewareAction::Continue)
+        ));
+        let mut req = Request::new("GET", "/metrics/tenants");
+        assert!(matches!(
+            mw.before(&mut req),
+            Ok(MiddlewareAction::Continue)
+        ));
+        let mut req = Request::new("GET", "/healthcheck-admin");
+        assert_eq!(mw.before(&mut req).err().map(|e| e.status), Some(400));
+    }
+}
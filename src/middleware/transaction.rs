@@ -0,0 +1,107 @@
+//! Transaction-per-request for Cello.
+//!
+//! Routes opted in with `@atomic` get a transaction opened before the
+//! handler runs. The handler sees it in place of the pool as the
+//! `"database"` dependency (and as `"transaction"`), so every query it
+//! makes is one unit of work:
+//! - committed when the handler returns a 2xx or 3xx response
+//! - rolled back when it raises or returns a 4xx or 5xx response
+//!
+//! A handler may still commit or roll back itself; the transaction is
+//! then left alone.
+
+use pyo3::prelude::*;
+use std::sync::Arc;
+
+use super::database::DatabaseError;
+use super::sql::{IsolationLevel, PyDatabaseTransaction, SqlPool};
+use crate::dependency::PyDependencyCache;
+
+/// Transaction settings for a route.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TransactionPolicy {
+    /// Isolation level, or the database default.
+    pub isolation: Option<IsolationLevel>,
+}
+
+impl TransactionPolicy {
+    /// Create a policy using the database's default isolation level.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the isolation level.
+    pub fn with_isolation(mut self, isolation: IsolationLevel) -> Self {
+        self.isolation = Some(isolation);
+        self
+    }
+
+    /// Open the transaction for a request.
+    pub async fn begin(&self, pool: &Arc<SqlPool>) -> Result<RequestTransaction, DatabaseError> {
+        let tx = pool.begin_with(self.isolation).await?;
+        Ok(RequestTransaction {
+            handle: PyDatabaseTransaction::started(pool.clone(), tx),
+        })
+    }
+}
+
+/// Whether a handler outcome commits: `None` means the handler raised.
+pub fn should_commit(status: Option<u16>) -> bool {
+    matches!(status, Some(200..=399))
+}
+
+/// A transaction opened for one request.
+pub struct RequestTransaction {
+    handle: PyDatabaseTransaction,
+}
+
+impl RequestTransaction {
+    /// Dependencies the handler resolves to this transaction.
+    pub fn dependencies(&self, py: Python<'_>) -> PyResult<PyDependencyCache> {
+        let tx: PyObject = Py::new(py, self.handle.clone())?.into_py(py);
+        Ok(PyDependencyCache::from([
+            ("database".to_string(), tx.clone_ref(py)),
+            ("transaction".to_string(), tx),
+        ]))
+    }
+
+    /// Commit or roll back for the handler's response status (`None` if it
+    /// raised). Returns whether the transaction was committed.
+    pub async fn finish(self, status: Option<u16>) -> Result<bool, DatabaseError> {
+        let commit = should_commit(status);
+        let settled = self.handle.settling(commit).await?;
+        Ok(settled && commit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::database::DatabaseConfig;
+
+    #[test]
+    fn test_should_commit() {
+        assert!(should_commit(Some(200)));
+        assert!(should_commit(Some(302)));
+        assert!(!should_commit(Some(422)));
+        assert!(!should_commit(Some(500)));
+        assert!(!should_commit(None));
+    }
+
+    #[tokio::test]
+    async fn test_finish_by_status() {
+        let config = DatabaseConfig::new("sqlite::memory:").pool_size(1);
+        let pool = Arc::new(SqlPool::connect(&config).await.unwrap());
+        let policy = TransactionPolicy::new().with_isolation(IsolationLevel::Serializable);
+
+        for (status, committed) in [(Some(201), true), (Some(500), false), (None, false)] {
+            let tx = policy.begin(&pool).await.unwrap();
+            assert_eq!(tx.finish(status).await.unwrap(), committed);
+        }
+
+        // A transaction the handler already finished is left alone.
+        let tx = policy.begin(&pool).await.unwrap();
+        assert!(tx.handle.settling(false).await.unwrap());
+        assert!(!tx.finish(Some(200)).await.unwrap());
+    }
+}
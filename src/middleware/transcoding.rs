@@ -0,0 +1,574 @@
+//! gRPC <-> REST transcoding for Cello Framework.
+//!
+//! Serves unary gRPC methods as REST endpoints using `google.api.http` style
+//! rules: the request message is built from the JSON body, path parameters
+//! and query string, dispatched through the `GrpcServer`, and the response
+//! message is returned as JSON. One service definition therefore answers both
+//! gRPC and REST clients from the same process.
+//!
+//! # Example
+//! ```python
+//! # service Catalog {
+//! #   rpc Get(GetItem) returns (Item) {
+//! #     option (google.api.http) = { get: "/v1/items/{id}" };
+//! #   }
+//! # }
+//! app.load_proto_descriptors("protos/shop.desc")
+//! app.enable_grpc()
+//! app.register_grpc_service(Catalog(name="shop.Catalog"))
+//!
+//! # GET /v1/items/7  ->  shop.Catalog/Get {"id": 7}
+//! ```
+
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+use super::grpc::{GrpcError, GrpcRequest, GrpcResponse, GrpcServer, GrpcStatus};
+use super::protobuf::{DescriptorPool, FieldType, HttpRule, MessageDescriptor, ScalarType};
+use crate::request::Request;
+use crate::response::Response;
+
+/// Prefix of HTTP headers carrying gRPC metadata, in both directions.
+pub const METADATA_HEADER_PREFIX: &str = "grpc-metadata-";
+
+// ============================================================================
+// Path Templates
+// ============================================================================
+
+/// Convert an HttpRule path template to a Cello route path.
+///
+/// `{field}` and `{field=*}` match one segment; a trailing `{field=**}`
+/// matches the rest of the path. Literal sub-patterns such as
+/// `{name=shelves/*}` and custom verbs (`:cancel`) are not supported.
+pub fn route_path(template: &str) -> Result<String, String> {
+    let unsupported = |reason: &str| format!("Unsupported path template {template}: {reason}");
+    if !template.starts_with('/') {
+        return Err(unsupported("must start with '/'"));
+    }
+
+    let mut route = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        route.push_str(check_literal(&rest[..start]).map_err(&unsupported)?);
+        let end = rest[start..]
+            .find('}')
+            .map(|i| start + i)
+            .ok_or_else(|| unsupported("unclosed '{'"))?;
+        let (field, pattern) = rest[start + 1..end]
+            .split_once('=')
+            .unwrap_or((&rest[start + 1..end], "*"));
+        if field.is_empty()
+            || !field
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+        {
+            return Err(unsupported("invalid field path"));
+        }
+        rest = &rest[end + 1..];
+        match pattern {
+            "*" => route.push_str(&format!("{{{field}}}")),
+            "**" if rest.is_empty() => route.push_str(&format!("{{*{field}}}")),
+            "**" => return Err(unsupported("'**' must end the path")),
+            _ => return Err(unsupported("only '*' and '**' variables are supported")),
+        }
+    }
+    route.push_str(check_literal(rest).map_err(unsupported)?);
+    Ok(route)
+}
+
+fn check_literal(literal: &str) -> Result<&str, &'static str> {
+    if literal.contains(':') {
+        Err("custom verbs are not supported")
+    } else if literal.contains('}') {
+        Err("unmatched '}'")
+    } else {
+        Ok(literal)
+    }
+}
+
+// ============================================================================
+// Request Messages
+// ============================================================================
+
+fn invalid_argument(message: &str) -> GrpcStatus {
+    GrpcStatus::error(GrpcError::InvalidMessage.code(), message)
+}
+
+/// Set a dotted field path in a JSON object, creating intermediate objects.
+fn set_path(target: &mut Value, path: &str, value: Value) {
+    let mut current = target;
+    let mut segments = path.split('.').peekable();
+    while let Some(segment) = segments.next() {
+        if !current.is_object() {
+            *current = Value::Object(Map::new());
+        }
+        let object = current.as_object_mut().expect("just made an object");
+        if segments.peek().is_none() {
+            object.insert(segment.to_string(), value);
+            return;
+        }
+        current = object
+            .entry(segment.to_string())
+            .or_insert_with(|| Value::Object(Map::new()));
+    }
+}
+
+/// Set `value` at `path`, appending to the array already there when both
+/// are arrays, as for a repeated field given more than once in the query.
+fn merge_path(target: &mut Value, path: &str, value: Value) {
+    let pointer = format!("/{}", path.replace('.', "/"));
+    if let (Some(Value::Array(existing)), Value::Array(items)) =
+        (target.pointer_mut(&pointer), &value)
+    {
+        existing.extend(items.iter().cloned());
+        return;
+    }
+    set_path(target, path, value);
+}
+
+/// Convert a path or query string value to JSON for the field at `path`.
+///
+/// Returns `Ok(None)` when the message has no such field.
+fn coerce(
+    pool: &DescriptorPool,
+    message: &MessageDescriptor,
+    path: &str,
+    raw: &str,
+) -> Result<Option<Value>, GrpcStatus> {
+    let mut message = message;
+    let mut segments = path.split('.').peekable();
+    while let Some(segment) = segments.next() {
+        let Some(field) = message.fields.iter().find(|f| f.name == segment) else {
+            return Ok(None);
+        };
+        if segments.peek().is_some() {
+            match (&field.field_type, field.repeated) {
+                (FieldType::Named(name), false) => match pool.message(name) {
+                    Some(nested) => {
+                        message = nested;
+                        continue;
+                    }
+                    None => return Ok(None),
+                },
+                _ => return Ok(None),
+            }
+        }
+
+        let value = match &field.field_type {
+            FieldType::Scalar(ScalarType::Bool) => match raw {
+                "true" => Value::Bool(true),
+                "false" => Value::Bool(false),
+                _ => return Err(invalid_argument(&format!("{path} must be true or false"))),
+            },
+            FieldType::Named(name) if pool.enum_type(name).is_some() => raw
+                .parse::<i64>()
+                .map(Value::from)
+                .unwrap_or_else(|_| Value::from(raw)),
+            FieldType::Named(_) => {
+                return Err(invalid_argument(&format!(
+                    "{path} is a message and cannot be set from the URL"
+                )))
+            }
+            // Numeric scalars accept numeric strings when encoded
+            FieldType::Scalar(_) => Value::from(raw),
+        };
+        return Ok(Some(if field.repeated {
+            Value::Array(vec![value])
+        } else {
+            value
+        }));
+    }
+    Ok(None)
+}
+
+/// Build the request message for `rule` from an HTTP request.
+///
+/// The body fills the field named by `rule.body` (the whole message for `*`),
+/// path parameters fill their field paths, and query parameters fill any
+/// remaining fields unless the body maps to the whole message. A repeated
+/// field takes every value given for it, in order; unknown query parameters
+/// are ignored.
+pub fn build_request_message(
+    pool: &DescriptorPool,
+    input_type: &str,
+    rule: &HttpRule,
+    request: &Request,
+) -> Result<Value, GrpcStatus> {
+    let descriptor = pool
+        .message(input_type)
+        .ok_or_else(|| invalid_argument(&format!("Unknown message type {input_type}")))?;
+
+    let mut message = Value::Object(Map::new());
+    if let Some(field) = rule.body.as_deref() {
+        let body = if request.body.is_empty() {
+            Value::Object(Map::new())
+        } else {
+            serde_json::from_slice(&request.body)
+                .map_err(|e| invalid_argument(&format!("Invalid JSON body: {e}")))?
+        };
+        if field == "*" {
+            if !body.is_object() {
+                return Err(invalid_argument("Request body must be a JSON object"));
+            }
+            message = body;
+        } else {
+            set_path(&mut message, field, body);
+        }
+    }
+
+    for (name, raw) in &request.params {
+        let value = coerce(pool, descriptor, name, raw)?
+            .ok_or_else(|| invalid_argument(&format!("{input_type} has no field {name}")))?;
+        set_path(&mut message, name, value);
+    }
+
+    if rule.body.as_deref() != Some("*") {
+        let body_field = rule.body.as_deref().unwrap_or_default();
+        for (name, raw) in request.query_list() {
+            let in_body = !body_field.is_empty()
+                && (name == body_field || name.starts_with(&format!("{body_field}.")));
+            if in_body || request.params.contains_key(&name) {
+                continue;
+            }
+            if let Some(value) = coerce(pool, descriptor, &name, &raw)? {
+                merge_path(&mut message, &name, value);
+            }
+        }
+    }
+    Ok(message)
+}
+
+// ============================================================================
+// Responses
+// ============================================================================
+
+/// HTTP status for a gRPC status code, following the `google.rpc.Code` mapping.
+pub fn http_status_for(code: i32) -> u16 {
+    match code {
+        0 => 200,
+        1 => 499,
+        3 | 9 | 11 => 400,
+        4 => 504,
+        5 => 404,
+        6 | 10 => 409,
+        7 => 403,
+        8 => 429,
+        12 => 501,
+        14 => 503,
+        16 => 401,
+        _ => 500,
+    }
+}
+
+/// JSON error response with `code` and `message`.
+fn error_response(status: &GrpcStatus) -> Response {
+    Response::from_json_value(
+        serde_json::json!({"code": status.code, "message": status.message}),
+        http_status_for(status.code),
+    )
+}
+
+/// Convert a gRPC response to JSON, applying `rule.response_body`.
+fn render_response(
+    pool: &DescriptorPool,
+    output_type: &str,
+    rule: &HttpRule,
+    response: &GrpcResponse,
+) -> Response {
+    let mut http = if !response.is_ok() {
+        error_response(&response.status)
+    } else {
+        match pool.decode(output_type, &response.payload) {
+            Ok(mut value) => {
+                if let Some(field) = rule.response_body.as_deref() {
+                    for segment in field.split('.') {
+                        value = value.get(segment).cloned().unwrap_or(Value::Null);
+                    }
+                }
+                Response::from_json_value(value, 200)
+            }
+            Err(e) => error_response(&GrpcStatus::error(
+                GrpcError::Internal.code(),
+                &format!("Invalid {output_type} from handler: {e}"),
+            )),
+        }
+    };
+    for (key, value) in &response.metadata {
+        http.set_header(&format!("Grpc-Metadata-{key}"), value);
+    }
+    http
+}
+
+// ============================================================================
+// Transcoding
+// ============================================================================
+
+/// gRPC metadata forwarded from an HTTP request: `authorization`, plus
+/// `Grpc-Metadata-*` headers with the prefix removed.
+fn request_metadata(headers: &crate::request::Headers) -> HashMap<String, String> {
+    headers
+        .iter()
+        .filter_map(|(name, value)| {
+            if name == "authorization" {
+                Some((name.to_string(), value.to_string()))
+            } else {
+                name.strip_prefix(METADATA_HEADER_PREFIX)
+                    .map(|key| (key.to_string(), value.to_string()))
+            }
+        })
+        .collect()
+}
+
+/// Serve an HTTP request through a unary gRPC method using `rule`'s mapping.
+///
+/// gRPC errors become JSON `{"code", "message"}` bodies with the matching
+/// HTTP status; response metadata is returned as `Grpc-Metadata-*` headers.
+pub async fn transcode(
+    server: &GrpcServer,
+    service: &str,
+    method: &str,
+    rule: &HttpRule,
+    request: &Request,
+) -> Response {
+    let method_def = match server.resolve_method(service, method) {
+        Ok(method_def) => method_def,
+        Err(err) => return error_response(&err.to_status()),
+    };
+
+    let payload = {
+        let pool = server.descriptors().read();
+        if pool.message(&method_def.output_type).is_none() {
+            return error_response(&GrpcStatus::error(
+                GrpcError::MethodNotFound.code(),
+                &format!("{service}/{method} has no registered message types"),
+            ));
+        }
+        build_request_message(&pool, &method_def.input_type, rule, request).and_then(|message| {
+            pool.encode(&method_def.input_type, &message)
+                .map_err(|e| invalid_argument(&e.to_string()))
+        })
+    };
+    let payload = match payload {
+        Ok(payload) => payload,
+        Err(status) => return error_response(&status),
+    };
+
+    let mut grpc_request = GrpcRequest::new(service, method, payload);
+    grpc_request.metadata = request_metadata(&request.headers);
+    let response = server.handle_request(&grpc_request).await;
+
+    let pool = server.descriptors().read();
+    render_response(&pool, &method_def.output_type, rule, &response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::grpc::{GrpcConfig, GrpcHandler};
+    use crate::middleware::protobuf::tests::shop_descriptor_set;
+
+    fn request(params: &[(&str, &str)], query: &[(&str, &str)], body: &str) -> Request {
+        let pairs = |items: &[(&str, &str)]| {
+            items
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<HashMap<_, _>>()
+        };
+        Request::py_new(
+            "GET".to_string(),
+            "/".to_string(),
+            Some(pairs(params)),
+            Some(pairs(query)),
+            Some(pairs(&[("grpc-metadata-tenant", "acme")])),
+            Some(body.as_bytes().to_vec()),
+        )
+    }
+
+    #[test]
+    fn test_route_path() {
+        assert_eq!(route_path("/v1/items/{id}").unwrap(), "/v1/items/{id}");
+        assert_eq!(
+            route_path("/v1/{item.id=*}/tags").unwrap(),
+            "/v1/{item.id}/tags"
+        );
+        assert_eq!(route_path("/files/{path=**}").unwrap(), "/files/{*path}");
+        assert!(route_path("v1/items").is_err());
+        assert!(route_path("/v1/{name=shelves/*}").is_err());
+        assert!(route_path("/v1/{path=**}/x").is_err());
+        assert!(route_path("/v1/items/{id}:cancel").is_err());
+        assert!(route_path("/v1/{id").is_err());
+    }
+
+    #[test]
+    fn test_build_request_message() {
+        let mut pool = DescriptorPool::new();
+        pool.load_file_descriptor_set(&shop_descriptor_set())
+            .unwrap();
+        let update = pool
+            .service("shop.Catalog")
+            .unwrap()
+            .method("Update")
+            .unwrap();
+        let (patch, put) = (&update.http_rules[0], &update.http_rules[1]);
+
+        // Body field, path parameter and query parameters combine
+        let message = build_request_message(
+            &pool,
+            "shop.UpdateItem",
+            patch,
+            &request(
+                &[("item.id", "7")],
+                &[("notify", "true"), ("item.tags", "x"), ("unknown", "1")],
+                r#"{"tags": ["a"]}"#,
+            ),
+        )
+        .unwrap();
+        assert_eq!(
+            message,
+            serde_json::json!({"item": {"tags": ["a"], "id": "7"}, "notify": true})
+        );
+        assert!(pool.encode("shop.UpdateItem", &message).is_ok());
+
+        // Repeated fields keep every query value, in order
+        let mut repeated = request(&[("item.id", "7")], &[], "");
+        repeated.query_string = "item.tags=b&item.tags=a&notify=false&notify=true".to_string();
+        let get = HttpRule::new("GET", "/v1/items/{item.id}");
+        let message = build_request_message(&pool, "shop.UpdateItem", &get, &repeated).unwrap();
+        assert_eq!(
+            message,
+            serde_json::json!({"item": {"id": "7", "tags": ["b", "a"]}, "notify": true})
+        );
+
+        // A whole-message body ignores the query string
+        let message = build_request_message(
+            &pool,
+            "shop.UpdateItem",
+            put,
+            &request(
+                &[("item.id", "7")],
+                &[("notify", "true")],
+                r#"{"notify": false}"#,
+            ),
+        )
+        .unwrap();
+        assert_eq!(
+            message,
+            serde_json::json!({"notify": false, "item": {"id": "7"}})
+        );
+
+        let bad_bool = request(&[], &[("notify", "yes")], "");
+        let err = build_request_message(&pool, "shop.UpdateItem", patch, &bad_bool).unwrap_err();
+        assert_eq!(err.code, GrpcError::InvalidMessage.code());
+        let bad_json = request(&[], &[], "{");
+        assert!(build_request_message(&pool, "shop.UpdateItem", put, &bad_json).is_err());
+        let unknown_param = request(&[("nope", "1")], &[], "");
+        assert!(build_request_message(&pool, "shop.UpdateItem", patch, &unknown_param).is_err());
+    }
+
+    #[test]
+    fn test_http_status_for() {
+        assert_eq!(http_status_for(0), 200);
+        assert_eq!(http_status_for(GrpcError::InvalidMessage.code()), 400);
+        assert_eq!(http_status_for(GrpcError::ServiceNotFound.code()), 404);
+        assert_eq!(http_status_for(GrpcError::MethodNotFound.code()), 501);
+        assert_eq!(http_status_for(GrpcError::Unauthenticated.code()), 401);
+        assert_eq!(http_status_for(99), 500);
+    }
+
+    #[tokio::test]
+    async fn test_transcode() {
+        let server = GrpcServer::new(GrpcConfig::default());
+        server
+            .load_file_descriptor_set(&shop_descriptor_set())
+            .unwrap();
+        let descriptors = server.descriptors().clone();
+        let handler = GrpcHandler::sync(move |request: GrpcRequest| {
+            let pool = descriptors.read();
+            let input = pool
+                .decode("shop.UpdateItem", &request.payload)
+                .map_err(|_| GrpcError::InvalidMessage)?;
+            if input["item"]["id"] == 0 {
+                return Err(GrpcError::ServiceNotFound);
+            }
+            let payload = pool.encode("shop.Item", &input["item"]).unwrap();
+            let tenant = request.get_metadata("tenant").unwrap_or_default();
+            Ok(GrpcResponse::ok(payload).with_metadata("tenant", tenant))
+        });
+        let method = server
+            .resolve_method("shop.Catalog", "Update")
+            .unwrap()
+            .with_handler(handler);
+        server.add_method("shop.Catalog", method);
+
+        let rules = server
+            .descriptors()
+            .read()
+            .service("shop.Catalog")
+            .unwrap()
+            .method("Update")
+            .unwrap()
+            .http_rules
+            .clone();
+        let response = transcode(
+            &server,
+            "shop.Catalog",
+            "Update",
+            &rules[0],
+            &request(
+                &[("item.id", "7")],
+                &[],
+                r#"{"tags": ["a"], "status": "LIVE"}"#,
+            ),
+        )
+        .await;
+        assert_eq!(response.status, 200);
+        assert_eq!(
+            serde_json::from_slice::<Value>(&response.body()).unwrap(),
+            serde_json::json!({"id": 7, "tags": ["a"], "status": "LIVE"})
+        );
+        assert_eq!(
+            response.headers.get("Grpc-Metadata-tenant").unwrap(),
+            "acme"
+        );
+
+        // response_body selects a single field
+        let response = transcode(
+            &server,
+            "shop.Catalog",
+            "Update",
+            &rules[1],
+            &request(&[("item.id", "7")], &[], r#"{"item": {"tags": ["b"]}}"#),
+        )
+        .await;
+        assert_eq!(response.body(), br#"["b"]"#);
+
+        // gRPC errors map to HTTP statuses
+        let response = transcode(
+            &server,
+            "shop.Catalog",
+            "Update",
+            &rules[0],
+            &request(&[("item.id", "0")], &[], ""),
+        )
+        .await;
+        assert_eq!(response.status, 404);
+        let response = transcode(
+            &server,
+            "shop.Catalog",
+            "Get",
+            &rules[0],
+            &request(&[("id", "1")], &[], ""),
+        )
+        .await;
+        assert_eq!(response.status, 501);
+        let response = transcode(
+            &server,
+            "shop.Catalog",
+            "Update",
+            &rules[0],
+            &request(&[("item.id", "x")], &[], ""),
+        )
+        .await;
+        assert_eq!(response.status, 400);
+    }
+}
@@ -0,0 +1,638 @@
+//! Native request/response transformations.
+//!
+//! Transforms run in Rust before routing, so they can rewrite the path the
+//! router sees, add request headers, or answer a request outright without
+//! a Python handler ever being called. A transform may also rewrite the
+//! response on the way out.
+//!
+//! Built-in transforms are created by name from JSON options:
+//!
+//! | Name | Options |
+//! |------|---------|
+//! | `trailing_slash` | `mode` (`"strip"`/`"append"`), `redirect`, `status` |
+//! | `https_redirect` | `status`, `port`, `exclude_paths` |
+//! | `rewrite` | `prefix`, `to` |
+//! | `request_header` | `name`, `value`, `overwrite` |
+//! | `response_header` | `name`, `value`, `overwrite` |
+//! | `respond` | `path`, `status`, `body`, `content_type` |
+//!
+//! Crates embedding Cello can add their own with [`register`] and
+//! [`MiddlewareChain::add_transform`](super::MiddlewareChain::add_transform).
+
+use hyper::header::{HeaderName, HeaderValue};
+use hyper::http::request::Parts;
+use hyper::Uri;
+use parking_lot::RwLock;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+
+use super::{path_matches_skip, Middleware, MiddlewareAction, MiddlewareResult};
+use crate::request::Request;
+use crate::response::Response;
+
+/// Options a transform is created from.
+pub type TransformOptions = Map<String, Value>;
+
+/// Creates a transform from its options.
+pub type TransformFactory = fn(&TransformOptions) -> Result<Arc<dyn Transform>, String>;
+
+/// A native request/response transformation.
+pub trait Transform: Send + Sync {
+    /// Transform name for debugging.
+    fn name(&self) -> &str;
+
+    /// Rewrite the request head before routing, or answer it directly.
+    #[inline]
+    fn request(&self, _head: &mut Parts) -> Option<Response> {
+        None
+    }
+
+    /// Whether [`Transform::response`] does anything; response transforms
+    /// take the request off the handler fast path.
+    #[inline]
+    fn rewrites_response(&self) -> bool {
+        false
+    }
+
+    /// Rewrite the response before it is sent.
+    #[inline]
+    fn response(&self, _request: &Request, _response: &mut Response) {}
+}
+
+/// Runs a transform's response hook as the outermost after-middleware.
+pub(super) struct ResponseTransform(pub(super) Arc<dyn Transform>);
+
+impl Middleware for ResponseTransform {
+    fn after(&self, request: &Request, response: &mut Response) -> MiddlewareResult {
+        self.0.response(request, response);
+        Ok(MiddlewareAction::Continue)
+    }
+
+    fn priority(&self) -> i32 {
+        -200
+    }
+
+    fn name(&self) -> &str {
+        self.0.name()
+    }
+}
+
+fn registry() -> &'static RwLock<HashMap<String, TransformFactory>> {
+    static REGISTRY: OnceLock<RwLock<HashMap<String, TransformFactory>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        let mut factories: HashMap<String, TransformFactory> = HashMap::new();
+        factories.insert("trailing_slash".into(), TrailingSlash::from_options);
+        factories.insert("https_redirect".into(), HttpsRedirect::from_options);
+        factories.insert("rewrite".into(), PathRewrite::from_options);
+        factories.insert("request_header".into(), RequestHeader::from_options);
+        factories.insert("response_header".into(), ResponseHeader::from_options);
+        factories.insert("respond".into(), Respond::from_options);
+        RwLock::new(factories)
+    })
+}
+
+/// Register a transform factory under `name`, replacing any existing one.
+pub fn register(name: &str, factory: TransformFactory) {
+    registry().write().insert(name.to_string(), factory);
+}
+
+/// Create a registered transform by name.
+pub fn create(name: &str, options: &TransformOptions) -> Result<Arc<dyn Transform>, String> {
+    let factory = registry()
+        .read()
+        .get(name)
+        .copied()
+        .ok_or_else(|| format!("unknown transform '{name}'"))?;
+    factory(options).map_err(|e| format!("transform '{name}': {e}"))
+}
+
+/// Names of the registered transforms, sorted.
+pub fn names() -> Vec<String> {
+    let mut names: Vec<String> = registry().read().keys().cloned().collect();
+    names.sort();
+    names
+}
+
+// ============================================================================
+// Option helpers
+// ============================================================================
+
+/// Typed access to transform options that rejects unknown keys.
+struct Options<'a> {
+    options: &'a TransformOptions,
+}
+
+impl<'a> Options<'a> {
+    fn new(options: &'a TransformOptions, known: &[&str]) -> Result<Self, String> {
+        if let Some(key) = options.keys().find(|key| !known.contains(&key.as_str())) {
+            return Err(format!("unknown option '{key}'"));
+        }
+        Ok(Options { options })
+    }
+
+    fn str(&self, key: &str) -> Result<Option<&'a str>, String> {
+        match self.options.get(key) {
+            None | Some(Value::Null) => Ok(None),
+            Some(Value::String(s)) => Ok(Some(s)),
+            Some(_) => Err(format!("option '{key}' must be a string")),
+        }
+    }
+
+    fn required_str(&self, key: &str) -> Result<&'a str, String> {
+        self.str(key)?
+            .ok_or_else(|| format!("option '{key}' is required"))
+    }
+
+    fn bool(&self, key: &str, default: bool) -> Result<bool, String> {
+        match self.options.get(key) {
+            None | Some(Value::Null) => Ok(default),
+            Some(Value::Bool(b)) => Ok(*b),
+            Some(_) => Err(format!("option '{key}' must be a boolean")),
+        }
+    }
+
+    fn u16(&self, key: &str) -> Result<Option<u16>, String> {
+        match self.options.get(key) {
+            None | Some(Value::Null) => Ok(None),
+            Some(value) => value
+                .as_u64()
+                .and_then(|n| u16::try_from(n).ok())
+                .map(Some)
+                .ok_or_else(|| format!("option '{key}' must be a port or status number")),
+        }
+    }
+
+    fn status(&self, key: &str, default: u16) -> Result<u16, String> {
+        let status = self.u16(key)?.unwrap_or(default);
+        if (100..600).contains(&status) {
+            Ok(status)
+        } else {
+            Err(format!("option '{key}' must be an HTTP status code"))
+        }
+    }
+
+    fn redirect_status(&self, default: u16) -> Result<u16, String> {
+        let status = self.status("status", default)?;
+        if matches!(status, 301 | 302 | 303 | 307 | 308) {
+            Ok(status)
+        } else {
+            Err(format!("{status} is not a redirect status"))
+        }
+    }
+
+    fn strings(&self, key: &str) -> Result<Vec<String>, String> {
+        match self.options.get(key) {
+            None | Some(Value::Null) => Ok(Vec::new()),
+            Some(Value::Array(items)) => items
+                .iter()
+                .map(|item| {
+                    item.as_str()
+                        .map(str::to_string)
+                        .ok_or_else(|| format!("option '{key}' must be a list of strings"))
+                })
+                .collect(),
+            Some(_) => Err(format!("option '{key}' must be a list of strings")),
+        }
+    }
+}
+
+/// Replace the path of a request URI, keeping its query string.
+pub fn set_path(head: &mut Parts, path: &str) {
+    let path_and_query = match head.uri.query() {
+        Some(query) => format!("{path}?{query}"),
+        None => path.to_string(),
+    };
+    let mut parts = std::mem::take(&mut head.uri).into_parts();
+    parts.path_and_query = path_and_query.parse().ok();
+    head.uri = Uri::from_parts(parts).unwrap_or_default();
+}
+
+fn redirect(location: &str, status: u16) -> Response {
+    let mut response = Response::new(status);
+    response.set_header("Location", location);
+    response
+}
+
+fn with_query(path: &str, uri: &Uri) -> String {
+    match uri.query() {
+        Some(query) => format!("{path}?{query}"),
+        None => path.to_string(),
+    }
+}
+
+// ============================================================================
+// Built-in transforms
+// ============================================================================
+
+/// Canonicalize trailing slashes, by redirect or in place.
+pub struct TrailingSlash {
+    pub append: bool,
+    pub redirect: Option<u16>,
+}
+
+impl TrailingSlash {
+    fn from_options(options: &TransformOptions) -> Result<Arc<dyn Transform>, String> {
+        let options = Options::new(options, &["mode", "redirect", "status"])?;
+        let append = match options.str("mode")?.unwrap_or("strip") {
+            "strip" => false,
+            "append" => true,
+            other => return Err(format!("mode must be 'strip' or 'append', not '{other}'")),
+        };
+        let redirect = if options.bool("redirect", true)? {
+            Some(options.redirect_status(308)?)
+        } else {
+            None
+        };
+        Ok(Arc::new(TrailingSlash { append, redirect }))
+    }
+
+    /// The canonical form of `path`, if it differs.
+    pub fn canonical(&self, path: &str) -> Option<String> {
+        if self.append {
+            let last = path.rsplit('/').next().unwrap_or("");
+            // Leave file-like paths such as `/static/app.js` alone
+            (!path.ends_with('/') && !last.contains('.')).then(|| format!("{path}/"))
+        } else {
+            let trimmed = path.trim_end_matches('/');
+            (trimmed.len() < path.len()).then(|| {
+                if trimmed.is_empty() {
+                    "/".to_string()
+                } else {
+                    trimmed.to_string()
+                }
+            })
+        }
+        .filter(|canonical| canonical != path)
+    }
+}
+
+impl Transform for TrailingSlash {
+    fn name(&self) -> &str {
+        "trailing_slash"
+    }
+
+    fn request(&self, head: &mut Parts) -> Option<Response> {
+        let canonical = self.canonical(head.uri.path())?;
+        match self.redirect {
+            Some(status) => Some(redirect(&with_query(&canonical, &head.uri), status)),
+            None => {
+                set_path(head, &canonical);
+                None
+            }
+        }
+    }
+}
+
+/// Redirect plain-HTTP requests to HTTPS.
+///
+/// The scheme is taken from `X-Forwarded-Proto` when a proxy sets it.
+pub struct HttpsRedirect {
+    pub status: u16,
+    pub port: Option<u16>,
+    pub exclude_paths: Vec<String>,
+}
+
+impl HttpsRedirect {
+    fn from_options(options: &TransformOptions) -> Result<Arc<dyn Transform>, String> {
+        let options = Options::new(options, &["status", "port", "exclude_paths"])?;
+        Ok(Arc::new(HttpsRedirect {
+            status: options.redirect_status(308)?,
+            port: options.u16("port")?,
+            exclude_paths: options.strings("exclude_paths")?,
+        }))
+    }
+}
+
+impl Transform for HttpsRedirect {
+    fn name(&self) -> &str {
+        "https_redirect"
+    }
+
+    fn request(&self, head: &mut Parts) -> Option<Response> {
+        let scheme = head
+            .headers
+            .get("x-forwarded-proto")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(',').next())
+            .map(str::trim)
+            .or_else(|| head.uri.scheme_str())
+            .unwrap_or("http");
+        if scheme.eq_ignore_ascii_case("https")
+            || self
+                .exclude_paths
+                .iter()
+                .any(|p| path_matches_skip(head.uri.path(), p))
+        {
+            return None;
+        }
+
+        let host = head
+            .headers
+            .get(hyper::header::HOST)
+            .and_then(|v| v.to_str().ok())
+            .or_else(|| head.uri.host())?;
+        let host = host.rsplit_once(':').map_or(host, |(name, port)| {
+            // Keep IPv6 literals such as `[::1]` intact
+            if port.ends_with(']') {
+                host
+            } else {
+                name
+            }
+        });
+        let authority = match self.port {
+            Some(port) if port != 443 => format!("{host}:{port}"),
+            _ => host.to_string(),
+        };
+        let path = head.uri.path_and_query().map_or("/", |pq| pq.as_str());
+        Some(redirect(&format!("https://{authority}{path}"), self.status))
+    }
+}
+
+/// Rewrite a path prefix, e.g. `/v1/users` to `/api/v1/users`.
+pub struct PathRewrite {
+    pub prefix: String,
+    pub to: String,
+}
+
+impl PathRewrite {
+    fn from_options(options: &TransformOptions) -> Result<Arc<dyn Transform>, String> {
+        let options = Options::new(options, &["prefix", "to"])?;
+        let prefix = options.required_str("prefix")?.trim_end_matches('/');
+        let to = options.required_str("to")?.trim_end_matches('/');
+        Ok(Arc::new(PathRewrite {
+            prefix: prefix.to_string(),
+            to: to.to_string(),
+        }))
+    }
+
+    /// The rewritten form of `path`, if it is under the prefix.
+    pub fn rewrite(&self, path: &str) -> Option<String> {
+        let rest = path.strip_prefix(&self.prefix)?;
+        if !rest.is_empty() && !rest.starts_with('/') {
+            return None;
+        }
+        let rewritten = format!("{}{rest}", self.to);
+        Some(if rewritten.is_empty() {
+            "/".to_string()
+        } else {
+            rewritten
+        })
+    }
+}
+
+impl Transform for PathRewrite {
+    fn name(&self) -> &str {
+        "rewrite"
+    }
+
+    fn request(&self, head: &mut Parts) -> Option<Response> {
+        if let Some(path) = self.rewrite(head.uri.path()) {
+            set_path(head, &path);
+        }
+        None
+    }
+}
+
+fn header_pair(options: &Options<'_>) -> Result<(HeaderName, HeaderValue), String> {
+    let name = options.required_str("name")?;
+    let value = options.required_str("value")?;
+    Ok((
+        HeaderName::from_bytes(name.as_bytes()).map_err(|_| format!("invalid header '{name}'"))?,
+        HeaderValue::from_str(value).map_err(|_| format!("invalid value for header '{name}'"))?,
+    ))
+}
+
+/// Add a header to every request.
+pub struct RequestHeader {
+    pub name: HeaderName,
+    pub value: HeaderValue,
+    pub overwrite: bool,
+}
+
+impl RequestHeader {
+    fn from_options(options: &TransformOptions) -> Result<Arc<dyn Transform>, String> {
+        let options = Options::new(options, &["name", "value", "overwrite"])?;
+        let (name, value) = header_pair(&options)?;
+        Ok(Arc::new(RequestHeader {
+            name,
+            value,
+            overwrite: options.bool("overwrite", true)?,
+        }))
+    }
+}
+
+impl Transform for RequestHeader {
+    fn name(&self) -> &str {
+        "request_header"
+    }
+
+    fn request(&self, head: &mut Parts) -> Option<Response> {
+        if self.overwrite || !head.headers.contains_key(&self.name) {
+            head.headers.insert(self.name.clone(), self.value.clone());
+        }
+        None
+    }
+}
+
+/// Add a header to every response.
+pub struct ResponseHeader {
+    pub name: String,
+    pub value: String,
+    pub overwrite: bool,
+}
+
+impl ResponseHeader {
+    fn from_options(options: &TransformOptions) -> Result<Arc<dyn Transform>, String> {
+        let options = Options::new(options, &["name", "value", "overwrite"])?;
+        // Validated like request headers, but responses keep the given case
+        header_pair(&options)?;
+        Ok(Arc::new(ResponseHeader {
+            name: options.required_str("name")?.to_string(),
+            value: options.required_str("value")?.to_string(),
+            overwrite: options.bool("overwrite", true)?,
+        }))
+    }
+}
+
+impl Transform for ResponseHeader {
+    fn name(&self) -> &str {
+        "response_header"
+    }
+
+    fn rewrites_response(&self) -> bool {
+        true
+    }
+
+    fn response(&self, _request: &Request, response: &mut Response) {
+        let existing = response
+            .headers
+            .keys()
+            .find(|key| key.eq_ignore_ascii_case(&self.name))
+            .cloned();
+        match existing {
+            Some(_) if !self.overwrite => {}
+            Some(key) => {
+                response.headers.remove(&key);
+                response.set_header(&self.name, &self.value);
+            }
+            None => response.set_header(&self.name, &self.value),
+        }
+    }
+}
+
+/// Answer requests for one path with a fixed response.
+pub struct Respond {
+    pub path: String,
+    pub status: u16,
+    pub body: Vec<u8>,
+    pub content_type: String,
+}
+
+impl Respond {
+    fn from_options(options: &TransformOptions) -> Result<Arc<dyn Transform>, String> {
+        let options = Options::new(options, &["path", "status", "body", "content_type"])?;
+        Ok(Arc::new(Respond {
+            path: options.required_str("path")?.to_string(),
+            status: options.status("status", 200)?,
+            body: options.str("body")?.unwrap_or_default().as_bytes().to_vec(),
+            content_type: options
+                .str("content_type")?
+                .unwrap_or("text/plain")
+                .to_string(),
+        }))
+    }
+}
+
+impl Transform for Respond {
+    fn name(&self) -> &str {
+        "respond"
+    }
+
+    fn request(&self, head: &mut Parts) -> Option<Response> {
+        (head.uri.path() == self.path).then(|| {
+            Response::binary(
+                self.body.clone(),
+                Some(&self.content_type),
+                Some(self.status),
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn head(uri: &str, headers: &[(&str, &str)]) -> Parts {
+        let mut builder = hyper::Request::builder().uri(uri);
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        builder.body(()).unwrap().into_parts().0
+    }
+
+    fn options(value: Value) -> TransformOptions {
+        value.as_object().cloned().unwrap()
+    }
+
+    #[test]
+    fn test_trailing_slash() {
+        let strip = create("trailing_slash", &options(json!({}))).unwrap();
+        let response = strip.request(&mut head("/users/?page=2", &[])).unwrap();
+        assert_eq!(response.status, 308);
+        assert_eq!(response.headers["Location"], "/users?page=2");
+        assert!(strip.request(&mut head("/", &[])).is_none());
+        assert!(strip.request(&mut head("/users", &[])).is_none());
+
+        let append = create(
+            "trailing_slash",
+            &options(json!({"mode": "append", "redirect": false})),
+        )
+        .unwrap();
+        let mut request = head("/docs?q=1", &[]);
+        assert!(append.request(&mut request).is_none());
+        assert_eq!(request.uri, "/docs/?q=1");
+        let mut file = head("/static/app.js", &[]);
+        append.request(&mut file);
+        assert_eq!(file.uri, "/static/app.js");
+    }
+
+    #[test]
+    fn test_https_redirect() {
+        let https = create(
+            "https_redirect",
+            &options(json!({"exclude_paths": ["/health"]})),
+        )
+        .unwrap();
+        let response = https
+            .request(&mut head("/a?b=1", &[("host", "example.com:8080")]))
+            .unwrap();
+        assert_eq!(response.headers["Location"], "https://example.com/a?b=1");
+        assert!(https
+            .request(&mut head(
+                "/a",
+                &[("host", "example.com"), ("x-forwarded-proto", "https")]
+            ))
+            .is_none());
+        assert!(https
+            .request(&mut head("/health", &[("host", "example.com")]))
+            .is_none());
+
+        let port = create("https_redirect", &options(json!({"port": 8443}))).unwrap();
+        let response = port.request(&mut head("/", &[("host", "[::1]")])).unwrap();
+        assert_eq!(response.headers["Location"], "https://[::1]:8443/");
+    }
+
+    #[test]
+    fn test_rewrite_and_headers() {
+        let rewrite = PathRewrite {
+            prefix: "/v1".into(),
+            to: "/api/v1".into(),
+        };
+        assert_eq!(
+            rewrite.rewrite("/v1/users").as_deref(),
+            Some("/api/v1/users")
+        );
+        assert_eq!(rewrite.rewrite("/v1").as_deref(), Some("/api/v1"));
+        assert_eq!(rewrite.rewrite("/v10"), None);
+
+        let header = create(
+            "request_header",
+            &options(json!({"name": "X-Edge", "value": "1", "overwrite": false})),
+        )
+        .unwrap();
+        let mut request = head("/", &[("x-edge", "0")]);
+        header.request(&mut request);
+        assert_eq!(request.headers["x-edge"], "0");
+
+        let header = create(
+            "response_header",
+            &options(json!({"name": "X-Frame-Options", "value": "DENY"})),
+        )
+        .unwrap();
+        let mut response = Response::new(200);
+        response.set_header("x-frame-options", "SAMEORIGIN");
+        header.response(&Request::default(), &mut response);
+        assert_eq!(response.headers.len(), 1);
+        assert_eq!(response.headers["X-Frame-Options"], "DENY");
+    }
+
+    #[test]
+    fn test_options_errors() {
+        assert!(create("missing", &options(json!({}))).is_err());
+        let error = create("trailing_slash", &options(json!({"mode": "both"})))
+            .err()
+            .unwrap();
+        assert!(error.starts_with("transform 'trailing_slash':"));
+        assert!(create("respond", &options(json!({"path": "/", "bogus": 1}))).is_err());
+        assert!(create("https_redirect", &options(json!({"status": 200}))).is_err());
+        assert!(create(
+            "request_header",
+            &options(json!({"name": "bad name", "value": "x"}))
+        )
+        .is_err());
+        assert!(names().contains(&"respond".to_string()));
+    }
+}
@@ -0,0 +1,359 @@
+//! Inbound webhook signature verification for Cello.
+//!
+//! Provides:
+//! - HMAC-SHA256 verification for Stripe, GitHub, Slack and Cello-style
+//!   signatures, compared in constant time
+//! - Timestamp tolerance for schemes that sign a timestamp
+//! - Replay protection by remembering recently accepted signatures
+//! - Multiple secrets, so keys can be rotated without downtime
+//!
+//! Verifiers are attached to routes and run in Rust before any Python
+//! middleware or handler sees the payload.
+
+use hmac::{Hmac, Mac};
+use parking_lot::Mutex;
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use super::{Middleware, MiddlewareAction, MiddlewareError, MiddlewareResult};
+use crate::request::Request;
+
+type HmacSha256 = Hmac<Sha256>;
+
+// ============================================================================
+// Schemes
+// ============================================================================
+
+/// How a provider signs its webhooks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureScheme {
+    /// `Stripe-Signature: t=<ts>,v1=<hex>` over `"<ts>.<body>"`.
+    Stripe,
+    /// `X-Hub-Signature-256: sha256=<hex>` over the body.
+    GitHub,
+    /// `X-Slack-Signature: v0=<hex>` over `"v0:<ts>:<body>"`, with the
+    /// timestamp in `X-Slack-Request-Timestamp`.
+    Slack,
+    /// `X-Webhook-Signature: t=<ts>,v1=<hex>` over `"<ts>.<body>"`, as sent
+    /// by Cello's own webhook dispatcher.
+    Cello,
+}
+
+impl SignatureScheme {
+    /// Header carrying the signature.
+    pub fn header(&self) -> &'static str {
+        match self {
+            SignatureScheme::Stripe => "stripe-signature",
+            SignatureScheme::GitHub => "x-hub-signature-256",
+            SignatureScheme::Slack => "x-slack-signature",
+            SignatureScheme::Cello => "x-webhook-signature",
+        }
+    }
+
+    /// Extract the signed timestamp (if the scheme has one), the candidate
+    /// signatures and the signed message prefix from a request.
+    fn parse(&self, request: &Request) -> Result<SignedParts, &'static str> {
+        let header = request
+            .get_header(self.header(), None)
+            .ok_or("Missing webhook signature")?;
+        match self {
+            SignatureScheme::Stripe | SignatureScheme::Cello => {
+                let mut timestamp = None;
+                let mut signatures = Vec::new();
+                for part in header.split(',') {
+                    match part.trim().split_once('=') {
+                        Some(("t", value)) => timestamp = value.parse::<u64>().ok(),
+                        Some(("v1", value)) => signatures.push(value.to_string()),
+                        _ => {}
+                    }
+                }
+                let timestamp = timestamp.ok_or("Malformed webhook signature")?;
+                Ok(SignedParts {
+                    timestamp: Some(timestamp),
+                    signatures,
+                    prefix: format!("{timestamp}."),
+                })
+            }
+            SignatureScheme::GitHub => Ok(SignedParts {
+                timestamp: None,
+                signatures: header
+                    .strip_prefix("sha256=")
+                    .map(|s| vec![s.to_string()])
+                    .unwrap_or_default(),
+                prefix: String::new(),
+            }),
+            SignatureScheme::Slack => {
+                let timestamp = request
+                    .get_header("x-slack-request-timestamp", None)
+                    .and_then(|t| t.trim().parse::<u64>().ok())
+                    .ok_or("Missing webhook timestamp")?;
+                Ok(SignedParts {
+                    timestamp: Some(timestamp),
+                    signatures: header
+                        .strip_prefix("v0=")
+                        .map(|s| vec![s.to_string()])
+                        .unwrap_or_default(),
+                    prefix: format!("v0:{timestamp}:"),
+                })
+            }
+        }
+    }
+}
+
+impl FromStr for SignatureScheme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "stripe" => Ok(SignatureScheme::Stripe),
+            "github" => Ok(SignatureScheme::GitHub),
+            "slack" => Ok(SignatureScheme::Slack),
+            "cello" => Ok(SignatureScheme::Cello),
+            other => Err(format!(
+                "Unknown webhook signature scheme '{other}' (expected stripe, github, slack or cello)"
+            )),
+        }
+    }
+}
+
+struct SignedParts {
+    timestamp: Option<u64>,
+    signatures: Vec<String>,
+    prefix: String,
+}
+
+// ============================================================================
+// Verifier
+// ============================================================================
+
+/// Verifies the signature of inbound webhook requests.
+pub struct WebhookVerifier {
+    scheme: SignatureScheme,
+    secrets: Vec<Vec<u8>>,
+    tolerance: Duration,
+    replay_window: Option<Duration>,
+    seen: Mutex<HashMap<String, Instant>>,
+}
+
+impl WebhookVerifier {
+    /// Create a verifier for `scheme` with a signing secret.
+    ///
+    /// Timestamps must be within 5 minutes and replayed signatures are
+    /// rejected for as long.
+    pub fn new(scheme: SignatureScheme, secret: &str) -> Self {
+        Self {
+            scheme,
+            secrets: vec![secret.as_bytes().to_vec()],
+            tolerance: Duration::from_secs(300),
+            replay_window: Some(Duration::from_secs(300)),
+            seen: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Also accept signatures made with `secret` (for key rotation).
+    pub fn with_secret(mut self, secret: &str) -> Self {
+        self.secrets.push(secret.as_bytes().to_vec());
+        self
+    }
+
+    /// Set how far a signed timestamp may be from the current time.
+    pub fn with_tolerance(mut self, tolerance: Duration) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Reject a signature seen within `window`, or disable replay
+    /// protection with `None`.
+    pub fn with_replay_window(mut self, window: Option<Duration>) -> Self {
+        self.replay_window = window;
+        self
+    }
+
+    /// Get the signature scheme.
+    pub fn scheme(&self) -> SignatureScheme {
+        self.scheme
+    }
+
+    /// Verify a request's signature against its body.
+    pub fn verify(&self, request: &Request) -> Result<(), MiddlewareError> {
+        let reject = |message: &str| {
+            MiddlewareError::unauthorized(message).with_code("INVALID_WEBHOOK_SIGNATURE")
+        };
+        let parts = self.scheme.parse(request).map_err(reject)?;
+
+        if let Some(timestamp) = parts.timestamp {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            if now.abs_diff(timestamp) > self.tolerance.as_secs() {
+                return Err(reject("Webhook timestamp outside the tolerance"));
+            }
+        }
+
+        let body = request.body_bytes();
+        let valid = parts.signatures.iter().find(|signature| {
+            let Ok(signature) = hex::decode(signature) else {
+                return false;
+            };
+            self.secrets.iter().any(|secret| {
+                let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts any key");
+                mac.update(parts.prefix.as_bytes());
+                mac.update(body);
+                // verify_slice compares in constant time.
+                mac.verify_slice(&signature).is_ok()
+            })
+        });
+        let Some(signature) = valid else {
+            return Err(reject("Invalid webhook signature"));
+        };
+
+        if let Some(window) = self.replay_window {
+            let now = Instant::now();
+            let mut seen = self.seen.lock();
+            seen.retain(|_, expires| *expires > now);
+            if seen.contains_key(signature) {
+                return Err(reject("Webhook already received"));
+            }
+            seen.insert(signature.clone(), now + window);
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for WebhookVerifier {
+    // Secrets are left out so they never end up in logs.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WebhookVerifier")
+            .field("scheme", &self.scheme)
+            .field("secrets", &self.secrets.len())
+            .field("tolerance", &self.tolerance)
+            .field("replay_window", &self.replay_window)
+            .finish()
+    }
+}
+
+impl Middleware for WebhookVerifier {
+    fn before(&self, request: &mut Request) -> MiddlewareResult {
+        self.verify(request)?;
+        Ok(MiddlewareAction::Continue)
+    }
+
+    fn name(&self) -> &str {
+        "webhook_verify"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signed(prefix: &str, secret: &str, body: &[u8]) -> String {
+        let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(prefix.as_bytes());
+        mac.update(body);
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    fn request(headers: &[(&str, String)], body: &[u8]) -> Request {
+        Request::from_http(
+            "POST".to_string(),
+            "/hooks".to_string(),
+            HashMap::new(),
+            HashMap::new(),
+            headers
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.clone()))
+                .collect(),
+            body.to_vec(),
+        )
+    }
+
+    fn unix_now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    #[test]
+    fn test_stripe_signature_and_tolerance() {
+        let verifier =
+            WebhookVerifier::new(SignatureScheme::Stripe, "whsec").with_replay_window(None);
+        let body = br#"{"id":"evt_1"}"#;
+        let t = unix_now();
+        let header = format!(
+            "t={t},v1={},v1={}",
+            signed(&format!("{t}."), "other", body),
+            signed(&format!("{t}."), "whsec", body)
+        );
+        assert!(verifier
+            .verify(&request(&[("Stripe-Signature", header.clone())], body))
+            .is_ok());
+        // A different body no longer matches.
+        assert!(verifier
+            .verify(&request(&[("Stripe-Signature", header)], b"{}"))
+            .is_err());
+
+        let old = t - 600;
+        let stale = format!("t={old},v1={}", signed(&format!("{old}."), "whsec", body));
+        let err = verifier
+            .verify(&request(&[("Stripe-Signature", stale)], body))
+            .unwrap_err();
+        assert_eq!(err.status, 401);
+        assert!(err.message.contains("tolerance"));
+    }
+
+    #[test]
+    fn test_github_and_secret_rotation() {
+        let verifier = WebhookVerifier::new(SignatureScheme::GitHub, "new").with_secret("old");
+        let body = b"payload";
+        for secret in ["new", "old"] {
+            let header = format!("sha256={}", signed("", secret, body));
+            assert!(verifier
+                .verify(&request(&[("X-Hub-Signature-256", header)], body))
+                .is_ok());
+        }
+        let header = format!("sha256={}", signed("", "wrong", body));
+        assert!(verifier
+            .verify(&request(&[("X-Hub-Signature-256", header)], body))
+            .is_err());
+        assert!(verifier.verify(&request(&[], body)).is_err());
+    }
+
+    #[test]
+    fn test_slack_signature() {
+        let verifier = WebhookVerifier::new(SignatureScheme::Slack, "slack-secret");
+        let body = b"token=x&team_id=T1";
+        let t = unix_now();
+        let headers = [
+            ("X-Slack-Request-Timestamp", t.to_string()),
+            (
+                "X-Slack-Signature",
+                format!("v0={}", signed(&format!("v0:{t}:"), "slack-secret", body)),
+            ),
+        ];
+        assert!(verifier.verify(&request(&headers, body)).is_ok());
+        assert!(verifier.verify(&request(&headers[1..], body)).is_err());
+    }
+
+    #[test]
+    fn test_replay_protection() {
+        let verifier = WebhookVerifier::new(SignatureScheme::Cello, "whsec");
+        let body = b"{}";
+        let t = unix_now();
+        let header = crate::webhooks::sign("whsec", t, body);
+        let req = request(&[("X-Webhook-Signature", header)], body);
+        assert!(verifier.verify(&req).is_ok());
+        let err = verifier.verify(&req).unwrap_err();
+        assert!(err.message.contains("already received"));
+    }
+
+    #[test]
+    fn test_scheme_from_str() {
+        assert_eq!("GitHub".parse(), Ok(SignatureScheme::GitHub));
+        assert!("paypal".parse::<SignatureScheme>().is_err());
+    }
+}
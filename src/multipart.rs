@@ -1,10 +1,79 @@
 //! Multipart form data handling.
 //!
-//! Provides file upload support and form parsing.
-
+//! Provides file upload support and form parsing. Multipart bodies are
+//! parsed as they stream in; uploaded files past a size threshold are
+//! spooled to a temporary file that is removed when the request ends.
+
+use bytes::Bytes;
+use futures_util::Stream;
+use parking_lot::Mutex;
+use pyo3::exceptions::{PyIOError, PyValueError};
 use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Default size above which an uploaded file is spooled to disk (1MB).
+pub const DEFAULT_SPOOL_THRESHOLD: usize = 1024 * 1024;
+
+/// How files are stored while a multipart body is parsed.
+#[derive(Clone, Debug)]
+pub struct UploadOptions {
+    /// Files larger than this many bytes are written to disk
+    pub spool_threshold: usize,
+    /// Compute a SHA-256 digest of each file while it is read
+    pub checksum: bool,
+    /// Directory for spooled files (the system temp dir by default)
+    pub upload_dir: Option<PathBuf>,
+}
+
+impl Default for UploadOptions {
+    fn default() -> Self {
+        Self {
+            spool_threshold: DEFAULT_SPOOL_THRESHOLD,
+            checksum: false,
+            upload_dir: None,
+        }
+    }
+}
+
+/// A file spooled to disk.
+///
+/// The file is removed when the last handle is dropped, which is when the
+/// request and any `UploadedFile` objects taken from it are released,
+/// unless it has been moved with `move_to()`.
+#[derive(Debug)]
+struct SpooledFile {
+    path: Mutex<PathBuf>,
+    /// Set once the file has been moved to a permanent location
+    kept: AtomicBool,
+}
+
+impl SpooledFile {
+    fn path(&self) -> PathBuf {
+        self.path.lock().clone()
+    }
+}
+
+impl Drop for SpooledFile {
+    fn drop(&mut self) {
+        if !self.kept.load(Ordering::Acquire) {
+            let _ = fs::remove_file(self.path.get_mut());
+        }
+    }
+}
+
+/// Where the content of an uploaded file lives.
+#[derive(Clone, Debug)]
+enum Content {
+    Memory(Bytes),
+    Disk(Arc<SpooledFile>),
+}
 
 /// Uploaded file information.
 #[pyclass]
@@ -18,36 +87,94 @@ pub struct UploadedFile {
     #[pyo3(get)]
     pub content_type: String,
 
-    /// File content as bytes
-    content: Vec<u8>,
+    /// File content, in memory or spooled to disk
+    content: Content,
+
+    /// File size in bytes
+    size: usize,
 
-    /// Temporary file path (if saved to disk)
+    /// Hex SHA-256 digest, if checksums were requested
     #[pyo3(get)]
-    pub temp_path: Option<String>,
+    pub sha256: Option<String>,
 }
 
 #[pymethods]
 impl UploadedFile {
     /// Get the file content as bytes.
-    pub fn read(&self) -> Vec<u8> {
-        self.content.clone()
+    pub fn read<'py>(&self, py: Python<'py>) -> PyResult<&'py PyBytes> {
+        let content = self
+            .content()
+            .map_err(|e| PyIOError::new_err(e.to_string()))?;
+        Ok(PyBytes::new(py, &content))
+    }
+
+    /// Read the file content without blocking the event loop; returns an
+    /// awaitable resolving to bytes.
+    pub fn aread<'py>(&self, py: Python<'py>) -> PyResult<&'py PyAny> {
+        let content = self.content.clone();
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            let content = match content {
+                Content::Memory(bytes) => bytes,
+                Content::Disk(file) => tokio::fs::read(file.path())
+                    .await
+                    .map_err(|e| PyIOError::new_err(e.to_string()))?
+                    .into(),
+            };
+            Ok(Python::with_gil(|py| {
+                PyBytes::new(py, &content).to_object(py)
+            }))
+        })
     }
 
     /// Get the file content as text (UTF-8).
     pub fn read_text(&self) -> PyResult<String> {
-        String::from_utf8(self.content.clone())
-            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))
+        let content = self
+            .content()
+            .map_err(|e| PyIOError::new_err(e.to_string()))?;
+        String::from_utf8(content.to_vec()).map_err(|e| PyValueError::new_err(e.to_string()))
     }
 
     /// Get the file size in bytes.
     pub fn size(&self) -> usize {
-        self.content.len()
+        self.size
+    }
+
+    /// Temporary file path, if the file was spooled to disk.
+    #[getter]
+    pub fn temp_path(&self) -> Option<String> {
+        match &self.content {
+            Content::Disk(file) if !file.kept.load(Ordering::Acquire) => {
+                Some(file.path().to_string_lossy().into_owned())
+            }
+            _ => None,
+        }
     }
 
-    /// Save the file to the specified path.
+    /// Save a copy of the file to the specified path.
     pub fn save(&self, path: &str) -> PyResult<()> {
-        std::fs::write(path, &self.content)
-            .map_err(|e| pyo3::exceptions::PyIOError::new_err(e.to_string()))
+        let result = match &self.content {
+            Content::Memory(bytes) => fs::write(path, bytes),
+            Content::Disk(file) => fs::copy(file.path(), path).map(|_| ()),
+        };
+        result.map_err(|e| PyIOError::new_err(e.to_string()))
+    }
+
+    /// Move the file to `path`.
+    ///
+    /// A spooled file is renamed rather than copied, and is no longer removed
+    /// when the request ends.
+    pub fn move_to(&self, path: &str) -> PyResult<()> {
+        let result = match &self.content {
+            Content::Memory(bytes) => fs::write(path, bytes),
+            Content::Disk(file) => {
+                let mut current = file.path.lock();
+                move_file(&current, Path::new(path)).map(|_| {
+                    *current = PathBuf::from(path);
+                    file.kept.store(true, Ordering::Release);
+                })
+            }
+        };
+        result.map_err(|e| PyIOError::new_err(e.to_string()))
     }
 
     /// Get file extension from filename.
@@ -64,14 +191,102 @@ impl UploadedFile {
         UploadedFile {
             filename,
             content_type,
-            content,
-            temp_path: None,
+            size: content.len(),
+            content: Content::Memory(content.into()),
+            sha256: None,
+        }
+    }
+
+    /// Get the raw content bytes, reading a spooled file from disk.
+    pub fn content(&self) -> std::io::Result<Bytes> {
+        match &self.content {
+            Content::Memory(bytes) => Ok(bytes.clone()),
+            Content::Disk(file) => fs::read(file.path()).map(Bytes::from),
         }
     }
 
-    /// Get the raw content bytes.
-    pub fn content(&self) -> &[u8] {
-        &self.content
+    /// Whether the file was spooled to disk.
+    pub fn is_spooled(&self) -> bool {
+        matches!(self.content, Content::Disk(_))
+    }
+}
+
+/// Rename `from` to `to`, copying when they are on different filesystems.
+fn move_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    fs::copy(from, to)?;
+    fs::remove_file(from)
+}
+
+/// Collects the chunks of one uploaded file, moving them to disk once they
+/// pass the spool threshold.
+struct FileSpooler<'a> {
+    options: &'a UploadOptions,
+    buf: Vec<u8>,
+    file: Option<(fs::File, Arc<SpooledFile>)>,
+    hasher: Option<Sha256>,
+    size: usize,
+}
+
+impl<'a> FileSpooler<'a> {
+    fn new(options: &'a UploadOptions) -> Self {
+        Self {
+            options,
+            buf: Vec::new(),
+            file: None,
+            hasher: options.checksum.then(Sha256::new),
+            size: 0,
+        }
+    }
+
+    fn write(&mut self, chunk: &[u8]) -> std::io::Result<()> {
+        self.size += chunk.len();
+        if let Some(hasher) = self.hasher.as_mut() {
+            hasher.update(chunk);
+        }
+        if self.file.is_none() && self.size > self.options.spool_threshold {
+            let dir = match &self.options.upload_dir {
+                Some(dir) => dir.clone(),
+                None => std::env::temp_dir(),
+            };
+            let path = dir.join(format!("cello-upload-{}", uuid::Uuid::new_v4().simple()));
+            let mut file = fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)?;
+            let spooled = Arc::new(SpooledFile {
+                path: Mutex::new(path),
+                kept: AtomicBool::new(false),
+            });
+            file.write_all(&std::mem::take(&mut self.buf))?;
+            self.file = Some((file, spooled));
+        }
+        match self.file.as_mut() {
+            Some((file, _)) => file.write_all(chunk),
+            None => {
+                self.buf.extend_from_slice(chunk);
+                Ok(())
+            }
+        }
+    }
+
+    fn finish(self, filename: String, content_type: String) -> std::io::Result<UploadedFile> {
+        let content = match self.file {
+            Some((mut file, spooled)) => {
+                file.flush()?;
+                Content::Disk(spooled)
+            }
+            None => Content::Memory(self.buf.into()),
+        };
+        Ok(UploadedFile {
+            filename,
+            content_type,
+            content,
+            size: self.size,
+            sha256: self.hasher.map(|hasher| hex::encode(hasher.finalize())),
+        })
     }
 }
 
@@ -172,31 +387,127 @@ impl FormData {
     }
 }
 
-/// Parse URL-encoded form data.
+/// Why a multipart body could not be parsed.
+#[derive(Debug)]
+pub enum MultipartError {
+    /// The body failed or arrived too slowly
+    Body(String),
+    /// The body is not valid multipart data or has too many fields
+    Invalid(String),
+    /// A file could not be spooled to disk
+    Io(std::io::Error),
+}
+
+impl From<MultipartError> for PyErr {
+    fn from(err: MultipartError) -> PyErr {
+        match err {
+            MultipartError::Body(e) => PyIOError::new_err(e),
+            MultipartError::Invalid(e) => PyValueError::new_err(e),
+            MultipartError::Io(e) => PyIOError::new_err(e.to_string()),
+        }
+    }
+}
+
+impl From<multer::Error> for MultipartError {
+    fn from(err: multer::Error) -> Self {
+        match err {
+            multer::Error::StreamReadFailed(e) => MultipartError::Body(e.to_string()),
+            e => MultipartError::Invalid(format!("Invalid multipart body: {e}")),
+        }
+    }
+}
+
+/// Parse a `multipart/form-data` body as it streams in.
+///
+/// File parts are spooled to disk once they pass `options.spool_threshold`
+/// bytes, so large uploads are never held in memory. Bodies with more than
+/// `max_fields` parts are rejected.
+pub async fn parse_multipart<S, E>(
+    body: S,
+    boundary: &str,
+    options: &UploadOptions,
+    max_fields: usize,
+) -> Result<FormData, MultipartError>
+where
+    S: Stream<Item = Result<Bytes, E>> + Send + 'static,
+    E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+{
+    let mut multipart = multer::Multipart::new(body, boundary);
+    let mut form = FormData::new();
+    let mut count = 0;
+    while let Some(mut field) = multipart.next_field().await? {
+        count += 1;
+        if count > max_fields {
+            return Err(MultipartError::Invalid(format!(
+                "Form data exceeds {max_fields} fields"
+            )));
+        }
+        let name = field.name().unwrap_or_default().to_string();
+        let Some(filename) = field.file_name().map(str::to_string) else {
+            form.add_field(name, field.text().await?);
+            continue;
+        };
+        let content_type = field
+            .content_type()
+            .map(|mime| mime.to_string())
+            .unwrap_or_else(|| "application/octet-stream".to_string());
+
+        let mut spooler = FileSpooler::new(options);
+        while let Some(chunk) = field.chunk().await? {
+            spooler.write(&chunk).map_err(MultipartError::Io)?;
+        }
+        let file = spooler
+            .finish(filename, content_type)
+            .map_err(MultipartError::Io)?;
+        form.add_file(name, file);
+    }
+    Ok(form)
+}
+
+/// Default maximum number of fields in a URL-encoded form.
+pub const DEFAULT_MAX_FORM_FIELDS: usize = 1000;
+
+/// Parse URL-encoded form data (last value wins for repeated keys).
 pub fn parse_urlencoded(body: &[u8]) -> Result<HashMap<String, String>, String> {
+    Ok(parse_urlencoded_pairs(body, DEFAULT_MAX_FORM_FIELDS)?
+        .into_iter()
+        .collect())
+}
+
+/// Parse URL-encoded form data into ordered `(key, value)` pairs.
+///
+/// Repeated keys are kept in order. Bodies with more than `max_fields`
+/// fields are rejected before the remaining fields are decoded.
+pub fn parse_urlencoded_pairs(
+    body: &[u8],
+    max_fields: usize,
+) -> Result<Vec<(String, String)>, String> {
     let body_str =
         std::str::from_utf8(body).map_err(|e| format!("Invalid UTF-8 in form data: {e}"))?;
 
-    let mut fields = HashMap::new();
+    let mut fields = Vec::new();
 
     for pair in body_str.split('&') {
         if pair.is_empty() {
             continue;
         }
+        if fields.len() >= max_fields {
+            return Err(format!("Form data exceeds {max_fields} fields"));
+        }
 
         let mut parts = pair.splitn(2, '=');
         let key = parts.next().unwrap_or("");
         let value = parts.next().unwrap_or("");
 
-        // URL decode
-        let key = urlencoding::decode(key)
+        // URL decode ('+' is a space in both keys and values)
+        let key = urlencoding::decode(&key.replace('+', " "))
             .map_err(|e| format!("Failed to decode key: {e}"))?
             .to_string();
         let value = urlencoding::decode(&value.replace('+', " "))
             .map_err(|e| format!("Failed to decode value: {e}"))?
             .to_string();
 
-        fields.insert(key, value);
+        fields.push((key, value));
     }
 
     Ok(fields)
@@ -247,4 +558,111 @@ mod tests {
         assert_eq!(result.get("email"), Some(&"john@example.com".to_string()));
         assert_eq!(result.get("message"), Some(&"Hello World".to_string()));
     }
+
+    #[test]
+    fn test_parse_urlencoded_pairs() {
+        let body = b"tag=a&first+name=Ada&tag=b%26c&flag&=empty";
+        let pairs = parse_urlencoded_pairs(body, 10).unwrap();
+        let expected = [
+            ("tag", "a"),
+            ("first name", "Ada"),
+            ("tag", "b&c"),
+            ("flag", ""),
+            ("", "empty"),
+        ];
+        assert_eq!(pairs.len(), expected.len());
+        for ((key, value), (k, v)) in pairs.iter().zip(expected) {
+            assert_eq!((key.as_str(), value.as_str()), (k, v));
+        }
+
+        assert!(parse_urlencoded_pairs(b"a=1&b=2&c=3", 2).is_err());
+        assert!(parse_urlencoded_pairs(b"a=1&&b=2&", 2).is_ok());
+        // Last value wins in the map form
+        assert_eq!(parse_urlencoded(b"k=1&k=2").unwrap()["k"], "2");
+    }
+
+    fn multipart_body() -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend_from_slice(b"--XyZ\r\n");
+        body.extend_from_slice(b"Content-Disposition: form-data; name=\"title\"\r\n\r\n");
+        body.extend_from_slice(b"Report\r\n--XyZ\r\n");
+        body.extend_from_slice(
+            b"Content-Disposition: form-data; name=\"doc\"; filename=\"a.txt\"\r\n",
+        );
+        body.extend_from_slice(b"Content-Type: text/plain\r\n\r\n");
+        body.extend_from_slice(&[b'x'; 100]);
+        body.extend_from_slice(b"\r\n--XyZ--\r\n");
+        body
+    }
+
+    async fn parse(body: Vec<u8>, options: &UploadOptions, max_fields: usize) -> FormData {
+        let chunks: Vec<Result<Bytes, std::convert::Infallible>> = body
+            .chunks(7)
+            .map(|c| Ok(Bytes::copy_from_slice(c)))
+            .collect();
+        parse_multipart(
+            futures_util::stream::iter(chunks),
+            "XyZ",
+            options,
+            max_fields,
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_parse_multipart() {
+        let form = parse(multipart_body(), &UploadOptions::default(), 10).await;
+        assert_eq!(form.get("title"), Some("Report".to_string()));
+
+        let file = form.get_file("doc").unwrap();
+        assert_eq!(file.filename, "a.txt");
+        assert_eq!(file.content_type, "text/plain");
+        assert_eq!(file.size(), 100);
+        assert!(!file.is_spooled());
+        assert_eq!(file.sha256, None);
+
+        let chunks = vec![Ok::<_, std::convert::Infallible>(Bytes::from(
+            multipart_body(),
+        ))];
+        let result = parse_multipart(
+            futures_util::stream::iter(chunks),
+            "XyZ",
+            &UploadOptions::default(),
+            1,
+        )
+        .await;
+        assert!(matches!(result, Err(MultipartError::Invalid(_))));
+    }
+
+    #[tokio::test]
+    async fn test_spooled_upload() {
+        let dir = tempfile::tempdir().unwrap();
+        let options = UploadOptions {
+            spool_threshold: 16,
+            checksum: true,
+            upload_dir: Some(dir.path().to_path_buf()),
+        };
+        let form = parse(multipart_body(), &options, 10).await;
+        let file = form.get_file("doc").unwrap();
+        assert!(file.is_spooled());
+        assert_eq!(file.content().unwrap(), vec![b'x'; 100]);
+        assert_eq!(
+            file.sha256.as_deref(),
+            Some(hex::encode(Sha256::digest([b'x'; 100])).as_str())
+        );
+
+        let temp_path = file.temp_path().unwrap();
+        assert!(Path::new(&temp_path).starts_with(dir.path()));
+        drop((form, file));
+        assert!(!Path::new(&temp_path).exists());
+
+        let form = parse(multipart_body(), &options, 10).await;
+        let file = form.get_file("doc").unwrap();
+        let target = dir.path().join("kept.txt");
+        file.move_to(target.to_str().unwrap()).unwrap();
+        assert_eq!(file.temp_path(), None);
+        drop((form, file));
+        assert_eq!(fs::read(&target).unwrap(), vec![b'x'; 100]);
+    }
 }
@@ -0,0 +1,164 @@
+//! Content negotiation for Cello.
+//!
+//! Parses `Accept` headers with quality factors and picks the best of the
+//! media types a handler can produce, as RFC 9110 describes: each offered
+//! type takes the quality of the most specific range matching it
+//! (`text/html` over `text/*` over `*/*`), the highest quality wins, and
+//! ties go to the type offered first. A missing header accepts anything.
+
+/// One media range of an `Accept` header.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaRange {
+    /// Type, e.g. `text`, or `*`
+    pub main: String,
+    /// Subtype, e.g. `html`, or `*`
+    pub sub: String,
+    /// Parameters other than `q`, lowercased names
+    pub params: Vec<(String, String)>,
+    /// Quality, from 0 to 1
+    pub quality: f32,
+}
+
+impl MediaRange {
+    /// Parse one comma-separated entry of an `Accept` header.
+    pub fn parse(entry: &str) -> Option<Self> {
+        let mut parts = entry.split(';');
+        let media_type = parts.next()?.trim().to_ascii_lowercase();
+        let (main, sub) = media_type.split_once('/')?;
+        if main.is_empty() || sub.is_empty() || (main == "*" && sub != "*") {
+            return None;
+        }
+        let mut params = Vec::new();
+        let mut quality = 1.0;
+        for param in parts {
+            let Some((name, value)) = param.split_once('=') else {
+                continue;
+            };
+            let name = name.trim().to_ascii_lowercase();
+            let value = value.trim().trim_matches('"');
+            if name == "q" {
+                quality = value.parse::<f32>().unwrap_or(0.0).clamp(0.0, 1.0);
+            } else {
+                params.push((name, value.to_string()));
+            }
+        }
+        Some(Self {
+            main: main.to_string(),
+            sub: sub.to_string(),
+            params,
+            quality,
+        })
+    }
+
+    /// The media type, e.g. `text/html`.
+    pub fn media_type(&self) -> String {
+        format!("{}/{}", self.main, self.sub)
+    }
+
+    /// How specific the range is: 0 for `*/*`, 1 for `text/*`, 2 for
+    /// `text/html` and 3 with parameters.
+    pub fn specificity(&self) -> u8 {
+        match (self.main.as_str(), self.sub.as_str()) {
+            ("*", _) => 0,
+            (_, "*") => 1,
+            _ if self.params.is_empty() => 2,
+            _ => 3,
+        }
+    }
+
+    /// Whether `media_type` (parameters allowed) is in this range.
+    pub fn matches(&self, media_type: &str) -> bool {
+        let Some(offered) = MediaRange::parse(media_type) else {
+            return false;
+        };
+        (self.main == "*" || self.main == offered.main)
+            && (self.sub == "*" || self.sub == offered.sub)
+            && self
+                .params
+                .iter()
+                .all(|param| offered.params.contains(param))
+    }
+}
+
+/// The media ranges of an `Accept` header, most preferred first: by
+/// quality, then specificity, then order. Invalid entries are skipped.
+pub fn parse_accept(accept: &str) -> Vec<MediaRange> {
+    let mut ranges: Vec<MediaRange> = accept.split(',').filter_map(MediaRange::parse).collect();
+    // Stable, so header order breaks the remaining ties
+    ranges.sort_by(|a, b| {
+        b.quality
+            .total_cmp(&a.quality)
+            .then(b.specificity().cmp(&a.specificity()))
+    });
+    ranges
+}
+
+/// Quality the client gives `media_type`: that of the most specific
+/// matching range, or 0 when none matches.
+pub fn quality(ranges: &[MediaRange], media_type: &str) -> f32 {
+    ranges
+        .iter()
+        .filter(|range| range.matches(media_type))
+        .max_by_key(|range| range.specificity())
+        .map_or(0.0, |range| range.quality)
+}
+
+/// The best of the `offered` media types for an `Accept` header, or `None`
+/// when the client accepts none of them. Without a header, or with an empty
+/// one, the first offered type is picked.
+pub fn best_match<'a>(accept: Option<&str>, offered: &[&'a str]) -> Option<&'a str> {
+    let ranges = match accept.map(str::trim) {
+        Some(accept) if !accept.is_empty() => parse_accept(accept),
+        _ => return offered.first().copied(),
+    };
+    let mut best: Option<(&str, f32)> = None;
+    for &media_type in offered {
+        let q = quality(&ranges, media_type);
+        if q > 0.0 && best.is_none_or(|(_, best_q)| q > best_q) {
+            best = Some((media_type, q));
+        }
+    }
+    best.map(|(media_type, _)| media_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_accept_order() {
+        let ranges = parse_accept("text/*;q=0.5, */*;q=0.1, text/html, application/json;v=2, bad");
+        let types: Vec<String> = ranges.iter().map(MediaRange::media_type).collect();
+        assert_eq!(
+            types,
+            vec!["application/json", "text/html", "text/*", "*/*"]
+        );
+        assert_eq!(ranges[0].params, vec![("v".to_string(), "2".to_string())]);
+        assert_eq!(ranges[2].quality, 0.5);
+    }
+
+    #[test]
+    fn test_best_match() {
+        let offered = ["application/json", "text/html"];
+        assert_eq!(best_match(None, &offered), Some("application/json"));
+        assert_eq!(
+            best_match(Some("text/html,application/xhtml+xml,*/*;q=0.8"), &offered),
+            Some("text/html")
+        );
+        // Ties go to the offered order
+        assert_eq!(
+            best_match(Some("text/html, application/json"), &offered),
+            Some("application/json")
+        );
+        // The most specific range decides, even when a wildcard rates higher
+        assert_eq!(
+            best_match(Some("*/*, application/json;q=0"), &offered),
+            Some("text/html")
+        );
+        assert_eq!(best_match(Some("image/png"), &offered), None);
+        assert_eq!(
+            best_match(Some("text/*;q=0.3"), &offered),
+            Some("text/html")
+        );
+    }
+}
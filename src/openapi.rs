@@ -1,18 +1,25 @@
 //! OpenAPI Schema Generation for Cello Framework
 //!
-//! This module provides automatic OpenAPI 3.0 schema generation from routes,
+//! This module provides automatic OpenAPI 3.1 schema generation from routes,
 //! similar to FastAPI's approach but implemented in Rust for maximum performance.
 
 use parking_lot::RwLock;
+use pyo3::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
 
+use crate::json::json_to_python;
+use crate::router::Router;
+
+/// OpenAPI specification version emitted by the generator.
+pub const OPENAPI_VERSION: &str = "3.1.0";
+
 // ============================================================================
 // OpenAPI Schema Types
 // ============================================================================
 
-/// OpenAPI 3.0 specification root object.
+/// OpenAPI 3.1 specification root object.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OpenAPISpec {
     pub openapi: String,
@@ -92,13 +99,13 @@ pub struct Operation {
     pub summary: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "operationId", skip_serializing_if = "Option::is_none")]
     pub operation_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tags: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub parameters: Option<Vec<Parameter>>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "requestBody", skip_serializing_if = "Option::is_none")]
     pub request_body: Option<RequestBody>,
     pub responses: HashMap<String, Response>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -132,10 +139,13 @@ pub struct RequestBody {
 }
 
 /// Media type definition.
+///
+/// Schemas are free-form JSON Schema (2020-12), as allowed by OpenAPI 3.1,
+/// so models exported from Python can be embedded as-is.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MediaType {
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub schema: Option<Schema>,
+    pub schema: Option<serde_json::Value>,
 }
 
 /// Response definition.
@@ -201,8 +211,11 @@ pub struct Tag {
 // OpenAPI Generator
 // ============================================================================
 
+/// Paths served by the documentation endpoints themselves.
+pub const DOCS_PATHS: [&str; 3] = ["/docs", "/redoc", "/openapi.json"];
+
 /// Route metadata for OpenAPI generation.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct RouteMetadata {
     pub method: String,
     pub path: String,
@@ -210,13 +223,36 @@ pub struct RouteMetadata {
     pub description: Option<String>,
     pub tags: Vec<String>,
     pub deprecated: bool,
+    /// Explicit operationId (derived from method and path when absent)
+    pub operation_id: Option<String>,
+    /// JSON Schema of the request body
+    pub request_schema: Option<serde_json::Value>,
+    /// JSON Schema of the successful response body
+    pub response_schema: Option<serde_json::Value>,
+    /// Names of security schemes that guard this route
+    pub security: Vec<String>,
+}
+
+impl RouteMetadata {
+    /// Create metadata for a route with no documentation attached.
+    pub fn new(method: &str, path: &str) -> Self {
+        Self {
+            method: method.to_uppercase(),
+            path: path.to_string(),
+            ..Default::default()
+        }
+    }
 }
 
 /// OpenAPI schema generator.
+///
+/// Walks the router for the registered routes and merges in any metadata
+/// registered from Python, so undocumented routes still appear in the spec.
 #[derive(Clone)]
 pub struct OpenAPIGenerator {
     info: Info,
     routes: Arc<RwLock<Vec<RouteMetadata>>>,
+    router: Option<Router>,
     servers: Vec<Server>,
     security_schemes: HashMap<String, SecurityScheme>,
 }
@@ -236,6 +272,7 @@ impl OpenAPIGenerator {
                 }),
             },
             routes: Arc::new(RwLock::new(Vec::new())),
+            router: None,
             servers: vec![Server {
                 url: "http://localhost:8000".to_string(),
                 description: Some("Development server".to_string()),
@@ -244,12 +281,31 @@ impl OpenAPIGenerator {
         }
     }
 
+    /// Set the API title.
+    pub fn title(mut self, title: &str) -> Self {
+        self.info.title = title.to_string();
+        self.info.description = Some(format!("{title} - Powered by Cello Framework"));
+        self
+    }
+
+    /// Set the API version.
+    pub fn version(mut self, version: &str) -> Self {
+        self.info.version = version.to_string();
+        self
+    }
+
     /// Set the API description.
     pub fn description(mut self, desc: &str) -> Self {
         self.info.description = Some(desc.to_string());
         self
     }
 
+    /// Document every route registered on the given router.
+    pub fn router(mut self, router: Router) -> Self {
+        self.router = Some(router);
+        self
+    }
+
     /// Add a server.
     pub fn add_server(mut self, url: &str, description: Option<&str>) -> Self {
         self.servers.push(Server {
@@ -261,16 +317,8 @@ impl OpenAPIGenerator {
 
     /// Add JWT bearer authentication scheme.
     pub fn add_jwt_auth(mut self) -> Self {
-        self.security_schemes.insert(
-            "bearerAuth".to_string(),
-            SecurityScheme {
-                scheme_type: "http".to_string(),
-                scheme: Some("bearer".to_string()),
-                bearer_format: Some("JWT".to_string()),
-                name: None,
-                location: None,
-            },
-        );
+        self.security_schemes
+            .insert("bearerAuth".to_string(), bearer_scheme());
         self
     }
 
@@ -290,28 +338,70 @@ impl OpenAPIGenerator {
     }
 
     /// Register a route for OpenAPI documentation.
+    ///
+    /// Registering the same method and path again replaces the earlier metadata.
     pub fn register_route(&self, metadata: RouteMetadata) {
-        self.routes.write().push(metadata);
+        let mut routes = self.routes.write();
+        match routes
+            .iter_mut()
+            .find(|r| r.method == metadata.method && r.path == metadata.path)
+        {
+            Some(existing) => *existing = metadata,
+            None => routes.push(metadata),
+        }
+    }
+
+    /// Collect the routes to document: registered metadata first, then any
+    /// router entries without metadata (documentation endpoints excluded).
+    fn collect_routes(&self) -> Vec<RouteMetadata> {
+        let mut routes = self.routes.read().clone();
+
+        if let Some(router) = &self.router {
+            for (method, path) in router.routes() {
+                if DOCS_PATHS.contains(&path.as_str()) {
+                    continue;
+                }
+                if !routes.iter().any(|r| r.method == method && r.path == path) {
+                    routes.push(RouteMetadata::new(&method, &path));
+                }
+            }
+        }
+
+        routes
     }
 
     /// Generate the OpenAPI specification.
     pub fn generate(&self) -> OpenAPISpec {
-        let routes = self.routes.read();
+        let routes = self.collect_routes();
         let mut paths: HashMap<String, PathItem> = HashMap::new();
+        let mut security_schemes = self.security_schemes.clone();
 
         for route in routes.iter() {
-            // Convert path parameters from {param} to OpenAPI format
-            let openapi_path = route.path.clone();
-
-            let path_item = paths.entry(openapi_path.clone()).or_default();
+            let path_item = paths.entry(route.path.clone()).or_default();
 
             // Extract path parameters
             let path_params: Vec<Parameter> = extract_path_params(&route.path);
 
+            // Routes guarded by an undeclared bearer scheme get the JWT default
+            for name in &route.security {
+                if name == "bearerAuth" {
+                    security_schemes
+                        .entry(name.clone())
+                        .or_insert_with(bearer_scheme);
+                }
+            }
+
+            let has_body = matches!(route.method.as_str(), "POST" | "PUT" | "PATCH");
+
             let operation = Operation {
                 summary: route.summary.clone(),
                 description: route.description.clone(),
-                operation_id: Some(generate_operation_id(&route.method, &route.path)),
+                operation_id: Some(
+                    route
+                        .operation_id
+                        .clone()
+                        .unwrap_or_else(|| generate_operation_id(&route.method, &route.path)),
+                ),
                 tags: if route.tags.is_empty() {
                     None
                 } else {
@@ -322,30 +412,10 @@ impl OpenAPIGenerator {
                 } else {
                     Some(path_params)
                 },
-                request_body: if route.method == "POST"
-                    || route.method == "PUT"
-                    || route.method == "PATCH"
-                {
+                request_body: if has_body || route.request_schema.is_some() {
                     Some(RequestBody {
                         description: Some("Request body".to_string()),
-                        content: {
-                            let mut content = HashMap::new();
-                            content.insert(
-                                "application/json".to_string(),
-                                MediaType {
-                                    schema: Some(Schema {
-                                        schema_type: Some("object".to_string()),
-                                        format: None,
-                                        properties: None,
-                                        items: None,
-                                        reference: None,
-                                        required: None,
-                                        example: None,
-                                    }),
-                                },
-                            );
-                            content
-                        },
+                        content: json_content(route.request_schema.clone()),
                         required: Some(true),
                     })
                 } else {
@@ -357,29 +427,31 @@ impl OpenAPIGenerator {
                         "200".to_string(),
                         Response {
                             description: "Successful response".to_string(),
-                            content: Some({
-                                let mut content = HashMap::new();
-                                content.insert(
-                                    "application/json".to_string(),
-                                    MediaType {
-                                        schema: Some(Schema {
-                                            schema_type: Some("object".to_string()),
-                                            format: None,
-                                            properties: None,
-                                            items: None,
-                                            reference: None,
-                                            required: None,
-                                            example: None,
-                                        }),
-                                    },
-                                );
-                                content
-                            }),
+                            content: Some(json_content(route.response_schema.clone())),
                         },
                     );
+                    if route.request_schema.is_some() {
+                        responses.insert(
+                            "422".to_string(),
+                            Response {
+                                description: "Validation error".to_string(),
+                                content: None,
+                            },
+                        );
+                    }
                     responses
                 },
-                security: None,
+                security: if route.security.is_empty() {
+                    None
+                } else {
+                    Some(
+                        route
+                            .security
+                            .iter()
+                            .map(|name| HashMap::from([(name.clone(), Vec::new())]))
+                            .collect(),
+                    )
+                },
                 deprecated: if route.deprecated { Some(true) } else { None },
             };
 
@@ -396,16 +468,16 @@ impl OpenAPIGenerator {
         }
 
         OpenAPISpec {
-            openapi: "3.0.3".to_string(),
+            openapi: OPENAPI_VERSION.to_string(),
             info: self.info.clone(),
             servers: Some(self.servers.clone()),
             paths,
-            components: if self.security_schemes.is_empty() {
+            components: if security_schemes.is_empty() {
                 None
             } else {
                 Some(Components {
                     schemas: None,
-                    security_schemes: Some(self.security_schemes.clone()),
+                    security_schemes: Some(security_schemes),
                 })
             },
             tags: None,
@@ -418,6 +490,28 @@ impl OpenAPIGenerator {
     }
 }
 
+/// Default JWT bearer security scheme.
+fn bearer_scheme() -> SecurityScheme {
+    SecurityScheme {
+        scheme_type: "http".to_string(),
+        scheme: Some("bearer".to_string()),
+        bearer_format: Some("JWT".to_string()),
+        name: None,
+        location: None,
+    }
+}
+
+/// Build an `application/json` content map, defaulting to a generic object schema.
+fn json_content(schema: Option<serde_json::Value>) -> HashMap<String, MediaType> {
+    let schema = schema.unwrap_or_else(|| serde_json::json!({"type": "object"}));
+    HashMap::from([(
+        "application/json".to_string(),
+        MediaType {
+            schema: Some(schema),
+        },
+    )])
+}
+
 /// Extract path parameters from a route path.
 /// PERF: Use thread_local cached regex instead of compiling on every call.
 fn extract_path_params(path: &str) -> Vec<Parameter> {
@@ -459,6 +553,53 @@ fn generate_operation_id(method: &str, path: &str) -> String {
     format!("{}_{}", method.to_lowercase(), clean_path)
 }
 
+// ============================================================================
+// Documentation Handlers
+// ============================================================================
+
+/// Handler serving the generated OpenAPI spec as JSON.
+///
+/// The spec is generated per request so routes added after `enable_openapi`
+/// are included.
+#[pyclass]
+pub struct OpenApiSpecHandler {
+    generator: OpenAPIGenerator,
+}
+
+impl OpenApiSpecHandler {
+    pub fn new(generator: OpenAPIGenerator) -> Self {
+        Self { generator }
+    }
+}
+
+#[pymethods]
+impl OpenApiSpecHandler {
+    fn __call__(&self, py: Python<'_>, _request: &PyAny) -> PyResult<PyObject> {
+        let spec = serde_json::to_value(self.generator.generate())
+            .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))?;
+        json_to_python(py, &spec)
+    }
+}
+
+/// Handler serving a fixed HTML documentation page.
+#[pyclass]
+pub struct DocsPageHandler {
+    html: String,
+}
+
+impl DocsPageHandler {
+    pub fn new(html: String) -> Self {
+        Self { html }
+    }
+}
+
+#[pymethods]
+impl DocsPageHandler {
+    fn __call__(&self, _request: &PyAny) -> crate::response::Response {
+        crate::response::Response::html(&self.html, None)
+    }
+}
+
 // ============================================================================
 // Swagger UI HTML Generator
 // ============================================================================
@@ -481,13 +622,14 @@ pub fn swagger_ui_html(openapi_url: &str, title: &str) -> String {
 <body>
     <div id="swagger-ui"></div>
     <script src="https://unpkg.com/swagger-ui-dist@5.9.0/swagger-ui-bundle.js"></script>
+    <script src="https://unpkg.com/swagger-ui-dist@5.9.0/swagger-ui-standalone-preset.js"></script>
     <script>
         window.onload = () => {{
             window.ui = SwaggerUIBundle({{
                 url: "{openapi_url}",
                 dom_id: '#swagger-ui',
                 deepLinking: true,
-                presets: [SwaggerUIBundle.presets.apis, SwaggerUIBundle.SwaggerUIStandalonePreset],
+                presets: [SwaggerUIBundle.presets.apis, SwaggerUIStandalonePreset],
                 layout: "StandaloneLayout"
             }});
         }};
@@ -538,10 +680,11 @@ mod tests {
             description: Some("Retrieves a user by their unique identifier".to_string()),
             tags: vec!["users".to_string()],
             deprecated: false,
+            ..Default::default()
         });
 
         let spec = generator.generate();
-        assert_eq!(spec.openapi, "3.0.3");
+        assert_eq!(spec.openapi, "3.1.0");
         assert_eq!(spec.info.title, "Test API");
         assert!(spec.paths.contains_key("/users/{id}"));
     }
@@ -560,4 +703,61 @@ mod tests {
         assert!(html.contains("swagger-ui"));
         assert!(html.contains("/openapi.json"));
     }
+
+    #[test]
+    fn test_generator_walks_router() {
+        let mut router = Router::new();
+        router.add_route("GET", "/items/{id}", 0).unwrap();
+        router.add_route("POST", "/items", 1).unwrap();
+        router.add_route("GET", "/openapi.json", 2).unwrap();
+
+        let generator = OpenAPIGenerator::new("Items", "2.0.0").router(router);
+        generator.register_route(RouteMetadata {
+            summary: Some("Create item".to_string()),
+            request_schema: Some(serde_json::json!({
+                "type": "object",
+                "properties": {"name": {"type": "string"}},
+                "required": ["name"]
+            })),
+            security: vec!["bearerAuth".to_string()],
+            ..RouteMetadata::new("POST", "/items")
+        });
+
+        let spec = serde_json::to_value(generator.generate()).unwrap();
+        let paths = spec["paths"].as_object().unwrap();
+        assert_eq!(paths.len(), 2);
+        assert!(!paths.contains_key("/openapi.json"));
+
+        // Undocumented routes still appear with their path parameters
+        let get = &spec["paths"]["/items/{id}"]["get"];
+        assert_eq!(get["operationId"], "get_items_id");
+        assert_eq!(get["parameters"][0]["name"], "id");
+
+        let post = &spec["paths"]["/items"]["post"];
+        assert_eq!(post["summary"], "Create item");
+        assert_eq!(
+            post["requestBody"]["content"]["application/json"]["schema"]["required"][0],
+            "name"
+        );
+        assert!(post["responses"].get("422").is_some());
+        assert!(post["security"][0].get("bearerAuth").is_some());
+        assert_eq!(
+            spec["components"]["securitySchemes"]["bearerAuth"]["scheme"],
+            "bearer"
+        );
+    }
+
+    #[test]
+    fn test_register_route_replaces_metadata() {
+        let generator = OpenAPIGenerator::new("API", "1.0.0");
+        generator.register_route(RouteMetadata::new("GET", "/a"));
+        generator.register_route(RouteMetadata {
+            summary: Some("Updated".to_string()),
+            ..RouteMetadata::new("get", "/a")
+        });
+
+        let spec = generator.generate();
+        let op = spec.paths["/a"].get.as_ref().unwrap();
+        assert_eq!(op.summary.as_deref(), Some("Updated"));
+    }
 }
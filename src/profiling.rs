@@ -0,0 +1,533 @@
+//! Profiling for live deployments.
+//!
+//! `CpuProfiler` samples which threads of the process were on CPU between
+//! two samples, from the kernel's per-thread CPU accounting, and charges
+//! the CPU time to the thread's current Python stack when it runs Python
+//! code. Threads that run no Python code (Tokio workers, background Rust
+//! threads) are sampled natively on Unix: a CPU-time timer interrupts
+//! whichever thread is running and records its Rust and C frames. Where
+//! native sampling is unavailable they are charged by thread name. The
+//! result is written as folded stacks, the input format of flamegraph tools.
+//!
+//! `memory_stats` reports the process's resident memory, the C heap as seen
+//! by the allocator and Python's allocations, with the top allocation sites
+//! when `tracemalloc` is tracing.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::time::Instant;
+
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+
+/// CPU time used by one thread, in clock ticks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ThreadCpu {
+    name: String,
+    ticks: u64,
+}
+
+/// CPU time used by each thread of this process, by native thread id.
+#[cfg(target_os = "linux")]
+fn thread_cpu() -> HashMap<u64, ThreadCpu> {
+    let mut threads = HashMap::new();
+    let Ok(tasks) = std::fs::read_dir("/proc/self/task") else {
+        return threads;
+    };
+    for task in tasks.flatten() {
+        let Some(tid) = task.file_name().to_str().and_then(|s| s.parse().ok()) else {
+            continue;
+        };
+        if let Some(cpu) = std::fs::read_to_string(task.path().join("stat"))
+            .ok()
+            .and_then(|stat| parse_stat(&stat))
+        {
+            threads.insert(tid, cpu);
+        }
+    }
+    threads
+}
+
+#[cfg(not(target_os = "linux"))]
+fn thread_cpu() -> HashMap<u64, ThreadCpu> {
+    HashMap::new()
+}
+
+/// Name and user plus system time from a `/proc/<pid>/task/<tid>/stat` line.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_stat(stat: &str) -> Option<ThreadCpu> {
+    // The name is in parentheses and may itself contain spaces or parentheses
+    let open = stat.find('(')?;
+    let close = stat.rfind(')')?;
+    let name = stat.get(open + 1..close)?.to_string();
+    // Fields after the name start at `state`; utime and stime follow 10 later
+    let mut fields = stat.get(close + 1..)?.split_whitespace().skip(11);
+    let utime: u64 = fields.next()?.parse().ok()?;
+    let stime: u64 = fields.next()?.parse().ok()?;
+    Some(ThreadCpu {
+        name,
+        ticks: utime + stime,
+    })
+}
+
+/// Clock ticks per second of the kernel's CPU accounting.
+fn ticks_per_second() -> u64 {
+    #[cfg(unix)]
+    {
+        // SAFETY: sysconf only reads a system constant
+        let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+        if ticks > 0 {
+            return ticks as u64;
+        }
+    }
+    100
+}
+
+/// A thread known to Python's `threading` module.
+#[derive(Debug, Clone, Default)]
+struct PyThread {
+    /// `threading.get_ident()`, the thread's `pthread_t`
+    ident: u64,
+    name: String,
+    /// Current Python stack, root first
+    stack: Vec<String>,
+}
+
+/// The threads known to `threading`, by native thread id.
+///
+/// The stack of the thread that takes the sample is left empty.
+fn python_threads(py: Python<'_>) -> PyResult<HashMap<u64, PyThread>> {
+    let threading = py.import("threading")?;
+    let current: u64 = threading.call_method0("get_native_id")?.extract()?;
+    let frames: &PyDict = py
+        .import("sys")?
+        .call_method0("_current_frames")?
+        .downcast()?;
+
+    let mut threads = HashMap::new();
+    for thread in threading.call_method0("enumerate")?.iter()? {
+        let thread = thread?;
+        let ident: Option<u64> = thread.getattr("ident")?.extract()?;
+        let native_id: Option<u64> = thread.getattr("native_id")?.extract()?;
+        let (Some(ident), Some(native_id)) = (ident, native_id) else {
+            continue;
+        };
+        let mut stack = Vec::new();
+        if native_id != current {
+            let mut frame = frames.get_item(ident)?;
+            while let Some(f) = frame.filter(|f| !f.is_none()) {
+                stack.push(frame_name(f)?);
+                frame = Some(f.getattr("f_back")?);
+            }
+            stack.reverse();
+        }
+        let name = thread.getattr("name")?.extract()?;
+        threads.insert(native_id, PyThread { ident, name, stack });
+    }
+    Ok(threads)
+}
+
+/// `qualname (file:line)` of a frame's function.
+fn frame_name(frame: &PyAny) -> PyResult<String> {
+    let code = frame.getattr("f_code")?;
+    let name: String = code.getattr("co_qualname")?.extract()?;
+    let file: String = code.getattr("co_filename")?.extract()?;
+    let line: u32 = code.getattr("co_firstlineno")?.extract()?;
+    let file = file.rsplit('/').next().unwrap_or(&file);
+    // `;` separates frames in folded stacks
+    Ok(format!("{name} ({file}:{line})").replace(';', ":"))
+}
+
+/// Native stacks of the threads that are on CPU, sampled `hz` times per
+/// second of CPU time.
+///
+/// Only one sampler runs per process; `None` if another is running.
+#[cfg(unix)]
+fn native_sampler(hz: u64) -> Option<pprof::ProfilerGuard<'static>> {
+    pprof::ProfilerGuardBuilder::default()
+        .frequency(hz as i32)
+        // Unwinding through these while they hold locks can deadlock
+        .blocklist(&["libc", "libgcc", "pthread", "vdso"])
+        .build()
+        .ok()
+}
+
+/// Folded native stacks, by `(pthread_t, stack)`, each root first and led
+/// by the thread's name.
+#[cfg(unix)]
+fn native_stacks(sampler: &pprof::ProfilerGuard<'static>) -> HashMap<(u64, String), u64> {
+    let mut stacks = HashMap::new();
+    let Ok(report) = sampler.report().build() else {
+        return stacks;
+    };
+    for (frames, count) in report.data {
+        let mut stack = frames.thread_name_or_id().replace(';', ":");
+        for symbol in frames
+            .frames
+            .iter()
+            .rev()
+            .flat_map(|frame| frame.iter().rev())
+        {
+            stack.push(';');
+            stack.push_str(&symbol.name().replace(';', ":"));
+        }
+        *stacks.entry((frames.thread_id, stack)).or_default() += count.max(0) as u64;
+    }
+    stacks
+}
+
+/// Samples the process's CPU use into folded stacks.
+///
+/// Call `sample()` at a regular interval, shorter than a clock tick
+/// (usually 10ms) for the best resolution, then `folded()` for the profile.
+#[pyclass(name = "CpuProfiler")]
+pub struct CpuProfiler {
+    started: Instant,
+    last: HashMap<u64, ThreadCpu>,
+    samples: u64,
+    /// CPU ticks of Python threads by folded stack
+    stacks: HashMap<String, u64>,
+    /// CPU ticks of other threads by thread name
+    native: HashMap<String, u64>,
+    /// CPU ticks by thread name
+    threads: HashMap<String, u64>,
+    /// `pthread_t` of every thread seen running Python code
+    python_idents: HashSet<u64>,
+    /// Native stack sampler, at one sample per clock tick
+    #[cfg(unix)]
+    sampler: Option<pprof::ProfilerGuard<'static>>,
+}
+
+impl CpuProfiler {
+    fn start() -> Self {
+        // Started first, so loading symbol tables is not part of the profile
+        #[cfg(unix)]
+        let sampler = native_sampler(ticks_per_second());
+        Self {
+            started: Instant::now(),
+            last: thread_cpu(),
+            samples: 0,
+            stacks: HashMap::new(),
+            native: HashMap::new(),
+            threads: HashMap::new(),
+            python_idents: HashSet::new(),
+            #[cfg(unix)]
+            sampler,
+        }
+    }
+
+    /// Charge the CPU used since the last sample, given each thread's
+    /// current CPU time and the threads known to Python.
+    fn record(&mut self, now: HashMap<u64, ThreadCpu>, python: &HashMap<u64, PyThread>) {
+        self.samples += 1;
+        for (tid, cpu) in &now {
+            let before = self.last.get(tid).map_or(0, |last| last.ticks);
+            let used = cpu.ticks.saturating_sub(before);
+            if used == 0 {
+                continue;
+            }
+            // Python names its threads; the OS name is usually the program's
+            let name = match python.get(tid) {
+                Some(thread) => {
+                    let mut stack = thread.name.replace(';', ":");
+                    for frame in &thread.stack {
+                        stack.push(';');
+                        stack.push_str(frame);
+                    }
+                    *self.stacks.entry(stack).or_default() += used;
+                    &thread.name
+                }
+                None => {
+                    *self.native.entry(cpu.name.replace(';', ":")).or_default() += used;
+                    &cpu.name
+                }
+            };
+            *self.threads.entry(name.clone()).or_default() += used;
+        }
+        self.python_idents
+            .extend(python.values().map(|thread| thread.ident));
+        self.last = now;
+    }
+
+    /// Folded stacks of the threads running no Python code, with their
+    /// native frames when they were sampled.
+    fn native_folded(&self) -> HashMap<String, u64> {
+        #[cfg(unix)]
+        if let Some(sampler) = &self.sampler {
+            let stacks: HashMap<String, u64> = native_stacks(sampler)
+                .into_iter()
+                .filter(|((ident, _), _)| !self.python_idents.contains(ident))
+                .fold(HashMap::new(), |mut stacks, ((_, stack), ticks)| {
+                    *stacks.entry(stack).or_default() += ticks;
+                    stacks
+                });
+            if !stacks.is_empty() {
+                return stacks;
+            }
+        }
+        self.native.clone()
+    }
+
+    /// The folded stacks, heaviest first.
+    fn fold(&self) -> String {
+        let mut stacks = self.native_folded();
+        for (stack, ticks) in &self.stacks {
+            *stacks.entry(stack.clone()).or_default() += ticks;
+        }
+        let mut stacks: Vec<_> = stacks.into_iter().collect();
+        stacks.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        let mut out = String::new();
+        for (stack, ticks) in stacks {
+            let _ = writeln!(out, "{stack} {ticks}");
+        }
+        out
+    }
+}
+
+#[pymethods]
+impl CpuProfiler {
+    /// Start profiling. CPU used before this point is not counted.
+    #[new]
+    pub fn new(py: Python<'_>) -> Self {
+        // The native sampler loads the symbol tables on first use
+        py.allow_threads(Self::start)
+    }
+
+    /// Take a sample.
+    pub fn sample(&mut self, py: Python<'_>) -> PyResult<()> {
+        let now = thread_cpu();
+        let python = python_threads(py)?;
+        self.record(now, &python);
+        Ok(())
+    }
+
+    /// The profile as folded stacks: one `thread;frame;frame ticks` line per
+    /// stack, heaviest first.
+    pub fn folded(&self, py: Python<'_>) -> String {
+        // Native frames are symbolized here
+        py.allow_threads(|| self.fold())
+    }
+
+    /// Profile duration, sample count and CPU seconds per thread name.
+    pub fn summary(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let hz = ticks_per_second() as f64;
+        let threads = PyDict::new(py);
+        for (name, ticks) in &self.threads {
+            threads.set_item(name, *ticks as f64 / hz)?;
+        }
+        let dict = PyDict::new(py);
+        dict.set_item("duration_seconds", self.started.elapsed().as_secs_f64())?;
+        dict.set_item("samples", self.samples)?;
+        dict.set_item("ticks_per_second", hz as u64)?;
+        dict.set_item(
+            "cpu_seconds",
+            self.threads.values().sum::<u64>() as f64 / hz,
+        )?;
+        dict.set_item("threads", threads)?;
+        Ok(dict.into())
+    }
+}
+
+impl Default for CpuProfiler {
+    fn default() -> Self {
+        Self::start()
+    }
+}
+
+/// Memory use of the process.
+///
+/// Returns a dict with `process` (resident and virtual memory from
+/// `/proc/self/status`), `heap` (the C allocator's arenas, glibc only) and
+/// `python` (allocated blocks, garbage collector counts and, when
+/// `tracemalloc` is tracing, its totals and the `top` allocation sites).
+#[pyfunction]
+#[pyo3(signature = (top = 20))]
+pub fn memory_stats(py: Python<'_>, top: usize) -> PyResult<PyObject> {
+    let stats = PyDict::new(py);
+
+    let process = PyDict::new(py);
+    if let Ok(status) = std::fs::read_to_string("/proc/self/status") {
+        for (field, key) in [
+            ("VmRSS", "rss_bytes"),
+            ("VmHWM", "peak_rss_bytes"),
+            ("VmSize", "virtual_bytes"),
+            ("Threads", "threads"),
+        ] {
+            if let Some(value) = status_field(&status, field) {
+                process.set_item(key, value)?;
+            }
+        }
+    }
+    stats.set_item("process", process)?;
+    stats.set_item("heap", heap_stats(py)?)?;
+
+    let sys = py.import("sys")?;
+    let gc = py.import("gc")?;
+    let python = PyDict::new(py);
+    python.set_item("allocated_blocks", sys.call_method0("getallocatedblocks")?)?;
+    python.set_item("gc_counts", gc.call_method0("get_count")?)?;
+    let tracemalloc = py.import("tracemalloc")?;
+    let tracing: bool = tracemalloc.call_method0("is_tracing")?.extract()?;
+    python.set_item("tracemalloc", tracing)?;
+    if tracing {
+        let (current, peak): (u64, u64) =
+            tracemalloc.call_method0("get_traced_memory")?.extract()?;
+        python.set_item("traced_bytes", current)?;
+        python.set_item("peak_traced_bytes", peak)?;
+        let statistics = tracemalloc
+            .call_method0("take_snapshot")?
+            .call_method1("statistics", ("lineno",))?;
+        let sites = PyList::empty(py);
+        for stat in statistics.iter()?.take(top) {
+            let stat = stat?;
+            let frame = stat.getattr("traceback")?.get_item(0)?;
+            let site = PyDict::new(py);
+            site.set_item("file", frame.getattr("filename")?)?;
+            site.set_item("line", frame.getattr("lineno")?)?;
+            site.set_item("bytes", stat.getattr("size")?)?;
+            site.set_item("blocks", stat.getattr("count")?)?;
+            sites.append(site)?;
+        }
+        python.set_item("top", sites)?;
+    }
+    stats.set_item("python", python)?;
+
+    Ok(stats.into())
+}
+
+/// A field of `/proc/self/status`, with sizes converted from kB to bytes.
+fn status_field(status: &str, field: &str) -> Option<u64> {
+    let line = status
+        .lines()
+        .find(|line| line.split(':').next() == Some(field))?;
+    let mut parts = line.split(':').nth(1)?.split_whitespace();
+    let value: u64 = parts.next()?.parse().ok()?;
+    match parts.next() {
+        Some("kB") => Some(value * 1024),
+        _ => Some(value),
+    }
+}
+
+/// The C heap as reported by glibc's `mallinfo2`.
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+fn heap_stats(py: Python<'_>) -> PyResult<PyObject> {
+    // SAFETY: mallinfo2 only reads the allocator's statistics
+    let info = unsafe { libc::mallinfo2() };
+    let heap = PyDict::new(py);
+    heap.set_item("arena_bytes", info.arena)?;
+    heap.set_item("mmap_bytes", info.hblkhd)?;
+    heap.set_item("in_use_bytes", info.uordblks)?;
+    heap.set_item("free_bytes", info.fordblks)?;
+    heap.set_item("releasable_bytes", info.keepcost)?;
+    Ok(heap.into())
+}
+
+#[cfg(not(all(target_os = "linux", target_env = "gnu")))]
+fn heap_stats(py: Python<'_>) -> PyResult<PyObject> {
+    Ok(PyDict::new(py).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_stat() {
+        let stat = "4242 (tokio (w) 1) S 1 4242 4242 0 -1 4194560 \
+                    1200 0 3 0 57 13 0 0 20 0 9 0 123 0 0";
+        assert_eq!(
+            parse_stat(stat),
+            Some(ThreadCpu {
+                name: "tokio (w) 1".to_string(),
+                ticks: 70,
+            })
+        );
+        assert_eq!(parse_stat("4242 (truncated"), None);
+    }
+
+    #[test]
+    fn test_folded_stacks() {
+        let cpu = |name: &str, ticks| ThreadCpu {
+            name: name.to_string(),
+            ticks,
+        };
+        let mut profiler = CpuProfiler {
+            started: Instant::now(),
+            last: HashMap::from([(1, cpu("tokio-runtime-w", 10)), (2, cpu("python", 5))]),
+            samples: 0,
+            stacks: HashMap::new(),
+            native: HashMap::new(),
+            threads: HashMap::new(),
+            python_idents: HashSet::new(),
+            #[cfg(unix)]
+            sampler: None,
+        };
+        let python = HashMap::from([(
+            2,
+            PyThread {
+                ident: 7,
+                name: "worker".to_string(),
+                stack: vec!["run (app.py:1)".to_string()],
+            },
+        )]);
+        profiler.record(
+            HashMap::from([(1, cpu("tokio-runtime-w", 13)), (2, cpu("python", 6))]),
+            &python,
+        );
+        profiler.record(
+            HashMap::from([(1, cpu("tokio-runtime-w", 13)), (2, cpu("python", 8))]),
+            &python,
+        );
+
+        assert_eq!(
+            profiler.fold(),
+            "tokio-runtime-w 3\nworker;run (app.py:1) 3\n"
+        );
+        assert_eq!(profiler.samples, 2);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_native_stacks() {
+        #[inline(never)]
+        fn spin(until: Instant) -> u64 {
+            let mut n = 0u64;
+            while Instant::now() < until {
+                n = std::hint::black_box(n.wrapping_mul(31).wrapping_add(7));
+            }
+            n
+        }
+
+        let mut profiler = CpuProfiler::start();
+        assert!(profiler.sampler.is_some());
+        let until = Instant::now() + std::time::Duration::from_millis(400);
+        let worker = std::thread::Builder::new()
+            .name("cello-spin".to_string())
+            .spawn(move || spin(until))
+            .unwrap();
+        while !worker.is_finished() {
+            profiler.record(thread_cpu(), &HashMap::new());
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        worker.join().unwrap();
+        profiler.record(thread_cpu(), &HashMap::new());
+
+        // The spinning thread is charged with its Rust frames, not just its name
+        let folded = profiler.fold();
+        assert!(
+            folded
+                .lines()
+                .any(|line| line.starts_with("cello-spin;") && line.contains("spin")),
+            "{folded}"
+        );
+        assert!(!folded.lines().any(|line| line.starts_with("cello-spin ")));
+    }
+
+    #[test]
+    fn test_status_field() {
+        let status = "Name:\tpython\nVmHWM:\t  2048 kB\nVmRSS:\t  1024 kB\nThreads:\t7\n";
+        assert_eq!(status_field(status, "VmRSS"), Some(1024 * 1024));
+        assert_eq!(status_field(status, "Threads"), Some(7));
+        assert_eq!(status_field(status, "VmSwap"), None);
+    }
+}
@@ -0,0 +1,232 @@
+//! Redaction of secrets and personal data.
+//!
+//! Rules are applied to text that leaves the process: the access log, error
+//! reports and the debug error page. A rule is one of:
+//! - a header name, whose value is replaced whole
+//! - a JSON field path such as `user.password` or `cards.*.number`, or a
+//!   bare field name such as `ssn`, matched at any depth and also against
+//!   query and path parameter names
+//! - a regex, whose matches are replaced in any text
+//!
+//! Names are matched case-insensitively.
+
+use std::borrow::Cow;
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+use regex::Regex;
+use serde_json::Value;
+
+/// Headers redacted when no header names are given.
+pub const DEFAULT_HEADERS: [&str; 5] = [
+    "authorization",
+    "cookie",
+    "set-cookie",
+    "proxy-authorization",
+    "x-api-key",
+];
+
+/// A set of redaction rules.
+#[derive(Debug, Clone)]
+pub struct RedactionRules {
+    headers: Vec<String>,
+    fields: Vec<Vec<String>>,
+    patterns: Vec<Regex>,
+    replacement: String,
+}
+
+impl Default for RedactionRules {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RedactionRules {
+    /// No rules, replacing redacted values by `[REDACTED]`.
+    pub fn new() -> Self {
+        Self {
+            headers: Vec::new(),
+            fields: Vec::new(),
+            patterns: Vec::new(),
+            replacement: "[REDACTED]".to_string(),
+        }
+    }
+
+    /// Redact the value of the header `name`.
+    pub fn header(mut self, name: &str) -> Self {
+        self.headers.push(name.to_ascii_lowercase());
+        self
+    }
+
+    /// Redact the JSON field at the dotted `path`; `*` matches any key or
+    /// array element.
+    pub fn field(mut self, path: &str) -> Self {
+        self.fields.push(
+            path.split('.')
+                .map(|segment| segment.to_ascii_lowercase())
+                .collect(),
+        );
+        self
+    }
+
+    /// Redact text matching `pattern`.
+    pub fn pattern(mut self, pattern: &str) -> Result<Self, regex::Error> {
+        self.patterns.push(Regex::new(pattern)?);
+        Ok(self)
+    }
+
+    /// Replace redacted values by `replacement`.
+    pub fn replacement(mut self, replacement: &str) -> Self {
+        self.replacement = replacement.to_string();
+        self
+    }
+
+    /// Redact the matches of every pattern in `text`.
+    pub fn text<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut text = Cow::Borrowed(text);
+        for pattern in &self.patterns {
+            if let Cow::Owned(replaced) = pattern.replace_all(&text, self.replacement.as_str()) {
+                text = Cow::Owned(replaced);
+            }
+        }
+        text
+    }
+
+    /// The value of a header or parameter: replaced whole if a header rule
+    /// or bare field rule names it, otherwise with patterns redacted.
+    pub fn value<'a>(&self, name: &str, value: &'a str) -> Cow<'a, str> {
+        let name = name.to_ascii_lowercase();
+        let named = self.headers.contains(&name)
+            || self
+                .fields
+                .iter()
+                .any(|field| field.len() == 1 && field[0] == name);
+        if named {
+            Cow::Owned(self.replacement.clone())
+        } else {
+            self.text(value)
+        }
+    }
+
+    /// Redact matching fields of `value`, and patterns in its strings.
+    pub fn json(&self, value: &mut Value) {
+        self.walk(value, &mut Vec::new());
+    }
+
+    fn walk(&self, value: &mut Value, path: &mut Vec<String>) {
+        match value {
+            Value::Object(map) => {
+                for (key, child) in map.iter_mut() {
+                    path.push(key.to_ascii_lowercase());
+                    self.visit(child, path);
+                    path.pop();
+                }
+            }
+            Value::Array(items) => {
+                for (index, child) in items.iter_mut().enumerate() {
+                    path.push(index.to_string());
+                    self.visit(child, path);
+                    path.pop();
+                }
+            }
+            Value::String(text) => {
+                if let Cow::Owned(redacted) = self.text(text) {
+                    *text = redacted;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn visit(&self, child: &mut Value, path: &mut Vec<String>) {
+        if self.fields.iter().any(|field| matches(field, path)) {
+            *child = Value::String(self.replacement.clone());
+        } else {
+            self.walk(child, path);
+        }
+    }
+}
+
+/// Whether the field rule `field` names the value at `path`.
+fn matches(field: &[String], path: &[String]) -> bool {
+    match field {
+        [name] => path.last() == Some(name),
+        _ => {
+            field.len() == path.len()
+                && field
+                    .iter()
+                    .zip(path)
+                    .all(|(rule, segment)| rule == "*" || rule == segment)
+        }
+    }
+}
+
+/// The app's redaction rules, shared by the access log, error reporting and
+/// debug pages. Empty until rules are set.
+#[derive(Clone, Default)]
+pub struct Redaction {
+    rules: Arc<RwLock<Option<Arc<RedactionRules>>>>,
+}
+
+impl Redaction {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the rules.
+    pub fn set(&self, rules: RedactionRules) {
+        *self.rules.write() = Some(Arc::new(rules));
+    }
+
+    /// The rules, if set.
+    pub fn rules(&self) -> Option<Arc<RedactionRules>> {
+        self.rules.read().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rules() -> RedactionRules {
+        RedactionRules::new()
+            .header("Authorization")
+            .field("password")
+            .field("cards.*.number")
+            .pattern(r"[\w.+-]+@[\w-]+\.[\w.]+")
+            .unwrap()
+    }
+
+    #[test]
+    fn test_redact_values_and_text() {
+        let rules = rules();
+        assert_eq!(rules.value("authorization", "Bearer x"), "[REDACTED]");
+        assert_eq!(rules.value("Password", "hunter2"), "[REDACTED]");
+        assert_eq!(rules.value("user-agent", "curl/8"), "curl/8");
+        assert!(matches!(rules.text("no secrets"), Cow::Borrowed(_)));
+        assert_eq!(
+            rules.text("mail ann@example.com or bob@example.org"),
+            "mail [REDACTED] or [REDACTED]"
+        );
+    }
+
+    #[test]
+    fn test_redact_json_fields() {
+        let rules = rules().replacement("***");
+        let mut body = json!({
+            "user": {"email": "ann@example.com", "password": "hunter2"},
+            "cards": [{"number": "4111", "brand": "visa"}],
+            "number": "7",
+        });
+        rules.json(&mut body);
+        assert_eq!(
+            body,
+            json!({
+                "user": {"email": "***", "password": "***"},
+                "cards": [{"number": "***", "brand": "visa"}],
+                "number": "7",
+            })
+        );
+    }
+}
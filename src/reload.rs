@@ -0,0 +1,361 @@
+//! Development-mode Route Reload for Cello Framework
+//!
+//! Watches the application's source directories and, when a file changes,
+//! rebuilds the routes in-process and swaps them into the running server:
+//! - Native file watching via `notify` (inotify, FSEvents, ReadDirectoryChangesW)
+//! - Debouncing, so an editor's burst of writes triggers one reload
+//! - Requests in flight finish on the routes they started with
+//! - A failed reload logs the error and keeps serving the previous routes
+//!
+//! Only routes and handlers are replaced; middleware, configuration and
+//! lifecycle hooks keep their startup values until the server restarts.
+//!
+//! # Example
+//! ```bash
+//! python app.py --reload
+//! ```
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use parking_lot::Mutex;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use crate::handler::HandlerRegistry;
+use crate::router::Router;
+use crate::server::RouteHandle;
+
+// ============================================================================
+// Configuration
+// ============================================================================
+
+/// What the reloader watches and how long it waits for changes to settle.
+#[derive(Debug, Clone)]
+pub struct ReloadConfig {
+    /// Directories watched recursively.
+    pub watch_dirs: Vec<PathBuf>,
+    /// File extensions (without the dot) that trigger a reload.
+    pub extensions: Vec<String>,
+    /// Directory names never watched.
+    pub ignore_dirs: Vec<String>,
+    /// Quiet period after the last change before reloading.
+    pub debounce: Duration,
+}
+
+impl ReloadConfig {
+    /// Watch `watch_dirs` for changes to `.py` files.
+    pub fn new(watch_dirs: Vec<PathBuf>) -> Self {
+        Self {
+            watch_dirs,
+            extensions: vec!["py".to_string()],
+            ignore_dirs: [".git", ".venv", "__pycache__", "node_modules", "target"]
+                .iter()
+                .map(|d| d.to_string())
+                .collect(),
+            debounce: Duration::from_millis(200),
+        }
+    }
+
+    /// Set the file extensions that trigger a reload.
+    pub fn with_extensions(mut self, extensions: Vec<String>) -> Self {
+        self.extensions = extensions
+            .into_iter()
+            .map(|e| e.trim_start_matches('.').to_string())
+            .collect();
+        self
+    }
+
+    /// Set the debounce period.
+    pub fn with_debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    /// Whether a change to `path` should trigger a reload.
+    pub fn is_watched(&self, path: &Path) -> bool {
+        let ignored = path.components().any(|c| {
+            let part = c.as_os_str().to_string_lossy();
+            self.ignore_dirs.iter().any(|d| *d == part)
+        });
+        if ignored {
+            return false;
+        }
+        path.extension()
+            .map(|ext| ext.to_string_lossy())
+            .is_some_and(|ext| self.extensions.iter().any(|e| *e == ext))
+    }
+
+    fn is_relevant(&self, event: &Event) -> bool {
+        matches!(
+            event.kind,
+            EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+        ) && event.paths.iter().any(|p| self.is_watched(p))
+    }
+}
+
+impl Default for ReloadConfig {
+    fn default() -> Self {
+        Self::new(vec![PathBuf::from(".")])
+    }
+}
+
+// ============================================================================
+// Reloader
+// ============================================================================
+
+/// Rebuilds the application's routes, e.g. by re-importing its module.
+pub type RouteLoader = Arc<dyn Fn() -> Result<(Router, HandlerRegistry), String> + Send + Sync>;
+
+/// Watches source files and swaps freshly loaded routes into a server.
+pub struct Reloader {
+    config: ReloadConfig,
+    loader: RouteLoader,
+    running: Mutex<Option<CancellationToken>>,
+    reloads: AtomicU64,
+    failures: AtomicU64,
+}
+
+impl Reloader {
+    /// Create a reloader rebuilding routes with `loader`.
+    pub fn new<F>(config: ReloadConfig, loader: F) -> Self
+    where
+        F: Fn() -> Result<(Router, HandlerRegistry), String> + Send + Sync + 'static,
+    {
+        Self {
+            config,
+            loader: Arc::new(loader),
+            running: Mutex::new(None),
+            reloads: AtomicU64::new(0),
+            failures: AtomicU64::new(0),
+        }
+    }
+
+    /// The watch configuration.
+    pub fn config(&self) -> &ReloadConfig {
+        &self.config
+    }
+
+    /// Whether the reloader is watching for changes.
+    pub fn is_running(&self) -> bool {
+        self.running.lock().is_some()
+    }
+
+    /// Number of successful reloads.
+    pub fn reloads(&self) -> u64 {
+        self.reloads.load(Ordering::Relaxed)
+    }
+
+    /// Number of failed reloads.
+    pub fn failures(&self) -> u64 {
+        self.failures.load(Ordering::Relaxed)
+    }
+
+    /// Rebuild the routes and swap them into `routes`.
+    ///
+    /// On failure the current routes stay in place.
+    pub fn reload(&self, routes: &RouteHandle) -> Result<(), String> {
+        match (self.loader)() {
+            Ok((router, handlers)) => {
+                routes.swap(router, handlers);
+                self.reloads.fetch_add(1, Ordering::Relaxed);
+                Ok(())
+            }
+            Err(e) => {
+                self.failures.fetch_add(1, Ordering::Relaxed);
+                Err(e)
+            }
+        }
+    }
+
+    /// Start watching and reload `routes` on changes. Must be called within
+    /// a Tokio runtime; does nothing if already running.
+    pub fn start(self: &Arc<Self>, routes: RouteHandle) -> Result<(), ReloadError> {
+        let mut running = self.running.lock();
+        if running.is_some() {
+            return Ok(());
+        }
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            if let Ok(event) = event {
+                let _ = tx.send(event);
+            }
+        })
+        .map_err(|e| ReloadError::Watch(e.to_string()))?;
+        for dir in &self.config.watch_dirs {
+            watcher
+                .watch(dir, RecursiveMode::Recursive)
+                .map_err(|e| ReloadError::Watch(format!("{}: {e}", dir.display())))?;
+        }
+
+        let token = CancellationToken::new();
+        tokio::spawn(self.clone().watch_loop(watcher, rx, routes, token.clone()));
+        *running = Some(token);
+        Ok(())
+    }
+
+    /// Stop watching for changes.
+    pub fn stop(&self) {
+        if let Some(token) = self.running.lock().take() {
+            token.cancel();
+        }
+    }
+
+    async fn watch_loop(
+        self: Arc<Self>,
+        // Dropping the watcher stops the notifications, so the loop owns it.
+        _watcher: RecommendedWatcher,
+        mut events: mpsc::UnboundedReceiver<Event>,
+        routes: RouteHandle,
+        token: CancellationToken,
+    ) {
+        loop {
+            let event = tokio::select! {
+                _ = token.cancelled() => return,
+                event = events.recv() => event,
+            };
+            let Some(event) = event else { return };
+            if !self.config.is_relevant(&event) {
+                continue;
+            }
+
+            // Wait until the files have been quiet for the debounce period.
+            while let Ok(Some(_)) = tokio::time::timeout(self.config.debounce, events.recv()).await
+            {
+            }
+            if token.is_cancelled() {
+                return;
+            }
+
+            // The loader runs Python code, so keep it off the runtime thread.
+            let reloader = self.clone();
+            let routes = routes.clone();
+            let result = tokio::task::spawn_blocking(move || reloader.reload(&routes)).await;
+            match result {
+                Ok(Ok(())) => eprintln!("Reloaded routes after change to {}", changed(&event)),
+                Ok(Err(e)) => eprintln!("Reload failed, keeping previous routes: {e}"),
+                Err(e) => eprintln!("Reload failed, keeping previous routes: {e}"),
+            }
+        }
+    }
+}
+
+fn changed(event: &Event) -> String {
+    event
+        .paths
+        .first()
+        .map(|p| p.display().to_string())
+        .unwrap_or_default()
+}
+
+/// Reloader errors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReloadError {
+    /// The file watcher could not be created or a directory not watched.
+    Watch(String),
+}
+
+impl std::fmt::Display for ReloadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReloadError::Watch(msg) => write!(f, "Cannot watch for changes: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for ReloadError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route_table(paths: &[&str]) -> (Router, HandlerRegistry) {
+        let mut router = Router::new();
+        for path in paths {
+            router.add_route("GET", path, 0).unwrap();
+        }
+        (router, HandlerRegistry::new())
+    }
+
+    // ---------- Config Tests ----------
+
+    #[test]
+    fn test_is_watched() {
+        let config = ReloadConfig::default();
+        assert!(config.is_watched(Path::new("./app/routes.py")));
+        assert!(!config.is_watched(Path::new("./app/routes.pyc")));
+        assert!(!config.is_watched(Path::new("./app/__pycache__/routes.py")));
+        assert!(!config.is_watched(Path::new("./.venv/lib/site.py")));
+        assert!(!config.is_watched(Path::new("./README")));
+    }
+
+    #[test]
+    fn test_extensions() {
+        let config = ReloadConfig::default().with_extensions(vec![".py".into(), "html".into()]);
+        assert!(config.is_watched(Path::new("templates/index.html")));
+        assert!(config.is_watched(Path::new("app.py")));
+        assert!(!config.is_watched(Path::new("style.css")));
+    }
+
+    // ---------- Reloader Tests ----------
+
+    #[test]
+    fn test_reload_swaps_routes() {
+        let (router, handlers) = route_table(&["/old"]);
+        let routes = RouteHandle::new(router, handlers);
+        let before = routes.current();
+
+        let reloader = Reloader::new(ReloadConfig::default(), || Ok(route_table(&["/new"])));
+        reloader.reload(&routes).unwrap();
+
+        assert_eq!(reloader.reloads(), 1);
+        assert!(routes.current().router.match_route("GET", "/new").is_some());
+        assert!(routes.current().router.match_route("GET", "/old").is_none());
+        // A request holding the old table keeps using it.
+        assert!(before.router.match_route("GET", "/old").is_some());
+    }
+
+    #[test]
+    fn test_failed_reload_keeps_routes() {
+        let (router, handlers) = route_table(&["/old"]);
+        let routes = RouteHandle::new(router, handlers);
+
+        let reloader = Reloader::new(ReloadConfig::default(), || {
+            Err("SyntaxError: invalid syntax".to_string())
+        });
+        assert!(reloader.reload(&routes).is_err());
+
+        assert_eq!(reloader.failures(), 1);
+        assert!(routes.current().router.match_route("GET", "/old").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_reload_on_file_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let (router, handlers) = route_table(&["/old"]);
+        let routes = RouteHandle::new(router, handlers);
+
+        let config = ReloadConfig::new(vec![dir.path().to_path_buf()])
+            .with_debounce(Duration::from_millis(20));
+        let reloader = Arc::new(Reloader::new(config, || Ok(route_table(&["/new"]))));
+        reloader.start(routes.clone()).unwrap();
+        assert!(reloader.is_running());
+
+        std::fs::write(dir.path().join("notes.txt"), "ignored").unwrap();
+        std::fs::write(dir.path().join("app.py"), "print('hi')").unwrap();
+        for _ in 0..100 {
+            if reloader.reloads() > 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        assert_eq!(reloader.reloads(), 1);
+        assert!(routes.current().router.match_route("GET", "/new").is_some());
+        reloader.stop();
+        assert!(!reloader.is_running());
+    }
+}
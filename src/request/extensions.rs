@@ -0,0 +1,238 @@
+//! Per-request extensions.
+//!
+//! Values attached to a request by middleware (auth claims, the tenant, a
+//! trace span, ...) and read by later middleware, the handler and
+//! after-middleware, without passing them through headers:
+//! - Rust middleware stores typed values, keyed by their type
+//! - Python code stores named objects on `request.state`
+//!
+//! Every copy of a request made while it is handled (for guards, Python
+//! middleware and after-middleware) shares the same extensions, so a value
+//! set at any stage is visible to all later ones. Values are only locked
+//! while they are read or written, never across an `.await`.
+
+use parking_lot::RwLock;
+use pyo3::exceptions::{PyAttributeError, PyKeyError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::json::json_to_python;
+
+type TypedMap = HashMap<TypeId, Box<dyn Any + Send + Sync>>;
+
+/// Values shared between the stages handling a request.
+#[derive(Clone, Default)]
+pub struct Extensions {
+    typed: Arc<RwLock<TypedMap>>,
+    state: Arc<RwLock<HashMap<String, PyObject>>>,
+}
+
+impl Extensions {
+    /// Create empty extensions.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Store `value`, returning the previous value of its type.
+    pub fn insert<T: Send + Sync + 'static>(&self, value: T) -> Option<T> {
+        self.typed
+            .write()
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|old| old.downcast().ok().map(|old| *old))
+    }
+
+    /// A copy of the stored value of type `T`.
+    pub fn get<T: Clone + Send + Sync + 'static>(&self) -> Option<T> {
+        self.with(T::clone)
+    }
+
+    /// Call `f` with the stored value of type `T`, without copying it.
+    pub fn with<T: Send + Sync + 'static, R>(&self, f: impl FnOnce(&T) -> R) -> Option<R> {
+        self.typed
+            .read()
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref())
+            .map(f)
+    }
+
+    /// Whether a value of type `T` is stored.
+    pub fn contains<T: Send + Sync + 'static>(&self) -> bool {
+        self.typed.read().contains_key(&TypeId::of::<T>())
+    }
+
+    /// Remove and return the stored value of type `T`.
+    pub fn remove<T: Send + Sync + 'static>(&self) -> Option<T> {
+        self.typed
+            .write()
+            .remove(&TypeId::of::<T>())
+            .and_then(|old| old.downcast().ok().map(|old| *old))
+    }
+
+    /// Set a named Python value, as `request.state.<name> = value` does.
+    pub fn set_state(&self, name: &str, value: PyObject) {
+        self.state.write().insert(name.to_string(), value);
+    }
+
+    /// A named Python value.
+    pub fn state(&self, py: Python<'_>, name: &str) -> Option<PyObject> {
+        self.state.read().get(name).map(|value| value.clone_ref(py))
+    }
+
+    /// Remove a named Python value.
+    pub fn remove_state(&self, name: &str) -> Option<PyObject> {
+        self.state.write().remove(name)
+    }
+
+    /// Names of the Python values, sorted.
+    pub fn state_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.state.read().keys().cloned().collect();
+        names.sort();
+        names
+    }
+}
+
+impl std::fmt::Debug for Extensions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Extensions")
+            .field("typed", &self.typed.read().len())
+            .field("state", &self.state_names())
+            .finish()
+    }
+}
+
+/// `request.state`: named values shared by middleware, the handler and
+/// after-middleware.
+///
+/// Attributes not set from Python fall back to the request context filled
+/// by built-in middleware (`request_id`, `tenant`, `jwt_claims`, ...),
+/// which can be read but not deleted through `state`.
+#[pyclass(name = "RequestState")]
+pub struct RequestState {
+    extensions: Extensions,
+    context: HashMap<String, serde_json::Value>,
+}
+
+impl RequestState {
+    pub fn new(extensions: Extensions, context: HashMap<String, serde_json::Value>) -> Self {
+        Self {
+            extensions,
+            context,
+        }
+    }
+
+    fn lookup(&self, py: Python<'_>, name: &str) -> PyResult<Option<PyObject>> {
+        if let Some(value) = self.extensions.state(py, name) {
+            return Ok(Some(value));
+        }
+        self.context
+            .get(name)
+            .map(|value| json_to_python(py, value))
+            .transpose()
+    }
+}
+
+#[pymethods]
+impl RequestState {
+    fn __getattr__(&self, py: Python<'_>, name: &str) -> PyResult<PyObject> {
+        self.lookup(py, name)?
+            .ok_or_else(|| PyAttributeError::new_err(format!("request.state has no '{name}'")))
+    }
+
+    fn __setattr__(&self, name: &str, value: PyObject) {
+        self.extensions.set_state(name, value);
+    }
+
+    fn __delattr__(&self, name: &str) -> PyResult<()> {
+        self.extensions
+            .remove_state(name)
+            .map(drop)
+            .ok_or_else(|| PyAttributeError::new_err(format!("request.state has no '{name}'")))
+    }
+
+    fn __getitem__(&self, py: Python<'_>, name: &str) -> PyResult<PyObject> {
+        self.lookup(py, name)?
+            .ok_or_else(|| PyKeyError::new_err(name.to_string()))
+    }
+
+    fn __setitem__(&self, name: &str, value: PyObject) {
+        self.extensions.set_state(name, value);
+    }
+
+    fn __contains__(&self, name: &str) -> bool {
+        self.extensions.state_names().iter().any(|n| n == name) || self.context.contains_key(name)
+    }
+
+    /// The value of `name`, or `default`.
+    #[pyo3(signature = (name, default=None))]
+    fn get(&self, py: Python<'_>, name: &str, default: Option<PyObject>) -> PyResult<PyObject> {
+        Ok(self
+            .lookup(py, name)?
+            .unwrap_or_else(|| default.unwrap_or_else(|| py.None())))
+    }
+
+    /// All values, context first, as a dict.
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<&'py PyDict> {
+        let dict = PyDict::new(py);
+        for (name, value) in &self.context {
+            dict.set_item(name, json_to_python(py, value)?)?;
+        }
+        for name in self.extensions.state_names() {
+            if let Some(value) = self.extensions.state(py, &name) {
+                dict.set_item(name, value)?;
+            }
+        }
+        Ok(dict)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("RequestState({})", self.extensions.state_names().join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Claims {
+        sub: String,
+    }
+
+    #[test]
+    fn test_typed_values() {
+        let extensions = Extensions::new();
+        assert!(!extensions.contains::<Claims>());
+        let claims = Claims {
+            sub: "alice".to_string(),
+        };
+        assert!(extensions.insert(claims.clone()).is_none());
+        extensions.insert(42u32);
+
+        assert_eq!(extensions.get::<Claims>(), Some(claims.clone()));
+        assert_eq!(extensions.with(|c: &Claims| c.sub.len()), Some(5));
+        assert_eq!(extensions.get::<u32>(), Some(42));
+        assert_eq!(extensions.get::<u64>(), None);
+
+        assert_eq!(extensions.remove::<Claims>(), Some(claims));
+        assert!(!extensions.contains::<Claims>());
+    }
+
+    #[test]
+    fn test_clones_share_values() {
+        let extensions = Extensions::new();
+        let copy = extensions.clone();
+        copy.insert("span-1".to_string());
+        assert_eq!(extensions.get::<String>().as_deref(), Some("span-1"));
+
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            copy.set_state("tenant", 7.into_py(py));
+            let tenant = extensions.state(py, "tenant").unwrap();
+            assert_eq!(tenant.extract::<i32>(py).unwrap(), 7);
+        });
+        assert_eq!(extensions.state_names(), vec!["tenant".to_string()]);
+    }
+}
@@ -0,0 +1,198 @@
+//! Request headers backed by hyper's `HeaderMap`.
+//!
+//! The server moves the parsed `HeaderMap` into the request instead of
+//! copying every header into an owned `HashMap<String, String>`. Lookups are
+//! case-insensitive and borrow from the map; a Python dict is only built
+//! when `request.headers` is accessed.
+
+use std::collections::HashMap;
+use std::ops::Index;
+
+use hyper::header::{HeaderName, HeaderValue};
+use hyper::HeaderMap;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+/// Request headers.
+///
+/// When a header is repeated, lookups return its last value, as the owned
+/// map used previously did.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Headers {
+    map: HeaderMap,
+}
+
+impl Headers {
+    /// Create an empty header set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get a header value by name (case-insensitive).
+    ///
+    /// Values that are not visible ASCII read as an empty string.
+    #[inline]
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.map
+            .get_all(name)
+            .iter()
+            .next_back()
+            .map(|value| value.to_str().unwrap_or(""))
+    }
+
+    /// Check if a header is present (case-insensitive).
+    #[inline]
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.map.contains_key(name)
+    }
+
+    /// Set a header, replacing any previous values.
+    ///
+    /// Names and values that are not valid HTTP are ignored.
+    pub fn insert(&mut self, name: &str, value: &str) {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(value),
+        ) {
+            self.map.insert(name, value);
+        }
+    }
+
+    /// Remove a header, returning its last value.
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        let value = self.get(name).map(str::to_string);
+        self.map.remove(name);
+        value
+    }
+
+    /// Iterate over `(name, value)` pairs; names are lowercase.
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            inner: self.map.iter(),
+        }
+    }
+
+    /// Number of header values.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Check if there are no headers.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// The underlying `HeaderMap`.
+    #[inline]
+    pub fn as_header_map(&self) -> &HeaderMap {
+        &self.map
+    }
+
+    /// Copy into an owned map, keeping the last value of repeated headers.
+    pub fn to_hash_map(&self) -> HashMap<String, String> {
+        self.iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    /// Build a Python dict of the headers.
+    pub fn to_py_dict<'py>(&self, py: Python<'py>) -> PyResult<&'py PyDict> {
+        let dict = PyDict::new(py);
+        for (name, value) in self.iter() {
+            dict.set_item(name, value)?;
+        }
+        Ok(dict)
+    }
+}
+
+impl From<HeaderMap> for Headers {
+    fn from(map: HeaderMap) -> Self {
+        Self { map }
+    }
+}
+
+impl<K: AsRef<str>, V: AsRef<str>> FromIterator<(K, V)> for Headers {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut headers = Headers::new();
+        for (name, value) in iter {
+            headers.insert(name.as_ref(), value.as_ref());
+        }
+        headers
+    }
+}
+
+impl From<HashMap<String, String>> for Headers {
+    fn from(map: HashMap<String, String>) -> Self {
+        map.into_iter().collect()
+    }
+}
+
+impl Index<&str> for Headers {
+    type Output = str;
+
+    fn index(&self, name: &str) -> &str {
+        self.get(name)
+            .unwrap_or_else(|| panic!("no header named '{name}'"))
+    }
+}
+
+impl<'a> IntoIterator for &'a Headers {
+    type Item = (&'a str, &'a str);
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+/// Iterator over request headers.
+pub struct Iter<'a> {
+    inner: hyper::header::Iter<'a, HeaderValue>,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (&'a str, &'a str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner
+            .next()
+            .map(|(name, value)| (name.as_str(), value.to_str().unwrap_or("")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup() {
+        let mut headers: Headers = [("Content-Type", "application/json"), ("x-id", "1")]
+            .into_iter()
+            .collect();
+        assert_eq!(headers.get("content-type"), Some("application/json"));
+        assert_eq!(headers.get("CONTENT-TYPE"), Some("application/json"));
+        assert_eq!(&headers["X-Id"], "1");
+        assert!(headers.get("accept").is_none());
+
+        headers.insert("X-Id", "2");
+        assert_eq!(headers.get("x-id"), Some("2"));
+        assert_eq!(headers.len(), 2);
+        assert_eq!(headers.remove("x-id").as_deref(), Some("2"));
+        assert!(!headers.contains_key("x-id"));
+
+        headers.insert("bad name", "v");
+        assert_eq!(headers.len(), 1);
+    }
+
+    #[test]
+    fn test_repeated_header_uses_last_value() {
+        let mut map = HeaderMap::new();
+        map.append("accept", HeaderValue::from_static("text/html"));
+        map.append("accept", HeaderValue::from_static("application/json"));
+        let headers = Headers::from(map);
+        assert_eq!(headers.get("accept"), Some("application/json"));
+        assert_eq!(headers.to_hash_map()["accept"], "application/json");
+    }
+}
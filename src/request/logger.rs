@@ -0,0 +1,429 @@
+//! Request-scoped structured logging.
+//!
+//! `request.logger` is bound to the request's ID, method, path, route,
+//! tenant and trace context. Records are queued for a background thread
+//! that emits them as `tracing` events with target `cello.request`, so a
+//! Python logging call never waits on the subscriber's I/O. While the queue
+//! is full, records are dropped and counted instead of blocking the caller.
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use serde_json::{Map, Value};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use super::Request;
+use crate::json::{json_to_python, python_to_json};
+
+/// Records queued before new ones are dropped.
+const QUEUE_CAPACITY: usize = 10_000;
+
+/// Severity of a log record.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warning,
+    Error,
+}
+
+impl LogLevel {
+    /// Parse a level name as used by Python's `logging` (`"warn"` and
+    /// `"critical"` are accepted too).
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "debug" => Some(LogLevel::Debug),
+            "info" => Some(LogLevel::Info),
+            "warning" | "warn" => Some(LogLevel::Warning),
+            "error" | "critical" => Some(LogLevel::Error),
+            _ => None,
+        }
+    }
+}
+
+/// Fields a logger is bound to.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LogContext {
+    pub request_id: Option<String>,
+    pub method: Option<String>,
+    pub path: Option<String>,
+    /// Matched route pattern, e.g. `/users/{id}`
+    pub route: Option<String>,
+    /// Tenant ID resolved by `app.enable_tenancy()`
+    pub tenant: Option<String>,
+    pub trace_id: Option<String>,
+    pub span_id: Option<String>,
+    /// Fields added with `bind()`
+    pub fields: Map<String, Value>,
+}
+
+impl LogContext {
+    /// The context of a request, as far as middleware has resolved it.
+    pub fn from_request(request: &Request) -> Self {
+        let context = |key: &str| {
+            request
+                .context
+                .get(key)
+                .and_then(Value::as_str)
+                .map(str::to_string)
+        };
+        Self {
+            request_id: crate::error::correlation_id(request),
+            method: Some(request.method.clone()),
+            path: Some(request.path.clone()),
+            route: request.route.as_deref().map(str::to_string),
+            tenant: request
+                .context
+                .get("tenant")
+                .and_then(|tenant| tenant.get("id"))
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            trace_id: context("trace_id"),
+            span_id: context("span_id"),
+            fields: Map::new(),
+        }
+    }
+
+    /// All bound fields as one JSON object.
+    pub fn to_json(&self) -> Map<String, Value> {
+        let mut json = Map::new();
+        for (key, value) in [
+            ("request_id", &self.request_id),
+            ("method", &self.method),
+            ("path", &self.path),
+            ("route", &self.route),
+            ("tenant", &self.tenant),
+            ("trace_id", &self.trace_id),
+            ("span_id", &self.span_id),
+        ] {
+            if let Some(value) = value {
+                json.insert(key.to_string(), Value::String(value.clone()));
+            }
+        }
+        json.extend(self.fields.clone());
+        json
+    }
+}
+
+/// A record waiting to be written.
+struct LogRecord {
+    level: LogLevel,
+    message: String,
+    context: Arc<LogContext>,
+    fields: Map<String, Value>,
+}
+
+enum Command {
+    Record(LogRecord),
+    Flush(SyncSender<()>),
+}
+
+struct Writer {
+    sender: SyncSender<Command>,
+    dropped: AtomicU64,
+}
+
+static WRITER: OnceLock<Writer> = OnceLock::new();
+
+/// The queue to the writer thread, started on first use.
+fn writer() -> &'static Writer {
+    WRITER.get_or_init(|| {
+        let (sender, receiver) = mpsc::sync_channel(QUEUE_CAPACITY);
+        // Without a thread, sends fail and records are counted as dropped
+        let _ = std::thread::Builder::new()
+            .name("cello-logger".to_string())
+            .spawn(move || write_records(receiver));
+        Writer {
+            sender,
+            dropped: AtomicU64::new(0),
+        }
+    })
+}
+
+fn write_records(receiver: Receiver<Command>) {
+    for command in receiver {
+        match command {
+            Command::Record(record) => emit(&record),
+            Command::Flush(done) => {
+                let _ = done.send(());
+            }
+        }
+    }
+}
+
+/// Emit a record as a `tracing` event.
+fn emit(record: &LogRecord) {
+    let context = &record.context;
+    let mut fields = context.fields.clone();
+    fields.extend(record.fields.clone());
+    let fields = (!fields.is_empty()).then(|| Value::Object(fields).to_string());
+
+    // Levels are part of each callsite's static metadata
+    macro_rules! event_at {
+        ($level:expr) => {
+            tracing::event!(
+                target: "cello.request",
+                $level,
+                request_id = context.request_id.as_deref(),
+                method = context.method.as_deref(),
+                path = context.path.as_deref(),
+                route = context.route.as_deref(),
+                tenant = context.tenant.as_deref(),
+                trace_id = context.trace_id.as_deref(),
+                span_id = context.span_id.as_deref(),
+                fields = fields.as_deref(),
+                "{}",
+                record.message
+            )
+        };
+    }
+    match record.level {
+        LogLevel::Debug => event_at!(tracing::Level::DEBUG),
+        LogLevel::Info => event_at!(tracing::Level::INFO),
+        LogLevel::Warning => event_at!(tracing::Level::WARN),
+        LogLevel::Error => event_at!(tracing::Level::ERROR),
+    }
+}
+
+/// Queue a record for the writer thread. Returns false when it was dropped
+/// because the queue is full.
+pub fn log(
+    level: LogLevel,
+    message: String,
+    context: Arc<LogContext>,
+    fields: Map<String, Value>,
+) -> bool {
+    let writer = writer();
+    let record = LogRecord {
+        level,
+        message,
+        context,
+        fields,
+    };
+    match writer.sender.try_send(Command::Record(record)) {
+        Ok(()) => true,
+        Err(_) => {
+            writer.dropped.fetch_add(1, Ordering::Relaxed);
+            false
+        }
+    }
+}
+
+/// Records dropped because the queue was full.
+pub fn dropped() -> u64 {
+    WRITER
+        .get()
+        .map_or(0, |writer| writer.dropped.load(Ordering::Relaxed))
+}
+
+/// Wait until the records queued so far have been written, for at most
+/// `timeout`. Blocks the calling thread.
+pub fn flush(timeout: Duration) -> bool {
+    let Some(writer) = WRITER.get() else {
+        return true;
+    };
+    let (done, wait) = mpsc::sync_channel(1);
+    if writer.sender.send(Command::Flush(done)).is_err() {
+        return false;
+    }
+    wait.recv_timeout(timeout).is_ok()
+}
+
+/// Convert keyword arguments to log fields.
+fn fields_from_python(py: Python<'_>, kwargs: Option<&PyDict>) -> PyResult<Map<String, Value>> {
+    let mut fields = Map::new();
+    if let Some(kwargs) = kwargs {
+        for (key, value) in kwargs.iter() {
+            let value = python_to_json(py, value).map_err(PyValueError::new_err)?;
+            fields.insert(key.extract()?, value);
+        }
+    }
+    Ok(fields)
+}
+
+// ============================================================================
+// Python Logger
+// ============================================================================
+
+/// Structured logger bound to a request, exposed to Python as
+/// `request.logger`.
+///
+/// Keyword arguments become fields of the record, e.g.
+/// `request.logger.info("order placed", order_id=42)`.
+#[pyclass(name = "RequestLogger")]
+#[derive(Clone)]
+pub struct RequestLogger {
+    context: Arc<LogContext>,
+}
+
+impl RequestLogger {
+    /// A logger bound to `request`.
+    pub fn for_request(request: &Request) -> Self {
+        Self {
+            context: Arc::new(LogContext::from_request(request)),
+        }
+    }
+
+    /// The fields this logger is bound to.
+    pub fn context(&self) -> &LogContext {
+        &self.context
+    }
+
+    fn write(
+        &self,
+        py: Python<'_>,
+        level: LogLevel,
+        message: String,
+        kwargs: Option<&PyDict>,
+    ) -> PyResult<bool> {
+        let fields = fields_from_python(py, kwargs)?;
+        Ok(log(level, message, self.context.clone(), fields))
+    }
+}
+
+#[pymethods]
+impl RequestLogger {
+    /// Log at DEBUG level.
+    #[pyo3(signature = (message, **fields))]
+    pub fn debug(
+        &self,
+        py: Python<'_>,
+        message: String,
+        fields: Option<&PyDict>,
+    ) -> PyResult<bool> {
+        self.write(py, LogLevel::Debug, message, fields)
+    }
+
+    /// Log at INFO level.
+    #[pyo3(signature = (message, **fields))]
+    pub fn info(&self, py: Python<'_>, message: String, fields: Option<&PyDict>) -> PyResult<bool> {
+        self.write(py, LogLevel::Info, message, fields)
+    }
+
+    /// Log at WARNING level.
+    #[pyo3(signature = (message, **fields))]
+    pub fn warning(
+        &self,
+        py: Python<'_>,
+        message: String,
+        fields: Option<&PyDict>,
+    ) -> PyResult<bool> {
+        self.write(py, LogLevel::Warning, message, fields)
+    }
+
+    /// Log at ERROR level.
+    #[pyo3(signature = (message, **fields))]
+    pub fn error(
+        &self,
+        py: Python<'_>,
+        message: String,
+        fields: Option<&PyDict>,
+    ) -> PyResult<bool> {
+        self.write(py, LogLevel::Error, message, fields)
+    }
+
+    /// Log at a level given by name: `"debug"`, `"info"`, `"warning"` or
+    /// `"error"`.
+    #[pyo3(signature = (level, message, **fields))]
+    pub fn log(
+        &self,
+        py: Python<'_>,
+        level: &str,
+        message: String,
+        fields: Option<&PyDict>,
+    ) -> PyResult<bool> {
+        let level = LogLevel::parse(level)
+            .ok_or_else(|| PyValueError::new_err(format!("Unknown log level '{level}'")))?;
+        self.write(py, level, message, fields)
+    }
+
+    /// A logger with further fields bound, e.g.
+    /// `log = request.logger.bind(user_id=user.id)`.
+    #[pyo3(signature = (**fields))]
+    pub fn bind(&self, py: Python<'_>, fields: Option<&PyDict>) -> PyResult<Self> {
+        let mut context = (*self.context).clone();
+        context.fields.extend(fields_from_python(py, fields)?);
+        Ok(Self {
+            context: Arc::new(context),
+        })
+    }
+
+    /// The bound fields as a dict.
+    #[getter(fields)]
+    pub fn py_fields(&self, py: Python<'_>) -> PyResult<PyObject> {
+        json_to_python(py, &Value::Object(self.context.to_json()))
+    }
+
+    /// Records dropped so far because the log queue was full.
+    #[staticmethod]
+    pub fn dropped() -> u64 {
+        dropped()
+    }
+
+    /// Wait up to `timeout` seconds for queued records to be written.
+    #[staticmethod]
+    #[pyo3(signature = (timeout=1.0))]
+    pub fn flush(py: Python<'_>, timeout: f64) -> PyResult<bool> {
+        let timeout = Duration::try_from_secs_f64(timeout).map_err(|_| {
+            PyValueError::new_err("timeout must be a non-negative number of seconds")
+        })?;
+        Ok(py.allow_threads(|| flush(timeout)))
+    }
+
+    fn __repr__(&self) -> String {
+        format!("RequestLogger({})", Value::Object(self.context.to_json()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_level_names() {
+        assert_eq!(LogLevel::parse("INFO"), Some(LogLevel::Info));
+        assert_eq!(LogLevel::parse("warn"), Some(LogLevel::Warning));
+        assert_eq!(LogLevel::parse("critical"), Some(LogLevel::Error));
+        assert_eq!(LogLevel::parse("verbose"), None);
+    }
+
+    #[test]
+    fn test_context_from_request() {
+        let mut request = Request::new("GET", "/orders/42");
+        request.route = Some(Arc::from("/orders/{id}"));
+        request.headers.insert("x-request-id", "req-1");
+        request.context.insert(
+            "tenant".to_string(),
+            serde_json::json!({"id": "acme", "source": "header"}),
+        );
+        request
+            .context
+            .insert("trace_id".to_string(), serde_json::json!("abc123"));
+
+        let context = LogContext::from_request(&request);
+        assert_eq!(context.request_id.as_deref(), Some("req-1"));
+        assert_eq!(context.route.as_deref(), Some("/orders/{id}"));
+        assert_eq!(context.tenant.as_deref(), Some("acme"));
+        assert_eq!(context.trace_id.as_deref(), Some("abc123"));
+        assert_eq!(context.span_id, None);
+
+        let json = context.to_json();
+        assert_eq!(json["method"], "GET");
+        assert!(!json.contains_key("span_id"));
+    }
+
+    #[test]
+    fn test_records_are_queued_and_flushed() {
+        let context = Arc::new(LogContext::default());
+        assert!(log(
+            LogLevel::Info,
+            "hello".to_string(),
+            context,
+            Map::new()
+        ));
+        assert!(flush(Duration::from_secs(5)));
+    }
+}
@@ -2,22 +2,48 @@
 //!
 //! This module provides:
 //! - HTTP Request wrapper with typed parameters
+//! - Header access without per-request copies
 //! - Lazy body parsing (JSON, form, multipart)
 //! - Request context for middleware data
+//! - Typed extensions and `request.state` shared between middleware stages
 //! - Streaming multipart uploads
+//! - Streaming request bodies
+//! - A structured logger bound to the request (`request.logger`)
 
+pub mod extensions;
+pub mod headers;
+pub mod logger;
 pub mod multipart_streaming;
 pub mod parsing;
+pub mod query;
+pub mod stream;
 
 use pyo3::prelude::*;
 use std::collections::HashMap;
 use std::sync::Arc;
 
+use crate::codec::{self, BodyFormat};
 use crate::json::{json_to_python, parse_json, python_to_json};
-use crate::multipart::parse_urlencoded;
-
+use crate::multipart::{
+    parse_multipart, parse_urlencoded_pairs, FormData, MultipartError, UploadOptions,
+    DEFAULT_MAX_FORM_FIELDS, DEFAULT_SPOOL_THRESHOLD,
+};
+use crate::negotiation;
+
+pub use extensions::{Extensions, RequestState};
+pub use headers::Headers;
+pub use logger::RequestLogger;
 pub use multipart_streaming::{MultipartPart, StreamingMultipart};
 pub use parsing::{LazyBody, ParamError, TypedParams};
+pub use query::{decode_query_pair, encode_query, parse_query_list, QueryArraySyntax};
+pub use stream::{BodyStream, NdjsonStream};
+
+pyo3::create_exception!(
+    _cello,
+    BodyDecodeError,
+    pyo3::exceptions::PyValueError,
+    "The request body does not decode in its Content-Type; answered with 400."
+);
 
 // ============================================================================
 // HTTP Request
@@ -43,35 +69,54 @@ pub struct Request {
     #[pyo3(get)]
     pub query_params: HashMap<String, String>,
 
-    /// Request headers
+    /// Raw query string, without the `?` (e.g. "tag=a&tag=b")
     #[pyo3(get)]
-    pub headers: HashMap<String, String>,
+    pub query_string: String,
+
+    /// How repeated query parameters are written, for `query_list()`
+    pub query_array_syntax: QueryArraySyntax,
+
+    /// Request headers
+    pub headers: Headers,
 
     /// Request body as bytes
     pub body: Vec<u8>,
 
-    /// Content type
-    content_type: Option<String>,
-
     /// Request context for middleware data sharing (internal)
     pub context: HashMap<String, serde_json::Value>,
 
+    /// Values shared between middleware, the handler and after-middleware
+    pub extensions: Extensions,
+
     /// Lazy body cache (internal)
     lazy_cache: LazyCache,
 
     /// Python-level Redis client injected when app.enable_redis() is configured.
     /// Wrapped in Arc so Clone stays GIL-free (atomic refcount only).
     pub redis_client: Option<Arc<PyObject>>,
+
+    /// Address of the connection's peer, set by the server (internal)
+    pub remote_addr: Option<std::net::SocketAddr>,
+
+    /// Unread body of a `@stream_body` route, set by the server (internal)
+    pub body_stream: Option<BodyStream>,
+
+    /// Pattern of the matched route, set by the server (internal)
+    pub route: Option<Arc<str>>,
 }
 
+/// Parsed form fields in body order, with repeated keys kept.
+type FormResult = Result<Vec<(String, String)>, String>;
+
 /// Internal cache for lazy parsing results.
 /// Uses RwLock for thread-safety to support async middleware.
 #[derive(Clone, Default)]
 pub struct LazyCache {
     json_parsed: std::sync::Arc<parking_lot::RwLock<Option<Result<serde_json::Value, String>>>>,
-    form_parsed:
-        std::sync::Arc<parking_lot::RwLock<Option<Result<HashMap<String, String>, String>>>>,
+    binary_parsed: std::sync::Arc<parking_lot::RwLock<Option<Result<serde_json::Value, String>>>>,
+    form_parsed: std::sync::Arc<parking_lot::RwLock<Option<(usize, FormResult)>>>,
     text_parsed: std::sync::Arc<parking_lot::RwLock<Option<Result<String, String>>>>,
+    multipart_parsed: std::sync::Arc<parking_lot::RwLock<Option<(usize, FormData)>>>,
 }
 
 #[pymethods]
@@ -87,23 +132,40 @@ impl Request {
         headers: Option<HashMap<String, String>>,
         body: Option<Vec<u8>>,
     ) -> Self {
-        let headers_map = headers.unwrap_or_default();
-        let content_type = headers_map.get("content-type").cloned();
+        let headers = headers.map(Headers::from).unwrap_or_default();
+        let query_params = query.unwrap_or_default();
+        let mut pairs: Vec<(&str, &str)> = query_params
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        pairs.sort();
+        let query_string = encode_query(pairs);
 
         Request {
             method,
             path,
             params: params.unwrap_or_default(),
-            query_params: query.unwrap_or_default(),
-            headers: headers_map,
+            query_params,
+            query_string,
+            query_array_syntax: QueryArraySyntax::default(),
+            headers,
             body: body.unwrap_or_default(),
-            content_type,
             context: HashMap::new(),
+            extensions: Extensions::default(),
             lazy_cache: LazyCache::default(),
             redis_client: None,
+            remote_addr: None,
+            body_stream: None,
+            route: None,
         }
     }
 
+    /// Get the request headers as a dict, with lowercase names.
+    #[getter(headers)]
+    pub fn py_headers<'py>(&self, py: Python<'py>) -> PyResult<&'py pyo3::types::PyDict> {
+        self.headers.to_py_dict(py)
+    }
+
     /// Get the query parameters dict.
     #[getter]
     pub fn query(&self) -> HashMap<String, String> {
@@ -112,6 +174,7 @@ impl Request {
 
     /// Get the request body as a string (cached).
     pub fn text(&self) -> PyResult<String> {
+        self.ensure_buffered()?;
         let mut cache = self.lazy_cache.text_parsed.write();
         if let Some(ref result) = *cache {
             return result
@@ -127,12 +190,118 @@ impl Request {
     }
 
     /// Get the request body as bytes.
-    pub fn body(&self) -> Vec<u8> {
-        self.body.clone()
+    pub fn body(&self) -> PyResult<Vec<u8>> {
+        self.ensure_buffered()?;
+        Ok(self.body.clone())
+    }
+
+    /// Get the body as an async iterator of byte chunks.
+    ///
+    /// On `@stream_body` routes chunks are read from the connection as they
+    /// arrive; otherwise the buffered body is yielded as one chunk.
+    pub fn stream(&self) -> PyResult<BodyStream> {
+        let stream = match &self.body_stream {
+            Some(stream) => stream.clone(),
+            None => BodyStream::from_bytes(self.body.clone()),
+        };
+        stream
+            .claim()
+            .map_err(pyo3::exceptions::PyRuntimeError::new_err)
+    }
+
+    /// Read the body as newline-delimited JSON.
+    ///
+    /// Returns an async iterator of parsed documents; lines longer than
+    /// `max_line_size` bytes raise `ValueError`. Like `stream()`, it reads
+    /// from the connection on `@stream_body` routes.
+    #[pyo3(signature = (max_line_size=stream::DEFAULT_MAX_LINE_SIZE))]
+    pub fn ndjson(&self, max_line_size: usize) -> PyResult<NdjsonStream> {
+        Ok(NdjsonStream::new(self.stream()?, max_line_size))
+    }
+
+    /// Parse a `multipart/form-data` body; returns an awaitable resolving to
+    /// a `FormData`.
+    ///
+    /// Uploaded files larger than `spool_threshold` bytes are spooled to a
+    /// temporary file in `upload_dir` (the system temp dir by default) and
+    /// removed when the request ends. With `checksum=True` each file's
+    /// SHA-256 is computed while it is read. On `@stream_body` routes the
+    /// body is parsed as it arrives, so it is never buffered whole.
+    #[pyo3(signature = (
+        spool_threshold=DEFAULT_SPOOL_THRESHOLD,
+        checksum=false,
+        upload_dir=None,
+        max_fields=None
+    ))]
+    pub fn multipart<'py>(
+        &self,
+        py: Python<'py>,
+        spool_threshold: usize,
+        checksum: bool,
+        upload_dir: Option<std::path::PathBuf>,
+        max_fields: Option<usize>,
+    ) -> PyResult<&'py PyAny> {
+        let boundary = self.multipart_boundary().ok_or_else(|| {
+            pyo3::exceptions::PyValueError::new_err("Request body is not multipart/form-data")
+        })?;
+        let stream = self.stream()?;
+        let options = UploadOptions {
+            spool_threshold,
+            checksum,
+            upload_dir,
+        };
+        let max_fields = max_fields.unwrap_or(DEFAULT_MAX_FORM_FIELDS);
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            let body = futures_util::stream::unfold(stream, |stream| async move {
+                match stream.next_chunk().await {
+                    Ok(Some(chunk)) => Some((Ok(chunk), stream)),
+                    Ok(None) => None,
+                    Err(e) => Some((Err(e), stream)),
+                }
+            });
+            let form = parse_multipart(body, &boundary, &options, max_fields).await?;
+            Ok(Python::with_gil(|py| form.into_py(py)))
+        })
+    }
+
+    /// Read the whole body; returns an awaitable resolving to bytes.
+    ///
+    /// On `@stream_body` routes this buffers the body, after which `body()`,
+    /// `json()` and the other accessors can be used.
+    pub fn read<'py>(slf: Py<Self>, py: Python<'py>) -> PyResult<&'py PyAny> {
+        let stream = match &slf.borrow(py).body_stream {
+            Some(stream) => Some(
+                stream
+                    .claim()
+                    .map_err(pyo3::exceptions::PyRuntimeError::new_err)?,
+            ),
+            None => None,
+        };
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            let body = match stream {
+                Some(stream) => Some(
+                    stream
+                        .read_to_end()
+                        .await
+                        .map_err(pyo3::exceptions::PyIOError::new_err)?,
+                ),
+                None => None,
+            };
+            Python::with_gil(|py| {
+                let mut request = slf.borrow_mut(py);
+                if let Some(body) = body {
+                    request.body = body;
+                    request.body_stream = None;
+                    request.lazy_cache = LazyCache::default();
+                }
+                Ok(pyo3::types::PyBytes::new(py, &request.body).to_object(py))
+            })
+        })
     }
 
     /// Parse the request body as JSON using SIMD acceleration (cached).
     pub fn json(&self, py: Python<'_>) -> PyResult<PyObject> {
+        self.ensure_buffered()?;
         let mut cache = self.lazy_cache.json_parsed.write();
 
         let value = if let Some(ref result) = *cache {
@@ -154,42 +323,105 @@ impl Request {
         json_to_python(py, &value)
     }
 
-    /// Parse the request body as form data (cached).
-    pub fn form(&self) -> PyResult<HashMap<String, String>> {
-        let mut cache = self.lazy_cache.form_parsed.write();
-
-        if let Some(ref result) = *cache {
-            return result
-                .clone()
-                .map_err(pyo3::exceptions::PyValueError::new_err);
+    /// Decode the body according to its Content-Type (cached).
+    ///
+    /// JSON, MessagePack (`application/msgpack`), CBOR (`application/cbor`)
+    /// and protobuf (`application/x-protobuf`, on routes with a bound message
+    /// type) bodies all decode to plain Python dicts, lists and scalars.
+    /// A malformed body raises `BodyDecodeError`, a `ValueError` that the
+    /// server answers with 400 unless an exception handler takes it.
+    pub fn data(&self, py: Python<'_>) -> PyResult<PyObject> {
+        self.ensure_buffered()?;
+        let format = self.body_format();
+        if format == BodyFormat::Json {
+            return self
+                .json(py)
+                .map_err(|e| BodyDecodeError::new_err(e.value(py).to_string()));
         }
 
-        let result = parse_urlencoded(&self.body);
-        let return_value = result.clone();
-        *cache = Some(result);
+        // Protobuf bodies are decoded before the handler runs, on routes with a message type
+        let mut cache = self.lazy_cache.binary_parsed.write();
+        let result = cache
+            .get_or_insert_with(|| codec::decode(&self.body, format))
+            .clone();
+        let value = result.map_err(BodyDecodeError::new_err)?;
+        json_to_python(py, &value)
+    }
 
-        return_value.map_err(pyo3::exceptions::PyValueError::new_err)
+    /// Parse the request body as form data (cached).
+    ///
+    /// URL-encoded bodies return a dict of field name to value; repeated keys
+    /// map to a list of values in body order. `multipart/form-data` bodies
+    /// return a `FormData` with the text fields and uploaded files. Bodies
+    /// with more than `max_fields` fields (default 1000) raise `ValueError`.
+    #[pyo3(signature = (max_fields=None))]
+    pub fn form(&self, py: Python<'_>, max_fields: Option<usize>) -> PyResult<PyObject> {
+        self.ensure_buffered()?;
+        let max_fields = max_fields.unwrap_or(DEFAULT_MAX_FORM_FIELDS);
+        if let Some(boundary) = self.multipart_boundary() {
+            return Ok(self.buffered_multipart(&boundary, max_fields)?.into_py(py));
+        }
+        let result = {
+            let mut cache = self.lazy_cache.form_parsed.write();
+            match *cache {
+                Some((limit, ref result)) if limit == max_fields => result.clone(),
+                _ => {
+                    let result = parse_urlencoded_pairs(&self.body, max_fields);
+                    *cache = Some((max_fields, result.clone()));
+                    result
+                }
+            }
+        };
+        let pairs = result.map_err(pyo3::exceptions::PyValueError::new_err)?;
+
+        let mut grouped: Vec<(String, Vec<String>)> = Vec::new();
+        let mut index: HashMap<String, usize> = HashMap::new();
+        for (key, value) in pairs {
+            match index.get(&key) {
+                Some(&i) => grouped[i].1.push(value),
+                None => {
+                    index.insert(key.clone(), grouped.len());
+                    grouped.push((key, vec![value]));
+                }
+            }
+        }
+
+        let dict = pyo3::types::PyDict::new(py);
+        for (key, mut values) in grouped {
+            if values.len() == 1 {
+                dict.set_item(key, values.pop())?;
+            } else {
+                dict.set_item(key, values)?;
+            }
+        }
+        Ok(dict.into())
     }
 
     /// Get the content type.
     pub fn content_type(&self) -> Option<String> {
-        self.content_type.clone()
+        self.headers.get("content-type").map(str::to_string)
     }
 
     /// Check if the request is JSON.
     #[inline]
     pub fn is_json(&self) -> bool {
-        self.content_type
-            .as_ref()
+        self.headers
+            .get("content-type")
             .map(|ct| ct.contains("application/json"))
             .unwrap_or(false)
     }
 
+    /// Check if the request body is MessagePack, CBOR or protobuf.
+    #[inline]
+    pub fn is_binary_data(&self) -> bool {
+        self.body_format() != BodyFormat::Json
+    }
+
     /// Check if the request is form data.
     #[inline]
     pub fn is_form(&self) -> bool {
-        self.content_type
-            .as_ref()
+        self.headers
+            .get("content-type")
             .map(|ct| ct.contains("application/x-www-form-urlencoded"))
             .unwrap_or(false)
     }
@@ -197,8 +429,8 @@ impl Request {
     /// Check if the request is multipart.
     #[inline]
     pub fn is_multipart(&self) -> bool {
-        self.content_type
-            .as_ref()
+        self.headers
+            .get("content-type")
             .map(|ct| ct.contains("multipart/form-data"))
             .unwrap_or(false)
     }
@@ -212,6 +444,22 @@ impl Request {
             .or_else(|| default.map(|s| s.to_string()))
     }
 
+    /// All query parameters as `(name, value)` pairs, in order, with
+    /// repeated names kept.
+    pub fn query_list(&self) -> Vec<(String, String)> {
+        parse_query_list(&self.query_string, self.query_array_syntax)
+    }
+
+    /// Every value of a repeated query parameter, in order, e.g. `["a", "b"]`
+    /// for `tag=a&tag=b` (or `tag[]=a&tag[]=b`).
+    pub fn get_query_list(&self, key: &str) -> Vec<String> {
+        self.query_list()
+            .into_iter()
+            .filter(|(name, _)| name == key)
+            .map(|(_, value)| value)
+            .collect()
+    }
+
     /// Get a query parameter as integer.
     #[pyo3(signature = (key, default=None))]
     pub fn get_query_int(&self, key: &str, default: Option<i64>) -> PyResult<Option<i64>> {
@@ -253,12 +501,7 @@ impl Request {
     /// Get a header by name (case-insensitive).
     #[pyo3(signature = (key, default=None))]
     pub fn get_header(&self, key: &str, default: Option<&str>) -> Option<String> {
-        let key_lower = key.to_lowercase();
-        self.headers
-            .iter()
-            .find(|(k, _)| k.to_lowercase() == key_lower)
-            .map(|(_, v)| v.clone())
-            .or_else(|| default.map(|s| s.to_string()))
+        self.headers.get(key).or(default).map(str::to_string)
     }
 
     /// Get a path parameter by name.
@@ -300,11 +543,24 @@ impl Request {
         self.get_header("user-agent", None)
     }
 
-    /// Check if the request accepts a specific content type.
-    pub fn accepts(&self, content_type: &str) -> bool {
-        self.get_header("accept", None)
-            .map(|accept| accept.contains(content_type))
-            .unwrap_or(false)
+    /// The best of `types` for the request's `Accept` header, by quality,
+    /// or None when none is acceptable, e.g.
+    /// `request.accepts("application/json", "text/html")`. Without
+    /// arguments, the accepted media types, most preferred first.
+    #[pyo3(signature = (*types))]
+    pub fn accepts(&self, py: Python<'_>, types: Vec<String>) -> PyObject {
+        let accept = self.headers.get("accept");
+        if types.is_empty() {
+            let ranges = accept.map(negotiation::parse_accept).unwrap_or_default();
+            let accepted: Vec<String> = ranges
+                .iter()
+                .filter(|range| range.quality > 0.0)
+                .map(negotiation::MediaRange::media_type)
+                .collect();
+            return accepted.into_py(py);
+        }
+        let offered: Vec<&str> = types.iter().map(String::as_str).collect();
+        negotiation::best_match(accept, &offered).into_py(py)
     }
 
     /// Check if the request is an AJAX/XHR request.
@@ -340,6 +596,35 @@ impl Request {
             .map(|s| s.to_string())
     }
 
+    /// The tenant resolved by `app.enable_tenancy()`, as a dict with `id`,
+    /// `source` and `metadata`, or None.
+    #[getter]
+    pub fn tenant(&self, py: Python<'_>) -> PyResult<PyObject> {
+        self.get_context(py, "tenant")
+    }
+
+    /// The matched route pattern, e.g. `/users/{id}`, or None outside a
+    /// routed request.
+    #[getter(route)]
+    pub fn py_route(&self) -> Option<String> {
+        self.route.as_deref().map(str::to_string)
+    }
+
+    /// A structured logger bound to the request ID, method, path, route,
+    /// tenant and trace IDs, e.g. `request.logger.info("paid", amount=10)`.
+    /// Records are written through `tracing` on a background thread.
+    #[getter]
+    pub fn logger(&self) -> RequestLogger {
+        RequestLogger::for_request(self)
+    }
+
+    /// Values shared between middleware, the handler and after-middleware,
+    /// e.g. `request.state.user = user` in a middleware's `before` hook.
+    #[getter]
+    pub fn state(&self) -> RequestState {
+        RequestState::new(self.extensions.clone(), self.context.clone())
+    }
+
     /// Get a context value by key.
     pub fn get_context(&self, py: Python<'_>, key: &str) -> PyResult<PyObject> {
         match self.context.get(key) {
@@ -393,12 +678,17 @@ impl Request {
             path: path.to_string(),
             params: HashMap::new(),
             query_params: HashMap::new(),
-            headers: HashMap::new(),
+            query_string: String::new(),
+            query_array_syntax: QueryArraySyntax::default(),
+            headers: Headers::new(),
             body: Vec::new(),
-            content_type: None,
             context: HashMap::new(),
+            extensions: Extensions::default(),
             lazy_cache: LazyCache::default(),
             redis_client: None,
+            remote_addr: None,
+            body_stream: None,
+            route: None,
         }
     }
 
@@ -409,23 +699,37 @@ impl Request {
         path: String,
         params: HashMap<String, String>,
         query: HashMap<String, String>,
-        headers: HashMap<String, String>,
+        headers: Headers,
         body: Vec<u8>,
     ) -> Self {
-        let content_type = headers.get("content-type").cloned();
-
         Request {
             method,
             path,
             params,
             query_params: query,
+            query_string: String::new(),
+            query_array_syntax: QueryArraySyntax::default(),
             headers,
             body,
-            content_type,
             context: HashMap::new(),
+            extensions: Extensions::default(),
             lazy_cache: LazyCache::default(),
             redis_client: None,
+            remote_addr: None,
+            body_stream: None,
+            route: None,
+        }
+    }
+
+    /// Clone for after-middleware, keeping the body only if it is at most
+    /// `body_limit` bytes; with a limit of 0 this is [`Self::clone_without_body`].
+    #[inline]
+    pub fn clone_for_after(&self, body_limit: usize) -> Self {
+        let mut request = self.clone_without_body();
+        if !self.body.is_empty() && self.body.len() <= body_limit {
+            request.body = self.body.clone();
         }
+        request
     }
 
     /// Create a lightweight clone without body bytes or lazy cache.
@@ -438,13 +742,51 @@ impl Request {
             path: self.path.clone(),
             params: self.params.clone(),
             query_params: self.query_params.clone(),
+            query_string: self.query_string.clone(),
+            query_array_syntax: self.query_array_syntax,
             headers: self.headers.clone(),
             body: Vec::new(),
-            content_type: self.content_type.clone(),
             context: self.context.clone(),
+            extensions: self.extensions.clone(),
             lazy_cache: LazyCache::default(),
             redis_client: self.redis_client.clone(),
+            remote_addr: self.remote_addr,
+            body_stream: None,
+            route: self.route.clone(),
+        }
+    }
+
+    /// Fail if the body is still an unread stream.
+    fn ensure_buffered(&self) -> PyResult<()> {
+        match self.body_stream {
+            Some(_) => Err(stream::unread_error()),
+            None => Ok(()),
+        }
+    }
+
+    /// Parse the buffered body as multipart form data (cached).
+    fn buffered_multipart(&self, boundary: &str, max_fields: usize) -> PyResult<FormData> {
+        if let Some((limit, ref form)) = *self.lazy_cache.multipart_parsed.read() {
+            if limit == max_fields {
+                return Ok(form.clone());
+            }
         }
+        let body = bytes::Bytes::from(self.body.clone());
+        let chunks = futures_util::stream::once(async { Ok::<_, std::convert::Infallible>(body) });
+        // The body is already in memory, so parsing completes without waiting
+        let form = futures_util::FutureExt::now_or_never(parse_multipart(
+            chunks,
+            boundary,
+            &UploadOptions::default(),
+            max_fields,
+        ))
+        .unwrap_or_else(|| {
+            Err(MultipartError::Invalid(
+                "Incomplete multipart body".to_string(),
+            ))
+        })?;
+        *self.lazy_cache.multipart_parsed.write() = Some((max_fields, form.clone()));
+        Ok(form)
     }
 
     /// Get the raw body bytes (internal use).
@@ -453,6 +795,36 @@ impl Request {
         &self.body
     }
 
+    /// Serialization format of the body, from its Content-Type.
+    #[inline]
+    pub fn body_format(&self) -> BodyFormat {
+        BodyFormat::from_content_type(self.headers.get("content-type"))
+    }
+
+    /// Decode the body according to its Content-Type (internal use).
+    /// Reuses a previously decoded binary body, e.g. a protobuf message.
+    pub fn decode_body(&self) -> Result<serde_json::Value, String> {
+        let format = self.body_format();
+        if format != BodyFormat::Json {
+            if let Some(result) = self.lazy_cache.binary_parsed.read().as_ref() {
+                return result.clone();
+            }
+        }
+        codec::decode(&self.body, format)
+    }
+
+    /// Seed the lazy body cache with an already-decoded body.
+    /// PERF: Lets request validation hand its parse result to `request.json()`
+    /// or `request.data()`.
+    #[inline]
+    pub fn set_parsed_body(&self, value: serde_json::Value) {
+        let cache = match self.body_format() {
+            BodyFormat::Json => &self.lazy_cache.json_parsed,
+            _ => &self.lazy_cache.binary_parsed,
+        };
+        *cache.write() = Some(Ok(value));
+    }
+
     /// Get typed parameters helper.
     pub fn typed_params(&self) -> TypedParams {
         TypedParams::from_map(&self.params)
@@ -470,7 +842,7 @@ impl Request {
 
     /// Get the multipart boundary if this is a multipart request.
     pub fn multipart_boundary(&self) -> Option<String> {
-        self.content_type.as_ref().and_then(|ct| {
+        self.headers.get("content-type").and_then(|ct| {
             if ct.contains("multipart/form-data") {
                 ct.split("boundary=")
                     .nth(1)
@@ -554,8 +926,10 @@ mod tests {
     #[test]
     fn test_multipart_boundary() {
         let mut request = Request::new("POST", "/upload");
-        request.content_type =
-            Some("multipart/form-data; boundary=----WebKitFormBoundary123".to_string());
+        request.headers.insert(
+            "content-type",
+            "multipart/form-data; boundary=----WebKitFormBoundary123",
+        );
 
         assert_eq!(
             request.multipart_boundary(),
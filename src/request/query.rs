@@ -0,0 +1,119 @@
+//! Query strings with repeated parameters.
+//!
+//! `request.query` keeps one value per key. The raw query string is kept
+//! too, so repeated parameters can be read in order, e.g. `tag=a&tag=b` or,
+//! with the bracket array syntax, `tag[]=a&tag[]=b`.
+
+/// How array parameters are written in query strings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QueryArraySyntax {
+    /// Repeated keys, `a=1&a=2`; `a[]` is a key of its own
+    Repeat,
+    /// Repeated keys, with an optional `[]` suffix: `a[]=1&a[]=2` is `a`
+    #[default]
+    Brackets,
+}
+
+impl QueryArraySyntax {
+    /// Parse a syntax name, `"repeat"` or `"brackets"`.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "repeat" => Some(QueryArraySyntax::Repeat),
+            "brackets" => Some(QueryArraySyntax::Brackets),
+            _ => None,
+        }
+    }
+
+    /// Syntax name.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QueryArraySyntax::Repeat => "repeat",
+            QueryArraySyntax::Brackets => "brackets",
+        }
+    }
+}
+
+/// Decode one `key=value` pair of a query string; `+` is a space.
+pub fn decode_query_pair(pair: &str) -> Option<(String, String)> {
+    let mut parts = pair.splitn(2, '=');
+    let key = urlencoding::decode(parts.next()?).unwrap_or_default();
+    let value = match parts.next() {
+        Some(value) => urlencoding::decode(&value.replace('+', " "))
+            .unwrap_or_default()
+            .to_string(),
+        None => String::new(),
+    };
+    Some((key.to_string(), value))
+}
+
+/// The decoded parameters of `query`, in order, repeated keys included.
+pub fn parse_query_list(query: &str, syntax: QueryArraySyntax) -> Vec<(String, String)> {
+    query
+        .split('&')
+        .filter(|s| !s.is_empty())
+        .filter_map(decode_query_pair)
+        .map(|(mut key, value)| {
+            if syntax == QueryArraySyntax::Brackets && key.ends_with("[]") {
+                key.truncate(key.len() - 2);
+            }
+            (key, value)
+        })
+        .collect()
+}
+
+/// Encode `pairs` as a query string.
+pub fn encode_query<'a>(pairs: impl IntoIterator<Item = (&'a str, &'a str)>) -> String {
+    pairs
+        .into_iter()
+        .map(|(key, value)| {
+            format!(
+                "{}={}",
+                urlencoding::encode(key),
+                urlencoding::encode(value)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repeated_parameters() {
+        let query = "tag=a&tag[]=b+c&page=2&flag&tag=%C3%A9";
+        let brackets = parse_query_list(query, QueryArraySyntax::Brackets);
+        let tags: Vec<&str> = brackets
+            .iter()
+            .filter(|(k, _)| k == "tag")
+            .map(|(_, v)| v.as_str())
+            .collect();
+        assert_eq!(tags, vec!["a", "b c", "é"]);
+        assert!(brackets.contains(&("flag".to_string(), String::new())));
+
+        let repeat = parse_query_list(query, QueryArraySyntax::Repeat);
+        assert_eq!(repeat[1], ("tag[]".to_string(), "b c".to_string()));
+        assert_eq!(repeat.iter().filter(|(k, _)| k == "tag").count(), 2);
+    }
+
+    #[test]
+    fn test_syntax_names_and_encoding() {
+        assert_eq!(
+            QueryArraySyntax::parse("Repeat"),
+            Some(QueryArraySyntax::Repeat)
+        );
+        assert_eq!(QueryArraySyntax::parse("comma"), None);
+        assert_eq!(QueryArraySyntax::default().as_str(), "brackets");
+
+        let raw = encode_query([("q", "a b&c"), ("n", "1")]);
+        assert_eq!(raw, "q=a%20b%26c&n=1");
+        assert_eq!(
+            parse_query_list(&raw, QueryArraySyntax::Repeat),
+            vec![
+                ("q".to_string(), "a b&c".to_string()),
+                ("n".to_string(), "1".to_string())
+            ]
+        );
+    }
+}
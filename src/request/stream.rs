@@ -0,0 +1,381 @@
+//! Streaming request bodies.
+//!
+//! Routes marked with `@stream_body` receive their body unbuffered: the
+//! handler reads it chunk by chunk with `async for chunk in request.stream()`,
+//! so a multi-gigabyte upload never sits in memory whole. `await
+//! request.read()` buffers the rest on demand, after which `body()`, `json()`
+//! and friends work as usual.
+//!
+//! `request.ndjson()` reads a body of newline-delimited JSON the same way,
+//! yielding one parsed document at a time.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use bytes::Bytes;
+use http_body_util::combinators::UnsyncBoxBody;
+use http_body_util::{BodyExt, Full};
+use pyo3::exceptions::{PyIOError, PyRuntimeError, PyStopAsyncIteration};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+use crate::json::{json_to_python, parse_json_bytes};
+use crate::request::BodyDecodeError;
+use crate::server::throttle::MinRate;
+
+/// Default maximum length of one NDJSON line.
+pub const DEFAULT_MAX_LINE_SIZE: usize = 1024 * 1024;
+
+/// A type-erased HTTP body.
+pub type BoxedBody = UnsyncBoxBody<Bytes, String>;
+
+/// The reading side of a streamed body.
+struct Reader {
+    /// The body, or `None` once it has ended
+    body: Option<BoxedBody>,
+    /// Minimum upload rate and when reading began
+    min_rate: Option<(MinRate, Instant)>,
+    /// Bytes read so far
+    received: u64,
+}
+
+/// A request body read incrementally, exposed to Python as `RequestStream`.
+///
+/// Clones share the underlying body, so each chunk is returned once.
+#[pyclass(name = "RequestStream")]
+#[derive(Clone)]
+pub struct BodyStream {
+    reader: Arc<Mutex<Reader>>,
+    /// Set when the handler has claimed the stream
+    claimed: Arc<AtomicBool>,
+}
+
+impl BodyStream {
+    /// Stream an HTTP body, failing if it arrives slower than `min_rate`.
+    pub fn new<B>(body: B, min_rate: Option<MinRate>) -> Self
+    where
+        B: hyper::body::Body<Data = Bytes> + Send + 'static,
+        B::Error: std::fmt::Display,
+    {
+        Self {
+            reader: Arc::new(Mutex::new(Reader {
+                body: Some(body.map_err(|e| e.to_string()).boxed_unsync()),
+                min_rate: min_rate.map(|rate| (rate, Instant::now())),
+                received: 0,
+            })),
+            claimed: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Stream an already-buffered body as a single chunk.
+    pub fn from_bytes(body: Vec<u8>) -> Self {
+        Self::new(Full::new(Bytes::from(body)), None)
+    }
+
+    /// Claim the stream for the handler; it can be claimed once.
+    pub fn claim(&self) -> Result<Self, String> {
+        if self.claimed.swap(true, Ordering::AcqRel) {
+            return Err("Request body stream has already been consumed".to_string());
+        }
+        Ok(self.clone())
+    }
+
+    /// Whether the handler has claimed the stream.
+    pub fn is_claimed(&self) -> bool {
+        self.claimed.load(Ordering::Acquire)
+    }
+
+    /// The next chunk, or `None` at the end of the body.
+    pub async fn next_chunk(&self) -> Result<Option<Bytes>, String> {
+        let mut reader = self.reader.lock().await;
+        loop {
+            let deadline = reader
+                .min_rate
+                .map(|(rate, start)| rate.deadline(start, reader.received));
+            let Some(body) = reader.body.as_mut() else {
+                return Ok(None);
+            };
+            let frame = match deadline {
+                Some(deadline) => match tokio::time::timeout_at(deadline, body.frame()).await {
+                    Ok(frame) => frame,
+                    Err(_) => {
+                        reader.body = None;
+                        return Err("Request body too slow".to_string());
+                    }
+                },
+                None => body.frame().await,
+            };
+            match frame {
+                None => {
+                    reader.body = None;
+                    return Ok(None);
+                }
+                Some(Err(e)) => {
+                    reader.body = None;
+                    return Err(e);
+                }
+                Some(Ok(frame)) => {
+                    if let Ok(data) = frame.into_data() {
+                        if !data.is_empty() {
+                            reader.received += data.len() as u64;
+                            return Ok(Some(data));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Read the rest of the body.
+    pub async fn read_to_end(&self) -> Result<Vec<u8>, String> {
+        let mut buf = Vec::new();
+        while let Some(chunk) = self.next_chunk().await? {
+            buf.extend_from_slice(&chunk);
+        }
+        Ok(buf)
+    }
+}
+
+#[pymethods]
+impl BodyStream {
+    fn __aiter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __anext__(&self, py: Python<'_>) -> PyResult<Option<PyObject>> {
+        let stream = self.clone();
+        let next = pyo3_asyncio::tokio::future_into_py(py, async move {
+            match stream.next_chunk().await {
+                Ok(Some(chunk)) => Ok(Python::with_gil(|py| {
+                    PyBytes::new(py, &chunk).to_object(py)
+                })),
+                Ok(None) => Err(PyStopAsyncIteration::new_err(())),
+                Err(e) => Err(PyIOError::new_err(e)),
+            }
+        })?;
+        Ok(Some(next.into()))
+    }
+
+    /// Read the rest of the body; returns an awaitable resolving to bytes.
+    #[pyo3(name = "read")]
+    fn py_read<'py>(&self, py: Python<'py>) -> PyResult<&'py PyAny> {
+        let stream = self.clone();
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            let body = stream.read_to_end().await.map_err(PyIOError::new_err)?;
+            Ok(Python::with_gil(|py| PyBytes::new(py, &body).to_object(py)))
+        })
+    }
+
+    fn __repr__(&self) -> String {
+        let state = if self.is_claimed() {
+            "claimed"
+        } else {
+            "unread"
+        };
+        format!("<RequestStream {state}>")
+    }
+}
+
+/// Why the next NDJSON document could not be read.
+#[derive(Debug, PartialEq)]
+pub enum NdjsonError {
+    /// The body failed or arrived too slowly
+    Body(String),
+    /// A line is not valid JSON or is too long
+    Invalid(String),
+}
+
+/// Line buffer of an NDJSON reader.
+struct Lines {
+    buf: Vec<u8>,
+    /// Bytes of `buf` already searched for a newline
+    scanned: usize,
+    /// Number of lines read so far
+    line: usize,
+    /// Whether the body has ended
+    done: bool,
+}
+
+/// A newline-delimited JSON body, exposed to Python as `NdjsonStream`.
+///
+/// Blank lines are skipped; a final line without a trailing newline is
+/// still read.
+#[pyclass(name = "NdjsonStream")]
+#[derive(Clone)]
+pub struct NdjsonStream {
+    body: BodyStream,
+    lines: Arc<Mutex<Lines>>,
+    max_line_size: usize,
+}
+
+impl NdjsonStream {
+    /// Read documents from a claimed body stream.
+    pub fn new(body: BodyStream, max_line_size: usize) -> Self {
+        Self {
+            body,
+            lines: Arc::new(Mutex::new(Lines {
+                buf: Vec::new(),
+                scanned: 0,
+                line: 0,
+                done: false,
+            })),
+            max_line_size,
+        }
+    }
+
+    /// The next document, or `None` at the end of the body.
+    pub async fn next_value(&self) -> Result<Option<serde_json::Value>, NdjsonError> {
+        let mut lines = self.lines.lock().await;
+        loop {
+            let newline = lines.buf[lines.scanned..].iter().position(|&b| b == b'\n');
+            let mut line = match newline {
+                Some(pos) => {
+                    let end = lines.scanned + pos;
+                    let line: Vec<u8> = lines.buf.drain(..=end).take(end).collect();
+                    lines.scanned = 0;
+                    line
+                }
+                None if lines.done => {
+                    if lines.buf.is_empty() {
+                        return Ok(None);
+                    }
+                    lines.scanned = 0;
+                    std::mem::take(&mut lines.buf)
+                }
+                None => {
+                    lines.scanned = lines.buf.len();
+                    if lines.scanned > self.max_line_size {
+                        return Err(NdjsonError::Invalid(format!(
+                            "NDJSON line {} exceeds {} bytes",
+                            lines.line + 1,
+                            self.max_line_size
+                        )));
+                    }
+                    match self.body.next_chunk().await.map_err(NdjsonError::Body)? {
+                        Some(chunk) => lines.buf.extend_from_slice(&chunk),
+                        None => lines.done = true,
+                    }
+                    continue;
+                }
+            };
+            lines.line += 1;
+            if line.len() > self.max_line_size {
+                return Err(NdjsonError::Invalid(format!(
+                    "NDJSON line {} exceeds {} bytes",
+                    lines.line, self.max_line_size
+                )));
+            }
+            if line.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+            return parse_json_bytes(&mut line)
+                .map(Some)
+                .map_err(|e| NdjsonError::Invalid(format!("NDJSON line {}: {e}", lines.line)));
+        }
+    }
+}
+
+#[pymethods]
+impl NdjsonStream {
+    fn __aiter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __anext__(&self, py: Python<'_>) -> PyResult<Option<PyObject>> {
+        let stream = self.clone();
+        let next = pyo3_asyncio::tokio::future_into_py(py, async move {
+            match stream.next_value().await {
+                Ok(Some(value)) => Python::with_gil(|py| json_to_python(py, &value)),
+                Ok(None) => Err(PyStopAsyncIteration::new_err(())),
+                Err(NdjsonError::Body(e)) => Err(PyIOError::new_err(e)),
+                Err(NdjsonError::Invalid(e)) => Err(BodyDecodeError::new_err(e)),
+            }
+        })?;
+        Ok(Some(next.into()))
+    }
+
+    fn __repr__(&self) -> String {
+        "<NdjsonStream>".to_string()
+    }
+}
+
+/// The error raised when a streamed body is accessed before it is read.
+pub(crate) fn unread_error() -> PyErr {
+    PyRuntimeError::new_err(
+        "Request body is streamed; use `async for chunk in request.stream()` or `await request.read()` first",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_chunks() {
+        let stream = BodyStream::from_bytes(b"hello".to_vec());
+        let claimed = stream.claim().unwrap();
+        assert!(stream.claim().is_err());
+        assert_eq!(claimed.next_chunk().await.unwrap().unwrap(), "hello");
+        assert_eq!(claimed.next_chunk().await.unwrap(), None);
+        assert!(claimed.read_to_end().await.unwrap().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_min_rate() {
+        let (tx, mut rx) =
+            tokio::sync::mpsc::channel::<Result<hyper::body::Frame<Bytes>, String>>(1);
+        let body = http_body_util::StreamBody::new(futures_util::stream::poll_fn(move |cx| {
+            rx.poll_recv(cx)
+        }));
+        let rate = MinRate {
+            bytes_per_sec: 10,
+            grace: Duration::from_secs(1),
+        };
+        let stream = BodyStream::new(body, Some(rate));
+        tx.send(Ok(hyper::body::Frame::data(Bytes::from_static(b"abc"))))
+            .await
+            .unwrap();
+        assert_eq!(stream.next_chunk().await.unwrap().unwrap(), "abc");
+        assert_eq!(
+            stream.next_chunk().await,
+            Err("Request body too slow".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ndjson_lines() {
+        let body = b"{\"a\":1}\r\n\n[2,3]\n\"last\"".to_vec();
+        let stream = NdjsonStream::new(BodyStream::from_bytes(body), DEFAULT_MAX_LINE_SIZE);
+        assert_eq!(
+            stream.next_value().await.unwrap(),
+            Some(serde_json::json!({"a": 1}))
+        );
+        assert_eq!(
+            stream.next_value().await.unwrap(),
+            Some(serde_json::json!([2, 3]))
+        );
+        assert_eq!(
+            stream.next_value().await.unwrap(),
+            Some(serde_json::json!("last"))
+        );
+        assert_eq!(stream.next_value().await, Ok(None));
+
+        let body = b"{\"a\":1}\n{oops}\n".to_vec();
+        let stream = NdjsonStream::new(BodyStream::from_bytes(body), DEFAULT_MAX_LINE_SIZE);
+        assert!(stream.next_value().await.unwrap().is_some());
+        assert!(matches!(
+            stream.next_value().await,
+            Err(NdjsonError::Invalid(e)) if e.starts_with("NDJSON line 2")
+        ));
+
+        let body = b"[1,2,3,4,5,6,7,8,9]\n".to_vec();
+        let stream = NdjsonStream::new(BodyStream::from_bytes(body), 8);
+        assert!(matches!(
+            stream.next_value().await,
+            Err(NdjsonError::Invalid(_))
+        ));
+    }
+}
@@ -0,0 +1,184 @@
+//! Streaming JSON arrays and NDJSON.
+//!
+//! A handler that returns `JsonStream(rows)` gets its list or generator
+//! encoded incrementally: items are serialized in batches on a blocking
+//! thread and each batch is flushed over a chunked response, so a response
+//! with tens of thousands of rows never sits in one buffer. The GIL is
+//! released between batches. `Response.ndjson(rows)` streams the same way,
+//! one JSON document per line.
+
+use bytes::Bytes;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyIterator;
+use tokio::sync::mpsc;
+
+use crate::json::write_json;
+use crate::server::{BodyError, ResponseBody};
+
+/// Default number of bytes encoded before a chunk is flushed.
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Chunks buffered ahead of a slow client.
+const CHUNK_BUFFER: usize = 4;
+
+/// A JSON array, or NDJSON, encoded from a Python iterable as the response
+/// is sent.
+#[pyclass(name = "JsonStream")]
+#[derive(Clone)]
+pub struct JsonStream {
+    items: Py<PyIterator>,
+    chunk_size: usize,
+    /// One document per line instead of an array
+    lines: bool,
+    /// HTTP status of the response
+    #[pyo3(get)]
+    status: u16,
+}
+
+#[pymethods]
+impl JsonStream {
+    /// Stream the items of `items` as a JSON array.
+    ///
+    /// `chunk_size` is the number of bytes encoded before each flush.
+    #[new]
+    #[pyo3(signature = (items, chunk_size=DEFAULT_CHUNK_SIZE, status=200))]
+    fn py_new(items: &PyAny, chunk_size: usize, status: u16) -> PyResult<Self> {
+        Self::new(items, chunk_size, status, false)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "JsonStream(status={}, chunk_size={}, ndjson={})",
+            self.status,
+            self.chunk_size,
+            if self.lines { "True" } else { "False" }
+        )
+    }
+}
+
+impl JsonStream {
+    /// Stream `items` as a JSON array, or as NDJSON when `lines` is set.
+    pub fn new(items: &PyAny, chunk_size: usize, status: u16, lines: bool) -> PyResult<Self> {
+        if chunk_size == 0 {
+            return Err(PyValueError::new_err("chunk_size must be positive"));
+        }
+        Ok(Self {
+            items: items.iter()?.into(),
+            chunk_size,
+            lines,
+            status,
+        })
+    }
+
+    /// Content type of the response.
+    #[inline]
+    pub fn content_type(&self) -> &'static str {
+        if self.lines {
+            "application/x-ndjson"
+        } else {
+            "application/json"
+        }
+    }
+
+    /// HTTP status of the response.
+    #[inline]
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    /// Start encoding on a blocking thread and return the response body.
+    ///
+    /// An error while iterating or encoding aborts the response; dropping the
+    /// body (a disconnected client) stops the iteration.
+    pub fn into_body(self) -> ResponseBody {
+        let (tx, body) = ResponseBody::channel(CHUNK_BUFFER);
+        tokio::task::spawn_blocking(move || self.produce(tx));
+        body
+    }
+
+    fn produce(self, tx: mpsc::Sender<Result<Bytes, BodyError>>) {
+        let mut buf = if self.lines { Vec::new() } else { vec![b'['] };
+        let mut first = true;
+        loop {
+            let batch = Python::with_gil(|py| -> Result<bool, String> {
+                let mut items = self.items.as_ref(py);
+                while buf.len() < self.chunk_size {
+                    let Some(item) = items.next() else {
+                        if !self.lines {
+                            buf.push(b']');
+                        }
+                        return Ok(true);
+                    };
+                    if !first && !self.lines {
+                        buf.push(b',');
+                    }
+                    first = false;
+                    write_json(py, item.map_err(|e| e.to_string())?, &mut buf)?;
+                    if self.lines {
+                        buf.push(b'\n');
+                    }
+                }
+                Ok(false)
+            });
+            let chunk = Bytes::from(std::mem::replace(
+                &mut buf,
+                Vec::with_capacity(self.chunk_size),
+            ));
+            match batch {
+                Ok(done) => {
+                    if tx.blocking_send(Ok(chunk)).is_err() || done {
+                        return;
+                    }
+                }
+                Err(e) => {
+                    eprintln!("JSON stream aborted: {e}");
+                    let _ = tx.blocking_send(Err(e.into()));
+                    return;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_body_util::BodyExt;
+
+    #[tokio::test]
+    async fn test_stream_chunks() {
+        pyo3::prepare_freethreaded_python();
+        let stream = Python::with_gil(|py| {
+            let items = py
+                .eval("({'id': i} for i in range(1000))", None, None)
+                .unwrap();
+            JsonStream::py_new(items, 256, 200).unwrap()
+        });
+
+        let mut body = stream.into_body();
+        let mut chunks = Vec::new();
+        while let Some(frame) = body.frame().await {
+            chunks.push(frame.unwrap().into_data().unwrap());
+        }
+        assert!(chunks.len() > 1);
+
+        let json: Vec<u8> = chunks.concat();
+        let value: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(value.as_array().unwrap().len(), 1000);
+        assert_eq!(value[999]["id"], 999);
+    }
+
+    #[tokio::test]
+    async fn test_ndjson() {
+        pyo3::prepare_freethreaded_python();
+        let stream = Python::with_gil(|py| {
+            let items = py.eval("[{'a': 1}, [2], 'three']", None, None).unwrap();
+            JsonStream::new(items, DEFAULT_CHUNK_SIZE, 200, true).unwrap()
+        });
+        assert_eq!(stream.content_type(), "application/x-ndjson");
+
+        let body = stream.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(&body[..], b"{\"a\":1}\n[2]\n\"three\"\n");
+    }
+}
@@ -1,12 +1,16 @@
 //! HTTP Response types and builders for Cello.
 //!
 //! This module provides:
-//! - Standard HTTP responses (JSON, HTML, text, binary)
+//! - Standard HTTP responses (JSON, MessagePack, CBOR, HTML, text, binary)
 //! - Streaming responses with backpressure
+//! - Streaming JSON arrays from Python iterables
 //! - File responses with zero-copy sendfile
 //! - XML serialization
 //! - Content negotiation helpers
+//! - Per-route header policies
 
+pub mod json_stream;
+pub mod policy;
 pub mod streaming;
 pub mod xml;
 
@@ -14,8 +18,13 @@ use pyo3::prelude::*;
 use std::collections::HashMap;
 use std::path::Path;
 
+use crate::codec::{python_to_bytes, BodyFormat};
 use crate::json::python_to_json;
+use crate::negotiation;
+use crate::request::Request;
 
+pub use json_stream::JsonStream;
+pub use policy::{CachePreset, HeaderPolicy};
 pub use streaming::{ChunkedBody, FileBody, StreamItem, StreamingResponse};
 pub use xml::{XmlResponse, XmlSerializer};
 
@@ -37,6 +46,8 @@ pub enum ResponseBody {
     File(String),
     /// Marker for chunked transfer
     Chunked,
+    /// Marker for a response never sent: the connection is dropped instead
+    Aborted,
 }
 
 // ============================================================================
@@ -114,6 +125,69 @@ impl Response {
         })
     }
 
+    /// Create a MessagePack response.
+    #[staticmethod]
+    #[pyo3(signature = (data, status=None))]
+    pub fn msgpack(data: &PyAny, status: Option<u16>) -> PyResult<Self> {
+        Self::encoded(data, BodyFormat::MsgPack, status)
+    }
+
+    /// Create a CBOR response.
+    #[staticmethod]
+    #[pyo3(signature = (data, status=None))]
+    pub fn cbor(data: &PyAny, status: Option<u16>) -> PyResult<Self> {
+        Self::encoded(data, BodyFormat::Cbor, status)
+    }
+
+    /// Respond with `data` in the format the request prefers: JSON,
+    /// MessagePack, CBOR or, when `html` is given, HTML.
+    ///
+    /// `html` is the HTML body, or a callable rendering `data` to it. JSON
+    /// is picked when the client has no preference; a client accepting none
+    /// of the formats gets 406 Not Acceptable.
+    #[staticmethod]
+    #[pyo3(signature = (request, data, status=None, html=None))]
+    pub fn negotiated(
+        py: Python<'_>,
+        request: &Request,
+        data: &PyAny,
+        status: Option<u16>,
+        html: Option<&PyAny>,
+    ) -> PyResult<Self> {
+        const FORMATS: [&str; 5] = [
+            "application/json",
+            "application/msgpack",
+            "application/x-msgpack",
+            "application/cbor",
+            "text/html",
+        ];
+        let offered = if html.is_some() {
+            &FORMATS[..]
+        } else {
+            &FORMATS[..4]
+        };
+        let accept = request.headers.get("accept");
+        let mut response = match negotiation::best_match(accept, offered) {
+            Some("text/html") => {
+                let html = html.expect("text/html is only offered with html");
+                let content = if html.is_callable() {
+                    html.call1((data,))?
+                } else {
+                    html
+                };
+                Self::html(content.extract()?, status)
+            }
+            Some("application/json") => Self::json(py, data, status)?,
+            Some(media_type) => {
+                let format = BodyFormat::from_media_type(media_type).unwrap_or_default();
+                Self::encoded(data, format, status)?
+            }
+            None => Self::error(406, "Not Acceptable"),
+        };
+        response.set_header("Vary", "Accept");
+        Ok(response)
+    }
+
     /// Create a plain text response.
     #[staticmethod]
     #[pyo3(signature = (content, status=None))]
@@ -171,12 +245,38 @@ impl Response {
         }
     }
 
+    /// Stream an iterable as NDJSON, one JSON document per line.
+    ///
+    /// Items are encoded in batches of about `chunk_size` bytes while the
+    /// response is sent.
+    #[staticmethod]
+    #[pyo3(signature = (items, chunk_size=json_stream::DEFAULT_CHUNK_SIZE, status=200))]
+    pub fn ndjson(items: &PyAny, chunk_size: usize, status: u16) -> PyResult<JsonStream> {
+        JsonStream::new(items, chunk_size, status, true)
+    }
+
     /// Create a file download response.
+    ///
+    /// The file is streamed from disk by the server, so it never passes
+    /// through Python, and `Range` requests are answered with partial
+    /// content. `disposition` is `"attachment"`, `"inline"` or `None` to
+    /// omit the `Content-Disposition` header.
+    ///
+    /// A missing file gives a 404 response and an unreadable one a 403; the
+    /// path and OS error are not sent to the client.
     #[staticmethod]
-    #[pyo3(signature = (path, filename=None, content_type=None))]
-    pub fn file(path: &str, filename: Option<&str>, content_type: Option<&str>) -> PyResult<Self> {
-        let data =
-            std::fs::read(path).map_err(|e| pyo3::exceptions::PyIOError::new_err(e.to_string()))?;
+    #[pyo3(signature = (path, filename=None, content_type=None, disposition=Some("attachment")))]
+    pub fn file(
+        path: &str,
+        filename: Option<&str>,
+        content_type: Option<&str>,
+        disposition: Option<&str>,
+    ) -> PyResult<Self> {
+        let metadata = match std::fs::File::open(path).and_then(|file| file.metadata()) {
+            Ok(metadata) if metadata.is_file() => metadata,
+            Ok(_) => return Ok(file_error(std::io::ErrorKind::NotFound)),
+            Err(e) => return Ok(file_error(e.kind())),
+        };
 
         let ct = content_type.map(|s| s.to_string()).unwrap_or_else(|| {
             mime_guess::from_path(path)
@@ -184,27 +284,35 @@ impl Response {
                 .to_string()
         });
 
-        let download_name = filename.map(|s| s.to_string()).unwrap_or_else(|| {
-            Path::new(path)
-                .file_name()
-                .map(|n| n.to_string_lossy().to_string())
-                .unwrap_or_else(|| "download".to_string())
-        });
-
         let mut headers = HashMap::new();
         headers.insert("Content-Type".to_string(), ct.clone());
-        headers.insert(
-            "Content-Disposition".to_string(),
-            format!("attachment; filename=\"{download_name}\""),
-        );
-        headers.insert("Content-Length".to_string(), data.len().to_string());
+        headers.insert("Content-Length".to_string(), metadata.len().to_string());
+        headers.insert("Accept-Ranges".to_string(), "bytes".to_string());
+
+        if let Some(disposition) = disposition {
+            if disposition != "attachment" && disposition != "inline" {
+                return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                    "Unknown disposition '{disposition}'; expected 'attachment' or 'inline'"
+                )));
+            }
+            let download_name = filename.map(|s| s.to_string()).unwrap_or_else(|| {
+                Path::new(path)
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_else(|| "download".to_string())
+            });
+            headers.insert(
+                "Content-Disposition".to_string(),
+                content_disposition(disposition, &download_name),
+            );
+        }
 
         Ok(Response {
             status: 200,
             headers,
-            body: data,
+            body: Vec::new(),
             content_type: ct,
-            body_type: ResponseBody::Bytes(Vec::new()),
+            body_type: ResponseBody::File(path.to_string()),
         })
     }
 
@@ -256,7 +364,7 @@ impl Response {
 
         // Parse range header (bytes=start-end)
         let (start, end) = parse_range_header(range_header, file_size)
-            .map_err(pyo3::exceptions::PyValueError::new_err)?;
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
 
         let content_length = end - start + 1;
 
@@ -434,6 +542,20 @@ impl Response {
         matches!(self.body_type, ResponseBody::Chunked)
     }
 
+    /// Check if the connection is dropped instead of sending this response.
+    #[inline]
+    pub fn is_aborted(&self) -> bool {
+        matches!(self.body_type, ResponseBody::Aborted)
+    }
+
+    /// A response that drops the connection instead of answering, e.g. to
+    /// simulate a network failure.
+    pub fn aborted() -> Self {
+        let mut response = Self::new(500);
+        response.body_type = ResponseBody::Aborted;
+        response
+    }
+
     /// Mark as streaming response.
     pub fn set_streaming(&mut self) {
         self.body_type = ResponseBody::Streaming;
@@ -480,6 +602,19 @@ impl Response {
         }
     }
 
+    /// Create a response from bytes already encoded in `format` (internal use).
+    #[inline]
+    pub fn from_encoded_bytes(format: BodyFormat, body: Vec<u8>, status: u16) -> Self {
+        Self::binary(body, Some(format.content_type()), Some(status))
+    }
+
+    /// Encode a Python value as MessagePack or CBOR (internal use).
+    fn encoded(data: &PyAny, format: BodyFormat, status: Option<u16>) -> PyResult<Self> {
+        let body =
+            python_to_bytes(data, format).map_err(pyo3::exceptions::PyValueError::new_err)?;
+        Ok(Self::binary(body, Some(format.content_type()), status))
+    }
+
     /// Create an error response (internal use).
     #[inline]
     pub fn error(status: u16, message: &str) -> Self {
@@ -520,42 +655,101 @@ impl Response {
 // Helper Functions
 // ============================================================================
 
-/// Parse HTTP Range header.
-fn parse_range_header(header: &str, file_size: u64) -> Result<(u64, u64), String> {
-    // Format: bytes=start-end or bytes=start- or bytes=-suffix
-    let header = header.trim();
-    if !header.starts_with("bytes=") {
-        return Err("Invalid range header format".to_string());
+/// Why a `Range` header could not be used.
+#[derive(Debug, PartialEq)]
+pub enum RangeError {
+    /// The header is malformed or asks for several ranges; it is ignored
+    Invalid,
+    /// The range lies outside the file (416 Range Not Satisfiable)
+    Unsatisfiable,
+}
+
+impl std::fmt::Display for RangeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RangeError::Invalid => f.write_str("Invalid range header"),
+            RangeError::Unsatisfiable => f.write_str("Range not satisfiable"),
+        }
     }
+}
 
-    let range = &header[6..];
-    let parts: Vec<&str> = range.split('-').collect();
+/// The response for a file that cannot be served: 404 when it is missing,
+/// 403 when it may not be read. The path and OS error stay out of the body.
+pub(crate) fn file_error(kind: std::io::ErrorKind) -> Response {
+    match kind {
+        std::io::ErrorKind::NotFound => Response::not_found("File not found"),
+        std::io::ErrorKind::PermissionDenied => Response::forbidden("Forbidden"),
+        _ => Response::internal_error("File could not be read"),
+    }
+}
 
-    if parts.len() != 2 {
-        return Err("Invalid range format".to_string());
+/// Parse an HTTP `Range` header into an inclusive byte range of the file.
+pub fn parse_range_header(header: &str, file_size: u64) -> Result<(u64, u64), RangeError> {
+    // Format: bytes=start-end or bytes=start- or bytes=-suffix
+    let range = header
+        .trim()
+        .strip_prefix("bytes=")
+        .ok_or(RangeError::Invalid)?;
+    let (first, last) = range.split_once('-').ok_or(RangeError::Invalid)?;
+    if last.contains(['-', ',']) {
+        return Err(RangeError::Invalid);
     }
 
-    let start = if parts[0].is_empty() {
+    let (start, end) = if first.is_empty() {
         // Suffix range: -500 means last 500 bytes
-        let suffix: u64 = parts[1].parse().map_err(|_| "Invalid suffix range")?;
-        file_size.saturating_sub(suffix)
-    } else {
-        parts[0].parse().map_err(|_| "Invalid start range")?
-    };
-
-    let end = if parts[1].is_empty() {
-        file_size - 1
+        let suffix: u64 = last.parse().map_err(|_| RangeError::Invalid)?;
+        if suffix == 0 {
+            return Err(RangeError::Unsatisfiable);
+        }
+        (
+            file_size.saturating_sub(suffix),
+            file_size.saturating_sub(1),
+        )
     } else {
-        parts[1].parse().map_err(|_| "Invalid end range")?
+        let start: u64 = first.parse().map_err(|_| RangeError::Invalid)?;
+        let end = if last.is_empty() {
+            file_size.saturating_sub(1)
+        } else {
+            let end: u64 = last.parse().map_err(|_| RangeError::Invalid)?;
+            if end < start {
+                return Err(RangeError::Invalid);
+            }
+            // An end past the file is clamped to its last byte
+            end.min(file_size.saturating_sub(1))
+        };
+        (start, end)
     };
 
-    if start > end || end >= file_size {
-        return Err("Range not satisfiable".to_string());
+    if start >= file_size {
+        return Err(RangeError::Unsatisfiable);
     }
 
     Ok((start, end))
 }
 
+/// Build a `Content-Disposition` value for `filename`.
+///
+/// Names that are not plain ASCII get an RFC 6266 `filename*` parameter,
+/// with an ASCII fallback for older clients.
+fn content_disposition(disposition: &str, filename: &str) -> String {
+    let fallback: String = filename
+        .chars()
+        .map(|c| match c {
+            '"' | '\\' => '_',
+            c if c.is_ascii() && !c.is_ascii_control() => c,
+            _ => '_',
+        })
+        .collect();
+    if fallback == filename {
+        format!("{disposition}; filename=\"{filename}\"")
+    } else {
+        format!(
+            "{disposition}; filename=\"{fallback}\"; filename*=UTF-8''{}",
+            urlencoding::encode(filename)
+        )
+    }
+}
+
 /// Get content type for Accept header negotiation.
 pub fn negotiate_content_type(accept: &str, available: &[&str]) -> Option<String> {
     // Parse Accept header and find best match
@@ -616,6 +810,43 @@ mod tests {
         assert_eq!(parse_range_header("bytes=-100", 1000), Ok((900, 999)));
         assert!(parse_range_header("bytes=500-400", 1000).is_err());
         assert!(parse_range_header("invalid", 1000).is_err());
+        assert_eq!(parse_range_header("bytes=900-5000", 1000), Ok((900, 999)));
+        assert_eq!(
+            parse_range_header("bytes=1000-", 1000),
+            Err(RangeError::Unsatisfiable)
+        );
+        assert_eq!(
+            parse_range_header("bytes=0-1,5-6", 1000),
+            Err(RangeError::Invalid)
+        );
+    }
+
+    #[test]
+    fn test_file_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing.bin");
+        let resp = Response::file(missing.to_str().unwrap(), None, None, None).unwrap();
+        assert_eq!(resp.status, 404);
+        assert!(!String::from_utf8_lossy(&resp.body).contains("missing.bin"));
+
+        let resp = Response::file(dir.path().to_str().unwrap(), None, None, None).unwrap();
+        assert_eq!(resp.status, 404);
+
+        let resp = file_error(std::io::ErrorKind::PermissionDenied);
+        assert_eq!(resp.status, 403);
+        assert!(!String::from_utf8_lossy(&resp.body).contains("os error"));
+    }
+
+    #[test]
+    fn test_content_disposition() {
+        assert_eq!(
+            content_disposition("attachment", "report.pdf"),
+            "attachment; filename=\"report.pdf\""
+        );
+        assert_eq!(
+            content_disposition("inline", "résumé \"v2\".pdf"),
+            "inline; filename=\"r_sum_ _v2_.pdf\"; filename*=UTF-8''r%C3%A9sum%C3%A9%20%22v2%22.pdf"
+        );
     }
 
     #[test]
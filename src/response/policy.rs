@@ -0,0 +1,202 @@
+//! Declarative per-route response header policies.
+//!
+//! A policy is attached to a route (or every route of a blueprint) at
+//! registration and applied to each response before after-middleware runs.
+//! Headers the handler sets itself always win; `Vary` values are merged.
+
+use std::str::FromStr;
+
+use super::Response;
+
+/// Named `Cache-Control` presets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CachePreset {
+    /// `no-store`: never cache.
+    NoStore,
+    /// `no-cache`: cache, but revalidate before each use.
+    NoCache,
+    /// `private`: browser cache only.
+    Private,
+    /// `public`: any cache.
+    Public,
+    /// `public, max-age=31536000, immutable`: fingerprinted assets.
+    Immutable,
+}
+
+impl FromStr for CachePreset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "no-store" | "no_store" => Ok(CachePreset::NoStore),
+            "no-cache" | "no_cache" => Ok(CachePreset::NoCache),
+            "private" => Ok(CachePreset::Private),
+            "public" => Ok(CachePreset::Public),
+            "immutable" => Ok(CachePreset::Immutable),
+            _ => Err(format!(
+                "Unknown cache preset '{s}'; expected 'no-store', 'no-cache', 'private', 'public' or 'immutable'"
+            )),
+        }
+    }
+}
+
+impl CachePreset {
+    /// The `Cache-Control` value, with `max_age` seconds where it applies.
+    pub fn directive(self, max_age: Option<u64>) -> Result<String, String> {
+        match (self, max_age) {
+            (CachePreset::NoStore, None) => Ok("no-store".to_string()),
+            (CachePreset::NoCache, None) => Ok("no-cache".to_string()),
+            (CachePreset::Private, None) => Ok("private, no-cache".to_string()),
+            (CachePreset::Private, Some(age)) => Ok(format!("private, max-age={age}")),
+            (CachePreset::Public, None) => Ok("public, no-cache".to_string()),
+            (CachePreset::Public, Some(age)) => Ok(format!("public, max-age={age}")),
+            (CachePreset::Immutable, None) => Ok("public, max-age=31536000, immutable".to_string()),
+            (CachePreset::Immutable, Some(age)) => Ok(format!("public, max-age={age}, immutable")),
+            (preset, Some(_)) => Err(format!("max_age cannot be combined with {preset:?}")),
+        }
+    }
+}
+
+/// Headers applied to every response of a route.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HeaderPolicy {
+    /// `Cache-Control` value, applied to responses below 400 only
+    pub cache_control: Option<String>,
+    /// Request headers the response varies by
+    pub vary: Vec<String>,
+    /// Other headers, in order
+    pub headers: Vec<(String, String)>,
+}
+
+impl HeaderPolicy {
+    /// Create an empty policy.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set `Cache-Control` from a preset name or a raw directive.
+    ///
+    /// A value that is not a preset name is used verbatim, so
+    /// `"public, s-maxage=600"` works as well as `"public"`.
+    pub fn cache(mut self, value: &str, max_age: Option<u64>) -> Result<Self, String> {
+        self.cache_control = Some(match value.parse::<CachePreset>() {
+            Ok(preset) => preset.directive(max_age)?,
+            Err(_) if value.contains(['=', ',']) && max_age.is_none() => value.to_string(),
+            Err(e) => return Err(e),
+        });
+        Ok(self)
+    }
+
+    /// Add request headers the response varies by.
+    pub fn vary<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.vary.extend(names.into_iter().map(Into::into));
+        self
+    }
+
+    /// Add a header.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Check if the policy sets nothing.
+    pub fn is_empty(&self) -> bool {
+        self.cache_control.is_none() && self.vary.is_empty() && self.headers.is_empty()
+    }
+
+    /// Apply the policy to a response.
+    pub fn apply(&self, response: &mut Response) {
+        if let Some(cache_control) = &self.cache_control {
+            if response.status < 400 && header_key(response, "cache-control").is_none() {
+                response.set_header("Cache-Control", cache_control);
+            }
+        }
+
+        if !self.vary.is_empty() {
+            let key = header_key(response, "vary");
+            let mut values: Vec<String> = key
+                .as_ref()
+                .and_then(|key| response.headers.remove(key))
+                .map(|vary| vary.split(',').map(|v| v.trim().to_string()).collect())
+                .unwrap_or_default();
+            for name in &self.vary {
+                if !values.iter().any(|v| v.eq_ignore_ascii_case(name)) {
+                    values.push(name.clone());
+                }
+            }
+            response.set_header(key.as_deref().unwrap_or("Vary"), &values.join(", "));
+        }
+
+        for (name, value) in &self.headers {
+            if header_key(response, name).is_none() {
+                response.set_header(name, value);
+            }
+        }
+    }
+}
+
+/// The key under which a response header is stored, matched case-insensitively.
+fn header_key(response: &Response, name: &str) -> Option<String> {
+    response
+        .headers
+        .keys()
+        .find(|key| key.eq_ignore_ascii_case(name))
+        .cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_presets() {
+        let policy = HeaderPolicy::new().cache("public", Some(60)).unwrap();
+        assert_eq!(policy.cache_control.as_deref(), Some("public, max-age=60"));
+        let policy = HeaderPolicy::new().cache("no-store", None).unwrap();
+        assert_eq!(policy.cache_control.as_deref(), Some("no-store"));
+        let policy = HeaderPolicy::new()
+            .cache("public, s-maxage=600", None)
+            .unwrap();
+        assert_eq!(
+            policy.cache_control.as_deref(),
+            Some("public, s-maxage=600")
+        );
+        assert!(HeaderPolicy::new().cache("no-store", Some(5)).is_err());
+        assert!(HeaderPolicy::new().cache("forever", None).is_err());
+    }
+
+    #[test]
+    fn test_apply() {
+        let policy = HeaderPolicy::new()
+            .cache("private", Some(30))
+            .unwrap()
+            .vary(["Accept", "Authorization"])
+            .header("X-Robots-Tag", "noindex");
+
+        let mut response = Response::new(200);
+        response.set_header("vary", "accept-encoding, accept");
+        response.set_header("x-robots-tag", "all");
+        policy.apply(&mut response);
+        assert_eq!(response.headers["Cache-Control"], "private, max-age=30");
+        assert_eq!(
+            response.headers["vary"],
+            "accept-encoding, accept, Authorization"
+        );
+        assert_eq!(response.headers["x-robots-tag"], "all");
+        assert!(!response.headers.contains_key("X-Robots-Tag"));
+
+        let mut error = Response::new(404);
+        policy.apply(&mut error);
+        assert!(!error.headers.contains_key("Cache-Control"));
+        assert_eq!(error.headers["Vary"], "Accept, Authorization");
+
+        let mut own = Response::new(200);
+        own.set_header("Cache-Control", "no-store");
+        policy.apply(&mut own);
+        assert_eq!(own.headers["Cache-Control"], "no-store");
+    }
+}
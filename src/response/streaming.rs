@@ -7,17 +7,27 @@
 //! - File body with sendfile support
 
 use std::collections::VecDeque;
+use std::io::SeekFrom;
 use std::path::PathBuf;
 use std::pin::Pin;
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 use std::task::{Context, Poll};
 
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
 use futures_core::Stream;
 use parking_lot::Mutex;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
 use tokio::sync::mpsc;
 
+use crate::server::ResponseBody;
+
+/// Bytes read from disk per chunk of a file body.
+const FILE_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Chunks of a file body read ahead of a slow client.
+const FILE_CHUNK_BUFFER: usize = 4;
+
 // ============================================================================
 // Stream Item
 // ============================================================================
@@ -405,6 +415,41 @@ impl FileBody {
     pub fn exists(&self) -> bool {
         self.path.exists()
     }
+
+    /// Open the file and stream its range as a response body.
+    ///
+    /// Chunks are read with `tokio::fs` on the runtime, so the content never
+    /// passes through Python. A file that shrinks while it is sent aborts the
+    /// response.
+    pub async fn into_body(self) -> std::io::Result<ResponseBody> {
+        let mut file = tokio::fs::File::open(&self.path).await?;
+        let file_size = file.metadata().await?.len();
+        let remaining = file_size.saturating_sub(self.offset);
+        let length = self.length.unwrap_or(remaining).min(remaining);
+        file.seek(SeekFrom::Start(self.offset)).await?;
+
+        let (tx, body) = ResponseBody::channel(FILE_CHUNK_BUFFER);
+        tokio::spawn(async move {
+            let mut reader = file.take(length);
+            let mut sent = 0;
+            while sent < length {
+                let mut chunk = BytesMut::with_capacity(FILE_CHUNK_SIZE);
+                let item = match reader.read_buf(&mut chunk).await {
+                    Ok(0) => Err("File truncated while sending".into()),
+                    Ok(n) => {
+                        sent += n as u64;
+                        Ok(chunk.freeze())
+                    }
+                    Err(e) => Err(e.into()),
+                };
+                let failed = item.is_err();
+                if tx.send(item).await.is_err() || failed {
+                    return;
+                }
+            }
+        });
+        Ok(body)
+    }
 }
 
 // ============================================================================
@@ -528,6 +573,34 @@ mod tests {
         assert_eq!(body.content_type, "text/plain");
     }
 
+    #[tokio::test]
+    async fn test_file_body_stream() {
+        use http_body_util::BodyExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.bin");
+        let data: Vec<u8> = (0..200_000u32).map(|i| i as u8).collect();
+        std::fs::write(&path, &data).unwrap();
+
+        let body = FileBody::new(&path).into_body().await.unwrap();
+        let sent = body.collect().await.unwrap().to_bytes();
+        assert_eq!(&sent[..], &data[..]);
+
+        let body = FileBody::new(&path)
+            .offset(70_000)
+            .length(100_000)
+            .into_body()
+            .await
+            .unwrap();
+        let sent = body.collect().await.unwrap().to_bytes();
+        assert_eq!(&sent[..], &data[70_000..170_000]);
+
+        assert!(FileBody::new(dir.path().join("missing"))
+            .into_body()
+            .await
+            .is_err());
+    }
+
     #[tokio::test]
     async fn test_streaming_response() {
         let (mut response, producer) = StreamingResponse::new(4);
@@ -7,18 +7,39 @@ use parking_lot::RwLock;
 use std::collections::HashMap;
 use std::sync::Arc;
 
+use crate::handler::HandlerMeta;
+use crate::wsgi::WsgiMount;
+
 /// Route information containing the handler ID and extracted parameters.
 #[derive(Clone, Debug)]
 pub struct RouteMatch {
     pub handler_id: usize,
+    /// Handler metadata stored at registration (skips the registry lookup)
+    pub handler: Option<Arc<HandlerMeta>>,
     pub params: HashMap<String, String>,
+    /// The matched path pattern, in `{param}` syntax
+    pub pattern: Arc<str>,
+}
+
+/// Value stored in the radix tree for each route.
+#[derive(Clone, Debug)]
+struct RouteEntry {
+    handler_id: usize,
+    handler: Option<Arc<HandlerMeta>>,
+    pattern: Arc<str>,
 }
 
 /// HTTP method-based router using radix trees.
 #[derive(Clone)]
 pub struct Router {
     /// Separate router for each HTTP method
-    routes: Arc<RwLock<HashMap<String, MatchitRouter<usize>>>>,
+    routes: Arc<RwLock<HashMap<String, MatchitRouter<RouteEntry>>>>,
+    /// Registered (method, path pattern, entry) triples in registration order
+    registered: Arc<RwLock<Vec<(String, String, RouteEntry)>>>,
+    /// Foreign apps mounted under a path prefix, longest prefix first
+    mounts: Arc<RwLock<Vec<Arc<WsgiMount>>>>,
+    /// Route names and the path patterns they build URLs for
+    names: Arc<RwLock<HashMap<String, String>>>,
 }
 
 impl Default for Router {
@@ -32,17 +53,24 @@ impl Router {
     pub fn new() -> Self {
         Router {
             routes: Arc::new(RwLock::new(HashMap::new())),
+            registered: Arc::new(RwLock::new(Vec::new())),
+            mounts: Arc::new(RwLock::new(Vec::new())),
+            names: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 
-    /// Convert Python-style path params {param} to matchit-style :param
+    /// Convert Python-style path params {param} to matchit-style :param,
+    /// and catch-all params {*param} to *param
     #[inline]
     fn convert_path_params(path: &str) -> String {
         let mut result = String::with_capacity(path.len());
 
-        for c in path.chars() {
+        let mut chars = path.chars().peekable();
+        while let Some(c) = chars.next() {
             if c == '{' {
-                result.push(':');
+                if chars.peek() != Some(&'*') {
+                    result.push(':');
+                }
             } else if c == '}' {
                 // Skip closing brace
             } else {
@@ -60,6 +88,40 @@ impl Router {
     /// * `path` - URL path pattern with optional parameters (e.g., "/users/{id}")
     /// * `handler_id` - ID of the registered handler
     pub fn add_route(&mut self, method: &str, path: &str, handler_id: usize) -> Result<(), String> {
+        self.insert(
+            method,
+            path,
+            RouteEntry {
+                handler_id,
+                handler: None,
+                pattern: path.into(),
+            },
+        )
+    }
+
+    /// Add a route that carries the handler's metadata.
+    ///
+    /// PERF: Matches on this route return the `Arc<HandlerMeta>` directly,
+    /// so dispatch needs no registry lock or index lookup.
+    pub fn add_handler_route(
+        &mut self,
+        method: &str,
+        path: &str,
+        handler_id: usize,
+        handler: Arc<HandlerMeta>,
+    ) -> Result<(), String> {
+        self.insert(
+            method,
+            path,
+            RouteEntry {
+                handler_id,
+                handler: Some(handler),
+                pattern: path.into(),
+            },
+        )
+    }
+
+    fn insert(&mut self, method: &str, path: &str, entry: RouteEntry) -> Result<(), String> {
         let mut routes = self.routes.write();
         let method_router = routes.entry(method.to_uppercase()).or_default();
 
@@ -67,8 +129,100 @@ impl Router {
         let converted_path = Self::convert_path_params(path);
 
         method_router
-            .insert(&converted_path, handler_id)
-            .map_err(|e| format!("Failed to add route: {e}"))
+            .insert(&converted_path, entry.clone())
+            .map_err(|e| format!("Failed to add route: {e}"))?;
+
+        self.registered
+            .write()
+            .push((method.to_uppercase(), path.to_string(), entry));
+        Ok(())
+    }
+
+    /// List registered routes as `(method, path)` pairs, using the original
+    /// `{param}` path syntax, in registration order.
+    pub fn routes(&self) -> Vec<(String, String)> {
+        self.registered
+            .read()
+            .iter()
+            .map(|(method, path, _)| (method.clone(), path.clone()))
+            .collect()
+    }
+
+    /// List registered routes that carry handler metadata, as `(method,
+    /// path, handler)`, in registration order.
+    pub fn route_handlers(&self) -> Vec<(String, String, Arc<HandlerMeta>)> {
+        self.registered
+            .read()
+            .iter()
+            .filter_map(|(method, path, entry)| {
+                let handler = entry.handler.clone()?;
+                Some((method.clone(), path.clone(), handler))
+            })
+            .collect()
+    }
+
+    /// Get the handler metadata registered for an exact `(method, path pattern)`.
+    pub fn route_handler(&self, method: &str, path: &str) -> Option<Arc<HandlerMeta>> {
+        let method = method.to_uppercase();
+        self.registered
+            .read()
+            .iter()
+            .find(|(m, p, _)| *m == method && p == path)
+            .and_then(|(_, _, entry)| entry.handler.clone())
+    }
+
+    /// Name the registered route `(method, path pattern)`, for `url_path`.
+    ///
+    /// A name builds one path pattern, so the routes of several methods on
+    /// the same path can share it but routes on different paths cannot.
+    pub fn name_route(&self, name: &str, method: &str, path: &str) -> Result<(), String> {
+        let method = method.to_uppercase();
+        if !self
+            .registered
+            .read()
+            .iter()
+            .any(|(m, p, _)| *m == method && p == path)
+        {
+            return Err(format!("No route {method} {path} to name '{name}'"));
+        }
+        let mut names = self.names.write();
+        match names.get(name) {
+            Some(existing) if existing != path => Err(format!(
+                "Route name '{name}' is already used for {existing}"
+            )),
+            _ => {
+                names.insert(name.to_string(), path.to_string());
+                Ok(())
+            }
+        }
+    }
+
+    /// The route names and their path patterns, sorted by name.
+    pub fn route_names(&self) -> Vec<(String, String)> {
+        let mut names: Vec<(String, String)> = self
+            .names
+            .read()
+            .iter()
+            .map(|(name, path)| (name.clone(), path.clone()))
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Build the path of the route named `name`, filling its parameters
+    /// from `params`. Returns the path and the names of the parameters it
+    /// used, so the caller can send the others as query parameters.
+    pub fn url_path(
+        &self,
+        name: &str,
+        params: &HashMap<String, String>,
+    ) -> Result<(String, Vec<String>), String> {
+        let names = self.names.read();
+        let pattern = names
+            .get(name)
+            .ok_or_else(|| format!("No route named '{name}'"))?;
+        build_path(pattern, params)
+            .map_err(|param| format!("Missing parameter '{param}' for route '{name}' ({pattern})"))
     }
 
     /// Match a request path against registered routes.
@@ -99,18 +253,101 @@ impl Router {
                 }
 
                 Some(RouteMatch {
-                    handler_id: *matched.value,
+                    handler_id: matched.value.handler_id,
+                    handler: matched.value.handler.clone(),
                     params,
+                    pattern: matched.value.pattern.clone(),
                 })
             }
             Err(_) => None,
         }
     }
+
+    /// Mount an app under its path prefix, replacing any mount at the same
+    /// prefix. Mounts only serve paths no route matches.
+    pub fn mount(&mut self, mount: Arc<WsgiMount>) {
+        let mut mounts = self.mounts.write();
+        mounts.retain(|m| m.prefix() != mount.prefix());
+        mounts.push(mount);
+        mounts.sort_by_key(|m| std::cmp::Reverse(m.prefix().len()));
+    }
+
+    /// Find the mount with the longest prefix covering `path`.
+    #[inline]
+    pub fn match_mount(&self, path: &str) -> Option<Arc<WsgiMount>> {
+        let mounts = self.mounts.read();
+        // PERF: Skip the scan entirely for apps without mounts
+        if mounts.is_empty() {
+            return None;
+        }
+        mounts.iter().find(|m| m.matches(path)).cloned()
+    }
+
+    /// List the methods that have a route matching `path`.
+    ///
+    /// HEAD is implied by GET and OPTIONS by any route, since the server
+    /// answers both automatically. Returns an empty list when no route matches
+    /// the path at all (a 404 rather than a 405).
+    pub fn allowed_methods(&self, path: &str) -> Vec<String> {
+        let routes = self.routes.read();
+        let mut methods: Vec<String> = routes
+            .iter()
+            .filter(|(_, method_router)| method_router.at(path).is_ok())
+            .map(|(method, _)| method.clone())
+            .collect();
+
+        if methods.is_empty() {
+            return methods;
+        }
+        if methods.iter().any(|m| m == "GET") && !methods.iter().any(|m| m == "HEAD") {
+            methods.push("HEAD".to_string());
+        }
+        if !methods.iter().any(|m| m == "OPTIONS") {
+            methods.push("OPTIONS".to_string());
+        }
+        methods.sort();
+        methods
+    }
+}
+
+/// Fill the `{param}` and `{*param}` segments of `pattern` from `params`,
+/// percent-encoding the values; a catch-all value keeps its slashes.
+/// Returns the path and the parameters used, or the first missing one.
+fn build_path(
+    pattern: &str,
+    params: &HashMap<String, String>,
+) -> Result<(String, Vec<String>), String> {
+    let mut path = String::with_capacity(pattern.len());
+    let mut used = Vec::new();
+    let mut rest = pattern;
+    while let Some(start) = rest.find('{') {
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        path.push_str(&rest[..start]);
+        let param = &rest[start + 1..start + len];
+        let (name, catch_all) = match param.strip_prefix('*') {
+            Some(name) => (name, true),
+            None => (param, false),
+        };
+        let value = params.get(name).ok_or_else(|| name.to_string())?;
+        if catch_all {
+            let segments: Vec<_> = value.split('/').map(urlencoding::encode).collect();
+            path.push_str(&segments.join("/"));
+        } else {
+            path.push_str(&urlencoding::encode(value));
+        }
+        used.push(name.to_string());
+        rest = &rest[start + len + 1..];
+    }
+    path.push_str(rest);
+    Ok((path, used))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use pyo3::Python;
 
     #[test]
     fn test_basic_routing() {
@@ -129,6 +366,24 @@ mod tests {
         assert_eq!(match3.handler_id, 2);
 
         assert!(router.match_route("DELETE", "/").is_none());
+        assert!(match1.handler.is_none());
+    }
+
+    #[test]
+    fn test_handler_route_carries_meta() {
+        pyo3::prepare_freethreaded_python();
+        let mut registry = crate::handler::HandlerRegistry::new();
+        let (id, meta) = Python::with_gil(|py| registry.register_with_meta(py.None()));
+
+        let mut router = Router::new();
+        router
+            .add_handler_route("GET", "/items/{id}", id, meta.clone())
+            .unwrap();
+
+        let matched = router.match_route("GET", "/items/7").unwrap();
+        assert_eq!(matched.handler_id, id);
+        assert!(Arc::ptr_eq(matched.handler.as_ref().unwrap(), &meta));
+        assert_eq!(matched.params.get("id"), Some(&"7".to_string()));
     }
 
     #[test]
@@ -157,5 +412,85 @@ mod tests {
         assert_eq!(match2.handler_id, 1);
         assert_eq!(match2.params.get("post_id"), Some(&"456".to_string()));
         assert_eq!(match2.params.get("comment_id"), Some(&"789".to_string()));
+
+        // Catch-all params capture the rest of the path
+        router.add_route("GET", "/files/{*path}", 2).unwrap();
+        router.add_route("GET", "/items/{item.id}", 3).unwrap();
+        let match3 = router.match_route("GET", "/files/a/b.txt").unwrap();
+        assert_eq!(match3.params.get("path"), Some(&"a/b.txt".to_string()));
+        let match4 = router.match_route("GET", "/items/9").unwrap();
+        assert_eq!(match4.params.get("item.id"), Some(&"9".to_string()));
+    }
+
+    #[test]
+    fn test_allowed_methods() {
+        let mut router = Router::new();
+        router.add_route("GET", "/users/{id}", 0).unwrap();
+        router.add_route("DELETE", "/users/{id}", 1).unwrap();
+        router.add_route("POST", "/users", 2).unwrap();
+
+        assert_eq!(
+            router.allowed_methods("/users/5"),
+            vec!["DELETE", "GET", "HEAD", "OPTIONS"]
+        );
+        assert_eq!(router.allowed_methods("/users"), vec!["OPTIONS", "POST"]);
+        assert!(router.allowed_methods("/missing").is_empty());
+    }
+
+    #[test]
+    fn test_registered_routes() {
+        let mut router = Router::new();
+        router.add_route("get", "/users/{id}", 0).unwrap();
+        router.add_route("POST", "/users", 1).unwrap();
+        // Conflicting routes are rejected and not listed
+        assert!(router.add_route("GET", "/users/{id}", 2).is_err());
+
+        assert_eq!(
+            router.routes(),
+            vec![
+                ("GET".to_string(), "/users/{id}".to_string()),
+                ("POST".to_string(), "/users".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_named_routes() {
+        let mut router = Router::new();
+        router.add_route("GET", "/users/{id}", 0).unwrap();
+        router.add_route("DELETE", "/users/{id}", 1).unwrap();
+        router.add_route("GET", "/files/{*path}", 2).unwrap();
+        router.name_route("user", "GET", "/users/{id}").unwrap();
+        router.name_route("user", "delete", "/users/{id}").unwrap();
+        router.name_route("file", "GET", "/files/{*path}").unwrap();
+
+        // Names only refer to registered routes, one path each
+        assert!(router.name_route("missing", "GET", "/missing").is_err());
+        assert!(router.name_route("user", "GET", "/files/{*path}").is_err());
+
+        let params: HashMap<String, String> = [
+            ("id".to_string(), "a b/c".to_string()),
+            ("path".to_string(), "docs/read me.txt".to_string()),
+            ("page".to_string(), "2".to_string()),
+        ]
+        .into_iter()
+        .collect();
+        let (path, used) = router.url_path("user", &params).unwrap();
+        assert_eq!(path, "/users/a%20b%2Fc");
+        assert_eq!(used, vec!["id"]);
+        // The built path routes back to the named route
+        assert_eq!(router.match_route("GET", "/users/7").unwrap().handler_id, 0);
+        let (path, _) = router.url_path("file", &params).unwrap();
+        assert_eq!(path, "/files/docs/read%20me.txt");
+
+        assert!(router.url_path("user", &HashMap::new()).is_err());
+        assert!(router.url_path("nope", &params).is_err());
+        assert_eq!(
+            router.route_names(),
+            vec![
+                ("file".to_string(), "/files/{*path}".to_string()),
+                ("user".to_string(), "/users/{id}".to_string()),
+            ]
+        );
     }
 }
@@ -0,0 +1,293 @@
+//! Canary routing between two versions of a route's handler.
+//!
+//! A canary handler takes a share of a route's requests, by weight, while
+//! the stable handler serves the rest. Clients can pin a version with a
+//! header or cookie (`canary`/`1`/`true` or `stable`/`0`/`false`), e.g.
+//! for testers. Requests, errors and latency are counted per version, so
+//! the canary's health can be compared with the stable handler's before
+//! raising its weight.
+
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use rand::Rng;
+
+use crate::handler::HandlerMeta;
+use crate::request::Request;
+
+/// Version of a route's handler serving a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CanaryVariant {
+    /// The route's own handler
+    Stable,
+    /// The canary handler
+    Canary,
+}
+
+impl CanaryVariant {
+    /// Parse a forcing header or cookie value.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "canary" | "1" | "true" | "always" => Some(CanaryVariant::Canary),
+            "stable" | "0" | "false" | "never" => Some(CanaryVariant::Stable),
+            _ => None,
+        }
+    }
+
+    /// Variant name, as used in metrics.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CanaryVariant::Stable => "stable",
+            CanaryVariant::Canary => "canary",
+        }
+    }
+}
+
+/// Request counters of one variant.
+#[derive(Debug, Default)]
+pub struct VariantStats {
+    requests: AtomicU64,
+    errors: AtomicU64,
+    latency_micros: AtomicU64,
+}
+
+impl VariantStats {
+    fn record(&self, status: u16, elapsed: Duration) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        if status >= 500 {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+        self.latency_micros
+            .fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// Current values of the counters.
+    pub fn snapshot(&self) -> VariantSnapshot {
+        let requests = self.requests.load(Ordering::Relaxed);
+        let errors = self.errors.load(Ordering::Relaxed);
+        let latency = self.latency_micros.load(Ordering::Relaxed);
+        let ratio = |n: u64| {
+            if requests == 0 {
+                0.0
+            } else {
+                n as f64 / requests as f64
+            }
+        };
+        VariantSnapshot {
+            requests,
+            errors,
+            error_rate: ratio(errors),
+            avg_latency_ms: ratio(latency) / 1000.0,
+        }
+    }
+}
+
+/// Point-in-time copy of [`VariantStats`].
+#[derive(Debug, Clone, Serialize)]
+pub struct VariantSnapshot {
+    /// Requests handled
+    pub requests: u64,
+    /// Handler errors and 5xx responses
+    pub errors: u64,
+    /// `errors / requests`
+    pub error_rate: f64,
+    /// Mean handler time in milliseconds
+    pub avg_latency_ms: f64,
+}
+
+/// Stats of both variants of a route.
+#[derive(Debug, Clone, Serialize)]
+pub struct CanarySnapshot {
+    /// Percentage of requests sent to the canary
+    pub weight: f64,
+    pub stable: VariantSnapshot,
+    pub canary: VariantSnapshot,
+}
+
+/// The canary of a route.
+pub struct Canary {
+    handler: Arc<HandlerMeta>,
+    /// Percentage of requests for the canary, as `f64` bits
+    weight: AtomicU64,
+    header: Option<String>,
+    cookie: Option<String>,
+    stable_stats: VariantStats,
+    canary_stats: VariantStats,
+}
+
+impl Canary {
+    /// Send `weight` percent of requests to `handler`.
+    pub fn new(handler: Arc<HandlerMeta>, weight: f64) -> Self {
+        Self {
+            handler,
+            weight: AtomicU64::new(weight.clamp(0.0, 100.0).to_bits()),
+            header: None,
+            cookie: None,
+            stable_stats: VariantStats::default(),
+            canary_stats: VariantStats::default(),
+        }
+    }
+
+    /// Let clients pick the variant with this request header.
+    pub fn with_header(mut self, header: &str) -> Self {
+        self.header = Some(header.to_string());
+        self
+    }
+
+    /// Let clients pick the variant with this cookie.
+    pub fn with_cookie(mut self, cookie: &str) -> Self {
+        self.cookie = Some(cookie.to_string());
+        self
+    }
+
+    /// The canary handler.
+    pub fn handler(&self) -> &Arc<HandlerMeta> {
+        &self.handler
+    }
+
+    /// Percentage of requests sent to the canary.
+    pub fn weight(&self) -> f64 {
+        f64::from_bits(self.weight.load(Ordering::Relaxed))
+    }
+
+    /// Change the canary's share of requests, clamped to 0..=100.
+    pub fn set_weight(&self, weight: f64) {
+        self.weight
+            .store(weight.clamp(0.0, 100.0).to_bits(), Ordering::Relaxed);
+    }
+
+    /// The variant forced by the request's header or cookie, if any.
+    fn forced(&self, request: &Request) -> Option<CanaryVariant> {
+        let header = self
+            .header
+            .as_deref()
+            .and_then(|name| request.headers.get(name))
+            .and_then(CanaryVariant::parse);
+        header.or_else(|| {
+            let name = self.cookie.as_deref()?;
+            request
+                .headers
+                .get("cookie")?
+                .split(';')
+                .filter_map(|cookie| cookie.trim().split_once('='))
+                .find(|(key, _)| *key == name)
+                .and_then(|(_, value)| CanaryVariant::parse(value))
+        })
+    }
+
+    /// Pick the variant serving `request`.
+    pub fn choose(&self, request: &Request) -> CanaryVariant {
+        if let Some(variant) = self.forced(request) {
+            return variant;
+        }
+        let canary = match self.weight() {
+            w if w <= 0.0 => false,
+            w if w >= 100.0 => true,
+            w => rand::thread_rng().gen_bool(w / 100.0),
+        };
+        if canary {
+            CanaryVariant::Canary
+        } else {
+            CanaryVariant::Stable
+        }
+    }
+
+    /// Count a request served by `variant`.
+    pub fn record(&self, variant: CanaryVariant, status: u16, elapsed: Duration) {
+        match variant {
+            CanaryVariant::Stable => self.stable_stats.record(status, elapsed),
+            CanaryVariant::Canary => self.canary_stats.record(status, elapsed),
+        }
+    }
+
+    /// Current stats of both variants.
+    pub fn snapshot(&self) -> CanarySnapshot {
+        CanarySnapshot {
+            weight: self.weight(),
+            stable: self.stable_stats.snapshot(),
+            canary: self.canary_stats.snapshot(),
+        }
+    }
+}
+
+impl std::fmt::Debug for Canary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Canary")
+            .field("weight", &self.weight())
+            .field("header", &self.header)
+            .field("cookie", &self.cookie)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handler::HandlerRegistry;
+    use pyo3::Python;
+
+    fn canary(weight: f64) -> Canary {
+        pyo3::prepare_freethreaded_python();
+        let mut registry = HandlerRegistry::new();
+        let (_, meta) = Python::with_gil(|py| registry.register_with_meta(py.None()));
+        Canary::new(meta, weight)
+    }
+
+    fn request(header: Option<(&str, &str)>) -> Request {
+        let mut request = Request::default();
+        if let Some((name, value)) = header {
+            request.headers.insert(name, value);
+        }
+        request
+    }
+
+    #[test]
+    fn test_weight_extremes() {
+        let canary = canary(0.0);
+        for _ in 0..50 {
+            assert_eq!(canary.choose(&request(None)), CanaryVariant::Stable);
+        }
+        canary.set_weight(100.0);
+        for _ in 0..50 {
+            assert_eq!(canary.choose(&request(None)), CanaryVariant::Canary);
+        }
+        canary.set_weight(250.0);
+        assert_eq!(canary.weight(), 100.0);
+    }
+
+    #[test]
+    fn test_forced_by_header_and_cookie() {
+        let canary = canary(0.0).with_header("X-Canary").with_cookie("canary");
+        let forced = request(Some(("x-canary", "always")));
+        assert_eq!(canary.choose(&forced), CanaryVariant::Canary);
+
+        let forced = request(Some(("cookie", "session=abc; canary=1")));
+        assert_eq!(canary.choose(&forced), CanaryVariant::Canary);
+
+        canary.set_weight(100.0);
+        let forced = request(Some(("X-Canary", "stable")));
+        assert_eq!(canary.choose(&forced), CanaryVariant::Stable);
+
+        // Unknown values fall back to the weight
+        let unknown = request(Some(("X-Canary", "maybe")));
+        assert_eq!(canary.choose(&unknown), CanaryVariant::Canary);
+    }
+
+    #[test]
+    fn test_per_variant_stats() {
+        let canary = canary(5.0);
+        canary.record(CanaryVariant::Stable, 200, Duration::from_millis(2));
+        canary.record(CanaryVariant::Stable, 200, Duration::from_millis(4));
+        canary.record(CanaryVariant::Canary, 503, Duration::from_millis(10));
+
+        let snapshot = canary.snapshot();
+        assert_eq!(snapshot.weight, 5.0);
+        assert_eq!(snapshot.stable.requests, 2);
+        assert_eq!(snapshot.stable.errors, 0);
+        assert!((snapshot.stable.avg_latency_ms - 3.0).abs() < 1e-9);
+        assert_eq!(snapshot.canary.requests, 1);
+        assert_eq!(snapshot.canary.error_rate, 1.0);
+    }
+}
@@ -6,11 +6,17 @@
 //! - API versioning via Accept header
 //! - Wildcard/catch-all routes
 //! - Route priority control
+//! - Weighted canary routing between handler versions
+//! - Disabling routes at runtime
 //! - Compile-time route optimization
 
+pub mod canary;
 pub mod constraints;
+pub mod toggle;
 
+pub use canary::*;
 pub use constraints::*;
+pub use toggle::*;
 
 use matchit::Router as MatchitRouter;
 use parking_lot::RwLock;
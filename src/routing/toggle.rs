@@ -0,0 +1,191 @@
+//! Runtime route toggles.
+//!
+//! A route can be disabled while the server runs, e.g. during an incident
+//! where a single endpoint misbehaves: new requests are answered with a 503
+//! or 404 without running middleware or the handler, while requests already
+//! being handled finish normally. Every toggle is recorded in an audit log.
+
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::response::Response;
+
+/// Most toggles kept in the audit log; older ones are dropped.
+pub const MAX_TOGGLES: usize = 1000;
+
+/// How a disabled route answers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Disabled {
+    /// 503 (temporarily unavailable) or 404 (hidden)
+    pub status: u16,
+    /// Seconds sent in `Retry-After` with a 503
+    pub retry_after: Option<u32>,
+}
+
+impl Disabled {
+    /// Answer with `status`, which must be 503 or 404.
+    pub fn new(status: u16, retry_after: Option<u32>) -> Result<Self, String> {
+        match status {
+            503 | 404 => Ok(Self {
+                status,
+                retry_after,
+            }),
+            _ => Err(format!(
+                "Disabled routes answer with 503 or 404, not {status}"
+            )),
+        }
+    }
+
+    /// The response to a request for the disabled route.
+    pub fn response(&self, method: &str, path: &str) -> Response {
+        if self.status == 404 {
+            return Response::not_found(&format!("Not Found: {method} {path}"));
+        }
+        let mut response = Response::error(
+            503,
+            &format!("Service Unavailable: {method} {path} is disabled"),
+        );
+        if let Some(seconds) = self.retry_after {
+            response.set_header("Retry-After", &seconds.to_string());
+        }
+        response
+    }
+}
+
+/// A route being disabled or enabled.
+#[derive(Debug, Clone, Serialize)]
+pub struct RouteToggle {
+    pub method: String,
+    pub path: String,
+    /// Whether the route was enabled (or disabled)
+    pub enabled: bool,
+    /// Status answered while disabled
+    pub status: Option<u16>,
+    /// Why the route was toggled
+    pub reason: Option<String>,
+    /// Who toggled the route
+    pub actor: Option<String>,
+    /// Unix time of the toggle, in seconds
+    pub timestamp: f64,
+}
+
+impl RouteToggle {
+    /// A toggle of `method` `path` happening now; `disabled` is `None` when
+    /// the route is enabled.
+    pub fn now(
+        method: &str,
+        path: &str,
+        disabled: Option<Disabled>,
+        reason: Option<&str>,
+        actor: Option<&str>,
+    ) -> Self {
+        Self {
+            method: method.to_uppercase(),
+            path: path.to_string(),
+            enabled: disabled.is_none(),
+            status: disabled.map(|d| d.status),
+            reason: reason.map(str::to_string),
+            actor: actor.map(str::to_string),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs_f64())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Audit log of route toggles, oldest first.
+#[derive(Debug, Default)]
+pub struct ToggleLog {
+    entries: Mutex<VecDeque<RouteToggle>>,
+}
+
+impl ToggleLog {
+    /// Create an empty log.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a toggle, dropping the oldest past [`MAX_TOGGLES`].
+    pub fn record(&self, toggle: RouteToggle) {
+        match &toggle.status {
+            Some(status) => tracing::warn!(
+                "Route {} {} disabled ({status}){}",
+                toggle.method,
+                toggle.path,
+                toggle
+                    .reason
+                    .as_deref()
+                    .map(|r| format!(": {r}"))
+                    .unwrap_or_default()
+            ),
+            None => tracing::info!("Route {} {} enabled", toggle.method, toggle.path),
+        }
+        let mut entries = self.entries.lock();
+        if entries.len() == MAX_TOGGLES {
+            entries.pop_front();
+        }
+        entries.push_back(toggle);
+    }
+
+    /// The recorded toggles, oldest first.
+    pub fn entries(&self) -> Vec<RouteToggle> {
+        self.entries.lock().iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disabled_responses() {
+        assert!(Disabled::new(500, None).is_err());
+
+        let response = Disabled::new(503, Some(30))
+            .unwrap()
+            .response("GET", "/search");
+        assert_eq!(response.status, 503);
+        assert_eq!(
+            response.headers.get("Retry-After").map(String::as_str),
+            Some("30")
+        );
+
+        let response = Disabled::new(404, Some(30))
+            .unwrap()
+            .response("GET", "/search");
+        assert_eq!(response.status, 404);
+        assert!(!response.headers.contains_key("Retry-After"));
+    }
+
+    #[test]
+    fn test_toggle_log_is_bounded() {
+        let log = ToggleLog::new();
+        let disabled = Disabled::new(503, None).ok();
+        log.record(RouteToggle::now(
+            "get",
+            "/search",
+            disabled,
+            Some("timeouts"),
+            None,
+        ));
+        log.record(RouteToggle::now("GET", "/search", None, None, Some("ops")));
+
+        let entries = log.entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].method, "GET");
+        assert!(!entries[0].enabled);
+        assert_eq!(entries[0].status, Some(503));
+        assert!(entries[1].enabled);
+        assert_eq!(entries[1].actor.as_deref(), Some("ops"));
+
+        for _ in 0..MAX_TOGGLES {
+            log.record(RouteToggle::now("GET", "/other", None, None, None));
+        }
+        let entries = log.entries();
+        assert_eq!(entries.len(), MAX_TOGGLES);
+        assert!(entries.iter().all(|t| t.path == "/other"));
+    }
+}
@@ -0,0 +1,958 @@
+//! Scheduled Jobs for Cello Framework
+//!
+//! Runs jobs on cron expressions or fixed intervals alongside the server:
+//! - Five-field cron expressions (`minute hour day-of-month month
+//!   day-of-week`) and `@hourly`-style macros, evaluated in UTC
+//! - Fixed intervals
+//! - Overlap policies for runs that outlast their period
+//! - Random start jitter to spread load
+//! - Per-job metrics
+//! - Leader-only execution, so in cluster mode a job runs in one worker
+//!
+//! # Example
+//! ```python
+//! @app.schedule(cron="*/5 * * * *", overlap="skip")
+//! async def cleanup_sessions():
+//!     await sessions.purge_expired()
+//!
+//! @app.schedule(every=30, jitter=5)
+//! def refresh_rates():
+//!     rates.refresh()
+//! ```
+
+use chrono::{DateTime, Datelike, Duration as ChronoDuration, TimeZone, Timelike, Utc};
+use parking_lot::{Mutex, RwLock};
+use pyo3::prelude::*;
+use rand::Rng;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+
+// ============================================================================
+// Cron Expressions
+// ============================================================================
+
+/// A parsed five-field cron expression.
+///
+/// Fields support `*`, lists (`1,15`), ranges (`9-17`), steps (`*/5`,
+/// `0-30/10`) and, for months and weekdays, three-letter names. Sunday is
+/// `0` or `7`. As in standard cron, when both day-of-month and day-of-week
+/// are restricted a day matching either one matches.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    any_day_of_month: bool,
+    any_day_of_week: bool,
+    expression: String,
+}
+
+const MONTH_NAMES: [&str; 12] = [
+    "JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC",
+];
+const WEEKDAY_NAMES: [&str; 7] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+
+impl CronSchedule {
+    /// Parse a cron expression.
+    pub fn parse(expression: &str) -> Result<Self, SchedulerError> {
+        let expanded = match expression.trim() {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            other => other,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(SchedulerError::InvalidSchedule(format!(
+                "'{expression}': expected 5 fields, got {}",
+                fields.len()
+            )));
+        }
+        let invalid = |e: String| SchedulerError::InvalidSchedule(format!("'{expression}': {e}"));
+
+        let mut days_of_week = parse_field(fields[4], 0, 7, &WEEKDAY_NAMES, 0).map_err(invalid)?;
+        // Sunday may be written as 7.
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week & !(1 << 7)) | 1;
+        }
+        Ok(Self {
+            minutes: parse_field(fields[0], 0, 59, &[], 0).map_err(invalid)?,
+            hours: parse_field(fields[1], 0, 23, &[], 0).map_err(invalid)?,
+            days_of_month: parse_field(fields[2], 1, 31, &[], 0).map_err(invalid)?,
+            months: parse_field(fields[3], 1, 12, &MONTH_NAMES, 1).map_err(invalid)?,
+            days_of_week,
+            any_day_of_month: fields[2] == "*",
+            any_day_of_week: fields[4] == "*",
+            expression: expression.trim().to_string(),
+        })
+    }
+
+    /// The expression this schedule was parsed from.
+    pub fn expression(&self) -> &str {
+        &self.expression
+    }
+
+    /// The first matching minute strictly after `after`, or `None` if the
+    /// expression never matches (e.g. `0 0 31 2 *`).
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut t = after.with_second(0)?.with_nanosecond(0)? + ChronoDuration::minutes(1);
+        let limit = after.year() + 5;
+        while t.year() <= limit {
+            if !bit(self.months, t.month()) {
+                let (year, month) = if t.month() == 12 {
+                    (t.year() + 1, 1)
+                } else {
+                    (t.year(), t.month() + 1)
+                };
+                t = Utc.with_ymd_and_hms(year, month, 1, 0, 0, 0).single()?;
+            } else if !self.matches_day(&t) {
+                t = (t + ChronoDuration::days(1)).with_hour(0)?.with_minute(0)?;
+            } else if !bit(self.hours, t.hour()) {
+                t = (t + ChronoDuration::hours(1)).with_minute(0)?;
+            } else if !bit(self.minutes, t.minute()) {
+                t += ChronoDuration::minutes(1);
+            } else {
+                return Some(t);
+            }
+        }
+        None
+    }
+
+    fn matches_day(&self, t: &DateTime<Utc>) -> bool {
+        let dom = bit(self.days_of_month, t.day());
+        let dow = bit(self.days_of_week, t.weekday().num_days_from_sunday());
+        match (self.any_day_of_month, self.any_day_of_week) {
+            (false, false) => dom || dow,
+            _ => dom && dow,
+        }
+    }
+}
+
+fn bit(set: u64, value: u32) -> bool {
+    set & (1 << value) != 0
+}
+
+/// Parse one cron field into a bit set of allowed values. `names[i]` is an
+/// alias for `first_name + i`.
+fn parse_field(
+    field: &str,
+    min: u32,
+    max: u32,
+    names: &[&str],
+    first_name: u32,
+) -> Result<u64, String> {
+    let value = |s: &str| -> Result<u32, String> {
+        let upper = s.to_ascii_uppercase();
+        let v = match names.iter().position(|n| *n == upper) {
+            Some(i) => i as u32 + first_name,
+            None => s.parse().map_err(|_| format!("invalid value '{s}'"))?,
+        };
+        if v < min || v > max {
+            return Err(format!("value {v} out of range {min}-{max}"));
+        }
+        Ok(v)
+    };
+
+    let mut set = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step.parse().map_err(|_| format!("invalid step '{step}'"))?;
+                if step == 0 {
+                    return Err("step must be positive".to_string());
+                }
+                (range, step)
+            }
+            None => (part, 1),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (value(start)?, value(end)?)
+        } else {
+            let start = value(range)?;
+            // `5/15` means every 15 starting at 5.
+            (start, if step > 1 { max } else { start })
+        };
+        if start > end {
+            return Err(format!("invalid range '{range}'"));
+        }
+        for v in (start..=end).step_by(step as usize) {
+            set |= 1 << v;
+        }
+    }
+    Ok(set)
+}
+
+// ============================================================================
+// Job Definitions
+// ============================================================================
+
+/// When a job runs.
+#[derive(Debug, Clone)]
+pub enum Schedule {
+    /// On every minute matching a cron expression.
+    Cron(CronSchedule),
+    /// Every fixed interval, starting one interval after the scheduler
+    /// starts.
+    Interval(Duration),
+}
+
+impl Schedule {
+    /// Parse a cron schedule.
+    pub fn cron(expression: &str) -> Result<Self, SchedulerError> {
+        CronSchedule::parse(expression).map(Schedule::Cron)
+    }
+
+    /// Create a fixed-interval schedule.
+    pub fn every(interval: Duration) -> Result<Self, SchedulerError> {
+        if interval.is_zero() {
+            return Err(SchedulerError::InvalidSchedule(
+                "interval must be positive".to_string(),
+            ));
+        }
+        Ok(Schedule::Interval(interval))
+    }
+
+    /// The next run strictly after `after`.
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            Schedule::Cron(cron) => cron.next_after(after),
+            Schedule::Interval(interval) => Some(after + ChronoDuration::from_std(*interval).ok()?),
+        }
+    }
+
+    /// Human-readable description, e.g. `*/5 * * * *` or `every 30s`.
+    pub fn describe(&self) -> String {
+        match self {
+            Schedule::Cron(cron) => cron.expression().to_string(),
+            Schedule::Interval(interval) => format!("every {}s", interval.as_secs_f64()),
+        }
+    }
+}
+
+/// What to do when a job is due while its previous run is still going.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OverlapPolicy {
+    /// Skip the new run.
+    #[default]
+    Skip,
+    /// Start the new run once the previous ones have finished.
+    Queue,
+    /// Start the new run alongside the previous one.
+    Parallel,
+}
+
+impl std::str::FromStr for OverlapPolicy {
+    type Err = SchedulerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "skip" => Ok(OverlapPolicy::Skip),
+            "queue" => Ok(OverlapPolicy::Queue),
+            "parallel" => Ok(OverlapPolicy::Parallel),
+            other => Err(SchedulerError::InvalidSchedule(format!(
+                "unknown overlap policy '{other}' (expected skip, queue or parallel)"
+            ))),
+        }
+    }
+}
+
+/// Future returned by a job handler.
+pub type JobFuture = Pin<Box<dyn Future<Output = Result<(), String>> + Send>>;
+
+/// The work a job does on each run.
+#[derive(Clone)]
+pub struct JobHandler(Arc<dyn Fn() -> JobFuture + Send + Sync>);
+
+impl JobHandler {
+    /// Create a handler from an async Rust closure.
+    pub fn new<F, Fut>(handler: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), String>> + Send + 'static,
+    {
+        Self(Arc::new(move || Box::pin(handler())))
+    }
+
+    /// Create a handler from a sync or async Python callable taking no
+    /// arguments. A raised exception fails the run.
+    pub fn python(handler: PyObject) -> Self {
+        let handler = Arc::new(handler);
+        Self::new(move || {
+            let handler = handler.clone();
+            async move {
+                let (result, is_coro) = Python::with_gil(|py| -> PyResult<(PyObject, bool)> {
+                    let ret = handler.call0(py)?;
+                    let is_coro = py
+                        .import("inspect")?
+                        .call_method1("iscoroutine", (ret.as_ref(py),))?
+                        .is_true()?;
+                    Ok((ret, is_coro))
+                })
+                .map_err(|e| e.to_string())?;
+                if is_coro {
+                    Python::with_gil(|py| crate::event_loop::into_future(result.as_ref(py)))
+                        .map_err(|e| e.to_string())?
+                        .await
+                        .map_err(|e| e.to_string())?;
+                }
+                Ok(())
+            }
+        })
+    }
+
+    /// Invoke the handler.
+    pub fn call(&self) -> JobFuture {
+        (self.0)()
+    }
+}
+
+impl std::fmt::Debug for JobHandler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("JobHandler")
+    }
+}
+
+/// A named job and when and how it runs.
+#[derive(Debug, Clone)]
+pub struct Job {
+    /// Unique job name.
+    pub name: String,
+    /// When the job runs.
+    pub schedule: Schedule,
+    /// The work done on each run.
+    pub handler: JobHandler,
+    /// What to do when a run is due while the previous one is going.
+    pub overlap: OverlapPolicy,
+    /// Upper bound of the random delay added to each run.
+    pub jitter: Duration,
+}
+
+impl Job {
+    /// Create a job with the skip overlap policy and no jitter.
+    pub fn new(name: &str, schedule: Schedule, handler: JobHandler) -> Self {
+        Self {
+            name: name.to_string(),
+            schedule,
+            handler,
+            overlap: OverlapPolicy::default(),
+            jitter: Duration::ZERO,
+        }
+    }
+
+    /// Set the overlap policy.
+    pub fn with_overlap(mut self, overlap: OverlapPolicy) -> Self {
+        self.overlap = overlap;
+        self
+    }
+
+    /// Delay each run by a random duration up to `jitter`.
+    pub fn with_jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+}
+
+/// Metrics for one job.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct JobStats {
+    /// Job name.
+    pub name: String,
+    /// Schedule description.
+    pub schedule: String,
+    /// Overlap policy.
+    pub overlap: OverlapPolicy,
+    /// Runs started.
+    pub runs: u64,
+    /// Runs that returned an error.
+    pub failures: u64,
+    /// Runs skipped because the previous run was still going.
+    pub skipped: u64,
+    /// Runs currently in progress, including queued ones.
+    pub running: u64,
+    /// Unix timestamp (seconds) the last run started.
+    pub last_run: Option<u64>,
+    /// Duration of the last finished run in milliseconds.
+    pub last_duration_ms: Option<u64>,
+    /// Error of the last run, if it failed.
+    pub last_error: Option<String>,
+    /// Unix timestamp (seconds) of the next scheduled run.
+    pub next_run: Option<u64>,
+}
+
+/// A registered job with its run state.
+struct JobEntry {
+    job: Job,
+    stats: Mutex<JobStats>,
+    /// Serializes runs under [`OverlapPolicy::Queue`].
+    queue: tokio::sync::Mutex<()>,
+    /// Cancelled when the job is unregistered.
+    removed: CancellationToken,
+}
+
+impl JobEntry {
+    /// Run the job once according to its overlap policy, returning `false`
+    /// if the run was skipped.
+    async fn run(self: Arc<Self>) -> bool {
+        {
+            let mut stats = self.stats.lock();
+            if self.job.overlap == OverlapPolicy::Skip && stats.running > 0 {
+                stats.skipped += 1;
+                return false;
+            }
+            stats.running += 1;
+        }
+        let _queued = match self.job.overlap {
+            OverlapPolicy::Queue => Some(self.queue.lock().await),
+            _ => None,
+        };
+
+        {
+            let mut stats = self.stats.lock();
+            stats.runs += 1;
+            stats.last_run = Some(Utc::now().timestamp() as u64);
+        }
+        let started = Instant::now();
+        let result = self.job.handler.call().await;
+
+        let mut stats = self.stats.lock();
+        stats.running -= 1;
+        stats.last_duration_ms = Some(started.elapsed().as_millis() as u64);
+        match result {
+            Ok(()) => stats.last_error = None,
+            Err(e) => {
+                tracing::warn!("Scheduled job '{}' failed: {e}", self.job.name);
+                stats.failures += 1;
+                stats.last_error = Some(e);
+            }
+        }
+        true
+    }
+}
+
+// ============================================================================
+// Scheduler
+// ============================================================================
+
+/// Runs registered jobs on their schedules.
+///
+/// Jobs can be added at any time but only run between [`Scheduler::start`]
+/// and [`Scheduler::stop`], and only while this process is the leader.
+/// In cluster mode every worker but one should call
+/// [`Scheduler::set_leader`] with `false` so each job runs once.
+pub struct Scheduler {
+    jobs: RwLock<HashMap<String, Arc<JobEntry>>>,
+    leader: AtomicBool,
+    running: Mutex<Option<CancellationToken>>,
+    tracker: TaskTracker,
+}
+
+impl Scheduler {
+    /// Create an empty scheduler that is the leader.
+    pub fn new() -> Self {
+        Self {
+            jobs: RwLock::new(HashMap::new()),
+            leader: AtomicBool::new(true),
+            running: Mutex::new(None),
+            tracker: TaskTracker::new(),
+        }
+    }
+
+    /// Register a job. Jobs added while the scheduler runs start right away.
+    pub fn add_job(&self, job: Job) -> Result<(), SchedulerError> {
+        let mut jobs = self.jobs.write();
+        if jobs.contains_key(&job.name) {
+            return Err(SchedulerError::DuplicateJob(job.name));
+        }
+        let entry = Arc::new(JobEntry {
+            stats: Mutex::new(JobStats {
+                name: job.name.clone(),
+                schedule: job.schedule.describe(),
+                overlap: job.overlap,
+                ..Default::default()
+            }),
+            job,
+            queue: tokio::sync::Mutex::new(()),
+            removed: CancellationToken::new(),
+        });
+        if let Some(token) = self.running.lock().as_ref() {
+            self.spawn_loop(entry.clone(), token.clone());
+        }
+        jobs.insert(entry.job.name.clone(), entry);
+        Ok(())
+    }
+
+    /// Unregister a job, returning whether it existed. Runs in progress
+    /// finish.
+    pub fn remove_job(&self, name: &str) -> bool {
+        match self.jobs.write().remove(name) {
+            Some(entry) => {
+                entry.removed.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Whether this process runs jobs.
+    pub fn is_leader(&self) -> bool {
+        self.leader.load(Ordering::Relaxed)
+    }
+
+    /// Set whether this process runs jobs. Takes effect on the next start.
+    pub fn set_leader(&self, leader: bool) {
+        self.leader.store(leader, Ordering::Relaxed);
+    }
+
+    /// Whether the scheduler has been started and not stopped.
+    pub fn is_running(&self) -> bool {
+        self.running.lock().is_some()
+    }
+
+    /// Start running jobs on their schedules. Must be called within a Tokio
+    /// runtime; does nothing if already running or not the leader.
+    pub fn start(&self) {
+        if !self.is_leader() {
+            return;
+        }
+        let mut running = self.running.lock();
+        if running.is_some() {
+            return;
+        }
+        let token = CancellationToken::new();
+        self.tracker.reopen();
+        for entry in self.jobs.read().values() {
+            self.spawn_loop(entry.clone(), token.clone());
+        }
+        *running = Some(token);
+    }
+
+    /// Stop scheduling runs and wait for runs in progress to finish.
+    pub async fn stop(&self) {
+        let Some(token) = self.running.lock().take() else {
+            return;
+        };
+        token.cancel();
+        self.tracker.close();
+        self.tracker.wait().await;
+    }
+
+    /// Run a job now, outside its schedule, and wait for it to finish.
+    ///
+    /// The overlap policy applies; returns `Ok(false)` if the run was
+    /// skipped.
+    pub async fn trigger(&self, name: &str) -> Result<bool, SchedulerError> {
+        let entry = self
+            .jobs
+            .read()
+            .get(name)
+            .cloned()
+            .ok_or_else(|| SchedulerError::JobNotFound(name.to_string()))?;
+        Ok(entry.run().await)
+    }
+
+    /// Metrics for one job.
+    pub fn job_stats(&self, name: &str) -> Option<JobStats> {
+        self.jobs.read().get(name).map(|e| e.stats.lock().clone())
+    }
+
+    /// Metrics for all jobs, sorted by name.
+    pub fn stats(&self) -> Vec<JobStats> {
+        let mut stats: Vec<JobStats> = self
+            .jobs
+            .read()
+            .values()
+            .map(|e| e.stats.lock().clone())
+            .collect();
+        stats.sort_by(|a, b| a.name.cmp(&b.name));
+        stats
+    }
+
+    /// Number of registered jobs.
+    pub fn job_count(&self) -> usize {
+        self.jobs.read().len()
+    }
+
+    fn spawn_loop(&self, entry: Arc<JobEntry>, token: CancellationToken) {
+        let tracker = self.tracker.clone();
+        self.tracker.spawn(async move {
+            let schedule = &entry.job.schedule;
+            let mut previous = Utc::now();
+            loop {
+                // Keep a steady cadence unaffected by jitter, unless runs were
+                // missed (e.g. the host was suspended).
+                let next = schedule
+                    .next_after(previous)
+                    .filter(|next| *next > Utc::now())
+                    .or_else(|| schedule.next_after(Utc::now()));
+                let Some(next) = next else {
+                    tracing::warn!("Scheduled job '{}' will never run again", entry.job.name);
+                    entry.stats.lock().next_run = None;
+                    return;
+                };
+                previous = next;
+                entry.stats.lock().next_run = Some(next.timestamp() as u64);
+
+                let jitter = match entry.job.jitter.as_millis() as u64 {
+                    0 => Duration::ZERO,
+                    max => Duration::from_millis(rand::thread_rng().gen_range(0..=max)),
+                };
+                let delay = (next - Utc::now()).to_std().unwrap_or_default() + jitter;
+                tokio::select! {
+                    _ = token.cancelled() => return,
+                    _ = entry.removed.cancelled() => return,
+                    _ = tokio::time::sleep(delay) => {}
+                }
+                tracker.spawn(entry.clone().run());
+            }
+        });
+    }
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ============================================================================
+// Errors
+// ============================================================================
+
+/// Scheduler errors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchedulerError {
+    /// The cron expression, interval or overlap policy is invalid.
+    InvalidSchedule(String),
+    /// A job with this name is already registered.
+    DuplicateJob(String),
+    /// No job with this name is registered.
+    JobNotFound(String),
+}
+
+impl std::fmt::Display for SchedulerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SchedulerError::InvalidSchedule(msg) => write!(f, "Invalid schedule: {msg}"),
+            SchedulerError::DuplicateJob(name) => write!(f, "Job already registered: {name}"),
+            SchedulerError::JobNotFound(name) => write!(f, "Job not found: {name}"),
+        }
+    }
+}
+
+impl std::error::Error for SchedulerError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicU64;
+
+    fn at(y: i32, mo: u32, d: u32, h: u32, mi: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, mo, d, h, mi, 0).unwrap()
+    }
+
+    fn counting_job(name: &str, schedule: Schedule, counter: Arc<AtomicU64>) -> Job {
+        Job::new(
+            name,
+            schedule,
+            JobHandler::new(move || {
+                let counter = counter.clone();
+                async move {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    Ok(())
+                }
+            }),
+        )
+    }
+
+    fn sleeping_job(name: &str, overlap: OverlapPolicy) -> Job {
+        Job::new(
+            name,
+            Schedule::every(Duration::from_secs(3600)).unwrap(),
+            JobHandler::new(|| async {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                Ok(())
+            }),
+        )
+        .with_overlap(overlap)
+    }
+
+    // ---------- Cron Tests ----------
+
+    #[test]
+    fn test_cron_every_five_minutes() {
+        let cron = CronSchedule::parse("*/5 * * * *").unwrap();
+        assert_eq!(
+            cron.next_after(at(2024, 1, 1, 10, 2)),
+            Some(at(2024, 1, 1, 10, 5))
+        );
+        assert_eq!(
+            cron.next_after(at(2024, 1, 1, 10, 55)),
+            Some(at(2024, 1, 1, 11, 0))
+        );
+    }
+
+    #[test]
+    fn test_cron_is_strictly_after() {
+        let cron = CronSchedule::parse("30 9 * * *").unwrap();
+        assert_eq!(
+            cron.next_after(at(2024, 1, 1, 9, 30)),
+            Some(at(2024, 1, 2, 9, 30))
+        );
+    }
+
+    #[test]
+    fn test_cron_ranges_lists_and_names() {
+        // Weekdays at 9:00 and 17:00; 2024-01-06 is a Saturday.
+        let cron = CronSchedule::parse("0 9,17 * * MON-FRI").unwrap();
+        assert_eq!(
+            cron.next_after(at(2024, 1, 5, 18, 0)),
+            Some(at(2024, 1, 8, 9, 0))
+        );
+
+        let cron = CronSchedule::parse("0 0 1 jan,jul *").unwrap();
+        assert_eq!(
+            cron.next_after(at(2024, 2, 1, 0, 0)),
+            Some(at(2024, 7, 1, 0, 0))
+        );
+    }
+
+    #[test]
+    fn test_cron_day_of_month_or_day_of_week() {
+        // The 15th or any Sunday (written as 7).
+        let cron = CronSchedule::parse("0 0 15 * 7").unwrap();
+        assert_eq!(
+            cron.next_after(at(2024, 1, 1, 0, 0)),
+            Some(at(2024, 1, 7, 0, 0))
+        );
+        assert_eq!(
+            cron.next_after(at(2024, 1, 14, 0, 0)),
+            Some(at(2024, 1, 15, 0, 0))
+        );
+    }
+
+    #[test]
+    fn test_cron_macros_and_impossible_dates() {
+        let cron = CronSchedule::parse("@daily").unwrap();
+        assert_eq!(
+            cron.next_after(at(2024, 2, 28, 12, 0)),
+            Some(at(2024, 2, 29, 0, 0))
+        );
+        assert_eq!(cron.expression(), "@daily");
+
+        let never = CronSchedule::parse("0 0 31 2 *").unwrap();
+        assert_eq!(never.next_after(at(2024, 1, 1, 0, 0)), None);
+    }
+
+    #[test]
+    fn test_cron_invalid() {
+        for expression in [
+            "* * * *",
+            "60 * * * *",
+            "*/0 * * * *",
+            "5-1 * * * *",
+            "x * * * *",
+        ] {
+            assert!(
+                matches!(
+                    CronSchedule::parse(expression),
+                    Err(SchedulerError::InvalidSchedule(_))
+                ),
+                "{expression}"
+            );
+        }
+    }
+
+    // ---------- Schedule Tests ----------
+
+    #[test]
+    fn test_interval_schedule() {
+        let schedule = Schedule::every(Duration::from_secs(90)).unwrap();
+        assert_eq!(
+            schedule.next_after(at(2024, 1, 1, 0, 0)),
+            Some(Utc.with_ymd_and_hms(2024, 1, 1, 0, 1, 30).unwrap())
+        );
+        assert_eq!(schedule.describe(), "every 90s");
+        assert!(Schedule::every(Duration::ZERO).is_err());
+    }
+
+    #[test]
+    fn test_overlap_policy_from_str() {
+        assert_eq!("skip".parse::<OverlapPolicy>(), Ok(OverlapPolicy::Skip));
+        assert_eq!("queue".parse::<OverlapPolicy>(), Ok(OverlapPolicy::Queue));
+        assert_eq!(
+            "parallel".parse::<OverlapPolicy>(),
+            Ok(OverlapPolicy::Parallel)
+        );
+        assert!("sometimes".parse::<OverlapPolicy>().is_err());
+    }
+
+    // ---------- Scheduler Tests ----------
+
+    #[tokio::test]
+    async fn test_scheduler_runs_interval_job_until_stopped() {
+        let counter = Arc::new(AtomicU64::new(0));
+        let scheduler = Scheduler::new();
+        scheduler
+            .add_job(counting_job(
+                "tick",
+                Schedule::every(Duration::from_millis(20)).unwrap(),
+                counter.clone(),
+            ))
+            .unwrap();
+
+        scheduler.start();
+        assert!(scheduler.is_running());
+        tokio::time::sleep(Duration::from_millis(110)).await;
+        scheduler.stop().await;
+        assert!(!scheduler.is_running());
+
+        let runs = counter.load(Ordering::SeqCst);
+        assert!(runs >= 3, "ran {runs} times");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(counter.load(Ordering::SeqCst), runs);
+
+        let stats = scheduler.job_stats("tick").unwrap();
+        assert_eq!(stats.runs, runs);
+        assert_eq!(stats.failures, 0);
+        assert!(stats.last_run.is_some());
+        assert!(stats.next_run.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_scheduler_follower_does_not_run_jobs() {
+        let counter = Arc::new(AtomicU64::new(0));
+        let scheduler = Scheduler::new();
+        scheduler.set_leader(false);
+        scheduler
+            .add_job(counting_job(
+                "tick",
+                Schedule::every(Duration::from_millis(10)).unwrap(),
+                counter.clone(),
+            ))
+            .unwrap();
+
+        scheduler.start();
+        assert!(!scheduler.is_running());
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        assert_eq!(counter.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_scheduler_removed_job_stops() {
+        let counter = Arc::new(AtomicU64::new(0));
+        let scheduler = Scheduler::new();
+        scheduler.start();
+        // Added while running.
+        scheduler
+            .add_job(counting_job(
+                "tick",
+                Schedule::every(Duration::from_millis(10)).unwrap(),
+                counter.clone(),
+            ))
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(35)).await;
+        assert!(scheduler.remove_job("tick"));
+        assert!(!scheduler.remove_job("tick"));
+        tokio::time::sleep(Duration::from_millis(15)).await;
+        let runs = counter.load(Ordering::SeqCst);
+        assert!(runs >= 1);
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        assert_eq!(counter.load(Ordering::SeqCst), runs);
+        scheduler.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_overlap_skip() {
+        let scheduler = Arc::new(Scheduler::new());
+        scheduler
+            .add_job(sleeping_job("slow", OverlapPolicy::Skip))
+            .unwrap();
+
+        let (first, second) = tokio::join!(scheduler.trigger("slow"), async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            scheduler.trigger("slow").await
+        });
+        assert_eq!(first, Ok(true));
+        assert_eq!(second, Ok(false));
+
+        let stats = scheduler.job_stats("slow").unwrap();
+        assert_eq!(stats.runs, 1);
+        assert_eq!(stats.skipped, 1);
+        assert_eq!(stats.running, 0);
+    }
+
+    #[tokio::test]
+    async fn test_overlap_queue_and_parallel() {
+        let scheduler = Arc::new(Scheduler::new());
+        scheduler
+            .add_job(sleeping_job("queued", OverlapPolicy::Queue))
+            .unwrap();
+        scheduler
+            .add_job(sleeping_job("parallel", OverlapPolicy::Parallel))
+            .unwrap();
+
+        let started = Instant::now();
+        let (a, b) = tokio::join!(scheduler.trigger("queued"), scheduler.trigger("queued"));
+        assert_eq!((a, b), (Ok(true), Ok(true)));
+        assert!(started.elapsed() >= Duration::from_millis(100));
+
+        let started = Instant::now();
+        let (a, b) = tokio::join!(scheduler.trigger("parallel"), scheduler.trigger("parallel"));
+        assert_eq!((a, b), (Ok(true), Ok(true)));
+        assert!(started.elapsed() < Duration::from_millis(100));
+
+        assert_eq!(scheduler.job_stats("queued").unwrap().runs, 2);
+        assert_eq!(scheduler.job_stats("parallel").unwrap().runs, 2);
+    }
+
+    #[tokio::test]
+    async fn test_failed_run_is_recorded() {
+        let scheduler = Scheduler::new();
+        scheduler
+            .add_job(Job::new(
+                "broken",
+                Schedule::cron("@hourly").unwrap(),
+                JobHandler::new(|| async { Err("boom".to_string()) }),
+            ))
+            .unwrap();
+
+        assert_eq!(scheduler.trigger("broken").await, Ok(true));
+        let stats = scheduler.job_stats("broken").unwrap();
+        assert_eq!(stats.failures, 1);
+        assert_eq!(stats.last_error.as_deref(), Some("boom"));
+        assert_eq!(stats.schedule, "@hourly");
+    }
+
+    #[test]
+    fn test_duplicate_and_unknown_jobs() {
+        let scheduler = Scheduler::new();
+        let job = || counting_job("a", Schedule::cron("@daily").unwrap(), Arc::default());
+        scheduler.add_job(job()).unwrap();
+        assert_eq!(
+            scheduler.add_job(job()),
+            Err(SchedulerError::DuplicateJob("a".to_string()))
+        );
+        assert_eq!(scheduler.job_count(), 1);
+        assert_eq!(scheduler.stats()[0].name, "a");
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        assert_eq!(
+            rt.block_on(scheduler.trigger("missing")),
+            Err(SchedulerError::JobNotFound("missing".to_string()))
+        );
+    }
+}
@@ -0,0 +1,1416 @@
+//! Automatic certificates from an ACME CA such as Let's Encrypt (RFC 8555).
+//!
+//! The [`AcmeManager`] registers an account, orders a certificate for the
+//! configured domains, proves control of them with an HTTP-01 or
+//! TLS-ALPN-01 challenge and installs the issued certificate in the
+//! server's [`CertStore`], where the next handshake picks it up. It then
+//! checks the certificate periodically and renews it ahead of expiry.
+//!
+//! Keys and certificates are cached on disk, so restarts reuse the
+//! current certificate instead of ordering a new one:
+//!
+//! ```text
+//! <cache_dir>/account.key           account key (PKCS#8 PEM)
+//! <cache_dir>/<domain>/cert.pem     certificate chain
+//! <cache_dir>/<domain>/key.pem      certificate key
+//! <cache_dir>/<domain>/domains      domains the certificate covers
+//! ```
+//!
+//! HTTP-01 challenges are answered by a plain HTTP listener on port 80
+//! (see [`AcmeManager::serve_http_challenges`]); TLS-ALPN-01 challenges by
+//! the TLS listener itself, on port 443.
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use bytes::Bytes;
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use http_body_util::Full;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper_util::rt::TokioIo;
+use parking_lot::RwLock;
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::signature::{
+    EcdsaKeyPair, KeyPair as _, ECDSA_P256_SHA256_ASN1_SIGNING, ECDSA_P256_SHA256_FIXED_SIGNING,
+};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use serde::Deserialize;
+use serde_json::{json, Value as JsonValue};
+use sha2::{Digest, Sha256};
+use tokio::net::TcpListener;
+use tokio_util::sync::CancellationToken;
+
+use super::tls::{self, CertStore};
+
+/// Let's Encrypt production directory.
+pub const LETS_ENCRYPT: &str = "https://acme-v02.api.letsencrypt.org/directory";
+
+/// Let's Encrypt staging directory, for testing without production rate limits.
+pub const LETS_ENCRYPT_STAGING: &str = "https://acme-staging-v02.api.letsencrypt.org/directory";
+
+/// Path prefix of HTTP-01 challenge requests.
+pub const CHALLENGE_PATH: &str = "/.well-known/acme-challenge/";
+
+// ============================================================================
+// Configuration
+// ============================================================================
+
+/// How control of a domain is proven to the CA.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChallengeType {
+    /// Serve a token over plain HTTP on port 80
+    Http01,
+    /// Present a validation certificate over TLS on port 443
+    TlsAlpn01,
+}
+
+impl ChallengeType {
+    /// Parse `"http-01"` or `"tls-alpn-01"`.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "http-01" => Some(ChallengeType::Http01),
+            "tls-alpn-01" => Some(ChallengeType::TlsAlpn01),
+            _ => None,
+        }
+    }
+
+    /// The challenge type as named by ACME.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChallengeType::Http01 => "http-01",
+            ChallengeType::TlsAlpn01 => "tls-alpn-01",
+        }
+    }
+}
+
+/// ACME certificate configuration.
+#[derive(Debug, Clone)]
+pub struct AcmeConfig {
+    /// Domains the certificate covers; the first names its cache directory
+    pub domains: Vec<String>,
+    /// Account contact URLs, e.g. `mailto:admin@example.com`
+    pub contact: Vec<String>,
+    /// ACME directory URL
+    pub directory_url: String,
+    /// Challenge used to prove control of the domains
+    pub challenge: ChallengeType,
+    /// Directory holding the account key and issued certificates
+    pub cache_dir: PathBuf,
+    /// Renew certificates expiring within this window
+    pub renew_before: Duration,
+    /// How often the certificate's expiry is checked
+    pub check_interval: Duration,
+    /// Port of the HTTP-01 challenge listener
+    pub http_port: u16,
+    /// Delay between polls of pending authorizations and orders
+    pub poll_interval: Duration,
+}
+
+impl AcmeConfig {
+    /// Create a config for `domains` using Let's Encrypt and HTTP-01.
+    pub fn new(domains: Vec<String>) -> Self {
+        Self {
+            domains,
+            contact: Vec::new(),
+            directory_url: LETS_ENCRYPT.to_string(),
+            challenge: ChallengeType::Http01,
+            cache_dir: PathBuf::from(".cello/acme"),
+            renew_before: Duration::from_secs(30 * 86400),
+            check_interval: Duration::from_secs(12 * 3600),
+            http_port: 80,
+            poll_interval: Duration::from_secs(2),
+        }
+    }
+
+    /// Set the account contact email.
+    pub fn email(mut self, email: &str) -> Self {
+        self.contact = vec![format!("mailto:{email}")];
+        self
+    }
+
+    /// Use another ACME directory.
+    pub fn directory(mut self, url: &str) -> Self {
+        self.directory_url = url.to_string();
+        self
+    }
+
+    /// Set the challenge type.
+    pub fn challenge(mut self, challenge: ChallengeType) -> Self {
+        self.challenge = challenge;
+        self
+    }
+
+    /// Set the cache directory.
+    pub fn cache_dir(mut self, path: impl Into<PathBuf>) -> Self {
+        self.cache_dir = path.into();
+        self
+    }
+
+    /// Renew certificates expiring within `window`.
+    pub fn renew_before(mut self, window: Duration) -> Self {
+        self.renew_before = window;
+        self
+    }
+
+    /// Set the port of the HTTP-01 challenge listener.
+    pub fn http_port(mut self, port: u16) -> Self {
+        self.http_port = port;
+        self
+    }
+
+    /// Check that the config can be used to order certificates.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.domains.is_empty() {
+            return Err("At least one domain is required".to_string());
+        }
+        for domain in &self.domains {
+            let valid = !domain.is_empty()
+                && domain.split('.').all(|label| {
+                    !label.is_empty()
+                        && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+                });
+            if !valid {
+                return Err(format!("Invalid domain: {domain:?}"));
+            }
+        }
+        if !self.directory_url.starts_with("https://") && !self.directory_url.starts_with("http://")
+        {
+            return Err(format!(
+                "Invalid ACME directory URL: {}",
+                self.directory_url
+            ));
+        }
+        Ok(())
+    }
+}
+
+// ============================================================================
+// Errors
+// ============================================================================
+
+/// ACME errors.
+#[derive(Debug)]
+pub enum AcmeError {
+    /// The CA could not be reached.
+    Http(String),
+    /// The CA rejected a request or answered unexpectedly.
+    Protocol(String),
+    /// The CA could not validate control of a domain.
+    Challenge { domain: String, detail: String },
+    /// Keys or certificates could not be read or written.
+    Cache(String),
+    /// A key or certificate request could not be created.
+    Crypto(String),
+}
+
+impl std::fmt::Display for AcmeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AcmeError::Http(msg) => write!(f, "ACME request failed: {msg}"),
+            AcmeError::Protocol(msg) => write!(f, "ACME error: {msg}"),
+            AcmeError::Challenge { domain, detail } => {
+                write!(f, "Validation of {domain} failed: {detail}")
+            }
+            AcmeError::Cache(msg) => write!(f, "ACME cache error: {msg}"),
+            AcmeError::Crypto(msg) => write!(f, "ACME key error: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for AcmeError {}
+
+// ============================================================================
+// Manager
+// ============================================================================
+
+/// Obtains and renews the certificate of the configured domains.
+pub struct AcmeManager {
+    config: AcmeConfig,
+    store: Arc<CertStore>,
+    client: reqwest::Client,
+    /// Key authorizations of pending HTTP-01 challenges, by token
+    http_tokens: RwLock<HashMap<String, String>>,
+    /// Expiry of the installed certificate
+    not_after: RwLock<Option<DateTime<Utc>>>,
+}
+
+impl AcmeManager {
+    /// Create a manager installing certificates in `store`.
+    pub fn new(config: AcmeConfig, store: Arc<CertStore>) -> Self {
+        Self {
+            config,
+            store,
+            client: reqwest::Client::new(),
+            http_tokens: RwLock::new(HashMap::new()),
+            not_after: RwLock::new(None),
+        }
+    }
+
+    /// The manager's config.
+    pub fn config(&self) -> &AcmeConfig {
+        &self.config
+    }
+
+    /// Expiry of the installed certificate.
+    pub fn not_after(&self) -> Option<DateTime<Utc>> {
+        *self.not_after.read()
+    }
+
+    /// Whether there is no certificate, or it expires within the renewal window.
+    pub fn needs_renewal(&self) -> bool {
+        match self.not_after() {
+            Some(not_after) => {
+                let window = chrono::Duration::from_std(self.config.renew_before)
+                    .unwrap_or(chrono::Duration::MAX);
+                not_after - Utc::now() <= window
+            }
+            None => true,
+        }
+    }
+
+    /// Key authorization of a pending HTTP-01 challenge.
+    ///
+    /// Challenges are also written to the cache, so other worker processes
+    /// sharing it can answer them.
+    pub fn http_challenge(&self, token: &str) -> Option<String> {
+        if let Some(key_authorization) = self.http_tokens.read().get(token) {
+            return Some(key_authorization.clone());
+        }
+        let valid = !token.is_empty()
+            && token
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return None;
+        }
+        std::fs::read_to_string(self.config.cache_dir.join("challenges").join(token)).ok()
+    }
+
+    /// The response to an HTTP-01 challenge request for `token`.
+    pub fn challenge_response(&self, token: &str) -> hyper::Response<Full<Bytes>> {
+        let response = match self.http_challenge(token) {
+            Some(key_authorization) => hyper::Response::builder()
+                .header("content-type", "application/octet-stream")
+                .body(Full::new(Bytes::from(key_authorization))),
+            None => hyper::Response::builder()
+                .status(404)
+                .body(Full::new(Bytes::new())),
+        };
+        response.expect("valid response")
+    }
+
+    fn domain_dir(&self) -> PathBuf {
+        self.config.cache_dir.join(&self.config.domains[0])
+    }
+
+    /// Install the cached certificate if it covers the configured domains
+    /// and is newer than the installed one. Returns whether it was installed.
+    pub fn load_cached(&self) -> Result<bool, AcmeError> {
+        let dir = self.domain_dir();
+        let domains = match std::fs::read_to_string(dir.join("domains")) {
+            Ok(domains) => domains,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(AcmeError::Cache(e.to_string())),
+        };
+        if domains.lines().collect::<Vec<_>>() != self.config.domains {
+            return Ok(false);
+        }
+        let read = |name: &str| {
+            std::fs::read(dir.join(name))
+                .map_err(|e| AcmeError::Cache(format!("{}: {e}", dir.join(name).display())))
+        };
+        let cert_pem = read("cert.pem")?;
+        let not_after = leaf_not_after(&cert_pem)
+            .ok_or_else(|| AcmeError::Cache("Cached certificate has no expiry".to_string()))?;
+        if self.not_after().is_some_and(|current| current >= not_after) {
+            return Ok(false);
+        }
+        self.install(&cert_pem, &read("key.pem")?, not_after)?;
+        Ok(true)
+    }
+
+    fn install(
+        &self,
+        cert_pem: &[u8],
+        key_pem: &[u8],
+        not_after: DateTime<Utc>,
+    ) -> Result<(), AcmeError> {
+        let key = tls::certified_key(cert_pem, key_pem).map_err(AcmeError::Cache)?;
+        self.store.set_certificate(&self.config.domains, key);
+        *self.not_after.write() = Some(not_after);
+        Ok(())
+    }
+
+    fn account_key(&self) -> Result<KeyPair, AcmeError> {
+        let path = self.config.cache_dir.join("account.key");
+        match std::fs::read(&path) {
+            Ok(pem) => KeyPair::from_pem(&pem),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let key = KeyPair::generate()?;
+                write_file(&path, key.to_pem().as_bytes())?;
+                Ok(key)
+            }
+            Err(e) => Err(AcmeError::Cache(format!("{}: {e}", path.display()))),
+        }
+    }
+
+    /// Order a certificate for the configured domains, cache it and
+    /// install it in the certificate store.
+    pub async fn issue(&self) -> Result<(), AcmeError> {
+        let account = self.account_key()?;
+        let mut session = Session::open(&self.client, &account, &self.config.directory_url).await?;
+        session.register(&self.config.contact).await?;
+
+        let identifiers: Vec<_> = self
+            .config
+            .domains
+            .iter()
+            .map(|domain| json!({"type": "dns", "value": domain}))
+            .collect();
+        let (headers, body) = session
+            .post(
+                &session.directory.new_order.clone(),
+                Some(&json!({ "identifiers": identifiers })),
+            )
+            .await?;
+        let order_url = location(&headers)?;
+        let order: Order = parse(&body)?;
+
+        for authorization in &order.authorizations {
+            self.authorize(&mut session, authorization, &account.thumbprint())
+                .await?;
+        }
+
+        let cert_key = KeyPair::generate()?;
+        let request = csr(&cert_key, &self.config.domains)?;
+        let (_, body) = session
+            .post(
+                &order.finalize,
+                Some(&json!({ "csr": URL_SAFE_NO_PAD.encode(request) })),
+            )
+            .await?;
+        let mut order: Order = parse(&body)?;
+        while order.status == "pending" || order.status == "ready" || order.status == "processing" {
+            tokio::time::sleep(self.config.poll_interval).await;
+            order = parse(&session.post(&order_url, None).await?.1)?;
+        }
+        let certificate = match (order.status.as_str(), order.certificate) {
+            ("valid", Some(url)) => url,
+            (status, _) => {
+                return Err(AcmeError::Protocol(format!(
+                    "Order ended {status}: {}",
+                    problem_detail(order.error.as_ref())
+                )))
+            }
+        };
+        let (_, cert_pem) = session.post(&certificate, None).await?;
+
+        let not_after = leaf_not_after(&cert_pem)
+            .ok_or_else(|| AcmeError::Protocol("Issued certificate has no expiry".to_string()))?;
+        let key_pem = cert_key.to_pem();
+        self.install(&cert_pem, key_pem.as_bytes(), not_after)?;
+        let dir = self.domain_dir();
+        write_file(&dir.join("key.pem"), key_pem.as_bytes())?;
+        write_file(&dir.join("cert.pem"), &cert_pem)?;
+        write_file(
+            &dir.join("domains"),
+            self.config.domains.join("\n").as_bytes(),
+        )?;
+        Ok(())
+    }
+
+    async fn authorize(
+        &self,
+        session: &mut Session<'_>,
+        url: &str,
+        thumbprint: &str,
+    ) -> Result<(), AcmeError> {
+        let authorization: Authorization = parse(&session.post(url, None).await?.1)?;
+        if authorization.status == "valid" {
+            return Ok(());
+        }
+        let domain = authorization.identifier.value;
+        let kind = self.config.challenge.as_str();
+        let challenge = authorization
+            .challenges
+            .into_iter()
+            .find(|c| c.kind == kind)
+            .ok_or_else(|| AcmeError::Challenge {
+                domain: domain.clone(),
+                detail: format!("CA offered no {kind} challenge"),
+            })?;
+        let token = challenge.token.unwrap_or_default();
+        let key_authorization = format!("{token}.{thumbprint}");
+
+        match self.config.challenge {
+            ChallengeType::Http01 => {
+                let path = self.config.cache_dir.join("challenges").join(&token);
+                if let Err(e) = write_file(&path, key_authorization.as_bytes()) {
+                    tracing::warn!("Failed to share ACME challenge with other workers: {e}");
+                }
+                self.http_tokens
+                    .write()
+                    .insert(token.clone(), key_authorization);
+            }
+            ChallengeType::TlsAlpn01 => {
+                let key = KeyPair::generate()?;
+                let digest = Sha256::digest(key_authorization.as_bytes());
+                let now = Utc::now();
+                let cert = self_signed(
+                    &key,
+                    &domain,
+                    now - chrono::Duration::days(1),
+                    now + chrono::Duration::days(7),
+                    &[acme_identifier_extension(&digest)],
+                )?;
+                let validation = tls::certified_key_der(
+                    vec![CertificateDer::from(cert)],
+                    PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key.pkcs8.clone())),
+                )
+                .map_err(AcmeError::Crypto)?;
+                self.store.set_challenge(&domain, validation);
+            }
+        }
+
+        let result = self
+            .complete_challenge(session, url, &challenge.url, &domain)
+            .await;
+        match self.config.challenge {
+            ChallengeType::Http01 => {
+                self.http_tokens.write().remove(&token);
+                let _ = std::fs::remove_file(self.config.cache_dir.join("challenges").join(&token));
+            }
+            ChallengeType::TlsAlpn01 => self.store.clear_challenge(&domain),
+        }
+        result
+    }
+
+    async fn complete_challenge(
+        &self,
+        session: &mut Session<'_>,
+        authorization_url: &str,
+        challenge_url: &str,
+        domain: &str,
+    ) -> Result<(), AcmeError> {
+        session.post(challenge_url, Some(&json!({}))).await?;
+        loop {
+            tokio::time::sleep(self.config.poll_interval).await;
+            let authorization: Authorization =
+                parse(&session.post(authorization_url, None).await?.1)?;
+            match authorization.status.as_str() {
+                "valid" => return Ok(()),
+                "pending" | "processing" => continue,
+                status => {
+                    let detail = authorization
+                        .challenges
+                        .iter()
+                        .find_map(|c| c.error.as_ref())
+                        .map(|error| problem_detail(Some(error)))
+                        .unwrap_or_else(|| format!("authorization is {status}"));
+                    return Err(AcmeError::Challenge {
+                        domain: domain.to_string(),
+                        detail,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Install the cached certificate and keep it current in the
+    /// background until `shutdown` is cancelled. With `issue` and a
+    /// `challenge_host`, HTTP-01 challenges are answered on that host and
+    /// the configured port; without a host another listener answers them.
+    pub async fn start(
+        self: Arc<Self>,
+        challenge_host: Option<&str>,
+        issue: bool,
+        shutdown: CancellationToken,
+    ) -> Result<(), String> {
+        if let Err(e) = self.load_cached() {
+            tracing::warn!("Failed to load cached certificate: {e}");
+        }
+        let http01 = self.config.challenge == ChallengeType::Http01;
+        if let (true, true, Some(host)) = (issue, http01, challenge_host) {
+            let port = self.config.http_port;
+            let listener = TcpListener::bind((host, port)).await.map_err(|e| {
+                format!("Failed to bind ACME challenge listener on port {port}: {e}")
+            })?;
+            tokio::spawn(
+                self.clone()
+                    .serve_http_challenges(listener, shutdown.clone()),
+            );
+        }
+        tokio::spawn(self.run(issue, shutdown));
+        Ok(())
+    }
+
+    /// Keep the certificate current until `shutdown` is cancelled.
+    ///
+    /// With `issue`, certificates due for renewal are ordered, retrying
+    /// failed orders with exponential backoff. Without it (worker
+    /// processes other than the leader) only the cache is re-read, so a
+    /// single process talks to the CA.
+    pub async fn run(self: Arc<Self>, issue: bool, shutdown: CancellationToken) {
+        let mut failures = 0u32;
+        loop {
+            let wait = if !self.needs_renewal() {
+                self.config.check_interval
+            } else if issue {
+                match self.issue().await {
+                    Ok(()) => {
+                        failures = 0;
+                        tracing::info!(
+                            "Issued certificate for {}, valid until {}",
+                            self.config.domains.join(", "),
+                            self.not_after().map(|t| t.to_rfc3339()).unwrap_or_default()
+                        );
+                        self.config.check_interval
+                    }
+                    Err(e) => {
+                        tracing::warn!("Failed to obtain certificate: {e}");
+                        failures += 1;
+                        let backoff = Duration::from_secs(60) * 2u32.pow(failures.min(6) - 1);
+                        backoff.min(self.config.check_interval)
+                    }
+                }
+            } else {
+                if let Err(e) = self.load_cached() {
+                    tracing::warn!("Failed to load cached certificate: {e}");
+                }
+                if self.store.has_certificate() {
+                    self.config.check_interval
+                } else {
+                    Duration::from_secs(10)
+                }
+            };
+            tokio::select! {
+                _ = shutdown.cancelled() => return,
+                _ = tokio::time::sleep(wait) => {}
+            }
+        }
+    }
+
+    /// Answer HTTP-01 challenge requests on `listener` until `shutdown` is
+    /// cancelled. Other requests get a 404.
+    pub async fn serve_http_challenges(
+        self: Arc<Self>,
+        listener: TcpListener,
+        shutdown: CancellationToken,
+    ) {
+        loop {
+            let stream = tokio::select! {
+                _ = shutdown.cancelled() => return,
+                accepted = listener.accept() => match accepted {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        tracing::warn!("ACME challenge listener accept error: {e}");
+                        continue;
+                    }
+                },
+            };
+            let manager = self.clone();
+            tokio::spawn(async move {
+                let service = service_fn(move |req: hyper::Request<hyper::body::Incoming>| {
+                    let token = req.uri().path().strip_prefix(CHALLENGE_PATH).unwrap_or("");
+                    let response = manager.challenge_response(token);
+                    async move { Ok::<_, Infallible>(response) }
+                });
+                let _ = http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await;
+            });
+        }
+    }
+}
+
+// ============================================================================
+// Protocol
+// ============================================================================
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Directory {
+    new_nonce: String,
+    new_account: String,
+    new_order: String,
+}
+
+#[derive(Deserialize)]
+struct Order {
+    status: String,
+    #[serde(default)]
+    authorizations: Vec<String>,
+    finalize: String,
+    certificate: Option<String>,
+    error: Option<JsonValue>,
+}
+
+#[derive(Deserialize)]
+struct Identifier {
+    value: String,
+}
+
+#[derive(Deserialize)]
+struct Authorization {
+    status: String,
+    identifier: Identifier,
+    #[serde(default)]
+    challenges: Vec<Challenge>,
+}
+
+#[derive(Deserialize)]
+struct Challenge {
+    #[serde(rename = "type")]
+    kind: String,
+    url: String,
+    token: Option<String>,
+    error: Option<JsonValue>,
+}
+
+/// Signed requests to the CA on behalf of one account.
+struct Session<'a> {
+    client: &'a reqwest::Client,
+    key: &'a KeyPair,
+    directory: Directory,
+    nonce: Option<String>,
+    kid: Option<String>,
+}
+
+impl<'a> Session<'a> {
+    async fn open(
+        client: &'a reqwest::Client,
+        key: &'a KeyPair,
+        directory_url: &str,
+    ) -> Result<Session<'a>, AcmeError> {
+        let response = client
+            .get(directory_url)
+            .send()
+            .await
+            .map_err(|e| AcmeError::Http(e.to_string()))?;
+        let body = response
+            .bytes()
+            .await
+            .map_err(|e| AcmeError::Http(e.to_string()))?;
+        Ok(Session {
+            client,
+            key,
+            directory: parse(&body)?,
+            nonce: None,
+            kid: None,
+        })
+    }
+
+    /// Register the account, or look up the one registered with this key.
+    async fn register(&mut self, contact: &[String]) -> Result<(), AcmeError> {
+        let url = self.directory.new_account.clone();
+        let (headers, _) = self
+            .post(
+                &url,
+                Some(&json!({"termsOfServiceAgreed": true, "contact": contact})),
+            )
+            .await?;
+        self.kid = Some(location(&headers)?);
+        Ok(())
+    }
+
+    async fn nonce(&mut self) -> Result<String, AcmeError> {
+        if let Some(nonce) = self.nonce.take() {
+            return Ok(nonce);
+        }
+        let response = self
+            .client
+            .head(&self.directory.new_nonce)
+            .send()
+            .await
+            .map_err(|e| AcmeError::Http(e.to_string()))?;
+        replay_nonce(response.headers())
+            .ok_or_else(|| AcmeError::Protocol("No Replay-Nonce in newNonce response".to_string()))
+    }
+
+    /// POST a JWS-signed `payload` to `url`, or a POST-as-GET without one.
+    /// Retries once with a fresh nonce if the CA rejects the nonce.
+    async fn post(
+        &mut self,
+        url: &str,
+        payload: Option<&JsonValue>,
+    ) -> Result<(reqwest::header::HeaderMap, Bytes), AcmeError> {
+        let mut retried = false;
+        loop {
+            let mut protected = json!({"alg": "ES256", "nonce": self.nonce().await?, "url": url});
+            match &self.kid {
+                Some(kid) => protected["kid"] = json!(kid),
+                None => protected["jwk"] = self.key.jwk(),
+            }
+            let body = self.key.jws(&protected, payload)?;
+            let response = self
+                .client
+                .post(url)
+                .header("content-type", "application/jose+json")
+                .body(body.to_string())
+                .send()
+                .await
+                .map_err(|e| AcmeError::Http(e.to_string()))?;
+            self.nonce = replay_nonce(response.headers());
+            let status = response.status();
+            let headers = response.headers().clone();
+            let body = response
+                .bytes()
+                .await
+                .map_err(|e| AcmeError::Http(e.to_string()))?;
+            if status.is_success() {
+                return Ok((headers, body));
+            }
+            let problem: JsonValue = serde_json::from_slice(&body).unwrap_or(JsonValue::Null);
+            if !retried && problem["type"] == "urn:ietf:params:acme:error:badNonce" {
+                retried = true;
+                continue;
+            }
+            return Err(AcmeError::Protocol(format!(
+                "{url} answered {status}: {}",
+                problem_detail(Some(&problem))
+            )));
+        }
+    }
+}
+
+fn parse<T: serde::de::DeserializeOwned>(body: &[u8]) -> Result<T, AcmeError> {
+    serde_json::from_slice(body).map_err(|e| AcmeError::Protocol(format!("Invalid response: {e}")))
+}
+
+fn location(headers: &reqwest::header::HeaderMap) -> Result<String, AcmeError> {
+    headers
+        .get("location")
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+        .ok_or_else(|| AcmeError::Protocol("Response has no Location".to_string()))
+}
+
+fn replay_nonce(headers: &reqwest::header::HeaderMap) -> Option<String> {
+    headers
+        .get("replay-nonce")
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+}
+
+fn problem_detail(problem: Option<&JsonValue>) -> String {
+    match problem {
+        Some(problem) => problem["detail"]
+            .as_str()
+            .or_else(|| problem["type"].as_str())
+            .unwrap_or("no details")
+            .to_string(),
+        None => "no details".to_string(),
+    }
+}
+
+fn write_file(path: &Path, contents: &[u8]) -> Result<(), AcmeError> {
+    let error = |e: std::io::Error| AcmeError::Cache(format!("{}: {e}", path.display()));
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(error)?;
+    }
+    let temp = path.with_extension("tmp");
+    std::fs::write(&temp, contents).map_err(error)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&temp, std::fs::Permissions::from_mode(0o600)).map_err(error)?;
+    }
+    std::fs::rename(&temp, path).map_err(error)
+}
+
+// ============================================================================
+// Keys
+// ============================================================================
+
+/// A P-256 key, used for the account (JWS ES256) and for certificates.
+pub(crate) struct KeyPair {
+    pkcs8: Vec<u8>,
+    fixed: EcdsaKeyPair,
+    asn1: EcdsaKeyPair,
+    rng: SystemRandom,
+}
+
+impl KeyPair {
+    pub(crate) fn generate() -> Result<Self, AcmeError> {
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng)
+            .map_err(|e| AcmeError::Crypto(e.to_string()))?;
+        Self::from_pkcs8(pkcs8.as_ref().to_vec())
+    }
+
+    fn from_pkcs8(pkcs8: Vec<u8>) -> Result<Self, AcmeError> {
+        let rng = SystemRandom::new();
+        let load = |alg| {
+            EcdsaKeyPair::from_pkcs8(alg, &pkcs8, &rng)
+                .map_err(|e| AcmeError::Crypto(format!("Invalid P-256 key: {e}")))
+        };
+        Ok(Self {
+            fixed: load(&ECDSA_P256_SHA256_FIXED_SIGNING)?,
+            asn1: load(&ECDSA_P256_SHA256_ASN1_SIGNING)?,
+            pkcs8,
+            rng,
+        })
+    }
+
+    fn from_pem(pem: &[u8]) -> Result<Self, AcmeError> {
+        match rustls_pemfile::private_key(&mut &pem[..]) {
+            Ok(Some(PrivateKeyDer::Pkcs8(key))) => {
+                Self::from_pkcs8(key.secret_pkcs8_der().to_vec())
+            }
+            _ => Err(AcmeError::Cache(
+                "Account key is not a PKCS#8 private key".to_string(),
+            )),
+        }
+    }
+
+    pub(crate) fn to_pem(&self) -> String {
+        pem("PRIVATE KEY", &self.pkcs8)
+    }
+
+    /// The uncompressed public point, `04 || x || y`.
+    fn public_point(&self) -> &[u8] {
+        self.fixed.public_key().as_ref()
+    }
+
+    fn jwk(&self) -> JsonValue {
+        let point = self.public_point();
+        json!({
+            "crv": "P-256",
+            "kty": "EC",
+            "x": URL_SAFE_NO_PAD.encode(&point[1..33]),
+            "y": URL_SAFE_NO_PAD.encode(&point[33..65]),
+        })
+    }
+
+    /// JWK thumbprint (RFC 7638) over the members in lexicographic order.
+    fn thumbprint(&self) -> String {
+        let jwk = self.jwk();
+        let canonical = format!(
+            r#"{{"crv":"P-256","kty":"EC","x":"{}","y":"{}"}}"#,
+            jwk["x"].as_str().unwrap_or_default(),
+            jwk["y"].as_str().unwrap_or_default()
+        );
+        URL_SAFE_NO_PAD.encode(Sha256::digest(canonical.as_bytes()))
+    }
+
+    /// A flattened JWS; an absent payload signs the empty string (POST-as-GET).
+    fn jws(
+        &self,
+        protected: &JsonValue,
+        payload: Option<&JsonValue>,
+    ) -> Result<JsonValue, AcmeError> {
+        let protected = URL_SAFE_NO_PAD.encode(protected.to_string());
+        let payload = payload
+            .map(|p| URL_SAFE_NO_PAD.encode(p.to_string()))
+            .unwrap_or_default();
+        let signature = self
+            .fixed
+            .sign(&self.rng, format!("{protected}.{payload}").as_bytes())
+            .map_err(|e| AcmeError::Crypto(e.to_string()))?;
+        Ok(json!({
+            "protected": protected,
+            "payload": payload,
+            "signature": URL_SAFE_NO_PAD.encode(signature.as_ref()),
+        }))
+    }
+
+    /// A DER-encoded ECDSA signature, as used in certificates and requests.
+    fn sign_der(&self, message: &[u8]) -> Result<Vec<u8>, AcmeError> {
+        self.asn1
+            .sign(&self.rng, message)
+            .map(|signature| signature.as_ref().to_vec())
+            .map_err(|e| AcmeError::Crypto(e.to_string()))
+    }
+}
+
+fn pem(label: &str, der: &[u8]) -> String {
+    let encoded = STANDARD.encode(der);
+    let mut pem = format!("-----BEGIN {label}-----\n");
+    for line in encoded.as_bytes().chunks(64) {
+        pem.push_str(std::str::from_utf8(line).unwrap_or_default());
+        pem.push('\n');
+    }
+    pem.push_str(&format!("-----END {label}-----\n"));
+    pem
+}
+
+// ============================================================================
+// Certificates and requests (DER)
+// ============================================================================
+
+mod oid {
+    pub const EC_PUBLIC_KEY: &[u8] = &[0x2A, 0x86, 0x48, 0xCE, 0x3D, 0x02, 0x01];
+    pub const PRIME256V1: &[u8] = &[0x2A, 0x86, 0x48, 0xCE, 0x3D, 0x03, 0x01, 0x07];
+    pub const ECDSA_WITH_SHA256: &[u8] = &[0x2A, 0x86, 0x48, 0xCE, 0x3D, 0x04, 0x03, 0x02];
+    pub const COMMON_NAME: &[u8] = &[0x55, 0x04, 0x03];
+    pub const EXTENSION_REQUEST: &[u8] = &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x09, 0x0E];
+    pub const SUBJECT_ALT_NAME: &[u8] = &[0x55, 0x1D, 0x11];
+    pub const ACME_IDENTIFIER: &[u8] = &[0x2B, 0x06, 0x01, 0x05, 0x05, 0x07, 0x01, 0x1F];
+}
+
+fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    let len = content.len();
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        let bytes: Vec<u8> = len
+            .to_be_bytes()
+            .into_iter()
+            .skip_while(|b| *b == 0)
+            .collect();
+        out.push(0x80 | bytes.len() as u8);
+        out.extend(bytes);
+    }
+    out.extend_from_slice(content);
+    out
+}
+
+fn sequence(items: &[Vec<u8>]) -> Vec<u8> {
+    tlv(0x30, &items.concat())
+}
+
+fn object_id(oid: &[u8]) -> Vec<u8> {
+    tlv(0x06, oid)
+}
+
+fn bit_string(bits: &[u8]) -> Vec<u8> {
+    tlv(0x03, &[&[0u8][..], bits].concat())
+}
+
+fn name(common_name: &str) -> Vec<u8> {
+    let attribute = sequence(&[
+        object_id(oid::COMMON_NAME),
+        tlv(0x0C, common_name.as_bytes()),
+    ]);
+    sequence(&[tlv(0x31, &attribute)])
+}
+
+fn public_key_info(key: &KeyPair) -> Vec<u8> {
+    sequence(&[
+        sequence(&[object_id(oid::EC_PUBLIC_KEY), object_id(oid::PRIME256V1)]),
+        bit_string(key.public_point()),
+    ])
+}
+
+fn signature_algorithm() -> Vec<u8> {
+    sequence(&[object_id(oid::ECDSA_WITH_SHA256)])
+}
+
+fn extension(oid: &[u8], critical: bool, value: &[u8]) -> Vec<u8> {
+    let mut parts = vec![object_id(oid)];
+    if critical {
+        parts.push(vec![0x01, 0x01, 0xFF]);
+    }
+    parts.push(tlv(0x04, value));
+    sequence(&parts)
+}
+
+fn subject_alt_names(domains: &[String]) -> Vec<u8> {
+    let names: Vec<_> = domains.iter().map(|d| tlv(0x82, d.as_bytes())).collect();
+    extension(oid::SUBJECT_ALT_NAME, false, &sequence(&names))
+}
+
+/// The critical `id-pe-acmeIdentifier` extension of TLS-ALPN-01 validation
+/// certificates, holding the SHA-256 digest of the key authorization.
+fn acme_identifier_extension(digest: &[u8]) -> Vec<u8> {
+    extension(oid::ACME_IDENTIFIER, true, &tlv(0x04, digest))
+}
+
+fn time(at: DateTime<Utc>) -> Vec<u8> {
+    if at.year() < 2050 {
+        tlv(0x17, at.format("%y%m%d%H%M%SZ").to_string().as_bytes())
+    } else {
+        tlv(0x18, at.format("%Y%m%d%H%M%SZ").to_string().as_bytes())
+    }
+}
+
+/// A PKCS#10 certificate signing request for `domains`.
+fn csr(key: &KeyPair, domains: &[String]) -> Result<Vec<u8>, AcmeError> {
+    let extensions = sequence(&[subject_alt_names(domains)]);
+    let attribute = sequence(&[object_id(oid::EXTENSION_REQUEST), tlv(0x31, &extensions)]);
+    let info = sequence(&[
+        vec![0x02, 0x01, 0x00],
+        name(&domains[0]),
+        public_key_info(key),
+        tlv(0xA0, &attribute),
+    ]);
+    let signature = key.sign_der(&info)?;
+    Ok(sequence(&[
+        info,
+        signature_algorithm(),
+        bit_string(&signature),
+    ]))
+}
+
+/// A self-signed X.509 v3 certificate for `domain`.
+pub(crate) fn self_signed(
+    key: &KeyPair,
+    domain: &str,
+    not_before: DateTime<Utc>,
+    not_after: DateTime<Utc>,
+    extensions: &[Vec<u8>],
+) -> Result<Vec<u8>, AcmeError> {
+    let mut serial = [0u8; 16];
+    key.rng
+        .fill(&mut serial)
+        .map_err(|e| AcmeError::Crypto(e.to_string()))?;
+    serial[0] = (serial[0] & 0x7F) | 0x01;
+
+    let mut all_extensions = vec![subject_alt_names(&[domain.to_string()])];
+    all_extensions.extend_from_slice(extensions);
+    let tbs = sequence(&[
+        tlv(0xA0, &[0x02, 0x01, 0x02]),
+        tlv(0x02, &serial),
+        signature_algorithm(),
+        name(domain),
+        sequence(&[time(not_before), time(not_after)]),
+        name(domain),
+        public_key_info(key),
+        tlv(0xA3, &sequence(&all_extensions)),
+    ]);
+    let signature = key.sign_der(&tbs)?;
+    Ok(sequence(&[
+        tbs,
+        signature_algorithm(),
+        bit_string(&signature),
+    ]))
+}
+
+/// Split a DER element into its tag, content and the bytes after it.
+fn read_tlv(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = data.split_first()?;
+    let (&first, rest) = rest.split_first()?;
+    let (len, rest) = if first < 0x80 {
+        (first as usize, rest)
+    } else {
+        let count = (first & 0x7F) as usize;
+        if count == 0 || count > std::mem::size_of::<usize>() || rest.len() < count {
+            return None;
+        }
+        let len = rest[..count]
+            .iter()
+            .fold(0usize, |len, b| (len << 8) | *b as usize);
+        (len, &rest[count..])
+    };
+    (rest.len() >= len).then(|| (tag, &rest[..len], &rest[len..]))
+}
+
+fn parse_time(tag: u8, value: &[u8]) -> Option<DateTime<Utc>> {
+    let text = std::str::from_utf8(value).ok()?.strip_suffix('Z')?;
+    let (year, rest) = match tag {
+        0x17 => {
+            let year: i32 = text.get(..2)?.parse().ok()?;
+            (
+                if year < 50 { 2000 + year } else { 1900 + year },
+                &text[2..],
+            )
+        }
+        0x18 => (text.get(..4)?.parse().ok()?, &text[4..]),
+        _ => return None,
+    };
+    let field = |i: usize| rest.get(i..i + 2)?.parse::<u32>().ok();
+    NaiveDate::from_ymd_opt(year, field(0)?, field(2)?)?
+        .and_hms_opt(field(4)?, field(6)?, field(8)?)
+        .map(|t| t.and_utc())
+}
+
+/// Expiry of a DER-encoded certificate.
+fn not_after(cert: &[u8]) -> Option<DateTime<Utc>> {
+    let (_, cert, _) = read_tlv(cert)?;
+    let (_, mut tbs, _) = read_tlv(cert)?;
+    if tbs.first() == Some(&0xA0) {
+        tbs = read_tlv(tbs)?.2;
+    }
+    // serialNumber, signature, issuer
+    for _ in 0..3 {
+        tbs = read_tlv(tbs)?.2;
+    }
+    let (_, validity, _) = read_tlv(tbs)?;
+    let (_, _, validity) = read_tlv(validity)?;
+    let (tag, value, _) = read_tlv(validity)?;
+    parse_time(tag, value)
+}
+
+/// Expiry of the first certificate of a PEM chain.
+fn leaf_not_after(pem: &[u8]) -> Option<DateTime<Utc>> {
+    let leaf = rustls_pemfile::certs(&mut &pem[..]).next()?.ok()?;
+    not_after(&leaf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parking_lot::Mutex;
+    use ring::signature::{UnparsedPublicKey, ECDSA_P256_SHA256_FIXED};
+
+    #[test]
+    fn test_acme_config() {
+        let config = AcmeConfig::new(vec!["example.com".to_string()])
+            .email("admin@example.com")
+            .challenge(ChallengeType::TlsAlpn01);
+        assert!(config.validate().is_ok());
+        assert_eq!(config.contact, vec!["mailto:admin@example.com"]);
+        assert_eq!(ChallengeType::parse("http-01"), Some(ChallengeType::Http01));
+        assert_eq!(ChallengeType::parse("dns-01"), None);
+
+        assert!(AcmeConfig::new(Vec::new()).validate().is_err());
+        assert!(AcmeConfig::new(vec!["bad domain".to_string()])
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn test_jws_signature() {
+        let key = KeyPair::generate().unwrap();
+        let jws = key
+            .jws(&json!({"alg": "ES256"}), Some(&json!({"a": 1})))
+            .unwrap();
+        let signed = format!(
+            "{}.{}",
+            jws["protected"].as_str().unwrap(),
+            jws["payload"].as_str().unwrap()
+        );
+        let signature = URL_SAFE_NO_PAD
+            .decode(jws["signature"].as_str().unwrap())
+            .unwrap();
+        UnparsedPublicKey::new(&ECDSA_P256_SHA256_FIXED, key.public_point())
+            .verify(signed.as_bytes(), &signature)
+            .unwrap();
+
+        let post_as_get = key.jws(&json!({"alg": "ES256"}), None).unwrap();
+        assert_eq!(post_as_get["payload"], "");
+
+        let reloaded = KeyPair::from_pem(key.to_pem().as_bytes()).unwrap();
+        assert_eq!(reloaded.thumbprint(), key.thumbprint());
+        assert_eq!(key.thumbprint().len(), 43);
+    }
+
+    #[test]
+    fn test_certificate_encoding() {
+        let key = KeyPair::generate().unwrap();
+        let expiry = Utc::now() + chrono::Duration::days(90);
+        let expiry = expiry - chrono::Duration::nanoseconds(expiry.timestamp_subsec_nanos() as i64);
+        let cert = self_signed(&key, "example.com", Utc::now(), expiry, &[]).unwrap();
+        assert_eq!(not_after(&cert), Some(expiry));
+
+        let chain = pem("CERTIFICATE", &cert);
+        assert_eq!(leaf_not_after(chain.as_bytes()), Some(expiry));
+        assert!(tls::certified_key(chain.as_bytes(), key.to_pem().as_bytes()).is_ok());
+
+        let far = NaiveDate::from_ymd_opt(2051, 1, 2)
+            .unwrap()
+            .and_hms_opt(3, 4, 5)
+            .unwrap()
+            .and_utc();
+        let encoded = time(far);
+        let (tag, value, _) = read_tlv(&encoded).unwrap();
+        assert_eq!(tag, 0x18);
+        assert_eq!(parse_time(tag, value), Some(far));
+
+        let request = csr(
+            &key,
+            &["example.com".to_string(), "www.example.com".to_string()],
+        )
+        .unwrap();
+        let (tag, content, rest) = read_tlv(&request).unwrap();
+        assert_eq!(tag, 0x30);
+        assert!(rest.is_empty());
+        let (_, info, after) = read_tlv(content).unwrap();
+        let info_der = &content[..content.len() - after.len()];
+        let (_, _, signature) = read_tlv(after).unwrap();
+        let (_, signature, _) = read_tlv(signature).unwrap();
+        UnparsedPublicKey::new(&ring::signature::ECDSA_P256_SHA256_ASN1, key.public_point())
+            .verify(info_der, &signature[1..])
+            .unwrap();
+        assert!(info.windows(15).any(|w| w == b"www.example.com"));
+    }
+
+    #[test]
+    fn test_shared_http_challenge() {
+        let cache = std::env::temp_dir().join(format!("cello-acme-{}", uuid::Uuid::new_v4()));
+        let config = AcmeConfig::new(vec!["example.com".to_string()]).cache_dir(&cache);
+        let manager = AcmeManager::new(config, Arc::new(CertStore::new()));
+        write_file(&cache.join("challenges/abc_-1"), b"abc_-1.thumb").unwrap();
+        write_file(&cache.join("secret"), b"secret").unwrap();
+
+        assert_eq!(
+            manager.http_challenge("abc_-1").as_deref(),
+            Some("abc_-1.thumb")
+        );
+        assert_eq!(manager.challenge_response("abc_-1").status(), 200);
+        assert!(manager.http_challenge("../secret").is_none());
+        assert_eq!(manager.challenge_response("missing").status(), 404);
+        let _ = std::fs::remove_dir_all(&cache);
+    }
+
+    /// A minimal ACME CA that validates HTTP-01 challenges by fetching them.
+    async fn mock_ca(challenge_port: u16, cert_pem: String) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let state = Arc::new(Mutex::new(("pending".to_string(), String::new())));
+        let root = base.clone();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let base = root.clone();
+                let state = state.clone();
+                let cert_pem = cert_pem.clone();
+                tokio::spawn(async move {
+                    let service = service_fn(move |req: hyper::Request<hyper::body::Incoming>| {
+                        let base = base.clone();
+                        let state = state.clone();
+                        let cert_pem = cert_pem.clone();
+                        async move {
+                            use http_body_util::BodyExt;
+                            let path = req.uri().path().to_string();
+                            let body = req.into_body().collect().await.unwrap().to_bytes();
+                            let jws: JsonValue =
+                                serde_json::from_slice(&body).unwrap_or(JsonValue::Null);
+                            let protected: JsonValue = jws["protected"]
+                                .as_str()
+                                .and_then(|p| URL_SAFE_NO_PAD.decode(p).ok())
+                                .and_then(|p| serde_json::from_slice(&p).ok())
+                                .unwrap_or(JsonValue::Null);
+                            if path == "/account" {
+                                let jwk = &protected["jwk"];
+                                let canonical = format!(
+                                    r#"{{"crv":"P-256","kty":"EC","x":"{}","y":"{}"}}"#,
+                                    jwk["x"].as_str().unwrap(),
+                                    jwk["y"].as_str().unwrap()
+                                );
+                                state.lock().1 =
+                                    URL_SAFE_NO_PAD.encode(Sha256::digest(canonical.as_bytes()));
+                            }
+                            if path == "/challenge" {
+                                let expected = format!("tok.{}", state.lock().1);
+                                let answer = reqwest::get(format!(
+                                    "http://127.0.0.1:{challenge_port}{CHALLENGE_PATH}tok"
+                                ))
+                                .await
+                                .unwrap()
+                                .text()
+                                .await
+                                .unwrap();
+                                state.lock().0 = if answer == expected {
+                                    "valid"
+                                } else {
+                                    "invalid"
+                                }
+                                .to_string();
+                            }
+                            let order = json!({
+                                "status": "valid",
+                                "authorizations": [format!("{base}/authz")],
+                                "finalize": format!("{base}/finalize"),
+                                "certificate": format!("{base}/cert"),
+                            });
+                            let (status, body) = match path.as_str() {
+                                "/directory" => (
+                                    200,
+                                    json!({
+                                        "newNonce": format!("{base}/nonce"),
+                                        "newAccount": format!("{base}/account"),
+                                        "newOrder": format!("{base}/order"),
+                                    })
+                                    .to_string(),
+                                ),
+                                "/order" | "/finalize" => (200, order.to_string()),
+                                "/authz" => (
+                                    200,
+                                    json!({
+                                        "status": state.lock().0,
+                                        "identifier": {"type": "dns", "value": "example.com"},
+                                        "challenges": [{
+                                            "type": "http-01",
+                                            "url": format!("{base}/challenge"),
+                                            "token": "tok",
+                                        }],
+                                    })
+                                    .to_string(),
+                                ),
+                                "/cert" => (200, cert_pem),
+                                _ => (200, "{}".to_string()),
+                            };
+                            let location = match path.as_str() {
+                                "/account" => format!("{base}/acct/1"),
+                                _ => format!("{base}/order"),
+                            };
+                            Ok::<_, Infallible>(
+                                hyper::Response::builder()
+                                    .status(status)
+                                    .header("replay-nonce", "nonce")
+                                    .header("location", location)
+                                    .body(Full::new(Bytes::from(body)))
+                                    .unwrap(),
+                            )
+                        }
+                    });
+                    let _ = http1::Builder::new()
+                        .serve_connection(TokioIo::new(stream), service)
+                        .await;
+                });
+            }
+        });
+        format!("{base}/directory")
+    }
+
+    #[tokio::test]
+    async fn test_issue_with_http_challenge() {
+        let cache = std::env::temp_dir().join(format!("cello-acme-{}", uuid::Uuid::new_v4()));
+        let issued_key = KeyPair::generate().unwrap();
+        let now = Utc::now();
+        let cert = self_signed(
+            &issued_key,
+            "example.com",
+            now,
+            now + chrono::Duration::days(90),
+            &[],
+        )
+        .unwrap();
+
+        let challenges = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let challenge_port = challenges.local_addr().unwrap().port();
+        let directory = mock_ca(challenge_port, pem("CERTIFICATE", &cert)).await;
+
+        let mut config = AcmeConfig::new(vec!["example.com".to_string()])
+            .directory(&directory)
+            .cache_dir(&cache);
+        config.poll_interval = Duration::from_millis(10);
+        let store = Arc::new(CertStore::new());
+        let manager = Arc::new(AcmeManager::new(config.clone(), store.clone()));
+        let shutdown = CancellationToken::new();
+        tokio::spawn(
+            manager
+                .clone()
+                .serve_http_challenges(challenges, shutdown.clone()),
+        );
+
+        assert!(manager.needs_renewal());
+        manager.issue().await.unwrap();
+        assert!(store.has_certificate());
+        assert!(!manager.needs_renewal());
+        assert!(manager.http_challenge("tok").is_none());
+        assert!(cache.join("account.key").exists());
+        assert!(cache.join("example.com/cert.pem").exists());
+
+        let restarted = AcmeManager::new(config.clone(), Arc::new(CertStore::new()));
+        assert!(restarted.load_cached().unwrap());
+        assert_eq!(restarted.not_after(), manager.not_after());
+        assert!(!restarted.load_cached().unwrap());
+
+        let mut widened = config;
+        widened.domains.push("www.example.com".to_string());
+        let widened = AcmeManager::new(widened, Arc::new(CertStore::new()));
+        assert!(!widened.load_cached().unwrap());
+
+        shutdown.cancel();
+        let _ = std::fs::remove_dir_all(&cache);
+    }
+}
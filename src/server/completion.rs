@@ -0,0 +1,185 @@
+//! Response completion hooks.
+//!
+//! A response handler returns before a streamed or SSE body is produced, so
+//! middleware that measures responses in `after` misses their size and
+//! duration. When any middleware observes response ends, the body is
+//! wrapped to count the bytes sent and to tell the middleware once it has
+//! been sent in full, it failed, or the client went away.
+
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use bytes::Bytes;
+use hyper::body::{Body, Frame};
+
+use super::{BodyError, ResponseBody};
+use crate::middleware::{Middleware, ResponseEnd};
+use crate::request::Extensions;
+
+/// A response body that reports how it ended to observing middleware.
+pub struct ObservedBody {
+    body: ResponseBody,
+    end: ResponseEnd,
+    started: Instant,
+    observers: Vec<Arc<dyn Middleware>>,
+    /// HEAD responses have no body to send, so dropping them is no abort
+    head: bool,
+    done: bool,
+}
+
+impl ObservedBody {
+    /// Observe `body`, the response with `status` to `method` `path`
+    /// received at `started`.
+    pub fn new(
+        body: ResponseBody,
+        method: &hyper::Method,
+        path: &str,
+        status: u16,
+        started: Instant,
+        extensions: Extensions,
+        observers: Vec<Arc<dyn Middleware>>,
+    ) -> Self {
+        Self {
+            body,
+            end: ResponseEnd {
+                method: method.as_str().to_string(),
+                path: path.to_string(),
+                status,
+                bytes: 0,
+                duration: Default::default(),
+                aborted: false,
+                extensions,
+            },
+            started,
+            observers,
+            head: method == hyper::Method::HEAD,
+            done: false,
+        }
+    }
+
+    fn finish(&mut self, aborted: bool) {
+        if std::mem::replace(&mut self.done, true) {
+            return;
+        }
+        self.end.aborted = aborted;
+        self.end.duration = self.started.elapsed();
+        for observer in &self.observers {
+            observer.on_response_end(&self.end);
+        }
+    }
+}
+
+impl Body for ObservedBody {
+    type Data = Bytes;
+    type Error = BodyError;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, BodyError>>> {
+        let this = self.get_mut();
+        let polled = Pin::new(&mut this.body).poll_frame(cx);
+        match &polled {
+            Poll::Ready(Some(Ok(frame))) => {
+                if let Some(data) = frame.data_ref() {
+                    this.end.bytes += data.len() as u64;
+                }
+            }
+            Poll::Ready(Some(Err(_))) => this.finish(true),
+            Poll::Ready(None) => this.finish(false),
+            Poll::Pending => {}
+        }
+        polled
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.body.is_end_stream()
+    }
+
+    fn size_hint(&self) -> hyper::body::SizeHint {
+        self.body.size_hint()
+    }
+}
+
+impl Drop for ObservedBody {
+    fn drop(&mut self) {
+        // Dropped unfinished: the connection closed before the body was sent
+        let aborted = !self.head && !self.body.is_end_stream();
+        self.finish(aborted);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_body_util::BodyExt;
+    use parking_lot::Mutex;
+
+    #[derive(Default)]
+    struct Recorder {
+        ends: Mutex<Vec<ResponseEnd>>,
+    }
+
+    impl Middleware for Recorder {
+        fn observes_response_end(&self) -> bool {
+            true
+        }
+
+        fn on_response_end(&self, end: &ResponseEnd) {
+            self.ends.lock().push(end.clone());
+        }
+    }
+
+    fn observed(body: ResponseBody, recorder: &Arc<Recorder>) -> ObservedBody {
+        ObservedBody::new(
+            body,
+            &hyper::Method::GET,
+            "/events",
+            200,
+            Instant::now(),
+            Extensions::new(),
+            vec![recorder.clone() as Arc<dyn Middleware>],
+        )
+    }
+
+    #[tokio::test]
+    async fn test_streamed_body_end() {
+        let recorder = Arc::new(Recorder::default());
+        let (tx, body) = ResponseBody::channel(4);
+        tx.send(Ok(Bytes::from_static(b"data: 1\n\n")))
+            .await
+            .unwrap();
+        tx.send(Ok(Bytes::from_static(b"data: 2\n\n")))
+            .await
+            .unwrap();
+        drop(tx);
+
+        let collected = observed(body, &recorder).collect().await.unwrap();
+        assert_eq!(collected.to_bytes().len(), 18);
+
+        let ends = recorder.ends.lock();
+        assert_eq!(ends.len(), 1);
+        assert_eq!((ends[0].bytes, ends[0].aborted), (18, false));
+        assert_eq!(ends[0].path, "/events");
+    }
+
+    #[tokio::test]
+    async fn test_client_abort() {
+        let recorder = Arc::new(Recorder::default());
+        let (tx, body) = ResponseBody::channel(4);
+        tx.send(Ok(Bytes::from_static(b"partial"))).await.unwrap();
+
+        let mut body = observed(body, &recorder);
+        let frame = body.frame().await.unwrap().unwrap();
+        assert_eq!(frame.data_ref().map(|d| d.len()), Some(7));
+        // The connection goes away mid-stream
+        drop(body);
+
+        let ends = recorder.ends.lock();
+        assert_eq!(ends.len(), 1);
+        assert_eq!((ends[0].bytes, ends[0].aborted), (7, true));
+        drop(tx);
+    }
+}
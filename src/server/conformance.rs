@@ -0,0 +1,1023 @@
+//! End-to-end conformance suite run against a live server.
+//!
+//! Boots the real accept loop on an ephemeral port and drives it over raw TCP,
+//! so wire-level behavior (keep-alive, chunked bodies, 100-continue,
+//! pipelining, malformed input) is checked exactly as clients see it. The
+//! same listener is exercised over TLS with a self-signed certificate, over
+//! HTTP/2 (negotiated by ALPN and by prior knowledge in the clear), and
+//! through the WebSocket handshake and framing.
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use std::sync::Arc;
+
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use pyo3::prelude::*;
+use rustls::pki_types::{CertificateDer, ServerName};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use tokio_rustls::client::TlsStream;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+
+use super::protocols::TlsVersion;
+use super::{acme, tls, Http2Config, Server, TlsConfig};
+use crate::handler::HandlerRegistry;
+use crate::middleware::MiddlewareChain;
+use crate::router::Router;
+use crate::websocket::{WebSocketConfig, WebSocketRegistry};
+
+/// Upper bound on any single read, so a broken server fails the test instead of hanging it.
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+
+// ============================================================================
+// Harness
+// ============================================================================
+
+/// A running server bound to an ephemeral localhost port.
+struct TestServer {
+    addr: SocketAddr,
+    router: Router,
+    task: JoinHandle<PyResult<()>>,
+    /// The self-signed certificate served over TLS, if any
+    certificate: Option<CertificateDer<'static>>,
+}
+
+impl TestServer {
+    /// Start a server with `(method, path, python_lambda_source)` routes.
+    async fn start(routes: &[(&str, &str, &str)]) -> Self {
+        Self::start_with(routes, WebSocketRegistry::new(), |server| server).await
+    }
+
+    /// Start a server whose WebSocket route `/ws` runs the Python function
+    /// `handler` defined in `source`, under `config`.
+    async fn websocket(source: &str, config: WebSocketConfig) -> Self {
+        pyo3::prepare_freethreaded_python();
+
+        let websockets = WebSocketRegistry::new();
+        websockets.set_config(config);
+        Python::with_gil(|py| {
+            let globals = pyo3::types::PyDict::new(py);
+            py.run(source, Some(globals), None).unwrap();
+            websockets.register("/ws", globals.get_item("handler").unwrap().unwrap().into());
+        });
+        Self::start_with(&[], websockets, |server| server).await
+    }
+
+    /// Start a server with `routes` and `websockets`, configured by `setup`.
+    async fn start_with(
+        routes: &[(&str, &str, &str)],
+        websockets: WebSocketRegistry,
+        setup: impl FnOnce(Server) -> Server,
+    ) -> Self {
+        pyo3::prepare_freethreaded_python();
+
+        let mut router = Router::new();
+        let mut handlers = HandlerRegistry::new();
+        Python::with_gil(|py| {
+            // Handlers may build explicit responses via `Response`
+            let globals = pyo3::types::PyDict::new(py);
+            globals
+                .set_item("Response", py.get_type::<crate::response::Response>())
+                .unwrap();
+            for (method, path, source) in routes {
+                let handler: PyObject = py.eval(source, Some(globals), None).unwrap().into();
+                let (id, meta) = handlers.register_with_meta(handler);
+                router.add_handler_route(method, path, id, meta).unwrap();
+            }
+        });
+
+        let std_listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        std_listener.set_nonblocking(true).unwrap();
+        let addr = std_listener.local_addr().unwrap();
+        let listener = TcpListener::from_std(std_listener).unwrap();
+
+        let server = setup(Server::simple(
+            addr.ip().to_string(),
+            addr.port(),
+            router.clone(),
+            handlers,
+            MiddlewareChain::new(),
+            websockets,
+        ));
+        let task = tokio::spawn(server.serve(listener));
+
+        TestServer {
+            addr,
+            router,
+            task,
+            certificate: None,
+        }
+    }
+
+    /// Server with the routes shared by most conformance cases.
+    async fn standard() -> Self {
+        Self::start(STANDARD_ROUTES).await
+    }
+
+    /// Standard server speaking HTTP/2 as well as HTTP/1.1.
+    async fn http2() -> Self {
+        Self::start_with(STANDARD_ROUTES, WebSocketRegistry::new(), |mut server| {
+            server.config.http2 = Some(Http2Config::new());
+            server
+        })
+        .await
+    }
+
+    /// Standard server terminating TLS with a self-signed certificate for
+    /// `localhost`, configured further by `setup`.
+    async fn tls(setup: impl FnOnce(Server) -> Server) -> Self {
+        let key = acme::KeyPair::generate().unwrap();
+        let now = chrono::Utc::now();
+        let day = chrono::Duration::days(1);
+        let certificate = CertificateDer::from(
+            acme::self_signed(&key, "localhost", now - day, now + day, &[]).unwrap(),
+        );
+        let private_key = rustls_pemfile::private_key(&mut key.to_pem().as_bytes())
+            .unwrap()
+            .unwrap();
+        let store = Arc::new(tls::CertStore::new());
+        store.set_certificate(
+            &[],
+            tls::certified_key_der(vec![certificate.clone()], private_key).unwrap(),
+        );
+
+        let mut server = Self::start_with(STANDARD_ROUTES, WebSocketRegistry::new(), |server| {
+            setup(server.with_certificates(store))
+        })
+        .await;
+        server.certificate = Some(certificate);
+        server
+    }
+
+    /// Open a WebSocket to `path`.
+    async fn websocket_connect(&self, path: &str) -> WebSocketStream<TcpStream> {
+        let stream = TcpStream::connect(self.addr).await.unwrap();
+        let url = format!("ws://{}{path}", self.addr);
+        let (ws, response) = tokio_tungstenite::client_async(url, stream).await.unwrap();
+        assert_eq!(response.status(), 101);
+        ws
+    }
+
+    async fn connect(&self) -> Connection {
+        Connection::new(TcpStream::connect(self.addr).await.unwrap())
+    }
+
+    /// Complete a TLS handshake offering `alpn`, limited to `versions`.
+    async fn connect_tls(
+        &self,
+        alpn: &[&[u8]],
+        versions: &[&'static rustls::SupportedProtocolVersion],
+    ) -> std::io::Result<TlsStream<TcpStream>> {
+        let mut roots = rustls::RootCertStore::empty();
+        roots
+            .add(self.certificate.clone().expect("server does not use TLS"))
+            .unwrap();
+        let mut config = rustls::ClientConfig::builder_with_protocol_versions(versions)
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        config.alpn_protocols = alpn.iter().map(|protocol| protocol.to_vec()).collect();
+
+        let stream = TcpStream::connect(self.addr).await.unwrap();
+        let server_name = ServerName::try_from("localhost").unwrap();
+        tokio_rustls::TlsConnector::from(Arc::new(config))
+            .connect(server_name, stream)
+            .await
+    }
+
+    /// Open an HTTP/2 connection over `io`, which must already be negotiated.
+    async fn http2_connect<S>(io: S) -> hyper::client::conn::http2::SendRequest<Full<Bytes>>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let (sender, connection) =
+            hyper::client::conn::http2::handshake(TokioExecutor::new(), TokioIo::new(io))
+                .await
+                .unwrap();
+        tokio::spawn(connection);
+        sender
+    }
+}
+
+/// `(method, path, python_lambda_source)` routes shared by most conformance cases.
+const STANDARD_ROUTES: &[(&str, &str, &str)] = &[
+    ("GET", "/ping", "lambda req: {'pong': True}"),
+    ("POST", "/echo", "lambda req: {'len': len(req.body())}"),
+    ("GET", "/users/{id}", "lambda req: {'id': req.params['id']}"),
+];
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// A parsed HTTP/1.1 response as read off the wire.
+#[derive(Debug)]
+struct RawResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl RawResponse {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn json(&self) -> serde_json::Value {
+        serde_json::from_slice(&self.body).unwrap()
+    }
+}
+
+/// Client side of a raw connection, buffering bytes across pipelined responses.
+struct Connection<S = TcpStream> {
+    stream: S,
+    buf: Vec<u8>,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> Connection<S> {
+    fn new(stream: S) -> Self {
+        Connection {
+            stream,
+            buf: Vec::new(),
+        }
+    }
+
+    async fn send(&mut self, raw: &[u8]) {
+        self.stream.write_all(raw).await.unwrap();
+    }
+
+    /// Read more bytes into the buffer; returns false on EOF.
+    async fn fill(&mut self) -> bool {
+        let mut chunk = [0u8; 4096];
+        let n = tokio::time::timeout(IO_TIMEOUT, self.stream.read(&mut chunk))
+            .await
+            .expect("timed out waiting for server")
+            .unwrap_or(0);
+        self.buf.extend_from_slice(&chunk[..n]);
+        n > 0
+    }
+
+    /// Read one response (interim 1xx responses are returned on their own).
+    async fn read_response(&mut self) -> Option<RawResponse> {
+        self.read_response_inner(true).await
+    }
+
+    /// Read the response to a HEAD request, which never carries a body.
+    async fn read_head_response(&mut self) -> Option<RawResponse> {
+        self.read_response_inner(false).await
+    }
+
+    async fn read_response_inner(&mut self, expect_body: bool) -> Option<RawResponse> {
+        let head_end = loop {
+            if let Some(pos) = self.buf.windows(4).position(|w| w == b"\r\n\r\n") {
+                break pos;
+            }
+            if !self.fill().await {
+                return None;
+            }
+        };
+
+        let head = String::from_utf8(self.buf[..head_end].to_vec()).unwrap();
+        self.buf.drain(..head_end + 4);
+
+        let mut lines = head.split("\r\n");
+        let status = lines
+            .next()
+            .and_then(|line| line.split_whitespace().nth(1))
+            .and_then(|code| code.parse().ok())
+            .expect("malformed status line");
+        let headers: Vec<(String, String)> = lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+            .collect();
+
+        let mut response = RawResponse {
+            status,
+            headers,
+            body: Vec::new(),
+        };
+
+        let length: usize = if expect_body {
+            response
+                .header("content-length")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0)
+        } else {
+            0
+        };
+        while self.buf.len() < length {
+            if !self.fill().await {
+                break;
+            }
+        }
+        let take = length.min(self.buf.len());
+        response.body = self.buf.drain(..take).collect();
+
+        Some(response)
+    }
+
+    /// Send a request and read its response.
+    async fn roundtrip(&mut self, raw: &[u8]) -> RawResponse {
+        self.send(raw).await;
+        self.read_response().await.expect("connection closed")
+    }
+}
+
+// ============================================================================
+// Conformance Cases
+// ============================================================================
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_basic_get_and_params() {
+    let server = TestServer::standard().await;
+    let mut conn = server.connect().await;
+
+    let res = conn
+        .roundtrip(b"GET /ping HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .await;
+    assert_eq!(res.status, 200);
+    assert_eq!(res.json(), serde_json::json!({"pong": true}));
+
+    let res = conn
+        .roundtrip(b"GET /users/42 HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .await;
+    assert_eq!(res.status, 200);
+    assert_eq!(res.json(), serde_json::json!({"id": "42"}));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_not_found() {
+    let server = TestServer::standard().await;
+    let mut conn = server.connect().await;
+
+    let res = conn
+        .roundtrip(b"GET /missing HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .await;
+    assert_eq!(res.status, 404);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_keep_alive() {
+    let server = TestServer::standard().await;
+    let mut conn = server.connect().await;
+
+    for _ in 0..3 {
+        let res = conn
+            .roundtrip(b"GET /ping HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await;
+        assert_eq!(res.status, 200);
+        assert_ne!(res.header("connection"), Some("close"));
+    }
+
+    // An explicit close is honored after the response is written
+    let res = conn
+        .roundtrip(b"GET /ping HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .await;
+    assert_eq!(res.status, 200);
+    assert!(conn.read_response().await.is_none());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_content_length_body() {
+    let server = TestServer::standard().await;
+    let mut conn = server.connect().await;
+
+    let res = conn
+        .roundtrip(
+            b"POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Length: 11\r\n\r\nhello world",
+        )
+        .await;
+    assert_eq!(res.status, 200);
+    assert_eq!(res.json(), serde_json::json!({"len": 11}));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_chunked_body() {
+    let server = TestServer::standard().await;
+    let mut conn = server.connect().await;
+
+    let res = conn
+        .roundtrip(
+            b"POST /echo HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n\
+              5\r\nhello\r\n6\r\n world\r\n0\r\n\r\n",
+        )
+        .await;
+    assert_eq!(res.status, 200);
+    assert_eq!(res.json(), serde_json::json!({"len": 11}));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_expect_100_continue() {
+    let server = TestServer::standard().await;
+    let mut conn = server.connect().await;
+
+    conn.send(b"POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Length: 4\r\nExpect: 100-continue\r\n\r\n")
+        .await;
+    let interim = conn.read_response().await.expect("connection closed");
+    assert_eq!(interim.status, 100);
+
+    let res = conn.roundtrip(b"data").await;
+    assert_eq!(res.status, 200);
+    assert_eq!(res.json(), serde_json::json!({"len": 4}));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_pipelining() {
+    let server = TestServer::standard().await;
+    let mut conn = server.connect().await;
+
+    // Both requests go out in a single write; responses must come back in order
+    conn.send(
+        b"GET /users/1 HTTP/1.1\r\nHost: localhost\r\n\r\n\
+          GET /users/2 HTTP/1.1\r\nHost: localhost\r\n\r\n",
+    )
+    .await;
+
+    let first = conn.read_response().await.expect("connection closed");
+    let second = conn.read_response().await.expect("connection closed");
+    assert_eq!(first.json(), serde_json::json!({"id": "1"}));
+    assert_eq!(second.json(), serde_json::json!({"id": "2"}));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_malformed_request() {
+    let server = TestServer::standard().await;
+
+    for raw in [
+        &b"NOT AN HTTP REQUEST\r\n\r\n"[..],
+        &b"GET /ping HTTP/1.1\r\nBad Header Without Colon\r\n\r\n"[..],
+        &b"POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Length: abc\r\n\r\n"[..],
+    ] {
+        let mut conn = server.connect().await;
+        conn.send(raw).await;
+        // The server must either reject with a 4xx or drop the connection, never dispatch
+        if let Some(res) = conn.read_response().await {
+            assert!((400..500).contains(&res.status), "got {}", res.status);
+        }
+    }
+
+    // The server keeps serving well-formed traffic afterwards
+    let mut conn = server.connect().await;
+    let res = conn
+        .roundtrip(b"GET /ping HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .await;
+    assert_eq!(res.status, 200);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_method_not_allowed() {
+    let server = TestServer::standard().await;
+    let mut conn = server.connect().await;
+
+    let res = conn
+        .roundtrip(b"DELETE /ping HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .await;
+    assert_eq!(res.status, 405);
+    assert_eq!(res.header("allow"), Some("GET, HEAD, OPTIONS"));
+
+    let res = conn
+        .roundtrip(b"GET /echo HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .await;
+    assert_eq!(res.status, 405);
+    assert_eq!(res.header("allow"), Some("OPTIONS, POST"));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_automatic_options() {
+    let server = TestServer::standard().await;
+    let mut conn = server.connect().await;
+
+    let res = conn
+        .roundtrip(b"OPTIONS /users/7 HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .await;
+    assert_eq!(res.status, 204);
+    assert_eq!(res.header("allow"), Some("GET, HEAD, OPTIONS"));
+
+    let res = conn
+        .roundtrip(b"OPTIONS /missing HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .await;
+    assert_eq!(res.status, 404);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_head_uses_get_handler() {
+    let server = TestServer::standard().await;
+    let mut conn = server.connect().await;
+
+    let get = conn
+        .roundtrip(b"GET /users/9 HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .await;
+
+    // HEAD reports the GET representation's length but sends no body; the
+    // following pipelined GET proves no stray body bytes were written.
+    conn.send(
+        b"HEAD /users/9 HTTP/1.1\r\nHost: localhost\r\n\r\n\
+          GET /ping HTTP/1.1\r\nHost: localhost\r\n\r\n",
+    )
+    .await;
+    let head = conn.read_head_response().await.expect("connection closed");
+    assert_eq!(head.status, 200);
+    assert_eq!(
+        head.header("content-length"),
+        Some(get.body.len().to_string().as_str())
+    );
+
+    let next = conn.read_response().await.expect("connection closed");
+    assert_eq!(next.json(), serde_json::json!({"pong": true}));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_schema_validation_rejects_before_handler() {
+    let server = TestServer::start(&[(
+        "POST",
+        "/users/{id}",
+        "lambda req: {'name': req.json()['name']}",
+    )])
+    .await;
+    let handler = server.router.route_handler("POST", "/users/{id}").unwrap();
+    handler
+        .set_validator(
+            crate::validation::RequestValidator::new()
+                .params(&serde_json::json!({
+                    "type": "object",
+                    "properties": {"id": {"type": "integer"}}
+                }))
+                .unwrap()
+                .body(&serde_json::json!({
+                    "type": "object",
+                    "properties": {"name": {"type": "string"}},
+                    "required": ["name"]
+                }))
+                .unwrap(),
+        )
+        .unwrap();
+
+    let mut conn = server.connect().await;
+    let res = conn
+        .roundtrip(b"POST /users/x HTTP/1.1\r\nHost: localhost\r\nContent-Length: 2\r\n\r\n{}")
+        .await;
+    assert_eq!(res.status, 422);
+    let detail = &res.json()["detail"];
+    assert_eq!(detail[0]["loc"], serde_json::json!(["path", "id"]));
+    assert_eq!(detail[1]["loc"], serde_json::json!(["body", "name"]));
+    assert_eq!(detail[1]["type"], "missing");
+
+    let res = conn
+        .roundtrip(
+            b"POST /users/1 HTTP/1.1\r\nHost: localhost\r\nContent-Length: 14\r\n\r\n{\"name\":\"Ada\"}",
+        )
+        .await;
+    assert_eq!(res.status, 200);
+    assert_eq!(res.json(), serde_json::json!({"name": "Ada"}));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_msgpack_and_cbor_negotiation() {
+    let server = TestServer::start(&[
+        ("POST", "/echo", "lambda req: {'got': req.data()}"),
+        ("GET", "/raw", "lambda req: Response.cbor([1, 2])"),
+    ])
+    .await;
+    let mut conn = server.connect().await;
+
+    // MessagePack in ({"a": 1}), MessagePack out ({"got": {"a": 1}})
+    let mut raw = b"POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/msgpack\r\nAccept: application/msgpack\r\nContent-Length: 4\r\n\r\n".to_vec();
+    raw.extend_from_slice(&[0x81, 0xa1, b'a', 0x01]);
+    let res = conn.roundtrip(&raw).await;
+    assert_eq!(res.status, 200);
+    assert_eq!(res.header("content-type"), Some("application/msgpack"));
+    assert_eq!(res.header("vary"), Some("Accept"));
+    assert_eq!(
+        crate::codec::decode_msgpack(&res.body).unwrap(),
+        serde_json::json!({"got": {"a": 1}})
+    );
+
+    // CBOR in, JSON out when the client prefers it
+    let mut raw = b"POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/cbor\r\nAccept: application/json, application/cbor;q=0.5\r\nContent-Length: 4\r\n\r\n".to_vec();
+    raw.extend_from_slice(&[0xa1, 0x61, b'a', 0x02]);
+    let res = conn.roundtrip(&raw).await;
+    assert_eq!(res.json(), serde_json::json!({"got": {"a": 2}}));
+
+    // Explicit binary Response objects keep their bytes intact
+    let res = conn
+        .roundtrip(b"GET /raw HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .await;
+    assert_eq!(res.header("content-type"), Some("application/cbor"));
+    assert_eq!(res.body, vec![0x82, 0x01, 0x02]);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_protobuf_bodies() {
+    use crate::middleware::protobuf::{
+        DescriptorPool, FieldDescriptor, FieldType, MessageDescriptor, ProtoBinding,
+    };
+
+    let server = TestServer::start(&[(
+        "POST",
+        "/items",
+        "lambda req: {'id': req.data()['id'] + 1, 'name': req.data()['name']}",
+    )])
+    .await;
+    let mut pool = DescriptorPool::new();
+    pool.add_message(
+        MessageDescriptor::new("shop.Item")
+            .field(FieldDescriptor::new("id", 1, FieldType::parse("int64")))
+            .field(FieldDescriptor::new("name", 2, FieldType::parse("string"))),
+    )
+    .unwrap();
+    let binding = ProtoBinding::new(
+        std::sync::Arc::new(parking_lot::RwLock::new(pool)),
+        Some("shop.Item"),
+        Some("shop.Item"),
+    )
+    .unwrap();
+    let handler = server.router.route_handler("POST", "/items").unwrap();
+    handler.set_protobuf(binding).unwrap();
+
+    let mut conn = server.connect().await;
+
+    // {id: 1, name: "ab"} in, {id: 2, name: "ab"} out
+    let mut raw = b"POST /items HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/x-protobuf\r\nAccept: application/x-protobuf\r\nContent-Length: 6\r\n\r\n".to_vec();
+    raw.extend_from_slice(&[0x08, 0x01, 0x12, 0x02, b'a', b'b']);
+    let res = conn.roundtrip(&raw).await;
+    assert_eq!(res.status, 200);
+    assert_eq!(res.header("content-type"), Some("application/x-protobuf"));
+    assert_eq!(res.body, vec![0x08, 0x02, 0x12, 0x02, b'a', b'b']);
+
+    // Same route still speaks JSON to JSON clients
+    let mut raw = b"POST /items HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/x-protobuf\r\nContent-Length: 2\r\n\r\n".to_vec();
+    raw.extend_from_slice(&[0x08, 0x07]);
+    let res = conn.roundtrip(&raw).await;
+    assert_eq!(res.json(), serde_json::json!({"id": 8, "name": ""}));
+
+    // Undecodable bodies are rejected before the handler runs
+    let mut raw = b"POST /items HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/x-protobuf\r\nContent-Length: 2\r\n\r\n".to_vec();
+    raw.extend_from_slice(&[0x12, 0x05]);
+    let res = conn.roundtrip(&raw).await;
+    assert_eq!(res.status, 400);
+}
+
+/// Send a request over an HTTP/2 connection and collect its response.
+async fn http2_roundtrip(
+    sender: &mut hyper::client::conn::http2::SendRequest<Full<Bytes>>,
+    method: &str,
+    uri: &str,
+    body: &'static [u8],
+) -> (u16, serde_json::Value) {
+    let request = hyper::Request::builder()
+        .method(method)
+        .uri(uri)
+        .body(Full::new(Bytes::from_static(body)))
+        .unwrap();
+    let response = tokio::time::timeout(IO_TIMEOUT, sender.send_request(request))
+        .await
+        .expect("timed out waiting for server")
+        .unwrap();
+    assert_eq!(response.version(), hyper::Version::HTTP_2);
+    let status = response.status().as_u16();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (status, serde_json::from_slice(&body).unwrap_or_default())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_tls_keep_alive() {
+    let server = TestServer::tls(|server| server).await;
+
+    // Without HTTP/2 enabled only HTTP/1.1 is negotiated, even when offered
+    let stream = server
+        .connect_tls(&[b"h2", b"http/1.1"], rustls::DEFAULT_VERSIONS)
+        .await
+        .unwrap();
+    assert_eq!(stream.get_ref().1.alpn_protocol(), Some(&b"http/1.1"[..]));
+
+    let mut conn = Connection::new(stream);
+    for id in 1..=2 {
+        let res = conn
+            .roundtrip(format!("GET /users/{id} HTTP/1.1\r\nHost: localhost\r\n\r\n").as_bytes())
+            .await;
+        assert_eq!(res.status, 200);
+        assert_eq!(res.json(), serde_json::json!({"id": id.to_string()}));
+    }
+    let res = conn
+        .roundtrip(b"POST /echo HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n0\r\n\r\n")
+        .await;
+    assert_eq!(res.json(), serde_json::json!({"len": 3}));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_tls_rejects_plaintext_and_old_versions() {
+    let server = TestServer::tls(|mut server| {
+        server.config.tls = Some(TlsConfig::new("", "").min_version(TlsVersion::Tls13));
+        server
+    })
+    .await;
+
+    // Plain HTTP on the TLS port fails the handshake and is never dispatched
+    let mut conn = server.connect().await;
+    conn.send(b"GET /ping HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .await;
+    assert!(conn.read_response().await.is_none());
+
+    // Versions below the configured minimum are refused
+    let refused = server
+        .connect_tls(&[b"http/1.1"], &[&rustls::version::TLS12])
+        .await;
+    assert!(refused.is_err());
+
+    let stream = server
+        .connect_tls(&[b"http/1.1"], &[&rustls::version::TLS13])
+        .await
+        .unwrap();
+    let mut conn = Connection::new(stream);
+    let res = conn
+        .roundtrip(b"GET /ping HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .await;
+    assert_eq!(res.json(), serde_json::json!({"pong": true}));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_http2_prior_knowledge() {
+    let server = TestServer::http2().await;
+    let stream = TcpStream::connect(server.addr).await.unwrap();
+    let mut sender = TestServer::http2_connect(stream).await;
+
+    let (status, body) = http2_roundtrip(&mut sender, "GET", "http://localhost/ping", b"").await;
+    assert_eq!((status, body), (200, serde_json::json!({"pong": true})));
+    let (status, body) =
+        http2_roundtrip(&mut sender, "POST", "http://localhost/echo", b"hello world").await;
+    assert_eq!((status, body), (200, serde_json::json!({"len": 11})));
+    let (status, _) = http2_roundtrip(&mut sender, "GET", "http://localhost/missing", b"").await;
+    assert_eq!(status, 404);
+
+    // Streams on one connection are served concurrently
+    let responses = futures_util::future::join_all((0..8).map(|id| {
+        let mut sender = sender.clone();
+        async move {
+            let uri = format!("http://localhost/users/{id}");
+            http2_roundtrip(&mut sender, "GET", &uri, b"").await
+        }
+    }))
+    .await;
+    for (id, response) in responses.into_iter().enumerate() {
+        assert_eq!(response, (200, serde_json::json!({"id": id.to_string()})));
+    }
+
+    // HTTP/1.1 clients are still served on the same listener
+    let mut conn = server.connect().await;
+    let res = conn
+        .roundtrip(b"GET /ping HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .await;
+    assert_eq!(res.status, 200);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_http2_over_tls() {
+    let server = TestServer::tls(|mut server| {
+        server.config.http2 = Some(Http2Config::new());
+        server
+    })
+    .await;
+
+    let stream = server
+        .connect_tls(&[b"h2", b"http/1.1"], rustls::DEFAULT_VERSIONS)
+        .await
+        .unwrap();
+    assert_eq!(stream.get_ref().1.alpn_protocol(), Some(&b"h2"[..]));
+    let mut sender = TestServer::http2_connect(stream).await;
+    let (status, body) =
+        http2_roundtrip(&mut sender, "GET", "https://localhost/users/5", b"").await;
+    assert_eq!((status, body), (200, serde_json::json!({"id": "5"})));
+
+    // Clients that only speak HTTP/1.1 still get it
+    let stream = server
+        .connect_tls(&[b"http/1.1"], rustls::DEFAULT_VERSIONS)
+        .await
+        .unwrap();
+    let mut conn = Connection::new(stream);
+    let res = conn
+        .roundtrip(b"GET /ping HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .await;
+    assert_eq!(res.status, 200);
+}
+
+/// Echoes text messages until the client closes the connection.
+const WEBSOCKET_ECHO: &str = "
+def handler(ws):
+    while (msg := ws.recv()) is not None:
+        ws.send_text(msg.text)
+";
+
+/// The next frame that is not a ping, which the client answers as it reads.
+async fn next_message(ws: &mut WebSocketStream<TcpStream>) -> Option<Message> {
+    use futures_util::StreamExt;
+
+    loop {
+        match tokio::time::timeout(IO_TIMEOUT, ws.next())
+            .await
+            .expect("timed out waiting for server")
+        {
+            Some(Ok(Message::Ping(_))) => continue,
+            Some(Ok(message)) => return Some(message),
+            _ => return None,
+        }
+    }
+}
+
+/// The code and reason of the close frame ending the connection.
+async fn close_frame(ws: &mut WebSocketStream<TcpStream>) -> (CloseCode, String) {
+    match next_message(ws).await {
+        Some(Message::Close(Some(frame))) => (frame.code, frame.reason.into_owned()),
+        other => panic!("expected a close frame, got {other:?}"),
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_websocket_heartbeats_and_size_limit() {
+    use futures_util::SinkExt;
+
+    let config = WebSocketConfig::default()
+        .with_ping_interval(Duration::from_millis(50))
+        .with_pong_timeout(Duration::from_millis(100))
+        .with_max_message_size(8);
+    let server = TestServer::websocket(WEBSOCKET_ECHO, config).await;
+
+    // A client that keeps reading answers the pings and stays connected
+    let mut ws = server.websocket_connect("/ws").await;
+    let deadline = tokio::time::Instant::now() + Duration::from_millis(300);
+    let mut pings = 0;
+    while let Ok(frame) =
+        tokio::time::timeout_at(deadline, futures_util::StreamExt::next(&mut ws)).await
+    {
+        match frame {
+            Some(Ok(Message::Ping(_))) => pings += 1,
+            other => panic!("unexpected frame {other:?}"),
+        }
+    }
+    assert!(pings >= 2, "got {pings} pings");
+    ws.send(Message::Text("hi".into())).await.unwrap();
+    assert_eq!(
+        next_message(&mut ws).await,
+        Some(Message::Text("hi".into()))
+    );
+
+    ws.send(Message::Text("far too long".into())).await.unwrap();
+    assert_eq!(
+        close_frame(&mut ws).await,
+        (CloseCode::Size, "message_too_big".to_string())
+    );
+
+    // One that never reads never answers a ping, and is closed
+    let mut ws = server.websocket_connect("/ws").await;
+    tokio::time::sleep(Duration::from_millis(400)).await;
+    assert_eq!(
+        close_frame(&mut ws).await,
+        (CloseCode::Away, "pong_timeout".to_string())
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_websocket_idle_timeout() {
+    use futures_util::SinkExt;
+
+    let config = WebSocketConfig::default().with_idle_timeout(Duration::from_millis(200));
+    let server = TestServer::websocket(WEBSOCKET_ECHO, config).await;
+    let mut ws = server.websocket_connect("/ws").await;
+
+    // Messages from the client keep the connection alive
+    for _ in 0..3 {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        ws.send(Message::Text("hi".into())).await.unwrap();
+        assert_eq!(
+            next_message(&mut ws).await,
+            Some(Message::Text("hi".into()))
+        );
+    }
+
+    let started = std::time::Instant::now();
+    assert_eq!(
+        close_frame(&mut ws).await,
+        (CloseCode::Away, "idle_timeout".to_string())
+    );
+    assert!(started.elapsed() < Duration::from_secs(2));
+}
+
+/// A masked client frame, as RFC 6455 requires of clients unless `masked` is false.
+fn websocket_frame(fin: bool, opcode: u8, payload: &[u8], masked: bool) -> Vec<u8> {
+    assert!(payload.len() < 126);
+    let mask = [0x37, 0xfa, 0x21, 0x3d];
+    let mut frame = vec![(u8::from(fin) << 7) | opcode];
+    if masked {
+        frame.push(0x80 | payload.len() as u8);
+        frame.extend_from_slice(&mask);
+        frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+    } else {
+        frame.push(payload.len() as u8);
+        frame.extend_from_slice(payload);
+    }
+    frame
+}
+
+impl Connection {
+    /// Read one short, unmasked server frame as `(first byte, payload)`.
+    async fn read_websocket_frame(&mut self) -> (u8, Vec<u8>) {
+        while self.buf.len() < 2 || self.buf.len() < 2 + usize::from(self.buf[1] & 0x7f) {
+            assert!(self.fill().await, "connection closed");
+        }
+        let len = usize::from(self.buf[1] & 0x7f);
+        assert!(len < 126 && self.buf[1] & 0x80 == 0);
+        let first = self.buf[0];
+        let payload = self.buf.drain(..2 + len).skip(2).collect();
+        (first, payload)
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_websocket_handshake() {
+    let server = TestServer::websocket(WEBSOCKET_ECHO, WebSocketConfig::default()).await;
+    let upgrade =
+        "GET /ws HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n";
+
+    // The accept key is derived from the client's key (RFC 6455 section 1.3)
+    let mut conn = server.connect().await;
+    let res = conn
+        .roundtrip(format!("{upgrade}Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n").as_bytes())
+        .await;
+    assert_eq!(res.status, 101);
+    assert_eq!(res.header("upgrade"), Some("websocket"));
+    assert_eq!(
+        res.header("sec-websocket-accept"),
+        Some("s3pPLMBiTxaQ9kYGzzhZRbK+xOo=")
+    );
+    assert_eq!(res.header("sec-websocket-extensions"), None);
+
+    // Unsupported versions are told which one the server speaks
+    let mut conn = server.connect().await;
+    let res = conn
+        .roundtrip(format!("{upgrade}Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 8\r\n\r\n").as_bytes())
+        .await;
+    assert_eq!(res.status, 426);
+    assert_eq!(res.header("sec-websocket-version"), Some("13"));
+
+    let mut conn = server.connect().await;
+    let res = conn
+        .roundtrip(format!("{upgrade}Sec-WebSocket-Version: 13\r\n\r\n").as_bytes())
+        .await;
+    assert_eq!(res.status, 400);
+
+    // Paths without a WebSocket handler fall through to the HTTP routes
+    let mut conn = server.connect().await;
+    let res = conn
+        .roundtrip(b"GET /elsewhere HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n")
+        .await;
+    assert_eq!(res.status, 404);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_websocket_framing() {
+    let server = TestServer::websocket(WEBSOCKET_ECHO, WebSocketConfig::default()).await;
+    let upgrade = b"GET /ws HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n";
+    let mut conn = server.connect().await;
+    assert_eq!(conn.roundtrip(upgrade).await.status, 101);
+
+    // A fragmented message, with a ping between its fragments, is reassembled
+    conn.send(&websocket_frame(false, 0x1, b"hel", true)).await;
+    conn.send(&websocket_frame(true, 0x9, b"beat", true)).await;
+    conn.send(&websocket_frame(true, 0x0, b"lo", true)).await;
+    assert_eq!(conn.read_websocket_frame().await, (0x8a, b"beat".to_vec()));
+    assert_eq!(conn.read_websocket_frame().await, (0x81, b"hello".to_vec()));
+
+    // A close frame is echoed with its status code
+    conn.send(&websocket_frame(true, 0x8, &1000u16.to_be_bytes(), true))
+        .await;
+    assert_eq!(
+        conn.read_websocket_frame().await,
+        (0x88, 1000u16.to_be_bytes().to_vec())
+    );
+
+    // Unmasked client frames are a protocol error
+    let mut conn = server.connect().await;
+    assert_eq!(conn.roundtrip(upgrade).await.status, 101);
+    conn.send(&websocket_frame(true, 0x1, b"hi", false)).await;
+    assert_eq!(
+        conn.read_websocket_frame().await,
+        (0x88, 1002u16.to_be_bytes().to_vec())
+    );
+
+    // So is text that is not UTF-8, which closes with 1007
+    let mut conn = server.connect().await;
+    assert_eq!(conn.roundtrip(upgrade).await.status, 101);
+    conn.send(&websocket_frame(true, 0x1, &[0xff, 0xfe], true))
+        .await;
+    let (first, payload) = conn.read_websocket_frame().await;
+    assert_eq!((first, &payload[..2]), (0x88, &1007u16.to_be_bytes()[..]));
+}
@@ -0,0 +1,214 @@
+//! Connection-level metrics.
+//!
+//! Counts the connections accepted and the requests that reused a kept-alive
+//! connection, and observes how long connections stay open, how many
+//! requests each one carries and how long TLS handshakes take. Together
+//! they show whether `keep_alive` and `max_connections` suit the traffic:
+//! short connections carrying one request each point at clients or proxies
+//! that do not reuse connections, long idle ones at a keep-alive timeout
+//! that holds connection slots for too long.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use prometheus::{Histogram, HistogramOpts, IntCounter, Registry};
+
+/// Connection duration buckets, in seconds.
+const DURATION_BUCKETS: [f64; 12] = [
+    0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0, 600.0,
+];
+
+/// Requests-per-connection buckets.
+const REQUEST_BUCKETS: [f64; 10] = [1.0, 2.0, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0];
+
+/// TLS handshake duration buckets, in seconds.
+const HANDSHAKE_BUCKETS: [f64; 10] = [0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0];
+
+/// Metrics of the connections a server accepts.
+pub struct ConnectionMetrics {
+    accepted: IntCounter,
+    reused: IntCounter,
+    duration: Histogram,
+    requests: Histogram,
+    tls_handshake: Histogram,
+}
+
+impl ConnectionMetrics {
+    /// Create empty connection metrics.
+    pub fn new() -> Self {
+        let histogram = |name: &str, help: &str, buckets: &[f64]| {
+            Histogram::with_opts(HistogramOpts::new(name, help).buckets(buckets.to_vec()))
+                .expect("valid histogram options")
+        };
+        let counter =
+            |name: &str, help: &str| IntCounter::new(name, help).expect("valid counter options");
+        Self {
+            accepted: counter("cello_connections_total", "Connections accepted"),
+            reused: counter(
+                "cello_connection_reused_requests_total",
+                "Requests served on a kept-alive connection after its first request",
+            ),
+            duration: histogram(
+                "cello_connection_duration_seconds",
+                "How long connections stay open, in seconds",
+                &DURATION_BUCKETS,
+            ),
+            requests: histogram(
+                "cello_connection_requests",
+                "Requests served per connection",
+                &REQUEST_BUCKETS,
+            ),
+            tls_handshake: histogram(
+                "cello_tls_handshake_duration_seconds",
+                "Time to complete TLS handshakes, in seconds",
+                &HANDSHAKE_BUCKETS,
+            ),
+        }
+    }
+
+    /// Track a newly accepted connection until the returned handle drops.
+    pub fn open(self: &Arc<Self>) -> Connection {
+        self.accepted.inc();
+        Connection {
+            metrics: self.clone(),
+            opened: Instant::now(),
+            requests: AtomicU64::new(0),
+        }
+    }
+
+    /// Observe the time taken by a TLS handshake.
+    pub fn record_tls_handshake(&self, duration: Duration) {
+        self.tls_handshake.observe(duration.as_secs_f64());
+    }
+
+    /// Expose the connection metrics in a Prometheus registry.
+    pub fn register_metrics(&self, registry: &Registry) -> Result<(), String> {
+        registry
+            .register(Box::new(self.accepted.clone()))
+            .and_then(|_| registry.register(Box::new(self.reused.clone())))
+            .and_then(|_| registry.register(Box::new(self.duration.clone())))
+            .and_then(|_| registry.register(Box::new(self.requests.clone())))
+            .and_then(|_| registry.register(Box::new(self.tls_handshake.clone())))
+            .map_err(|e| e.to_string())
+    }
+
+    /// Totals and averages of the connections closed so far.
+    pub fn snapshot(&self) -> ConnectionStats {
+        let average = |histogram: &Histogram| {
+            let count = histogram.get_sample_count();
+            if count == 0 {
+                0.0
+            } else {
+                histogram.get_sample_sum() / count as f64
+            }
+        };
+        ConnectionStats {
+            accepted: self.accepted.get(),
+            closed: self.duration.get_sample_count(),
+            reused_requests: self.reused.get(),
+            avg_duration_secs: average(&self.duration),
+            avg_requests_per_connection: average(&self.requests),
+            tls_handshakes: self.tls_handshake.get_sample_count(),
+            avg_tls_handshake_ms: average(&self.tls_handshake) * 1000.0,
+        }
+    }
+}
+
+impl Default for ConnectionMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An open connection. Its duration and request count are observed when
+/// it drops.
+pub struct Connection {
+    metrics: Arc<ConnectionMetrics>,
+    opened: Instant,
+    requests: AtomicU64,
+}
+
+impl Connection {
+    /// Count a request received on this connection.
+    #[inline]
+    pub fn request(&self) {
+        if self.requests.fetch_add(1, Ordering::Relaxed) > 0 {
+            self.metrics.reused.inc();
+        }
+    }
+
+    /// Observe the time taken by this connection's TLS handshake.
+    pub fn record_tls_handshake(&self, duration: Duration) {
+        self.metrics.record_tls_handshake(duration);
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.metrics
+            .duration
+            .observe(self.opened.elapsed().as_secs_f64());
+        self.metrics
+            .requests
+            .observe(self.requests.load(Ordering::Relaxed) as f64);
+    }
+}
+
+/// Connection totals and averages.
+#[derive(Clone, Debug, Default, serde::Serialize)]
+pub struct ConnectionStats {
+    /// Connections accepted
+    pub accepted: u64,
+    /// Connections closed
+    pub closed: u64,
+    /// Requests served on a kept-alive connection after its first request
+    pub reused_requests: u64,
+    /// Average lifetime of closed connections
+    pub avg_duration_secs: f64,
+    /// Average requests served per closed connection
+    pub avg_requests_per_connection: f64,
+    /// TLS handshakes completed
+    pub tls_handshakes: u64,
+    /// Average TLS handshake time
+    pub avg_tls_handshake_ms: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connection_stats() {
+        let metrics = Arc::new(ConnectionMetrics::new());
+        let first = metrics.open();
+        for _ in 0..3 {
+            first.request();
+        }
+        let second = metrics.open();
+        second.request();
+        drop(first);
+
+        let stats = metrics.snapshot();
+        assert_eq!(stats.accepted, 2);
+        assert_eq!(stats.closed, 1);
+        assert_eq!(stats.reused_requests, 2);
+        assert_eq!(stats.avg_requests_per_connection, 3.0);
+
+        drop(second);
+        metrics.record_tls_handshake(Duration::from_millis(4));
+        let stats = metrics.snapshot();
+        assert_eq!(stats.avg_requests_per_connection, 2.0);
+        assert_eq!(stats.tls_handshakes, 1);
+        assert!((stats.avg_tls_handshake_ms - 4.0).abs() < 1e-9);
+
+        let registry = Registry::new();
+        metrics.register_metrics(&registry).unwrap();
+        let names: Vec<_> = registry
+            .gather()
+            .iter()
+            .map(|family| family.get_name().to_string())
+            .collect();
+        assert!(names.contains(&"cello_connection_requests".to_string()));
+    }
+}
@@ -0,0 +1,176 @@
+//! Response integrity checks.
+//!
+//! Optional checks on buffered responses, applied as they are built:
+//! - `Content-Length` is set from the body, replacing a header that
+//!   disagrees with it (a wrong length hangs or truncates clients)
+//! - a `Content-MD5` (RFC 1864) or `Digest` (RFC 3230) header carries a
+//!   checksum of the body
+//! - a body over the size limit is logged and replaced by a 500, so a
+//!   handler that accidentally returns a huge payload doesn't send it
+//!
+//! Streamed and file responses are never buffered, so they are not checked.
+
+use std::collections::HashMap;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use md5::Md5;
+use sha2::{Digest, Sha256};
+
+/// Checksum sent with response bodies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DigestAlgorithm {
+    /// `Content-MD5: <base64>`
+    Md5,
+    /// `Digest: sha-256=<base64>`
+    Sha256,
+}
+
+impl DigestAlgorithm {
+    /// Parse an algorithm name, `"md5"` or `"sha-256"`.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "md5" => Some(DigestAlgorithm::Md5),
+            "sha-256" | "sha256" => Some(DigestAlgorithm::Sha256),
+            _ => None,
+        }
+    }
+
+    /// The header carrying the checksum.
+    pub fn header(&self) -> &'static str {
+        match self {
+            DigestAlgorithm::Md5 => "Content-MD5",
+            DigestAlgorithm::Sha256 => "Digest",
+        }
+    }
+
+    /// The header value for `body`.
+    pub fn value(&self, body: &[u8]) -> String {
+        match self {
+            DigestAlgorithm::Md5 => STANDARD.encode(Md5::digest(body)),
+            DigestAlgorithm::Sha256 => format!("sha-256={}", STANDARD.encode(Sha256::digest(body))),
+        }
+    }
+}
+
+/// Checks applied to buffered responses.
+#[derive(Debug, Clone)]
+pub struct ResponseChecks {
+    /// Set `Content-Length` from the body
+    pub content_length: bool,
+    /// Send a checksum of the body
+    pub digest: Option<DigestAlgorithm>,
+    /// Largest body sent, in bytes
+    pub max_body_size: Option<usize>,
+}
+
+impl Default for ResponseChecks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ResponseChecks {
+    /// Enforce `Content-Length`, without a checksum or size limit.
+    pub fn new() -> Self {
+        Self {
+            content_length: true,
+            digest: None,
+            max_body_size: None,
+        }
+    }
+
+    /// Set whether `Content-Length` is set from the body.
+    pub fn content_length(mut self, enabled: bool) -> Self {
+        self.content_length = enabled;
+        self
+    }
+
+    /// Send a checksum of the body.
+    pub fn digest(mut self, algorithm: DigestAlgorithm) -> Self {
+        self.digest = Some(algorithm);
+        self
+    }
+
+    /// Refuse to send bodies over `bytes`.
+    pub fn max_body_size(mut self, bytes: usize) -> Self {
+        self.max_body_size = Some(bytes);
+        self
+    }
+
+    /// The headers to send with `body`, replacing any of the same names in
+    /// `headers`, or `Err(limit)` when the body is over the size limit.
+    ///
+    /// 204 and 304 responses have no body of their own, so they get no
+    /// headers.
+    pub fn check(
+        &self,
+        status: u16,
+        headers: &HashMap<String, String>,
+        body: &[u8],
+    ) -> Result<Vec<(&'static str, String)>, usize> {
+        if let Some(limit) = self.max_body_size.filter(|&limit| body.len() > limit) {
+            return Err(limit);
+        }
+        let mut checked = Vec::new();
+        if status == 204 || status == 304 {
+            return Ok(checked);
+        }
+        if self.content_length {
+            let length = body.len().to_string();
+            let declared = headers
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+                .map(|(_, value)| value.trim());
+            if let Some(declared) = declared.filter(|declared| *declared != length) {
+                eprintln!(
+                    "Response Content-Length {declared} does not match its {length} byte body; sending {length}"
+                );
+            }
+            checked.push(("Content-Length", length));
+        }
+        if let Some(digest) = self.digest {
+            checked.push((digest.header(), digest.value(body)));
+        }
+        Ok(checked)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checked_headers() {
+        let checks = ResponseChecks::new().digest(DigestAlgorithm::Md5);
+        let mut headers = HashMap::new();
+        headers.insert("content-length".to_string(), "99".to_string());
+
+        let checked = checks.check(200, &headers, b"hello").unwrap();
+        assert_eq!(
+            checked,
+            vec![
+                ("Content-Length", "5".to_string()),
+                ("Content-MD5", "XUFAKrxLKna5cZ2REBfFkg==".to_string()),
+            ]
+        );
+        assert!(checks.check(204, &headers, b"").unwrap().is_empty());
+
+        let sha = DigestAlgorithm::parse("SHA-256").unwrap();
+        assert_eq!(
+            sha.value(b"hello"),
+            "sha-256=LPJNul+wow4m6DsqxbninhsWHlwfp0JecwQzYpOLmCQ="
+        );
+        assert_eq!(DigestAlgorithm::parse("crc32"), None);
+    }
+
+    #[test]
+    fn test_body_size_limit() {
+        let checks = ResponseChecks::new().content_length(false).max_body_size(4);
+        assert_eq!(checks.check(200, &HashMap::new(), b"hello"), Err(4));
+        assert!(checks
+            .check(200, &HashMap::new(), b"hi")
+            .unwrap()
+            .is_empty());
+    }
+}
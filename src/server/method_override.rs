@@ -0,0 +1,166 @@
+//! HTTP method override.
+//!
+//! Clients that can only send GET and POST (HTML forms, some proxies and
+//! SDKs) ask for another method with an `X-HTTP-Method-Override` header or
+//! a `_method` field in a urlencoded form. The override is applied before
+//! routing, so the request reaches the PUT, PATCH or DELETE handler as if
+//! it had been sent with that method.
+//!
+//! Only POST requests are overridden, and only to methods in the allowlist.
+//! Safe methods (GET, HEAD, OPTIONS) are never allowed as targets: a POST
+//! turned into a GET or HEAD could be cached or replayed as a safe request.
+//! Overrides to other methods are ignored and the request stays a POST.
+
+use hyper::header::CONTENT_TYPE;
+use hyper::{HeaderMap, Method};
+
+use crate::multipart::{parse_urlencoded_pairs, DEFAULT_MAX_FORM_FIELDS};
+
+/// Methods an override can never target.
+const EXCLUDED: [Method; 5] = [
+    Method::GET,
+    Method::HEAD,
+    Method::OPTIONS,
+    Method::POST,
+    Method::CONNECT,
+];
+
+/// Request extension marking an overridden request, with the method it
+/// was sent with.
+#[derive(Debug, Clone)]
+pub struct OriginalMethod(pub Method);
+
+/// Method override configuration.
+#[derive(Debug, Clone)]
+pub struct MethodOverride {
+    /// Methods a POST may be overridden to
+    pub methods: Vec<Method>,
+    /// Header carrying the method, `X-HTTP-Method-Override` by default
+    pub header: Option<String>,
+    /// Urlencoded form field carrying the method, `_method` by default
+    pub form_field: Option<String>,
+}
+
+impl Default for MethodOverride {
+    fn default() -> Self {
+        Self {
+            methods: vec![Method::PUT, Method::PATCH, Method::DELETE],
+            header: Some("x-http-method-override".to_string()),
+            form_field: Some("_method".to_string()),
+        }
+    }
+}
+
+impl MethodOverride {
+    /// Allow overrides to PUT, PATCH and DELETE from the default header and
+    /// form field.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allow overrides to `methods` only.
+    pub fn methods(mut self, methods: &[&str]) -> Result<Self, String> {
+        self.methods = methods
+            .iter()
+            .map(|name| {
+                let method = Method::from_bytes(name.to_ascii_uppercase().as_bytes())
+                    .map_err(|_| format!("invalid method '{name}'"))?;
+                if EXCLUDED.contains(&method) {
+                    return Err(format!("a POST cannot be overridden to {method}"));
+                }
+                Ok(method)
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(self)
+    }
+
+    /// Read the method from `name`, or from no header.
+    pub fn header(mut self, name: Option<&str>) -> Self {
+        self.header = name.map(str::to_ascii_lowercase);
+        self
+    }
+
+    /// Read the method from the form field `name`, or from no field.
+    pub fn form_field(mut self, name: Option<&str>) -> Self {
+        self.form_field = name.map(str::to_string);
+        self
+    }
+
+    /// Whether a request with `method` may be overridden.
+    pub fn applies(&self, method: &Method) -> bool {
+        *method == Method::POST
+    }
+
+    /// The allowed method named by the override header.
+    pub fn from_header(&self, headers: &HeaderMap) -> Option<Method> {
+        let value = headers.get(self.header.as_deref()?)?.to_str().ok()?;
+        self.allowed(value)
+    }
+
+    /// Whether the method may be in the form body, so it must be read
+    /// before routing.
+    pub fn reads_form(&self, headers: &HeaderMap) -> bool {
+        self.form_field.is_some()
+            && headers
+                .get(CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .is_some_and(|ct| ct.starts_with("application/x-www-form-urlencoded"))
+    }
+
+    /// The allowed method named by the form field in `body`.
+    pub fn from_form(&self, body: &[u8]) -> Option<Method> {
+        let field = self.form_field.as_deref()?;
+        let fields = parse_urlencoded_pairs(body, DEFAULT_MAX_FORM_FIELDS).ok()?;
+        let (_, value) = fields.iter().find(|(name, _)| name == field)?;
+        self.allowed(value)
+    }
+
+    fn allowed(&self, name: &str) -> Option<Method> {
+        let name = name.trim().to_ascii_uppercase();
+        self.methods
+            .iter()
+            .find(|method| method.as_str() == name)
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_and_form_override() {
+        let overrides = MethodOverride::new();
+        assert!(overrides.applies(&Method::POST));
+        assert!(!overrides.applies(&Method::GET));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-http-method-override", "delete".parse().unwrap());
+        assert_eq!(overrides.from_header(&headers), Some(Method::DELETE));
+        headers.insert("x-http-method-override", "GET".parse().unwrap());
+        assert_eq!(overrides.from_header(&headers), None);
+
+        headers.insert(
+            CONTENT_TYPE,
+            "application/x-www-form-urlencoded; charset=utf-8"
+                .parse()
+                .unwrap(),
+        );
+        assert!(overrides.reads_form(&headers));
+        assert_eq!(
+            overrides.from_form(b"name=ann&_method=PUT"),
+            Some(Method::PUT)
+        );
+        assert_eq!(overrides.from_form(b"_method=TRACE"), None);
+        assert!(!overrides.clone().form_field(None).reads_form(&headers));
+    }
+
+    #[test]
+    fn test_allowlist() {
+        let overrides = MethodOverride::new().methods(&["delete"]).unwrap();
+        assert_eq!(overrides.allowed("DELETE"), Some(Method::DELETE));
+        assert_eq!(overrides.allowed("PUT"), None);
+        assert!(MethodOverride::new().methods(&["HEAD"]).is_err());
+        assert!(MethodOverride::new().methods(&["NOT A METHOD"]).is_err());
+    }
+}
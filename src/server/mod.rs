@@ -8,15 +8,32 @@
 //! - TLS configuration
 //! - Server metrics
 
+pub mod acme;
 pub mod cluster;
+pub mod completion;
+pub mod connections;
+pub mod integrity;
+pub mod method_override;
 pub mod protocols;
+pub mod recording;
+pub mod redirect;
+pub mod stats;
+pub mod test_client;
+pub mod throttle;
+pub mod timing;
+pub mod tls;
+mod websocket;
+
+#[cfg(test)]
+mod conformance;
 
 use bytes::Bytes;
 use http_body_util::{BodyExt, Full};
 use hyper::server::conn::http1;
 use hyper::service::service_fn;
-use hyper::{body::Incoming, Request as HyperRequest, Response as HyperResponse, StatusCode};
-use hyper_util::rt::TokioIo;
+use hyper::{Request as HyperRequest, Response as HyperResponse, StatusCode};
+use hyper_util::rt::{TokioExecutor, TokioIo, TokioTimer};
+use hyper_util::server::conn::auto;
 use parking_lot::RwLock;
 use pyo3::prelude::*;
 use std::collections::HashMap;
@@ -27,13 +44,15 @@ use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::net::TcpListener;
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, mpsc};
 
 use crate::handler::{HandlerRegistry, HandlerResult};
+use crate::middleware::idempotency::Admission;
 use crate::middleware::{MiddlewareAction, MiddlewareChain};
-use crate::request::Request;
+use crate::request::{Headers, QueryArraySyntax, Request};
 use crate::response::Response;
 use crate::router::Router;
+use crate::routing::CanaryVariant;
 use crate::websocket::WebSocketRegistry;
 
 pub use cluster::{ClusterConfig, ClusterManager};
@@ -74,6 +93,26 @@ pub struct ServerConfig {
     pub http3: Option<Http3Config>,
     /// Cluster configuration
     pub cluster: Option<ClusterConfig>,
+    /// Bandwidth throttling and slow-client protection
+    pub throttle: Option<throttle::ThrottleConfig>,
+    /// Log requests that take longer than this
+    pub slow_request_threshold: Option<Duration>,
+    /// Send each request's phase times in a `Server-Timing` header
+    pub server_timing: bool,
+    /// How repeated query parameters are written
+    pub query_array_syntax: QueryArraySyntax,
+    /// Length, checksum and size checks on buffered responses
+    pub response_checks: Option<integrity::ResponseChecks>,
+    /// Path serving the aggregated statistics snapshot
+    pub stats_endpoint: Option<String>,
+    /// Records sampled request/response pairs per route
+    pub recorder: Option<Arc<recording::Recorder>>,
+    /// Tag JSON responses with an ETag of their body
+    pub json_etags: bool,
+    /// Lets POST requests ask for another method
+    pub method_override: Option<method_override::MethodOverride>,
+    /// Plain HTTP listener redirecting to this (HTTPS) server
+    pub redirect_http: Option<redirect::RedirectConfig>,
 }
 
 impl ServerConfig {
@@ -94,6 +133,16 @@ impl ServerConfig {
             http2: None,
             http3: None,
             cluster: None,
+            throttle: None,
+            slow_request_threshold: None,
+            server_timing: false,
+            query_array_syntax: QueryArraySyntax::default(),
+            response_checks: None,
+            stats_endpoint: None,
+            recorder: None,
+            json_etags: false,
+            method_override: None,
+            redirect_http: None,
         }
     }
 
@@ -139,6 +188,74 @@ impl ServerConfig {
         self
     }
 
+    /// Enable bandwidth throttling and slow-client protection.
+    pub fn throttle(mut self, config: throttle::ThrottleConfig) -> Self {
+        self.throttle = Some(config);
+        self
+    }
+
+    /// Log requests slower than `threshold` with their phase breakdown.
+    pub fn slow_request_threshold(mut self, threshold: Duration) -> Self {
+        self.slow_request_threshold = Some(threshold);
+        self
+    }
+
+    /// Send each request's phase times in a `Server-Timing` header.
+    pub fn server_timing(mut self, enabled: bool) -> Self {
+        self.server_timing = enabled;
+        self
+    }
+
+    /// Read repeated query parameters written with `syntax`.
+    pub fn query_array_syntax(mut self, syntax: QueryArraySyntax) -> Self {
+        self.query_array_syntax = syntax;
+        self
+    }
+
+    /// Check the length, checksum and size of buffered responses.
+    pub fn response_checks(mut self, checks: integrity::ResponseChecks) -> Self {
+        self.response_checks = Some(checks);
+        self
+    }
+
+    /// Serve the aggregated statistics snapshot at `path`.
+    pub fn stats_endpoint(mut self, path: &str) -> Self {
+        self.stats_endpoint = Some(path.to_string());
+        self
+    }
+
+    /// Record sampled request/response pairs with `recorder`.
+    pub fn recorder(mut self, recorder: Arc<recording::Recorder>) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Tag JSON responses with an ETag of their body, and answer 304 when
+    /// `If-None-Match` already has it.
+    pub fn json_etags(mut self, enabled: bool) -> Self {
+        self.json_etags = enabled;
+        self
+    }
+
+    /// Let POST requests ask for another method before routing.
+    pub fn method_override(mut self, overrides: method_override::MethodOverride) -> Self {
+        self.method_override = Some(overrides);
+        self
+    }
+
+    /// Redirect plain HTTP requests on `port` to this server over HTTPS,
+    /// and send a one-year HSTS policy on its responses.
+    pub fn redirect_http(mut self, port: u16) -> Self {
+        self.redirect_http = Some(redirect::RedirectConfig::new(port));
+        self
+    }
+
+    /// Redirect plain HTTP requests to this server as configured.
+    pub fn redirect(mut self, config: redirect::RedirectConfig) -> Self {
+        self.redirect_http = Some(config);
+        self
+    }
+
     /// Set shutdown timeout.
     pub fn shutdown_timeout(mut self, duration: Duration) -> Self {
         self.shutdown_timeout = duration;
@@ -169,10 +286,18 @@ pub struct ServerMetrics {
     pub bytes_sent: Arc<AtomicU64>,
     /// Total errors
     pub total_errors: Arc<AtomicU64>,
+    /// Requests whose handling panicked
+    pub total_panics: Arc<AtomicU64>,
     /// Server start time
     pub start_time: Instant,
     /// PERF: Request latency ring buffer (VecDeque for O(1) push/pop)
     latencies: Arc<RwLock<VecDeque<Duration>>>,
+    /// SQL pool whose statistics are included in snapshots
+    database: Option<Arc<crate::middleware::sql::SqlPool>>,
+    /// Time spent in each phase of handling requests
+    pub phases: Arc<timing::PhaseMetrics>,
+    /// Keep-alive reuse, connection lifetimes and TLS handshake times
+    pub connections: Arc<connections::ConnectionMetrics>,
 }
 
 impl ServerMetrics {
@@ -184,8 +309,12 @@ impl ServerMetrics {
             bytes_received: Arc::new(AtomicU64::new(0)),
             bytes_sent: Arc::new(AtomicU64::new(0)),
             total_errors: Arc::new(AtomicU64::new(0)),
+            total_panics: Arc::new(AtomicU64::new(0)),
             start_time: Instant::now(),
             latencies: Arc::new(RwLock::new(VecDeque::with_capacity(1024))),
+            database: None,
+            phases: Arc::new(timing::PhaseMetrics::new()),
+            connections: Arc::new(connections::ConnectionMetrics::new()),
         }
     }
 
@@ -225,6 +354,13 @@ impl ServerMetrics {
         self.total_errors.fetch_add(1, Ordering::Relaxed);
     }
 
+    /// Record a request whose handling panicked (also counted as an error).
+    #[inline]
+    pub fn inc_panics(&self) {
+        self.total_panics.fetch_add(1, Ordering::Relaxed);
+        self.inc_errors();
+    }
+
     /// Record request latency.
     /// PERF: Sample-based recording - only records every 64th request to avoid
     /// write lock contention on the VecDeque under high load. This gives accurate
@@ -274,9 +410,13 @@ impl ServerMetrics {
             bytes_received: self.bytes_received.load(Ordering::Relaxed),
             bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
             total_errors: self.total_errors.load(Ordering::Relaxed),
+            total_panics: self.total_panics.load(Ordering::Relaxed),
             uptime_secs: self.start_time.elapsed().as_secs(),
             requests_per_second: self.requests_per_second(),
             avg_latency_ms: self.avg_latency().as_millis() as f64,
+            database: self.database.as_ref().map(|pool| pool.stats()),
+            phases: self.phases.snapshot(),
+            connections: self.connections.snapshot(),
         }
     }
 }
@@ -295,9 +435,16 @@ pub struct MetricsSnapshot {
     pub bytes_received: u64,
     pub bytes_sent: u64,
     pub total_errors: u64,
+    pub total_panics: u64,
     pub uptime_secs: u64,
     pub requests_per_second: f64,
     pub avg_latency_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub database: Option<crate::middleware::database::DatabaseStats>,
+    /// Average time per request in each phase
+    pub phases: timing::PhaseTimings,
+    /// Connection totals, keep-alive reuse and averages
+    pub connections: connections::ConnectionStats,
 }
 
 // ============================================================================
@@ -379,6 +526,162 @@ impl ShutdownCoordinator {
     }
 }
 
+// ============================================================================
+// Response Body
+// ============================================================================
+
+/// Error that ends a streamed response body early, aborting the response.
+pub type BodyError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Body of a response: a complete buffer, or chunks streamed from a channel.
+///
+/// PERF: Buffered responses (the common case) are not boxed.
+pub enum ResponseBody {
+    Full(Full<Bytes>),
+    Stream(mpsc::Receiver<Result<Bytes, BodyError>>),
+    /// A body whose write time is recorded
+    Timed(Box<timing::TimedBody>),
+    /// A body whose end is reported to middleware
+    Observed(Box<completion::ObservedBody>),
+}
+
+impl ResponseBody {
+    /// A complete body.
+    #[inline]
+    pub fn full(bytes: impl Into<Bytes>) -> Self {
+        ResponseBody::Full(Full::new(bytes.into()))
+    }
+
+    /// An empty body.
+    #[inline]
+    pub fn empty() -> Self {
+        ResponseBody::Full(Full::new(Bytes::new()))
+    }
+
+    /// A body streamed from the returned sender; it ends when the sender
+    /// is dropped. Sending an `Err` aborts the response.
+    pub fn channel(capacity: usize) -> (mpsc::Sender<Result<Bytes, BodyError>>, Self) {
+        let (tx, rx) = mpsc::channel(capacity);
+        (tx, ResponseBody::Stream(rx))
+    }
+}
+
+impl hyper::body::Body for ResponseBody {
+    type Data = Bytes;
+    type Error = BodyError;
+
+    fn poll_frame(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Result<hyper::body::Frame<Bytes>, BodyError>>> {
+        match self.get_mut() {
+            ResponseBody::Full(body) => std::pin::Pin::new(body)
+                .poll_frame(cx)
+                .map_err(|never| match never {}),
+            ResponseBody::Stream(rx) => rx
+                .poll_recv(cx)
+                .map(|item| item.map(|chunk| chunk.map(hyper::body::Frame::data))),
+            ResponseBody::Timed(timed) => std::pin::Pin::new(&mut timed.body).poll_frame(cx),
+            ResponseBody::Observed(observed) => std::pin::Pin::new(&mut **observed).poll_frame(cx),
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        match self {
+            ResponseBody::Full(body) => body.is_end_stream(),
+            ResponseBody::Stream(_) => false,
+            ResponseBody::Timed(timed) => timed.body.is_end_stream(),
+            ResponseBody::Observed(observed) => observed.is_end_stream(),
+        }
+    }
+
+    fn size_hint(&self) -> hyper::body::SizeHint {
+        match self {
+            ResponseBody::Full(body) => body.size_hint(),
+            ResponseBody::Stream(_) => hyper::body::SizeHint::default(),
+            ResponseBody::Timed(timed) => timed.body.size_hint(),
+            ResponseBody::Observed(observed) => observed.size_hint(),
+        }
+    }
+}
+
+// ============================================================================
+// Route Table
+// ============================================================================
+
+/// The routes and handlers a server dispatches to.
+pub struct RouteTable {
+    pub router: Router,
+    pub handlers: HandlerRegistry,
+}
+
+/// How requests are handled, taken from the [`ServerConfig`] when the server
+/// is built.
+#[derive(Clone, Default)]
+pub(crate) struct RequestSettings {
+    /// Requests slower than this are logged
+    slow_request_threshold: Option<Duration>,
+    /// Send each request's phase times in a `Server-Timing` header
+    server_timing: bool,
+    /// How repeated query parameters are written
+    query_array_syntax: QueryArraySyntax,
+    /// Length, checksum and size checks on buffered responses
+    response_checks: Option<integrity::ResponseChecks>,
+    /// Stats endpoint path and the sources it reports
+    stats: Option<(String, stats::StatsSources)>,
+    /// Records sampled request/response pairs per route
+    recorder: Option<Arc<recording::Recorder>>,
+    /// Tag JSON responses with an ETag of their body
+    json_etags: bool,
+    /// Lets POST requests ask for another method
+    method_override: Option<method_override::MethodOverride>,
+}
+
+impl RequestSettings {
+    fn new(config: &ServerConfig) -> Self {
+        Self {
+            slow_request_threshold: config.slow_request_threshold,
+            server_timing: config.server_timing,
+            query_array_syntax: config.query_array_syntax,
+            response_checks: config.response_checks.clone(),
+            stats: None,
+            recorder: config.recorder.clone(),
+            json_etags: config.json_etags,
+            method_override: config.method_override.clone(),
+        }
+    }
+}
+
+/// Shared handle to a server's route table.
+///
+/// Each request reads the current table once, so replacing it never
+/// affects requests already in flight: they finish on the table they
+/// started with, and later requests see the new one.
+#[derive(Clone)]
+pub struct RouteHandle {
+    table: Arc<RwLock<Arc<RouteTable>>>,
+}
+
+impl RouteHandle {
+    /// Create a handle to a route table.
+    pub fn new(router: Router, handlers: HandlerRegistry) -> Self {
+        Self {
+            table: Arc::new(RwLock::new(Arc::new(RouteTable { router, handlers }))),
+        }
+    }
+
+    /// The current route table.
+    #[inline]
+    pub fn current(&self) -> Arc<RouteTable> {
+        self.table.read().clone()
+    }
+
+    /// Atomically replace the route table.
+    pub fn swap(&self, router: Router, handlers: HandlerRegistry) {
+        *self.table.write() = Arc::new(RouteTable { router, handlers });
+    }
+}
+
 // ============================================================================
 // HTTP Server
 // ============================================================================
@@ -386,16 +689,19 @@ impl ShutdownCoordinator {
 /// The main HTTP server.
 pub struct Server {
     config: ServerConfig,
-    router: Router,
-    handlers: HandlerRegistry,
+    routes: RouteHandle,
     middleware: MiddlewareChain,
     websocket_handlers: WebSocketRegistry,
     metrics: ServerMetrics,
+    settings: RequestSettings,
     shutdown: ShutdownCoordinator,
     dependency_container: Arc<crate::dependency::DependencyContainer>,
     guards: Arc<crate::middleware::guards::GuardsMiddleware>,
     prometheus:
         Arc<parking_lot::RwLock<Option<crate::middleware::prometheus::PrometheusMiddleware>>>,
+    lifecycle: crate::lifecycle::ServerLifecycle,
+    certificates: Option<Arc<tls::CertStore>>,
+    http_challenges: Option<Arc<acme::AcmeManager>>,
 }
 
 impl Server {
@@ -412,20 +718,76 @@ impl Server {
         >,
     ) -> Self {
         let shutdown = ShutdownCoordinator::new(config.shutdown_timeout);
+        let settings = RequestSettings::new(&config);
         Server {
             config,
-            router,
-            handlers,
+            routes: RouteHandle::new(router, handlers),
             middleware,
             websocket_handlers,
             metrics: ServerMetrics::new(),
+            settings,
             shutdown,
             dependency_container,
             guards,
             prometheus,
+            lifecycle: crate::lifecycle::ServerLifecycle::new(),
+            certificates: None,
+            http_challenges: None,
         }
     }
 
+    /// Set the startup and shutdown hooks awaited by `run()`.
+    pub fn with_lifecycle(mut self, lifecycle: crate::lifecycle::ServerLifecycle) -> Self {
+        self.lifecycle = lifecycle;
+        self
+    }
+
+    /// Include the SQL pool's statistics in metrics snapshots.
+    pub fn with_database(mut self, pool: Arc<crate::middleware::sql::SqlPool>) -> Self {
+        self.metrics.database = Some(pool);
+        self
+    }
+
+    /// Record connection metrics into `connections`, shared with the app so
+    /// they outlive this server and stay registered with Prometheus.
+    pub fn with_connection_metrics(
+        mut self,
+        connections: Arc<connections::ConnectionMetrics>,
+    ) -> Self {
+        self.metrics.connections = connections;
+        self
+    }
+
+    /// Report this server's metrics to `sources`, and serve their snapshot
+    /// at the configured stats endpoint.
+    ///
+    /// Call it after `with_database` and `with_connection_metrics`, so the
+    /// reported metrics include theirs.
+    pub fn with_stats(mut self, sources: stats::StatsSources) -> Self {
+        let metrics = self.metrics.clone();
+        sources.register("server", move || metrics.snapshot());
+        self.settings.stats = self
+            .config
+            .stats_endpoint
+            .clone()
+            .map(|endpoint| (endpoint, sources));
+        self
+    }
+
+    /// Terminate TLS with the certificates in `store`, which can be
+    /// replaced while the server runs (see [`acme::AcmeManager`]).
+    pub fn with_certificates(mut self, store: Arc<tls::CertStore>) -> Self {
+        self.certificates = Some(store);
+        self
+    }
+
+    /// Answer the HTTP-01 challenges of `manager` on the redirect listener,
+    /// which then shares port 80 with ACME.
+    pub fn with_http_challenges(mut self, manager: Arc<acme::AcmeManager>) -> Self {
+        self.http_challenges = Some(manager);
+        self
+    }
+
     /// Create a server with simple parameters (legacy compatibility).
     pub fn simple(
         host: String,
@@ -448,6 +810,11 @@ impl Server {
         )
     }
 
+    /// Handle to the route table, for replacing routes while serving.
+    pub fn routes(&self) -> RouteHandle {
+        self.routes.clone()
+    }
+
     /// Get server metrics.
     pub fn metrics(&self) -> &ServerMetrics {
         &self.metrics
@@ -459,8 +826,45 @@ impl Server {
     }
 
     /// Run the server (blocking).
-    pub async fn run(self) -> PyResult<()> {
-        let addr: SocketAddr = format!("{}:{}", self.config.host, self.config.port)
+    ///
+    /// Startup hooks run after the socket is bound and before connections
+    /// are accepted; an aborting startup hook fails the run without serving.
+    /// Shutdown hooks run after in-flight requests have drained.
+    pub async fn run(mut self) -> PyResult<()> {
+        let listener = self.bind()?;
+        let redirect = match &self.config.redirect_http {
+            Some(config) => Some((
+                config.https_port.unwrap_or(self.config.port),
+                self.bind_port(config.port)?,
+            )),
+            None => None,
+        };
+        let lifecycle = std::mem::take(&mut self.lifecycle);
+        lifecycle
+            .run_startup()
+            .await
+            .map_err(pyo3::exceptions::PyRuntimeError::new_err)?;
+        if let Some((https_port, listener)) = redirect {
+            tokio::spawn(redirect::serve(
+                listener,
+                https_port,
+                self.http_challenges.clone(),
+                self.shutdown.subscribe(),
+            ));
+        }
+        let result = self.serve(listener).await;
+        lifecycle.run_shutdown().await;
+        result
+    }
+
+    /// Bind the listening socket described by the server config.
+    pub fn bind(&self) -> PyResult<TcpListener> {
+        self.bind_port(self.config.port)
+    }
+
+    /// Bind a listening socket on the configured host and `port`.
+    fn bind_port(&self, port: u16) -> PyResult<TcpListener> {
+        let addr: SocketAddr = format!("{}:{}", self.config.host, port)
             .parse()
             .map_err(|e| {
                 pyo3::exceptions::PyValueError::new_err(format!("Invalid address: {e}"))
@@ -505,21 +909,49 @@ impl Server {
         })?;
 
         let std_listener: std::net::TcpListener = socket.into();
-        let listener = TcpListener::from_std(std_listener).map_err(|e| {
+        TcpListener::from_std(std_listener).map_err(|e| {
             pyo3::exceptions::PyRuntimeError::new_err(format!("Failed to create listener: {e}"))
-        })?;
+        })
+    }
 
+    /// Serve connections from an already-bound listener until shutdown.
+    ///
+    /// Used by `run()` and by the conformance harness, which binds an
+    /// ephemeral port itself so it knows the address to connect to.
+    pub async fn serve(self, listener: TcpListener) -> PyResult<()> {
         // Banner and server details are printed by Python
 
-        let router = Arc::new(self.router);
-        let handlers = Arc::new(self.handlers);
+        let routes = self.routes;
         let middleware = Arc::new(self.middleware);
-        let _websocket_handlers = Arc::new(self.websocket_handlers);
+        let websocket_handlers = Arc::new(self.websocket_handlers);
         let metrics = Arc::new(self.metrics);
+        let settings = Arc::new(self.settings);
         let shutdown = Arc::new(self.shutdown);
         let dependency_container = self.dependency_container.clone();
         let guards = self.guards.clone();
         let prometheus = self.prometheus.clone();
+        let throttling = self.config.throttle.clone().map(throttle::Throttle::new);
+        let header_read_timeout = throttling
+            .as_ref()
+            .and_then(|t| t.config().header_read_timeout);
+        let min_body_rate = throttling.as_ref().and_then(|t| t.config().min_body_rate);
+        let hsts = self
+            .config
+            .redirect_http
+            .as_ref()
+            .and_then(|redirect| redirect.hsts.as_ref())
+            .and_then(|hsts| hyper::header::HeaderValue::from_str(&hsts.build()).ok());
+        let http2 = self.config.http2.clone();
+        let tls_acceptor = match (self.certificates.clone(), &self.config.tls) {
+            (Some(store), config) => Some(tls::acceptor(store, config.as_ref(), http2.is_some())),
+            (None, Some(config)) => Some(
+                tls::load(config)
+                    .and_then(|store| tls::acceptor(store, Some(config), http2.is_some())),
+            ),
+            (None, None) => None,
+        }
+        .transpose()
+        .map_err(pyo3::exceptions::PyRuntimeError::new_err)?;
 
         let mut shutdown_rx = shutdown.subscribe();
 
@@ -528,7 +960,9 @@ impl Server {
         tokio::task::spawn(async move {
             #[cfg(unix)]
             {
-                if let Ok(mut sig) = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+                if let Ok(mut sig) =
+                    tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+                {
                     let _ = sig.recv().await;
                     shutdown_sigterm.shutdown();
                 }
@@ -570,60 +1004,123 @@ impl Server {
                                 continue;
                             }
 
+                            let permit = match &throttling {
+                                Some(throttling) => match throttling.admit(peer_addr.ip()) {
+                                    Some(permit) => Some(permit),
+                                    None => {
+                                        eprintln!("Per-client connection limit reached, rejecting connection from {peer_addr}");
+                                        continue;
+                                    }
+                                },
+                                None => None,
+                            };
+
                             // PERF: Apply TCP_NODELAY to reduce latency for small responses
                             let _ = stream.set_nodelay(true);
 
                             metrics.inc_connections();
+                            let connection = metrics.connections.open();
 
-                            let io = TokioIo::new(stream);
-                            let router = router.clone();
-                            let handlers = handlers.clone();
+                            let stream = throttle::ThrottledIo::new(stream, permit);
+                            let tls_acceptor = tls_acceptor.clone();
+                            let http2 = http2.clone();
+                            let hsts = hsts.clone();
+                            let routes = routes.clone();
                             let middleware = middleware.clone();
                             let metrics_for_service = metrics.clone();
                             let metrics_for_cleanup = metrics.clone();
+                            let settings = settings.clone();
                             let shutdown = shutdown.clone();
 
                             let dependency_container = dependency_container.clone();
                             let guards = guards.clone();
                             let prometheus = prometheus.clone();
+                            let websocket_handlers = websocket_handlers.clone();
 
                             tokio::task::spawn(async move {
+                                let stream = match tls_acceptor {
+                                    Some(acceptor) => {
+                                        let started = Instant::now();
+                                        let accepted = tokio::time::timeout(
+                                            tls::HANDSHAKE_TIMEOUT,
+                                            acceptor.accept(stream),
+                                        )
+                                        .await;
+                                        match accepted {
+                                            Ok(Ok(stream)) => {
+                                                connection.record_tls_handshake(started.elapsed());
+                                                if tls::is_validation(&stream) {
+                                                    metrics_for_cleanup.dec_connections();
+                                                    return;
+                                                }
+                                                tls::MaybeTls::Tls(Box::new(stream))
+                                            }
+                                            _ => {
+                                                metrics_for_cleanup.dec_connections();
+                                                return;
+                                            }
+                                        }
+                                    }
+                                    None => tls::MaybeTls::Plain(stream),
+                                };
+                                let io = TokioIo::new(stream);
+
                                 // PERF: Clone Arcs once per connection, not per request.
                                 // For keep-alive connections, this avoids repeated Arc refcount bumps.
-                                let conn_router = router;
-                                let conn_handlers = handlers;
+                                let conn_routes = routes;
                                 let conn_middleware = middleware;
                                 let conn_metrics = metrics_for_service;
+                                let conn_settings = settings;
                                 let conn_shutdown = shutdown;
                                 let conn_deps = dependency_container;
                                 let conn_guards = guards;
                                 let conn_prometheus = prometheus;
+                                let conn_websockets = websocket_handlers;
 
-                                let service = service_fn(move |req| {
-                                    let router = conn_router.clone();
-                                    let handlers = conn_handlers.clone();
+                                let service = service_fn(move |mut req: HyperRequest<hyper::body::Incoming>| {
+                                    connection.request();
+                                    req.extensions_mut().insert(peer_addr);
+                                    if let Some(rate) = min_body_rate {
+                                        req.extensions_mut().insert(rate);
+                                    }
+                                    let routes = conn_routes.current();
                                     let middleware = conn_middleware.clone();
                                     let metrics = conn_metrics.clone();
+                                    let settings = conn_settings.clone();
                                     let shutdown = conn_shutdown.clone();
                                     let dependency_container = conn_deps.clone();
                                     let guards = conn_guards.clone();
                                     let prometheus = conn_prometheus.clone();
+                                    let websockets = conn_websockets.clone();
+                                    let hsts = hsts.clone();
 
                                     async move {
                                         shutdown.request_started();
                                         let start = Instant::now();
-
-                                        let result = handle_request(
-                                            req,
-                                            &router,
-                                            &handlers,
-                                            &middleware,
-                                            &metrics,
-                                            &dependency_container,
-                                            &guards,
-                                            &prometheus,
-                                        )
-                                        .await;
+                                        let is_head = req.method() == hyper::Method::HEAD;
+
+                                        let ctx = RequestContext {
+                                            router: &routes.router,
+                                            handlers: &routes.handlers,
+                                            middleware: &middleware,
+                                            metrics: &metrics,
+                                            settings: &settings,
+                                            dependency_container: &dependency_container,
+                                            guards: &guards,
+                                            prometheus: &prometheus,
+                                            websockets: &websockets,
+                                        };
+                                        let result = handle_request(req, &ctx)
+                                            .await
+                                            .map(|res| if is_head { strip_head_body(res) } else { res })
+                                            .map(|mut res| {
+                                                if let Some(hsts) = hsts {
+                                                    res.headers_mut()
+                                                        .entry(hyper::header::STRICT_TRANSPORT_SECURITY)
+                                                        .or_insert(hsts);
+                                                }
+                                                res
+                                            });
 
                                         metrics.record_latency(start.elapsed());
                                         shutdown.request_finished();
@@ -633,15 +1130,39 @@ impl Server {
                                 });
 
                                 // PERF: Enable keep-alive and pipelining for better throughput
-                                let serve_res: Result<(), hyper::Error> = http1::Builder::new()
-                                    .keep_alive(true)
-                                    .pipeline_flush(true)
-                                    .serve_connection(io, service)
-                                    .await;
-                                
+                                let serve_res = match http2 {
+                                    // HTTP/2 is picked by ALPN over TLS and by the connection
+                                    // preface in the clear; HTTP/1.1 is served otherwise
+                                    Some(config) => {
+                                        let mut builder = auto::Builder::new(TokioExecutor::new());
+                                        let mut http1 = builder.http1();
+                                        http1.keep_alive(true).pipeline_flush(true);
+                                        if let Some(timeout) = header_read_timeout {
+                                            http1.timer(TokioTimer::new()).header_read_timeout(timeout);
+                                        }
+                                        configure_http2(builder.http2(), &config);
+                                        builder.serve_connection_with_upgrades(io, service).await
+                                    }
+                                    None => {
+                                        let mut builder = http1::Builder::new();
+                                        builder.keep_alive(true).pipeline_flush(true);
+                                        if let Some(timeout) = header_read_timeout {
+                                            builder.timer(TokioTimer::new()).header_read_timeout(timeout);
+                                        }
+                                        builder
+                                            .serve_connection(io, service)
+                                            .with_upgrades()
+                                            .await
+                                            .map_err(Into::into)
+                                    }
+                                };
+
                                 if let Err(err) = serve_res {
                                     // Only log if not a normal connection close
-                                    if !err.is_incomplete_message() {
+                                    let incomplete = err
+                                        .downcast_ref::<hyper::Error>()
+                                        .is_some_and(hyper::Error::is_incomplete_message);
+                                    if !incomplete {
                                         eprintln!("Connection error: {err:?}");
                                     }
                                 }
@@ -666,87 +1187,343 @@ impl Server {
     }
 }
 
-async fn handle_request(
-    req: HyperRequest<Incoming>,
-    router: &Arc<Router>,
-    handlers: &Arc<HandlerRegistry>,
-    middleware: &Arc<MiddlewareChain>,
-    metrics: &Arc<ServerMetrics>,
-    dependency_container: &Arc<crate::dependency::DependencyContainer>,
-    guards: &Arc<crate::middleware::guards::GuardsMiddleware>,
-    prometheus: &Arc<
-        parking_lot::RwLock<Option<crate::middleware::prometheus::PrometheusMiddleware>>,
-    >,
-) -> Result<HyperResponse<Full<Bytes>>, Infallible> {
+/// Apply `config` to the HTTP/2 half of a connection builder.
+fn configure_http2(mut builder: auto::Http2Builder<'_, TokioExecutor>, config: &Http2Config) {
+    builder
+        .timer(TokioTimer::new())
+        .max_concurrent_streams(config.max_concurrent_streams)
+        .initial_connection_window_size(config.initial_connection_window_size)
+        .initial_stream_window_size(config.initial_stream_window_size)
+        .max_frame_size(config.max_frame_size)
+        .max_header_list_size(config.max_header_list_size)
+        .keep_alive_interval(config.ping_interval)
+        .keep_alive_timeout(config.ping_timeout);
+}
+
+/// The app state a request is served with.
+#[derive(Clone, Copy)]
+struct RequestContext<'a> {
+    router: &'a Router,
+    handlers: &'a HandlerRegistry,
+    middleware: &'a Arc<MiddlewareChain>,
+    metrics: &'a Arc<ServerMetrics>,
+    settings: &'a RequestSettings,
+    dependency_container: &'a Arc<crate::dependency::DependencyContainer>,
+    guards: &'a Arc<crate::middleware::guards::GuardsMiddleware>,
+    prometheus: &'a Arc<RwLock<Option<crate::middleware::prometheus::PrometheusMiddleware>>>,
+    websockets: &'a WebSocketRegistry,
+}
+
+/// Handle a request, isolating panics and timing its phases.
+///
+/// A panic anywhere in the request path is logged and answered with a 500
+/// instead of unwinding through the connection task, which would drop the
+/// connection along with any other requests pipelined on it. Requests slower
+/// than the configured threshold are logged with their phase breakdown.
+async fn handle_request<B>(
+    mut req: HyperRequest<B>,
+    ctx: &RequestContext<'_>,
+) -> Result<HyperResponse<ResponseBody>, Infallible>
+where
+    B: hyper::body::Body<Data = Bytes> + Send + 'static,
+    B::Error: std::fmt::Display,
+{
+    use futures_util::FutureExt;
+
+    let RequestContext {
+        handlers,
+        metrics,
+        prometheus,
+        middleware,
+        ..
+    } = *ctx;
+
+    let method = req.method().clone();
+    let uri = req.uri().clone();
+    let started = Instant::now();
+
+    // Middleware told when the response ends shares the request's extensions
+    let mut observers = middleware.response_end_observers();
+    if let Some(prometheus) = prometheus.read().as_ref() {
+        observers.push(Arc::new(prometheus.clone()));
+    }
+    let extensions = (!observers.is_empty()).then(|| {
+        let extensions = crate::request::Extensions::new();
+        req.extensions_mut().insert(extensions.clone());
+        extensions
+    });
+
+    let slow_request = ctx
+        .settings
+        .slow_request_threshold
+        .map(|threshold| (threshold, timing::SlowRequest::capture(&req)));
+    let mut timer = timing::RequestTimer::start();
+    let processed = std::panic::AssertUnwindSafe(process_request(req, ctx, &mut timer))
+        .catch_unwind()
+        .await;
+
+    let result = match processed {
+        Ok(result) => result,
+        Err(payload) => {
+            let reason = payload
+                .downcast_ref::<&str>()
+                .copied()
+                .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+                .unwrap_or("unknown panic");
+            eprintln!("Panic while handling {method} {uri}: {reason}");
+            metrics.inc_panics();
+
+            let request = Request::new(method.as_str(), uri.path());
+            let error_handlers = handlers.error_handlers();
+            if let Some(reporter) = error_handlers.reporter() {
+                reporter.capture_error("Panic", reason, Some(&request));
+            }
+            let response = error_handlers
+                .error_response(&request, 500, "Internal Server Error")
+                .await;
+            build_hyper_response(&response, ctx)
+        }
+    };
+
+    let mut response = match result {
+        Ok(response) => response,
+        Err(never) => match never {},
+    };
+
+    let total = timer.finish();
+    metrics.phases.record(&timer);
+    if let Some((threshold, request)) = &slow_request {
+        if total >= *threshold {
+            metrics.phases.inc_slow_requests();
+            let status = response.status().as_u16();
+            eprintln!("{}", request.describe(status, total, &timer));
+        }
+    }
+    if ctx.settings.server_timing {
+        if let Ok(value) = hyper::header::HeaderValue::from_str(&timer.server_timing()) {
+            response.headers_mut().insert("server-timing", value);
+        }
+    }
+
+    // Phase histograms; the write is timed only for them
+    let histograms = prometheus
+        .read()
+        .as_ref()
+        .filter(|prometheus| prometheus.observes(uri.path()))
+        .map(|prometheus| prometheus.metrics());
+    if let Some(histograms) = histograms {
+        histograms.observe_phases(&timer);
+        let write = histograms.phase_histogram(timing::Phase::Write);
+        let phases = metrics.phases.clone();
+        response = response
+            .map(|body| ResponseBody::Timed(Box::new(timing::TimedBody::new(body, phases, write))));
+    }
+    if let Some(extensions) = extensions {
+        let status = response.status().as_u16();
+        response = response.map(|body| {
+            ResponseBody::Observed(Box::new(completion::ObservedBody::new(
+                body,
+                &method,
+                uri.path(),
+                status,
+                started,
+                extensions,
+                observers,
+            )))
+        });
+    }
+    Ok(response)
+}
+
+async fn process_request<B>(
+    mut req: HyperRequest<B>,
+    ctx: &RequestContext<'_>,
+    timer: &mut timing::RequestTimer,
+) -> Result<HyperResponse<ResponseBody>, Infallible>
+where
+    B: hyper::body::Body<Data = Bytes> + Send + 'static,
+    B::Error: std::fmt::Display,
+{
+    let RequestContext {
+        middleware,
+        metrics,
+        ..
+    } = *ctx;
     metrics.inc_requests();
 
+    // Native transforms rewrite the request head before the router sees it
+    if middleware.has_transforms() {
+        let (mut head, body) = req.into_parts();
+        if let Some(response) = middleware.transform_request(&mut head) {
+            return build_hyper_response(&response, ctx);
+        }
+        req = HyperRequest::from_parts(head, body);
+    }
+
+    // Method overrides apply before routing; a method in a form body needs
+    // the body read first
+    let overrides = ctx.settings.method_override.as_ref();
+    if let Some(overrides) = overrides.filter(|o| o.applies(req.method())) {
+        if let Some(method) = overrides.from_header(req.headers()) {
+            let (mut head, body) = req.into_parts();
+            override_method(&mut head, method);
+            req = HyperRequest::from_parts(head, body);
+        } else if overrides.reads_form(req.headers()) {
+            let (mut head, body) = req.into_parts();
+            let min_rate = head.extensions.get::<throttle::MinRate>().copied();
+            let bytes = match read_body(body, min_rate).await {
+                Ok(bytes) => bytes,
+                Err(throttle::BodyReadError::TooSlow) => {
+                    let response = Response::error(408, "Request body too slow");
+                    return build_hyper_response(&response, ctx);
+                }
+                Err(throttle::BodyReadError::Failed) => {
+                    metrics.inc_errors();
+                    Bytes::new()
+                }
+            };
+            if let Some(method) = overrides.from_form(&bytes) {
+                override_method(&mut head, method);
+            }
+            let req = HyperRequest::from_parts(head, Full::new(bytes));
+            return route_request(req, ctx, timer).await;
+        }
+    }
+
+    route_request(req, ctx, timer).await
+}
+
+/// Serve `req` from its route, or from a mount, the metrics or stats
+/// endpoints, or as a 404 or 405.
+async fn route_request<B>(
+    mut req: HyperRequest<B>,
+    ctx: &RequestContext<'_>,
+    timer: &mut timing::RequestTimer,
+) -> Result<HyperResponse<ResponseBody>, Infallible>
+where
+    B: hyper::body::Body<Data = Bytes> + Send + 'static,
+    B::Error: std::fmt::Display,
+{
+    let RequestContext {
+        router,
+        handlers,
+        middleware,
+        metrics,
+        dependency_container,
+        guards,
+        prometheus,
+        ..
+    } = *ctx;
+    let remote_addr = req.extensions().get::<std::net::SocketAddr>().copied();
+    let extensions = req.extensions_mut().remove::<crate::request::Extensions>();
+
     // PERF: Extract method and path WITHOUT owning - use references as long as possible
     let method = req.method().clone();
     let method_str = method.as_str();
     let uri = req.uri().clone();
     let path = uri.path();
 
+    // WebSocket upgrades are admitted before the HTTP routes are consulted
+    if let Some(handler) = websocket::upgrade_handler(&req, ctx) {
+        return websocket::upgrade(req, handler, ctx).await;
+    }
+
     // PERF: Route match FIRST - fail fast on 404 before any allocation
-    let route_match = router.match_route(method_str, path);
+    let route_match = router.match_route(method_str, path).or_else(|| {
+        // HEAD falls back to the GET handler; the body is stripped when serving
+        if method == hyper::Method::HEAD {
+            router.match_route("GET", path)
+        } else {
+            None
+        }
+    });
 
-    // PERF: Fast-return 404 before allocating Request object
+    // PERF: Fast-return 404/405/OPTIONS before allocating Request object
     let route_match = match route_match {
         Some(m) => m,
         None => {
-            let response = Response::not_found(&format!("Not Found: {method_str} {path}"));
-            return build_hyper_response(&response, metrics);
+            if let Some(mount) = router.match_mount(path) {
+                return dispatch_mount(req, mount, ctx).await;
+            }
+            // The metrics and stats endpoints have no route of their own
+            let gzip = stats::accepts_gzip(
+                req.headers()
+                    .get(hyper::header::ACCEPT_ENCODING)
+                    .and_then(|v| v.to_str().ok()),
+            );
+            let scrape = prometheus
+                .read()
+                .as_ref()
+                .and_then(|prometheus| prometheus.endpoint_response(path, gzip));
+            if let Some(response) = scrape {
+                return build_hyper_response(&response, ctx);
+            }
+            if let Some((_, sources)) = ctx.settings.stats.as_ref().filter(|(p, _)| p == path) {
+                return build_hyper_response(&sources.response(gzip), ctx);
+            }
+            let mut response = unmatched_route_response(router, method_str, path);
+            if handlers.error_handlers().customizes(response.status) {
+                let request = Request::from_http(
+                    method_str.to_owned(),
+                    path.to_owned(),
+                    HashMap::new(),
+                    parse_query(uri.query().unwrap_or("")),
+                    Headers::from(std::mem::take(req.headers_mut())),
+                    Vec::new(),
+                );
+                let reason = if response.status == 404 {
+                    "Not Found"
+                } else {
+                    "Method Not Allowed"
+                };
+                let message = format!("{reason}: {method_str} {path}");
+                let allow = response.headers.remove("Allow");
+                response = error_response(handlers, &request, response.status, &message).await;
+                if let Some(allow) = allow {
+                    response.set_header("Allow", &allow);
+                }
+            }
+            return build_hyper_response(&response, ctx);
         }
     };
 
-    let params = route_match.params.clone();
+    // Disabled routes answer before middleware or the handler run
+    if let Some(disabled) = route_match.handler.as_ref().and_then(|m| m.disabled()) {
+        return build_hyper_response(&disabled.response(method_str, path), ctx);
+    }
 
-    // PERF: Only parse query string when present
-    let query_string = uri.query().unwrap_or("");
-    let query: HashMap<String, String> = if query_string.is_empty() {
-        HashMap::new()
-    } else {
-        query_string
-            .split('&')
-            .filter(|s| !s.is_empty())
-            .filter_map(|pair| {
-                let mut parts = pair.splitn(2, '=');
-                match (parts.next(), parts.next()) {
-                    (Some(key), Some(value)) => {
-                        let value_with_spaces = value.replace('+', " ");
-                        Some((
-                            urlencoding::decode(key).unwrap_or_default().to_string(),
-                            urlencoding::decode(&value_with_spaces)
-                                .unwrap_or_default()
-                                .to_string(),
-                        ))
-                    }
-                    (Some(key), None) => Some((
-                        urlencoding::decode(key).unwrap_or_default().to_string(),
-                        String::new(),
-                    )),
-                    _ => None,
-                }
-            })
-            .collect()
-    };
+    timer.enter(timing::Phase::Body);
+
+    let params = route_match.params.clone();
+    let query = parse_query(uri.query().unwrap_or(""));
 
     // PERF: Only copy headers for matched routes (skip for 404s)
-    let header_count = req.headers().len();
-    let mut headers: HashMap<String, String> = HashMap::with_capacity(header_count);
-    for (k, v) in req.headers().iter() {
-        headers.insert(k.as_str().to_owned(), v.to_str().unwrap_or("").to_owned());
-    }
+    let headers = Headers::from(std::mem::take(req.headers_mut()));
 
     // PERF: Only collect body for methods that carry payloads
+    let overridden = req
+        .extensions()
+        .get::<method_override::OriginalMethod>()
+        .is_some();
+    let mut body_stream = None;
     let body_bytes: Vec<u8> = match method_str {
-        "GET" | "HEAD" | "OPTIONS" | "DELETE" => {
+        "GET" | "HEAD" | "OPTIONS" | "DELETE" if !overridden => {
             // Fast path: drop body without draining - hyper handles cleanup
             drop(req);
             Vec::new()
         }
-        _ => match req.collect().await {
-            Ok(collected) => {
-                let bytes = collected.to_bytes();
+        // Streaming routes hand the unread body to the handler
+        _ if route_match
+            .handler
+            .as_ref()
+            .is_some_and(|m| m.stream_body()) =>
+        {
+            let min_rate = req.extensions().get::<throttle::MinRate>().copied();
+            body_stream = Some(crate::request::BodyStream::new(req.into_body(), min_rate));
+            Vec::new()
+        }
+        _ => match read_request_body(req).await {
+            Ok(bytes) => {
                 if bytes.is_empty() {
                     Vec::new()
                 } else {
@@ -754,7 +1531,11 @@ async fn handle_request(
                     bytes.to_vec()
                 }
             }
-            Err(_) => {
+            Err(throttle::BodyReadError::TooSlow) => {
+                let response = Response::error(408, "Request body too slow");
+                return build_hyper_response(&response, ctx);
+            }
+            Err(throttle::BodyReadError::Failed) => {
                 metrics.inc_errors();
                 Vec::new()
             }
@@ -766,18 +1547,37 @@ async fn handle_request(
     let path_owned = path.to_owned();
     let mut request =
         Request::from_http(method_owned, path_owned, params, query, headers, body_bytes);
+    request.remote_addr = remote_addr;
+    request.body_stream = body_stream;
+    request.route = Some(route_match.pattern.clone());
+    if let Some(raw) = uri.query() {
+        request.query_string = raw.to_owned();
+        request.query_array_syntax = ctx.settings.query_array_syntax;
+    }
+    if let Some(extensions) = extensions {
+        request.extensions = extensions;
+    }
+    timer.enter(timing::Phase::BeforeMiddleware);
+
+    // Verify webhook signatures before any Python code sees the payload
+    if let Some(verifier) = route_match.handler.as_ref().and_then(|m| m.webhook()) {
+        if let Err(e) = verifier.verify(&request) {
+            let response = error_response(handlers, &request, e.status, &e.message).await;
+            return build_hyper_response(&response, ctx);
+        }
+    }
 
     // PERF: Skip middleware execution if no middleware registered
     if !middleware.is_empty() {
         match middleware.execute_before(&mut request) {
             Ok(MiddlewareAction::Continue) => {}
             Ok(MiddlewareAction::Stop(response)) => {
-                return build_hyper_response(&response, metrics);
+                return build_hyper_response(&response, ctx);
             }
             Err(e) => {
                 metrics.inc_errors();
-                let response = Response::error(e.status, &e.message);
-                return build_hyper_response(&response, metrics);
+                let response = error_response(handlers, &request, e.status, &e.message).await;
+                return build_hyper_response(&response, ctx);
             }
         }
     }
@@ -787,12 +1587,12 @@ async fn handle_request(
         match middleware.execute_before_async(&mut request).await {
             Ok(MiddlewareAction::Continue) => {}
             Ok(MiddlewareAction::Stop(response)) => {
-                return build_hyper_response(&response, metrics);
+                return build_hyper_response(&response, ctx);
             }
             Err(e) => {
                 metrics.inc_errors();
-                let response = Response::error(e.status, &e.message);
-                return build_hyper_response(&response, metrics);
+                let response = error_response(handlers, &request, e.status, &e.message).await;
+                return build_hyper_response(&response, ctx);
             }
         }
     }
@@ -805,12 +1605,12 @@ async fn handle_request(
             match p.before(&mut request) {
                 Ok(MiddlewareAction::Continue) => {}
                 Ok(MiddlewareAction::Stop(response)) => {
-                    return build_hyper_response(&response, metrics);
+                    return build_hyper_response(&response, ctx);
                 }
                 Err(e) => {
                     metrics.inc_errors();
                     let response = Response::error(e.status, &e.message);
-                    return build_hyper_response(&response, metrics);
+                    return build_hyper_response(&response, ctx);
                 }
             }
         }
@@ -822,36 +1622,197 @@ async fn handle_request(
         match Middleware::before(&**guards, &mut request) {
             Ok(MiddlewareAction::Continue) => {}
             Ok(MiddlewareAction::Stop(response)) => {
-                return build_hyper_response(&response, metrics);
+                return build_hyper_response(&response, ctx);
             }
             Err(e) => {
                 metrics.inc_errors();
-                let response = Response::error(e.status, &e.message);
-                return build_hyper_response(&response, metrics);
+                let response = error_response(handlers, &request, e.status, &e.message).await;
+                return build_hyper_response(&response, ctx);
             }
         }
     }
 
+    // Middleware attached to this route and its groups, compiled at startup
+    let route_middleware = route_match.handler.as_ref().and_then(|m| m.middleware());
+    if let Some(chain) = &route_middleware {
+        match chain.before(&mut request) {
+            Ok(MiddlewareAction::Continue) => {}
+            Ok(MiddlewareAction::Stop(response)) => {
+                return build_hyper_response(&response, ctx);
+            }
+            Err(e) => {
+                metrics.inc_errors();
+                let response = error_response(handlers, &request, e.status, &e.message).await;
+                return build_hyper_response(&response, ctx);
+            }
+        }
+    }
+
+    // Validate against the route's JSON Schemas before invoking the handler
+    // Decode protobuf bodies on routes with a bound request message type
+    if let Some(binding) = route_match.handler.as_ref().and_then(|m| m.protobuf()) {
+        if request.body_format() == crate::codec::BodyFormat::Protobuf {
+            match binding.decode_request(request.body_bytes()) {
+                Some(Ok(value)) => request.set_parsed_body(value),
+                Some(Err(e)) => {
+                    let response = error_response(handlers, &request, 400, &e.to_string()).await;
+                    return build_hyper_response(&response, ctx);
+                }
+                None => {}
+            }
+        }
+    }
+
+    if let Some(validator) = route_match.handler.as_ref().and_then(|m| m.validator()) {
+        if let Err(issues) = validator.validate(&request) {
+            let error_handlers = handlers.error_handlers();
+            let response = if error_handlers.customizes(422) {
+                let mut problem =
+                    error_handlers.problem(&request, 422, "Request validation failed");
+                problem
+                    .extensions
+                    .insert("errors".to_string(), serde_json::json!(issues));
+                error_handlers.respond(&request, problem).await
+            } else {
+                crate::validation::validation_error_response(&issues)
+            };
+            return build_hyper_response(&response, ctx);
+        }
+    }
+
+    // Claim the idempotency key, or answer a retry without running the handler
+    let idempotency = match route_match.handler.as_ref().and_then(|m| m.idempotency()) {
+        Some(idempotency) => match idempotency.admit(&request) {
+            Ok(Admission::Proceed(reservation)) => reservation,
+            Ok(Admission::Replay(response)) => return build_hyper_response(&response, ctx),
+            Err(e) => {
+                let response = error_response(handlers, &request, e.status, &e.message).await;
+                return build_hyper_response(&response, ctx);
+            }
+        },
+        None => None,
+    };
+
+    // Open the route's transaction; the handler gets it as its "database"
+    let transaction = match route_match.handler.as_ref().and_then(|m| m.transaction()) {
+        Some(policy) => match begin_transaction(policy, metrics).await {
+            Ok(tx) => Some(tx),
+            Err(response) => {
+                metrics.inc_errors();
+                return build_hyper_response(&response, ctx);
+            }
+        },
+        None => None,
+    };
+    let scope = match &transaction {
+        Some(tx) => match Python::with_gil(|py| tx.dependencies(py)) {
+            Ok(scope) => scope,
+            Err(e) => {
+                metrics.inc_errors();
+                let response = Response::error(500, &e.to_string());
+                return build_hyper_response(&response, ctx);
+            }
+        },
+        None => crate::dependency::PyDependencyCache::new(),
+    };
+
     // Handle route
-    let has_after_middleware = !middleware.is_empty() || !middleware.is_async_empty();
+    let has_after_middleware =
+        !middleware.is_empty() || !middleware.is_async_empty() || route_middleware.is_some();
 
-    // PERF: Create lightweight request for after-middleware (no body copy)
+    // PERF: Create lightweight request for after-middleware (no body copy
+    // unless a middleware asks to keep it)
     let after_request = if has_after_middleware {
-        Some(request.clone_without_body())
+        let body_limit = route_middleware
+            .as_ref()
+            .map_or(0, |chain| chain.after_body_limit())
+            .max(middleware.after_body_limit());
+        Some(request.clone_for_after(body_limit))
     } else {
         None
     };
 
+    // JSON ETags are checked against `If-None-Match` after the request has
+    // been consumed
+    let json_etags = ctx.settings.json_etags && matches!(method_str, "GET" | "HEAD");
+    let if_none_match = json_etags
+        .then(|| request.headers.get("if-none-match").map(str::to_string))
+        .flatten();
+
+    // File responses answer Range requests after the request has been consumed
+    let range = request.headers.get("range").map(|range| {
+        let if_range = request.headers.get("if-range").map(str::to_string);
+        (range.to_string(), if_range)
+    });
+
+    // Sampled requests are recorded as the handler receives them
+    let recording = ctx.settings.recorder.as_ref().and_then(|recorder| {
+        let pending = recorder.capture(&route_match.pattern, &request)?;
+        Some((recorder, pending, Instant::now()))
+    });
+
+    timer.enter(timing::Phase::Handler);
+
     // Pass the full request (with body) to the handler by value - no clone needed
-    let handler_id = route_match.handler_id;
-    let result = handlers
-        .invoke_async(handler_id, request, dependency_container.clone())
-        .await;
+    // PERF: Use the handler metadata stored in the route entry (no registry lock)
+    let result = match &route_match.handler {
+        Some(meta) => match meta.canary() {
+            Some(canary) => {
+                // Serve the request from the stable or canary handler
+                let variant = canary.choose(&request);
+                let target = match variant {
+                    CanaryVariant::Stable => meta,
+                    CanaryVariant::Canary => canary.handler(),
+                };
+                let started = Instant::now();
+                let result = handlers
+                    .invoke_meta_async(target, request, dependency_container.clone(), scope)
+                    .await;
+                let status = result.as_ref().map(handler_status).unwrap_or(500);
+                canary.record(variant, status, started.elapsed());
+                result
+            }
+            None => {
+                handlers
+                    .invoke_meta_async(meta, request, dependency_container.clone(), scope)
+                    .await
+            }
+        },
+        None => {
+            handlers
+                .invoke_async(
+                    route_match.handler_id,
+                    request,
+                    dependency_container.clone(),
+                )
+                .await
+        }
+    };
+
+    // Commit or roll back by the handler's outcome, before the response is sent
+    let result = match transaction {
+        Some(tx) => {
+            let status = result.as_ref().ok().map(handler_status);
+            match tx.finish(status).await {
+                Ok(_) => result,
+                Err(e) => Err(e.to_string()),
+            }
+        }
+        None => result,
+    };
+
+    timer.enter(timing::Phase::Serialization);
 
     // PERF: Ultra-fast path for the most common case:
     // Handler returned a dict (JsonBytes), no after-middleware, no Prometheus.
     // Skip Response struct allocation entirely and build hyper response directly.
-    if !has_after_middleware && !guards.has_guards() {
+    let header_policy = route_match.handler.as_ref().and_then(|m| m.header_policy());
+    if !has_after_middleware
+        && !guards.has_guards()
+        && header_policy.is_none()
+        && idempotency.is_none()
+        && recording.is_none()
+    {
         let prom_guard = prometheus.read();
         let no_prometheus = prom_guard.is_none();
         drop(prom_guard);
@@ -859,13 +1820,49 @@ async fn handle_request(
         if no_prometheus {
             match result {
                 Ok(HandlerResult::JsonBytes(bytes)) => {
+                    let mut builder = HyperResponse::builder()
+                        .status(StatusCode::OK)
+                        .header("Content-Type", "application/json")
+                        .header("Vary", "Accept");
+                    if json_etags {
+                        let etag = crate::middleware::etag::json_etag(&bytes);
+                        if is_fresh(if_none_match.as_deref(), &etag) {
+                            let mut resp = not_modified(&etag);
+                            resp.set_header("Vary", "Accept");
+                            return build_hyper_response(&resp, ctx);
+                        }
+                        builder = builder.header("ETag", etag);
+                    }
+                    metrics.add_bytes_sent(bytes.len() as u64);
+                    let hyper_resp = builder.body(ResponseBody::full(bytes)).unwrap_or_else(|_| {
+                        HyperResponse::new(ResponseBody::full(Bytes::from_static(
+                            b"Internal Server Error",
+                        )))
+                    });
+                    return Ok(hyper_resp);
+                }
+                Ok(HandlerResult::Encoded(format, bytes)) => {
                     metrics.add_bytes_sent(bytes.len() as u64);
                     let hyper_resp = HyperResponse::builder()
                         .status(StatusCode::OK)
-                        .header("Content-Type", "application/json")
-                        .body(Full::new(Bytes::from(bytes)))
+                        .header("Content-Type", format.content_type())
+                        .header("Vary", "Accept")
+                        .body(ResponseBody::full(bytes))
+                        .unwrap_or_else(|_| {
+                            HyperResponse::new(ResponseBody::full(Bytes::from_static(
+                                b"Internal Server Error",
+                            )))
+                        });
+                    return Ok(hyper_resp);
+                }
+                Ok(HandlerResult::Raw(content_type, bytes)) => {
+                    metrics.add_bytes_sent(bytes.len() as u64);
+                    let hyper_resp = HyperResponse::builder()
+                        .status(StatusCode::OK)
+                        .header("Content-Type", content_type)
+                        .body(ResponseBody::full(bytes))
                         .unwrap_or_else(|_| {
-                            HyperResponse::new(Full::new(Bytes::from_static(
+                            HyperResponse::new(ResponseBody::full(Bytes::from_static(
                                 b"Internal Server Error",
                             )))
                         });
@@ -879,10 +1876,45 @@ async fn handle_request(
     // Restore request for after-middleware (the original was moved into the handler)
     let request = after_request.unwrap_or_default();
 
+    // A streamed body starts producing once the response head is final
+    let mut stream_body: Option<Box<dyn FnOnce() -> ResponseBody + Send>> = None;
+
+    // Returned values were serialized in the format the Accept header chose
+    let negotiated = matches!(&result, Ok(r) if is_negotiated(r));
+
     let mut response = match result {
         Ok(handler_result) => match handler_result {
             // PERF: Fast path - pre-serialized JSON bytes, no serde_json::Value involved
+            HandlerResult::JsonBytes(bytes) if json_etags => {
+                let etag = crate::middleware::etag::json_etag(&bytes);
+                if is_fresh(if_none_match.as_deref(), &etag) {
+                    not_modified(&etag)
+                } else {
+                    let mut resp = Response::from_json_bytes(bytes, 200);
+                    resp.set_header("ETag", &etag);
+                    resp
+                }
+            }
             HandlerResult::JsonBytes(bytes) => Response::from_json_bytes(bytes, 200),
+            HandlerResult::Encoded(format, bytes) => {
+                Response::from_encoded_bytes(format, bytes, 200)
+            }
+            HandlerResult::Raw(content_type, bytes) => {
+                Response::binary(bytes, Some(content_type), Some(200))
+            }
+            HandlerResult::Stream(stream) => {
+                let mut resp = Response::new(stream.status());
+                resp.set_header("Content-Type", stream.content_type());
+                resp.set_streaming();
+                stream_body = Some(Box::new(move || stream.into_body()));
+                resp
+            }
+            HandlerResult::File(resp) | HandlerResult::Handled(resp) => resp,
+            HandlerResult::Download(download) => {
+                let resp = download.response();
+                stream_body = Some(Box::new(move || download.into_body()));
+                resp
+            }
             // Slow path - Response objects that need special handling via serde_json::Value
             HandlerResult::JsonValue(json_value) => {
                 if let Some(obj) = json_value.as_object() {
@@ -894,10 +1926,23 @@ async fn handle_request(
                         // Reconstruct Response from serialized format
                         let status =
                             obj.get("status").and_then(|v| v.as_u64()).unwrap_or(200) as u16;
-                        let body = obj.get("body").and_then(|v| v.as_str()).unwrap_or("");
+                        let body = match obj.get("body_base64").and_then(|v| v.as_str()) {
+                            Some(encoded) => {
+                                use base64::Engine;
+                                base64::engine::general_purpose::STANDARD
+                                    .decode(encoded)
+                                    .unwrap_or_default()
+                            }
+                            None => obj
+                                .get("body")
+                                .and_then(|v| v.as_str())
+                                .unwrap_or("")
+                                .as_bytes()
+                                .to_vec(),
+                        };
 
                         let mut resp = Response::new(status);
-                        resp.set_body(body.as_bytes().to_vec());
+                        resp.set_body(body);
 
                         // Copy headers
                         if let Some(headers) = obj.get("headers").and_then(|v| v.as_object()) {
@@ -919,9 +1964,43 @@ async fn handle_request(
         },
         Err(err) => {
             metrics.inc_errors();
-            Response::error(500, &err)
+            // Handler exceptions were reported with their traceback
+            handlers
+                .error_handlers()
+                .error_response(&request, 500, &err)
+                .await
         }
     };
+    if negotiated {
+        response.set_header("Vary", "Accept");
+    }
+
+    // The response is recorded as the handler produced it
+    if let Some((recorder, pending, started)) = recording {
+        recorder.record(pending, &response, started.elapsed());
+    }
+
+    timer.enter(timing::Phase::AfterMiddleware);
+
+    // Route header policy applies before after-middleware sees the response
+    if let Some(policy) = header_policy {
+        policy.apply(&mut response);
+    }
+
+    // Route middleware is innermost, so its after hooks run first
+    if let Some(chain) = &route_middleware {
+        match chain.after(&request, &mut response) {
+            Ok(MiddlewareAction::Continue) => {}
+            Ok(MiddlewareAction::Stop(new_response)) => {
+                return build_hyper_response(&new_response, ctx);
+            }
+            Err(e) => {
+                metrics.inc_errors();
+                let error_response = error_response(handlers, &request, e.status, &e.message).await;
+                return build_hyper_response(&error_response, ctx);
+            }
+        }
+    }
 
     // PERF: Skip after middleware if none registered
     if !middleware.is_async_empty() {
@@ -931,12 +2010,12 @@ async fn handle_request(
         {
             Ok(MiddlewareAction::Continue) => {}
             Ok(MiddlewareAction::Stop(new_response)) => {
-                return build_hyper_response(&new_response, metrics);
+                return build_hyper_response(&new_response, ctx);
             }
             Err(e) => {
                 metrics.inc_errors();
-                let error_response = Response::error(e.status, &e.message);
-                return build_hyper_response(&error_response, metrics);
+                let error_response = error_response(handlers, &request, e.status, &e.message).await;
+                return build_hyper_response(&error_response, ctx);
             }
         }
     }
@@ -945,26 +2024,242 @@ async fn handle_request(
         match middleware.execute_after(&request, &mut response) {
             Ok(MiddlewareAction::Continue) => {}
             Ok(MiddlewareAction::Stop(new_response)) => {
-                return build_hyper_response(&new_response, metrics);
+                return build_hyper_response(&new_response, ctx);
             }
             Err(e) => {
                 metrics.inc_errors();
-                let error_response = Response::error(e.status, &e.message);
-                return build_hyper_response(&error_response, metrics);
+                let error_response = error_response(handlers, &request, e.status, &e.message).await;
+                return build_hyper_response(&error_response, ctx);
             }
         }
     }
 
-    // PERF: Only run Prometheus after if configured
-    {
-        let prom_guard = prometheus.read();
-        if let Some(ref p) = *prom_guard {
-            use crate::middleware::Middleware;
-            let _ = p.after(&request, &mut response);
+    // Store the final response for retries with the same key
+    if let Some(reservation) = idempotency {
+        reservation.finish(&response);
+    }
+
+    timer.enter(timing::Phase::Serialization);
+
+    match stream_body {
+        Some(body) if response.is_streaming() => Ok(build_streaming_response(&response, body())),
+        _ if response.is_file() => build_file_response(response, range, ctx).await,
+        _ => build_hyper_response(&response, ctx),
+    }
+}
+
+/// The error response for `status`, built by the app's handler for that
+/// status or as problem details when either is configured. Server errors
+/// are reported when error reporting is enabled.
+async fn error_response(
+    handlers: &HandlerRegistry,
+    request: &Request,
+    status: u16,
+    message: &str,
+) -> Response {
+    let error_handlers = handlers.error_handlers();
+    if status >= 500 {
+        if let Some(reporter) = error_handlers.reporter() {
+            reporter.capture_error("ServerError", message, Some(request));
+        }
+    }
+    error_handlers
+        .error_response(request, status, message)
+        .await
+}
+
+/// Begin a route's transaction on the server's database pool.
+async fn begin_transaction(
+    policy: &crate::middleware::transaction::TransactionPolicy,
+    metrics: &ServerMetrics,
+) -> Result<crate::middleware::transaction::RequestTransaction, Response> {
+    let pool = metrics.database.as_ref().ok_or_else(|| {
+        Response::error(
+            500,
+            "Route requires a transaction but no database is enabled",
+        )
+    })?;
+    policy.begin(pool).await.map_err(|e| match e {
+        crate::middleware::database::DatabaseError::Timeout => Response::error(503, &e.to_string()),
+        _ => Response::error(500, &e.to_string()),
+    })
+}
+
+/// Status code of a handler's response.
+fn handler_status(result: &HandlerResult) -> u16 {
+    match result {
+        HandlerResult::JsonValue(value) if value.get("__cello_response__").is_some() => {
+            value.get("status").and_then(|v| v.as_u64()).unwrap_or(200) as u16
+        }
+        HandlerResult::Stream(stream) => stream.status(),
+        HandlerResult::File(response) | HandlerResult::Handled(response) => response.status,
+        _ => 200,
+    }
+}
+
+/// Whether a handler's result was a returned value serialized in the
+/// negotiated format, rather than a response the handler built itself.
+fn is_negotiated(result: &HandlerResult) -> bool {
+    match result {
+        HandlerResult::JsonBytes(_) | HandlerResult::Encoded(..) => true,
+        HandlerResult::JsonValue(value) => value.get("__cello_response__").is_none(),
+        _ => false,
+    }
+}
+
+/// Build the response for a request whose method has no matching route.
+///
+/// Distinguishes an unknown path (404) from a known path with the wrong method
+/// (405 with `Allow`), and answers OPTIONS with the allowed method set.
+/// Parse a raw query string into decoded key/value pairs.
+fn parse_query(query_string: &str) -> HashMap<String, String> {
+    // PERF: Only parse query string when present
+    if query_string.is_empty() {
+        return HashMap::new();
+    }
+    query_string
+        .split('&')
+        .filter(|s| !s.is_empty())
+        .filter_map(crate::request::decode_query_pair)
+        .collect()
+}
+
+/// Read a request body, enforcing the minimum upload rate when one is set.
+async fn read_request_body<B>(req: HyperRequest<B>) -> Result<Bytes, throttle::BodyReadError>
+where
+    B: hyper::body::Body<Data = Bytes>,
+{
+    let min_rate = req.extensions().get::<throttle::MinRate>().copied();
+    read_body(req.into_body(), min_rate).await
+}
+
+async fn read_body<B>(
+    body: B,
+    min_rate: Option<throttle::MinRate>,
+) -> Result<Bytes, throttle::BodyReadError>
+where
+    B: hyper::body::Body<Data = Bytes>,
+{
+    match min_rate {
+        Some(rate) => throttle::read_body(body, rate).await,
+        None => body
+            .collect()
+            .await
+            .map(|collected| collected.to_bytes())
+            .map_err(|_| throttle::BodyReadError::Failed),
+    }
+}
+
+/// Serve a POST request as `method`, logging the override.
+fn override_method(head: &mut hyper::http::request::Parts, method: hyper::Method) {
+    eprintln!(
+        "Method override: {} {} served as {method}",
+        head.method,
+        head.uri.path()
+    );
+    let original = std::mem::replace(&mut head.method, method);
+    head.extensions
+        .insert(method_override::OriginalMethod(original));
+}
+
+/// Serve a request no route matched from a mounted WSGI app.
+///
+/// Before-middleware and guards run first and may answer the request
+/// themselves; the app's response is then streamed back as it is produced.
+async fn dispatch_mount<B>(
+    req: HyperRequest<B>,
+    mount: Arc<crate::wsgi::WsgiMount>,
+    ctx: &RequestContext<'_>,
+) -> Result<HyperResponse<ResponseBody>, Infallible>
+where
+    B: hyper::body::Body<Data = Bytes>,
+{
+    let RequestContext {
+        middleware,
+        metrics,
+        guards,
+        ..
+    } = *ctx;
+    let (mut parts, body) = req.into_parts();
+    let query_string = parts.uri.query().unwrap_or("").to_owned();
+    let body_bytes = match body.collect().await {
+        Ok(collected) => collected.to_bytes().to_vec(),
+        Err(_) => {
+            metrics.inc_errors();
+            Vec::new()
         }
+    };
+    metrics.add_bytes_received(body_bytes.len() as u64);
+
+    let mut request = Request::from_http(
+        parts.method.as_str().to_owned(),
+        parts.uri.path().to_owned(),
+        HashMap::new(),
+        parse_query(&query_string),
+        Headers::from(std::mem::take(&mut parts.headers)),
+        body_bytes,
+    );
+    request.remote_addr = parts.extensions.get::<std::net::SocketAddr>().copied();
+
+    match run_before(&mut request, middleware, guards).await {
+        Ok(MiddlewareAction::Continue) => Ok(mount.call(request, query_string).await),
+        Ok(MiddlewareAction::Stop(response)) => build_hyper_response(&response, ctx),
+        Err(e) => {
+            metrics.inc_errors();
+            let response = Response::error(e.status, &e.message);
+            build_hyper_response(&response, ctx)
+        }
+    }
+}
+
+/// Run the app's before-middleware, sync then async, and its guards on a
+/// request that no route handler sees: a WSGI mount, or a WebSocket upgrade.
+pub(crate) async fn run_before(
+    request: &mut Request,
+    middleware: &MiddlewareChain,
+    guards: &crate::middleware::guards::GuardsMiddleware,
+) -> crate::middleware::MiddlewareResult {
+    let mut outcome = Ok(MiddlewareAction::Continue);
+    if !middleware.is_empty() {
+        outcome = middleware.execute_before(request);
+    }
+    if matches!(outcome, Ok(MiddlewareAction::Continue)) && !middleware.is_async_empty() {
+        outcome = middleware.execute_before_async(request).await;
+    }
+    if matches!(outcome, Ok(MiddlewareAction::Continue)) && guards.has_guards() {
+        outcome = crate::middleware::Middleware::before(guards, request);
+    }
+    outcome
+}
+
+fn unmatched_route_response(router: &Router, method: &str, path: &str) -> Response {
+    let allowed = router.allowed_methods(path);
+    if allowed.is_empty() {
+        return Response::not_found(&format!("Not Found: {method} {path}"));
     }
 
-    build_hyper_response(&response, metrics)
+    let allow = allowed.join(", ");
+    let mut response = if method.eq_ignore_ascii_case("OPTIONS") {
+        Response::new(204)
+    } else {
+        Response::error(405, &format!("Method Not Allowed: {method} {path}"))
+    };
+    response.set_header("Allow", &allow);
+    response
+}
+
+/// Strip the body from a response to a HEAD request, keeping its Content-Length.
+fn strip_head_body(response: HyperResponse<ResponseBody>) -> HyperResponse<ResponseBody> {
+    use hyper::body::Body;
+
+    let (mut parts, body) = response.into_parts();
+    if let Some(len) = body.size_hint().exact() {
+        parts.headers.insert(
+            hyper::header::CONTENT_LENGTH,
+            hyper::header::HeaderValue::from(len),
+        );
+    }
+    HyperResponse::from_parts(parts, ResponseBody::empty())
 }
 
 /// Build a Hyper response from our Response type.
@@ -972,26 +2267,175 @@ async fn handle_request(
 #[inline]
 fn build_hyper_response(
     response: &Response,
-    metrics: &Arc<ServerMetrics>,
-) -> Result<HyperResponse<Full<Bytes>>, Infallible> {
+    ctx: &RequestContext<'_>,
+) -> Result<HyperResponse<ResponseBody>, Infallible> {
+    // The body fails before anything is written, so the connection is dropped
+    if response.is_aborted() {
+        let (tx, body) = ResponseBody::channel(1);
+        let _ = tx.try_send(Err("response aborted".into()));
+        return Ok(HyperResponse::new(body));
+    }
+
     let status = StatusCode::from_u16(response.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
 
     let mut builder = HyperResponse::builder().status(status);
 
+    // PERF: Create Bytes directly from slice - avoids intermediate Vec allocation
+    let body_slice = response.body_bytes();
+
+    let checked = match &ctx.settings.response_checks {
+        Some(checks) => match checks.check(response.status, &response.headers, body_slice) {
+            Ok(checked) => checked,
+            Err(limit) => {
+                eprintln!(
+                    "Response body of {} bytes exceeds the {limit} byte limit; sending 500",
+                    body_slice.len()
+                );
+                return build_hyper_response(&Response::error(500, "Internal Server Error"), ctx);
+            }
+        },
+        None => Vec::new(),
+    };
+
     for (key, value) in &response.headers {
-        builder = builder.header(key.as_str(), value.as_str());
+        if !checked
+            .iter()
+            .any(|(name, _)| key.eq_ignore_ascii_case(name))
+        {
+            builder = builder.header(key.as_str(), value.as_str());
+        }
+    }
+    for (name, value) in checked {
+        builder = builder.header(name, value);
     }
 
-    // PERF: Create Bytes directly from slice - avoids intermediate Vec allocation
-    let body_slice = response.body_bytes();
-    metrics.add_bytes_sent(body_slice.len() as u64);
-    let body = Full::new(Bytes::copy_from_slice(body_slice));
+    ctx.metrics.add_bytes_sent(body_slice.len() as u64);
+    let body = ResponseBody::full(Bytes::copy_from_slice(body_slice));
 
     Ok(builder.body(body).unwrap_or_else(|_| {
-        HyperResponse::new(Full::new(Bytes::from_static(b"Internal Server Error")))
+        HyperResponse::new(ResponseBody::full(Bytes::from_static(
+            b"Internal Server Error",
+        )))
     }))
 }
 
+/// Build a Hyper response whose body is streamed.
+///
+/// Framing is left to Hyper, so `Transfer-Encoding` is not copied.
+fn build_streaming_response(
+    response: &Response,
+    body: ResponseBody,
+) -> HyperResponse<ResponseBody> {
+    let status = StatusCode::from_u16(response.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    let mut builder = HyperResponse::builder().status(status);
+    for (key, value) in &response.headers {
+        if !key.eq_ignore_ascii_case("transfer-encoding") {
+            builder = builder.header(key.as_str(), value.as_str());
+        }
+    }
+    builder.body(body).unwrap_or_else(|_| {
+        HyperResponse::new(ResponseBody::full(Bytes::from_static(
+            b"Internal Server Error",
+        )))
+    })
+}
+
+/// Whether the client's `If-None-Match` validators include `etag`.
+fn is_fresh(if_none_match: Option<&str>, etag: &str) -> bool {
+    if_none_match
+        .is_some_and(|validators| crate::middleware::etag::matches_if_none_match(validators, etag))
+}
+
+/// A 304 response for a body the client has cached as `etag`.
+fn not_modified(etag: &str) -> Response {
+    let mut response = Response::new(304);
+    response.set_header("ETag", etag);
+    response
+}
+
+/// Serve a file response from disk.
+///
+/// `range` holds the request's `Range` and `If-Range` headers. A range
+/// against a whole-file 200 response gets 206 Partial Content, or 416 when
+/// it lies outside the file; `If-Range` must match the response's `ETag` or
+/// `Last-Modified` for the range to apply.
+/// The internal `X-Sendfile-*` headers are not sent.
+async fn build_file_response(
+    mut response: Response,
+    range: Option<(String, Option<String>)>,
+    ctx: &RequestContext<'_>,
+) -> Result<HyperResponse<ResponseBody>, Infallible> {
+    let crate::response::ResponseBody::File(path) = response.body_type().clone() else {
+        return build_hyper_response(&response, ctx);
+    };
+    let file_size = match tokio::fs::metadata(&path).await {
+        Ok(metadata) if metadata.is_file() => metadata.len(),
+        Ok(_) => {
+            let resp = crate::response::file_error(std::io::ErrorKind::NotFound);
+            return build_hyper_response(&resp, ctx);
+        }
+        Err(e) => return build_hyper_response(&crate::response::file_error(e.kind()), ctx),
+    };
+
+    let offset = response
+        .headers
+        .remove("X-Sendfile-Offset")
+        .and_then(|v| v.parse::<u64>().ok());
+    let length = response
+        .headers
+        .remove("X-Sendfile-Length")
+        .and_then(|v| v.parse::<u64>().ok());
+    response.headers.remove("X-Sendfile-Path");
+
+    let mut file = crate::response::FileBody::new(&path).offset(offset.unwrap_or(0));
+    let mut content_length = file_size.saturating_sub(offset.unwrap_or(0));
+    if let Some(length) = length {
+        file = file.length(length);
+        content_length = content_length.min(length);
+    }
+
+    let range = range.filter(|(_, if_range)| {
+        offset.is_none()
+            && response.status == 200
+            && if_range.as_ref().is_none_or(|validator| {
+                response.headers.iter().any(|(name, value)| {
+                    (name.eq_ignore_ascii_case("etag")
+                        || name.eq_ignore_ascii_case("last-modified"))
+                        && value == validator
+                })
+            })
+    });
+    if let Some((range, _)) = range {
+        match crate::response::parse_range_header(&range, file_size) {
+            Ok((start, end)) => {
+                response.status = 206;
+                response.set_header("Content-Range", &format!("bytes {start}-{end}/{file_size}"));
+                file = file.offset(start).length(end - start + 1);
+                content_length = end - start + 1;
+            }
+            Err(crate::response::RangeError::Unsatisfiable) => {
+                let mut resp = Response::error(416, "Range not satisfiable");
+                resp.set_header("Content-Range", &format!("bytes */{file_size}"));
+                return build_hyper_response(&resp, ctx);
+            }
+            // A malformed or multi-range header is ignored
+            Err(crate::response::RangeError::Invalid) => {}
+        }
+    }
+    response.set_header("Content-Length", &content_length.to_string());
+
+    match file.into_body().await {
+        Ok(body) => {
+            ctx.metrics.add_bytes_sent(content_length);
+            Ok(build_streaming_response(&response, body))
+        }
+        Err(e) => {
+            ctx.metrics.inc_errors();
+            build_hyper_response(&crate::response::file_error(e.kind()), ctx)
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1035,6 +2479,7 @@ mod tests {
         assert_eq!(snapshot.total_requests, 1);
         assert_eq!(snapshot.bytes_received, 100);
         assert_eq!(snapshot.bytes_sent, 200);
+        assert!(snapshot.database.is_none());
     }
 
     #[tokio::test]
@@ -116,7 +116,7 @@ impl TlsConfig {
 }
 
 /// TLS version.
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum TlsVersion {
     Tls10,
     Tls11,
@@ -0,0 +1,422 @@
+//! Request/response recording.
+//!
+//! In recording mode, a sample of the requests handled by each route is
+//! kept with its response in a ring buffer per route (`GET /users/{id}`),
+//! for writing OpenAPI examples and contract tests from real traffic or for
+//! debugging an integration. Bodies are truncated to a configured size;
+//! JSON bodies that fit are kept as JSON. The app's redaction rules are
+//! applied as pairs are recorded, so redacted values never sit in the
+//! buffers.
+//!
+//! Requests answered before their handler runs (by middleware, guards or a
+//! disabled route) are not recorded.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use parking_lot::Mutex;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::redaction::{Redaction, RedactionRules};
+use crate::request::Request;
+use crate::response::Response;
+
+/// Recording configuration.
+#[derive(Debug, Clone)]
+pub struct RecordingConfig {
+    /// Fraction of requests recorded, from 0.0 to 1.0
+    pub sample_rate: f64,
+    /// Bodies are truncated to this many bytes
+    pub max_body_size: usize,
+    /// Pairs kept per route; the oldest are dropped first
+    pub per_route: usize,
+    /// Path prefixes never recorded
+    pub exclude_paths: Vec<String>,
+}
+
+impl Default for RecordingConfig {
+    fn default() -> Self {
+        Self {
+            sample_rate: 1.0,
+            max_body_size: 4096,
+            per_route: 20,
+            exclude_paths: Vec::new(),
+        }
+    }
+}
+
+impl RecordingConfig {
+    /// Record this fraction of requests.
+    pub fn sample_rate(mut self, rate: f64) -> Result<Self, String> {
+        if !(0.0..=1.0).contains(&rate) {
+            return Err(format!("sample_rate must be between 0 and 1, got {rate}"));
+        }
+        self.sample_rate = rate;
+        Ok(self)
+    }
+
+    pub fn max_body_size(mut self, bytes: usize) -> Self {
+        self.max_body_size = bytes;
+        self
+    }
+
+    pub fn per_route(mut self, count: usize) -> Self {
+        self.per_route = count.max(1);
+        self
+    }
+
+    pub fn exclude_path(mut self, prefix: &str) -> Self {
+        self.exclude_paths.push(prefix.to_string());
+        self
+    }
+}
+
+/// A recorded message body.
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordedBody {
+    /// Size of the whole body, in bytes
+    pub size: usize,
+    /// Whether `content` holds only the start of the body
+    pub truncated: bool,
+    /// `json`, `text` or `base64`
+    pub encoding: &'static str,
+    pub content: Value,
+}
+
+/// A recorded request.
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    pub query: Vec<(String, String)>,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<RecordedBody>,
+}
+
+/// A recorded response.
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordedResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    /// `None` for empty, streamed and file bodies
+    pub body: Option<RecordedBody>,
+}
+
+/// A request and the response it got.
+#[derive(Debug, Clone, Serialize)]
+pub struct Recording {
+    /// Sequence number, increasing across routes
+    pub id: u64,
+    /// Unix time the request was recorded
+    pub timestamp: f64,
+    /// The route, as `METHOD /path/{param}`
+    pub route: String,
+    pub duration_ms: f64,
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+/// A sampled request, waiting for its response.
+pub struct PendingRecording {
+    route: String,
+    request: RecordedRequest,
+}
+
+/// Keeps the recorded pairs of each route.
+pub struct Recorder {
+    config: RecordingConfig,
+    redaction: Redaction,
+    routes: Mutex<HashMap<String, VecDeque<Recording>>>,
+    next_id: AtomicU64,
+}
+
+impl Recorder {
+    /// Create a recorder applying `redaction` to what it records.
+    pub fn new(config: RecordingConfig, redaction: Redaction) -> Self {
+        Self {
+            config,
+            redaction,
+            routes: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    /// Get the configuration.
+    pub fn config(&self) -> &RecordingConfig {
+        &self.config
+    }
+
+    /// Capture `request`, matched by the route `pattern`, if it is sampled.
+    pub fn capture(&self, pattern: &str, request: &Request) -> Option<PendingRecording> {
+        let path = request.path.as_str();
+        if self
+            .config
+            .exclude_paths
+            .iter()
+            .any(|p| path.starts_with(p.as_str()))
+            || (self.config.sample_rate < 1.0 && rand::random::<f64>() >= self.config.sample_rate)
+        {
+            return None;
+        }
+        let rules = self.redaction.rules();
+        let rules = rules.as_deref();
+        let content_type = request.headers.get("content-type");
+        let mut query: Vec<(String, String)> = request
+            .query_params
+            .iter()
+            .map(|(name, value)| (name.clone(), redact_value(rules, name, value)))
+            .collect();
+        query.sort();
+        Some(PendingRecording {
+            route: format!("{} {pattern}", request.method),
+            request: RecordedRequest {
+                method: request.method.clone(),
+                path: rules.map_or_else(|| path.to_string(), |r| r.text(path).into_owned()),
+                query,
+                headers: redact_headers(
+                    rules,
+                    request
+                        .headers
+                        .iter()
+                        .map(|(k, v)| (k.to_string(), v.to_string())),
+                ),
+                body: self.body(rules, content_type, &request.body),
+            },
+        })
+    }
+
+    /// Record `pending` with the `response` it got after `duration`.
+    pub fn record(&self, pending: PendingRecording, response: &Response, duration: Duration) {
+        let rules = self.redaction.rules();
+        let rules = rules.as_deref();
+        let body = if response.is_streaming() || response.is_file() {
+            None
+        } else {
+            let content_type = response
+                .headers
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+                .map(|(_, value)| value.as_str());
+            self.body(rules, content_type, response.body_bytes())
+        };
+        let recording = Recording {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs_f64(),
+            route: pending.route.clone(),
+            duration_ms: duration.as_secs_f64() * 1000.0,
+            request: pending.request,
+            response: RecordedResponse {
+                status: response.status,
+                headers: redact_headers(
+                    rules,
+                    response.headers.iter().map(|(k, v)| (k.clone(), v.clone())),
+                ),
+                body,
+            },
+        };
+
+        let mut routes = self.routes.lock();
+        let buffer = routes.entry(pending.route).or_default();
+        if buffer.len() >= self.config.per_route {
+            buffer.pop_front();
+        }
+        buffer.push_back(recording);
+    }
+
+    /// The recorded pairs of `route` (`METHOD /pattern`), or of every
+    /// route, oldest first.
+    pub fn recordings(&self, route: Option<&str>) -> Vec<Recording> {
+        let routes = self.routes.lock();
+        let mut recordings: Vec<Recording> = match route {
+            Some(route) => routes.get(route).into_iter().flatten().cloned().collect(),
+            None => routes.values().flatten().cloned().collect(),
+        };
+        recordings.sort_by_key(|recording| recording.id);
+        recordings
+    }
+
+    /// The routes with recordings and how many each has, sorted by route.
+    pub fn routes(&self) -> Vec<(String, usize)> {
+        let mut routes: Vec<(String, usize)> = self
+            .routes
+            .lock()
+            .iter()
+            .map(|(route, buffer)| (route.clone(), buffer.len()))
+            .collect();
+        routes.sort();
+        routes
+    }
+
+    /// Drop every recording.
+    pub fn clear(&self) {
+        self.routes.lock().clear();
+    }
+
+    /// A body truncated to the size limit: JSON if it is JSON and fits,
+    /// text if it is UTF-8, base64 otherwise. JSON is redacted before it is
+    /// truncated, so a cut never exposes a redacted field.
+    fn body(
+        &self,
+        rules: Option<&RedactionRules>,
+        content_type: Option<&str>,
+        bytes: &[u8],
+    ) -> Option<RecordedBody> {
+        if bytes.is_empty() {
+            return None;
+        }
+        let limit = self.config.max_body_size;
+        let recorded = |truncated, encoding, content| RecordedBody {
+            size: bytes.len(),
+            truncated,
+            encoding,
+            content,
+        };
+
+        let json = content_type
+            .filter(|ct| ct.contains("json"))
+            .and_then(|_| serde_json::from_slice::<Value>(bytes).ok());
+        if let Some(mut value) = json {
+            if let Some(rules) = rules {
+                rules.json(&mut value);
+            }
+            let text = value.to_string();
+            if text.len() <= limit {
+                return Some(recorded(false, "json", value));
+            }
+            let text = truncate(&text, limit);
+            return Some(recorded(true, "text", Value::String(text.to_string())));
+        }
+
+        let truncated = bytes.len() > limit;
+        let kept = &bytes[..bytes.len().min(limit)];
+        Some(match std::str::from_utf8(kept) {
+            Ok(text) => recorded(truncated, "text", Value::String(redact_text(rules, text))),
+            // A truncated body may end inside a character
+            Err(e) if truncated && e.error_len().is_none() => {
+                let text = std::str::from_utf8(&kept[..e.valid_up_to()]).unwrap_or_default();
+                recorded(truncated, "text", Value::String(redact_text(rules, text)))
+            }
+            Err(_) => recorded(truncated, "base64", Value::String(STANDARD.encode(kept))),
+        })
+    }
+}
+
+/// The longest prefix of `text` of at most `limit` bytes.
+fn truncate(text: &str, limit: usize) -> &str {
+    let mut end = limit.min(text.len());
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+fn redact_text(rules: Option<&RedactionRules>, text: &str) -> String {
+    rules.map_or_else(|| text.to_string(), |rules| rules.text(text).into_owned())
+}
+
+fn redact_value(rules: Option<&RedactionRules>, name: &str, value: &str) -> String {
+    rules.map_or_else(
+        || value.to_string(),
+        |rules| rules.value(name, value).into_owned(),
+    )
+}
+
+fn redact_headers(
+    rules: Option<&RedactionRules>,
+    headers: impl Iterator<Item = (String, String)>,
+) -> Vec<(String, String)> {
+    let mut headers: Vec<(String, String)> = headers
+        .map(|(name, value)| {
+            let value = redact_value(rules, &name, &value);
+            (name.to_ascii_lowercase(), value)
+        })
+        .collect();
+    headers.sort();
+    headers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(body: &[u8]) -> Request {
+        let mut request = Request::new("POST", "/users/7");
+        request.headers.insert("content-type", "application/json");
+        request.headers.insert("authorization", "Bearer s3cret");
+        request.body = body.to_vec();
+        request
+    }
+
+    #[test]
+    fn test_ring_buffer_per_route() {
+        let recorder = Recorder::new(RecordingConfig::default().per_route(2), Redaction::new());
+        for id in 0..3 {
+            let pending = recorder.capture("/users/{id}", &request(b"{}")).unwrap();
+            let response = Response::from_json_value(serde_json::json!({ "id": id }), 201);
+            recorder.record(pending, &response, Duration::from_millis(3));
+        }
+
+        assert_eq!(recorder.routes(), vec![("POST /users/{id}".to_string(), 2)]);
+        let recordings = recorder.recordings(Some("POST /users/{id}"));
+        assert_eq!(recordings.len(), 2);
+        assert_eq!(recordings[0].id, 2);
+        let body = recordings[1].response.body.as_ref().unwrap();
+        assert_eq!(
+            (body.encoding, &body.content),
+            ("json", &serde_json::json!({"id": 2}))
+        );
+        assert!(recorder.recordings(Some("GET /")).is_empty());
+
+        recorder.clear();
+        assert!(recorder.recordings(None).is_empty());
+    }
+
+    #[test]
+    fn test_truncation_and_redaction() {
+        let redaction = Redaction::new();
+        redaction.set(
+            RedactionRules::new()
+                .header("authorization")
+                .field("password"),
+        );
+        let config = RecordingConfig::default()
+            .max_body_size(8)
+            .exclude_path("/_debug");
+        let recorder = Recorder::new(config, redaction.clone());
+
+        let pending = recorder
+            .capture("/users/{id}", &request(br#"{"password": "hunter2"}"#))
+            .unwrap();
+        assert_eq!(
+            pending.request.headers[0],
+            ("authorization".to_string(), "[REDACTED]".to_string())
+        );
+        let body = pending.request.body.as_ref().unwrap();
+        assert!(body.truncated);
+        assert_eq!((body.size, body.encoding), (23, "text"));
+        assert_eq!(body.content, r#"{"passwo"#);
+        assert_eq!(truncate("héllo", 2), "h");
+
+        let recorder = Recorder::new(RecordingConfig::default(), redaction);
+        let pending = recorder
+            .capture("/users/{id}", &request(br#"{"password": "hunter2"}"#))
+            .unwrap();
+        let body = pending.request.body.unwrap();
+        assert_eq!(body.content, serde_json::json!({"password": "[REDACTED]"}));
+
+        let mut excluded = request(b"");
+        excluded.path = "/_debug/recordings".to_string();
+        let recorder = Recorder::new(
+            RecordingConfig::default().exclude_path("/_debug"),
+            Redaction::new(),
+        );
+        assert!(recorder.capture("/_debug/recordings", &excluded).is_none());
+    }
+}
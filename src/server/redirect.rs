@@ -0,0 +1,180 @@
+//! Plain HTTP listener redirecting to the HTTPS origin.
+//!
+//! Clients that type a bare domain or follow an old `http://` link reach
+//! port 80. The redirect listener answers every request there with a
+//! permanent redirect to the same path and query on HTTPS: `301` for
+//! `GET` and `HEAD`, `308` for other methods so that the method and body
+//! are kept. HTTPS responses then carry `Strict-Transport-Security`, after
+//! which browsers go to HTTPS directly. The header is not sent on the
+//! redirect itself, as browsers ignore it over plain HTTP (RFC 6797).
+//!
+//! ACME HTTP-01 challenges must be answered over plain HTTP on port 80, so
+//! when certificates come from an [`AcmeManager`] the listener answers its
+//! challenge requests instead of redirecting them.
+
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use http_body_util::Full;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper_util::rt::TokioIo;
+use tokio::net::TcpListener;
+use tokio::sync::broadcast;
+
+use super::acme::{AcmeManager, CHALLENGE_PATH};
+use crate::middleware::security::HstsConfig;
+
+/// Redirect listener configuration.
+#[derive(Clone)]
+pub struct RedirectConfig {
+    /// Port of the plain HTTP listener
+    pub port: u16,
+    /// Port of the HTTPS origin (defaults to the server's port)
+    pub https_port: Option<u16>,
+    /// HSTS header added to HTTPS responses
+    pub hsts: Option<HstsConfig>,
+}
+
+impl RedirectConfig {
+    /// Redirect plain HTTP requests on `port`, with a one-year HSTS policy.
+    pub fn new(port: u16) -> Self {
+        Self {
+            port,
+            https_port: None,
+            hsts: Some(HstsConfig::one_year()),
+        }
+    }
+
+    /// Redirect to another HTTPS port, e.g. 443 behind port forwarding.
+    pub fn https_port(mut self, port: u16) -> Self {
+        self.https_port = Some(port);
+        self
+    }
+
+    /// Set the HSTS policy.
+    pub fn hsts(mut self, hsts: HstsConfig) -> Self {
+        self.hsts = Some(hsts);
+        self
+    }
+
+    /// Don't send HSTS headers.
+    pub fn no_hsts(mut self) -> Self {
+        self.hsts = None;
+        self
+    }
+}
+
+/// The HTTPS URL for a request to `host` (the `Host` header, with or
+/// without a port), keeping its path and query.
+pub fn location(host: &str, https_port: u16, path_and_query: &str) -> String {
+    let host = match host.rsplit_once(':') {
+        // Keep IPv6 literals like [::1] intact
+        Some((name, port)) if !port.contains(']') => name,
+        _ => host,
+    };
+    if https_port == 443 {
+        format!("https://{host}{path_and_query}")
+    } else {
+        format!("https://{host}:{https_port}{path_and_query}")
+    }
+}
+
+fn respond(
+    req: &hyper::Request<hyper::body::Incoming>,
+    https_port: u16,
+    challenges: Option<&AcmeManager>,
+) -> hyper::Response<Full<Bytes>> {
+    let path = req.uri().path();
+    if let (Some(token), Some(manager)) = (path.strip_prefix(CHALLENGE_PATH), challenges) {
+        return manager.challenge_response(token);
+    }
+
+    let host = req
+        .headers()
+        .get(hyper::header::HOST)
+        .and_then(|h| h.to_str().ok())
+        .or_else(|| req.uri().host());
+    let Some(host) = host else {
+        return hyper::Response::builder()
+            .status(400)
+            .body(Full::new(Bytes::from_static(b"Missing Host header")))
+            .expect("valid response");
+    };
+    let path_and_query = req.uri().path_and_query().map_or("/", |p| p.as_str());
+    let status = match *req.method() {
+        hyper::Method::GET | hyper::Method::HEAD => 301,
+        _ => 308,
+    };
+    let response = hyper::Response::builder()
+        .status(status)
+        .header(
+            hyper::header::LOCATION,
+            location(host, https_port, path_and_query),
+        )
+        .header(hyper::header::CONNECTION, "close")
+        .body(Full::new(Bytes::new()));
+    // Hosts that are not valid header values can't be redirected to
+    response.unwrap_or_else(|_| {
+        hyper::Response::builder()
+            .status(400)
+            .body(Full::new(Bytes::from_static(b"Invalid Host header")))
+            .expect("valid response")
+    })
+}
+
+/// Redirect requests on `listener` to `https_port` until shutdown.
+pub async fn serve(
+    listener: TcpListener,
+    https_port: u16,
+    challenges: Option<Arc<AcmeManager>>,
+    mut shutdown: broadcast::Receiver<()>,
+) {
+    loop {
+        let stream = tokio::select! {
+            _ = shutdown.recv() => return,
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    eprintln!("Redirect listener accept error: {e}");
+                    continue;
+                }
+            },
+        };
+        let challenges = challenges.clone();
+        tokio::spawn(async move {
+            let service = service_fn(move |req: hyper::Request<hyper::body::Incoming>| {
+                let response = respond(&req, https_port, challenges.as_deref());
+                async move { Ok::<_, Infallible>(response) }
+            });
+            let _ = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await;
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redirect_location() {
+        assert_eq!(
+            location("example.com", 443, "/a?b=1"),
+            "https://example.com/a?b=1"
+        );
+        assert_eq!(
+            location("example.com:80", 8443, "/"),
+            "https://example.com:8443/"
+        );
+        assert_eq!(location("[::1]:80", 443, "/x"), "https://[::1]/x");
+        assert_eq!(location("[::1]", 443, "/x"), "https://[::1]/x");
+
+        let config = RedirectConfig::new(80).https_port(443);
+        assert_eq!(config.https_port, Some(443));
+        assert!(config.hsts.is_some());
+        assert!(config.no_hsts().hsts.is_none());
+    }
+}
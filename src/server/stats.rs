@@ -0,0 +1,157 @@
+//! Aggregated statistics snapshot.
+//!
+//! The server, gRPC, sagas, Redis and event sourcing each keep their own
+//! statistics. Each enabled component registers a source here, and one
+//! snapshot collects them all into a single JSON object keyed by component
+//! (`server`, `grpc`, `saga`, `redis`, `event_sourcing`), served at the
+//! stats endpoint and returned by `app.stats_snapshot()`. Sources only read
+//! atomics and locks, so collecting them needs no GIL.
+
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::response::Response;
+
+type Source = Arc<dyn Fn() -> Value + Send + Sync>;
+
+/// The components reporting statistics, in registration order.
+#[derive(Clone, Default)]
+pub struct StatsSources {
+    sources: Arc<RwLock<Vec<(String, Source)>>>,
+}
+
+impl StatsSources {
+    /// Create an empty set of sources.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Report the statistics returned by `stats` under `name`, replacing a
+    /// source of the same name.
+    pub fn register<T, F>(&self, name: &str, stats: F)
+    where
+        T: Serialize,
+        F: Fn() -> T + Send + Sync + 'static,
+    {
+        let source: Source = Arc::new(move || serde_json::to_value(stats()).unwrap_or(Value::Null));
+        let mut sources = self.sources.write();
+        match sources.iter_mut().find(|(n, _)| n == name) {
+            Some(existing) => existing.1 = source,
+            None => sources.push((name.to_string(), source)),
+        }
+    }
+
+    /// Names of the registered sources.
+    pub fn names(&self) -> Vec<String> {
+        self.sources
+            .read()
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// Collect every source into one object.
+    pub fn snapshot(&self) -> Value {
+        // Sources run outside the lock, so one can register another
+        let sources = self.sources.read().clone();
+        let stats: Map<String, Value> = sources
+            .into_iter()
+            .map(|(name, source)| (name, source()))
+            .collect();
+        Value::Object(stats)
+    }
+
+    /// The snapshot as a JSON response, gzip-compressed when `gzip` is set.
+    pub fn response(&self, gzip: bool) -> Response {
+        let body = serde_json::to_vec(&self.snapshot()).unwrap_or_default();
+        let mut response = Response::new(200);
+        response.set_header("Content-Type", "application/json");
+        response.set_header("Cache-Control", "no-store");
+        set_body(&mut response, body, gzip);
+        response
+    }
+}
+
+/// Set `body` on `response`, gzip-compressed when `gzip` is set and
+/// compression succeeds.
+pub fn set_body(response: &mut Response, body: Vec<u8>, gzip: bool) {
+    if gzip {
+        if let Ok(compressed) = crate::middleware::compress_gzip(&body, 6) {
+            response.set_header("Content-Encoding", "gzip");
+            response.set_header("Vary", "Accept-Encoding");
+            response.set_body(compressed);
+            return;
+        }
+    }
+    response.set_body(body);
+}
+
+/// Whether an `Accept-Encoding` header accepts gzip.
+pub fn accepts_gzip(accept_encoding: Option<&str>) -> bool {
+    accept_encoding.is_some_and(|header| {
+        header.split(',').any(|coding| {
+            let mut parts = coding.split(';');
+            let name = parts.next().unwrap_or("").trim();
+            let refused = parts.any(|param| {
+                param
+                    .trim()
+                    .strip_prefix("q=")
+                    .and_then(|q| q.trim().parse::<f32>().ok())
+                    == Some(0.0)
+            });
+            (name.eq_ignore_ascii_case("gzip") || name == "*") && !refused
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    #[test]
+    fn test_snapshot_collects_sources() {
+        let sources = StatsSources::new();
+        let requests = Arc::new(AtomicU64::new(0));
+        let counter = requests.clone();
+        sources.register(
+            "server",
+            move || serde_json::json!({"total_requests": counter.load(Ordering::Relaxed)}),
+        );
+        sources.register("saga", crate::middleware::saga::SagaStats::default);
+
+        requests.store(3, Ordering::Relaxed);
+        let snapshot = sources.snapshot();
+        assert_eq!(snapshot["server"]["total_requests"], 3);
+        assert_eq!(snapshot["saga"]["completed"], 0);
+
+        sources.register("saga", || serde_json::json!({"replaced": true}));
+        assert_eq!(sources.names(), vec!["server", "saga"]);
+        assert_eq!(sources.snapshot()["saga"]["replaced"], true);
+    }
+
+    #[test]
+    fn test_gzip_response() {
+        use std::io::Read;
+
+        let sources = StatsSources::new();
+        sources.register("server", || serde_json::json!({"ok": true}));
+        assert!(accepts_gzip(Some("br, gzip;q=0.8")));
+        assert!(!accepts_gzip(Some("gzip;q=0, br")));
+        assert!(!accepts_gzip(None));
+
+        let response = sources.response(true);
+        assert_eq!(
+            response.headers.get("Content-Encoding").map(String::as_str),
+            Some("gzip")
+        );
+        let mut json = String::new();
+        flate2::read::GzDecoder::new(response.body_bytes())
+            .read_to_string(&mut json)
+            .unwrap();
+        assert_eq!(json, r#"{"server":{"ok":true}}"#);
+    }
+}
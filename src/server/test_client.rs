@@ -0,0 +1,638 @@
+//! In-process test client.
+//!
+//! Drives requests through the same pipeline as the accept loop
+//! (routing, middleware, guards, handlers) without opening a socket, and
+//! runs WebSocket handlers against an in-memory peer once their upgrade
+//! request has passed the middleware and guards.
+//!
+//! # Example
+//! ```python
+//! from cello.testing import TestClient
+//!
+//! client = TestClient(app)
+//! response = client.get("/users/1")
+//! assert response.status_code == 200
+//! assert response.json() == {"id": "1"}
+//!
+//! with client.websocket_connect("/ws") as ws:
+//!     ws.send_text("hi")
+//!     assert ws.receive_text() == "Echo: hi"
+//! ```
+
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper::{Request as HyperRequest, Response as HyperResponse};
+use parking_lot::Mutex;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict};
+
+use super::{
+    handle_request, strip_head_body, websocket, RequestContext, RequestSettings, ResponseBody,
+    RouteHandle, Server, ServerMetrics,
+};
+use crate::middleware::guards::GuardsMiddleware;
+use crate::middleware::prometheus::PrometheusMiddleware;
+use crate::middleware::MiddlewareChain;
+use crate::request::{Headers, Request};
+use crate::response::Response;
+use crate::websocket::{WebSocketMessage, WebSocketPeer, WebSocketRegistry};
+
+// ============================================================================
+// Test Client
+// ============================================================================
+
+/// A response returned by the test client.
+#[derive(Debug, Clone)]
+pub struct TestResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Bytes,
+}
+
+impl TestResponse {
+    /// The first value of a header, matched case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Sends requests to a server's routes without a network connection.
+pub struct TestClient {
+    routes: RouteHandle,
+    middleware: Arc<MiddlewareChain>,
+    metrics: Arc<ServerMetrics>,
+    settings: RequestSettings,
+    dependency_container: Arc<crate::dependency::DependencyContainer>,
+    guards: Arc<GuardsMiddleware>,
+    prometheus: Arc<parking_lot::RwLock<Option<PrometheusMiddleware>>>,
+    websocket_handlers: WebSocketRegistry,
+}
+
+impl TestClient {
+    /// Create a client for the routes and middleware of `server`.
+    pub fn new(server: Server) -> Self {
+        Self {
+            routes: server.routes,
+            middleware: Arc::new(server.middleware),
+            metrics: Arc::new(server.metrics),
+            settings: server.settings,
+            dependency_container: server.dependency_container,
+            guards: server.guards,
+            prometheus: server.prometheus,
+            websocket_handlers: server.websocket_handlers,
+        }
+    }
+
+    /// Handle a request as the server would, including stripping the body
+    /// of a HEAD response.
+    pub async fn request(&self, req: HyperRequest<Full<Bytes>>) -> HyperResponse<ResponseBody> {
+        let is_head = req.method() == hyper::Method::HEAD;
+        let routes = self.routes.current();
+        let ctx = RequestContext {
+            router: &routes.router,
+            handlers: &routes.handlers,
+            middleware: &self.middleware,
+            metrics: &self.metrics,
+            settings: &self.settings,
+            dependency_container: &self.dependency_container,
+            guards: &self.guards,
+            prometheus: &self.prometheus,
+            websockets: &self.websocket_handlers,
+        };
+        let response = match handle_request(req, &ctx).await {
+            Ok(response) => response,
+            Err(never) => match never {},
+        };
+        if is_head {
+            strip_head_body(response)
+        } else {
+            response
+        }
+    }
+
+    /// Send a request built from its parts, as a client at 127.0.0.1.
+    pub async fn send(
+        &self,
+        method: &str,
+        uri: &str,
+        headers: &[(String, String)],
+        body: Vec<u8>,
+    ) -> Result<TestResponse, String> {
+        let mut builder = HyperRequest::builder()
+            .method(method)
+            .uri(uri)
+            .extension(std::net::SocketAddr::from(([127, 0, 0, 1], 0)));
+        for (name, value) in headers {
+            builder = builder.header(name.as_str(), value.as_str());
+        }
+        let req = builder
+            .body(Full::new(Bytes::from(body)))
+            .map_err(|e| format!("Invalid request: {e}"))?;
+
+        let (parts, body) = self.request(req).await.into_parts();
+        let body = body
+            .collect()
+            .await
+            .map_err(|e| format!("Response body failed: {e}"))?
+            .to_bytes();
+        Ok(TestResponse {
+            status: parts.status.as_u16(),
+            headers: parts
+                .headers
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_str().unwrap_or("").to_string()))
+                .collect(),
+            body,
+        })
+    }
+
+    /// The WebSocket handler registered for `path`.
+    pub fn websocket_handler(&self, path: &str) -> Option<PyObject> {
+        self.websocket_handlers.get(path)
+    }
+
+    /// Run the before-middleware and guards on a WebSocket upgrade request
+    /// before the handler is connected, as the accept loop does.
+    ///
+    /// Returns the admitted request, carrying whatever auth middleware
+    /// stored in its context, or the response rejecting the upgrade.
+    pub async fn admit_websocket(&self, request: Request) -> Result<Request, Box<Response>> {
+        websocket::admit(request, &self.middleware, &self.guards).await
+    }
+}
+
+/// A WebSocket upgrade request for `uri` from a client at 127.0.0.1.
+fn upgrade_request(uri: &str, headers: &[(String, String)], offer: Option<&str>) -> Request {
+    let (path, query) = uri.split_once('?').unwrap_or((uri, ""));
+    let mut upgrade = Headers::new();
+    upgrade.insert("connection", "Upgrade");
+    upgrade.insert("upgrade", "websocket");
+    upgrade.insert("sec-websocket-version", "13");
+    upgrade.insert("sec-websocket-key", "dGhlIHNhbXBsZSBub25jZQ==");
+    if let Some(offer) = offer {
+        upgrade.insert("sec-websocket-extensions", offer);
+    }
+    for (name, value) in headers {
+        upgrade.insert(name, value);
+    }
+    let mut request = Request::from_http(
+        "GET".to_string(),
+        path.to_string(),
+        std::collections::HashMap::new(),
+        super::parse_query(query),
+        upgrade,
+        Vec::new(),
+    );
+    request.remote_addr = Some(std::net::SocketAddr::from(([127, 0, 0, 1], 0)));
+    request
+}
+
+// ============================================================================
+// Python Bindings
+// ============================================================================
+
+/// Test client for a Cello app, exposed to Python as `TestClient`.
+#[pyclass(name = "TestClient")]
+pub struct PyTestClient {
+    client: Arc<TestClient>,
+}
+
+#[pymethods]
+impl PyTestClient {
+    #[new]
+    fn new(app: PyRef<'_, crate::Cello>) -> Self {
+        let config = super::ServerConfig::new("127.0.0.1", 0);
+        Self {
+            client: Arc::new(TestClient::new(app.server(config))),
+        }
+    }
+
+    /// Send a request; returns an awaitable resolving to a `TestResponse`.
+    ///
+    /// `path` may include a query string.
+    #[pyo3(signature = (method, path, headers=None, body=None))]
+    fn request<'py>(
+        &self,
+        py: Python<'py>,
+        method: String,
+        path: String,
+        headers: Option<Vec<(String, String)>>,
+        body: Option<Vec<u8>>,
+    ) -> PyResult<&'py PyAny> {
+        let client = self.client.clone();
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            client
+                .send(
+                    &method.to_uppercase(),
+                    &path,
+                    &headers.unwrap_or_default(),
+                    body.unwrap_or_default(),
+                )
+                .await
+                .map(PyTestResponse::from)
+                .map_err(pyo3::exceptions::PyValueError::new_err)
+        })
+    }
+
+    /// Connect to a WebSocket route, running its handler in a background
+    /// thread against an in-memory socket. `extensions` is sent as the
+    /// `Sec-WebSocket-Extensions` offer.
+    ///
+    /// The upgrade request, with `headers`, first passes through the app's
+    /// middleware and guards; a rejection raises `ConnectionRefusedError`
+    /// carrying the `Response`.
+    #[pyo3(signature = (path, extensions=None, headers=None))]
+    fn websocket_connect(
+        &self,
+        py: Python<'_>,
+        path: &str,
+        extensions: Option<&str>,
+        headers: Option<Vec<(String, String)>>,
+    ) -> PyResult<PyWebSocketSession> {
+        let route = path.split_once('?').map_or(path, |(route, _)| route);
+        let handler = self.client.websocket_handler(route).ok_or_else(|| {
+            pyo3::exceptions::PyValueError::new_err(format!(
+                "No WebSocket route registered for {route}"
+            ))
+        })?;
+
+        let request = upgrade_request(path, &headers.unwrap_or_default(), extensions);
+        let client = self.client.clone();
+        let base = request.clone();
+        let admitted = py.allow_threads(|| {
+            pyo3_asyncio::tokio::get_runtime().block_on(client.admit_websocket(request))
+        });
+        let request = match admitted {
+            Ok(request) => request,
+            Err(response) => {
+                return Err(pyo3::exceptions::PyConnectionRefusedError::new_err((
+                    (*response).into_py(py),
+                )))
+            }
+        };
+        let authenticator = websocket::authenticator(
+            base,
+            client.middleware.clone(),
+            client.guards.clone(),
+            pyo3_asyncio::tokio::get_runtime().handle().clone(),
+        );
+
+        let (socket, peer) =
+            self.client
+                .websocket_handlers
+                .accept(request, extensions, Some(authenticator));
+        let socket = Py::new(py, socket)?;
+
+        let thread = websocket::spawn_handler(handler, socket, peer.outgoing.clone());
+
+        Ok(PyWebSocketSession {
+            peer,
+            thread: Mutex::new(Some(thread)),
+        })
+    }
+}
+
+/// A response from the test client, exposed to Python as `TestResponse`.
+#[pyclass(name = "TestResponse")]
+pub struct PyTestResponse {
+    response: TestResponse,
+}
+
+impl From<TestResponse> for PyTestResponse {
+    fn from(response: TestResponse) -> Self {
+        Self { response }
+    }
+}
+
+#[pymethods]
+impl PyTestResponse {
+    /// HTTP status code.
+    #[getter]
+    fn status_code(&self) -> u16 {
+        self.response.status
+    }
+
+    /// Response headers, with lowercase names.
+    #[getter]
+    fn headers<'py>(&self, py: Python<'py>) -> PyResult<&'py PyDict> {
+        let headers = PyDict::new(py);
+        for (name, value) in &self.response.headers {
+            headers.set_item(name, value)?;
+        }
+        Ok(headers)
+    }
+
+    /// Raw response body.
+    #[getter]
+    fn content<'py>(&self, py: Python<'py>) -> &'py PyBytes {
+        PyBytes::new(py, &self.response.body)
+    }
+
+    /// Response body decoded as UTF-8.
+    #[getter]
+    fn text(&self) -> String {
+        String::from_utf8_lossy(&self.response.body).into_owned()
+    }
+
+    /// Response body parsed as JSON.
+    fn json(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let value: serde_json::Value = serde_json::from_slice(&self.response.body)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        crate::json::json_to_python(py, &value)
+    }
+
+    /// Look up a header case-insensitively.
+    fn header(&self, name: &str) -> Option<String> {
+        self.response.header(name).map(str::to_string)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<TestResponse [{}]>", self.response.status)
+    }
+}
+
+/// Client side of a WebSocket test session, exposed to Python as
+/// `WebSocketSession`.
+#[pyclass(name = "WebSocketSession")]
+pub struct PyWebSocketSession {
+    peer: WebSocketPeer,
+    thread: Mutex<Option<JoinHandle<Result<(), String>>>>,
+}
+
+#[pymethods]
+impl PyWebSocketSession {
+    /// Send a text message to the handler.
+    fn send_text(&self, text: &str) {
+        self.peer.send(WebSocketMessage::from_text(text));
+    }
+
+    /// Send a binary message to the handler.
+    fn send_bytes(&self, data: Vec<u8>) {
+        self.peer.send(WebSocketMessage::from_binary(data));
+    }
+
+    /// Send a message to the handler.
+    fn send(&self, message: WebSocketMessage) {
+        self.peer.send(message);
+    }
+
+    /// Receive the next message sent by the handler.
+    ///
+    /// Server pings are answered while this waits. Returns `None` once the
+    /// handler has closed the connection or returned; raises `TimeoutError`
+    /// if nothing arrives within `timeout` seconds.
+    #[pyo3(signature = (timeout=5.0))]
+    fn receive(&self, py: Python<'_>, timeout: f64) -> PyResult<Option<WebSocketMessage>> {
+        let timeout = crate::seconds_arg("timeout", timeout)?;
+        let peer = self.peer.clone();
+        match py.allow_threads(move || peer.recv(Some(timeout))) {
+            Some(message) => Ok(Some(message)),
+            None if self.peer.outgoing.is_closed() => Ok(None),
+            None => Err(pyo3::exceptions::PyTimeoutError::new_err(
+                "No WebSocket message received before the timeout",
+            )),
+        }
+    }
+
+    /// Whether the handler has closed the connection.
+    #[getter]
+    fn closed(&self) -> bool {
+        self.peer.outgoing.is_closed()
+    }
+
+    /// Why the server closed the connection (`"pong_timeout"`,
+    /// `"idle_timeout"`, `"message_too_big"`, `"token_expired"` or
+    /// `"invalid_message"`), if it
+    /// did.
+    #[getter]
+    fn close_reason(&self) -> Option<&'static str> {
+        self.peer.liveness.close_reason().map(|r| r.as_str())
+    }
+
+    /// The negotiated `Sec-WebSocket-Extensions` value, if any.
+    #[getter]
+    fn extensions(&self) -> Option<String> {
+        self.peer
+            .deflate
+            .as_ref()
+            .map(|params| params.response_header())
+    }
+
+    /// Close the connection and wait for the handler to return.
+    ///
+    /// Raises `RuntimeError` if the handler raised.
+    #[pyo3(signature = (timeout=5.0))]
+    fn close(&self, py: Python<'_>, timeout: f64) -> PyResult<()> {
+        let timeout = crate::seconds_arg("timeout", timeout)?;
+        self.peer.send(WebSocketMessage::close());
+        let Some(thread) = self.thread.lock().take() else {
+            return Ok(());
+        };
+        let outgoing = self.peer.outgoing.clone();
+        let finished = py.allow_threads(|| wait_closed(&outgoing, timeout));
+        if !finished {
+            // Leave the thread running; it ends when the handler returns.
+            return Err(pyo3::exceptions::PyTimeoutError::new_err(
+                "WebSocket handler did not return after the connection closed",
+            ));
+        }
+        match py.allow_threads(|| thread.join()) {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(e)) => Err(pyo3::exceptions::PyRuntimeError::new_err(format!(
+                "WebSocket handler failed: {e}"
+            ))),
+            Err(_) => Err(pyo3::exceptions::PyRuntimeError::new_err(
+                "WebSocket handler panicked",
+            )),
+        }
+    }
+}
+
+/// Wait for the handler to close its side, discarding unread messages.
+fn wait_closed(outgoing: &crate::websocket::MessageQueue, timeout: Duration) -> bool {
+    let deadline = std::time::Instant::now() + timeout;
+    loop {
+        let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+        if outgoing.pop(Some(remaining)).is_none() {
+            return outgoing.is_closed();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handler::HandlerRegistry;
+    use crate::router::Router;
+
+    fn client(routes: &[(&str, &str, &str)]) -> TestClient {
+        pyo3::prepare_freethreaded_python();
+
+        let mut router = Router::new();
+        let mut handlers = HandlerRegistry::new();
+        Python::with_gil(|py| {
+            let globals = PyDict::new(py);
+            globals
+                .set_item("Response", py.get_type::<crate::response::Response>())
+                .unwrap();
+            for (method, path, source) in routes {
+                let handler: PyObject = py.eval(source, Some(globals), None).unwrap().into();
+                let (id, meta) = handlers.register_with_meta(handler);
+                router.add_handler_route(method, path, id, meta).unwrap();
+            }
+        });
+
+        TestClient::new(Server::simple(
+            "127.0.0.1".to_string(),
+            0,
+            router,
+            handlers,
+            MiddlewareChain::new(),
+            WebSocketRegistry::new(),
+        ))
+    }
+
+    // ---------- Test Client Tests ----------
+
+    #[tokio::test]
+    async fn test_get_json() {
+        let client = client(&[("GET", "/users/{id}", "lambda req: {'id': req.params['id']}")]);
+        let response = client
+            .send("GET", "/users/7", &[], Vec::new())
+            .await
+            .unwrap();
+
+        assert_eq!(response.status, 200);
+        assert_eq!(response.header("content-type"), Some("application/json"));
+        let body: serde_json::Value = serde_json::from_slice(&response.body).unwrap();
+        assert_eq!(body, serde_json::json!({"id": "7"}));
+    }
+
+    #[tokio::test]
+    async fn test_post_body_and_headers() {
+        let client = client(&[(
+            "POST",
+            "/echo",
+            "lambda req: {'body': req.text(), 'token': req.get_header('x-token')}",
+        )]);
+        let headers = vec![("X-Token".to_string(), "abc".to_string())];
+        let response = client
+            .send("POST", "/echo", &headers, b"hello".to_vec())
+            .await
+            .unwrap();
+
+        let body: serde_json::Value = serde_json::from_slice(&response.body).unwrap();
+        assert_eq!(body, serde_json::json!({"body": "hello", "token": "abc"}));
+    }
+
+    #[tokio::test]
+    async fn test_not_found_and_head() {
+        let client = client(&[("GET", "/", "lambda req: {'ok': True}")]);
+
+        let missing = client
+            .send("GET", "/missing", &[], Vec::new())
+            .await
+            .unwrap();
+        assert_eq!(missing.status, 404);
+
+        let head = client.send("HEAD", "/", &[], Vec::new()).await.unwrap();
+        assert_eq!(head.status, 200);
+        assert!(head.body.is_empty());
+        assert_eq!(head.header("content-length"), Some("11"));
+    }
+
+    #[tokio::test]
+    async fn test_panic_isolation() {
+        struct Explode;
+
+        impl crate::middleware::Middleware for Explode {
+            fn before(
+                &self,
+                request: &mut crate::request::Request,
+            ) -> crate::middleware::MiddlewareResult {
+                if request.path == "/explode" {
+                    panic!("middleware exploded");
+                }
+                Ok(crate::middleware::MiddlewareAction::Continue)
+            }
+        }
+
+        let client = client(&[
+            ("GET", "/", "lambda req: {'ok': True}"),
+            ("GET", "/explode", "lambda req: {'ok': True}"),
+        ]);
+        client.middleware.add(Explode);
+
+        let response = client
+            .send("GET", "/explode", &[], Vec::new())
+            .await
+            .unwrap();
+        assert_eq!(response.status, 500);
+        assert_eq!(client.metrics.snapshot().total_panics, 1);
+
+        let response = client.send("GET", "/", &[], Vec::new()).await.unwrap();
+        assert_eq!(response.status, 200);
+    }
+
+    #[tokio::test]
+    async fn test_websocket_upgrade_admission() {
+        struct BearerUser;
+
+        impl crate::middleware::Middleware for BearerUser {
+            fn before(&self, request: &mut Request) -> crate::middleware::MiddlewareResult {
+                if let Some(token) = request.headers.get("authorization") {
+                    let name = token.trim_start_matches("Bearer ").to_string();
+                    request
+                        .context
+                        .insert("user".to_string(), serde_json::json!({"name": name}));
+                }
+                Ok(crate::middleware::MiddlewareAction::Continue)
+            }
+        }
+
+        let client = client(&[]);
+        client.middleware.add(BearerUser);
+        client
+            .guards
+            .add_guard(crate::middleware::guards::AuthenticatedGuard::new());
+
+        match client
+            .admit_websocket(upgrade_request("/ws", &[], None))
+            .await
+        {
+            Ok(_) => panic!("upgrade without a token was admitted"),
+            Err(rejected) => assert_eq!(rejected.status, 401),
+        }
+
+        let headers = vec![("Authorization".to_string(), "Bearer alice".to_string())];
+        let admitted = match client
+            .admit_websocket(upgrade_request("/ws?room=1", &headers, None))
+            .await
+        {
+            Ok(admitted) => admitted,
+            Err(rejected) => panic!("upgrade rejected with {}", rejected.status),
+        };
+        assert_eq!(admitted.path, "/ws");
+        assert_eq!(admitted.headers.get("upgrade"), Some("websocket"));
+        assert_eq!(
+            crate::websocket::principal(&admitted),
+            Some(&serde_json::json!({"name": "alice"}))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_invalid_method() {
+        let client = client(&[]);
+        assert!(client
+            .send("BAD METHOD", "/", &[], Vec::new())
+            .await
+            .is_err());
+    }
+}
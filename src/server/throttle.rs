@@ -0,0 +1,519 @@
+//! Bandwidth throttling and slow-client protection.
+//!
+//! Request-count rate limiting does not help against clients that hold
+//! connections open by trickling bytes (slowloris and slow-POST attacks) or
+//! that saturate the link with a single large transfer. This module covers
+//! the connection level:
+//!
+//! - a deadline for reading the request head,
+//! - a minimum upload rate for request bodies,
+//! - a per-client cap on concurrent connections,
+//! - token-bucket bandwidth limits per connection and per client IP.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use bytes::{Bytes, BytesMut};
+use http_body_util::BodyExt;
+use parking_lot::Mutex;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::{Instant, Sleep};
+
+// ============================================================================
+// Configuration
+// ============================================================================
+
+/// Minimum upload rate for request bodies.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MinRate {
+    /// Bytes per second the client must sustain
+    pub bytes_per_sec: u64,
+    /// Time allowed before the rate is enforced
+    pub grace: Duration,
+}
+
+impl MinRate {
+    /// The latest instant by which `received` bytes must have arrived.
+    pub(crate) fn deadline(&self, start: Instant, received: u64) -> Instant {
+        start + self.grace + Duration::from_secs_f64(received as f64 / self.bytes_per_sec as f64)
+    }
+}
+
+/// Connection throttling configuration.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ThrottleConfig {
+    /// Deadline for receiving a complete request head
+    pub header_read_timeout: Option<Duration>,
+    /// Minimum request body upload rate
+    pub min_body_rate: Option<MinRate>,
+    /// Bytes per second, in each direction, for a single connection
+    pub connection_bandwidth: Option<u64>,
+    /// Bytes per second, in each direction, shared by all connections of a client IP
+    pub client_bandwidth: Option<u64>,
+    /// Maximum concurrent connections from a single client IP
+    pub max_connections_per_client: Option<usize>,
+}
+
+impl ThrottleConfig {
+    /// Create an empty configuration that throttles nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the deadline for receiving a complete request head.
+    pub fn header_read_timeout(mut self, timeout: Duration) -> Self {
+        self.header_read_timeout = Some(timeout);
+        self
+    }
+
+    /// Require request bodies to arrive at `bytes_per_sec` after `grace`.
+    pub fn min_body_rate(mut self, bytes_per_sec: u64, grace: Duration) -> Self {
+        self.min_body_rate = Some(MinRate {
+            bytes_per_sec,
+            grace,
+        });
+        self
+    }
+
+    /// Limit each connection to `bytes_per_sec` in each direction.
+    pub fn connection_bandwidth(mut self, bytes_per_sec: u64) -> Self {
+        self.connection_bandwidth = Some(bytes_per_sec);
+        self
+    }
+
+    /// Limit each client IP to `bytes_per_sec` in each direction.
+    pub fn client_bandwidth(mut self, bytes_per_sec: u64) -> Self {
+        self.client_bandwidth = Some(bytes_per_sec);
+        self
+    }
+
+    /// Limit concurrent connections per client IP.
+    pub fn max_connections_per_client(mut self, max: usize) -> Self {
+        self.max_connections_per_client = Some(max);
+        self
+    }
+
+    /// Check that every configured limit is non-zero.
+    pub fn validate(&self) -> Result<(), String> {
+        let zero = |value: Option<u64>| value == Some(0);
+        if self.header_read_timeout == Some(Duration::ZERO) {
+            return Err("header_timeout must be positive".to_string());
+        }
+        if zero(self.min_body_rate.map(|rate| rate.bytes_per_sec)) {
+            return Err("min_body_rate must be positive".to_string());
+        }
+        if zero(self.connection_bandwidth) || zero(self.client_bandwidth) {
+            return Err("bandwidth limits must be positive".to_string());
+        }
+        if self.max_connections_per_client == Some(0) {
+            return Err("max_connections_per_client must be positive".to_string());
+        }
+        Ok(())
+    }
+}
+
+// ============================================================================
+// Token Bucket
+// ============================================================================
+
+/// A token bucket refilled at `rate` bytes per second, holding at most one
+/// second of tokens.
+#[derive(Debug)]
+pub struct Bandwidth {
+    rate: f64,
+    state: Mutex<(f64, Instant)>,
+}
+
+impl Bandwidth {
+    /// Create a full bucket.
+    pub fn new(bytes_per_sec: u64) -> Self {
+        let rate = bytes_per_sec as f64;
+        Self {
+            rate,
+            state: Mutex::new((rate, Instant::now())),
+        }
+    }
+
+    /// Whole bytes available now.
+    fn available(&self) -> usize {
+        let mut state = self.state.lock();
+        let now = Instant::now();
+        let elapsed = now.duration_since(state.1).as_secs_f64();
+        *state = ((state.0 + elapsed * self.rate).min(self.rate), now);
+        state.0.max(0.0) as usize
+    }
+
+    /// Time until at least one byte is available.
+    fn wait(&self) -> Duration {
+        let tokens = self.state.lock().0;
+        Duration::from_secs_f64(((1.0 - tokens) / self.rate).max(0.0))
+    }
+
+    /// Spend `bytes` tokens.
+    fn consume(&self, bytes: usize) {
+        self.state.lock().0 -= bytes as f64;
+    }
+}
+
+/// The buckets governing one direction of a connection.
+struct Limiter {
+    buckets: Vec<Arc<Bandwidth>>,
+    sleep: Option<Pin<Box<Sleep>>>,
+}
+
+impl Limiter {
+    fn new(buckets: impl IntoIterator<Item = Option<Arc<Bandwidth>>>) -> Self {
+        Self {
+            buckets: buckets.into_iter().flatten().collect(),
+            sleep: None,
+        }
+    }
+
+    /// Bytes that may be transferred now, at most `want`, or `Pending` until
+    /// every bucket has refilled.
+    fn poll_quota(&mut self, cx: &mut Context<'_>, want: usize) -> Poll<usize> {
+        if self.buckets.is_empty() || want == 0 {
+            return Poll::Ready(want);
+        }
+        loop {
+            if let Some(sleep) = self.sleep.as_mut() {
+                if sleep.as_mut().poll(cx).is_pending() {
+                    return Poll::Pending;
+                }
+                self.sleep = None;
+            }
+            let quota = self
+                .buckets
+                .iter()
+                .map(|bucket| bucket.available())
+                .fold(want, usize::min);
+            if quota > 0 {
+                return Poll::Ready(quota);
+            }
+            let wait = self
+                .buckets
+                .iter()
+                .map(|bucket| bucket.wait())
+                .max()
+                .unwrap_or_default();
+            self.sleep = Some(Box::pin(tokio::time::sleep(wait)));
+        }
+    }
+
+    fn consume(&self, bytes: usize) {
+        for bucket in &self.buckets {
+            bucket.consume(bytes);
+        }
+    }
+}
+
+// ============================================================================
+// Per-Client State
+// ============================================================================
+
+struct Client {
+    connections: usize,
+    read: Option<Arc<Bandwidth>>,
+    write: Option<Arc<Bandwidth>>,
+}
+
+/// Shared throttling state for a server.
+pub struct Throttle {
+    config: ThrottleConfig,
+    clients: Mutex<HashMap<IpAddr, Client>>,
+}
+
+impl Throttle {
+    /// Create throttling state from a configuration.
+    pub fn new(config: ThrottleConfig) -> Arc<Self> {
+        Arc::new(Self {
+            config,
+            clients: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// The configuration.
+    pub fn config(&self) -> &ThrottleConfig {
+        &self.config
+    }
+
+    /// Admit a connection from `ip`, or `None` when the client already holds
+    /// its maximum number of connections.
+    pub fn admit(self: &Arc<Self>, ip: IpAddr) -> Option<Permit> {
+        let ip = ip.to_canonical();
+        let mut clients = self.clients.lock();
+        let client = clients.entry(ip).or_insert_with(|| Client {
+            connections: 0,
+            read: self
+                .config
+                .client_bandwidth
+                .map(|rate| Arc::new(Bandwidth::new(rate))),
+            write: self
+                .config
+                .client_bandwidth
+                .map(|rate| Arc::new(Bandwidth::new(rate))),
+        });
+        if self
+            .config
+            .max_connections_per_client
+            .is_some_and(|max| client.connections >= max)
+        {
+            return None;
+        }
+        client.connections += 1;
+        Some(Permit {
+            throttle: self.clone(),
+            ip,
+            read: client.read.clone(),
+            write: client.write.clone(),
+        })
+    }
+
+    /// Number of open connections from `ip`.
+    pub fn connections(&self, ip: IpAddr) -> usize {
+        self.clients
+            .lock()
+            .get(&ip.to_canonical())
+            .map_or(0, |client| client.connections)
+    }
+}
+
+/// A client's claim on one connection slot, released on drop.
+pub struct Permit {
+    throttle: Arc<Throttle>,
+    ip: IpAddr,
+    read: Option<Arc<Bandwidth>>,
+    write: Option<Arc<Bandwidth>>,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let mut clients = self.throttle.clients.lock();
+        if let Some(client) = clients.get_mut(&self.ip) {
+            client.connections -= 1;
+            if client.connections == 0 {
+                clients.remove(&self.ip);
+            }
+        }
+    }
+}
+
+// ============================================================================
+// Throttled Stream
+// ============================================================================
+
+/// A stream whose reads and writes are paced by token buckets.
+pub struct ThrottledIo<T> {
+    inner: T,
+    read: Limiter,
+    write: Limiter,
+    _permit: Option<Permit>,
+}
+
+impl<T> ThrottledIo<T> {
+    /// Wrap an accepted stream with the limits of its permit. Without a
+    /// permit the stream is passed through untouched.
+    pub fn new(inner: T, permit: Option<Permit>) -> Self {
+        let (read, write) = match &permit {
+            Some(permit) => {
+                let connection = || {
+                    permit
+                        .throttle
+                        .config
+                        .connection_bandwidth
+                        .map(|rate| Arc::new(Bandwidth::new(rate)))
+                };
+                (
+                    Limiter::new([connection(), permit.read.clone()]),
+                    Limiter::new([connection(), permit.write.clone()]),
+                )
+            }
+            None => (Limiter::new([]), Limiter::new([])),
+        };
+        Self {
+            inner,
+            read,
+            write,
+            _permit: permit,
+        }
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for ThrottledIo<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if this.read.buckets.is_empty() {
+            return Pin::new(&mut this.inner).poll_read(cx, buf);
+        }
+        let quota = match this.read.poll_quota(cx, buf.remaining()) {
+            Poll::Ready(quota) => quota,
+            Poll::Pending => return Poll::Pending,
+        };
+        let mut limited = ReadBuf::new(&mut buf.initialize_unfilled()[..quota]);
+        let result = Pin::new(&mut this.inner).poll_read(cx, &mut limited);
+        let read = limited.filled().len();
+        buf.advance(read);
+        this.read.consume(read);
+        result
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for ThrottledIo<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let quota = match this.write.poll_quota(cx, buf.len()) {
+            Poll::Ready(quota) => quota,
+            Poll::Pending => return Poll::Pending,
+        };
+        let result = Pin::new(&mut this.inner).poll_write(cx, &buf[..quota]);
+        if let Poll::Ready(Ok(written)) = result {
+            this.write.consume(written);
+        }
+        result
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+// ============================================================================
+// Body Reading
+// ============================================================================
+
+/// Why a request body could not be read.
+#[derive(Debug, PartialEq, Eq)]
+pub enum BodyReadError {
+    /// The client sent the body slower than the minimum rate
+    TooSlow,
+    /// The connection failed
+    Failed,
+}
+
+/// Read a whole body, failing if it arrives slower than `rate`.
+pub async fn read_body<B>(body: B, rate: MinRate) -> Result<Bytes, BodyReadError>
+where
+    B: hyper::body::Body<Data = Bytes>,
+{
+    let start = Instant::now();
+    let mut body = std::pin::pin!(body);
+    let mut buf = BytesMut::new();
+    loop {
+        let deadline = rate.deadline(start, buf.len() as u64);
+        match tokio::time::timeout_at(deadline, body.frame()).await {
+            Err(_) => return Err(BodyReadError::TooSlow),
+            Ok(None) => return Ok(buf.freeze()),
+            Ok(Some(Err(_))) => return Err(BodyReadError::Failed),
+            Ok(Some(Ok(frame))) => {
+                if let Ok(data) = frame.into_data() {
+                    buf.extend_from_slice(&data);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_body_util::StreamBody;
+    use hyper::body::Frame;
+    use std::convert::Infallible;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn ip(last: u8) -> IpAddr {
+        IpAddr::from([10, 0, 0, last])
+    }
+
+    #[test]
+    fn test_connections_per_client() {
+        let throttle = Throttle::new(ThrottleConfig::new().max_connections_per_client(2));
+        let first = throttle.admit(ip(1)).unwrap();
+        let _second = throttle.admit(ip(1)).unwrap();
+        assert!(throttle.admit(ip(1)).is_none());
+        assert!(throttle.admit(ip(2)).is_some());
+        assert_eq!(throttle.connections(ip(1)), 2);
+
+        drop(first);
+        assert_eq!(throttle.connections(ip(1)), 1);
+        assert!(throttle.admit(ip(1)).is_some());
+        assert_eq!(throttle.connections(ip(2)), 0);
+
+        assert!(ThrottleConfig::new()
+            .client_bandwidth(0)
+            .validate()
+            .is_err());
+        assert!(ThrottleConfig::new()
+            .min_body_rate(100, Duration::from_secs(1))
+            .validate()
+            .is_ok());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_bandwidth() {
+        let throttle = Throttle::new(ThrottleConfig::new().connection_bandwidth(1000));
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let mut server = ThrottledIo::new(server, throttle.admit(ip(1)));
+        let mut client = client;
+
+        let start = Instant::now();
+        server.write_all(&[7u8; 3000]).await.unwrap();
+        assert!(start.elapsed() >= Duration::from_secs(2));
+
+        let mut received = vec![0u8; 3000];
+        client.read_exact(&mut received).await.unwrap();
+        client.write_all(&received).await.unwrap();
+
+        let start = Instant::now();
+        let mut echoed = vec![0u8; 3000];
+        server.read_exact(&mut echoed).await.unwrap();
+        assert!(start.elapsed() >= Duration::from_secs(2));
+        assert_eq!(echoed, received);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_min_body_rate() {
+        let rate = MinRate {
+            bytes_per_sec: 100,
+            grace: Duration::from_secs(1),
+        };
+        let chunk = |n| Ok::<_, Infallible>(Frame::data(Bytes::from(vec![b'x'; n])));
+
+        let (tx, rx) = tokio::sync::mpsc::channel(4);
+        tx.send(chunk(100)).await.unwrap();
+        tx.send(chunk(100)).await.unwrap();
+        drop(tx);
+        let body = StreamBody::new(channel_stream(rx));
+        assert_eq!(read_body(body, rate).await.unwrap().len(), 200);
+
+        let (tx, rx) = tokio::sync::mpsc::channel(4);
+        tx.send(chunk(10)).await.unwrap();
+        let body = StreamBody::new(channel_stream(rx));
+        assert_eq!(read_body(body, rate).await, Err(BodyReadError::TooSlow));
+        drop(tx);
+    }
+
+    fn channel_stream<T>(
+        mut rx: tokio::sync::mpsc::Receiver<T>,
+    ) -> impl futures_util::Stream<Item = T> {
+        futures_util::stream::poll_fn(move |cx| rx.poll_recv(cx))
+    }
+}
@@ -0,0 +1,363 @@
+//! Per-phase request timing and the slow request log.
+//!
+//! The server splits each request's latency into phases: routing, reading
+//! the body, middleware before the handler (including guards and
+//! validation), the handler, middleware after it, building the response,
+//! and writing it. Totals across requests feed the phase breakdown of
+//! `MetricsSnapshot`, and each phase is observed in a Prometheus histogram
+//! when Prometheus metrics are enabled. The phases up to the response head
+//! can be sent to the client in a `Server-Timing` header. When a slow
+//! request threshold is configured, requests that exceed it are logged with
+//! their breakdown and request metadata.
+
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use hyper::{Method, Uri};
+
+use super::ResponseBody;
+
+/// A phase of handling a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Matching the route
+    Routing,
+    /// Reading the request body
+    Body,
+    /// Middleware, guards and validation before the handler
+    BeforeMiddleware,
+    /// The route handler
+    Handler,
+    /// Middleware after the handler
+    AfterMiddleware,
+    /// Building the response
+    Serialization,
+    /// Sending the response body, after the response head is returned
+    Write,
+}
+
+impl Phase {
+    /// All phases, in the order a request goes through them.
+    pub const ALL: [Phase; 7] = [
+        Phase::Routing,
+        Phase::Body,
+        Phase::BeforeMiddleware,
+        Phase::Handler,
+        Phase::AfterMiddleware,
+        Phase::Serialization,
+        Phase::Write,
+    ];
+
+    /// Phase name, as used in logs, metric labels and `Server-Timing`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Phase::Routing => "routing",
+            Phase::Body => "body",
+            Phase::BeforeMiddleware => "before_middleware",
+            Phase::Handler => "handler",
+            Phase::AfterMiddleware => "after_middleware",
+            Phase::Serialization => "serialization",
+            Phase::Write => "write",
+        }
+    }
+}
+
+/// Phases timed while handling the request, before the response is written.
+const HANDLING: [Phase; 6] = [
+    Phase::Routing,
+    Phase::Body,
+    Phase::BeforeMiddleware,
+    Phase::Handler,
+    Phase::AfterMiddleware,
+    Phase::Serialization,
+];
+
+/// Time spent in each phase of one request.
+#[derive(Debug, Clone)]
+pub struct RequestTimer {
+    start: Instant,
+    mark: Instant,
+    current: Phase,
+    spent: [Duration; 7],
+}
+
+impl RequestTimer {
+    /// Start timing a request, in the routing phase.
+    pub fn start() -> Self {
+        let now = Instant::now();
+        Self {
+            start: now,
+            mark: now,
+            current: Phase::Routing,
+            spent: [Duration::ZERO; 7],
+        }
+    }
+
+    /// Charge the time since the last phase change to the current phase and
+    /// enter `phase`.
+    #[inline]
+    pub fn enter(&mut self, phase: Phase) {
+        let now = Instant::now();
+        self.spent[self.current as usize] += now - self.mark;
+        self.mark = now;
+        self.current = phase;
+    }
+
+    /// Charge the remaining time to the current phase and return the
+    /// request's total latency.
+    pub fn finish(&mut self) -> Duration {
+        self.enter(self.current);
+        self.mark - self.start
+    }
+
+    /// Time spent in `phase`.
+    pub fn spent(&self, phase: Phase) -> Duration {
+        self.spent[phase as usize]
+    }
+
+    /// Time per phase, e.g. `routing 0.1ms, body 0.0ms, ...`.
+    pub fn breakdown(&self) -> String {
+        let mut out = String::new();
+        for phase in HANDLING {
+            if !out.is_empty() {
+                out.push_str(", ");
+            }
+            let _ = write!(out, "{} {:.1}ms", phase.name(), millis(self.spent(phase)));
+        }
+        out
+    }
+
+    /// `Server-Timing` header value with each phase and the total.
+    pub fn server_timing(&self) -> String {
+        let mut out = String::new();
+        for phase in HANDLING {
+            let _ = write!(
+                out,
+                "{};dur={:.3}, ",
+                phase.name(),
+                millis(self.spent(phase))
+            );
+        }
+        let _ = write!(out, "total;dur={:.3}", millis(self.mark - self.start));
+        out
+    }
+}
+
+/// Phase times accumulated across requests.
+#[derive(Debug, Default)]
+pub struct PhaseMetrics {
+    requests: AtomicU64,
+    nanos: [AtomicU64; 7],
+    /// Responses whose write was timed
+    writes: AtomicU64,
+    slow_requests: AtomicU64,
+}
+
+impl PhaseMetrics {
+    /// Create empty phase metrics.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a handled request's phase times.
+    #[inline]
+    pub fn record(&self, timer: &RequestTimer) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        for phase in HANDLING {
+            self.add(phase, timer.spent(phase));
+        }
+    }
+
+    /// Add the time taken to write a response.
+    #[inline]
+    pub fn record_write(&self, duration: Duration) {
+        self.writes.fetch_add(1, Ordering::Relaxed);
+        self.add(Phase::Write, duration);
+    }
+
+    #[inline]
+    fn add(&self, phase: Phase, duration: Duration) {
+        let nanos = duration.as_nanos() as u64;
+        if nanos > 0 {
+            self.nanos[phase as usize].fetch_add(nanos, Ordering::Relaxed);
+        }
+    }
+
+    /// Count a request logged as slow.
+    #[inline]
+    pub fn inc_slow_requests(&self) {
+        self.slow_requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Average time per request spent in each phase.
+    pub fn snapshot(&self) -> PhaseTimings {
+        let requests = self.requests.load(Ordering::Relaxed).max(1) as f64;
+        let writes = self.writes.load(Ordering::Relaxed).max(1) as f64;
+        let total = |phase: Phase| self.nanos[phase as usize].load(Ordering::Relaxed) as f64;
+        let avg = |phase: Phase| total(phase) / requests / 1_000_000.0;
+        PhaseTimings {
+            routing_ms: avg(Phase::Routing),
+            body_ms: avg(Phase::Body),
+            before_middleware_ms: avg(Phase::BeforeMiddleware),
+            handler_ms: avg(Phase::Handler),
+            after_middleware_ms: avg(Phase::AfterMiddleware),
+            serialization_ms: avg(Phase::Serialization),
+            write_ms: total(Phase::Write) / writes / 1_000_000.0,
+            slow_requests: self.slow_requests.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Average time per request spent in each phase, in milliseconds.
+#[derive(Clone, Debug, Default, serde::Serialize)]
+pub struct PhaseTimings {
+    pub routing_ms: f64,
+    pub body_ms: f64,
+    pub before_middleware_ms: f64,
+    pub handler_ms: f64,
+    pub after_middleware_ms: f64,
+    pub serialization_ms: f64,
+    /// Average over the responses whose write was timed (those sent while
+    /// Prometheus metrics are enabled)
+    pub write_ms: f64,
+    /// Requests that exceeded the slow request threshold
+    pub slow_requests: u64,
+}
+
+/// Request metadata kept for the slow request log.
+#[derive(Debug, Clone)]
+pub struct SlowRequest {
+    method: Method,
+    uri: Uri,
+    client: Option<SocketAddr>,
+    user_agent: Option<String>,
+    request_id: Option<String>,
+}
+
+impl SlowRequest {
+    /// Capture the metadata of a request before it is handled.
+    pub fn capture<B>(req: &hyper::Request<B>) -> Self {
+        let header = |name: &str| {
+            req.headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        Self {
+            method: req.method().clone(),
+            uri: req.uri().clone(),
+            client: req.extensions().get::<SocketAddr>().copied(),
+            user_agent: header("user-agent"),
+            request_id: header("x-request-id"),
+        }
+    }
+
+    /// The log line for this request, answered with `status`.
+    pub fn describe(&self, status: u16, total: Duration, timer: &RequestTimer) -> String {
+        let mut line = format!(
+            "Slow request: {} {} -> {} in {:.1}ms ({})",
+            self.method,
+            self.uri,
+            status,
+            millis(total),
+            timer.breakdown()
+        );
+        if let Some(client) = self.client {
+            let _ = write!(line, " client={client}");
+        }
+        if let Some(id) = &self.request_id {
+            let _ = write!(line, " request_id={id}");
+        }
+        if let Some(agent) = &self.user_agent {
+            let _ = write!(line, " user_agent={agent:?}");
+        }
+        line
+    }
+}
+
+/// A response body that records how long it took to send.
+///
+/// The write phase ends when the connection is done with the body: it has
+/// been sent in full, or the client went away.
+pub struct TimedBody {
+    pub(super) body: ResponseBody,
+    started: Instant,
+    phases: Arc<PhaseMetrics>,
+    histogram: prometheus::Histogram,
+}
+
+impl TimedBody {
+    /// Time the write of `body` from now, into `phases` and `histogram`.
+    pub fn new(
+        body: ResponseBody,
+        phases: Arc<PhaseMetrics>,
+        histogram: prometheus::Histogram,
+    ) -> Self {
+        Self {
+            body,
+            started: Instant::now(),
+            phases,
+            histogram,
+        }
+    }
+}
+
+impl Drop for TimedBody {
+    fn drop(&mut self) {
+        let elapsed = self.started.elapsed();
+        self.phases.record_write(elapsed);
+        self.histogram.observe(elapsed.as_secs_f64());
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_phase_breakdown() {
+        let mut timer = RequestTimer::start();
+        timer.enter(Phase::Handler);
+        std::thread::sleep(Duration::from_millis(20));
+        timer.enter(Phase::Serialization);
+        let total = timer.finish();
+
+        assert!(timer.spent(Phase::Handler) >= Duration::from_millis(20));
+        assert!(total >= timer.spent(Phase::Handler));
+        let sum: Duration = Phase::ALL.iter().map(|&p| timer.spent(p)).sum();
+        assert_eq!(sum, total);
+        assert!(timer.breakdown().starts_with("routing "));
+
+        let metrics = PhaseMetrics::new();
+        metrics.record(&timer);
+        metrics.record(&RequestTimer::start());
+        let snapshot = metrics.snapshot();
+        assert!(snapshot.handler_ms >= 10.0);
+        assert_eq!(snapshot.slow_requests, 0);
+    }
+
+    #[test]
+    fn test_slow_request_line() {
+        let req = hyper::Request::builder()
+            .method("POST")
+            .uri("/orders?id=7")
+            .header("x-request-id", "abc")
+            .extension(SocketAddr::from(([10, 0, 0, 1], 4000)))
+            .body(())
+            .unwrap();
+        let mut timer = RequestTimer::start();
+        let total = timer.finish();
+
+        let line = SlowRequest::capture(&req).describe(201, total, &timer);
+        assert!(line.starts_with("Slow request: POST /orders?id=7 -> 201 in "));
+        assert!(line.contains("handler 0.0ms"));
+        assert!(line.ends_with("client=10.0.0.1:4000 request_id=abc"));
+    }
+}
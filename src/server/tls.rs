@@ -0,0 +1,225 @@
+//! TLS termination.
+//!
+//! Certificates live in a [`CertStore`] that the acceptor consults on every
+//! handshake, so a renewed certificate is served from the next connection
+//! on without restarting the listener. The store also answers ACME
+//! TLS-ALPN-01 challenges: handshakes that offer only the `acme-tls/1`
+//! protocol get the challenge certificate of the requested name.
+
+use std::collections::HashMap;
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use parking_lot::RwLock;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::CertifiedKey;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use super::protocols::{TlsConfig, TlsVersion};
+
+/// ALPN protocol of ACME TLS-ALPN-01 validation handshakes (RFC 8737).
+pub const ACME_TLS_ALPN: &[u8] = b"acme-tls/1";
+
+/// Time allowed for a client to complete the TLS handshake.
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Certificates served by the TLS acceptor, replaceable at runtime.
+#[derive(Default)]
+pub struct CertStore {
+    certificates: RwLock<HashMap<String, Arc<CertifiedKey>>>,
+    default: RwLock<Option<Arc<CertifiedKey>>>,
+    challenges: RwLock<HashMap<String, Arc<CertifiedKey>>>,
+}
+
+impl CertStore {
+    /// Create an empty store; handshakes fail until a certificate is set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serve `key` for `names`, and for names without a certificate of
+    /// their own. Replaces the certificate previously set for them.
+    pub fn set_certificate(&self, names: &[String], key: Arc<CertifiedKey>) {
+        let mut certificates = self.certificates.write();
+        for name in names {
+            certificates.insert(name.to_ascii_lowercase(), key.clone());
+        }
+        *self.default.write() = Some(key);
+    }
+
+    /// Whether a certificate has been set.
+    pub fn has_certificate(&self) -> bool {
+        self.default.read().is_some()
+    }
+
+    /// Answer TLS-ALPN-01 validation handshakes for `name` with `key`.
+    pub fn set_challenge(&self, name: &str, key: Arc<CertifiedKey>) {
+        self.challenges
+            .write()
+            .insert(name.to_ascii_lowercase(), key);
+    }
+
+    /// Stop answering TLS-ALPN-01 validation handshakes for `name`.
+    pub fn clear_challenge(&self, name: &str) {
+        self.challenges.write().remove(&name.to_ascii_lowercase());
+    }
+}
+
+impl std::fmt::Debug for CertStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CertStore")
+            .field(
+                "names",
+                &self.certificates.read().keys().collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl ResolvesServerCert for CertStore {
+    fn resolve(&self, client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        let name = client_hello.server_name().map(str::to_ascii_lowercase);
+        let validation = client_hello
+            .alpn()
+            .is_some_and(|mut protocols| protocols.any(|p| p == ACME_TLS_ALPN));
+        if validation {
+            return self.challenges.read().get(name.as_deref()?).cloned();
+        }
+        name.and_then(|name| self.certificates.read().get(&name).cloned())
+            .or_else(|| self.default.read().clone())
+    }
+}
+
+/// Build a certificate and signing key from a PEM certificate chain and a
+/// PEM private key.
+pub fn certified_key(cert_pem: &[u8], key_pem: &[u8]) -> Result<Arc<CertifiedKey>, String> {
+    let chain = rustls_pemfile::certs(&mut &cert_pem[..])
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Invalid certificate: {e}"))?;
+    if chain.is_empty() {
+        return Err("No certificate found".to_string());
+    }
+    let key = rustls_pemfile::private_key(&mut &key_pem[..])
+        .map_err(|e| format!("Invalid private key: {e}"))?
+        .ok_or_else(|| "No private key found".to_string())?;
+    certified_key_der(chain, key)
+}
+
+/// Build a certificate and signing key from DER.
+pub fn certified_key_der(
+    chain: Vec<CertificateDer<'static>>,
+    key: PrivateKeyDer<'_>,
+) -> Result<Arc<CertifiedKey>, String> {
+    let key = rustls::crypto::ring::sign::any_supported_type(&key)
+        .map_err(|e| format!("Unsupported private key: {e}"))?;
+    Ok(Arc::new(CertifiedKey::new(chain, key)))
+}
+
+/// Load the certificate and key files of a TLS config into a new store.
+pub fn load(config: &TlsConfig) -> Result<Arc<CertStore>, String> {
+    let read = |path: &std::path::Path| {
+        std::fs::read(path).map_err(|e| format!("Failed to read {}: {e}", path.display()))
+    };
+    let key = certified_key(&read(&config.cert_path)?, &read(&config.key_path)?)?;
+    let store = CertStore::new();
+    store.set_certificate(&[], key);
+    Ok(Arc::new(store))
+}
+
+/// Build a TLS acceptor serving the certificates in `store`.
+///
+/// HTTP/1.1 is negotiated, preceded by `h2` when `http2` is enabled
+/// (besides `acme-tls/1` for validation handshakes). Versions are limited
+/// to the range in `config`, TLS 1.2 to 1.3 by default.
+pub fn acceptor(
+    store: Arc<CertStore>,
+    config: Option<&TlsConfig>,
+    http2: bool,
+) -> Result<tokio_rustls::TlsAcceptor, String> {
+    let (min, max) = config.map_or((TlsVersion::Tls12, TlsVersion::Tls13), |c| {
+        (c.min_version, c.max_version)
+    });
+    let versions: Vec<_> = [
+        (TlsVersion::Tls12, &rustls::version::TLS12),
+        (TlsVersion::Tls13, &rustls::version::TLS13),
+    ]
+    .into_iter()
+    .filter(|(version, _)| (min..=max).contains(version))
+    .map(|(_, supported)| supported)
+    .collect();
+    if versions.is_empty() {
+        return Err(format!(
+            "No supported TLS version between {} and {}",
+            min.as_str(),
+            max.as_str()
+        ));
+    }
+
+    let mut server_config = rustls::ServerConfig::builder_with_protocol_versions(&versions)
+        .with_no_client_auth()
+        .with_cert_resolver(store);
+    server_config.alpn_protocols = http2
+        .then(|| b"h2".to_vec())
+        .into_iter()
+        .chain([b"http/1.1".to_vec(), ACME_TLS_ALPN.to_vec()])
+        .collect();
+    Ok(tokio_rustls::TlsAcceptor::from(Arc::new(server_config)))
+}
+
+/// Whether a completed handshake was an ACME validation handshake, after
+/// which the connection is closed without serving requests.
+pub fn is_validation<S>(stream: &tokio_rustls::server::TlsStream<S>) -> bool {
+    stream.get_ref().1.alpn_protocol() == Some(ACME_TLS_ALPN)
+}
+
+/// A connection, with or without TLS.
+pub enum MaybeTls<S> {
+    /// Plain TCP
+    Plain(S),
+    /// TLS over TCP
+    Tls(Box<tokio_rustls::server::TlsStream<S>>),
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncRead for MaybeTls<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            MaybeTls::Plain(stream) => Pin::new(stream).poll_read(cx, buf),
+            MaybeTls::Tls(stream) => Pin::new(stream.as_mut()).poll_read(cx, buf),
+        }
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncWrite for MaybeTls<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            MaybeTls::Plain(stream) => Pin::new(stream).poll_write(cx, buf),
+            MaybeTls::Tls(stream) => Pin::new(stream.as_mut()).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            MaybeTls::Plain(stream) => Pin::new(stream).poll_flush(cx),
+            MaybeTls::Tls(stream) => Pin::new(stream.as_mut()).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            MaybeTls::Plain(stream) => Pin::new(stream).poll_shutdown(cx),
+            MaybeTls::Tls(stream) => Pin::new(stream.as_mut()).poll_shutdown(cx),
+        }
+    }
+}
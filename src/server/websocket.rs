@@ -0,0 +1,488 @@
+//! WebSocket upgrades on the accept loop.
+//!
+//! A `GET` asking to upgrade to `websocket` on a registered WebSocket route
+//! is admitted like a request that no route handler sees: the app's
+//! before-middleware and guards run on it first (see [`super::run_before`]),
+//! and a rejection is answered as an ordinary HTTP response. Admitted
+//! upgrades are answered with `101 Switching Protocols`, and the upgraded
+//! connection is bridged to a [`WebSocketPeer`] opened on the registry, so
+//! the handler gets the same socket, principal and expiry handling as under
+//! the test client.
+//!
+//! permessage-deflate is negotiated in the handshake from the client's
+//! `Sec-WebSocket-Extensions` offer. Compressed messages set the RSV1 bit,
+//! which tungstenite's message layer refuses, so frames are read and written
+//! here with its frame header codec; the handler's socket compresses and
+//! inflates them.
+//!
+//! The handler runs on its own thread, as its `recv()` blocks; async
+//! handlers run to completion on a fresh event loop there.
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::io::Cursor;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use hyper::header::{
+    HeaderValue, CONNECTION, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_EXTENSIONS, SEC_WEBSOCKET_KEY,
+    UPGRADE,
+};
+use hyper::{Request as HyperRequest, Response as HyperResponse, StatusCode};
+use hyper_util::rt::TokioIo;
+use pyo3::prelude::*;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::{Control, Data, OpCode};
+use tokio_tungstenite::tungstenite::protocol::frame::FrameHeader;
+
+use super::{build_hyper_response, parse_query, RequestContext, ResponseBody};
+use crate::middleware::guards::GuardsMiddleware;
+use crate::middleware::{CredentialLocation, MiddlewareAction, MiddlewareChain};
+use crate::request::{Headers, Request};
+use crate::response::Response;
+use crate::websocket::{
+    Authenticator, CloseReason, MessageQueue, WebSocket, WebSocketMessage, WebSocketPeer,
+};
+
+/// Messages buffered between the handler's queue and the socket.
+const SEND_BUFFER: usize = 16;
+
+/// Largest message read from a client when no size limit is configured.
+const DEFAULT_MAX_MESSAGE_SIZE: usize = 64 << 20;
+
+/// The handler for `req` if it asks to upgrade to a WebSocket on one of
+/// the app's WebSocket routes over a connection that can be upgraded.
+pub(super) fn upgrade_handler<B>(
+    req: &HyperRequest<B>,
+    ctx: &RequestContext<'_>,
+) -> Option<PyObject> {
+    let upgrade = req.headers().get(UPGRADE)?.to_str().ok()?;
+    if !upgrade.eq_ignore_ascii_case("websocket")
+        || req
+            .extensions()
+            .get::<hyper::upgrade::OnUpgrade>()
+            .is_none()
+    {
+        return None;
+    }
+    ctx.websockets.get(req.uri().path())
+}
+
+/// Admit the upgrade request `req` and switch its connection over to
+/// `handler`, or answer why it was refused.
+pub(super) async fn upgrade<B>(
+    mut req: HyperRequest<B>,
+    handler: PyObject,
+    ctx: &RequestContext<'_>,
+) -> Result<HyperResponse<ResponseBody>, Infallible> {
+    let key = req.headers().get(SEC_WEBSOCKET_KEY).cloned();
+    let version = req.headers().get("sec-websocket-version").cloned();
+    let key = match key {
+        Some(key) if req.method() == hyper::Method::GET => key,
+        _ => {
+            let response = Response::error(400, "Invalid WebSocket upgrade request");
+            return build_hyper_response(&response, ctx);
+        }
+    };
+    if version.as_ref().map(HeaderValue::as_bytes) != Some(b"13") {
+        let mut response = Response::error(426, "Unsupported WebSocket version");
+        response.set_header("Sec-WebSocket-Version", "13");
+        return build_hyper_response(&response, ctx);
+    }
+    let offer = req
+        .headers()
+        .get_all(SEC_WEBSOCKET_EXTENSIONS)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let on_upgrade = hyper::upgrade::on(&mut req);
+    let (parts, _) = req.into_parts();
+    let mut request = Request::from_http(
+        parts.method.as_str().to_owned(),
+        parts.uri.path().to_owned(),
+        HashMap::new(),
+        parse_query(parts.uri.query().unwrap_or("")),
+        Headers::from(parts.headers),
+        Vec::new(),
+    );
+    request.remote_addr = parts.extensions.get::<std::net::SocketAddr>().copied();
+    if let Some(raw) = parts.uri.query() {
+        request.query_string = raw.to_owned();
+    }
+
+    let base = request.clone();
+    let request = match admit(request, ctx.middleware, ctx.guards).await {
+        Ok(request) => request,
+        Err(response) => return build_hyper_response(&response, ctx),
+    };
+    let authenticator = authenticator(
+        base,
+        ctx.middleware.clone(),
+        ctx.guards.clone(),
+        tokio::runtime::Handle::current(),
+    );
+    let offer = (!offer.is_empty()).then_some(offer.as_str());
+    let (socket, peer) = ctx.websockets.accept(request, offer, Some(authenticator));
+    let extensions = socket.extensions.clone();
+    let peer = peer.into_remote();
+
+    tokio::spawn(async move {
+        match on_upgrade.await {
+            Ok(upgraded) => {
+                let socket = Python::with_gil(|py| Py::new(py, socket));
+                match socket {
+                    Ok(socket) => {
+                        spawn_handler(handler, socket, peer.outgoing.clone());
+                        bridge(TokioIo::new(upgraded), peer).await;
+                    }
+                    Err(err) => {
+                        eprintln!("WebSocket handler failed: {err}");
+                        peer.outgoing.close();
+                        peer.incoming.close();
+                    }
+                }
+            }
+            Err(err) => {
+                eprintln!("WebSocket upgrade failed: {err}");
+                peer.outgoing.close();
+                peer.incoming.close();
+            }
+        }
+    });
+
+    let mut response = HyperResponse::new(ResponseBody::empty());
+    *response.status_mut() = StatusCode::SWITCHING_PROTOCOLS;
+    let headers = response.headers_mut();
+    headers.insert(CONNECTION, HeaderValue::from_static("Upgrade"));
+    headers.insert(UPGRADE, HeaderValue::from_static("websocket"));
+    if let Ok(accept) = HeaderValue::from_str(&derive_accept_key(key.as_bytes())) {
+        headers.insert(SEC_WEBSOCKET_ACCEPT, accept);
+    }
+    if let Some(value) = extensions.and_then(|e| HeaderValue::from_str(&e).ok()) {
+        headers.insert(SEC_WEBSOCKET_EXTENSIONS, value);
+    }
+    Ok(response)
+}
+
+/// Run the app's before-middleware and guards on a WebSocket upgrade
+/// request before the handler is connected.
+///
+/// Returns the admitted request, carrying whatever auth middleware stored
+/// in its context, or the response rejecting the upgrade.
+pub(crate) async fn admit(
+    mut request: Request,
+    middleware: &MiddlewareChain,
+    guards: &GuardsMiddleware,
+) -> Result<Request, Box<Response>> {
+    match super::run_before(&mut request, middleware, guards).await {
+        Ok(MiddlewareAction::Continue) => Ok(request),
+        Ok(MiddlewareAction::Stop(response)) => Err(Box::new(response)),
+        Err(e) => Err(Box::new(Response::error(e.status, &e.message))),
+    }
+}
+
+/// Re-admits the upgrade request `base` with a refreshed token, placed
+/// where the route's auth middleware reads it, on `runtime`.
+pub(crate) fn authenticator(
+    base: Request,
+    middleware: Arc<MiddlewareChain>,
+    guards: Arc<GuardsMiddleware>,
+    runtime: tokio::runtime::Handle,
+) -> Authenticator {
+    let location = middleware
+        .credential_location(&base.path)
+        .unwrap_or_else(|| CredentialLocation::Header {
+            name: "Authorization".to_string(),
+            prefix: "Bearer ".to_string(),
+        });
+    Arc::new(move |token: &str| {
+        let mut request = base.clone();
+        location.apply(&mut request, token);
+        runtime.block_on(admit(request, &middleware, &guards))
+    })
+}
+
+/// Run `handler` with `socket` on a thread of its own; its return ends the
+/// connection by closing `outgoing`.
+pub(crate) fn spawn_handler(
+    handler: PyObject,
+    socket: Py<WebSocket>,
+    outgoing: Arc<MessageQueue>,
+) -> JoinHandle<Result<(), String>> {
+    std::thread::spawn(move || {
+        let result = Python::with_gil(|py| -> PyResult<()> {
+            let ret = handler.call1(py, (socket,))?;
+            let inspect = py.import("inspect")?;
+            if inspect
+                .call_method1("iscoroutine", (ret.as_ref(py),))?
+                .is_true()?
+            {
+                py.import("asyncio")?.call_method1("run", (ret,))?;
+            }
+            Ok(())
+        })
+        .map_err(|e| e.to_string());
+        outgoing.close();
+        result
+    })
+}
+
+/// Carry messages between the client on `io` and `peer` until either side
+/// closes the connection.
+///
+/// The peer enforces the registry's heartbeats and limits as it does for
+/// the test client: its pings go out as ping frames, the client's pongs
+/// count as activity, and a connection it closes, or one whose client sends
+/// a message over the size limit, gets a close frame saying why.
+async fn bridge<S>(io: S, peer: WebSocketPeer)
+where
+    S: AsyncRead + AsyncWrite,
+{
+    let (reader, mut writer) = tokio::io::split(io);
+    let mut reader = FrameReader {
+        reader,
+        buf: Vec::new(),
+        partial: None,
+        compressed: peer.deflate.is_some(),
+        max_size: peer.max_message_size().unwrap_or(DEFAULT_MAX_MESSAGE_SIZE),
+    };
+
+    // The handler's queue blocks, so it is drained on a thread of its own.
+    let (tx, mut rx) = mpsc::channel(SEND_BUFFER);
+    let outgoing = peer.outgoing.clone();
+    std::thread::spawn(move || {
+        while let Some(message) = outgoing.pop(None) {
+            if tx.blocking_send(message).is_err() {
+                return;
+            }
+        }
+    });
+
+    let close = loop {
+        tokio::select! {
+            message = rx.recv() => match message {
+                Some(message) => {
+                    let (opcode, compressed, payload) = outgoing_frame(message);
+                    if write_frame(&mut writer, opcode, compressed, &payload).await.is_err() {
+                        break None;
+                    }
+                }
+                // The handler returned or the connection was closed.
+                None => break Some(close_payload(&peer, None)),
+            },
+            incoming = reader.next() => match incoming {
+                Ok(Incoming::Message(message)) => peer.send(message),
+                Ok(Incoming::Ping(payload)) => {
+                    peer.pinged();
+                    let pong = OpCode::Control(Control::Pong);
+                    if write_frame(&mut writer, pong, false, &payload).await.is_err() {
+                        break None;
+                    }
+                }
+                // The client's close is echoed with its status code.
+                Ok(Incoming::Close(payload)) => break Some(payload.get(..2).unwrap_or_default().to_vec()),
+                Err(ReadError::Closed) => break None,
+                Err(ReadError::TooBig) => {
+                    peer.close_with(CloseReason::MessageTooBig);
+                    break Some(close_payload(&peer, None));
+                }
+                Err(ReadError::InvalidText) => {
+                    peer.close_with(CloseReason::InvalidMessage);
+                    break Some(close_payload(&peer, None));
+                }
+                Err(ReadError::Protocol) => break Some(close_payload(&peer, Some(1002))),
+            },
+        }
+    };
+    peer.incoming.close();
+    peer.outgoing.close();
+    if let Some(payload) = close {
+        let close = OpCode::Control(Control::Close);
+        let _ = write_frame(&mut writer, close, false, &payload).await;
+    }
+    let _ = writer.shutdown().await;
+}
+
+/// What the client sent.
+enum Incoming {
+    /// A data message, still compressed if it was sent so, or a pong.
+    Message(WebSocketMessage),
+    /// A ping, to be answered with its payload.
+    Ping(Vec<u8>),
+    /// A close frame's payload.
+    Close(Vec<u8>),
+}
+
+/// Why no message could be read from the client.
+enum ReadError {
+    /// The message is over the size limit.
+    TooBig,
+    /// A text message is not UTF-8.
+    InvalidText,
+    /// The client broke the framing rules, e.g. with an unmasked frame.
+    Protocol,
+    /// The connection dropped.
+    Closed,
+}
+
+/// Reads the client's frames and reassembles them into messages.
+struct FrameReader<R> {
+    reader: R,
+    /// Bytes read but not yet parsed.
+    buf: Vec<u8>,
+    /// The fragmented message being reassembled: its type, whether it is
+    /// compressed, and its payload so far.
+    partial: Option<(Data, bool, Vec<u8>)>,
+    /// Whether permessage-deflate was agreed, allowing RSV1.
+    compressed: bool,
+    max_size: usize,
+}
+
+impl<R: AsyncRead + Unpin> FrameReader<R> {
+    /// The next message or control frame.
+    ///
+    /// Safe to cancel: bytes already read stay buffered for the next call.
+    async fn next(&mut self) -> Result<Incoming, ReadError> {
+        loop {
+            if let Some(incoming) = self.parse()? {
+                return Ok(incoming);
+            }
+            self.buf.reserve(4096);
+            match self.reader.read_buf(&mut self.buf).await {
+                Ok(0) | Err(_) => return Err(ReadError::Closed),
+                Ok(_) => {}
+            }
+        }
+    }
+
+    /// Take complete frames off the buffer until one finishes a message or
+    /// is a control frame.
+    fn parse(&mut self) -> Result<Option<Incoming>, ReadError> {
+        loop {
+            let mut cursor = Cursor::new(&self.buf[..]);
+            let Some((header, len)) =
+                FrameHeader::parse(&mut cursor).map_err(|_| ReadError::Protocol)?
+            else {
+                return Ok(None);
+            };
+            let start = cursor.position() as usize;
+            // Oversized messages are refused before their payload is read.
+            let so_far = self
+                .partial
+                .as_ref()
+                .map_or(0, |(_, _, payload)| payload.len());
+            let len = usize::try_from(len)
+                .ok()
+                .filter(|len| so_far + len <= self.max_size)
+                .ok_or(ReadError::TooBig)?;
+            if self.buf.len() < start + len {
+                return Ok(None);
+            }
+            let mut payload = self.buf[start..start + len].to_vec();
+            self.buf.drain(..start + len);
+
+            // Clients must mask their frames.
+            let mask = header.mask.ok_or(ReadError::Protocol)?;
+            for (i, byte) in payload.iter_mut().enumerate() {
+                *byte ^= mask[i % 4];
+            }
+            if header.rsv2 || header.rsv3 {
+                return Err(ReadError::Protocol);
+            }
+
+            match header.opcode {
+                OpCode::Control(control) => {
+                    if !header.is_final || header.rsv1 || len > 125 {
+                        return Err(ReadError::Protocol);
+                    }
+                    return Ok(Some(match control {
+                        Control::Ping => Incoming::Ping(payload),
+                        Control::Pong => Incoming::Message(WebSocketMessage::pong()),
+                        Control::Close => Incoming::Close(payload),
+                        Control::Reserved(_) => return Err(ReadError::Protocol),
+                    }));
+                }
+                OpCode::Data(Data::Continue) => match self.partial.as_mut() {
+                    Some((_, _, message)) if !header.rsv1 => message.extend_from_slice(&payload),
+                    _ => return Err(ReadError::Protocol),
+                },
+                OpCode::Data(kind) => {
+                    if self.partial.is_some() || (header.rsv1 && !self.compressed) {
+                        return Err(ReadError::Protocol);
+                    }
+                    self.partial = Some((kind, header.rsv1, payload));
+                }
+            }
+
+            if header.is_final {
+                if let Some((kind, compressed, payload)) = self.partial.take() {
+                    let text = kind == Data::Text;
+                    let message = if compressed {
+                        WebSocketMessage::from_compressed(text, payload)
+                    } else if text {
+                        let text =
+                            String::from_utf8(payload).map_err(|_| ReadError::InvalidText)?;
+                        WebSocketMessage::from_text(&text)
+                    } else {
+                        WebSocketMessage::from_binary(payload)
+                    };
+                    return Ok(Some(Incoming::Message(message)));
+                }
+            }
+        }
+    }
+}
+
+/// Write one unfragmented, unmasked frame to the client.
+async fn write_frame<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    opcode: OpCode,
+    compressed: bool,
+    payload: &[u8],
+) -> std::io::Result<()> {
+    let header = FrameHeader {
+        rsv1: compressed,
+        opcode,
+        ..FrameHeader::default()
+    };
+    let len = payload.len() as u64;
+    let mut frame = Vec::with_capacity(header.len(len) + payload.len());
+    header
+        .format(len, &mut frame)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    frame.extend_from_slice(payload);
+    writer.write_all(&frame).await?;
+    writer.flush().await
+}
+
+/// The opcode, RSV1 bit and payload of the frame carrying a message from
+/// the handler.
+fn outgoing_frame(message: WebSocketMessage) -> (OpCode, bool, Vec<u8>) {
+    let opcode = match message.msg_type.as_str() {
+        "text" => OpCode::Data(Data::Text),
+        "ping" => OpCode::Control(Control::Ping),
+        "pong" => OpCode::Control(Control::Pong),
+        _ => OpCode::Data(Data::Binary),
+    };
+    let payload = match message.text {
+        Some(text) => text.into_bytes(),
+        None => message.data.unwrap_or_default(),
+    };
+    (opcode, message.compressed, payload)
+}
+
+/// The payload of the close frame ending `peer`'s connection: `code`, or
+/// else the status and reason the server closed it with, if it did.
+fn close_payload(peer: &WebSocketPeer, code: Option<u16>) -> Vec<u8> {
+    if let Some(code) = code {
+        return code.to_be_bytes().to_vec();
+    }
+    match peer.liveness.close_reason() {
+        Some(reason) => [&reason.code().to_be_bytes()[..], reason.as_str().as_bytes()].concat(),
+        None => Vec::new(),
+    }
+}
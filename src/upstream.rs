@@ -0,0 +1,435 @@
+//! Load-balanced Upstream Groups for Cello Framework
+//!
+//! Named groups of upstream servers that outbound requests are spread across:
+//! - Round-robin, least-connections and smooth weighted round-robin balancing
+//! - Passive health checking: an upstream that fails repeatedly is ejected
+//!   for a cool-down period, then tried again
+//! - Retries on connection failure against the next healthy upstream
+//!
+//! If every upstream is ejected the group fails open and keeps balancing
+//! across all of them rather than refusing requests.
+//!
+//! # Example
+//! ```python
+//! client = AsyncClient()
+//! client.add_upstream_group(
+//!     "users",
+//!     ["http://10.0.0.1:8000", ("http://10.0.0.2:8000", 3)],
+//!     strategy="weighted",
+//! )
+//! resp = await client.get("upstream://users/api/users/1")
+//! ```
+
+use parking_lot::Mutex;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// URL scheme addressing an upstream group: `upstream://<group>/<path>`.
+pub const UPSTREAM_SCHEME: &str = "upstream://";
+
+// ============================================================================
+// Configuration
+// ============================================================================
+
+/// How a group picks the upstream for a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LoadBalancing {
+    /// Each upstream in turn.
+    #[default]
+    RoundRobin,
+    /// The upstream with the fewest requests in flight.
+    LeastConnections,
+    /// Round-robin in proportion to each upstream's weight.
+    Weighted,
+}
+
+impl FromStr for LoadBalancing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace('-', "_").as_str() {
+            "round_robin" => Ok(LoadBalancing::RoundRobin),
+            "least_connections" | "least_conn" => Ok(LoadBalancing::LeastConnections),
+            "weighted" | "weighted_round_robin" => Ok(LoadBalancing::Weighted),
+            _ => Err(format!(
+                "Unknown load balancing strategy '{s}'; expected 'round_robin', \
+                 'least_connections' or 'weighted'"
+            )),
+        }
+    }
+}
+
+/// Passive health checking settings.
+#[derive(Debug, Clone)]
+pub struct HealthConfig {
+    /// Consecutive failures before an upstream is ejected.
+    pub max_failures: u32,
+    /// How long an ejected upstream is skipped.
+    pub ejection: Duration,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            max_failures: 3,
+            ejection: Duration::from_secs(30),
+        }
+    }
+}
+
+// ============================================================================
+// Upstream
+// ============================================================================
+
+/// One server in a group.
+#[derive(Debug)]
+pub struct Upstream {
+    url: String,
+    weight: u32,
+    active: AtomicUsize,
+    failures: AtomicU32,
+    requests: AtomicU64,
+    ejected_until: Mutex<Option<Instant>>,
+}
+
+impl Upstream {
+    fn new(url: &str, weight: u32) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_string(),
+            weight: weight.max(1),
+            active: AtomicUsize::new(0),
+            failures: AtomicU32::new(0),
+            requests: AtomicU64::new(0),
+            ejected_until: Mutex::new(None),
+        }
+    }
+
+    /// Base URL, without a trailing slash.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Relative share of traffic under weighted balancing.
+    pub fn weight(&self) -> u32 {
+        self.weight
+    }
+
+    /// Requests currently in flight.
+    pub fn active(&self) -> usize {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    /// Requests sent to this upstream.
+    pub fn requests(&self) -> u64 {
+        self.requests.load(Ordering::Relaxed)
+    }
+
+    /// Consecutive failures since the last success.
+    pub fn failures(&self) -> u32 {
+        self.failures.load(Ordering::Relaxed)
+    }
+
+    /// Whether the upstream is currently ejected.
+    pub fn is_ejected(&self) -> bool {
+        self.ejected_until
+            .lock()
+            .is_some_and(|until| Instant::now() < until)
+    }
+
+    /// Join `path` onto the base URL.
+    pub fn url_for(&self, path: &str) -> String {
+        if path.is_empty() || path.starts_with('/') || path.starts_with('?') {
+            format!("{}{path}", self.url)
+        } else {
+            format!("{}/{path}", self.url)
+        }
+    }
+}
+
+/// Marks a request in flight on an upstream until dropped.
+pub struct UpstreamGuard {
+    upstream: Arc<Upstream>,
+}
+
+impl UpstreamGuard {
+    /// The upstream the request was sent to.
+    pub fn upstream(&self) -> &Upstream {
+        &self.upstream
+    }
+}
+
+impl Drop for UpstreamGuard {
+    fn drop(&mut self) {
+        self.upstream.active.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+// ============================================================================
+// Group
+// ============================================================================
+
+/// A named, load-balanced set of upstreams.
+#[derive(Debug)]
+pub struct UpstreamGroup {
+    name: String,
+    strategy: LoadBalancing,
+    upstreams: Vec<Arc<Upstream>>,
+    health: HealthConfig,
+    retries: usize,
+    cursor: AtomicUsize,
+    /// Current weights for smooth weighted round-robin.
+    current_weights: Mutex<Vec<i64>>,
+}
+
+impl UpstreamGroup {
+    /// Create a group from `(url, weight)` pairs.
+    pub fn new(name: &str, upstreams: Vec<(String, u32)>, strategy: LoadBalancing) -> Self {
+        let upstreams: Vec<Arc<Upstream>> = upstreams
+            .iter()
+            .map(|(url, weight)| Arc::new(Upstream::new(url, *weight)))
+            .collect();
+        Self {
+            name: name.to_string(),
+            strategy,
+            current_weights: Mutex::new(vec![0; upstreams.len()]),
+            upstreams,
+            health: HealthConfig::default(),
+            retries: 1,
+            cursor: AtomicUsize::new(0),
+        }
+    }
+
+    /// Set the passive health checking settings.
+    pub fn with_health(mut self, health: HealthConfig) -> Self {
+        self.health = health;
+        self
+    }
+
+    /// Set how many other upstreams a request is retried against after a
+    /// connection failure.
+    pub fn with_retries(mut self, retries: usize) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Group name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Balancing strategy.
+    pub fn strategy(&self) -> LoadBalancing {
+        self.strategy
+    }
+
+    /// Retries after a connection failure.
+    pub fn retries(&self) -> usize {
+        self.retries
+    }
+
+    /// The group's upstreams.
+    pub fn upstreams(&self) -> &[Arc<Upstream>] {
+        &self.upstreams
+    }
+
+    /// Pick an upstream, skipping the indices in `tried`.
+    ///
+    /// Ejected upstreams are skipped unless every remaining one is ejected.
+    pub fn select(&self, tried: &[usize]) -> Option<usize> {
+        let untried: Vec<usize> = (0..self.upstreams.len())
+            .filter(|i| !tried.contains(i))
+            .collect();
+        let healthy: Vec<usize> = untried
+            .iter()
+            .copied()
+            .filter(|&i| !self.upstreams[i].is_ejected())
+            .collect();
+        let candidates = if healthy.is_empty() { untried } else { healthy };
+        if candidates.is_empty() {
+            return None;
+        }
+
+        let chosen = match self.strategy {
+            LoadBalancing::RoundRobin => {
+                candidates[self.cursor.fetch_add(1, Ordering::Relaxed) % candidates.len()]
+            }
+            LoadBalancing::LeastConnections => {
+                // Start from a rotating offset so ties are spread out.
+                let start = self.cursor.fetch_add(1, Ordering::Relaxed);
+                (0..candidates.len())
+                    .map(|k| candidates[(start + k) % candidates.len()])
+                    .min_by_key(|&i| self.upstreams[i].active())
+                    .unwrap_or(candidates[0])
+            }
+            LoadBalancing::Weighted => self.select_weighted(&candidates),
+        };
+        Some(chosen)
+    }
+
+    /// Smooth weighted round-robin: every candidate gains its weight, the
+    /// highest is chosen and pays back the total.
+    fn select_weighted(&self, candidates: &[usize]) -> usize {
+        let mut current = self.current_weights.lock();
+        let total: i64 = candidates
+            .iter()
+            .map(|&i| self.upstreams[i].weight as i64)
+            .sum();
+        let mut best = candidates[0];
+        for &i in candidates {
+            current[i] += self.upstreams[i].weight as i64;
+            if current[i] > current[best] {
+                best = i;
+            }
+        }
+        current[best] -= total;
+        best
+    }
+
+    /// Mark a request in flight on upstream `index`.
+    pub fn acquire(&self, index: usize) -> UpstreamGuard {
+        let upstream = self.upstreams[index].clone();
+        upstream.active.fetch_add(1, Ordering::Relaxed);
+        upstream.requests.fetch_add(1, Ordering::Relaxed);
+        UpstreamGuard { upstream }
+    }
+
+    /// Record a successful response from upstream `index`.
+    pub fn report_success(&self, index: usize) {
+        let upstream = &self.upstreams[index];
+        upstream.failures.store(0, Ordering::Relaxed);
+        *upstream.ejected_until.lock() = None;
+    }
+
+    /// Record a failure on upstream `index`, ejecting it once it reaches
+    /// the failure limit. Returns whether it was ejected.
+    pub fn report_failure(&self, index: usize) -> bool {
+        let upstream = &self.upstreams[index];
+        let failures = upstream.failures.fetch_add(1, Ordering::Relaxed) + 1;
+        if failures >= self.health.max_failures {
+            *upstream.ejected_until.lock() = Some(Instant::now() + self.health.ejection);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Split `upstream://<group>/<path>` into the group name and path.
+pub fn parse_upstream_url(url: &str) -> Option<(&str, &str)> {
+    let rest = url.strip_prefix(UPSTREAM_SCHEME)?;
+    match rest.find(['/', '?']) {
+        Some(pos) => Some((&rest[..pos], &rest[pos..])),
+        None => Some((rest, "")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group(strategy: LoadBalancing, weights: &[u32]) -> UpstreamGroup {
+        let upstreams = weights
+            .iter()
+            .enumerate()
+            .map(|(i, w)| (format!("http://u{i}/"), *w))
+            .collect();
+        UpstreamGroup::new("svc", upstreams, strategy)
+    }
+
+    // ---------- Selection Tests ----------
+
+    #[test]
+    fn test_round_robin() {
+        let group = group(LoadBalancing::RoundRobin, &[1, 1, 1]);
+        let picks: Vec<usize> = (0..6).map(|_| group.select(&[]).unwrap()).collect();
+        assert_eq!(picks, vec![0, 1, 2, 0, 1, 2]);
+        assert_eq!(group.select(&[0, 1, 2]), None);
+    }
+
+    #[test]
+    fn test_least_connections() {
+        let group = group(LoadBalancing::LeastConnections, &[1, 1, 1]);
+        let _a = group.acquire(0);
+        let _b = group.acquire(0);
+        let _c = group.acquire(2);
+        assert_eq!(group.select(&[]), Some(1));
+        drop(_a);
+        drop(_b);
+        assert_eq!(group.upstreams()[0].active(), 0);
+        assert_eq!(group.upstreams()[0].requests(), 2);
+    }
+
+    #[test]
+    fn test_weighted_is_proportional_and_smooth() {
+        let group = group(LoadBalancing::Weighted, &[5, 1, 1]);
+        let picks: Vec<usize> = (0..7).map(|_| group.select(&[]).unwrap()).collect();
+        assert_eq!(picks.iter().filter(|&&i| i == 0).count(), 5);
+        assert_eq!(picks.iter().filter(|&&i| i == 1).count(), 1);
+        // Smooth: the heavy upstream is not picked five times in a row.
+        assert_eq!(picks, vec![0, 0, 1, 0, 2, 0, 0]);
+    }
+
+    // ---------- Health Tests ----------
+
+    #[test]
+    fn test_ejection_and_recovery() {
+        let group = group(LoadBalancing::RoundRobin, &[1, 1]).with_health(HealthConfig {
+            max_failures: 2,
+            ejection: Duration::from_millis(30),
+        });
+
+        assert!(!group.report_failure(0));
+        assert!(group.report_failure(0));
+        assert!(group.upstreams()[0].is_ejected());
+        for _ in 0..4 {
+            assert_eq!(group.select(&[]), Some(1));
+        }
+
+        std::thread::sleep(Duration::from_millis(40));
+        assert!(!group.upstreams()[0].is_ejected());
+        group.report_success(0);
+        assert_eq!(group.upstreams()[0].failures(), 0);
+    }
+
+    #[test]
+    fn test_all_ejected_fails_open() {
+        let group = group(LoadBalancing::RoundRobin, &[1, 1]).with_health(HealthConfig {
+            max_failures: 1,
+            ejection: Duration::from_secs(60),
+        });
+        group.report_failure(0);
+        group.report_failure(1);
+        assert!(group.select(&[]).is_some());
+        // A retry still moves on to an upstream not yet tried.
+        assert_eq!(group.select(&[0]), Some(1));
+    }
+
+    // ---------- URL Tests ----------
+
+    #[test]
+    fn test_parse_upstream_url() {
+        assert_eq!(
+            parse_upstream_url("upstream://users/api/v1?x=1"),
+            Some(("users", "/api/v1?x=1"))
+        );
+        assert_eq!(parse_upstream_url("upstream://users"), Some(("users", "")));
+        assert_eq!(parse_upstream_url("https://users/api"), None);
+
+        let group = group(LoadBalancing::RoundRobin, &[1]);
+        assert_eq!(group.upstreams()[0].url_for("/api"), "http://u0/api");
+        assert_eq!(group.upstreams()[0].url_for("api"), "http://u0/api");
+    }
+
+    #[test]
+    fn test_strategy_from_str() {
+        assert_eq!("round-robin".parse(), Ok(LoadBalancing::RoundRobin));
+        assert_eq!(
+            "least_connections".parse(),
+            Ok(LoadBalancing::LeastConnections)
+        );
+        assert_eq!("weighted".parse(), Ok(LoadBalancing::Weighted));
+        assert!("random".parse::<LoadBalancing>().is_err());
+    }
+}
@@ -0,0 +1,1014 @@
+//! Native request validation from JSON Schema.
+//!
+//! Routes can attach JSON Schemas for the body, query string and path
+//! parameters. Schemas are compiled once at registration and checked in Rust
+//! before the handler runs; invalid requests get a 422 response with
+//! Pydantic-style error details:
+//!
+//! ```json
+//! {"detail": [{"loc": ["body", "name"], "msg": "Field required", "type": "missing"}]}
+//! ```
+//!
+//! Supported keywords: `type`, `properties`, `required`, `additionalProperties`,
+//! `items`, `enum`, `const`, `minimum`/`maximum` (and exclusive variants),
+//! `multipleOf`, `minLength`/`maxLength`, `pattern`, `format` (email, uuid,
+//! date, date-time), `minItems`/`maxItems`, `uniqueItems`, `anyOf`/`oneOf`/
+//! `allOf`/`not`, and local `$ref` into `$defs`/`definitions`.
+
+use regex::Regex;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::request::Request;
+use crate::response::Response;
+
+// ============================================================================
+// Errors
+// ============================================================================
+
+/// A single validation failure.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ValidationIssue {
+    /// Location of the failing value, e.g. `["body", "items", 0]`
+    pub loc: Vec<Value>,
+    /// Human-readable message
+    pub msg: String,
+    /// Machine-readable error type
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+impl ValidationIssue {
+    fn new(loc: &[Value], msg: impl Into<String>, kind: &str) -> Self {
+        Self {
+            loc: loc.to_vec(),
+            msg: msg.into(),
+            kind: kind.to_string(),
+        }
+    }
+}
+
+/// Build the 422 response for a set of validation issues.
+pub fn validation_error_response(issues: &[ValidationIssue]) -> Response {
+    Response::from_json_value(serde_json::json!({ "detail": issues }), 422)
+}
+
+// ============================================================================
+// Schema Compilation
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum JsonType {
+    Null,
+    Boolean,
+    Integer,
+    Number,
+    String,
+    Array,
+    Object,
+}
+
+impl JsonType {
+    fn parse(name: &str) -> Result<Self, String> {
+        Ok(match name {
+            "null" => JsonType::Null,
+            "boolean" => JsonType::Boolean,
+            "integer" => JsonType::Integer,
+            "number" => JsonType::Number,
+            "string" => JsonType::String,
+            "array" => JsonType::Array,
+            "object" => JsonType::Object,
+            other => return Err(format!("Unknown schema type: {other}")),
+        })
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            JsonType::Null => "null",
+            JsonType::Boolean => "boolean",
+            JsonType::Integer => "integer",
+            JsonType::Number => "number",
+            JsonType::String => "string",
+            JsonType::Array => "array",
+            JsonType::Object => "object",
+        }
+    }
+
+    fn matches(&self, value: &Value) -> bool {
+        match self {
+            JsonType::Null => value.is_null(),
+            JsonType::Boolean => value.is_boolean(),
+            JsonType::Integer => {
+                value.is_i64()
+                    || value.is_u64()
+                    || value.as_f64().map(|f| f.fract() == 0.0).unwrap_or(false)
+            }
+            JsonType::Number => value.is_number(),
+            JsonType::String => value.is_string(),
+            JsonType::Array => value.is_array(),
+            JsonType::Object => value.is_object(),
+        }
+    }
+}
+
+/// Policy for properties not listed in `properties`.
+#[derive(Debug, Clone)]
+enum Additional {
+    Allow,
+    Deny,
+    Schema(Box<SchemaNode>),
+}
+
+/// A compiled schema node.
+#[derive(Debug, Clone)]
+struct SchemaNode {
+    /// `false` schema: nothing validates
+    never: bool,
+    reference: Option<String>,
+    types: Vec<JsonType>,
+    properties: Vec<(String, SchemaNode)>,
+    required: Vec<String>,
+    additional: Additional,
+    items: Option<Box<SchemaNode>>,
+    enum_values: Option<Vec<Value>>,
+    const_value: Option<Value>,
+    minimum: Option<f64>,
+    maximum: Option<f64>,
+    exclusive_minimum: Option<f64>,
+    exclusive_maximum: Option<f64>,
+    multiple_of: Option<f64>,
+    min_length: Option<usize>,
+    max_length: Option<usize>,
+    pattern: Option<Regex>,
+    format: Option<String>,
+    min_items: Option<usize>,
+    max_items: Option<usize>,
+    unique_items: bool,
+    any_of: Vec<SchemaNode>,
+    one_of: Vec<SchemaNode>,
+    all_of: Vec<SchemaNode>,
+    not: Option<Box<SchemaNode>>,
+}
+
+impl Default for SchemaNode {
+    fn default() -> Self {
+        Self {
+            never: false,
+            reference: None,
+            types: Vec::new(),
+            properties: Vec::new(),
+            required: Vec::new(),
+            additional: Additional::Allow,
+            items: None,
+            enum_values: None,
+            const_value: None,
+            minimum: None,
+            maximum: None,
+            exclusive_minimum: None,
+            exclusive_maximum: None,
+            multiple_of: None,
+            min_length: None,
+            max_length: None,
+            pattern: None,
+            format: None,
+            min_items: None,
+            max_items: None,
+            unique_items: false,
+            any_of: Vec::new(),
+            one_of: Vec::new(),
+            all_of: Vec::new(),
+            not: None,
+        }
+    }
+}
+
+impl SchemaNode {
+    fn compile(schema: &Value) -> Result<Self, String> {
+        let obj = match schema {
+            Value::Bool(true) => return Ok(Self::default()),
+            Value::Bool(false) => {
+                return Ok(Self {
+                    never: true,
+                    ..Self::default()
+                })
+            }
+            Value::Object(obj) => obj,
+            _ => return Err("Schema must be an object or boolean".to_string()),
+        };
+
+        let mut node = Self::default();
+
+        if let Some(reference) = obj.get("$ref").and_then(Value::as_str) {
+            node.reference = Some(reference.to_string());
+        }
+
+        match obj.get("type") {
+            Some(Value::String(name)) => node.types.push(JsonType::parse(name)?),
+            Some(Value::Array(names)) => {
+                for name in names {
+                    let name = name.as_str().ok_or("Schema type must be a string")?;
+                    node.types.push(JsonType::parse(name)?);
+                }
+            }
+            Some(_) => return Err("Schema type must be a string or array".to_string()),
+            None => {}
+        }
+
+        if let Some(props) = obj.get("properties").and_then(Value::as_object) {
+            for (name, prop) in props {
+                node.properties.push((name.clone(), Self::compile(prop)?));
+            }
+        }
+        if let Some(required) = obj.get("required").and_then(Value::as_array) {
+            node.required = required
+                .iter()
+                .filter_map(|v| v.as_str().map(str::to_string))
+                .collect();
+        }
+        node.additional = match obj.get("additionalProperties") {
+            None | Some(Value::Bool(true)) => Additional::Allow,
+            Some(Value::Bool(false)) => Additional::Deny,
+            Some(schema) => Additional::Schema(Box::new(Self::compile(schema)?)),
+        };
+
+        if let Some(items) = obj.get("items") {
+            node.items = Some(Box::new(Self::compile(items)?));
+        }
+        node.enum_values = obj.get("enum").and_then(Value::as_array).cloned();
+        node.const_value = obj.get("const").cloned();
+
+        node.minimum = obj.get("minimum").and_then(Value::as_f64);
+        node.maximum = obj.get("maximum").and_then(Value::as_f64);
+        node.exclusive_minimum = obj.get("exclusiveMinimum").and_then(Value::as_f64);
+        node.exclusive_maximum = obj.get("exclusiveMaximum").and_then(Value::as_f64);
+        node.multiple_of = obj.get("multipleOf").and_then(Value::as_f64);
+
+        node.min_length = obj.get("minLength").and_then(as_usize);
+        node.max_length = obj.get("maxLength").and_then(as_usize);
+        if let Some(pattern) = obj.get("pattern").and_then(Value::as_str) {
+            node.pattern =
+                Some(Regex::new(pattern).map_err(|e| format!("Invalid pattern {pattern:?}: {e}"))?);
+        }
+        node.format = obj
+            .get("format")
+            .and_then(Value::as_str)
+            .map(str::to_string);
+
+        node.min_items = obj.get("minItems").and_then(as_usize);
+        node.max_items = obj.get("maxItems").and_then(as_usize);
+        node.unique_items = obj
+            .get("uniqueItems")
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+
+        for (key, target) in [
+            ("anyOf", &mut node.any_of),
+            ("oneOf", &mut node.one_of),
+            ("allOf", &mut node.all_of),
+        ] {
+            if let Some(list) = obj.get(key).and_then(Value::as_array) {
+                for sub in list {
+                    target.push(Self::compile(sub)?);
+                }
+            }
+        }
+        if let Some(not) = obj.get("not") {
+            node.not = Some(Box::new(Self::compile(not)?));
+        }
+
+        Ok(node)
+    }
+
+    /// First non-null type this node (or its reference/anyOf) expects.
+    fn scalar_type<'a>(&'a self, schema: &'a JsonSchema) -> Option<JsonType> {
+        if let Some(target) = self.reference.as_deref().and_then(|r| schema.resolve(r)) {
+            return target.scalar_type(schema);
+        }
+        self.types
+            .iter()
+            .copied()
+            .find(|t| *t != JsonType::Null)
+            .or_else(|| self.any_of.iter().find_map(|s| s.scalar_type(schema)))
+    }
+}
+
+fn as_usize(value: &Value) -> Option<usize> {
+    value.as_u64().map(|v| v as usize)
+}
+
+/// A compiled JSON Schema document.
+#[derive(Debug, Clone)]
+pub struct JsonSchema {
+    root: SchemaNode,
+    defs: HashMap<String, SchemaNode>,
+}
+
+impl JsonSchema {
+    /// Compile a JSON Schema document.
+    pub fn compile(schema: &Value) -> Result<Self, String> {
+        let mut defs = HashMap::new();
+        for key in ["$defs", "definitions"] {
+            if let Some(map) = schema.get(key).and_then(Value::as_object) {
+                for (name, def) in map {
+                    defs.insert(format!("#/{key}/{name}"), SchemaNode::compile(def)?);
+                }
+            }
+        }
+
+        let schema = Self {
+            root: SchemaNode::compile(schema)?,
+            defs,
+        };
+        schema.check_refs(&schema.root)?;
+        for def in schema.defs.values() {
+            schema.check_refs(def)?;
+        }
+        schema.check_cycles(&schema.root, &mut Vec::new())?;
+        for def in schema.defs.values() {
+            schema.check_cycles(def, &mut Vec::new())?;
+        }
+        Ok(schema)
+    }
+
+    fn resolve(&self, reference: &str) -> Option<&SchemaNode> {
+        if reference == "#" {
+            return Some(&self.root);
+        }
+        self.defs.get(reference)
+    }
+
+    /// Ensure every `$ref` points at a known definition.
+    fn check_refs(&self, node: &SchemaNode) -> Result<(), String> {
+        if let Some(reference) = &node.reference {
+            if self.resolve(reference).is_none() {
+                return Err(format!("Unresolvable schema reference: {reference}"));
+            }
+        }
+        let children = node
+            .properties
+            .iter()
+            .map(|(_, s)| s)
+            .chain(node.items.as_deref())
+            .chain(node.not.as_deref())
+            .chain(match &node.additional {
+                Additional::Schema(s) => Some(s.as_ref()),
+                _ => None,
+            })
+            .chain(node.any_of.iter())
+            .chain(node.one_of.iter())
+            .chain(node.all_of.iter());
+        for child in children {
+            self.check_refs(child)?;
+        }
+        Ok(())
+    }
+
+    /// Reject `$ref` cycles that reach the same schema again without
+    /// descending into a property or item, which would never terminate.
+    fn check_cycles<'a>(
+        &'a self,
+        node: &'a SchemaNode,
+        visiting: &mut Vec<&'a str>,
+    ) -> Result<(), String> {
+        if let Some(reference) = node.reference.as_deref() {
+            if visiting.contains(&reference) {
+                return Err(format!("Schema reference cycle: {reference}"));
+            }
+            if let Some(target) = self.resolve(reference) {
+                visiting.push(reference);
+                self.check_cycles(target, visiting)?;
+                visiting.pop();
+            }
+        }
+        let children = node
+            .any_of
+            .iter()
+            .chain(node.one_of.iter())
+            .chain(node.all_of.iter())
+            .chain(node.not.as_deref());
+        for child in children {
+            self.check_cycles(child, visiting)?;
+        }
+        Ok(())
+    }
+
+    /// Validate a value, returning every issue found.
+    pub fn validate(&self, value: &Value, loc: &[Value]) -> Vec<ValidationIssue> {
+        let mut issues = Vec::new();
+        let mut loc = loc.to_vec();
+        self.validate_node(&self.root, value, &mut loc, &mut issues);
+        issues
+    }
+
+    /// Convert string parameters (query or path) to typed values guided by the
+    /// schema's property types, so `"42"` validates against `integer`.
+    fn coerce_params(&self, params: &HashMap<String, String>) -> Value {
+        let root = match self.root.reference.as_deref().and_then(|r| self.resolve(r)) {
+            Some(target) => target,
+            None => &self.root,
+        };
+
+        let mut object = serde_json::Map::with_capacity(params.len());
+        for (key, raw) in params {
+            let expected = root
+                .properties
+                .iter()
+                .find(|(name, _)| name == key)
+                .and_then(|(_, prop)| prop.scalar_type(self));
+            object.insert(key.clone(), coerce_scalar(raw, expected));
+        }
+        Value::Object(object)
+    }
+
+    fn validate_node(
+        &self,
+        node: &SchemaNode,
+        value: &Value,
+        loc: &mut Vec<Value>,
+        issues: &mut Vec<ValidationIssue>,
+    ) {
+        if node.never {
+            issues.push(ValidationIssue::new(
+                loc,
+                "Value is not allowed",
+                "not_allowed",
+            ));
+            return;
+        }
+
+        if let Some(target) = node.reference.as_deref().and_then(|r| self.resolve(r)) {
+            self.validate_node(target, value, loc, issues);
+        }
+
+        if !node.types.is_empty() && !node.types.iter().any(|t| t.matches(value)) {
+            let expected = node
+                .types
+                .iter()
+                .find(|t| **t != JsonType::Null)
+                .unwrap_or(&node.types[0]);
+            issues.push(ValidationIssue::new(
+                loc,
+                format!("Input should be a valid {}", expected.name()),
+                &format!("{}_type", expected.name()),
+            ));
+            return;
+        }
+
+        if let Some(values) = &node.enum_values {
+            if !values.contains(value) {
+                let allowed: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+                issues.push(ValidationIssue::new(
+                    loc,
+                    format!("Input should be one of: {}", allowed.join(", ")),
+                    "enum",
+                ));
+            }
+        }
+        if let Some(expected) = &node.const_value {
+            if value != expected {
+                issues.push(ValidationIssue::new(
+                    loc,
+                    format!("Input should be {expected}"),
+                    "literal_error",
+                ));
+            }
+        }
+
+        match value {
+            Value::Number(n) => self.validate_number(node, n.as_f64().unwrap_or(0.0), loc, issues),
+            Value::String(s) => self.validate_string(node, s, loc, issues),
+            Value::Array(items) => self.validate_array(node, items, loc, issues),
+            Value::Object(map) => self.validate_object(node, map, loc, issues),
+            _ => {}
+        }
+
+        if !node.any_of.is_empty() && !node.any_of.iter().any(|s| self.passes(s, value, loc)) {
+            self.push_branch_issues(&node.any_of, value, loc, issues);
+        }
+        if !node.one_of.is_empty() {
+            let matched = node
+                .one_of
+                .iter()
+                .filter(|s| self.passes(s, value, loc))
+                .count();
+            if matched == 0 {
+                self.push_branch_issues(&node.one_of, value, loc, issues);
+            } else if matched > 1 {
+                issues.push(ValidationIssue::new(
+                    loc,
+                    "Input matches more than one allowed schema",
+                    "one_of",
+                ));
+            }
+        }
+        for sub in &node.all_of {
+            self.validate_node(sub, value, loc, issues);
+        }
+        if let Some(not) = &node.not {
+            if self.passes(not, value, loc) {
+                issues.push(ValidationIssue::new(
+                    loc,
+                    "Value is not allowed",
+                    "not_allowed",
+                ));
+            }
+        }
+    }
+
+    fn passes(&self, node: &SchemaNode, value: &Value, loc: &mut Vec<Value>) -> bool {
+        let mut scratch = Vec::new();
+        self.validate_node(node, value, loc, &mut scratch);
+        scratch.is_empty()
+    }
+
+    /// Report a failed anyOf/oneOf: the nested issues when only one branch is
+    /// a real alternative (e.g. `Optional[Model]`), a summary otherwise.
+    fn push_branch_issues(
+        &self,
+        branches: &[SchemaNode],
+        value: &Value,
+        loc: &mut Vec<Value>,
+        issues: &mut Vec<ValidationIssue>,
+    ) {
+        let non_null: Vec<&SchemaNode> = branches
+            .iter()
+            .filter(|s| s.types.as_slice() != [JsonType::Null])
+            .collect();
+        if let [only] = non_null.as_slice() {
+            self.validate_node(only, value, loc, issues);
+        } else {
+            issues.push(ValidationIssue::new(
+                loc,
+                "Input does not match any allowed schema",
+                "union",
+            ));
+        }
+    }
+
+    fn validate_number(
+        &self,
+        node: &SchemaNode,
+        n: f64,
+        loc: &[Value],
+        issues: &mut Vec<ValidationIssue>,
+    ) {
+        if let Some(min) = node.minimum {
+            if n < min {
+                issues.push(ValidationIssue::new(
+                    loc,
+                    format!("Input should be greater than or equal to {min}"),
+                    "greater_than_equal",
+                ));
+            }
+        }
+        if let Some(max) = node.maximum {
+            if n > max {
+                issues.push(ValidationIssue::new(
+                    loc,
+                    format!("Input should be less than or equal to {max}"),
+                    "less_than_equal",
+                ));
+            }
+        }
+        if let Some(min) = node.exclusive_minimum {
+            if n <= min {
+                issues.push(ValidationIssue::new(
+                    loc,
+                    format!("Input should be greater than {min}"),
+                    "greater_than",
+                ));
+            }
+        }
+        if let Some(max) = node.exclusive_maximum {
+            if n >= max {
+                issues.push(ValidationIssue::new(
+                    loc,
+                    format!("Input should be less than {max}"),
+                    "less_than",
+                ));
+            }
+        }
+        if let Some(step) = node.multiple_of {
+            if step > 0.0 && (n / step).fract().abs() > f64::EPSILON {
+                issues.push(ValidationIssue::new(
+                    loc,
+                    format!("Input should be a multiple of {step}"),
+                    "multiple_of",
+                ));
+            }
+        }
+    }
+
+    fn validate_string(
+        &self,
+        node: &SchemaNode,
+        s: &str,
+        loc: &[Value],
+        issues: &mut Vec<ValidationIssue>,
+    ) {
+        let len = s.chars().count();
+        if let Some(min) = node.min_length {
+            if len < min {
+                issues.push(ValidationIssue::new(
+                    loc,
+                    format!("String should have at least {min} characters"),
+                    "string_too_short",
+                ));
+            }
+        }
+        if let Some(max) = node.max_length {
+            if len > max {
+                issues.push(ValidationIssue::new(
+                    loc,
+                    format!("String should have at most {max} characters"),
+                    "string_too_long",
+                ));
+            }
+        }
+        if let Some(pattern) = &node.pattern {
+            if !pattern.is_match(s) {
+                issues.push(ValidationIssue::new(
+                    loc,
+                    format!("String should match pattern '{}'", pattern.as_str()),
+                    "string_pattern_mismatch",
+                ));
+            }
+        }
+        if let Some(format) = &node.format {
+            if !check_format(format, s) {
+                issues.push(ValidationIssue::new(
+                    loc,
+                    format!("Input should be a valid {format}"),
+                    "value_error",
+                ));
+            }
+        }
+    }
+
+    fn validate_array(
+        &self,
+        node: &SchemaNode,
+        items: &[Value],
+        loc: &mut Vec<Value>,
+        issues: &mut Vec<ValidationIssue>,
+    ) {
+        if let Some(min) = node.min_items {
+            if items.len() < min {
+                issues.push(ValidationIssue::new(
+                    loc,
+                    format!("List should have at least {min} items"),
+                    "too_short",
+                ));
+            }
+        }
+        if let Some(max) = node.max_items {
+            if items.len() > max {
+                issues.push(ValidationIssue::new(
+                    loc,
+                    format!("List should have at most {max} items"),
+                    "too_long",
+                ));
+            }
+        }
+        if node.unique_items {
+            let duplicate = items
+                .iter()
+                .enumerate()
+                .any(|(i, a)| items[..i].contains(a));
+            if duplicate {
+                issues.push(ValidationIssue::new(
+                    loc,
+                    "List items should be unique",
+                    "unique_items",
+                ));
+            }
+        }
+        if let Some(item_schema) = &node.items {
+            for (index, item) in items.iter().enumerate() {
+                loc.push(Value::from(index));
+                self.validate_node(item_schema, item, loc, issues);
+                loc.pop();
+            }
+        }
+    }
+
+    fn validate_object(
+        &self,
+        node: &SchemaNode,
+        map: &serde_json::Map<String, Value>,
+        loc: &mut Vec<Value>,
+        issues: &mut Vec<ValidationIssue>,
+    ) {
+        for name in &node.required {
+            if !map.contains_key(name) {
+                loc.push(Value::from(name.as_str()));
+                issues.push(ValidationIssue::new(loc, "Field required", "missing"));
+                loc.pop();
+            }
+        }
+
+        for (key, value) in map {
+            loc.push(Value::from(key.as_str()));
+            match node.properties.iter().find(|(name, _)| name == key) {
+                Some((_, prop)) => self.validate_node(prop, value, loc, issues),
+                None => match &node.additional {
+                    Additional::Allow => {}
+                    Additional::Deny => issues.push(ValidationIssue::new(
+                        loc,
+                        "Extra inputs are not permitted",
+                        "extra_forbidden",
+                    )),
+                    Additional::Schema(schema) => self.validate_node(schema, value, loc, issues),
+                },
+            }
+            loc.pop();
+        }
+    }
+}
+
+/// Convert a raw string parameter to the JSON type the schema expects.
+fn coerce_scalar(raw: &str, expected: Option<JsonType>) -> Value {
+    match expected {
+        Some(JsonType::Integer) => raw
+            .parse::<i64>()
+            .map(Value::from)
+            .unwrap_or_else(|_| Value::String(raw.to_string())),
+        Some(JsonType::Number) => raw
+            .parse::<f64>()
+            .ok()
+            .and_then(serde_json::Number::from_f64)
+            .map(Value::Number)
+            .unwrap_or_else(|| Value::String(raw.to_string())),
+        Some(JsonType::Boolean) => match raw.to_ascii_lowercase().as_str() {
+            "true" | "1" | "yes" | "on" => Value::Bool(true),
+            "false" | "0" | "no" | "off" => Value::Bool(false),
+            _ => Value::String(raw.to_string()),
+        },
+        Some(JsonType::Array) => Value::Array(
+            raw.split(',')
+                .map(|part| Value::String(part.to_string()))
+                .collect(),
+        ),
+        _ => Value::String(raw.to_string()),
+    }
+}
+
+/// Check the common `format` values; unknown formats are accepted.
+fn check_format(format: &str, s: &str) -> bool {
+    thread_local! {
+        static EMAIL_RE: Regex = Regex::new(r"^[^@\s]+@[^@\s]+\.[^@\s]+$").unwrap();
+        static UUID_RE: Regex = Regex::new(
+            r"^[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}$",
+        )
+        .unwrap();
+        static DATE_RE: Regex = Regex::new(r"^\d{4}-\d{2}-\d{2}$").unwrap();
+        static DATE_TIME_RE: Regex = Regex::new(
+            r"^\d{4}-\d{2}-\d{2}[Tt ]\d{2}:\d{2}:\d{2}(\.\d+)?([Zz]|[+-]\d{2}:?\d{2})?$",
+        )
+        .unwrap();
+    }
+
+    match format {
+        "email" => EMAIL_RE.with(|re| re.is_match(s)),
+        "uuid" => UUID_RE.with(|re| re.is_match(s)),
+        "date" => DATE_RE.with(|re| re.is_match(s)),
+        "date-time" => DATE_TIME_RE.with(|re| re.is_match(s)),
+        _ => true,
+    }
+}
+
+// ============================================================================
+// Request Validator
+// ============================================================================
+
+/// Schemas attached to a route for its body, query string and path params.
+#[derive(Debug, Clone, Default)]
+pub struct RequestValidator {
+    body: Option<JsonSchema>,
+    query: Option<JsonSchema>,
+    params: Option<JsonSchema>,
+}
+
+impl RequestValidator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Validate the request body (JSON, MessagePack or CBOR) against a schema.
+    pub fn body(mut self, schema: &Value) -> Result<Self, String> {
+        self.body = Some(JsonSchema::compile(schema)?);
+        Ok(self)
+    }
+
+    /// Validate query parameters against an object schema.
+    pub fn query(mut self, schema: &Value) -> Result<Self, String> {
+        self.query = Some(JsonSchema::compile(schema)?);
+        Ok(self)
+    }
+
+    /// Validate path parameters against an object schema.
+    pub fn params(mut self, schema: &Value) -> Result<Self, String> {
+        self.params = Some(JsonSchema::compile(schema)?);
+        Ok(self)
+    }
+
+    /// Whether any schema is attached.
+    pub fn is_empty(&self) -> bool {
+        self.body.is_none() && self.query.is_none() && self.params.is_none()
+    }
+
+    /// Validate a request, collecting issues from every part.
+    pub fn validate(&self, request: &Request) -> Result<(), Vec<ValidationIssue>> {
+        let mut issues = Vec::new();
+
+        if let Some(schema) = &self.params {
+            let value = schema.coerce_params(&request.params);
+            issues.extend(schema.validate(&value, &[Value::from("path")]));
+        }
+        if let Some(schema) = &self.query {
+            let value = schema.coerce_params(&request.query_params);
+            issues.extend(schema.validate(&value, &[Value::from("query")]));
+        }
+        if let Some(schema) = &self.body {
+            let loc = [Value::from("body")];
+            if request.body.is_empty() {
+                issues.push(ValidationIssue::new(&loc, "Field required", "missing"));
+            } else {
+                match request.decode_body() {
+                    Ok(value) => {
+                        issues.extend(schema.validate(&value, &loc));
+                        // PERF: The handler's request.json()/data() reuses this parse
+                        request.set_parsed_body(value);
+                    }
+                    Err(_) if request.is_binary_data() => issues.push(ValidationIssue::new(
+                        &loc,
+                        "Invalid request body",
+                        "value_error.body",
+                    )),
+                    Err(_) => issues.push(ValidationIssue::new(
+                        &loc,
+                        "Invalid JSON body",
+                        "value_error.json",
+                    )),
+                }
+            }
+        }
+
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(issues)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn user_schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "name": {"type": "string", "minLength": 2},
+                "age": {"type": "integer", "minimum": 0},
+                "email": {"type": "string", "format": "email"},
+                "tags": {"type": "array", "items": {"type": "string"}, "maxItems": 2},
+                "address": {"$ref": "#/$defs/Address"},
+                "nickname": {"anyOf": [{"type": "string"}, {"type": "null"}]}
+            },
+            "required": ["name", "age"],
+            "additionalProperties": false,
+            "$defs": {
+                "Address": {
+                    "type": "object",
+                    "properties": {"city": {"type": "string"}},
+                    "required": ["city"]
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn test_valid_document() {
+        let schema = JsonSchema::compile(&user_schema()).unwrap();
+        let value = json!({
+            "name": "Ada",
+            "age": 36,
+            "email": "ada@example.com",
+            "tags": ["math"],
+            "address": {"city": "London"},
+            "nickname": null
+        });
+        assert!(schema.validate(&value, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_collects_issues_with_locations() {
+        let schema = JsonSchema::compile(&user_schema()).unwrap();
+        let value = json!({
+            "name": "A",
+            "email": "not-an-email",
+            "tags": ["a", 1, "c"],
+            "address": {},
+            "nickname": 5,
+            "extra": true
+        });
+        let issues = schema.validate(&value, &[json!("body")]);
+        let found: Vec<(Vec<Value>, &str)> = issues
+            .iter()
+            .map(|i| (i.loc.clone(), i.kind.as_str()))
+            .collect();
+
+        assert!(found.contains(&(vec![json!("body"), json!("age")], "missing")));
+        assert!(found.contains(&(vec![json!("body"), json!("name")], "string_too_short")));
+        assert!(found.contains(&(vec![json!("body"), json!("email")], "value_error")));
+        assert!(found.contains(&(vec![json!("body"), json!("tags")], "too_long")));
+        assert!(found.contains(&(vec![json!("body"), json!("tags"), json!(1)], "string_type")));
+        assert!(found.contains(&(
+            vec![json!("body"), json!("address"), json!("city")],
+            "missing"
+        )));
+        assert!(found.contains(&(vec![json!("body"), json!("nickname")], "string_type")));
+        assert!(found.contains(&(vec![json!("body"), json!("extra")], "extra_forbidden")));
+    }
+
+    #[test]
+    fn test_compile_errors() {
+        assert!(JsonSchema::compile(&json!({"type": "bogus"})).is_err());
+        assert!(JsonSchema::compile(&json!({"pattern": "("})).is_err());
+        assert!(JsonSchema::compile(&json!({"$ref": "#/$defs/Missing"})).is_err());
+    }
+
+    #[test]
+    fn test_reference_cycles() {
+        for schema in [
+            json!({"$ref": "#"}),
+            json!({"anyOf": [{"$ref": "#"}]}),
+            json!({"$defs": {"A": {"$ref": "#/$defs/A"}}}),
+            json!({
+                "$ref": "#/$defs/A",
+                "$defs": {"A": {"allOf": [{"$ref": "#/$defs/B"}]}, "B": {"$ref": "#/$defs/A"}},
+            }),
+        ] {
+            let err = JsonSchema::compile(&schema).unwrap_err();
+            assert!(err.starts_with("Schema reference cycle"), "{err}");
+        }
+
+        // Recursion through properties and items consumes the value.
+        let tree = JsonSchema::compile(&json!({
+            "type": "object",
+            "properties": {"children": {"type": "array", "items": {"$ref": "#"}}},
+        }))
+        .unwrap();
+        let value = json!({"children": [{"children": []}, {"children": [1]}]});
+        let issues = tree.validate(&value, &[]);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].kind, "object_type");
+    }
+
+    #[test]
+    fn test_request_validator() {
+        let validator = RequestValidator::new()
+            .params(&json!({
+                "type": "object",
+                "properties": {"id": {"type": "integer", "minimum": 1}}
+            }))
+            .unwrap()
+            .query(&json!({
+                "type": "object",
+                "properties": {"verbose": {"type": "boolean"}, "limit": {"type": "integer"}},
+                "required": ["limit"]
+            }))
+            .unwrap()
+            .body(&user_schema())
+            .unwrap();
+
+        let mut request = Request::default();
+        request.params.insert("id".to_string(), "7".to_string());
+        request
+            .query_params
+            .insert("limit".to_string(), "10".to_string());
+        request
+            .query_params
+            .insert("verbose".to_string(), "true".to_string());
+        request.body = br#"{"name": "Ada", "age": 36}"#.to_vec();
+        assert!(validator.validate(&request).is_ok());
+
+        request.params.insert("id".to_string(), "abc".to_string());
+        request.query_params.remove("limit");
+        request.body = b"{not json".to_vec();
+        let issues = validator.validate(&request).unwrap_err();
+        let kinds: Vec<&str> = issues.iter().map(|i| i.kind.as_str()).collect();
+        assert_eq!(kinds, vec!["integer_type", "missing", "value_error.json"]);
+        assert_eq!(issues[0].loc, vec![json!("path"), json!("id")]);
+        assert_eq!(issues[1].loc, vec![json!("query"), json!("limit")]);
+
+        let response = validation_error_response(&issues);
+        assert_eq!(response.status, 422);
+        let body: Value = serde_json::from_slice(response.body_bytes()).unwrap();
+        assert_eq!(body["detail"][1]["msg"], "Field required");
+    }
+}
@@ -0,0 +1,847 @@
+//! Outgoing Webhooks for Cello Framework
+//!
+//! Delivers events to HTTP endpoints registered per event type:
+//! - JSON payloads signed with HMAC-SHA256 over the timestamp and body
+//! - Retries with exponential backoff on errors and non-2xx responses
+//! - Per-delivery attempt history and dispatcher-wide stats
+//! - Fan-out of domain events from the event store, resuming from a
+//!   checkpoint after a restart
+//!
+//! # Example
+//! ```python
+//! app.enable_webhooks(max_attempts=5)
+//! app.register_webhook("https://example.com/hooks", ["order.*"], secret="whsec")
+//!
+//! app.dispatch_webhook("order.created", {"id": 42})
+//! ```
+//!
+//! Each request carries `X-Webhook-Id` (stable across retries, so receivers
+//! can deduplicate), `X-Webhook-Event` and, when the endpoint has a secret,
+//! `X-Webhook-Signature: t=<unix seconds>,v1=<hex hmac of "<t>.<body>">`.
+
+use bytes::Bytes;
+use hmac::{Hmac, Mac};
+use parking_lot::{Mutex, RwLock};
+use serde::Serialize;
+use serde_json::{json, Value as JsonValue};
+use sha2::Sha256;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::runtime::Handle;
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+
+use crate::middleware::eventsourcing::{EventStore, EventSubscription};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Header carrying the delivery id.
+pub const ID_HEADER: &str = "X-Webhook-Id";
+/// Header carrying the event type.
+pub const EVENT_HEADER: &str = "X-Webhook-Event";
+/// Header carrying the timestamped signature.
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+
+/// Subscriber name used to checkpoint forwarded domain events.
+pub const EVENT_SUBSCRIBER: &str = "webhooks";
+
+// ============================================================================
+// Configuration
+// ============================================================================
+
+/// Delivery settings shared by all endpoints.
+#[derive(Debug, Clone)]
+pub struct WebhookConfig {
+    /// Attempts per delivery, including the first.
+    pub max_attempts: u32,
+    /// Wait before the first retry; doubled for each later one.
+    pub initial_backoff: Duration,
+    /// Upper bound on the wait between retries.
+    pub max_backoff: Duration,
+    /// Timeout for a single request.
+    pub timeout: Duration,
+    /// Finished deliveries kept for inspection.
+    pub history_limit: usize,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(300),
+            timeout: Duration::from_secs(10),
+            history_limit: 1000,
+        }
+    }
+}
+
+impl WebhookConfig {
+    /// Set the attempts per delivery (at least one).
+    pub fn with_max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+
+    /// Set the initial and maximum retry backoff.
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max.max(initial);
+        self
+    }
+
+    /// Set the per-request timeout.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Wait before retry number `retry` (1 for the first retry).
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+/// A registered delivery target.
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookEndpoint {
+    /// Endpoint identifier.
+    pub id: String,
+    /// URL the payloads are POSTed to.
+    pub url: String,
+    /// Event types delivered: exact names, `prefix.*` or `*`.
+    pub events: Vec<String>,
+    /// Key for the signature header; unsigned if `None`.
+    #[serde(skip)]
+    pub secret: Option<String>,
+    /// Whether new events are delivered to the endpoint.
+    pub active: bool,
+}
+
+impl WebhookEndpoint {
+    /// Create an active endpoint.
+    pub fn new(id: &str, url: &str, events: Vec<String>) -> Self {
+        Self {
+            id: id.to_string(),
+            url: url.to_string(),
+            events,
+            secret: None,
+            active: true,
+        }
+    }
+
+    /// Sign payloads with `secret`.
+    pub fn with_secret(mut self, secret: &str) -> Self {
+        self.secret = Some(secret.to_string());
+        self
+    }
+
+    /// Check whether the endpoint subscribes to `event_type`.
+    pub fn matches(&self, event_type: &str) -> bool {
+        self.events
+            .iter()
+            .any(|pattern| match pattern.strip_suffix('*') {
+                Some(prefix) => event_type.starts_with(prefix),
+                None => pattern == event_type,
+            })
+    }
+}
+
+/// Compute the signature header value for `body` sent at `timestamp`.
+pub fn sign(secret: &str, timestamp: u64, body: &[u8]) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!(
+        "t={timestamp},v1={}",
+        hex::encode(mac.finalize().into_bytes())
+    )
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+// ============================================================================
+// Deliveries
+// ============================================================================
+
+/// Delivery state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    /// Waiting for its first attempt.
+    Pending,
+    /// An attempt failed; another is scheduled.
+    Retrying,
+    /// The endpoint answered with a 2xx status.
+    Delivered,
+    /// Every attempt failed.
+    Failed,
+}
+
+/// One request made for a delivery.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeliveryAttempt {
+    /// Attempt number, starting at 1.
+    pub attempt: u32,
+    /// Response status, if a response was received.
+    pub status_code: Option<u16>,
+    /// Transport error, if the request failed.
+    pub error: Option<String>,
+    /// Request duration in milliseconds.
+    pub duration_ms: u64,
+    /// Unix timestamp (seconds) of the attempt.
+    pub timestamp: u64,
+}
+
+/// An event being delivered to one endpoint.
+#[derive(Debug, Clone, Serialize)]
+pub struct Delivery {
+    /// Delivery identifier, sent as `X-Webhook-Id`.
+    pub id: String,
+    /// Target endpoint.
+    pub endpoint_id: String,
+    /// Event type.
+    pub event_type: String,
+    /// Current state.
+    pub status: DeliveryStatus,
+    /// Attempts made so far.
+    pub attempts: Vec<DeliveryAttempt>,
+    /// Unix timestamp (seconds) the delivery was created.
+    pub created_at: u64,
+    /// Signed JSON body.
+    #[serde(skip)]
+    body: Bytes,
+}
+
+/// Dispatcher statistics.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct WebhookStats {
+    /// Registered endpoints.
+    pub endpoints: usize,
+    /// Deliveries created.
+    pub dispatched: u64,
+    /// Deliveries that succeeded.
+    pub delivered: u64,
+    /// Deliveries that ran out of attempts.
+    pub failed: u64,
+    /// Deliveries still in progress.
+    pub in_flight: u64,
+    /// Requests made, including retries.
+    pub attempts: u64,
+}
+
+#[derive(Default)]
+struct Metrics {
+    dispatched: AtomicU64,
+    delivered: AtomicU64,
+    failed: AtomicU64,
+    attempts: AtomicU64,
+}
+
+// ============================================================================
+// Transport
+// ============================================================================
+
+/// Future returned by a [`WebhookTransport`]: the response status or an error.
+pub type TransportFuture = Pin<Box<dyn Future<Output = Result<u16, String>> + Send>>;
+
+/// Sends webhook requests.
+pub trait WebhookTransport: Send + Sync {
+    /// POST `body` to `url` with `headers`, giving up after `timeout`.
+    fn post(
+        &self,
+        url: &str,
+        headers: Vec<(String, String)>,
+        body: Bytes,
+        timeout: Duration,
+    ) -> TransportFuture;
+}
+
+/// HTTP transport backed by reqwest.
+#[derive(Default)]
+pub struct HttpTransport {
+    client: reqwest::Client,
+}
+
+impl WebhookTransport for HttpTransport {
+    fn post(
+        &self,
+        url: &str,
+        headers: Vec<(String, String)>,
+        body: Bytes,
+        timeout: Duration,
+    ) -> TransportFuture {
+        let mut request = self.client.post(url).timeout(timeout).body(body);
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+        Box::pin(async move {
+            request
+                .send()
+                .await
+                .map(|response| response.status().as_u16())
+                .map_err(|e| e.to_string())
+        })
+    }
+}
+
+// ============================================================================
+// Dispatcher
+// ============================================================================
+
+/// Delivers events to registered endpoints in the background.
+pub struct WebhookDispatcher {
+    config: WebhookConfig,
+    endpoints: RwLock<Vec<WebhookEndpoint>>,
+    deliveries: Mutex<VecDeque<Arc<Mutex<Delivery>>>>,
+    transport: Arc<dyn WebhookTransport>,
+    runtime: Handle,
+    tracker: TaskTracker,
+    forwarding: Mutex<Option<CancellationToken>>,
+    metrics: Metrics,
+}
+
+impl WebhookDispatcher {
+    /// Create a dispatcher that spawns deliveries on `runtime`.
+    pub fn new(
+        config: WebhookConfig,
+        transport: Arc<dyn WebhookTransport>,
+        runtime: Handle,
+    ) -> Self {
+        Self {
+            config,
+            endpoints: RwLock::new(Vec::new()),
+            deliveries: Mutex::new(VecDeque::new()),
+            transport,
+            runtime,
+            tracker: TaskTracker::new(),
+            forwarding: Mutex::new(None),
+            metrics: Metrics::default(),
+        }
+    }
+
+    /// Get the configuration.
+    pub fn config(&self) -> &WebhookConfig {
+        &self.config
+    }
+
+    /// Register an endpoint.
+    pub fn register(&self, endpoint: WebhookEndpoint) -> Result<(), WebhookError> {
+        if !endpoint.url.starts_with("http://") && !endpoint.url.starts_with("https://") {
+            return Err(WebhookError::InvalidEndpoint(format!(
+                "URL must be http(s): {}",
+                endpoint.url
+            )));
+        }
+        let mut endpoints = self.endpoints.write();
+        if endpoints.iter().any(|e| e.id == endpoint.id) {
+            return Err(WebhookError::DuplicateEndpoint(endpoint.id));
+        }
+        endpoints.push(endpoint);
+        Ok(())
+    }
+
+    /// Remove an endpoint; deliveries already in progress continue.
+    pub fn unregister(&self, id: &str) -> bool {
+        let mut endpoints = self.endpoints.write();
+        let before = endpoints.len();
+        endpoints.retain(|e| e.id != id);
+        endpoints.len() != before
+    }
+
+    /// Pause or resume deliveries to an endpoint.
+    pub fn set_active(&self, id: &str, active: bool) -> Result<(), WebhookError> {
+        let mut endpoints = self.endpoints.write();
+        let endpoint = endpoints
+            .iter_mut()
+            .find(|e| e.id == id)
+            .ok_or_else(|| WebhookError::EndpointNotFound(id.to_string()))?;
+        endpoint.active = active;
+        Ok(())
+    }
+
+    /// Registered endpoints.
+    pub fn endpoints(&self) -> Vec<WebhookEndpoint> {
+        self.endpoints.read().clone()
+    }
+
+    /// Deliver `payload` to every active endpoint subscribed to
+    /// `event_type`, returning the delivery ids.
+    pub fn dispatch(self: &Arc<Self>, event_type: &str, payload: JsonValue) -> Vec<String> {
+        let targets: Vec<WebhookEndpoint> = self
+            .endpoints
+            .read()
+            .iter()
+            .filter(|e| e.active && e.matches(event_type))
+            .cloned()
+            .collect();
+
+        let created_at = unix_now();
+        let mut ids = Vec::with_capacity(targets.len());
+        for endpoint in targets {
+            let id = format!("whd_{}", uuid::Uuid::new_v4().simple());
+            let body = json!({
+                "id": id,
+                "type": event_type,
+                "created_at": created_at,
+                "data": payload,
+            });
+            let delivery = Arc::new(Mutex::new(Delivery {
+                id: id.clone(),
+                endpoint_id: endpoint.id.clone(),
+                event_type: event_type.to_string(),
+                status: DeliveryStatus::Pending,
+                attempts: Vec::new(),
+                created_at,
+                body: Bytes::from(serde_json::to_vec(&body).unwrap_or_default()),
+            }));
+            self.record(delivery.clone());
+            self.metrics.dispatched.fetch_add(1, Ordering::Relaxed);
+
+            let dispatcher = self.clone();
+            self.tracker
+                .spawn_on(dispatcher.deliver(endpoint, delivery), &self.runtime);
+            ids.push(id);
+        }
+        ids
+    }
+
+    fn record(&self, delivery: Arc<Mutex<Delivery>>) {
+        let mut deliveries = self.deliveries.lock();
+        deliveries.push_back(delivery);
+        // Drop the oldest finished deliveries beyond the history limit.
+        while deliveries.len() > self.config.history_limit {
+            let finished = deliveries.iter().position(|d| {
+                matches!(
+                    d.lock().status,
+                    DeliveryStatus::Delivered | DeliveryStatus::Failed
+                )
+            });
+            match finished {
+                Some(index) => {
+                    deliveries.remove(index);
+                }
+                None => break,
+            }
+        }
+    }
+
+    async fn deliver(self: Arc<Self>, endpoint: WebhookEndpoint, delivery: Arc<Mutex<Delivery>>) {
+        let (id, event_type, body) = {
+            let d = delivery.lock();
+            (d.id.clone(), d.event_type.clone(), d.body.clone())
+        };
+
+        for attempt in 1..=self.config.max_attempts {
+            if attempt > 1 {
+                tokio::time::sleep(self.config.backoff(attempt - 1)).await;
+            }
+            let mut headers = vec![
+                ("Content-Type".to_string(), "application/json".to_string()),
+                (ID_HEADER.to_string(), id.clone()),
+                (EVENT_HEADER.to_string(), event_type.clone()),
+            ];
+            if let Some(secret) = &endpoint.secret {
+                headers.push((
+                    SIGNATURE_HEADER.to_string(),
+                    sign(secret, unix_now(), &body),
+                ));
+            }
+
+            let started = Instant::now();
+            let result = self
+                .transport
+                .post(&endpoint.url, headers, body.clone(), self.config.timeout)
+                .await;
+            self.metrics.attempts.fetch_add(1, Ordering::Relaxed);
+
+            let succeeded = matches!(result, Ok(status) if (200..300).contains(&status));
+            let mut d = delivery.lock();
+            d.attempts.push(DeliveryAttempt {
+                attempt,
+                status_code: result.as_ref().ok().copied(),
+                error: result.err(),
+                duration_ms: started.elapsed().as_millis() as u64,
+                timestamp: unix_now(),
+            });
+            if succeeded {
+                d.status = DeliveryStatus::Delivered;
+                self.metrics.delivered.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+            d.status = DeliveryStatus::Retrying;
+        }
+
+        delivery.lock().status = DeliveryStatus::Failed;
+        self.metrics.failed.fetch_add(1, Ordering::Relaxed);
+        tracing::warn!(
+            "Webhook delivery {id} to {} failed after {} attempts",
+            endpoint.url,
+            self.config.max_attempts
+        );
+    }
+
+    /// Look up a delivery.
+    pub fn delivery(&self, id: &str) -> Option<Delivery> {
+        self.deliveries
+            .lock()
+            .iter()
+            .map(|d| d.lock())
+            .find(|d| d.id == id)
+            .map(|d| d.clone())
+    }
+
+    /// Recent deliveries, newest first.
+    pub fn deliveries(&self, limit: Option<usize>) -> Vec<Delivery> {
+        self.deliveries
+            .lock()
+            .iter()
+            .rev()
+            .take(limit.unwrap_or(usize::MAX))
+            .map(|d| d.lock().clone())
+            .collect()
+    }
+
+    /// Current statistics.
+    pub fn stats(&self) -> WebhookStats {
+        let dispatched = self.metrics.dispatched.load(Ordering::Relaxed);
+        let delivered = self.metrics.delivered.load(Ordering::Relaxed);
+        let failed = self.metrics.failed.load(Ordering::Relaxed);
+        WebhookStats {
+            endpoints: self.endpoints.read().len(),
+            dispatched,
+            delivered,
+            failed,
+            in_flight: dispatched - delivered - failed,
+            attempts: self.metrics.attempts.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Wait for every delivery in progress to finish.
+    pub async fn drain(&self) {
+        self.tracker.close();
+        self.tracker.wait().await;
+        self.tracker.reopen();
+    }
+
+    // ------------------------------------------------------------------------
+    // Event sourcing
+    // ------------------------------------------------------------------------
+
+    /// Fan out events appended to `store` to the endpoints subscribed to
+    /// their type, resuming after the last forwarded event. Does nothing if
+    /// already forwarding.
+    pub fn forward_events(
+        self: &Arc<Self>,
+        store: Arc<dyn EventStore>,
+    ) -> Result<(), WebhookError> {
+        let mut forwarding = self.forwarding.lock();
+        if forwarding.is_some() {
+            return Ok(());
+        }
+        let mut subscription = EventSubscription::new(store, EVENT_SUBSCRIBER)
+            .map_err(|e| WebhookError::EventStore(e.to_string()))?;
+        let token = CancellationToken::new();
+        let cancelled = token.clone();
+        let dispatcher = self.clone();
+        self.runtime.spawn(async move {
+            loop {
+                let event = tokio::select! {
+                    _ = cancelled.cancelled() => return,
+                    event = subscription.next() => event,
+                };
+                match event {
+                    Ok(event) => {
+                        let payload = serde_json::to_value(&event).unwrap_or(JsonValue::Null);
+                        dispatcher.dispatch(&event.event_type, payload);
+                        if let Err(e) = subscription.checkpoint() {
+                            tracing::warn!("Failed to checkpoint webhook events: {e}");
+                        }
+                    }
+                    Err(e) => {
+                        tracing::warn!("Stopped forwarding events to webhooks: {e}");
+                        return;
+                    }
+                }
+            }
+        });
+        *forwarding = Some(token);
+        Ok(())
+    }
+
+    /// Stop forwarding events from the event store.
+    pub fn stop_forwarding(&self) {
+        if let Some(token) = self.forwarding.lock().take() {
+            token.cancel();
+        }
+    }
+}
+
+impl Drop for WebhookDispatcher {
+    fn drop(&mut self) {
+        self.stop_forwarding();
+    }
+}
+
+// ============================================================================
+// Error Types
+// ============================================================================
+
+/// Webhook error types.
+#[derive(Debug, Clone)]
+pub enum WebhookError {
+    /// The endpoint's URL or settings are invalid.
+    InvalidEndpoint(String),
+    /// An endpoint with this id is already registered.
+    DuplicateEndpoint(String),
+    /// No endpoint with this id is registered.
+    EndpointNotFound(String),
+    /// The event store could not be subscribed to.
+    EventStore(String),
+}
+
+impl std::fmt::Display for WebhookError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WebhookError::InvalidEndpoint(msg) => write!(f, "Invalid webhook endpoint: {msg}"),
+            WebhookError::DuplicateEndpoint(id) => {
+                write!(f, "Webhook endpoint already registered: {id}")
+            }
+            WebhookError::EndpointNotFound(id) => write!(f, "Webhook endpoint not found: {id}"),
+            WebhookError::EventStore(msg) => write!(f, "Event store error: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for WebhookError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::eventsourcing::{Event, InMemoryEventStore};
+
+    /// A request seen by the mock: URL, headers and body.
+    type SentRequest = (String, Vec<(String, String)>, Bytes);
+
+    /// Records requests and answers with scripted statuses.
+    #[derive(Default)]
+    struct MockTransport {
+        statuses: Mutex<VecDeque<Result<u16, String>>>,
+        requests: Mutex<Vec<SentRequest>>,
+    }
+
+    impl MockTransport {
+        fn answering(statuses: Vec<Result<u16, String>>) -> Arc<Self> {
+            Arc::new(Self {
+                statuses: Mutex::new(statuses.into()),
+                requests: Mutex::default(),
+            })
+        }
+    }
+
+    impl WebhookTransport for MockTransport {
+        fn post(
+            &self,
+            url: &str,
+            headers: Vec<(String, String)>,
+            body: Bytes,
+            _timeout: Duration,
+        ) -> TransportFuture {
+            self.requests.lock().push((url.to_string(), headers, body));
+            let status = self.statuses.lock().pop_front().unwrap_or(Ok(200));
+            Box::pin(async move { status })
+        }
+    }
+
+    fn dispatcher(transport: Arc<MockTransport>) -> Arc<WebhookDispatcher> {
+        let config = WebhookConfig::default()
+            .with_max_attempts(3)
+            .with_backoff(Duration::from_millis(1), Duration::from_millis(2));
+        Arc::new(WebhookDispatcher::new(config, transport, Handle::current()))
+    }
+
+    fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+        headers
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    // ---------- Endpoint Tests ----------
+
+    #[test]
+    fn test_endpoint_matching() {
+        let endpoint = WebhookEndpoint::new(
+            "e1",
+            "http://hooks.test",
+            vec!["order.*".to_string(), "user.created".to_string()],
+        );
+        assert!(endpoint.matches("order.created"));
+        assert!(endpoint.matches("user.created"));
+        assert!(!endpoint.matches("user.deleted"));
+        assert!(
+            WebhookEndpoint::new("e2", "http://hooks.test", vec!["*".to_string()])
+                .matches("anything")
+        );
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_max() {
+        let config =
+            WebhookConfig::default().with_backoff(Duration::from_secs(1), Duration::from_secs(5));
+        assert_eq!(config.backoff(1), Duration::from_secs(1));
+        assert_eq!(config.backoff(2), Duration::from_secs(2));
+        assert_eq!(config.backoff(3), Duration::from_secs(4));
+        assert_eq!(config.backoff(4), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_sign() {
+        let signature = sign("secret", 1700000000, b"{}");
+        let (timestamp, digest) = signature.split_once(",v1=").unwrap();
+        assert_eq!(timestamp, "t=1700000000");
+        assert_eq!(digest.len(), 64);
+        assert_ne!(signature, sign("other", 1700000000, b"{}"));
+    }
+
+    // ---------- Delivery Tests ----------
+
+    #[tokio::test]
+    async fn test_dispatch_signs_and_delivers() {
+        let transport = MockTransport::answering(vec![]);
+        let webhooks = dispatcher(transport.clone());
+        webhooks
+            .register(
+                WebhookEndpoint::new("e1", "http://hooks.test/a", vec!["order.*".to_string()])
+                    .with_secret("whsec"),
+            )
+            .unwrap();
+        webhooks
+            .register(WebhookEndpoint::new(
+                "e2",
+                "http://hooks.test/b",
+                vec!["user.*".to_string()],
+            ))
+            .unwrap();
+        assert!(webhooks
+            .register(WebhookEndpoint::new("e1", "http://hooks.test/c", vec![]))
+            .is_err());
+
+        let ids = webhooks.dispatch("order.created", json!({"id": 42}));
+        assert_eq!(ids.len(), 1);
+        webhooks.drain().await;
+
+        let requests = transport.requests.lock();
+        let (url, headers, body) = &requests[0];
+        assert_eq!(url, "http://hooks.test/a");
+        assert_eq!(header(headers, ID_HEADER), Some(ids[0].as_str()));
+        assert_eq!(header(headers, EVENT_HEADER), Some("order.created"));
+        let signature = header(headers, SIGNATURE_HEADER).unwrap();
+        let timestamp: u64 = signature[2..signature.find(',').unwrap()].parse().unwrap();
+        assert_eq!(signature, sign("whsec", timestamp, body));
+        let body: JsonValue = serde_json::from_slice(body).unwrap();
+        assert_eq!(body["data"], json!({"id": 42}));
+
+        let delivery = webhooks.delivery(&ids[0]).unwrap();
+        assert_eq!(delivery.status, DeliveryStatus::Delivered);
+        assert_eq!(delivery.attempts.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_retries_until_success_or_exhausted() {
+        let transport =
+            MockTransport::answering(vec![Err("refused".to_string()), Ok(503), Ok(204)]);
+        let webhooks = dispatcher(transport.clone());
+        webhooks
+            .register(WebhookEndpoint::new(
+                "e1",
+                "http://hooks.test",
+                vec!["*".to_string()],
+            ))
+            .unwrap();
+
+        let ok = webhooks.dispatch("a", json!(1));
+        webhooks.drain().await;
+        let delivery = webhooks.delivery(&ok[0]).unwrap();
+        assert_eq!(delivery.status, DeliveryStatus::Delivered);
+        let statuses: Vec<_> = delivery.attempts.iter().map(|a| a.status_code).collect();
+        assert_eq!(statuses, vec![None, Some(503), Some(204)]);
+        assert_eq!(delivery.attempts[0].error.as_deref(), Some("refused"));
+
+        transport
+            .statuses
+            .lock()
+            .extend([Ok(500), Ok(500), Ok(500)]);
+        let failed = webhooks.dispatch("b", json!(2));
+        webhooks.drain().await;
+        assert_eq!(
+            webhooks.delivery(&failed[0]).unwrap().status,
+            DeliveryStatus::Failed
+        );
+
+        let stats = webhooks.stats();
+        assert_eq!(stats.dispatched, 2);
+        assert_eq!(stats.delivered, 1);
+        assert_eq!(stats.failed, 1);
+        assert_eq!(stats.in_flight, 0);
+        assert_eq!(stats.attempts, 6);
+        assert_eq!(webhooks.deliveries(Some(1))[0].id, failed[0]);
+    }
+
+    #[tokio::test]
+    async fn test_forwards_domain_events() {
+        let transport = MockTransport::answering(vec![]);
+        let webhooks = dispatcher(transport.clone());
+        webhooks
+            .register(WebhookEndpoint::new(
+                "e1",
+                "http://hooks.test",
+                vec!["OrderPlaced".to_string()],
+            ))
+            .unwrap();
+
+        let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+        let placed =
+            |id: &str, total: u64| Event::new(id, "OrderPlaced", json!({"total": total}), 1);
+        store
+            .append_events("order-1", &[placed("order-1", 5)], 0)
+            .unwrap();
+        // Events stored before forwarding starts are caught up on.
+        webhooks.forward_events(store.clone()).unwrap();
+        store
+            .append_events(
+                "order-1",
+                &[Event::new("order-1", "OrderShipped", json!({}), 2)],
+                1,
+            )
+            .unwrap();
+        store
+            .append_events("order-2", &[placed("order-2", 7)], 0)
+            .unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while webhooks.stats().delivered < 2 && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        webhooks.stop_forwarding();
+
+        let requests = transport.requests.lock();
+        assert_eq!(requests.len(), 2);
+        let body: JsonValue = serde_json::from_slice(&requests[1].2).unwrap();
+        assert_eq!(body["data"]["aggregate_id"], "order-2");
+        assert_eq!(store.get_checkpoint(EVENT_SUBSCRIBER).unwrap(), Some(3));
+    }
+}
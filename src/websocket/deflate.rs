@@ -0,0 +1,457 @@
+//! permessage-deflate (RFC 7692) for WebSocket messages.
+//!
+//! Negotiates the extension from the client's `Sec-WebSocket-Extensions`
+//! offer and compresses/decompresses message payloads:
+//! - Window bits (9-15) for both directions and no-context-takeover modes
+//! - Messages below a size threshold are sent uncompressed
+//! - Offers the server cannot honour are declined rather than half-accepted
+//!
+//! Chatty JSON traffic typically shrinks several times over, since repeated
+//! keys compress against the sliding window shared across messages.
+
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress, Status};
+
+/// Extension token in `Sec-WebSocket-Extensions`.
+pub const EXTENSION: &str = "permessage-deflate";
+
+/// Empty stored block that ends every flushed message; stripped on the wire.
+const TAIL: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+
+// ============================================================================
+// Configuration & Negotiation
+// ============================================================================
+
+/// Server-side permessage-deflate settings.
+#[derive(Debug, Clone)]
+pub struct DeflateConfig {
+    /// Largest LZ77 window (as a power of two) the server compresses with.
+    pub server_max_window_bits: u8,
+    /// Window the client is asked to limit itself to, if it supports that.
+    pub client_max_window_bits: u8,
+    /// Reset the server's compressor after every message.
+    pub server_no_context_takeover: bool,
+    /// Ask the client to reset its compressor after every message.
+    pub client_no_context_takeover: bool,
+    /// Messages smaller than this many bytes are sent uncompressed.
+    pub threshold: usize,
+    /// Compression level (0-9).
+    pub level: u32,
+}
+
+impl Default for DeflateConfig {
+    fn default() -> Self {
+        Self {
+            server_max_window_bits: 15,
+            client_max_window_bits: 15,
+            server_no_context_takeover: false,
+            client_no_context_takeover: false,
+            threshold: 256,
+            level: 6,
+        }
+    }
+}
+
+impl DeflateConfig {
+    /// Set the server and client window bits, each clamped to 9-15.
+    pub fn with_window_bits(mut self, server: u8, client: u8) -> Self {
+        self.server_max_window_bits = server.clamp(9, 15);
+        self.client_max_window_bits = client.clamp(9, 15);
+        self
+    }
+
+    /// Set the compression threshold in bytes.
+    pub fn with_threshold(mut self, threshold: usize) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Accept the first acceptable `permessage-deflate` offer in a
+    /// `Sec-WebSocket-Extensions` header, or `None` to run uncompressed.
+    pub fn negotiate(&self, header: &str) -> Option<DeflateParams> {
+        header
+            .split(',')
+            .filter_map(|offer| self.accept(offer))
+            .next()
+    }
+
+    fn accept(&self, offer: &str) -> Option<DeflateParams> {
+        let mut parts = offer.split(';').map(str::trim);
+        if !parts.next()?.eq_ignore_ascii_case(EXTENSION) {
+            return None;
+        }
+
+        let mut params = DeflateParams {
+            server_max_window_bits: self.server_max_window_bits,
+            client_max_window_bits: 15,
+            server_no_context_takeover: self.server_no_context_takeover,
+            client_no_context_takeover: self.client_no_context_takeover,
+            announce_server_window: self.server_max_window_bits < 15,
+            announce_client_window: false,
+        };
+        let mut client_window_supported = false;
+        for param in parts.filter(|p| !p.is_empty()) {
+            let (name, value) = match param.split_once('=') {
+                Some((name, value)) => (name.trim(), Some(value.trim().trim_matches('"'))),
+                None => (param, None),
+            };
+            match (name.to_ascii_lowercase().as_str(), value) {
+                ("server_no_context_takeover", None) => params.server_no_context_takeover = true,
+                ("client_no_context_takeover", None) => params.client_no_context_takeover = true,
+                ("server_max_window_bits", Some(bits)) => {
+                    // zlib cannot compress with an 8-bit window, so decline.
+                    let bits = window_bits(bits).filter(|b| *b >= 9)?;
+                    params.server_max_window_bits = bits.min(self.server_max_window_bits);
+                    params.announce_server_window = true;
+                }
+                ("client_max_window_bits", bits) => {
+                    client_window_supported = true;
+                    if let Some(bits) = bits {
+                        params.client_max_window_bits = window_bits(bits)?;
+                    }
+                }
+                // Unknown or malformed parameters make the offer unacceptable.
+                _ => return None,
+            }
+        }
+
+        if self.client_max_window_bits < params.client_max_window_bits {
+            // Limiting the client needs its support for the parameter.
+            if !client_window_supported {
+                return None;
+            }
+            params.client_max_window_bits = self.client_max_window_bits;
+        }
+        params.announce_client_window =
+            client_window_supported && params.client_max_window_bits < 15;
+        Some(params)
+    }
+
+    /// Compressor and decompressor for a connection using `params`.
+    pub fn codec(&self, params: &DeflateParams) -> (Deflater, Inflater) {
+        (
+            Deflater::new(
+                params.server_max_window_bits,
+                params.server_no_context_takeover,
+                self.level,
+                self.threshold,
+            ),
+            Inflater::new(params.client_no_context_takeover),
+        )
+    }
+
+    /// Compressor and decompressor for the client's end of a connection
+    /// using `params`, as the test client runs it; network clients bring
+    /// their own.
+    pub fn client_codec(&self, params: &DeflateParams) -> (Deflater, Inflater) {
+        (
+            Deflater::new(
+                params.client_max_window_bits,
+                params.client_no_context_takeover,
+                self.level,
+                self.threshold,
+            ),
+            Inflater::new(params.server_no_context_takeover),
+        )
+    }
+}
+
+fn window_bits(value: &str) -> Option<u8> {
+    value.parse::<u8>().ok().filter(|b| (8..=15).contains(b))
+}
+
+/// Parameters agreed with a client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeflateParams {
+    pub server_max_window_bits: u8,
+    pub client_max_window_bits: u8,
+    pub server_no_context_takeover: bool,
+    pub client_no_context_takeover: bool,
+    announce_server_window: bool,
+    announce_client_window: bool,
+}
+
+impl DeflateParams {
+    /// Value for the handshake response's `Sec-WebSocket-Extensions` header.
+    pub fn response_header(&self) -> String {
+        let mut header = EXTENSION.to_string();
+        if self.server_no_context_takeover {
+            header.push_str("; server_no_context_takeover");
+        }
+        if self.client_no_context_takeover {
+            header.push_str("; client_no_context_takeover");
+        }
+        if self.announce_server_window {
+            header.push_str(&format!(
+                "; server_max_window_bits={}",
+                self.server_max_window_bits
+            ));
+        }
+        if self.announce_client_window {
+            header.push_str(&format!(
+                "; client_max_window_bits={}",
+                self.client_max_window_bits
+            ));
+        }
+        header
+    }
+}
+
+// ============================================================================
+// Codec
+// ============================================================================
+
+/// Compresses outgoing message payloads.
+pub struct Deflater {
+    compress: Compress,
+    no_context_takeover: bool,
+    threshold: usize,
+}
+
+impl Deflater {
+    fn new(window_bits: u8, no_context_takeover: bool, level: u32, threshold: usize) -> Self {
+        Self {
+            // zlib has no 8-bit window; a smaller window than agreed is
+            // always decodable.
+            compress: Compress::new_with_window_bits(
+                Compression::new(level.min(9)),
+                false,
+                window_bits.clamp(9, 15),
+            ),
+            no_context_takeover,
+            threshold,
+        }
+    }
+
+    /// Compress a message payload, or `None` if it is below the threshold
+    /// and should be sent as is (with RSV1 unset).
+    pub fn compress(&mut self, payload: &[u8]) -> Result<Option<Vec<u8>>, DeflateError> {
+        if payload.len() < self.threshold {
+            return Ok(None);
+        }
+        let start = self.compress.total_in();
+        let mut out = Vec::with_capacity(payload.len() / 2 + 64);
+        loop {
+            if out.len() == out.capacity() {
+                out.reserve(out.capacity().max(64));
+            }
+            let consumed = (self.compress.total_in() - start) as usize;
+            self.compress
+                .compress_vec(&payload[consumed..], &mut out, FlushCompress::Sync)
+                .map_err(|e| DeflateError::Compress(e.to_string()))?;
+            let consumed = (self.compress.total_in() - start) as usize;
+            // Spare output space after consuming everything means the flush
+            // completed.
+            if consumed == payload.len() && out.len() < out.capacity() {
+                break;
+            }
+        }
+        if out.ends_with(&TAIL) {
+            out.truncate(out.len() - TAIL.len());
+        }
+        if self.no_context_takeover {
+            self.compress.reset();
+        }
+        Ok(Some(out))
+    }
+}
+
+/// Decompresses incoming message payloads.
+pub struct Inflater {
+    decompress: Decompress,
+    no_context_takeover: bool,
+}
+
+impl Inflater {
+    fn new(no_context_takeover: bool) -> Self {
+        Self {
+            // A full window decodes anything the other end may send.
+            decompress: Decompress::new(false),
+            no_context_takeover,
+        }
+    }
+
+    /// Decompress a payload received with RSV1 set, failing if the result
+    /// would exceed `max_size` bytes.
+    pub fn decompress(
+        &mut self,
+        payload: &[u8],
+        max_size: Option<usize>,
+    ) -> Result<Vec<u8>, DeflateError> {
+        let mut input = Vec::with_capacity(payload.len() + TAIL.len());
+        input.extend_from_slice(payload);
+        input.extend_from_slice(&TAIL);
+
+        let start = self.decompress.total_in();
+        let mut out = Vec::with_capacity(payload.len() * 3 + 64);
+        loop {
+            if out.len() == out.capacity() {
+                out.reserve(out.capacity());
+            }
+            let consumed = (self.decompress.total_in() - start) as usize;
+            let produced = out.len();
+            let status = self
+                .decompress
+                .decompress_vec(&input[consumed..], &mut out, FlushDecompress::Sync)
+                .map_err(|e| DeflateError::Decompress(e.to_string()))?;
+            if max_size.is_some_and(|max| out.len() > max) {
+                return Err(DeflateError::TooLarge);
+            }
+            let now_consumed = (self.decompress.total_in() - start) as usize;
+            if now_consumed == input.len() && out.len() < out.capacity() {
+                break;
+            }
+            if status == Status::BufError && now_consumed == consumed && out.len() == produced {
+                return Err(DeflateError::Decompress("truncated stream".to_string()));
+            }
+        }
+        if self.no_context_takeover {
+            self.decompress.reset(false);
+        }
+        Ok(out)
+    }
+}
+
+/// permessage-deflate errors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeflateError {
+    /// The compressor failed.
+    Compress(String),
+    /// The payload is not valid deflate data.
+    Decompress(String),
+    /// The decompressed message exceeds the size limit.
+    TooLarge,
+}
+
+impl std::fmt::Display for DeflateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeflateError::Compress(msg) => write!(f, "Compression failed: {msg}"),
+            DeflateError::Decompress(msg) => write!(f, "Invalid compressed message: {msg}"),
+            DeflateError::TooLarge => write!(f, "Decompressed message too large"),
+        }
+    }
+}
+
+impl std::error::Error for DeflateError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chatty_json(i: usize) -> Vec<u8> {
+        format!(
+            r#"{{"type":"presence","user":{{"id":{i},"name":"user-{i}","status":"online"}},"room":"general"}}"#
+        )
+        .repeat(4)
+        .into_bytes()
+    }
+
+    // ---------- Negotiation Tests ----------
+
+    #[test]
+    fn test_negotiate_defaults() {
+        let config = DeflateConfig::default();
+        let params = config
+            .negotiate("permessage-deflate; client_max_window_bits")
+            .unwrap();
+        assert_eq!(params.server_max_window_bits, 15);
+        assert_eq!(params.response_header(), "permessage-deflate");
+        assert!(config.negotiate("x-webkit-deflate-frame").is_none());
+    }
+
+    #[test]
+    fn test_negotiate_window_bits() {
+        let config = DeflateConfig::default().with_window_bits(12, 10);
+        let params = config
+            .negotiate("permessage-deflate; server_max_window_bits=10; client_max_window_bits")
+            .unwrap();
+        assert_eq!(params.server_max_window_bits, 10);
+        assert_eq!(params.client_max_window_bits, 10);
+        assert_eq!(
+            params.response_header(),
+            "permessage-deflate; server_max_window_bits=10; client_max_window_bits=10"
+        );
+
+        // The client cannot be limited without client_max_window_bits, so
+        // the first offer is declined and the fallback accepted.
+        let params = config
+            .negotiate(
+                "permessage-deflate; server_no_context_takeover, \
+                 permessage-deflate; client_max_window_bits=9",
+            )
+            .unwrap();
+        assert_eq!(params.client_max_window_bits, 9);
+        assert_eq!(params.server_max_window_bits, 12);
+    }
+
+    #[test]
+    fn test_negotiate_declines_bad_offers() {
+        let config = DeflateConfig::default();
+        assert!(config
+            .negotiate("permessage-deflate; server_max_window_bits=8")
+            .is_none());
+        assert!(config
+            .negotiate("permessage-deflate; server_max_window_bits=16")
+            .is_none());
+        assert!(config.negotiate("permessage-deflate; unknown=1").is_none());
+        let params = config
+            .negotiate("permessage-deflate; server_no_context_takeover")
+            .unwrap();
+        assert_eq!(
+            params.response_header(),
+            "permessage-deflate; server_no_context_takeover"
+        );
+    }
+
+    // ---------- Codec Tests ----------
+
+    #[test]
+    fn test_round_trip_with_context_takeover() {
+        let config = DeflateConfig::default().with_threshold(16);
+        let params = config.negotiate("permessage-deflate").unwrap();
+        let (mut deflater, _) = config.codec(&params);
+        let (_, mut inflater) = config.codec(&params);
+
+        let mut sizes = Vec::new();
+        for i in 0..3 {
+            let message = chatty_json(i);
+            let compressed = deflater.compress(&message).unwrap().unwrap();
+            assert!(!compressed.ends_with(&TAIL));
+            sizes.push(compressed.len());
+            assert_eq!(inflater.decompress(&compressed, None).unwrap(), message);
+        }
+        // Later messages reuse the shared window and shrink further.
+        assert!(sizes[1] < sizes[0]);
+        assert!(sizes[0] < chatty_json(0).len() / 2);
+    }
+
+    #[test]
+    fn test_threshold_and_no_context_takeover() {
+        let config = DeflateConfig {
+            server_no_context_takeover: true,
+            ..DeflateConfig::default().with_window_bits(9, 15)
+        };
+        let params = config.negotiate("permessage-deflate").unwrap();
+        let (mut deflater, _) = config.codec(&params);
+        assert!(deflater.compress(b"tiny").unwrap().is_none());
+
+        let first = deflater.compress(&chatty_json(1)).unwrap().unwrap();
+        let second = deflater.compress(&chatty_json(1)).unwrap().unwrap();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_decompress_limits() {
+        let config = DeflateConfig::default().with_threshold(0);
+        let params = config.negotiate("permessage-deflate").unwrap();
+        let (mut deflater, mut inflater) = config.codec(&params);
+
+        let compressed = deflater.compress(&vec![b'a'; 10_000]).unwrap().unwrap();
+        assert_eq!(
+            inflater.decompress(&compressed, Some(1_000)),
+            Err(DeflateError::TooLarge)
+        );
+        let (_, mut inflater) = config.codec(&params);
+        assert!(inflater.decompress(b"\xff\xff\xff", None).is_err());
+    }
+}
@@ -0,0 +1,1533 @@
+//! WebSocket support for Cello.
+//!
+//! Provides the connection registry, messages and handler-side sockets.
+//! Connections are opened by the server's accept loop, which bridges each
+//! upgraded connection to a [`WebSocketPeer`], and by the test client,
+//! which plays the peer itself.
+//!
+//! Connections are kept honest by heartbeats configured on the registry:
+//! automatic pings with a pong deadline, idle timeouts and a maximum message
+//! size. A background reaper closes connections that break these rules, so
+//! dead sockets do not accumulate.
+//!
+//! Message compression (permessage-deflate) is negotiated per connection
+//! when enabled on the registry; see [`deflate`]. Once agreed, data messages
+//! over the threshold travel compressed between the handler and its peer,
+//! and the size limit applies to the inflated message.
+//!
+//! Connections opened with [`WebSocketRegistry::accept`] carry the upgrade
+//! request after the app's before-middleware and guards admitted it, and
+//! hand its principal to the handler; when the credentials carry an expiry,
+//! the connection is closed once they lapse unless the handler refreshes
+//! them.
+
+pub mod deflate;
+
+use deflate::{DeflateConfig, DeflateError, DeflateParams, Deflater, Inflater};
+use parking_lot::{Condvar, Mutex, RwLock};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::request::Request;
+use crate::response::Response;
+
+/// WebSocket message types for Python.
+#[pyclass]
+#[derive(Clone)]
+pub struct WebSocketMessage {
+    /// Message type: "text", "binary", "ping", "pong", "close"
+    #[pyo3(get)]
+    pub msg_type: String,
+
+    /// Text data (for text messages), read in Python through the `text`
+    /// class attribute.
+    pub text: Option<String>,
+
+    /// Binary data (for binary messages)
+    #[pyo3(get)]
+    pub data: Option<Vec<u8>>,
+
+    /// Whether the payload, held in `data`, is permessage-deflate
+    /// compressed; only set while the message is in transit.
+    pub compressed: bool,
+}
+
+#[pymethods]
+impl WebSocketMessage {
+    /// `WebSocketMessage.text(...)` builds a text message, while `msg.text`
+    /// reads a message's text.
+    #[classattr]
+    fn text() -> TextAttribute {
+        TextAttribute
+    }
+
+    /// Create a text message.
+    #[staticmethod]
+    pub fn from_text(content: &str) -> Self {
+        WebSocketMessage {
+            msg_type: "text".to_string(),
+            text: Some(content.to_string()),
+            data: None,
+            compressed: false,
+        }
+    }
+
+    /// Create a binary message.
+    #[staticmethod]
+    #[pyo3(name = "binary")]
+    pub fn from_binary(content: Vec<u8>) -> Self {
+        WebSocketMessage {
+            msg_type: "binary".to_string(),
+            text: None,
+            data: Some(content),
+            compressed: false,
+        }
+    }
+
+    /// Create a ping message.
+    #[staticmethod]
+    pub fn ping() -> Self {
+        WebSocketMessage {
+            msg_type: "ping".to_string(),
+            text: None,
+            data: None,
+            compressed: false,
+        }
+    }
+
+    /// Create a pong message.
+    #[staticmethod]
+    pub fn pong() -> Self {
+        WebSocketMessage {
+            msg_type: "pong".to_string(),
+            text: None,
+            data: None,
+            compressed: false,
+        }
+    }
+
+    /// Create a close message.
+    #[staticmethod]
+    pub fn close() -> Self {
+        WebSocketMessage {
+            msg_type: "close".to_string(),
+            text: None,
+            data: None,
+            compressed: false,
+        }
+    }
+
+    /// Check if this is a text message.
+    pub fn is_text(&self) -> bool {
+        self.msg_type == "text"
+    }
+
+    /// Check if this is a binary message.
+    pub fn is_binary(&self) -> bool {
+        self.msg_type == "binary"
+    }
+
+    /// Check if this is a close message.
+    pub fn is_close(&self) -> bool {
+        self.msg_type == "close"
+    }
+
+    /// Check if this is a ping message.
+    pub fn is_ping(&self) -> bool {
+        self.msg_type == "ping"
+    }
+
+    /// Check if this is a pong message.
+    pub fn is_pong(&self) -> bool {
+        self.msg_type == "pong"
+    }
+}
+
+impl WebSocketMessage {
+    /// Payload size in bytes.
+    pub fn len(&self) -> usize {
+        self.text.as_ref().map_or(0, |t| t.len()) + self.data.as_ref().map_or(0, |d| d.len())
+    }
+
+    /// Whether the message has no payload.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn payload(&self) -> &[u8] {
+        match &self.text {
+            Some(text) => text.as_bytes(),
+            None => self.data.as_deref().unwrap_or_default(),
+        }
+    }
+
+    /// A data message whose `payload` arrived permessage-deflate
+    /// compressed, for the receiving socket to inflate.
+    pub fn from_compressed(text: bool, payload: Vec<u8>) -> Self {
+        WebSocketMessage {
+            msg_type: if text { "text" } else { "binary" }.to_string(),
+            text: None,
+            data: Some(payload),
+            compressed: true,
+        }
+    }
+
+    /// A data message of `msg_type` carrying `payload`.
+    fn with_payload(msg_type: &str, payload: Vec<u8>) -> Result<Self, DeflateError> {
+        if msg_type != "text" {
+            return Ok(Self::from_binary(payload));
+        }
+        let text = String::from_utf8(payload)
+            .map_err(|_| DeflateError::Decompress("text message is not UTF-8".to_string()))?;
+        Ok(Self::from_text(&text))
+    }
+}
+
+/// Descriptor behind `WebSocketMessage.text`: the text constructor when read
+/// from the class, the message text when read from an instance.
+#[pyclass]
+pub struct TextAttribute;
+
+#[pymethods]
+impl TextAttribute {
+    fn __get__(&self, py: Python<'_>, instance: &PyAny, owner: &PyAny) -> PyResult<PyObject> {
+        if instance.is_none() {
+            return Ok(owner.getattr("from_text")?.into_py(py));
+        }
+        let message = instance.extract::<PyRef<'_, WebSocketMessage>>()?;
+        Ok(message.text.clone().into_py(py))
+    }
+}
+
+/// Messages travelling in one direction over an in-process WebSocket.
+///
+/// A close message, or `close()`, ends the stream: `pop` drains the
+/// remaining messages and then returns `None`.
+#[derive(Default)]
+pub struct MessageQueue {
+    state: Mutex<(VecDeque<WebSocketMessage>, bool)>,
+    ready: Condvar,
+}
+
+impl MessageQueue {
+    /// Create an open, empty queue.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue a message; a close message also closes the queue.
+    pub fn push(&self, message: WebSocketMessage) {
+        let mut state = self.state.lock();
+        if state.1 {
+            return;
+        }
+        if message.is_close() {
+            state.1 = true;
+        } else {
+            state.0.push_back(message);
+        }
+        self.ready.notify_all();
+    }
+
+    /// Close the queue without a message.
+    pub fn close(&self) {
+        self.state.lock().1 = true;
+        self.ready.notify_all();
+    }
+
+    /// Whether the queue has been closed.
+    pub fn is_closed(&self) -> bool {
+        self.state.lock().1
+    }
+
+    /// Take the next message, waiting up to `timeout` (forever if `None`).
+    ///
+    /// Returns `None` once the queue is closed and drained, or on timeout.
+    pub fn pop(&self, timeout: Option<Duration>) -> Option<WebSocketMessage> {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        let mut state = self.state.lock();
+        loop {
+            if let Some(message) = state.0.pop_front() {
+                return Some(message);
+            }
+            if state.1 {
+                return None;
+            }
+            match deadline {
+                Some(deadline) => {
+                    if self.ready.wait_until(&mut state, deadline).timed_out() {
+                        return state.0.pop_front();
+                    }
+                }
+                None => self.ready.wait(&mut state),
+            }
+        }
+    }
+}
+
+// ============================================================================
+// Compression
+// ============================================================================
+
+/// One end's permessage-deflate state.
+///
+/// Each compressor is held while its message is queued and each
+/// decompressor while its message is taken, so both ends see messages in
+/// the same order and their shared windows stay in step.
+#[derive(Clone)]
+struct Compression {
+    deflater: Arc<Mutex<Deflater>>,
+    inflater: Arc<Mutex<Inflater>>,
+}
+
+impl Compression {
+    fn new((deflater, inflater): (Deflater, Inflater)) -> Self {
+        Self {
+            deflater: Arc::new(Mutex::new(deflater)),
+            inflater: Arc::new(Mutex::new(inflater)),
+        }
+    }
+
+    /// Compress a data message over the threshold and hand it to `push`.
+    fn send(&self, message: WebSocketMessage, push: impl FnOnce(WebSocketMessage)) {
+        if !message.is_text() && !message.is_binary() {
+            return push(message);
+        }
+        let mut deflater = self.deflater.lock();
+        match deflater.compress(message.payload()) {
+            Ok(Some(payload)) => push(WebSocketMessage {
+                msg_type: message.msg_type,
+                text: None,
+                data: Some(payload),
+                compressed: true,
+            }),
+            // Any message may be sent uncompressed.
+            _ => push(message),
+        }
+    }
+
+    /// Take a message with `pop`, inflating it to at most `max_size` bytes
+    /// if it arrived compressed.
+    fn receive(
+        &self,
+        pop: impl FnOnce() -> Option<WebSocketMessage>,
+        max_size: Option<usize>,
+    ) -> Option<Result<WebSocketMessage, DeflateError>> {
+        let mut inflater = self.inflater.lock();
+        let message = pop()?;
+        if !message.compressed {
+            return Some(Ok(message));
+        }
+        Some(
+            inflater
+                .decompress(message.payload(), max_size)
+                .and_then(|payload| WebSocketMessage::with_payload(&message.msg_type, payload)),
+        )
+    }
+}
+
+// ============================================================================
+// Heartbeats
+// ============================================================================
+
+/// Heartbeat and limit settings applied to every connection.
+#[derive(Debug, Clone)]
+pub struct WebSocketConfig {
+    /// Send a ping after this long without one (disabled if `None`).
+    pub ping_interval: Option<Duration>,
+    /// Close the connection if a ping is not answered within this time.
+    pub pong_timeout: Duration,
+    /// Close the connection after this long without a message from the peer.
+    pub idle_timeout: Option<Duration>,
+    /// Close the connection when the peer sends a larger message.
+    pub max_message_size: Option<usize>,
+    /// Offer permessage-deflate to clients that request it.
+    pub deflate: Option<DeflateConfig>,
+    /// What to do when the principal's credentials expire.
+    pub on_expiry: ExpiryPolicy,
+    /// How long past expiry a connection may stay open awaiting a refresh.
+    pub expiry_grace: Duration,
+}
+
+impl Default for WebSocketConfig {
+    fn default() -> Self {
+        Self {
+            ping_interval: None,
+            pong_timeout: Duration::from_secs(10),
+            idle_timeout: None,
+            max_message_size: None,
+            deflate: None,
+            on_expiry: ExpiryPolicy::Close,
+            expiry_grace: Duration::ZERO,
+        }
+    }
+}
+
+impl WebSocketConfig {
+    /// Ping every `interval`.
+    pub fn with_ping_interval(mut self, interval: Duration) -> Self {
+        self.ping_interval = Some(interval);
+        self
+    }
+
+    /// Wait up to `timeout` for a pong.
+    pub fn with_pong_timeout(mut self, timeout: Duration) -> Self {
+        self.pong_timeout = timeout;
+        self
+    }
+
+    /// Close connections idle for `timeout`.
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Reject messages larger than `bytes`.
+    pub fn with_max_message_size(mut self, bytes: usize) -> Self {
+        self.max_message_size = Some(bytes);
+        self
+    }
+
+    /// Negotiate permessage-deflate with `config`.
+    pub fn with_deflate(mut self, config: DeflateConfig) -> Self {
+        self.deflate = Some(config);
+        self
+    }
+
+    /// Apply `policy` when credentials expire.
+    pub fn with_expiry_policy(mut self, policy: ExpiryPolicy) -> Self {
+        self.on_expiry = policy;
+        self
+    }
+
+    /// Keep expired connections open for `grace` so they can refresh.
+    pub fn with_expiry_grace(mut self, grace: Duration) -> Self {
+        self.expiry_grace = grace;
+        self
+    }
+
+    /// Whether any timer needs the reaper running.
+    fn has_timers(&self) -> bool {
+        self.ping_interval.is_some() || self.idle_timeout.is_some()
+    }
+
+    /// How often the reaper checks connections.
+    fn tick(&self) -> Duration {
+        [
+            self.ping_interval,
+            Some(self.pong_timeout),
+            self.idle_timeout,
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+        .map(|d| d / 4)
+        .unwrap_or(Duration::from_secs(1))
+        .clamp(Duration::from_millis(10), Duration::from_secs(1))
+    }
+}
+
+/// What happens to a connection whose credentials expire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExpiryPolicy {
+    /// Close the connection once the expiry (plus grace) has passed.
+    #[default]
+    Close,
+    /// Leave the connection open; the handler decides.
+    Ignore,
+}
+
+impl ExpiryPolicy {
+    /// Parse `"close"` or `"ignore"`.
+    pub fn parse(policy: &str) -> Option<Self> {
+        match policy.to_ascii_lowercase().as_str() {
+            "close" => Some(ExpiryPolicy::Close),
+            "ignore" => Some(ExpiryPolicy::Ignore),
+            _ => None,
+        }
+    }
+}
+
+/// Why a connection was closed by the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloseReason {
+    /// A ping went unanswered past the pong timeout.
+    PongTimeout,
+    /// No message arrived within the idle timeout.
+    IdleTimeout,
+    /// The peer sent a message over the size limit.
+    MessageTooBig,
+    /// The principal's credentials expired without a refresh.
+    TokenExpired,
+    /// The peer sent a compressed message that could not be inflated.
+    InvalidMessage,
+}
+
+impl CloseReason {
+    /// Reason as shown to Python.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CloseReason::PongTimeout => "pong_timeout",
+            CloseReason::IdleTimeout => "idle_timeout",
+            CloseReason::MessageTooBig => "message_too_big",
+            CloseReason::TokenExpired => "token_expired",
+            CloseReason::InvalidMessage => "invalid_message",
+        }
+    }
+
+    /// Status code sent to the client in the close frame (RFC 6455 §7.4.1).
+    pub fn code(&self) -> u16 {
+        match self {
+            CloseReason::PongTimeout | CloseReason::IdleTimeout => 1001,
+            CloseReason::MessageTooBig => 1009,
+            CloseReason::TokenExpired => 1008,
+            CloseReason::InvalidMessage => 1007,
+        }
+    }
+}
+
+/// Liveness of one connection, shared by both ends and the reaper.
+#[derive(Debug)]
+pub struct Liveness {
+    connected_at: Instant,
+    state: Mutex<LivenessState>,
+}
+
+#[derive(Debug)]
+struct LivenessState {
+    last_activity: Instant,
+    last_ping: Option<Instant>,
+    awaiting_pong: Option<Instant>,
+    last_pong: Option<Instant>,
+    pings_sent: u64,
+    pongs_received: u64,
+    close_reason: Option<CloseReason>,
+    expires_at: Option<Instant>,
+}
+
+impl Liveness {
+    fn new() -> Self {
+        let now = Instant::now();
+        Self {
+            connected_at: now,
+            state: Mutex::new(LivenessState {
+                last_activity: now,
+                last_ping: None,
+                awaiting_pong: None,
+                last_pong: None,
+                pings_sent: 0,
+                pongs_received: 0,
+                close_reason: None,
+                expires_at: None,
+            }),
+        }
+    }
+
+    /// Time since the peer last sent anything.
+    pub fn idle(&self) -> Duration {
+        self.state.lock().last_activity.elapsed()
+    }
+
+    /// Pings sent to the peer.
+    pub fn pings_sent(&self) -> u64 {
+        self.state.lock().pings_sent
+    }
+
+    /// Pongs received from the peer.
+    pub fn pongs_received(&self) -> u64 {
+        self.state.lock().pongs_received
+    }
+
+    /// Why the server closed the connection, if it did.
+    pub fn close_reason(&self) -> Option<CloseReason> {
+        self.state.lock().close_reason
+    }
+
+    /// When the principal's credentials expire, if they do.
+    pub fn expires_at(&self) -> Option<Instant> {
+        self.state.lock().expires_at
+    }
+
+    /// Set or clear the credential expiry.
+    pub fn set_expiry(&self, expires_at: Option<Instant>) {
+        self.state.lock().expires_at = expires_at;
+    }
+
+    /// Record why the server closed the connection, keeping the first reason.
+    fn set_close_reason(&self, reason: CloseReason) {
+        self.state.lock().close_reason.get_or_insert(reason);
+    }
+
+    fn record_activity(&self, pong: bool) {
+        let mut state = self.state.lock();
+        let now = Instant::now();
+        state.last_activity = now;
+        if pong {
+            state.awaiting_pong = None;
+            state.last_pong = Some(now);
+            state.pongs_received += 1;
+        }
+    }
+
+    /// Liveness as a Python dict.
+    pub fn to_dict<'py>(&self, py: Python<'py>, open: bool) -> PyResult<&'py PyDict> {
+        let state = self.state.lock();
+        let dict = PyDict::new(py);
+        dict.set_item("open", open)?;
+        dict.set_item(
+            "connected_seconds",
+            self.connected_at.elapsed().as_secs_f64(),
+        )?;
+        dict.set_item("idle_seconds", state.last_activity.elapsed().as_secs_f64())?;
+        dict.set_item("pings_sent", state.pings_sent)?;
+        dict.set_item("pongs_received", state.pongs_received)?;
+        dict.set_item("awaiting_pong", state.awaiting_pong.is_some())?;
+        dict.set_item(
+            "last_pong_seconds",
+            state.last_pong.map(|t| t.elapsed().as_secs_f64()),
+        )?;
+        dict.set_item("close_reason", state.close_reason.map(|r| r.as_str()))?;
+        dict.set_item(
+            "expires_in_seconds",
+            state
+                .expires_at
+                .map(|t| t.saturating_duration_since(Instant::now()).as_secs_f64()),
+        )?;
+        Ok(dict)
+    }
+}
+
+/// The client end of an in-process WebSocket created by [`WebSocket::pair`].
+#[derive(Clone)]
+pub struct WebSocketPeer {
+    /// Messages from the client to the handler.
+    pub incoming: Arc<MessageQueue>,
+    /// Messages from the handler to the client.
+    pub outgoing: Arc<MessageQueue>,
+    /// Connection liveness.
+    pub liveness: Arc<Liveness>,
+    /// permessage-deflate parameters agreed during the handshake.
+    pub deflate: Option<DeflateParams>,
+    /// The client's compression state, once permessage-deflate is agreed.
+    compression: Option<Compression>,
+    config: WebSocketConfig,
+}
+
+impl WebSocketPeer {
+    /// Send a message from the client to the handler.
+    ///
+    /// Pings are answered and pongs recorded without reaching the handler;
+    /// a message over the size limit closes the connection. Compressed
+    /// messages are checked against the limit once the handler inflates
+    /// them.
+    pub fn send(&self, message: WebSocketMessage) {
+        if self.is_closed() {
+            return;
+        }
+        self.liveness.record_activity(message.is_pong());
+        if message.is_pong() {
+            return;
+        }
+        if message.is_ping() {
+            self.outgoing.push(WebSocketMessage::pong());
+            return;
+        }
+        let push = |message: WebSocketMessage| {
+            if !message.compressed
+                && self
+                    .config
+                    .max_message_size
+                    .is_some_and(|max| message.len() > max)
+            {
+                self.close_with(CloseReason::MessageTooBig);
+            } else {
+                self.incoming.push(message);
+            }
+        };
+        match &self.compression {
+            Some(compression) => compression.send(message, push),
+            None => push(message),
+        }
+    }
+
+    /// Receive the next message from the handler, answering its pings.
+    pub fn recv(&self, timeout: Option<Duration>) -> Option<WebSocketMessage> {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        loop {
+            let remaining = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+            let pop = || self.outgoing.pop(remaining);
+            let message = match &self.compression {
+                Some(compression) => match compression.receive(pop, None)? {
+                    Ok(message) => message,
+                    Err(_) => {
+                        self.close_with(CloseReason::InvalidMessage);
+                        return None;
+                    }
+                },
+                None => pop()?,
+            };
+            if message.is_ping() {
+                self.send(WebSocketMessage::pong());
+                continue;
+            }
+            return Some(message);
+        }
+    }
+
+    /// This peer as the end of a network connection, whose client
+    /// compresses and inflates its messages itself: compressed messages
+    /// pass through it unchanged in both directions.
+    pub fn into_remote(mut self) -> Self {
+        self.compression = None;
+        self
+    }
+
+    /// Largest message accepted from the client, once inflated.
+    pub fn max_message_size(&self) -> Option<usize> {
+        self.config.max_message_size
+    }
+
+    /// Record a ping from the client that the transport already answered.
+    pub fn pinged(&self) {
+        self.liveness.record_activity(false);
+    }
+
+    /// Whether either side has closed the connection.
+    pub fn is_closed(&self) -> bool {
+        self.incoming.is_closed() || self.outgoing.is_closed()
+    }
+
+    /// Close both directions, telling the client why.
+    pub fn close_with(&self, reason: CloseReason) {
+        self.liveness.set_close_reason(reason);
+        self.outgoing.push(WebSocketMessage::close());
+        self.incoming.close();
+    }
+
+    /// Send a due ping and enforce the deadlines. Returns false once the
+    /// connection is closed.
+    pub fn heartbeat(&self, now: Instant) -> bool {
+        if self.is_closed() {
+            return false;
+        }
+        let mut state = self.liveness.state.lock();
+        let reason = if state
+            .awaiting_pong
+            .is_some_and(|sent| now.saturating_duration_since(sent) > self.config.pong_timeout)
+        {
+            Some(CloseReason::PongTimeout)
+        } else if self
+            .config
+            .idle_timeout
+            .is_some_and(|idle| now.saturating_duration_since(state.last_activity) > idle)
+        {
+            Some(CloseReason::IdleTimeout)
+        } else if self.config.on_expiry == ExpiryPolicy::Close
+            && state
+                .expires_at
+                .is_some_and(|at| now.saturating_duration_since(at) > self.config.expiry_grace)
+        {
+            Some(CloseReason::TokenExpired)
+        } else {
+            None
+        };
+        if let Some(reason) = reason {
+            drop(state);
+            self.close_with(reason);
+            return false;
+        }
+
+        if let Some(interval) = self.config.ping_interval {
+            let since = state.last_ping.unwrap_or(self.liveness.connected_at);
+            if state.awaiting_pong.is_none() && now.saturating_duration_since(since) >= interval {
+                state.last_ping = Some(now);
+                state.awaiting_pong = Some(now);
+                state.pings_sent += 1;
+                self.outgoing.push(WebSocketMessage::ping());
+            }
+        }
+        true
+    }
+
+    /// Whether the reaper must watch this connection.
+    fn needs_reaper(&self) -> bool {
+        self.config.has_timers()
+            || (self.config.on_expiry == ExpiryPolicy::Close
+                && self.liveness.expires_at().is_some())
+    }
+}
+
+/// Open connections, swept by a reaper thread while any timers are set.
+#[derive(Default)]
+pub struct ConnectionTracker {
+    connections: Mutex<Vec<WebSocketPeer>>,
+    reaping: AtomicBool,
+}
+
+impl ConnectionTracker {
+    /// Track `peer`, starting the reaper if it needs one.
+    pub fn track(self: &Arc<Self>, peer: WebSocketPeer) {
+        let mut connections = self.connections.lock();
+        connections.retain(|c| !c.is_closed());
+        let tick = peer.needs_reaper().then(|| peer.config.tick());
+        connections.push(peer);
+        drop(connections);
+        if let Some(tick) = tick {
+            self.wake(tick);
+        }
+    }
+
+    /// Start the reaper if it is not running, e.g. after a connection gains
+    /// an expiry.
+    pub fn wake(self: &Arc<Self>, tick: Duration) {
+        if !self.reaping.swap(true, Ordering::AcqRel) {
+            let tracker = Arc::downgrade(self);
+            std::thread::spawn(move || Self::reap(tracker, tick));
+        }
+    }
+
+    /// Open connections.
+    pub fn open(&self) -> Vec<WebSocketPeer> {
+        let mut connections = self.connections.lock();
+        connections.retain(|c| !c.is_closed());
+        connections.clone()
+    }
+
+    /// Run heartbeats on every connection, dropping closed ones. Returns
+    /// whether any connection still needs the reaper.
+    pub fn sweep(&self, now: Instant) -> bool {
+        let mut connections = self.connections.lock();
+        connections.retain(|c| c.heartbeat(now));
+        let needed = connections.iter().any(WebSocketPeer::needs_reaper);
+        if !needed {
+            self.reaping.store(false, Ordering::Release);
+        }
+        needed
+    }
+
+    fn reap(tracker: Weak<Self>, tick: Duration) {
+        loop {
+            std::thread::sleep(tick);
+            let Some(tracker) = tracker.upgrade() else {
+                return;
+            };
+            if !tracker.sweep(Instant::now()) {
+                return;
+            }
+        }
+    }
+}
+
+// ============================================================================
+// Authentication
+// ============================================================================
+
+/// Context keys auth middleware stores the principal under, in the order
+/// they are tried.
+pub const PRINCIPAL_KEYS: [&str; 3] = ["user", "jwt_claims", "api_key_client"];
+
+/// Runs upgrade admission again with a fresh token, presented where the
+/// app's auth middleware reads its credential, returning the admitted
+/// request or the rejection.
+pub type Authenticator = Arc<dyn Fn(&str) -> Result<Request, Box<Response>> + Send + Sync>;
+
+/// The principal auth middleware stored on `request`, if any.
+pub fn principal(request: &Request) -> Option<&serde_json::Value> {
+    PRINCIPAL_KEYS
+        .iter()
+        .find_map(|key| request.context.get(*key))
+}
+
+/// When a principal's `exp` claim, in seconds since the epoch, falls due.
+pub fn principal_expiry(principal: &serde_json::Value) -> Option<Instant> {
+    let exp = principal.get("exp")?.as_f64()?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?;
+    let remaining = Duration::try_from_secs_f64(exp - now.as_secs_f64()).unwrap_or_default();
+    Some(Instant::now() + remaining)
+}
+
+/// WebSocket connection handler for Python.
+///
+/// A socket created with `WebSocket()` only records what the handler sends;
+/// one created by [`WebSocket::pair`] also exchanges messages with a peer,
+/// as used by the server and the test client.
+#[pyclass]
+pub struct WebSocket {
+    /// Connection state
+    #[pyo3(get)]
+    pub connected: bool,
+
+    /// Internal message queue (simulated)
+    messages: Arc<RwLock<Vec<WebSocketMessage>>>,
+
+    /// Messages received from the peer.
+    incoming: Arc<MessageQueue>,
+
+    /// Messages delivered to the peer, when connected to one.
+    outgoing: Option<Arc<MessageQueue>>,
+
+    /// Connection liveness.
+    liveness: Arc<Liveness>,
+
+    /// Negotiated `Sec-WebSocket-Extensions` value, if any.
+    #[pyo3(get)]
+    pub extensions: Option<String>,
+
+    /// The upgrade request as admitted by middleware and guards.
+    upgrade: RwLock<Option<Request>>,
+
+    /// Re-admits the upgrade request with a refreshed token.
+    authenticator: Option<Authenticator>,
+
+    /// The tracker whose reaper enforces this connection's expiry.
+    reaper: Option<(Weak<ConnectionTracker>, Duration)>,
+
+    /// The handler's compression state, once permessage-deflate is agreed.
+    compression: Option<Compression>,
+
+    /// Largest message accepted from the peer, once inflated.
+    max_message_size: Option<usize>,
+}
+
+#[pymethods]
+impl WebSocket {
+    /// Create a new WebSocket (for testing/mocking).
+    #[new]
+    pub fn new() -> Self {
+        let incoming = MessageQueue::new();
+        incoming.close();
+        WebSocket {
+            connected: true,
+            messages: Arc::new(RwLock::new(Vec::new())),
+            incoming: Arc::new(incoming),
+            outgoing: None,
+            liveness: Arc::new(Liveness::new()),
+            extensions: None,
+            upgrade: RwLock::new(None),
+            authenticator: None,
+            reaper: None,
+            compression: None,
+            max_message_size: None,
+        }
+    }
+
+    /// Send a text message (queues for sending).
+    pub fn send_text(&self, text: &str) -> PyResult<()> {
+        self.deliver(WebSocketMessage::from_text(text));
+        Ok(())
+    }
+
+    /// Send a binary message (queues for sending).
+    pub fn send_binary(&self, data: Vec<u8>) -> PyResult<()> {
+        self.deliver(WebSocketMessage::from_binary(data));
+        Ok(())
+    }
+
+    /// Send a message (queues for sending).
+    pub fn send(&self, message: WebSocketMessage) -> PyResult<()> {
+        self.deliver(message);
+        Ok(())
+    }
+
+    /// Receive the next message from the peer.
+    ///
+    /// Blocks until a message arrives, releasing the GIL. Returns `None`
+    /// once the peer has closed the connection or after `timeout` seconds.
+    #[pyo3(signature = (timeout=None))]
+    pub fn recv(&self, py: Python<'_>, timeout: Option<f64>) -> Option<WebSocketMessage> {
+        let timeout = timeout.map(|t| Duration::try_from_secs_f64(t).unwrap_or_default());
+        py.allow_threads(|| self.receive(timeout))
+    }
+
+    /// Get queued messages (for testing).
+    pub fn get_queued_messages(&self) -> Vec<WebSocketMessage> {
+        self.messages.read().clone()
+    }
+
+    /// Close the WebSocket connection.
+    pub fn close(&self) -> PyResult<()> {
+        self.deliver(WebSocketMessage::close());
+        Ok(())
+    }
+
+    /// Liveness of the connection: `open`, `connected_seconds`,
+    /// `idle_seconds`, `pings_sent`, `pongs_received`, `awaiting_pong`,
+    /// `last_pong_seconds`, `close_reason` and `expires_in_seconds`.
+    pub fn liveness<'py>(&self, py: Python<'py>) -> PyResult<&'py PyDict> {
+        let open =
+            !self.incoming.is_closed() && !self.outgoing.as_ref().is_some_and(|o| o.is_closed());
+        self.liveness.to_dict(py, open)
+    }
+
+    /// The upgrade request, after middleware and guards ran on it.
+    #[getter(request)]
+    fn py_request(&self) -> Option<Request> {
+        self.upgrade.read().clone()
+    }
+
+    /// The authenticated principal: the `user`, `jwt_claims` or
+    /// `api_key_client` value auth middleware stored on the upgrade request.
+    #[getter(principal)]
+    fn py_principal(&self, py: Python<'_>) -> PyResult<PyObject> {
+        match self.upgrade.read().as_ref().and_then(principal) {
+            Some(principal) => crate::json::json_to_python(py, principal),
+            None => Ok(py.None()),
+        }
+    }
+
+    /// Seconds until the principal's credentials expire, if they do.
+    #[getter]
+    fn expires_in(&self) -> Option<f64> {
+        self.liveness
+            .expires_at()
+            .map(|t| t.saturating_duration_since(Instant::now()).as_secs_f64())
+    }
+
+    /// Refresh the connection's credentials.
+    ///
+    /// With a `token`, the upgrade request's middleware and guards run again
+    /// with it in place of the original credential, read from where the
+    /// auth middleware looks (an `Authorization: Bearer` header if none is
+    /// configured); returns False, keeping the current principal, if they
+    /// reject it. The expiry becomes `expires_in` seconds
+    /// from now, or else the principal's `exp` claim.
+    #[pyo3(signature = (token=None, expires_in=None))]
+    fn refresh(
+        &self,
+        py: Python<'_>,
+        token: Option<String>,
+        expires_in: Option<f64>,
+    ) -> PyResult<bool> {
+        let expires_in = expires_in
+            .map(|seconds| crate::seconds_arg("expires_in", seconds))
+            .transpose()?;
+        let admitted = match token {
+            Some(token) => {
+                let authenticate = self.authenticator.clone().ok_or_else(|| {
+                    pyo3::exceptions::PyRuntimeError::new_err(
+                        "WebSocket was not opened through an upgrade request",
+                    )
+                })?;
+                match py.allow_threads(move || authenticate(token.as_str())) {
+                    Ok(request) => Some(request),
+                    Err(_) => return Ok(false),
+                }
+            }
+            None => None,
+        };
+        self.refresh_principal(admitted, expires_in);
+        Ok(true)
+    }
+}
+
+impl WebSocket {
+    /// Create a socket for a handler connected in-process to a peer.
+    pub fn pair() -> (WebSocket, WebSocketPeer) {
+        Self::pair_with(WebSocketConfig::default())
+    }
+
+    /// Create a connected socket whose peer enforces `config`.
+    pub fn pair_with(config: WebSocketConfig) -> (WebSocket, WebSocketPeer) {
+        Self::pair_negotiated(config, None)
+    }
+
+    /// Create a connected socket, negotiating extensions from the client's
+    /// `Sec-WebSocket-Extensions` offer.
+    pub fn pair_negotiated(
+        config: WebSocketConfig,
+        offer: Option<&str>,
+    ) -> (WebSocket, WebSocketPeer) {
+        let deflate = config
+            .deflate
+            .as_ref()
+            .zip(offer)
+            .and_then(|(deflate, offer)| deflate.negotiate(offer));
+        let codecs = config.deflate.as_ref().zip(deflate.as_ref()).map(|(c, p)| {
+            (
+                Compression::new(c.codec(p)),
+                Compression::new(c.client_codec(p)),
+            )
+        });
+        let (compression, client_compression) = codecs.unzip();
+        let max_message_size = config.max_message_size;
+        let peer = WebSocketPeer {
+            incoming: Arc::new(MessageQueue::new()),
+            outgoing: Arc::new(MessageQueue::new()),
+            liveness: Arc::new(Liveness::new()),
+            deflate,
+            compression: client_compression,
+            config,
+        };
+        let socket = WebSocket {
+            connected: true,
+            messages: Arc::new(RwLock::new(Vec::new())),
+            incoming: peer.incoming.clone(),
+            outgoing: Some(peer.outgoing.clone()),
+            liveness: peer.liveness.clone(),
+            extensions: peer.deflate.as_ref().map(DeflateParams::response_header),
+            upgrade: RwLock::new(None),
+            authenticator: None,
+            reaper: None,
+            compression,
+            max_message_size,
+        };
+        (socket, peer)
+    }
+
+    /// Replace the admitted upgrade request, if given, and reset the expiry
+    /// to `expires_in` from now or the principal's `exp` claim.
+    pub fn refresh_principal(&self, request: Option<Request>, expires_in: Option<Duration>) {
+        let mut upgrade = self.upgrade.write();
+        if let Some(request) = request {
+            *upgrade = Some(request);
+        }
+        let expires_at = match expires_in {
+            Some(expires_in) => Some(Instant::now() + expires_in),
+            None => upgrade
+                .as_ref()
+                .and_then(principal)
+                .and_then(principal_expiry),
+        };
+        drop(upgrade);
+
+        self.liveness.set_expiry(expires_at);
+        if expires_at.is_some() {
+            if let Some((tracker, tick)) = &self.reaper {
+                if let Some(tracker) = tracker.upgrade() {
+                    tracker.wake(*tick);
+                }
+            }
+        }
+    }
+
+    fn deliver(&self, message: WebSocketMessage) {
+        if let Some(outgoing) = &self.outgoing {
+            match &self.compression {
+                Some(compression) => compression.send(message.clone(), |m| outgoing.push(m)),
+                None => outgoing.push(message.clone()),
+            }
+        }
+        self.messages.write().push(message);
+    }
+
+    /// Take the next message from the peer, inflating it if compressed.
+    ///
+    /// A message that cannot be inflated within the size limit closes the
+    /// connection.
+    fn receive(&self, timeout: Option<Duration>) -> Option<WebSocketMessage> {
+        let pop = || self.incoming.pop(timeout);
+        let Some(compression) = &self.compression else {
+            return pop();
+        };
+        match compression.receive(pop, self.max_message_size)? {
+            Ok(message) => Some(message),
+            Err(err) => {
+                self.liveness.set_close_reason(match err {
+                    DeflateError::TooLarge => CloseReason::MessageTooBig,
+                    _ => CloseReason::InvalidMessage,
+                });
+                if let Some(outgoing) = &self.outgoing {
+                    outgoing.push(WebSocketMessage::close());
+                }
+                self.incoming.close();
+                None
+            }
+        }
+    }
+}
+
+impl Default for WebSocket {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// WebSocket handler registry.
+pub struct WebSocketRegistry {
+    handlers: Arc<RwLock<HashMap<String, PyObject>>>,
+    config: Arc<RwLock<WebSocketConfig>>,
+    connections: Arc<ConnectionTracker>,
+}
+
+impl WebSocketRegistry {
+    pub fn new() -> Self {
+        WebSocketRegistry {
+            handlers: Arc::new(RwLock::new(HashMap::new())),
+            config: Arc::new(RwLock::new(WebSocketConfig::default())),
+            connections: Arc::new(ConnectionTracker::default()),
+        }
+    }
+
+    /// Set the heartbeat and limit settings for new connections.
+    pub fn set_config(&self, config: WebSocketConfig) {
+        *self.config.write() = config;
+    }
+
+    /// Current heartbeat and limit settings.
+    pub fn config(&self) -> WebSocketConfig {
+        self.config.read().clone()
+    }
+
+    /// Open a tracked connection: the socket for the handler and its peer.
+    ///
+    /// `offer` is the client's `Sec-WebSocket-Extensions` header.
+    pub fn connect(&self, offer: Option<&str>) -> (WebSocket, WebSocketPeer) {
+        let (socket, peer) = WebSocket::pair_negotiated(self.config(), offer);
+        self.connections.track(peer.clone());
+        (socket, peer)
+    }
+
+    /// Open a tracked connection for an upgrade `request` that passed the
+    /// middleware and guards.
+    ///
+    /// The principal's `exp` claim sets the connection's expiry, and
+    /// `authenticator` lets the handler refresh it with a new token.
+    pub fn accept(
+        &self,
+        request: Request,
+        offer: Option<&str>,
+        authenticator: Option<Authenticator>,
+    ) -> (WebSocket, WebSocketPeer) {
+        let config = self.config();
+        let tick = config.tick();
+        let (mut socket, peer) = WebSocket::pair_negotiated(config, offer);
+        socket.authenticator = authenticator;
+        socket.reaper = Some((Arc::downgrade(&self.connections), tick));
+        socket.refresh_principal(Some(request), None);
+        self.connections.track(peer.clone());
+        (socket, peer)
+    }
+
+    /// Connections still open.
+    pub fn open_connections(&self) -> Vec<WebSocketPeer> {
+        self.connections.open()
+    }
+
+    pub fn register(&self, path: &str, handler: PyObject) {
+        self.handlers.write().insert(path.to_string(), handler);
+    }
+
+    pub fn get(&self, path: &str) -> Option<PyObject> {
+        self.handlers.read().get(path).cloned()
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        self.handlers.read().contains_key(path)
+    }
+}
+
+impl Default for WebSocketRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clone for WebSocketRegistry {
+    fn clone(&self) -> Self {
+        WebSocketRegistry {
+            handlers: self.handlers.clone(),
+            config: self.config.clone(),
+            connections: self.connections.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_websocket_message_text() {
+        let msg = WebSocketMessage::from_text("Hello");
+        assert!(msg.is_text());
+        assert!(!msg.is_binary());
+        assert_eq!(msg.text, Some("Hello".to_string()));
+    }
+
+    #[test]
+    fn test_websocket_message_binary() {
+        let msg = WebSocketMessage::from_binary(vec![1, 2, 3]);
+        assert!(msg.is_binary());
+        assert!(!msg.is_text());
+    }
+
+    #[test]
+    fn test_message_queue_close_drains() {
+        let queue = MessageQueue::new();
+        queue.push(WebSocketMessage::from_text("a"));
+        queue.push(WebSocketMessage::close());
+        queue.push(WebSocketMessage::from_text("ignored"));
+
+        assert_eq!(queue.pop(None).unwrap().text, Some("a".to_string()));
+        assert!(queue.pop(None).is_none());
+        assert!(queue.is_closed());
+    }
+
+    #[test]
+    fn test_message_queue_timeout() {
+        let queue = MessageQueue::new();
+        assert!(queue.pop(Some(Duration::from_millis(10))).is_none());
+        assert!(!queue.is_closed());
+    }
+
+    #[test]
+    fn test_websocket_pair() {
+        let (socket, peer) = WebSocket::pair();
+        let handler = std::thread::spawn(move || {
+            while let Some(msg) = socket.incoming.pop(None) {
+                socket.deliver(WebSocketMessage::from_text(&format!(
+                    "echo: {}",
+                    msg.text.unwrap_or_default()
+                )));
+            }
+            socket.deliver(WebSocketMessage::close());
+        });
+
+        peer.incoming.push(WebSocketMessage::from_text("hi"));
+        let reply = peer.outgoing.pop(Some(Duration::from_secs(5))).unwrap();
+        assert_eq!(reply.text, Some("echo: hi".to_string()));
+
+        peer.incoming.push(WebSocketMessage::close());
+        handler.join().unwrap();
+        assert!(peer.outgoing.pop(None).is_none());
+    }
+
+    #[test]
+    fn test_websocket_registry() {
+        let registry = WebSocketRegistry::new();
+        assert!(!registry.contains("/ws"));
+    }
+
+    // ---------- Heartbeat Tests ----------
+
+    #[test]
+    fn test_ping_and_pong_deadline() {
+        let config = WebSocketConfig::default()
+            .with_ping_interval(Duration::from_secs(1))
+            .with_pong_timeout(Duration::from_secs(2));
+        let (socket, peer) = WebSocket::pair_with(config);
+        let start = Instant::now();
+
+        assert!(peer.heartbeat(start));
+        assert!(peer.outgoing.pop(Some(Duration::ZERO)).is_none());
+
+        assert!(peer.heartbeat(start + Duration::from_secs(1)));
+        assert!(peer.outgoing.pop(Some(Duration::ZERO)).unwrap().is_ping());
+        assert_eq!(socket.liveness.pings_sent(), 1);
+
+        // Answered: the connection stays open and pings again later.
+        peer.send(WebSocketMessage::pong());
+        assert_eq!(socket.liveness.pongs_received(), 1);
+        assert!(socket.incoming.pop(Some(Duration::ZERO)).is_none());
+        assert!(peer.heartbeat(start + Duration::from_secs(2)));
+        assert_eq!(socket.liveness.pings_sent(), 2);
+
+        // Unanswered: closed once the pong deadline passes.
+        assert!(peer.heartbeat(start + Duration::from_secs(4)));
+        assert!(!peer.heartbeat(start + Duration::from_secs(5)));
+        assert_eq!(
+            socket.liveness.close_reason(),
+            Some(CloseReason::PongTimeout)
+        );
+        assert!(socket.incoming.pop(None).is_none());
+    }
+
+    #[test]
+    fn test_idle_timeout() {
+        let config = WebSocketConfig::default().with_idle_timeout(Duration::from_secs(5));
+        let (socket, peer) = WebSocket::pair_with(config);
+
+        peer.send(WebSocketMessage::from_text("hi"));
+        assert!(peer.heartbeat(Instant::now() + Duration::from_secs(4)));
+        assert!(!peer.heartbeat(Instant::now() + Duration::from_secs(6)));
+        assert_eq!(
+            socket.liveness.close_reason(),
+            Some(CloseReason::IdleTimeout)
+        );
+        assert!(peer.recv(None).is_none());
+    }
+
+    #[test]
+    fn test_max_message_size_and_client_ping() {
+        let config = WebSocketConfig::default().with_max_message_size(4);
+        let (socket, peer) = WebSocket::pair_with(config);
+
+        peer.send(WebSocketMessage::ping());
+        assert!(peer.outgoing.pop(Some(Duration::ZERO)).unwrap().is_pong());
+
+        peer.send(WebSocketMessage::from_text("tiny"));
+        peer.send(WebSocketMessage::from_text("too large"));
+        assert_eq!(
+            socket.incoming.pop(None).unwrap().text,
+            Some("tiny".to_string())
+        );
+        assert!(socket.incoming.pop(None).is_none());
+        assert_eq!(
+            socket.liveness.close_reason(),
+            Some(CloseReason::MessageTooBig)
+        );
+    }
+
+    #[test]
+    fn test_reaper_closes_dead_connections() {
+        let registry = WebSocketRegistry::new();
+        registry.set_config(
+            WebSocketConfig::default()
+                .with_ping_interval(Duration::from_millis(10))
+                .with_pong_timeout(Duration::from_millis(20)),
+        );
+        let (socket, _peer) = registry.connect(None);
+        assert_eq!(registry.open_connections().len(), 1);
+
+        // The peer never answers, so the reaper closes the connection.
+        assert!(socket.incoming.pop(Some(Duration::from_secs(5))).is_none());
+        assert_eq!(
+            socket.liveness.close_reason(),
+            Some(CloseReason::PongTimeout)
+        );
+        assert!(registry.open_connections().is_empty());
+    }
+
+    #[test]
+    fn test_connect_negotiates_deflate() {
+        let registry = WebSocketRegistry::new();
+        let (socket, peer) = registry.connect(Some("permessage-deflate"));
+        assert!(socket.extensions.is_none());
+        assert!(peer.deflate.is_none());
+
+        registry.set_config(WebSocketConfig::default().with_deflate(DeflateConfig::default()));
+        let (socket, peer) = registry.connect(Some("permessage-deflate; client_max_window_bits"));
+        assert_eq!(socket.extensions.as_deref(), Some("permessage-deflate"));
+        assert_eq!(peer.deflate.unwrap().server_max_window_bits, 15);
+
+        let (socket, _) = registry.connect(None);
+        assert!(socket.extensions.is_none());
+    }
+
+    #[test]
+    fn test_deflate_compresses_messages_in_transit() {
+        let config = WebSocketConfig::default()
+            .with_max_message_size(2_000)
+            .with_deflate(DeflateConfig::default().with_threshold(64));
+        let chatty = r#"{"type":"presence","status":"online"}"#.repeat(20);
+        let (socket, peer) = WebSocket::pair_negotiated(config, Some("permessage-deflate"));
+
+        // Handler to client: compressed on the queue, inflated on receipt.
+        socket.deliver(WebSocketMessage::from_text(&chatty));
+        let wire = peer.outgoing.pop(Some(Duration::ZERO)).unwrap();
+        assert!(wire.compressed && wire.len() < chatty.len() / 4);
+        let compression = peer.compression.as_ref().unwrap();
+        let message = compression.receive(|| Some(wire), None).unwrap().unwrap();
+        assert_eq!(message.text, Some(chatty.clone()));
+        socket.deliver(WebSocketMessage::from_text("tiny"));
+        socket.deliver(WebSocketMessage::from_text(&chatty));
+        assert!(!peer.outgoing.pop(Some(Duration::ZERO)).unwrap().compressed);
+        assert_eq!(
+            peer.recv(Some(Duration::ZERO)).unwrap().text,
+            Some(chatty.clone())
+        );
+
+        // Client to handler, with the size limit applied once inflated.
+        peer.send(WebSocketMessage::from_binary(vec![7; 1_000]));
+        let message = socket.receive(Some(Duration::ZERO)).unwrap();
+        assert_eq!(message.data, Some(vec![7; 1_000]));
+        peer.send(WebSocketMessage::from_binary(vec![7; 10_000]));
+        assert!(socket.receive(Some(Duration::ZERO)).is_none());
+        assert_eq!(
+            socket.liveness.close_reason(),
+            Some(CloseReason::MessageTooBig)
+        );
+    }
+
+    // ---------- Authentication Tests ----------
+
+    fn upgrade_with_exp(exp_offset: i64) -> Request {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        let mut request = Request::default();
+        request.context.insert(
+            "jwt_claims".to_string(),
+            serde_json::json!({"sub": "alice", "exp": now + exp_offset}),
+        );
+        request
+    }
+
+    #[test]
+    fn test_principal_from_context() {
+        let mut request = Request::default();
+        assert!(principal(&request).is_none());
+
+        request
+            .context
+            .insert("api_key_client".to_string(), serde_json::json!("client-1"));
+        request
+            .context
+            .insert("user".to_string(), serde_json::json!({"id": 7}));
+        assert_eq!(principal(&request), Some(&serde_json::json!({"id": 7})));
+        assert!(principal_expiry(principal(&request).unwrap()).is_none());
+    }
+
+    #[test]
+    fn test_expired_principal_closes() {
+        let registry = WebSocketRegistry::new();
+        let (socket, peer) = registry.accept(upgrade_with_exp(60), None, None);
+        let expires_in = socket.expires_in().unwrap();
+        assert!(expires_in > 55.0 && expires_in <= 60.0);
+
+        assert!(peer.heartbeat(Instant::now()));
+        assert!(!peer.heartbeat(Instant::now() + Duration::from_secs(61)));
+        assert_eq!(
+            socket.liveness.close_reason(),
+            Some(CloseReason::TokenExpired)
+        );
+    }
+
+    #[test]
+    fn test_expiry_grace_and_ignore() {
+        let registry = WebSocketRegistry::new();
+        registry.set_config(WebSocketConfig::default().with_expiry_grace(Duration::from_secs(10)));
+        let (_, peer) = registry.accept(upgrade_with_exp(0), None, None);
+        assert!(peer.heartbeat(Instant::now() + Duration::from_secs(5)));
+        assert!(!peer.heartbeat(Instant::now() + Duration::from_secs(11)));
+
+        registry.set_config(WebSocketConfig::default().with_expiry_policy(ExpiryPolicy::Ignore));
+        let (socket, peer) = registry.accept(upgrade_with_exp(0), None, None);
+        assert!(peer.heartbeat(Instant::now() + Duration::from_secs(3600)));
+        assert_eq!(socket.expires_in(), Some(0.0));
+    }
+
+    #[test]
+    fn test_refresh_extends_expiry() {
+        let registry = WebSocketRegistry::new();
+        let (socket, peer) = registry.accept(upgrade_with_exp(1), None, None);
+
+        socket.refresh_principal(Some(upgrade_with_exp(120)), None);
+        assert!(peer.heartbeat(Instant::now() + Duration::from_secs(60)));
+
+        socket.refresh_principal(None, Some(Duration::from_secs(30)));
+        assert!(peer.heartbeat(Instant::now() + Duration::from_secs(20)));
+        assert!(!peer.heartbeat(Instant::now() + Duration::from_secs(31)));
+    }
+
+    #[test]
+    fn test_reaper_enforces_expiry() {
+        let registry = WebSocketRegistry::new();
+        let (socket, _peer) = registry.accept(Request::default(), None, None);
+        assert!(socket.expires_in().is_none());
+
+        // A deadline set after the connection opened still wakes the reaper.
+        socket.refresh_principal(None, Some(Duration::from_millis(20)));
+        assert!(socket.incoming.pop(Some(Duration::from_secs(5))).is_none());
+        assert_eq!(
+            socket.liveness.close_reason(),
+            Some(CloseReason::TokenExpired)
+        );
+        assert!(registry.open_connections().is_empty());
+    }
+}
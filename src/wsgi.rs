@@ -0,0 +1,546 @@
+//! WSGI compatibility for Cello Framework
+//!
+//! Serves a WSGI application (Flask, Django, ...) under a path prefix so a
+//! legacy app can run behind the Cello server while it is being migrated:
+//! - The WSGI callable runs on Tokio's blocking thread pool, bounded by a
+//!   per-mount worker limit, never on the async runtime threads
+//! - Response chunks are streamed to the client as the app yields them
+//! - `SCRIPT_NAME` is the mount prefix and `PATH_INFO` the rest of the path
+//! - A client disconnect stops iteration and closes the app's iterable
+//!
+//! Mounts only see requests no Cello route matches. Before-middleware and
+//! guards run as usual; after-middleware does not, since the response body
+//! is streamed straight from the app.
+//!
+//! # Example
+//! ```python
+//! from legacy.wsgi import application
+//!
+//! app.mount_wsgi("/legacy", application)
+//! ```
+
+use bytes::Bytes;
+use hyper::{Response as HyperResponse, StatusCode};
+use pyo3::exceptions::{PyRuntimeError, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict};
+use std::sync::Arc;
+use tokio::sync::{mpsc, oneshot, Semaphore};
+
+use crate::request::Request;
+use crate::server::{BodyError, ResponseBody};
+
+/// Chunks buffered between the WSGI thread and the connection.
+const CHUNK_BUFFER: usize = 16;
+
+/// Default number of WSGI calls a mount runs at once.
+pub const DEFAULT_WORKERS: usize = 8;
+
+/// Headers the server manages itself and drops from WSGI responses.
+const HOP_BY_HOP: &[&str] = &[
+    "connection",
+    "keep-alive",
+    "proxy-connection",
+    "transfer-encoding",
+    "upgrade",
+];
+
+// ============================================================================
+// Mount
+// ============================================================================
+
+/// A WSGI application served under a path prefix.
+pub struct WsgiMount {
+    prefix: String,
+    app: PyObject,
+    workers: usize,
+    permits: Arc<Semaphore>,
+}
+
+impl WsgiMount {
+    /// Mount `app` under `prefix`, running at most `workers` calls at once.
+    pub fn new(prefix: &str, app: PyObject, workers: usize) -> Self {
+        let workers = workers.max(1);
+        Self {
+            prefix: prefix.trim_end_matches('/').to_string(),
+            app,
+            workers,
+            permits: Arc::new(Semaphore::new(workers)),
+        }
+    }
+
+    /// The mount prefix, without a trailing slash (empty for `/`).
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// Maximum number of concurrent calls into the app.
+    pub fn workers(&self) -> usize {
+        self.workers
+    }
+
+    /// Whether `path` is served by this mount.
+    #[inline]
+    pub fn matches(&self, path: &str) -> bool {
+        match path.strip_prefix(self.prefix.as_str()) {
+            Some(rest) => rest.is_empty() || rest.starts_with('/'),
+            None => false,
+        }
+    }
+
+    /// Run the app for `request` and stream its response.
+    ///
+    /// Waits for a free worker, then returns as soon as the app has produced
+    /// its status and headers; the body follows as the app yields it. An app
+    /// error before the headers are sent becomes a 500, one after aborts the
+    /// response.
+    pub async fn call(
+        self: Arc<Self>,
+        request: Request,
+        query_string: String,
+    ) -> HyperResponse<ResponseBody> {
+        let permit = self.permits.clone().acquire_owned().await.ok();
+        let (head_tx, head_rx) = oneshot::channel();
+        let (body_tx, body) = ResponseBody::channel(CHUNK_BUFFER);
+
+        let mount = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            Python::with_gil(|py| mount.run(py, &request, &query_string, head_tx, body_tx));
+        });
+
+        match head_rx.await {
+            Ok(Ok(head)) => head.into_response(body),
+            Ok(Err(e)) => {
+                eprintln!(
+                    "WSGI app mounted at '{}' failed: {e}",
+                    self.prefix_display()
+                );
+                error_response()
+            }
+            Err(_) => error_response(),
+        }
+    }
+
+    fn prefix_display(&self) -> &str {
+        if self.prefix.is_empty() {
+            "/"
+        } else {
+            &self.prefix
+        }
+    }
+
+    fn run(
+        &self,
+        py: Python<'_>,
+        request: &Request,
+        query_string: &str,
+        head_tx: oneshot::Sender<Result<WsgiHead, String>>,
+        body_tx: mpsc::Sender<Result<Bytes, BodyError>>,
+    ) {
+        let mut stream = match PyCell::new(py, StartResponse::default()) {
+            Ok(start) => ResponseStream {
+                start,
+                head_tx: Some(head_tx),
+                body_tx,
+            },
+            Err(e) => {
+                let _ = head_tx.send(Err(e.to_string()));
+                return;
+            }
+        };
+
+        if let Err(e) = self.serve(py, request, query_string, &mut stream) {
+            match stream.head_tx.take() {
+                Some(head_tx) => {
+                    let _ = head_tx.send(Err(e.to_string()));
+                }
+                None => {
+                    eprintln!(
+                        "WSGI app mounted at '{}' failed mid-response: {e}",
+                        self.prefix_display()
+                    );
+                    let body_tx = &stream.body_tx;
+                    let _ = py.allow_threads(|| body_tx.blocking_send(Err(e.into())));
+                }
+            }
+        }
+    }
+
+    fn serve(
+        &self,
+        py: Python<'_>,
+        request: &Request,
+        query_string: &str,
+        stream: &mut ResponseStream<'_>,
+    ) -> PyResult<()> {
+        let environ = self.environ(py, request, query_string)?;
+        let result = self.app.call1(py, (environ, stream.start))?;
+        let outcome = stream.drain(py, result.as_ref(py));
+
+        // PEP 3333: close() is called however iteration ended.
+        let closed = match result.as_ref(py).getattr("close") {
+            Ok(close) => close.call0().map(|_| ()),
+            Err(_) => Ok(()),
+        };
+        outcome.and(closed)
+    }
+
+    /// Build the PEP 3333 environ for `request`.
+    fn environ<'py>(
+        &self,
+        py: Python<'py>,
+        request: &Request,
+        query_string: &str,
+    ) -> PyResult<&'py PyDict> {
+        let environ = PyDict::new(py);
+        let path_info = request.path.get(self.prefix.len()..).unwrap_or("");
+        let (server_name, server_port) = server_address(request);
+
+        environ.set_item("REQUEST_METHOD", &request.method)?;
+        environ.set_item("SCRIPT_NAME", &self.prefix)?;
+        environ.set_item("PATH_INFO", latin1_path(path_info))?;
+        environ.set_item("QUERY_STRING", query_string)?;
+        environ.set_item("SERVER_NAME", server_name)?;
+        environ.set_item("SERVER_PORT", server_port)?;
+        environ.set_item("SERVER_PROTOCOL", "HTTP/1.1")?;
+        if let Some(ip) = request.client_ip() {
+            environ.set_item("REMOTE_ADDR", ip)?;
+        }
+
+        for (name, value) in &request.headers {
+            let key = match name.to_ascii_lowercase().as_str() {
+                "content-type" => "CONTENT_TYPE".to_string(),
+                "content-length" => "CONTENT_LENGTH".to_string(),
+                lower => format!("HTTP_{}", lower.to_ascii_uppercase().replace('-', "_")),
+            };
+            environ.set_item(key, value)?;
+        }
+        if !request.body.is_empty() && !environ.contains("CONTENT_LENGTH")? {
+            environ.set_item("CONTENT_LENGTH", request.body.len().to_string())?;
+        }
+
+        let input = py
+            .import("io")?
+            .getattr("BytesIO")?
+            .call1((PyBytes::new(py, &request.body),))?;
+        let scheme = if request.is_secure() { "https" } else { "http" };
+        environ.set_item("wsgi.version", (1, 0))?;
+        environ.set_item("wsgi.url_scheme", scheme)?;
+        environ.set_item("wsgi.input", input)?;
+        environ.set_item("wsgi.errors", py.import("sys")?.getattr("stderr")?)?;
+        environ.set_item("wsgi.multithread", true)?;
+        environ.set_item("wsgi.multiprocess", false)?;
+        environ.set_item("wsgi.run_once", false)?;
+        Ok(environ)
+    }
+}
+
+/// `SERVER_NAME` and `SERVER_PORT` from the Host header.
+fn server_address(request: &Request) -> (String, String) {
+    let default_port = if request.is_secure() { "443" } else { "80" };
+    let host = request.headers.get("host").unwrap_or("localhost");
+    match host.rsplit_once(':') {
+        Some((name, port)) if !name.is_empty() && port.bytes().all(|b| b.is_ascii_digit()) => {
+            (name.to_string(), port.to_string())
+        }
+        _ => (host.to_string(), default_port.to_string()),
+    }
+}
+
+/// Percent-decode `path` into the latin-1 string WSGI expects.
+fn latin1_path(path: &str) -> String {
+    urlencoding::decode_binary(path.as_bytes())
+        .iter()
+        .map(|&b| b as char)
+        .collect()
+}
+
+fn error_response() -> HyperResponse<ResponseBody> {
+    let mut response = HyperResponse::new(ResponseBody::full(Bytes::from_static(
+        b"Internal Server Error",
+    )));
+    *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+    response
+}
+
+// ============================================================================
+// Response
+// ============================================================================
+
+/// Status and headers passed to `start_response`.
+struct WsgiHead {
+    status: StatusCode,
+    headers: Vec<(String, String)>,
+}
+
+impl WsgiHead {
+    fn parse(status: &str, headers: Vec<(String, String)>) -> PyResult<Self> {
+        let status = status
+            .split_whitespace()
+            .next()
+            .and_then(|code| code.parse::<u16>().ok())
+            .and_then(|code| StatusCode::from_u16(code).ok())
+            .ok_or_else(|| PyValueError::new_err(format!("Invalid WSGI status: '{status}'")))?;
+        Ok(Self { status, headers })
+    }
+
+    fn into_response(self, body: ResponseBody) -> HyperResponse<ResponseBody> {
+        let mut builder = HyperResponse::builder().status(self.status);
+        for (name, value) in &self.headers {
+            if !HOP_BY_HOP.contains(&name.to_ascii_lowercase().as_str()) {
+                builder = builder.header(name, value);
+            }
+        }
+        builder.body(body).unwrap_or_else(|_| error_response())
+    }
+}
+
+/// The `start_response` callable handed to the app.
+#[pyclass(name = "StartResponse")]
+#[derive(Default)]
+struct StartResponse {
+    head: Option<(String, Vec<(String, String)>)>,
+    head_sent: bool,
+    /// Data passed to `write()`, sent before the next yielded chunk
+    written: Vec<Bytes>,
+}
+
+#[pymethods]
+impl StartResponse {
+    #[pyo3(signature = (status, headers, exc_info=None))]
+    fn __call__(
+        slf: &PyCell<Self>,
+        status: String,
+        headers: Vec<(String, String)>,
+        exc_info: Option<&PyAny>,
+    ) -> PyResult<PyObject> {
+        {
+            let mut this = slf.borrow_mut();
+            match exc_info {
+                // Too late to change the response: re-raise the app's error.
+                Some(exc_info) if this.head_sent => {
+                    return Err(PyErr::from_value(exc_info.get_item(1)?));
+                }
+                None if this.head.is_some() => {
+                    return Err(PyRuntimeError::new_err(
+                        "start_response() called again without exc_info",
+                    ));
+                }
+                _ => {}
+            }
+            this.head = Some((status, headers));
+        }
+        Ok(slf.getattr("write")?.into())
+    }
+
+    fn write(&mut self, data: &PyBytes) {
+        self.written.push(Bytes::copy_from_slice(data.as_bytes()));
+    }
+}
+
+/// Moves the app's output to the connection.
+struct ResponseStream<'py> {
+    start: &'py PyCell<StartResponse>,
+    head_tx: Option<oneshot::Sender<Result<WsgiHead, String>>>,
+    body_tx: mpsc::Sender<Result<Bytes, BodyError>>,
+}
+
+impl ResponseStream<'_> {
+    /// Iterate the app's result, streaming each chunk.
+    fn drain(&mut self, py: Python<'_>, result: &PyAny) -> PyResult<()> {
+        for item in result.iter()? {
+            let item = item?;
+            let chunk = item.downcast::<PyBytes>().map_err(|_| {
+                PyTypeError::new_err(format!(
+                    "WSGI application must yield bytes, not {}",
+                    item.get_type().name().unwrap_or("object")
+                ))
+            })?;
+            // Headers wait for the first non-empty chunk, so the app can
+            // still replace them with an error response until then.
+            if chunk.as_bytes().is_empty() && self.head_tx.is_some() {
+                continue;
+            }
+            if !self.flush(py)? || !self.send(py, Bytes::copy_from_slice(chunk.as_bytes())) {
+                // The client went away.
+                return Ok(());
+            }
+        }
+        self.flush(py).map(|_| ())
+    }
+
+    /// Send the headers if not yet sent, then any data passed to `write()`.
+    /// Returns false once the client has gone away.
+    fn flush(&mut self, py: Python<'_>) -> PyResult<bool> {
+        let written = {
+            let mut start = self.start.borrow_mut();
+            if let Some(head_tx) = self.head_tx.take() {
+                let (status, headers) = start.head.take().ok_or_else(|| {
+                    PyRuntimeError::new_err("WSGI application did not call start_response()")
+                })?;
+                start.head_sent = true;
+                if head_tx
+                    .send(Ok(WsgiHead::parse(&status, headers)?))
+                    .is_err()
+                {
+                    return Ok(false);
+                }
+            }
+            std::mem::take(&mut start.written)
+        };
+        Ok(written.into_iter().all(|chunk| self.send(py, chunk)))
+    }
+
+    fn send(&self, py: Python<'_>, chunk: Bytes) -> bool {
+        if chunk.is_empty() {
+            return true;
+        }
+        let body_tx = &self.body_tx;
+        py.allow_threads(|| body_tx.blocking_send(Ok(chunk)).is_ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_body_util::BodyExt;
+    use std::collections::HashMap;
+
+    fn wsgi_app(code: &str) -> PyObject {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let module = PyModule::from_code(py, code, "wsgi_app.py", "wsgi_app").unwrap();
+            module.getattr("app").unwrap().into()
+        })
+    }
+
+    fn get(path: &str) -> Request {
+        let headers = [("host", "example.com:8080"), ("x-trace", "abc")];
+        Request::from_http(
+            "GET".to_string(),
+            path.to_string(),
+            HashMap::new(),
+            HashMap::new(),
+            headers.into_iter().collect(),
+            Vec::new(),
+        )
+    }
+
+    async fn body_text(response: HyperResponse<ResponseBody>) -> Result<String, BodyError> {
+        let bytes = response.into_body().collect().await?.to_bytes();
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    // ---------- Mount Tests ----------
+
+    #[test]
+    fn test_prefix_matching() {
+        pyo3::prepare_freethreaded_python();
+        let app = Python::with_gil(|py| py.None());
+        let mount = WsgiMount::new("/legacy/", app.clone(), 4);
+        assert_eq!(mount.prefix(), "/legacy");
+        assert!(mount.matches("/legacy"));
+        assert!(mount.matches("/legacy/users/1"));
+        assert!(!mount.matches("/legacyx"));
+        assert!(!mount.matches("/other"));
+
+        let root = WsgiMount::new("/", app, 0);
+        assert_eq!(root.prefix(), "");
+        assert_eq!(root.workers(), 1);
+        assert!(root.matches("/anything"));
+    }
+
+    #[test]
+    fn test_router_prefers_longest_mount() {
+        pyo3::prepare_freethreaded_python();
+        let app = Python::with_gil(|py| py.None());
+        let mut router = crate::router::Router::new();
+        router.mount(Arc::new(WsgiMount::new("/a", app.clone(), 1)));
+        router.mount(Arc::new(WsgiMount::new("/a/b", app, 1)));
+
+        assert_eq!(router.match_mount("/a/b/c").unwrap().prefix(), "/a/b");
+        assert_eq!(router.match_mount("/a/c").unwrap().prefix(), "/a");
+        assert!(router.match_mount("/b").is_none());
+    }
+
+    // ---------- Call Tests ----------
+
+    #[tokio::test]
+    async fn test_streams_response_with_environ() {
+        let app = wsgi_app(
+            r#"
+def app(environ, start_response):
+    write = start_response("201 Created", [("Content-Type", "text/plain"), ("Connection", "close")])
+    write(b"head;")
+    yield b""
+    for key in ("SCRIPT_NAME", "PATH_INFO", "QUERY_STRING", "SERVER_PORT", "HTTP_X_TRACE"):
+        yield (environ[key] + ";").encode()
+"#,
+        );
+        let mount = Arc::new(WsgiMount::new("/legacy", app, 2));
+
+        let response = mount
+            .call(get("/legacy/caf%C3%A9"), "page=2".to_string())
+            .await;
+
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response.headers()["content-type"], "text/plain");
+        assert!(response.headers().get("connection").is_none());
+        assert_eq!(
+            body_text(response).await.unwrap(),
+            "head;/legacy;/caf\u{c3}\u{a9};page=2;8080;abc;"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_error_before_headers_is_500() {
+        let app = wsgi_app(
+            r#"
+def app(environ, start_response):
+    raise ValueError("boom")
+"#,
+        );
+        let mount = Arc::new(WsgiMount::new("/", app, 1));
+
+        let response = mount.call(get("/"), String::new()).await;
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[tokio::test]
+    async fn test_error_mid_stream_aborts_body() {
+        let app = wsgi_app(
+            r#"
+closed = []
+
+class Body:
+    def __iter__(self):
+        yield b"partial"
+        raise ValueError("boom")
+
+    def close(self):
+        closed.append(True)
+
+def app(environ, start_response):
+    start_response("200 OK", [])
+    return Body()
+"#,
+        );
+        let mount = Arc::new(WsgiMount::new("/", app, 1));
+
+        let response = mount.clone().call(get("/"), String::new()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(body_text(response).await.is_err());
+
+        let closed = Python::with_gil(|py| {
+            let globals = mount.app.getattr(py, "__globals__").unwrap();
+            globals
+                .as_ref(py)
+                .get_item("closed")
+                .unwrap()
+                .len()
+                .unwrap()
+        });
+        assert_eq!(closed, 1);
+    }
+}
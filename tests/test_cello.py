@@ -518,6 +518,27 @@ def test_request_form_parsing():
     assert form["email"] == "john@example.com"
 
 
+def test_request_form_repeated_keys_and_limit():
+    """Test repeated form keys and the max field count."""
+    from cello import Request
+
+    req = Request(
+        method="POST",
+        path="/test",
+        headers={"content-type": "application/x-www-form-urlencoded"},
+        body=b"tag=a&first+name=Ada&tag=b%26c",
+    )
+
+    form = req.form()
+    assert form == {"tag": ["a", "b&c"], "first name": "Ada"}
+
+    try:
+        req.form(max_fields=2)
+        assert False, "expected ValueError"
+    except ValueError:
+        pass
+
+
 # =============================================================================
 # Unit Tests - Response
 # =============================================================================
@@ -591,6 +612,66 @@ def test_response_binary():
     assert resp.status == 200
 
 
+def test_msgpack_and_cbor_bodies():
+    """Test MessagePack/CBOR request decoding and responses."""
+    from cello import Request, Response
+
+    msgpack_req = Request(
+        method="POST",
+        path="/test",
+        headers={"content-type": "application/msgpack"},
+        body=b"\x81\xa1a\x01",
+    )
+    assert msgpack_req.is_binary_data()
+    assert msgpack_req.data() == {"a": 1}
+
+    cbor_req = Request(
+        method="POST",
+        path="/test",
+        headers={"content-type": "application/cbor"},
+        body=b"\xa1\x61a\x82\x01\x02",
+    )
+    assert cbor_req.data() == {"a": [1, 2]}
+
+    resp = Response.msgpack({"a": 1})
+    assert resp.content_type() == "application/msgpack"
+    assert bytes(resp.body()) == b"\x81\xa1a\x01"
+    assert Response.cbor([1, 2], status=201).status == 201
+
+
+def test_app_negotiated_bodies():
+    """Test Vary on negotiated responses and 400 for malformed bodies."""
+    import pytest
+    from cello import App, BodyDecodeError, Request
+    from cello.testing import TestClient
+
+    with pytest.raises(BodyDecodeError):
+        Request("POST", "/", headers={"content-type": "application/msgpack"}, body=b"\xc1").data()
+    assert issubclass(BodyDecodeError, ValueError)
+
+    app = App()
+
+    @app.post("/echo")
+    def echo(request):
+        return {"data": request.data()}
+
+    client = TestClient(app)
+    res = client.post("/echo", json={"a": 1})
+    assert res.headers["content-type"] == "application/json"
+    assert res.headers["vary"] == "Accept"
+    res = client.post("/echo", json={"a": 1}, headers={"Accept": "application/msgpack"})
+    assert res.headers["content-type"] == "application/msgpack"
+    assert res.headers["vary"] == "Accept"
+
+    for content_type, body in [
+        ("application/msgpack", b"\xc1"),
+        ("application/cbor", b"\xff\xff"),
+        ("application/json", b"{bad"),
+    ]:
+        res = client.post("/echo", data=body, headers={"content-type": content_type})
+        assert res.status_code == 400
+
+
 # =============================================================================
 # Unit Tests - SSE
 # =============================================================================
@@ -1222,6 +1303,40 @@ def test_enable_openapi_default_version():
     assert result is None
 
 
+def test_openapi_schema_from_routes():
+    """Test that the generated spec covers routes, metadata and guards."""
+    from cello import App, Authenticated, api_doc
+
+    app = App()
+
+    @app.get("/users/{id}", tags=["users"], guards=[Authenticated()])
+    def get_user(request):
+        """Fetch a user."""
+        return {}
+
+    @app.post("/items")
+    @api_doc(summary="Create an item", deprecated=True)
+    def create_item(request):
+        return {}
+
+    app.enable_openapi(title="Test API", version="2.0.0")
+    spec = app.openapi_schema()
+
+    assert spec["openapi"] == "3.1.0"
+    assert spec["info"]["version"] == "2.0.0"
+    assert "/openapi.json" not in spec["paths"]
+
+    get_op = spec["paths"]["/users/{id}"]["get"]
+    assert get_op["summary"] == "Fetch a user."
+    assert get_op["tags"] == ["users"]
+    assert get_op["parameters"][0]["name"] == "id"
+    assert get_op["security"] == [{"bearerAuth": []}]
+
+    post_op = spec["paths"]["/items"]["post"]
+    assert post_op["summary"] == "Create an item"
+    assert post_op["deprecated"] is True
+
+
 # =============================================================================
 # v0.8.0 Data Layer Feature Tests
 # =============================================================================
@@ -1412,75 +1527,72 @@ def test_database_class_with_config():
     assert db._config is config
 
 
+async def _memory_database():
+    from cello import DatabaseConfig
+    from cello.database import Database
+
+    db = await Database.connect(DatabaseConfig("sqlite::memory:", pool_size=1))
+    await db.execute("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)")
+    await db.execute("INSERT INTO users (name) VALUES ($1)", "Alice")
+    return db
+
+
 @pytest.mark.asyncio
 async def test_database_connect():
     """Test Database.connect class method."""
-    from cello import DatabaseConfig
+    from cello import DatabaseConfig, DatabasePool
     from cello.database import Database
 
-    config = DatabaseConfig("postgresql://localhost/test")
+    config = DatabaseConfig("sqlite::memory:", pool_size=1)
     db = await Database.connect(config)
     assert db is not None
-    assert db._pool is not None
+    assert isinstance(db._pool, DatabasePool)
+    assert db.pool.backend == "sqlite"
 
 
 @pytest.mark.asyncio
 async def test_database_fetch_all():
-    """Test Database.fetch_all returns empty list (mock)."""
-    from cello import DatabaseConfig
-    from cello.database import Database
-
-    config = DatabaseConfig("postgresql://localhost/test")
-    db = await Database.connect(config)
-    rows = await db.fetch_all("SELECT * FROM users")
-    assert isinstance(rows, list)
-    assert len(rows) == 0
+    """Test Database.fetch_all returns rows as dicts."""
+    db = await _memory_database()
+    rows = await db.fetch_all("SELECT id, name FROM users")
+    assert rows == [{"id": 1, "name": "Alice"}]
+    assert await db.fetch_all("SELECT * FROM users WHERE id = $1", 99) == []
 
 
 @pytest.mark.asyncio
 async def test_database_fetch_one():
-    """Test Database.fetch_one returns None (mock)."""
-    from cello import DatabaseConfig
-    from cello.database import Database
-
-    config = DatabaseConfig("postgresql://localhost/test")
-    db = await Database.connect(config)
-    row = await db.fetch_one("SELECT * FROM users WHERE id = $1", 1)
-    assert row is None
+    """Test Database.fetch_one returns a row or None."""
+    db = await _memory_database()
+    row = await db.fetch_one("SELECT name FROM users WHERE id = $1", 1)
+    assert row == {"name": "Alice"}
+    assert await db.fetch_one("SELECT * FROM users WHERE id = $1", 2) is None
+    assert await db.fetch_val("SELECT COUNT(*) FROM users") == 1
 
 
 @pytest.mark.asyncio
 async def test_database_execute():
-    """Test Database.execute returns 0 (mock)."""
-    from cello import DatabaseConfig
-    from cello.database import Database
-
-    config = DatabaseConfig("postgresql://localhost/test")
-    db = await Database.connect(config)
-    count = await db.execute("INSERT INTO users (name) VALUES ($1)", "Alice")
-    assert count == 0
+    """Test Database.execute returns the affected row count."""
+    db = await _memory_database()
+    count = await db.execute("INSERT INTO users (name) VALUES ($1)", "Bob")
+    assert count == 1
+    assert await db.execute("UPDATE users SET name = $1", "X") == 2
 
 
 @pytest.mark.asyncio
 async def test_database_begin_transaction():
     """Test Database.begin returns a Transaction."""
-    from cello import DatabaseConfig
-    from cello.database import Database, Transaction
+    from cello.database import Transaction
 
-    config = DatabaseConfig("postgresql://localhost/test")
-    db = await Database.connect(config)
+    db = await _memory_database()
     tx = await db.begin()
     assert isinstance(tx, Transaction)
+    await tx.rollback()
 
 
 @pytest.mark.asyncio
 async def test_database_close():
     """Test Database.close resets pool."""
-    from cello import DatabaseConfig
-    from cello.database import Database
-
-    config = DatabaseConfig("postgresql://localhost/test")
-    db = await Database.connect(config)
+    db = await _memory_database()
     assert db._pool is not None
     await db.close()
     assert db._pool is None
@@ -1494,51 +1606,44 @@ async def test_database_close():
 @pytest.mark.asyncio
 async def test_transaction_commit():
     """Test Transaction commit."""
-    from cello import DatabaseConfig
-    from cello.database import Database
-
-    db = await Database.connect(DatabaseConfig("sqlite://test.db"))
+    db = await _memory_database()
     tx = await db.begin()
     assert tx._committed is False
+    await tx.execute("INSERT INTO users (name) VALUES ($1)", "Bob")
     await tx.commit()
     assert tx._committed is True
+    assert await db.fetch_val("SELECT COUNT(*) FROM users") == 2
 
 
 @pytest.mark.asyncio
 async def test_transaction_rollback():
     """Test Transaction rollback."""
-    from cello import DatabaseConfig
-    from cello.database import Database
-
-    db = await Database.connect(DatabaseConfig("sqlite://test.db"))
+    db = await _memory_database()
     tx = await db.begin()
     assert tx._rolled_back is False
+    await tx.execute("INSERT INTO users (name) VALUES ($1)", "Bob")
     await tx.rollback()
     assert tx._rolled_back is True
+    assert await db.fetch_val("SELECT COUNT(*) FROM users") == 1
 
 
 @pytest.mark.asyncio
 async def test_transaction_context_manager_commit():
     """Test Transaction as async context manager auto-commits."""
-    from cello import DatabaseConfig
-    from cello.database import Database
-
-    db = await Database.connect(DatabaseConfig("sqlite://test.db"))
+    db = await _memory_database()
     tx = await db.begin()
     async with tx:
         await tx.execute("INSERT INTO users (name) VALUES ($1)", "Bob")
     # Should auto-commit on clean exit
     assert tx._committed is True
     assert tx._rolled_back is False
+    assert await db.fetch_val("SELECT COUNT(*) FROM users") == 2
 
 
 @pytest.mark.asyncio
 async def test_transaction_context_manager_rollback():
     """Test Transaction as async context manager auto-rollbacks on exception."""
-    from cello import DatabaseConfig
-    from cello.database import Database
-
-    db = await Database.connect(DatabaseConfig("sqlite://test.db"))
+    db = await _memory_database()
     tx = await db.begin()
 
     with pytest.raises(ValueError):
@@ -1549,42 +1654,38 @@ async def test_transaction_context_manager_rollback():
     # Should auto-rollback on exception
     assert tx._rolled_back is True
     assert tx._committed is False
+    assert await db.fetch_val("SELECT COUNT(*) FROM users") == 1
 
 
 @pytest.mark.asyncio
 async def test_transaction_execute():
     """Test executing queries within a transaction."""
-    from cello import DatabaseConfig
-    from cello.database import Database
-
-    db = await Database.connect(DatabaseConfig("sqlite://test.db"))
+    db = await _memory_database()
     tx = await db.begin()
-    count = await tx.execute("UPDATE accounts SET balance = 100 WHERE id = $1", 1)
-    assert count == 0  # Mock returns 0
+    count = await tx.execute("UPDATE users SET name = $1 WHERE id = $2", "Ann", 1)
+    assert count == 1
+    await tx.rollback()
 
 
 @pytest.mark.asyncio
 async def test_transaction_fetch_all():
     """Test fetching rows within a transaction."""
-    from cello import DatabaseConfig
-    from cello.database import Database
-
-    db = await Database.connect(DatabaseConfig("sqlite://test.db"))
+    db = await _memory_database()
     tx = await db.begin()
-    rows = await tx.fetch_all("SELECT * FROM accounts")
-    assert isinstance(rows, list)
+    await tx.execute("INSERT INTO users (name) VALUES ($1)", "Bob")
+    rows = await tx.fetch_all("SELECT name FROM users ORDER BY id")
+    assert rows == [{"name": "Alice"}, {"name": "Bob"}]
+    await tx.rollback()
 
 
 @pytest.mark.asyncio
 async def test_transaction_fetch_one():
     """Test fetching a single row within a transaction."""
-    from cello import DatabaseConfig
-    from cello.database import Database
-
-    db = await Database.connect(DatabaseConfig("sqlite://test.db"))
+    db = await _memory_database()
     tx = await db.begin()
-    row = await tx.fetch_one("SELECT * FROM accounts WHERE id = $1", 1)
-    assert row is None  # Mock returns None
+    row = await tx.fetch_one("SELECT * FROM users WHERE id = $1", 2)
+    assert row is None
+    await tx.rollback()
 
 
 # =============================================================================
@@ -1786,7 +1887,7 @@ async def test_transactional_commits_on_success():
     from cello.database import transactional, Database, Transaction
     from cello import DatabaseConfig
 
-    db = await Database.connect(DatabaseConfig("sqlite://test.db"))
+    db = await Database.connect(DatabaseConfig("sqlite::memory:"))
 
     @transactional
     async def my_handler(request, db=None, **kwargs):
@@ -1803,7 +1904,7 @@ async def test_transactional_rollbacks_on_error():
     from cello.database import transactional, Database
     from cello import DatabaseConfig
 
-    db = await Database.connect(DatabaseConfig("sqlite://test.db"))
+    db = await Database.connect(DatabaseConfig("sqlite::memory:"))
 
     @transactional
     async def failing_handler(request, db=None, **kwargs):
@@ -2638,6 +2739,225 @@ def test_app_add_grpc_service_no_methods():
     assert result is None
 
 
+def test_app_register_grpc_service_dispatch():
+    """Test gRPC handlers are dispatched with message types, metadata and deadlines."""
+    import asyncio
+    from cello import App
+    from cello.grpc import GrpcService, GrpcResponse, GrpcError, grpc_method
+
+    class Users(GrpcService):
+        @grpc_method(input_type="users.Get", output_type="users.User")
+        def get_user(self, request):
+            if request.data["id"] == 0:
+                raise GrpcError(GrpcError.NOT_FOUND, "no user")
+            if request.data["id"] < 0:
+                return {}["k"]
+            return {"id": request.data["id"], "name": request.metadata["x-name"]}
+
+        @grpc_method
+        async def echo(self, request):
+            response = GrpcResponse.ok(request.data)
+            response.metadata["x-echo"] = "1"
+            return response
+
+        @grpc_method(deadline=0.01)
+        async def slow(self, request):
+            await asyncio.sleep(1)
+
+    app = App()
+    app.enable_grpc()
+    app.register_proto_message("users.Get", [("id", 1, "int32")])
+    app.register_proto_message("users.User", [("id", 1, "int32"), ("name", 2, "string")])
+    app.register_grpc_service(Users())
+
+    async def run():
+        payload = app.proto_encode("users.Get", {"id": 7})
+        result = await app.grpc_call("Users", "get_user", payload, {"x-name": "Ada"})
+        assert result["code"] == 0
+        assert app.proto_decode("users.User", result["payload"]) == {"id": 7, "name": "Ada"}
+
+        payload = app.proto_encode("users.Get", {"id": 0})
+        result = await app.grpc_call("Users", "get_user", payload, {"x-name": "Ada"})
+        assert (result["code"], result["message"]) == (GrpcError.NOT_FOUND, "no user")
+
+        payload = app.proto_encode("users.Get", {"id": -1})
+        result = await app.grpc_call("Users", "get_user", payload, {"x-name": "Ada"})
+        assert (result["code"], result["message"]) == (GrpcError.UNKNOWN, "KeyError: 'k'")
+
+        result = await app.grpc_call("Users", "echo", b"raw")
+        assert result["payload"] == b"raw"
+        assert result["metadata"] == {"x-echo": "1"}
+
+        result = await app.grpc_call("Users", "slow", b"")
+        assert result["code"] == GrpcError.DEADLINE_EXCEEDED
+
+        result = await app.grpc_call("Users", "missing", b"")
+        assert result["code"] == GrpcError.UNIMPLEMENTED
+
+    asyncio.run(run())
+
+
+def test_app_load_proto_descriptors(tmp_path):
+    """Test loading a FileDescriptorSet registers types and gRPC services."""
+    import asyncio
+    from cello import App
+    from cello.grpc import GrpcService, GrpcError, grpc_method
+
+    # Encode a descriptor set using the relevant parts of descriptor.proto
+    builder = App()
+    builder.register_proto_message("FileDescriptorSet", [("file", 1, "File", True)])
+    builder.register_proto_message("File", [
+        ("package", 2, "string"),
+        ("message_type", 4, "Message", True),
+        ("service", 6, "Service", True),
+    ])
+    builder.register_proto_message("Message", [("name", 1, "string"), ("field", 2, "Field", True)])
+    builder.register_proto_message("Field", [
+        ("name", 1, "string"), ("number", 3, "int32"), ("label", 4, "int32"), ("type", 5, "int32"),
+    ])
+    builder.register_proto_message("Service", [("name", 1, "string"), ("method", 2, "Method", True)])
+    builder.register_proto_message("Method", [
+        ("name", 1, "string"), ("input_type", 2, "string"), ("output_type", 3, "string"),
+    ])
+    descriptor_set = builder.proto_encode("FileDescriptorSet", {"file": [{
+        "package": "users",
+        "message_type": [
+            {"name": "GetUser", "field": [{"name": "id", "number": 1, "label": 1, "type": 5}]},
+            {"name": "User", "field": [
+                {"name": "id", "number": 1, "label": 1, "type": 5},
+                {"name": "name", "number": 2, "label": 1, "type": 9},
+            ]},
+        ],
+        "service": [{"name": "UserService", "method": [
+            {"name": "GetUser", "input_type": ".users.GetUser", "output_type": ".users.User"},
+        ]}],
+    }]})
+    path = tmp_path / "users.desc"
+    path.write_bytes(descriptor_set)
+
+    class UserService(GrpcService):
+        @grpc_method
+        def GetUser(self, request):
+            return {"id": request.data["id"], "name": "Alice"}
+
+    app = App()
+    assert app.load_proto_descriptors(str(path)) == ["users.UserService"]
+    app.enable_grpc()
+    app.register_grpc_service(UserService(name="users.UserService"))
+
+    async def run():
+        payload = app.proto_encode("users.GetUser", {"id": 5})
+        result = await app.grpc_call("users.UserService", "GetUser", payload)
+        assert app.proto_decode("users.User", result["payload"]) == {"id": 5, "name": "Alice"}
+
+        result = await app.grpc_call("users.UserService", "GetUser", b"\x0a\x09")
+        assert result["code"] == GrpcError.INVALID_ARGUMENT
+
+    asyncio.run(run())
+
+    bad = tmp_path / "bad.desc"
+    bad.write_bytes(b"\x0a\x05\x01")
+    try:
+        app.load_proto_descriptors(str(bad))
+        assert False, "expected ValueError"
+    except ValueError:
+        pass
+
+
+def test_app_grpc_transcoding(tmp_path):
+    """Test gRPC methods with google.api.http options are served as REST routes."""
+    import asyncio
+    import json
+    from cello import App, Request
+    from cello.grpc import GrpcService, GrpcError, grpc_method
+
+    builder = App()
+    builder.register_proto_message("FileDescriptorSet", [("file", 1, "File", True)])
+    builder.register_proto_message("File", [
+        ("package", 2, "string"),
+        ("message_type", 4, "Message", True),
+        ("service", 6, "Service", True),
+    ])
+    builder.register_proto_message("Message", [("name", 1, "string"), ("field", 2, "Field", True)])
+    builder.register_proto_message("Field", [
+        ("name", 1, "string"), ("number", 3, "int32"), ("label", 4, "int32"), ("type", 5, "int32"),
+    ])
+    builder.register_proto_message("Service", [("name", 1, "string"), ("method", 2, "Method", True)])
+    builder.register_proto_message("Method", [
+        ("name", 1, "string"), ("input_type", 2, "string"), ("output_type", 3, "string"),
+        ("options", 4, "MethodOptions"),
+    ])
+    builder.register_proto_message("MethodOptions", [("http", 72295728, "HttpRule")])
+    builder.register_proto_message("HttpRule", [
+        ("get", 2, "string"), ("post", 4, "string"), ("body", 7, "string"),
+    ])
+    descriptor_set = builder.proto_encode("FileDescriptorSet", {"file": [{
+        "package": "users",
+        "message_type": [
+            {"name": "GetUser", "field": [
+                {"name": "id", "number": 1, "label": 1, "type": 5},
+                {"name": "verbose", "number": 2, "label": 1, "type": 8},
+            ]},
+            {"name": "User", "field": [
+                {"name": "id", "number": 1, "label": 1, "type": 5},
+                {"name": "name", "number": 2, "label": 1, "type": 9},
+            ]},
+        ],
+        "service": [{"name": "UserService", "method": [
+            {"name": "GetUser", "input_type": ".users.GetUser", "output_type": ".users.User",
+             "options": {"http": {"get": "/v1/users/{id}"}}},
+            {"name": "CreateUser", "input_type": ".users.User", "output_type": ".users.User",
+             "options": {"http": {"post": "/v1/users", "body": "*"}}},
+        ]}],
+    }]})
+    path = tmp_path / "users.desc"
+    path.write_bytes(descriptor_set)
+
+    class UserService(GrpcService):
+        @grpc_method
+        def GetUser(self, request):
+            if request.data["id"] == 0:
+                raise GrpcError(GrpcError.NOT_FOUND, "no such user")
+            name = "Alice (verbose)" if request.data["verbose"] else "Alice"
+            return {"id": request.data["id"], "name": name}
+
+        @grpc_method
+        async def CreateUser(self, request):
+            return request.data
+
+    app = App()
+    app.load_proto_descriptors(str(path))
+    app.enable_grpc()
+    app.register_grpc_service(UserService(name="users.UserService"))
+    routes = {(r["method"], r["path"]) for r in app._routes}
+    assert ("GET", "/v1/users/{id}") in routes
+    assert ("POST", "/v1/users") in routes
+
+    async def run():
+        request = Request("GET", "/v1/users/5", params={"id": "5"}, query={"verbose": "true"})
+        response = await app._app.grpc_transcode(request, "users.UserService", "GetUser")
+        assert response.status == 200
+        assert json.loads(bytes(response.body())) == {"id": 5, "name": "Alice (verbose)"}
+
+        request = Request("GET", "/v1/users/0", params={"id": "0"})
+        response = await app._app.grpc_transcode(request, "users.UserService", "GetUser")
+        assert response.status == 404
+        assert json.loads(bytes(response.body())) == {"code": GrpcError.NOT_FOUND, "message": "no such user"}
+
+        request = Request("POST", "/v1/users", body=b'{"id": 9, "name": "Bob"}')
+        response = await app._app.grpc_transcode(
+            request, "users.UserService", "CreateUser", body="*", response_body="name"
+        )
+        assert json.loads(bytes(response.body())) == "Bob"
+
+    asyncio.run(run())
+
+    with pytest.raises(ValueError):
+        app.add_grpc_transcoding("users.UserService", "GetUser", "GET", "/v1/{name=shelves/*}")
+    with pytest.raises(ValueError):
+        app.add_grpc_transcoding("users.UserService", "GetUser", "TRACE", "/v1/users")
+
+
 def test_app_enable_messaging():
     """Test App.enable_messaging() with KafkaConfig."""
     from cello import App, KafkaConfig
@@ -3467,12 +3787,24 @@ async def test_grpc_channel_connect():
 
 @pytest.mark.asyncio
 async def test_grpc_channel_call():
-    """Test GrpcChannel.call() returns a dict."""
-    from cello.grpc import GrpcChannel
+    """Test GrpcChannel.call() encodes requests and reports transport errors."""
+    import socket
+    from cello import App
+    from cello.grpc import GrpcChannel, GrpcError
 
-    channel = await GrpcChannel.connect("localhost:50051")
-    result = await channel.call("UserService", "GetUser", {"id": 1})
-    assert isinstance(result, dict)
+    app = App()
+    app.register_proto_message("users.Get", [("id", 1, "int32")])
+
+    with socket.socket() as sock:
+        sock.bind(("127.0.0.1", 0))
+        port = sock.getsockname()[1]
+
+    channel = await GrpcChannel.connect(f"127.0.0.1:{port}", app=app, max_retries=0)
+    with pytest.raises(ValueError):
+        await channel.call("UserService", "GetUser", {"id": 1})
+    with pytest.raises(GrpcError) as exc_info:
+        await channel.call("UserService", "GetUser", {"id": 1}, input_type="users.Get")
+    assert exc_info.value.code == GrpcError.UNAVAILABLE
 
 
 @pytest.mark.asyncio
@@ -4280,6 +4612,91 @@ def test_app_enable_event_sourcing_memory():
     assert result is app  # returns self for chaining
 
 
+
+def test_app_append_events_across_aggregates():
+    """Test App.append_events() writes all streams or none."""
+    from cello import App, ConcurrencyError
+
+    app = App()
+    app.enable_event_sourcing()
+    result = app.append_events([
+        {"aggregate_id": "account-1",
+         "events": [{"event_type": "Debited", "data": {"amount": 10}}]},
+        {"aggregate_id": "account-2",
+         "events": [{"event_type": "Credited", "data": {"amount": 10}}]},
+    ])
+    events = result["events"]
+    assert [(e["aggregate_id"], e["version"]) for e in events] == [
+        ("account-1", 1), ("account-2", 1),
+    ]
+    assert all(
+        e["metadata"]["transaction_id"] == result["transaction_id"] for e in events
+    )
+
+    with pytest.raises(ConcurrencyError) as exc:
+        app.append_events([
+            {"aggregate_id": "account-1", "expected_version": 1,
+             "events": [{"event_type": "Debited", "data": {"amount": 5}}]},
+            {"aggregate_id": "account-2", "expected_version": 0,
+             "events": [{"event_type": "Credited", "data": {"amount": 5}}]},
+            {"aggregate_id": "account-3", "expected_version": 2,
+             "events": [{"event_type": "Credited", "data": {"amount": 5}}]},
+        ])
+    assert exc.value.conflicts == [
+        {"aggregate_id": "account-2", "expected": 0, "actual": 1},
+        {"aggregate_id": "account-3", "expected": 2, "actual": 0},
+    ]
+
+    # Nothing from the rejected transaction was written.
+    result = app.append_events([
+        {"aggregate_id": "account-1", "expected_version": 1,
+         "events": [{"event_type": "Debited", "data": {"amount": 1}}]},
+    ])
+    assert result["events"][0]["version"] == 2
+
+
+def test_app_event_encryption_and_shredding():
+    """Test encrypted aggregates can be forgotten by shredding their key."""
+    from cello import App, RedisConfig
+
+    app = App()
+    app.enable_event_sourcing(encrypt=["customer-*"])
+    app.append_events([
+        {"aggregate_id": "customer-1",
+         "events": [{"event_type": "Registered", "data": {"email": "ada@example.com"}}]},
+        {"aggregate_id": "order-1",
+         "events": [{"event_type": "Placed", "data": {"total": 5}}]},
+    ])
+    [event] = app.get_events("customer-1")
+    assert event["data"] == {"email": "ada@example.com"}
+    assert event["metadata"]["encryption_key_id"].startswith("events:customer-1:")
+    assert "encryption_key_id" not in app.get_events("order-1")[0]["metadata"]
+
+    assert app.shred_aggregate("customer-1") is True
+    [event] = app.get_events("customer-1")
+    assert event["data"] is None
+    assert event["metadata"]["shredded"] == "true"
+    assert app.get_events("order-1")[0]["data"] == {"total": 5}
+    assert app.get_events("missing") == []
+
+    # Events appended after shredding get a new key and stay readable.
+    app.append_events([
+        {"aggregate_id": "customer-1",
+         "events": [{"event_type": "Registered", "data": {"email": "bob@example.com"}}]},
+    ])
+    shredded, event = app.get_events("customer-1")
+    assert shredded["data"] is None
+    assert event["data"] == {"email": "bob@example.com"}
+
+    with pytest.raises(RuntimeError):
+        App().shred_aggregate("customer-1")
+
+    shared = App()
+    shared.enable_redis(RedisConfig())
+    with pytest.raises(RuntimeError):
+        shared.enable_event_sourcing(encrypt=["*"], secrets="redis")
+
+
 def test_app_enable_cqrs():
     """Test App.enable_cqrs() does not raise errors."""
     from cello import App
@@ -4312,84 +4729,3784 @@ def test_app_enable_saga():
     assert result is app  # returns self for chaining
 
 
-def test_app_with_all_v0100_features():
-    """Test an App with all v0.10.0 features enabled together."""
-    from cello import App, EventSourcingConfig, CqrsConfig, SagaConfig
+def test_app_run_saga_retries_and_compensates():
+    """Test App.run_saga() retries failing steps and reports the cause."""
+    import asyncio
+    from cello import App, SagaConfig
+    from cello.saga import Saga, SagaStep
 
-    app = App()
+    attempts = []
 
-    # Core middleware
-    app.enable_cors()
-    app.enable_logging()
+    def reserve(context):
+        return {"qty": context["input"]["qty"]}
 
-    # v0.10.0 Advanced Pattern features
-    app.enable_event_sourcing(EventSourcingConfig.memory())
-    app.enable_cqrs(CqrsConfig())
-    app.enable_saga(SagaConfig())
+    async def charge(context):
+        attempts.append(context["results"]["reserve"]["qty"])
+        if len(attempts) < 2:
+            raise RuntimeError("busy")
+        return {"charged": True}
 
-    # Register routes
-    @app.get("/")
-    def home(req):
-        return {"version": "1.0.1"}
+    def ship(context):
+        raise RuntimeError("no courier")
 
-    # Verify route handler was registered
-    assert callable(home)
+    app = App()
+    app.enable_saga(SagaConfig(max_retries=1, retry_delay_ms=1, enable_logging=False))
+    assert SagaConfig().backoff_multiplier == 2.0
 
+    order = Saga(name="order")
+    order.add_step(SagaStep("reserve", reserve))
+    order.add_step(SagaStep("charge", charge, timeout=1))
+    app.register_saga(order)
 
-# ---------------------------------------------------------------------------
-# v0.10.0 Event Sourcing Python Module Tests
-# ---------------------------------------------------------------------------
+    shipping = Saga(name="shipping")
+    shipping.add_step(SagaStep("ship", ship))
+    app.register_saga(shipping)
 
+    async def run():
+        execution = await app.run_saga("order", {"qty": 2})
+        assert execution["status"] == "Completed"
+        assert execution["steps"][1]["result"] == {"charged": True}
+        assert attempts == [2, 2]
 
-def test_event_creation():
-    """Test Event creation with type and data."""
-    from cello.eventsourcing import Event
+        execution = await app.run_saga("shipping")
+        assert execution["status"] == "Compensated"
+        assert execution["error"] == {"MaxRetriesExceeded": {"step": "ship", "attempts": 2}}
+        assert "no courier" in execution["steps"][0]["error"]
 
-    event = Event("OrderCreated", {"id": 1})
-    assert event is not None
-    assert event.event_type == "OrderCreated"
-    assert event.data == {"id": 1}
+    asyncio.run(run())
 
 
-def test_event_properties():
-    """Test Event has all expected properties."""
-    from cello.eventsourcing import Event
+def test_app_run_saga_stops_timed_out_steps():
+    """Test App.run_saga() stops timed-out attempts instead of abandoning them."""
+    import asyncio
+    import time
+    from cello import App, SagaConfig
+    from cello.saga import Saga, SagaStep
 
-    event = Event("OrderCreated", {"id": 1})
-    assert event.id is not None
-    assert event.event_type == "OrderCreated"
-    assert event.data == {"id": 1}
-    assert event.aggregate_id is None
-    assert event.metadata == {}
-    assert event.version == 0
-    assert event.timestamp is not None
+    effects = []
 
+    async def charge(context):
+        await asyncio.sleep(0.3)
+        effects.append("charge")
 
-def test_event_with_aggregate_id():
-    """Test Event creation with aggregate_id."""
-    from cello.eventsourcing import Event
+    def ship(context):
+        time.sleep(0.3)
+        effects.append("ship")
 
-    event = Event("OrderCreated", {"id": 1}, aggregate_id="order-1")
-    assert event.aggregate_id == "order-1"
-    assert event.event_type == "OrderCreated"
+    app = App()
+    app.enable_saga(SagaConfig(max_retries=2, retry_delay_ms=1, enable_logging=False))
+    charging = Saga(name="charging")
+    charging.add_step(SagaStep("charge", charge, timeout=0.1))
+    app.register_saga(charging)
+    shipping = Saga(name="shipping")
+    shipping.add_step(SagaStep("ship", ship, timeout=0.1))
+    app.register_saga(shipping)
+
+    async def run():
+        for name, step in [("charging", "charge"), ("shipping", "ship")]:
+            execution = await app.run_saga(name)
+            assert execution["status"] == "Compensated"
+            assert execution["error"] == {"MaxRetriesExceeded": {"step": step, "attempts": 3}}
+        await asyncio.sleep(0.5)
+        assert effects == []
+
+    asyncio.run(run())
+
+
+def test_app_run_saga_runs_compensations():
+    """Test App.run_saga() runs compensations in reverse and records failures."""
+    import asyncio
+    from cello import App, SagaConfig
+    from cello.saga import Saga, SagaStep
 
+    undone = []
 
-def test_event_with_metadata():
-    """Test Event creation with metadata."""
-    from cello.eventsourcing import Event
+    def reserve(context):
+        return {"reservation": context["input"]["item"]}
 
-    event = Event("OrderCreated", {}, metadata={"user": "admin"})
-    assert event.metadata == {"user": "admin"}
+    async def cancel_reservation(context):
+        undone.append(("reserve", context["result"]["reservation"]))
 
+    def charge(context):
+        return {"payment": "pay-1"}
 
-def test_event_json():
-    """Test Event.to_dict() returns dict with all fields."""
-    from cello.eventsourcing import Event
+    def refund(context):
+        undone.append(("charge", context["result"]["payment"]))
+        raise RuntimeError("refund rejected")
 
-    event = Event("OrderCreated", {"id": 1}, aggregate_id="order-1")
-    data = event.json()
-    assert isinstance(data, dict)
-    assert data["event_type"] == "OrderCreated"
+    def ship(context):
+        raise RuntimeError("no courier")
+
+    app = App()
+    app.enable_saga(SagaConfig(
+        max_retries=0,
+        compensation_max_retries=1,
+        compensation_retry_delay_ms=1,
+        enable_logging=False,
+    ))
+    order = Saga(name="order")
+    order.add_step(SagaStep("reserve", reserve, cancel_reservation))
+    order.add_step(SagaStep("charge", charge, refund))
+    order.add_step(SagaStep("ship", ship))
+    app.register_saga(order)
+
+    execution = asyncio.run(app.run_saga("order", {"item": "book"}))
+    assert undone == [("charge", "pay-1"), ("charge", "pay-1"), ("reserve", "book")]
+    assert execution["status"] == "Failed"
+    assert [s["status"] for s in execution["steps"]] == ["Compensated", "CompensationFailed", "Failed"]
+    assert len(execution["compensation_errors"]) == 1
+    assert "refund rejected" in execution["compensation_errors"][0]["CompensationFailed"]
+
+
+def test_app_run_saga_deduplicates_steps():
+    """Test App.run_saga() with a key does not repeat completed steps."""
+    import asyncio
+    import pytest
+    from cello import App, RedisConfig, SagaConfig
+    from cello.saga import Saga, SagaStep
+
+    charges = []
+    shipped = {"ok": False}
+
+    def charge(context):
+        charges.append(context["idempotency_key"])
+        return {"payment": "pay-1"}
+
+    def ship(context):
+        if not shipped["ok"]:
+            raise RuntimeError("no courier")
+        return {"tracking": "t-1"}
+
+    app = App()
+    with pytest.raises(ValueError):
+        app.enable_saga(SagaConfig(enable_logging=False), dedup="disk")
+    # Results in the in-process Redis client would not survive a crash.
+    app.enable_redis(RedisConfig())
+    with pytest.raises(RuntimeError):
+        app.enable_saga(SagaConfig(enable_logging=False), dedup="redis")
+    app.enable_saga(SagaConfig(max_retries=0, enable_logging=False), dedup="memory")
+    order = Saga(name="order")
+    order.add_step(SagaStep("charge", charge))
+    order.add_step(SagaStep("ship", ship))
+    app.register_saga(order)
+
+    async def run():
+        execution = await app.run_saga("order", {"id": 7}, key="order-7")
+        assert execution["status"] == "Compensated"
+        assert execution["idempotency_key"] == "order-7"
+
+        shipped["ok"] = True
+        execution = await app.run_saga("order", {"id": 7}, key="order-7")
+        assert execution["status"] == "Completed"
+        assert execution["steps"][0]["result"] == {"payment": "pay-1"}
+        assert charges == ["order:order-7:charge"]
+
+        await app.run_saga("order", {"id": 8})
+        assert len(charges) == 2
+
+    asyncio.run(run())
+    assert app.stats_snapshot()["saga"]["deduplicated_steps"] == 1
+
+
+def test_app_saga_timer_and_signal_steps():
+    """Test saga timer steps, signal steps and restoring waiting executions."""
+    import asyncio
+    import time
+    import pytest
+    from cello import App, SagaConfig
+    from cello.saga import Saga, SagaStep
+    from cello.testing import TestClient
+
+    tokens = []
+
+    def request_approval(context):
+        tokens.append(context["signal_token"])
+
+    def pay(context):
+        return {"paid_by": context["results"]["approve"]["by"]}
+
+    app = App()
+    app.enable_saga(SagaConfig(enable_logging=False))
+    expense = Saga(name="expense")
+    expense.add_step(SagaStep.timer("cool_off", delay=0.02))
+    expense.add_step(SagaStep.timer("due", until=time.time() - 1))
+    expense.add_step(SagaStep.signal("approve", notify=request_approval, timeout=5))
+    expense.add_step(SagaStep("pay", pay))
+    app.register_saga(expense)
+    app.enable_saga_signals()
+    client = TestClient(app)
+
+    with pytest.raises(ValueError):
+        SagaStep.timer("both", delay=1, until=1)
+    with pytest.raises(ValueError):
+        app.signal_saga("unknown")
+    assert client.post("/_saga/signals/unknown", json={}).status_code == 404
+
+    async def waiting_execution():
+        for _ in range(200):
+            executions = app.saga_executions(status="running")["executions"]
+            if executions and executions[0]["steps"][2]["status"] == "Waiting":
+                return executions[0]
+            await asyncio.sleep(0.01)
+        raise AssertionError("no saga step is waiting")
+
+    async def run():
+        task = asyncio.ensure_future(app.run_saga("expense", {"amount": 40}))
+        waiting = await waiting_execution()
+        assert waiting["steps"][0]["wake_at"] is not None
+        assert waiting["steps"][2]["signal_token"] == tokens[0]
+
+        # TestClient runs its own event loop, so it is called from a thread
+        response = await asyncio.to_thread(
+            client.post, f"/_saga/signals/{tokens[0]}", json={"by": "ann"}
+        )
+        assert response.status_code == 202
+        execution = await task
+        assert execution["status"] == "Completed"
+        assert execution["steps"][3]["result"] == {"paid_by": "ann"}
+
+        # A waiting execution saved before a restart is restored and resumed.
+        task = asyncio.ensure_future(app.run_saga("expense", {"amount": 50}))
+        saved = await waiting_execution()
+        restarted = App()
+        restarted.enable_saga(SagaConfig(enable_logging=False))
+        restarted.register_saga(expense)
+        restarted.restore_saga(saved)
+        with pytest.raises(ValueError):
+            restarted.restore_saga(saved)
+        resumed = asyncio.ensure_future(restarted.resume_saga(saved["id"]))
+        for _ in range(200):
+            try:
+                restarted.signal_saga(saved["steps"][2]["signal_token"], {"by": "bob"})
+                break
+            except ValueError:
+                await asyncio.sleep(0.01)
+        execution = await resumed
+        assert execution["steps"][3]["result"] == {"paid_by": "bob"}
+        assert len(tokens) == 2
+        app.signal_saga(tokens[1], {"by": "bob"})
+        await task
+
+    asyncio.run(run())
+
+
+def test_app_saga_admin():
+    """Test saga execution queries, manual repair and the admin routes."""
+    import asyncio
+    import pytest
+    from cello import App, SagaConfig
+    from cello.saga import Saga, SagaStep
+    from cello.testing import TestClient
+
+    def charge(context):
+        if context["input"]["amount"] > 100:
+            raise RuntimeError("limit exceeded")
+        return {"charged": context["input"]["amount"]}
+
+    app = App()
+    app.enable_saga(SagaConfig(max_retries=0, enable_logging=False))
+    payment = Saga(name="payment")
+    payment.add_step(SagaStep("charge", charge))
+    app.register_saga(payment)
+
+    async def run():
+        for amount in (10, 20, 500):
+            await app.run_saga("payment", {"amount": amount})
+
+    asyncio.run(run())
+
+    page = app.saga_executions(saga="payment", limit=2)
+    assert page["total"] == 3
+    assert [e["input"]["amount"] for e in page["executions"]] == [500, 20]
+    compensated = app.saga_executions(status="compensated")["executions"]
+    assert len(compensated) == 1
+    execution_id = compensated[0]["id"]
+    assert app.saga_execution(execution_id)["steps"][0]["status"] == "Failed"
+    assert app.saga_execution("missing") is None
+    with pytest.raises(ValueError):
+        app.saga_executions(status="stuck")
+    with pytest.raises(ValueError):
+        asyncio.run(app.resume_saga(execution_id))
+
+    with pytest.raises(ValueError):
+        app.enable_saga_admin()
+    app.enable_saga_admin(guards=[lambda request: request.get_header("x-operator") == "ops"])
+    routes = {(r["method"], r["path"]) for r in app._routes}
+    assert ("GET", "/_saga/executions") in routes
+    assert ("GET", "/_saga/executions/{id}") in routes
+    assert ("POST", "/_saga/executions/{id}/retry") in routes
+    assert ("POST", "/_saga/executions/{id}/compensate") in routes
+
+    client = TestClient(app)
+    operator = {"x-operator": "ops"}
+    assert client.get("/_saga/executions").status_code == 403
+    assert client.post(f"/_saga/executions/{execution_id}/compensate").status_code == 403
+    assert client.get("/_saga/executions", headers=operator).json()["total"] == 3
+    compensate = f"/_saga/executions/{execution_id}/compensate"
+    assert client.post(compensate, json=["oops"], headers=operator).status_code == 400
+    assert client.post(compensate, json={"reason": 5}, headers=operator).status_code == 400
+
+
+def test_app_export_saga():
+    """Test exporting saga definitions as a state machine and diagrams."""
+    import pytest
+    from cello import App, SagaConfig
+    from cello.saga import Saga, SagaStep
+
+    app = App()
+    app.enable_saga(SagaConfig(enable_logging=False))
+    order = Saga(name="order")
+    order.add_step(SagaStep("reserve", lambda ctx: None, compensate=lambda ctx: None))
+    order.add_step(SagaStep("charge", lambda ctx: None))
+    app.register_saga(order)
+
+    machine = app.export_saga("order")
+    assert machine["initial"] == "start"
+    assert [s["id"] for s in machine["states"]] == [
+        "start", "s0", "c0", "s1", "completed", "compensated", "failed",
+    ]
+    assert {"from": "s1", "to": "c0", "on": "failed", "kind": "failure"} in machine["transitions"]
+    assert app.export_saga("order", format="dot").startswith('digraph "order" {')
+    assert "c0 -.->|all_compensated| compensated" in app.export_saga("order", format="mermaid")
+    with pytest.raises(ValueError):
+        app.export_saga("order", format="svg")
+    with pytest.raises(ValueError):
+        app.export_saga("missing")
+
+
+def test_app_cqrs_async_handlers():
+    """Test dispatching commands and queries to sync and async Python handlers."""
+    import asyncio
+    import pytest
+    from cello import App, CqrsConfig
+    from cello.cqrs import Command, CommandResult, QueryResult
+
+    class CreateOrder(Command):
+        pass
+
+    async def create_order(command):
+        await asyncio.sleep(0)
+        if not command["data"]["items"]:
+            return CommandResult.rejected("no items")
+        return {"order_id": "order-1", "user": command["metadata"]["user"]}
+
+    async def stall(command):
+        await asyncio.sleep(1)
+
+    def get_order(query):
+        if query["params"]["id"] == "missing":
+            return None
+        return QueryResult.ok({"id": query["params"]["id"]})
+
+    app = App()
+    app.enable_cqrs(CqrsConfig(command_timeout_ms=50))
+    app.register_command_handler(CreateOrder, create_order)
+    app.register_command_handler("Stall", stall)
+    app.register_query_handler("GetOrder", get_order)
+
+    async def run():
+        created = await app.dispatch_command("CreateOrder", {"items": [1]}, {"user": "u1"})
+        assert created == {"success": True, "data": {"order_id": "order-1", "user": "u1"}, "error": None}
+        rejected = await app.dispatch_command("CreateOrder", {"items": []})
+        assert rejected["error"] == "Rejected: no items"
+        with pytest.raises(TimeoutError):
+            await app.dispatch_command("Stall")
+        with pytest.raises(ValueError):
+            await app.execute_query("Unknown")
+        results = await app.execute_queries([("GetOrder", {"id": "o1"}), ("GetOrder", {"id": "missing"})])
+        assert results[0] == {"found": True, "data": {"id": "o1"}, "error": None}
+        assert results[1]["found"] is False
+
+    asyncio.run(run())
+
+
+def test_app_cqrs_timeouts_stop_handlers():
+    """Test that a command or query that times out is stopped, sync or async."""
+    import asyncio
+    import time
+    import pytest
+    from cello import App, CqrsConfig
+
+    effects = []
+
+    def sync_charge(command):
+        time.sleep(0.5)
+        effects.append("sync")
+
+    async def async_charge(command):
+        await asyncio.sleep(0.5)
+        effects.append("async")
+
+    def report(query):
+        time.sleep(0.5)
+        effects.append("query")
+
+    app = App()
+    app.enable_cqrs(CqrsConfig(command_timeout_ms=200, query_timeout_ms=200, max_retries=0))
+    app.register_command_handler("SyncCharge", sync_charge)
+    app.register_command_handler("AsyncCharge", async_charge)
+    app.register_query_handler("Report", report)
+
+    async def run():
+        with pytest.raises(TimeoutError):
+            await app.dispatch_command("SyncCharge")
+        with pytest.raises(TimeoutError):
+            await app.dispatch_command("AsyncCharge")
+        with pytest.raises(TimeoutError):
+            await app.execute_query("Report")
+        await asyncio.sleep(0.6)
+        assert effects == []
+
+    asyncio.run(run())
+
+
+def test_app_cqrs_retries_wait_for_timed_out_attempts():
+    """Test that a timed-out command is stopped before it is retried."""
+    import asyncio
+    import time
+    import pytest
+    from cello import App, CqrsConfig
+
+    running = []
+    overlaps = []
+    effects = []
+
+    async def async_charge(command):
+        overlaps.append(len(running))
+        running.append(1)
+        try:
+            await asyncio.sleep(0.3)
+            effects.append("async")
+        finally:
+            running.pop()
+
+    def sync_charge(command):
+        overlaps.append(len(running))
+        running.append(1)
+        try:
+            time.sleep(0.3)
+            effects.append("sync")
+        finally:
+            running.pop()
+
+    app = App()
+    app.enable_cqrs(CqrsConfig(command_timeout_ms=100, max_retries=2))
+    app.register_command_handler("AsyncCharge", async_charge)
+    app.register_command_handler("SyncCharge", sync_charge)
+
+    async def run():
+        for command_type in ["AsyncCharge", "SyncCharge"]:
+            with pytest.raises(TimeoutError):
+                await app.dispatch_command(command_type)
+        await asyncio.sleep(0.5)
+        assert overlaps == [0] * 6
+        assert effects == []
+
+    asyncio.run(run())
+
+
+def test_app_cqrs_pipeline():
+    """Test the CQRS middleware pipeline: roles, validation and deduplication."""
+    import asyncio
+    import pytest
+    from cello import App
+
+    charges = []
+
+    def charge(command):
+        charges.append(command["data"]["amount"])
+        return {"charge": len(charges)}
+
+    def require_amount(data):
+        if data.get("amount", 0) <= 0:
+            raise ValueError("amount must be positive")
+
+    app = App()
+    app.enable_cqrs()
+    app.register_command_handler("Charge", charge)
+    app.register_query_handler("Report", lambda query: {"total": sum(charges)})
+    app.enable_cqrs_pipeline(
+        logging=True,
+        idempotency_ttl_secs=60,
+        roles={"Report": "admin"},
+        validators={"Charge": require_amount},
+    )
+
+    async def run():
+        first = await app.dispatch_command("Charge", {"amount": 5}, {"idempotency_key": "k1"})
+        replay = await app.dispatch_command("Charge", {"amount": 5}, {"idempotency_key": "k1"})
+        assert first == replay
+        rejected = await app.dispatch_command("Charge", {"amount": 0})
+        assert rejected["error"] == "Rejected: amount must be positive"
+        assert charges == [5]
+        with pytest.raises(ValueError):
+            await app.execute_query("Report")
+        report = await app.execute_query("Report", metadata={"roles": "admin"})
+        assert report["data"] == {"total": 5}
+
+    asyncio.run(run())
+
+
+def test_app_cqrs_event_sync():
+    """Test that events recorded by commands are appended to the event store."""
+    import asyncio
+    from cello import App, CqrsConfig
+
+    def rename(command):
+        return {"events": [
+            {"aggregate_id": "u-1", "event_type": "UserRenamed", "data": command["data"]},
+        ]}
+
+    def stale_rename(command):
+        return {"events": [
+            {"aggregate_id": "u-1", "event_type": "UserRenamed", "expected_version": 0},
+        ]}
+
+    app = App()
+    app.enable_event_sourcing()
+    app.enable_cqrs(CqrsConfig(max_retries=0))
+    app.register_command_handler("Rename", rename)
+    app.register_command_handler("StaleRename", stale_rename)
+
+    async def run():
+        first = await app.dispatch_command("Rename", {"name": "Ada"})
+        second = await app.dispatch_command("Rename", {"name": "Grace"})
+        assert first["success"] and second["success"]
+        event = second["data"]["events"][0]
+        assert event["version"] == 2
+        assert event["data"] == {"name": "Grace"}
+        assert event["metadata"]["command_type"] == "Rename"
+        stale = await app.dispatch_command("StaleRename")
+        assert not stale["success"]
+
+    asyncio.run(run())
+
+
+def test_app_scheduled_jobs():
+    """Test registering and running scheduled jobs."""
+    import asyncio
+    import pytest
+    from cello import App
+
+    runs = []
+    app = App()
+
+    @app.schedule(cron="*/5 * * * *", overlap="queue")
+    async def cleanup():
+        runs.append("cleanup")
+
+    @app.schedule(every=30, name="refresh", jitter=2)
+    def refresh_rates():
+        raise RuntimeError("rates unavailable")
+
+    with pytest.raises(ValueError):
+        app.schedule(cron="61 * * * *")(lambda: None)
+    with pytest.raises(ValueError):
+        app.schedule(cron="@daily", every=10)(lambda: None)
+    with pytest.raises(ValueError):
+        app.schedule(every=10, overlap="sometimes")(lambda: None)
+    with pytest.raises(ValueError):
+        app.schedule(every=10, name="cleanup")(lambda: None)
+
+    async def run():
+        assert await app.run_job("cleanup") is True
+        assert await app.run_job("refresh") is True
+        with pytest.raises(KeyError):
+            await app.run_job("missing")
+
+    asyncio.run(run())
+    assert runs == ["cleanup"]
+
+    jobs = {job["name"]: job for job in app.scheduled_jobs()}
+    assert jobs["cleanup"]["schedule"] == "*/5 * * * *"
+    assert jobs["cleanup"]["overlap"] == "queue"
+    assert jobs["cleanup"]["runs"] == 1
+    assert jobs["refresh"]["schedule"] == "every 30s"
+    assert jobs["refresh"]["failures"] == 1
+    assert "rates unavailable" in jobs["refresh"]["last_error"]
+
+
+def test_app_distributed_locks():
+    """Test distributed locks as async context managers."""
+    import asyncio
+    import pytest
+    from cello import App, RedisConfig
+
+    app = App()
+    with pytest.raises(RuntimeError):
+        app.lock("jobs")
+    with pytest.raises(RuntimeError):
+        app.enable_distributed_locks(backend="redis")
+    with pytest.raises(ValueError):
+        app.enable_distributed_locks(backend="zookeeper")
+
+    # The in-process Redis client would give each worker its own locks.
+    app.enable_redis(RedisConfig())
+    with pytest.raises(RuntimeError):
+        app.enable_distributed_locks(backend="redis")
+    app.enable_distributed_locks(backend="memory", ttl=5, timeout=0.2)
+
+    async def run():
+        async with app.lock("jobs") as held:
+            assert held.key == "jobs"
+            assert held.held
+            with pytest.raises(TimeoutError):
+                async with app.lock("jobs", timeout=0):
+                    pass
+        assert not held.held
+        async with app.lock("jobs", timeout=0):
+            pass
+
+        lock = app.lock("reports")
+        await lock.acquire()
+        assert lock.held
+        assert lock.release() is True
+        assert lock.release() is False
+
+    asyncio.run(run())
+
+
+def test_app_lifecycle_hook_policies():
+    """Test startup hook timeouts and error policies."""
+    import socket
+    import pytest
+    from cello import App
+
+    calls = []
+    app = App()
+
+    @app.on_event("startup", timeout=5.0, on_error="abort")
+    def connect():
+        calls.append("connect")
+        raise RuntimeError("database unavailable")
+
+    @app.on_event("startup")
+    def warm_cache():
+        calls.append("warm_cache")
+
+    @app.on_event("shutdown")
+    def disconnect():
+        calls.append("disconnect")
+
+    with pytest.raises(ValueError):
+        app.on_event("startup", on_error="ignore")(lambda: None)
+    with pytest.raises(ValueError):
+        app.on_event("shutdown", timeout=-1)(lambda: None)
+
+    with socket.socket() as sock:
+        sock.bind(("127.0.0.1", 0))
+        port = sock.getsockname()[1]
+
+    # The aborting startup hook fails the run before any connection is served.
+    with pytest.raises(RuntimeError):
+        app._app.run("127.0.0.1", port, None)
+    assert calls == ["connect"]
+
+
+def test_app_reload_routes(tmp_path):
+    """Test re-running an app script to collect its routes for reload."""
+    import pytest
+    from cello import App
+    from cello._cello import Cello
+
+    (tmp_path / "helpers.py").write_text("GREETING = 'hello'\n")
+    script = tmp_path / "app.py"
+    script.write_text(
+        "from cello import App\n"
+        "from helpers import GREETING\n"
+        "app = App()\n"
+        "@app.get('/')\n"
+        "def index(request):\n"
+        "    return {'greeting': GREETING}\n"
+        "app.run()\n"
+    )
+
+    import sys
+    sys.path.insert(0, str(tmp_path))
+    try:
+        reloaded = App._reload_routes(str(script), str(tmp_path))
+        assert isinstance(reloaded, Cello)
+        assert not App._reloading
+
+        # Edited helper modules are imported afresh.
+        (tmp_path / "helpers.py").write_text("GREETING = 'hi'\n")
+        App._reload_routes(str(script), str(tmp_path))
+        assert sys.modules["helpers"].GREETING == "hi"
+
+        script.write_text("print('no app here')\n")
+        with pytest.raises(RuntimeError):
+            App._reload_routes(str(script), str(tmp_path))
+    finally:
+        sys.path.remove(str(tmp_path))
+        sys.modules.pop("helpers", None)
+
+
+def test_app_test_client():
+    """Test sending in-process requests and WebSocket messages."""
+    import pytest
+    from cello import App
+    from cello.testing import TestClient
+
+    app = App()
+
+    @app.get("/users/{id}")
+    def get_user(request):
+        return {"id": request.params["id"], "fields": request.query.get("fields")}
+
+    @app.post("/echo")
+    async def echo(request):
+        return {"body": request.json(), "token": request.get_header("x-token")}
+
+    @app.websocket("/ws")
+    def chat(ws):
+        while (msg := ws.recv()) is not None:
+            if msg.text == "boom":
+                raise ValueError("boom")
+            ws.send_text(f"Echo: {msg.text}")
+
+    client = TestClient(app, headers={"X-Token": "abc"})
+
+    resp = client.get("/users/7", params={"fields": "name"})
+    assert resp.status_code == 200
+    assert resp.headers["content-type"] == "application/json"
+    assert resp.json() == {"id": "7", "fields": "name"}
+
+    resp = client.post("/echo", json={"n": 1})
+    assert resp.json() == {"body": {"n": 1}, "token": "abc"}
+
+    assert client.get("/missing").status_code == 404
+    assert client.head("/users/7").content == b""
+
+    with client.websocket_connect("/ws") as ws:
+        ws.send_text("hi")
+        assert ws.receive_text() == "Echo: hi"
+        with pytest.raises(TimeoutError):
+            ws.receive(timeout=0.05)
+
+    ws = client.websocket_connect("/ws")
+    ws.send_text("boom")
+    assert ws.receive() is None
+    with pytest.raises(RuntimeError):
+        ws.close()
+
+    with pytest.raises(ValueError):
+        client.websocket_connect("/nope")
+    client.close()
+
+
+def test_app_websocket_heartbeats():
+    """Test WebSocket pings, size limits and liveness info."""
+    import time
+
+    import pytest
+    from cello import App
+    from cello.testing import TestClient
+
+    app = App()
+    app.configure_websockets(ping_interval=0.02, pong_timeout=0.05, max_message_size=8)
+    seen = []
+
+    @app.websocket("/ws")
+    def handler(ws):
+        while (msg := ws.recv()) is not None:
+            seen.append(ws.liveness())
+            ws.send_text(msg.text)
+
+    client = TestClient(app)
+    with client.websocket_connect("/ws") as ws:
+        # Waiting in receive() answers the pings, so the connection stays open.
+        with pytest.raises(TimeoutError):
+            ws.receive(timeout=0.1)
+        ws.send_text("hi")
+        assert ws.receive_text() == "hi"
+        assert seen[-1]["open"] and seen[-1]["pings_sent"] >= 1
+        assert seen[-1]["pongs_received"] >= 1
+        assert len(app.websocket_connections()) == 1
+
+        ws.send_text("far too long")
+        assert ws.receive() is None
+        assert ws.close_reason == "message_too_big"
+
+    # A client that never reads never answers pings and is reaped.
+    ws = client.websocket_connect("/ws")
+    deadline = time.time() + 5
+    while not ws.closed and time.time() < deadline:
+        time.sleep(0.01)
+    assert ws.close_reason == "pong_timeout"
+    ws.close()
+    assert app.websocket_connections() == []
+
+
+def test_app_websocket_compression():
+    """Test permessage-deflate negotiation and compressed messages."""
+    import pytest
+    from cello import App
+    from cello.testing import TestClient
+
+    app = App()
+    seen = []
+
+    @app.websocket("/ws")
+    def handler(ws):
+        seen.append(ws.extensions)
+
+    @app.websocket("/echo")
+    def echo(ws):
+        while (msg := ws.recv()) is not None:
+            ws.send_text(msg.text * 2)
+
+    client = TestClient(app)
+    with client.websocket_connect("/ws", extensions="permessage-deflate") as ws:
+        assert ws.extensions is None
+
+    app.configure_websockets(compression=True, server_max_window_bits=12)
+    with client.websocket_connect(
+        "/ws", extensions="permessage-deflate; client_max_window_bits"
+    ) as ws:
+        assert ws.extensions == "permessage-deflate; server_max_window_bits=12"
+    # An 8-bit server window can't be honoured, so the offer is declined.
+    with client.websocket_connect(
+        "/ws", extensions="permessage-deflate; server_max_window_bits=8"
+    ) as ws:
+        assert ws.extensions is None
+    assert seen == [None, "permessage-deflate; server_max_window_bits=12", None]
+
+    # Messages over the threshold travel compressed, and the size limit
+    # applies once they are inflated.
+    app.configure_websockets(compression=True, compression_threshold=16, max_message_size=2000)
+    with client.websocket_connect("/echo", extensions="permessage-deflate") as ws:
+        for text in ["hi", '{"status": "online"}' * 50, '{"status": "online"}' * 50]:
+            ws.send_text(text)
+            assert ws.receive().text == text * 2
+        ws.send_text("x" * 5000)
+        assert ws.receive() is None
+        assert ws.close_reason == "message_too_big"
+
+    with pytest.raises(ValueError):
+        app.configure_websockets(compression=True, client_max_window_bits=8)
+
+
+def test_app_websocket_auth():
+    """Test guards on WebSocket upgrades, token refresh and expiry."""
+    import time
+
+    import pytest
+    from cello import App
+    from cello.testing import TestClient, WebSocketDenied
+
+    app = App()
+    app.add_guard(
+        lambda req: req.get_header("authorization") in ("Bearer good", "Bearer fresh"),
+        prefix="/ws",
+    )
+
+    @app.websocket("/ws")
+    def handler(ws):
+        ws.send_text(ws.request.get_header("authorization"))
+        while (msg := ws.recv()) is not None:
+            if msg.text == "expire":
+                ws.refresh(expires_in=0.05)
+            else:
+                ws.send_text(str(ws.refresh(msg.text)))
+
+    client = TestClient(app)
+    with pytest.raises(WebSocketDenied) as denied:
+        client.websocket_connect("/ws")
+    assert denied.value.status_code == 403
+
+    ws = client.websocket_connect("/ws", headers={"Authorization": "Bearer good"})
+    assert ws.receive_text() == "Bearer good"
+    ws.send_text("bad")
+    assert ws.receive_text() == "False"
+    ws.send_text("fresh")
+    assert ws.receive_text() == "True"
+
+    ws.send_text("expire")
+    deadline = time.time() + 5
+    while not ws.closed and time.time() < deadline:
+        time.sleep(0.01)
+    assert ws.close_reason == "token_expired"
+    ws.close()
+
+    with pytest.raises(ValueError):
+        app.configure_websockets(on_token_expiry="warn")
+
+
+def test_app_websocket_server(tmp_path):
+    """Test WebSocket upgrades and compression on the built-in server."""
+    import base64
+    import os
+    import socket
+    import struct
+    import subprocess
+    import sys
+    import time
+    import zlib
+
+    with socket.socket() as sock:
+        sock.bind(("127.0.0.1", 0))
+        port = sock.getsockname()[1]
+
+    script = tmp_path / "app.py"
+    script.write_text(
+        "from cello import App\n"
+        "app = App()\n"
+        "app.configure_websockets(compression=True, compression_threshold=16)\n"
+        "app.add_guard(lambda req: req.get_header('authorization') == 'Bearer good', prefix='/ws')\n"
+        "@app.websocket('/ws')\n"
+        "def echo(ws):\n"
+        "    ws.send_text(ws.request.query.get('room', ''))\n"
+        "    while (msg := ws.recv()) is not None:\n"
+        "        ws.send_text('Echo: ' + msg.text)\n"
+        f"app.run(host='127.0.0.1', port={port})\n"
+    )
+    env = {**os.environ, "PYTHONPATH": os.pathsep.join(sys.path)}
+    server = subprocess.Popen(
+        [sys.executable, str(script)], env=env,
+        stdout=subprocess.DEVNULL, stderr=subprocess.DEVNULL,
+    )
+
+    def connect(path, headers=""):
+        key = base64.b64encode(os.urandom(16)).decode()
+        sock = socket.create_connection(("127.0.0.1", port), timeout=5)
+        sock.sendall(
+            f"GET {path} HTTP/1.1\r\nHost: localhost\r\nConnection: Upgrade\r\n"
+            f"Upgrade: websocket\r\nSec-WebSocket-Version: 13\r\n"
+            f"Sec-WebSocket-Key: {key}\r\n{headers}\r\n".encode()
+        )
+        head = b""
+        while b"\r\n\r\n" not in head:
+            chunk = sock.recv(1)
+            if not chunk:
+                break
+            head += chunk
+        return sock, head.decode()
+
+    def send(sock, opcode, payload, compressed=False):
+        mask = os.urandom(4)
+        masked = bytes(b ^ mask[i % 4] for i, b in enumerate(payload))
+        first = 0x80 | (0x40 if compressed else 0) | opcode
+        sock.sendall(struct.pack("!BB", first, 0x80 | len(payload)) + mask + masked)
+
+    def recv_exact(sock, n):
+        data = b""
+        while len(data) < n:
+            chunk = sock.recv(n - len(data))
+            assert chunk, "connection closed"
+            data += chunk
+        return data
+
+    def receive(sock):
+        first, second = recv_exact(sock, 2)
+        length = second & 0x7F
+        if length == 126:
+            length = struct.unpack("!H", recv_exact(sock, 2))[0]
+        return first & 0x0F, recv_exact(sock, length), bool(first & 0x40)
+
+    try:
+        for _ in range(100):
+            try:
+                sock, head = connect("/ws?room=lobby")
+                break
+            except OSError:
+                time.sleep(0.05)
+        else:
+            raise AssertionError("server did not start")
+        # The guard rejects the upgrade before the handler runs.
+        assert head.startswith("HTTP/1.1 403")
+        sock.close()
+
+        sock, head = connect("/ws?room=lobby", "Authorization: Bearer good\r\n")
+        assert head.startswith("HTTP/1.1 101")
+        assert "sec-websocket-accept" in head.lower()
+        assert "sec-websocket-extensions" not in head.lower()
+        assert receive(sock) == (0x1, b"lobby", False)
+        send(sock, 0x1, b"hi")
+        assert receive(sock) == (0x1, b"Echo: hi", False)
+        send(sock, 0x9, b"")
+        assert receive(sock) == (0xA, b"", False)
+        send(sock, 0x8, struct.pack("!H", 1000))
+        assert receive(sock) == (0x8, struct.pack("!H", 1000), False)
+        sock.close()
+
+        # With permessage-deflate agreed, messages over the threshold travel
+        # compressed both ways, sharing their window across messages.
+        sock, head = connect(
+            "/ws", "Authorization: Bearer good\r\n"
+            "Sec-WebSocket-Extensions: permessage-deflate; client_max_window_bits\r\n"
+        )
+        assert "sec-websocket-extensions: permessage-deflate\r\n" in head.lower()
+        assert receive(sock) == (0x1, b"", False)
+        deflate = zlib.compressobj(wbits=-15)
+        inflate = zlib.decompressobj(wbits=-15)
+        for text in [b'{"status": "online"}' * 4] * 2:
+            payload = deflate.compress(text) + deflate.flush(zlib.Z_SYNC_FLUSH)
+            send(sock, 0x1, payload[:-4], compressed=True)
+            opcode, payload, compressed = receive(sock)
+            assert (opcode, compressed) == (0x1, True)
+            assert inflate.decompress(payload + b"\x00\x00\xff\xff") == b"Echo: " + text
+        send(sock, 0x1, b"hi")
+        assert receive(sock) == (0x1, b"Echo: hi", False)
+        sock.close()
+    finally:
+        server.terminate()
+        server.wait(timeout=10)
+
+def test_app_jsonrpc():
+    """Test JSON-RPC 2.0 calls, batches, notifications and guards."""
+    from cello import App
+    from cello.jsonrpc import JsonRpcError, JsonRpcRouter
+    from cello.testing import TestClient
+
+    app = App()
+    rpc = JsonRpcRouter("/rpc")
+    notified = []
+
+    @rpc.method()
+    def add(a: int, b: int) -> int:
+        return a + b
+
+    @rpc.method("users.get")
+    async def get_user(id: int) -> dict:
+        if id != 1:
+            raise JsonRpcError(-32004, "User not found", {"id": id})
+        return {"id": 1, "name": "Alice"}
+
+    @rpc.method()
+    def notify(event: str):
+        notified.append(event)
+
+    def token_guard(request):
+        return request.get_header("x-token") == "s3cret"
+
+    @rpc.method(guards=[token_guard])
+    def secret(request) -> str:
+        return request.get_header("x-token")
+
+    @rpc.method()
+    def crash():
+        raise RuntimeError("boom")
+
+    app.register_jsonrpc(rpc)
+    client = TestClient(app)
+
+    def call(payload, **kwargs):
+        return client.post("/rpc", json=payload, **kwargs)
+
+    res = call({"jsonrpc": "2.0", "method": "add", "params": [1, 2], "id": 1})
+    assert res.json() == {"jsonrpc": "2.0", "result": 3, "id": 1}
+    res = call({"jsonrpc": "2.0", "method": "users.get", "params": {"id": 1}, "id": "a"})
+    assert res.json()["result"] == {"id": 1, "name": "Alice"}
+    res = call({"jsonrpc": "2.0", "method": "users.get", "params": {"id": 2}, "id": 2})
+    assert res.json()["error"] == {"code": -32004, "message": "User not found", "data": {"id": 2}}
+
+    # Standard errors
+    assert call({"jsonrpc": "2.0", "method": "nope", "id": 3}).json()["error"]["code"] == -32601
+    assert call({"jsonrpc": "2.0", "method": "add", "params": [1], "id": 4}).json()["error"]["code"] == -32602
+    assert call({"jsonrpc": "2.0", "method": "crash", "id": 5}).json()["error"] == {
+        "code": -32603,
+        "message": "Internal error",
+    }
+    assert call({"method": "add", "id": 6}).json()["error"]["code"] == -32600
+    assert client.post("/rpc", data="{not json").json() == {
+        "jsonrpc": "2.0",
+        "error": {"code": -32700, "message": "Parse error"},
+        "id": None,
+    }
+    assert call([]).json()["error"]["code"] == -32600
+
+    # Guards
+    assert call({"jsonrpc": "2.0", "method": "secret", "id": 7}).json()["error"]["code"] == -32001
+    res = call({"jsonrpc": "2.0", "method": "secret", "id": 8}, headers={"X-Token": "s3cret"})
+    assert res.json()["result"] == "s3cret"
+
+    # Notifications get no response
+    res = call({"jsonrpc": "2.0", "method": "notify", "params": ["one"]})
+    assert res.status_code == 204
+    assert notified == ["one"]
+
+    # Batches answer every call but notifications
+    res = call([
+        {"jsonrpc": "2.0", "method": "add", "params": [2, 3], "id": 1},
+        {"jsonrpc": "2.0", "method": "notify", "params": ["two"]},
+        {"jsonrpc": "2.0", "method": "nope", "id": 2},
+        1,
+    ])
+    body = res.json()
+    assert len(body) == 3
+    assert body[0] == {"jsonrpc": "2.0", "result": 5, "id": 1}
+    assert body[1]["error"]["code"] == -32601
+    assert body[2] == {"jsonrpc": "2.0", "error": {"code": -32600, "message": "Invalid Request"}, "id": None}
+    assert notified == ["one", "two"]
+    assert rpc.methods == ["add", "crash", "notify", "secret", "users.get"]
+
+
+def test_app_webhooks():
+    """Test signed webhook delivery, retries and domain event fan-out."""
+    import asyncio
+    import hashlib
+    import hmac
+    import json
+    import threading
+    import time
+    from http.server import BaseHTTPRequestHandler, HTTPServer
+
+    import pytest
+    from cello import App
+
+    received = []
+    failures = {"/flaky": 1}
+
+    class Handler(BaseHTTPRequestHandler):
+        def do_POST(self):
+            body = self.rfile.read(int(self.headers["Content-Length"]))
+            received.append((self.path, self.headers, body))
+            status = 500 if failures.get(self.path, 0) > 0 else 200
+            failures[self.path] = failures.get(self.path, 0) - 1
+            self.send_response(status)
+            self.send_header("Content-Length", "0")
+            self.end_headers()
+
+        def log_message(self, *args):
+            pass
+
+    server = HTTPServer(("127.0.0.1", 0), Handler)
+    threading.Thread(target=server.serve_forever, daemon=True).start()
+    base = f"http://127.0.0.1:{server.server_port}"
+
+    app = App()
+    with pytest.raises(RuntimeError):
+        app.register_webhook(f"{base}/orders", ["*"])
+    app.enable_event_sourcing()
+    app.enable_cqrs()
+    app.enable_webhooks(max_attempts=3, initial_backoff=0.01, max_backoff=0.02)
+    app.register_webhook(f"{base}/orders", ["order.*", "OrderPlaced"], secret="whsec", id="orders")
+    app.register_webhook(f"{base}/flaky", ["order.created"], id="flaky")
+    with pytest.raises(ValueError):
+        app.register_webhook("ftp://example.com", ["*"])
+
+    try:
+        ids = app.dispatch_webhook("order.created", {"id": 42})
+        assert len(ids) == 2
+        asyncio.run(app.drain_webhooks())
+
+        path, headers, body = next(r for r in received if r[0] == "/orders")
+        assert headers["X-Webhook-Event"] == "order.created"
+        assert json.loads(body)["data"] == {"id": 42}
+        timestamp, signature = headers["X-Webhook-Signature"].split(",v1=")
+        expected = hmac.new(
+            b"whsec", timestamp[2:].encode() + b"." + body, hashlib.sha256
+        ).hexdigest()
+        assert hmac.compare_digest(signature, expected)
+
+        flaky = next(d for d in app.webhook_deliveries() if d["endpoint_id"] == "flaky")
+        assert flaky["id"] in ids
+        assert app.webhook_delivery(flaky["id"])["status"] == "delivered"
+        assert [a["status_code"] for a in flaky["attempts"]] == [500, 200]
+
+        # Domain events appended through CQRS fan out to subscribed endpoints.
+        app.register_command_handler(
+            "PlaceOrder",
+            lambda command: {
+                "events": [{"aggregate_id": "order-7", "event_type": "OrderPlaced", "data": {"total": 3}}]
+            },
+        )
+        asyncio.run(app.dispatch_command("PlaceOrder", {}))
+        deadline = time.time() + 5
+        while app.webhook_stats()["delivered"] < 3 and time.time() < deadline:
+            time.sleep(0.01)
+        event = json.loads(received[-1][2])
+        assert event["type"] == "OrderPlaced"
+        assert event["data"]["aggregate_id"] == "order-7"
+
+        stats = app.webhook_stats()
+        assert stats == {
+            "endpoints": 2,
+            "dispatched": 3,
+            "delivered": 3,
+            "failed": 0,
+            "in_flight": 0,
+            "attempts": 4,
+        }
+        assert app.unregister_webhook("flaky") is True
+        assert [e["id"] for e in app.webhook_endpoints()] == ["orders"]
+    finally:
+        server.shutdown()
+
+
+def test_app_verify_webhook():
+    """Test inbound webhook signature verification on a route."""
+    import hashlib
+    import hmac
+    import time
+
+    from cello import App, verify_webhook
+    from cello.testing import TestClient
+
+    app = App()
+    seen = []
+
+    @app.post("/hooks/stripe")
+    @verify_webhook("stripe", secret=["whsec_new", "whsec_old"])
+    def stripe_hook(request):
+        seen.append(request.json())
+        return {"ok": True}
+
+    @app.post("/hooks/github")
+    @verify_webhook("github", secret="gh", replay_window=None)
+    def github_hook(request):
+        return {"ok": True}
+
+    client = TestClient(app)
+    body = b'{"id": "evt_1"}'
+
+    def stripe_headers(secret, timestamp):
+        digest = hmac.new(secret.encode(), f"{timestamp}.".encode() + body, hashlib.sha256)
+        return {"Stripe-Signature": f"t={timestamp},v1={digest.hexdigest()}"}
+
+    now = int(time.time())
+    res = client.post("/hooks/stripe", data=body, headers=stripe_headers("whsec_old", now))
+    assert res.status_code == 200
+    assert seen == [{"id": "evt_1"}]
+
+    # Replays, stale timestamps, wrong secrets and missing headers never reach Python.
+    assert client.post("/hooks/stripe", data=body, headers=stripe_headers("whsec_old", now)).status_code == 401
+    assert client.post("/hooks/stripe", data=body, headers=stripe_headers("whsec_new", now - 3600)).status_code == 401
+    assert client.post("/hooks/stripe", data=body, headers=stripe_headers("nope", now)).status_code == 401
+    assert client.post("/hooks/stripe", data=body).status_code == 401
+    assert len(seen) == 1
+
+    signature = "sha256=" + hmac.new(b"gh", body, hashlib.sha256).hexdigest()
+    for _ in range(2):
+        res = client.post("/hooks/github", data=body, headers={"X-Hub-Signature-256": signature})
+        assert res.status_code == 200
+
+
+def test_app_idempotency_keys():
+    """Test idempotency keys replaying the first response to retries."""
+    import pytest
+    from cello import App, RedisConfig, Response, idempotent
+    from cello.testing import TestClient
+
+    # Keys in the in-process Redis client would not be shared by workers.
+    shared = App()
+    shared.enable_redis(RedisConfig())
+    with pytest.raises(RuntimeError):
+        shared.enable_idempotency(backend="redis")
+
+    app = App()
+    app.enable_idempotency(paths=["/api/"])
+    calls = []
+
+    @app.post("/payments")
+    @idempotent(required=True)
+    def create_payment(request):
+        calls.append(request.json())
+        if request.json().get("fail"):
+            return Response.json({"error": "gateway down"}, status=502)
+        return Response.json({"id": len(calls)}, status=201)
+
+    @app.post("/api/orders")
+    def create_order(request):
+        calls.append("order")
+        return {"order": len(calls)}
+
+    client = TestClient(app)
+    headers = {"Idempotency-Key": "pay-1"}
+    first = client.post("/payments", json={"amount": 5}, headers=headers)
+    assert first.status_code == 201
+    assert first.json() == {"id": 1}
+
+    retry = client.post("/payments", json={"amount": 5}, headers=headers)
+    assert retry.status_code == 201
+    assert retry.json() == {"id": 1}
+    assert retry.headers.get("idempotent-replayed") == "true"
+    assert len(calls) == 1
+
+    # Same key for a different payload, and no key at all
+    assert client.post("/payments", json={"amount": 6}, headers=headers).status_code == 422
+    assert client.post("/payments", json={"amount": 5}).status_code == 400
+
+    # Server errors are not stored, so the retry runs again
+    headers = {"Idempotency-Key": "pay-2"}
+    assert client.post("/payments", json={"fail": True}, headers=headers).status_code == 502
+    assert client.post("/payments", json={"fail": True}, headers=headers).status_code == 502
+    assert len(calls) == 3
+
+    # App-wide checking under a path prefix
+    headers = {"Idempotency-Key": "order-1"}
+    assert client.post("/api/orders", json={}, headers=headers).json() == {"order": 4}
+    assert client.post("/api/orders", json={}, headers=headers).json() == {"order": 4}
+    assert client.post("/api/orders", json={}).json() == {"order": 5}
+
+    # Keys are per caller, so another caller's retry runs the handler
+    alice = {"Idempotency-Key": "pay-3", "Authorization": "Bearer alice"}
+    bob = {"Idempotency-Key": "pay-3", "Authorization": "Bearer bob"}
+    assert client.post("/payments", json={"amount": 7}, headers=alice).json() == {"id": 6}
+    other = client.post("/payments", json={"amount": 7}, headers=bob)
+    assert other.json() == {"id": 7}
+    assert other.headers.get("idempotent-replayed") is None
+
+    unconfigured = App()
+    try:
+        @unconfigured.post("/x")
+        @idempotent()
+        def x(request):
+            return {}
+    except RuntimeError as e:
+        assert "enable_idempotency" in str(e)
+    else:
+        raise AssertionError("expected RuntimeError")
+
+
+def test_app_traffic_mirroring():
+    """Test mirroring requests to a shadow upstream without affecting responses."""
+    import threading
+    import time
+    from http.server import BaseHTTPRequestHandler, ThreadingHTTPServer
+
+    from cello import App
+    from cello.testing import TestClient
+
+    seen = []
+
+    class Shadow(BaseHTTPRequestHandler):
+        def do_POST(self):
+            length = int(self.headers.get("Content-Length", 0))
+            seen.append((self.command, self.path, self.headers.get("X-Mirrored-From"),
+                         self.rfile.read(length)))
+            self.send_response(500)
+            self.end_headers()
+
+        do_GET = do_POST
+
+        def log_message(self, *args):
+            pass
+
+    shadow = ThreadingHTTPServer(("127.0.0.1", 0), Shadow)
+    threading.Thread(target=shadow.serve_forever, daemon=True).start()
+
+    app = App()
+    app.enable_mirroring(f"http://127.0.0.1:{shadow.server_port}", exclude_paths=["/health"])
+
+    @app.post("/orders")
+    def create_order(request):
+        return {"created": request.json()["id"]}
+
+    @app.get("/health")
+    def health(request):
+        return {"ok": True}
+
+    client = TestClient(app)
+    try:
+        res = client.post("/orders?dry=1", json={"id": 7}, headers={"Host": "api.example.com"})
+        assert res.status_code == 200
+        assert res.json() == {"created": 7}
+        assert client.get("/health").status_code == 200
+
+        for _ in range(100):
+            if app.mirror_stats()["in_flight"] == 0 and seen:
+                break
+            time.sleep(0.02)
+        assert seen == [("POST", "/orders?dry=1", "api.example.com", b'{"id": 7}')]
+        assert app.mirror_stats() == {"mirrored": 1, "dropped": 0, "failed": 0, "in_flight": 0}
+    finally:
+        shadow.shutdown()
+
+    try:
+        App().enable_mirroring("users-v2:8000")
+    except ValueError:
+        pass
+    else:
+        raise AssertionError("expected ValueError")
+
+
+def test_app_route_middleware():
+    """Test per-route and per-group Python middleware and its ordering."""
+    from cello import App, Blueprint, Response, use_middleware
+    from cello.testing import TestClient
+
+    calls = []
+
+    class Tag:
+        def __init__(self, name):
+            self.__name__ = name
+
+        def before(self, request):
+            calls.append(f"before:{self.__name__}")
+
+        def after(self, request, response):
+            calls.append(f"after:{self.__name__}")
+            response.set_header(f"X-{self.__name__}", "1")
+
+    def block(request):
+        if request.get_header("x-block"):
+            return Response.json({"blocked": True}, status=403)
+
+    app = App()
+    app.add_middleware(Tag("app"))
+    app.add_middleware(Tag("early"), prefix="/api", priority=-10)
+
+    api = Blueprint("/api", middleware=[Tag("group")])
+
+    @api.get("/items")
+    @use_middleware(Tag("route"), block)
+    def items(request):
+        calls.append("handler")
+        return {"items": []}
+
+    app.register_blueprint(api)
+
+    @app.get("/health")
+    def health(request):
+        return {"ok": True}
+
+    assert app.route_middleware("GET", "/api/items") == ["early", "app", "group", "route", "block"]
+    assert app.route_middleware("GET", "/health") == ["app"]
+
+    client = TestClient(app)
+    res = client.get("/api/items")
+    assert res.status_code == 200
+    assert res.headers.get("x-route") == "1"
+    assert res.headers.get("x-early") == "1"
+    assert calls == [
+        "before:early", "before:app", "before:group", "before:route", "handler",
+        "after:route", "after:group", "after:app", "after:early",
+    ]
+
+    calls.clear()
+    res = client.get("/api/items", headers={"X-Block": "1"})
+    assert res.status_code == 403
+    assert "handler" not in calls
+
+    calls.clear()
+    assert client.get("/health").headers.get("x-app") == "1"
+    assert calls == ["before:app", "after:app"]
+
+    with pytest.raises(TypeError):
+        app.add_middleware(42)
+    with pytest.raises(ValueError):
+        app._app.add_route_middleware("GET", "/missing", block)
+
+
+def test_app_route_disable():
+    """Test disabling and enabling routes at runtime with an audit log."""
+    import time
+
+    from cello import App
+    from cello.testing import TestClient
+
+    app = App()
+
+    @app.get("/search")
+    def search(request):
+        return {"results": []}
+
+    @app.post("/exports")
+    def export(request):
+        return {"queued": True}
+
+    client = TestClient(app)
+    started = time.time()
+    app.disable_route("get", "/search", retry_after=120, reason="timeouts", actor="alice")
+    res = client.get("/search")
+    assert res.status_code == 503
+    assert res.headers.get("retry-after") == "120"
+    assert client.post("/exports").status_code == 200
+
+    app.disable_route("POST", "/exports", status=404)
+    assert client.post("/exports").status_code == 404
+    assert app.disabled_routes() == [
+        {"method": "GET", "path": "/search", "status": 503, "retry_after": 120},
+        {"method": "POST", "path": "/exports", "status": 404, "retry_after": None},
+    ]
+
+    app.enable_route("GET", "/search", reason="fixed")
+    assert client.get("/search").json() == {"results": []}
+    assert [r["path"] for r in app.disabled_routes()] == ["/exports"]
+
+    toggles = app.route_toggles()
+    assert [(t["path"], t["enabled"], t["status"]) for t in toggles] == [
+        ("/search", False, 503),
+        ("/exports", False, 404),
+        ("/search", True, None),
+    ]
+    assert toggles[0]["reason"] == "timeouts"
+    assert toggles[0]["actor"] == "alice"
+    assert toggles[0]["timestamp"] >= started - 1
+
+    with pytest.raises(ValueError):
+        app.disable_route("GET", "/search", status=500)
+    with pytest.raises(ValueError):
+        app.disable_route("GET", "/missing")
+
+
+def test_app_fault_injection():
+    """Test injecting latency, errors and aborted connections."""
+    import time
+
+    from cello import App
+    from cello.testing import TestClient
+
+    app = App()
+
+    @app.get("/api/orders")
+    def orders(request):
+        return {"orders": []}
+
+    @app.get("/health")
+    def health(request):
+        return {"ok": True}
+
+    client = TestClient(app)
+    try:
+        app.add_fault("error")
+    except RuntimeError:
+        pass
+    else:
+        raise AssertionError("expected RuntimeError")
+
+    app.enable_fault_injection()
+    assert client.get("/api/orders").status_code == 200
+
+    app.add_fault("latency", delay=0.05, paths=["/api/"])
+    app.add_fault("error", status=503, header="X-Chaos", header_value="on")
+    started = time.monotonic()
+    assert client.get("/api/orders").status_code == 200
+    assert time.monotonic() - started >= 0.05
+
+    res = client.get("/health", headers={"X-Chaos": "on"})
+    assert res.status_code == 503
+    assert res.headers.get("x-fault-injected") == "error"
+    assert client.get("/health", headers={"X-Chaos": "off"}).status_code == 200
+
+    app.clear_faults()
+    app.add_fault("abort", methods=["GET"], exclude_paths=["/health"])
+    with pytest.raises(Exception):
+        client.get("/api/orders")
+    assert client.get("/health").status_code == 200
+
+    assert app.fault_stats() == {"delayed": 1, "errors": 1, "aborted": 1}
+    app.clear_faults()
+    assert client.get("/api/orders").status_code == 200
+
+    with pytest.raises(ValueError):
+        app.add_fault("latency")
+    with pytest.raises(ValueError):
+        app.add_fault("explode")
+
+
+def test_app_canary_routing():
+    """Test canary routing between two versions of a route's handler."""
+    from cello import App, Response
+    from cello.testing import TestClient
+
+    app = App()
+
+    @app.get("/search")
+    def search(request):
+        return {"version": "stable"}
+
+    @app.canary("GET", "/search", weight=0, cookie="canary")
+    def search_v2(request):
+        if request.query.get("fail"):
+            return Response.json({"error": "boom"}, status=500)
+        return {"version": "canary"}
+
+    client = TestClient(app)
+    assert client.get("/search").json() == {"version": "stable"}
+    assert client.get("/search", headers={"X-Canary": "1"}).json() == {"version": "canary"}
+    assert client.get("/search", headers={"Cookie": "canary=true"}).json() == {"version": "canary"}
+    assert client.get("/search?fail=1", headers={"X-Canary": "canary"}).status_code == 500
+
+    app.set_canary_weight("GET", "/search", 100)
+    assert client.get("/search").json() == {"version": "canary"}
+    assert client.get("/search", headers={"X-Canary": "stable"}).json() == {"version": "stable"}
+
+    stats = app.canary_stats("GET", "/search")
+    assert stats["weight"] == 100
+    assert stats["stable"]["requests"] == 2
+    assert stats["stable"]["errors"] == 0
+    assert stats["canary"]["requests"] == 4
+    assert stats["canary"]["errors"] == 1
+    assert stats["canary"]["error_rate"] == 0.25
+
+    with pytest.raises(ValueError):
+        app.set_canary_weight("GET", "/search", 150)
+    with pytest.raises(ValueError):
+        app.canary_stats("GET", "/missing")
+
+
+def test_app_nested_dependencies_and_overrides():
+    """Test nested dependency resolution, cycle detection and overrides."""
+    from cello import App, Depends
+    from cello.testing import TestClient
+
+    app = App()
+    app.register_singleton("config", {"dsn": "postgres://prod"})
+    calls = []
+
+    def get_db(config=Depends("config")):
+        calls.append("db")
+        return {"dsn": config["dsn"]}
+
+    def get_repo(request, db=Depends(get_db)):
+        return {"db": db, "path": request.path}
+
+    def get_service(repo=Depends(get_repo), db=Depends(get_db)):
+        return {"repo": repo, "same_db": repo["db"] is db}
+
+    app.register_dependency("service", get_service)
+
+    @app.get("/service")
+    def service(request, svc=Depends("service")):
+        return svc
+
+    def loop_a(b=Depends("loop_b")):
+        return b
+
+    def loop_b(a=Depends("loop_a")):
+        return a
+
+    app.register_dependency("loop_a", loop_a)
+    app.register_dependency("loop_b", loop_b)
+
+    @app.get("/loop")
+    def loop(request, value=Depends("loop_a")):
+        return {"value": value}
+
+    client = TestClient(app)
+    res = client.get("/service")
+    assert res.status_code == 200
+    # get_db is request-scoped: shared by get_repo and get_service.
+    assert res.json() == {
+        "repo": {"db": {"dsn": "postgres://prod"}, "path": "/service"},
+        "same_db": True,
+    }
+    assert calls == ["db"]
+
+    assert client.get("/loop").status_code == 500
+
+    app.dependency_overrides[get_db] = lambda: {"dsn": "sqlite://"}
+    app.dependency_overrides["config"] = {"dsn": "unused"}
+    assert client.get("/service").json()["repo"]["db"] == {"dsn": "sqlite://"}
+    assert len(app.dependency_overrides) == 2
+
+    del app.dependency_overrides[get_db]
+    assert client.get("/service").json()["repo"]["db"] == {"dsn": "unused"}
+    app.dependency_overrides.clear()
+    assert client.get("/service").json()["repo"]["db"] == {"dsn": "postgres://prod"}
+
+    with pytest.raises(ValueError):
+        app.register_dependency("bad", get_db, scope="forever")
+
+
+def test_app_async_dependencies():
+    """Test coroutines of async dependency factories are awaited."""
+    import asyncio
+
+    from cello import App, Depends
+    from cello.testing import TestClient
+
+    app = App()
+    app.register_singleton("config", {"user": "ada"})
+    calls = []
+
+    async def get_user(config=Depends("config")):
+        await asyncio.sleep(0)
+        calls.append("user")
+        return {"name": config["user"]}
+
+    def get_greeting(user=Depends(get_user)):
+        return f"hello {user['name']}"
+
+    @app.get("/sync")
+    def sync_route(request, user=Depends(get_user), greeting=Depends(get_greeting)):
+        return {"user": user, "greeting": greeting}
+
+    @app.get("/async")
+    async def async_route(request, user=Depends(get_user)):
+        return user
+
+    client = TestClient(app)
+    assert client.get("/sync").json() == {"user": {"name": "ada"}, "greeting": "hello ada"}
+    # get_user is request-scoped: awaited once, shared by the route and get_greeting.
+    assert calls == ["user"]
+    assert client.get("/async").json() == {"name": "ada"}
+
+
+def test_app_database_pool():
+    """Test the SQL pool: queries, transactions, DI and pool stats."""
+    import asyncio
+
+    from cello import App, DatabaseConfig, Depends
+    from cello.testing import TestClient
+
+    app = App()
+    app.enable_database(DatabaseConfig("sqlite::memory:", pool_size=1))
+    db = app.database()
+    assert db.backend == "sqlite"
+
+    async def setup():
+        await db.execute("CREATE TABLE items (id INTEGER PRIMARY KEY, name TEXT, price REAL)")
+        assert await db.execute("INSERT INTO items (name, price) VALUES ($1, $2)", "pen", 1.5) == 1
+        async with db.transaction() as tx:
+            await tx.execute("INSERT INTO items (name, price) VALUES ($1, $2)", "ink", 3.0)
+        with pytest.raises(RuntimeError):
+            async with db.transaction() as tx:
+                await tx.execute("INSERT INTO items (name, price) VALUES ($1, $2)", "lost", 0.0)
+                raise RuntimeError("abort")
+        return await db.fetch_all("SELECT name, price FROM items ORDER BY id")
+
+    assert asyncio.run(setup()) == [
+        {"name": "pen", "price": 1.5},
+        {"name": "ink", "price": 3.0},
+    ]
+
+    @app.get("/items/{name}")
+    async def get_item(request, db=Depends("database")):
+        row = await db.fetch_one("SELECT id, name FROM items WHERE name = $1", request.params["name"])
+        total = await db.fetch_val("SELECT COUNT(*) FROM items")
+        return {"item": row, "total": total}
+
+    client = TestClient(app)
+    res = client.get("/items/ink")
+    assert res.status_code == 200
+    assert res.json() == {"item": {"id": 2, "name": "ink"}, "total": 2}
+    assert client.get("/items/none").json()["item"] is None
+
+    stats = app.database_stats()
+    for key in ("total_connections", "active_connections", "idle_connections",
+                "avg_wait_time_ms", "max_wait_time_ms", "total_queries"):
+        assert key in stats
+    assert stats["total_queries"] >= 5
+
+    with pytest.raises(ValueError):
+        App().enable_database(DatabaseConfig("oracle://localhost/db"))
+
+
+def test_app_transaction_per_request():
+    """Test routes run in a transaction committed or rolled back by status."""
+    import asyncio
+
+    from cello import App, DatabaseConfig, DatabaseTransaction, Depends, Response, atomic
+    from cello.testing import TestClient
+
+    app = App()
+    app.enable_database(DatabaseConfig("sqlite::memory:", pool_size=1))
+    db = app.database()
+
+    async def query(sql):
+        return await db.fetch_all(sql)
+
+    asyncio.run(query("CREATE TABLE log (msg TEXT)"))
+
+    @app.post("/log/{msg}")
+    @atomic(isolation="serializable")
+    async def write(request, db=Depends("database"), tx=Depends("transaction")):
+        assert isinstance(db, DatabaseTransaction) and tx is db
+        await db.execute("INSERT INTO log (msg) VALUES ($1)", request.params["msg"])
+        if request.params["msg"] == "boom":
+            raise RuntimeError("handler failed")
+        if request.params["msg"] == "conflict":
+            return Response.json({"error": "conflict"}, status=409)
+        return {"ok": True}
+
+    @app.post("/manual")
+    @atomic
+    async def manual(request, db=Depends("database")):
+        await db.execute("INSERT INTO log (msg) VALUES ($1)", "manual")
+        await db.rollback()
+        return {"ok": True}
+
+    client = TestClient(app)
+    assert client.post("/log/kept").status_code == 200
+    assert client.post("/log/boom").status_code == 500
+    assert client.post("/log/conflict").status_code == 409
+    assert client.post("/manual").status_code == 200
+
+    assert asyncio.run(query("SELECT msg FROM log")) == [{"msg": "kept"}]
+
+    with pytest.raises(ValueError):
+        @app.post("/bad")
+        @atomic(isolation="snapshot")
+        def bad(request):
+            return {}
+
+
+def test_app_mongo():
+    """Test MongoDB config, ObjectId and client registration."""
+    from cello import App, MongoConfig, ObjectId
+
+    config = MongoConfig()
+    assert config.url == "mongodb://127.0.0.1:27017"
+    assert config.database is None
+    assert config.max_pool_size == 10
+    assert MongoConfig.local("shop").database == "shop"
+
+    oid = ObjectId()
+    assert len(str(oid)) == 24
+    assert ObjectId(str(oid)) == oid
+    assert hash(ObjectId(str(oid))) == hash(oid)
+    assert repr(oid) == f"ObjectId('{oid}')"
+    assert oid.generation_time is not None
+    try:
+        ObjectId("not-an-object-id")
+        assert False, "expected ValueError"
+    except ValueError:
+        pass
+
+    app = App()
+    try:
+        app.enable_mongo(MongoConfig.local("shop"))
+    except RuntimeError as e:
+        assert "mongodb" in str(e)
+        return
+    assert app.mongo().name == "shop"
+    assert app.mongo()["users"].name == "users"
+    assert app.mongo("audit").name == "audit"
+
+
+def test_app_cache():
+    """Test the in-memory cache store and its use for query caching."""
+    import asyncio
+    from cello import App
+
+    app = App()
+    try:
+        app.cache
+        assert False, "expected RuntimeError"
+    except RuntimeError:
+        pass
+    try:
+        app.enable_cache(policy="fifo")
+        assert False, "expected ValueError"
+    except ValueError:
+        pass
+
+    app.enable_cache(max_entries=2, policy="lru", shards=1)
+    cache = app.cache
+    assert cache.set("a", {"id": 1})
+    cache.set("b", b"payload")
+    assert cache.get("a") == {"id": 1}
+    cache.set("c", "third")
+    assert "a" in cache and "b" not in cache and "c" in cache
+    assert len(cache) == 2
+    assert cache.get("missing", "default") == "default"
+    assert cache.delete("c") and not cache.delete("c")
+
+    cache.set("short", 1, ttl=0)
+    assert cache.get("short") is None
+
+    calls = []
+
+    async def query():
+        calls.append(1)
+        return [{"id": 7}]
+
+    async def run():
+        first = await cache.get_or_set("users", query, ttl=60)
+        second = await cache.get_or_set("users", query, ttl=60)
+        plain = await cache.get_or_set("plain", lambda: 42)
+        return first, second, plain
+
+    first, second, plain = asyncio.run(run())
+    assert first == second == [{"id": 7}]
+    assert plain == 42
+    assert len(calls) == 1
+
+    stats = cache.stats()
+    assert stats["hits"] >= 2
+    assert stats["evictions"] >= 1
+    assert stats["expirations"] == 1
+    assert 0 < stats["hit_ratio"] < 1
+
+    cache.clear()
+    assert len(cache) == 0
+
+    bounded = App()
+    bounded.enable_cache(max_memory=1000, shards=1)
+    assert not bounded.cache.set("big", b"x" * 5000)
+    bounded.enable_caching(ttl=60)
+
+
+def test_app_load_config():
+    """Test loading layered config and feeding config classes from it."""
+    import os
+    import tempfile
+    from cello import App, AppConfig, RedisConfig
+
+    with tempfile.TemporaryDirectory() as tmp:
+        path = os.path.join(tmp, "cello.toml")
+        with open(path, "w") as f:
+            f.write(
+                "[server]\nport = 8000\n\n"
+                "[redis]\nurl = \"redis://localhost:6379\"\npool_size = 5\n\n"
+                "[profiles.prod.server]\nhost = \"0.0.0.0\"\n"
+            )
+        with open(os.path.join(tmp, "cello.prod.toml"), "w") as f:
+            f.write("[server]\nworkers = 4\n")
+
+        os.environ["CELLO_REDIS__POOL_SIZE"] = "20"
+        try:
+            app = App()
+            config = app.load_config(path, profile="prod", defaults={"server": {"env": "production"}})
+        finally:
+            del os.environ["CELLO_REDIS__POOL_SIZE"]
+
+        assert app.config is config
+        assert config.profile == "prod"
+        assert config.get_str("server.host") == "0.0.0.0"
+        assert config.get_int("server.port") == 8000
+        assert config["server.workers"] == 4
+        assert config.get("server.env") == "production"
+        assert config.get("missing", "fallback") == "fallback"
+        assert "redis.url" in config and "profiles" not in config
+        assert config.section("redis") == {"url": "redis://localhost:6379", "pool_size": 20}
+
+        redis = app._config_section("redis", RedisConfig)
+        assert redis.url == "redis://localhost:6379"
+        assert redis.pool_size == 20
+
+        dev = AppConfig.load(path, profile="dev")
+        assert dev.get_str("server.host") is None
+        try:
+            dev.get_bool("server.port")
+            assert False, "expected TypeError"
+        except TypeError:
+            pass
+        try:
+            dev.require("server.port", "database.url")
+            assert False, "expected ValueError"
+        except ValueError as e:
+            assert "database.url" in str(e)
+
+        bad = os.path.join(tmp, "bad.yaml")
+        with open(bad, "w") as f:
+            f.write("redis:\n  url: redis://localhost\n  pool_sise: 5\n")
+        try:
+            App().load_config(bad)
+            assert False, "expected ValueError"
+        except ValueError as e:
+            assert "[redis]" in str(e)
+
+        try:
+            AppConfig.load(os.path.join(tmp, "missing.toml"))
+            assert False, "expected FileNotFoundError"
+        except FileNotFoundError:
+            pass
+
+
+def test_app_tenancy():
+    """Test tenant resolution and per-tenant DI resources."""
+    import asyncio
+
+    from cello import App, DatabaseConfig, Depends, RateLimitConfig
+    from cello.testing import TestClient
+
+    app = App()
+    with pytest.raises(RuntimeError):
+        app.add_tenant("acme")
+    with pytest.raises(ValueError):
+        app.enable_tenancy(resolve=["cookie"])
+
+    app.enable_cache(shards=1)
+    app.enable_tenancy(resolve=["header", "path"])
+    app.add_tenant("acme", database=DatabaseConfig("sqlite::memory:", pool_size=1),
+                   metadata={"plan": "pro"})
+    app.add_tenant("globex", rate_limit=RateLimitConfig.token_bucket(1, 0))
+    app.add_tenant("initech")
+    assert app.tenants() == ["acme", "globex", "initech"]
+    assert app.remove_tenant("initech") and not app.remove_tenant("initech")
+
+    @app.get("/whoami")
+    def whoami(request, tenant=Depends("tenant"), cache=Depends("cache")):
+        cache.set("hits", (cache.get("hits") or 0) + 1)
+        return {"id": tenant["id"], "source": request.tenant["source"],
+                "metadata": tenant["metadata"], "hits": cache.get("hits")}
+
+    @app.get("/{tenant}/db")
+    async def db(request, db=Depends("database")):
+        return await db.fetch_all("SELECT 1 AS one")
+
+    @app.get("/health")
+    def health(request):
+        return {"ok": True}
+
+    client = TestClient(app)
+    acme = {"X-Tenant-ID": "acme"}
+    assert client.get("/whoami", headers=acme).json() == {
+        "id": "acme", "source": "header", "metadata": {"plan": "pro"}, "hits": 1,
+    }
+    assert client.get("/whoami", headers=acme).json()["hits"] == 2
+    assert client.get("/whoami", headers={"X-Tenant-ID": "globex"}).json()["hits"] == 1
+    assert "tenant:acme:hits" in app.cache
+
+    assert client.get("/acme/db").json() == [{"one": 1}]
+    assert client.get("/whoami").status_code == 400
+    assert client.get("/whoami", headers={"X-Tenant-ID": "umbrella"}).status_code == 404
+    assert client.get("/whoami", headers={"X-Tenant-ID": "globex"}).status_code == 429
+    assert client.get("/health").status_code == 200
+
+
+def test_app_transforms():
+    """Test native transforms rewrite and answer requests before routing."""
+    from cello import App
+    from cello.testing import TestClient
+
+    app = App()
+    with pytest.raises(ValueError):
+        app.add_transform("gzip")
+    with pytest.raises(ValueError):
+        app.add_transform("trailing_slash", mode="both")
+
+    app.add_transform("trailing_slash")
+    app.add_transform("rewrite", prefix="/v1", to="/api")
+    app.add_transform("request_header", name="X-Edge", value="native")
+    app.add_transform("response_header", name="X-Frame-Options", value="DENY")
+    app.add_transform("respond", path="/robots.txt", body="User-agent: *")
+
+    @app.get("/api/users")
+    def users(request):
+        return {"edge": request.get_header("x-edge"), "page": request.query.get("page")}
+
+    client = TestClient(app)
+    response = client.get("/v1/users", params={"page": "2"})
+    assert response.status_code == 200
+    assert response.json() == {"edge": "native", "page": "2"}
+    assert response.headers.get("x-frame-options") == "DENY"
+
+    response = client.get("/api/users/")
+    assert response.status_code == 308
+    assert response.headers.get("location") == "/api/users"
+
+    response = client.get("/robots.txt")
+    assert response.status_code == 200 and response.text == "User-agent: *"
+
+
+def test_app_response_headers():
+    """Test per-route and blueprint response header policies."""
+    from cello import App, Blueprint, Response, response_headers
+    from cello.testing import TestClient
+
+    app = App()
+
+    @app.get("/products")
+    @response_headers(cache="public", max_age=300, vary=["Accept-Language"],
+                      headers={"X-Robots-Tag": "noindex"})
+    def products(request):
+        return {"items": []}
+
+    @app.get("/own")
+    @response_headers(cache="public", max_age=60, vary=["Accept"])
+    def own(request):
+        response = Response.json({"ok": True})
+        response.set_header("Cache-Control", "no-store")
+        response.set_header("Vary", "Cookie")
+        return response
+
+    @app.get("/missing")
+    @response_headers(cache="immutable")
+    def missing(request):
+        return Response.json({"error": "gone"}, status=404)
+
+    admin = Blueprint("/admin", headers=response_headers(cache="no-store", vary=["Authorization"]))
+    reports = Blueprint("/reports")
+    admin.register(reports)
+
+    @admin.get("/users")
+    def users(request):
+        return []
+
+    @reports.get("/daily")
+    @response_headers(cache="private", max_age=30)
+    def daily(request):
+        return []
+
+    app.register_blueprint(admin)
+
+    with pytest.raises(ValueError):
+        @app.get("/bad")
+        @response_headers(cache="forever")
+        def bad(request):
+            return {}
+
+    client = TestClient(app)
+    response = client.get("/products")
+    assert response.headers.get("cache-control") == "public, max-age=300"
+    assert response.headers.get("vary") == "Accept, Accept-Language"
+    assert response.headers.get("x-robots-tag") == "noindex"
+
+    response = client.get("/own")
+    assert response.headers.get("cache-control") == "no-store"
+    assert response.headers.get("vary") == "Cookie, Accept"
+
+    response = client.get("/missing")
+    assert response.status_code == 404
+    assert response.headers.get("cache-control") is None
+
+    response = client.get("/admin/users")
+    assert response.headers.get("cache-control") == "no-store"
+    assert response.headers.get("vary") == "Accept, Authorization"
+
+    response = client.get("/admin/reports/daily")
+    assert response.headers.get("cache-control") == "private, max-age=30"
+    assert response.headers.get("vary") == "Accept, Authorization"
+
+
+def test_app_ip_filter():
+    """Test CIDR allow/deny lists, proxy-aware client IPs and runtime updates."""
+    from cello import App
+    from cello.testing import TestClient
+
+    app = App()
+    with pytest.raises(RuntimeError):
+        app.ip_filter
+    with pytest.raises(ValueError):
+        app.enable_ip_filter(deny=["10.0.0.0/33"])
+
+    # The test client connects from 127.0.0.1, here a trusted proxy
+    app.enable_ip_filter(allow=["203.0.113.0/24"], deny=["203.0.113.66"],
+                         trusted_proxies=["127.0.0.0/8"], exclude_paths=["/health"])
+
+    @app.get("/")
+    def index(request):
+        return {"ok": True}
+
+    @app.get("/health")
+    def health(request):
+        return {"ok": True}
+
+    client = TestClient(app)
+
+    def status(ip):
+        return client.get("/", headers={"X-Forwarded-For": ip}).status_code
+
+    assert status("203.0.113.5") == 200
+    assert status("203.0.113.66") == 403
+    assert status("198.51.100.1") == 403
+    # Only the rightmost untrusted hop counts
+    assert status("203.0.113.5, 198.51.100.1") == 403
+    assert client.get("/").status_code == 403
+    assert client.get("/health").status_code == 200
+
+    ip_filter = app.ip_filter
+    assert ip_filter.allowed == ["203.0.113.0/24"]
+    assert ip_filter.denied == ["203.0.113.66/32"]
+    assert ip_filter.allow("198.51.100.0/24") and not ip_filter.allow("198.51.100.0/24")
+    assert status("198.51.100.1") == 200
+    assert ip_filter.remove("203.0.113.66")
+    assert status("203.0.113.66") == 200
+    assert ip_filter.is_allowed("::ffff:198.51.100.7")
+    assert not ip_filter.is_allowed("192.0.2.1")
+
+    stats = ip_filter.stats()
+    assert stats["denied"] == 1 and stats["not_allowed"] == 3 and stats["total"] == 4
+
+
+def test_app_throttling():
+    """Test throttling configuration and validation."""
+    import pytest
+    from cello import App
+    from cello.testing import TestClient
+
+    app = App()
+
+    @app.post("/upload")
+    def upload(request):
+        return {"size": len(request.body())}
+
+    assert app.enable_throttling(
+        header_timeout=10,
+        min_body_rate=1024,
+        body_grace=2.5,
+        connection_bandwidth=1_000_000,
+        client_bandwidth=4_000_000,
+        max_connections_per_client=20,
+    ) is app
+
+    for bad in (
+        {"header_timeout": 0},
+        {"header_timeout": -1},
+        {"min_body_rate": 0},
+        {"min_body_rate": 10, "body_grace": -1},
+        {"client_bandwidth": 0},
+        {"max_connections_per_client": 0},
+    ):
+        with pytest.raises(ValueError):
+            App().enable_throttling(**bad)
+
+    # Throttling applies to network connections; in-process requests pass through.
+    client = TestClient(app)
+    assert client.post("/upload", data=b"x" * 4096).json() == {"size": 4096}
+
+
+def test_app_stream_body():
+    """Test streaming request bodies to handlers without buffering."""
+    from cello import App, Blueprint, stream_body, verify_webhook
+    from cello.testing import TestClient
+
+    app = App()
+
+    @app.post("/upload")
+    @stream_body
+    async def upload(request):
+        chunks = [chunk async for chunk in request.stream()]
+        try:
+            request.stream()
+        except RuntimeError:
+            reused = False
+        else:
+            reused = True
+        return {"size": sum(len(c) for c in chunks), "reused": reused}
+
+    @app.post("/json")
+    @stream_body
+    async def json_body(request):
+        try:
+            request.json()
+        except RuntimeError:
+            early = "error"
+        else:
+            early = "ok"
+        raw = await request.read()
+        return {"early": early, "raw": len(raw), "data": request.json()}
+
+    @app.post("/buffered")
+    async def buffered(request):
+        chunks = [chunk async for chunk in request.stream()]
+        return {"chunks": len(chunks), "body": len(request.body())}
+
+    api = Blueprint("/api")
+
+    @api.post("/upload")
+    @stream_body
+    def sync_upload(request):
+        try:
+            request.body()
+        except RuntimeError:
+            return {"streamed": True}
+        return {"streamed": False}
+
+    app.register_blueprint(api)
+    client = TestClient(app)
+
+    assert client.post("/upload", data=b"x" * 100_000).json() == {"size": 100_000, "reused": False}
+    assert client.post("/json", json={"a": 1}).json() == {"early": "error", "raw": 8, "data": {"a": 1}}
+    assert client.post("/buffered", data=b"abc").json() == {"chunks": 1, "body": 3}
+    assert client.post("/api/upload", data=b"abc").json() == {"streamed": True}
+
+    # Webhook signatures cover the whole body, so it cannot be streamed.
+    with pytest.raises(ValueError):
+        @app.post("/hooks")
+        @stream_body
+        @verify_webhook("github", secret="gh")
+        def hook(request):
+            return {"ok": True}
+
+
+def test_app_response_format():
+    """Test route-level response serialization presets."""
+    from cello import App, Blueprint, Response, response_format
+    from cello.testing import TestClient
+
+    app = App()
+
+    @app.get("/json")
+    @response_format("json")
+    def as_json(request):
+        return {"ok": True, "items": [1, 2]}
+
+    @app.get("/text")
+    @response_format("text")
+    async def as_text(request):
+        return "pong"
+
+    @app.get("/bytes")
+    @response_format("bytes")
+    def as_bytes(request):
+        return b"\x00\x01\x02"
+
+    @app.get("/fallback")
+    @response_format("json")
+    def fallback(request):
+        return Response.text("custom", status=201)
+
+    api = Blueprint("/api")
+
+    @api.get("/text")
+    @response_format("text")
+    def bp_text(request):
+        return "from blueprint"
+
+    app.register_blueprint(api)
+    client = TestClient(app)
+
+    resp = client.get("/json")
+    assert resp.json() == {"ok": True, "items": [1, 2]}
+    assert resp.headers["content-type"] == "application/json"
+
+    resp = client.get("/text")
+    assert resp.text == "pong"
+    assert resp.headers["content-type"].startswith("text/plain")
+
+    resp = client.get("/bytes")
+    assert resp.content == b"\x00\x01\x02"
+    assert resp.headers["content-type"] == "application/octet-stream"
+
+    resp = client.get("/fallback")
+    assert resp.status_code == 201
+    assert resp.text == "custom"
+
+    assert client.get("/api/text").text == "from blueprint"
+
+    with pytest.raises(ValueError):
+        response_format("xml")
+
+
+def test_app_native_json_types():
+    """Test native serialization of datetimes, UUIDs, Decimals, dataclasses and models."""
+    import dataclasses
+    import datetime
+    import decimal
+    import uuid
+    from cello import App
+    from cello.testing import TestClient
+
+    @dataclasses.dataclass
+    class Item:
+        name: str
+        price: decimal.Decimal
+        added: datetime.date
+
+    class Model:
+        def __init__(self, **fields):
+            self.fields = fields
+
+        def model_dump(self):
+            return dict(self.fields)
+
+    app = App()
+    order_id = uuid.uuid4()
+
+    @app.get("/order")
+    def order(request):
+        return {
+            "id": order_id,
+            "created": datetime.datetime(2024, 5, 1, 9, 15, 30, 500000),
+            "items": [Item("pen", decimal.Decimal("1.50"), datetime.date(2024, 4, 30))],
+            "customer": Model(name="Ada", since=datetime.time(8, 0)),
+        }
+
+    @app.get("/item")
+    def item(request):
+        return Item("ink", decimal.Decimal("2.25"), datetime.date(2024, 1, 2))
+
+    client = TestClient(app)
+
+    assert client.get("/order").json() == {
+        "id": str(order_id),
+        "created": "2024-05-01T09:15:30.500000",
+        "items": [{"name": "pen", "price": "1.50", "added": "2024-04-30"}],
+        "customer": {"name": "Ada", "since": "08:00:00"},
+    }
+
+    app.configure_json(decimal="float")
+    try:
+        assert client.get("/item").json() == {"name": "ink", "price": 2.25, "added": "2024-01-02"}
+    finally:
+        app.configure_json(decimal="str")
+
+    with pytest.raises(ValueError):
+        app.configure_json(decimal="money")
+
+
+def test_app_json_stream():
+    """Test streaming large JSON arrays from lists and generators."""
+    import datetime
+    from cello import App, JsonStream
+    from cello.testing import TestClient
+
+    app = App()
+    app.enable_caching(ttl=60)
+
+    @app.get("/rows")
+    def rows(request):
+        return JsonStream(({"id": i, "day": datetime.date(2024, 1, 1)} for i in range(20_000)), chunk_size=4096)
+
+    @app.get("/empty")
+    def empty(request):
+        return JsonStream([], status=206)
+
+    client = TestClient(app)
+
+    for _ in range(2):
+        resp = client.get("/rows")
+        assert resp.status_code == 200
+        assert resp.headers["content-type"] == "application/json"
+        data = resp.json()
+        assert len(data) == 20_000
+        assert data[-1] == {"id": 19_999, "day": "2024-01-01"}
+
+    resp = client.get("/empty")
+    assert resp.status_code == 206
+    assert resp.json() == []
+
+    with pytest.raises(TypeError):
+        JsonStream(42)
+
+
+def test_app_ndjson():
+    """Test NDJSON responses and request bodies."""
+    from cello import App, BodyDecodeError, Response, stream_body
+    from cello.testing import TestClient
+
+    app = App()
+
+    @app.get("/export")
+    def export(request):
+        return Response.ndjson({"id": i} for i in range(3))
+
+    @app.post("/ingest")
+    @stream_body
+    async def ingest(request):
+        ids = [doc["id"] async for doc in request.ndjson()]
+        return {"ids": ids}
+
+    @app.post("/buffered")
+    async def buffered(request):
+        return {"docs": [doc async for doc in request.ndjson(max_line_size=16)]}
+
+    @app.post("/caught")
+    async def caught(request):
+        try:
+            return {"docs": [doc async for doc in request.ndjson()]}
+        except BodyDecodeError as e:
+            return Response.json({"caught": str(e)}, status=422)
+
+    client = TestClient(app)
+
+    resp = client.get("/export")
+    assert resp.headers["content-type"] == "application/x-ndjson"
+    assert resp.text == '{"id":0}\n{"id":1}\n{"id":2}\n'
+
+    body = "".join(f'{{"id": {i}}}\n' for i in range(500)).encode()
+    assert client.post("/ingest", data=body).json() == {"ids": list(range(500))}
+
+    assert client.post("/buffered", data=b'[1]\n\n"two"').json() == {"docs": [[1], "two"]}
+    resp = client.post("/buffered", data=b'{"a": 1}\n{"bad"\n')
+    assert resp.status_code == 400
+    assert "line 2" in resp.json()["error"]
+    resp = client.post("/buffered", data=b'{"long": "' + b"x" * 32 + b'"}')
+    assert resp.status_code == 400
+    assert "exceeds 16 bytes" in resp.json()["error"]
+
+    resp = client.post("/caught", data=b"{not json}\n")
+    assert resp.status_code == 422
+    assert "line 1" in resp.json()["caught"]
+
+
+def test_app_file_uploads(tmp_path):
+    """Test multipart uploads with disk spooling, checksums and move_to()."""
+    import gc
+    import hashlib
+    import os
+
+    from cello import App, stream_body
+    from cello.testing import TestClient
+
+    app = App()
+    spooled = []
+
+    def multipart(content):
+        return (
+            b"--XyZ\r\n"
+            b'Content-Disposition: form-data; name="title"\r\n\r\n'
+            b"Report\r\n--XyZ\r\n"
+            b'Content-Disposition: form-data; name="doc"; filename="report.csv"\r\n'
+            b"Content-Type: text/csv\r\n\r\n" + content + b"\r\n--XyZ--\r\n"
+        )
+
+    headers = {"Content-Type": "multipart/form-data; boundary=XyZ"}
+
+    @app.post("/form")
+    def form(request):
+        data = request.form()
+        doc = data.get_file("doc")
+        return {
+            "title": data.get("title"),
+            "filename": doc.filename,
+            "content_type": doc.content_type,
+            "size": doc.size(),
+            "text": doc.read_text(),
+            "temp_path": doc.temp_path,
+        }
+
+    @app.post("/upload")
+    @stream_body
+    async def upload(request):
+        data = await request.multipart(
+            spool_threshold=1024, checksum=True, upload_dir=str(tmp_path)
+        )
+        doc = data.get_file("doc")
+        spooled.append(doc.temp_path)
+        assert os.path.exists(doc.temp_path)
+        content = await doc.aread()
+        return {"size": doc.size(), "sha256": doc.sha256, "read": len(content)}
+
+    @app.post("/keep")
+    async def keep(request):
+        data = await request.multipart(spool_threshold=1024, upload_dir=str(tmp_path))
+        doc = data.get_file("doc")
+        doc.move_to(str(tmp_path / "kept.csv"))
+        return {"temp_path": doc.temp_path}
+
+    client = TestClient(app)
+
+    resp = client.post("/form", data=multipart(b"a,b\n1,2"), headers=headers)
+    assert resp.json() == {
+        "title": "Report",
+        "filename": "report.csv",
+        "content_type": "text/csv",
+        "size": 7,
+        "text": "a,b\n1,2",
+        "temp_path": None,
+    }
+
+    content = b"x" * 100_000
+    resp = client.post("/upload", data=multipart(content), headers=headers)
+    assert resp.json() == {
+        "size": 100_000,
+        "sha256": hashlib.sha256(content).hexdigest(),
+        "read": 100_000,
+    }
+    assert spooled[0].startswith(str(tmp_path))
+    gc.collect()
+    assert not os.path.exists(spooled[0])
+
+    resp = client.post("/keep", data=multipart(content), headers=headers)
+    assert resp.json() == {"temp_path": None}
+    assert (tmp_path / "kept.csv").read_bytes() == content
+
+    resp = client.post("/form", data=b"--XyZ\r\nbroken", headers=headers)
+    assert resp.status_code == 500
+    assert "Invalid multipart body" in resp.json()["error"]
+
+
+def test_app_file_downloads(tmp_path):
+    """Test file responses streamed from disk with Range support."""
+    import os
+
+    from cello import App, Response
+    from cello.testing import TestClient
+
+    data = bytes(range(256)) * 1000
+    (tmp_path / "data.bin").write_bytes(data)
+    (tmp_path / "notes.txt").write_text("hello")
+
+    app = App()
+
+    @app.get("/data")
+    def download(request):
+        return Response.file(str(tmp_path / "data.bin"), filename="export.bin")
+
+    @app.get("/notes")
+    def notes(request):
+        return Response.file(str(tmp_path / "notes.txt"), disposition="inline")
+
+    @app.get("/plain")
+    def plain(request):
+        return Response.file(str(tmp_path / "notes.txt"), disposition=None)
+
+    @app.get("/sendfile")
+    def sendfile(request):
+        return Response.sendfile(str(tmp_path / "data.bin"))
+
+    @app.get("/missing")
+    def missing(request):
+        return Response.file(str(tmp_path / "missing.bin"))
+
+    @app.get("/directory")
+    def directory(request):
+        return Response.file(str(tmp_path))
+
+    client = TestClient(app)
+
+    resp = client.get("/data")
+    assert resp.status_code == 200
+    assert resp.content == data
+    assert resp.headers["content-type"] == "application/octet-stream"
+    assert resp.headers["content-length"] == str(len(data))
+    assert resp.headers["accept-ranges"] == "bytes"
+    assert resp.headers["content-disposition"] == 'attachment; filename="export.bin"'
+
+    resp = client.get("/data", headers={"Range": "bytes=1000-1999"})
+    assert resp.status_code == 206
+    assert resp.content == data[1000:2000]
+    assert resp.headers["content-range"] == f"bytes 1000-1999/{len(data)}"
+    assert resp.headers["content-length"] == "1000"
+
+    resp = client.get("/data", headers={"Range": "bytes=-10"})
+    assert resp.status_code == 206
+    assert resp.content == data[-10:]
+
+    resp = client.get("/data", headers={"Range": f"bytes={len(data)}-"})
+    assert resp.status_code == 416
+    assert resp.headers["content-range"] == f"bytes */{len(data)}"
+
+    resp = client.get("/data", headers={"Range": "bytes=0-1", "If-Range": '"stale"'})
+    assert resp.status_code == 200
+    assert resp.content == data
+
+    resp = client.get("/notes")
+    assert resp.text == "hello"
+    assert resp.headers["content-type"] == "text/plain"
+    assert resp.headers["content-disposition"] == 'inline; filename="notes.txt"'
+    assert "content-disposition" not in client.get("/plain").headers
+
+    resp = client.get("/sendfile")
+    assert resp.content == data
+    assert not any(h.lower().startswith("x-sendfile") for h in resp.headers)
+
+    resp = client.get("/missing")
+    assert resp.status_code == 404
+    assert "missing.bin" not in resp.text
+    assert str(tmp_path) not in resp.text
+    assert "os error" not in resp.text
+    assert client.get("/directory").status_code == 404
+
+    os.chmod(tmp_path / "notes.txt", 0)
+    try:
+        if not os.access(tmp_path / "notes.txt", os.R_OK):
+            resp = client.get("/notes")
+            assert resp.status_code == 403
+            assert "notes.txt" not in resp.text
+    finally:
+        os.chmod(tmp_path / "notes.txt", 0o644)
+
+
+def test_app_object_store():
+    """Test the S3 object store client against a local fake service."""
+    import threading
+    from http.server import BaseHTTPRequestHandler, ThreadingHTTPServer
+    from urllib.parse import parse_qs, unquote, urlparse
+
+    import pytest
+    from cello import App, Depends, ObjectStoreConfig, stream_body
+    from cello.testing import TestClient
+
+    objects = {}
+    uploads = {}
+    requests = []
+
+    class Handler(BaseHTTPRequestHandler):
+        protocol_version = "HTTP/1.1"
+
+        def _target(self):
+            url = urlparse(self.path)
+            bucket, _, key = url.path.lstrip("/").partition("/")
+            assert bucket == "media"
+            assert self.headers["Authorization"].startswith("AWS4-HMAC-SHA256 Credential=key/")
+            requests.append((self.command, unquote(key)))
+            return unquote(key), parse_qs(url.query, keep_blank_values=True)
+
+        def _reply(self, status, body=b"", headers=None):
+            self.send_response(status)
+            for name, value in (headers or {}).items():
+                self.send_header(name, value)
+            self.send_header("Content-Length", str(len(body)))
+            self.end_headers()
+            if self.command != "HEAD":
+                self.wfile.write(body)
+
+        def _body(self):
+            return self.rfile.read(int(self.headers.get("Content-Length", 0)))
+
+        def do_PUT(self):
+            key, query = self._target()
+            body = self._body()
+            if "uploadId" in query:
+                uploads[query["uploadId"][0]][int(query["partNumber"][0])] = body
+                return self._reply(200, headers={"ETag": f'"part{query["partNumber"][0]}"'})
+            objects[key] = (body, self.headers["Content-Type"])
+            self._reply(200, headers={"ETag": '"etag1"'})
+
+        def do_POST(self):
+            key, query = self._target()
+            body = self._body()
+            if "uploads" in query:
+                uploads["u1"] = {}
+                xml = b"<InitiateMultipartUploadResult><UploadId>u1</UploadId></InitiateMultipartUploadResult>"
+                return self._reply(200, xml)
+            parts = uploads.pop(query["uploadId"][0])
+            assert body.count(b"<Part>") == len(parts)
+            objects[key] = (b"".join(parts[n] for n in sorted(parts)), "application/octet-stream")
+            self._reply(200, b"<CompleteMultipartUploadResult><ETag>&quot;multi&quot;</ETag></CompleteMultipartUploadResult>")
+
+        def do_GET(self):
+            key, query = self._target()
+            if not key:
+                prefix = query.get("prefix", [""])[0]
+                entries = "".join(
+                    f"<Contents><Key>{k}</Key><Size>{len(v[0])}</Size></Contents>"
+                    for k, v in sorted(objects.items()) if k.startswith(prefix)
+                )
+                return self._reply(200, f"<ListBucketResult>{entries}</ListBucketResult>".encode())
+            if key not in objects:
+                return self._reply(404, b"<Error><Code>NoSuchKey</Code><Message>No key</Message></Error>")
+            body, content_type = objects[key]
+            self._reply(200, body, {"Content-Type": content_type, "ETag": '"etag1"'})
+
+        do_HEAD = do_GET
+
+        def do_DELETE(self):
+            key, _ = self._target()
+            objects.pop(key, None)
+            self._reply(204)
+
+        def log_message(self, *args):
+            pass
+
+    server = ThreadingHTTPServer(("127.0.0.1", 0), Handler)
+    threading.Thread(target=server.serve_forever, daemon=True).start()
+    endpoint = f"http://127.0.0.1:{server.server_port}"
+
+    app = App()
+    with pytest.raises(ValueError):
+        app.enable_object_store()
+    with pytest.raises(RuntimeError):
+        app.object_store()
+    config = ObjectStoreConfig.minio("media", endpoint, "key", "secret")
+    config.part_size = 5 * 1024 * 1024
+    app.enable_object_store(config)
+
+    @app.post("/notes/{name}")
+    async def put_note(request, storage=Depends("storage")):
+        return await storage.put("notes/" + request.params["name"], request.text())
+
+    @app.get("/notes/{name}")
+    async def get_note(request, storage=Depends("storage")):
+        try:
+            return {"text": (await storage.get("notes/" + request.params["name"])).decode()}
+        except FileNotFoundError:
+            return {"missing": True}
+
+    @app.get("/notes")
+    async def list_notes(request, storage=Depends("storage")):
+        return {"keys": [o["key"] for o in await storage.list("notes/")]}
+
+    @app.delete("/notes/{name}")
+    async def delete_note(request, storage=Depends("storage")):
+        await storage.delete("notes/" + request.params["name"])
+        return {"deleted": True}
+
+    @app.put("/blobs/{name}")
+    @stream_body
+    async def upload(request, storage=Depends("storage")):
+        return await storage.put(request.params["name"], request.stream())
+
+    @app.get("/blobs/{name}")
+    async def download(request, storage=Depends("storage")):
+        return await storage.download(request.params["name"], filename="blob.bin")
+
+    client = TestClient(app)
+
+    info = client.post("/notes/a.txt", data="hello").json()
+    assert info["key"] == "notes/a.txt"
+    assert info["size"] == 5
+    assert info["content_type"] == "text/plain"
+    client.post("/notes/b.txt", data="world")
+    client.post("/notes/c.txt", data="!")
+    assert objects["notes/a.txt"] == (b"hello", "text/plain")
+    assert client.get("/notes/a.txt").json() == {"text": "hello"}
+    assert client.get("/notes/nope.txt").json() == {"missing": True}
+    assert client.get("/notes").json() == {"keys": ["notes/a.txt", "notes/b.txt", "notes/c.txt"]}
+    assert client.delete("/notes/c.txt").json() == {"deleted": True}
+    assert "notes/c.txt" not in objects
+
+    # Bodies larger than a part go up as a multipart upload
+    data = bytes(range(256)) * (6 * 1024 * 4 + 1)
+    info = client.put("/blobs/big.bin", data=data).json()
+    assert info["size"] == len(data)
+    assert info["etag"] == '"multi"'
+    assert objects["big.bin"][0] == data
+    assert ("POST", "big.bin") in requests
+
+    resp = client.get("/blobs/big.bin")
+    assert resp.status_code == 200
+    assert resp.content == data
+    assert resp.headers["content-length"] == str(len(data))
+    assert resp.headers["content-disposition"] == 'attachment; filename="blob.bin"'
+
+    store = app.object_store()
+    assert store.bucket == "media"
+    url = store.presign("a b.txt", expires=60)
+    assert url.startswith(f"{endpoint}/media/a%20b.txt?X-Amz-Algorithm=AWS4-HMAC-SHA256")
+    assert "X-Amz-Expires=60" in url and "X-Amz-Signature=" in url
+    with pytest.raises(ValueError):
+        store.presign("a.txt", expires=0)
+    server.shutdown()
+
+
+def test_app_exception_handlers():
+    """Test status and exception handler registration and dispatch."""
+    import pytest
+    from cello import App, ProblemDetails, Response
+    from cello.testing import TestClient
+
+    class AppError(Exception):
+        pass
+
+    class NotAllowed(AppError):
+        pass
+
+    app = App()
+
+    @app.exception_handler(404)
+    def not_found(request, problem):
+        return Response.json(
+            {"missing": request.path, "title": problem.title, "detail": problem.detail}, status=404
+        )
+
+    @app.exception_handler(405)
+    def not_allowed(request, problem):
+        return {"status": problem.status, "instance": problem.instance}
+
+    @app.exception_handler(AppError)
+    def app_error(request, exc):
+        return Response.json({"app_error": str(exc)}, status=400)
+
+    @app.exception_handler(NotAllowed)
+    async def forbidden(request, exc):
+        return ProblemDetails("/errors/forbidden", "Forbidden", 403, str(exc), request.path)
+
+    @app.exception_handler(500)
+    def internal(request, problem):
+        return Response.json({"internal": problem.detail}, status=500)
+
+    @app.get("/items/{id}")
+    def get_item(request):
+        return {"id": request.params["id"]}
+
+    @app.get("/app-error")
+    def raise_app_error(request):
+        raise AppError("bad input")
+
+    @app.get("/forbidden")
+    async def raise_forbidden(request):
+        raise NotAllowed("no access")
+
+    @app.get("/crash")
+    async def crash(request):
+        raise RuntimeError("boom")
+
+    @app.get("/explicit")
+    def explicit(request):
+        return Response.json({"own": True}, status=404)
+
+    with pytest.raises(TypeError):
+        app.exception_handler("404")(not_found)
+    with pytest.raises(TypeError):
+        app.exception_handler(int)(not_found)
+    with pytest.raises(ValueError):
+        app.exception_handler(200)(not_found)
+
+    client = TestClient(app)
+
+    resp = client.get("/nowhere")
+    assert resp.status_code == 404
+    assert resp.json() == {
+        "missing": "/nowhere",
+        "title": "Not Found",
+        "detail": "Not Found: GET /nowhere",
+    }
+
+    resp = client.post("/items/1")
+    assert resp.status_code == 405
+    assert resp.json() == {"status": 405, "instance": "/items/1"}
+    assert resp.headers["allow"] == "GET, HEAD, OPTIONS"
+
+    resp = client.get("/app-error")
+    assert resp.status_code == 400
+    assert resp.json() == {"app_error": "bad input"}
+
+    # The closest class in the MRO wins, and async handlers are awaited
+    resp = client.get("/forbidden")
+    assert resp.status_code == 403
+    assert resp.headers["content-type"] == "application/problem+json"
+    assert resp.json()["detail"] == "no access"
+    assert resp.json()["instance"] == "/forbidden"
+
+    resp = client.get("/crash")
+    assert resp.status_code == 500
+    assert "RuntimeError: boom" in resp.json()["internal"]
+
+    # Responses returned by route handlers are left alone
+    assert client.get("/explicit").json() == {"own": True}
+    assert client.get("/items/7").json() == {"id": "7"}
+
+    # A failing handler falls back to the default error response
+    plain = App()
+
+    @plain.exception_handler(ValueError)
+    def broken(request, exc):
+        raise RuntimeError("handler failed")
+
+    @plain.get("/value")
+    def value(request):
+        raise ValueError("bad")
+
+    resp = TestClient(plain).get("/value")
+    assert resp.status_code == 500
+    assert "bad" in resp.json()["error"]
+
+
+def test_app_problem_details():
+    """Test RFC 7807 problem details for the framework's error responses."""
+    from cello import App
+    from cello.testing import TestClient
+    from cello.validation import validate_request
+
+    app = App()
+    app.enable_problem_details()
+
+    @app.get("/items")
+    def items(request):
+        return {"items": []}
+
+    @app.post("/items")
+    @validate_request(body={"type": "object", "required": ["name"]})
+    def create_item(request):
+        return {"created": True}
+
+    @app.get("/boom")
+    def boom(request):
+        raise RuntimeError("boom")
+
+    client = TestClient(app)
+
+    resp = client.get("/missing", headers={"X-Request-ID": "req-42"})
+    assert resp.status_code == 404
+    assert resp.headers["content-type"] == "application/problem+json"
+    body = resp.json()
+    assert body["type"] == "/errors/not-found"
+    assert body["title"] == "Not Found"
+    assert body["status"] == 404
+    assert body["instance"] == "/missing"
+    assert body["correlation_id"] == "req-42"
+    assert "detail" in body
+
+    resp = client.delete("/items")
+    assert resp.status_code == 405
+    assert resp.headers["content-type"] == "application/problem+json"
+    assert "GET" in resp.headers["allow"]
+    body = resp.json()
+    assert body["type"] == "/errors/method-not-allowed"
+    assert body["title"] == "Method Not Allowed"
+    assert body["status"] == 405
+
+    resp = client.post("/items", json={})
+    assert resp.status_code == 422
+    body = resp.json()
+    assert body["status"] == 422
+    assert body["instance"] == "/items"
+    assert len(body["errors"]) == 1
+
+    resp = client.get("/boom")
+    assert resp.status_code == 500
+    body = resp.json()
+    assert body["status"] == 500
+    assert "traceback" not in body
+
+    debug_app = App()
+    debug_app.enable_problem_details(debug=True)
+    debug_app.add_guard(lambda request: request.path != "/admin")
+
+    @debug_app.get("/boom")
+    def debug_boom(request):
+        raise RuntimeError("boom")
+
+    @debug_app.get("/admin")
+    def admin(request):
+        return {"admin": True}
+
+    @debug_app.exception_handler(404)
+    def not_found(request, problem):
+        return {"extensions": problem.extensions, "instance": problem.instance}
+
+    debug_client = TestClient(debug_app)
+
+    body = debug_client.get("/boom").json()
+    assert "RuntimeError: boom" in body["traceback"]
+
+    resp = debug_client.get("/admin")
+    assert resp.status_code == 403
+    assert resp.headers["content-type"] == "application/problem+json"
+    assert resp.json()["instance"] == "/admin"
+
+    resp = debug_client.get("/nowhere", headers={"X-Correlation-ID": "abc"})
+    assert resp.status_code == 404
+    assert resp.json() == {"extensions": {"correlation_id": "abc"}, "instance": "/nowhere"}
+
+
+def test_app_debug_pages():
+    """Test development error pages for unhandled exceptions."""
+    from cello import App, Response
+    from cello.testing import TestClient
+
+    app = App()
+    app.enable_debug_pages()
+
+    @app.get("/users/{id}")
+    def show_user(request):
+        raise LookupError(f"no user <{request.params['id']}>")
+
+    @app.get("/async")
+    async def async_fail(request):
+        raise RuntimeError("async boom")
+
+    @app.exception_handler(KeyError)
+    def missing(request, exc):
+        return Response.json({"missing": str(exc)}, status=400)
+
+    @app.get("/handled")
+    def handled(request):
+        raise KeyError("name")
+
+    client = TestClient(app)
+
+    resp = client.get("/users/7?full=1", headers={"Accept": "text/html"})
+    assert resp.status_code == 500
+    assert resp.headers["content-type"].startswith("text/html")
+    page = resp.content.decode()
+    assert "LookupError at GET /users/7" in page
+    assert "no user &lt;7&gt;" in page
+    assert "Traceback" in page and "show_user" in page
+    assert "<td>full</td><td>1</td>" in page
+
+    resp = client.get("/async", headers={"Accept": "application/json"})
+    assert resp.status_code == 500
+    body = resp.json()
+    assert body["error"]["type"] == "RuntimeError"
+    assert body["error"]["message"] == "async boom"
+    assert "raise RuntimeError" in body["error"]["traceback"]
+    assert body["request"]["method"] == "GET"
+    assert body["request"]["path"] == "/async"
+    assert body["route"]["handler"].endswith("async_fail")
+    assert body["route"]["source"].startswith(__file__)
+
+    resp = client.get("/handled")
+    assert resp.status_code == 400
+    assert resp.json() == {"missing": "'name'"}
+
+    plain = App()
+
+    @plain.get("/boom")
+    def boom(request):
+        raise RuntimeError("boom")
+
+    resp = TestClient(plain).get("/boom", headers={"Accept": "text/html"})
+    assert resp.status_code == 500
+    assert "Traceback" not in resp.content.decode()
+
+
+def test_app_slow_request_log(tmp_path):
+    """Test that requests over the threshold are logged with a phase breakdown."""
+    import os
+    import time
+    import pytest
+    from cello import App
+    from cello.testing import TestClient
+
+    app = App()
+    app.enable_slow_request_log(threshold=0.05)
+
+    @app.get("/slow")
+    def slow(request):
+        time.sleep(0.1)
+        return {"ok": True}
+
+    @app.get("/fast")
+    def fast(request):
+        return {"ok": True}
+
+    client = TestClient(app)
+    log_path = tmp_path / "stderr.log"
+    saved = os.dup(2)
+    with open(log_path, "w") as log:
+        os.dup2(log.fileno(), 2)
+        try:
+            assert client.get("/fast").status_code == 200
+            assert client.get("/slow?page=2", headers={"X-Request-ID": "r-1"}).status_code == 200
+        finally:
+            os.dup2(saved, 2)
+            os.close(saved)
+
+    lines = [line for line in log_path.read_text().splitlines() if line.startswith("Slow request")]
+    assert len(lines) == 1
+    line = lines[0]
+    assert line.startswith("Slow request: GET /slow?page=2 -> 200 in ")
+    for phase in ("routing", "body", "before_middleware", "handler", "after_middleware",
+                  "serialization"):
+        assert f"{phase} " in line
+    handler_ms = float(line.split("handler ")[1].split("ms")[0])
+    assert handler_ms >= 100
+    assert "request_id=r-1" in line
+
+    with pytest.raises(ValueError):
+        app.enable_slow_request_log(threshold=-1)
+
+
+def test_app_server_timing_and_phase_histograms():
+    """Test the Server-Timing header and the per-phase Prometheus histograms."""
+    import time
+    from cello import App
+    from cello.testing import TestClient
+
+    app = App()
+    app.enable_server_timing()
+    app.enable_prometheus()
+
+    @app.get("/work")
+    def work(request):
+        time.sleep(0.02)
+        return {"ok": True}
+
+    client = TestClient(app)
+    resp = client.get("/work")
+    assert resp.status_code == 200
+    timings = {}
+    for entry in resp.headers["server-timing"].split(", "):
+        name, duration = entry.split(";dur=")
+        timings[name] = float(duration)
+    assert list(timings) == [
+        "routing", "body", "before_middleware", "handler", "after_middleware",
+        "serialization", "total",
+    ]
+    assert timings["handler"] >= 20
+    assert timings["total"] >= timings["handler"]
+
+    metrics = client.get("/metrics").content.decode()
+    assert "server-timing" in client.get("/missing").headers
+    for phase in ("routing", "handler", "serialization", "write"):
+        assert f'cello_http_request_phase_duration_seconds_count{{phase="{phase}"}} 1' in metrics
+    assert 'cello_http_request_phase_duration_seconds_bucket{phase="handler",le="0.01"} 0' in metrics
+
+
+def test_app_profiling_endpoints():
+    """Test the token-guarded CPU profile and heap stats endpoints."""
+    import threading
+    import time
+    import tracemalloc
+    from cello import App
+    from cello.testing import TestClient
+
+    app = App()
+    app.enable_profiling(token="s3cret")
+    client = TestClient(app)
+
+    assert client.get("/_debug/pprof/heap").status_code == 401
+    resp = client.get("/_debug/pprof/heap", headers={"Authorization": "Bearer wrong"})
+    assert resp.status_code == 401
+    assert resp.headers["www-authenticate"] == "Bearer"
+    assert client.get("/_debug/pprof/profile?seconds=0.1").status_code == 401
+
+    done = threading.Event()
+
+    def spin():
+        while not done.is_set():
+            sum(range(1000))
+
+    busy = threading.Thread(target=spin, name="busy-worker")
+    busy.start()
+    try:
+        resp = client.get(
+            "/_debug/pprof/profile?seconds=0.4", headers={"Authorization": "Bearer s3cret"}
+        )
+    finally:
+        done.set()
+        busy.join()
+    assert resp.status_code == 200
+    folded = resp.content.decode().splitlines()
+    assert any(line.startswith("busy-worker;") and "spin (" in line for line in folded)
+    ticks = int(folded[0].rsplit(" ", 1)[1])
+    assert ticks > 0
+
+    resp = client.get(
+        "/_debug/pprof/profile?seconds=0.05&format=json", headers={"X-Admin-Token": "s3cret"}
+    )
+    summary = resp.json()
+    assert summary["samples"] >= 1
+    assert summary["ticks_per_second"] > 0
+    assert "threads" in summary and "stacks" in summary
+    resp = client.get("/_debug/pprof/profile?seconds=900", headers={"X-Admin-Token": "s3cret"})
+    assert resp.status_code == 400
+
+    tracemalloc.start()
+    try:
+        retained = [bytearray(1024) for _ in range(100)]
+        resp = client.get("/_debug/pprof/heap?top=5", headers={"X-Admin-Token": "s3cret"})
+    finally:
+        tracemalloc.stop()
+    assert resp.status_code == 200
+    stats = resp.json()
+    assert stats["process"]["rss_bytes"] > 0
+    assert stats["python"]["allocated_blocks"] > 0
+    assert stats["python"]["tracemalloc"] is True
+    assert 0 < len(stats["python"]["top"]) <= 5
+    assert stats["python"]["traced_bytes"] >= 100 * 1024
+    assert len(retained) == 100
+
+    try:
+        app.enable_profiling(token="")
+        assert False, "expected ValueError"
+    except ValueError:
+        pass
+
+
+def test_app_guard_expressions():
+    """Test permission expression guards on routes and blueprints."""
+    from cello import App, Blueprint, require
+    from cello.testing import TestClient
+
+    app = App()
+    app.enable_problem_details()
+
+    def authenticate(request):
+        roles = request.get_header("x-roles")
+        if roles is not None:
+            request.set_context("user", {"roles": roles.split(",")})
+        return True
+
+    @app.get("/posts/{id}", guards=[authenticate, require("admin | (editor & owner)")])
+    def edit_post(request):
+        return {"id": request.params["id"]}
+
+    admin = Blueprint("/admin", guards=[require("admin")])
+
+    @admin.get("/stats")
+    def stats(request):
+        return {"ok": True}
+
+    app.register_blueprint(admin)
+
+    @app.get("/public")
+    def public(request):
+        return {"ok": True}
+
+    client = TestClient(app)
+
+    assert client.get("/posts/1", headers={"X-Roles": "admin"}).status_code == 200
+    assert client.get("/posts/1", headers={"X-Roles": "editor,owner"}).status_code == 200
+    resp = client.get("/posts/1", headers={"X-Roles": "editor"})
+    assert resp.status_code == 403
+    assert resp.headers["content-type"] == "application/problem+json"
+    body = resp.json()
+    assert body["status"] == 403
+    assert "admin | (editor & owner)" in body["detail"]
+    assert client.get("/posts/1").status_code == 401
+
+    resp = client.get("/admin/stats")
+    assert resp.status_code == 401
+    assert resp.json()["status"] == 401
+    assert client.get("/public").status_code == 200
+
+    guard = require("admin|editor")
+    assert guard.expression == "admin|editor"
+    try:
+        require("admin & (editor")
+        assert False, "expected ValueError"
+    except ValueError:
+        pass
+
+
+def test_app_async_handlers_on_server(tmp_path):
+    """Test async handlers and hooks running on the server's persistent event loop."""
+    import http.client
+    import json
+    import os
+    import socket
+    import subprocess
+    import sys
+    import time
+
+    with socket.socket() as sock:
+        sock.bind(("127.0.0.1", 0))
+        port = sock.getsockname()[1]
+
+    script = tmp_path / "app.py"
+    script.write_text(
+        "import asyncio, threading\n"
+        "from cello import App, stream_body\n"
+        "app = App()\n"
+        "state = {}\n"
+        "@app.on_event('startup')\n"
+        "async def boot():\n"
+        "    state['loop'] = id(asyncio.get_running_loop())\n"
+        "@app.get('/loop')\n"
+        "async def loop(request):\n"
+        "    await asyncio.sleep(0)\n"
+        "    return {'same': id(asyncio.get_running_loop()) == state['loop'],\n"
+        "            'thread': threading.current_thread().name}\n"
+        "@app.post('/upload')\n"
+        "@stream_body\n"
+        "async def upload(request):\n"
+        "    return {'size': sum([len(c) async for c in request.stream()])}\n"
+        f"app.run(host='127.0.0.1', port={port})\n"
+    )
+    env = {**os.environ, "PYTHONPATH": os.pathsep.join(sys.path)}
+    server = subprocess.Popen(
+        [sys.executable, str(script)], env=env,
+        stdout=subprocess.DEVNULL, stderr=subprocess.DEVNULL,
+    )
+
+    def request(method, path, body=None):
+        conn = http.client.HTTPConnection("127.0.0.1", port, timeout=5)
+        conn.request(method, path, body=body)
+        return json.loads(conn.getresponse().read())
+
+    try:
+        for _ in range(100):
+            try:
+                first = request("GET", "/loop")
+                break
+            except OSError:
+                time.sleep(0.05)
+        else:
+            raise AssertionError("server did not start")
+        assert first == {"same": True, "thread": "cello-asyncio"}
+        assert request("GET", "/loop") == first
+        assert request("POST", "/upload", body=b"x" * 300_000) == {"size": 300_000}
+    finally:
+        server.terminate()
+        server.wait()
+
+
+def test_app_connection_metrics(tmp_path):
+    """Test keep-alive reuse and per-connection metrics on a running server."""
+    import http.client
+    import os
+    import socket
+    import subprocess
+    import sys
+    import time
+
+    with socket.socket() as sock:
+        sock.bind(("127.0.0.1", 0))
+        port = sock.getsockname()[1]
+
+    script = tmp_path / "app.py"
+    script.write_text(
+        "from cello import App\n"
+        "app = App()\n"
+        "app.enable_prometheus()\n"
+        "@app.get('/ping')\n"
+        "def ping(request):\n"
+        "    return {'ok': True}\n"
+        f"app.run(host='127.0.0.1', port={port})\n"
+    )
+    env = {**os.environ, "PYTHONPATH": os.pathsep.join(sys.path)}
+    server = subprocess.Popen(
+        [sys.executable, str(script)], env=env,
+        stdout=subprocess.DEVNULL, stderr=subprocess.DEVNULL,
+    )
+
+    def metric(text, name):
+        for line in text.splitlines():
+            if line.startswith(name + " "):
+                return float(line.split()[1])
+        raise AssertionError(f"{name} missing")
+
+    try:
+        for _ in range(100):
+            try:
+                conn = http.client.HTTPConnection("127.0.0.1", port, timeout=5)
+                conn.request("GET", "/ping")
+                conn.getresponse().read()
+                break
+            except OSError:
+                time.sleep(0.05)
+        else:
+            raise AssertionError("server did not start")
+        # Three more requests on the same kept-alive connection
+        for _ in range(3):
+            conn.request("GET", "/ping")
+            assert conn.getresponse().read() == b'{"ok":true}'
+        conn.close()
+
+        for _ in range(50):
+            scrape = http.client.HTTPConnection("127.0.0.1", port, timeout=5)
+            scrape.request("GET", "/metrics")
+            text = scrape.getresponse().read().decode()
+            scrape.close()
+            if metric(text, "cello_connection_requests_count") >= 1:
+                break
+            time.sleep(0.05)
+        assert metric(text, "cello_connection_reused_requests_total") >= 3
+        assert metric(text, "cello_connections_total") >= 2
+        assert metric(text, "cello_connection_requests_sum") >= 4
+        assert metric(text, "cello_connection_duration_seconds_count") >= 1
+        assert metric(text, "cello_tls_handshake_duration_seconds_count") == 0
+    finally:
+        server.terminate()
+        server.wait()
+
+
+def test_app_acme_https(tmp_path):
+    """Test serving HTTPS with a cached ACME certificate."""
+    import http.client
+    import os
+    import shutil
+    import socket
+    import ssl
+    import subprocess
+    import sys
+    import time
+    from cello import App
+
+    app = App()
+    for kwargs in ({"domains": []}, {"domains": ["bad domain"]},
+                   {"domains": ["example.com"], "challenge": "dns-01"}):
+        try:
+            app.enable_acme(**kwargs)
+            assert False, "expected ValueError"
+        except ValueError:
+            pass
+
+    if shutil.which("openssl") is None:
+        return
+    cache = tmp_path / "acme"
+    domain_dir = cache / "example.com"
+    domain_dir.mkdir(parents=True)
+    subprocess.run(
+        ["openssl", "req", "-x509", "-newkey", "ec",
+         "-pkeyopt", "ec_paramgen_curve:prime256v1", "-nodes",
+         "-keyout", str(domain_dir / "key.pem"), "-out", str(domain_dir / "cert.pem"),
+         "-days", "90", "-subj", "/CN=example.com",
+         "-addext", "subjectAltName=DNS:example.com"],
+        check=True, capture_output=True,
+    )
+    (domain_dir / "domains").write_text("example.com")
+
+    with socket.socket() as sock:
+        sock.bind(("127.0.0.1", 0))
+        port = sock.getsockname()[1]
+    script = tmp_path / "app.py"
+    script.write_text(
+        "from cello import App\n"
+        "app = App()\n"
+        "app.enable_prometheus()\n"
+        "app.enable_acme(['example.com'], directory='http://127.0.0.1:9/directory',\n"
+        f"                challenge='tls-alpn-01', cache_dir={str(cache)!r})\n"
+        "@app.get('/ping')\n"
+        "def ping(request):\n"
+        "    return {'ok': True}\n"
+        f"app.run(host='127.0.0.1', port={port})\n"
+    )
+    env = {**os.environ, "PYTHONPATH": os.pathsep.join(sys.path)}
+    server = subprocess.Popen(
+        [sys.executable, str(script)], env=env,
+        stdout=subprocess.DEVNULL, stderr=subprocess.DEVNULL,
+    )
+    context = ssl.create_default_context(cafile=str(domain_dir / "cert.pem"))
+
+    def connect():
+        conn = http.client.HTTPSConnection("127.0.0.1", port, timeout=5, context=context)
+        conn.sock = context.wrap_socket(
+            socket.create_connection(("127.0.0.1", port), timeout=5),
+            server_hostname="example.com",
+        )
+        return conn
+
+    try:
+        for _ in range(100):
+            try:
+                conn = connect()
+                conn.request("GET", "/ping")
+                resp = conn.getresponse()
+                break
+            except (OSError, ssl.SSLError):
+                time.sleep(0.05)
+        else:
+            raise AssertionError("server did not start")
+        assert resp.status == 200
+        assert resp.read() == b'{"ok":true}'
+        assert conn.sock.version() in ("TLSv1.2", "TLSv1.3")
+        conn.close()
+
+        # Plain HTTP is not served on the HTTPS port
+        plain = http.client.HTTPConnection("127.0.0.1", port, timeout=5)
+        try:
+            plain.request("GET", "/ping")
+            plain.getresponse()
+            assert False, "expected the plain HTTP request to fail"
+        except (OSError, http.client.HTTPException):
+            pass
+
+        # Validation handshakes are refused without a pending challenge
+        validation = ssl.create_default_context()
+        validation.check_hostname = False
+        validation.verify_mode = ssl.CERT_NONE
+        validation.set_alpn_protocols(["acme-tls/1"])
+        try:
+            validation.wrap_socket(
+                socket.create_connection(("127.0.0.1", port), timeout=5),
+                server_hostname="example.com",
+            )
+            assert False, "expected the validation handshake to fail"
+        except (OSError, ssl.SSLError):
+            pass
+
+        scrape = connect()
+        scrape.request("GET", "/metrics")
+        text = scrape.getresponse().read().decode()
+        handshakes = [line for line in text.splitlines()
+                      if line.startswith("cello_tls_handshake_duration_seconds_count ")]
+        assert float(handshakes[0].split()[1]) >= 1
+    finally:
+        server.terminate()
+        server.wait()
+
+
+def test_app_https_redirect(tmp_path):
+    """Test the plain HTTP listener redirecting to the HTTPS origin."""
+    import http.client
+    import os
+    import socket
+    import subprocess
+    import sys
+    import time
+
+    ports = []
+    for _ in range(2):
+        with socket.socket() as sock:
+            sock.bind(("127.0.0.1", 0))
+            ports.append(sock.getsockname()[1])
+    port, http_port = ports
+
+    script = tmp_path / "app.py"
+    script.write_text(
+        "from cello import App\n"
+        "app = App()\n"
+        f"app.enable_https_redirect(port={http_port})\n"
+        "@app.get('/ping')\n"
+        "def ping(request):\n"
+        "    return {'ok': True}\n"
+        f"app.run(host='127.0.0.1', port={port})\n"
+    )
+    env = {**os.environ, "PYTHONPATH": os.pathsep.join(sys.path)}
+    server = subprocess.Popen(
+        [sys.executable, str(script)], env=env,
+        stdout=subprocess.DEVNULL, stderr=subprocess.DEVNULL,
+    )
+
+    def request(method, path, target_port, host="example.com"):
+        conn = http.client.HTTPConnection("127.0.0.1", target_port, timeout=5)
+        conn.request(method, path, body=b"x" if method == "POST" else None,
+                     headers={"Host": host})
+        resp = conn.getresponse()
+        resp.read()
+        conn.close()
+        return resp
+
+    try:
+        for _ in range(100):
+            try:
+                resp = request("GET", "/ping", port)
+                break
+            except OSError:
+                time.sleep(0.05)
+        else:
+            raise AssertionError("server did not start")
+        assert resp.status == 200
+        assert resp.getheader("Strict-Transport-Security") == "max-age=31536000"
+
+        resp = request("GET", "/ping?a=1&b=2", http_port, host=f"example.com:{http_port}")
+        assert resp.status == 301
+        assert resp.getheader("Location") == f"https://example.com:{port}/ping?a=1&b=2"
+        assert resp.getheader("Strict-Transport-Security") is None
+
+        resp = request("POST", "/orders", http_port)
+        assert resp.status == 308
+        assert resp.getheader("Location") == f"https://example.com:{port}/orders"
+    finally:
+        server.terminate()
+        server.wait()
+
+
+def test_app_mount_wsgi():
+    """Test serving a WSGI app under a path prefix."""
+    import pytest
+    from cello import App
+    from cello.testing import TestClient
+
+    def legacy(environ, start_response):
+        if environ["PATH_INFO"] == "/fail":
+            raise RuntimeError("legacy failure")
+        body = environ["wsgi.input"].read()
+        start_response("200 OK", [("Content-Type", "text/plain"), ("X-Legacy", "1")])
+        yield f"{environ['REQUEST_METHOD']} {environ['SCRIPT_NAME']}".encode()
+        yield f" {environ['PATH_INFO']}?{environ['QUERY_STRING']} ".encode()
+        yield body
+
+    app = App()
+
+    @app.get("/legacy/native")
+    def native(request):
+        return {"native": True}
+
+    app.mount_wsgi("/legacy", legacy, workers=2)
+
+    with TestClient(app) as client:
+        resp = client.post("/legacy/users/1?page=2", data=b"payload")
+        assert resp.status_code == 200
+        assert resp.headers["x-legacy"] == "1"
+        assert resp.text == "POST /legacy /users/1?page=2 payload"
+
+        assert client.get("/legacy/native").json() == {"native": True}
+        assert client.get("/legacy/fail").status_code == 500
+        assert client.get("/other").status_code == 404
+
+    with pytest.raises(ValueError):
+        app.mount_wsgi("legacy", legacy)
+    with pytest.raises(TypeError):
+        app.mount_wsgi("/x", "not callable")
+
+
+def test_async_client_upstream_groups():
+    """Test load-balanced upstream groups with ejection and retries."""
+    import asyncio
+    import threading
+    from http.server import BaseHTTPRequestHandler, HTTPServer
+
+    import pytest
+    from cello import AsyncClient
+
+    class Handler(BaseHTTPRequestHandler):
+        def do_GET(self):
+            body = f"{self.server.server_port}{self.path}".encode()
+            self.send_response(200)
+            self.send_header("Content-Length", str(len(body)))
+            self.end_headers()
+            self.wfile.write(body)
+
+        def log_message(self, *args):
+            pass
+
+    servers = [HTTPServer(("127.0.0.1", 0), Handler) for _ in range(2)]
+    for server in servers:
+        threading.Thread(target=server.serve_forever, daemon=True).start()
+    live = [f"http://127.0.0.1:{s.server_port}" for s in servers]
+    dead = "http://127.0.0.1:1"
+
+    client = AsyncClient(timeout=5.0)
+    client.add_upstream_group("svc", [dead, live[0], (live[1], 2)], max_failures=1)
+
+    async def fetch(n):
+        return [(await client.get("upstream://svc/ping?x=1")).text for _ in range(n)]
+
+    async def fetch_down():
+        return await client.get("upstream://down/")
+
+    try:
+        bodies = asyncio.run(fetch(6))
+        # The dead upstream is retried past, then ejected.
+        assert all(b.endswith("/ping?x=1") for b in bodies)
+        status = client.upstream_status("svc")
+        assert [s["healthy"] for s in status] == [False, True, True]
+        assert status[0]["failures"] == 1
+        assert status[1]["requests"] + status[2]["requests"] == 6
+
+        client.add_upstream_group("down", [dead], retries=0)
+        with pytest.raises(RuntimeError):
+            asyncio.run(fetch_down())
+        with pytest.raises(ValueError):
+            client.upstream_status("missing")
+        with pytest.raises(ValueError):
+            client.add_upstream_group("bad", [live[0]], strategy="random")
+    finally:
+        for server in servers:
+            server.shutdown()
+
+
+def test_app_with_all_v0100_features():
+    """Test an App with all v0.10.0 features enabled together."""
+    from cello import App, EventSourcingConfig, CqrsConfig, SagaConfig
+
+    app = App()
+
+    # Core middleware
+    app.enable_cors()
+    app.enable_logging()
+
+    # v0.10.0 Advanced Pattern features
+    app.enable_event_sourcing(EventSourcingConfig.memory())
+    app.enable_cqrs(CqrsConfig())
+    app.enable_saga(SagaConfig())
+
+    # Register routes
+    @app.get("/")
+    def home(req):
+        return {"version": "1.0.1"}
+
+    # Verify route handler was registered
+    assert callable(home)
+
+
+# ---------------------------------------------------------------------------
+# v0.10.0 Event Sourcing Python Module Tests
+# ---------------------------------------------------------------------------
+
+
+def test_event_creation():
+    """Test Event creation with type and data."""
+    from cello.eventsourcing import Event
+
+    event = Event("OrderCreated", {"id": 1})
+    assert event is not None
+    assert event.event_type == "OrderCreated"
+    assert event.data == {"id": 1}
+
+
+def test_event_properties():
+    """Test Event has all expected properties."""
+    from cello.eventsourcing import Event
+
+    event = Event("OrderCreated", {"id": 1})
+    assert event.id is not None
+    assert event.event_type == "OrderCreated"
+    assert event.data == {"id": 1}
+    assert event.aggregate_id is None
+    assert event.metadata == {}
+    assert event.version == 0
+    assert event.timestamp is not None
+
+
+def test_event_with_aggregate_id():
+    """Test Event creation with aggregate_id."""
+    from cello.eventsourcing import Event
+
+    event = Event("OrderCreated", {"id": 1}, aggregate_id="order-1")
+    assert event.aggregate_id == "order-1"
+    assert event.event_type == "OrderCreated"
+
+
+def test_event_with_metadata():
+    """Test Event creation with metadata."""
+    from cello.eventsourcing import Event
+
+    event = Event("OrderCreated", {}, metadata={"user": "admin"})
+    assert event.metadata == {"user": "admin"}
+
+
+def test_event_json():
+    """Test Event.to_dict() returns dict with all fields."""
+    from cello.eventsourcing import Event
+
+    event = Event("OrderCreated", {"id": 1}, aggregate_id="order-1")
+    data = event.json()
+    assert isinstance(data, dict)
+    assert data["event_type"] == "OrderCreated"
     assert data["data"] == {"id": 1}
     assert data["aggregate_id"] == "order-1"
     assert "id" in data
@@ -5744,3 +9861,705 @@ def test_message_ack_nack():
     msg2 = Message(topic="test", value=b"data")
     msg2.nack()
     assert msg2._nacked is True
+
+
+def test_request_state_shared_between_stages():
+    """Test request.state carrying values from middleware to the handler and after hooks."""
+    from cello import App, use_middleware
+    from cello.testing import TestClient
+
+    class User:
+        def __init__(self, name):
+            self.name = name
+
+    class Auth:
+        def before(self, request):
+            request.state.user = User(request.get_header("x-user") or "anonymous")
+            request.state["roles"] = ["reader"]
+
+        def after(self, request, response):
+            response.set_header("X-User", request.state.user.name)
+            response.set_header("X-Seen", str(request.state.get("seen", False)))
+
+    app = App()
+
+    @app.get("/me")
+    @use_middleware(Auth())
+    def me(request):
+        request.state.seen = True
+        assert "user" in request.state
+        assert "missing" not in request.state
+        assert request.state.get("missing", 1) == 1
+        return {"name": request.state.user.name, "roles": request.state.roles}
+
+    @app.get("/plain")
+    def plain(request):
+        try:
+            request.state.user
+        except AttributeError:
+            return {"user": None}
+        return {"user": "leaked"}
+
+    client = TestClient(app)
+    res = client.get("/me", headers={"X-User": "alice"})
+    assert res.status_code == 200
+    assert res.json() == {"name": "alice", "roles": ["reader"]}
+    assert res.headers.get("x-user") == "alice"
+    assert res.headers.get("x-seen") == "True"
+
+    assert client.get("/plain").json() == {"user": None}
+
+
+def test_after_middleware_request_body():
+    """Test after hooks seeing the request body only when a middleware asks for it."""
+    import hashlib
+    from cello import App, use_middleware
+    from cello.testing import TestClient
+
+    seen = {}
+
+    class Digest:
+        after_body_limit = 16
+
+        def after(self, request, response):
+            response.set_header("X-Body-Sha256", hashlib.sha256(bytes(request.body())).hexdigest())
+
+    class Plain:
+        def after(self, request, response):
+            seen["plain"] = bytes(request.body())
+
+    app = App()
+
+    @app.post("/audited")
+    @use_middleware(Digest())
+    def audited(request):
+        return {"ok": True}
+
+    @app.post("/plain")
+    @use_middleware(Plain())
+    def plain(request):
+        return {"ok": True}
+
+    client = TestClient(app)
+    res = client.post("/audited", data=b"payload")
+    assert res.headers.get("x-body-sha256") == hashlib.sha256(b"payload").hexdigest()
+
+    # Bodies over the limit are left out
+    res = client.post("/audited", data=b"x" * 17)
+    assert res.headers.get("x-body-sha256") == hashlib.sha256(b"").hexdigest()
+
+    client.post("/plain", data=b"payload")
+    assert seen["plain"] == b""
+
+
+def test_prometheus_measures_streamed_responses():
+    """Test Prometheus counting streamed responses once their body is sent."""
+    from cello import App, JsonStream
+    from cello.testing import TestClient
+
+    app = App()
+    app.enable_prometheus()
+
+    @app.get("/rows")
+    def rows(request):
+        return JsonStream(({"id": i} for i in range(2000)), chunk_size=1024)
+
+    client = TestClient(app)
+    resp = client.get("/rows")
+    assert resp.status_code == 200
+
+    metrics = client.get("/metrics").content.decode()
+    labels = 'method="GET",path="/rows",status="200"'
+    assert f"cello_http_requests_total{{{labels}}} 1" in metrics
+    assert f"cello_http_response_size_bytes_sum{{{labels}}} {len(resp.content)}" in metrics
+    assert 'cello_http_requests_in_progress{method="GET",path="/rows"} 0' in metrics
+
+
+def test_request_repeated_query_parameters():
+    """Test the raw query string and repeated query parameters."""
+    from cello import App, Request
+    from cello.testing import TestClient
+
+    app = App()
+
+    @app.get("/search")
+    def search(request):
+        return {
+            "raw": request.query_string,
+            "tags": request.get_query_list("tag"),
+            "pairs": request.query_list(),
+            "page": request.query.get("page"),
+        }
+
+    client = TestClient(app)
+    data = client.get("/search?tag=a&tag[]=b+c&page=2&tag=%C3%A9").json()
+    assert data["raw"] == "tag=a&tag[]=b+c&page=2&tag=%C3%A9"
+    assert data["tags"] == ["a", "b c", "é"]
+    assert data["pairs"] == [["tag", "a"], ["tag", "b c"], ["page", "2"], ["tag", "é"]]
+    assert data["page"] == "2"
+
+    app.set_query_array_syntax("repeat")
+    data = TestClient(app).get("/search?tag=a&tag[]=b").json()
+    assert data["tags"] == ["a"]
+    assert data["pairs"][1] == ["tag[]", "b"]
+
+    with pytest.raises(ValueError):
+        app.set_query_array_syntax("comma")
+
+    request = Request("GET", "/", query={"q": "a b"})
+    assert request.query_list() == [("q", "a b")]
+    assert request.get_query_list("missing") == []
+
+
+def test_content_negotiation():
+    """Test Accept parsing on the request and negotiated responses."""
+    from cello import App, Request, Response
+    from cello.testing import TestClient
+
+    request = Request("GET", "/", headers={"Accept": "text/html;q=0.9, application/json, */*;q=0.1"})
+    assert request.accepts("text/html", "application/json") == "application/json"
+    assert request.accepts("image/png") == "image/png"
+    assert request.accepts() == ["application/json", "text/html", "*/*"]
+    assert Request("GET", "/", headers={"Accept": "text/html"}).accepts("application/json") is None
+    assert Request("GET", "/").accepts("text/html", "application/json") == "text/html"
+
+    app = App()
+
+    @app.get("/user")
+    def user(request):
+        data = {"name": "alice"}
+        return Response.negotiated(request, data, html=lambda d: f"<h1>{d['name']}</h1>")
+
+    @app.get("/plain")
+    def plain(request):
+        return Response.negotiated(request, [1, 2], status=201)
+
+    client = TestClient(app)
+    res = client.get("/user")
+    assert res.headers["content-type"] == "application/json"
+    assert res.json() == {"name": "alice"}
+    assert res.headers["vary"] == "Accept"
+
+    res = client.get("/user", headers={"Accept": "text/html,application/xhtml+xml,*/*;q=0.8"})
+    assert res.text == "<h1>alice</h1>"
+
+    res = client.get("/user", headers={"Accept": "application/x-msgpack"})
+    assert res.headers["content-type"] == "application/msgpack"
+
+    res = client.get("/plain", headers={"Accept": "text/html"})
+    assert res.status_code == 406
+
+    res = client.get("/plain", headers={"Accept": "application/cbor"})
+    assert res.status_code == 201
+    assert res.headers["content-type"] == "application/cbor"
+
+
+def test_url_for_named_routes():
+    """Test building URLs from named routes, including blueprint routes."""
+    from cello import App, Blueprint
+    from cello.testing import TestClient
+
+    app = App()
+
+    @app.get("/users/{id}", name="user")
+    def user(request):
+        return {"id": request.params["id"]}
+
+    @app.get("/files/{*path}", name="file")
+    def file(request):
+        return {"path": request.params["path"]}
+
+    @app.get("/links")
+    def links(request):
+        return {"user": app.url_for("user", id=7, tab="posts")}
+
+    api = Blueprint("/api", name="api")
+    posts = Blueprint("/posts", name="posts")
+
+    @posts.get("/{slug}", name="show")
+    def show(request):
+        return {"slug": request.params["slug"]}
+
+    api.register(posts)
+    app.register_blueprint(api)
+
+    assert app.url_for("user", id=7) == "/users/7"
+    assert app.url_for("user", id="a b", tag=["x", "y"], draft=True) == "/users/a%20b?tag=x&tag=y&draft=true"
+    assert app.url_for("file", path="docs/read me.txt") == "/files/docs/read%20me.txt"
+    assert app.url_for("api.posts.show", slug="hello") == "/api/posts/hello"
+    assert app.route_names()["api.posts.show"] == "/api/posts/{slug}"
+
+    # Built URLs route back to their handlers
+    client = TestClient(app)
+    assert client.get(app.url_for("user", id=42)).json() == {"id": "42"}
+    assert client.get(app.url_for("api.posts.show", slug="hi")).json() == {"slug": "hi"}
+    assert client.get("/links").json() == {"user": "/users/7?tab=posts"}
+
+    with pytest.raises(ValueError):
+        app.url_for("user")
+    with pytest.raises(ValueError):
+        app.url_for("missing")
+    with pytest.raises(ValueError):
+        app.url_for("user", id=1, _external=True)
+    with pytest.raises(ValueError):
+        app._app.name_route("GET", "/files/{*path}", "user")
+
+    app.set_server_name("api.example.com", scheme="https")
+    assert app.url_for("user", id=1, _external=True) == "https://api.example.com/users/1"
+    with pytest.raises(ValueError):
+        app.set_server_name("api.example.com", scheme="ftp")
+
+
+def test_response_checks():
+    """Test Content-Length enforcement, digests and the response size limit."""
+    import base64
+    import hashlib
+    from cello import App, Response
+    from cello.testing import TestClient
+
+    app = App()
+
+    @app.get("/hello")
+    def hello(request):
+        response = Response.text("hello")
+        response.set_header("Content-Length", "99")
+        return response
+
+    @app.get("/big")
+    def big(request):
+        return Response.text("x" * 2048)
+
+    app.enable_response_checks(digest="md5", max_body_size=1024)
+    client = TestClient(app)
+
+    response = client.get("/hello")
+    assert response.status_code == 200
+    assert response.text == "hello"
+    assert response.headers["content-length"] == "5"
+    assert response.headers["content-md5"] == base64.b64encode(hashlib.md5(b"hello").digest()).decode()
+
+    assert client.get("/big").status_code == 500
+
+    app.enable_response_checks(digest="sha-256")
+    response = TestClient(app).get("/big")
+    assert response.status_code == 200
+    expected = base64.b64encode(hashlib.sha256(b"x" * 2048).digest()).decode()
+    assert response.headers["digest"] == f"sha-256={expected}"
+
+    with pytest.raises(ValueError):
+        app.enable_response_checks(digest="crc32")
+
+
+def test_stats_snapshot_and_endpoint():
+    """Test the aggregated stats snapshot, its endpoint and gzip metrics."""
+    import gzip
+    import json
+    from cello import App
+    from cello.testing import TestClient
+
+    app = App()
+
+    @app.get("/ping")
+    def ping(request):
+        return {"ok": True}
+
+    assert app.stats_snapshot() == {}
+
+    app.enable_saga()
+    app.enable_stats()
+    app.enable_prometheus()
+    client = TestClient(app)
+    client.get("/ping")
+    client.get("/ping")
+
+    snapshot = app.stats_snapshot()
+    assert snapshot["server"]["total_requests"] >= 2
+    assert snapshot["saga"]["total_executions"] == 0
+    assert "phases" in snapshot["server"]
+
+    response = client.get("/stats")
+    assert response.status_code == 200
+    assert response.json()["server"]["total_requests"] >= 2
+
+    response = client.get("/stats", headers={"Accept-Encoding": "gzip"})
+    assert response.headers["content-encoding"] == "gzip"
+    assert set(json.loads(gzip.decompress(response.content))) == {"saga", "server"}
+
+    response = client.get("/metrics", headers={"Accept-Encoding": "gzip"})
+    assert response.headers["content-encoding"] == "gzip"
+    assert b"http_requests_total" in gzip.decompress(response.content)
+
+
+def test_statsd_exporter():
+    """Test pushing request and custom metrics to a DogStatsD agent."""
+    import socket
+    from cello import App
+    from cello.testing import TestClient
+
+    agent = socket.socket(socket.AF_INET, socket.SOCK_DGRAM)
+    agent.bind(("127.0.0.1", 0))
+    agent.settimeout(2)
+
+    app = App()
+
+    @app.get("/users")
+    def users(request):
+        return {"users": []}
+
+    with pytest.raises(RuntimeError):
+        app.statsd_increment("signups")
+
+    app.enable_statsd(port=agent.getsockname()[1], dogstatsd=True, tags={"env": "test"},
+                      flush_interval=3600)
+    TestClient(app).get("/users")
+    app.statsd_increment("signups", tags={"plan": "pro"})
+    app.statsd_gauge("queue.depth", 3)
+    app.statsd_flush()
+
+    lines = agent.recv(4096).decode().split("\n")
+    agent.close()
+    assert "cello.http.requests:1|c|#env:test,method:GET,status:200,path:/users" in lines
+    assert "cello.signups:1|c|#env:test,plan:pro" in lines
+    assert "cello.queue.depth:3|g|#env:test" in lines
+    assert any(line.startswith("cello.http.request.duration:") for line in lines)
+
+
+def test_sentry_error_reporting():
+    """Test reporting unhandled exceptions to a Sentry-compatible DSN."""
+    import json
+    import threading
+    from http.server import BaseHTTPRequestHandler, HTTPServer
+    from cello import App, AppConfig, SentryConfig
+    from cello.testing import TestClient
+
+    envelopes = []
+
+    class Sentry(BaseHTTPRequestHandler):
+        def do_POST(self):
+            body = self.rfile.read(int(self.headers["Content-Length"]))
+            envelopes.append((self.path, self.headers["X-Sentry-Auth"], body))
+            self.send_response(200)
+            self.end_headers()
+
+        def log_message(self, *args):
+            pass
+
+    server = HTTPServer(("127.0.0.1", 0), Sentry)
+    threading.Thread(target=server.serve_forever, daemon=True).start()
+    dsn = f"http://public@127.0.0.1:{server.server_port}/42"
+
+    with pytest.raises(ValueError):
+        SentryConfig("not-a-dsn")
+
+    app = App(AppConfig.load(defaults={"sentry": {"dsn": dsn, "environment": "test"}}))
+
+    @app.get("/boom")
+    def boom(request):
+        raise ValueError("kaboom")
+
+    @app.get("/handled")
+    def handled(request):
+        raise KeyError("missing")
+
+    @app.exception_handler(KeyError)
+    def on_key_error(request, exc):
+        return {"error": "not found"}
+
+    def before_send(event):
+        event["tags"]["team"] = "payments"
+        return event
+
+    app.enable_sentry(before_send=before_send)
+    client = TestClient(app)
+    response = client.get("/boom?token=s3cret&page=2",
+                          headers={"X-Request-ID": "req-7", "Cookie": "sid=1"})
+    assert response.status_code == 500
+    client.get("/handled")
+    assert app.flush_errors()
+    server.shutdown()
+
+    assert len(envelopes) == 1
+    path, auth, body = envelopes[0]
+    assert path == "/api/42/envelope/"
+    assert "sentry_key=public" in auth
+    header, item, event = [json.loads(line) for line in body.decode().splitlines()]
+    assert item["type"] == "event" and header["event_id"] == event["event_id"]
+    assert event["environment"] == "test"
+    assert event["tags"]["route"].endswith("test_sentry_error_reporting.<locals>.boom")
+    assert (event["tags"]["request_id"], event["tags"]["team"]) == ("req-7", "payments")
+    exception = event["exception"]["values"][0]
+    assert (exception["type"], exception["value"]) == ("ValueError", "kaboom")
+    assert exception["stacktrace"]["frames"][-1]["function"] == "boom"
+    assert event["request"]["query_string"] == {"token": "[Filtered]", "page": "2"}
+    assert "cookie" not in event["request"]["headers"]
+    assert app.stats_snapshot()["sentry"] == {"captured": 1, "sent": 1, "dropped": 0, "failed": 0}
+
+
+def test_redaction_rules():
+    """Test redacting headers, fields and patterns in logs, reports and debug pages."""
+    from cello import App
+    from cello.testing import TestClient
+
+    app = App()
+
+    @app.get("/users/{email}")
+    def show_user(request):
+        raise ValueError(f"no user {request.params['email']}")
+
+    assert app.redact("ann@example.com") == "ann@example.com"
+    with pytest.raises(ValueError):
+        app.enable_redaction(patterns=["("])
+
+    app.enable_redaction(fields=["password", "cards.*.number", "token"],
+                         patterns=[r"[\w.+-]+@[\w-]+\.[\w.]+"])
+    assert app.redact("mail ann@example.com") == "mail [REDACTED]"
+    assert app.redact({"password": "x", "cards": [{"number": "4111", "brand": "visa"}]}) == {
+        "password": "[REDACTED]", "cards": [{"number": "[REDACTED]", "brand": "visa"}],
+    }
+
+    app.enable_debug_pages()
+    response = TestClient(app).get(
+        "/users/ann@example.com?token=abc&page=1",
+        headers={"Accept": "application/json", "Authorization": "Bearer s3cret"},
+    )
+    assert response.status_code == 500
+    page = response.json()
+    assert page["error"]["message"] == "no user [REDACTED]"
+    assert "ann@example.com" not in page["error"]["traceback"]
+    assert page["request"]["path"] == "/users/[REDACTED]"
+    assert page["request"]["params"] == {"email": "[REDACTED]"}
+    assert page["request"]["query"] == {"token": "[REDACTED]", "page": "1"}
+    assert page["request"]["headers"]["authorization"] == "[REDACTED]"
+
+
+def test_request_recording():
+    """Test recording sampled request/response pairs per route."""
+    from cello import App
+    from cello.testing import TestClient
+
+    app = App()
+
+    @app.post("/users/{id}")
+    def update_user(request):
+        return {"id": request.params["id"], "password": "hunter2", "bio": "x" * 100}
+
+    with pytest.raises(RuntimeError):
+        app.recordings()
+    with pytest.raises(ValueError):
+        app.enable_recording(token="s3cret", sample_rate=2.0)
+
+    app.enable_redaction(fields=["password"])
+    app.enable_recording(token="s3cret", per_route=2)
+    client = TestClient(app)
+    for user_id in (1, 2, 3):
+        assert client.post(f"/users/{user_id}", json={"password": "pw"}).status_code == 200
+
+    recordings = app.recordings("POST /users/{id}")
+    assert [r["request"]["path"] for r in recordings] == ["/users/2", "/users/3"]
+    recording = recordings[-1]
+    assert recording["route"] == "POST /users/{id}"
+    assert recording["request"]["body"]["content"] == {"password": "[REDACTED]"}
+    assert recording["response"]["status"] == 200
+    assert recording["response"]["body"]["encoding"] == "json"
+    assert recording["response"]["body"]["content"]["password"] == "[REDACTED]"
+
+    assert client.get("/_debug/recordings").status_code == 401
+    response = client.get("/_debug/recordings", params={"route": "POST /users/{id}"},
+                          headers={"Authorization": "Bearer s3cret"})
+    assert response.status_code == 200
+    assert response.json()["routes"] == {"POST /users/{id}": 2}
+    assert len(response.json()["recordings"]) == 2
+
+    response = client.delete("/_debug/recordings", headers={"X-Admin-Token": "s3cret"})
+    assert response.status_code == 204
+    assert app.recordings() == []
+
+
+def test_method_override():
+    """Test overriding the method of POST requests before routing."""
+    from cello import App
+    from cello.testing import TestClient
+
+    app = App()
+
+    @app.post("/posts/{id}")
+    def update_post(request):
+        return {"method": "POST"}
+
+    @app.delete("/posts/{id}")
+    def delete_post(request):
+        return {"method": "DELETE", "form": request.form()}
+
+    @app.put("/posts/{id}")
+    def replace_post(request):
+        return {"method": "PUT"}
+
+    client = TestClient(app)
+    headers = {"X-HTTP-Method-Override": "DELETE"}
+    # Disabled by default
+    assert client.post("/posts/7", headers=headers).json() == {"method": "POST"}
+
+    with pytest.raises(ValueError):
+        app.enable_method_override(methods=["HEAD"])
+    app.enable_method_override(methods=["DELETE"])
+    client = TestClient(app)
+    assert client.post("/posts/7", headers=headers).json()["method"] == "DELETE"
+    response = client.post("/posts/7", data={"_method": "delete", "token": "abc"})
+    assert response.json() == {"method": "DELETE", "form": {"_method": "delete", "token": "abc"}}
+    # Not allowed, or not a POST
+    assert client.post("/posts/7", headers={"X-HTTP-Method-Override": "PUT"}).json() == {"method": "POST"}
+    assert client.put("/posts/7", headers=headers).json() == {"method": "PUT"}
+
+
+def test_json_etags():
+    """Test ETags of JSON responses and 304s for unchanged bodies."""
+    from cello import App, Response
+    from cello.testing import TestClient
+
+    app = App()
+    state = {"version": 1}
+
+    @app.get("/status")
+    def status(request):
+        return {"version": state["version"]}
+
+    @app.get("/text")
+    def text(request):
+        return Response.text("plain")
+
+    client = TestClient(app)
+    assert "etag" not in client.get("/status").headers
+
+    app.enable_json_etags()
+    client = TestClient(app)
+    response = client.get("/status")
+    etag = response.headers["etag"]
+    assert etag.startswith('W/"')
+
+    cached = client.get("/status", headers={"If-None-Match": etag})
+    assert cached.status_code == 304
+    assert cached.content == b""
+    assert client.get("/status", headers={"If-None-Match": '"other", ' + etag}).status_code == 304
+
+    state["version"] = 2
+    changed = client.get("/status", headers={"If-None-Match": etag})
+    assert changed.status_code == 200
+    assert changed.json() == {"version": 2}
+
+    # Slow path: after-middleware runs on the 304 too
+    app.enable_cors()
+    client = TestClient(app)
+    etag = client.get("/status").headers["etag"]
+    assert client.get("/status", headers={"If-None-Match": etag}).status_code == 304
+    assert "etag" not in client.get("/text").headers
+
+
+def test_grpc_deadline_propagation():
+    """Test grpc-timeout is enforced, cancels handlers and is exposed to them."""
+    import asyncio
+    import time
+    from cello import App
+    from cello.grpc import GrpcService, GrpcError, GrpcRequest, grpc_method
+
+    state = {"cancelled": False, "finished": False}
+
+    class Jobs(GrpcService):
+        @grpc_method
+        def remaining(self, request):
+            return str(request.time_remaining()).encode()
+
+        @grpc_method
+        async def slow(self, request):
+            try:
+                await asyncio.sleep(5)
+            except asyncio.CancelledError:
+                state["cancelled"] = True
+                raise
+
+        @grpc_method
+        def blocking(self, request):
+            time.sleep(1)
+            state["finished"] = True
+
+    app = App()
+    app.enable_grpc()
+    app.register_grpc_service(Jobs())
+
+    async def run():
+        result = await app.grpc_call("Jobs", "remaining", b"")
+        assert result["payload"] == b"None"
+        result = await app.grpc_call("Jobs", "remaining", b"", {"grpc-timeout": "2S"})
+        assert 1.5 < float(result["payload"]) <= 2.0
+
+        result = await app.grpc_call("Jobs", "slow", b"", {"grpc-timeout": "50m"})
+        assert result["code"] == GrpcError.DEADLINE_EXCEEDED
+        for _ in range(50):
+            if state["cancelled"]:
+                break
+            await asyncio.sleep(0.01)
+        assert state["cancelled"]
+
+        # A sync handler is answered at the deadline, and stopped once its
+        # blocking call returns
+        started = time.monotonic()
+        result = await app.grpc_call("Jobs", "blocking", b"", {"grpc-timeout": "200m"})
+        assert result["code"] == GrpcError.DEADLINE_EXCEEDED
+        assert time.monotonic() - started < 0.6
+        await asyncio.sleep(1.2)
+        assert not state["finished"]
+
+    asyncio.run(run())
+
+    request = GrpcRequest("Jobs", "slow", timeout=0.5)
+    assert 0.4 < request.time_remaining() <= 0.5
+    assert GrpcRequest("Jobs", "slow").time_remaining() is None
+
+
+def test_request_logger(tmp_path):
+    """Test request.logger binding request fields and writing JSON lines through tracing."""
+    import json
+    import os
+    import pytest
+    from cello import App, RequestLogger
+    from cello.testing import TestClient
+
+    app = App()
+    assert app.enable_structured_logging(level="info")
+
+    @app.get("/orders/{id}")
+    def order(request):
+        log = request.logger.bind(order_id=request.params["id"])
+        assert log.info("order viewed", items=3)
+        assert log.debug("not written")
+        with pytest.raises(ValueError):
+            log.log("verbose", "unknown level")
+        return {"route": request.route, "fields": log.fields}
+
+    client = TestClient(app)
+    log_path = tmp_path / "stderr.log"
+    saved = os.dup(2)
+    with open(log_path, "w") as log:
+        os.dup2(log.fileno(), 2)
+        try:
+            res = client.get("/orders/42", headers={"X-Request-ID": "req-9"})
+            assert RequestLogger.flush(timeout=5.0)
+        finally:
+            os.dup2(saved, 2)
+            os.close(saved)
+
+    body = res.json()
+    assert body["route"] == "/orders/{id}"
+    assert body["fields"] == {"request_id": "req-9", "method": "GET", "path": "/orders/42",
+                              "route": "/orders/{id}", "order_id": "42"}
+
+    records = [json.loads(line) for line in log_path.read_text().splitlines()
+               if '"cello.request"' in line]
+    assert len(records) == 1
+    record = records[0]
+    assert record["level"] == "INFO"
+    assert record["fields"]["message"] == "order viewed"
+    assert record["fields"]["request_id"] == "req-9"
+    assert record["fields"]["route"] == "/orders/{id}"
+    assert json.loads(record["fields"]["fields"]) == {"order_id": "42", "items": 3}
+    assert RequestLogger.dropped() == 0
+    assert not app.enable_structured_logging()